@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use warp_terminal::output_buffer::OutputBuffer;
+use warp_terminal::render_bench::{chunk_str, RenderBenchmark, Scenario, PTY_CHUNK_SIZE};
+
+const BYTES: usize = 8 * 1024 * 1024;
+
+fn bench_ingest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("output_ingest");
+    group.throughput(Throughput::Bytes(BYTES as u64));
+
+    for scenario in Scenario::all() {
+        let data = scenario.generate(BYTES);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{:?}", scenario)),
+            &data,
+            |b, data| {
+                b.iter(|| {
+                    let mut buffer = OutputBuffer::new(10_000);
+                    for chunk in chunk_str(data, PTY_CHUNK_SIZE) {
+                        buffer.push_chunk(black_box(chunk));
+                    }
+                    buffer.len()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_render_pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_pipeline");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(BYTES as u64));
+
+    let bench = RenderBenchmark::new(200, 50, 10_000);
+    for scenario in Scenario::all() {
+        group.bench_function(
+            BenchmarkId::from_parameter(format!("{:?}", scenario)),
+            |b| b.iter(|| bench.run(black_box(scenario), BYTES).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_ingest, bench_render_pipeline);
+criterion_main!(benches);
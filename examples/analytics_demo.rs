@@ -2,6 +2,7 @@ use chrono::Utc;
 use std::collections::HashMap;
 use warp_terminal::{
     analytics::{AnalyticsEngine, AnalyticsEvent, EventType, PerformanceData, TimeRange},
+    config::TelemetryMode,
     error::WarpError,
 };
 
@@ -9,6 +10,7 @@ use warp_terminal::{
 async fn main() -> Result<(), WarpError> {
     // Initialize the analytics engine
     let analytics = AnalyticsEngine::new().await?;
+    analytics.set_telemetry(TelemetryMode::LocalOnly).await;
 
     println!("📊 Warp Analytics & Performance Monitoring Demo\n");
 
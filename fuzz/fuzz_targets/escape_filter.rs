@@ -0,0 +1,56 @@
+#![no_main]
+
+// Filtering must not depend on how the output was cut into reads, and
+// nothing but SGR may survive the untrusted policy
+use libfuzzer_sys::fuzz_target;
+use warp_terminal::config::{TrustLevel, WarpConfig};
+use warp_terminal::escape_filter::EscapeFilter;
+
+fn filter(trust: TrustLevel) -> EscapeFilter {
+    let mut filter = EscapeFilter::new(WarpConfig::default().security.escapes);
+    filter.set_trust(trust);
+    filter
+}
+
+fn only_style_remains(text: &str) -> bool {
+    let mut rest = text;
+    while let Some(i) = rest.find([
+        '\x1b', '\u{9b}', '\u{9d}', '\u{90}', '\u{98}', '\u{9e}', '\u{9f}',
+    ]) {
+        let after = &rest[i..];
+        let Some(params) = after
+            .strip_prefix("\x1b[")
+            .or_else(|| after.strip_prefix('\u{9b}'))
+        else {
+            return false;
+        };
+        let Some(end) = params.find(|c: char| ('@'..='~').contains(&c)) else {
+            return false;
+        };
+        if !params[end..].starts_with('m') {
+            return false;
+        }
+        rest = &params[end + 1..];
+    }
+    true
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&read_size, bytes)) = data.split_first() else {
+        return;
+    };
+    let text = String::from_utf8_lossy(bytes);
+    let chars: Vec<char> = text.chars().collect();
+    for trust in [TrustLevel::Local, TrustLevel::Remote, TrustLevel::Untrusted] {
+        let whole = filter(trust).filter(&text).text;
+        let mut chunked = filter(trust);
+        let out: String = chars
+            .chunks(read_size as usize % 8 + 1)
+            .map(|chunk| chunked.filter(&chunk.iter().collect::<String>()).text)
+            .collect();
+        assert_eq!(out, whole);
+        if trust == TrustLevel::Untrusted {
+            assert!(only_style_remains(&whole), "{:?}", whole);
+        }
+    }
+});
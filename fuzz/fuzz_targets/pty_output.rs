@@ -0,0 +1,31 @@
+#![no_main]
+
+// Raw PTY bytes through the same path as the app: streaming UTF-8 decode,
+// then the scrollback with truncation on. The first two bytes pick the read
+// size and the line limit so the fuzzer explores both.
+use libfuzzer_sys::fuzz_target;
+use warp_terminal::output_buffer::{OutputBuffer, TruncationLimits};
+use warp_terminal::utf8::Utf8Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let [read_size, line_limit, bytes @ ..] = data else {
+        return;
+    };
+    let max_line_bytes = *line_limit as usize + 4;
+    let mut buffer = OutputBuffer::new(64);
+    buffer.set_truncation(Some(TruncationLimits {
+        head_lines: 8,
+        tail_lines: 8,
+        max_line_bytes,
+    }));
+    let mut decoder = Utf8Decoder::new();
+    for chunk in bytes.chunks(*read_size as usize + 1) {
+        buffer.push_chunk(&decoder.decode(chunk));
+        assert!(buffer.len() <= 64);
+    }
+    buffer.push_chunk(&decoder.finish());
+    buffer.start_block();
+    let _ = buffer.take_dirty();
+    assert!(buffer.visible(10).count() <= 10);
+    let _ = buffer.current_block().count();
+});
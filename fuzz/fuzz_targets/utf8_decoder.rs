@@ -0,0 +1,19 @@
+#![no_main]
+
+// However the input is cut into reads, the decoded text must equal
+// decoding it in one go
+use libfuzzer_sys::fuzz_target;
+use warp_terminal::utf8::Utf8Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&read_size, bytes)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = Utf8Decoder::new();
+    let mut out = String::new();
+    for chunk in bytes.chunks(read_size as usize % 8 + 1) {
+        out.push_str(&decoder.decode(chunk));
+    }
+    out.push_str(&decoder.finish());
+    assert_eq!(out, String::from_utf8_lossy(bytes));
+});
@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::analytics::{AnalyticsEngine, AnalyticsEvent, EventType};
+use crate::error::WarpError;
+
+// Why an exposure was logged: the user was just allocated, or code asked
+// whether their variant is on
+pub const SOURCE_ALLOCATION: &str = "allocation";
+pub const SOURCE_CHECK: &str = "check";
+
+// Conversion and exposure counts for one variant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VariantFunnel {
+    pub exposed_users: u64,
+    pub converted_users: u64,
+    pub conversions: u64,
+    pub total_value: f64,
+}
+
+impl VariantFunnel {
+    pub fn conversion_rate(&self) -> f64 {
+        if self.exposed_users == 0 {
+            0.0
+        } else {
+            self.converted_users as f64 / self.exposed_users as f64
+        }
+    }
+}
+
+// Keeps exposures and conversions for the join, and sends them on to
+// analytics once that's set. A user's exposure to a variant is logged once;
+// only the first one matters to the join.
+pub struct ExposureLogger {
+    analytics: Mutex<Option<Arc<AnalyticsEngine>>>,
+    logged: Mutex<HashSet<(String, String, String)>>,
+    events: Mutex<Vec<AnalyticsEvent>>,
+}
+
+impl ExposureLogger {
+    pub fn new() -> Self {
+        Self {
+            analytics: Mutex::new(None),
+            logged: Mutex::new(HashSet::new()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub async fn set_analytics(&self, analytics: Arc<AnalyticsEngine>) {
+        *self.analytics.lock().await = Some(analytics);
+    }
+
+    pub async fn log_exposure(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        experiment_id: &str,
+        variant_id: &str,
+        source: &str,
+    ) -> Result<(), WarpError> {
+        let key = (
+            user_id.to_string(),
+            experiment_id.to_string(),
+            variant_id.to_string(),
+        );
+        if !self.logged.lock().await.insert(key) {
+            return Ok(());
+        }
+        let metadata = HashMap::from([
+            (
+                "experiment_id".to_string(),
+                serde_json::json!(experiment_id),
+            ),
+            ("variant_id".to_string(), serde_json::json!(variant_id)),
+            ("source".to_string(), serde_json::json!(source)),
+        ]);
+        let exposure = event(
+            EventType::ExperimentExposure,
+            user_id,
+            session_id,
+            experiment_id,
+            metadata,
+        );
+        self.record(exposure).await
+    }
+
+    pub async fn log_conversion(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        experiment_id: &str,
+        variant_id: &str,
+        metric_name: &str,
+        value: f64,
+    ) -> Result<(), WarpError> {
+        let metadata = HashMap::from([
+            (
+                "experiment_id".to_string(),
+                serde_json::json!(experiment_id),
+            ),
+            ("variant_id".to_string(), serde_json::json!(variant_id)),
+            ("metric".to_string(), serde_json::json!(metric_name)),
+            ("value".to_string(), serde_json::json!(value)),
+        ]);
+        let conversion = event(
+            EventType::ExperimentConversion,
+            user_id,
+            session_id,
+            experiment_id,
+            metadata,
+        );
+        self.record(conversion).await
+    }
+
+    async fn record(&self, event: AnalyticsEvent) -> Result<(), WarpError> {
+        self.events.lock().await.push(event.clone());
+        let analytics = self.analytics.lock().await.clone();
+        match analytics {
+            Some(analytics) => analytics.track_event(event).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn funnels(
+        &self,
+        experiment_id: &str,
+        metric_name: &str,
+    ) -> HashMap<String, VariantFunnel> {
+        join(&self.events.lock().await, experiment_id, metric_name)
+    }
+}
+
+impl Default for ExposureLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn event(
+    event_type: EventType,
+    user_id: &str,
+    session_id: &str,
+    experiment_id: &str,
+    metadata: HashMap<String, serde_json::Value>,
+) -> AnalyticsEvent {
+    AnalyticsEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        event_type,
+        timestamp: Utc::now(),
+        user_id: Some(user_id.to_string()),
+        session_id: session_id.to_string(),
+        item_id: Some(experiment_id.to_string()),
+        metadata,
+        performance_data: None,
+    }
+}
+
+fn field<'a>(event: &'a AnalyticsEvent, name: &str) -> Option<&'a str> {
+    event.metadata.get(name).and_then(|value| value.as_str())
+}
+
+// Joins exposures to conversions of `metric_name` for one experiment. A
+// conversion counts towards the variant the user was first exposed to, and
+// only if it happened after that exposure; users never exposed don't count.
+pub fn join(
+    events: &[AnalyticsEvent],
+    experiment_id: &str,
+    metric_name: &str,
+) -> HashMap<String, VariantFunnel> {
+    let mut exposures: HashMap<&str, (&str, DateTime<Utc>)> = HashMap::new();
+    for event in events {
+        if !matches!(event.event_type, EventType::ExperimentExposure)
+            || field(event, "experiment_id") != Some(experiment_id)
+        {
+            continue;
+        }
+        if let (Some(user_id), Some(variant_id)) =
+            (event.user_id.as_deref(), field(event, "variant_id"))
+        {
+            let first = exposures
+                .entry(user_id)
+                .or_insert((variant_id, event.timestamp));
+            if event.timestamp < first.1 {
+                *first = (variant_id, event.timestamp);
+            }
+        }
+    }
+
+    let mut funnels: HashMap<String, VariantFunnel> = HashMap::new();
+    for (variant_id, _) in exposures.values() {
+        funnels
+            .entry(variant_id.to_string())
+            .or_default()
+            .exposed_users += 1;
+    }
+    let mut converted: HashSet<&str> = HashSet::new();
+    for event in events {
+        if !matches!(event.event_type, EventType::ExperimentConversion)
+            || field(event, "experiment_id") != Some(experiment_id)
+            || field(event, "metric") != Some(metric_name)
+        {
+            continue;
+        }
+        let Some(user_id) = event.user_id.as_deref() else {
+            continue;
+        };
+        let Some((variant_id, exposed_at)) = exposures.get(user_id) else {
+            continue;
+        };
+        if event.timestamp < *exposed_at {
+            continue;
+        }
+        let funnel = funnels.entry(variant_id.to_string()).or_default();
+        funnel.conversions += 1;
+        funnel.total_value += event
+            .metadata
+            .get("value")
+            .and_then(|value| value.as_f64())
+            .unwrap_or(0.0);
+        if converted.insert(user_id) {
+            funnel.converted_users += 1;
+        }
+    }
+    funnels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(event_type: EventType, user: &str, variant: &str, seconds: i64) -> AnalyticsEvent {
+        let mut event = event(
+            event_type,
+            user,
+            "session",
+            "exp",
+            HashMap::from([
+                ("experiment_id".to_string(), serde_json::json!("exp")),
+                ("variant_id".to_string(), serde_json::json!(variant)),
+                ("metric".to_string(), serde_json::json!("signup")),
+                ("value".to_string(), serde_json::json!(2.0)),
+            ]),
+        );
+        event.timestamp = DateTime::<Utc>::from_timestamp(1_700_000_000 + seconds, 0).unwrap();
+        event
+    }
+
+    #[test]
+    fn test_join_counts_conversions_after_exposure() {
+        let events = vec![
+            at(EventType::ExperimentExposure, "ada", "control", 0),
+            at(EventType::ExperimentExposure, "bob", "treatment", 0),
+            at(EventType::ExperimentExposure, "carol", "treatment", 10),
+            at(EventType::ExperimentConversion, "bob", "treatment", 5),
+            at(EventType::ExperimentConversion, "bob", "treatment", 6),
+            // Before carol saw the variant
+            at(EventType::ExperimentConversion, "carol", "treatment", 5),
+            // Never exposed
+            at(EventType::ExperimentConversion, "dan", "control", 5),
+        ];
+        let funnels = join(&events, "exp", "signup");
+        let treatment = &funnels["treatment"];
+        assert_eq!(
+            (
+                treatment.exposed_users,
+                treatment.converted_users,
+                treatment.conversions
+            ),
+            (2, 1, 2)
+        );
+        assert_eq!(treatment.total_value, 4.0);
+        assert_eq!(treatment.conversion_rate(), 0.5);
+        assert_eq!(funnels["control"].conversion_rate(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_logger_joins_what_it_logged() {
+        let logger = ExposureLogger::new();
+        logger
+            .log_exposure("ada", "s1", "exp", "treatment", SOURCE_ALLOCATION)
+            .await
+            .unwrap();
+        logger
+            .log_exposure("ada", "s1", "exp", "treatment", SOURCE_CHECK)
+            .await
+            .unwrap();
+        logger
+            .log_exposure("bob", "s2", "exp", "treatment", SOURCE_ALLOCATION)
+            .await
+            .unwrap();
+        logger
+            .log_conversion("ada", "s1", "exp", "treatment", "signup", 1.0)
+            .await
+            .unwrap();
+
+        let funnels = logger.funnels("exp", "signup").await;
+        assert_eq!(funnels["treatment"].exposed_users, 2);
+        assert_eq!(funnels["treatment"].converted_users, 1);
+        assert_eq!(logger.events.lock().await.len(), 3);
+    }
+}
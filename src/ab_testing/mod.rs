@@ -1,22 +1,16 @@
+use crate::error::WarpError;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
-use chrono::{DateTime, Utc, Duration};
 
-pub mod experiment;
-pub mod variant;
-pub mod allocation;
-pub mod metrics;
-pub mod analysis;
+pub mod exposure;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ABTestingFramework {
     experiments: Arc<Mutex<HashMap<String, Experiment>>>,
     user_allocations: Arc<Mutex<HashMap<String, UserAllocation>>>,
-    metrics_collector: Arc<metrics::MetricsCollector>,
-    analyzer: Arc<analysis::StatisticalAnalyzer>,
+    exposures: exposure::ExposureLogger,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,11 +53,25 @@ pub struct Variant {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VariantConfiguration {
-    FeatureFlag { enabled: bool },
-    ConfigValue { key: String, value: serde_json::Value },
-    UIComponent { component_id: String, props: HashMap<String, serde_json::Value> },
-    Algorithm { algorithm_id: String, parameters: HashMap<String, f64> },
-    Theme { theme_id: String, customizations: HashMap<String, String> },
+    FeatureFlag {
+        enabled: bool,
+    },
+    ConfigValue {
+        key: String,
+        value: serde_json::Value,
+    },
+    UIComponent {
+        component_id: String,
+        props: HashMap<String, serde_json::Value>,
+    },
+    Algorithm {
+        algorithm_id: String,
+        parameters: HashMap<String, f64>,
+    },
+    Theme {
+        theme_id: String,
+        customizations: HashMap<String, String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -207,20 +215,24 @@ impl ABTestingFramework {
         Ok(Self {
             experiments: Arc::new(Mutex::new(HashMap::new())),
             user_allocations: Arc::new(Mutex::new(HashMap::new())),
-            metrics_collector: Arc::new(metrics::MetricsCollector::new().await?),
-            analyzer: Arc::new(analysis::StatisticalAnalyzer::new().await?),
+            exposures: exposure::ExposureLogger::new(),
         })
     }
 
+    // Exposures and conversions are tracked as analytics events from then on
+    pub async fn set_analytics(&self, analytics: Arc<crate::analytics::AnalyticsEngine>) {
+        self.exposures.set_analytics(analytics).await;
+    }
+
     pub async fn create_experiment(&self, experiment: Experiment) -> Result<String, WarpError> {
         let experiment_id = experiment.id.clone();
-        
+
         // Validate experiment configuration
         self.validate_experiment(&experiment).await?;
-        
+
         let mut experiments = self.experiments.lock().await;
         experiments.insert(experiment_id.clone(), experiment);
-        
+
         Ok(experiment_id)
     }
 
@@ -233,32 +245,41 @@ impl ABTestingFramework {
         Ok(())
     }
 
-    pub async fn stop_experiment(&self, experiment_id: &str) -> Result<ExperimentResult, WarpError> {
+    pub async fn stop_experiment(&self, experiment_id: &str) -> Result<(), WarpError> {
         let mut experiments = self.experiments.lock().await;
-        if let Some(experiment) = experiments.get_mut(experiment_id) {
-            experiment.status = ExperimentStatus::Completed;
-            experiment.end_date = Some(Utc::now());
-            
-            // Generate final results
-            return self.analyze_experiment(experiment_id).await;
-        }
-        
-        Err(WarpError::ConfigError(format!("Experiment not found: {}", experiment_id)))
+        let experiment = experiments
+            .get_mut(experiment_id)
+            .ok_or_else(|| WarpError::not_found("experiment", experiment_id))?;
+        experiment.status = ExperimentStatus::Completed;
+        experiment.end_date = Some(Utc::now());
+        Ok(())
     }
 
-    pub async fn allocate_user(&self, user_id: &str, experiment_id: &str, user_properties: HashMap<String, serde_json::Value>) -> Result<String, WarpError> {
+    pub async fn allocate_user(
+        &self,
+        user_id: &str,
+        experiment_id: &str,
+        user_properties: HashMap<String, serde_json::Value>,
+    ) -> Result<String, WarpError> {
         let experiments = self.experiments.lock().await;
-        let experiment = experiments.get(experiment_id)
-            .ok_or_else(|| WarpError::ConfigError(format!("Experiment not found: {}", experiment_id)))?;
+        let experiment = experiments
+            .get(experiment_id)
+            .ok_or_else(|| WarpError::not_found("experiment", experiment_id))?;
 
         // Check if user matches experiment filters
-        if !self.user_matches_filters(user_id, &user_properties, &experiment.filters).await? {
-            return Err(WarpError::ConfigError("User does not match experiment filters".to_string()));
+        if !self
+            .user_matches_filters(user_id, &user_properties, &experiment.filters)
+            .await?
+        {
+            return Err(WarpError::Validation(
+                "User does not match experiment filters".to_string(),
+            ));
         }
 
         // Allocate user to variant
         let variant_id = self.allocate_to_variant(user_id, experiment).await?;
-        
+        drop(experiments);
+
         let allocation = UserAllocation {
             user_id: user_id.to_string(),
             experiment_id: experiment_id.to_string(),
@@ -267,36 +288,107 @@ impl ABTestingFramework {
             session_id: uuid::Uuid::new_v4().to_string(),
             user_properties,
         };
+        let session_id = allocation.session_id.clone();
 
         let mut allocations = self.user_allocations.lock().await;
         allocations.insert(format!("{}:{}", user_id, experiment_id), allocation);
+        drop(allocations);
+
+        let source = exposure::SOURCE_ALLOCATION;
+        self.exposures
+            .log_exposure(user_id, &session_id, experiment_id, &variant_id, source)
+            .await?;
 
         Ok(variant_id)
     }
 
-    pub async fn get_user_variant(&self, user_id: &str, experiment_id: &str) -> Result<Option<String>, WarpError> {
+    pub async fn get_user_variant(
+        &self,
+        user_id: &str,
+        experiment_id: &str,
+    ) -> Result<Option<String>, WarpError> {
         let allocations = self.user_allocations.lock().await;
         let key = format!("{}:{}", user_id, experiment_id);
-        
-        Ok(allocations.get(&key).map(|allocation| allocation.variant_id.clone()))
+
+        Ok(allocations
+            .get(&key)
+            .map(|allocation| allocation.variant_id.clone()))
     }
 
-    pub async fn track_conversion(&self, user_id: &str, experiment_id: &str, metric_name: &str, value: f64) -> Result<(), WarpError> {
-        self.metrics_collector.track_conversion(user_id, experiment_id, metric_name, value).await
+    // Whether the user sees `variant_id`. Users outside the experiment see
+    // none of its variants and aren't counted as exposed.
+    pub async fn is_variant_enabled(
+        &self,
+        user_id: &str,
+        experiment_id: &str,
+        variant_id: &str,
+    ) -> Result<bool, WarpError> {
+        let allocation = self.allocation(user_id, experiment_id).await;
+        let Some(allocation) = allocation else {
+            return Ok(false);
+        };
+        self.exposures
+            .log_exposure(
+                user_id,
+                &allocation.session_id,
+                experiment_id,
+                &allocation.variant_id,
+                exposure::SOURCE_CHECK,
+            )
+            .await?;
+        Ok(allocation.variant_id == variant_id)
     }
 
-    pub async fn analyze_experiment(&self, experiment_id: &str) -> Result<ExperimentResult, WarpError> {
-        let experiments = self.experiments.lock().await;
-        let experiment = experiments.get(experiment_id)
-            .ok_or_else(|| WarpError::ConfigError(format!("Experiment not found: {}", experiment_id)))?;
+    // Conversions from users never allocated to the experiment aren't counted
+    pub async fn track_conversion(
+        &self,
+        user_id: &str,
+        experiment_id: &str,
+        metric_name: &str,
+        value: f64,
+    ) -> Result<(), WarpError> {
+        if let Some(allocation) = self.allocation(user_id, experiment_id).await {
+            self.exposures
+                .log_conversion(
+                    user_id,
+                    &allocation.session_id,
+                    experiment_id,
+                    &allocation.variant_id,
+                    metric_name,
+                    value,
+                )
+                .await?;
+        }
+        Ok(())
+    }
 
-        self.analyzer.analyze_experiment(experiment).await
+    async fn allocation(&self, user_id: &str, experiment_id: &str) -> Option<UserAllocation> {
+        let allocations = self.user_allocations.lock().await;
+        allocations
+            .get(&format!("{}:{}", user_id, experiment_id))
+            .cloned()
+    }
+
+    // Per-variant funnels for one metric, from the exposure/conversion join
+    pub async fn analyze_experiment(
+        &self,
+        experiment_id: &str,
+        metric_name: &str,
+    ) -> Result<HashMap<String, exposure::VariantFunnel>, WarpError> {
+        if !self.experiments.lock().await.contains_key(experiment_id) {
+            return Err(WarpError::not_found("experiment", experiment_id));
+        }
+        Ok(self.exposures.funnels(experiment_id, metric_name).await)
     }
 
-    pub async fn get_experiment_status(&self, experiment_id: &str) -> Result<ExperimentStatus, WarpError> {
+    pub async fn get_experiment_status(
+        &self,
+        experiment_id: &str,
+    ) -> Result<ExperimentStatus, WarpError> {
         let experiments = self.experiments.lock().await;
-        let experiment = experiments.get(experiment_id)
-            .ok_or_else(|| WarpError::ConfigError(format!("Experiment not found: {}", experiment_id)))?;
+        let experiment = experiments
+            .get(experiment_id)
+            .ok_or_else(|| WarpError::not_found("experiment", experiment_id))?;
 
         Ok(experiment.status.clone())
     }
@@ -308,49 +400,68 @@ impl ABTestingFramework {
 
     async fn validate_experiment(&self, experiment: &Experiment) -> Result<(), WarpError> {
         // Validate allocation percentages sum to 100%
-        let total_allocation: f64 = experiment.variants.iter()
+        let total_allocation: f64 = experiment
+            .variants
+            .iter()
             .map(|v| v.allocation_percentage)
             .sum();
-        
+
         if (total_allocation - 100.0).abs() > 0.01 {
-            return Err(WarpError::ConfigError("Variant allocation percentages must sum to 100%".to_string()));
+            return Err(WarpError::Validation(
+                "Variant allocation percentages must sum to 100%".to_string(),
+            ));
         }
 
         // Validate at least one control variant
         if !experiment.variants.iter().any(|v| v.is_control) {
-            return Err(WarpError::ConfigError("Experiment must have at least one control variant".to_string()));
+            return Err(WarpError::Validation(
+                "Experiment must have at least one control variant".to_string(),
+            ));
         }
 
         // Validate target metrics
         if experiment.target_metrics.is_empty() {
-            return Err(WarpError::ConfigError("Experiment must have at least one target metric".to_string()));
+            return Err(WarpError::Validation(
+                "Experiment must have at least one target metric".to_string(),
+            ));
         }
 
         Ok(())
     }
 
-    async fn user_matches_filters(&self, _user_id: &str, user_properties: &HashMap<String, serde_json::Value>, filters: &[ExperimentFilter]) -> Result<bool, WarpError> {
+    async fn user_matches_filters(
+        &self,
+        _user_id: &str,
+        user_properties: &HashMap<String, serde_json::Value>,
+        filters: &[ExperimentFilter],
+    ) -> Result<bool, WarpError> {
         for filter in filters {
             if let Some(property_value) = user_properties.get(&filter.field) {
                 let matches = match filter.operator {
                     FilterOperator::Equals => property_value == &filter.value,
                     FilterOperator::NotEquals => property_value != &filter.value,
                     FilterOperator::GreaterThan => {
-                        if let (Some(prop_num), Some(filter_num)) = (property_value.as_f64(), filter.value.as_f64()) {
+                        if let (Some(prop_num), Some(filter_num)) =
+                            (property_value.as_f64(), filter.value.as_f64())
+                        {
                             prop_num > filter_num
                         } else {
                             false
                         }
                     }
                     FilterOperator::LessThan => {
-                        if let (Some(prop_num), Some(filter_num)) = (property_value.as_f64(), filter.value.as_f64()) {
+                        if let (Some(prop_num), Some(filter_num)) =
+                            (property_value.as_f64(), filter.value.as_f64())
+                        {
                             prop_num < filter_num
                         } else {
                             false
                         }
                     }
                     FilterOperator::Contains => {
-                        if let (Some(prop_str), Some(filter_str)) = (property_value.as_str(), filter.value.as_str()) {
+                        if let (Some(prop_str), Some(filter_str)) =
+                            (property_value.as_str(), filter.value.as_str())
+                        {
                             prop_str.contains(filter_str)
                         } else {
                             false
@@ -383,7 +494,11 @@ impl ABTestingFramework {
         Ok(true)
     }
 
-    async fn allocate_to_variant(&self, user_id: &str, experiment: &Experiment) -> Result<String, WarpError> {
+    async fn allocate_to_variant(
+        &self,
+        user_id: &str,
+        experiment: &Experiment,
+    ) -> Result<String, WarpError> {
         match &experiment.allocation_strategy {
             AllocationStrategy::Random => {
                 let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -391,17 +506,17 @@ impl ABTestingFramework {
                 user_id.hash(&mut hasher);
                 experiment.id.hash(&mut hasher);
                 let hash = hasher.finish();
-                
+
                 let random_value = (hash % 10000) as f64 / 100.0;
                 let mut cumulative = 0.0;
-                
+
                 for variant in &experiment.variants {
                     cumulative += variant.allocation_percentage;
                     if random_value < cumulative {
                         return Ok(variant.id.clone());
                     }
                 }
-                
+
                 // Fallback to first variant
                 Ok(experiment.variants[0].id.clone())
             }
@@ -411,7 +526,7 @@ impl ABTestingFramework {
                 user_id.hash(&mut hasher);
                 seed.hash(&mut hasher);
                 let hash = hasher.finish();
-                
+
                 let variant_index = (hash as usize) % experiment.variants.len();
                 Ok(experiment.variants[variant_index].id.clone())
             }
@@ -421,10 +536,10 @@ impl ABTestingFramework {
                 use std::hash::{Hash, Hasher};
                 user_id.hash(&mut hasher);
                 let hash = hasher.finish();
-                
+
                 let random_value = (hash % 10000) as f64 / 10000.0 * total_weight;
                 let mut cumulative = 0.0;
-                
+
                 for variant in &experiment.variants {
                     if let Some(weight) = weights.get(&variant.id) {
                         cumulative += weight;
@@ -433,15 +548,19 @@ impl ABTestingFramework {
                         }
                     }
                 }
-                
+
                 Ok(experiment.variants[0].id.clone())
             }
             _ => {
                 // For other strategies, use random allocation as fallback
-                self.allocate_to_variant(user_id, &Experiment {
-                    allocation_strategy: AllocationStrategy::Random,
-                    ..experiment.clone()
-                }).await
+                Box::pin(self.allocate_to_variant(
+                    user_id,
+                    &Experiment {
+                        allocation_strategy: AllocationStrategy::Random,
+                        ..experiment.clone()
+                    },
+                ))
+                .await
             }
         }
     }
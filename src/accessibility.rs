@@ -0,0 +1,317 @@
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::config::{AccessibilityConfig, WarpConfig};
+use crate::error::WarpError;
+use crate::i18n;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnnouncementPriority {
+    Polite,    // queued after whatever the screen reader is currently saying
+    Assertive, // interrupts, reserved for errors and state changes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Announcement {
+    pub text: String,
+    pub priority: AnnouncementPriority,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnnouncementBackend {
+    VoiceOver,
+    SpeechDispatcher,
+    LogOnly,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UiPalette {
+    pub header: Color,
+    pub output: Color,
+    pub input: Color,
+    pub assistant: Color,
+    pub error: Color,
+    pub border: Color,
+}
+
+impl Default for UiPalette {
+    fn default() -> Self {
+        Self {
+            header: Color::Cyan,
+            output: Color::White,
+            input: Color::Green,
+            assistant: Color::Yellow,
+            error: Color::Red,
+            border: Color::Grey,
+        }
+    }
+}
+
+impl UiPalette {
+    // White text and borders; input, assistant and errors keep bright,
+    // distinct hues so they still read apart without relying on shade
+    pub fn high_contrast() -> Self {
+        Self {
+            header: Color::White,
+            output: Color::White,
+            input: Color::Yellow,
+            assistant: Color::Cyan,
+            error: Color::Rgb {
+                r: 255,
+                g: 80,
+                b: 80,
+            },
+            border: Color::White,
+        }
+    }
+}
+
+struct PendingOutput {
+    lines: usize,
+    first_seen: Instant,
+}
+
+pub struct AccessibilityManager {
+    config: AccessibilityConfig,
+    backend: AnnouncementBackend,
+    pending_output: Mutex<Option<PendingOutput>>,
+    last_announcement: Mutex<Option<String>>,
+}
+
+impl AccessibilityManager {
+    pub async fn new(config: AccessibilityConfig) -> Result<Self, WarpError> {
+        let backend = if !config.screen_reader_announcements {
+            AnnouncementBackend::LogOnly
+        } else {
+            Self::detect_backend().await
+        };
+
+        Ok(Self {
+            config,
+            backend,
+            pending_output: Mutex::new(None),
+            last_announcement: Mutex::new(None),
+        })
+    }
+
+    async fn detect_backend() -> AnnouncementBackend {
+        if cfg!(target_os = "macos") {
+            return AnnouncementBackend::VoiceOver;
+        }
+
+        let has_spd = Command::new("spd-say")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .map(|s| s.success())
+            .unwrap_or(false);
+
+        if has_spd {
+            AnnouncementBackend::SpeechDispatcher
+        } else {
+            AnnouncementBackend::LogOnly
+        }
+    }
+
+    pub fn palette(&self) -> UiPalette {
+        if self.config.high_contrast {
+            UiPalette::high_contrast()
+        } else {
+            UiPalette::default()
+        }
+    }
+
+    pub fn reduced_motion(&self) -> bool {
+        self.config.reduced_motion
+    }
+
+    // Folds accessibility preferences into the effective configuration so
+    // every subsystem that reads WarpConfig picks them up without extra checks
+    pub fn apply_to_config(&self, config: &mut WarpConfig) {
+        if self.config.reduced_motion {
+            config.ui.animations = false;
+            config.ui.blur = false;
+            config.terminal.cursor_blink = false;
+            config.gpu.max_fps = config.gpu.max_fps.min(30);
+        }
+        if self.config.high_contrast {
+            config.ui.opacity = 1.0;
+        }
+        config.ui.font_size = config.ui.font_size.max(self.config.min_font_size);
+    }
+
+    pub async fn announce(
+        &self,
+        text: impl Into<String>,
+        priority: AnnouncementPriority,
+    ) -> Result<(), WarpError> {
+        let announcement = Announcement {
+            text: text.into(),
+            priority,
+        };
+
+        if !self.config.screen_reader_announcements || announcement.text.trim().is_empty() {
+            return Ok(());
+        }
+
+        {
+            // Screen readers repeating the same status twice is noise, not information
+            let mut last = self.last_announcement.lock().await;
+            if last.as_deref() == Some(announcement.text.as_str())
+                && priority == AnnouncementPriority::Polite
+            {
+                return Ok(());
+            }
+            *last = Some(announcement.text.clone());
+        }
+
+        self.dispatch(&announcement).await
+    }
+
+    // New output is summarised rather than read verbatim; bursts within the
+    // coalesce window collapse into a single "N new lines" announcement
+    pub async fn note_output(&self, lines: usize) -> Result<(), WarpError> {
+        if lines == 0 || !self.config.announce_output {
+            return Ok(());
+        }
+
+        let mut pending = self.pending_output.lock().await;
+        match pending.as_mut() {
+            Some(p) => p.lines += lines,
+            None => {
+                *pending = Some(PendingOutput {
+                    lines,
+                    first_seen: Instant::now(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn flush_output_announcements(&self) -> Result<(), WarpError> {
+        let window = Duration::from_millis(self.config.output_coalesce_ms);
+        let ready = {
+            let mut pending = self.pending_output.lock().await;
+            match pending.as_ref() {
+                Some(p) if p.first_seen.elapsed() >= window => pending.take(),
+                _ => None,
+            }
+        };
+
+        if let Some(p) = ready {
+            let text = i18n::t_plural("a11y.new_output", p.lines as i64);
+            self.announce(text, AnnouncementPriority::Polite).await?;
+        }
+        Ok(())
+    }
+
+    async fn dispatch(&self, announcement: &Announcement) -> Result<(), WarpError> {
+        match self.backend {
+            AnnouncementBackend::VoiceOver => {
+                let script = format!(
+                    "tell application \"VoiceOver\" to output \"{}\"",
+                    announcement.text.replace('\\', "\\\\").replace('"', "\\\"")
+                );
+                Command::new("osascript")
+                    .arg("-e")
+                    .arg(script)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+            }
+            AnnouncementBackend::SpeechDispatcher => {
+                let mut command = Command::new("spd-say");
+                if announcement.priority == AnnouncementPriority::Assertive {
+                    command.arg("--cancel");
+                }
+                command
+                    .arg("--")
+                    .arg(&announcement.text)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+            }
+            AnnouncementBackend::LogOnly => {}
+        }
+
+        log::debug!(
+            "a11y announcement ({:?}): {}",
+            announcement.priority,
+            announcement.text
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(high_contrast: bool, reduced_motion: bool) -> AccessibilityConfig {
+        AccessibilityConfig {
+            screen_reader_announcements: false,
+            announce_output: true,
+            output_coalesce_ms: 0,
+            high_contrast,
+            reduced_motion,
+            min_font_size: 16,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reduced_motion_folds_into_config() {
+        let manager = AccessibilityManager::new(config(false, true))
+            .await
+            .unwrap();
+        let mut warp = WarpConfig::default();
+        warp.ui.font_size = 12;
+        warp.gpu.max_fps = 120;
+        manager.apply_to_config(&mut warp);
+
+        assert!(!warp.ui.animations);
+        assert!(!warp.terminal.cursor_blink);
+        assert_eq!(warp.gpu.max_fps, 30);
+        assert_eq!(warp.ui.font_size, 16);
+    }
+
+    #[tokio::test]
+    async fn test_high_contrast_palette() {
+        let manager = AccessibilityManager::new(config(true, false))
+            .await
+            .unwrap();
+        let mut warp = WarpConfig::default();
+        warp.ui.opacity = 0.8;
+        manager.apply_to_config(&mut warp);
+
+        assert_eq!(warp.ui.opacity, 1.0);
+        assert_eq!(manager.palette().border, Color::White);
+        assert!(!manager.reduced_motion());
+    }
+
+    #[tokio::test]
+    async fn test_output_is_coalesced_until_flushed() {
+        let manager = AccessibilityManager::new(config(false, false))
+            .await
+            .unwrap();
+        manager.note_output(2).await.unwrap();
+        manager.note_output(3).await.unwrap();
+        assert_eq!(
+            manager
+                .pending_output
+                .lock()
+                .await
+                .as_ref()
+                .map(|p| p.lines),
+            Some(5)
+        );
+
+        manager.flush_output_announcements().await.unwrap();
+        assert!(manager.pending_output.lock().await.is_none());
+    }
+}
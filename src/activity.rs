@@ -0,0 +1,235 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::config::ActivityConfig;
+use crate::error::WarpError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    Output,  // new output since the tab was last looked at
+    Silence, // busy, then quiet: probably done
+}
+
+#[derive(Debug, Default)]
+struct PaneActivity {
+    last_output: Option<Instant>,
+    busy_since: Option<Instant>, // start of the current run of output
+    indicator: Option<Indicator>,
+}
+
+// Per-pane output and silence, for the tab strip and the auto-focus rules.
+// The active pane is tracked too, so a build that was started in the
+// foreground still reports going quiet once the user switches away.
+pub struct ActivityMonitor {
+    silence: Duration,
+    min_busy: Duration,
+    focus_on_silence: bool,
+    focus_patterns: Vec<Regex>,
+    panes: HashMap<usize, PaneActivity>,
+    muted: HashSet<usize>,
+}
+
+impl ActivityMonitor {
+    pub fn from_config(config: &ActivityConfig) -> Result<Self, WarpError> {
+        let focus_patterns = config
+            .focus_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    WarpError::config_err(format!("Invalid focus pattern '{}': {}", pattern, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            silence: Duration::from_secs(config.silence_secs.max(1)),
+            min_busy: Duration::from_secs(config.min_busy_secs),
+            focus_on_silence: config.focus_on_silence,
+            focus_patterns,
+            panes: HashMap::new(),
+            muted: HashSet::new(),
+        })
+    }
+
+    // Records output from a pane. Returns whether a background pane's text
+    // matched a focus pattern.
+    pub fn output(&mut self, pane: usize, text: &str, active: bool, now: Instant) -> bool {
+        let silence = self.silence;
+        let activity = self.panes.entry(pane).or_default();
+        if activity
+            .last_output
+            .is_none_or(|last| now.duration_since(last) >= silence)
+        {
+            activity.busy_since = Some(now);
+        }
+        activity.last_output = Some(now);
+        if active || self.muted.contains(&pane) {
+            return false;
+        }
+        activity.indicator = Some(Indicator::Output);
+        self.focus_patterns
+            .iter()
+            .any(|pattern| pattern.is_match(text))
+    }
+
+    // Marks panes that were busy for a while and have gone quiet. Returns
+    // the first of them to focus when focus_on_silence is set.
+    pub fn tick(&mut self, active: Option<usize>, now: Instant) -> Option<usize> {
+        let mut focus = None;
+        let mut panes: Vec<_> = self.panes.iter_mut().collect();
+        panes.sort_by_key(|(pane, _)| **pane);
+        for (pane, activity) in panes {
+            let (Some(last), Some(since)) = (activity.last_output, activity.busy_since) else {
+                continue;
+            };
+            if now.duration_since(last) < self.silence {
+                continue;
+            }
+            activity.busy_since = None;
+            if last.duration_since(since) < self.min_busy
+                || active == Some(*pane)
+                || self.muted.contains(pane)
+            {
+                continue;
+            }
+            activity.indicator = Some(Indicator::Silence);
+            if self.focus_on_silence && focus.is_none() {
+                focus = Some(*pane);
+            }
+        }
+        focus
+    }
+
+    // The user is looking at the pane now
+    pub fn focused(&mut self, pane: usize) {
+        if let Some(activity) = self.panes.get_mut(&pane) {
+            activity.indicator = None;
+        }
+    }
+
+    // Returns whether the pane is muted now
+    pub fn toggle_mute(&mut self, pane: usize) -> bool {
+        if self.muted.remove(&pane) {
+            return false;
+        }
+        self.muted.insert(pane);
+        self.focused(pane);
+        true
+    }
+
+    // The next pane after `active` with an indicator, wrapping around
+    pub fn next_with_indicator(&self, active: Option<usize>, count: usize) -> Option<usize> {
+        let start = active.map_or(0, |active| active + 1);
+        (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|pane| self.indicator(*pane).is_some())
+    }
+
+    pub fn indicator(&self, pane: usize) -> Option<Indicator> {
+        self.panes
+            .get(&pane)
+            .and_then(|activity| activity.indicator)
+    }
+
+    pub fn badges(&self, titles: &[String], active: Option<usize>) -> Vec<TabBadge> {
+        titles
+            .iter()
+            .enumerate()
+            .map(|(pane, title)| TabBadge {
+                pane,
+                title: title.clone(),
+                active: active == Some(pane),
+                indicator: self.indicator(pane),
+                muted: self.muted.contains(&pane),
+            })
+            .collect()
+    }
+}
+
+// One tab in the header's tab strip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabBadge {
+    pub pane: usize,
+    pub title: String,
+    pub active: bool,
+    pub indicator: Option<Indicator>,
+    pub muted: bool,
+}
+
+impl TabBadge {
+    // `1:zsh●`: number, title, then activity or silence, or ∅ when muted
+    pub fn label(&self) -> String {
+        let mark = match (self.muted, self.indicator) {
+            (true, _) => "∅",
+            (false, Some(Indicator::Output)) => "●",
+            (false, Some(Indicator::Silence)) => "✓",
+            (false, None) => "",
+        };
+        format!("{}:{}{}", self.pane, self.title, mark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(focus_on_silence: bool, patterns: &[&str]) -> ActivityMonitor {
+        ActivityMonitor::from_config(&ActivityConfig {
+            silence_secs: 5,
+            min_busy_secs: 3,
+            focus_on_silence,
+            focus_patterns: patterns.iter().map(|pattern| pattern.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_output_and_focus_patterns() {
+        let mut monitor = monitor(false, &["(?i)error"]);
+        let now = Instant::now();
+        assert!(!monitor.output(0, "Compiling", true, now));
+        assert_eq!(monitor.indicator(0), None);
+        assert!(!monitor.output(1, "Compiling", false, now));
+        assert_eq!(monitor.indicator(1), Some(Indicator::Output));
+        assert!(monitor.output(1, "ERROR: linker failed", false, now));
+        assert_eq!(monitor.next_with_indicator(Some(1), 3), Some(1));
+        monitor.focused(1);
+        assert_eq!(monitor.next_with_indicator(Some(1), 3), None);
+
+        // Muted panes neither show activity nor take focus
+        assert!(monitor.toggle_mute(2));
+        assert!(!monitor.output(2, "error", false, now));
+        let titles = vec!["zsh".to_string(), "bash".to_string(), "fish".to_string()];
+        let labels: Vec<String> = monitor
+            .badges(&titles, Some(0))
+            .iter()
+            .map(TabBadge::label)
+            .collect();
+        assert_eq!(labels, vec!["0:zsh", "1:bash", "2:fish∅"]);
+    }
+
+    #[test]
+    fn test_silence_after_busy() {
+        let mut monitor = monitor(true, &[]);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        // A build in pane 1 writes for 4 seconds, a blip in pane 2 for none
+        for secs in 0..=4 {
+            monitor.output(1, "building", false, at(secs));
+        }
+        monitor.output(2, "hi", false, at(4));
+        assert_eq!(monitor.tick(Some(0), at(6)), None);
+        assert_eq!(monitor.tick(Some(0), at(9)), Some(1));
+        assert_eq!(monitor.indicator(1), Some(Indicator::Silence));
+        assert_eq!(monitor.indicator(2), Some(Indicator::Output));
+        // Reported once per run of output
+        assert_eq!(monitor.tick(Some(0), at(20)), None);
+
+        // Going quiet in the active pane isn't news
+        for secs in 30..=34 {
+            monitor.output(0, "building", true, at(secs));
+        }
+        assert_eq!(monitor.tick(Some(0), at(40)), None);
+        assert_eq!(monitor.indicator(0), None);
+    }
+}
@@ -64,7 +64,7 @@ impl OpenAICompletionProvider {
 
     async fn call_openai(&self, messages: Vec<Message>) -> Result<String, WarpError> {
         let api_key = self.api_key.as_ref()
-            .ok_or_else(|| WarpError::AIError("OpenAI API key not set".to_string()))?;
+            .ok_or_else(|| WarpError::Ai("OpenAI API key not set".to_string()))?;
 
         let request = CompletionRequest {
             model: self.model.clone(),
@@ -81,17 +81,17 @@ impl OpenAICompletionProvider {
             .json(&request)
             .send()
             .await
-            .map_err(|e| WarpError::AIError(format!("OpenAI API request failed: {}", e)))?;
+            .map_err(|e| WarpError::network("OpenAI API request failed", e))?;
 
         let completion: CompletionResponse = response
             .json()
             .await
-            .map_err(|e| WarpError::AIError(format!("Failed to parse OpenAI response: {}", e)))?;
+            .map_err(|e| WarpError::Ai(format!("Failed to parse OpenAI response: {}", e)))?;
 
         completion.choices
             .first()
             .and_then(|choice| choice.message.content.clone())
-            .ok_or_else(|| WarpError::AIError("No completion received from OpenAI".to_string()))
+            .ok_or_else(|| WarpError::Ai("No completion received from OpenAI".to_string()))
     }
 }
 
@@ -169,7 +169,7 @@ impl AICompletionProvider for OpenAICompletionProvider {
         context: &CompletionContext,
     ) -> Result<String, WarpError> {
         if self.api_key.is_none() {
-            return Err(WarpError::AIError("OpenAI API key not set".to_string()));
+            return Err(WarpError::Ai("OpenAI API key not set".to_string()));
         }
 
         let messages = vec![
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::config::{AlertSource, AlertsConfig, NotificationLevel};
+
+// Alerts raised faster than the router drains them are dropped oldest first
+const BUS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub source: AlertSource,
+    pub level: NotificationLevel,
+    pub title: String,
+    pub body: Option<String>,
+    pub at: DateTime<Local>,
+}
+
+impl Alert {
+    pub fn new(source: AlertSource, level: NotificationLevel, title: impl Into<String>) -> Self {
+        Self {
+            source,
+            level,
+            title: title.into(),
+            body: None,
+            at: Local::now(),
+        }
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    // One line for a chat system message, e.g. "[CI] error: build failed on #12"
+    pub fn chat_text(&self) -> String {
+        let level = match self.level {
+            NotificationLevel::Info => "info",
+            NotificationLevel::Success => "ok",
+            NotificationLevel::Warning => "warning",
+            NotificationLevel::Error => "error",
+        };
+        let mut text = format!("[{:?}] {}: {}", self.source, level, self.title);
+        if let Some(body) = &self.body {
+            text.push_str(" — ");
+            text.push_str(body);
+        }
+        text
+    }
+}
+
+// Producers publish raised alerts; the app routes each one once and
+// forwards those bound for chat, which a collaboration session relays
+#[derive(Clone)]
+pub struct AlertBus {
+    raised: broadcast::Sender<Alert>,
+    chat: broadcast::Sender<Alert>,
+}
+
+impl AlertBus {
+    pub fn new() -> Self {
+        Self {
+            raised: broadcast::channel(BUS_CAPACITY).0,
+            chat: broadcast::channel(BUS_CAPACITY).0,
+        }
+    }
+
+    pub fn publish(&self, alert: Alert) {
+        // Nobody listening just means routing isn't running yet
+        let _ = self.raised.send(alert);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Alert> {
+        self.raised.subscribe()
+    }
+
+    pub fn forward_to_chat(&self, alert: Alert) {
+        let _ = self.chat.send(alert);
+    }
+
+    pub fn subscribe_chat(&self) -> broadcast::Receiver<Alert> {
+        self.chat.subscribe()
+    }
+}
+
+impl Default for AlertBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Route {
+    pub notify: bool,
+    pub chat: bool,
+}
+
+pub struct AlertRouter {
+    config: AlertsConfig,
+    // When each (source, title) was last posted to chat
+    posted: HashMap<(AlertSource, String), Instant>,
+}
+
+impl AlertRouter {
+    pub fn new(config: AlertsConfig) -> Self {
+        Self {
+            config,
+            posted: HashMap::new(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: AlertsConfig) {
+        self.config = config;
+    }
+
+    // Repeats inside the window still reach the notification center, which
+    // is private history; only the shared chat is spared them
+    pub fn route(&mut self, alert: &Alert, now: Instant) -> Route {
+        let Some(rule) = self.config.routes.iter().find(|rule| {
+            rule.source.is_none_or(|source| source == alert.source) && alert.level >= rule.min_level
+        }) else {
+            return Route::default();
+        };
+        let mut route = Route {
+            notify: rule.notify,
+            chat: rule.chat,
+        };
+        if route.chat {
+            let window = Duration::from_secs(self.config.repeat_secs);
+            self.posted.retain(|_, at| now.duration_since(*at) < window);
+            let key = (alert.source, alert.title.clone());
+            match self.posted.entry(key) {
+                std::collections::hash_map::Entry::Occupied(_) => route.chat = false,
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(now);
+                }
+            }
+        }
+        route
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AlertRoute, WarpConfig};
+
+    #[test]
+    fn test_routes_by_source_and_level() {
+        let mut config = WarpConfig::default().alerts;
+        config.routes.insert(
+            0,
+            AlertRoute {
+                source: Some(AlertSource::Anomalies),
+                min_level: NotificationLevel::Error,
+                notify: false,
+                chat: true,
+            },
+        );
+        let mut router = AlertRouter::new(config);
+        let now = Instant::now();
+
+        let anomaly = Alert::new(
+            AlertSource::Anomalies,
+            NotificationLevel::Error,
+            "error rate spike",
+        );
+        assert_eq!(
+            router.route(&anomaly, now),
+            Route {
+                notify: false,
+                chat: true
+            }
+        );
+        let warning = Alert::new(AlertSource::CI, NotificationLevel::Warning, "flaky test");
+        assert_eq!(
+            router.route(&warning, now),
+            Route {
+                notify: true,
+                chat: true
+            }
+        );
+        let info = Alert::new(
+            AlertSource::Metrics,
+            NotificationLevel::Info,
+            "p95 recovered",
+        );
+        assert_eq!(
+            router.route(&info, now),
+            Route {
+                notify: true,
+                chat: false
+            }
+        );
+
+        // Repeats stay out of chat until the window passes
+        assert_eq!(
+            router.route(&warning, now),
+            Route {
+                notify: true,
+                chat: false
+            }
+        );
+        assert!(router.route(&warning, now + Duration::from_secs(601)).chat);
+    }
+
+    #[test]
+    fn test_chat_text() {
+        let alert = Alert::new(
+            AlertSource::CI,
+            NotificationLevel::Error,
+            "CI failed on #12",
+        )
+        .with_body("lint");
+        assert_eq!(alert.chat_text(), "[CI] error: CI failed on #12 — lint");
+    }
+}
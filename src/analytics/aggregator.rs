@@ -60,7 +60,7 @@ impl MetricsAggregator {
     }
 
     pub async fn process_pending_events(&mut self) -> Result<(), WarpError> {
-        for event in self.pending_events.drain(..) {
+        for event in std::mem::take(&mut self.pending_events) {
             self.process_event(event).await?;
         }
         Ok(())
@@ -109,8 +109,10 @@ impl MetricsAggregator {
 
     async fn process_activation_event(&mut self, event: &AnalyticsEvent) -> Result<(), WarpError> {
         if let Some(item_id) = &event.item_id {
-            let metrics = self.usage_metrics.entry(item_id.clone()).or_insert_with(|| {
-                UsageMetrics {
+            let metrics = self
+                .usage_metrics
+                .entry(item_id.clone())
+                .or_insert_with(|| UsageMetrics {
                     item_id: item_id.clone(),
                     total_activations: 0,
                     total_usage_time: Duration::seconds(0),
@@ -123,22 +125,22 @@ impl MetricsAggregator {
                     error_rate: 0.0,
                     performance_score: 0.0,
                     user_satisfaction: 0.0,
-                }
-            });
+                });
 
             metrics.total_activations += 1;
-            
+
             // Update real-time metrics
-            let real_time = self.real_time_cache.entry(item_id.clone()).or_insert_with(|| {
-                RealTimeMetrics {
+            let real_time = self
+                .real_time_cache
+                .entry(item_id.clone())
+                .or_insert_with(|| RealTimeMetrics {
                     active_users: 0,
                     current_usage: 0,
                     error_rate: 0.0,
                     performance_score: 0.0,
                     last_updated: Utc::now(),
-                }
-            });
-            
+                });
+
             real_time.active_users += 1;
             real_time.current_usage += 1;
             real_time.last_updated = Utc::now();
@@ -146,7 +148,10 @@ impl MetricsAggregator {
         Ok(())
     }
 
-    async fn process_deactivation_event(&mut self, event: &AnalyticsEvent) -> Result<(), WarpError> {
+    async fn process_deactivation_event(
+        &mut self,
+        event: &AnalyticsEvent,
+    ) -> Result<(), WarpError> {
         if let Some(item_id) = &event.item_id {
             if let Some(real_time) = self.real_time_cache.get_mut(item_id) {
                 if real_time.active_users > 0 {
@@ -161,11 +166,12 @@ impl MetricsAggregator {
                     // This would calculate actual session duration based on activation/deactivation times
                     let session_duration = Duration::minutes(5); // Placeholder
                     metrics.total_usage_time = metrics.total_usage_time + session_duration;
-                    
+
                     // Update average session duration
                     if metrics.total_activations > 0 {
                         metrics.average_session_duration = Duration::seconds(
-                            metrics.total_usage_time.num_seconds() / metrics.total_activations as i64
+                            metrics.total_usage_time.num_seconds()
+                                / metrics.total_activations as i64,
                         );
                     }
                 }
@@ -177,8 +183,10 @@ impl MetricsAggregator {
     async fn process_usage_event(&mut self, event: &AnalyticsEvent) -> Result<(), WarpError> {
         if let Some(item_id) = &event.item_id {
             // Update user behavior metrics
-            let behavior_metrics = self.user_behavior_metrics.entry(item_id.clone()).or_insert_with(|| {
-                UserBehaviorMetrics {
+            let behavior_metrics = self
+                .user_behavior_metrics
+                .entry(item_id.clone())
+                .or_insert_with(|| UserBehaviorMetrics {
                     item_id: item_id.clone(),
                     feature_usage: HashMap::new(),
                     user_flows: vec![],
@@ -191,13 +199,15 @@ impl MetricsAggregator {
                         feature_discovery_rate: 0.0,
                         user_progression_stages: vec![],
                     },
-                }
-            });
+                });
 
             // Track feature usage
             if let Some(action) = event.metadata.get("action") {
                 if let Some(action_str) = action.as_str() {
-                    *behavior_metrics.feature_usage.entry(action_str.to_string()).or_insert(0) += 1;
+                    *behavior_metrics
+                        .feature_usage
+                        .entry(action_str.to_string())
+                        .or_insert(0) += 1;
                 }
             }
 
@@ -247,8 +257,10 @@ impl MetricsAggregator {
 
     async fn process_load_time_event(&mut self, event: &AnalyticsEvent) -> Result<(), WarpError> {
         if let Some(item_id) = &event.item_id {
-            let perf_metrics = self.performance_metrics.entry(item_id.clone()).or_insert_with(|| {
-                PerformanceMetrics {
+            let perf_metrics = self
+                .performance_metrics
+                .entry(item_id.clone())
+                .or_insert_with(|| PerformanceMetrics {
                     item_id: item_id.clone(),
                     average_load_time: Duration::seconds(0),
                     p95_load_time: Duration::seconds(0),
@@ -260,8 +272,7 @@ impl MetricsAggregator {
                     network_efficiency: 0.0,
                     stability_score: 1.0,
                     resource_efficiency: 0.0,
-                }
-            });
+                });
 
             if let Some(load_time_value) = event.metadata.get("load_time_ms") {
                 if let Some(load_time_ms) = load_time_value.as_u64() {
@@ -276,8 +287,10 @@ impl MetricsAggregator {
     async fn process_performance_event(&mut self, event: &AnalyticsEvent) -> Result<(), WarpError> {
         if let Some(item_id) = &event.item_id {
             if let Some(performance_data) = &event.performance_data {
-                let perf_metrics = self.performance_metrics.entry(item_id.clone()).or_insert_with(|| {
-                    PerformanceMetrics {
+                let perf_metrics = self
+                    .performance_metrics
+                    .entry(item_id.clone())
+                    .or_insert_with(|| PerformanceMetrics {
                         item_id: item_id.clone(),
                         average_load_time: Duration::seconds(0),
                         p95_load_time: Duration::seconds(0),
@@ -289,29 +302,32 @@ impl MetricsAggregator {
                         network_efficiency: 0.0,
                         stability_score: 1.0,
                         resource_efficiency: 0.0,
-                    }
-                });
+                    });
 
                 // Update performance metrics
-                perf_metrics.average_cpu_usage = (perf_metrics.average_cpu_usage + performance_data.cpu_usage) / 2.0;
-                perf_metrics.average_memory_usage = (perf_metrics.average_memory_usage + performance_data.memory_usage) / 2;
-                
+                perf_metrics.average_cpu_usage =
+                    (perf_metrics.average_cpu_usage + performance_data.cpu_usage) / 2.0;
+                perf_metrics.average_memory_usage =
+                    (perf_metrics.average_memory_usage + performance_data.memory_usage) / 2;
+
                 if performance_data.cpu_usage > perf_metrics.peak_cpu_usage {
                     perf_metrics.peak_cpu_usage = performance_data.cpu_usage;
                 }
-                
+
                 if performance_data.memory_usage > perf_metrics.peak_memory_usage {
                     perf_metrics.peak_memory_usage = performance_data.memory_usage;
                 }
 
                 // Calculate resource efficiency score
                 let cpu_efficiency = 1.0 - (performance_data.cpu_usage / 100.0);
-                let memory_efficiency = 1.0 - (performance_data.memory_usage as f32 / (8 * 1024 * 1024 * 1024) as f32); // Assume 8GB baseline
+                let memory_efficiency =
+                    1.0 - (performance_data.memory_usage as f32 / (8 * 1024 * 1024 * 1024) as f32); // Assume 8GB baseline
                 perf_metrics.resource_efficiency = (cpu_efficiency + memory_efficiency) / 2.0;
 
                 // Update real-time performance score
                 if let Some(real_time) = self.real_time_cache.get_mut(item_id) {
-                    real_time.performance_score = perf_metrics.resource_efficiency * perf_metrics.stability_score;
+                    real_time.performance_score =
+                        perf_metrics.resource_efficiency * perf_metrics.stability_score;
                     real_time.last_updated = Utc::now();
                 }
             }
@@ -322,12 +338,12 @@ impl MetricsAggregator {
     async fn process_install_event(&mut self, event: &AnalyticsEvent) -> Result<(), WarpError> {
         // Update marketplace analytics
         self.marketplace_analytics.total_downloads += 1;
-        
+
         if let Some(item_id) = &event.item_id {
             // This would update category distribution, trending items, etc.
             // For now, just increment total downloads
         }
-        
+
         Ok(())
     }
 
@@ -337,7 +353,8 @@ impl MetricsAggregator {
                 // Update user satisfaction based on rating
                 if let Some(rating_value) = event.metadata.get("rating") {
                     if let Some(rating) = rating_value.as_f64() {
-                        metrics.user_satisfaction = (metrics.user_satisfaction + rating as f32) / 2.0;
+                        metrics.user_satisfaction =
+                            (metrics.user_satisfaction + rating as f32) / 2.0;
                     }
                 }
             }
@@ -345,45 +362,69 @@ impl MetricsAggregator {
         Ok(())
     }
 
-    pub async fn get_usage_metrics(&self, item_id: &str, _time_range: TimeRange) -> Result<UsageMetrics, WarpError> {
-        self.usage_metrics.get(item_id)
+    pub async fn get_usage_metrics(
+        &self,
+        item_id: &str,
+        _time_range: TimeRange,
+    ) -> Result<UsageMetrics, WarpError> {
+        self.usage_metrics
+            .get(item_id)
             .cloned()
-            .ok_or_else(|| WarpError::ConfigError(format!("No usage metrics found for item: {}", item_id)))
+            .ok_or_else(|| WarpError::not_found("usage metrics", item_id))
     }
 
-    pub async fn get_performance_metrics(&self, item_id: &str, _time_range: TimeRange) -> Result<PerformanceMetrics, WarpError> {
-        self.performance_metrics.get(item_id)
+    pub async fn get_performance_metrics(
+        &self,
+        item_id: &str,
+        _time_range: TimeRange,
+    ) -> Result<PerformanceMetrics, WarpError> {
+        self.performance_metrics
+            .get(item_id)
             .cloned()
-            .ok_or_else(|| WarpError::ConfigError(format!("No performance metrics found for item: {}", item_id)))
+            .ok_or_else(|| WarpError::not_found("performance metrics", item_id))
     }
 
-    pub async fn get_user_behavior_metrics(&self, item_id: &str, _time_range: TimeRange) -> Result<UserBehaviorMetrics, WarpError> {
-        self.user_behavior_metrics.get(item_id)
+    pub async fn get_user_behavior_metrics(
+        &self,
+        item_id: &str,
+        _time_range: TimeRange,
+    ) -> Result<UserBehaviorMetrics, WarpError> {
+        self.user_behavior_metrics
+            .get(item_id)
             .cloned()
-            .ok_or_else(|| WarpError::ConfigError(format!("No user behavior metrics found for item: {}", item_id)))
+            .ok_or_else(|| WarpError::not_found("user behavior metrics", item_id))
     }
 
-    pub async fn get_marketplace_analytics(&self, _time_range: TimeRange) -> Result<MarketplaceAnalytics, WarpError> {
+    pub async fn get_marketplace_analytics(
+        &self,
+        _time_range: TimeRange,
+    ) -> Result<MarketplaceAnalytics, WarpError> {
         Ok(self.marketplace_analytics.clone())
     }
 
     pub async fn update_real_time_metrics(&mut self) -> Result<(), WarpError> {
         let now = Utc::now();
-        
+
         // Update marketplace totals
-        self.marketplace_analytics.total_active_users = self.real_time_cache.values()
+        self.marketplace_analytics.total_active_users = self
+            .real_time_cache
+            .values()
             .map(|metrics| metrics.active_users)
             .sum();
 
         // Calculate trending items based on recent activity
         let mut trending_items = Vec::new();
         for (item_id, real_time) in &self.real_time_cache {
-            if now.signed_duration_since(real_time.last_updated).num_minutes() < 60 {
+            if now
+                .signed_duration_since(real_time.last_updated)
+                .num_minutes()
+                < 60
+            {
                 let momentum_score = real_time.current_usage as f32 * real_time.performance_score;
                 trending_items.push(TrendingItem {
                     item_id: item_id.clone(),
                     name: format!("Item {}", item_id), // Would be fetched from item metadata
-                    growth_rate: 0.1, // Would be calculated from historical data
+                    growth_rate: 0.1,                  // Would be calculated from historical data
                     velocity: real_time.current_usage as f32,
                     momentum_score,
                 });
@@ -391,9 +432,13 @@ impl MetricsAggregator {
         }
 
         // Sort by momentum score
-        trending_items.sort_by(|a, b| b.momentum_score.partial_cmp(&a.momentum_score).unwrap_or(std::cmp::Ordering::Equal));
+        trending_items.sort_by(|a, b| {
+            b.momentum_score
+                .partial_cmp(&a.momentum_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
         trending_items.truncate(10);
-        
+
         self.marketplace_analytics.trending_items = trending_items;
 
         Ok(())
@@ -401,11 +446,10 @@ impl MetricsAggregator {
 
     pub async fn cleanup_old_data(&mut self) -> Result<(), WarpError> {
         let cutoff_time = Utc::now() - Duration::hours(24);
-        
+
         // Remove stale real-time metrics
-        self.real_time_cache.retain(|_, metrics| {
-            metrics.last_updated > cutoff_time
-        });
+        self.real_time_cache
+            .retain(|_, metrics| metrics.last_updated > cutoff_time);
 
         Ok(())
     }
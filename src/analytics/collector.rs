@@ -2,8 +2,8 @@ use super::*;
 use crate::error::WarpError;
 use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::sync::{Mutex, mpsc};
-use sysinfo::{System, SystemExt, ProcessExt, CpuExt};
+use sysinfo::System;
+use tokio::sync::{mpsc, Mutex};
 
 pub struct EventCollector {
     event_queue: Arc<Mutex<VecDeque<AnalyticsEvent>>>,
@@ -52,10 +52,10 @@ impl EventCollector {
     pub async fn new() -> Result<Self, WarpError> {
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
         let session_id = uuid::Uuid::new_v4().to_string();
-        
+
         let mut system = System::new_all();
         system.refresh_all();
-        
+
         let baseline = SystemBaseline {
             baseline_cpu: system.global_cpu_info().cpu_usage(),
             baseline_memory: system.used_memory(),
@@ -82,7 +82,7 @@ impl EventCollector {
         {
             let mut queue = self.event_queue.lock().await;
             queue.push_back(event.clone());
-            
+
             // Limit queue size
             if queue.len() > 10000 {
                 queue.pop_front();
@@ -90,8 +90,9 @@ impl EventCollector {
         }
 
         // Send to processing pipeline
-        self.event_sender.send(event)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to send event: {}", e)))?;
+        self.event_sender
+            .send(event)
+            .map_err(|e| WarpError::network("Failed to send event", e))?;
 
         Ok(())
     }
@@ -110,14 +111,14 @@ impl EventCollector {
 
         // Start performance monitoring for this item
         self.start_item_monitoring(item_id).await?;
-        
+
         self.collect_event(event).await
     }
 
     pub async fn track_item_deactivation(&self, item_id: &str) -> Result<(), WarpError> {
         // Stop performance monitoring and collect final metrics
         let performance_data = self.stop_item_monitoring(item_id).await?;
-        
+
         let event = AnalyticsEvent {
             id: uuid::Uuid::new_v4().to_string(),
             event_type: EventType::ItemDeactivation,
@@ -132,9 +133,17 @@ impl EventCollector {
         self.collect_event(event).await
     }
 
-    pub async fn track_item_usage(&self, item_id: &str, action: &str, metadata: HashMap<String, serde_json::Value>) -> Result<(), WarpError> {
+    pub async fn track_item_usage(
+        &self,
+        item_id: &str,
+        action: &str,
+        metadata: HashMap<String, serde_json::Value>,
+    ) -> Result<(), WarpError> {
         let mut event_metadata = metadata;
-        event_metadata.insert("action".to_string(), serde_json::Value::String(action.to_string()));
+        event_metadata.insert(
+            "action".to_string(),
+            serde_json::Value::String(action.to_string()),
+        );
 
         let event = AnalyticsEvent {
             id: uuid::Uuid::new_v4().to_string(),
@@ -150,11 +159,22 @@ impl EventCollector {
         self.collect_event(event).await
     }
 
-    pub async fn track_item_error(&self, item_id: &str, error: &str, stack_trace: Option<&str>) -> Result<(), WarpError> {
+    pub async fn track_item_error(
+        &self,
+        item_id: &str,
+        error: &str,
+        stack_trace: Option<&str>,
+    ) -> Result<(), WarpError> {
         let mut metadata = HashMap::new();
-        metadata.insert("error".to_string(), serde_json::Value::String(error.to_string()));
+        metadata.insert(
+            "error".to_string(),
+            serde_json::Value::String(error.to_string()),
+        );
         if let Some(stack) = stack_trace {
-            metadata.insert("stack_trace".to_string(), serde_json::Value::String(stack.to_string()));
+            metadata.insert(
+                "stack_trace".to_string(),
+                serde_json::Value::String(stack.to_string()),
+            );
         }
 
         // Update error count
@@ -181,7 +201,10 @@ impl EventCollector {
 
     pub async fn track_item_crash(&self, item_id: &str, crash_info: &str) -> Result<(), WarpError> {
         let mut metadata = HashMap::new();
-        metadata.insert("crash_info".to_string(), serde_json::Value::String(crash_info.to_string()));
+        metadata.insert(
+            "crash_info".to_string(),
+            serde_json::Value::String(crash_info.to_string()),
+        );
 
         // Update crash count
         {
@@ -218,7 +241,10 @@ impl EventCollector {
         }
 
         let mut metadata = HashMap::new();
-        metadata.insert("load_time_ms".to_string(), serde_json::Value::Number(serde_json::Number::from(load_time_ms)));
+        metadata.insert(
+            "load_time_ms".to_string(),
+            serde_json::Value::Number(serde_json::Number::from(load_time_ms)),
+        );
 
         let event = AnalyticsEvent {
             id: uuid::Uuid::new_v4().to_string(),
@@ -236,7 +262,7 @@ impl EventCollector {
 
     async fn start_item_monitoring(&self, item_id: &str) -> Result<(), WarpError> {
         let mut tracker = self.performance_tracker.lock().await;
-        
+
         let metrics = ItemPerformanceMetrics {
             item_id: item_id.to_string(),
             start_time: Utc::now(),
@@ -250,46 +276,60 @@ impl EventCollector {
         };
 
         tracker.item_metrics.insert(item_id.to_string(), metrics);
-        
+
         Ok(())
     }
 
-    async fn stop_item_monitoring(&self, item_id: &str) -> Result<Option<PerformanceData>, WarpError> {
+    async fn stop_item_monitoring(
+        &self,
+        item_id: &str,
+    ) -> Result<Option<PerformanceData>, WarpError> {
         let mut tracker = self.performance_tracker.lock().await;
-        
+
         if let Some(metrics) = tracker.item_metrics.remove(item_id) {
             let performance_data = PerformanceData {
-                cpu_usage: metrics.cpu_samples.iter().sum::<f32>() / metrics.cpu_samples.len() as f32,
-                memory_usage: metrics.memory_samples.iter().sum::<u64>() / metrics.memory_samples.len() as u64,
+                cpu_usage: metrics.cpu_samples.iter().sum::<f32>()
+                    / metrics.cpu_samples.len() as f32,
+                memory_usage: metrics.memory_samples.iter().sum::<u64>()
+                    / metrics.memory_samples.len() as u64,
                 disk_usage: 0, // Would be calculated
                 network_bytes_sent: metrics.network_samples.iter().map(|s| s.bytes_sent).sum(),
-                network_bytes_received: metrics.network_samples.iter().map(|s| s.bytes_received).sum(),
-                load_time_ms: metrics.load_times.iter().sum::<u64>() / metrics.load_times.len() as u64,
-                response_time_ms: metrics.response_times.iter().sum::<u64>() / metrics.response_times.len() as u64,
+                network_bytes_received: metrics
+                    .network_samples
+                    .iter()
+                    .map(|s| s.bytes_received)
+                    .sum(),
+                load_time_ms: metrics.load_times.iter().sum::<u64>()
+                    / metrics.load_times.len() as u64,
+                response_time_ms: metrics.response_times.iter().sum::<u64>()
+                    / metrics.response_times.len() as u64,
                 error_count: metrics.error_count,
                 crash_count: metrics.crash_count,
             };
-            
+
             Ok(Some(performance_data))
         } else {
             Ok(None)
         }
     }
 
-    async fn get_current_performance_data(&self, item_id: &str) -> Result<Option<PerformanceData>, WarpError> {
+    async fn get_current_performance_data(
+        &self,
+        item_id: &str,
+    ) -> Result<Option<PerformanceData>, WarpError> {
         let mut system = self.system_monitor.lock().await;
         system.refresh_all();
-        
+
         let tracker = self.performance_tracker.lock().await;
-        
+
         if let Some(metrics) = tracker.item_metrics.get(item_id) {
             let current_cpu = system.global_cpu_info().cpu_usage();
             let current_memory = system.used_memory();
-            
+
             let performance_data = PerformanceData {
                 cpu_usage: current_cpu,
                 memory_usage: current_memory,
-                disk_usage: 0, // Would be calculated from disk I/O
+                disk_usage: 0,         // Would be calculated from disk I/O
                 network_bytes_sent: 0, // Would be calculated from network interfaces
                 network_bytes_received: 0,
                 load_time_ms: 0,
@@ -297,7 +337,7 @@ impl EventCollector {
                 error_count: metrics.error_count,
                 crash_count: metrics.crash_count,
             };
-            
+
             Ok(Some(performance_data))
         } else {
             Ok(None)
@@ -317,10 +357,12 @@ impl EventCollector {
                     performance_tracker.clone(),
                     event_sender.clone(),
                     session_id.clone(),
-                ).await {
+                )
+                .await
+                {
                     log::error!("System metrics collection failed: {}", e);
                 }
-                
+
                 tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
             }
         });
@@ -336,19 +378,19 @@ impl EventCollector {
     ) -> Result<(), WarpError> {
         let mut system = system_monitor.lock().await;
         system.refresh_all();
-        
+
         let current_cpu = system.global_cpu_info().cpu_usage();
         let current_memory = system.used_memory();
-        
+
         // Update performance metrics for all monitored items
         {
             let mut tracker = performance_tracker.lock().await;
             let now = Utc::now();
-            
+
             for (item_id, metrics) in tracker.item_metrics.iter_mut() {
                 metrics.cpu_samples.push_back(current_cpu);
                 metrics.memory_samples.push_back(current_memory);
-                
+
                 // Limit sample size
                 if metrics.cpu_samples.len() > 1000 {
                     metrics.cpu_samples.pop_front();
@@ -356,7 +398,7 @@ impl EventCollector {
                 if metrics.memory_samples.len() > 1000 {
                     metrics.memory_samples.pop_front();
                 }
-                
+
                 // Create performance event
                 let performance_data = PerformanceData {
                     cpu_usage: current_cpu,
@@ -369,7 +411,7 @@ impl EventCollector {
                     error_count: metrics.error_count,
                     crash_count: metrics.crash_count,
                 };
-                
+
                 let event = AnalyticsEvent {
                     id: uuid::Uuid::new_v4().to_string(),
                     event_type: EventType::ItemMemoryUsage,
@@ -380,11 +422,11 @@ impl EventCollector {
                     metadata: HashMap::new(),
                     performance_data: Some(performance_data),
                 };
-                
+
                 let _ = event_sender.send(event);
             }
         }
-        
+
         Ok(())
     }
 
@@ -1,15 +1,16 @@
 use super::*;
 use crate::error::WarpError;
+use crate::ml_insights::training::PipelineSummary;
+use crate::ml_insights::{FactorDirection, UserBehaviorPrediction};
 use ratatui::{
-    backend::Backend,
-    layout::{Constraint, Direction, Layout, Rect, Alignment},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans},
+    symbols,
+    text::{Line, Span},
     widgets::{
-        Block, Borders, Chart, Dataset, Axis, GraphType, Gauge, List, ListItem, 
-        Paragraph, Tabs, Table, Row, Cell, BarChart, Sparkline
+        Axis, BarChart, Block, Borders, Cell, Chart, Dataset, Gauge, GraphType, List, ListItem,
+        Paragraph, Row, Sparkline, Table, Tabs,
     },
-    symbols,
     Frame,
 };
 use std::collections::VecDeque;
@@ -21,6 +22,8 @@ pub struct AnalyticsDashboard {
     real_time_data: HashMap<String, VecDeque<f64>>,
     refresh_interval: std::time::Duration,
     last_refresh: DateTime<Utc>,
+    insights: Vec<PipelineSummary>,
+    prediction: Option<UserBehaviorPrediction>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +35,7 @@ pub enum DashboardTab {
     Marketplace,
     RealTime,
     Alerts,
+    Insights,
 }
 
 impl AnalyticsDashboard {
@@ -43,20 +47,22 @@ impl AnalyticsDashboard {
             real_time_data: HashMap::new(),
             refresh_interval: std::time::Duration::from_secs(30),
             last_refresh: Utc::now(),
+            insights: Vec::new(),
+            prediction: None,
         })
     }
 
-    pub async fn render<B: Backend>(
+    pub async fn render(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(3),  // Header/Tabs
-                Constraint::Min(0),     // Main content
-                Constraint::Length(3),  // Status bar
+                Constraint::Length(3), // Header/Tabs
+                Constraint::Min(0),    // Main content
+                Constraint::Length(3), // Status bar
             ])
             .split(f.size());
 
@@ -68,10 +74,13 @@ impl AnalyticsDashboard {
             DashboardTab::Overview => self.render_overview(f, chunks[1], analytics).await?,
             DashboardTab::Performance => self.render_performance(f, chunks[1], analytics).await?,
             DashboardTab::Usage => self.render_usage(f, chunks[1], analytics).await?,
-            DashboardTab::UserBehavior => self.render_user_behavior(f, chunks[1], analytics).await?,
+            DashboardTab::UserBehavior => {
+                self.render_user_behavior(f, chunks[1], analytics).await?
+            }
             DashboardTab::Marketplace => self.render_marketplace(f, chunks[1], analytics).await?,
             DashboardTab::RealTime => self.render_real_time(f, chunks[1], analytics).await?,
             DashboardTab::Alerts => self.render_alerts(f, chunks[1], analytics).await?,
+            DashboardTab::Insights => self.render_insights(f, chunks[1]),
         }
 
         // Render status bar
@@ -80,7 +89,7 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    fn render_tabs<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+    fn render_tabs(&self, f: &mut Frame<'_>, area: Rect) {
         let titles = vec![
             "Overview",
             "Performance",
@@ -89,8 +98,9 @@ impl AnalyticsDashboard {
             "Marketplace",
             "Real-time",
             "Alerts",
+            "ML Insights",
         ];
-        
+
         let selected_tab = match self.current_tab {
             DashboardTab::Overview => 0,
             DashboardTab::Performance => 1,
@@ -99,28 +109,37 @@ impl AnalyticsDashboard {
             DashboardTab::Marketplace => 4,
             DashboardTab::RealTime => 5,
             DashboardTab::Alerts => 6,
+            DashboardTab::Insights => 7,
         };
 
         let tabs = Tabs::new(titles)
-            .block(Block::default().borders(Borders::ALL).title("Analytics Dashboard"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Analytics Dashboard"),
+            )
             .select(selected_tab)
             .style(Style::default().fg(Color::White))
-            .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            );
 
         f.render_widget(tabs, area);
     }
 
-    async fn render_overview<B: Backend>(
+    async fn render_overview(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(8),  // Key metrics
-                Constraint::Min(0),     // Charts and details
+                Constraint::Length(8), // Key metrics
+                Constraint::Min(0),    // Charts and details
             ])
             .split(area);
 
@@ -134,17 +153,19 @@ impl AnalyticsDashboard {
             .split(chunks[1]);
 
         // Usage trend chart
-        self.render_usage_trend_chart(f, chart_chunks[0], analytics).await?;
+        self.render_usage_trend_chart(f, chart_chunks[0], analytics)
+            .await?;
 
         // Performance overview chart
-        self.render_performance_overview_chart(f, chart_chunks[1], analytics).await?;
+        self.render_performance_overview_chart(f, chart_chunks[1], analytics)
+            .await?;
 
         Ok(())
     }
 
-    async fn render_key_metrics<B: Backend>(
+    async fn render_key_metrics(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -159,22 +180,26 @@ impl AnalyticsDashboard {
             .split(area);
 
         // Get marketplace analytics
-        let marketplace_analytics = analytics.get_marketplace_analytics(self.time_range.clone()).await?;
+        let marketplace_analytics = analytics
+            .get_marketplace_analytics(self.time_range.clone())
+            .await?;
 
         // Total Downloads
         let downloads_text = vec![
-            Spans::from(vec![
-                Span::styled("Total Downloads", Style::default().fg(Color::Gray)),
-            ]),
-            Spans::from(vec![
-                Span::styled(
-                    format!("{}", marketplace_analytics.total_downloads),
-                    Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
-                ),
-            ]),
-            Spans::from(vec![
-                Span::styled("↗ +12.5%", Style::default().fg(Color::Green)),
-            ]),
+            Line::from(vec![Span::styled(
+                "Total Downloads",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
+                format!("{}", marketplace_analytics.total_downloads),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "↗ +12.5%",
+                Style::default().fg(Color::Green),
+            )]),
         ];
 
         let downloads_widget = Paragraph::new(downloads_text)
@@ -184,18 +209,20 @@ impl AnalyticsDashboard {
 
         // Active Users
         let users_text = vec![
-            Spans::from(vec![
-                Span::styled("Active Users", Style::default().fg(Color::Gray)),
-            ]),
-            Spans::from(vec![
-                Span::styled(
-                    format!("{}", marketplace_analytics.total_active_users),
-                    Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
-                ),
-            ]),
-            Spans::from(vec![
-                Span::styled("↗ +8.3%", Style::default().fg(Color::Green)),
-            ]),
+            Line::from(vec![Span::styled(
+                "Active Users",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
+                format!("{}", marketplace_analytics.total_active_users),
+                Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "↗ +8.3%",
+                Style::default().fg(Color::Green),
+            )]),
         ];
 
         let users_widget = Paragraph::new(users_text)
@@ -205,18 +232,23 @@ impl AnalyticsDashboard {
 
         // Revenue
         let revenue_text = vec![
-            Spans::from(vec![
-                Span::styled("Revenue", Style::default().fg(Color::Gray)),
-            ]),
-            Spans::from(vec![
-                Span::styled(
-                    format!("${:.2}", marketplace_analytics.revenue_metrics.total_revenue),
-                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            Line::from(vec![Span::styled(
+                "Revenue",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
+                format!(
+                    "${:.2}",
+                    marketplace_analytics.revenue_metrics.total_revenue
                 ),
-            ]),
-            Spans::from(vec![
-                Span::styled("↗ +15.7%", Style::default().fg(Color::Green)),
-            ]),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "↗ +15.7%",
+                Style::default().fg(Color::Green),
+            )]),
         ];
 
         let revenue_widget = Paragraph::new(revenue_text)
@@ -226,18 +258,23 @@ impl AnalyticsDashboard {
 
         // Conversion Rate
         let conversion_text = vec![
-            Spans::from(vec![
-                Span::styled("Conversion Rate", Style::default().fg(Color::Gray)),
-            ]),
-            Spans::from(vec![
-                Span::styled(
-                    format!("{:.1}%", marketplace_analytics.revenue_metrics.conversion_rate * 100.0),
-                    Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            Line::from(vec![Span::styled(
+                "Conversion Rate",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
+                format!(
+                    "{:.1}%",
+                    marketplace_analytics.revenue_metrics.conversion_rate * 100.0
                 ),
-            ]),
-            Spans::from(vec![
-                Span::styled("↘ -2.1%", Style::default().fg(Color::Red)),
-            ]),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "↘ -2.1%",
+                Style::default().fg(Color::Red),
+            )]),
         ];
 
         let conversion_widget = Paragraph::new(conversion_text)
@@ -248,9 +285,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_usage_trend_chart<B: Backend>(
+    async fn render_usage_trend_chart(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -267,7 +304,11 @@ impl AnalyticsDashboard {
             .data(&data)];
 
         let chart = Chart::new(datasets)
-            .block(Block::default().title("Usage Trend (24h)").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Usage Trend (24h)")
+                    .borders(Borders::ALL),
+            )
             .x_axis(
                 Axis::default()
                     .title("Hours")
@@ -295,9 +336,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_performance_overview_chart<B: Backend>(
+    async fn render_performance_overview_chart(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -327,9 +368,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_performance<B: Backend>(
+    async fn render_performance(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -339,17 +380,19 @@ impl AnalyticsDashboard {
             .split(area);
 
         // Performance metrics table
-        self.render_performance_table(f, chunks[0], analytics).await?;
+        self.render_performance_table(f, chunks[0], analytics)
+            .await?;
 
         // Performance trends
-        self.render_performance_trends(f, chunks[1], analytics).await?;
+        self.render_performance_trends(f, chunks[1], analytics)
+            .await?;
 
         Ok(())
     }
 
-    async fn render_performance_table<B: Backend>(
+    async fn render_performance_table(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -370,20 +413,28 @@ impl AnalyticsDashboard {
             })
             .collect();
 
-        let table = Table::new(table_rows)
+        let widths = [
+            Constraint::Percentage(30),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+        ];
+        let table = Table::new(table_rows, widths)
             .header(
                 Row::new(header.iter().map(|&h| Cell::from(h)).collect::<Vec<_>>())
-                    .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+                    .style(
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    )
                     .bottom_margin(1),
             )
-            .block(Block::default().title("Performance Metrics").borders(Borders::ALL))
-            .widths(&[
-                Constraint::Percentage(30),
-                Constraint::Percentage(15),
-                Constraint::Percentage(15),
-                Constraint::Percentage(15),
-                Constraint::Percentage(15),
-            ])
+            .block(
+                Block::default()
+                    .title("Performance Metrics")
+                    .borders(Borders::ALL),
+            )
             .column_spacing(1)
             .highlight_style(Style::default().bg(Color::DarkGray))
             .highlight_symbol("▶ ");
@@ -392,9 +443,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_performance_trends<B: Backend>(
+    async fn render_performance_trends(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -410,7 +461,11 @@ impl AnalyticsDashboard {
         // Load time sparkline
         let load_time_data = vec![120, 115, 130, 125, 118, 122, 119, 124, 121, 117];
         let load_time_sparkline = Sparkline::default()
-            .block(Block::default().title("Load Time Trend").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Load Time Trend")
+                    .borders(Borders::ALL),
+            )
             .data(&load_time_data)
             .style(Style::default().fg(Color::Green));
         f.render_widget(load_time_sparkline, chunks[0]);
@@ -426,7 +481,11 @@ impl AnalyticsDashboard {
         // Performance score sparkline
         let perf_score_data = vec![92, 91, 89, 90, 93, 94, 93, 91, 92, 94];
         let perf_score_sparkline = Sparkline::default()
-            .block(Block::default().title("Performance Score").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Performance Score")
+                    .borders(Borders::ALL),
+            )
             .data(&perf_score_data)
             .style(Style::default().fg(Color::Blue));
         f.render_widget(perf_score_sparkline, chunks[2]);
@@ -434,9 +493,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_usage<B: Backend>(
+    async fn render_usage(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -454,32 +513,50 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_top_items<B: Backend>(
+    async fn render_top_items(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
-        let marketplace_analytics = analytics.get_marketplace_analytics(self.time_range.clone()).await?;
-        
-        let items: Vec<ListItem> = marketplace_analytics.top_items
+        let marketplace_analytics = analytics
+            .get_marketplace_analytics(self.time_range.clone())
+            .await?;
+
+        let items: Vec<ListItem> = marketplace_analytics
+            .top_items
             .iter()
             .enumerate()
             .map(|(i, item)| {
                 let spans = vec![
                     Span::styled(format!("{}. ", i + 1), Style::default().fg(Color::Gray)),
-                    Span::styled(&item.name, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+                    Span::styled(
+                        &item.name,
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD),
+                    ),
                     Span::raw(" - "),
-                    Span::styled(format!("{} downloads", item.downloads), Style::default().fg(Color::Green)),
+                    Span::styled(
+                        format!("{} downloads", item.downloads),
+                        Style::default().fg(Color::Green),
+                    ),
                     Span::raw(" "),
-                    Span::styled(format!("⭐ {:.1}", item.rating), Style::default().fg(Color::Yellow)),
+                    Span::styled(
+                        format!("⭐ {:.1}", item.rating),
+                        Style::default().fg(Color::Yellow),
+                    ),
                 ];
-                ListItem::new(Spans::from(spans))
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
         let list = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("Top Items by Usage"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Top Items by Usage"),
+            )
             .highlight_style(Style::default().bg(Color::DarkGray))
             .highlight_symbol("▶ ");
 
@@ -487,9 +564,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_usage_patterns<B: Backend>(
+    async fn render_usage_patterns(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -503,19 +580,27 @@ impl AnalyticsDashboard {
         ];
 
         let bar_chart = BarChart::default()
-            .block(Block::default().title("Usage by Category").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Usage by Category")
+                    .borders(Borders::ALL),
+            )
             .data(&data)
             .bar_width(8)
             .bar_style(Style::default().fg(Color::Cyan))
-            .value_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
+            .value_style(
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            );
 
         f.render_widget(bar_chart, area);
         Ok(())
     }
 
-    async fn render_user_behavior<B: Backend>(
+    async fn render_user_behavior(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -533,9 +618,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_user_journey_funnel<B: Backend>(
+    async fn render_user_journey_funnel(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
     ) -> Result<(), WarpError> {
         let funnel_data = vec![
@@ -552,27 +637,33 @@ impl AnalyticsDashboard {
                 let bar_width = (*percentage / 100.0 * 30.0) as usize;
                 let bar = "█".repeat(bar_width);
                 let spaces = " ".repeat(30 - bar_width);
-                
+
                 let spans = vec![
                     Span::styled(format!("{:<12}", stage), Style::default().fg(Color::White)),
                     Span::styled(bar, Style::default().fg(Color::Green)),
                     Span::raw(spaces),
-                    Span::styled(format!(" {} ({:.1}%)", count, percentage), Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        format!(" {} ({:.1}%)", count, percentage),
+                        Style::default().fg(Color::Gray),
+                    ),
                 ];
-                ListItem::new(Spans::from(spans))
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
-        let list = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("User Journey Funnel"));
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("User Journey Funnel"),
+        );
 
         f.render_widget(list, area);
         Ok(())
     }
 
-    async fn render_feature_adoption<B: Backend>(
+    async fn render_feature_adoption(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
     ) -> Result<(), WarpError> {
         let adoption_data = vec![
@@ -584,19 +675,27 @@ impl AnalyticsDashboard {
         ];
 
         let bar_chart = BarChart::default()
-            .block(Block::default().title("Feature Adoption %").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Feature Adoption %")
+                    .borders(Borders::ALL),
+            )
             .data(&adoption_data)
             .bar_width(6)
             .bar_style(Style::default().fg(Color::Magenta))
-            .value_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
+            .value_style(
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            );
 
         f.render_widget(bar_chart, area);
         Ok(())
     }
 
-    async fn render_marketplace<B: Backend>(
+    async fn render_marketplace(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
@@ -617,19 +716,23 @@ impl AnalyticsDashboard {
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
             .split(chunks[1]);
 
-        self.render_category_distribution(f, bottom_chunks[0], analytics).await?;
-        self.render_trending_items(f, bottom_chunks[1], analytics).await?;
+        self.render_category_distribution(f, bottom_chunks[0], analytics)
+            .await?;
+        self.render_trending_items(f, bottom_chunks[1], analytics)
+            .await?;
 
         Ok(())
     }
 
-    async fn render_revenue_metrics<B: Backend>(
+    async fn render_revenue_metrics(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
-        let marketplace_analytics = analytics.get_marketplace_analytics(self.time_range.clone()).await?;
+        let marketplace_analytics = analytics
+            .get_marketplace_analytics(self.time_range.clone())
+            .await?;
         let revenue = &marketplace_analytics.revenue_metrics;
 
         let chunks = Layout::default()
@@ -644,10 +747,15 @@ impl AnalyticsDashboard {
 
         // Total Revenue
         let total_revenue_text = vec![
-            Spans::from(vec![Span::styled("Total Revenue", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled(
+                "Total Revenue",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
                 format!("${:.2}", revenue.total_revenue),
-                Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
             )]),
         ];
         let total_revenue_widget = Paragraph::new(total_revenue_text)
@@ -657,10 +765,12 @@ impl AnalyticsDashboard {
 
         // MRR
         let mrr_text = vec![
-            Spans::from(vec![Span::styled("MRR", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled("MRR", Style::default().fg(Color::Gray))]),
+            Line::from(vec![Span::styled(
                 format!("${:.2}", revenue.monthly_recurring_revenue),
-                Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
             )]),
         ];
         let mrr_widget = Paragraph::new(mrr_text)
@@ -670,10 +780,12 @@ impl AnalyticsDashboard {
 
         // ARPU
         let arpu_text = vec![
-            Spans::from(vec![Span::styled("ARPU", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled("ARPU", Style::default().fg(Color::Gray))]),
+            Line::from(vec![Span::styled(
                 format!("${:.2}", revenue.average_revenue_per_user),
-                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
             )]),
         ];
         let arpu_widget = Paragraph::new(arpu_text)
@@ -683,8 +795,11 @@ impl AnalyticsDashboard {
 
         // Churn Rate
         let churn_text = vec![
-            Spans::from(vec![Span::styled("Churn Rate", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled(
+                "Churn Rate",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
                 format!("{:.1}%", revenue.churn_rate * 100.0),
                 Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
             )]),
@@ -697,56 +812,85 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_category_distribution<B: Backend>(
+    async fn render_category_distribution(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
-        let marketplace_analytics = analytics.get_marketplace_analytics(self.time_range.clone()).await?;
-        
-        let data: Vec<(&str, u64)> = marketplace_analytics.category_distribution
+        let marketplace_analytics = analytics
+            .get_marketplace_analytics(self.time_range.clone())
+            .await?;
+
+        let data: Vec<(&str, u64)> = marketplace_analytics
+            .category_distribution
             .iter()
             .map(|(category, count)| (category.as_str(), *count as u64))
             .collect();
 
         let bar_chart = BarChart::default()
-            .block(Block::default().title("Items by Category").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Items by Category")
+                    .borders(Borders::ALL),
+            )
             .data(&data)
             .bar_width(8)
             .bar_style(Style::default().fg(Color::Cyan))
-            .value_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
+            .value_style(
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            );
 
         f.render_widget(bar_chart, area);
         Ok(())
     }
 
-    async fn render_trending_items<B: Backend>(
+    async fn render_trending_items(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
-        let marketplace_analytics = analytics.get_marketplace_analytics(self.time_range.clone()).await?;
-        
-        let items: Vec<ListItem> = marketplace_analytics.trending_items
+        let marketplace_analytics = analytics
+            .get_marketplace_analytics(self.time_range.clone())
+            .await?;
+
+        let items: Vec<ListItem> = marketplace_analytics
+            .trending_items
             .iter()
             .enumerate()
             .map(|(i, item)| {
                 let spans = vec![
                     Span::styled(format!("{}. ", i + 1), Style::default().fg(Color::Gray)),
-                    Span::styled(&item.name, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+                    Span::styled(
+                        &item.name,
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD),
+                    ),
                     Span::raw(" "),
-                    Span::styled(format!("🔥 {:.1}", item.momentum_score), Style::default().fg(Color::Red)),
+                    Span::styled(
+                        format!("🔥 {:.1}", item.momentum_score),
+                        Style::default().fg(Color::Red),
+                    ),
                     Span::raw(" "),
-                    Span::styled(format!("↗ {:.1}%", item.growth_rate * 100.0), Style::default().fg(Color::Green)),
+                    Span::styled(
+                        format!("↗ {:.1}%", item.growth_rate * 100.0),
+                        Style::default().fg(Color::Green),
+                    ),
                 ];
-                ListItem::new(Spans::from(spans))
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
         let list = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("Trending Items"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Trending Items"),
+            )
             .highlight_style(Style::default().bg(Color::DarkGray))
             .highlight_symbol("▶ ");
 
@@ -754,17 +898,17 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_real_time<B: Backend>(
+    async fn render_real_time(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(6),  // Real-time stats
-                Constraint::Min(0),     // Live charts
+                Constraint::Length(6), // Real-time stats
+                Constraint::Min(0),    // Live charts
             ])
             .split(area);
 
@@ -777,9 +921,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_real_time_stats<B: Backend>(
+    async fn render_real_time_stats(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
     ) -> Result<(), WarpError> {
         let chunks = Layout::default()
@@ -794,12 +938,20 @@ impl AnalyticsDashboard {
 
         // Active Users
         let active_users_text = vec![
-            Spans::from(vec![Span::styled("Active Users", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled(
+                "Active Users",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
                 "1,247",
-                Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "🟢 Live",
+                Style::default().fg(Color::Green),
             )]),
-            Spans::from(vec![Span::styled("🟢 Live", Style::default().fg(Color::Green))]),
         ];
         let active_users_widget = Paragraph::new(active_users_text)
             .block(Block::default().borders(Borders::ALL))
@@ -808,12 +960,20 @@ impl AnalyticsDashboard {
 
         // Downloads/min
         let downloads_text = vec![
-            Spans::from(vec![Span::styled("Downloads/min", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled(
+                "Downloads/min",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
                 "23",
-                Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "📈 +15%",
+                Style::default().fg(Color::Green),
             )]),
-            Spans::from(vec![Span::styled("📈 +15%", Style::default().fg(Color::Green))]),
         ];
         let downloads_widget = Paragraph::new(downloads_text)
             .block(Block::default().borders(Borders::ALL))
@@ -822,12 +982,20 @@ impl AnalyticsDashboard {
 
         // Error Rate
         let error_rate_text = vec![
-            Spans::from(vec![Span::styled("Error Rate", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled(
+                "Error Rate",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
                 "0.12%",
-                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "⚠️ Normal",
+                Style::default().fg(Color::Yellow),
             )]),
-            Spans::from(vec![Span::styled("⚠️ Normal", Style::default().fg(Color::Yellow))]),
         ];
         let error_rate_widget = Paragraph::new(error_rate_text)
             .block(Block::default().borders(Borders::ALL))
@@ -836,12 +1004,20 @@ impl AnalyticsDashboard {
 
         // System Load
         let system_load_text = vec![
-            Spans::from(vec![Span::styled("System Load", Style::default().fg(Color::Gray))]),
-            Spans::from(vec![Span::styled(
+            Line::from(vec![Span::styled(
+                "System Load",
+                Style::default().fg(Color::Gray),
+            )]),
+            Line::from(vec![Span::styled(
                 "68%",
-                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::styled(
+                "⚡ Optimal",
+                Style::default().fg(Color::Green),
             )]),
-            Spans::from(vec![Span::styled("⚡ Optimal", Style::default().fg(Color::Green))]),
         ];
         let system_load_widget = Paragraph::new(system_load_text)
             .block(Block::default().borders(Borders::ALL))
@@ -851,9 +1027,9 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_live_activity_chart<B: Backend>(
+    async fn render_live_activity_chart(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
     ) -> Result<(), WarpError> {
         // Mock real-time data
@@ -873,7 +1049,11 @@ impl AnalyticsDashboard {
             .data(&data)];
 
         let chart = Chart::new(datasets)
-            .block(Block::default().title("Live Activity (Last 60 minutes)").borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title("Live Activity (Last 60 minutes)")
+                    .borders(Borders::ALL),
+            )
             .x_axis(
                 Axis::default()
                     .title("Minutes Ago")
@@ -901,19 +1081,39 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    async fn render_alerts<B: Backend>(
+    async fn render_alerts(
         &mut self,
-        f: &mut Frame<B>,
+        f: &mut Frame<'_>,
         area: Rect,
         _analytics: &AnalyticsEngine,
     ) -> Result<(), WarpError> {
         // Mock alerts data
         let alerts = vec![
-            ("🔴 Critical", "High memory usage detected in git-enhanced plugin", "2 min ago"),
-            ("🟡 Warning", "Error rate increased by 15% for ai-assistant", "5 min ago"),
-            ("🔵 Info", "New trending item: catppuccin-theme", "12 min ago"),
-            ("🟡 Warning", "Slow response time for marketplace API", "18 min ago"),
-            ("🔴 Critical", "Plugin crash detected: docker-helper", "25 min ago"),
+            (
+                "🔴 Critical",
+                "High memory usage detected in git-enhanced plugin",
+                "2 min ago",
+            ),
+            (
+                "🟡 Warning",
+                "Error rate increased by 15% for ai-assistant",
+                "5 min ago",
+            ),
+            (
+                "🔵 Info",
+                "New trending item: catppuccin-theme",
+                "12 min ago",
+            ),
+            (
+                "🟡 Warning",
+                "Slow response time for marketplace API",
+                "18 min ago",
+            ),
+            (
+                "🔴 Critical",
+                "Plugin crash detected: docker-helper",
+                "25 min ago",
+            ),
         ];
 
         let items: Vec<ListItem> = alerts
@@ -921,15 +1121,19 @@ impl AnalyticsDashboard {
             .map(|(severity, message, time)| {
                 let spans = vec![
                     Span::raw(format!("{} ", severity)),
-                    Span::styled(message, Style::default().fg(Color::White)),
+                    Span::styled(*message, Style::default().fg(Color::White)),
                     Span::styled(format!(" ({})", time), Style::default().fg(Color::Gray)),
                 ];
-                ListItem::new(Spans::from(spans))
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
         let list = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("System Alerts"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("System Alerts"),
+            )
             .highlight_style(Style::default().bg(Color::DarkGray))
             .highlight_symbol("▶ ");
 
@@ -937,7 +1141,181 @@ impl AnalyticsDashboard {
         Ok(())
     }
 
-    fn render_status_bar<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+    // Churn and feature-adoption pipelines: when they last trained, how well
+    // calibrated they are on held-out users, and who currently scores highest
+    fn render_insights(&self, f: &mut Frame<'_>, area: Rect) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(columns[0]);
+
+        let header = ["Model", "Trained", "Next", "Examples", "Brier", "ECE"];
+        let rows: Vec<Row> = self
+            .insights
+            .iter()
+            .map(|summary| {
+                let time = |at: Option<DateTime<Utc>>| {
+                    at.map(|at| at.format("%m-%d %H:%M").to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                };
+                let (brier, ece) = match &summary.calibration {
+                    Some(report) => (
+                        format!("{:.3}", report.brier_score),
+                        format!("{:.1}%", report.expected_calibration_error * 100.0),
+                    ),
+                    None => ("-".to_string(), "-".to_string()),
+                };
+                Row::new(vec![
+                    Cell::from(summary.model_name.clone()),
+                    Cell::from(time(summary.last_trained)),
+                    Cell::from(time(summary.next_training)),
+                    Cell::from(summary.training_examples.to_string()),
+                    Cell::from(brier),
+                    Cell::from(ece),
+                ])
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Percentage(22),
+            Constraint::Percentage(18),
+            Constraint::Percentage(18),
+            Constraint::Percentage(14),
+            Constraint::Percentage(14),
+            Constraint::Percentage(14),
+        ];
+        let table = Table::new(rows, widths)
+            .header(
+                Row::new(header.iter().map(|&h| Cell::from(h)).collect::<Vec<_>>())
+                    .style(
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                    .bottom_margin(1),
+            )
+            .block(
+                Block::default()
+                    .title("Prediction Models")
+                    .borders(Borders::ALL),
+            )
+            .column_spacing(1);
+        f.render_widget(table, chunks[0]);
+
+        let mut items = Vec::new();
+        for summary in &self.insights {
+            items.push(ListItem::new(Line::from(vec![Span::styled(
+                summary.model_name.clone(),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            )])));
+            for (user_id, score) in &summary.top_users {
+                let color = if *score >= 0.7 {
+                    Color::Red
+                } else if *score >= 0.4 {
+                    Color::Yellow
+                } else {
+                    Color::Green
+                };
+                items.push(ListItem::new(Line::from(vec![
+                    Span::raw(format!("  {} ", user_id)),
+                    Span::styled(format!("{:.0}%", score * 100.0), Style::default().fg(color)),
+                ])));
+            }
+        }
+        if items.is_empty() {
+            items.push(ListItem::new("No models trained yet"));
+        }
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Highest Scores"),
+        );
+        f.render_widget(list, columns[1]);
+
+        self.render_attributions(f, chunks[1]);
+    }
+
+    // One bar per feature for the selected user's prediction, red where it
+    // raised the score and green where it lowered it
+    fn render_attributions(&self, f: &mut Frame<'_>, area: Rect) {
+        let Some(prediction) = &self.prediction else {
+            let hint = Paragraph::new("Select a user to see what drove their scores")
+                .block(Block::default().borders(Borders::ALL).title("Attributions"))
+                .style(Style::default().fg(Color::Gray));
+            f.render_widget(hint, area);
+            return;
+        };
+
+        let mut predictions: Vec<_> = prediction.predictions.iter().collect();
+        predictions.sort_by(|a, b| a.0.cmp(b.0));
+        let label_width = predictions
+            .iter()
+            .flat_map(|(_, result)| {
+                result
+                    .factors
+                    .iter()
+                    .map(|factor| factor.feature_name.len())
+            })
+            .max()
+            .unwrap_or(0);
+        let bar_width = (area.width as usize)
+            .saturating_sub(label_width + 14)
+            .max(1);
+
+        let mut items = Vec::new();
+        for (name, result) in predictions {
+            items.push(ListItem::new(Line::from(vec![
+                Span::styled(
+                    name.clone(),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!(" {:.0}%", result.value * 100.0)),
+            ])));
+            let largest = result
+                .factors
+                .iter()
+                .map(|factor| factor.importance)
+                .fold(0.0, f64::max);
+            for factor in &result.factors {
+                let length = if largest > 0.0 {
+                    (factor.importance / largest * bar_width as f64).round() as usize
+                } else {
+                    0
+                };
+                let (sign, color) = match factor.direction {
+                    FactorDirection::Positive => ('+', Color::Red),
+                    FactorDirection::Negative => ('-', Color::Green),
+                    FactorDirection::Neutral => (' ', Color::Gray),
+                };
+                items.push(ListItem::new(Line::from(vec![
+                    Span::raw(format!(
+                        "  {:<width$} ",
+                        factor.feature_name,
+                        width = label_width
+                    )),
+                    Span::styled("█".repeat(length), Style::default().fg(color)),
+                    Span::styled(
+                        format!(" {}{:.1}", sign, factor.importance * 100.0),
+                        Style::default().fg(color),
+                    ),
+                ])));
+            }
+        }
+
+        let title = format!("Attributions for {}", prediction.user_id);
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(list, area);
+    }
+
+    fn render_status_bar(&self, f: &mut Frame<'_>, area: Rect) {
         let status_text = format!(
             "Analytics Dashboard • Tab: {:?} • Time Range: {:?} • Last Refresh: {} • Press 'r' to refresh",
             self.current_tab,
@@ -957,7 +1335,7 @@ impl AnalyticsDashboard {
             crossterm::event::KeyCode::Tab => {
                 self.switch_tab();
             }
-            crossterm::event::KeyCode::Char('r') | crossterm::event::KeyCode::F5 => {
+            crossterm::event::KeyCode::Char('r') | crossterm::event::KeyCode::F(5) => {
                 self.refresh_data().await?;
             }
             crossterm::event::KeyCode::Char('1') => {
@@ -986,7 +1364,8 @@ impl AnalyticsDashboard {
             DashboardTab::UserBehavior => DashboardTab::Marketplace,
             DashboardTab::Marketplace => DashboardTab::RealTime,
             DashboardTab::RealTime => DashboardTab::Alerts,
-            DashboardTab::Alerts => DashboardTab::Overview,
+            DashboardTab::Alerts => DashboardTab::Insights,
+            DashboardTab::Insights => DashboardTab::Overview,
         };
     }
 
@@ -1000,6 +1379,14 @@ impl AnalyticsDashboard {
         self.selected_item = item_id;
     }
 
+    pub fn set_insights(&mut self, insights: Vec<PipelineSummary>) {
+        self.insights = insights;
+    }
+
+    pub fn set_prediction(&mut self, prediction: Option<UserBehaviorPrediction>) {
+        self.prediction = prediction;
+    }
+
     pub fn set_time_range(&mut self, time_range: TimeRange) {
         self.time_range = time_range;
     }
@@ -0,0 +1,446 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+use crate::analytics::privacy::{PrivacyManager, QueuedEvent};
+use crate::config::{DropPolicy, TelemetryDeliveryConfig, TelemetryMode};
+use crate::error::WarpError;
+use crate::network;
+use crate::shutdown::{write_atomic, ShutdownSignal};
+
+const SEGMENT_SUFFIX: &str = ".json.gz";
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+// An upload that takes longer is treated as the endpoint being down
+const POST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Batches waiting to be sent, one gzipped JSON array per file, named so
+// they sort oldest first. Survives restarts and outages; bounded by size
+// and age.
+pub struct OfflineQueue {
+    dir: PathBuf,
+    max_bytes: u64,
+    drop_policy: DropPolicy,
+    max_age: Duration,
+    counter: AtomicU64,
+}
+
+impl OfflineQueue {
+    pub fn default_dir() -> Result<PathBuf, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        Ok(data_dir.join("warp").join("telemetry"))
+    }
+
+    pub async fn open(dir: &Path, config: &TelemetryDeliveryConfig) -> Result<Self, WarpError> {
+        fs::create_dir_all(dir).await?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_bytes: config.max_queue_mb * 1024 * 1024,
+            drop_policy: config.drop_policy,
+            max_age: Duration::from_secs(config.max_age_days as u64 * 24 * 60 * 60),
+            counter: AtomicU64::new(0),
+        })
+    }
+
+    // Waiting batches with their sizes, oldest first
+    async fn segments(&self) -> Result<Vec<(PathBuf, u64)>, WarpError> {
+        let mut segments = Vec::new();
+        let mut entries = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.to_string_lossy().ends_with(SEGMENT_SUFFIX) {
+                segments.push((path, entry.metadata().await?.len()));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    pub async fn pending(&self) -> Result<Vec<PathBuf>, WarpError> {
+        Ok(self
+            .segments()
+            .await?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect())
+    }
+
+    // Writes the events as batches of `batch_size`. Returns how many events
+    // the quota made it drop, queued or new.
+    pub async fn push(
+        &self,
+        events: &[QueuedEvent],
+        batch_size: usize,
+    ) -> Result<usize, WarpError> {
+        let mut dropped = 0;
+        for batch in events.chunks(batch_size.max(1)) {
+            let bytes = compress(batch)?;
+            match self.make_room(bytes.len() as u64).await? {
+                Some(removed) => dropped += removed,
+                None => {
+                    dropped += batch.len();
+                    continue;
+                }
+            }
+            let name = format!(
+                "{:020}-{:06}{}",
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis(),
+                self.counter.fetch_add(1, Ordering::Relaxed),
+                SEGMENT_SUFFIX
+            );
+            write_atomic(&self.dir.join(name), &bytes).await?;
+        }
+        if dropped > 0 {
+            log::warn!("Telemetry queue is full; dropped {} events", dropped);
+        }
+        Ok(dropped)
+    }
+
+    // Some(events removed to fit `incoming` bytes), or None when the new
+    // batch has to go instead
+    async fn make_room(&self, incoming: u64) -> Result<Option<usize>, WarpError> {
+        let mut segments = self.segments().await?;
+        let mut total: u64 = segments.iter().map(|(_, size)| size).sum();
+        if total + incoming <= self.max_bytes {
+            return Ok(Some(0));
+        }
+        if self.drop_policy == DropPolicy::DropNewest || incoming > self.max_bytes {
+            return Ok(None);
+        }
+        let mut removed = 0;
+        segments.reverse();
+        while total + incoming > self.max_bytes {
+            let Some((path, size)) = segments.pop() else {
+                break;
+            };
+            removed += batch_len(&fs::read(&path).await?);
+            fs::remove_file(&path).await?;
+            total -= size;
+        }
+        Ok(Some(removed))
+    }
+
+    // Drops batches that have waited longer than the configured age
+    pub async fn expire(&self, now: SystemTime) -> Result<usize, WarpError> {
+        let mut expired = 0;
+        for (path, _) in self.segments().await? {
+            let modified = fs::metadata(&path).await?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() > self.max_age {
+                fs::remove_file(&path).await?;
+                expired += 1;
+            }
+        }
+        Ok(expired)
+    }
+
+    pub async fn clear(&self) -> Result<usize, WarpError> {
+        let segments = self.segments().await?;
+        for (path, _) in &segments {
+            fs::remove_file(path).await?;
+        }
+        Ok(segments.len())
+    }
+}
+
+fn compress(events: &[QueuedEvent]) -> Result<Vec<u8>, WarpError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, events)?;
+    encoder.flush()?;
+    Ok(encoder.finish()?)
+}
+
+// Events in a stored batch, for the drop count
+fn batch_len(bytes: &[u8]) -> usize {
+    let decoder = flate2::read::GzDecoder::new(bytes);
+    serde_json::from_reader::<_, Vec<serde_json::Value>>(decoder).map_or(0, |events| events.len())
+}
+
+// Worth another try later: the endpoint was unreachable, overloaded or
+// asked us to slow down. Anything else means the batch itself was refused.
+fn retryable(e: &WarpError) -> bool {
+    match e.root() {
+        WarpError::Network { .. } => true,
+        WarpError::Api { status, .. } => {
+            status.is_some_and(|status| status >= 500 || status == 429)
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeliveryReport {
+    pub sent: usize,     // batches
+    pub rejected: usize, // refused by an endpoint and dropped
+    pub offline: bool,   // no endpoint took the oldest batch; the rest wait
+}
+
+// Sends the waiting batches oldest first, each to the first endpoint that
+// takes it. Stops at the first batch none of them would take so order is
+// kept across outages.
+pub async fn deliver<F, Fut>(
+    queue: &OfflineQueue,
+    endpoints: &[String],
+    send: F,
+) -> Result<DeliveryReport, WarpError>
+where
+    F: Fn(String, Vec<u8>) -> Fut,
+    Fut: Future<Output = Result<(), WarpError>>,
+{
+    let mut report = DeliveryReport::default();
+    'batches: for path in queue.pending().await? {
+        let body = fs::read(&path).await?;
+        for endpoint in endpoints {
+            match send(endpoint.clone(), body.clone()).await {
+                Ok(()) => {
+                    report.sent += 1;
+                }
+                Err(e) if retryable(&e) => {
+                    log::debug!("Telemetry endpoint {} unavailable: {}", endpoint, e);
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("Telemetry endpoint {} refused a batch: {}", endpoint, e);
+                    report.rejected += 1;
+                }
+            }
+            fs::remove_file(&path).await?;
+            continue 'batches;
+        }
+        report.offline = true;
+        break;
+    }
+    Ok(report)
+}
+
+// Doubles the wait after each failed round, up to an hour; a round that
+// gets through goes back to the flush interval
+pub struct Backoff {
+    base: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration) -> Self {
+        Self { base, failures: 0 }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.base
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(MAX_BACKOFF.max(self.base))
+    }
+
+    pub fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+    }
+}
+
+// Moves shared events from the privacy manager to disk and from disk to the
+// endpoints, in the background so the terminal never waits on it
+pub struct Reporter {
+    privacy: Arc<PrivacyManager>,
+    queue: OfflineQueue,
+    config: TelemetryDeliveryConfig,
+    client: reqwest::Client,
+}
+
+impl Reporter {
+    pub async fn new(
+        privacy: Arc<PrivacyManager>,
+        config: TelemetryDeliveryConfig,
+    ) -> Result<Self, WarpError> {
+        Ok(Self {
+            privacy,
+            queue: OfflineQueue::open(&OfflineQueue::default_dir()?, &config).await?,
+            config,
+            client: network::client()?,
+        })
+    }
+
+    // Only Share lets anything leave; otherwise batches written while it
+    // was on are dropped, like the privacy manager's own queue
+    pub async fn flush(&self) -> Result<DeliveryReport, WarpError> {
+        if self.privacy.mode().await != TelemetryMode::Share {
+            self.queue.clear().await?;
+            return Ok(DeliveryReport::default());
+        }
+        self.spill().await?;
+        self.queue.expire(SystemTime::now()).await?;
+        deliver(&self.queue, &self.config.endpoints, |endpoint, body| {
+            self.post(endpoint, body)
+        })
+        .await
+    }
+
+    // Writes what's waiting in memory to disk without sending it
+    pub async fn spill(&self) -> Result<(), WarpError> {
+        let outgoing = self.privacy.take_outgoing().await;
+        self.queue.push(&outgoing, self.config.batch_size).await?;
+        Ok(())
+    }
+
+    async fn post(&self, endpoint: String, body: Vec<u8>) -> Result<(), WarpError> {
+        let response = self
+            .client
+            .post(network::endpoint(&endpoint))
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_ENCODING, "gzip")
+            .timeout(POST_TIMEOUT)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| WarpError::network("Telemetry upload failed", e))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(WarpError::api(
+                Some(response.status().as_u16()),
+                "Telemetry upload failed",
+            ))
+        }
+    }
+
+    // Flushes every interval, backing off while the endpoints are down. At
+    // shutdown the app's flush hook spills what's still in memory, after
+    // everything that records events has stopped.
+    pub async fn run(self: Arc<Self>, mut signal: ShutdownSignal) {
+        let mut backoff = Backoff::new(Duration::from_secs(self.config.flush_interval_secs));
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(backoff.delay()) => {}
+                _ = signal.triggered() => return,
+            }
+            match self.flush().await {
+                Ok(report) if report.offline => backoff.failed(),
+                Ok(_) => backoff.succeeded(),
+                Err(e) => {
+                    log::warn!("Telemetry delivery failed: {}", e);
+                    backoff.failed();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn events(count: usize) -> Vec<QueuedEvent> {
+        (0..count)
+            .map(|i| QueuedEvent {
+                kind: "command".to_string(),
+                recorded_at: chrono::Utc::now(),
+                payload: serde_json::json!({ "n": i, "padding": "x".repeat(64) }),
+            })
+            .collect()
+    }
+
+    async fn queue(dir: &Path, max_bytes: u64, drop_policy: DropPolicy) -> OfflineQueue {
+        let mut queue = OfflineQueue::open(dir, &TelemetryDeliveryConfig::default())
+            .await
+            .unwrap();
+        queue.max_bytes = max_bytes;
+        queue.drop_policy = drop_policy;
+        queue
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_drop_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = queue(dir.path(), 1024 * 1024, DropPolicy::DropOldest).await;
+        queue.push(&events(5), 2).await.unwrap();
+        let pending = queue.pending().await.unwrap();
+        assert_eq!(pending.len(), 3);
+        assert_eq!(batch_len(&std::fs::read(&pending[0]).unwrap()), 2);
+
+        // Room for about two batches: the oldest gives way
+        let size = std::fs::metadata(&pending[0]).unwrap().len();
+        let mut oldest = queue;
+        oldest.clear().await.unwrap();
+        oldest.max_bytes = size * 2 + size / 2;
+        oldest.push(&events(4), 2).await.unwrap();
+        let full = oldest.pending().await.unwrap();
+        assert_eq!(oldest.push(&events(2), 2).await.unwrap(), 2);
+        let remaining = oldest.pending().await.unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert!(!remaining.contains(&full[0]));
+
+        // or the new one is turned away
+        oldest.drop_policy = DropPolicy::DropNewest;
+        assert_eq!(oldest.push(&events(2), 2).await.unwrap(), 2);
+        assert_eq!(oldest.pending().await.unwrap(), remaining);
+    }
+
+    #[tokio::test]
+    async fn test_failover_keeps_batches_while_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = queue(dir.path(), 1024 * 1024, DropPolicy::DropOldest).await;
+        queue.push(&events(3), 1).await.unwrap();
+        let endpoints = vec!["https://primary".to_string(), "https://backup".to_string()];
+
+        // Primary down, backup takes everything
+        let calls = Mutex::new(Vec::new());
+        let report = deliver(&queue, &endpoints, |endpoint, _| {
+            calls.lock().unwrap().push(endpoint.clone());
+            async move {
+                match endpoint.as_str() {
+                    "https://primary" => Err(WarpError::api(Some(503), "down")),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(report.sent, 3);
+        assert_eq!(calls.lock().unwrap().len(), 6);
+
+        // Both down: nothing is lost
+        queue.push(&events(2), 1).await.unwrap();
+        let report = deliver(&queue, &endpoints, |_, _| async {
+            Err(WarpError::api(Some(429), "slow down"))
+        })
+        .await
+        .unwrap();
+        assert!(report.offline);
+        assert_eq!(queue.pending().await.unwrap().len(), 2);
+
+        // A refused batch is dropped rather than retried forever
+        let report = deliver(&queue, &endpoints, |_, _| async {
+            Err(WarpError::api(Some(400), "bad batch"))
+        })
+        .await
+        .unwrap();
+        assert_eq!(report.rejected, 2);
+        assert!(queue.pending().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(60));
+        backoff.failed();
+        backoff.failed();
+        assert_eq!(backoff.delay(), Duration::from_secs(240));
+        for _ in 0..20 {
+            backoff.failed();
+        }
+        assert_eq!(backoff.delay(), MAX_BACKOFF);
+        backoff.succeeded();
+        assert_eq!(backoff.delay(), Duration::from_secs(60));
+    }
+}
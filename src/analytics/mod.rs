@@ -1,16 +1,15 @@
+use crate::config::TelemetryMode;
+use crate::error::WarpError;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc, Duration};
-use crate::error::WarpError;
 
-pub mod collector;
 pub mod aggregator;
-pub mod reporter;
+pub mod collector;
 pub mod dashboard;
-pub mod metrics;
-pub mod storage;
+pub mod delivery;
 pub mod privacy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,26 +34,30 @@ pub enum EventType {
     ItemRating,
     ItemSearch,
     ItemDownload,
-    
+
     // Usage Events
     ItemActivation,
     ItemDeactivation,
     ItemUsage,
     ItemError,
     ItemCrash,
-    
+
     // Performance Events
     ItemLoadTime,
     ItemMemoryUsage,
     ItemCpuUsage,
     ItemNetworkUsage,
-    
+
     // User Interaction Events
     UserLogin,
     UserLogout,
     UserPreferenceChange,
     UserFeedback,
-    
+
+    // Experiment Events
+    ExperimentExposure,
+    ExperimentConversion,
+
     // System Events
     SystemStartup,
     SystemShutdown,
@@ -209,8 +212,6 @@ pub struct ConversionFunnel {
 pub struct AnalyticsEngine {
     collector: Arc<collector::EventCollector>,
     aggregator: Arc<Mutex<aggregator::MetricsAggregator>>,
-    reporter: Arc<reporter::AnalyticsReporter>,
-    storage: Arc<Mutex<storage::AnalyticsStorage>>,
     privacy_manager: Arc<privacy::PrivacyManager>,
     dashboard: Arc<Mutex<dashboard::AnalyticsDashboard>>,
 }
@@ -219,61 +220,84 @@ impl AnalyticsEngine {
     pub async fn new() -> Result<Self, WarpError> {
         let collector = Arc::new(collector::EventCollector::new().await?);
         let aggregator = Arc::new(Mutex::new(aggregator::MetricsAggregator::new().await?));
-        let reporter = Arc::new(reporter::AnalyticsReporter::new().await?);
-        let storage = Arc::new(Mutex::new(storage::AnalyticsStorage::new().await?));
         let privacy_manager = Arc::new(privacy::PrivacyManager::new().await?);
         let dashboard = Arc::new(Mutex::new(dashboard::AnalyticsDashboard::new().await?));
 
         Ok(Self {
             collector,
             aggregator,
-            reporter,
-            storage,
             privacy_manager,
             dashboard,
         })
     }
 
     pub async fn track_event(&self, event: AnalyticsEvent) -> Result<(), WarpError> {
-        // Privacy check
-        if !self.privacy_manager.should_track_event(&event).await? {
+        // Privacy check; also queues the event for the viewer and the upload
+        let kind = format!("{:?}", event.event_type);
+        if !self.privacy_manager.record(&kind, &event).await? {
             return Ok(());
         }
 
         // Collect the event
-        self.collector.collect_event(event.clone()).await?;
-
-        // Store the event
-        let mut storage = self.storage.lock().await;
-        storage.store_event(event).await?;
+        self.collector.collect_event(event).await?;
 
         Ok(())
     }
 
-    pub async fn get_usage_metrics(&self, item_id: &str, time_range: TimeRange) -> Result<UsageMetrics, WarpError> {
+    // From GeneralConfig.telemetry; until it is set nothing is tracked
+    pub async fn set_telemetry(&self, mode: TelemetryMode) {
+        self.privacy_manager.set_mode(mode).await;
+    }
+
+    pub fn privacy_manager(&self) -> Arc<privacy::PrivacyManager> {
+        self.privacy_manager.clone()
+    }
+
+    // Drops every event not yet sent. Returns how many were queued.
+    pub async fn purge(&self) -> Result<usize, WarpError> {
+        self.collector.get_pending_events().await?;
+        Ok(self.privacy_manager.purge().await)
+    }
+
+    pub async fn get_usage_metrics(
+        &self,
+        item_id: &str,
+        time_range: TimeRange,
+    ) -> Result<UsageMetrics, WarpError> {
         let aggregator = self.aggregator.lock().await;
         aggregator.get_usage_metrics(item_id, time_range).await
     }
 
-    pub async fn get_performance_metrics(&self, item_id: &str, time_range: TimeRange) -> Result<PerformanceMetrics, WarpError> {
+    pub async fn get_performance_metrics(
+        &self,
+        item_id: &str,
+        time_range: TimeRange,
+    ) -> Result<PerformanceMetrics, WarpError> {
         let aggregator = self.aggregator.lock().await;
-        aggregator.get_performance_metrics(item_id, time_range).await
+        aggregator
+            .get_performance_metrics(item_id, time_range)
+            .await
     }
 
-    pub async fn get_user_behavior_metrics(&self, item_id: &str, time_range: TimeRange) -> Result<UserBehaviorMetrics, WarpError> {
+    pub async fn get_user_behavior_metrics(
+        &self,
+        item_id: &str,
+        time_range: TimeRange,
+    ) -> Result<UserBehaviorMetrics, WarpError> {
         let aggregator = self.aggregator.lock().await;
-        aggregator.get_user_behavior_metrics(item_id, time_range).await
+        aggregator
+            .get_user_behavior_metrics(item_id, time_range)
+            .await
     }
 
-    pub async fn get_marketplace_analytics(&self, time_range: TimeRange) -> Result<MarketplaceAnalytics, WarpError> {
+    pub async fn get_marketplace_analytics(
+        &self,
+        time_range: TimeRange,
+    ) -> Result<MarketplaceAnalytics, WarpError> {
         let aggregator = self.aggregator.lock().await;
         aggregator.get_marketplace_analytics(time_range).await
     }
 
-    pub async fn generate_report(&self, report_type: ReportType, time_range: TimeRange) -> Result<AnalyticsReport, WarpError> {
-        self.reporter.generate_report(report_type, time_range).await
-    }
-
     pub async fn start_background_processing(&self) -> Result<(), WarpError> {
         // Start aggregation tasks
         let aggregator = self.aggregator.clone();
@@ -282,25 +306,17 @@ impl AnalyticsEngine {
                 if let Err(e) = Self::run_aggregation_cycle(aggregator.clone()).await {
                     log::error!("Aggregation cycle failed: {}", e);
                 }
-                tokio::time::sleep(tokio::time::Duration::from_secs(300)).await; // 5 minutes
-            }
-        });
-
-        // Start reporting tasks
-        let reporter = self.reporter.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Err(e) = reporter.generate_scheduled_reports().await {
-                    log::error!("Scheduled reporting failed: {}", e);
-                }
-                tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await; // 1 hour
+                tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
+                // 5 minutes
             }
         });
 
         Ok(())
     }
 
-    async fn run_aggregation_cycle(aggregator: Arc<Mutex<aggregator::MetricsAggregator>>) -> Result<(), WarpError> {
+    async fn run_aggregation_cycle(
+        aggregator: Arc<Mutex<aggregator::MetricsAggregator>>,
+    ) -> Result<(), WarpError> {
         let mut agg = aggregator.lock().await;
         agg.process_pending_events().await?;
         agg.update_real_time_metrics().await?;
@@ -316,7 +332,10 @@ pub enum TimeRange {
     LastWeek,
     LastMonth,
     LastYear,
-    Custom { start: DateTime<Utc>, end: DateTime<Utc> },
+    Custom {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Utc};
+use crossterm::style::Color;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use tokio::sync::Mutex;
+
+use crate::config::TelemetryMode;
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Oldest events are dropped past this; nothing is sent while they wait
+const MAX_QUEUED: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedEvent {
+    pub kind: String,
+    pub recorded_at: DateTime<Utc>,
+    pub payload: Value, // exactly what would be sent
+}
+
+// Every analytics event passes through here. Nothing is recorded until the
+// user has answered the opt-in prompt, and only Share lets events be taken
+// for upload; LocalOnly keeps them where the viewer can show them.
+pub struct PrivacyManager {
+    mode: Mutex<TelemetryMode>,
+    queue: Mutex<VecDeque<QueuedEvent>>,
+}
+
+impl PrivacyManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self::with_mode(TelemetryMode::Ask))
+    }
+
+    pub fn with_mode(mode: TelemetryMode) -> Self {
+        Self {
+            mode: Mutex::new(mode),
+            queue: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub async fn mode(&self) -> TelemetryMode {
+        *self.mode.lock().await
+    }
+
+    // Turning telemetry off also drops whatever was waiting
+    pub async fn set_mode(&self, mode: TelemetryMode) {
+        *self.mode.lock().await = mode;
+        if matches!(mode, TelemetryMode::Off | TelemetryMode::Ask) {
+            self.purge().await;
+        }
+    }
+
+    pub async fn should_track_event<E: Serialize>(&self, _event: &E) -> Result<bool, WarpError> {
+        Ok(matches!(
+            self.mode().await,
+            TelemetryMode::LocalOnly | TelemetryMode::Share
+        ))
+    }
+
+    // Queues the event as it would be serialized for upload. Returns whether
+    // it was recorded.
+    pub async fn record<E: Serialize>(&self, kind: &str, event: &E) -> Result<bool, WarpError> {
+        if !self.should_track_event(event).await? {
+            return Ok(false);
+        }
+        let event = QueuedEvent {
+            kind: kind.to_string(),
+            recorded_at: Utc::now(),
+            payload: serde_json::to_value(event)?,
+        };
+        let mut queue = self.queue.lock().await;
+        queue.push_back(event);
+        if queue.len() > MAX_QUEUED {
+            queue.pop_front();
+        }
+        Ok(true)
+    }
+
+    pub async fn queued(&self) -> Vec<QueuedEvent> {
+        self.queue.lock().await.iter().cloned().collect()
+    }
+
+    // The events a reporter may send; always empty unless the user chose Share
+    pub async fn take_outgoing(&self) -> Vec<QueuedEvent> {
+        if self.mode().await != TelemetryMode::Share {
+            return Vec::new();
+        }
+        self.queue.lock().await.drain(..).collect()
+    }
+
+    pub async fn purge(&self) -> usize {
+        let mut queue = self.queue.lock().await;
+        let count = queue.len();
+        queue.clear();
+        count
+    }
+}
+
+fn mode_label(mode: TelemetryMode) -> String {
+    i18n::t(match mode {
+        TelemetryMode::Ask => "telemetry.mode_ask",
+        TelemetryMode::Off => "telemetry.mode_off",
+        TelemetryMode::LocalOnly => "telemetry.mode_local",
+        TelemetryMode::Share => "telemetry.mode_share",
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelemetryCommand {
+    None,
+    SetMode(TelemetryMode),
+    Purge,
+}
+
+// The opt-in prompt and the "what's being sent" viewer in one: the queued
+// events with their payloads, and keys to change the mode or purge
+pub struct TelemetryPanel {
+    mode: TelemetryMode,
+    events: Vec<QueuedEvent>,
+    selected: usize,
+}
+
+impl TelemetryPanel {
+    pub fn new(mode: TelemetryMode, events: Vec<QueuedEvent>) -> Self {
+        Self {
+            mode,
+            events,
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.events.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> TelemetryCommand {
+        self.select(index);
+        match key {
+            '1' => TelemetryCommand::SetMode(TelemetryMode::Share),
+            '2' => TelemetryCommand::SetMode(TelemetryMode::LocalOnly),
+            '3' => TelemetryCommand::SetMode(TelemetryMode::Off),
+            'x' if !self.events.is_empty() => TelemetryCommand::Purge,
+            _ => TelemetryCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .events
+            .iter()
+            .map(|event| {
+                PanelItem::styled(vec![
+                    (
+                        format!("{} ", event.recorded_at.format("%H:%M:%S")),
+                        Some(Color::DarkGrey),
+                    ),
+                    (event.kind.clone(), None),
+                ])
+            })
+            .collect();
+        let preview = match (self.mode, self.events.get(self.selected)) {
+            (TelemetryMode::Ask, _) => [
+                "telemetry.prompt",
+                "telemetry.prompt_share",
+                "telemetry.prompt_local",
+                "telemetry.prompt_off",
+            ]
+            .into_iter()
+            .map(|key| i18n::t(key).into())
+            .collect(),
+            (_, Some(event)) => serde_json::to_string_pretty(&event.payload)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| line.to_string().into())
+                .collect(),
+            (_, None) => vec![i18n::t("telemetry.empty").into()],
+        };
+        PanelView {
+            kind: PanelKind::Telemetry,
+            title: i18n::t_args(
+                "panel.telemetry",
+                &[
+                    ("mode", &mode_label(self.mode)),
+                    ("count", &self.events.len().to_string()),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.telemetry_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_nothing_recorded_before_opt_in() {
+        let privacy = PrivacyManager::new().await.unwrap();
+        assert!(!privacy.record("startup", &json!({})).await.unwrap());
+        privacy.set_mode(TelemetryMode::Off).await;
+        assert!(!privacy.record("startup", &json!({})).await.unwrap());
+        assert!(privacy.queued().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_only_never_leaves() {
+        let privacy = PrivacyManager::with_mode(TelemetryMode::LocalOnly);
+        privacy
+            .record("startup", &json!({ "version": "1.0" }))
+            .await
+            .unwrap();
+        assert_eq!(privacy.queued().await[0].payload["version"], "1.0");
+        assert!(privacy.take_outgoing().await.is_empty());
+
+        privacy.set_mode(TelemetryMode::Share).await;
+        assert_eq!(privacy.take_outgoing().await.len(), 1);
+        assert!(privacy.queued().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_purge_and_opt_out_clear_the_queue() {
+        let privacy = PrivacyManager::with_mode(TelemetryMode::Share);
+        privacy.record("a", &json!(1)).await.unwrap();
+        privacy.record("b", &json!(2)).await.unwrap();
+        assert_eq!(privacy.purge().await, 2);
+        privacy.record("c", &json!(3)).await.unwrap();
+        privacy.set_mode(TelemetryMode::Off).await;
+        assert!(privacy.queued().await.is_empty());
+    }
+
+    #[test]
+    fn test_panel_keys() {
+        let event = QueuedEvent {
+            kind: "startup".to_string(),
+            recorded_at: Utc::now(),
+            payload: json!({ "os": "linux" }),
+        };
+        let mut panel = TelemetryPanel::new(TelemetryMode::LocalOnly, vec![event]);
+        assert_eq!(panel.view().items.len(), 1);
+        assert!(panel.view().preview.len() > 1);
+        assert_eq!(
+            panel.handle_key('1', 0),
+            TelemetryCommand::SetMode(TelemetryMode::Share)
+        );
+        assert_eq!(panel.handle_key('x', 0), TelemetryCommand::Purge);
+
+        let mut empty = TelemetryPanel::new(TelemetryMode::Ask, Vec::new());
+        assert_eq!(empty.handle_key('x', 0), TelemetryCommand::None);
+    }
+}
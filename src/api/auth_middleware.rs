@@ -0,0 +1,713 @@
+use super::*;
+use crate::error::WarpError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+// Clock difference tolerated when checking exp and iat
+const LEEWAY_SECS: i64 = 30;
+const ISSUER: &str = "warp-api";
+// Requests to these paths need no token; snapshot URLs carry their own
+const PUBLIC_ROUTES: &[&str] = &[
+    "/health",
+    "/docs",
+    "/auth/token",
+    "/auth/refresh",
+    "/snapshots",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claims {
+    pub iss: String,
+    pub sub: String,
+    pub jti: String,
+    pub fam: String, // refresh family the token was issued in
+    pub iat: i64,
+    pub exp: i64,
+    pub scopes: Vec<APIScope>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+}
+
+// The scopes a route needs: the longest matching prefix for the method wins
+#[derive(Debug, Clone)]
+pub struct RouteRule {
+    pub method: Option<String>, // None matches any method
+    pub prefix: String,
+    pub scopes: Vec<APIScope>,
+}
+
+impl RouteRule {
+    pub fn new(method: Option<&str>, prefix: &str, scopes: Vec<APIScope>) -> Self {
+        Self {
+            method: method.map(|method| method.to_ascii_uppercase()),
+            prefix: prefix.to_string(),
+            scopes,
+        }
+    }
+
+    fn matches(&self, method: &str, path: &str) -> bool {
+        self.method
+            .as_deref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(method))
+            && (path == self.prefix
+                || path.starts_with(&format!("{}/", self.prefix.trim_end_matches('/'))))
+    }
+}
+
+// Who made a request, once authorize() has let it through
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    pub user_id: String,
+    pub token_id: String,
+    pub scopes: Vec<APIScope>,
+}
+
+// A refresh token as stored: hashed, tied to the user and the family it
+// was rotated from. Using a spent one revokes the whole family, since it
+// means the token was copied.
+#[derive(Debug, Clone)]
+struct RefreshRecord {
+    user_id: String,
+    family: String,
+    scopes: Vec<APIScope>,
+    expires_at: i64,
+    spent: bool,
+}
+
+#[derive(Default)]
+struct TokenState {
+    refresh_tokens: HashMap<String, RefreshRecord>, // by hash
+    revoked_access: HashMap<String, i64>,           // jti -> exp, pruned once expired
+    // Family -> when every token issued in it has expired, pruned after that
+    revoked_families: HashMap<String, i64>,
+}
+
+pub struct AuthMiddleware {
+    config: Arc<Mutex<APIConfig>>,
+    rng: SystemRandom,
+    routes: Vec<RouteRule>,
+    state: Mutex<TokenState>,
+}
+
+impl AuthMiddleware {
+    pub async fn new(config: Arc<Mutex<APIConfig>>) -> Result<Self, WarpError> {
+        let secret_len = config.lock().await.authentication.jwt_secret.len();
+        if secret_len < 32 {
+            return Err(WarpError::Config(
+                "jwt_secret must be at least 32 bytes".to_string(),
+            ));
+        }
+        Ok(Self {
+            config,
+            rng: SystemRandom::new(),
+            routes: default_routes(),
+            state: Mutex::new(TokenState::default()),
+        })
+    }
+
+    pub fn with_routes(mut self, routes: Vec<RouteRule>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    pub async fn issue_tokens(
+        &self,
+        user_id: &str,
+        scopes: Vec<APIScope>,
+    ) -> Result<TokenPair, WarpError> {
+        let family = self.random_id()?;
+        self.issue_in_family(user_id, scopes, family, now()).await
+    }
+
+    // Rotation: the presented refresh token is spent and a new pair issued
+    // in the same family
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenPair, WarpError> {
+        let now = now();
+        let hash = hash_token(refresh_token);
+        let revoke_until = self.family_expiry(now).await;
+        let record = {
+            let mut state = self.state.lock().await;
+            let record = state
+                .refresh_tokens
+                .get(&hash)
+                .cloned()
+                .ok_or_else(|| WarpError::Auth("Unknown refresh token".to_string()))?;
+            if record.spent {
+                log::warn!(
+                    "Refresh token reuse for {}; revoking its session",
+                    record.user_id
+                );
+                state
+                    .revoked_families
+                    .insert(record.family.clone(), revoke_until);
+                return Err(WarpError::Auth(
+                    "Refresh token was already used".to_string(),
+                ));
+            }
+            if state.revoked_families.contains_key(&record.family) {
+                return Err(WarpError::Auth("Session has been revoked".to_string()));
+            }
+            if record.expires_at <= now {
+                state.refresh_tokens.remove(&hash);
+                return Err(WarpError::Auth("Refresh token has expired".to_string()));
+            }
+            if let Some(entry) = state.refresh_tokens.get_mut(&hash) {
+                entry.spent = true;
+            }
+            record
+        };
+        self.issue_in_family(&record.user_id, record.scopes, record.family, now)
+            .await
+    }
+
+    pub async fn validate(&self, token: &str) -> Result<Claims, WarpError> {
+        let secret = self.config.lock().await.authentication.jwt_secret.clone();
+        let claims = decode(token, secret.as_bytes(), now())?;
+        let state = self.state.lock().await;
+        if state.revoked_access.contains_key(&claims.jti)
+            || state.revoked_families.contains_key(&claims.fam)
+        {
+            return Err(WarpError::Auth("Token has been revoked".to_string()));
+        }
+        Ok(claims)
+    }
+
+    // Checks the bearer token against the route's scopes. Public routes
+    // pass with an anonymous context.
+    pub async fn authorize(&self, request: &APIRequest) -> Result<AuthContext, WarpError> {
+        check_path(&request.path)?;
+        if is_public(&request.path) {
+            return Ok(AuthContext {
+                user_id: String::new(),
+                token_id: String::new(),
+                scopes: Vec::new(),
+            });
+        }
+        let token = bearer_token(request)
+            .ok_or_else(|| WarpError::Auth("Missing bearer token".to_string()))?;
+        let claims = self.validate(token).await?;
+        self.check_scopes(&request.method, &request.path, &claims.scopes)?;
+        Ok(AuthContext {
+            user_id: claims.sub,
+            token_id: claims.jti,
+            scopes: claims.scopes,
+        })
+    }
+
+    pub fn check_scopes(
+        &self,
+        method: &str,
+        path: &str,
+        held: &[APIScope],
+    ) -> Result<(), WarpError> {
+        let required = self.required_scopes(method, path);
+        match required
+            .iter()
+            .find(|scope| !held.iter().any(|held| grants(held, scope)))
+        {
+            Some(missing) => Err(WarpError::Permission(format!(
+                "{} {} needs the {:?} scope",
+                method, path, missing
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // Routes nothing matches need SystemAdmin, so a new endpoint is closed
+    // until someone gives it a rule
+    pub fn required_scopes(&self, method: &str, path: &str) -> Vec<APIScope> {
+        self.routes
+            .iter()
+            .filter(|rule| rule.matches(method, path))
+            .max_by_key(|rule| (rule.prefix.len(), rule.method.is_some()))
+            .map(|rule| rule.scopes.clone())
+            .unwrap_or_else(|| vec![APIScope::SystemAdmin])
+    }
+
+    pub async fn revoke_access_token(&self, token: &str) -> Result<(), WarpError> {
+        let secret = self.config.lock().await.authentication.jwt_secret.clone();
+        // An expired token needs no revoking
+        let claims = match decode(token, secret.as_bytes(), now()) {
+            Ok(claims) => claims,
+            Err(_) => return Ok(()),
+        };
+        let mut state = self.state.lock().await;
+        state.revoked_access.insert(claims.jti, claims.exp);
+        Ok(())
+    }
+
+    // Revokes the token's family, so the access tokens issued with it stop
+    // working too
+    pub async fn revoke_refresh_token(&self, refresh_token: &str) {
+        let until = self.family_expiry(now()).await;
+        let mut state = self.state.lock().await;
+        if let Some(record) = state.refresh_tokens.remove(&hash_token(refresh_token)) {
+            state.revoked_families.insert(record.family, until);
+        }
+    }
+
+    // Signs the user out everywhere by revoking every family they hold.
+    // Tokens issued afterwards, even within the same second, still work.
+    pub async fn revoke_user(&self, user_id: &str) {
+        let until = self.family_expiry(now()).await;
+        let mut state = self.state.lock().await;
+        let families: Vec<String> = state
+            .refresh_tokens
+            .values()
+            .filter(|record| record.user_id == user_id)
+            .map(|record| record.family.clone())
+            .collect();
+        for family in families {
+            state.revoked_families.insert(family, until);
+        }
+        state
+            .refresh_tokens
+            .retain(|_, record| record.user_id != user_id);
+    }
+
+    // Drops revocation and refresh entries whose tokens have expired anyway
+    pub async fn prune(&self) {
+        let now = now();
+        let mut state = self.state.lock().await;
+        state
+            .revoked_access
+            .retain(|_, exp| *exp + LEEWAY_SECS > now);
+        state
+            .revoked_families
+            .retain(|_, until| *until + LEEWAY_SECS > now);
+        state
+            .refresh_tokens
+            .retain(|_, record| record.expires_at > now);
+    }
+
+    // Any token in a family was issued by now, so it's expired once the
+    // longer of the two lifetimes has passed
+    async fn family_expiry(&self, now: i64) -> i64 {
+        let config = self.config.lock().await;
+        let auth = &config.authentication;
+        now + auth.token_expiry.max(auth.refresh_token_expiry) as i64
+    }
+
+    async fn issue_in_family(
+        &self,
+        user_id: &str,
+        scopes: Vec<APIScope>,
+        family: String,
+        now: i64,
+    ) -> Result<TokenPair, WarpError> {
+        let (secret, access_ttl, refresh_ttl) = {
+            let config = self.config.lock().await;
+            let auth = &config.authentication;
+            (
+                auth.jwt_secret.clone(),
+                auth.token_expiry,
+                auth.refresh_token_expiry,
+            )
+        };
+        let claims = Claims {
+            iss: ISSUER.to_string(),
+            sub: user_id.to_string(),
+            jti: self.random_id()?,
+            fam: family.clone(),
+            iat: now,
+            exp: now + access_ttl as i64,
+            scopes: scopes.clone(),
+        };
+        let access_token = encode(&claims, secret.as_bytes())?;
+        let refresh_token = self.random_id()?;
+
+        let mut state = self.state.lock().await;
+        state.refresh_tokens.insert(
+            hash_token(&refresh_token),
+            RefreshRecord {
+                user_id: user_id.to_string(),
+                family,
+                scopes,
+                expires_at: now + refresh_ttl as i64,
+                spent: false,
+            },
+        );
+        Ok(TokenPair {
+            access_token,
+            refresh_token,
+            token_type: "Bearer".to_string(),
+            expires_in: access_ttl,
+        })
+    }
+
+    fn random_id(&self) -> Result<String, WarpError> {
+        let mut bytes = [0u8; 32];
+        self.rng
+            .fill(&mut bytes)
+            .map_err(|_| WarpError::Config("Could not generate a token".to_string()))?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+// Rules match on path prefixes, so a path that a server or proxy could
+// resolve to somewhere else, like /health/../system, is refused outright
+// rather than normalized here and read differently there
+pub(crate) fn check_path(path: &str) -> Result<(), WarpError> {
+    let lowered = path.to_ascii_lowercase();
+    let ambiguous = !path.starts_with('/')
+        || path.contains('\\')
+        || ["%2e", "%2f", "%5c"]
+            .iter()
+            .any(|encoded| lowered.contains(encoded))
+        || (path != "/"
+            && path[1..]
+                .split('/')
+                .any(|segment| segment.is_empty() || segment == "." || segment == ".."));
+    if ambiguous {
+        return Err(WarpError::Validation(format!(
+            "Path '{}' isn't in canonical form",
+            path
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn is_public(path: &str) -> bool {
+    PUBLIC_ROUTES
+        .iter()
+        .any(|route| path == *route || path.starts_with(&format!("{}/", route)))
+}
+
+fn bearer_token(request: &APIRequest) -> Option<&str> {
+    request
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| {
+            value
+                .strip_prefix("Bearer ")
+                .or_else(|| value.strip_prefix("bearer "))
+        })
+        .map(str::trim)
+}
+
+// Admin scopes include write, and write includes read, within an area;
+// SystemAdmin holds everything
+fn grants(held: &APIScope, required: &APIScope) -> bool {
+    use APIScope::*;
+    if held == required || *held == SystemAdmin {
+        return true;
+    }
+    matches!(
+        (held, required),
+        (MarketplaceAdmin, MarketplaceWrite | MarketplaceRead)
+            | (MarketplaceWrite, MarketplaceRead)
+            | (AnalyticsWrite, AnalyticsRead)
+            | (UserWrite, UserRead)
+            | (CICDExecute, CICDWrite | CICDRead)
+            | (CICDWrite, CICDRead)
+            | (CollaborationManage, CollaborationWrite | CollaborationRead)
+            | (CollaborationWrite, CollaborationRead)
+            | (VisualizationWrite, VisualizationRead)
+            | (SystemWrite, SystemRead)
+    )
+}
+
+fn default_routes() -> Vec<RouteRule> {
+    use APIScope::*;
+    vec![
+        RouteRule::new(Some("GET"), "/marketplace", vec![MarketplaceRead]),
+        RouteRule::new(None, "/marketplace", vec![MarketplaceWrite]),
+        RouteRule::new(None, "/marketplace/admin", vec![MarketplaceAdmin]),
+        RouteRule::new(Some("GET"), "/analytics", vec![AnalyticsRead]),
+        RouteRule::new(None, "/analytics", vec![AnalyticsWrite]),
+        RouteRule::new(Some("GET"), "/users", vec![UserRead]),
+        RouteRule::new(None, "/users", vec![UserWrite]),
+        RouteRule::new(Some("GET"), "/cicd", vec![CICDRead]),
+        RouteRule::new(None, "/cicd", vec![CICDWrite]),
+        RouteRule::new(Some("POST"), "/cicd/run", vec![CICDExecute]),
+        RouteRule::new(Some("GET"), "/collaboration", vec![CollaborationRead]),
+        RouteRule::new(None, "/collaboration", vec![CollaborationWrite]),
+        RouteRule::new(Some("GET"), "/visualization", vec![VisualizationRead]),
+        RouteRule::new(None, "/visualization", vec![VisualizationWrite]),
+        RouteRule::new(Some("GET"), "/system", vec![SystemRead]),
+        RouteRule::new(None, "/system", vec![SystemWrite]),
+        // GraphQL checks field scopes itself; reaching it needs any valid token
+        RouteRule::new(None, "/graphql", Vec::new()),
+    ]
+}
+
+// Refresh tokens and API keys are stored hashed so a dump of the state
+// can't be replayed
+pub(crate) fn hash_token(token: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+    URL_SAFE_NO_PAD.encode(digest.as_ref())
+}
+
+fn encode(claims: &Claims, secret: &[u8]) -> Result<String, WarpError> {
+    let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+    let signing_input = format!("{}.{}", header, payload);
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    let signature = hmac::sign(&key, signing_input.as_bytes());
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature.as_ref())
+    ))
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+}
+
+// Only HS256 is accepted; the header can't pick a weaker algorithm
+fn decode(token: &str, secret: &[u8], now: i64) -> Result<Claims, WarpError> {
+    let invalid = || WarpError::Auth("Invalid token".to_string());
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let signing_input = format!("{}.{}", header, payload);
+    let header: Header = URL_SAFE_NO_PAD
+        .decode(header)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(invalid)?;
+    if header.alg != "HS256" {
+        return Err(invalid());
+    }
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hmac::verify(&key, signing_input.as_bytes(), &signature).map_err(|_| invalid())?;
+
+    let claims: Claims = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(invalid)?;
+    if claims.iss != ISSUER || claims.iat > now + LEEWAY_SECS {
+        return Err(invalid());
+    }
+    if claims.exp + LEEWAY_SECS <= now {
+        return Err(WarpError::Auth("Token has expired".to_string()));
+    }
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn middleware() -> AuthMiddleware {
+        let mut config = APIConfig::default();
+        config.authentication.jwt_secret = "0123456789abcdef0123456789abcdef".to_string();
+        AuthMiddleware::new(Arc::new(Mutex::new(config)))
+            .await
+            .unwrap()
+    }
+
+    fn request(method: &str, path: &str, token: Option<&str>) -> APIRequest {
+        let mut headers = HashMap::new();
+        if let Some(token) = token {
+            headers.insert("Authorization".to_string(), format!("Bearer {}", token));
+        }
+        APIRequest {
+            request_id: "r".to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            query_params: HashMap::new(),
+            headers,
+            body: None,
+            user_id: None,
+            api_key_id: None,
+            timestamp: chrono::Utc::now(),
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_other_algorithms() {
+        let secret = b"0123456789abcdef0123456789abcdef";
+        let claims = Claims {
+            iss: ISSUER.to_string(),
+            sub: "alice".to_string(),
+            jti: "1".to_string(),
+            fam: "f".to_string(),
+            iat: 1000,
+            exp: 2000,
+            scopes: vec![APIScope::MarketplaceRead],
+        };
+        let token = encode(&claims, secret).unwrap();
+        assert_eq!(decode(&token, secret, 1500).unwrap(), claims);
+        assert!(decode(&token, b"another secret, also 32 bytes lo", 1500).is_err());
+        assert!(decode(&token, secret, 2100).is_err());
+
+        let mut forged = claims.clone();
+        forged.scopes = vec![APIScope::SystemAdmin];
+        let forged_payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        let parts: Vec<&str> = token.split('.').collect();
+        assert!(decode(
+            &format!("{}.{}.{}", parts[0], forged_payload, parts[2]),
+            secret,
+            1500
+        )
+        .is_err());
+
+        let none_header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none"}"#);
+        assert!(decode(&format!("{}.{}.", none_header, parts[1]), secret, 1500).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_scopes() {
+        let auth = middleware().await;
+        let reader = auth
+            .issue_tokens("alice", vec![APIScope::MarketplaceRead])
+            .await
+            .unwrap();
+        let admin = auth
+            .issue_tokens("root", vec![APIScope::MarketplaceAdmin])
+            .await
+            .unwrap();
+
+        assert!(auth
+            .authorize(&request(
+                "GET",
+                "/marketplace/items",
+                Some(&reader.access_token)
+            ))
+            .await
+            .is_ok());
+        assert!(auth
+            .authorize(&request(
+                "POST",
+                "/marketplace/items",
+                Some(&reader.access_token)
+            ))
+            .await
+            .is_err());
+        assert!(auth
+            .authorize(&request(
+                "POST",
+                "/marketplace/items",
+                Some(&admin.access_token)
+            ))
+            .await
+            .is_ok());
+        // Unlisted routes are admin-only; public ones need nothing
+        assert!(auth
+            .authorize(&request("GET", "/internal", Some(&admin.access_token)))
+            .await
+            .is_err());
+        assert!(auth
+            .authorize(&request("GET", "/health", None))
+            .await
+            .is_ok());
+        assert!(auth
+            .authorize(&request("GET", "/marketplace", None))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_public_prefixes_need_canonical_paths() {
+        let auth = middleware().await;
+        assert!(auth
+            .authorize(&request("GET", "/snapshots/abc", None))
+            .await
+            .is_ok());
+        for path in [
+            "/health/../system",
+            "/health/./x",
+            "/snapshots//x",
+            "/health/%2e%2e/system",
+            "/health/..%2Fsystem",
+            "health",
+        ] {
+            assert!(
+                auth.authorize(&request("GET", path, None)).await.is_err(),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotation_and_reuse() {
+        let auth = middleware().await;
+        let first = auth
+            .issue_tokens("alice", vec![APIScope::UserRead])
+            .await
+            .unwrap();
+        let second = auth.refresh(&first.refresh_token).await.unwrap();
+        assert_eq!(
+            auth.validate(&second.access_token).await.unwrap().sub,
+            "alice"
+        );
+
+        // Replaying the spent token revokes the family, including the new one
+        assert!(auth.refresh(&first.refresh_token).await.is_err());
+        assert!(auth.refresh(&second.refresh_token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revocation() {
+        let auth = middleware().await;
+        let pair = auth
+            .issue_tokens("alice", vec![APIScope::UserRead])
+            .await
+            .unwrap();
+        auth.revoke_access_token(&pair.access_token).await.unwrap();
+        assert!(auth.validate(&pair.access_token).await.is_err());
+
+        let pair = auth
+            .issue_tokens("bob", vec![APIScope::UserRead])
+            .await
+            .unwrap();
+        auth.revoke_user("bob").await;
+        assert!(auth.validate(&pair.access_token).await.is_err());
+        assert!(auth.refresh(&pair.refresh_token).await.is_err());
+        // Signing back in right away isn't caught by the revocation
+        let again = auth
+            .issue_tokens("bob", vec![APIScope::UserRead])
+            .await
+            .unwrap();
+        assert!(auth.validate(&again.access_token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_revoked_family_rejects_its_access_tokens() {
+        let auth = middleware().await;
+        let first = auth
+            .issue_tokens("alice", vec![APIScope::UserRead])
+            .await
+            .unwrap();
+        let rotated = auth.refresh(&first.refresh_token).await.unwrap();
+        let other = auth
+            .issue_tokens("alice", vec![APIScope::UserRead])
+            .await
+            .unwrap();
+
+        auth.revoke_refresh_token(&rotated.refresh_token).await;
+        auth.prune().await;
+        assert!(auth.validate(&first.access_token).await.is_err());
+        assert!(auth.validate(&rotated.access_token).await.is_err());
+        assert!(auth.validate(&other.access_token).await.is_ok());
+
+        // Reuse of a spent refresh token takes its family's access tokens down as well
+        let replayed = auth.refresh(&other.refresh_token).await.unwrap();
+        assert!(auth.refresh(&other.refresh_token).await.is_err());
+        assert!(auth.validate(&replayed.access_token).await.is_err());
+    }
+}
@@ -0,0 +1,666 @@
+use super::*;
+use crate::analytics::{AnalyticsEngine, AnalyticsEvent, EventType};
+use crate::custom_metrics::{CustomMetricsManager, MetricDataPoint, MetricValue};
+use crate::error::WarpError;
+use crate::network;
+use crate::oauth;
+use crate::secrets::SecretsManager;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::time::Duration;
+
+// How often the scheduler looks for integrations that are due
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+// `parameters` keys that steer the sync rather than going into the query string
+const RECORDS_PATH: &str = "sync.records_path"; // dotted path to the record array
+const API_KEY_HEADER: &str = "sync.api_key_header"; // defaults to X-API-Key
+const ANALYTICS_EVENT: &str = "sync.analytics_event"; // EventType name; records also become analytics events
+
+// data_mapping targets. `metric.<id>` becomes a custom-metrics data point,
+// `dimension.<name>` a dimension on every point from the record, `timestamp`
+// the time of the record; anything else is kept as event metadata.
+const METRIC_PREFIX: &str = "metric.";
+const DIMENSION_PREFIX: &str = "dimension.";
+const TIMESTAMP_FIELD: &str = "timestamp";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncHealth {
+    pub last_attempt: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub last_records: usize,
+    pub total_records: u64,
+    pub last_duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub records: usize,
+    pub data_points: usize,
+    pub events: usize,
+    pub skipped: usize, // records the mapping couldn't use
+}
+
+pub struct IntegrationManager {
+    client: reqwest::Client,
+    registered: Mutex<HashSet<String>>,
+    health: Mutex<HashMap<String, SyncHealth>>,
+    metrics: Mutex<Option<Arc<CustomMetricsManager>>>,
+    analytics: Mutex<Option<Arc<AnalyticsEngine>>>,
+    secrets: Mutex<Option<Arc<SecretsManager>>>,
+}
+
+impl IntegrationManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self {
+            client: network::client()?,
+            registered: Mutex::new(HashSet::new()),
+            health: Mutex::new(HashMap::new()),
+            metrics: Mutex::new(None),
+            analytics: Mutex::new(None),
+            secrets: Mutex::new(None),
+        })
+    }
+
+    pub async fn set_metrics_store(&self, metrics: Arc<CustomMetricsManager>) {
+        *self.metrics.lock().await = Some(metrics);
+    }
+
+    pub async fn set_analytics(&self, analytics: Arc<AnalyticsEngine>) {
+        *self.analytics.lock().await = Some(analytics);
+    }
+
+    // Needed for IntegrationAuth::DeviceCode
+    pub async fn set_secrets(&self, secrets: Arc<SecretsManager>) {
+        *self.secrets.lock().await = Some(secrets);
+    }
+
+    pub async fn register_integration(&self, integration_id: &str) -> Result<(), WarpError> {
+        self.registered
+            .lock()
+            .await
+            .insert(integration_id.to_string());
+        self.health
+            .lock()
+            .await
+            .entry(integration_id.to_string())
+            .or_default();
+        Ok(())
+    }
+
+    pub async fn unregister_integration(&self, integration_id: &str) {
+        self.registered.lock().await.remove(integration_id);
+        self.health.lock().await.remove(integration_id);
+    }
+
+    pub async fn health(&self, integration_id: &str) -> Option<SyncHealth> {
+        self.health.lock().await.get(integration_id).cloned()
+    }
+
+    pub async fn all_health(&self) -> HashMap<String, SyncHealth> {
+        self.health.lock().await.clone()
+    }
+
+    // Reachability and credentials only; nothing is written
+    pub async fn test_integration(&self, integration: &Integration) -> Result<bool, WarpError> {
+        let response = self
+            .request(&integration.configuration)
+            .await?
+            .send()
+            .await
+            .map_err(|e| WarpError::network(format!("Could not reach {}", integration.name), e))?;
+        Ok(response.status().is_success())
+    }
+
+    // Pulls the endpoint, maps each record and writes the results to the
+    // metrics and analytics stores. Health is recorded either way.
+    pub async fn sync_integration(
+        &self,
+        integration: &Integration,
+    ) -> Result<SyncReport, WarpError> {
+        let started = std::time::Instant::now();
+        let attempt = Utc::now();
+        let result = self.pull_and_store(integration).await;
+
+        let mut health = self.health.lock().await;
+        let entry = health
+            .entry(integration.integration_id.clone())
+            .or_default();
+        entry.last_attempt = Some(attempt);
+        entry.last_duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(report) => {
+                entry.last_success = Some(attempt);
+                entry.last_error = None;
+                entry.consecutive_failures = 0;
+                entry.last_records = report.records;
+                entry.total_records += report.records as u64;
+            }
+            Err(e) => {
+                entry.last_error = Some(e.to_string());
+                entry.consecutive_failures += 1;
+            }
+        }
+        result
+    }
+
+    // Syncs one integration from the shared table, updating its status,
+    // last_sync, error_count and last_error. The table isn't locked while
+    // the request is in flight.
+    pub async fn run_sync(
+        &self,
+        integrations: &Mutex<HashMap<String, Integration>>,
+        integration_id: &str,
+    ) -> Result<SyncReport, WarpError> {
+        let integration = {
+            let mut integrations = integrations.lock().await;
+            let integration = integrations
+                .get_mut(integration_id)
+                .ok_or_else(|| WarpError::not_found("integration", integration_id))?;
+            integration.status = IntegrationStatus::Syncing;
+            integration.last_sync = Some(Utc::now());
+            integration.clone()
+        };
+
+        let result = self.sync_integration(&integration).await;
+
+        let mut integrations = integrations.lock().await;
+        if let Some(integration) = integrations.get_mut(integration_id) {
+            integration.updated_at = Utc::now();
+            match &result {
+                Ok(_) => {
+                    integration.status = IntegrationStatus::Active;
+                    integration.error_count = 0;
+                    integration.last_error = None;
+                }
+                Err(e) => {
+                    integration.status = IntegrationStatus::Error;
+                    integration.error_count += 1;
+                    integration.last_error = Some(e.to_string());
+                }
+            }
+        }
+        result
+    }
+
+    // Syncs every registered integration whose sync_frequency has elapsed.
+    // Paused and inactive ones are left alone; ones in error keep retrying
+    // on their normal schedule.
+    pub fn start_scheduler(
+        self: Arc<Self>,
+        integrations: Arc<Mutex<HashMap<String, Integration>>>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SCHEDULER_TICK);
+            loop {
+                interval.tick().await;
+                let due: Vec<String> = {
+                    let registered = self.registered.lock().await;
+                    let integrations = integrations.lock().await;
+                    let now = Utc::now();
+                    integrations
+                        .values()
+                        .filter(|integration| registered.contains(&integration.integration_id))
+                        .filter(|integration| is_due(integration, now))
+                        .map(|integration| integration.integration_id.clone())
+                        .collect()
+                };
+                for integration_id in due {
+                    if let Err(e) = self.run_sync(&integrations, &integration_id).await {
+                        log::warn!("Sync of integration {} failed: {}", integration_id, e);
+                    }
+                }
+            }
+        })
+    }
+
+    async fn pull_and_store(&self, integration: &Integration) -> Result<SyncReport, WarpError> {
+        let config = &integration.configuration;
+        let body = self.fetch(config).await?;
+        let records = extract_records(
+            &body,
+            config.parameters.get(RECORDS_PATH).map(String::as_str),
+        )?;
+
+        let metrics = self.metrics.lock().await.clone();
+        let analytics = self.analytics.lock().await.clone();
+        let event_type = match config.parameters.get(ANALYTICS_EVENT) {
+            Some(name) => Some(
+                serde_json::from_value::<EventType>(serde_json::Value::String(name.clone()))
+                    .map_err(|_| {
+                        WarpError::Config(format!("Unknown analytics event type '{}'", name))
+                    })?,
+            ),
+            None => None,
+        };
+        if metrics.is_none() && analytics.is_none() {
+            return Err(WarpError::Config(
+                "No metrics or analytics store to sync into".to_string(),
+            ));
+        }
+
+        let mut report = SyncReport {
+            records: records.len(),
+            ..SyncReport::default()
+        };
+        for record in &records {
+            let mapped = match map_record(record, &config.data_mapping) {
+                Ok(mapped) => mapped,
+                Err(e) => {
+                    log::debug!("Skipping record from {}: {}", integration.name, e);
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+
+            if let Some(metrics) = &metrics {
+                for (metric_id, value) in &mapped.metrics {
+                    metrics
+                        .record_metric(MetricDataPoint {
+                            metric_id: metric_id.clone(),
+                            value: value.clone(),
+                            dimensions: mapped.dimensions.clone(),
+                            timestamp: mapped.timestamp,
+                            source: format!("integration:{}", integration.integration_id),
+                            metadata: mapped.fields.clone(),
+                        })
+                        .await?;
+                    report.data_points += 1;
+                }
+            }
+            if let (Some(analytics), Some(event_type)) = (&analytics, &event_type) {
+                let mut metadata = mapped.fields.clone();
+                metadata.extend(
+                    mapped
+                        .dimensions
+                        .iter()
+                        .map(|(k, v)| (k.clone(), serde_json::json!(v))),
+                );
+                analytics
+                    .track_event(AnalyticsEvent {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        event_type: event_type.clone(),
+                        timestamp: mapped.timestamp,
+                        user_id: None,
+                        session_id: format!("integration:{}", integration.integration_id),
+                        item_id: None,
+                        metadata,
+                        performance_data: None,
+                    })
+                    .await?;
+                report.events += 1;
+            }
+        }
+        Ok(report)
+    }
+
+    // GET with the integration's auth, headers and query, retried per its
+    // RetryConfig on network errors and the listed statuses
+    async fn fetch(&self, config: &IntegrationConfig) -> Result<serde_json::Value, WarpError> {
+        let retry = &config.retry_config;
+        let mut delay = Duration::from_millis(retry.initial_delay);
+        let mut attempt = 1;
+        loop {
+            let outcome = match self.request(config).await?.send().await {
+                Ok(response) if response.status().is_success() => {
+                    return response.json().await.map_err(|e| {
+                        WarpError::network(format!("{} returned invalid JSON", config.endpoint), e)
+                    });
+                }
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    let body = response.text().await.unwrap_or_default();
+                    let retryable = retry.retry_on_status.contains(&status);
+                    (WarpError::api(Some(status), body), retryable)
+                }
+                Err(e) => (
+                    WarpError::network(format!("Could not reach {}", config.endpoint), e),
+                    true,
+                ),
+            };
+            match outcome {
+                (error, true) if attempt < retry.max_attempts => {
+                    log::debug!("Retrying {} after: {}", config.endpoint, error);
+                    tokio::time::sleep(delay).await;
+                    delay = next_delay(delay, retry);
+                    attempt += 1;
+                }
+                (error, _) => return Err(error),
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        config: &IntegrationConfig,
+    ) -> Result<reqwest::RequestBuilder, WarpError> {
+        let query: Vec<(&String, &String)> = config
+            .parameters
+            .iter()
+            .filter(|(key, _)| !key.starts_with("sync."))
+            .collect();
+        let mut request = self
+            .client
+            .get(network::endpoint(&config.endpoint))
+            .query(&query)
+            .timeout(Duration::from_secs(config.timeout.max(1)));
+        for (name, value) in &config.headers {
+            request = request.header(name, value);
+        }
+        Ok(match &config.authentication {
+            IntegrationAuth::None => request,
+            IntegrationAuth::ApiKey { key } => {
+                let header = config
+                    .parameters
+                    .get(API_KEY_HEADER)
+                    .map(String::as_str)
+                    .unwrap_or("X-API-Key");
+                request.header(header, key)
+            }
+            IntegrationAuth::Bearer { token } => request.bearer_auth(token),
+            IntegrationAuth::Basic { username, password } => {
+                request.basic_auth(username, Some(password))
+            }
+            IntegrationAuth::OAuth2 { access_token, .. } => request.bearer_auth(access_token),
+            IntegrationAuth::DeviceCode(provider) => {
+                let secrets = self.secrets.lock().await.clone().ok_or_else(|| {
+                    WarpError::Config("No secrets manager for OAuth integrations".to_string())
+                })?;
+                let token = oauth::access_token(&secrets, provider)
+                    .await?
+                    .ok_or_else(|| {
+                        WarpError::Auth(format!(
+                            "Not signed in; run `warp auth login {}`",
+                            provider.name
+                        ))
+                    })?;
+                request.bearer_auth(token)
+            }
+            IntegrationAuth::Custom(headers) => {
+                headers.iter().fold(request, |request, (name, value)| {
+                    request.header(name, value)
+                })
+            }
+        })
+    }
+}
+
+fn is_due(integration: &Integration, now: DateTime<Utc>) -> bool {
+    if matches!(
+        integration.status,
+        IntegrationStatus::Paused | IntegrationStatus::Inactive | IntegrationStatus::Syncing
+    ) {
+        return false;
+    }
+    match integration.last_sync {
+        Some(last) => now - last >= chrono::Duration::seconds(integration.sync_frequency as i64),
+        None => true,
+    }
+}
+
+fn next_delay(delay: Duration, retry: &RetryConfig) -> Duration {
+    let next = delay.mul_f64(retry.backoff_multiplier.max(1.0));
+    next.min(Duration::from_millis(
+        retry.max_delay.max(retry.initial_delay),
+    ))
+}
+
+// The records in a response: the array at `path`, the top-level array, or
+// the single object
+fn extract_records(
+    body: &serde_json::Value,
+    path: Option<&str>,
+) -> Result<Vec<serde_json::Value>, WarpError> {
+    let target = match path {
+        Some(path) => lookup(body, path)
+            .ok_or_else(|| WarpError::Validation(format!("Response has no '{}'", path)))?,
+        None => body,
+    };
+    match target {
+        serde_json::Value::Array(records) => Ok(records.clone()),
+        serde_json::Value::Object(_) => Ok(vec![target.clone()]),
+        _ => Err(WarpError::Validation(
+            "Response holds no records".to_string(),
+        )),
+    }
+}
+
+// `a.b.0.c`; `$` is the record itself
+fn lookup<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    if path == "$" {
+        return Some(value);
+    }
+    path.split('.').try_fold(value, |value, key| match value {
+        serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MappedRecord {
+    metrics: Vec<(String, MetricValue)>,
+    dimensions: HashMap<String, String>,
+    fields: HashMap<String, serde_json::Value>,
+    timestamp: DateTime<Utc>,
+}
+
+// Each mapping is `target -> source`, where the source is a path followed by
+// optional `|` transforms (`price.amount | number`), or `=literal`.
+fn map_record(
+    record: &serde_json::Value,
+    mapping: &HashMap<String, String>,
+) -> Result<MappedRecord, WarpError> {
+    let mut mapped = MappedRecord {
+        metrics: Vec::new(),
+        dimensions: HashMap::new(),
+        fields: HashMap::new(),
+        timestamp: Utc::now(),
+    };
+    let mut targets: Vec<&String> = mapping.keys().collect();
+    targets.sort();
+    for target in targets {
+        let value = evaluate(record, &mapping[target])
+            .map_err(|e| WarpError::Validation(format!("{}: {}", target, e)))?;
+        if let Some(metric_id) = target.strip_prefix(METRIC_PREFIX) {
+            if !value.is_null() {
+                mapped
+                    .metrics
+                    .push((metric_id.to_string(), metric_value(value)));
+            }
+        } else if let Some(dimension) = target.strip_prefix(DIMENSION_PREFIX) {
+            if let Some(text) = as_text(&value) {
+                mapped.dimensions.insert(dimension.to_string(), text);
+            }
+        } else if target == TIMESTAMP_FIELD {
+            mapped.timestamp = parse_timestamp(&value).ok_or_else(|| {
+                WarpError::Validation(format!("timestamp: cannot read {}", value))
+            })?;
+        } else {
+            mapped.fields.insert(target.clone(), value);
+        }
+    }
+    Ok(mapped)
+}
+
+fn evaluate(record: &serde_json::Value, expression: &str) -> Result<serde_json::Value, String> {
+    let mut parts = expression.split('|').map(str::trim);
+    let source = parts.next().unwrap_or_default();
+    let mut value = match source.strip_prefix('=') {
+        Some(literal) => serde_json::Value::String(literal.to_string()),
+        None => lookup(record, source)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null),
+    };
+    for transform in parts {
+        value = apply_transform(value, transform)?;
+    }
+    Ok(value)
+}
+
+fn apply_transform(value: serde_json::Value, transform: &str) -> Result<serde_json::Value, String> {
+    use serde_json::Value;
+    if value.is_null() {
+        return Ok(value);
+    }
+    let text = || as_text(&value).unwrap_or_default();
+    Ok(match transform {
+        "lower" => Value::String(text().to_lowercase()),
+        "upper" => Value::String(text().to_uppercase()),
+        "trim" => Value::String(text().trim().to_string()),
+        "string" => Value::String(text()),
+        "number" => match &value {
+            Value::Number(_) => value,
+            _ => text()
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| format!("'{}' is not a number", text()))?,
+        },
+        "bool" => match &value {
+            Value::Bool(_) => value,
+            _ => Value::Bool(matches!(
+                text().to_ascii_lowercase().as_str(),
+                "true" | "yes" | "1" | "on"
+            )),
+        },
+        // Seconds or milliseconds since the epoch, to RFC 3339
+        "epoch" => {
+            let number = value
+                .as_f64()
+                .or_else(|| text().parse().ok())
+                .ok_or_else(|| format!("'{}' is not an epoch time", text()))?;
+            let millis = if number > 1e11 {
+                number as i64
+            } else {
+                (number * 1000.0) as i64
+            };
+            let time = DateTime::<Utc>::from_timestamp_millis(millis)
+                .ok_or_else(|| format!("{} is out of range", number))?;
+            Value::String(time.to_rfc3339())
+        }
+        other => return Err(format!("unknown transform '{}'", other)),
+    })
+}
+
+fn as_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn metric_value(value: serde_json::Value) -> MetricValue {
+    match value {
+        serde_json::Value::Bool(flag) => MetricValue::Boolean(flag),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => MetricValue::Integer(integer),
+            None => MetricValue::Float(number.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(text) => MetricValue::String(text),
+        other => MetricValue::JSON(other),
+    }
+}
+
+fn parse_timestamp(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+    match value {
+        serde_json::Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+        serde_json::Value::Number(number) => {
+            let number = number.as_f64()?;
+            let millis = if number > 1e11 {
+                number as i64
+            } else {
+                (number * 1000.0) as i64
+            };
+            DateTime::<Utc>::from_timestamp_millis(millis)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapping(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_records() {
+        let body = json!({ "data": { "items": [{ "id": 1 }, { "id": 2 }] } });
+        assert_eq!(extract_records(&body, Some("data.items")).unwrap().len(), 2);
+        assert_eq!(extract_records(&json!({ "id": 1 }), None).unwrap().len(), 1);
+        assert!(extract_records(&body, Some("data.missing")).is_err());
+    }
+
+    #[test]
+    fn test_map_record_routes_targets() {
+        let record = json!({
+            "region": " EU-West ",
+            "stats": { "latency": "12.5", "errors": 3 },
+            "at": 1700000000,
+            "tags": ["a", "b"]
+        });
+        let mapped = map_record(
+            &record,
+            &mapping(&[
+                ("metric.latency_ms", "stats.latency | number"),
+                ("metric.error_count", "stats.errors"),
+                ("dimension.region", "region | trim | lower"),
+                ("dimension.source", "=status-page"),
+                ("timestamp", "at"),
+                ("first_tag", "tags.0"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(mapped.dimensions["region"], "eu-west");
+        assert_eq!(mapped.dimensions["source"], "status-page");
+        assert_eq!(mapped.timestamp.timestamp(), 1_700_000_000);
+        assert_eq!(mapped.fields["first_tag"], json!("a"));
+        assert!(
+            matches!(mapped.metrics[0], (ref id, MetricValue::Integer(3)) if id == "error_count")
+        );
+        assert!(
+            matches!(mapped.metrics[1], (ref id, MetricValue::Float(v)) if id == "latency_ms" && v == 12.5)
+        );
+    }
+
+    #[test]
+    fn test_map_record_rejects_bad_values() {
+        let record = json!({ "latency": "n/a" });
+        let error =
+            map_record(&record, &mapping(&[("metric.latency", "latency | number")])).unwrap_err();
+        assert!(error.to_string().contains("metric.latency"));
+        assert!(map_record(&record, &mapping(&[("x", "latency | reverse")])).is_err());
+        // A missing source is null, and null metrics are left out
+        let mapped =
+            map_record(&record, &mapping(&[("metric.other", "missing | number")])).unwrap();
+        assert!(mapped.metrics.is_empty());
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let retry = RetryConfig {
+            max_attempts: 5,
+            initial_delay: 100,
+            max_delay: 300,
+            backoff_multiplier: 2.0,
+            retry_on_status: vec![503],
+        };
+        let delay = next_delay(Duration::from_millis(100), &retry);
+        assert_eq!(delay, Duration::from_millis(200));
+        assert_eq!(next_delay(delay, &retry), Duration::from_millis(300));
+    }
+}
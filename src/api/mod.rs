@@ -1,20 +1,27 @@
+use crate::audit::{AuditAction, AuditLog};
+use crate::error::WarpError;
+use crate::secrets::SecretsManager;
+use crate::visualization::sharing::{ShareManager, SNAPSHOT_ROUTE};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
 
-pub mod rest_api;
-pub mod graphql_api;
-pub mod webhook_api;
 pub mod auth_middleware;
-pub mod rate_limiting;
-pub mod api_documentation;
-pub mod sdk_generator;
 pub mod integration_manager;
+pub mod server;
+pub mod tls;
+
+// Where JWT signing secrets are kept when the config leaves jwt_secret empty
+const JWT_SECRET_NAME: &str = "api.jwt_secret";
+// Answered by dispatch(); metrics are kept per route
+const ROUTES: &[&str] = &["/health", "/auth/token", "/auth/refresh", "/system/metrics"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct APIConfig {
+    pub enabled: bool, // the embedded API server is opt-in
+    pub port: u16,
     pub base_url: String,
     pub version: String,
     pub rate_limits: RateLimitConfig,
@@ -24,6 +31,8 @@ pub struct APIConfig {
     pub metrics_enabled: bool,
     pub logging_enabled: bool,
     pub webhook_config: WebhookConfig,
+    #[serde(default)]
+    pub server: tls::ServerConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +47,8 @@ pub struct RateLimitConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
+    // Empty means one is generated on first start and kept in the secrets
+    // store, so tokens stay valid across restarts
     pub jwt_secret: String,
     pub token_expiry: u64,
     pub refresh_token_expiry: u64,
@@ -94,7 +105,7 @@ pub enum WebhookEvent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct APIKey {
     pub key_id: String,
-    pub key_value: String,
+    pub key_hash: String, // the key itself is only returned when it's created
     pub name: String,
     pub description: String,
     pub user_id: String,
@@ -106,40 +117,40 @@ pub struct APIKey {
     pub is_active: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum APIScope {
     // Marketplace scopes
     MarketplaceRead,
     MarketplaceWrite,
     MarketplaceAdmin,
-    
+
     // Analytics scopes
     AnalyticsRead,
     AnalyticsWrite,
-    
+
     // User scopes
     UserRead,
     UserWrite,
-    
+
     // CI/CD scopes
     CICDRead,
     CICDWrite,
     CICDExecute,
-    
+
     // Collaboration scopes
     CollaborationRead,
     CollaborationWrite,
     CollaborationManage,
-    
+
     // Visualization scopes
     VisualizationRead,
     VisualizationWrite,
-    
+
     // System scopes
     SystemRead,
     SystemWrite,
     SystemAdmin,
-    
+
     // Custom scopes
     Custom(String),
 }
@@ -225,10 +236,24 @@ pub struct IntegrationConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IntegrationAuth {
     None,
-    ApiKey { key: String },
-    Bearer { token: String },
-    Basic { username: String, password: String },
-    OAuth2 { client_id: String, client_secret: String, access_token: String, refresh_token: String },
+    ApiKey {
+        key: String,
+    },
+    Bearer {
+        token: String,
+    },
+    Basic {
+        username: String,
+        password: String,
+    },
+    OAuth2 {
+        client_id: String,
+        client_secret: String,
+        access_token: String,
+        refresh_token: String,
+    },
+    // Signed in with `warp auth login`; tokens come from the secrets manager and are refreshed as needed
+    DeviceCode(crate::config::OAuthProviderConfig),
     Custom(HashMap<String, String>),
 }
 
@@ -252,65 +277,83 @@ pub enum IntegrationStatus {
 
 pub struct MarketplaceAPI {
     config: Arc<Mutex<APIConfig>>,
-    rest_api: Arc<rest_api::RestAPI>,
-    graphql_api: Arc<graphql_api::GraphQLAPI>,
-    webhook_api: Arc<webhook_api::WebhookAPI>,
     auth_middleware: Arc<auth_middleware::AuthMiddleware>,
-    rate_limiting: Arc<rate_limiting::RateLimiter>,
-    api_documentation: Arc<api_documentation::APIDocumentation>,
-    sdk_generator: Arc<sdk_generator::SDKGenerator>,
     integration_manager: Arc<integration_manager::IntegrationManager>,
     api_keys: Arc<Mutex<HashMap<String, APIKey>>>,
     integrations: Arc<Mutex<HashMap<String, Integration>>>,
     metrics: Arc<Mutex<APIMetrics>>,
+    // Key creation and revocation are recorded here when the audit log is on
+    audit: Option<Arc<AuditLog>>,
+    // Serves GET /snapshots/<token> once mounted
+    snapshots: Mutex<Option<Arc<ShareManager>>>,
 }
 
 impl MarketplaceAPI {
-    pub async fn new() -> Result<Self, WarpError> {
-        let config = Arc::new(Mutex::new(APIConfig::default()));
-        
+    pub async fn new(config: APIConfig, audit: Option<Arc<AuditLog>>) -> Result<Self, WarpError> {
+        let config = Arc::new(Mutex::new(config));
+
         Ok(Self {
             config: config.clone(),
-            rest_api: Arc::new(rest_api::RestAPI::new(config.clone()).await?),
-            graphql_api: Arc::new(graphql_api::GraphQLAPI::new(config.clone()).await?),
-            webhook_api: Arc::new(webhook_api::WebhookAPI::new(config.clone()).await?),
-            auth_middleware: Arc::new(auth_middleware::AuthMiddleware::new(config.clone()).await?),
-            rate_limiting: Arc::new(rate_limiting::RateLimiter::new(config.clone()).await?),
-            api_documentation: Arc::new(api_documentation::APIDocumentation::new().await?),
-            sdk_generator: Arc::new(sdk_generator::SDKGenerator::new().await?),
+            auth_middleware: Arc::new(auth_middleware::AuthMiddleware::new(config).await?),
             integration_manager: Arc::new(integration_manager::IntegrationManager::new().await?),
             api_keys: Arc::new(Mutex::new(HashMap::new())),
             integrations: Arc::new(Mutex::new(HashMap::new())),
             metrics: Arc::new(Mutex::new(APIMetrics::default())),
+            audit,
+            snapshots: Mutex::new(None),
         })
     }
 
-    pub async fn start_server(&self, port: u16) -> Result<(), WarpError> {
-        // Start REST API server
-        let rest_server = self.rest_api.start_server(port).await?;
-        
-        // Start GraphQL server
-        let graphql_server = self.graphql_api.start_server(port + 1).await?;
-        
-        // Start webhook server
-        let webhook_server = self.webhook_api.start_server(port + 2).await?;
-        
+    async fn audit(&self, action: AuditAction) {
+        let Some(audit) = &self.audit else {
+            return;
+        };
+        if let Err(e) = audit.record(action).await {
+            log::warn!("Failed to write the audit log: {}", e);
+        }
+    }
+
+    // Binds the listener and starts the background work; server::serve
+    // takes the listener from here
+    pub async fn start_server(&self, port: u16) -> Result<tls::SecureListener, WarpError> {
+        let server_config = self.config.lock().await.server.clone();
+        let listener = tls::SecureListener::bind(&server_config, port).await?;
+
         // Start metrics collection
         self.start_metrics_collection().await?;
-        
-        // Wait for all servers
-        tokio::try_join!(rest_server, graphql_server, webhook_server)?;
-        
-        Ok(())
+
+        // Sync integrations on their own schedules
+        self.integration_manager
+            .clone()
+            .start_scheduler(self.integrations.clone());
+
+        // Forget revoked tokens once they would have expired anyway
+        let auth = self.auth_middleware.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
+            loop {
+                interval.tick().await;
+                auth.prune().await;
+            }
+        });
+
+        Ok(listener)
     }
 
-    pub async fn create_api_key(&self, user_id: &str, name: &str, scopes: Vec<APIScope>, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Result<APIKey, WarpError> {
+    // Returns the key along with its value, which isn't retrievable afterwards
+    pub async fn create_api_key(
+        &self,
+        user_id: &str,
+        name: &str,
+        scopes: Vec<APIScope>,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<(APIKey, String), WarpError> {
         let key_id = uuid::Uuid::new_v4().to_string();
         let key_value = self.generate_api_key().await?;
-        
+
         let api_key = APIKey {
             key_id: key_id.clone(),
-            key_value: key_value.clone(),
+            key_hash: auth_middleware::hash_token(&key_value),
             name: name.to_string(),
             description: String::new(),
             user_id: user_id.to_string(),
@@ -324,23 +367,216 @@ impl MarketplaceAPI {
 
         let mut api_keys = self.api_keys.lock().await;
         api_keys.insert(key_id.clone(), api_key.clone());
-
-        Ok(api_key)
+        drop(api_keys);
+
+        self.audit(AuditAction::ApiKeyCreated {
+            key_id,
+            name: api_key.name.clone(),
+            scopes: api_key
+                .scopes
+                .iter()
+                .map(|scope| format!("{:?}", scope))
+                .collect(),
+        })
+        .await;
+        Ok((api_key, key_value))
     }
 
     pub async fn revoke_api_key(&self, key_id: &str) -> Result<(), WarpError> {
         let mut api_keys = self.api_keys.lock().await;
         if let Some(api_key) = api_keys.get_mut(key_id) {
             api_key.is_active = false;
+            drop(api_keys);
+            self.audit(AuditAction::ApiKeyRevoked {
+                key_id: key_id.to_string(),
+            })
+            .await;
             Ok(())
         } else {
-            Err(WarpError::ConfigError("API key not found".to_string()))
+            Err(WarpError::Auth("API key not found".to_string()))
+        }
+    }
+
+    pub async fn issue_tokens(
+        &self,
+        user_id: &str,
+        scopes: Vec<APIScope>,
+    ) -> Result<auth_middleware::TokenPair, WarpError> {
+        self.auth_middleware.issue_tokens(user_id, scopes).await
+    }
+
+    pub async fn refresh_tokens(
+        &self,
+        refresh_token: &str,
+    ) -> Result<auth_middleware::TokenPair, WarpError> {
+        self.auth_middleware.refresh(refresh_token).await
+    }
+
+    pub async fn revoke_tokens(
+        &self,
+        access_token: &str,
+        refresh_token: Option<&str>,
+    ) -> Result<(), WarpError> {
+        self.auth_middleware
+            .revoke_access_token(access_token)
+            .await?;
+        if let Some(refresh_token) = refresh_token {
+            self.auth_middleware
+                .revoke_refresh_token(refresh_token)
+                .await;
+        }
+        Ok(())
+    }
+
+    // A request carrying an X-API-Key header is checked against the stored
+    // keys; anything else goes to the bearer token middleware
+    pub async fn authorize(
+        &self,
+        request: &APIRequest,
+    ) -> Result<auth_middleware::AuthContext, WarpError> {
+        let Some(key) = header(request, "x-api-key") else {
+            return self.auth_middleware.authorize(request).await;
+        };
+        if !self.config.lock().await.authentication.api_key_enabled {
+            return Err(WarpError::Auth("API keys are disabled".to_string()));
+        }
+        auth_middleware::check_path(&request.path)?;
+        let context = self.api_key_context(key).await?;
+        if !auth_middleware::is_public(&request.path) {
+            self.auth_middleware
+                .check_scopes(&request.method, &request.path, &context.scopes)?;
+        }
+        Ok(context)
+    }
+
+    async fn api_key_context(&self, key: &str) -> Result<auth_middleware::AuthContext, WarpError> {
+        let hash = auth_middleware::hash_token(key);
+        let now = chrono::Utc::now();
+        let mut api_keys = self.api_keys.lock().await;
+        let api_key = api_keys
+            .values_mut()
+            .find(|api_key| api_key.key_hash == hash)
+            .filter(|api_key| {
+                api_key.is_active && api_key.expires_at.is_none_or(|expires| now < expires)
+            })
+            .ok_or_else(|| WarpError::Auth("Invalid API key".to_string()))?;
+        api_key.last_used = Some(now);
+        Ok(auth_middleware::AuthContext {
+            user_id: api_key.user_id.clone(),
+            token_id: api_key.key_id.clone(),
+            scopes: api_key.scopes.clone(),
+        })
+    }
+
+    // Every request the server takes comes through here: it's authorized
+    // first, then dispatched on the same path that was checked
+    pub async fn handle(&self, request: APIRequest) -> APIResponse {
+        let started = std::time::Instant::now();
+        let result = match self.authorize(&request).await {
+            Ok(context) => {
+                // Snapshot pages are HTML with their own headers, so they're
+                // passed through as the share manager answers them
+                if let Some(response) = self.serve_snapshot(&request).await {
+                    self.record(&request, response.status_code, started.elapsed())
+                        .await;
+                    return response;
+                }
+                self.dispatch(&request, &context).await
+            }
+            Err(e) => Err(e),
+        };
+        let (status_code, body) = match result {
+            Ok(Some(body)) => (200, body),
+            Ok(None) => (404, serde_json::json!({ "error": "Not found" })),
+            Err(e) => (
+                status_for(&e),
+                serde_json::json!({ "error": e.to_string() }),
+            ),
+        };
+        self.record(&request, status_code, started.elapsed()).await;
+        APIResponse {
+            request_id: request.request_id,
+            status_code,
+            headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+            body: Some(body),
+            processing_time: started.elapsed(),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    // Snapshot tokens are the credential, so the route is public to
+    // authorize(); it still runs first to refuse non-canonical paths
+    pub async fn mount_snapshots(&self, shares: Arc<ShareManager>) {
+        *self.snapshots.lock().await = Some(shares);
+    }
+
+    async fn serve_snapshot(&self, request: &APIRequest) -> Option<APIResponse> {
+        let shares = self.snapshots.lock().await.clone()?;
+        shares.serve(request).await
+    }
+
+    async fn dispatch(
+        &self,
+        request: &APIRequest,
+        context: &auth_middleware::AuthContext,
+    ) -> Result<Option<serde_json::Value>, WarpError> {
+        let body = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/health") => serde_json::json!({ "status": "ok" }),
+            // Trades an API key for a token pair with the key's scopes
+            ("POST", "/auth/token") => {
+                if context.user_id.is_empty() {
+                    return Err(WarpError::Auth("An API key is required".to_string()));
+                }
+                serde_json::to_value(
+                    self.issue_tokens(&context.user_id, context.scopes.clone())
+                        .await?,
+                )?
+            }
+            ("POST", "/auth/refresh") => {
+                let refresh_token = request
+                    .body
+                    .as_ref()
+                    .and_then(|body| body.get("refresh_token"))
+                    .and_then(serde_json::Value::as_str)
+                    .ok_or_else(|| {
+                        WarpError::Validation("refresh_token is required".to_string())
+                    })?;
+                serde_json::to_value(self.refresh_tokens(refresh_token).await?)?
+            }
+            ("GET", "/system/metrics") => serde_json::to_value(self.get_metrics().await?)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(body))
+    }
+
+    async fn record(&self, request: &APIRequest, status_code: u16, elapsed: std::time::Duration) {
+        let mut metrics = self.metrics.lock().await;
+        let previous = metrics.total_requests as f64;
+        metrics.total_requests += 1;
+        if status_code < 400 {
+            metrics.successful_requests += 1;
+        } else {
+            metrics.failed_requests += 1;
+            *metrics.error_rates.entry(status_code).or_insert(0) += 1;
         }
+        metrics.average_response_time = (metrics.average_response_time * previous
+            + elapsed.as_secs_f64() * 1000.0)
+            / metrics.total_requests as f64;
+        *metrics
+            .requests_per_endpoint
+            .entry(endpoint(&request.path).to_string())
+            .or_insert(0) += 1;
     }
 
-    pub async fn create_integration(&self, user_id: &str, name: &str, integration_type: IntegrationType, config: IntegrationConfig) -> Result<String, WarpError> {
+    pub async fn create_integration(
+        &self,
+        user_id: &str,
+        name: &str,
+        integration_type: IntegrationType,
+        config: IntegrationConfig,
+    ) -> Result<String, WarpError> {
         let integration_id = uuid::Uuid::new_v4().to_string();
-        
+
         let integration = Integration {
             integration_id: integration_id.clone(),
             name: name.to_string(),
@@ -361,7 +597,9 @@ impl MarketplaceAPI {
         integrations.insert(integration_id.clone(), integration);
 
         // Register integration with manager
-        self.integration_manager.register_integration(&integration_id).await?;
+        self.integration_manager
+            .register_integration(&integration_id)
+            .await?;
 
         Ok(integration_id)
     }
@@ -371,47 +609,24 @@ impl MarketplaceAPI {
         if let Some(integration) = integrations.get(integration_id) {
             self.integration_manager.test_integration(integration).await
         } else {
-            Err(WarpError::ConfigError("Integration not found".to_string()))
-        }
-    }
-
-    pub async fn sync_integration(&self, integration_id: &str) -> Result<(), WarpError> {
-        let mut integrations = self.integrations.lock().await;
-        if let Some(integration) = integrations.get_mut(integration_id) {
-            integration.status = IntegrationStatus::Syncing;
-            integration.last_sync = Some(chrono::Utc::now());
-            
-            match self.integration_manager.sync_integration(integration).await {
-                Ok(_) => {
-                    integration.status = IntegrationStatus::Active;
-                    integration.error_count = 0;
-                    integration.last_error = None;
-                }
-                Err(e) => {
-                    integration.status = IntegrationStatus::Error;
-                    integration.error_count += 1;
-                    integration.last_error = Some(e.to_string());
-                    return Err(e);
-                }
-            }
+            Err(WarpError::not_found("integration", integration_id))
         }
-        Ok(())
-    }
-
-    pub async fn generate_sdk(&self, language: &str, version: &str) -> Result<Vec<u8>, WarpError> {
-        self.sdk_generator.generate_sdk(language, version).await
     }
 
-    pub async fn get_api_documentation(&self, format: &str) -> Result<String, WarpError> {
-        self.api_documentation.generate_documentation(format).await
+    pub async fn sync_integration(
+        &self,
+        integration_id: &str,
+    ) -> Result<integration_manager::SyncReport, WarpError> {
+        self.integration_manager
+            .run_sync(&self.integrations, integration_id)
+            .await
     }
 
-    pub async fn register_webhook(&self, user_id: &str, url: &str, events: Vec<WebhookEvent>, secret: Option<String>) -> Result<String, WarpError> {
-        self.webhook_api.register_webhook(user_id, url, events, secret).await
-    }
-
-    pub async fn send_webhook(&self, webhook_id: &str, event: WebhookEvent, payload: serde_json::Value) -> Result<(), WarpError> {
-        self.webhook_api.send_webhook(webhook_id, event, payload).await
+    pub async fn integration_health(
+        &self,
+        integration_id: &str,
+    ) -> Option<integration_manager::SyncHealth> {
+        self.integration_manager.health(integration_id).await
     }
 
     pub async fn get_metrics(&self) -> Result<APIMetrics, WarpError> {
@@ -420,44 +635,94 @@ impl MarketplaceAPI {
     }
 
     async fn generate_api_key(&self) -> Result<String, WarpError> {
-        use rand::Rng;
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
-        const KEY_LEN: usize = 64;
-        
-        let mut rng = rand::thread_rng();
-        let key: String = (0..KEY_LEN)
-            .map(|_| {
-                let idx = rng.gen_range(0..CHARSET.len());
-                CHARSET[idx] as char
-            })
-            .collect();
-        
-        Ok(format!("warp_{}", key))
+        use base64::Engine;
+        use ring::rand::{SecureRandom, SystemRandom};
+
+        let mut bytes = [0u8; 48];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| WarpError::Config("Could not generate an API key".to_string()))?;
+        Ok(format!(
+            "warp_{}",
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+        ))
     }
 
     async fn start_metrics_collection(&self) -> Result<(), WarpError> {
         let metrics = self.metrics.clone();
-        
+
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
-            
+
             loop {
                 interval.tick().await;
-                
+
                 // Update metrics
                 let mut metrics_guard = metrics.lock().await;
                 metrics_guard.last_updated = chrono::Utc::now();
                 // Additional metrics collection logic would go here
             }
         });
-        
+
         Ok(())
     }
 }
 
+// The configured JWT secret, or the one kept in the secrets store, which
+// is generated the first time
+pub async fn jwt_secret(config: &APIConfig, secrets: &SecretsManager) -> Result<String, WarpError> {
+    use base64::Engine;
+    use ring::rand::{SecureRandom, SystemRandom};
+
+    if !config.authentication.jwt_secret.is_empty() {
+        return Ok(config.authentication.jwt_secret.clone());
+    }
+    if let Some(secret) = secrets.get(JWT_SECRET_NAME).await? {
+        return Ok(secret);
+    }
+    let mut bytes = [0u8; 48];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| WarpError::Config("Could not generate a JWT secret".to_string()))?;
+    let secret = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+    secrets.set(JWT_SECRET_NAME, &secret).await?;
+    Ok(secret)
+}
+
+fn header<'a>(request: &'a APIRequest, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+// Snapshot tokens and unknown paths aren't kept as keys of their own
+fn endpoint(path: &str) -> &str {
+    if let Some(route) = ROUTES.iter().find(|route| **route == path) {
+        return route;
+    }
+    match path.strip_prefix(SNAPSHOT_ROUTE) {
+        Some(rest) if rest.starts_with('/') => SNAPSHOT_ROUTE,
+        _ => "other",
+    }
+}
+
+fn status_for(error: &WarpError) -> u16 {
+    match error {
+        WarpError::Auth(_) => 401,
+        WarpError::Permission(_) => 403,
+        WarpError::NotFound { .. } => 404,
+        WarpError::Validation(_) | WarpError::Serialization(_) => 400,
+        _ => 500,
+    }
+}
+
 impl Default for APIConfig {
     fn default() -> Self {
         Self {
+            enabled: false,
+            port: 7878,
             base_url: "https://api.warp.dev".to_string(),
             version: "v1".to_string(),
             rate_limits: RateLimitConfig {
@@ -469,8 +734,8 @@ impl Default for APIConfig {
                 blacklist: Vec::new(),
             },
             authentication: AuthConfig {
-                jwt_secret: uuid::Uuid::new_v4().to_string(),
-                token_expiry: 3600,      // 1 hour
+                jwt_secret: String::new(),
+                token_expiry: 3600,           // 1 hour
                 refresh_token_expiry: 604800, // 1 week
                 api_key_enabled: true,
                 oauth_enabled: true,
@@ -478,7 +743,12 @@ impl Default for APIConfig {
             },
             cors_config: CorsConfig {
                 allowed_origins: vec!["*".to_string()],
-                allowed_methods: vec!["GET".to_string(), "POST".to_string(), "PUT".to_string(), "DELETE".to_string()],
+                allowed_methods: vec![
+                    "GET".to_string(),
+                    "POST".to_string(),
+                    "PUT".to_string(),
+                    "DELETE".to_string(),
+                ],
                 allowed_headers: vec!["Content-Type".to_string(), "Authorization".to_string()],
                 expose_headers: Vec::new(),
                 max_age: 3600,
@@ -503,6 +773,7 @@ impl Default for APIConfig {
                     WebhookEvent::AnalyticsUpdate,
                 ],
             },
+            server: tls::ServerConfig::default(),
         }
     }
 }
@@ -522,3 +793,183 @@ impl Default for APIMetrics {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn api(dir: &std::path::Path) -> MarketplaceAPI {
+        let mut config = APIConfig::default();
+        config.authentication.jwt_secret = "0123456789abcdef0123456789abcdef".to_string();
+        let audit = Arc::new(
+            AuditLog::open(&dir.join("audit.jsonl"), "session")
+                .await
+                .unwrap(),
+        );
+        MarketplaceAPI::new(config, Some(audit)).await.unwrap()
+    }
+
+    fn request(method: &str, path: &str, headers: &[(&str, &str)]) -> APIRequest {
+        APIRequest {
+            request_id: "r".to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            query_params: HashMap::new(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: None,
+            user_id: None,
+            api_key_id: None,
+            timestamp: chrono::Utc::now(),
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: "test".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_changes_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let api = api(dir.path()).await;
+
+        let (key, value) = api
+            .create_api_key("alice", "ci", vec![APIScope::CICDExecute], None)
+            .await
+            .unwrap();
+        assert!(value.starts_with("warp_") && value.len() == 69);
+        assert!(!key.key_hash.contains(&value));
+        api.revoke_api_key(&key.key_id).await.unwrap();
+        assert!(api.revoke_api_key("missing").await.is_err());
+
+        let records = AuditLog::read(&path).await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(
+            matches!(&records[0].action, AuditAction::ApiKeyCreated { key_id, .. } if *key_id == key.key_id)
+        );
+        assert!(
+            matches!(&records[1].action, AuditAction::ApiKeyRevoked { key_id } if *key_id == key.key_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_are_checked_and_can_be_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = api(dir.path()).await;
+        let (key, value) = api
+            .create_api_key("alice", "reader", vec![APIScope::SystemRead], None)
+            .await
+            .unwrap();
+
+        let response = api
+            .handle(request("GET", "/system/metrics", &[("X-API-Key", &value)]))
+            .await;
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            api.handle(request(
+                "GET",
+                "/system/metrics",
+                &[("X-API-Key", "warp_wrong")]
+            ))
+            .await
+            .status_code,
+            401
+        );
+        assert_eq!(
+            api.handle(request("POST", "/system/metrics", &[("X-API-Key", &value)]))
+                .await
+                .status_code,
+            403
+        );
+        assert_eq!(
+            api.handle(request("GET", "/system/metrics", &[]))
+                .await
+                .status_code,
+            401
+        );
+
+        let pair = api
+            .handle(request("POST", "/auth/token", &[("X-API-Key", &value)]))
+            .await;
+        assert_eq!(pair.status_code, 200);
+        assert!(pair.body.unwrap()["access_token"].is_string());
+
+        api.revoke_api_key(&key.key_id).await.unwrap();
+        assert_eq!(
+            api.handle(request("GET", "/system/metrics", &[("X-API-Key", &value)]))
+                .await
+                .status_code,
+            401
+        );
+        let (_, value) = api
+            .create_api_key("alice", "reader", vec![APIScope::SystemRead], None)
+            .await
+            .unwrap();
+        api.config.lock().await.authentication.api_key_enabled = false;
+        assert_eq!(
+            api.handle(request("GET", "/system/metrics", &[("X-API-Key", &value)]))
+                .await
+                .status_code,
+            401
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jwt_secret_is_generated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::open(dir.path()).await.unwrap();
+        let mut config = APIConfig::default();
+        let first = jwt_secret(&config, &secrets).await.unwrap();
+        assert!(first.len() >= 32);
+        assert_eq!(jwt_secret(&config, &secrets).await.unwrap(), first);
+
+        config.authentication.jwt_secret = "configured".to_string();
+        assert_eq!(jwt_secret(&config, &secrets).await.unwrap(), "configured");
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_are_served_once_mounted() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = api(dir.path()).await;
+        let visualization = crate::visualization::VisualizationManager::new()
+            .await
+            .unwrap();
+        let dashboard_id = visualization
+            .create_dashboard("alice", "Deploys", "")
+            .await
+            .unwrap();
+        visualization
+            .set_public(&dashboard_id, "alice", true)
+            .await
+            .unwrap();
+        let (_, token) = visualization
+            .create_snapshot(&dashboard_id, "alice", None)
+            .await
+            .unwrap();
+        let path = format!("{}/{}", SNAPSHOT_ROUTE, token);
+
+        assert_eq!(
+            api.handle(request("GET", &path, &[])).await.status_code,
+            404
+        );
+        api.mount_snapshots(visualization.sharing()).await;
+        let response = api.handle(request("GET", &path, &[])).await;
+        assert_eq!(response.status_code, 200);
+        assert!(response.headers["Content-Security-Policy"].contains("default-src 'none'"));
+        assert_eq!(
+            api.handle(request(
+                "GET",
+                &format!("{}/../{}", SNAPSHOT_ROUTE, token),
+                &[]
+            ))
+            .await
+            .status_code,
+            400
+        );
+        assert_eq!(
+            api.get_metrics().await.unwrap().requests_per_endpoint[SNAPSHOT_ROUTE],
+            3
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use super::*;
+use hyper::body::HttpBody;
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response};
+use percent_encoding::percent_decode_str;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use crate::shutdown::ShutdownSignal;
+
+// Bodies are read before authorization, so they're capped
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+// Request line and headers, also read before authorization
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Serves HTTP/1.1 on the listener until shutdown. Each connection gets its
+// own task, which does the TLS handshake and then hands every request to
+// MarketplaceAPI::handle.
+pub async fn serve(
+    listener: tls::SecureListener,
+    api: Arc<MarketplaceAPI>,
+    mut signal: ShutdownSignal,
+) {
+    loop {
+        let incoming = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(incoming) => incoming,
+                Err(e) => {
+                    log::warn!("API accept failed: {}", e);
+                    continue;
+                }
+            },
+            _ = signal.triggered() => break,
+        };
+        let api = api.clone();
+        tokio::spawn(async move {
+            let addr = incoming.addr();
+            let (stream, peer) = match incoming.handshake().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    log::debug!("{}", e);
+                    return;
+                }
+            };
+            let service = service_fn(move |request| {
+                let api = api.clone();
+                let peer = peer.clone();
+                async move { Ok::<_, Infallible>(respond(&api, &peer, request).await) }
+            });
+            let served = hyper::server::conn::Http::new()
+                .http1_only(true)
+                .http1_header_read_timeout(HEADER_TIMEOUT)
+                .max_buf_size(MAX_HEADER_BYTES)
+                .serve_connection(stream, service)
+                .await;
+            if let Err(e) = served {
+                log::debug!("API connection from {} closed: {}", addr, e);
+            }
+        });
+    }
+}
+
+async fn respond(api: &MarketplaceAPI, peer: &tls::Peer, request: Request<Body>) -> Response<Body> {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let response = match api_request(request_id.clone(), peer, request).await {
+        Ok(request) => api.handle(request).await,
+        Err(e) => APIResponse {
+            request_id,
+            status_code: 400,
+            headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+            body: Some(serde_json::json!({ "error": e.to_string() })),
+            processing_time: Duration::ZERO,
+            timestamp: chrono::Utc::now(),
+        },
+    };
+    http_response(response)
+}
+
+async fn api_request(
+    request_id: String,
+    peer: &tls::Peer,
+    request: Request<Body>,
+) -> Result<APIRequest, WarpError> {
+    let (parts, body) = request.into_parts();
+    let headers: HashMap<String, String> = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect();
+    let body = read_body(body).await?;
+    let body = if body.is_empty() {
+        None
+    } else {
+        Some(serde_json::from_slice(&body)?)
+    };
+    Ok(APIRequest {
+        request_id,
+        method: parts.method.as_str().to_string(),
+        // Left encoded: authorize() refuses anything that isn't canonical
+        path: parts.uri.path().to_string(),
+        query_params: parts.uri.query().map(query_params).unwrap_or_default(),
+        user_agent: headers.get("user-agent").cloned().unwrap_or_default(),
+        headers,
+        body,
+        user_id: None,
+        api_key_id: None,
+        timestamp: chrono::Utc::now(),
+        ip_address: peer.addr.ip().to_string(),
+    })
+}
+
+async fn read_body(mut body: Body) -> Result<Vec<u8>, WarpError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk
+            .map_err(|e| WarpError::Validation(format!("Couldn't read the request body: {}", e)))?;
+        if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(WarpError::Validation(format!(
+                "Request body exceeds {} bytes",
+                MAX_BODY_BYTES
+            )));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+fn query_params(query: &str) -> HashMap<String, String> {
+    let decode = |text: &str| {
+        percent_decode_str(&text.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+// A string body is sent as is, like a snapshot's HTML; anything else as JSON
+fn http_response(response: APIResponse) -> Response<Body> {
+    let body = match response.body {
+        Some(serde_json::Value::String(text)) => Body::from(text),
+        Some(value) => Body::from(serde_json::to_vec(&value).unwrap_or_default()),
+        None => Body::empty(),
+    };
+    let mut builder = Response::builder().status(response.status_code);
+    for (name, value) in &response.headers {
+        builder = builder.header(name, value);
+    }
+    builder.body(body).unwrap_or_else(|_| {
+        let mut fallback = Response::new(Body::empty());
+        *fallback.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
+        fallback
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_query_params_are_decoded() {
+        let params = query_params("q=a%20b+c&flag&x=%E2%9C%94");
+        assert_eq!(params["q"], "a b c");
+        assert_eq!(params["flag"], "");
+        assert_eq!(params["x"], "✔");
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_authorization() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = APIConfig::default();
+        config.authentication.jwt_secret = "0123456789abcdef0123456789abcdef".to_string();
+        let audit = Arc::new(
+            AuditLog::open(&dir.path().join("audit.jsonl"), "session")
+                .await
+                .unwrap(),
+        );
+        let api = Arc::new(MarketplaceAPI::new(config, Some(audit)).await.unwrap());
+        let listener = tls::SecureListener::bind(&tls::ServerConfig::default(), 0)
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = crate::shutdown::ShutdownController::new(Duration::from_secs(1));
+        tokio::spawn(serve(listener, api, shutdown.subscribe()));
+
+        let get = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                path
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        assert!(get("/health").await.starts_with("HTTP/1.1 200"));
+        assert!(get("/system/metrics").await.starts_with("HTTP/1.1 401"));
+        assert!(get("/health/../system/metrics")
+            .await
+            .starts_with("HTTP/1.1 400"));
+    }
+}
@@ -0,0 +1,484 @@
+use super::*;
+use std::io::BufReader;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient,
+};
+use tokio_rustls::rustls::{self, Certificate, PrivateKey, RootCertStore};
+use tokio_rustls::TlsAcceptor;
+
+// A client that opens a connection and never finishes the handshake
+// is dropped rather than kept waiting
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Where the embedded servers listen and how connections are secured
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub bind_address: IpAddr, // loopback unless allow_remote is set
+    pub allow_remote: bool,
+    pub tls: TlsConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::from([127, 0, 0, 1]),
+            allow_remote: false,
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    Off,
+    SelfSigned, // generated once and kept in cert_dir
+    Provided,   // cert_path and key_path
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub mode: TlsMode,
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    pub cert_dir: PathBuf,               // self-signed certificate and key
+    pub client_ca_path: Option<PathBuf>, // enables mutual TLS
+    pub require_client_cert: bool,       // otherwise a client cert is optional
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            mode: TlsMode::Off,
+            cert_path: None,
+            key_path: None,
+            cert_dir: dirs::config_dir().unwrap_or_default().join("warp/api-tls"),
+            client_ca_path: None,
+            require_client_cert: true,
+        }
+    }
+}
+
+impl ServerConfig {
+    // Listening beyond loopback is opt-in, and never in plain text
+    pub fn validate(&self) -> Result<(), WarpError> {
+        if !self.bind_address.is_loopback() {
+            if !self.allow_remote {
+                return Err(WarpError::Config(format!(
+                    "Refusing to listen on {} without server.allow_remote",
+                    self.bind_address
+                )));
+            }
+            if self.tls.mode == TlsMode::Off {
+                return Err(WarpError::Config(format!(
+                    "Listening on {} requires TLS; set server.tls.mode",
+                    self.bind_address
+                )));
+            }
+        }
+        if self.tls.mode == TlsMode::Provided
+            && (self.tls.cert_path.is_none() || self.tls.key_path.is_none())
+        {
+            return Err(WarpError::Config(
+                "server.tls.mode = provided needs cert_path and key_path".to_string(),
+            ));
+        }
+        if self.tls.client_ca_path.is_some() && self.tls.mode == TlsMode::Off {
+            return Err(WarpError::Config(
+                "server.tls.client_ca_path needs TLS enabled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// The peer of an accepted connection. client_certificate is the DER of
+// the verified leaf when the client presented one over mutual TLS.
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    pub client_certificate: Option<Vec<u8>>,
+}
+
+impl Peer {
+    // SHA-256 of the client certificate, which is what callers are
+    // identified by in logs and API key bindings
+    pub fn fingerprint(&self) -> Option<String> {
+        self.client_certificate.as_deref().map(fingerprint)
+    }
+}
+
+pub enum ServerStream {
+    Plain(TcpStream),
+    Tls(Box<tokio_rustls::server::TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for ServerStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ServerStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+pub struct SecureListener {
+    listener: TcpListener,
+    acceptor: Option<TlsAcceptor>,
+}
+
+impl SecureListener {
+    pub async fn bind(config: &ServerConfig, port: u16) -> Result<Self, WarpError> {
+        config.validate()?;
+        let acceptor = acceptor(&config.tls).await?;
+        let addr = SocketAddr::new(config.bind_address, port);
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| WarpError::Config(format!("Cannot listen on {}: {}", addr, e)))?;
+        log::info!(
+            "API listening on {}{}",
+            addr,
+            if acceptor.is_some() { " (TLS)" } else { "" }
+        );
+        Ok(Self { listener, acceptor })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, WarpError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.acceptor.is_some()
+    }
+
+    // Returns as soon as TCP accepts; the handshake is left to the
+    // connection's own task so a slow client can't hold up the others
+    pub async fn accept(&self) -> Result<Incoming, WarpError> {
+        let (stream, addr) = self.listener.accept().await?;
+        Ok(Incoming {
+            stream,
+            addr,
+            acceptor: self.acceptor.clone(),
+        })
+    }
+}
+
+// A connection that hasn't been through the TLS handshake yet
+pub struct Incoming {
+    stream: TcpStream,
+    addr: SocketAddr,
+    acceptor: Option<TlsAcceptor>,
+}
+
+impl Incoming {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // A failed handshake is an error for that connection only
+    pub async fn handshake(self) -> Result<(ServerStream, Peer), WarpError> {
+        let Incoming {
+            stream,
+            addr,
+            acceptor,
+        } = self;
+        let Some(acceptor) = acceptor else {
+            return Ok((
+                ServerStream::Plain(stream),
+                Peer {
+                    addr,
+                    client_certificate: None,
+                },
+            ));
+        };
+        let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+            .await
+            .map_err(|_| WarpError::Validation(format!("TLS handshake with {} timed out", addr)))?
+            .map_err(|e| {
+                WarpError::Validation(format!("TLS handshake with {} failed: {}", addr, e))
+            })?;
+        let client_certificate = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|chain| chain.first())
+            .map(|certificate| certificate.0.clone());
+        Ok((
+            ServerStream::Tls(Box::new(stream)),
+            Peer {
+                addr,
+                client_certificate,
+            },
+        ))
+    }
+}
+
+async fn acceptor(config: &TlsConfig) -> Result<Option<TlsAcceptor>, WarpError> {
+    let (certs, key) = match config.mode {
+        TlsMode::Off => return Ok(None),
+        TlsMode::Provided => {
+            let (Some(cert_path), Some(key_path)) = (&config.cert_path, &config.key_path) else {
+                return Err(WarpError::Config(
+                    "TLS needs cert_path and key_path".to_string(),
+                ));
+            };
+            (load_certs(cert_path)?, load_key(key_path)?)
+        }
+        TlsMode::SelfSigned => {
+            let (cert_path, key_path) = self_signed(&config.cert_dir).await?;
+            (load_certs(&cert_path)?, load_key(&key_path)?)
+        }
+    };
+
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let server = match &config.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in load_certs(ca_path)? {
+                roots.add(&certificate).map_err(|e| {
+                    WarpError::Config(format!("Bad client CA in {}: {}", ca_path.display(), e))
+                })?;
+            }
+            let verifier = if config.require_client_cert {
+                AllowAnyAuthenticatedClient::new(roots).boxed()
+            } else {
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()
+            };
+            builder
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certs, key)
+        }
+        None => builder.with_no_client_auth().with_single_cert(certs, key),
+    }
+    .map_err(|e| WarpError::Config(format!("Invalid TLS certificate or key: {}", e)))?;
+    Ok(Some(TlsAcceptor::from(Arc::new(server))))
+}
+
+// Generates a certificate for localhost on first use and keeps it, so
+// clients that pinned its fingerprint keep working across restarts
+pub async fn self_signed(dir: &Path) -> Result<(PathBuf, PathBuf), WarpError> {
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path));
+    }
+    let names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    let generated = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| WarpError::Config(format!("Cannot generate a TLS certificate: {}", e)))?;
+    let cert_pem = generated
+        .serialize_pem()
+        .map_err(|e| WarpError::Config(format!("Cannot generate a TLS certificate: {}", e)))?;
+    write_private(&key_path, generated.serialize_private_key_pem().as_bytes()).await?;
+    crate::shutdown::write_atomic(&cert_path, cert_pem.as_bytes()).await?;
+    if let Some(certificate) = load_certs(&cert_path)?.first() {
+        log::info!(
+            "Generated a self-signed API certificate, SHA-256 {}",
+            fingerprint(&certificate.0)
+        );
+    }
+    Ok((cert_path, key_path))
+}
+
+// Like shutdown::write_atomic, but the temporary file is created 0600 so
+// the key is never readable by others, not even briefly
+async fn write_private(path: &Path, contents: &[u8]) -> Result<(), WarpError> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp_path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+pub fn load_certs(path: &Path) -> Result<Vec<Certificate>, WarpError> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| WarpError::Config(format!("Cannot read {}: {}", path.display(), e)))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| WarpError::Config(format!("Bad certificate in {}: {}", path.display(), e)))?;
+    if certs.is_empty() {
+        return Err(WarpError::Config(format!(
+            "No certificates found in {}",
+            path.display()
+        )));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+pub fn load_key(path: &Path) -> Result<PrivateKey, WarpError> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| WarpError::Config(format!("Cannot read {}: {}", path.display(), e)))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| WarpError::Config(format!("Bad private key in {}: {}", path.display(), e)))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| WarpError::Config(format!("No private key found in {}", path.display())))
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, der);
+    digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_remote_listening_needs_opt_in_and_tls() {
+        let mut config = ServerConfig::default();
+        assert!(config.validate().is_ok());
+
+        config.bind_address = IpAddr::from([0, 0, 0, 0]);
+        assert!(config.validate().is_err());
+        config.allow_remote = true;
+        assert!(config.validate().is_err());
+        config.tls.mode = TlsMode::SelfSigned;
+        assert!(config.validate().is_ok());
+
+        config.tls.mode = TlsMode::Provided;
+        assert!(config.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_self_signed_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = self_signed(dir.path()).await.unwrap();
+        let first = load_certs(&cert_path).unwrap();
+        assert!(load_key(&key_path).is_ok());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                std::fs::metadata(&key_path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+
+        self_signed(dir.path()).await.unwrap();
+        assert_eq!(load_certs(&cert_path).unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_requires_client_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = rcgen::generate_simple_self_signed(vec!["client".to_string()]).unwrap();
+        let client_der = client.serialize_der().unwrap();
+        let ca_path = dir.path().join("clients.pem");
+        std::fs::write(&ca_path, client.serialize_pem().unwrap()).unwrap();
+
+        let mut config = ServerConfig::default();
+        config.tls.mode = TlsMode::SelfSigned;
+        config.tls.cert_dir = dir.path().join("server");
+        config.tls.client_ca_path = Some(ca_path);
+        let listener = SecureListener::bind(&config, 0).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_cert = load_certs(&config.tls.cert_dir.join("cert.pem")).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(&server_cert[0]).unwrap();
+        let connect = |identity: Option<(Vec<Certificate>, PrivateKey)>| {
+            let roots = roots.clone();
+            async move {
+                let builder = rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots);
+                let config = match identity {
+                    Some((certs, key)) => builder.with_client_auth_cert(certs, key).unwrap(),
+                    None => builder.with_no_client_auth(),
+                };
+                let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let name = rustls::ServerName::try_from("localhost").unwrap();
+                let mut stream = connector.connect(name, stream).await?;
+                stream.write_all(b"ping").await?;
+                stream.flush().await
+            }
+        };
+
+        let anonymous = tokio::spawn(connect(None));
+        assert!(listener.accept().await.unwrap().handshake().await.is_err());
+        let _ = anonymous.await;
+
+        let identity = (
+            vec![Certificate(client_der.clone())],
+            PrivateKey(client.serialize_private_key_der()),
+        );
+        let authenticated = tokio::spawn(connect(Some(identity)));
+        let (mut stream, peer) = listener.accept().await.unwrap().handshake().await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        assert_eq!(peer.fingerprint(), Some(fingerprint(&client_der)));
+        authenticated.await.unwrap().unwrap();
+    }
+}
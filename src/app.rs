@@ -1,236 +1,6418 @@
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
-    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    event::{
+        EnableBracketedPaste, EnableFocusChange, Event, EventStream, KeyCode, KeyEvent,
+        KeyModifiers,
+    },
+    terminal::{self, EnterAlternateScreen},
     ExecutableCommand,
 };
-use std::io::{self, stdout};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex, OnceCell};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
 
 use crate::{
+    accessibility::AccessibilityManager,
+    activity::ActivityMonitor,
+    ai::completion::CompletionType,
     ai::AIAssistant,
     ai::{AdvancedAI, CompletionContext, CompletionItem, ContextualSuggestion},
-    completion::CompletionEngine,
-    config::Config,
+    alerts::{Alert, AlertBus, AlertRouter},
+    analytics::delivery::Reporter,
+    analytics::privacy::{PrivacyManager, TelemetryCommand, TelemetryPanel},
+    api::{self, APIConfig, MarketplaceAPI},
+    audit::{self, AuditAction, AuditLog},
+    bell::{self, BellDetector, BellPolicy},
+    block_tags::{format_tags, parse_tags, BlockRef, Mark, PinnedCommand, PinnedPanel},
+    cloud::{CloudCommand, CloudDetector, CloudGuard, CloudPanel},
+    collaboration::CollaborationManager,
+    command_not_found,
+    completion::{
+        lsp::{self, Document, LanguageServers, LspEvent, LspRequest},
+        CompletionEngine,
+    },
+    completion_import,
+    compliance::ComplianceLogger,
+    config::{
+        AlertSource, AlertsConfig, CloudConfig, CompletionImportConfig, Config, CorrectionConfig,
+        DockerConfig, EditorConfig, EscapeFilterConfig, ForgeConfig, IpcConfig, LimitAction,
+        MemoryConfig, NotebookConfig, NotificationCategory, NotificationLevel, OutputConfig,
+        PolicyAction, ProblemsConfig, ReplayConfig, RunbookConfig, SSHConfig, SearchConfig,
+        StartupConfig, StatusAction, StatusBarConfig, StatusProvider, StatusWidget, TelemetryMode,
+        TrustLevel, WASMConfig, WatchdogConfig,
+    },
+    connections::{self, ConnectionCommand, ConnectionsPanel},
+    container, correction,
+    dev_tools::builder::{self, BuildConfig},
+    dev_tools::console::{ConsoleCommand, DevConsole},
+    dev_tools::debugger::panel::{DebugCommand, DebugPanel},
+    dev_tools::hot_reload::{HotReloadManager, ReloadEvent},
+    dev_tools::testing::{
+        self,
+        panel::{TestCommand, TestPanel},
+    },
+    dev_tools::trace::{self, BlockTracer, TraceCommand},
+    dev_tools::{CoverageData, DevToolsManager, TestResult, TestSuite},
+    diff_view::{self, DiffSide, DiffSource, DiffView},
+    download::DownloadManager,
+    editor,
     error::WarpError,
+    escape_filter::{self, EscapeFilter},
+    export::{
+        terminal::TerminalSource, DataSource, ExportDestination, ExportFormat, ExportManager,
+        ExportRequest, ExportStatus,
+    },
+    file_manager::{FileCommand, FileManager},
+    forge::{
+        detect_remote,
+        panel::{ForgeCommand, ForgePanel},
+        ForgeClient, PullRequest,
+    },
+    git::{
+        panel::{GitCommand, GitPanel},
+        GitManager, GitProgress, PullOutcome,
+    },
     history::HistoryManager,
+    i18n,
+    ipc::{IpcContext, IpcServer},
+    lint::CommandLinter,
+    locations::{LocationCommand, LocationsPanel},
+    marketplace::{ui::MarketplaceUI, Marketplace},
+    memory::{self, MemoryCommand, MemoryPanel, MemoryTracker},
+    ml_insights::{panel::InsightsPanel, MLInsightsEngine},
     multiplexer::SessionMultiplexer,
+    network_inspector::NetworkInspector,
+    notebook::{self, Notebook, NotebookCommand, NotebookPanel},
+    notifications::{self, NotificationCenter, NotificationCommand, NotificationsPanel},
+    output_buffer::TruncationLimits,
+    package::Manifest,
+    pager::parse_ansi,
+    pane_tuning::{PaneLimits, PaneTuning, PaneTuningPanel, ProjectTunings, TuningCommand},
+    paste::{self, PasteCommand, PasteGuard, PastePanel},
+    permalinks,
+    plugin_activity::{ActivityCommand, PluginActivityPanel},
+    plugin_host::PluginHost,
+    plugin_permissions::{PermissionCommand, Permissions, PermissionsPanel},
     plugins::PluginManager,
+    ports::{self, PortAction, PortWatcher},
+    problems::{ProblemCommand, ProblemParser, ProblemsPanel},
+    process_tree::{self, format_bytes, process_cwd, ProcessSignal, ProcessTreeMonitor},
+    project_tasks::{self, TaskCache, TaskCommand, TasksPanel},
     pty::PtyManager,
-    search::SearchEngine,
-    shell::ShellManager,
-    terminal::Terminal,
-    ui::{UIEvent, UI},
+    replay::{self, RecordingHeader, Replay, ReplayCommand, ReplayPanel, SessionRecorder},
+    runbook::{self, RunbookCommand, RunbookPanel, RunbookRun},
+    search::{BlockRecord, SearchCommand, SearchEngine, SearchPanel, SearchQuery},
+    secrets::SecretsManager,
+    security::SecurityManager,
+    settings::{self, Change, SettingsCommand, SettingsPanel},
+    shell::{NewTabCommand, NewTabPanel, PaneRequest, ShellManager},
+    shutdown::{
+        self, SessionState, ShutdownController, ShutdownReason, ShutdownSignal, ShutdownStage,
+    },
+    ssh::{
+        broadcast::{self, BroadcastCommand, BroadcastPanel},
+        SshEvent, SshSessionManager,
+    },
+    startup::{LazyService, StartupProfile},
+    status_bar::{self, Segment, StatusBar, StatusCommand, StatusPanel},
+    structured::{self, StructuredCommand, StructuredPanel},
+    transforms::{TransformCommand, TransformsPanel},
+    ui::{AppAction, PanelItem, PanelKind, PanelView, PromptHint, UIEvent, UI},
+    visualization::VisualizationManager,
+    watchdog::{self, Violation, Watchdog, WatchedCommand},
+    widgets::{self, History, LogStream},
+    workspace_trust::{self, ProjectFiles, Trust, TrustCommand, TrustPanel, TrustStore},
 };
 
+// Wakes the loop without input so paced frames, timers and background
+// updates reach the screen even when the user isn't typing
+const TICK_INTERVAL: Duration = Duration::from_millis(33);
+// Upper bound on queued events handled before a frame is drawn
+const MAX_EVENTS_PER_FRAME: usize = 256;
+// How long a background task or flush hook gets to finish once shutdown starts
+const SHUTDOWN_TASK_TIMEOUT: Duration = Duration::from_secs(2);
+const PROCESS_TREE_REFRESH: Duration = Duration::from_secs(1);
+const PORTS_REFRESH: Duration = Duration::from_secs(2);
+// Characters of a tailed log line shown in the status bar
+const STATUS_LOG_WIDTH: usize = 48;
+// A plugin's status segment is skipped for this round if it takes longer
+const STATUS_PLUGIN_TIMEOUT: Duration = Duration::from_millis(250);
+const GIT_REFRESH: Duration = Duration::from_secs(2);
+// gcloud and az switch by rewriting their config files, so those are re-read
+const CLOUD_REFRESH: Duration = Duration::from_secs(5);
+const FILES_SYNC: Duration = Duration::from_secs(1);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+// Most records a crash can cost the compliance log beyond its batch
+const COMPLIANCE_SYNC: Duration = Duration::from_secs(1);
+const HOT_RELOAD_POLL: Duration = Duration::from_millis(500);
+const PLUGIN_ACTIVITY_REFRESH: Duration = Duration::from_secs(1);
+// Adapter events (a breakpoint hit, output) show up this quickly
+const DEBUGGER_REFRESH: Duration = Duration::from_millis(500);
+// CPU and memory samples for the insights engine, kept for a day
+const INSIGHTS_SAMPLE: Duration = Duration::from_secs(60);
+const INSIGHTS_KEEP_HOURS: i64 = 24;
+// Samples this recent are compared with the ones before them for anomalies
+const INSIGHTS_WINDOW_HOURS: i64 = 1;
+const KILL_SWITCH_CHECK: Duration = Duration::from_millis(250);
+// Longest wait on the active pane before the background panes are read
+const BACKGROUND_POLL: Duration = Duration::from_millis(100);
+const ACTIVITY_TICK: Duration = Duration::from_secs(1);
+// A command with no processes after this long has finished (or was a builtin)
+const WATCHDOG_GRACE: Duration = Duration::from_secs(2);
+// Commands running at least this long get a notification when they finish
+const LONG_COMMAND: Duration = Duration::from_secs(10);
+
 pub struct WarpApp {
     config: Arc<Mutex<Config>>,
-    terminal: Arc<Mutex<Terminal>>,
     ui: Arc<Mutex<UI>>,
-    ai_assistant: Arc<AIAssistant>,
+    ai_assistant: Arc<LazyService<AIAssistant>>,
     plugin_manager: Arc<PluginManager>,
     pty_manager: Arc<Mutex<PtyManager>>,
+    compliance: Option<Arc<ComplianceLogger>>,
     shell_manager: Arc<Mutex<ShellManager>>,
     history_manager: Arc<Mutex<HistoryManager>>,
     completion_engine: Arc<CompletionEngine>,
-    search_engine: Arc<SearchEngine>,
-    session_multiplexer: Arc<Mutex<SessionMultiplexer>>,
+    search_engine: Arc<LazyService<SearchEngine>>,
+    search_config: SearchConfig,
+    search_panel: Arc<Mutex<Option<SearchPanel>>>,
+    // Identifies this run's blocks in the search index and its audit records
+    session_id: String,
+    // None when the audit log is turned off or couldn't be opened
+    audit: Option<Arc<AuditLog>>,
+    // The block still running, indexed once the next command starts
+    search_block: Arc<Mutex<Option<BlockRecord>>>,
+    pinned_panel: Arc<Mutex<Option<PinnedPanel>>>,
+    // The block the tag prompt is for
+    tagging: Arc<Mutex<Option<BlockRef>>>,
+    replay_config: ReplayConfig,
+    recorder: Arc<Mutex<Option<SessionRecorder>>>,
+    replay_panel: Arc<Mutex<Option<ReplayPanel>>>,
+    session_multiplexer: LazyService<Mutex<SessionMultiplexer>>,
     event_sender: mpsc::UnboundedSender<UIEvent>,
     event_receiver: Arc<Mutex<mpsc::UnboundedReceiver<UIEvent>>>,
-    advanced_ai: Arc<AdvancedAI>,
+    advanced_ai: LazyService<AdvancedAI>,
+    shutdown: Arc<ShutdownController>,
+    process_tree: Arc<Mutex<ProcessTreeMonitor>>,
+    port_watcher: Arc<Mutex<PortWatcher>>,
+    // Present while the git panel is open
+    git_panel: Arc<Mutex<Option<GitPanel>>>,
+    forge_panel: Arc<Mutex<Option<ForgePanel>>>,
+    file_manager: Arc<Mutex<Option<FileManager>>>,
+    locations: Arc<Mutex<Option<LocationsPanel>>>,
+    problems: Arc<Mutex<Option<ProblemsPanel>>>,
+    problem_parser: Arc<ProblemParser>,
+    tasks: Arc<Mutex<Option<TasksPanel>>>,
+    // Project tasks for the shell's directory, shared with completions
+    task_cache: Mutex<TaskCache>,
+    settings: Arc<Mutex<Option<SettingsPanel>>>,
+    // Where the settings panel writes changes
+    config_path: PathBuf,
+    notifications: Arc<Mutex<NotificationCenter>>,
+    notifications_panel: Arc<Mutex<Option<NotificationsPanel>>>,
+    memory_config: MemoryConfig,
+    memory: Arc<Mutex<MemoryTracker>>,
+    memory_panel: Arc<Mutex<Option<MemoryPanel>>>,
+    status_bar_config: StatusBarConfig,
+    alerts: AlertBus,
+    alerts_config: AlertsConfig,
+    notebook: Arc<Mutex<Option<NotebookPanel>>>,
+    notebook_config: NotebookConfig,
+    runbooks: Arc<Mutex<Option<RunbookPanel>>>,
+    runbook_config: RunbookConfig,
+    docker_config: DockerConfig,
+    cloud: Arc<Mutex<CloudDetector>>,
+    cloud_panel: Arc<Mutex<Option<CloudPanel>>>,
+    cloud_config: CloudConfig,
+    // Shared sessions; their participant strips go to the header
+    collaboration: Arc<CollaborationManager>,
+    // None when the watchdog is disabled
+    watchdog: Option<Arc<Watchdog>>,
+    // The command the watchdog is following, until its processes exit
+    watched: Arc<Mutex<Option<WatchedCommand>>>,
+    forge_config: ForgeConfig,
+    security: Arc<SecurityManager>,
+    install_suggestions: bool,
+    paste_guard: PasteGuard,
+    paste_panel: Mutex<Option<PastePanel>>,
+    new_tab: Mutex<Option<NewTabPanel>>,
+    structured: Mutex<Option<StructuredPanel>>,
+    privacy: Arc<PrivacyManager>,
+    telemetry: Mutex<Option<TelemetryPanel>>,
+    // None when WebAssembly plugins are disabled
+    plugin_host: Option<Arc<PluginHost>>,
+    wasm_config: WASMConfig,
+    hot_reload: Arc<HotReloadManager>,
+    dev_console: Arc<Mutex<DevConsole>>,
+    // Debugs and tests the plugin package in the shell's directory
+    dev_tools: Arc<LazyService<DevToolsManager>>,
+    // Kept while the session lasts, so closing the panel doesn't detach
+    debug_panel: Arc<Mutex<Option<DebugPanel>>>,
+    // Kept with the suite they ran, by package id, for re-runs
+    test_panel: Arc<Mutex<Option<TestPanel>>>,
+    test_suite: Mutex<Option<(String, TestSuite)>>,
+    // Rollbacks reload plugins through plugin_host
+    marketplace: Arc<LazyService<Marketplace>>,
+    // Takes the whole screen and every key while open
+    marketplace_ui: Mutex<Option<MarketplaceUI>>,
+    // Fed the terminal's CPU and memory samples; the insights panel shows
+    // its anomalies and forecasts
+    ml_insights: Arc<MLInsightsEngine>,
+    block_tracer: Arc<std::sync::Mutex<BlockTracer>>,
+    permissions_panel: Arc<Mutex<Option<PermissionsPanel>>>,
+    // Trusted folders, and the last project root the prompt was shown for
+    workspace_trust: Mutex<TrustStore>,
+    workspace_root: Mutex<Option<PathBuf>>,
+    trust_panel: Mutex<Option<TrustPanel>>,
+    // Scrollback, capture limits and frame rate: the config's, overrides
+    // per pane id for the session, and tunings saved per project
+    pane_limits: Mutex<PaneLimits>,
+    pane_tuning: Mutex<HashMap<usize, PaneTuning>>,
+    project_tunings: Mutex<ProjectTunings>,
+    tuning_panel: Mutex<Option<PaneTuningPanel>>,
+    ssh_config: SSHConfig,
+    // Connection tabs whose ssh is supervised (reconnects, gap recovery),
+    // and the pane each session's output goes to
+    ssh_sessions: Arc<SshSessionManager>,
+    ssh_panes: Arc<Mutex<HashMap<String, usize>>>,
+    ssh_events: Mutex<Option<mpsc::UnboundedReceiver<SshEvent>>>,
+    broadcast_panel: Arc<Mutex<Option<BroadcastPanel>>>,
+    connections_panel: Mutex<Option<ConnectionsPanel>>,
+    // The app can't read the clipboard, only what gets pasted into it
+    last_paste: Mutex<Option<String>>,
+    transforms_panel: Mutex<Option<TransformsPanel>>,
+    status_panel: Mutex<Option<StatusPanel>>,
+    // `warp connect <name>`, opened once the UI is up
+    initial_connection: Option<String>,
+    plugin_activity: Arc<Mutex<Option<PluginActivityPanel>>>,
+    // When the event loop last finished a frame, for the plugin kill switch
+    heartbeat: Arc<std::sync::Mutex<Instant>>,
+    // One per pane, since each is its own stream. Trust starts from the pane
+    // kind and follows the running command; see escape_filter::pane_trust
+    escape_filters: Mutex<HashMap<usize, EscapeFilter>>,
+    escapes: EscapeFilterConfig,
+    correction: CorrectionConfig,
+    ipc_config: IpcConfig,
+    api_config: APIConfig,
+    editor_config: EditorConfig,
+    completion_import: CompletionImportConfig,
+    // Set once a language server is enabled
+    script_servers: std::sync::Mutex<Option<mpsc::UnboundedSender<LspRequest>>>,
+    // The last command sent from the prompt, so a typo in it can be fixed
+    last_command: Mutex<Option<String>>,
+    bell_detector: Mutex<BellDetector>,
+    bell_policy: Mutex<BellPolicy>,
+    activity: Arc<Mutex<ActivityMonitor>>,
+    // From the host terminal's focus reports; focused until one says otherwise
+    focused: AtomicBool,
+    // Opened on first use so the key file is only created when needed
+    secrets: OnceCell<Arc<SecretsManager>>,
+    startup: StartupConfig,
+    profile: Arc<StartupProfile>,
+    // Which side panel is open, so refresh tasks know whether to push updates
+    open_panel: Arc<std::sync::Mutex<Option<PanelKind>>>,
+    network_inspector: Option<Arc<NetworkInspector>>,
+    network_block: Mutex<Option<String>>,
 }
 
 impl WarpApp {
     pub async fn new(config: Arc<Mutex<Config>>) -> Result<Self, WarpError> {
+        Self::with_profile(config, Arc::new(StartupProfile::new())).await
+    }
+
+    // Only what the first frame needs is built here; the AI, search and
+    // multiplexer subsystems start on first use. Init times go to `profile`.
+    pub async fn with_profile(
+        config: Arc<Mutex<Config>>,
+        profile: Arc<StartupProfile>,
+    ) -> Result<Self, WarpError> {
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
 
-        let terminal = Arc::new(Mutex::new(Terminal::new().await?));
+        // Folded in before anything reads the config, so reduced motion and
+        // the minimum font size apply everywhere
+        let accessibility_config = config.lock().await.accessibility.clone();
+        let accessibility = Arc::new(AccessibilityManager::new(accessibility_config).await?);
+        accessibility.apply_to_config(&mut *config.lock().await);
+        // Sections that are read once at startup
+        let loaded = config.lock().await.clone();
+
         let ui = Arc::new(Mutex::new(
-            UI::new(config.clone(), event_sender.clone()).await?,
+            profile
+                .measure("ui", UI::new(config.clone(), event_sender.clone()))
+                .await?,
+        ));
+        ui.lock().await.set_accessibility(accessibility);
+        let plugin_manager = Arc::new(
+            profile
+                .measure("plugins", PluginManager::new(config.clone()))
+                .await?,
+        );
+        let pty_manager = Arc::new(Mutex::new(profile.measure("pty", PtyManager::new()).await?));
+        let compliance_config = config.lock().await.compliance.clone();
+        let compliance = match compliance_config.enabled {
+            true => Some(Arc::new(ComplianceLogger::new(compliance_config).await?)),
+            false => None,
+        };
+        if let Some(logger) = &compliance {
+            pty_manager
+                .lock()
+                .await
+                .set_compliance_logger(logger.clone());
+        }
+        let shell_manager = Arc::new(Mutex::new(
+            profile
+                .measure("shell", ShellManager::new(config.clone()))
+                .await?,
+        ));
+        let history_manager = Arc::new(Mutex::new(
+            profile
+                .measure("history", HistoryManager::new(config.clone()))
+                .await?,
         ));
-        let ai_assistant = Arc::new(AIAssistant::new(config.clone()).await?);
-        let plugin_manager = Arc::new(PluginManager::new(config.clone()).await?);
-        let pty_manager = Arc::new(Mutex::new(PtyManager::new().await?));
-        let shell_manager = Arc::new(Mutex::new(ShellManager::new(config.clone()).await?));
-        let history_manager = Arc::new(Mutex::new(HistoryManager::new(config.clone()).await?));
-        let completion_engine = Arc::new(CompletionEngine::new(config.clone()).await?);
-        let search_engine = Arc::new(SearchEngine::new().await?);
-        let session_multiplexer = Arc::new(Mutex::new(SessionMultiplexer::new().await?));
+        let completion_engine = Arc::new(
+            profile
+                .measure("completion", CompletionEngine::new(config.clone()))
+                .await?,
+        );
+        if config.lock().await.terminal.command_lint {
+            ui.lock()
+                .await
+                .set_linter(CommandLinter::new(completion_engine.specs()));
+        }
+        ui.lock()
+            .await
+            .set_output_truncation(Self::truncation_limits(&loaded.output));
+        let cloud_config = loaded.cloud.clone();
+        ui.lock()
+            .await
+            .set_cloud_guard(Self::cloud_guard(&cloud_config)?);
+        let (ssh_sender, ssh_events) = mpsc::unbounded_channel();
+        let ssh_sessions =
+            Arc::new(SshSessionManager::new(config.lock().await.ssh.clone(), ssh_sender).await?);
 
-        let advanced_ai = Arc::new(AdvancedAI::new().await?);
+        let ai_config = config.clone();
+        let ai_assistant = Arc::new(
+            LazyService::new("ai-assistant", profile.clone(), move || {
+                AIAssistant::new(ai_config.clone())
+            })
+            .with_progress(event_sender.clone()),
+        );
+        let advanced_ai = LazyService::new("advanced-ai", profile.clone(), AdvancedAI::new)
+            .with_progress(event_sender.clone());
+        let search_engine = Arc::new(LazyService::new(
+            "search",
+            profile.clone(),
+            SearchEngine::new,
+        ));
+        let dev_tools_config = loaded.clone();
+        let dev_tools = Arc::new(
+            LazyService::new("dev-tools", profile.clone(), move || {
+                let config = dev_tools_config.clone();
+                async move { DevToolsManager::new(&config).await }
+            })
+            .with_progress(event_sender.clone()),
+        );
+        let session_multiplexer = LazyService::new("multiplexer", profile.clone(), || async {
+            SessionMultiplexer::new().await.map(Mutex::new)
+        });
+        let security_config = loaded.security.clone();
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let audit = if security_config.audit_log {
+            match Self::open_audit_log(&session_id).await {
+                Ok(audit_log) => Some(Arc::new(audit_log)),
+                Err(e) => {
+                    log::warn!("Failed to open the audit log: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let network_inspector = match loaded.network_inspector.enabled {
+            true => Some(Arc::new(
+                NetworkInspector::new(loaded.network_inspector.clone()).await?,
+            )),
+            false => None,
+        };
+        let memory_config = loaded.memory.clone();
+        let wasm_config = loaded.wasm.clone();
+        let plugin_host = Self::plugin_host(&wasm_config)?;
+        let marketplace_config = loaded.clone();
+        let marketplace_host = plugin_host.clone();
+        let marketplace = Arc::new(
+            LazyService::new("marketplace", profile.clone(), move || {
+                let config = marketplace_config.clone();
+                let host = marketplace_host.clone();
+                async move {
+                    let downloads = Arc::new(DownloadManager::new(&config.downloads)?);
+                    let marketplace = Marketplace::new(
+                        downloads,
+                        &config.plugins,
+                        &config.oauth,
+                        config.general.update_channel,
+                    )
+                    .await?;
+                    Ok(match host {
+                        Some(host) => marketplace.with_plugin_host(host),
+                        None => marketplace,
+                    })
+                }
+            })
+            .with_progress(event_sender.clone()),
+        );
 
         Ok(Self {
             config,
-            terminal,
             ui,
             ai_assistant,
             plugin_manager,
             pty_manager,
+            compliance,
             shell_manager,
             history_manager,
             completion_engine,
             search_engine,
+            search_config: loaded.search.clone(),
+            search_panel: Arc::new(Mutex::new(None)),
+            session_id,
+            audit,
+            search_block: Arc::new(Mutex::new(None)),
+            pinned_panel: Arc::new(Mutex::new(None)),
+            tagging: Arc::new(Mutex::new(None)),
+            replay_config: loaded.replay.clone(),
+            recorder: Arc::new(Mutex::new(None)),
+            replay_panel: Arc::new(Mutex::new(None)),
             session_multiplexer,
             event_sender,
             event_receiver: Arc::new(Mutex::new(event_receiver)),
             advanced_ai,
+            shutdown: Arc::new(ShutdownController::new(SHUTDOWN_TASK_TIMEOUT)),
+            process_tree: Arc::new(Mutex::new(ProcessTreeMonitor::new())),
+            port_watcher: Arc::new(Mutex::new(PortWatcher::new())),
+            git_panel: Arc::new(Mutex::new(None)),
+            forge_panel: Arc::new(Mutex::new(None)),
+            file_manager: Arc::new(Mutex::new(None)),
+            locations: Arc::new(Mutex::new(None)),
+            problems: Arc::new(Mutex::new(None)),
+            problem_parser: Arc::new(Self::problem_parser(&loaded.problems)),
+            tasks: Arc::new(Mutex::new(None)),
+            task_cache: Mutex::new(TaskCache::default()),
+            settings: Arc::new(Mutex::new(None)),
+            config_path: settings::default_config_path(),
+            notifications: Arc::new(Mutex::new(NotificationCenter::new(
+                loaded.notifications.clone(),
+            ))),
+            notifications_panel: Arc::new(Mutex::new(None)),
+            memory: Arc::new(Mutex::new(MemoryTracker::new(&memory_config))),
+            memory_config,
+            memory_panel: Arc::new(Mutex::new(None)),
+            status_bar_config: loaded.status_bar.clone(),
+            alerts: AlertBus::new(),
+            alerts_config: loaded.alerts.clone(),
+            notebook: Arc::new(Mutex::new(None)),
+            notebook_config: loaded.notebooks.clone(),
+            runbooks: Arc::new(Mutex::new(None)),
+            runbook_config: loaded.runbooks.clone(),
+            docker_config: loaded.docker.clone(),
+            cloud: Arc::new(Mutex::new(CloudDetector::new())),
+            cloud_panel: Arc::new(Mutex::new(None)),
+            cloud_config,
+            collaboration: Arc::new(CollaborationManager::new(&loaded).await?),
+            watchdog: Self::watchdog(&loaded.watchdog)?,
+            watched: Arc::new(Mutex::new(None)),
+            forge_config: loaded.forge.clone(),
+            security: Arc::new(SecurityManager::from_config(&security_config)?),
+            install_suggestions: security_config.install_suggestions,
+            paste_guard: PasteGuard::from_config(&security_config.paste)?,
+            paste_panel: Mutex::new(None),
+            new_tab: Mutex::new(None),
+            structured: Mutex::new(None),
+            privacy: Arc::new(PrivacyManager::with_mode(loaded.general.telemetry)),
+            telemetry: Mutex::new(None),
+            plugin_host,
+            wasm_config,
+            hot_reload: Arc::new(HotReloadManager::new().await?),
+            dev_console: Arc::new(Mutex::new(DevConsole::new())),
+            dev_tools,
+            debug_panel: Arc::new(Mutex::new(None)),
+            test_panel: Arc::new(Mutex::new(None)),
+            test_suite: Mutex::new(None),
+            marketplace,
+            marketplace_ui: Mutex::new(None),
+            ml_insights: Arc::new(MLInsightsEngine::new().await?),
+            block_tracer: Arc::new(std::sync::Mutex::new(BlockTracer::new(
+                loaded.debug.trace_sample_rate,
+            ))),
+            permissions_panel: Arc::new(Mutex::new(None)),
+            workspace_trust: Mutex::new(TrustStore::default()),
+            workspace_root: Mutex::new(None),
+            trust_panel: Mutex::new(None),
+            pane_limits: Mutex::new(PaneLimits::from_config(&loaded)),
+            pane_tuning: Mutex::new(HashMap::new()),
+            project_tunings: Mutex::new(ProjectTunings::default()),
+            tuning_panel: Mutex::new(None),
+            ssh_config: loaded.ssh.clone(),
+            ssh_sessions,
+            ssh_panes: Arc::new(Mutex::new(HashMap::new())),
+            ssh_events: Mutex::new(Some(ssh_events)),
+            broadcast_panel: Arc::new(Mutex::new(None)),
+            connections_panel: Mutex::new(None),
+            last_paste: Mutex::new(None),
+            transforms_panel: Mutex::new(None),
+            status_panel: Mutex::new(None),
+            initial_connection: None,
+            plugin_activity: Arc::new(Mutex::new(None)),
+            heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+            escape_filters: Mutex::new(HashMap::new()),
+            escapes: security_config.escapes,
+            correction: loaded.correction.clone(),
+            ipc_config: loaded.ipc.clone(),
+            api_config: loaded.api.clone(),
+            editor_config: loaded.editor.clone(),
+            completion_import: loaded.completions.clone(),
+            script_servers: std::sync::Mutex::new(None),
+            last_command: Mutex::new(None),
+            bell_detector: Mutex::new(BellDetector::default()),
+            bell_policy: Mutex::new(BellPolicy::from_config(&loaded.terminal)?),
+            activity: Arc::new(Mutex::new(ActivityMonitor::from_config(
+                &loaded.terminal.activity,
+            )?)),
+            focused: AtomicBool::new(true),
+            secrets: OnceCell::new(),
+            startup: loaded.startup.clone(),
+            profile,
+            open_panel: Arc::new(std::sync::Mutex::new(None)),
+            network_inspector,
+            network_block: Mutex::new(None),
+        })
+    }
+
+    pub async fn run(&self) -> Result<(), WarpError> {
+        // Initialize terminal
+        shutdown::install_panic_hook();
+        terminal::enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        stdout().execute(EnableBracketedPaste)?;
+        stdout().execute(EnableFocusChange)?;
+
+        let signal_listener = self.shutdown.listen_for_signals(self.event_sender.clone());
+
+        // Start background tasks, then run the main event loop
+        let result = match self
+            .profile
+            .measure("background-tasks", self.start_background_tasks())
+            .await
+        {
+            Ok(()) => {
+                self.check_startup_budget();
+                self.offer_telemetry().await;
+                if let Some(name) = &self.initial_connection {
+                    self.connect(name).await;
+                }
+                self.event_loop().await
+            }
+            Err(e) => Err(e),
+        };
+
+        // Cleanup; a no-op trigger if a signal already started shutdown
+        self.shutdown.trigger(if result.is_ok() {
+            ShutdownReason::UserExit
+        } else {
+            ShutdownReason::Error
+        });
+        signal_listener.abort();
+        self.shutdown_gracefully().await;
+        shutdown::restore_terminal();
+
+        result
+    }
+
+    pub fn set_config_path(&mut self, path: PathBuf) {
+        self.config_path = path;
+    }
+
+    // Metric alerts, anomaly detection and CI integrations publish here
+    pub fn alert_bus(&self) -> AlertBus {
+        self.alerts.clone()
+    }
+
+    pub fn privacy_manager(&self) -> Arc<PrivacyManager> {
+        self.privacy.clone()
+    }
+
+    fn plugin_host(config: &WASMConfig) -> Result<Option<Arc<PluginHost>>, WarpError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(PluginHost::new(config)?)))
+    }
+
+    pub fn startup_profile(&self) -> Arc<StartupProfile> {
+        self.profile.clone()
+    }
+
+    fn check_startup_budget(&self) {
+        let total = self.profile.eager_total();
+        let budget = Duration::from_millis(self.startup.budget_ms);
+        let Some(slowest) = self.profile.slowest_eager().filter(|_| total > budget) else {
+            return;
+        };
+        log::warn!(
+            "Startup took {:?}, over the {:?} budget; slowest: {} ({:?})",
+            total,
+            budget,
+            slowest.name,
+            slowest.elapsed
+        );
+        let _ = self.event_sender.send(UIEvent::Notify {
+            category: NotificationCategory::System,
+            level: NotificationLevel::Warning,
+            title: i18n::t_args(
+                "startup.over_budget",
+                &[
+                    ("total", &total.as_millis().to_string()),
+                    ("budget", &budget.as_millis().to_string()),
+                ],
+            ),
+            body: Some(i18n::t_args(
+                "startup.slowest",
+                &[
+                    ("name", slowest.name),
+                    ("elapsed", &slowest.elapsed.as_millis().to_string()),
+                ],
+            )),
+        });
+    }
+
+    // Starts every lazy subsystem now, for --profile-startup
+    pub async fn load_lazy_services(&self) -> Result<(), WarpError> {
+        self.ai_assistant.get().await?;
+        self.advanced_ai.get().await?;
+        self.search_engine.get().await?;
+        self.session_multiplexer.get().await?;
+        Ok(())
+    }
+
+    // A matcher that fails to compile disables only the configured ones
+    fn problem_parser(config: &ProblemsConfig) -> ProblemParser {
+        ProblemParser::new(&config.matchers).unwrap_or_else(|e| {
+            log::warn!("Ignoring configured problem matchers: {}", e);
+            ProblemParser::builtin()
+        })
+    }
+
+    fn watchdog(config: &WatchdogConfig) -> Result<Option<Arc<Watchdog>>, WarpError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(Watchdog::from_config(config)?)))
+    }
+
+    pub async fn set_output_config(&self, config: &OutputConfig) {
+        self.pane_limits.lock().await.output = config.clone();
+        self.apply_pane_tuning().await;
+    }
+
+    pub fn set_initial_connection(&mut self, name: String) {
+        self.initial_connection = Some(name);
+    }
+
+    fn cloud_guard(config: &CloudConfig) -> Result<Option<Arc<CloudGuard>>, WarpError> {
+        Ok(match config.enabled {
+            true => Some(Arc::new(CloudGuard::from_config(config)?)),
+            false => None,
+        })
+    }
+
+    fn truncation_limits(config: &OutputConfig) -> Option<TruncationLimits> {
+        config.truncate.then_some(TruncationLimits {
+            head_lines: config.head_lines,
+            tail_lines: config.tail_lines,
+            max_line_bytes: config.max_line_bytes,
         })
     }
 
-    pub async fn run(&self) -> Result<(), WarpError> {
-        // Initialize terminal
-        terminal::enable_raw_mode()?;
-        stdout().execute(EnterAlternateScreen)?;
-
-        // Start background tasks
-        self.start_background_tasks().await?;
-
-        // Main event loop
-        let result = self.event_loop().await;
-
-        // Cleanup
-        terminal::disable_raw_mode()?;
-        stdout().execute(LeaveAlternateScreen)?;
+    pub async fn secrets(&self) -> Result<Arc<SecretsManager>, WarpError> {
+        self.secrets
+            .get_or_try_init(|| async { SecretsManager::new().await.map(Arc::new) })
+            .await
+            .cloned()
+    }
+
+    pub fn shutdown_controller(&self) -> Arc<ShutdownController> {
+        self.shutdown.clone()
+    }
+
+    // Stops producers before the things they feed: background readers first,
+    // then plugins, then child shells, and only then persists state
+    async fn shutdown_gracefully(&self) {
+        self.shutdown.stop_stage(ShutdownStage::Background).await;
+
+        if let Err(e) = self.plugin_manager.stop().await {
+            log::error!("Failed to stop plugins: {}", e);
+        }
+        self.shutdown.stop_stage(ShutdownStage::Plugins).await;
+
+        let grace = Duration::from_millis(self.config.lock().await.terminal.shutdown_grace_ms);
+        let session = {
+            let mut pty = self.pty_manager.lock().await;
+            let session = SessionState {
+                working_directory: std::env::current_dir().unwrap_or_default(),
+                shells: pty.process_commands(),
+                active_shell: pty.get_active_process_id(),
+                saved_at: chrono::Utc::now(),
+            };
+            match pty.shutdown_all(grace).await {
+                Ok(0) => {}
+                Ok(killed) => log::warn!(
+                    "Killed {} shell(s) that outlived the {:?} grace period",
+                    killed,
+                    grace
+                ),
+                Err(e) => log::error!("Failed to terminate shells: {}", e),
+            }
+            session
+        };
+        self.shutdown.stop_stage(ShutdownStage::Processes).await;
+
+        if let Err(e) = self.history_manager.lock().await.flush().await {
+            log::error!("Failed to flush history: {}", e);
+        }
+        if let Some(indexing) = self.finish_search_block(None).await {
+            let _ = indexing.await;
+        }
+        self.shutdown.run_flush_hooks().await;
+        let saved = match SessionState::default_path() {
+            Ok(path) => session.save(&path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = saved {
+            log::error!("Failed to save session state: {}", e);
+        }
+        self.shutdown.stop_stage(ShutdownStage::Persistence).await;
+    }
+
+    async fn start_background_tasks(&self) -> Result<(), WarpError> {
+        // Pipeline spans land in the sampled block traces
+        trace::install(self.block_tracer.clone());
+
+        // Start PTY monitoring
+        let pty_manager = self.pty_manager.clone();
+        let event_sender = self.event_sender.clone();
+        let signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = Self::pty_monitor_task(pty_manager, event_sender, signal).await {
+                log::error!("PTY monitor task failed: {}", e);
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "pty-monitor", handle)
+            .await;
+
+        // Supervised SSH sessions: output for the tab each one belongs to,
+        // and state changes for the header
+        if let Some(mut events) = self.ssh_events.lock().await.take() {
+            let ssh_panes = self.ssh_panes.clone();
+            let pty_manager = self.pty_manager.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                loop {
+                    let event = tokio::select! {
+                        event = events.recv() => match event {
+                            Some(event) => event,
+                            None => return,
+                        },
+                        _ = signal.triggered() => return,
+                    };
+                    let (session_id, lines) = match event {
+                        SshEvent::StateChanged { session_id, state } => {
+                            if event_sender
+                                .send(UIEvent::SshStateChanged(session_id, state))
+                                .is_err()
+                            {
+                                return;
+                            }
+                            continue;
+                        }
+                        SshEvent::Output { session_id, data } => (session_id, vec![data]),
+                        SshEvent::GapRecovered { session_id, lines } => (session_id, lines),
+                    };
+                    let Some(pane) = ssh_panes.lock().await.get(&session_id).copied() else {
+                        continue;
+                    };
+                    let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+                    let active = pty_manager
+                        .lock()
+                        .await
+                        .get_active_process_id()
+                        .unwrap_or(0);
+                    let event = if pane == active {
+                        UIEvent::PtyOutput(text)
+                    } else {
+                        UIEvent::BackgroundOutput(pane, text)
+                    };
+                    if event_sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "ssh-sessions", handle)
+                .await;
+        }
+
+        // Background tabs that went quiet after being busy
+        let activity = self.activity.clone();
+        let pty_manager = self.pty_manager.clone();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ACTIVITY_TICK);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                let active = pty_manager.lock().await.get_active_process_id();
+                let focus = activity.lock().await.tick(active, Instant::now());
+                if event_sender.send(UIEvent::TabActivity { focus }).is_err() {
+                    return;
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "tab-activity", handle)
+            .await;
+
+        // Recording this session for replay
+        if self.replay_config.enabled {
+            let directory = self.replay_config.directory.clone();
+            // This session's recording counts towards the ones kept
+            let keep = self.replay_config.keep_sessions.saturating_sub(1);
+            if let Err(e) = replay::prune_recordings(&directory, keep).await {
+                log::warn!("Failed to remove old recordings: {}", e);
+            }
+            let header = RecordingHeader {
+                session: self.session_id.clone(),
+                started: chrono::Utc::now(),
+                cwd: std::env::current_dir().ok(),
+            };
+            match SessionRecorder::start(&directory, header, self.shutdown.subscribe()).await {
+                Ok((recorder, handle)) => {
+                    *self.recorder.lock().await = Some(recorder);
+                    self.shutdown
+                        .register_task(ShutdownStage::Background, "replay-recorder", handle)
+                        .await;
+                }
+                Err(e) => log::warn!("Failed to start recording the session: {}", e),
+            }
+        }
+
+        // AI assistant background processing, once something has used it
+        let ai_assistant = self.ai_assistant.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            tokio::select! {
+                _ = async { ai_assistant.initialized().await.start_background_processing().await } => {}
+                _ = signal.triggered() => {}
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "ai-background", handle)
+            .await;
+
+        // Live process tree refresh, only while the panel is open
+        let process_tree = self.process_tree.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PROCESS_TREE_REFRESH);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                if *open_panel.lock().unwrap() != Some(PanelKind::ProcessTree) {
+                    continue;
+                }
+                let view = Self::process_tree_view(&mut *process_tree.lock().await);
+                if event_sender.send(UIEvent::ShowPanel(view)).is_err() {
+                    return;
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "process-tree", handle)
+            .await;
+
+        // The debugger panel follows the adapter while it's open
+        let dev_tools = self.dev_tools.clone();
+        let debug_panel = self.debug_panel.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let dev_tools = tokio::select! {
+                dev_tools = dev_tools.initialized() => dev_tools,
+                _ = signal.triggered() => return,
+            };
+            let mut interval = tokio::time::interval(DEBUGGER_REFRESH);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                if *open_panel.lock().unwrap() != Some(PanelKind::Debugger) {
+                    continue;
+                }
+                match Self::debug_panel_view(&dev_tools, &debug_panel).await {
+                    Ok(Some(view)) => {
+                        if event_sender.send(UIEvent::ShowPanel(view)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::debug!("Debugger refresh failed: {}", e),
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "debugger", handle)
+            .await;
+
+        // CPU and memory as metric series, for anomaly detection and forecasts
+        let features = self.ml_insights.feature_store();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(INSIGHTS_SAMPLE);
+            let mut system = sysinfo::System::new();
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                system.refresh_cpu();
+                system.refresh_memory();
+                let now = chrono::Utc::now();
+                let memory =
+                    system.used_memory() as f64 / system.total_memory().max(1) as f64 * 100.0;
+                features
+                    .record_metric(
+                        "cpu_percent",
+                        now,
+                        system.global_cpu_info().cpu_usage() as f64,
+                    )
+                    .await;
+                features.record_metric("memory_percent", now, memory).await;
+                features
+                    .prune_metrics(chrono::Duration::hours(INSIGHTS_KEEP_HOURS))
+                    .await;
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "insights-sampler", handle)
+            .await;
+
+        // Listening ports: always watched for the status bar, panel updated when open
+        let port_watcher = self.port_watcher.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PORTS_REFRESH);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                let mut watcher = port_watcher.lock().await;
+                let diff = match watcher.refresh().await {
+                    Ok(diff) => diff,
+                    Err(e) => {
+                        log::debug!("Port refresh failed: {}", e);
+                        continue;
+                    }
+                };
+                let mut events = vec![UIEvent::PortStatus(watcher.status_summary())];
+                for port in diff.opened.iter().filter(|p| p.from_session) {
+                    let name = port.process_name.clone().unwrap_or_default();
+                    events.push(UIEvent::Notify {
+                        category: NotificationCategory::System,
+                        level: NotificationLevel::Info,
+                        title: i18n::t_args(
+                            "ports.opened",
+                            &[("port", &port.port().to_string()), ("process", &name)],
+                        ),
+                        body: None,
+                    });
+                }
+                if *open_panel.lock().unwrap() == Some(PanelKind::Ports) {
+                    events.push(UIEvent::ShowPanel(Self::ports_view(&watcher)));
+                }
+                for event in events {
+                    if event_sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "ports", handle)
+            .await;
+
+        // Shared analytics go out through the on-disk queue, only when an
+        // endpoint is configured
+        let delivery = self.config.lock().await.general.telemetry_delivery.clone();
+        if !delivery.endpoints.is_empty() {
+            match Reporter::new(self.privacy.clone(), delivery).await {
+                Ok(reporter) => {
+                    let reporter = Arc::new(reporter);
+                    let handle = tokio::spawn(reporter.clone().run(self.shutdown.subscribe()));
+                    self.shutdown
+                        .register_task(ShutdownStage::Background, "telemetry-delivery", handle)
+                        .await;
+                    let hook: shutdown::FlushHook =
+                        Box::new(move || Box::pin(async move { reporter.spill().await }));
+                    self.shutdown.register_flush_hook("telemetry", hook).await;
+                }
+                Err(e) => log::warn!("Failed to start telemetry delivery: {}", e),
+            }
+        }
+
+        // Status bar widgets, redrawn every sample whether or not anything changed
+        if !self.status_bar_config.widgets.is_empty() {
+            let config = self.status_bar_config.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(Duration::from_secs(config.sample_secs.max(1)));
+                let mut system = sysinfo::System::new();
+                let mut cpu = History::new(config.width);
+                let mut logs: Vec<LogStream> = config
+                    .widgets
+                    .iter()
+                    .filter_map(|widget| match widget {
+                        StatusWidget::LogTail(path) => Some(LogStream::file(path, 1)),
+                        _ => None,
+                    })
+                    .collect();
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    system.refresh_cpu();
+                    system.refresh_memory();
+                    cpu.push(system.global_cpu_info().cpu_usage() as f64);
+                    for log in &mut logs {
+                        if let Err(e) = log.poll().await {
+                            log::debug!(
+                                "Reading {:?} for the status bar failed: {}",
+                                log.path(),
+                                e
+                            );
+                        }
+                    }
+
+                    let mut tails = logs.iter();
+                    let parts: Vec<String> = config
+                        .widgets
+                        .iter()
+                        .filter_map(|widget| match widget {
+                            StatusWidget::CpuSparkline => Some(i18n::t_args(
+                                "status.cpu",
+                                &[(
+                                    "spark",
+                                    &widgets::sparkline(&cpu.values(), config.width, Some(100.0)),
+                                )],
+                            )),
+                            StatusWidget::MemoryGauge => {
+                                let total = system.total_memory();
+                                let used = system.used_memory() as f64 / total.max(1) as f64;
+                                Some(i18n::t_args(
+                                    "status.memory",
+                                    &[("gauge", &widgets::gauge(used, config.width))],
+                                ))
+                            }
+                            StatusWidget::LogTail(_) => tails
+                                .next()
+                                .and_then(LogStream::last_line)
+                                .map(|line| line.chars().take(STATUS_LOG_WIDTH).collect()),
+                        })
+                        .collect();
+                    let text = (!parts.is_empty()).then(|| format!(" {} ", parts.join("  ")));
+                    if event_sender.send(UIEvent::StatusWidgets(text)).is_err() {
+                        return;
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "status-bar", handle)
+                .await;
+        }
+
+        // Status bar segments, each provider refreshed on its own interval
+        let segments = self.status_bar_config.segments.clone();
+        self.ui
+            .lock()
+            .await
+            .set_status_bar(StatusBar::new(&segments));
+        for (id, segment) in segments.into_iter().enumerate() {
+            let pty_manager = self.pty_manager.clone();
+            let plugin_host = self.plugin_host.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(Duration::from_secs(segment.refresh_secs.max(1)));
+                let mut system = sysinfo::System::new();
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    let shown = Self::status_segment(
+                        &segment.provider,
+                        &pty_manager,
+                        plugin_host.as_deref(),
+                        &mut system,
+                    )
+                    .await;
+                    if event_sender
+                        .send(UIEvent::StatusSegment(id, shown))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "status-segment", handle)
+                .await;
+        }
+
+        // Presence changes in shared sessions, as the header's participant strip
+        let mut presence = self.collaboration.subscribe_presence();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            loop {
+                let update = tokio::select! {
+                    received = presence.recv() => match received {
+                        Ok(update) => update,
+                        // Each update is the whole strip, so the next one catches up
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = signal.triggered() => return,
+                };
+                if event_sender
+                    .send(UIEvent::Participants(update.badges))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "presence", handle)
+            .await;
+
+        // Alerts from metrics, anomaly detection and CI, routed per source and level
+        let mut raised = self.alerts.subscribe();
+        let alerts = self.alerts.clone();
+        let mut router = AlertRouter::new(self.alerts_config.clone());
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            loop {
+                let alert = tokio::select! {
+                    received = raised.recv() => match received {
+                        Ok(alert) => alert,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                            log::warn!("Alert routing fell behind; {} alert(s) dropped", missed);
+                            continue;
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = signal.triggered() => return,
+                };
+                let route = router.route(&alert, Instant::now());
+                if route.notify {
+                    let event = UIEvent::Notify {
+                        category: NotificationCategory::Alerts,
+                        level: alert.level,
+                        title: alert.title.clone(),
+                        body: alert.body.clone(),
+                    };
+                    if event_sender.send(event).is_err() {
+                        return;
+                    }
+                }
+                if route.chat {
+                    alerts.forward_to_chat(alert);
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "alerts", handle)
+            .await;
+
+        // Cloud CLI contexts for the header, and the switcher when it is open
+        if self.cloud_config.enabled {
+            let cloud = self.cloud.clone();
+            let cloud_panel = self.cloud_panel.clone();
+            let open_panel = self.open_panel.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(CLOUD_REFRESH);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    let detector = cloud.lock().await;
+                    let mut events = vec![UIEvent::CloudContexts(detector.detect())];
+                    if *open_panel.lock().unwrap() == Some(PanelKind::Cloud) {
+                        if let Some(panel) = cloud_panel.lock().await.as_mut() {
+                            panel.refresh(&detector);
+                            events.push(UIEvent::ShowPanel(panel.view()));
+                        }
+                    }
+                    for event in events {
+                        if event_sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "cloud", handle)
+                .await;
+        }
+
+        // Picks up changes made from the shell while the git panel is open
+        let git_panel = self.git_panel.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(GIT_REFRESH);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                if *open_panel.lock().unwrap() != Some(PanelKind::Git) {
+                    continue;
+                }
+                let mut panel = git_panel.lock().await;
+                let Some(panel) = panel.as_mut() else {
+                    continue;
+                };
+                if let Err(e) = panel.refresh().await {
+                    log::debug!("Git refresh failed: {}", e);
+                    continue;
+                }
+                if event_sender.send(UIEvent::ShowPanel(panel.view())).is_err() {
+                    return;
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "git", handle)
+            .await;
+
+        // Follows the pane's shell into new directories while the file manager is open
+        let file_manager = self.file_manager.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+        let mut signal = self.shutdown.subscribe();
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FILES_SYNC);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = signal.triggered() => return,
+                }
+                if *open_panel.lock().unwrap() != Some(PanelKind::Files) {
+                    continue;
+                }
+                let mut manager = file_manager.lock().await;
+                let Some(manager) = manager.as_mut() else {
+                    continue;
+                };
+                match manager.sync_with_pane().await {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        if event_sender
+                            .send(UIEvent::ShowPanel(manager.view()))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(e) => log::debug!("File manager sync failed: {}", e),
+                }
+            }
+        });
+        self.shutdown
+            .register_task(ShutdownStage::Background, "file-manager", handle)
+            .await;
+
+        // Local control socket for editors and scripts; opt-in, and a bind
+        // failure leaves the terminal usable
+        if self.ipc_config.enabled {
+            match IpcServer::bind(&self.ipc_config).await {
+                Ok(server) => {
+                    log::info!("IPC listening on {}", server.socket_path().display());
+                    let context = Arc::new(IpcContext {
+                        ui: self.ui.clone(),
+                        pty_manager: self.pty_manager.clone(),
+                        event_sender: self.event_sender.clone(),
+                        security: self.security.clone(),
+                        shell_manager: self.shell_manager.clone(),
+                    });
+                    let handle = tokio::spawn(server.serve(context, self.shutdown.subscribe()));
+                    self.shutdown
+                        .register_task(ShutdownStage::Background, "ipc", handle)
+                        .await;
+                }
+                Err(e) => log::error!("IPC disabled: {}", e),
+            }
+        }
+
+        // Embedded HTTP API, opt-in as well
+        if self.api_config.enabled {
+            match self.start_api().await {
+                Ok(handle) => {
+                    self.shutdown
+                        .register_task(ShutdownStage::Background, "api", handle)
+                        .await
+                }
+                Err(e) => log::error!("API disabled: {}", e),
+            }
+        }
+
+        if self.watchdog.is_some() {
+            let watched = self.watched.clone();
+            let pty_manager = self.pty_manager.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(WATCHDOG_INTERVAL);
+                let mut system = sysinfo::System::new();
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    if watched.lock().await.is_none() {
+                        continue;
+                    }
+                    let Some(shell_pid) = pty_manager.lock().await.active_pid().await else {
+                        continue;
+                    };
+                    system.refresh_processes();
+                    let processes = watchdog::command_processes(&system, shell_pid);
+                    for event in Self::watchdog_tick(&watched, &system, &processes).await {
+                        if event_sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "watchdog", handle)
+                .await;
+        }
+
+        // Buffered records are synced on an interval; expired logs go at startup
+        if let Some(logger) = self.compliance.clone() {
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                if let Err(e) = logger.apply_retention().await {
+                    log::error!("Compliance retention failed: {}", e);
+                }
+                let mut interval = tokio::time::interval(COMPLIANCE_SYNC);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    if let Err(e) = logger.flush().await {
+                        log::error!("Failed to sync compliance logs: {}", e);
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "compliance", handle)
+                .await;
+        }
+
+        if self.memory_config.enabled {
+            let memory = self.memory.clone();
+            let memory_panel = self.memory_panel.clone();
+            let ui = self.ui.clone();
+            let history_manager = self.history_manager.clone();
+            let notifications = self.notifications.clone();
+            let open_panel = self.open_panel.clone();
+            let event_sender = self.event_sender.clone();
+            let period = Duration::from_secs(self.memory_config.sample_secs.max(1));
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(period);
+                let mut system = sysinfo::System::new();
+                let pid = sysinfo::get_current_pid().ok();
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    let (scrollback, block) = ui.lock().await.memory_usage();
+                    let history = memory::strings_bytes(history_manager.lock().await.commands());
+                    let notices = notifications.lock().await.memory_bytes();
+                    let mut sizes = vec![
+                        ("scrollback", scrollback),
+                        ("block", block),
+                        ("history", history),
+                        ("notifications", notices),
+                    ];
+                    if let Some(pid) = pid.filter(|pid| system.refresh_process(*pid)) {
+                        sizes.extend(
+                            system
+                                .process(pid)
+                                .map(|process| ("process", process.memory())),
+                        );
+                    }
+
+                    let mut events = Vec::new();
+                    let mut tracker = memory.lock().await;
+                    let now = Instant::now();
+                    for (name, bytes) in sizes {
+                        if let Some(alert) = tracker.record(name, bytes, now) {
+                            events.push(UIEvent::Notify {
+                                category: NotificationCategory::Alerts,
+                                level: NotificationLevel::Warning,
+                                title: i18n::t_args(
+                                    "memory.over_budget",
+                                    &[
+                                        ("name", alert.name),
+                                        ("size", &format_bytes(alert.bytes)),
+                                        ("budget", &format_bytes(alert.budget)),
+                                    ],
+                                ),
+                                body: Some(i18n::t("memory.over_budget_hint")),
+                            });
+                        }
+                    }
+                    if *open_panel.lock().unwrap() == Some(PanelKind::Performance) {
+                        if let Some(panel) = memory_panel.lock().await.as_mut() {
+                            panel.refresh(&tracker);
+                            events.push(UIEvent::ShowPanel(panel.view()));
+                        }
+                    }
+                    drop(tracker);
+                    for event in events {
+                        if event_sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "memory", handle)
+                .await;
+        }
+
+        // Specs for commands without a built-in one, read from the shell
+        // completions installed on the system; probing bash scripts can take
+        // a while, so the terminal is usable before it finishes
+        if self.completion_import.import_system {
+            let specs = self.completion_engine.specs();
+            let probe_bash = self.completion_import.probe_bash;
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let import = tokio::task::spawn_blocking(move || {
+                    completion_import::import_all(&specs, probe_bash)
+                });
+                tokio::select! {
+                    result = import => match result {
+                        Ok(added) => log::info!("Imported {} completion specs", added),
+                        Err(e) => log::warn!("Completion import failed: {}", e),
+                    },
+                    _ = signal.triggered() => {}
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "completion-import", handle)
+                .await;
+        }
+
+        // Language servers for scripts at the prompt and in notebooks; each
+        // starts the first time a script in its language comes up
+        let servers = &self.completion_import.language_servers;
+        if servers.values().any(|server| server.enabled) {
+            let (events, mut received) = mpsc::unbounded_channel();
+            let (requests, receiver) = mpsc::unbounded_channel();
+            let servers = LanguageServers::new(servers.clone(), events);
+            let mut signal = self.shutdown.subscribe();
+            let event_sender = self.event_sender.clone();
+            let handle = tokio::spawn(async move {
+                tokio::select! {
+                    _ = servers.serve(receiver) => {}
+                    _ = async {
+                        while let Some(event) = received.recv().await {
+                            if event_sender.send(UIEvent::Lsp(event)).is_err() {
+                                return;
+                            }
+                        }
+                    } => {}
+                    _ = signal.triggered() => {}
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "language-servers", handle)
+                .await;
+            *self.script_servers.lock().unwrap() = Some(requests);
+            self.ui.lock().await.set_script_servers(true);
+        }
+
+        // Project settings stay off until the folder is trusted; the shell's
+        // starting directory is asked about right away
+        match Self::load_workspace_trust().await {
+            Ok(store) => *self.workspace_trust.lock().await = store,
+            Err(e) => log::warn!("Failed to load trusted folders: {}", e),
+        }
+        self.check_workspace().await;
+
+        match Self::load_project_tunings().await {
+            Ok(tunings) => *self.project_tunings.lock().await = tunings,
+            Err(e) => log::warn!("Failed to load saved pane tunings: {}", e),
+        }
+        self.apply_pane_tuning().await;
+
+        // WebAssembly plugins with the capabilities granted in earlier sessions
+        if let Some(host) = &self.plugin_host {
+            match Self::load_permissions().await {
+                Ok(permissions) => host.set_permissions(permissions),
+                Err(e) => log::warn!("Failed to load plugin permissions: {}", e),
+            }
+            let plugins = self.config.lock().await.plugins.clone();
+            for e in host
+                .load_installed(&plugins.plugin_directory, &plugins.enabled_plugins)
+                .await
+            {
+                log::warn!("{}", e);
+            }
+
+            let host = host.clone();
+            let plugin_activity = self.plugin_activity.clone();
+            let open_panel = self.open_panel.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(PLUGIN_ACTIVITY_REFRESH);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    if *open_panel.lock().unwrap() != Some(PanelKind::PluginActivity) {
+                        continue;
+                    }
+                    let usage = host.usage().await;
+                    let view = match plugin_activity.lock().await.as_mut() {
+                        Some(panel) => {
+                            panel.refresh(usage, host.is_killed());
+                            panel.view()
+                        }
+                        None => continue,
+                    };
+                    if event_sender.send(UIEvent::ShowPanel(view)).is_err() {
+                        return;
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "plugin-activity", handle)
+                .await;
+        }
+
+        // The kill switch: a plain thread, so it still runs when the runtime
+        // is what's stuck, switches every plugin off once a frame is overdue
+        if let Some(host) = self
+            .plugin_host
+            .clone()
+            .filter(|_| self.wasm_config.kill_switch_ms > 0)
+        {
+            let stall = Duration::from_millis(self.wasm_config.kill_switch_ms);
+            let heartbeat = self.heartbeat.clone();
+            *heartbeat.lock().unwrap() = Instant::now();
+            let event_sender = self.event_sender.clone();
+            let signal = self.shutdown.subscribe();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(KILL_SWITCH_CHECK);
+                if signal.is_triggered() {
+                    return;
+                }
+                let stalled = heartbeat.lock().unwrap().elapsed();
+                if stalled < stall || host.is_killed() {
+                    continue;
+                }
+                host.kill_all();
+                log::warn!("No frame for {:?}; switched all plugins off", stalled);
+                let notice = UIEvent::Notify {
+                    category: NotificationCategory::System,
+                    level: NotificationLevel::Error,
+                    title: i18n::t("plugin_activity.killed"),
+                    body: Some(i18n::t("plugin_activity.killed_hint")),
+                };
+                if event_sender.send(notice).is_err() {
+                    return;
+                }
+            });
+        }
+
+        // Plugins under development: rebuilt when their source changes and
+        // reloaded in place when a new module appears
+        if let Some(host) = self
+            .plugin_host
+            .clone()
+            .filter(|_| !self.wasm_config.dev_plugins.is_empty())
+        {
+            let mut console = self.dev_console.lock().await;
+            for dir in &self.wasm_config.dev_plugins {
+                let path = dir.display().to_string();
+                match self.hot_reload.watch(dir).await {
+                    Ok(name) => console.info(
+                        &name,
+                        i18n::t_args("dev_console.watching", &[("dir", &path)]),
+                    ),
+                    Err(e) => {
+                        console.error(&path, i18n::t("dev_console.watch_failed"), &e.to_string())
+                    }
+                }
+            }
+            for (name, wasm) in self.hot_reload.watching().await {
+                if !wasm.exists() {
+                    continue;
+                }
+                match host.load(&name, &wasm).await {
+                    Ok(()) => console.info(&name, i18n::t("dev_console.loaded")),
+                    Err(e) => {
+                        console.error(&name, i18n::t("dev_console.load_failed"), &e.to_string())
+                    }
+                }
+            }
+            drop(console);
+
+            let hot_reload = self.hot_reload.clone();
+            let dev_console = self.dev_console.clone();
+            let build = BuildConfig {
+                command: self.wasm_config.dev_build_command.clone(),
+            };
+            let open_panel = self.open_panel.clone();
+            let event_sender = self.event_sender.clone();
+            let mut signal = self.shutdown.subscribe();
+            let handle = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(HOT_RELOAD_POLL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = signal.triggered() => return,
+                    }
+                    let mut events = Vec::new();
+                    for change in hot_reload.poll().await {
+                        events.extend(
+                            Self::hot_reload_step(change, &host, &build, &dev_console).await,
+                        );
+                    }
+                    let mut console = dev_console.lock().await;
+                    for (name, lines) in host.drain_logs().await {
+                        for line in lines {
+                            console.info(&name, line);
+                        }
+                    }
+                    if *open_panel.lock().unwrap() == Some(PanelKind::DevConsole) {
+                        events.push(UIEvent::ShowPanel(console.view()));
+                    }
+                    drop(console);
+                    for event in events {
+                        if event_sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            self.shutdown
+                .register_task(ShutdownStage::Background, "hot-reload", handle)
+                .await;
+        }
+
+        // Start plugin manager
+        self.plugin_manager.start().await?;
+
+        Ok(())
+    }
+
+    async fn load_permissions() -> Result<Permissions, WarpError> {
+        Permissions::load(&Permissions::default_path()?).await
+    }
+
+    async fn save_permissions(bytes: Result<Vec<u8>, WarpError>) -> Result<(), WarpError> {
+        Permissions::save(&bytes?, &Permissions::default_path()?).await
+    }
+
+    async fn start_api(&self) -> Result<JoinHandle<()>, WarpError> {
+        let mut config = self.api_config.clone();
+        config.authentication.jwt_secret =
+            api::jwt_secret(&config, &*self.secrets().await?).await?;
+        let port = config.port;
+        let api = Arc::new(MarketplaceAPI::new(config, self.audit.clone()).await?);
+        // Dashboards publish their read-only snapshots here
+        api.mount_snapshots(VisualizationManager::new().await?.sharing())
+            .await;
+        let listener = api.start_server(port).await?;
+        Ok(tokio::spawn(api::server::serve(
+            listener,
+            api,
+            self.shutdown.subscribe(),
+        )))
+    }
+
+    async fn open_audit_log(session_id: &str) -> Result<AuditLog, WarpError> {
+        AuditLog::open(&AuditLog::default_path()?, session_id).await
+    }
+
+    async fn audit(&self, action: AuditAction) {
+        let Some(audit_log) = &self.audit else {
+            return;
+        };
+        if let Err(e) = audit_log.record(action).await {
+            log::warn!("Failed to write the audit log: {}", e);
+        }
+    }
+
+    // Commands run with sudo and the like, and ones the command policy
+    // would have asked about or refused had the terminal been running them
+    async fn audit_command(&self, command: &str) {
+        let action = if audit::is_elevated(command) {
+            AuditAction::Sudo {
+                command: command.to_string(),
+            }
+        } else {
+            let decision = self.security.check_command(command);
+            if decision.action == PolicyAction::Allow {
+                return;
+            }
+            AuditAction::PolicyOverride {
+                command: command.to_string(),
+                reason: decision.reason.unwrap_or_default(),
+            }
+        };
+        self.audit(action).await;
+    }
+
+    async fn load_workspace_trust() -> Result<TrustStore, WarpError> {
+        TrustStore::load(&TrustStore::default_path()?).await
+    }
+
+    async fn save_workspace_trust(bytes: Result<Vec<u8>, WarpError>) -> Result<(), WarpError> {
+        TrustStore::save(&bytes?, &TrustStore::default_path()?).await
+    }
+
+    // The project around the shell's directory, if it has settings and
+    // hasn't been trusted or restricted yet
+    async fn untrusted_workspace(&self) -> Option<ProjectFiles> {
+        let cwd = self.shell_cwd().await.ok()?;
+        let files = ProjectFiles::find(&cwd)?;
+        let trust = self.workspace_trust.lock().await.trust_for(&files.root);
+        (trust == Trust::Unknown).then_some(files)
+    }
+
+    // Asks about each new project root the shell enters, once a session.
+    // The prompt opens unless another panel is open; then a notification
+    // points to it instead.
+    async fn check_workspace(&self) {
+        let Some(files) = self.untrusted_workspace().await else {
+            return;
+        };
+        {
+            let mut last = self.workspace_root.lock().await;
+            if last.as_ref() == Some(&files.root) {
+                return;
+            }
+            *last = Some(files.root);
+        }
+        let open = *self.open_panel.lock().unwrap();
+        match open {
+            None | Some(PanelKind::WorkspaceTrust) => {
+                self.refresh_trust_panel().await;
+                *self.open_panel.lock().unwrap() = Some(PanelKind::WorkspaceTrust);
+            }
+            Some(_) => {
+                let _ = self.event_sender.send(UIEvent::Notify {
+                    category: NotificationCategory::Alerts,
+                    level: NotificationLevel::Warning,
+                    title: i18n::t("workspace_trust.waiting"),
+                    body: Some(i18n::t("workspace_trust.waiting_hint")),
+                });
+            }
+        }
+    }
+
+    // Plugins see each command as it's submitted, with the previous command's
+    // output for read_block. Replies are printed under the command.
+    async fn dispatch_to_plugins(&self, command: &str) {
+        let Some(host) = self.plugin_host.clone() else {
+            return;
+        };
+        if host.names().await.is_empty() {
+            return;
+        }
+        host.set_block(self.ui.lock().await.previous_block());
+        let command = command.to_string();
+        let dev_console = self.dev_console.clone();
+        let permissions_panel = self.permissions_panel.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            let asked = host.permissions().lock().unwrap().pending().len();
+            let mut events = Vec::new();
+            for (name, reply) in host.broadcast(&command).await {
+                match reply {
+                    Ok(reply) if !reply.is_empty() => {
+                        events.push(UIEvent::Notice(
+                            reply
+                                .lines()
+                                .map(|line| format!("[{}] {}", name, line))
+                                .collect(),
+                        ));
+                    }
+                    Ok(_) => {}
+                    Err(e) => dev_console.lock().await.error(
+                        &name,
+                        i18n::t("dev_console.call_failed"),
+                        &e.to_string(),
+                    ),
+                }
+            }
+            if host.permissions().lock().unwrap().pending().len() > asked {
+                events.push(Self::permission_prompt(&host, &permissions_panel, &open_panel).await);
+            }
+            for event in events {
+                if event_sender.send(event).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    // Opens the prompt for new capability requests, unless another panel is
+    // open; then a notification points to it instead
+    async fn permission_prompt(
+        host: &PluginHost,
+        permissions_panel: &Mutex<Option<PermissionsPanel>>,
+        open_panel: &std::sync::Mutex<Option<PanelKind>>,
+    ) -> UIEvent {
+        let panel = PermissionsPanel::new(&host.permissions().lock().unwrap());
+        let open = *open_panel.lock().unwrap();
+        match open {
+            None | Some(PanelKind::PluginPermissions) => {
+                let view = panel.view();
+                *permissions_panel.lock().await = Some(panel);
+                *open_panel.lock().unwrap() = Some(PanelKind::PluginPermissions);
+                UIEvent::ShowPanel(view)
+            }
+            Some(_) => UIEvent::Notify {
+                category: NotificationCategory::Alerts,
+                level: NotificationLevel::Warning,
+                title: i18n::t("plugin_permissions.waiting"),
+                body: Some(i18n::t("plugin_permissions.waiting_hint")),
+            },
+        }
+    }
+
+    // Rebuilds or reloads a plugin under development, logging to the dev
+    // console; failures also raise a notification since the console may be
+    // closed
+    async fn hot_reload_step(
+        change: ReloadEvent,
+        host: &PluginHost,
+        build: &BuildConfig,
+        dev_console: &Mutex<DevConsole>,
+    ) -> Option<UIEvent> {
+        let (name, summary, detail) = match change {
+            ReloadEvent::SourceChanged { name, dir } => {
+                match builder::build_plugin(&dir, build).await {
+                    Ok(result) if result.success => {
+                        let millis = result.duration.as_millis().to_string();
+                        dev_console
+                            .lock()
+                            .await
+                            .info(&name, i18n::t_args("dev_console.built", &[("ms", &millis)]));
+                        return None;
+                    }
+                    Ok(result) => (name, i18n::t("dev_console.build_failed"), result.output),
+                    Err(e) => (name, i18n::t("dev_console.build_failed"), e.to_string()),
+                }
+            }
+            ReloadEvent::WasmChanged { name, wasm } => match host.reload(&name, &wasm).await {
+                Ok(outcome) => {
+                    let key = if outcome.state_kept {
+                        "dev_console.reloaded_state"
+                    } else {
+                        "dev_console.reloaded"
+                    };
+                    dev_console.lock().await.info(&name, i18n::t(key));
+                    return None;
+                }
+                Err(e) => (name, i18n::t("dev_console.reload_failed"), e.to_string()),
+            },
+        };
+        dev_console
+            .lock()
+            .await
+            .error(&name, summary.clone(), &detail);
+        Some(UIEvent::Notify {
+            category: NotificationCategory::Alerts,
+            level: NotificationLevel::Error,
+            title: format!("{}: {}", name, summary),
+            body: Some(i18n::t("dev_console.see_console")),
+        })
+    }
+
+    // Checks the watched command against its policy and returns what to
+    // show. Stops watching once the command's processes are gone, with a
+    // notification if it ran long enough to have been waited on.
+    async fn watchdog_tick(
+        watched: &Mutex<Option<WatchedCommand>>,
+        system: &sysinfo::System,
+        processes: &[(u32, u64)],
+    ) -> Vec<UIEvent> {
+        let mut guard = watched.lock().await;
+        let Some(command) = guard.as_mut() else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let runtime = command.runtime(now);
+        if processes.is_empty() && runtime > WATCHDOG_GRACE {
+            let mut events = Vec::new();
+            if let bytes @ 1.. = command.dropped_bytes() {
+                events.push(UIEvent::Notice(vec![i18n::t_args(
+                    "watchdog.dropped",
+                    &[("size", &format_bytes(bytes))],
+                )]));
+            }
+            if runtime >= LONG_COMMAND {
+                events.push(UIEvent::Notify {
+                    category: NotificationCategory::Jobs,
+                    level: NotificationLevel::Info,
+                    title: i18n::t_args(
+                        "notifications.finished",
+                        &[
+                            ("command", &command.command),
+                            ("duration", &notifications::format_elapsed(runtime)),
+                        ],
+                    ),
+                    body: None,
+                });
+            }
+            *guard = None;
+            return events;
+        }
+        let memory = processes.iter().map(|(_, bytes)| bytes).sum();
+        let mut events = Vec::new();
+        for violation in command.check(memory, now) {
+            events.push(Self::violation_event(&violation, &command.command));
+            if violation.action == LimitAction::Kill {
+                let pids: Vec<u32> = processes.iter().map(|(pid, _)| *pid).collect();
+                if let Err(e) = watchdog::kill_processes(system, &pids) {
+                    events.push(UIEvent::Error(e.render()));
+                }
+            }
+        }
+        events
+    }
+
+    fn violation_event(violation: &Violation, command: &str) -> UIEvent {
+        UIEvent::Notify {
+            category: NotificationCategory::Alerts,
+            level: match violation.action {
+                LimitAction::Kill => NotificationLevel::Error,
+                LimitAction::Warn | LimitAction::Throttle => NotificationLevel::Warning,
+            },
+            title: violation.message(command),
+            body: None,
+        }
+    }
+
+    async fn pty_monitor_task(
+        pty_manager: Arc<Mutex<PtyManager>>,
+        event_sender: mpsc::UnboundedSender<UIEvent>,
+        mut signal: ShutdownSignal,
+    ) -> Result<(), WarpError> {
+        loop {
+            // Dropping the pending read on shutdown releases the PTY lock so
+            // the shells can be terminated
+            let read = tracing::trace_span!("pty_read", bytes = tracing::field::Empty);
+            let step = async {
+                let mut pty = pty_manager.lock().await;
+                let output = pty.read_output_within(BACKGROUND_POLL).await?;
+                Ok::<_, WarpError>((output, pty.drain_background().await?))
+            };
+            let (output, background) = tokio::select! {
+                step = step.instrument(read.clone()) => step?,
+                _ = signal.triggered() => return Ok(()),
+            };
+            read.record("bytes", output.len() as u64);
+            drop(read);
+
+            for (pane, text) in background {
+                if event_sender
+                    .send(UIEvent::BackgroundOutput(pane, text))
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+
+            if output.is_empty() {
+                // Only back off when idle; under load keep reading so output isn't throttled to 100 reads/s
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+                    _ = signal.triggered() => return Ok(()),
+                }
+            } else if event_sender.send(UIEvent::PtyOutput(output)).is_err() {
+                // Event loop has shut down
+                return Ok(());
+            }
+        }
+    }
+
+    async fn event_loop(&self) -> Result<(), WarpError> {
+        let mut input_events = EventStream::new();
+        let mut receiver = self.event_receiver.lock().await;
+        let mut ticker = tokio::time::interval(TICK_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            match next_wake(&mut input_events, &mut receiver, &mut ticker).await {
+                // Handle terminal events
+                Wake::Input(input) => match input {
+                    Some(Ok(Event::Key(key_event))) => {
+                        if self.handle_key_event(key_event).await? {
+                            break;
+                        }
+                    }
+                    Some(Ok(Event::Resize(width, height))) => {
+                        self.handle_resize(width, height).await?;
+                    }
+                    Some(Ok(Event::Paste(text))) => self.handle_paste(text).await,
+                    Some(Ok(Event::FocusGained)) => self.focused.store(true, Ordering::Relaxed),
+                    Some(Ok(Event::FocusLost)) => self.focused.store(false, Ordering::Relaxed),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        log::error!("Error reading event: {}", e);
+                    }
+                    // Input stream closed (stdin gone)
+                    None => break,
+                },
+
+                // Handle UI events (PTY output, AI responses, timers, ...)
+                Wake::Ui(Some(UIEvent::Quit)) | Wake::Ui(None) => break,
+                Wake::Ui(Some(event)) => self.dispatch_ui_event(event).await?,
+
+                // Periodic tick
+                Wake::Tick => {}
+            }
+
+            let (queued, quit) = drain_queued(&mut receiver);
+            for event in queued {
+                self.dispatch_ui_event(event).await?;
+            }
+            if quit {
+                break;
+            }
+
+            // Render UI (no-op when nothing changed)
+            self.render().await?;
+            *self.heartbeat.lock().unwrap() = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    pub fn event_sender(&self) -> mpsc::UnboundedSender<UIEvent> {
+        self.event_sender.clone()
+    }
+
+    // Fires UIEvent::Timer(name) on the event loop after `delay`
+    pub fn schedule_timer(&self, name: impl Into<String>, delay: Duration) {
+        let name = name.into();
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = event_sender.send(UIEvent::Timer(name));
+        });
+    }
+
+    async fn handle_key_event(&self, key_event: KeyEvent) -> Result<bool, WarpError> {
+        if self.marketplace_key(key_event).await? {
+            return Ok(false);
+        }
+        match key_event {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => return Ok(true), // Exit
+
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                // Toggle debug mode
+                let mut config = self.config.lock().await;
+                config.debug.enabled = !config.debug.enabled;
+            }
+
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_process_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_ports_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_git_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_file_manager().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('b'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.ui.lock().await.toggle_pager(),
+
+            KeyEvent {
+                code: KeyCode::Char('b'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.page_hidden_output().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('k'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_locations_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_problems_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_tasks_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_settings_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_notifications_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_performance_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('l'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.copy_block_link().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_runbooks_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_cloud_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('t'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_new_tab_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_structured_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_telemetry_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_dev_console_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_permissions_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_activity_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('o'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.cycle_output_encoding().await,
+
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_search_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.tag_current_block().await,
+
+            KeyEvent {
+                code: KeyCode::Char('k'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.mark_current_block(Mark::Pinned).await,
+
+            KeyEvent {
+                code: KeyCode::Char('j'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_pinned_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('h'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_replay_panel().await?,
+
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_trust_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('i'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_traces_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_tuning_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_broadcast_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.toggle_connections_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => self.begin_diff().await,
+
+            // Alt+Shift, since every Alt letter is taken
+            KeyEvent {
+                code: KeyCode::Char('D'),
+                modifiers,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.toggle_debugger_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('T'),
+                modifiers,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.toggle_tests_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('I'),
+                modifiers,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.toggle_insights_panel().await,
+
+            KeyEvent {
+                code: KeyCode::Char('E'),
+                modifiers,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.begin_export().await,
+
+            KeyEvent {
+                code: KeyCode::Char('M'),
+                modifiers,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.open_marketplace().await,
+
+            _ => {
+                // Forward to UI
+                let mut ui = self.ui.lock().await;
+                ui.handle_key_event(key_event).await?;
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Returns true if the panel was open and has been closed
+    async fn close_if_open(&self, kind: PanelKind) -> bool {
+        let was_open = {
+            let mut open = self.open_panel.lock().unwrap();
+            let was_open = *open == Some(kind);
+            if was_open {
+                *open = None;
+            }
+            was_open
+        };
+        if was_open {
+            self.ui.lock().await.close_panel(kind);
+        }
+        was_open
+    }
+
+    async fn toggle_process_panel(&self) {
+        if self.close_if_open(PanelKind::ProcessTree).await {
+            return;
+        }
+
+        // Resolve the pid before taking the UI lock; the PTY lock can be held
+        // by the monitor while it waits for output
+        let root = self.pty_manager.lock().await.active_pid().await;
+        let view = {
+            let mut monitor = self.process_tree.lock().await;
+            monitor.set_root(root);
+            Self::process_tree_view(&mut monitor)
+        };
+        *self.open_panel.lock().unwrap() = Some(PanelKind::ProcessTree);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn toggle_ports_panel(&self) {
+        if self.close_if_open(PanelKind::Ports).await {
+            return;
+        }
+        let view = Self::ports_view(&*self.port_watcher.lock().await);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Ports);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn toggle_git_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Git).await {
+            *self.git_panel.lock().await = None;
+            return Ok(());
+        }
+        let cwd = std::env::current_dir()?;
+        let mut panel = GitPanel::new(GitManager::discover(&cwd)?);
+        panel.refresh().await?;
+        let view = panel.view();
+        *self.git_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Git);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    async fn toggle_file_manager(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Files).await {
+            *self.file_manager.lock().await = None;
+            return Ok(());
+        }
+        let pid = self.pty_manager.lock().await.active_pid().await;
+        let manager = FileManager::open(pid).await?;
+        let view = manager.view();
+        *self.file_manager.lock().await = Some(manager);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Files);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    // file:line references in the latest block, resolved against the
+    // shell's working directory
+    async fn toggle_locations_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Locations).await {
+            *self.locations.lock().await = None;
+            return Ok(());
+        }
+        let cwd = self.shell_cwd().await?;
+        let lines = self.ui.lock().await.current_block();
+        let panel = LocationsPanel::new(&lines, cwd);
+        let view = panel.view();
+        *self.locations.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Locations);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    // Compiler, linter and test diagnostics from the latest block
+    async fn toggle_problems_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Problems).await {
+            *self.problems.lock().await = None;
+            return Ok(());
+        }
+        let cwd = self.shell_cwd().await?;
+        let lines = self.ui.lock().await.current_block();
+        let panel = ProblemsPanel::new(&self.problem_parser, &lines, cwd);
+        let view = panel.view();
+        *self.problems.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Problems);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    // Build, test and run tasks from the project files around the shell's
+    // directory: Cargo.toml, package.json, Makefile, justfile, compose
+    async fn toggle_tasks_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Tasks).await {
+            *self.tasks.lock().await = None;
+            return Ok(());
+        }
+        let panel = self.scan_tasks().await?;
+        let view = panel.view();
+        *self.tasks.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Tasks);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    async fn toggle_settings_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Settings).await {
+            *self.settings.lock().await = None;
+            return Ok(());
+        }
+        let current = settings::load_current(&self.config_path).await?;
+        let panel = SettingsPanel::new(&current, self.config_path.clone())?;
+        let view = panel.view();
+        *self.settings.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Settings);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    // Opening the history marks everything in it as read
+    async fn toggle_notifications_panel(&self) {
+        if self.close_if_open(PanelKind::Notifications).await {
+            *self.notifications_panel.lock().await = None;
+            return;
+        }
+        let panel = {
+            let mut center = self.notifications.lock().await;
+            center.mark_read();
+            NotificationsPanel::new(&center, chrono::Local::now().time())
+        };
+        let view = panel.view();
+        *self.notifications_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Notifications);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // The performance overlay; the sampler task refreshes it while open
+    async fn toggle_performance_panel(&self) {
+        if self.close_if_open(PanelKind::Performance).await {
+            *self.memory_panel.lock().await = None;
+            return;
+        }
+        let panel = MemoryPanel::new(&*self.memory.lock().await, self.memory_config.sample_secs);
+        let view = panel.view();
+        *self.memory_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Performance);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // Bracketed paste arrives as one event, so a trailing newline never
+    // reaches the shell as Enter. Multi-line scripts wait in the review
+    // panel; a risky single line lands in the input with a warning.
+    async fn handle_paste(&self, text: String) {
+        let paste = self.paste_guard.prepare(&text);
+        *self.last_paste.lock().await = Some(paste.text.clone());
+        let mut ui = self.ui.lock().await;
+        if ui.is_prompting() {
+            ui.insert_text(&paste.text);
+            return;
+        }
+        // An open panel has the keyboard
+        if ui.panel_kind().is_some() || paste.text.is_empty() {
+            return;
+        }
+        if self.paste_guard.needs_review(&paste) {
+            let panel = PastePanel::new(paste);
+            ui.show_panel(panel.view());
+            *self.paste_panel.lock().await = Some(panel);
+            *self.open_panel.lock().unwrap() = Some(PanelKind::Paste);
+            return;
+        }
+        ui.insert_text(&paste.text);
+        if let Some(warning) = paste.warnings.first() {
+            ui.set_hint(Some(PromptHint {
+                message: paste::warning_message(warning),
+                command: None,
+                confirm: false,
+            }));
+        }
+    }
+
+    async fn toggle_cloud_panel(&self) {
+        if self.close_if_open(PanelKind::Cloud).await {
+            *self.cloud_panel.lock().await = None;
+            return;
+        }
+        let shell = self.config.lock().await.terminal.shell.clone();
+        let panel = CloudPanel::new(&*self.cloud.lock().await, &shell);
+        let view = panel.view();
+        *self.cloud_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Cloud);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn toggle_new_tab_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::NewTab).await {
+            *self.new_tab.lock().await = None;
+            return Ok(());
+        }
+        let cwd = self.shell_cwd().await?;
+        let panel = NewTabPanel::new(&*self.shell_manager.lock().await, cwd).await;
+        let view = panel.view();
+        *self.new_tab.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::NewTab);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    // Nushell prints its values as tables; the last one in the current block
+    // opens as rows that can be sorted and copied as JSON
+    async fn toggle_structured_panel(&self) {
+        if self.close_if_open(PanelKind::Structured).await {
+            *self.structured.lock().await = None;
+            return;
+        }
+        let mut ui = self.ui.lock().await;
+        let Some(table) = structured::parse_table(&ui.current_block()) else {
+            ui.push_lines(vec![i18n::t("structured.no_table")]);
+            return;
+        };
+        let panel = StructuredPanel::new(table);
+        ui.show_panel(panel.view());
+        *self.structured.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Structured);
+    }
+
+    // Until telemetry has been answered the panel opens at startup as the
+    // opt-in prompt; nothing is recorded before then
+    async fn offer_telemetry(&self) {
+        match self.privacy.mode().await {
+            TelemetryMode::Ask => self.toggle_telemetry_panel().await,
+            _ => self.record_session_start().await,
+        }
+    }
+
+    async fn record_session_start(&self) {
+        let event = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+        });
+        if let Err(e) = self.privacy.record("session_start", &event).await {
+            log::warn!("Failed to record telemetry event: {}", e);
+        }
+    }
+
+    async fn toggle_telemetry_panel(&self) {
+        if self.close_if_open(PanelKind::Telemetry).await {
+            *self.telemetry.lock().await = None;
+            return;
+        }
+        let panel = TelemetryPanel::new(self.privacy.mode().await, self.privacy.queued().await);
+        self.ui.lock().await.show_panel(panel.view());
+        *self.telemetry.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Telemetry);
+    }
+
+    async fn toggle_dev_console_panel(&self) {
+        if self.close_if_open(PanelKind::DevConsole).await {
+            return;
+        }
+        let view = self.dev_console.lock().await.view();
+        self.ui.lock().await.show_panel(view);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::DevConsole);
+    }
+
+    // Attaches to the plugin package in the shell's directory unless a debug
+    // session is already running
+    async fn toggle_debugger_panel(&self) {
+        if self.close_if_open(PanelKind::Debugger).await {
+            return;
+        }
+        if let Err(e) = self.open_debugger_panel().await {
+            let _ = self.event_sender.send(UIEvent::Error(
+                e.context(i18n::t("debugger.failed")).render(),
+            ));
+        }
+    }
+
+    async fn open_debugger_panel(&self) -> Result<(), WarpError> {
+        let dev_tools = self.dev_tools.get().await?;
+        if self.debug_session_id().await.is_none() {
+            let dir = self.shell_cwd().await?;
+            let manifest = Manifest::load(&dir).await?;
+            dev_tools.register_item_dir(&manifest.id, dir).await;
+            let session_id = dev_tools.start_debug_session(&manifest.id).await?;
+            let mut panel = DebugPanel::new();
+            panel.set_session(dev_tools.refresh_debug_session(&session_id).await?);
+            *self.debug_panel.lock().await = Some(panel);
+        }
+        if let Some(view) = Self::debug_panel_view(&dev_tools, &self.debug_panel).await? {
+            self.ui.lock().await.show_panel(view);
+            *self.open_panel.lock().unwrap() = Some(PanelKind::Debugger);
+        }
+        Ok(())
+    }
+
+    async fn debug_session_id(&self) -> Option<String> {
+        let panel = self.debug_panel.lock().await;
+        panel
+            .as_ref()?
+            .session()
+            .map(|session| session.session_id.clone())
+    }
+
+    // The panel with the session as of the adapter's latest events
+    async fn debug_panel_view(
+        dev_tools: &DevToolsManager,
+        debug_panel: &Mutex<Option<DebugPanel>>,
+    ) -> Result<Option<PanelView>, WarpError> {
+        let mut panel = debug_panel.lock().await;
+        let Some(panel) = panel.as_mut() else {
+            return Ok(None);
+        };
+        if let Some(session_id) = panel.session().map(|session| session.session_id.clone()) {
+            panel.set_session(dev_tools.refresh_debug_session(&session_id).await?);
+        }
+        Ok(Some(panel.view()))
+    }
+
+    async fn run_debug_command(&self, command: DebugCommand) -> Result<(), WarpError> {
+        let Some(session_id) = self.debug_session_id().await else {
+            return Ok(());
+        };
+        let dev_tools = self.dev_tools.get().await?;
+        match command {
+            DebugCommand::None => return Ok(()),
+            DebugCommand::Redraw => {}
+            DebugCommand::Step(kind) => dev_tools.step(&session_id, kind).await?,
+            DebugCommand::Prompt(label) => {
+                self.ui
+                    .lock()
+                    .await
+                    .begin_prompt(PanelKind::Debugger, label);
+                return Ok(());
+            }
+            DebugCommand::EvaluateWatches => self.evaluate_watches(&dev_tools, &session_id).await,
+            DebugCommand::SetBreakpoint {
+                file_path,
+                line_number,
+                condition,
+            } => {
+                dev_tools
+                    .set_breakpoint(&session_id, &file_path, line_number, condition)
+                    .await?;
+            }
+            DebugCommand::RemoveBreakpoint(index) => {
+                let breakpoint = self.debug_panel.lock().await.as_ref().and_then(|panel| {
+                    panel
+                        .session()?
+                        .breakpoints
+                        .get(index)
+                        .map(|breakpoint| breakpoint.id.clone())
+                });
+                if let Some(breakpoint) = breakpoint {
+                    dev_tools
+                        .remove_breakpoint(&session_id, &breakpoint)
+                        .await?;
+                }
+            }
+            DebugCommand::Stop => {
+                *self.debug_panel.lock().await = None;
+                self.close_if_open(PanelKind::Debugger).await;
+                return dev_tools.stop_debug_session(&session_id).await;
+            }
+        }
+        if let Some(view) = Self::debug_panel_view(&dev_tools, &self.debug_panel).await? {
+            self.ui.lock().await.show_panel(view);
+        }
+        Ok(())
+    }
+
+    // Each watch in the selected frame; a failed one shows its error
+    async fn evaluate_watches(&self, dev_tools: &DevToolsManager, session_id: &str) {
+        let (watches, frame) = match self.debug_panel.lock().await.as_ref() {
+            Some(panel) => (
+                panel.watches().map(String::from).collect::<Vec<_>>(),
+                panel.selected_frame(),
+            ),
+            None => return,
+        };
+        for (index, expression) in watches.iter().enumerate() {
+            let value = dev_tools
+                .evaluate_watch(session_id, expression, frame)
+                .await
+                .map_err(|e| e.to_string());
+            if let Some(panel) = self.debug_panel.lock().await.as_mut() {
+                panel.set_watch_value(index, value);
+            }
+        }
+    }
+
+    // Runs the suite of the plugin package in the shell's directory the
+    // first time; after that it shows the last results
+    async fn toggle_tests_panel(&self) {
+        if self.close_if_open(PanelKind::TestRunner).await {
+            return;
+        }
+        if let Err(e) = self.open_tests_panel().await {
+            let _ = self
+                .event_sender
+                .send(UIEvent::Error(e.context(i18n::t("tests.failed")).render()));
+        }
+    }
+
+    async fn open_tests_panel(&self) -> Result<(), WarpError> {
+        let first_run = self.test_panel.lock().await.is_none();
+        if first_run {
+            let dir = self.shell_cwd().await?;
+            let manifest = Manifest::load(&dir).await?;
+            let suite = testing::load_suite(&dir).await?;
+            self.dev_tools
+                .get()
+                .await?
+                .register_item_dir(&manifest.id, dir)
+                .await;
+            *self.test_panel.lock().await = Some(TestPanel::new(&suite.name));
+            *self.test_suite.lock().await = Some((manifest.id, suite));
+        }
+        let Some(view) = self.test_panel.lock().await.as_ref().map(TestPanel::view) else {
+            return Ok(());
+        };
+        self.ui.lock().await.show_panel(view);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::TestRunner);
+        if first_run {
+            self.spawn_test_run(None).await;
+        }
+        Ok(())
+    }
+
+    // None runs the whole suite; a re-run only replaces its tests' results
+    async fn spawn_test_run(&self, names: Option<Vec<String>>) {
+        let Some((item_id, suite)) = self.test_suite.lock().await.clone() else {
+            return;
+        };
+        self.ui
+            .lock()
+            .await
+            .set_panel_footer(PanelKind::TestRunner, Some(i18n::t("tests.running")));
+        let dev_tools = self.dev_tools.clone();
+        let panel = self.test_panel.clone();
+        let ui = self.ui.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+
+        tokio::spawn(async move {
+            let run = Self::run_tests(&dev_tools, &item_id, &suite, names.as_deref()).await;
+            let view = {
+                let mut guard = panel.lock().await;
+                let Some(panel) = guard.as_mut() else {
+                    return;
+                };
+                match run {
+                    Ok((results, coverage)) if names.is_some() => {
+                        panel.update_results(results, coverage)
+                    }
+                    Ok((results, coverage)) => panel.set_results(results, coverage),
+                    Err(e) => {
+                        let _ = event_sender
+                            .send(UIEvent::Error(e.context(i18n::t("tests.failed")).render()));
+                    }
+                }
+                panel.view()
+            };
+            // A run finishing after Esc must not reopen the panel
+            if *open_panel.lock().unwrap() == Some(PanelKind::TestRunner) {
+                ui.lock().await.show_panel(view);
+            }
+        });
+    }
+
+    async fn run_tests(
+        dev_tools: &LazyService<DevToolsManager>,
+        item_id: &str,
+        suite: &TestSuite,
+        names: Option<&[String]>,
+    ) -> Result<(Vec<TestResult>, Option<CoverageData>), WarpError> {
+        let dev_tools = dev_tools.get().await?;
+        let results = match names {
+            Some(names) => dev_tools.rerun_tests(item_id, suite, names).await?,
+            None => dev_tools.run_tests(item_id, suite).await?,
+        };
+        Ok((results, dev_tools.test_coverage(item_id).await))
+    }
+
+    async fn toggle_insights_panel(&self) {
+        if self.close_if_open(PanelKind::Insights).await {
+            return;
+        }
+        self.show_insights_panel().await;
+    }
+
+    // Recomputed on every open and refresh; the engine keeps only the samples
+    async fn show_insights_panel(&self) {
+        match Self::insights_view(&self.ml_insights).await {
+            Ok(view) => {
+                self.ui.lock().await.show_panel(view);
+                *self.open_panel.lock().unwrap() = Some(PanelKind::Insights);
+            }
+            Err(e) => {
+                let _ = self.event_sender.send(UIEvent::Error(
+                    e.context(i18n::t("insights.failed")).render(),
+                ));
+            }
+        }
+    }
+
+    async fn insights_view(engine: &MLInsightsEngine) -> Result<PanelView, WarpError> {
+        let anomalies = engine
+            .detect_anomalies(chrono::Duration::hours(INSIGHTS_WINDOW_HOURS))
+            .await?;
+        let mut trends = Vec::new();
+        for metric_name in engine.feature_store().metric_series().await.into_keys() {
+            trends.push(
+                engine
+                    .analyze_trends(&metric_name, chrono::Duration::hours(INSIGHTS_KEEP_HOURS))
+                    .await?,
+            );
+        }
+        let panel = InsightsPanel::new(
+            engine.pipeline_summaries().await,
+            anomalies.anomalies,
+            trends,
+        );
+        Ok(panel.view())
+    }
+
+    async fn toggle_traces_panel(&self) {
+        if self.close_if_open(PanelKind::Traces).await {
+            return;
+        }
+        let view = self.block_tracer.lock().unwrap().view();
+        self.ui.lock().await.show_panel(view);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Traces);
+    }
+
+    async fn toggle_permissions_panel(&self) {
+        if self.close_if_open(PanelKind::PluginPermissions).await {
+            *self.permissions_panel.lock().await = None;
+            return;
+        }
+        self.refresh_permissions_panel().await;
+        *self.open_panel.lock().unwrap() = Some(PanelKind::PluginPermissions);
+    }
+
+    async fn toggle_trust_panel(&self) {
+        if self.close_if_open(PanelKind::WorkspaceTrust).await {
+            *self.trust_panel.lock().await = None;
+            return;
+        }
+        self.refresh_trust_panel().await;
+        *self.open_panel.lock().unwrap() = Some(PanelKind::WorkspaceTrust);
+    }
+
+    async fn toggle_search_panel(&self) {
+        if self.close_if_open(PanelKind::Search).await {
+            *self.search_panel.lock().await = None;
+            return;
+        }
+        let panel = SearchPanel::new(&self.session_id);
+        let view = panel.view();
+        *self.search_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Search);
+        let mut ui = self.ui.lock().await;
+        ui.show_panel(view);
+        ui.begin_prompt(PanelKind::Search, SearchPanel::prompt_label());
+    }
+
+    // Off the loop; the results arrive as ShowPanel
+    async fn run_search(&self, text: String) {
+        let mut query = match SearchQuery::parse(&text, chrono::Utc::now()) {
+            Ok(query) => query,
+            Err(e) => {
+                let _ = self.event_sender.send(UIEvent::Error(e.render()));
+                return;
+            }
+        };
+        if !query.tags().is_empty() {
+            let blocks = self
+                .history_manager
+                .lock()
+                .await
+                .tags()
+                .tagged_all(query.tags());
+            query.restrict_to(blocks);
+        }
+        let engine = self.search_engine.clone();
+        let search_panel = self.search_panel.clone();
+        let history_manager = self.history_manager.clone();
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            let results = match engine.get().await {
+                Ok(engine) => {
+                    match tokio::task::spawn_blocking(move || engine.search(&query)).await {
+                        Ok(results) => results,
+                        Err(e) => Err(WarpError::command_err(e.to_string())),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+            let event = match results {
+                Ok(results) => match search_panel.lock().await.as_mut() {
+                    Some(panel) => {
+                        panel.set_results(&text, results, history_manager.lock().await.tags());
+                        UIEvent::ShowPanel(panel.view())
+                    }
+                    None => return,
+                },
+                Err(e) => UIEvent::Error(e.context(i18n::t("search.failed")).render()),
+            };
+            let _ = event_sender.send(event);
+        });
+    }
+
+    // The last block, with its command read back from the line it starts with
+    async fn current_block_ref(&self) -> Option<BlockRef> {
+        let ui = self.ui.lock().await;
+        let block = ui.current_block_id()?;
+        let first = ui.block_lines(&block).into_iter().next()?;
+        Some(BlockRef {
+            session: self.session_id.clone(),
+            command: first.strip_prefix("❯ ").unwrap_or(&first).to_string(),
+            block,
+        })
+    }
+
+    async fn tag_current_block(&self) {
+        match self.current_block_ref().await {
+            Some(target) => self.begin_tagging(target).await,
+            None => self
+                .ui
+                .lock()
+                .await
+                .push_lines(vec![i18n::t("permalink.no_block")]),
+        }
+    }
+
+    async fn begin_tagging(&self, target: BlockRef) {
+        let label = i18n::t_args("block_tags.prompt", &[("command", &target.command)]);
+        *self.tagging.lock().await = Some(target);
+        self.ui
+            .lock()
+            .await
+            .begin_prompt(PanelKind::PinnedBlocks, label);
+    }
+
+    async fn apply_tags(&self, text: &str) {
+        let Some(target) = self.tagging.lock().await.take() else {
+            return;
+        };
+        let (add, remove) = match parse_tags(text) {
+            Ok(tags) => tags,
+            Err(e) => {
+                self.ui.lock().await.push_lines(e.render());
+                return;
+            }
+        };
+        let tags = {
+            let mut history = self.history_manager.lock().await;
+            history.tags_mut().retag(&target, &add, &remove);
+            history
+                .tags()
+                .get(&target.block)
+                .map(|marks| format_tags(&marks.tags))
+                .unwrap_or_default()
+        };
+        self.save_block_tags().await;
+        let text = i18n::t_args(
+            "block_tags.tagged",
+            &[("command", &target.command), ("tags", tags.trim())],
+        );
+        self.ui.lock().await.show_toast(
+            text,
+            crossterm::style::Color::Cyan,
+            Duration::from_secs(3),
+        );
+    }
+
+    async fn mark_current_block(&self, mark: Mark) {
+        match self.current_block_ref().await {
+            Some(target) => self.mark_block(&target, mark).await,
+            None => self
+                .ui
+                .lock()
+                .await
+                .push_lines(vec![i18n::t("permalink.no_block")]),
+        }
+    }
+
+    // Pins or favorites a block, or takes it off again
+    async fn mark_block(&self, target: &BlockRef, mark: Mark) {
+        let on = self
+            .history_manager
+            .lock()
+            .await
+            .tags_mut()
+            .toggle(target, mark);
+        self.save_block_tags().await;
+        let key = match (mark, on) {
+            (Mark::Pinned, true) => "block_tags.pinned",
+            (Mark::Pinned, false) => "block_tags.unpinned",
+            (Mark::Favorite, true) => "block_tags.favorited",
+            (Mark::Favorite, false) => "block_tags.unfavorited",
+        };
+        let text = i18n::t_args(key, &[("command", &target.command)]);
+        self.ui.lock().await.show_toast(
+            text,
+            crossterm::style::Color::Cyan,
+            Duration::from_secs(3),
+        );
+    }
+
+    // Written straight away rather than at exit, then the sidebar refreshed
+    async fn save_block_tags(&self) {
+        if let Err(e) = self.history_manager.lock().await.flush().await {
+            log::error!("Failed to save block tags: {}", e);
+        }
+        let view = {
+            let mut panel = self.pinned_panel.lock().await;
+            let Some(panel) = panel.as_mut() else {
+                return;
+            };
+            panel.refresh(self.history_manager.lock().await.tags());
+            panel.view()
+        };
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn toggle_replay_panel(&self) -> Result<(), WarpError> {
+        if self.close_if_open(PanelKind::Replay).await {
+            *self.replay_panel.lock().await = None;
+            return Ok(());
+        }
+        let recordings = replay::list_recordings(&self.replay_config.directory).await?;
+        let panel = ReplayPanel::new(recordings);
+        let view = panel.view();
+        *self.replay_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Replay);
+        self.ui.lock().await.show_panel(view);
+        Ok(())
+    }
+
+    async fn toggle_pinned_panel(&self) {
+        if self.close_if_open(PanelKind::PinnedBlocks).await {
+            *self.pinned_panel.lock().await = None;
+            return;
+        }
+        let panel = PinnedPanel::new(self.history_manager.lock().await.tags());
+        let view = panel.view();
+        *self.pinned_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::PinnedBlocks);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // From the scrollback when it's still there, otherwise from the search
+    // index, which keeps output from earlier sessions
+    async fn open_marked_block(&self, target: BlockRef) {
+        if !self.ui.lock().await.block_lines(&target.block).is_empty() {
+            self.ui.lock().await.jump_to_block(&target.block);
+            return;
+        }
+        let engine = self.search_engine.clone();
+        let ui = self.ui.clone();
+        tokio::spawn(async move {
+            let block = target.block.clone();
+            let found = match engine.get().await {
+                Ok(engine) => tokio::task::spawn_blocking(move || engine.find_block(&block))
+                    .await
+                    .ok(),
+                Err(_) => None,
+            };
+            let output = match found {
+                Some(Ok(Some(record))) => record.output,
+                _ => String::new(),
+            };
+            ui.lock()
+                .await
+                .open_block(&target.block, &target.command, &output);
+        });
+    }
+
+    // A block ends when the next command starts, or when the app exits
+    // (`next` None). The one that ended goes into the search index and the
+    // new one starts recording.
+    async fn finish_search_block(&self, next: Option<&str>) -> Option<JoinHandle<()>> {
+        if !self.search_config.enabled {
+            return None;
+        }
+        let block = self.ui.lock().await.current_block_id();
+        let next = match (next, block) {
+            (Some(command), Some(block)) => {
+                let cwd = self.shell_cwd().await.ok();
+                Some(BlockRecord::new(&self.session_id, &block, command, cwd))
+            }
+            _ => None,
+        };
+        let mut record = std::mem::replace(&mut *self.search_block.lock().await, next)?;
+        if self.search_config.index_output {
+            let lines = self.ui.lock().await.block_lines(&record.block);
+            // The first line is the command as typed
+            let output: Vec<String> = lines
+                .iter()
+                .skip(1)
+                .map(|line| parse_ansi(line).into_iter().map(|(text, _)| text).collect())
+                .collect();
+            record.set_output(&output.join("\n"), self.search_config.max_output_kb * 1024);
+        }
+        let engine = self.search_engine.clone();
+        Some(tokio::spawn(async move {
+            let indexed = match engine.get().await {
+                Ok(engine) => {
+                    match tokio::task::spawn_blocking(move || engine.index_block(&record)).await {
+                        Ok(indexed) => indexed,
+                        Err(e) => Err(WarpError::command_err(e.to_string())),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = indexed {
+                log::warn!("Failed to index block: {}", e);
+            }
+        }))
+    }
+
+    async fn toggle_activity_panel(&self) {
+        if self.close_if_open(PanelKind::PluginActivity).await {
+            *self.plugin_activity.lock().await = None;
+            return;
+        }
+        *self.open_panel.lock().unwrap() = Some(PanelKind::PluginActivity);
+        self.refresh_activity_panel().await;
+    }
+
+    async fn refresh_activity_panel(&self) {
+        let (usage, killed) = match &self.plugin_host {
+            Some(host) => (host.usage().await, host.is_killed()),
+            None => (Vec::new(), false),
+        };
+        let view = {
+            let mut panel = self.plugin_activity.lock().await;
+            let panel = panel.get_or_insert_with(|| PluginActivityPanel::new(Vec::new(), false));
+            panel.refresh(usage, killed);
+            panel.view()
+        };
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn refresh_permissions_panel(&self) {
+        let panel = match &self.plugin_host {
+            Some(host) => PermissionsPanel::new(&host.permissions().lock().unwrap()),
+            None => PermissionsPanel::new(&Permissions::default()),
+        };
+        self.ui.lock().await.show_panel(panel.view());
+        *self.permissions_panel.lock().await = Some(panel);
+    }
+
+    async fn load_project_tunings() -> Result<ProjectTunings, WarpError> {
+        ProjectTunings::load(&ProjectTunings::default_path()?).await
+    }
+
+    async fn save_project_tunings(bytes: Result<Vec<u8>, WarpError>) -> Result<(), WarpError> {
+        ProjectTunings::save(&bytes?, &ProjectTunings::default_path()?).await
+    }
+
+    async fn active_pane(&self) -> usize {
+        self.pty_manager
+            .lock()
+            .await
+            .get_active_process_id()
+            .unwrap_or(0)
+    }
+
+    async fn is_ssh_pane(&self, pane: usize) -> bool {
+        self.ssh_panes.lock().await.values().any(|p| *p == pane)
+    }
+
+    async fn set_pane_trust(&self, pane: usize, command: TrustLevel) {
+        let trust = escape_filter::pane_trust(self.is_ssh_pane(pane).await, command);
+        self.escape_filters
+            .lock()
+            .await
+            .entry(pane)
+            .or_insert_with(|| EscapeFilter::new(self.escapes.clone()))
+            .set_trust(trust);
+    }
+
+    // Runs a pane's output through its own filter, created on first use, and
+    // warns about anything it reported
+    async fn filter_escapes(&self, pane: usize, output: &str) -> String {
+        let remote = self.is_ssh_pane(pane).await;
+        let (filtered, trust) = {
+            let mut filters = self.escape_filters.lock().await;
+            let filter = filters.entry(pane).or_insert_with(|| {
+                let mut filter = EscapeFilter::new(self.escapes.clone());
+                filter.set_trust(escape_filter::pane_trust(remote, TrustLevel::Local));
+                filter
+            });
+            (filter.filter(output), filter.trust())
+        };
+        if !filtered.reported.is_empty() {
+            let what = filtered
+                .reported
+                .iter()
+                .map(|class| i18n::t(class.i18n_key()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let command = self.last_command.lock().await.clone().unwrap_or_default();
+            self.notify(
+                NotificationCategory::Alerts,
+                NotificationLevel::Warning,
+                i18n::t_args(
+                    "escapes.blocked",
+                    &[
+                        ("what", &what),
+                        ("trust", &i18n::t(escape_filter::trust_i18n_key(trust))),
+                    ],
+                ),
+                Some(i18n::t_args(
+                    "escapes.blocked_body",
+                    &[("command", &command)],
+                )),
+            )
+            .await;
+        }
+        filtered.text
+    }
+
+    async fn active_ssh_session(&self) -> Option<String> {
+        let pane = self.active_pane().await;
+        let panes = self.ssh_panes.lock().await;
+        panes
+            .iter()
+            .find(|(_, p)| **p == pane)
+            .map(|(session, _)| session.clone())
+    }
+
+    // The active pane's project tuning, and the root a new one is saved
+    // for: the saved root around the shell's directory, else its project
+    async fn project_tuning(&self) -> (PathBuf, PaneTuning) {
+        let cwd = self.shell_cwd().await.unwrap_or_default();
+        if let Some((root, tuning)) = self.project_tunings.lock().await.for_dir(&cwd) {
+            return (root.to_path_buf(), tuning);
+        }
+        let root = project_tasks::find_root(&cwd).unwrap_or(cwd);
+        (root, PaneTuning::default())
+    }
+
+    // Scrollback, capture limits and frame rate for the active pane: its
+    // overrides over its project's over the config
+    async fn apply_pane_tuning(&self) {
+        let pane = self.active_pane().await;
+        let (_, project) = self.project_tuning().await;
+        let overrides = self
+            .pane_tuning
+            .lock()
+            .await
+            .get(&pane)
+            .copied()
+            .unwrap_or_default();
+        let limits = self.pane_limits.lock().await.tuned(&overrides.or(project));
+        self.ui.lock().await.set_pane_limits(
+            limits.scrollback_lines,
+            Self::truncation_limits(&limits.output),
+            limits.frame_interval(),
+        );
+    }
+
+    async fn toggle_tuning_panel(&self) {
+        if self.close_if_open(PanelKind::PaneTuning).await {
+            *self.tuning_panel.lock().await = None;
+            return;
+        }
+        let pane = self.active_pane().await;
+        let (root, project) = self.project_tuning().await;
+        let overrides = self
+            .pane_tuning
+            .lock()
+            .await
+            .get(&pane)
+            .copied()
+            .unwrap_or_default();
+        let config = self.pane_limits.lock().await.clone();
+        let panel = PaneTuningPanel::new(pane, config, root, project, overrides);
+        self.ui.lock().await.show_panel(panel.view());
+        *self.tuning_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::PaneTuning);
+    }
+
+    async fn toggle_broadcast_panel(&self) {
+        if self.close_if_open(PanelKind::Broadcast).await {
+            *self.broadcast_panel.lock().await = None;
+            return;
+        }
+        let panel = BroadcastPanel::new(&self.ssh_config);
+        self.ui.lock().await.show_panel(panel.view());
+        *self.broadcast_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Broadcast);
+    }
+
+    // Results stream into the panel as each host finishes
+    async fn handle_broadcast_command(&self, command: BroadcastCommand, view: PanelView) {
+        match command {
+            BroadcastCommand::None => {}
+            BroadcastCommand::Prompt(label) => self
+                .ui
+                .lock()
+                .await
+                .begin_prompt(PanelKind::Broadcast, label),
+            BroadcastCommand::Run { id, hosts, command } => {
+                let decision = self.security.check_command(&command);
+                if decision.action == PolicyAction::Deny {
+                    let reason = decision.reason.unwrap_or_default();
+                    let _ = self.event_sender.send(UIEvent::Error(vec![i18n::t_args(
+                        "broadcast.denied",
+                        &[("command", &command), ("reason", &reason)],
+                    )]));
+                    return;
+                }
+                self.audit_command(&command).await;
+                let mut results = Box::pin(broadcast::broadcast(&self.ssh_config, hosts, command));
+                let panel = self.broadcast_panel.clone();
+                let event_sender = self.event_sender.clone();
+                let mut signal = self.shutdown.subscribe();
+                tokio::spawn(async move {
+                    loop {
+                        let result = tokio::select! {
+                            _ = signal.triggered() => return,
+                            result = results.next() => result,
+                        };
+                        let Some(result) = result else {
+                            return;
+                        };
+                        let view = match panel.lock().await.as_mut() {
+                            Some(panel) => {
+                                panel.record(id, result);
+                                panel.view()
+                            }
+                            // Closed; dropping the stream kills the remaining ssh processes
+                            None => return,
+                        };
+                        if event_sender.send(UIEvent::ShowPanel(view)).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        }
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn refresh_trust_panel(&self) {
+        let prompt = self.untrusted_workspace().await;
+        let panel = TrustPanel::new(&*self.workspace_trust.lock().await, prompt);
+        self.ui.lock().await.show_panel(panel.view());
+        *self.trust_panel.lock().await = Some(panel);
+    }
+
+    // Saves the choice so the prompt isn't shown again, then applies it
+    async fn set_telemetry(&self, mode: TelemetryMode) -> Result<(), WarpError> {
+        let path = ["general".to_string(), "telemetry".to_string()];
+        settings::write_setting(&self.config_path, &path, &serde_json::to_value(mode)?).await?;
+        let previous = self.privacy.mode().await;
+        self.privacy.set_mode(mode).await;
+        if previous == TelemetryMode::Ask {
+            self.record_session_start().await;
+        }
+        Ok(())
+    }
+
+    async fn refresh_telemetry_panel(&self) {
+        let panel = TelemetryPanel::new(self.privacy.mode().await, self.privacy.queued().await);
+        self.ui.lock().await.show_panel(panel.view());
+        *self.telemetry.lock().await = Some(panel);
+    }
+
+    // Starts the tab's shell and makes it the active one; its shell and
+    // profile go in the header. A trusted project's .warp/config.toml and
+    // .env apply to tabs opened inside it.
+    async fn open_tab(&self, mut request: PaneRequest) -> Result<(), WarpError> {
+        let project = if request.project_defaults {
+            workspace_trust::settings_for(&*self.workspace_trust.lock().await, &request.cwd)
+        } else {
+            None
+        };
+        if let Some(project) = &project {
+            request.shell = request.shell.or_else(|| project.defaults.shell.clone());
+            request.profile = request.profile.or_else(|| project.defaults.profile.clone());
+        }
+        let mut shells = self.shell_manager.lock().await;
+        let mut spec = shells.resolve(&request).await?;
+        if let Some(project) = project {
+            if let Some(encoding) = project.defaults.encoding {
+                spec.encoding = encoding;
+            }
+            let listed = |name: &String| project.env.iter().any(|(key, _)| key == name);
+            spec.unset.retain(|name| !listed(name));
+            spec.env.retain(|(name, _)| !listed(name));
+            spec.env.extend(project.env.iter().cloned());
+            spec.env.sort();
+        }
+        let tab = self.pty_manager.lock().await.spawn_pane(&spec).await?;
+        shells.opened(&spec);
+        drop(shells);
+        self.apply_pane_tuning().await;
+        self.refresh_tabs().await;
+        let mut ui = self.ui.lock().await;
+        ui.set_shell_label(Some(spec.title()));
+        ui.set_accent(None);
+        ui.set_encoding(spec.encoding);
+        ui.push_lines(vec![i18n::t_args(
+            "shell.tab_opened",
+            &[("tab", &tab.to_string()), ("title", &spec.title())],
+        )]);
+        Ok(())
+    }
+
+    async fn refresh_tabs(&self) {
+        let (titles, active) = {
+            let pty = self.pty_manager.lock().await;
+            (pty.tab_titles().to_vec(), pty.get_active_process_id())
+        };
+        let tabs = self.activity.lock().await.badges(&titles, active);
+        self.ui.lock().await.set_tabs(tabs);
+    }
+
+    // Makes `pane` the active tab. What it wrote in the background is
+    // shown with the next read.
+    async fn focus_tab(&self, pane: usize) -> Result<(), WarpError> {
+        let (title, encoding) = {
+            let mut pty = self.pty_manager.lock().await;
+            pty.switch_to_process(pane).await?;
+            (
+                pty.tab_titles().get(pane).cloned().unwrap_or_default(),
+                pty.encoding().await,
+            )
+        };
+        self.activity.lock().await.focused(pane);
+        {
+            let mut ui = self.ui.lock().await;
+            ui.set_shell_label(Some(title.clone()));
+            ui.set_accent(None);
+            if let Some(encoding) = encoding {
+                ui.set_encoding(encoding);
+            }
+            ui.push_lines(vec![i18n::t_args(
+                "tabs.switched",
+                &[("tab", &pane.to_string()), ("title", &title)],
+            )]);
+        }
+        self.apply_pane_tuning().await;
+        self.refresh_tabs().await;
+        Ok(())
+    }
+
+    // The next tab, or with `active_only` the next one showing output or silence
+    async fn next_tab(&self, active_only: bool) -> Result<(), WarpError> {
+        let (count, active) = {
+            let pty = self.pty_manager.lock().await;
+            (pty.tab_titles().len(), pty.get_active_process_id())
+        };
+        let next = if active_only {
+            self.activity
+                .lock()
+                .await
+                .next_with_indicator(active, count)
+        } else {
+            (count > 1).then(|| active.map_or(0, |active| (active + 1) % count))
+        };
+        match next {
+            Some(pane) if Some(pane) != active => self.focus_tab(pane).await,
+            _ => Ok(()),
+        }
+    }
+
+    // An auto-focus rule fired for `focus`, or just the indicators changed.
+    // Focus isn't taken away from a half-typed command.
+    async fn tab_activity(&self, focus: Option<usize>) -> Result<(), WarpError> {
+        match focus {
+            Some(pane) if self.ui.lock().await.input_text().is_empty() => {
+                self.focus_tab(pane).await
+            }
+            _ => {
+                self.refresh_tabs().await;
+                Ok(())
+            }
+        }
+    }
+
+    async fn toggle_tab_mute(&self) {
+        let pane = self.active_pane().await;
+        let muted = self.activity.lock().await.toggle_mute(pane);
+        let text = if muted {
+            i18n::t("tabs.muted")
+        } else {
+            i18n::t("tabs.unmuted")
+        };
+        self.ui.lock().await.show_toast(
+            text,
+            crossterm::style::Color::Cyan,
+            Duration::from_secs(3),
+        );
+        self.refresh_tabs().await;
+    }
+
+    async fn toggle_connections_panel(&self) {
+        if self.close_if_open(PanelKind::Connections).await {
+            *self.connections_panel.lock().await = None;
+            return;
+        }
+        let panel = ConnectionsPanel::new(self.shell_manager.lock().await.connections());
+        self.ui.lock().await.show_panel(panel.view());
+        *self.connections_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Connections);
+    }
+
+    async fn toggle_transforms_panel(&self) {
+        if self.close_if_open(PanelKind::Transforms).await {
+            *self.transforms_panel.lock().await = None;
+            return;
+        }
+        let last_paste = self.last_paste.lock().await.clone();
+        let mut ui = self.ui.lock().await;
+        let block = match ui.current_block_id() {
+            Some(id) => ui.block_lines(&id),
+            None => Vec::new(),
+        };
+        let panel = TransformsPanel::new(ui.input_text(), last_paste.as_deref(), &block);
+        ui.show_panel(panel.view());
+        *self.transforms_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Transforms);
+    }
+
+    async fn apply_transform(&self, command: TransformCommand) -> Result<(), WarpError> {
+        if let TransformCommand::None | TransformCommand::Redraw = command {
+            return Ok(());
+        }
+        *self.transforms_panel.lock().await = None;
+        self.close_if_open(PanelKind::Transforms).await;
+        let mut ui = self.ui.lock().await;
+        match command {
+            TransformCommand::Insert {
+                text,
+                replace: true,
+            } => ui.replace_input(&text),
+            TransformCommand::Insert {
+                text,
+                replace: false,
+            } => ui.insert_text(&text),
+            TransformCommand::Copy(text) => {
+                ports::copy_to_clipboard(&text)?;
+                ui.show_toast(
+                    i18n::t("transforms.copied"),
+                    crossterm::style::Color::Green,
+                    Duration::from_secs(3),
+                );
+            }
+            TransformCommand::Block { command, lines } => {
+                ui.push_block(&command, lines);
+            }
+            TransformCommand::None | TransformCommand::Redraw => {}
+        }
+        Ok(())
+    }
+
+    async fn begin_diff(&self) {
+        self.ui
+            .lock()
+            .await
+            .begin_prompt(PanelKind::Diff, i18n::t("diff.prompt"));
+    }
+
+    // Browsing, installs, version history with rollback, and reviews
+    async fn open_marketplace(&self) {
+        let opened = match self.marketplace.get().await {
+            Ok(marketplace) => MarketplaceUI::new(marketplace).await,
+            Err(e) => Err(e),
+        };
+        match opened {
+            Ok(marketplace_ui) => *self.marketplace_ui.lock().await = Some(marketplace_ui),
+            Err(e) => {
+                let _ = self.event_sender.send(UIEvent::Error(
+                    e.context(i18n::t("marketplace.failed")).render(),
+                ));
+            }
+        }
+    }
+
+    // False when the marketplace isn't open. Its errors show once it closes.
+    async fn marketplace_key(&self, key_event: KeyEvent) -> Result<bool, WarpError> {
+        let mut marketplace_ui = self.marketplace_ui.lock().await;
+        let Some(marketplace) = marketplace_ui.as_mut() else {
+            return Ok(false);
+        };
+        if let Err(e) = marketplace.handle_input(key_event.code).await {
+            let _ = self.event_sender.send(UIEvent::Error(e.render()));
+        }
+        if marketplace.is_closed() {
+            *marketplace_ui = None;
+            // render() takes the UI lock first
+            drop(marketplace_ui);
+            self.ui.lock().await.force_redraw()?;
+        }
+        Ok(true)
+    }
+
+    async fn begin_export(&self) {
+        self.ui
+            .lock()
+            .await
+            .begin_prompt(PanelKind::Export, i18n::t("export.prompt"));
+    }
+
+    async fn export_history(&self, text: &str) {
+        match self.write_export(text.trim()).await {
+            Ok((path, rows)) => {
+                let text = i18n::t_args(
+                    "export.done",
+                    &[
+                        ("rows", &rows.to_string()),
+                        ("path", &path.display().to_string()),
+                    ],
+                );
+                self.ui.lock().await.show_toast(
+                    text,
+                    crossterm::style::Color::Green,
+                    Duration::from_secs(3),
+                );
+            }
+            Err(e) => {
+                let context = i18n::t_args("export.failed", &[("path", text.trim())]);
+                let _ = self
+                    .event_sender
+                    .send(UIEvent::Error(e.context(context).render()));
+            }
+        }
+    }
+
+    // Every block with its metadata and tags (the command history alone with
+    // search off), in the format the file's extension names. A relative path
+    // is taken from the shell's directory.
+    async fn write_export(&self, path: &str) -> Result<(PathBuf, u64), WarpError> {
+        let mut path = PathBuf::from(path);
+        let format = path
+            .extension()
+            .and_then(|extension| ExportFormat::from_extension(&extension.to_string_lossy()))
+            .ok_or_else(|| WarpError::Validation(i18n::t("export.usage")))?;
+        if path.is_relative() {
+            path = self.shell_cwd().await?.join(path);
+        }
+
+        let mut manager = ExportManager::new().await?;
+        manager.set_terminal_source(Arc::new(TerminalSource::new(
+            self.search_engine.clone(),
+            self.history_manager.clone(),
+            self.search_config.enabled,
+        )));
+        let result = manager
+            .export_data(ExportRequest {
+                request_id: uuid::Uuid::new_v4().to_string(),
+                format,
+                data_source: DataSource::Terminal,
+                filters: Vec::new(),
+                columns: None,
+                time_range: None,
+                template: None,
+                destination: ExportDestination::LocalFile { path: path.clone() },
+                compression: None,
+                encryption: None,
+                metadata: HashMap::new(),
+            })
+            .await?;
+        match result.status {
+            ExportStatus::Completed => Ok((path, result.row_count.unwrap_or_default())),
+            _ => Err(WarpError::command_err(
+                result.error_message.unwrap_or_default(),
+            )),
+        }
+    }
+
+    async fn show_diff(&self, text: &str) {
+        match self.load_diff(text).await {
+            Ok(diff) => self.ui.lock().await.show_diff(diff),
+            Err(e) => {
+                let context = i18n::t_args("diff.failed", &[("operands", text.trim())]);
+                let _ = self
+                    .event_sender
+                    .send(UIEvent::Error(e.context(context).render()));
+            }
+        }
+    }
+
+    async fn load_diff(&self, text: &str) -> Result<DiffView, WarpError> {
+        let (left, right) = diff_view::parse_sources(text)?;
+        DiffView::new(self.diff_side(left).await?, self.diff_side(right).await?)
+    }
+
+    // Relative paths are taken from the shell's directory, not warp's
+    async fn diff_side(&self, source: DiffSource) -> Result<DiffSide, WarpError> {
+        let block = match source {
+            DiffSource::File(path) => {
+                let path = self.shell_cwd().await?.join(path);
+                let text = tokio::fs::read_to_string(&path).await?;
+                return Ok(DiffSide::file(&path, text));
+            }
+            DiffSource::Block(back) => self
+                .ui
+                .lock()
+                .await
+                .recent_block_id(back - 1)
+                .ok_or_else(|| WarpError::not_found("block", format!("@{}", back)))?,
+            DiffSource::BlockId(id) => id,
+        };
+        let lines = self.ui.lock().await.block_lines(&block);
+        if lines.is_empty() {
+            return Err(WarpError::not_found("block", block));
+        }
+        Ok(DiffSide::block(&lines))
+    }
+
+    async fn connect(&self, name: &str) {
+        if let Err(e) = self.open_connection(name).await {
+            let context = i18n::t_args("connections.failed", &[("name", name)]);
+            let _ = self
+                .event_sender
+                .send(UIEvent::Error(e.context(context).render()));
+        }
+    }
+
+    // A saved connection's tab: its profile, then ssh and the startup
+    // commands typed in for the user
+    async fn open_connection(&self, name: &str) -> Result<(), WarpError> {
+        let connection = self
+            .shell_manager
+            .lock()
+            .await
+            .connection(name)
+            .cloned()
+            .ok_or_else(|| WarpError::not_found("connection", name))?;
+        let launch =
+            connections::plan(name, &connection, &self.ssh_config, self.shell_cwd().await?);
+        self.open_tab(launch.request).await?;
+        {
+            let mut ui = self.ui.lock().await;
+            ui.set_shell_label(Some(launch.label));
+            ui.set_accent(launch.accent);
+        }
+        if let Some(target) = launch.ssh {
+            let session = self.ssh_sessions.connect(target, launch.input).await?;
+            let pane = self.active_pane().await;
+            self.ssh_panes.lock().await.insert(session, pane);
+            self.set_pane_trust(pane, TrustLevel::Local).await;
+            return Ok(());
+        }
+        let mut pty = self.pty_manager.lock().await;
+        for line in &launch.input {
+            pty.write_input(&format!("{}\n", line)).await?;
+        }
+        Ok(())
+    }
+
+    // Alt+O: for tools whose output comes out as mojibake in UTF-8
+    async fn cycle_output_encoding(&self) {
+        let result = {
+            let pty = self.pty_manager.lock().await;
+            let Some(encoding) = pty.encoding().await.map(|encoding| encoding.next()) else {
+                return;
+            };
+            pty.set_encoding(encoding).await.map(|()| encoding)
+        };
+        let mut ui = self.ui.lock().await;
+        match result {
+            Ok(encoding) => {
+                ui.set_encoding(encoding);
+                ui.push_lines(vec![i18n::t_args(
+                    "shell.encoding",
+                    &[("encoding", encoding.label())],
+                )]);
+            }
+            Err(e) => ui.push_lines(e.render()),
+        }
+    }
+
+    async fn refresh_cloud(&self) {
+        let (contexts, view) = {
+            let detector = self.cloud.lock().await;
+            let view = self.cloud_panel.lock().await.as_mut().map(|panel| {
+                panel.refresh(&detector);
+                panel.view()
+            });
+            (detector.detect(), view)
+        };
+        let mut ui = self.ui.lock().await;
+        if self.cloud_config.enabled {
+            ui.set_cloud_contexts(contexts);
+        }
+        if let Some(view) = view {
+            ui.show_panel(view);
+        }
+    }
+
+    async fn toggle_runbooks_panel(&self) {
+        if self.close_if_open(PanelKind::Runbooks).await {
+            self.close_runbooks().await;
+            return;
+        }
+        let panel = RunbookPanel::new(runbook::discover(&self.runbook_config.directories).await);
+        let view = panel.view();
+        *self.runbooks.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Runbooks);
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // Closing the panel mid-run still leaves a report of what was done
+    async fn close_runbooks(&self) {
+        let Some(panel) = self.runbooks.lock().await.take() else {
+            return;
+        };
+        if let Some(run) = panel.run().filter(|run| !run.is_finished()) {
+            let event = runbook_report(run, &self.runbook_config.report_directory).await;
+            let _ = self.event_sender.send(event);
+        }
+    }
+
+    async fn handle_runbook_command(
+        &self,
+        command: RunbookCommand,
+        view: PanelView,
+    ) -> Result<(), WarpError> {
+        match command {
+            RunbookCommand::None => {}
+            RunbookCommand::Redraw => self.ui.lock().await.show_panel(view),
+            RunbookCommand::Prompt(label) => {
+                let mut ui = self.ui.lock().await;
+                ui.show_panel(view);
+                ui.begin_prompt(PanelKind::Runbooks, label);
+            }
+            RunbookCommand::Run {
+                step,
+                command,
+                timeout,
+            } => {
+                self.ui.lock().await.show_panel(view);
+                self.spawn_runbook_step(step, command, timeout).await?;
+            }
+            RunbookCommand::Finished => {
+                self.ui.lock().await.show_panel(view);
+                let event = match self
+                    .runbooks
+                    .lock()
+                    .await
+                    .as_ref()
+                    .and_then(|panel| panel.run())
+                {
+                    Some(run) => runbook_report(run, &self.runbook_config.report_directory).await,
+                    None => return Ok(()),
+                };
+                let _ = self.event_sender.send(event);
+            }
+            RunbookCommand::Report => {
+                let status = match self
+                    .runbooks
+                    .lock()
+                    .await
+                    .as_ref()
+                    .and_then(|panel| panel.run())
+                {
+                    Some(run) => match run
+                        .write_report(&self.runbook_config.report_directory)
+                        .await
+                    {
+                        Ok(path) => i18n::t_args(
+                            "runbook.report_written",
+                            &[("path", &path.display().to_string())],
+                        ),
+                        Err(e) => e.to_string(),
+                    },
+                    None => return Ok(()),
+                };
+                self.set_runbook_status(Some(status)).await;
+            }
+            RunbookCommand::Edit(path) => {
+                self.open_in_editor(&path, 1, None).await?;
+                self.rescan_runbooks().await;
+            }
+            RunbookCommand::Rescan => self.rescan_runbooks().await,
+        }
+        Ok(())
+    }
+
+    async fn rescan_runbooks(&self) {
+        let entries = runbook::discover(&self.runbook_config.directories).await;
+        let view = match self.runbooks.lock().await.as_mut() {
+            Some(panel) => {
+                panel.set_entries(entries);
+                panel.view()
+            }
+            None => return,
+        };
+        self.ui.lock().await.show_panel(view);
+    }
+
+    async fn set_runbook_status(&self, status: Option<String>) {
+        let view = match self.runbooks.lock().await.as_mut() {
+            Some(panel) => {
+                panel.set_status(status);
+                panel.view()
+            }
+            None => return,
+        };
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // A step runs off the event loop in a fresh shell in the shell's current
+    // directory. Its output is added to the scrollback as a block of its own,
+    // whose link becomes the step's evidence.
+    async fn spawn_runbook_step(
+        &self,
+        step: usize,
+        command: String,
+        timeout: Option<Duration>,
+    ) -> Result<(), WarpError> {
+        let directory = self.shell_cwd().await?;
+        let shell = self.config.lock().await.terminal.shell.clone();
+        let timeout = timeout.unwrap_or(Duration::from_secs(self.runbook_config.step_timeout_secs));
+        let report_directory = self.runbook_config.report_directory.clone();
+        let panel = self.runbooks.clone();
+        let ui = self.ui.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+
+        tokio::spawn(async move {
+            let result = notebook::run_cell(&shell, &directory, &command, timeout).await;
+            let evidence = match &result {
+                Ok(result) => {
+                    let lines = result
+                        .stdout
+                        .lines()
+                        .chain(result.stderr.lines())
+                        .map(str::to_string)
+                        .collect();
+                    let id = ui.lock().await.push_block(&command, lines);
+                    Some(permalinks::block_link(&id))
+                }
+                Err(_) => None,
+            };
+            let mut guard = panel.lock().await;
+            let Some(panel) = guard.as_mut() else {
+                return;
+            };
+            let next =
+                panel.finish_step(step, result.as_ref().map_err(|e| e.to_string()), evidence);
+            // A step finishing after Esc must not reopen the panel
+            if *open_panel.lock().unwrap() == Some(PanelKind::Runbooks) {
+                ui.lock().await.show_panel(panel.view());
+            }
+            if let (RunbookCommand::Finished, Some(run)) = (next, panel.run()) {
+                let _ = event_sender.send(runbook_report(run, &report_directory).await);
+            }
+        });
+        Ok(())
+    }
+
+    // `@` commands, and those a trusted project's policy contains, run in a
+    // throwaway container with the shell's directory mounted. The output
+    // goes into the command's block when the container exits.
+    async fn run_in_container(&self, command: &str) -> Result<(), WarpError> {
+        let directory = self.shell_cwd().await?;
+        let policy =
+            match workspace_trust::settings_for(&*self.workspace_trust.lock().await, &directory) {
+                Some(project) if project.defaults.container.is_some() => project.defaults.container,
+                _ => {
+                    self.shell_manager
+                        .lock()
+                        .await
+                        .project_defaults(&directory)
+                        .container
+                }
+            };
+        let Some(run) = container::plan(command, &self.docker_config, policy.as_ref(), &directory)?
+        else {
+            return Ok(());
+        };
+        self.ui.lock().await.push_lines(vec![i18n::t_args(
+            "container.starting",
+            &[("image", &run.image), ("runtime", &run.runtime)],
+        )]);
+        let shell = self.config.lock().await.terminal.shell.clone();
+        let timeout = Duration::from_secs(self.docker_config.run.timeout_secs);
+        let ui = self.ui.clone();
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            match container::run(&run, &shell, timeout).await {
+                Ok(result) => ui.lock().await.push_lines(container::result_lines(&result)),
+                Err(e) => {
+                    let _ = event_sender.send(UIEvent::Error(
+                        e.context(i18n::t("container.failed")).render(),
+                    ));
+                }
+            }
+        });
+        Ok(())
+    }
+
+    // Markdown runbooks are imported and saved alongside as .warpnb
+    async fn open_notebook(&self, path: &Path) -> Result<(), WarpError> {
+        let notebook = Notebook::load(path).await?;
+        let path = if notebook::is_notebook(path) {
+            path.to_path_buf()
+        } else {
+            let target = path.with_extension(notebook::EXTENSION);
+            notebook.save(&target).await?;
+            target
+        };
+        let panel = NotebookPanel::new(path, notebook);
+        let view = panel.view();
+        *self.notebook.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Notebook);
+        self.ui.lock().await.show_panel(view);
+        self.check_notebook_cells().await;
+        Ok(())
+    }
+
+    // The prompt's script goes to its language's server; with an offset,
+    // Tab wants completions there
+    async fn check_script(&self, text: String, offset: Option<usize>) -> Result<(), WarpError> {
+        let Some(requests) = self.script_servers.lock().unwrap().clone() else {
+            return Ok(());
+        };
+        let document = Document {
+            uri: lsp::PROMPT_URI.to_string(),
+            language: lsp::language_of(&text),
+            root: self.shell_cwd().await?,
+            text,
+        };
+        let _ = requests.send(match offset {
+            Some(offset) => LspRequest::Complete(document, offset),
+            None => LspRequest::Update(document),
+        });
+        Ok(())
+    }
+
+    // Command cells are checked when a notebook is opened or edited; what
+    // the server finds shows under the selected cell
+    async fn check_notebook_cells(&self) {
+        let Some(requests) = self.script_servers.lock().unwrap().clone() else {
+            return;
+        };
+        let guard = self.notebook.lock().await;
+        let Some(panel) = guard.as_ref() else {
+            return;
+        };
+        let root = panel
+            .path()
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let notebook = panel.notebook();
+        for (index, command) in
+            (0..notebook.cells.len()).filter_map(|index| Some((index, notebook.command(index)?)))
+        {
+            let _ = requests.send(LspRequest::Update(Document {
+                uri: lsp::notebook_uri(index),
+                language: lsp::language_of(command),
+                root: root.clone(),
+                text: command.to_string(),
+            }));
+        }
+    }
+
+    async fn handle_lsp_event(&self, event: LspEvent) {
+        match event {
+            LspEvent::Diagnostics { uri, diagnostics } if uri == lsp::PROMPT_URI => {
+                self.ui.lock().await.set_script_diagnostics(diagnostics);
+            }
+            LspEvent::Diagnostics { uri, diagnostics } => {
+                let Some(index) = lsp::notebook_cell(&uri) else {
+                    return;
+                };
+                let view = match self.notebook.lock().await.as_mut() {
+                    Some(panel) => {
+                        panel.set_diagnostics(index, diagnostics);
+                        panel.view()
+                    }
+                    None => return,
+                };
+                if *self.open_panel.lock().unwrap() == Some(PanelKind::Notebook) {
+                    self.ui.lock().await.show_panel(view);
+                }
+            }
+            LspEvent::Completions { uri, items } => {
+                if uri == lsp::PROMPT_URI {
+                    self.ui.lock().await.complete_script(&items);
+                }
+            }
+            LspEvent::Failed { language, error } => {
+                let text =
+                    i18n::t_args("lsp.failed", &[("language", &language), ("error", &error)]);
+                self.ui.lock().await.show_toast(
+                    text,
+                    crossterm::style::Color::Red,
+                    Duration::from_secs(5),
+                );
+            }
+        }
+    }
+
+    async fn set_notebook_status(&self, status: Option<String>) {
+        let view = match self.notebook.lock().await.as_mut() {
+            Some(panel) => {
+                panel.set_status(status);
+                panel.view()
+            }
+            None => return,
+        };
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // Runs the cells one after another off the event loop, saving the
+    // notebook after each so outputs survive a crash or a closed panel
+    async fn spawn_notebook_run(&self, cells: Vec<usize>) {
+        let Some(directory) = self
+            .notebook
+            .lock()
+            .await
+            .as_ref()
+            .map(|panel| notebook::directory(panel.path()))
+        else {
+            return;
+        };
+        let shell = self.config.lock().await.terminal.shell.clone();
+        let timeout = Duration::from_secs(self.notebook_config.cell_timeout_secs);
+        let stop_on_failure = self.notebook_config.stop_on_failure;
+        let panel = self.notebook.clone();
+        let ui = self.ui.clone();
+        let open_panel = self.open_panel.clone();
+        let event_sender = self.event_sender.clone();
+
+        tokio::spawn(async move {
+            let show = |view: PanelView| {
+                let ui = ui.clone();
+                let open_panel = open_panel.clone();
+                async move {
+                    // A run finishing after Esc must not reopen the panel
+                    if *open_panel.lock().unwrap() == Some(PanelKind::Notebook) {
+                        ui.lock().await.show_panel(view);
+                    }
+                }
+            };
+            let (mut ran, mut failed) = (0, None);
+            for index in cells {
+                let command = {
+                    let mut guard = panel.lock().await;
+                    let Some(panel) = guard.as_mut() else {
+                        return;
+                    };
+                    panel.set_running(Some(index));
+                    panel.set_status(None);
+                    show(panel.view()).await;
+                    panel.notebook().command(index).map(str::to_string)
+                };
+                let Some(command) = command else {
+                    continue;
+                };
+                let result = notebook::run_cell(&shell, &directory, &command, timeout).await;
+                let mut guard = panel.lock().await;
+                let Some(panel) = guard.as_mut() else {
+                    return;
+                };
+                let succeeded = match result {
+                    Ok(result) => {
+                        let succeeded = result.exit_code == Some(0);
+                        panel.set_output(index, result);
+                        ran += 1;
+                        if let Err(e) = panel.notebook().save(panel.path()).await {
+                            panel.set_status(Some(e.to_string()));
+                        }
+                        succeeded
+                    }
+                    Err(e) => {
+                        panel.set_status(Some(e.to_string()));
+                        false
+                    }
+                };
+                if !succeeded {
+                    failed = Some(command);
+                    if stop_on_failure {
+                        break;
+                    }
+                }
+            }
+            let view = {
+                let mut guard = panel.lock().await;
+                let Some(panel) = guard.as_mut() else {
+                    return;
+                };
+                panel.set_running(None);
+                panel.view()
+            };
+            show(view).await;
+            let (level, title) = match failed {
+                None => (
+                    NotificationLevel::Success,
+                    i18n::t_args("notebook.finished", &[("count", &ran.to_string())]),
+                ),
+                Some(command) => (
+                    NotificationLevel::Error,
+                    i18n::t_args("notebook.failed", &[("command", &command)]),
+                ),
+            };
+            let _ = event_sender.send(UIEvent::Notify {
+                category: NotificationCategory::Jobs,
+                level,
+                title,
+                body: None,
+            });
+        });
+    }
+
+    // Bells never reach the host terminal directly; this rings them the
+    // way the config and the pane's mute say
+    async fn ring_bell(&self) {
+        let pane = self.active_pane().await;
+        let command = self.last_command.lock().await.clone();
+        let focused = self.focused.load(Ordering::Relaxed);
+        let (ring, sound) = {
+            let mut policy = self.bell_policy.lock().await;
+            let ring = policy.ring(pane, command.as_deref(), focused, Instant::now());
+            (ring, policy.sound().map(Path::to_path_buf))
+        };
+        let Some(ring) = ring else {
+            return;
+        };
+        if let Some(duration) = ring.flash {
+            if let Err(e) = self.ui.lock().await.visual_bell(duration).await {
+                log::debug!("Visual bell failed: {}", e);
+            }
+        }
+        if ring.sound {
+            if let Err(e) = bell::play(sound.as_deref()) {
+                log::debug!("Bell sound failed: {}", e);
+            }
+        }
+        if ring.notify {
+            let title = match &command {
+                Some(command) => i18n::t_args("bell.notify_command", &[("command", command)]),
+                None => i18n::t("bell.notify"),
+            };
+            self.notify(
+                NotificationCategory::Alerts,
+                NotificationLevel::Info,
+                title,
+                None,
+            )
+            .await;
+        }
+    }
+
+    // One refresh of a status bar provider; a failing one just shows nothing
+    async fn status_segment(
+        provider: &StatusProvider,
+        pty_manager: &Mutex<PtyManager>,
+        plugin_host: Option<&PluginHost>,
+        system: &mut sysinfo::System,
+    ) -> Option<Segment> {
+        let shell_pid = pty_manager.lock().await.active_pid().await;
+        let segment = match provider {
+            StatusProvider::Git => match shell_pid.and_then(process_cwd) {
+                Some(cwd) => status_bar::git_segment(&cwd).await,
+                None => Ok(None),
+            },
+            StatusProvider::Kubernetes => status_bar::kube_segment().await,
+            StatusProvider::Time(format) => {
+                Ok(status_bar::time_segment(format, chrono::Local::now()))
+            }
+            StatusProvider::Jobs => {
+                system.refresh_processes();
+                let processes = process_tree::snapshot(system);
+                Ok(shell_pid.and_then(|pid| status_bar::jobs_segment(&processes, pid)))
+            }
+            StatusProvider::Plugin(name) => match plugin_host {
+                Some(host) => host
+                    .call_with_timeout(name, "status_segment", STATUS_PLUGIN_TIMEOUT)
+                    .await
+                    .map(|reply| status_bar::plugin_segment(&reply)),
+                None => Ok(None),
+            },
+        };
+        segment.unwrap_or_else(|e| {
+            log::debug!("Status segment {:?} failed: {}", provider, e);
+            None
+        })
+    }
+
+    async fn toggle_status_panel(&self) {
+        if self.close_if_open(PanelKind::StatusBar).await {
+            *self.status_panel.lock().await = None;
+            return;
+        }
+        let mut ui = self.ui.lock().await;
+        let panel = StatusPanel::new(ui.status_bar());
+        ui.show_panel(panel.view());
+        *self.status_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::StatusBar);
+    }
+
+    async fn run_status_command(&self, command: StatusCommand) -> Result<(), WarpError> {
+        if let StatusCommand::None = command {
+            return Ok(());
+        }
+        *self.status_panel.lock().await = None;
+        self.close_if_open(PanelKind::StatusBar).await;
+        match command {
+            StatusCommand::Run(StatusAction::Action(name)) => {
+                let action = AppAction::from_name(&name)
+                    .ok_or_else(|| WarpError::not_found("action", name))?;
+                let _ = self.event_sender.send(UIEvent::Action(action));
+            }
+            StatusCommand::Run(StatusAction::Command(command)) => {
+                self.ui.lock().await.run_command(command)
+            }
+            StatusCommand::Plugin(name) => {
+                let host = self
+                    .plugin_host
+                    .clone()
+                    .ok_or_else(|| WarpError::not_found("plugin", name.clone()))?;
+                let reply = host.call(&name, "status_click").await?;
+                if !reply.is_empty() {
+                    let lines = reply
+                        .lines()
+                        .map(|line| format!("[{}] {}", name, line))
+                        .collect();
+                    self.ui.lock().await.push_lines(lines);
+                }
+            }
+            StatusCommand::None => {}
+        }
+        Ok(())
+    }
+
+    async fn toggle_bell_mute(&self) {
+        let pane = self.active_pane().await;
+        let muted = self.bell_policy.lock().await.toggle_mute(pane);
+        let text = if muted {
+            i18n::t("bell.muted")
+        } else {
+            i18n::t("bell.unmuted")
+        };
+        self.ui.lock().await.show_toast(
+            text,
+            crossterm::style::Color::Cyan,
+            Duration::from_secs(3),
+        );
+    }
+
+    // Records a notification and shows it as a toast and on the desktop
+    // unless its category's filter or do-not-disturb holds it back
+    async fn notify(
+        &self,
+        category: NotificationCategory,
+        level: NotificationLevel,
+        title: String,
+        body: Option<String>,
+    ) {
+        let now = chrono::Local::now();
+        let (notification, delivery, toast_secs) = {
+            let mut center = self.notifications.lock().await;
+            let (notification, delivery) = center.notify(category, level, title, body, now);
+            (notification, delivery, center.config().toast_secs)
+        };
+        if delivery.os {
+            if let Err(e) = notifications::send_os_notification(
+                &notification.title,
+                notification.body.as_deref(),
+            ) {
+                log::debug!("Desktop notification failed: {}", e);
+            }
+        }
+        if delivery.toast {
+            self.ui.lock().await.show_toast(
+                format!(
+                    "{}: {}",
+                    notifications::category_name(category),
+                    notification.title
+                ),
+                notifications::level_color(level),
+                Duration::from_secs(toast_secs),
+            );
+        }
+        self.refresh_notifications_panel().await;
+    }
+
+    async fn refresh_notifications_panel(&self) {
+        let view = {
+            let center = self.notifications.lock().await;
+            let mut panel = self.notifications_panel.lock().await;
+            let Some(panel) = panel.as_mut() else {
+                return;
+            };
+            panel.refresh(&center, chrono::Local::now().time());
+            panel.view()
+        };
+        self.ui.lock().await.show_panel(view);
+    }
+
+    // Saves a validated change to the config file, then applies it to the
+    // running terminal where a live setting backs it. Returns the status
+    // line for the panel.
+    async fn apply_setting(&self, change: Change) -> String {
+        let key = change.path.join(".");
+        if let Err(e) =
+            settings::write_setting(&self.config_path, &change.path, &change.value).await
+        {
+            return e.to_string();
+        }
+        self.audit(AuditAction::ConfigChanged {
+            setting: key.clone(),
+            value: change.value.to_string(),
+        })
+        .await;
+        let mut live = {
+            let mut config = self.config.lock().await;
+            let mut value = serde_json::to_value(&*config).unwrap_or_default();
+            match value.pointer_mut(&settings::pointer(&change.path)) {
+                Some(slot) => {
+                    *slot = change.value.clone();
+                    match serde_json::from_value(value) {
+                        Ok(updated) => {
+                            *config = updated;
+                            true
+                        }
+                        Err(_) => false,
+                    }
+                }
+                None => false,
+            }
+        };
+        match key.as_str() {
+            "ui.locale" => live = i18n::init(change.config.ui.locale.as_deref()).is_ok(),
+            "ui.theme" => {
+                let _ = self
+                    .event_sender
+                    .send(UIEvent::ThemeChanged(change.config.ui.theme.clone()));
+            }
+            _ if change.path[0] == "output" => {
+                self.set_output_config(&change.config.output).await;
+                live = true;
+            }
+            "terminal.scrollback_lines" | "gpu.max_fps" => {
+                *self.pane_limits.lock().await = PaneLimits::from_config(&change.config);
+                self.apply_pane_tuning().await;
+                live = true;
+            }
+            _ => {}
+        }
+        if live {
+            i18n::t_args("settings.applied", &[("key", &key)])
+        } else {
+            i18n::t_args("settings.restart", &[("key", &key)])
+        }
+    }
+
+    async fn handle_settings_command(&self, command: SettingsCommand, view: PanelView) {
+        match command {
+            SettingsCommand::None => {}
+            SettingsCommand::Redraw => self.ui.lock().await.show_panel(view),
+            SettingsCommand::Prompt { label, current } => {
+                let mut ui = self.ui.lock().await;
+                ui.begin_prompt(PanelKind::Settings, label);
+                ui.insert_text(&current);
+            }
+            SettingsCommand::Apply(change) => {
+                let status = self.apply_setting(change).await;
+                let view = {
+                    let mut panel = self.settings.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return;
+                    };
+                    panel.set_status(Some(status));
+                    panel.view()
+                };
+                self.ui.lock().await.show_panel(view);
+            }
+        }
+    }
+
+    async fn scan_tasks(&self) -> Result<TasksPanel, WarpError> {
+        let cwd = self.shell_cwd().await?;
+        let mut cache = self.task_cache.lock().await;
+        let tasks = cache.tasks_for(&cwd).to_vec();
+        Ok(TasksPanel::new(cache.root().map(Path::to_path_buf), tasks))
+    }
+
+    async fn shell_cwd(&self) -> Result<PathBuf, WarpError> {
+        let pid = self.pty_manager.lock().await.active_pid().await;
+        match pid.and_then(process_cwd) {
+            Some(cwd) => Ok(cwd),
+            None => Ok(std::env::current_dir()?),
+        }
+    }
+
+    // GUI editors are started detached; terminal editors get the screen
+    // until they exit, then the UI is drawn again from scratch
+    async fn open_in_editor(
+        &self,
+        path: &Path,
+        line: u32,
+        column: Option<u32>,
+    ) -> Result<(), WarpError> {
+        let launch = editor::launch_for(path, line, column.unwrap_or(1), &self.editor_config)
+            .ok_or_else(|| WarpError::config_err(i18n::t("editor.no_command")))?;
+        let failed = |e: std::io::Error| {
+            WarpError::command_err(i18n::t_args(
+                "editor.failed",
+                &[("editor", &launch.program), ("error", &e.to_string())],
+            ))
+        };
+
+        if !launch.foreground {
+            tokio::process::Command::new(&launch.program)
+                .args(&launch.args)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .map_err(failed)?;
+            return Ok(());
+        }
+
+        self.run_foreground(launch.program.clone(), launch.args.clone())
+            .await?
+            .map_err(failed)?;
+        Ok(())
+    }
+
+    // Hands the terminal to `program` until it exits. The UI lock is held
+    // throughout so nothing draws over it.
+    async fn run_foreground(
+        &self,
+        program: String,
+        args: Vec<String>,
+    ) -> Result<std::io::Result<std::process::ExitStatus>, WarpError> {
+        let mut ui = self.ui.lock().await;
+        shutdown::restore_terminal();
+        let status = tokio::task::spawn_blocking(move || {
+            std::process::Command::new(program).args(args).status()
+        })
+        .await
+        .map_err(|e| WarpError::command_err(e.to_string()));
+        terminal::enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        stdout().execute(EnableBracketedPaste)?;
+        stdout().execute(EnableFocusChange)?;
+        ui.force_redraw()?;
+        status
+    }
+
+    // Opens the lines a truncated block kept on disk in $PAGER
+    async fn page_hidden_output(&self) -> Result<(), WarpError> {
+        let Some(path) = self.ui.lock().await.hidden_output() else {
+            self.ui
+                .lock()
+                .await
+                .push_lines(vec![i18n::t("output.nothing_hidden")]);
+            return Ok(());
+        };
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+        let mut words = pager.split_whitespace().map(str::to_string);
+        let program = words.next().unwrap_or_else(|| "less".to_string());
+        let mut args: Vec<String> = words.collect();
+        args.push(path.to_string_lossy().to_string());
+        self.run_foreground(program.clone(), args)
+            .await?
+            .map_err(|e| {
+                WarpError::command_err(i18n::t_args(
+                    "editor.failed",
+                    &[("editor", &program), ("error", &e.to_string())],
+                ))
+            })?;
+        Ok(())
+    }
+
+    // Puts a warp://block/ link to the current block on the clipboard
+    async fn copy_block_link(&self) -> Result<(), WarpError> {
+        let mut ui = self.ui.lock().await;
+        let Some(link) = ui.current_block_link() else {
+            ui.push_lines(vec![i18n::t("permalink.no_block")]);
+            return Ok(());
+        };
+        ports::copy_to_clipboard(&link)?;
+        ui.show_toast(
+            i18n::t_args("permalink.copied", &[("link", &link)]),
+            crossterm::style::Color::Green,
+            Duration::from_secs(3),
+        );
+        Ok(())
+    }
+
+    fn ports_view(watcher: &PortWatcher) -> PanelView {
+        let mut items: Vec<PanelItem> = watcher
+            .ports()
+            .iter()
+            .map(|port| port.display_line().into())
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.ports_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::Ports,
+            title: i18n::t("panel.ports"),
+            items,
+            footer: Some(i18n::t("panel.ports_keys")),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+
+    fn process_tree_view(monitor: &mut ProcessTreeMonitor) -> PanelView {
+        let mut items: Vec<PanelItem> = monitor
+            .refresh()
+            .iter()
+            .map(|node| node.display_line().into())
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.process_tree_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::ProcessTree,
+            title: i18n::t("panel.process_tree"),
+            items,
+            footer: Some(i18n::t("panel.process_tree_keys")),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+
+    async fn handle_panel_action(
+        &self,
+        kind: PanelKind,
+        key: char,
+        index: usize,
+    ) -> Result<(), WarpError> {
+        match kind {
+            PanelKind::ProcessTree => {
+                let Some(signal) = ProcessSignal::from_key(key) else {
+                    return Ok(());
+                };
+                let mut monitor = self.process_tree.lock().await;
+                let Some(pid) = monitor.tree().get(index).map(|node| node.info.pid) else {
+                    return Ok(());
+                };
+                monitor.send_signal(pid, signal)?;
+                let view = Self::process_tree_view(&mut monitor);
+                self.ui.lock().await.show_panel(view);
+            }
+            PanelKind::Ports => {
+                let Some(action) = PortAction::from_key(key) else {
+                    return Ok(());
+                };
+                self.port_watcher
+                    .lock()
+                    .await
+                    .perform(index, action)
+                    .await?;
+            }
+            PanelKind::Git => {
+                let (command, view, git) = {
+                    let mut panel = self.git_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index).await?;
+                    (command, panel.view(), panel.git().clone())
+                };
+                match command {
+                    GitCommand::None => {}
+                    GitCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    GitCommand::PromptCommitMessage => {
+                        self.ui
+                            .lock()
+                            .await
+                            .begin_prompt(PanelKind::Git, i18n::t("git.commit_prompt"));
+                    }
+                    GitCommand::Push => self.spawn_git_transfer(git, false),
+                    GitCommand::Pull => self.spawn_git_transfer(git, true),
+                    GitCommand::OpenForge => self.open_forge_panel().await?,
+                }
+            }
+            PanelKind::Forge => {
+                let (command, view, client) = {
+                    let mut panel = self.forge_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    if matches!(command, ForgeCommand::Refresh) {
+                        panel.set_loading();
+                    }
+                    (command, panel.view(), panel.client())
+                };
+                match command {
+                    ForgeCommand::None => {}
+                    ForgeCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    ForgeCommand::Refresh => {
+                        self.ui.lock().await.show_panel(view);
+                        self.spawn_forge_refresh(client);
+                    }
+                    ForgeCommand::OpenUrl(url) => ports::open_url(&url).await?,
+                    ForgeCommand::CopyUrl(url) => ports::copy_to_clipboard(&url)?,
+                    ForgeCommand::Checkout(pr) => self.spawn_review_checkout(&client, pr)?,
+                }
+            }
+            PanelKind::Files => {
+                let (command, view) = {
+                    let mut manager = self.file_manager.lock().await;
+                    let Some(manager) = manager.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = manager.handle_key(key, index).await;
+                    // A failed rename or delete still redraws: the listing may be stale
+                    (command, manager.view())
+                };
+                let mut ui = self.ui.lock().await;
+                match command? {
+                    FileCommand::None => {}
+                    FileCommand::Redraw => ui.show_panel(view),
+                    FileCommand::Prompt(label) => ui.begin_prompt(PanelKind::Files, label),
+                    FileCommand::InsertText(text) => ui.insert_text(&text),
+                    FileCommand::OpenNotebook(path) => {
+                        drop(ui);
+                        *self.file_manager.lock().await = None;
+                        self.open_notebook(&path).await?;
+                    }
+                }
+            }
+            PanelKind::Locations => {
+                let (command, view) = {
+                    let mut panel = self.locations.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    LocationCommand::None => {}
+                    LocationCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    LocationCommand::Rescan => {
+                        let lines = self.ui.lock().await.current_block();
+                        let view = {
+                            let mut panel = self.locations.lock().await;
+                            let Some(panel) = panel.as_mut() else {
+                                return Ok(());
+                            };
+                            panel.rescan(&lines);
+                            panel.view()
+                        };
+                        self.ui.lock().await.show_panel(view);
+                    }
+                    LocationCommand::Open(reference) => {
+                        self.open_in_editor(&reference.path, reference.line, reference.column)
+                            .await?
+                    }
+                }
+            }
+            PanelKind::Problems => {
+                let (command, view) = {
+                    let mut panel = self.problems.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    ProblemCommand::None => {}
+                    ProblemCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    ProblemCommand::Rescan => {
+                        let lines = self.ui.lock().await.current_block();
+                        let view = {
+                            let mut panel = self.problems.lock().await;
+                            let Some(panel) = panel.as_mut() else {
+                                return Ok(());
+                            };
+                            panel.rescan(&self.problem_parser, &lines);
+                            panel.view()
+                        };
+                        self.ui.lock().await.show_panel(view);
+                    }
+                    ProblemCommand::Open(problem) => {
+                        self.open_in_editor(&problem.path, problem.line, problem.column)
+                            .await?
+                    }
+                }
+            }
+            PanelKind::Tasks => {
+                let (command, view) = {
+                    let mut panel = self.tasks.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    TaskCommand::None => {}
+                    TaskCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    TaskCommand::Rescan => {
+                        let panel = self.scan_tasks().await?;
+                        let view = panel.view();
+                        *self.tasks.lock().await = Some(panel);
+                        self.ui.lock().await.show_panel(view);
+                    }
+                    TaskCommand::Run(command) => self.ui.lock().await.run_command(command),
+                    TaskCommand::Edit(command) => self.ui.lock().await.insert_text(&command),
+                }
+            }
+            PanelKind::Settings => {
+                let (command, view) = {
+                    let mut panel = self.settings.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                self.handle_settings_command(command, view).await;
+            }
+            PanelKind::Performance => {
+                let command = match self.memory_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                let status = match command {
+                    MemoryCommand::None => return Ok(()),
+                    MemoryCommand::Redraw => None,
+                    MemoryCommand::Dump => {
+                        let directory = self
+                            .memory_config
+                            .dump_directory
+                            .clone()
+                            .unwrap_or_else(std::env::temp_dir);
+                        Some(match self.memory.lock().await.dump(&directory) {
+                            Ok(path) => i18n::t_args(
+                                "memory.dumped",
+                                &[("path", &path.display().to_string())],
+                            ),
+                            Err(e) => e.to_string(),
+                        })
+                    }
+                };
+                let view = {
+                    let tracker = self.memory.lock().await;
+                    let mut panel = self.memory_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    panel.refresh(&tracker);
+                    if status.is_some() {
+                        panel.set_status(status);
+                    }
+                    panel.view()
+                };
+                self.ui.lock().await.show_panel(view);
+            }
+            PanelKind::Cloud => {
+                let (command, view) = {
+                    let mut panel = self.cloud_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    CloudCommand::None => {}
+                    CloudCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    CloudCommand::Refresh => self.refresh_cloud().await,
+                    // Run in the shell like a typed command, which is also
+                    // how an export reaches the detector
+                    CloudCommand::Switch(command) => self.ui.lock().await.run_command(command),
+                }
+            }
+            PanelKind::NewTab => {
+                let (command, view) = {
+                    let mut panel = self.new_tab.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    NewTabCommand::None => {}
+                    NewTabCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    NewTabCommand::Open(request) => {
+                        *self.new_tab.lock().await = None;
+                        self.close_if_open(PanelKind::NewTab).await;
+                        if let Err(e) = self.open_tab(request).await {
+                            let _ = self.event_sender.send(UIEvent::Error(
+                                e.context(i18n::t("shell.tab_failed")).render(),
+                            ));
+                        }
+                    }
+                }
+            }
+            PanelKind::Structured => {
+                let (command, view) = {
+                    let mut panel = self.structured.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    StructuredCommand::None => {}
+                    StructuredCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    StructuredCommand::Copy(json) => {
+                        ports::copy_to_clipboard(&json)?;
+                        self.ui.lock().await.show_toast(
+                            i18n::t("structured.copied"),
+                            crossterm::style::Color::Green,
+                            Duration::from_secs(3),
+                        );
+                    }
+                }
+            }
+            PanelKind::Telemetry => {
+                let command = match self.telemetry.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                match command {
+                    TelemetryCommand::None => {}
+                    TelemetryCommand::SetMode(mode) => {
+                        if let Err(e) = self.set_telemetry(mode).await {
+                            let _ = self.event_sender.send(UIEvent::Error(
+                                e.context(i18n::t("telemetry.save_failed")).render(),
+                            ));
+                        }
+                        self.refresh_telemetry_panel().await;
+                    }
+                    TelemetryCommand::Purge => {
+                        let count = self.privacy.purge().await;
+                        self.refresh_telemetry_panel().await;
+                        self.ui.lock().await.show_toast(
+                            i18n::t_args("telemetry.purged", &[("count", &count.to_string())]),
+                            crossterm::style::Color::Green,
+                            Duration::from_secs(3),
+                        );
+                    }
+                }
+            }
+            PanelKind::Replay => {
+                let (command, view) = match self.replay_panel.lock().await.as_mut() {
+                    Some(panel) => (panel.handle_key(key, index), panel.view()),
+                    None => return Ok(()),
+                };
+                match command {
+                    ReplayCommand::None => self.ui.lock().await.show_panel(view),
+                    ReplayCommand::Load(path) => {
+                        let replay = Replay::load(&path).await?;
+                        let view = match self.replay_panel.lock().await.as_mut() {
+                            Some(panel) => {
+                                panel.open(replay);
+                                panel.view()
+                            }
+                            None => return Ok(()),
+                        };
+                        self.ui.lock().await.show_panel(view);
+                    }
+                    ReplayCommand::Page(lines) => self.ui.lock().await.page_lines(&lines),
+                }
+            }
+            PanelKind::PinnedBlocks => {
+                let command = match self.pinned_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                match command {
+                    PinnedCommand::None => {}
+                    PinnedCommand::Open(target) => {
+                        self.close_if_open(PanelKind::PinnedBlocks).await;
+                        *self.pinned_panel.lock().await = None;
+                        self.open_marked_block(target).await;
+                    }
+                    PinnedCommand::FilterTag(tag) => {
+                        self.close_if_open(PanelKind::PinnedBlocks).await;
+                        *self.pinned_panel.lock().await = None;
+                        let blocks: Vec<(String, String)> = {
+                            let history = self.history_manager.lock().await;
+                            let tagged = history.tags().with_tag(&tag);
+                            tagged
+                                .into_iter()
+                                .map(|(block, marks)| (block.to_string(), marks.command.clone()))
+                                .collect()
+                        };
+                        self.ui.lock().await.show_blocks(&blocks);
+                    }
+                    PinnedCommand::Toggle(target, mark) => self.mark_block(&target, mark).await,
+                    PinnedCommand::Retag(target) => self.begin_tagging(target).await,
+                }
+            }
+            PanelKind::Search => {
+                let command = match self.search_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                match command {
+                    SearchCommand::None => {}
+                    SearchCommand::Prompt(label) => {
+                        self.ui.lock().await.begin_prompt(PanelKind::Search, label)
+                    }
+                    SearchCommand::Open(hit) => {
+                        self.close_if_open(PanelKind::Search).await;
+                        *self.search_panel.lock().await = None;
+                        self.ui
+                            .lock()
+                            .await
+                            .open_block(&hit.block, &hit.command, &hit.output);
+                    }
+                }
+            }
+            PanelKind::PluginActivity => {
+                let command = match self.plugin_activity.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                let Some(host) = &self.plugin_host else {
+                    return Ok(());
+                };
+                match command {
+                    ActivityCommand::None => return Ok(()),
+                    ActivityCommand::Suspend(name) => {
+                        host.set_suspended(&name, true).await;
+                    }
+                    ActivityCommand::Resume(name) => {
+                        host.set_suspended(&name, false).await;
+                    }
+                    ActivityCommand::KillAll => host.kill_all(),
+                    ActivityCommand::Revive => host.revive(),
+                }
+                self.refresh_activity_panel().await;
+            }
+            PanelKind::PluginPermissions => {
+                let command = match self.permissions_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                let Some(host) = &self.plugin_host else {
+                    return Ok(());
+                };
+                let bytes = {
+                    let permissions = host.permissions();
+                    let mut permissions = permissions.lock().unwrap();
+                    match &command {
+                        PermissionCommand::None => return Ok(()),
+                        PermissionCommand::Allow(request) => permissions.allow(request),
+                        PermissionCommand::Deny(request) => permissions.deny(request),
+                        PermissionCommand::Revoke(grant) => {
+                            permissions.revoke(&grant.plugin, &grant.capability)
+                        }
+                    }
+                    permissions.to_bytes()
+                };
+                match command {
+                    PermissionCommand::Allow(request) => {
+                        self.audit(AuditAction::PermissionGranted {
+                            plugin: request.plugin,
+                            capability: request.capability.label(),
+                        })
+                        .await
+                    }
+                    PermissionCommand::Revoke(grant) => {
+                        self.audit(AuditAction::PermissionRevoked {
+                            plugin: grant.plugin,
+                            capability: grant.capability.label(),
+                        })
+                        .await
+                    }
+                    _ => {}
+                }
+                if let Err(e) = Self::save_permissions(bytes).await {
+                    let _ = self.event_sender.send(UIEvent::Error(
+                        e.context(i18n::t("plugin_permissions.save_failed"))
+                            .render(),
+                    ));
+                }
+                self.refresh_permissions_panel().await;
+            }
+            PanelKind::WorkspaceTrust => {
+                let command = match self.trust_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                let bytes = {
+                    let mut store = self.workspace_trust.lock().await;
+                    match &command {
+                        TrustCommand::None => return Ok(()),
+                        TrustCommand::Trust(path) => store.trust(path),
+                        TrustCommand::Restrict(root) => store.restrict(root),
+                        TrustCommand::Revoke(path) => store.revoke(path),
+                    }
+                    store.to_bytes()
+                };
+                match command {
+                    TrustCommand::Trust(path) => {
+                        self.audit(AuditAction::WorkspaceTrusted { path }).await
+                    }
+                    TrustCommand::Revoke(path) => {
+                        self.audit(AuditAction::WorkspaceTrustRevoked { path })
+                            .await
+                    }
+                    _ => {}
+                }
+                if let Err(e) = Self::save_workspace_trust(bytes).await {
+                    let _ = self.event_sender.send(UIEvent::Error(
+                        e.context(i18n::t("workspace_trust.save_failed")).render(),
+                    ));
+                }
+                self.refresh_trust_panel().await;
+            }
+            PanelKind::PaneTuning => {
+                let (command, pane, root, view) = {
+                    let mut panel = self.tuning_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (
+                        command,
+                        panel.pane(),
+                        panel.root().to_path_buf(),
+                        panel.view(),
+                    )
+                };
+                match command {
+                    TuningCommand::None => return Ok(()),
+                    TuningCommand::Apply(overrides) => {
+                        self.pane_tuning.lock().await.insert(pane, overrides);
+                    }
+                    TuningCommand::SaveProject(tuning) => {
+                        let bytes = {
+                            let mut tunings = self.project_tunings.lock().await;
+                            tunings.set(&root, tuning);
+                            tunings.to_bytes()
+                        };
+                        if let Err(e) = Self::save_project_tunings(bytes).await {
+                            let _ = self.event_sender.send(UIEvent::Error(
+                                e.context(i18n::t("pane_tuning.save_failed")).render(),
+                            ));
+                        }
+                    }
+                }
+                self.apply_pane_tuning().await;
+                self.ui.lock().await.show_panel(view);
+            }
+            PanelKind::Broadcast => {
+                let (command, view) = {
+                    let mut panel = self.broadcast_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                self.handle_broadcast_command(command, view).await;
+            }
+            PanelKind::Connections => {
+                let command = {
+                    let mut panel = self.connections_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    panel.handle_key(key, index)
+                };
+                if let ConnectionCommand::Open(name) = command {
+                    *self.connections_panel.lock().await = None;
+                    self.close_if_open(PanelKind::Connections).await;
+                    self.connect(&name).await;
+                }
+            }
+            PanelKind::Transforms => {
+                let (command, view) = {
+                    let mut panel = self.transforms_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                if let TransformCommand::Redraw = command {
+                    self.ui.lock().await.show_panel(view);
+                }
+                self.apply_transform(command).await?;
+            }
+            PanelKind::StatusBar => {
+                let command = match self.status_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                self.run_status_command(command).await?;
+            }
+            PanelKind::DevConsole => {
+                let view = {
+                    let mut console = self.dev_console.lock().await;
+                    match console.handle_key(key, index) {
+                        ConsoleCommand::None => return Ok(()),
+                        ConsoleCommand::Redraw => console.view(),
+                    }
+                };
+                self.ui.lock().await.show_panel(view);
+            }
+            PanelKind::Traces => {
+                let view = {
+                    let mut tracer = self.block_tracer.lock().unwrap();
+                    match tracer.handle_key(key, index) {
+                        TraceCommand::None => return Ok(()),
+                        TraceCommand::Redraw => tracer.view(),
+                    }
+                };
+                self.ui.lock().await.show_panel(view);
+            }
+            PanelKind::Paste => {
+                let command = match self.paste_panel.lock().await.as_ref() {
+                    Some(panel) => panel.handle_key(key),
+                    None => return Ok(()),
+                };
+                if command == PasteCommand::None {
+                    return Ok(());
+                }
+                *self.paste_panel.lock().await = None;
+                self.close_if_open(PanelKind::Paste).await;
+                let mut ui = self.ui.lock().await;
+                match command {
+                    PasteCommand::Run(script) => ui.run_command(script),
+                    PasteCommand::Edit(script) => ui.insert_text(&script),
+                    PasteCommand::None => {}
+                }
+            }
+            PanelKind::Runbooks => {
+                let (command, view) = {
+                    let mut panel = self.runbooks.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                self.handle_runbook_command(command, view).await?;
+            }
+            PanelKind::Notebook => {
+                let (command, view) = {
+                    let mut panel = self.notebook.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    NotebookCommand::None => {}
+                    NotebookCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    NotebookCommand::Run(cells) => self.spawn_notebook_run(cells).await,
+                    NotebookCommand::Export => {
+                        let status = match self.notebook.lock().await.as_ref() {
+                            Some(panel) => match panel.notebook().export_html(panel.path()).await {
+                                Ok(path) => i18n::t_args(
+                                    "notebook.exported",
+                                    &[("path", &path.display().to_string())],
+                                ),
+                                Err(e) => e.to_string(),
+                            },
+                            None => return Ok(()),
+                        };
+                        self.set_notebook_status(Some(status)).await;
+                    }
+                    NotebookCommand::Edit(path) => {
+                        self.open_in_editor(&path, 1, None).await?;
+                        let notebook = Notebook::load(&path).await?;
+                        if let Some(panel) = self.notebook.lock().await.as_mut() {
+                            panel.set_notebook(notebook);
+                        }
+                        self.check_notebook_cells().await;
+                        self.set_notebook_status(None).await;
+                    }
+                    NotebookCommand::Save => {
+                        let status = match self.notebook.lock().await.as_ref() {
+                            Some(panel) => panel
+                                .notebook()
+                                .save(panel.path())
+                                .await
+                                .err()
+                                .map(|e| e.to_string()),
+                            None => return Ok(()),
+                        };
+                        self.set_notebook_status(status).await;
+                    }
+                }
+            }
+            PanelKind::Notifications => {
+                let command = match self.notifications_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                match command {
+                    NotificationCommand::None | NotificationCommand::Redraw => {}
+                    NotificationCommand::Clear => self.notifications.lock().await.clear(),
+                    NotificationCommand::ToggleDnd => {
+                        self.notifications
+                            .lock()
+                            .await
+                            .toggle_dnd(chrono::Local::now().time());
+                    }
+                }
+                if command != NotificationCommand::None {
+                    self.refresh_notifications_panel().await;
+                }
+            }
+            PanelKind::Debugger => {
+                let command = match self.debug_panel.lock().await.as_mut() {
+                    Some(panel) => panel.handle_key(key, index),
+                    None => return Ok(()),
+                };
+                self.run_debug_command(command).await?;
+            }
+            PanelKind::TestRunner => {
+                let (command, view) = {
+                    let mut panel = self.test_panel.lock().await;
+                    let Some(panel) = panel.as_mut() else {
+                        return Ok(());
+                    };
+                    let command = panel.handle_key(key, index);
+                    (command, panel.view())
+                };
+                match command {
+                    TestCommand::None => {}
+                    TestCommand::Redraw => self.ui.lock().await.show_panel(view),
+                    TestCommand::Rerun(names) => self.spawn_test_run(Some(names)).await,
+                }
+            }
+            PanelKind::Insights => {
+                if InsightsPanel::wants_refresh(key) {
+                    self.show_insights_panel().await;
+                }
+            }
+            // A diff opens in the pager, which handles its keys (hunks, side by
+            // side) itself; Diff only names the operands prompt, whose answer
+            // arrives as PanelInput
+            PanelKind::Diff => {}
+            // Only names the path prompt
+            PanelKind::Export => {}
+        }
+        Ok(())
+    }
+
+    // Replaces the git panel. The listing loads in the background; the panel
+    // shows a loading footer until it arrives.
+    async fn open_forge_panel(&self) -> Result<(), WarpError> {
+        let remote = detect_remote(&std::env::current_dir()?, &self.forge_config)?;
+        let secrets = self.secrets().await?;
+        let client = Arc::new(ForgeClient::new(remote, &secrets, &self.forge_config).await?);
+        let panel = ForgePanel::new(client.clone());
+        let view = panel.view();
 
-        result
+        *self.git_panel.lock().await = None;
+        *self.forge_panel.lock().await = Some(panel);
+        *self.open_panel.lock().unwrap() = Some(PanelKind::Forge);
+        self.ui.lock().await.show_panel(view);
+        self.spawn_forge_refresh(client);
+        Ok(())
     }
 
-    async fn start_background_tasks(&self) -> Result<(), WarpError> {
-        // Start PTY monitoring
-        let pty_manager = self.pty_manager.clone();
+    fn spawn_forge_refresh(&self, client: Arc<ForgeClient>) {
+        let forge_panel = self.forge_panel.clone();
         let event_sender = self.event_sender.clone();
+        let alerts = self.alerts.clone();
         tokio::spawn(async move {
-            if let Err(e) = Self::pty_monitor_task(pty_manager, event_sender).await {
-                log::error!("PTY monitor task failed: {}", e);
+            let result = client.snapshot().await;
+            let mut panel = forge_panel.lock().await;
+            // Closed while loading
+            let Some(panel) = panel.as_mut() else {
+                return;
+            };
+            if let Ok(snapshot) = &result {
+                for pr in snapshot.newly_failing(panel.snapshot()) {
+                    let number = pr.number.to_string();
+                    let title = i18n::t_args(
+                        "alerts.ci_failed",
+                        &[("number", &number), ("title", &pr.title)],
+                    );
+                    let mut alert = Alert::new(AlertSource::CI, NotificationLevel::Error, title);
+                    if let Some(url) = pr.ci.as_ref().and_then(|ci| ci.details_url.clone()) {
+                        alert = alert.with_body(url);
+                    }
+                    alerts.publish(alert);
+                }
             }
+            panel.apply(result);
+            let _ = event_sender.send(UIEvent::ShowPanel(panel.view()));
         });
+    }
 
-        // Start AI assistant background processing
-        let ai_assistant = self.ai_assistant.clone();
+    fn spawn_review_checkout(
+        &self,
+        client: &ForgeClient,
+        pr: PullRequest,
+    ) -> Result<(), WarpError> {
+        let git = GitManager::discover(&std::env::current_dir()?)?;
+        let remote = client.remote().clone();
+        let forge_panel = self.forge_panel.clone();
+        let event_sender = self.event_sender.clone();
         tokio::spawn(async move {
-            ai_assistant.start_background_processing().await;
-        });
+            let (progress, forward) = forward_progress(&event_sender, PanelKind::Forge);
+            let branch = pr.local_branch(remote.kind);
+            let result = git
+                .checkout_remote_ref(
+                    &remote.remote_name,
+                    &pr.fetch_ref(remote.kind),
+                    &branch,
+                    progress,
+                )
+                .await;
+            let _ = forward.await;
 
-        // Start plugin manager
-        self.plugin_manager.start().await?;
+            let event = match result {
+                Ok(()) => UIEvent::Notice(vec![i18n::t_args(
+                    "forge.checked_out",
+                    &[("number", &pr.number.to_string()), ("branch", &branch)],
+                )]),
+                Err(e) => UIEvent::Error(e.context(i18n::t("forge.checkout_failed")).render()),
+            };
+            let _ = event_sender.send(event);
+            // Puts the key help back in place of the progress footer
+            if let Some(panel) = forge_panel.lock().await.as_ref() {
+                let _ = event_sender.send(UIEvent::ShowPanel(panel.view()));
+            }
+        });
+        Ok(())
+    }
 
+    async fn commit_from_prompt(&self, message: &str) -> Result<(), WarpError> {
+        let mut panel = self.git_panel.lock().await;
+        let Some(panel) = panel.as_mut() else {
+            return Ok(());
+        };
+        let id = panel.git().commit(message).await?;
+        panel.refresh().await?;
+        let view = panel.view();
+        let summary = message.lines().next().unwrap_or_default();
+        let mut ui = self.ui.lock().await;
+        ui.push_lines(vec![i18n::t_args(
+            "git.committed",
+            &[("id", &id), ("summary", summary)],
+        )]);
+        ui.show_panel(view);
         Ok(())
     }
 
-    async fn pty_monitor_task(
-        pty_manager: Arc<Mutex<PtyManager>>,
-        event_sender: mpsc::UnboundedSender<UIEvent>,
-    ) -> Result<(), WarpError> {
-        loop {
-            let output = {
-                let mut pty = pty_manager.lock().await;
-                pty.read_output().await?
+    // Push and pull can take a while on slow remotes, so they run off the
+    // loop. Progress goes to the panel footer; completion asks the panel to
+    // refresh through its own 'r' binding.
+    fn spawn_git_transfer(&self, git: GitManager, pull: bool) {
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            let (progress, forward) = forward_progress(&event_sender, PanelKind::Git);
+
+            let result = if pull {
+                git.pull(progress).await.map(|outcome| match outcome {
+                    PullOutcome::UpToDate => i18n::t("git.up_to_date"),
+                    PullOutcome::FastForwarded => i18n::t("git.pulled"),
+                })
+            } else {
+                git.push(progress).await.map(|_| i18n::t("git.pushed"))
             };
+            let _ = forward.await;
 
-            if !output.is_empty() {
-                let _ = event_sender.send(UIEvent::PtyOutput(output));
-            }
+            let failed = if pull {
+                "git.pull_failed"
+            } else {
+                "git.push_failed"
+            };
+            let event = match result {
+                Ok(message) => UIEvent::Notice(vec![message]),
+                Err(e) => UIEvent::Error(e.context(i18n::t(failed)).render()),
+            };
+            let _ = event_sender.send(event);
+            let _ = event_sender.send(UIEvent::PanelAction {
+                kind: PanelKind::Git,
+                key: 'r',
+                index: 0,
+            });
+        });
+    }
 
-            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    // Offers the first install command the security policy doesn't deny.
+    // Running it still goes through the prompt's y/n confirmation.
+    fn install_hint(&self, missing: &str) -> Option<PromptHint> {
+        let suggestions = command_not_found::install_suggestions(missing);
+        if suggestions.is_empty() {
+            return None;
         }
+        let allowed = suggestions
+            .iter()
+            .find(|s| self.security.check_command(&s.command).action != PolicyAction::Deny);
+        Some(match allowed {
+            Some(suggestion) => PromptHint {
+                message: i18n::t_args(
+                    "cnf.install",
+                    &[("command", missing), ("install", &suggestion.command)],
+                ),
+                command: Some(suggestion.command.clone()),
+                confirm: true,
+            },
+            None => PromptHint {
+                message: i18n::t_args("cnf.blocked", &[("command", missing)]),
+                command: None,
+                confirm: true,
+            },
+        })
     }
 
-    async fn event_loop(&self) -> Result<(), WarpError> {
-        loop {
-            tokio::select! {
-                // Handle terminal events
-                event = event::read().await => {
-                    match event {
-                        Ok(evt) => {
-                            match evt {
-                                Event::Key(key_event) => {
-                                    if self.handle_key_event(key_event).await? {
-                                        break;
-                                    }
-                                }
-                                Event::Resize(width, height) => {
-                                    self.handle_resize(width, height).await?;
-                                }
-                                _ => {}
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Error reading event: {}", e);
-                        }
-                    }
-                }
-
-                // Handle UI events
-                ui_event = async {
-                    let mut receiver = self.event_receiver.lock().await;
-                    receiver.recv().await
-                } => {
-                    if let Some(event) = ui_event {
-                        self.handle_ui_event(event).await?;
-                    }
-                }
-            }
-
-            // Render UI
-            self.render().await?;
+    // A trusted typo in the last command, fixed, when auto-correct is on and
+    // the policy would run the result without asking
+    async fn auto_correction(&self, missing: &str) -> Option<String> {
+        if !self.correction.auto_correct {
+            return None;
         }
-
-        Ok(())
+        let fixed = self.correction.trusted.get(missing)?;
+        let line = self.last_command.lock().await.clone()?;
+        let command = correction::correct_command(&line, missing, fixed)?;
+        (self.security.check_command(&command).action == PolicyAction::Allow).then_some(command)
     }
 
-    async fn handle_key_event(&self, key_event: KeyEvent) -> Result<bool, WarpError> {
-        match key_event {
-            KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL,
-                ..
-            } => return Ok(true), // Exit
-
-            KeyEvent {
-                code: KeyCode::Char('d'),
-                modifiers: KeyModifiers::CONTROL,
-                ..
-            } => {
-                // Toggle debug mode
-                let mut config = self.config.lock().await;
-                config.debug.enabled = !config.debug.enabled;
-            }
+    // "Did you mean" for a mistyped command: the closest PATH binary, alias or
+    // history entry, runnable with Alt+Enter unless the policy denies it
+    async fn correction_hint(&self, missing: &str) -> Option<PromptHint> {
+        let history = correction::history_counts(self.history_manager.lock().await.commands());
+        let typo = missing.to_string();
+        let suggestions = tokio::task::spawn_blocking(move || correction::suggest(&typo, &history))
+            .await
+            .ok()?;
+        let fixed = suggestions.first()?;
+        let line = self.last_command.lock().await.clone();
+        let command = line
+            .and_then(|line| correction::correct_command(&line, missing, fixed))
+            .map(|command| {
+                let action = self.security.check_command(&command).action;
+                (command, action)
+            })
+            .filter(|(_, action)| *action != PolicyAction::Deny);
+        Some(match command {
+            Some((command, action)) => PromptHint {
+                message: i18n::t_args(
+                    "typo.run_corrected",
+                    &[("command", missing), ("corrected", &command)],
+                ),
+                command: Some(command),
+                confirm: action == PolicyAction::Confirm,
+            },
+            None => PromptHint {
+                message: i18n::t_args(
+                    "typo.did_you_mean",
+                    &[("command", missing), ("fixed", fixed)],
+                ),
+                command: None,
+                confirm: false,
+            },
+        })
+    }
 
-            _ => {
-                // Forward to UI
-                let mut ui = self.ui.lock().await;
-                ui.handle_key_event(key_event).await?;
+    // Each command is a block: close out the previous block's capture and
+    // report unexpected destinations, then start capturing the new one
+    async fn track_network_block(&self, command: &str) {
+        let Some(inspector) = &self.network_inspector else {
+            return;
+        };
+        let previous = self.network_block.lock().await.take();
+        if let Some(block_id) = previous {
+            if let Some(summary) = inspector.finish_block(&block_id).await {
+                log::debug!(
+                    "Network summary for '{}': {:?}",
+                    summary.command,
+                    summary.destinations
+                );
+                if summary.unexpected().next().is_some() {
+                    let mut lines = vec![i18n::t_args(
+                        "network.unexpected",
+                        &[("command", &summary.command)],
+                    )];
+                    lines.extend(summary.display_lines());
+                    self.ui.lock().await.push_lines(lines);
+                }
             }
         }
 
-        Ok(false)
+        if let Some(root_pid) = self.pty_manager.lock().await.active_pid().await {
+            let block_id = uuid::Uuid::new_v4().to_string();
+            inspector.start_block(&block_id, command, root_pid).await;
+            *self.network_block.lock().await = Some(block_id);
+        }
     }
 
     async fn handle_resize(&self, width: u16, height: u16) -> Result<(), WarpError> {
-        let mut terminal = self.terminal.lock().await;
-        terminal.resize(width, height).await?;
-
         let mut ui = self.ui.lock().await;
         ui.resize(width, height).await?;
 
         Ok(())
     }
 
+    // A failing event handler is reported in the UI rather than tearing down
+    // the loop; only an error while showing the error is fatal
+    async fn dispatch_ui_event(&self, event: UIEvent) -> Result<(), WarpError> {
+        if let Err(e) = self.handle_ui_event(event).await {
+            log::error!("{:?}", e);
+            let mut ui = self.ui.lock().await;
+            ui.show_error(&e).await?;
+        }
+        Ok(())
+    }
+
     async fn handle_ui_event(&self, event: UIEvent) -> Result<(), WarpError> {
         match event {
             UIEvent::PtyOutput(output) => {
+                // Filtered before anything else so a dangerous sequence is
+                // never half-passed when output is throttled
+                let pane = self.active_pane().await;
+                let output = self
+                    .filter_escapes(pane, &output)
+                    .instrument(tracing::trace_span!("parse"))
+                    .await;
+                let (output, bells) = self.bell_detector.lock().await.strip(&output);
+                if bells > 0 {
+                    self.ring_bell().await;
+                }
+                if output.is_empty() {
+                    return Ok(());
+                }
+                self.activity
+                    .lock()
+                    .await
+                    .output(pane, &output, true, Instant::now());
+                // A throttled command keeps running but most of its output is
+                // dropped before it reaches the UI
+                let (keep, violation) = match self.watched.lock().await.as_mut() {
+                    Some(watched) => {
+                        let (keep, violation) = watched.record_output(output.len(), Instant::now());
+                        (
+                            keep,
+                            violation.map(|violation| {
+                                Self::violation_event(&violation, &watched.command)
+                            }),
+                        )
+                    }
+                    None => (true, None),
+                };
+                if let Some(UIEvent::Notify {
+                    category,
+                    level,
+                    title,
+                    body,
+                }) = violation
+                {
+                    self.notify(category, level, title, body).await;
+                }
+                if !keep {
+                    return Ok(());
+                }
+                let mut hint = None;
+                let mut corrected = None;
+                if let Some(missing) = command_not_found::detect(&output) {
+                    async {
+                        corrected = self.auto_correction(&missing).await;
+                        if corrected.is_none() && self.install_suggestions {
+                            hint = self.install_hint(&missing);
+                        }
+                        if corrected.is_none() && hint.is_none() && self.correction.suggestions {
+                            hint = self.correction_hint(&missing).await;
+                        }
+                    }
+                    .instrument(tracing::trace_span!("ai_hooks"))
+                    .await;
+                }
+                if let Some(recorder) = self.recorder.lock().await.as_ref() {
+                    recorder.output(&output);
+                }
+                if let Some(block) = self.search_block.lock().await.as_mut() {
+                    block.touch();
+                }
                 let mut ui = self.ui.lock().await;
-                ui.append_output(output).await?;
+                ui.append_output(output)
+                    .instrument(tracing::trace_span!("parse"))
+                    .await?;
+                if let Some(command) = corrected {
+                    ui.push_lines(vec![i18n::t_args(
+                        "typo.auto_corrected",
+                        &[("command", &command)],
+                    )]);
+                    ui.run_command(command);
+                } else if hint.is_some() {
+                    ui.set_hint(hint);
+                }
             }
             UIEvent::CommandExecuted(command) => {
+                let block_id = self.ui.lock().await.current_block_id().unwrap_or_default();
+                self.block_tracer
+                    .lock()
+                    .unwrap()
+                    .start_block(&block_id, &command);
+                // A supervised SSH tab runs the command on the remote host
+                if let Some(session) = self.active_ssh_session().await {
+                    if let Err(e) = self
+                        .ssh_sessions
+                        .write_input(&session, &format!("{}\n", command))
+                        .await
+                    {
+                        let _ = self.event_sender.send(UIEvent::Error(
+                            e.context(i18n::t("ssh.input_failed")).render(),
+                        ));
+                    }
+                } else if let Err(e) = self.run_in_container(&command).await {
+                    let _ = self.event_sender.send(UIEvent::Error(
+                        e.context(i18n::t("container.failed")).render(),
+                    ));
+                }
+                async {
+                    self.check_workspace().await;
+                    self.audit_command(&command).await;
+                    let pane = self.active_pane().await;
+                    self.set_pane_trust(pane, self.security.trust_for_command(&command))
+                        .await;
+                    if let Some(watchdog) = &self.watchdog {
+                        let cwd = self.shell_cwd().await?;
+                        *self.watched.lock().await =
+                            Some(watchdog.watch(&command, &cwd, Instant::now()));
+                    }
+                    self.track_network_block(&command).await;
+                    Ok::<_, WarpError>(())
+                }
+                .instrument(tracing::trace_span!("spawn"))
+                .await?;
+                self.dispatch_to_plugins(&command)
+                    .instrument(tracing::trace_span!("ai_hooks"))
+                    .await;
+                if self.cloud.lock().await.observe_command(&command) {
+                    self.refresh_cloud().await;
+                }
+                self.finish_search_block(Some(&command)).await;
+                if let Some(recorder) = self.recorder.lock().await.as_ref() {
+                    recorder.command(&command);
+                }
+                *self.last_command.lock().await = Some(command.clone());
                 let mut history = self.history_manager.lock().await;
-                history.add_command(command).await?;
+                history
+                    .add_command(command)
+                    .instrument(tracing::trace_span!("history_persist"))
+                    .await?;
+            }
+            UIEvent::SshStateChanged(session_id, state) => {
+                // Other tabs' sessions only matter once they're switched to
+                let pane = self.ssh_panes.lock().await.get(&session_id).copied();
+                if pane == Some(self.active_pane().await) {
+                    self.ui.lock().await.set_ssh_status(state).await?;
+                }
             }
             UIEvent::AIQuery(query) => {
-                let response = self.ai_assistant.process_query(&query).await?;
+                // Run off the loop; the response arrives later as UIEvent::AIResponse
+                let ai_assistant = self.ai_assistant.clone();
+                let event_sender = self.event_sender.clone();
+                tokio::spawn(async move {
+                    let response = match ai_assistant.get().await {
+                        Ok(ai_assistant) => ai_assistant.process_query(&query).await,
+                        Err(e) => Err(e),
+                    };
+                    let event = match response {
+                        Ok(response) => UIEvent::AIResponse(response.content),
+                        Err(e) => UIEvent::Error(e.context("AI request failed").render()),
+                    };
+                    let _ = event_sender.send(event);
+                });
+            }
+            UIEvent::AIResponse(response) => {
                 let mut ui = self.ui.lock().await;
                 ui.show_ai_response(response).await?;
             }
+            UIEvent::Error(lines) => {
+                let mut ui = self.ui.lock().await;
+                ui.show_error_lines(lines).await?;
+            }
+            UIEvent::ShowPanel(view) => {
+                let mut ui = self.ui.lock().await;
+                // A refresh racing with Esc must not reopen the panel
+                if *self.open_panel.lock().unwrap() == Some(view.kind) {
+                    ui.show_panel(view);
+                }
+            }
+            UIEvent::PanelClosed(kind) => {
+                {
+                    let mut open = self.open_panel.lock().unwrap();
+                    if *open == Some(kind) {
+                        *open = None;
+                    }
+                }
+                match kind {
+                    PanelKind::Git => *self.git_panel.lock().await = None,
+                    PanelKind::Forge => *self.forge_panel.lock().await = None,
+                    PanelKind::Files => *self.file_manager.lock().await = None,
+                    PanelKind::Locations => *self.locations.lock().await = None,
+                    PanelKind::Problems => *self.problems.lock().await = None,
+                    PanelKind::Tasks => *self.tasks.lock().await = None,
+                    PanelKind::Settings => *self.settings.lock().await = None,
+                    PanelKind::Notifications => *self.notifications_panel.lock().await = None,
+                    PanelKind::Performance => *self.memory_panel.lock().await = None,
+                    PanelKind::Notebook => *self.notebook.lock().await = None,
+                    PanelKind::Runbooks => self.close_runbooks().await,
+                    PanelKind::Cloud => *self.cloud_panel.lock().await = None,
+                    PanelKind::Paste => *self.paste_panel.lock().await = None,
+                    PanelKind::NewTab => *self.new_tab.lock().await = None,
+                    PanelKind::Structured => *self.structured.lock().await = None,
+                    PanelKind::Telemetry => *self.telemetry.lock().await = None,
+                    PanelKind::PluginPermissions => *self.permissions_panel.lock().await = None,
+                    PanelKind::PluginActivity => *self.plugin_activity.lock().await = None,
+                    PanelKind::Search => *self.search_panel.lock().await = None,
+                    PanelKind::PinnedBlocks => *self.pinned_panel.lock().await = None,
+                    PanelKind::Replay => *self.replay_panel.lock().await = None,
+                    PanelKind::WorkspaceTrust => *self.trust_panel.lock().await = None,
+                    PanelKind::PaneTuning => *self.tuning_panel.lock().await = None,
+                    PanelKind::Broadcast => *self.broadcast_panel.lock().await = None,
+                    PanelKind::Connections => *self.connections_panel.lock().await = None,
+                    PanelKind::Transforms => *self.transforms_panel.lock().await = None,
+                    PanelKind::StatusBar => *self.status_panel.lock().await = None,
+                    _ => {}
+                }
+            }
+            UIEvent::Notice(lines) => {
+                self.ui.lock().await.push_lines(lines);
+            }
+            UIEvent::Notify {
+                category,
+                level,
+                title,
+                body,
+            } => {
+                self.notify(category, level, title, body).await;
+            }
+            UIEvent::Action(action) => match action {
+                AppAction::ToggleProcessTree => self.toggle_process_panel().await,
+                AppAction::TogglePorts => self.toggle_ports_panel().await,
+                AppAction::ToggleGit => self.toggle_git_panel().await?,
+                AppAction::ToggleFiles => self.toggle_file_manager().await?,
+                AppAction::ToggleLocations => self.toggle_locations_panel().await?,
+                AppAction::ToggleProblems => self.toggle_problems_panel().await?,
+                AppAction::ToggleTasks => self.toggle_tasks_panel().await?,
+                AppAction::ToggleSettings => self.toggle_settings_panel().await?,
+                AppAction::ToggleNotifications => self.toggle_notifications_panel().await,
+                AppAction::TogglePerformance => self.toggle_performance_panel().await,
+                AppAction::TogglePager => self.ui.lock().await.toggle_pager(),
+                AppAction::CopyBlockLink => self.copy_block_link().await?,
+                AppAction::ToggleRunbooks => self.toggle_runbooks_panel().await,
+                AppAction::ToggleCloud => self.toggle_cloud_panel().await,
+                AppAction::NewTab => self.toggle_new_tab_panel().await?,
+                AppAction::ToggleTable => self.toggle_structured_panel().await,
+                AppAction::ToggleTelemetry => self.toggle_telemetry_panel().await,
+                AppAction::ToggleDevConsole => self.toggle_dev_console_panel().await,
+                AppAction::TogglePluginPermissions => self.toggle_permissions_panel().await,
+                AppAction::TogglePluginActivity => self.toggle_activity_panel().await,
+                AppAction::CycleOutputEncoding => self.cycle_output_encoding().await,
+                AppAction::ToggleSearch => self.toggle_search_panel().await,
+                AppAction::TagBlock => self.tag_current_block().await,
+                AppAction::TogglePinBlock => self.mark_current_block(Mark::Pinned).await,
+                AppAction::ToggleFavoriteBlock => self.mark_current_block(Mark::Favorite).await,
+                AppAction::TogglePinnedBlocks => self.toggle_pinned_panel().await,
+                AppAction::ToggleReplay => self.toggle_replay_panel().await?,
+                AppAction::ToggleWorkspaceTrust => self.toggle_trust_panel().await,
+                AppAction::ToggleTraces => self.toggle_traces_panel().await,
+                AppAction::ToggleDebugger => self.toggle_debugger_panel().await,
+                AppAction::ToggleTests => self.toggle_tests_panel().await,
+                AppAction::ToggleInsights => self.toggle_insights_panel().await,
+                AppAction::TogglePaneTuning => self.toggle_tuning_panel().await,
+                AppAction::ToggleBroadcast => self.toggle_broadcast_panel().await,
+                AppAction::ToggleConnections => self.toggle_connections_panel().await,
+                AppAction::ShowDiff => self.begin_diff().await,
+                AppAction::ExportHistory => self.begin_export().await,
+                AppAction::OpenMarketplace => self.open_marketplace().await,
+                AppAction::ToggleTransforms => self.toggle_transforms_panel().await,
+                AppAction::ToggleBellMute => self.toggle_bell_mute().await,
+                AppAction::ToggleStatusBar => self.toggle_status_panel().await,
+                AppAction::NextTab => self.next_tab(false).await?,
+                AppAction::NextActiveTab => self.next_tab(true).await?,
+                AppAction::ToggleTabMute => self.toggle_tab_mute().await,
+            },
+            UIEvent::PortStatus(summary) => {
+                self.ui.lock().await.set_port_status(summary);
+            }
+            UIEvent::StatusWidgets(widgets) => {
+                self.ui.lock().await.set_status_widgets(widgets);
+            }
+            UIEvent::StatusSegment(id, segment) => {
+                self.ui.lock().await.set_status_segment(id, segment);
+            }
+            UIEvent::BackgroundOutput(pane, text) => {
+                let text = self.filter_escapes(pane, &text).await;
+                if text.is_empty() {
+                    return Ok(());
+                }
+                let focus = self
+                    .activity
+                    .lock()
+                    .await
+                    .output(pane, &text, false, Instant::now());
+                self.tab_activity(focus.then_some(pane)).await?;
+            }
+            UIEvent::TabActivity { focus } => self.tab_activity(focus).await?,
+            UIEvent::ScriptEdited(text) => self.check_script(text, None).await?,
+            UIEvent::ScriptCompletion { text, offset } => {
+                self.check_script(text, Some(offset)).await?
+            }
+            UIEvent::Lsp(event) => self.handle_lsp_event(event).await,
+            UIEvent::Participants(participants) => {
+                self.ui.lock().await.set_participants(participants);
+            }
+            UIEvent::CloudContexts(contexts) => {
+                self.ui.lock().await.set_cloud_contexts(contexts);
+            }
+            UIEvent::Loading(status) => {
+                self.ui.lock().await.set_loading(status);
+            }
+            UIEvent::PanelAction { kind, key, index } => {
+                self.handle_panel_action(kind, key, index).await?;
+            }
+            UIEvent::PanelSelection { kind, index } => match kind {
+                PanelKind::Files => {
+                    let mut manager = self.file_manager.lock().await;
+                    if let Some(manager) = manager.as_mut() {
+                        manager.select(index).await;
+                        self.ui.lock().await.show_panel(manager.view());
+                    }
+                }
+                PanelKind::Locations => {
+                    let view = {
+                        let mut panel = self.locations.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Problems => {
+                    let view = {
+                        let mut panel = self.problems.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Tasks => {
+                    let view = {
+                        let mut panel = self.tasks.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Settings => {
+                    let view = {
+                        let mut panel = self.settings.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Performance => {
+                    let view = {
+                        let mut panel = self.memory_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Notifications => {
+                    let view = {
+                        let mut panel = self.notifications_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Notebook => {
+                    let view = {
+                        let mut panel = self.notebook.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Runbooks => {
+                    let view = {
+                        let mut panel = self.runbooks.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Cloud => {
+                    let view = {
+                        let mut panel = self.cloud_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::NewTab => {
+                    let view = {
+                        let mut panel = self.new_tab.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Structured => {
+                    let view = {
+                        let mut panel = self.structured.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Telemetry => {
+                    let view = {
+                        let mut panel = self.telemetry.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Replay => {
+                    let view = {
+                        let mut panel = self.replay_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::PinnedBlocks => {
+                    let view = {
+                        let mut panel = self.pinned_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Search => {
+                    let view = {
+                        let mut panel = self.search_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::PluginActivity => {
+                    let view = {
+                        let mut panel = self.plugin_activity.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::PluginPermissions => {
+                    let view = {
+                        let mut panel = self.permissions_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::WorkspaceTrust => {
+                    let view = {
+                        let mut panel = self.trust_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::PaneTuning => {
+                    let view = {
+                        let mut panel = self.tuning_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Broadcast => {
+                    let view = {
+                        let mut panel = self.broadcast_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Connections => {
+                    let view = {
+                        let mut panel = self.connections_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Transforms => {
+                    let view = {
+                        let mut panel = self.transforms_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::StatusBar => {
+                    let view = {
+                        let mut panel = self.status_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        panel.select(index);
+                        panel.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::DevConsole => {
+                    let view = {
+                        let mut console = self.dev_console.lock().await;
+                        console.select(index);
+                        console.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                PanelKind::Traces => {
+                    let view = {
+                        let mut tracer = self.block_tracer.lock().unwrap();
+                        tracer.select(index);
+                        tracer.view()
+                    };
+                    self.ui.lock().await.show_panel(view);
+                }
+                _ => {}
+            },
+            UIEvent::PanelInput { kind, text } => match kind {
+                PanelKind::Git => self.commit_from_prompt(&text).await?,
+                PanelKind::Files => {
+                    let mut manager = self.file_manager.lock().await;
+                    if let Some(manager) = manager.as_mut() {
+                        let result = manager.submit_input(&text).await;
+                        self.ui.lock().await.show_panel(manager.view());
+                        result?;
+                    }
+                }
+                PanelKind::Settings => {
+                    let (command, view) = {
+                        let mut panel = self.settings.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        let command = panel.submit_input(&text);
+                        (command, panel.view())
+                    };
+                    self.handle_settings_command(command, view).await;
+                }
+                PanelKind::Runbooks => {
+                    let (command, view) = {
+                        let mut panel = self.runbooks.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        let command = panel.submit_input(&text);
+                        (command, panel.view())
+                    };
+                    self.handle_runbook_command(command, view).await?;
+                }
+                PanelKind::Search => self.run_search(text).await,
+                PanelKind::PinnedBlocks => self.apply_tags(&text).await,
+                PanelKind::Diff => self.show_diff(&text).await,
+                PanelKind::Export => self.export_history(&text).await,
+                PanelKind::Debugger => {
+                    let command = match self.debug_panel.lock().await.as_mut() {
+                        Some(panel) => panel.submit_input(&text),
+                        None => return Ok(()),
+                    };
+                    self.run_debug_command(command).await?;
+                }
+                PanelKind::Broadcast => {
+                    let (command, view) = {
+                        let mut panel = self.broadcast_panel.lock().await;
+                        let Some(panel) = panel.as_mut() else {
+                            return Ok(());
+                        };
+                        let command = panel.submit_input(&text);
+                        (command, panel.view())
+                    };
+                    self.handle_broadcast_command(command, view).await;
+                }
+                _ => {}
+            },
+            UIEvent::PanelFooter { kind, footer } => {
+                self.ui.lock().await.set_panel_footer(kind, footer);
+            }
+            UIEvent::Timer(name) => {
+                log::debug!("Timer fired: {}", name);
+            }
             _ => {}
         }
 
@@ -239,6 +6421,9 @@ impl WarpApp {
 
     async fn render(&self) -> Result<(), WarpError> {
         let mut ui = self.ui.lock().await;
+        if let Some(marketplace) = self.marketplace_ui.lock().await.as_mut() {
+            return ui.draw_fullscreen(|f| marketplace.render(f));
+        }
         ui.render().await?;
         Ok(())
     }
@@ -262,7 +6447,34 @@ impl WarpApp {
             docker_context: None, // This would be detected
         };
 
-        self.advanced_ai.get_completions(context).await
+        // Tasks from the project's own files come before generic suggestions
+        let cwd = self.shell_cwd().await?;
+        let mut completions: Vec<CompletionItem> = {
+            let mut cache = self.task_cache.lock().await;
+            project_tasks::matching(
+                cache.tasks_for(&cwd),
+                input.get(..cursor_pos).unwrap_or(input),
+            )
+            .into_iter()
+            .map(|task| CompletionItem {
+                text: task.command.clone(),
+                display_text: task.command.clone(),
+                description: task.description.clone(),
+                completion_type: CompletionType::Command,
+                score: 1.0,
+                insert_text: task.command.clone(),
+                documentation: None,
+            })
+            .collect()
+        };
+        completions.extend(
+            self.advanced_ai
+                .get()
+                .await?
+                .get_completions(context)
+                .await?,
+        );
+        Ok(completions)
     }
 
     pub async fn get_smart_suggestions(
@@ -283,6 +6495,175 @@ impl WarpApp {
             docker_context: None,
         };
 
-        self.advanced_ai.get_smart_suggestions(context).await
+        self.advanced_ai
+            .get()
+            .await?
+            .get_smart_suggestions(context)
+            .await
+    }
+}
+
+// Shows git transfer progress in a panel's footer. The forwarder ends once
+// the operation drops its sender.
+fn forward_progress(
+    event_sender: &mpsc::UnboundedSender<UIEvent>,
+    kind: PanelKind,
+) -> (mpsc::UnboundedSender<GitProgress>, JoinHandle<()>) {
+    let (progress, mut updates) = mpsc::unbounded_channel::<GitProgress>();
+    let event_sender = event_sender.clone();
+    let forward = tokio::spawn(async move {
+        while let Some(update) = updates.recv().await {
+            let _ = event_sender.send(UIEvent::PanelFooter {
+                kind,
+                footer: Some(update.describe()),
+            });
+        }
+    });
+    (progress, forward)
+}
+
+// Writes the run's report and describes the outcome as a notification
+async fn runbook_report(run: &RunbookRun, directory: &Path) -> UIEvent {
+    let name = run.runbook.name.as_str();
+    let (level, title, body) = match run.write_report(directory).await {
+        Err(e) => (
+            NotificationLevel::Error,
+            i18n::t_args("runbook.report_failed", &[("name", name)]),
+            e.to_string(),
+        ),
+        Ok(path) => {
+            let body = i18n::t_args(
+                "runbook.report_written",
+                &[("path", &path.display().to_string())],
+            );
+            if run.succeeded() {
+                (
+                    NotificationLevel::Success,
+                    i18n::t_args("runbook.finished", &[("name", name)]),
+                    body,
+                )
+            } else {
+                (
+                    NotificationLevel::Warning,
+                    i18n::t_args("runbook.incomplete", &[("name", name)]),
+                    body,
+                )
+            }
+        }
+    };
+    UIEvent::Notify {
+        category: NotificationCategory::Jobs,
+        level,
+        title,
+        body: Some(body),
+    }
+}
+
+// What woke the event loop
+enum Wake {
+    Input(Option<std::io::Result<Event>>),
+    Ui(Option<UIEvent>),
+    Tick,
+}
+
+async fn next_wake<S>(
+    input: &mut S,
+    receiver: &mut mpsc::UnboundedReceiver<UIEvent>,
+    ticker: &mut tokio::time::Interval,
+) -> Wake
+where
+    S: futures::Stream<Item = std::io::Result<Event>> + Unpin,
+{
+    tokio::select! {
+        input = input.next() => Wake::Input(input),
+        ui_event = receiver.recv() => Wake::Ui(ui_event),
+        _ = ticker.tick() => Wake::Tick,
+    }
+}
+
+// Whatever else is queued, up to a frame's worth, so a burst of PTY output
+// becomes one frame. Stops at Quit and reports it.
+fn drain_queued(receiver: &mut mpsc::UnboundedReceiver<UIEvent>) -> (Vec<UIEvent>, bool) {
+    let mut queued = Vec::new();
+    while queued.len() < MAX_EVENTS_PER_FRAME {
+        match receiver.try_recv() {
+            Ok(UIEvent::Quit) => return (queued, true),
+            Ok(event) => queued.push(event),
+            Err(_) => break,
+        }
+    }
+    (queued, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn idle_ticker(period: Duration) -> tokio::time::Interval {
+        let mut ticker = tokio::time::interval(period);
+        // The first tick completes immediately
+        ticker.tick().await;
+        ticker
+    }
+
+    #[tokio::test]
+    async fn test_ui_events_wake_the_loop_without_input() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut input = futures::stream::pending();
+        let mut ticker = idle_ticker(Duration::from_secs(3600)).await;
+
+        let waiting = tokio::spawn(async move {
+            matches!(
+                next_wake(&mut input, &mut receiver, &mut ticker).await,
+                Wake::Ui(Some(UIEvent::Resize(80, 24)))
+            )
+        });
+        sender.send(UIEvent::Resize(80, 24)).unwrap();
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ticks_and_input_wake_the_loop() {
+        let (_sender, mut receiver) = mpsc::unbounded_channel();
+        let mut ticker = idle_ticker(Duration::from_millis(10)).await;
+        let mut idle = futures::stream::pending();
+        assert!(matches!(
+            next_wake(&mut idle, &mut receiver, &mut ticker).await,
+            Wake::Tick
+        ));
+
+        let mut ticker = idle_ticker(Duration::from_secs(3600)).await;
+        let mut input = futures::stream::iter(vec![Ok(Event::FocusGained)]);
+        assert!(matches!(
+            next_wake(&mut input, &mut receiver, &mut ticker).await,
+            Wake::Input(Some(Ok(Event::FocusGained)))
+        ));
+        assert!(matches!(
+            next_wake(&mut input, &mut receiver, &mut ticker).await,
+            Wake::Input(None)
+        ));
+    }
+
+    #[test]
+    fn test_drain_stops_at_quit_and_frame_limit() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        for event in [
+            UIEvent::Resize(1, 1),
+            UIEvent::Resize(2, 2),
+            UIEvent::Quit,
+            UIEvent::Resize(3, 3),
+        ] {
+            sender.send(event).unwrap();
+        }
+        let (queued, quit) = drain_queued(&mut receiver);
+        assert_eq!(queued.len(), 2);
+        assert!(quit);
+
+        for _ in 0..MAX_EVENTS_PER_FRAME + 5 {
+            sender.send(UIEvent::Resize(0, 0)).unwrap();
+        }
+        let (queued, quit) = drain_queued(&mut receiver);
+        assert_eq!(queued.len(), MAX_EVENTS_PER_FRAME);
+        assert!(!quit);
     }
 }
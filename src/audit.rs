@@ -0,0 +1,517 @@
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::compliance::{to_hex, VerificationReport, GENESIS_HASH};
+use crate::error::WarpError;
+
+// Programs that run the rest of the command line as another user
+const ELEVATORS: &[&str] = &["sudo", "doas", "pkexec", "run0"];
+
+// How much of the file is read per step when looking for the last record
+const TAIL_CHUNK: u64 = 4096;
+
+// A security-relevant thing the user did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AuditAction {
+    Sudo {
+        command: String,
+    },
+    // A command the command policy asks about or denies, run anyway
+    PolicyOverride {
+        command: String,
+        reason: String,
+    },
+    PermissionGranted {
+        plugin: String,
+        capability: String,
+    },
+    PermissionRevoked {
+        plugin: String,
+        capability: String,
+    },
+    ApiKeyCreated {
+        key_id: String,
+        name: String,
+        scopes: Vec<String>,
+    },
+    ApiKeyRevoked {
+        key_id: String,
+    },
+    ConfigChanged {
+        setting: String,
+        value: String,
+    },
+    WorkspaceTrusted {
+        path: PathBuf,
+    },
+    WorkspaceTrustRevoked {
+        path: PathBuf,
+    },
+}
+
+impl AuditAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuditAction::Sudo { .. } => "sudo",
+            AuditAction::PolicyOverride { .. } => "policy_override",
+            AuditAction::PermissionGranted { .. } => "permission_granted",
+            AuditAction::PermissionRevoked { .. } => "permission_revoked",
+            AuditAction::ApiKeyCreated { .. } => "api_key_created",
+            AuditAction::ApiKeyRevoked { .. } => "api_key_revoked",
+            AuditAction::ConfigChanged { .. } => "config_changed",
+            AuditAction::WorkspaceTrusted { .. } => "workspace_trusted",
+            AuditAction::WorkspaceTrustRevoked { .. } => "workspace_trust_revoked",
+        }
+    }
+
+    // One line for listings, and what text searches match against
+    pub fn summary(&self) -> String {
+        match self {
+            AuditAction::Sudo { command } => command.clone(),
+            AuditAction::PolicyOverride { command, reason } => format!("{} ({})", command, reason),
+            AuditAction::PermissionGranted { plugin, capability }
+            | AuditAction::PermissionRevoked { plugin, capability } => {
+                format!("{}: {}", plugin, capability)
+            }
+            AuditAction::ApiKeyCreated {
+                key_id,
+                name,
+                scopes,
+            } => {
+                format!("{} {} [{}]", key_id, name, scopes.join(", "))
+            }
+            AuditAction::ApiKeyRevoked { key_id } => key_id.clone(),
+            AuditAction::ConfigChanged { setting, value } => format!("{} = {}", setting, value),
+            AuditAction::WorkspaceTrusted { path }
+            | AuditAction::WorkspaceTrustRevoked { path } => path.display().to_string(),
+        }
+    }
+}
+
+// Records are hash-chained like the compliance logs, so an edited or
+// removed entry shows up in `verify`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub sequence: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub user: String,
+    pub session_id: String,
+    #[serde(flatten)]
+    pub action: AuditAction,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn compute_hash(&self) -> String {
+        let payload = format!(
+            "{}|{}|{}|{}|{}|{}",
+            self.sequence,
+            self.timestamp.to_rfc3339(),
+            self.user,
+            self.session_id,
+            serde_json::to_string(&self.action).unwrap_or_default(),
+            self.prev_hash
+        );
+        to_hex(digest(&SHA256, payload.as_bytes()).as_ref())
+    }
+}
+
+// The audit log file; one per user, appended to by every session. Each
+// append holds an exclusive lock on the file and links to whatever record
+// is last at that moment, so concurrent sessions keep a single chain.
+pub struct AuditLog {
+    path: PathBuf,
+    user: String,
+    session_id: String,
+}
+
+impl AuditLog {
+    pub fn default_path() -> Result<PathBuf, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        Ok(data_dir.join("warp").join("audit.jsonl"))
+    }
+
+    pub async fn open(path: &Path, session_id: &str) -> Result<Self, WarpError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let opened = path.to_path_buf();
+        tokio::task::spawn_blocking(move || Self::open_file(&opened))
+            .await
+            .map_err(|e| WarpError::command_err(e.to_string()))??;
+        Ok(Self {
+            path: path.to_path_buf(),
+            user: std::env::var("USER").unwrap_or_default(),
+            session_id: session_id.to_string(),
+        })
+    }
+
+    // Commands run with sudo end up in here, so only the owner may read it
+    fn open_file(path: &Path) -> Result<File, WarpError> {
+        let mut options = OpenOptions::new();
+        options.create(true).read(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        Ok(options.open(path)?)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn record(&self, action: AuditAction) -> Result<(), WarpError> {
+        let path = self.path.clone();
+        let record = AuditRecord {
+            sequence: 0,
+            timestamp: chrono::Utc::now(),
+            user: self.user.clone(),
+            session_id: self.session_id.clone(),
+            action,
+            prev_hash: String::new(),
+            hash: String::new(),
+        };
+        tokio::task::spawn_blocking(move || Self::append(&path, record))
+            .await
+            .map_err(|e| WarpError::command_err(e.to_string()))?
+    }
+
+    fn append(path: &Path, mut record: AuditRecord) -> Result<(), WarpError> {
+        let mut file = Self::open_file(path)?;
+        // Released when the file is closed
+        file.lock()?;
+        let (sequence, prev_hash) = match last_line(&mut file)? {
+            Some(line) => {
+                let last: AuditRecord = serde_json::from_str(&line)?;
+                (last.sequence + 1, last.hash)
+            }
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        record.sequence = sequence;
+        record.prev_hash = prev_hash;
+        record.hash = record.compute_hash();
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    // No file yet is an empty log
+    async fn content(path: &Path) -> Result<String, WarpError> {
+        match fs::read_to_string(path).await {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn read(path: &Path) -> Result<Vec<AuditRecord>, WarpError> {
+        Self::content(path)
+            .await?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    pub async fn verify(path: &Path) -> Result<VerificationReport, WarpError> {
+        let mut report = verify_chain(&Self::content(path).await?);
+        report.path = path.to_path_buf();
+        Ok(report)
+    }
+}
+
+// The last non-empty line, read backwards from the end so appending
+// doesn't cost a read of the whole log
+fn last_line(file: &mut File) -> std::io::Result<Option<String>> {
+    let mut position = file.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+    while position > 0 {
+        let step = position.min(TAIL_CHUNK);
+        position -= step;
+        let mut chunk = vec![0; step as usize];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+
+        let end = tail
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        if let Some(start) = tail[..end].iter().rposition(|&b| b == b'\n') {
+            return Ok(Some(
+                String::from_utf8_lossy(&tail[start + 1..end]).into_owned(),
+            ));
+        }
+    }
+    let line = String::from_utf8_lossy(&tail).trim().to_string();
+    Ok((!line.is_empty()).then_some(line))
+}
+
+pub fn verify_chain(content: &str) -> VerificationReport {
+    let mut report = VerificationReport {
+        path: PathBuf::new(),
+        records_checked: 0,
+        valid: true,
+        first_broken_sequence: None,
+        error: None,
+    };
+    let mut expected_prev = GENESIS_HASH.to_string();
+
+    for (index, line) in content.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+        let index = index as u64;
+        let problem = match serde_json::from_str::<AuditRecord>(line) {
+            Err(e) => Some(format!("Unparseable record: {}", e)),
+            Ok(record) if record.sequence != index => {
+                Some("sequence gap (record removed or reordered)".to_string())
+            }
+            Ok(record) if record.prev_hash != expected_prev => {
+                Some("chain link mismatch".to_string())
+            }
+            Ok(record) if record.compute_hash() != record.hash => {
+                Some("record contents modified".to_string())
+            }
+            Ok(record) => {
+                expected_prev = record.hash;
+                None
+            }
+        };
+        if let Some(problem) = problem {
+            report.valid = false;
+            report.first_broken_sequence = Some(index);
+            report.error = Some(problem);
+            return report;
+        }
+        report.records_checked += 1;
+    }
+
+    report
+}
+
+// Whether a command line runs anything with elevated privileges, in any
+// part of a pipeline or list
+pub fn is_elevated(command: &str) -> bool {
+    command
+        .split(['|', ';', '&'])
+        .filter_map(|part| {
+            // VAR=value prefixes set the environment for the program after them
+            part.split_whitespace().find(|word| !word.contains('='))
+        })
+        .any(|program| {
+            let name = program.rsplit('/').next().unwrap_or(program);
+            ELEVATORS.contains(&name)
+        })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub text: Option<String>,   // case-insensitive, in the summary or user
+    pub action: Option<String>, // AuditAction::kind
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let text = self.text.as_ref().map(|text| text.to_lowercase());
+        text.is_none_or(|text| {
+            record.action.summary().to_lowercase().contains(&text)
+                || record.user.to_lowercase().contains(&text)
+        }) && self
+            .action
+            .as_deref()
+            .is_none_or(|kind| record.action.kind() == kind)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+pub fn search<'a>(records: &'a [AuditRecord], query: &AuditQuery) -> Vec<&'a AuditRecord> {
+    records
+        .iter()
+        .filter(|record| query.matches(record))
+        .collect()
+}
+
+// Rows for the export module's AuditLog data source
+pub fn export_rows(records: &[&AuditRecord]) -> Vec<HashMap<String, serde_json::Value>> {
+    records
+        .iter()
+        .map(|record| {
+            let mut row = HashMap::new();
+            row.insert("sequence".to_string(), serde_json::json!(record.sequence));
+            row.insert(
+                "timestamp".to_string(),
+                serde_json::json!(record.timestamp.to_rfc3339()),
+            );
+            row.insert("user".to_string(), serde_json::json!(record.user));
+            row.insert(
+                "session_id".to_string(),
+                serde_json::json!(record.session_id),
+            );
+            row.insert(
+                "action".to_string(),
+                serde_json::json!(record.action.kind()),
+            );
+            row.insert(
+                "details".to_string(),
+                serde_json::json!(record.action.summary()),
+            );
+            row.insert("hash".to_string(), serde_json::json!(record.hash));
+            row
+        })
+        .collect()
+}
+
+pub fn to_csv(records: &[&AuditRecord]) -> String {
+    let field = |value: &str| {
+        if value.contains([',', '"', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let mut csv = String::from("sequence,timestamp,user,session_id,action,details\n");
+    for record in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            record.sequence,
+            record.timestamp.to_rfc3339(),
+            field(&record.user),
+            field(&record.session_id),
+            record.action.kind(),
+            field(&record.action.summary())
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_elevated() {
+        assert!(is_elevated("sudo apt install jq"));
+        assert!(is_elevated("echo hi | /usr/bin/sudo tee /etc/motd"));
+        assert!(is_elevated("make && DEBUG=1 doas make install"));
+        assert!(!is_elevated("echo sudo"));
+        assert!(!is_elevated("grep -r pkexec src"));
+    }
+
+    #[tokio::test]
+    async fn test_chain_resumes_across_sessions_and_detects_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::open(&path, "one").await.unwrap();
+        log.record(AuditAction::Sudo {
+            command: "sudo reboot".to_string(),
+        })
+        .await
+        .unwrap();
+        let log = AuditLog::open(&path, "two").await.unwrap();
+        log.record(AuditAction::ConfigChanged {
+            setting: "ui.theme".to_string(),
+            value: "\"dark\"".to_string(),
+        })
+        .await
+        .unwrap();
+
+        let report = AuditLog::verify(&path).await.unwrap();
+        assert!(report.valid);
+        assert_eq!(report.records_checked, 2);
+
+        let content = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("sudo reboot", "ls");
+        assert_eq!(verify_chain(&content).first_broken_sequence, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_sessions_appending_together_keep_one_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let one = AuditLog::open(&path, "one").await.unwrap();
+        let two = AuditLog::open(&path, "two").await.unwrap();
+        for i in 0..3 {
+            let command = format!("sudo make install {}", i);
+            one.record(AuditAction::Sudo {
+                command: command.clone(),
+            })
+            .await
+            .unwrap();
+            two.record(AuditAction::Sudo { command }).await.unwrap();
+        }
+
+        let report = AuditLog::verify(&path).await.unwrap();
+        assert!(report.valid, "{:?}", report.error);
+        assert_eq!(report.records_checked, 6);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_last_line_spans_chunks() {
+        let mut file = tempfile::tempfile().unwrap();
+        let long = "x".repeat(TAIL_CHUNK as usize * 2);
+        write!(file, "first\n{}\n\n", long).unwrap();
+        assert_eq!(last_line(&mut file).unwrap(), Some(long));
+
+        let mut empty = tempfile::tempfile().unwrap();
+        assert_eq!(last_line(&mut empty).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_search_and_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::open(&path, "s").await.unwrap();
+        for action in [
+            AuditAction::Sudo {
+                command: "sudo systemctl restart nginx".to_string(),
+            },
+            AuditAction::PermissionGranted {
+                plugin: "weather".to_string(),
+                capability: "connect to api.example.com".to_string(),
+            },
+        ] {
+            log.record(action).await.unwrap();
+        }
+        let records = AuditLog::read(&path).await.unwrap();
+
+        let query = AuditQuery {
+            text: Some("NGINX".to_string()),
+            ..AuditQuery::default()
+        };
+        assert_eq!(search(&records, &query).len(), 1);
+        let query = AuditQuery {
+            action: Some("permission_granted".to_string()),
+            since: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+            ..AuditQuery::default()
+        };
+        assert!(search(&records, &query).is_empty());
+
+        let csv = to_csv(&records.iter().collect::<Vec<_>>());
+        assert!(csv
+            .lines()
+            .nth(2)
+            .unwrap()
+            .ends_with(",permission_granted,weather: connect to api.example.com"));
+    }
+}
@@ -0,0 +1,232 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::{BellConfig, TerminalConfig};
+use crate::error::WarpError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+// Takes BEL characters out of PTY output so the host terminal never sees
+// them. A BEL that ends an OSC (titles, hyperlinks) is a terminator, not a
+// bell, and stays; the state carries over between chunks.
+pub struct BellDetector {
+    state: State,
+}
+
+impl Default for BellDetector {
+    fn default() -> Self {
+        Self {
+            state: State::Ground,
+        }
+    }
+}
+
+impl BellDetector {
+    // The output without its bells, and how many there were
+    pub fn strip(&mut self, text: &str) -> (String, usize) {
+        let mut out = String::with_capacity(text.len());
+        let mut bells = 0;
+        for c in text.chars() {
+            self.state = match (self.state, c) {
+                (State::Ground, '\x07') => {
+                    bells += 1;
+                    continue;
+                }
+                (State::Ground, '\x1b') => State::Escape,
+                (State::Ground, '\u{9d}') => State::Osc,
+                (State::Ground, _) => State::Ground,
+                (State::Escape, ']') => State::Osc,
+                (State::Escape, '\x1b') => State::Escape,
+                (State::Escape, _) => State::Ground,
+                (State::Osc, '\x07' | '\u{9c}') => State::Ground,
+                (State::Osc | State::OscEscape, '\x1b') => State::OscEscape,
+                (State::Osc, _) => State::Osc,
+                // ESC \ is the string terminator; anything else abandons it
+                (State::OscEscape, _) => State::Ground,
+            };
+            out.push(c);
+        }
+        (out, bells)
+    }
+}
+
+// What a bell does this time round
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ring {
+    pub flash: Option<Duration>,
+    pub sound: bool,
+    pub notify: bool,
+}
+
+pub struct BellPolicy {
+    config: BellConfig,
+    audible: bool,
+    sound: Option<PathBuf>,
+    suppress: Vec<Regex>,
+    muted: HashSet<usize>, // panes
+    last: Option<Instant>,
+}
+
+impl BellPolicy {
+    pub fn from_config(terminal: &TerminalConfig) -> Result<Self, WarpError> {
+        let config = terminal.bell_options.clone();
+        let suppress = config
+            .suppress_commands
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    WarpError::config_err(format!(
+                        "Invalid bell suppression rule '{}': {}",
+                        pattern, e
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            config,
+            audible: terminal.bell,
+            sound: terminal.bell_sound.as_ref().map(PathBuf::from),
+            suppress,
+            muted: HashSet::new(),
+            last: None,
+        })
+    }
+
+    pub fn sound(&self) -> Option<&Path> {
+        self.sound.as_deref()
+    }
+
+    // Returns whether the pane is muted now
+    pub fn toggle_mute(&mut self, pane: usize) -> bool {
+        if self.muted.remove(&pane) {
+            return false;
+        }
+        self.muted.insert(pane);
+        true
+    }
+
+    pub fn ring(
+        &mut self,
+        pane: usize,
+        command: Option<&str>,
+        focused: bool,
+        now: Instant,
+    ) -> Option<Ring> {
+        let ring = Ring {
+            flash: self
+                .config
+                .visual
+                .then(|| Duration::from_millis(self.config.flash_ms)),
+            sound: self.audible,
+            notify: self.config.notify_when_unfocused && !focused,
+        };
+        if ring == Ring::default() || self.muted.contains(&pane) {
+            return None;
+        }
+        if command.is_some_and(|command| self.suppress.iter().any(|regex| regex.is_match(command)))
+        {
+            return None;
+        }
+        let interval = Duration::from_millis(self.config.min_interval_ms);
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < interval)
+        {
+            return None;
+        }
+        self.last = Some(now);
+        Some(ring)
+    }
+}
+
+// The host terminal's own bell, unless a sound file is configured
+pub fn play(sound: Option<&Path>) -> Result<(), WarpError> {
+    let Some(sound) = sound else {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(b"\x07")?;
+        stdout.flush()?;
+        return Ok(());
+    };
+    let player = if cfg!(target_os = "macos") {
+        "afplay"
+    } else {
+        "paplay"
+    };
+    Command::new(player)
+        .arg(sound)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+
+    #[test]
+    fn test_detector_skips_osc_terminators() {
+        let mut detector = BellDetector::default();
+        assert_eq!(
+            detector.strip("done\x07\x1b]0;title\x07"),
+            ("done\x1b]0;title\x07".to_string(), 1)
+        );
+        // An OSC split across reads still ends at its BEL
+        assert_eq!(detector.strip("\x1b]8;;https://x").1, 0);
+        assert_eq!(
+            detector.strip("\x07link\x1b]8;;\x1b\\\x07\x07"),
+            ("\x07link\x1b]8;;\x1b\\".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn test_policy_rules_mutes_and_rate_limit() {
+        let mut terminal = WarpConfig::default().terminal;
+        terminal.bell = true;
+        terminal.bell_options.suppress_commands = vec![r"^(vim|less)\b".to_string()];
+        let mut policy = BellPolicy::from_config(&terminal).unwrap();
+        let start = Instant::now();
+
+        let ring = policy.ring(0, Some("make"), false, start).unwrap();
+        assert_eq!(ring.flash, Some(Duration::from_millis(150)));
+        assert!(ring.sound && ring.notify);
+        // Too soon after the last one
+        assert_eq!(
+            policy.ring(0, Some("make"), true, start + Duration::from_millis(100)),
+            None
+        );
+        let later = start + Duration::from_secs(1);
+        assert!(!policy.ring(0, None, true, later).unwrap().notify);
+
+        assert_eq!(
+            policy.ring(
+                0,
+                Some("vim notes.md"),
+                false,
+                later + Duration::from_secs(1)
+            ),
+            None
+        );
+        assert!(policy.toggle_mute(1));
+        assert_eq!(
+            policy.ring(1, Some("make"), false, later + Duration::from_secs(2)),
+            None
+        );
+        assert!(!policy.toggle_mute(1));
+
+        terminal.bell_options.suppress_commands = vec!["(".to_string()];
+        assert!(BellPolicy::from_config(&terminal).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use chrono::{DateTime, Utc};
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// What the user marked on one block. The command is kept so the block can
+// be listed after it has left the scrollback, or the app has restarted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockMarks {
+    pub session: String,
+    pub command: String,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub favorite: bool,
+    pub marked: DateTime<Utc>,
+}
+
+impl BlockMarks {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && !self.pinned && !self.favorite
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Pinned,
+    Favorite,
+}
+
+// The block a mark is being added to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRef {
+    pub session: String,
+    pub block: String,
+    pub command: String,
+}
+
+// "#deploy", "incident-42"; letters, digits and - _ . /
+pub fn tag_name(token: &str) -> Option<String> {
+    let tag = token.strip_prefix('#').unwrap_or(token).to_lowercase();
+    let valid = tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    (valid && !tag.is_empty()).then_some(tag)
+}
+
+// Tags entered at the prompt: "#deploy #incident-42" adds, "-#old" removes
+pub fn parse_tags(input: &str) -> Result<(Vec<String>, Vec<String>), WarpError> {
+    let mut add = Vec::new();
+    let mut remove = Vec::new();
+    for token in input.split_whitespace() {
+        let (list, name) = match token.strip_prefix('-') {
+            Some(name) => (&mut remove, name),
+            None => (&mut add, token),
+        };
+        let tag = tag_name(name).ok_or_else(|| {
+            WarpError::Validation(i18n::t_args("block_tags.invalid", &[("tag", token)]))
+        })?;
+        list.push(tag);
+    }
+    Ok((add, remove))
+}
+
+// Marks by block id. Saved next to the command history and flushed with it.
+#[derive(Debug, Default)]
+pub struct BlockTags {
+    blocks: BTreeMap<String, BlockMarks>,
+    dirty: bool,
+}
+
+impl BlockTags {
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let blocks = match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            blocks,
+            dirty: false,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WarpError> {
+        Ok(serde_json::to_vec_pretty(&self.blocks)?)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn get(&self, block: &str) -> Option<&BlockMarks> {
+        self.blocks.get(block)
+    }
+
+    // Changes the marks on a block, dropping it once nothing is left
+    fn update(&mut self, target: &BlockRef, change: impl FnOnce(&mut BlockMarks)) {
+        let marks = self
+            .blocks
+            .entry(target.block.clone())
+            .or_insert_with(|| BlockMarks {
+                session: target.session.clone(),
+                command: target.command.clone(),
+                tags: BTreeSet::new(),
+                pinned: false,
+                favorite: false,
+                marked: Utc::now(),
+            });
+        change(marks);
+        if marks.is_empty() {
+            self.blocks.remove(&target.block);
+        }
+        self.dirty = true;
+    }
+
+    pub fn retag(&mut self, target: &BlockRef, add: &[String], remove: &[String]) {
+        self.update(target, |marks| {
+            marks.tags.extend(add.iter().cloned());
+            for tag in remove {
+                marks.tags.remove(tag);
+            }
+        });
+    }
+
+    // Whether the block has the mark afterwards
+    pub fn toggle(&mut self, target: &BlockRef, mark: Mark) -> bool {
+        let mut on = false;
+        self.update(target, |marks| {
+            let flag = match mark {
+                Mark::Pinned => &mut marks.pinned,
+                Mark::Favorite => &mut marks.favorite,
+            };
+            *flag = !*flag;
+            on = *flag;
+        });
+        on
+    }
+
+    // Oldest first, the order they would appear in the scrollback
+    pub fn with_tag(&self, tag: &str) -> Vec<(&str, &BlockMarks)> {
+        let mut blocks: Vec<_> = self
+            .blocks
+            .iter()
+            .filter(|(_, marks)| marks.tags.contains(tag))
+            .map(|(block, marks)| (block.as_str(), marks))
+            .collect();
+        blocks.sort_by_key(|(_, marks)| marks.marked);
+        blocks
+    }
+
+    // Blocks carrying every one of `tags`
+    pub fn tagged_all(&self, tags: &[String]) -> HashSet<String> {
+        self.blocks
+            .iter()
+            .filter(|(_, marks)| tags.iter().all(|tag| marks.tags.contains(tag)))
+            .map(|(block, _)| block.clone())
+            .collect()
+    }
+
+    pub fn tag_counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for tag in self.blocks.values().flat_map(|marks| &marks.tags) {
+            *counts.entry(tag.as_str()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinnedCommand {
+    None,
+    Open(BlockRef),
+    FilterTag(String),
+    Toggle(BlockRef, Mark),
+    // Ask for tags for this block at the panel prompt
+    Retag(BlockRef),
+}
+
+#[derive(Debug, Clone)]
+enum Row {
+    Block(BlockRef, BlockMarks),
+    Tag(String, usize),
+}
+
+// The sidebar: pinned blocks, then favorites, then every tag in use with
+// how many blocks carry it
+pub struct PinnedPanel {
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+impl PinnedPanel {
+    pub fn new(tags: &BlockTags) -> Self {
+        let mut panel = Self {
+            rows: Vec::new(),
+            selected: 0,
+        };
+        panel.refresh(tags);
+        panel
+    }
+
+    pub fn refresh(&mut self, tags: &BlockTags) {
+        let mut blocks: Vec<_> = tags
+            .blocks
+            .iter()
+            .filter(|(_, marks)| marks.pinned || marks.favorite)
+            .collect();
+        blocks.sort_by_key(|(_, marks)| (!marks.pinned, std::cmp::Reverse(marks.marked)));
+        self.rows = blocks
+            .into_iter()
+            .map(|(block, marks)| {
+                let target = BlockRef {
+                    session: marks.session.clone(),
+                    block: block.clone(),
+                    command: marks.command.clone(),
+                };
+                Row::Block(target, marks.clone())
+            })
+            .chain(
+                tags.tag_counts()
+                    .into_iter()
+                    .map(|(tag, count)| Row::Tag(tag.to_string(), count)),
+            )
+            .collect();
+        self.select(self.selected);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> PinnedCommand {
+        self.select(index);
+        match (key, self.rows.get(self.selected)) {
+            ('\n', Some(Row::Block(target, _))) => PinnedCommand::Open(target.clone()),
+            ('\n', Some(Row::Tag(tag, _))) => PinnedCommand::FilterTag(tag.clone()),
+            ('p', Some(Row::Block(target, _))) => {
+                PinnedCommand::Toggle(target.clone(), Mark::Pinned)
+            }
+            ('f', Some(Row::Block(target, _))) => {
+                PinnedCommand::Toggle(target.clone(), Mark::Favorite)
+            }
+            ('t', Some(Row::Block(target, _))) => PinnedCommand::Retag(target.clone()),
+            _ => PinnedCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Block(target, marks) => {
+                    let icon = match marks.pinned {
+                        true => ("📌 ", None),
+                        false => ("★ ", Some(Color::Yellow)),
+                    };
+                    PanelItem::styled(vec![
+                        (icon.0.to_string(), icon.1),
+                        (target.command.clone(), None),
+                        (format_tags(&marks.tags), Some(Color::Cyan)),
+                    ])
+                }
+                Row::Tag(tag, count) => PanelItem::styled(vec![
+                    (format!("#{} ", tag), Some(Color::Cyan)),
+                    (
+                        i18n::t_args("block_tags.count", &[("count", &count.to_string())]),
+                        Some(Color::DarkGrey),
+                    ),
+                ]),
+            })
+            .collect();
+        let preview = match self.rows.get(self.selected) {
+            Some(Row::Block(_, marks)) => vec![PanelItem::styled(vec![(
+                marks
+                    .marked
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                Some(Color::DarkGrey),
+            )])],
+            Some(Row::Tag(..)) => vec![i18n::t("block_tags.filter_hint").into()],
+            None => vec![i18n::t("block_tags.empty").into()],
+        };
+        PanelView {
+            kind: PanelKind::PinnedBlocks,
+            title: i18n::t("panel.pinned_blocks"),
+            items,
+            footer: Some(i18n::t("panel.pinned_blocks_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+// " #deploy #incident-42", or nothing
+pub fn format_tags(tags: &BTreeSet<String>) -> String {
+    tags.iter().map(|tag| format!(" #{}", tag)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(block: &str, command: &str) -> BlockRef {
+        BlockRef {
+            session: "s1".to_string(),
+            block: block.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags("#Deploy incident-42 -#old").unwrap(),
+            (
+                vec!["deploy".to_string(), "incident-42".to_string()],
+                vec!["old".to_string()]
+            )
+        );
+        assert!(parse_tags("#a,b").is_err());
+        assert!(parse_tags("#").is_err());
+    }
+
+    #[test]
+    fn test_marks_are_dropped_when_empty() {
+        let mut tags = BlockTags::default();
+        let deploy = target("b1", "make deploy");
+        tags.retag(&deploy, &["deploy".to_string()], &[]);
+        assert!(tags.toggle(&deploy, Mark::Pinned));
+        tags.retag(
+            &target("b2", "kubectl logs api"),
+            &["deploy".to_string(), "incident-42".to_string()],
+            &[],
+        );
+        assert_eq!(
+            tags.with_tag("deploy")
+                .into_iter()
+                .map(|(block, _)| block)
+                .collect::<Vec<_>>(),
+            vec!["b1", "b2"]
+        );
+        assert_eq!(
+            tags.tagged_all(&["deploy".to_string(), "incident-42".to_string()]),
+            HashSet::from(["b2".to_string()])
+        );
+
+        tags.retag(&deploy, &[], &["deploy".to_string()]);
+        assert!(!tags.toggle(&deploy, Mark::Pinned));
+        assert!(tags.get("b1").is_none());
+        assert_eq!(tags.tag_counts().get("deploy"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("block_tags.json");
+        let mut tags = BlockTags::default();
+        tags.retag(&target("b1", "make deploy"), &["deploy".to_string()], &[]);
+        tags.toggle(&target("b1", "make deploy"), Mark::Favorite);
+        assert!(tags.is_dirty());
+        tokio::fs::write(&path, tags.to_bytes().unwrap())
+            .await
+            .unwrap();
+
+        let loaded = BlockTags::load(&path).await.unwrap();
+        let marks = loaded.get("b1").unwrap();
+        assert!(marks.favorite && !marks.pinned);
+        assert_eq!(marks.command, "make deploy");
+        assert!(!loaded.is_dirty());
+        assert!(BlockTags::load(&dir.path().join("missing.json"))
+            .await
+            .unwrap()
+            .get("b1")
+            .is_none());
+    }
+
+    #[test]
+    fn test_panel() {
+        let mut tags = BlockTags::default();
+        tags.toggle(&target("b1", "make deploy"), Mark::Pinned);
+        tags.toggle(&target("b2", "cargo bench"), Mark::Favorite);
+        tags.retag(&target("b3", "ls"), &["misc".to_string()], &[]);
+        let mut panel = PinnedPanel::new(&tags);
+        assert_eq!(panel.view().items.len(), 3);
+        assert_eq!(
+            panel.handle_key('\n', 0),
+            PinnedCommand::Open(target("b1", "make deploy"))
+        );
+        assert_eq!(
+            panel.handle_key('f', 1),
+            PinnedCommand::Toggle(target("b2", "cargo bench"), Mark::Favorite)
+        );
+        assert_eq!(
+            panel.handle_key('\n', 2),
+            PinnedCommand::FilterTag("misc".to_string())
+        );
+        assert_eq!(panel.handle_key('p', 2), PinnedCommand::None);
+    }
+}
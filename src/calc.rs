@@ -0,0 +1,633 @@
+use std::fmt;
+
+use crate::error::WarpError;
+use crate::i18n;
+
+// A small expression engine: arithmetic, hex/binary/octal literals, a few
+// functions, and quantities in data, time, length and mass units. The
+// prompt uses it for `= …` lines and exports for calculated columns.
+
+// Typed at the start of the input line
+pub const PROMPT_PREFIX: char = '=';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Data,
+    Time,
+    Length,
+    Mass,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub symbol: &'static str,
+    pub dimension: Dimension,
+    factor: f64, // in bytes, seconds, metres or grams
+}
+
+const fn unit(symbol: &'static str, dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        symbol,
+        dimension,
+        factor,
+    }
+}
+
+const KI: f64 = 1024.0;
+
+const UNITS: &[Unit] = &[
+    unit("bit", Dimension::Data, 0.125),
+    unit("Kbit", Dimension::Data, 125.0),
+    unit("Mbit", Dimension::Data, 125e3),
+    unit("Gbit", Dimension::Data, 125e6),
+    unit("B", Dimension::Data, 1.0),
+    unit("kB", Dimension::Data, 1e3),
+    unit("MB", Dimension::Data, 1e6),
+    unit("GB", Dimension::Data, 1e9),
+    unit("TB", Dimension::Data, 1e12),
+    unit("PB", Dimension::Data, 1e15),
+    unit("KiB", Dimension::Data, KI),
+    unit("MiB", Dimension::Data, KI * KI),
+    unit("GiB", Dimension::Data, KI * KI * KI),
+    unit("TiB", Dimension::Data, KI * KI * KI * KI),
+    unit("PiB", Dimension::Data, KI * KI * KI * KI * KI),
+    unit("ns", Dimension::Time, 1e-9),
+    unit("us", Dimension::Time, 1e-6),
+    unit("ms", Dimension::Time, 1e-3),
+    unit("s", Dimension::Time, 1.0),
+    unit("min", Dimension::Time, 60.0),
+    unit("h", Dimension::Time, 3600.0),
+    unit("d", Dimension::Time, 86400.0),
+    unit("w", Dimension::Time, 604800.0),
+    unit("mm", Dimension::Length, 1e-3),
+    unit("cm", Dimension::Length, 1e-2),
+    unit("m", Dimension::Length, 1.0),
+    unit("km", Dimension::Length, 1e3),
+    unit("inch", Dimension::Length, 0.0254),
+    unit("ft", Dimension::Length, 0.3048),
+    unit("yd", Dimension::Length, 0.9144),
+    unit("mi", Dimension::Length, 1609.344),
+    unit("mg", Dimension::Mass, 1e-3),
+    unit("g", Dimension::Mass, 1.0),
+    unit("kg", Dimension::Mass, 1e3),
+    unit("t", Dimension::Mass, 1e6),
+    unit("oz", Dimension::Mass, 28.349523125),
+    unit("lb", Dimension::Mass, 453.59237),
+];
+
+// Exact first, so m and M, or Mbit and MB, stay apart; then "gib" or
+// "KB" find the one unit they can mean
+pub fn find_unit(name: &str) -> Option<Unit> {
+    if let Some(unit) = UNITS.iter().find(|unit| unit.symbol == name) {
+        return Some(*unit);
+    }
+    let mut matches = UNITS
+        .iter()
+        .filter(|unit| unit.symbol.eq_ignore_ascii_case(name));
+    match (matches.next(), matches.next()) {
+        (Some(unit), None) => Some(*unit),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Decimal,
+    Hex,
+    Binary,
+    Octal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Value {
+    pub number: f64,
+    pub unit: Option<Unit>,
+    pub radix: Radix,
+}
+
+impl Value {
+    fn plain(number: f64) -> Self {
+        Self {
+            number,
+            unit: None,
+            radix: Radix::Decimal,
+        }
+    }
+
+    fn with_unit(number: f64, unit: Option<Unit>) -> Self {
+        Self {
+            number,
+            unit,
+            radix: Radix::Decimal,
+        }
+    }
+
+    // Converts to `unit`, for adding, comparing or `in`
+    fn to(self, unit: Unit) -> Result<f64, WarpError> {
+        match self.unit {
+            Some(own) if own.dimension == unit.dimension => {
+                Ok(self.number * own.factor / unit.factor)
+            }
+            own => Err(mismatch(own, Some(unit))),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let integer = self.number.fract() == 0.0 && self.number.abs() < 1e18;
+        let number = match (self.radix, integer) {
+            (Radix::Hex, true) => radix_string(self.number, "0x", |n| format!("{:x}", n)),
+            (Radix::Binary, true) => radix_string(self.number, "0b", |n| format!("{:b}", n)),
+            (Radix::Octal, true) => radix_string(self.number, "0o", |n| format!("{:o}", n)),
+            _ => format_number(self.number),
+        };
+        match self.unit {
+            Some(unit) => write!(f, "{} {}", number, unit.symbol),
+            None => f.write_str(&number),
+        }
+    }
+}
+
+fn radix_string(number: f64, prefix: &str, digits: impl Fn(u64) -> String) -> String {
+    let sign = if number < 0.0 { "-" } else { "" };
+    format!("{}{}{}", sign, prefix, digits(number.abs() as u64))
+}
+
+// Ten significant decimals hide binary noise (0.1 + 0.2 is 0.3); very
+// large and very small numbers switch to exponents
+fn format_number(number: f64) -> String {
+    let magnitude = number.abs();
+    if number.fract() == 0.0 && magnitude < 1e15 {
+        return format!("{}", number as i64);
+    }
+    if !(1e-6..1e15).contains(&magnitude) {
+        let text = format!("{:.9e}", number);
+        let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+        return format!("{}e{}", trim_zeros(mantissa), exponent);
+    }
+    trim_zeros(&format!("{:.10}", number)).to_string()
+}
+
+fn trim_zeros(text: &str) -> &str {
+    if !text.contains('.') {
+        return text;
+    }
+    text.trim_end_matches('0').trim_end_matches('.')
+}
+
+fn mismatch(left: Option<Unit>, right: Option<Unit>) -> WarpError {
+    let name = |unit: Option<Unit>| {
+        unit.map(|u| u.symbol.to_string())
+            .unwrap_or_else(|| i18n::t("calc.number"))
+    };
+    WarpError::Validation(i18n::t_args(
+        "calc.units",
+        &[("left", &name(left)), ("right", &name(right))],
+    ))
+}
+
+// The expression after the prompt prefix, when the line has one
+pub fn prompt_expression(input: &str) -> Option<&str> {
+    let expression = input.trim_start().strip_prefix(PROMPT_PREFIX)?.trim();
+    (!expression.is_empty()).then_some(expression)
+}
+
+pub fn evaluate(text: &str) -> Result<Value, WarpError> {
+    evaluate_with(text, &|_| None)
+}
+
+// `variables` resolves names that aren't functions or constants, e.g. an
+// export row's columns
+pub fn evaluate_with(
+    text: &str,
+    variables: &dyn Fn(&str) -> Option<f64>,
+) -> Result<Value, WarpError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+        variables,
+    };
+    let value = parser.conversion()?;
+    if let Some(token) = parser.peek() {
+        return Err(unexpected(Some(token)));
+    }
+    if !value.number.is_finite() {
+        return Err(WarpError::Validation(i18n::t("calc.not_finite")));
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "{}", format_number(*number)),
+            Token::Name(name) => f.write_str(name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+fn unexpected(token: Option<&Token>) -> WarpError {
+    let token = match token {
+        Some(token) => format!("'{}'", token),
+        None => i18n::t("calc.end"),
+    };
+    WarpError::Validation(i18n::t_args("calc.unexpected", &[("token", &token)]))
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, WarpError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            let mut previous = ' ';
+            // Letters end a number unless it's a radix literal or an
+            // exponent, so 5GiB is a number then a unit
+            let radix = ["0x", "0b", "0o"]
+                .iter()
+                .any(|prefix| text[start..].starts_with(prefix));
+            while let Some(&(i, c)) = chars.peek() {
+                let accepted = c.is_ascii_digit()
+                    || c == '_'
+                    || c == '.'
+                    || (radix && c.is_ascii_alphanumeric())
+                    || (!radix && (c == 'e' || c == 'E') && exponent_follows(&text[i + 1..]))
+                    || ((c == '+' || c == '-') && (previous == 'e' || previous == 'E') && !radix);
+                if !accepted {
+                    break;
+                }
+                previous = c;
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Number(parse_number(&text[start..end])?));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars
+                .peek()
+                .filter(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '.')
+            {
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(text[start..end].to_string()));
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(unexpected(Some(&Token::Symbol(c))));
+        }
+    }
+    Ok(tokens)
+}
+
+fn exponent_follows(rest: &str) -> bool {
+    let rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+    rest.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn parse_number(text: &str) -> Result<f64, WarpError> {
+    let digits = text.replace('_', "");
+    let parsed = match digits.get(..2) {
+        Some("0x") => u64::from_str_radix(&digits[2..], 16).map(|n| n as f64).ok(),
+        Some("0b") => u64::from_str_radix(&digits[2..], 2).map(|n| n as f64).ok(),
+        Some("0o") => u64::from_str_radix(&digits[2..], 8).map(|n| n as f64).ok(),
+        _ => digits.parse().ok(),
+    };
+    parsed
+        .ok_or_else(|| WarpError::Validation(i18n::t_args("calc.bad_number", &[("number", text)])))
+}
+
+// Brackets, calls, signs and exponents nested deeper than this are refused
+// rather than recursing until the stack overflows
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+    variables: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), WarpError> {
+        if !self.eat(symbol) {
+            return Err(unexpected(self.peek()));
+        }
+        Ok(())
+    }
+
+    // sum [in|to|as unit-or-radix]
+    fn conversion(&mut self) -> Result<Value, WarpError> {
+        let value = self.sum()?;
+        if !matches!(self.peek(), Some(Token::Name(name)) if ["in", "to", "as"].contains(&name.as_str()))
+        {
+            return Ok(value);
+        }
+        self.position += 1;
+        let target = match self.next() {
+            Some(Token::Name(target)) => target,
+            token => return Err(unexpected(token.as_ref())),
+        };
+        let radix = match target.to_ascii_lowercase().as_str() {
+            "hex" => Some(Radix::Hex),
+            "bin" | "binary" => Some(Radix::Binary),
+            "oct" | "octal" => Some(Radix::Octal),
+            "dec" | "decimal" => Some(Radix::Decimal),
+            _ => None,
+        };
+        match (radix, find_unit(&target)) {
+            (Some(radix), _) => Ok(Value { radix, ..value }),
+            (None, Some(unit)) => Ok(Value::with_unit(value.to(unit)?, Some(unit))),
+            (None, None) => Err(unknown(&target)),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Value, WarpError> {
+        let mut value = self.product()?;
+        loop {
+            let sign = if self.eat('+') {
+                1.0
+            } else if self.eat('-') {
+                -1.0
+            } else {
+                return Ok(value);
+            };
+            let right = self.product()?;
+            value = match (value.unit, right.unit) {
+                (None, None) => Value::plain(value.number + sign * right.number),
+                (Some(unit), Some(_)) => {
+                    Value::with_unit(value.number + sign * right.to(unit)?, Some(unit))
+                }
+                (left, right) => return Err(mismatch(left, right)),
+            };
+        }
+    }
+
+    fn product(&mut self) -> Result<Value, WarpError> {
+        let mut value = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Symbol(c @ ('*' | '/' | '%'))) => *c,
+                _ => return Ok(value),
+            };
+            self.position += 1;
+            let right = self.unary()?;
+            value = match (operator, value.unit, right.unit) {
+                ('*', unit, None) | ('/', unit, None) | ('%', unit @ None, None) => {
+                    let number = match operator {
+                        '*' => value.number * right.number,
+                        '/' => value.number / right.number,
+                        _ => value.number % right.number,
+                    };
+                    Value::with_unit(number, unit)
+                }
+                ('*', None, unit) => Value::with_unit(value.number * right.number, unit),
+                // 5GiB / 1MB is a plain ratio
+                ('/', Some(unit), Some(_)) => Value::plain(value.number / right.to(unit)?),
+                ('%', Some(unit), Some(_)) => {
+                    Value::with_unit(value.number % right.to(unit)?, Some(unit))
+                }
+                (_, left, right) => return Err(mismatch(left, right)),
+            };
+        }
+    }
+
+    // Every nested parse comes back through here, so it keeps the depth
+    fn unary(&mut self) -> Result<Value, WarpError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(WarpError::Validation(i18n::t("calc.too_deep")));
+        }
+        self.depth += 1;
+        let value = self.signed();
+        self.depth -= 1;
+        value
+    }
+
+    fn signed(&mut self) -> Result<Value, WarpError> {
+        if self.eat('-') {
+            let value = self.unary()?;
+            return Ok(Value {
+                number: -value.number,
+                ..value
+            });
+        }
+        self.eat('+');
+        self.power()
+    }
+
+    // Right-associative, and binds tighter than a leading minus: -2^2 is -4
+    fn power(&mut self) -> Result<Value, WarpError> {
+        let base = self.quantity()?;
+        if !self.eat('^') {
+            return Ok(base);
+        }
+        let exponent = self.unary()?;
+        match (base.unit, exponent.unit) {
+            (None, None) => Ok(Value::plain(base.number.powf(exponent.number))),
+            (left, right) => Err(mismatch(left, right)),
+        }
+    }
+
+    // A unit straight after a number or a bracket applies to it: 5GiB, (1 + 2) h
+    fn quantity(&mut self) -> Result<Value, WarpError> {
+        let value = self.primary()?;
+        let unit = match self.peek() {
+            Some(Token::Name(name)) if value.unit.is_none() => find_unit(name),
+            _ => None,
+        };
+        match unit {
+            Some(unit) => {
+                self.position += 1;
+                Ok(Value::with_unit(value.number, Some(unit)))
+            }
+            None => Ok(value),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, WarpError> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Value::plain(number)),
+            Some(Token::Symbol('(')) => {
+                let value = self.sum()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some(Token::Name(name)) if self.peek() == Some(&Token::Symbol('(')) => {
+                self.position += 1;
+                let mut arguments = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        arguments.push(self.sum()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                call(&name, &arguments)
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "pi" => Ok(Value::plain(std::f64::consts::PI)),
+                "e" => Ok(Value::plain(std::f64::consts::E)),
+                _ => match ((self.variables)(&name), find_unit(&name)) {
+                    (Some(number), _) => Ok(Value::plain(number)),
+                    // A bare unit is one of it: GiB in MB
+                    (None, Some(unit)) => Ok(Value::with_unit(1.0, Some(unit))),
+                    (None, None) => Err(unknown(&name)),
+                },
+            },
+            token => Err(unexpected(token.as_ref())),
+        }
+    }
+}
+
+fn unknown(name: &str) -> WarpError {
+    WarpError::Validation(i18n::t_args("calc.unknown", &[("name", name)]))
+}
+
+fn call(name: &str, arguments: &[Value]) -> Result<Value, WarpError> {
+    let arity = |count: &str| {
+        WarpError::Validation(i18n::t_args(
+            "calc.arguments",
+            &[("function", name), ("count", count)],
+        ))
+    };
+    match name {
+        "min" | "max" => {
+            let (first, rest) = arguments.split_first().ok_or_else(|| arity("1+"))?;
+            let mut best = first.number;
+            for argument in rest {
+                let number = match first.unit {
+                    Some(unit) => argument.to(unit)?,
+                    None if argument.unit.is_none() => argument.number,
+                    None => return Err(mismatch(None, argument.unit)),
+                };
+                best = if name == "min" {
+                    best.min(number)
+                } else {
+                    best.max(number)
+                };
+            }
+            return Ok(Value::with_unit(best, first.unit));
+        }
+        _ if arguments.len() != 1 => return Err(arity("1")),
+        _ => {}
+    }
+    let value = arguments[0];
+    let keep_unit = |number: f64| Ok(Value::with_unit(number, value.unit));
+    let unitless = |f: fn(f64) -> f64| match value.unit {
+        None => Ok(Value::plain(f(value.number))),
+        unit => Err(mismatch(unit, None)),
+    };
+    match name {
+        "abs" => keep_unit(value.number.abs()),
+        "round" => keep_unit(value.number.round()),
+        "floor" => keep_unit(value.number.floor()),
+        "ceil" => keep_unit(value.number.ceil()),
+        "sqrt" => unitless(f64::sqrt),
+        "ln" => unitless(f64::ln),
+        "log2" => unitless(f64::log2),
+        "log10" | "log" => unitless(f64::log10),
+        _ => Err(unknown(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(text: &str) -> String {
+        evaluate(text)
+            .map(|value| value.to_string())
+            .unwrap_or_else(|e| format!("error: {:?}", e))
+    }
+
+    #[test]
+    fn test_arithmetic_and_literals() {
+        assert_eq!(show("0x1f * 3"), "93");
+        assert_eq!(show("2 + 3 * 4 ^ 2 / 8"), "8");
+        assert_eq!(show("-2^2 + (1 - 3) % 3"), "-6");
+        assert_eq!(show("0.1 + 0.2"), "0.3");
+        assert_eq!(show("1_000 * 1.5e3"), "1500000");
+        assert_eq!(show("255 in hex"), "0xff");
+        assert_eq!(show("0b1010 + 0o7 to bin"), "0b10001");
+        assert_eq!(show("sqrt(2) * max(1, 3, 2)"), "4.2426406871");
+        assert_eq!(show("2 ^ 80"), "1.20892582e24");
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("2 +").is_err());
+        assert!(evaluate("3 apples").is_err());
+    }
+
+    #[test]
+    fn test_units() {
+        assert_eq!(show("5GiB in MB"), "5368.70912 MB");
+        assert_eq!(show("1.5 h + 30min to min"), "120 min");
+        assert_eq!(show("10 km in mi"), "6.2137119224 mi");
+        assert_eq!(show("5GiB / 512MiB"), "10");
+        assert_eq!(show("2 * 3 kb"), "6 kB");
+        assert_eq!(show("GiB in KiB"), "1048576 KiB");
+        assert!(evaluate("1 GB + 1 s").is_err());
+        assert!(evaluate("1 GB * 2 GB").is_err());
+        assert!(evaluate("100 in MB").is_err());
+    }
+
+    #[test]
+    fn test_prompt_prefix_and_variables() {
+        assert_eq!(prompt_expression("  = 5GiB in MB "), Some("5GiB in MB"));
+        assert_eq!(prompt_expression("="), None);
+        assert_eq!(prompt_expression("echo = 1"), None);
+
+        let row = |name: &str| match name {
+            "bytes_in" => Some(2048.0),
+            "requests" => Some(4.0),
+            _ => None,
+        };
+        let value = evaluate_with("bytes_in / requests", &row).unwrap();
+        assert_eq!(value.number, 512.0);
+        assert!(evaluate_with("bytes_out / requests", &row).is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        assert_eq!(show(&format!("{}1{}", "(".repeat(20), ")".repeat(20))), "1");
+        for text in [
+            "(".repeat(100_000),
+            "-".repeat(100_000),
+            "2^".repeat(100_000),
+        ] {
+            assert!(evaluate(&text).is_err());
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::error::WarpError;
+use crate::network;
+
+// Set to re-record cassettes against the live services
+pub const RECORD_VAR: &str = "WARP_RECORD_CASSETTES";
+// Status for a request the cassette has no answer for
+const UNMATCHED_STATUS: u16 = 599;
+const MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+// Only these response headers are kept; cookies and the like never are
+const RECORDED_HEADERS: &[&str] = &["content-type", "content-range", "link"];
+
+// Cassettes redirect the whole process, so one plays at a time
+static PLAYING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Replay, // answers from the file; anything else fails the test
+    Record, // forwards to the real service and writes what came back
+}
+
+// Recorded HTTP interactions standing in for a network-backed subsystem.
+// Request credentials are never written, only method, URL and body.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: &Path) -> Result<Self, WarpError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            WarpError::Config(format!("Cannot read cassette {}: {}", path.display(), e))
+        })?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), WarpError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    // The first unused interaction for this request, so repeated calls
+    // replay in recorded order
+    fn take(&self, used: &mut [bool], request: &RecordedRequest) -> Option<RecordedResponse> {
+        let index = (0..self.interactions.len()).find(|&index| {
+            !used[index] && request_matches(&self.interactions[index].request, request)
+        })?;
+        used[index] = true;
+        Some(self.interactions[index].response.clone())
+    }
+}
+
+fn request_matches(recorded: &RecordedRequest, actual: &RecordedRequest) -> bool {
+    if recorded.method != actual.method || recorded.url != actual.url {
+        return false;
+    }
+    match (&recorded.body, &actual.body) {
+        (None, _) => true,
+        // JSON bodies compare by value so key order doesn't matter
+        (Some(expected), Some(body)) => match (
+            serde_json::from_str::<serde_json::Value>(expected),
+            serde_json::from_str::<serde_json::Value>(body),
+        ) {
+            (Ok(expected), Ok(body)) => expected == body,
+            _ => expected == body,
+        },
+        (Some(_), None) => false,
+    }
+}
+
+struct State {
+    cassette: Cassette,
+    used: Vec<bool>,
+    unmatched: Vec<String>,
+}
+
+// A cassette being played. While it is alive every URL built through
+// network::endpoint() is served by a loopback server instead of the real
+// host; finish() restores normal networking and reports the outcome.
+pub struct Playback {
+    path: PathBuf,
+    mode: Mode,
+    state: Arc<Mutex<State>>,
+    server: JoinHandle<()>,
+    _playing: tokio::sync::MutexGuard<'static, ()>,
+}
+
+impl Playback {
+    // Replays `path`, or records it when WARP_RECORD_CASSETTES is set
+    pub async fn start(path: impl Into<PathBuf>) -> Result<Self, WarpError> {
+        let mode = if std::env::var_os(RECORD_VAR).is_some() {
+            Mode::Record
+        } else {
+            Mode::Replay
+        };
+        Self::with_mode(path, mode).await
+    }
+
+    pub async fn with_mode(path: impl Into<PathBuf>, mode: Mode) -> Result<Self, WarpError> {
+        let path = path.into();
+        let cassette = match mode {
+            Mode::Replay => Cassette::load(&path)?,
+            Mode::Record => Cassette::default(),
+        };
+        let playing = PLAYING.lock().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            used: vec![false; cassette.interactions.len()],
+            cassette,
+            unmatched: Vec::new(),
+        }));
+        let upstream = match mode {
+            Mode::Record => Some(
+                network::upstream_client_builder()
+                    .build()
+                    .map_err(|e| WarpError::network("Failed to set up the recording client", e))?,
+            ),
+            Mode::Replay => None,
+        };
+
+        let server_state = state.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = server_state.clone();
+                let upstream = upstream.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, state, upstream).await {
+                        log::debug!("Cassette connection failed: {}", e);
+                    }
+                });
+            }
+        });
+        network::set_endpoint_override(Some(format!("http://{}", address)));
+        Ok(Self {
+            path,
+            mode,
+            state,
+            server,
+            _playing: playing,
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    // Writes a recording; in replay, fails on any request the cassette
+    // couldn't answer. Interactions that were never requested are allowed.
+    pub fn finish(self) -> Result<(), WarpError> {
+        network::set_endpoint_override(None);
+        self.server.abort();
+        let state = self.state.lock().unwrap();
+        match self.mode {
+            Mode::Record => state.cassette.save(&self.path),
+            Mode::Replay if state.unmatched.is_empty() => Ok(()),
+            Mode::Replay => Err(WarpError::Validation(format!(
+                "{} has no recording for: {}",
+                self.path.display(),
+                state.unmatched.join(", ")
+            ))),
+        }
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        network::set_endpoint_override(None);
+        self.server.abort();
+    }
+}
+
+// `/https/api.github.com/repos/x?y` back to `https://api.github.com/repos/x?y`
+fn original_url(target: &str) -> Option<String> {
+    let (scheme, rest) = target.strip_prefix('/')?.split_once('/')?;
+    matches!(scheme, "http" | "https").then(|| format!("{}://{}", scheme, rest))
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    state: Arc<Mutex<State>>,
+    upstream: Option<reqwest::Client>,
+) -> Result<(), WarpError> {
+    let Some((request, headers)) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let response = match upstream {
+        Some(client) => {
+            let response = forward(&client, &request, headers).await;
+            if let Ok(response) = &response {
+                state
+                    .lock()
+                    .unwrap()
+                    .cassette
+                    .interactions
+                    .push(Interaction {
+                        request: request.clone(),
+                        response: response.clone(),
+                    });
+            }
+            response.unwrap_or_else(|e| unmatched_response(e.to_string()))
+        }
+        None => {
+            let mut state = state.lock().unwrap();
+            let State {
+                cassette,
+                used,
+                unmatched,
+            } = &mut *state;
+            cassette.take(used, &request).unwrap_or_else(|| {
+                let label = format!("{} {}", request.method, request.url);
+                unmatched.push(label.clone());
+                unmatched_response(format!("No recorded interaction for {}", label))
+            })
+        }
+    };
+    write_response(&mut stream, &response).await
+}
+
+fn unmatched_response(message: String) -> RecordedResponse {
+    RecordedResponse {
+        status: UNMATCHED_STATUS,
+        headers: vec![("content-type".to_string(), "text/plain".to_string())],
+        body: message,
+    }
+}
+
+async fn forward(
+    client: &reqwest::Client,
+    request: &RecordedRequest,
+    headers: Vec<(String, String)>,
+) -> Result<RecordedResponse, WarpError> {
+    let method = reqwest::Method::from_bytes(request.method.as_bytes())
+        .map_err(|e| WarpError::Validation(e.to_string()))?;
+    let mut builder = client.request(method, &request.url);
+    for (name, value) in headers {
+        if !matches!(name.as_str(), "host" | "content-length" | "connection") {
+            builder = builder.header(name, value);
+        }
+    }
+    if let Some(body) = &request.body {
+        builder = builder.body(body.clone());
+    }
+    let response = builder
+        .send()
+        .await
+        .map_err(|e| WarpError::network(format!("Recording {} failed", request.url), e))?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter(|(name, _)| RECORDED_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| WarpError::network(format!("Recording {} failed", request.url), e))?;
+    Ok(RecordedResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+// Just enough HTTP/1.1 for reqwest: a request line, headers and a
+// Content-Length body, one request per connection
+async fn read_request(
+    stream: &mut TcpStream,
+) -> Result<Option<(RecordedRequest, Vec<(String, String)>)>, WarpError> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if buffer.len() > MAX_REQUEST_BYTES {
+            return Err(WarpError::Validation(
+                "Cassette request too large".to_string(),
+            ));
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Ok(None);
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let length: usize = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_REQUEST_BYTES {
+        return Err(WarpError::Validation(
+            "Cassette request too large".to_string(),
+        ));
+    }
+
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < length {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+    body.truncate(length);
+
+    let url = original_url(target).unwrap_or_else(|| target.to_string());
+    let request = RecordedRequest {
+        method: method.to_string(),
+        url,
+        body: (!body.is_empty()).then(|| String::from_utf8_lossy(&body).to_string()),
+    };
+    Ok(Some((request, headers)))
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    response: &RecordedResponse,
+) -> Result<(), WarpError> {
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, reason);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "content-length: {}\r\nconnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interaction(
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        status: u16,
+        response: &str,
+    ) -> Interaction {
+        Interaction {
+            request: RecordedRequest {
+                method: method.to_string(),
+                url: url.to_string(),
+                body: body.map(str::to_string),
+            },
+            response: RecordedResponse {
+                status,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: response.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_original_url() {
+        assert_eq!(
+            original_url("/https/api.github.com/repos/a/b?state=open").as_deref(),
+            Some("https://api.github.com/repos/a/b?state=open")
+        );
+        assert_eq!(original_url("/ftp/example.com"), None);
+        assert_eq!(original_url("/"), None);
+    }
+
+    #[test]
+    fn test_json_bodies_match_by_value() {
+        let recorded =
+            interaction("POST", "https://x/y", Some(r#"{"a":1,"b":2}"#), 200, "").request;
+        let mut actual = recorded.clone();
+        actual.body = Some(r#"{ "b": 2, "a": 1 }"#.to_string());
+        assert!(request_matches(&recorded, &actual));
+        actual.body = Some(r#"{"a":2}"#.to_string());
+        assert!(!request_matches(&recorded, &actual));
+    }
+
+    #[tokio::test]
+    async fn test_replay_answers_in_order_and_reports_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+        Cassette {
+            interactions: vec![
+                interaction("GET", "https://api.example.com/items", None, 200, "[1]"),
+                interaction("GET", "https://api.example.com/items", None, 200, "[1,2]"),
+                interaction(
+                    "POST",
+                    "https://api.example.com/items",
+                    Some(r#"{"id":3}"#),
+                    201,
+                    "{}",
+                ),
+            ],
+        }
+        .save(&path)
+        .unwrap();
+
+        let playback = Playback::with_mode(&path, Mode::Replay).await.unwrap();
+        let client = network::client().unwrap();
+        let url = network::endpoint("https://api.example.com/items");
+        assert_eq!(
+            client.get(&url).send().await.unwrap().text().await.unwrap(),
+            "[1]"
+        );
+        assert_eq!(
+            client.get(&url).send().await.unwrap().text().await.unwrap(),
+            "[1,2]"
+        );
+        let created = client
+            .post(&url)
+            .json(&serde_json::json!({ "id": 3 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(created.status(), reqwest::StatusCode::CREATED);
+        playback.finish().unwrap();
+        assert_eq!(
+            network::endpoint("https://api.example.com/items"),
+            "https://api.example.com/items"
+        );
+
+        let playback = Playback::with_mode(&path, Mode::Replay).await.unwrap();
+        let url = network::endpoint("https://api.example.com/missing");
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), UNMATCHED_STATUS);
+        let error = playback.finish().unwrap_err().to_string();
+        assert!(error.contains("GET https://api.example.com/missing"));
+    }
+}
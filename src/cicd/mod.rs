@@ -1,18 +1,5 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
-
-pub mod github_actions;
-pub mod gitlab_ci;
-pub mod jenkins;
-pub mod azure_devops;
-pub mod circleci;
-pub mod travis_ci;
-pub mod pipeline_manager;
-pub mod webhook_handler;
-pub mod deployment;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CICDConfig {
@@ -117,7 +104,7 @@ pub enum PipelineTrigger {
     Webhook { event: String },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PipelineStatus {
     Pending,
     Running,
@@ -216,12 +203,26 @@ pub struct NotificationConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NotificationChannel {
-    Email { addresses: Vec<String> },
-    Slack { webhook_url: String, channel: String },
-    Discord { webhook_url: String },
-    Teams { webhook_url: String },
-    Webhook { url: String, headers: HashMap<String, String> },
-    SMS { numbers: Vec<String> },
+    Email {
+        addresses: Vec<String>,
+    },
+    Slack {
+        webhook_url: String,
+        channel: String,
+    },
+    Discord {
+        webhook_url: String,
+    },
+    Teams {
+        webhook_url: String,
+    },
+    Webhook {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+    SMS {
+        numbers: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -247,167 +248,19 @@ pub enum NotificationCondition {
     OnRecovery,
 }
 
-pub struct CICDManager {
-    config: Arc<Mutex<CICDConfig>>,
-    pipeline_manager: Arc<pipeline_manager::PipelineManager>,
-    webhook_handler: Arc<webhook_handler::WebhookHandler>,
-    deployment_manager: Arc<deployment::DeploymentManager>,
-    providers: HashMap<CICDProvider, Box<dyn CICDProviderTrait>>,
-    active_runs: Arc<Mutex<HashMap<String, PipelineRun>>>,
-}
-
-#[async_trait::async_trait]
-pub trait CICDProviderTrait: Send + Sync {
-    async fn create_pipeline(&self, pipeline: &Pipeline) -> Result<String, WarpError>;
-    async fn update_pipeline(&self, pipeline: &Pipeline) -> Result<(), WarpError>;
-    async fn delete_pipeline(&self, pipeline_id: &str) -> Result<(), WarpError>;
-    async fn trigger_pipeline(&self, pipeline_id: &str, parameters: HashMap<String, String>) -> Result<String, WarpError>;
-    async fn get_pipeline_status(&self, run_id: &str) -> Result<PipelineStatus, WarpError>;
-    async fn get_pipeline_logs(&self, run_id: &str) -> Result<Vec<LogEntry>, WarpError>;
-    async fn cancel_pipeline(&self, run_id: &str) -> Result<(), WarpError>;
-    async fn get_artifacts(&self, run_id: &str) -> Result<Vec<Artifact>, WarpError>;
-}
-
-impl CICDManager {
-    pub async fn new() -> Result<Self, WarpError> {
-        let config = Arc::new(Mutex::new(CICDConfig::default()));
-        let pipeline_manager = Arc::new(pipeline_manager::PipelineManager::new().await?);
-        let webhook_handler = Arc::new(webhook_handler::WebhookHandler::new().await?);
-        let deployment_manager = Arc::new(deployment::DeploymentManager::new().await?);
-
-        let mut providers: HashMap<CICDProvider, Box<dyn CICDProviderTrait>> = HashMap::new();
-        providers.insert(CICDProvider::GitHubActions, Box::new(github_actions::GitHubActionsProvider::new().await?));
-        providers.insert(CICDProvider::GitLabCI, Box::new(gitlab_ci::GitLabCIProvider::new().await?));
-        providers.insert(CICDProvider::Jenkins, Box::new(jenkins::JenkinsProvider::new().await?));
-        providers.insert(CICDProvider::AzureDevOps, Box::new(azure_devops::AzureDevOpsProvider::new().await?));
-        providers.insert(CICDProvider::CircleCI, Box::new(circleci::CircleCIProvider::new().await?));
-        providers.insert(CICDProvider::TravisCI, Box::new(travis_ci::TravisCIProvider::new().await?));
-
-        Ok(Self {
-            config,
-            pipeline_manager,
-            webhook_handler,
-            deployment_manager,
-            providers,
-            active_runs: Arc::new(Mutex::new(HashMap::new())),
-        })
-    }
-
-    pub async fn create_pipeline(&self, pipeline: Pipeline) -> Result<String, WarpError> {
-        // Validate pipeline configuration
-        self.validate_pipeline(&pipeline).await?;
-
-        // Create pipeline with provider
-        if let Some(provider) = self.providers.get(&pipeline.provider) {
-            let pipeline_id = provider.create_pipeline(&pipeline).await?;
-            
-            // Store pipeline configuration
-            self.pipeline_manager.store_pipeline(pipeline).await?;
-            
-            // Setup webhook
-            self.webhook_handler.setup_webhook(&pipeline_id, &pipeline.repository.webhook_url).await?;
-            
-            Ok(pipeline_id)
-        } else {
-            Err(WarpError::ConfigError(format!("Unsupported CI/CD provider: {:?}", pipeline.provider)))
-        }
-    }
-
-    pub async fn trigger_pipeline(&self, pipeline_id: &str, parameters: HashMap<String, String>) -> Result<String, WarpError> {
-        let pipeline = self.pipeline_manager.get_pipeline(pipeline_id).await?;
-        
-        if let Some(provider) = self.providers.get(&pipeline.provider) {
-            let run_id = provider.trigger_pipeline(pipeline_id, parameters).await?;
-            
-            // Create pipeline run record
-            let pipeline_run = PipelineRun {
-                id: run_id.clone(),
-                pipeline_id: pipeline_id.to_string(),
-                run_number: self.get_next_run_number(pipeline_id).await?,
-                commit_sha: "".to_string(), // Will be updated by webhook
-                branch: pipeline.repository.branch.clone(),
-                triggered_by: "manual".to_string(), // Will be updated based on trigger
-                trigger_type: PipelineTrigger::Manual,
-                started_at: chrono::Utc::now(),
-                finished_at: None,
-                status: PipelineStatus::Pending,
-                stages: Vec::new(),
-                artifacts: Vec::new(),
-                logs: Vec::new(),
-            };
-
-            let mut active_runs = self.active_runs.lock().await;
-            active_runs.insert(run_id.clone(), pipeline_run);
-
-            Ok(run_id)
-        } else {
-            Err(WarpError::ConfigError(format!("Unsupported CI/CD provider: {:?}", pipeline.provider)))
+// CI results reported on pull/merge requests by the forge integration
+impl From<crate::forge::CiState> for PipelineStatus {
+    fn from(state: crate::forge::CiState) -> Self {
+        use crate::forge::CiState;
+        match state {
+            CiState::Pending => PipelineStatus::Pending,
+            CiState::Running => PipelineStatus::Running,
+            CiState::Success => PipelineStatus::Success,
+            CiState::Failure => PipelineStatus::Failed,
+            CiState::Cancelled => PipelineStatus::Cancelled,
+            CiState::Skipped => PipelineStatus::Skipped,
         }
     }
-
-    pub async fn get_pipeline_status(&self, run_id: &str) -> Result<PipelineRun, WarpError> {
-        let active_runs = self.active_runs.lock().await;
-        if let Some(run) = active_runs.get(run_id) {
-            Ok(run.clone())
-        } else {
-            // Try to fetch from storage
-            self.pipeline_manager.get_pipeline_run(run_id).await
-        }
-    }
-
-    pub async fn cancel_pipeline(&self, run_id: &str) -> Result<(), WarpError> {
-        let active_runs = self.active_runs.lock().await;
-        if let Some(run) = active_runs.get(run_id) {
-            let pipeline = self.pipeline_manager.get_pipeline(&run.pipeline_id).await?;
-            
-            if let Some(provider) = self.providers.get(&pipeline.provider) {
-                provider.cancel_pipeline(run_id).await?;
-            }
-        }
-        Ok(())
-    }
-
-    pub async fn handle_webhook(&self, payload: serde_json::Value, headers: HashMap<String, String>) -> Result<(), WarpError> {
-        self.webhook_handler.handle_webhook(payload, headers).await
-    }
-
-    pub async fn deploy_to_environment(&self, pipeline_id: &str, environment: &str, version: &str) -> Result<String, WarpError> {
-        self.deployment_manager.deploy(pipeline_id, environment, version).await
-    }
-
-    pub async fn get_deployment_status(&self, deployment_id: &str) -> Result<deployment::DeploymentStatus, WarpError> {
-        self.deployment_manager.get_status(deployment_id).await
-    }
-
-    pub async fn rollback_deployment(&self, deployment_id: &str) -> Result<(), WarpError> {
-        self.deployment_manager.rollback(deployment_id).await
-    }
-
-    async fn validate_pipeline(&self, pipeline: &Pipeline) -> Result<(), WarpError> {
-        // Validate pipeline configuration
-        if pipeline.name.is_empty() {
-            return Err(WarpError::ConfigError("Pipeline name cannot be empty".to_string()));
-        }
-
-        if pipeline.stages.is_empty() {
-            return Err(WarpError::ConfigError("Pipeline must have at least one stage".to_string()));
-        }
-
-        // Validate stage dependencies
-        for stage in &pipeline.stages {
-            for dependency in &stage.dependencies {
-                if !pipeline.stages.iter().any(|s| s.name == *dependency) {
-                    return Err(WarpError::ConfigError(format!("Stage dependency '{}' not found", dependency)));
-                }
-            }
-        }
-
-        Ok(())
-    }
-
-    async fn get_next_run_number(&self, pipeline_id: &str) -> Result<u64, WarpError> {
-        self.pipeline_manager.get_next_run_number(pipeline_id).await
-    }
 }
 
 impl Default for CICDConfig {
@@ -455,3 +308,25 @@ impl Default for CICDConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::CiState;
+
+    #[test]
+    fn test_forge_ci_states_map_to_pipeline_statuses() {
+        assert_eq!(
+            PipelineStatus::from(CiState::Failure),
+            PipelineStatus::Failed
+        );
+        assert_eq!(
+            PipelineStatus::from(CiState::Success),
+            PipelineStatus::Success
+        );
+        assert_eq!(
+            PipelineStatus::from(CiState::Skipped),
+            PipelineStatus::Skipped
+        );
+    }
+}
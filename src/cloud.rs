@@ -0,0 +1,725 @@
+use crossterm::style::Color;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::CloudConfig;
+use crate::error::WarpError;
+use crate::file_manager::shell_quote;
+use crate::i18n;
+use crate::security::compile;
+use crate::shell::ShellKind;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// The shell's copy of these is tracked from the commands it runs, since a
+// running shell's environment can't be read back
+const ENV_KEYS: &[&str] = &[
+    "AWS_PROFILE",
+    "AWS_DEFAULT_PROFILE",
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+    "AWS_CONFIG_FILE",
+    "AWS_SHARED_CREDENTIALS_FILE",
+    "CLOUDSDK_CONFIG",
+    "CLOUDSDK_ACTIVE_CONFIG_NAME",
+    "CLOUDSDK_CORE_PROJECT",
+    "AZURE_CONFIG_DIR",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+impl Provider {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Provider::Aws => "aws",
+            Provider::Gcp => "gcp",
+            Provider::Azure => "az",
+        }
+    }
+}
+
+// The active profile, project or subscription of one CLI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudContext {
+    pub provider: Provider,
+    pub name: String,
+    pub detail: Option<String>, // AWS region or gcloud configuration
+}
+
+impl CloudContext {
+    pub fn label(&self) -> String {
+        match &self.detail {
+            Some(detail) => format!("{}:{} ({})", self.provider.label(), self.name, detail),
+            None => format!("{}:{}", self.provider.label(), self.name),
+        }
+    }
+}
+
+// Something the picker can switch to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudOption {
+    pub provider: Provider,
+    pub name: String,
+    pub value: String, // what the switch command takes, e.g. a subscription id
+    pub active: bool,
+}
+
+type Ini = Vec<(String, Vec<(String, String)>)>;
+
+fn parse_ini(text: &str) -> Ini {
+    let mut sections: Ini = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.trim().to_string(), Vec::new()));
+        } else if let (Some((key, value)), Some((_, entries))) =
+            (line.split_once('='), sections.last_mut())
+        {
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    sections
+}
+
+fn read_ini(path: &Path) -> Ini {
+    std::fs::read_to_string(path)
+        .map(|text| parse_ini(&text))
+        .unwrap_or_default()
+}
+
+fn ini_value<'a>(ini: &'a Ini, section: &str, key: &str) -> Option<&'a str> {
+    ini.iter()
+        .filter(|(name, _)| name == section)
+        .flat_map(|(_, entries)| entries)
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value.as_str())
+}
+
+fn env_assignment_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // $env.KEY = value (nushell), $env:KEY = value (PowerShell)
+    PATTERN.get_or_init(|| Regex::new(r"^\s*\$env[.:](\w+)\s*=(.*)$").expect("static pattern"))
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+pub struct CloudDetector {
+    home: PathBuf,
+    env: HashMap<String, String>,
+}
+
+impl Default for CloudDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CloudDetector {
+    pub fn new() -> Self {
+        Self::with_env(dirs::home_dir().unwrap_or_default(), std::env::vars())
+    }
+
+    pub fn with_env(home: PathBuf, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            home,
+            env: vars
+                .into_iter()
+                .filter(|(key, _)| ENV_KEYS.contains(&key.as_str()))
+                .collect(),
+        }
+    }
+
+    fn var(&self, key: &str) -> Option<&str> {
+        self.env
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+
+    // Follows `export`/`unset` (and fish's `set -x`/`set -e`) of the
+    // variables above. True when something changed.
+    pub fn observe_command(&mut self, command: &str) -> bool {
+        let mut changed = false;
+        for segment in command.split(['\n', ';', '&', '|']) {
+            let words: Vec<&str> = segment.split_whitespace().collect();
+            // None unsets the variable
+            let assignments: Vec<(&str, Option<String>)> =
+                if let Some(captures) = env_assignment_pattern().captures(segment) {
+                    let key = captures.get(1).map_or("", |m| m.as_str());
+                    vec![(key, Some(unquote(&captures[2])))]
+                } else {
+                    match words.as_slice() {
+                        ["hide-env", rest @ ..] => rest
+                            .iter()
+                            .filter(|key| !key.starts_with('-'))
+                            .map(|key| (*key, None))
+                            .collect(),
+                        ["Remove-Item", path] => match path.get(..4) {
+                            Some(drive) if drive.eq_ignore_ascii_case("env:") => {
+                                vec![(&path[4..], None)]
+                            }
+                            _ => continue,
+                        },
+                        ["export", rest @ ..] => rest
+                            .iter()
+                            .filter_map(|word| word.split_once('='))
+                            .map(|(key, value)| (key, Some(unquote(value))))
+                            .collect(),
+                        ["unset", rest @ ..] => rest.iter().map(|key| (*key, None)).collect(),
+                        ["set", rest @ ..] => {
+                            let flag_count =
+                                rest.iter().take_while(|word| word.starts_with('-')).count();
+                            let flags = rest[..flag_count].concat();
+                            match &rest[flag_count..] {
+                                [key, ..] if flags.contains('e') => vec![(*key, None)],
+                                [key, value @ ..] if flags.contains('x') => {
+                                    vec![(*key, Some(unquote(&value.join(" "))))]
+                                }
+                                _ => continue,
+                            }
+                        }
+                        _ => continue,
+                    }
+                };
+            for (key, value) in assignments {
+                if !ENV_KEYS.contains(&key) {
+                    continue;
+                }
+                changed |= match value {
+                    Some(value) => self.env.insert(key.to_string(), value.clone()) != Some(value),
+                    None => self.env.remove(key).is_some(),
+                };
+            }
+        }
+        changed
+    }
+
+    fn aws_files(&self) -> (PathBuf, PathBuf) {
+        let aws = self.home.join(".aws");
+        (
+            self.var("AWS_CONFIG_FILE")
+                .map_or_else(|| aws.join("config"), PathBuf::from),
+            self.var("AWS_SHARED_CREDENTIALS_FILE")
+                .map_or_else(|| aws.join("credentials"), PathBuf::from),
+        )
+    }
+
+    // Profile names from both files; the config file prefixes them with
+    // "profile " except for the default
+    fn aws_profiles(&self) -> Vec<String> {
+        let (config, credentials) = self.aws_files();
+        let mut profiles: Vec<String> = read_ini(&config)
+            .into_iter()
+            .map(|(name, _)| {
+                name.strip_prefix("profile ")
+                    .map_or(name.clone(), |n| n.trim().to_string())
+            })
+            .chain(read_ini(&credentials).into_iter().map(|(name, _)| name))
+            .collect();
+        profiles.sort();
+        profiles.dedup();
+        profiles
+    }
+
+    fn aws(&self) -> Option<CloudContext> {
+        let profile = self
+            .var("AWS_PROFILE")
+            .or_else(|| self.var("AWS_DEFAULT_PROFILE"))
+            .map(str::to_string);
+        // Without an explicit profile only a configured default counts
+        let profile = match profile {
+            Some(profile) => profile,
+            None if self.aws_profiles().iter().any(|p| p == "default") => "default".to_string(),
+            None => return None,
+        };
+        let section = match profile.as_str() {
+            "default" => "default".to_string(),
+            name => format!("profile {}", name),
+        };
+        let region = self
+            .var("AWS_REGION")
+            .or_else(|| self.var("AWS_DEFAULT_REGION"))
+            .map(str::to_string)
+            .or_else(|| {
+                ini_value(&read_ini(&self.aws_files().0), &section, "region").map(str::to_string)
+            });
+        Some(CloudContext {
+            provider: Provider::Aws,
+            name: profile,
+            detail: region,
+        })
+    }
+
+    fn gcloud_dir(&self) -> PathBuf {
+        if let Some(dir) = self.var("CLOUDSDK_CONFIG") {
+            return PathBuf::from(dir);
+        }
+        if cfg!(windows) {
+            dirs::config_dir().unwrap_or_default().join("gcloud")
+        } else {
+            self.home.join(".config/gcloud")
+        }
+    }
+
+    fn gcloud_configuration(&self) -> String {
+        self.var("CLOUDSDK_ACTIVE_CONFIG_NAME")
+            .map(str::to_string)
+            .or_else(|| {
+                std::fs::read_to_string(self.gcloud_dir().join("active_config"))
+                    .ok()
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+            })
+            .unwrap_or_else(|| "default".to_string())
+    }
+
+    fn gcloud_configurations(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(self.gcloud_dir().join("configurations")) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .strip_prefix("config_")
+                    .map(str::to_string)
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn gcp(&self) -> Option<CloudContext> {
+        let configuration = self.gcloud_configuration();
+        let project = self
+            .var("CLOUDSDK_CORE_PROJECT")
+            .map(str::to_string)
+            .or_else(|| {
+                let path = self
+                    .gcloud_dir()
+                    .join("configurations")
+                    .join(format!("config_{}", configuration));
+                ini_value(&read_ini(&path), "core", "project").map(str::to_string)
+            })?;
+        Some(CloudContext {
+            provider: Provider::Gcp,
+            name: project,
+            detail: (configuration != "default").then_some(configuration),
+        })
+    }
+
+    // (id, name, is default) from the az CLI's profile, which starts with a BOM
+    fn azure_subscriptions(&self) -> Vec<(String, String, bool)> {
+        let dir = self
+            .var("AZURE_CONFIG_DIR")
+            .map_or_else(|| self.home.join(".azure"), PathBuf::from);
+        let Ok(text) = std::fs::read_to_string(dir.join("azureProfile.json")) else {
+            return Vec::new();
+        };
+        let Ok(profile) =
+            serde_json::from_str::<serde_json::Value>(text.trim_start_matches('\u{feff}'))
+        else {
+            return Vec::new();
+        };
+        profile["subscriptions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|subscription| {
+                Some((
+                    subscription["id"].as_str()?.to_string(),
+                    subscription["name"].as_str()?.to_string(),
+                    subscription["isDefault"].as_bool().unwrap_or(false),
+                ))
+            })
+            .collect()
+    }
+
+    fn azure(&self) -> Option<CloudContext> {
+        let (_, name, _) = self
+            .azure_subscriptions()
+            .into_iter()
+            .find(|(_, _, default)| *default)?;
+        Some(CloudContext {
+            provider: Provider::Azure,
+            name,
+            detail: None,
+        })
+    }
+
+    pub fn detect(&self) -> Vec<CloudContext> {
+        [self.aws(), self.gcp(), self.azure()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    pub fn options(&self) -> Vec<CloudOption> {
+        let aws = self.aws().map(|context| context.name);
+        let gcp = self.gcloud_configuration();
+        let mut options: Vec<CloudOption> = self
+            .aws_profiles()
+            .into_iter()
+            .map(|name| CloudOption {
+                provider: Provider::Aws,
+                active: aws.as_deref() == Some(name.as_str()),
+                value: name.clone(),
+                name,
+            })
+            .collect();
+        options.extend(
+            self.gcloud_configurations()
+                .into_iter()
+                .map(|name| CloudOption {
+                    provider: Provider::Gcp,
+                    active: name == gcp,
+                    value: name.clone(),
+                    name,
+                }),
+        );
+        options.extend(
+            self.azure_subscriptions()
+                .into_iter()
+                .map(|(id, name, active)| CloudOption {
+                    provider: Provider::Azure,
+                    name,
+                    value: id,
+                    active,
+                }),
+        );
+        options
+    }
+}
+
+// The command that makes `option` active in the user's shell
+pub fn switch_command(option: &CloudOption, shell: &str) -> String {
+    let value = shell_quote(Path::new(&option.value));
+    match (option.provider, ShellKind::detect(shell)) {
+        (Provider::Aws, ShellKind::Fish) => format!("set -gx AWS_PROFILE {}", value),
+        (Provider::Aws, ShellKind::Nushell) => {
+            format!("$env.AWS_PROFILE = {}", nu_quote(&option.value))
+        }
+        (Provider::Aws, ShellKind::PowerShell) => {
+            format!("$env:AWS_PROFILE = '{}'", option.value.replace('\'', "''"))
+        }
+        (Provider::Aws, _) => format!("export AWS_PROFILE={}", value),
+        (Provider::Gcp, _) => format!("gcloud config configurations activate {}", value),
+        (Provider::Azure, _) => format!("az account set --subscription {}", value),
+    }
+}
+
+// nushell has no escapes inside '...', so a value with a quote uses "..."
+fn nu_quote(value: &str) -> String {
+    match value.contains('\'') {
+        true => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        false => format!("'{}'", value),
+    }
+}
+
+// Warns before destructive commands while a production context is active
+pub struct CloudGuard {
+    production: Vec<Regex>,
+    destructive: Vec<Regex>,
+}
+
+impl CloudGuard {
+    pub fn from_config(config: &CloudConfig) -> Result<Self, WarpError> {
+        Ok(Self {
+            production: compile("production pattern", &config.production_patterns)?,
+            destructive: compile("destructive command pattern", &config.destructive_commands)?,
+        })
+    }
+
+    pub fn is_production(&self, context: &CloudContext) -> bool {
+        self.production
+            .iter()
+            .any(|regex| regex.is_match(&context.name))
+    }
+
+    pub fn check(&self, contexts: &[CloudContext], command: &str) -> Option<String> {
+        if !self.destructive.iter().any(|regex| regex.is_match(command)) {
+            return None;
+        }
+        let context = contexts
+            .iter()
+            .find(|context| self.is_production(context))?;
+        Some(i18n::t_args(
+            "cloud.production_warning",
+            &[("context", &context.label())],
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloudCommand {
+    None,
+    Redraw,
+    Refresh,
+    Switch(String),
+}
+
+// Quick switcher over every profile, configuration and subscription found
+pub struct CloudPanel {
+    options: Vec<CloudOption>,
+    shell: String,
+    selected: usize,
+}
+
+impl CloudPanel {
+    pub fn new(detector: &CloudDetector, shell: &str) -> Self {
+        Self {
+            options: detector.options(),
+            shell: shell.to_string(),
+            selected: 0,
+        }
+    }
+
+    pub fn refresh(&mut self, detector: &CloudDetector) {
+        self.options = detector.options();
+        self.select(self.selected);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.options.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> CloudCommand {
+        self.select(index);
+        match (key, self.options.get(self.selected)) {
+            ('\n', Some(option)) if !option.active => {
+                CloudCommand::Switch(switch_command(option, &self.shell))
+            }
+            ('\n', _) => CloudCommand::None,
+            ('r', _) => CloudCommand::Refresh,
+            _ => CloudCommand::Redraw,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .options
+            .iter()
+            .map(|option| {
+                let (mark, color) = match option.active {
+                    true => ("● ", Some(Color::Green)),
+                    false => ("  ", None),
+                };
+                PanelItem::styled(vec![
+                    (mark.to_string(), color),
+                    (
+                        format!("{:<4}", option.provider.label()),
+                        Some(Color::DarkGrey),
+                    ),
+                    (option.name.clone(), color),
+                ])
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.cloud_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::Cloud,
+            title: i18n::t("panel.cloud"),
+            items,
+            footer: Some(i18n::t("panel.cloud_keys")),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home() -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let root = home.path();
+        std::fs::create_dir_all(root.join(".aws")).unwrap();
+        std::fs::write(
+            root.join(".aws/config"),
+            "[default]\nregion = us-east-1\n\n[profile prod]\nregion = eu-west-1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join(".aws/credentials"),
+            "[staging]\naws_access_key_id = x\n",
+        )
+        .unwrap();
+
+        let gcloud = root.join(".config/gcloud");
+        std::fs::create_dir_all(gcloud.join("configurations")).unwrap();
+        std::fs::write(gcloud.join("active_config"), "work\n").unwrap();
+        std::fs::write(
+            gcloud.join("configurations/config_work"),
+            "[core]\naccount = me@example.com\nproject = acme-prod\n",
+        )
+        .unwrap();
+        std::fs::write(gcloud.join("configurations/config_default"), "[core]\n").unwrap();
+
+        std::fs::create_dir_all(root.join(".azure")).unwrap();
+        std::fs::write(
+            root.join(".azure/azureProfile.json"),
+            "\u{feff}{\"subscriptions\": [{\"id\": \"1111\", \"name\": \"Dev\", \"isDefault\": false}, {\"id\": \"2222\", \"name\": \"Production\", \"isDefault\": true}]}",
+        )
+        .unwrap();
+        home
+    }
+
+    #[test]
+    fn test_detects_each_cli() {
+        let home = home();
+        let detector = CloudDetector::with_env(home.path().to_path_buf(), Vec::new());
+        let labels: Vec<String> = detector.detect().iter().map(CloudContext::label).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "aws:default (us-east-1)",
+                "gcp:acme-prod (work)",
+                "az:Production"
+            ]
+        );
+
+        let detector = CloudDetector::with_env(
+            home.path().to_path_buf(),
+            vec![
+                ("AWS_PROFILE".to_string(), "prod".to_string()),
+                ("CLOUDSDK_CORE_PROJECT".to_string(), "sandbox".to_string()),
+                ("HOME".to_string(), "/ignored".to_string()),
+            ],
+        );
+        let labels: Vec<String> = detector.detect().iter().map(CloudContext::label).collect();
+        assert_eq!(labels[0], "aws:prod (eu-west-1)");
+        assert_eq!(labels[1], "gcp:sandbox (work)");
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(
+            CloudDetector::with_env(empty.path().to_path_buf(), Vec::new())
+                .detect()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_follows_exports() {
+        let home = home();
+        let mut detector = CloudDetector::with_env(home.path().to_path_buf(), Vec::new());
+        assert!(detector.observe_command("export AWS_PROFILE='prod' && aws s3 ls"));
+        assert_eq!(detector.detect()[0].name, "prod");
+        assert!(!detector.observe_command("export AWS_PROFILE=prod"));
+        assert!(!detector.observe_command("export PATH=/bin"));
+        assert!(detector.observe_command("unset AWS_PROFILE"));
+        assert_eq!(detector.detect()[0].name, "default");
+        assert!(detector.observe_command("set -gx AWS_PROFILE staging"));
+        assert_eq!(detector.detect()[0].label(), "aws:staging");
+        assert!(detector.observe_command("set -e AWS_PROFILE"));
+
+        // nushell and PowerShell
+        assert!(detector.observe_command("$env.AWS_PROFILE = \"prod\"; aws s3 ls"));
+        assert_eq!(detector.detect()[0].name, "prod");
+        assert!(detector.observe_command("hide-env AWS_PROFILE"));
+        assert!(detector.observe_command("$env:AWS_PROFILE='staging'"));
+        assert_eq!(detector.detect()[0].name, "staging");
+        assert!(detector.observe_command("Remove-Item Env:AWS_PROFILE"));
+        assert_eq!(detector.detect()[0].name, "default");
+    }
+
+    #[test]
+    fn test_options_and_switching() {
+        let home = home();
+        let detector = CloudDetector::with_env(home.path().to_path_buf(), Vec::new());
+        let options = detector.options();
+        let names: Vec<(&str, &str, bool)> = options
+            .iter()
+            .map(|o| (o.provider.label(), o.name.as_str(), o.active))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("aws", "default", true),
+                ("aws", "prod", false),
+                ("aws", "staging", false),
+                ("gcp", "default", false),
+                ("gcp", "work", true),
+                ("az", "Dev", false),
+                ("az", "Production", true),
+            ]
+        );
+        assert_eq!(
+            switch_command(&options[1], "/bin/zsh"),
+            "export AWS_PROFILE=prod"
+        );
+        assert_eq!(
+            switch_command(&options[1], "/usr/bin/fish"),
+            "set -gx AWS_PROFILE prod"
+        );
+        assert_eq!(
+            switch_command(&options[1], "/opt/homebrew/bin/nu"),
+            "$env.AWS_PROFILE = 'prod'"
+        );
+        assert_eq!(
+            switch_command(&options[1], "pwsh"),
+            "$env:AWS_PROFILE = 'prod'"
+        );
+        assert_eq!(
+            switch_command(&options[5], "bash"),
+            "az account set --subscription 1111"
+        );
+
+        let mut panel = CloudPanel::new(&detector, "bash");
+        assert_eq!(panel.handle_key('\n', 0), CloudCommand::None);
+        assert_eq!(
+            panel.handle_key('\n', 3),
+            CloudCommand::Switch("gcloud config configurations activate default".to_string())
+        );
+    }
+
+    #[test]
+    fn test_guard_warns_in_production_only() {
+        let guard = CloudGuard::from_config(&crate::config::WarpConfig::default().cloud).unwrap();
+        let prod = vec![CloudContext {
+            provider: Provider::Aws,
+            name: "prod".to_string(),
+            detail: None,
+        }];
+        let dev = vec![CloudContext {
+            provider: Provider::Aws,
+            name: "dev".to_string(),
+            detail: None,
+        }];
+        for command in [
+            "aws ec2 terminate-instances --instance-ids i-1",
+            "aws s3 rm s3://bucket --recursive",
+            "terraform destroy",
+            "gcloud compute instances delete web-1",
+            "az group delete --name rg",
+            "kubectl delete namespace shop",
+        ] {
+            assert!(guard.check(&prod, command).is_some(), "{}", command);
+            assert!(guard.check(&dev, command).is_none(), "{}", command);
+        }
+        assert!(guard.check(&prod, "aws s3 ls").is_none());
+        assert!(guard.check(&prod, "terraform plan").is_none());
+    }
+}
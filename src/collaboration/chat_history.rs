@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+
+use super::ChatMessage;
+use crate::error::WarpError;
+
+// Messages per session kept in memory; the file has all of them
+const MAX_MESSAGES: usize = 500;
+
+// Chat messages per session, appended as JSON lines to one file per session
+// so a conversation outlives the process. Without a directory they are only
+// kept in memory.
+pub struct ChatHistory {
+    dir: Option<PathBuf>,
+    sessions: RwLock<HashMap<String, Vec<ChatMessage>>>,
+}
+
+impl ChatHistory {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("warp").join("chat"))
+    }
+
+    pub async fn store(&self, message: &ChatMessage) -> Result<(), WarpError> {
+        if let (Some(dir), Some(path)) = (&self.dir, self.path(&message.session_id)) {
+            tokio::fs::create_dir_all(dir).await?;
+            let mut line = serde_json::to_vec(message)?;
+            line.push(b'\n');
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            file.write_all(&line).await?;
+        }
+        let mut sessions = self.sessions.write().await;
+        let messages = sessions.entry(message.session_id.clone()).or_default();
+        messages.push(message.clone());
+        let excess = messages.len().saturating_sub(MAX_MESSAGES);
+        messages.drain(..excess);
+        Ok(())
+    }
+
+    // Oldest first. A session this process hasn't seen is read from its file.
+    pub async fn messages(&self, session_id: &str) -> Result<Vec<ChatMessage>, WarpError> {
+        if let Some(messages) = self.sessions.read().await.get(session_id) {
+            return Ok(messages.clone());
+        }
+        let Some(path) = self.path(session_id) else {
+            return Ok(Vec::new());
+        };
+        let text = match tokio::fs::read_to_string(&path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        // A line cut short by a crash is skipped
+        let mut messages: Vec<ChatMessage> = text
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let excess = messages.len().saturating_sub(MAX_MESSAGES);
+        messages.drain(..excess);
+        self.sessions
+            .write()
+            .await
+            .insert(session_id.to_string(), messages.clone());
+        Ok(messages)
+    }
+
+    // Session ids come from peers too, so only plain ones name a file
+    fn path(&self, session_id: &str) -> Option<PathBuf> {
+        let plain = !session_id.is_empty()
+            && session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        let dir = self.dir.as_ref().filter(|_| plain)?;
+        Some(dir.join(format!("{}.jsonl", session_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collaboration::MessageType;
+
+    fn message(session_id: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            message_id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            user_id: "ana".to_string(),
+            username: "ana".to_string(),
+            content: content.to_string(),
+            message_type: MessageType::Text,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            reactions: HashMap::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_outlive_the_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = ChatHistory::new(Some(dir.path().to_path_buf()));
+        history.store(&message("s-1", "hello")).await.unwrap();
+        history.store(&message("s-1", "again")).await.unwrap();
+
+        let reopened = ChatHistory::new(Some(dir.path().to_path_buf()));
+        let contents: Vec<_> = reopened
+            .messages("s-1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents, ["hello", "again"]);
+        assert!(reopened.messages("s-2").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_odd_session_ids_stay_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = ChatHistory::new(Some(dir.path().to_path_buf()));
+        history.store(&message("../escape", "hi")).await.unwrap();
+        assert_eq!(history.messages("../escape").await.unwrap().len(), 1);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,582 @@
+use std::collections::{HashMap, VecDeque};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+
+use super::{ParticipantRole, SessionSettings};
+use crate::error::WarpError;
+
+pub const JOIN_LINK_PREFIX: &str = "warp://join/";
+// Codes are read aloud and typed, so no 0/O or 1/I
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 8;
+// Draws before giving up on a code no outstanding invite has
+const CODE_ATTEMPTS: usize = 16;
+// Codes are only 40 bits, so wrong ones are limited across all callers.
+// Signed links aren't guessable and keep working while codes are locked.
+const MAX_FAILED_CODES: usize = 20;
+const FAILED_CODE_WINDOW_SECS: i64 = 60;
+// Join requests waiting for the owner, per session
+const MAX_PENDING: usize = 50;
+
+// What a join link carries. It's signed, so a link can't be edited to
+// another session, role or expiry; revocation still needs the invite table.
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    invite_id: String,
+    session_id: String,
+    role: ParticipantRole,
+    expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    pub invite_id: String,
+    pub session_id: String,
+    pub created_by: String,
+    pub role: ParticipantRole,
+    // Short form of the link, e.g. `K7QX-2MPA`
+    pub code: String,
+    pub link: String,
+    pub expires_at: DateTime<Utc>,
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinRequest {
+    pub request_id: String,
+    pub session_id: String,
+    pub invite_id: String,
+    pub user_id: String,
+    pub role: ParticipantRole,
+    // Joined without an account, under a generated name
+    pub anonymous: bool,
+    pub requested_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub enum JoinOutcome {
+    Admitted(JoinRequest),
+    // Waiting in the owner's approval queue
+    Pending(JoinRequest),
+}
+
+// Join links and codes for sessions, and the queue of requests waiting for
+// the owner when a session requires approval
+pub struct InvitationManager {
+    key: hmac::Key,
+    rng: SystemRandom,
+    invites: RwLock<HashMap<String, Invite>>,
+    pending: RwLock<HashMap<String, JoinRequest>>,
+    // When recent wrong codes were entered, oldest first
+    failed_codes: Mutex<VecDeque<DateTime<Utc>>>,
+}
+
+impl InvitationManager {
+    // Links are signed with a key that lives as long as the process, like
+    // the invites themselves
+    pub async fn new() -> Result<Self, WarpError> {
+        let rng = SystemRandom::new();
+        let key = hmac::Key::generate(hmac::HMAC_SHA256, &rng)
+            .map_err(|_| WarpError::Config("Could not generate an invitation key".to_string()))?;
+        Ok(Self {
+            key,
+            rng,
+            invites: RwLock::new(HashMap::new()),
+            pending: RwLock::new(HashMap::new()),
+            failed_codes: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    pub async fn create_invite(
+        &self,
+        session_id: &str,
+        created_by: &str,
+        role: ParticipantRole,
+        ttl: Duration,
+        max_uses: Option<u32>,
+        now: DateTime<Utc>,
+    ) -> Result<Invite, WarpError> {
+        if matches!(role, ParticipantRole::Owner) {
+            return Err(WarpError::Validation(
+                "Invitations can't make someone the owner".to_string(),
+            ));
+        }
+        if ttl <= Duration::zero() {
+            return Err(WarpError::Validation(
+                "An invitation has to expire in the future".to_string(),
+            ));
+        }
+        let invite_id = uuid::Uuid::new_v4().to_string();
+        let expires_at = now + ttl;
+        let claims = Claims {
+            invite_id: invite_id.clone(),
+            session_id: session_id.to_string(),
+            role: role.clone(),
+            expires_at: expires_at.timestamp(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?);
+        let signature = URL_SAFE_NO_PAD.encode(hmac::sign(&self.key, payload.as_bytes()));
+        let mut invites = self.invites.write().await;
+        let invite = Invite {
+            invite_id: invite_id.clone(),
+            session_id: session_id.to_string(),
+            created_by: created_by.to_string(),
+            role,
+            code: unique_code(|| self.random_code(), &invites)?,
+            link: format!("{}{}.{}", JOIN_LINK_PREFIX, payload, signature),
+            expires_at,
+            max_uses,
+            uses: 0,
+        };
+        invites.insert(invite_id, invite.clone());
+        Ok(invite)
+    }
+
+    // Accepts a join link or a code. Anonymous joiners need the session to
+    // allow them and always come in as guests, whatever the invite says.
+    pub async fn redeem(
+        &self,
+        link_or_code: &str,
+        user_id: Option<&str>,
+        settings: &SessionSettings,
+        now: DateTime<Utc>,
+    ) -> Result<JoinOutcome, WarpError> {
+        let invite = self.resolve(link_or_code, now).await?;
+        let (user_id, role, anonymous) = match user_id {
+            Some(user_id) => (user_id.to_string(), invite.role.clone(), false),
+            None if settings.allow_anonymous => {
+                let name = format!("guest-{}", &uuid::Uuid::new_v4().simple().to_string()[..6]);
+                (name, ParticipantRole::Guest, true)
+            }
+            None => {
+                return Err(WarpError::Permission(
+                    "This session doesn't allow anonymous guests".to_string(),
+                ))
+            }
+        };
+        let request = JoinRequest {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            session_id: invite.session_id.clone(),
+            invite_id: invite.invite_id.clone(),
+            user_id,
+            role,
+            anonymous,
+            requested_at: now,
+        };
+        if !settings.require_approval {
+            self.use_invite(&invite.invite_id, now).await?;
+            return Ok(JoinOutcome::Admitted(request));
+        }
+        let mut pending = self.pending.write().await;
+        let queued = pending
+            .values()
+            .filter(|queued| queued.session_id == request.session_id)
+            .count();
+        if queued >= MAX_PENDING {
+            return Err(WarpError::Validation(
+                "Too many join requests are waiting".to_string(),
+            ));
+        }
+        pending.insert(request.request_id.clone(), request.clone());
+        Ok(JoinOutcome::Pending(request))
+    }
+
+    // The session a link or code is for, so its settings can be looked up
+    // before redeeming
+    pub async fn session_for(
+        &self,
+        link_or_code: &str,
+        now: DateTime<Utc>,
+    ) -> Result<String, WarpError> {
+        Ok(self.resolve(link_or_code, now).await?.session_id)
+    }
+
+    // Oldest first
+    pub async fn pending(&self, session_id: &str) -> Vec<JoinRequest> {
+        let pending = self.pending.read().await;
+        let mut requests: Vec<JoinRequest> = pending
+            .values()
+            .filter(|request| request.session_id == session_id)
+            .cloned()
+            .collect();
+        requests.sort_by_key(|request| request.requested_at);
+        requests
+    }
+
+    // Takes the request off the queue. An approval still fails if the
+    // invite was revoked, expired or used up in the meantime.
+    pub async fn decide(
+        &self,
+        session_id: &str,
+        request_id: &str,
+        approve: bool,
+        now: DateTime<Utc>,
+    ) -> Result<JoinRequest, WarpError> {
+        let mut pending = self.pending.write().await;
+        let ours = pending
+            .get(request_id)
+            .is_some_and(|request| request.session_id == session_id);
+        let request = ours
+            .then(|| pending.remove(request_id))
+            .flatten()
+            .ok_or_else(|| WarpError::not_found("join request", request_id))?;
+        drop(pending);
+        if approve {
+            self.use_invite(&request.invite_id, now).await?;
+        }
+        Ok(request)
+    }
+
+    pub async fn invites(&self, session_id: &str) -> Vec<Invite> {
+        let invites = self.invites.read().await;
+        let mut outstanding: Vec<Invite> = invites
+            .values()
+            .filter(|invite| invite.session_id == session_id)
+            .cloned()
+            .collect();
+        outstanding.sort_by_key(|invite| invite.expires_at);
+        outstanding
+    }
+
+    // Also drops requests made with it that are still waiting
+    pub async fn revoke(&self, session_id: &str, invite_id: &str) -> Result<(), WarpError> {
+        let mut invites = self.invites.write().await;
+        if invites
+            .get(invite_id)
+            .is_none_or(|invite| invite.session_id != session_id)
+        {
+            return Err(WarpError::not_found("invitation", invite_id));
+        }
+        invites.remove(invite_id);
+        self.pending
+            .write()
+            .await
+            .retain(|_, request| request.invite_id != invite_id);
+        Ok(())
+    }
+
+    pub async fn cleanup_session(&self, session_id: &str) {
+        self.invites
+            .write()
+            .await
+            .retain(|_, invite| invite.session_id != session_id);
+        self.pending
+            .write()
+            .await
+            .retain(|_, request| request.session_id != session_id);
+    }
+
+    async fn resolve(&self, link_or_code: &str, now: DateTime<Utc>) -> Result<Invite, WarpError> {
+        let text = link_or_code.trim();
+        let invite_id = match text.strip_prefix(JOIN_LINK_PREFIX) {
+            Some(token) => self.verify(token)?.invite_id,
+            None => {
+                let mut failed = self.failed_codes.lock().await;
+                let window = Duration::seconds(FAILED_CODE_WINDOW_SECS);
+                while failed.front().is_some_and(|at| now - *at >= window) {
+                    failed.pop_front();
+                }
+                if failed.len() >= MAX_FAILED_CODES {
+                    return Err(WarpError::Validation(
+                        "Too many wrong invitation codes; try again in a minute or use the join link".to_string(),
+                    ));
+                }
+                let code = normalize_code(text);
+                let invites = self.invites.read().await;
+                match invites
+                    .values()
+                    .find(|invite| normalize_code(&invite.code) == code)
+                {
+                    Some(invite) => invite.invite_id.clone(),
+                    None => {
+                        failed.push_back(now);
+                        return Err(WarpError::Validation(
+                            "Unknown or revoked invitation".to_string(),
+                        ));
+                    }
+                }
+            }
+        };
+        let invites = self.invites.read().await;
+        let invite = invites
+            .get(&invite_id)
+            .ok_or_else(|| WarpError::Validation("Unknown or revoked invitation".to_string()))?;
+        check_usable(invite, now)?;
+        Ok(invite.clone())
+    }
+
+    fn verify(&self, token: &str) -> Result<Claims, WarpError> {
+        let invalid = || WarpError::Validation("Invalid join link".to_string());
+        let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        hmac::verify(&self.key, payload.as_bytes(), &signature).map_err(|_| invalid())?;
+        let claims = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+        Ok(serde_json::from_slice(&claims)?)
+    }
+
+    async fn use_invite(&self, invite_id: &str, now: DateTime<Utc>) -> Result<(), WarpError> {
+        let mut invites = self.invites.write().await;
+        let invite = invites
+            .get_mut(invite_id)
+            .ok_or_else(|| WarpError::Validation("Unknown or revoked invitation".to_string()))?;
+        check_usable(invite, now)?;
+        invite.uses += 1;
+        Ok(())
+    }
+
+    fn random_code(&self) -> Result<String, WarpError> {
+        let mut bytes = [0u8; CODE_LEN];
+        self.rng
+            .fill(&mut bytes)
+            .map_err(|_| WarpError::Config("Could not generate an invitation code".to_string()))?;
+        let chars: String = bytes
+            .iter()
+            .map(|byte| CODE_ALPHABET[*byte as usize % CODE_ALPHABET.len()] as char)
+            .collect();
+        Ok(format!(
+            "{}-{}",
+            &chars[..CODE_LEN / 2],
+            &chars[CODE_LEN / 2..]
+        ))
+    }
+}
+
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+// Draws again while the code belongs to an outstanding invite, since a
+// code has to name exactly one
+fn unique_code(
+    mut draw: impl FnMut() -> Result<String, WarpError>,
+    invites: &HashMap<String, Invite>,
+) -> Result<String, WarpError> {
+    for _ in 0..CODE_ATTEMPTS {
+        let code = draw()?;
+        if !invites.values().any(|invite| invite.code == code) {
+            return Ok(code);
+        }
+    }
+    Err(WarpError::Config(
+        "Could not find an unused invitation code".to_string(),
+    ))
+}
+
+fn check_usable(invite: &Invite, now: DateTime<Utc>) -> Result<(), WarpError> {
+    if now >= invite.expires_at {
+        return Err(WarpError::Validation(
+            "This invitation has expired".to_string(),
+        ));
+    }
+    if invite.max_uses.is_some_and(|max| invite.uses >= max) {
+        return Err(WarpError::Validation(
+            "This invitation has been used up".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(require_approval: bool, allow_anonymous: bool) -> SessionSettings {
+        SessionSettings {
+            max_participants: 10,
+            require_approval,
+            allow_anonymous,
+            enable_voice_chat: true,
+            enable_screen_sharing: true,
+            enable_file_sharing: true,
+            enable_whiteboard: true,
+            auto_save_interval: 60,
+            session_timeout: 3600,
+            recording_enabled: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_links_codes_and_expiry() {
+        let manager = InvitationManager::new().await.unwrap();
+        let now = Utc::now();
+        let invite = manager
+            .create_invite(
+                "s1",
+                "ada",
+                ParticipantRole::Contributor,
+                Duration::hours(1),
+                Some(1),
+                now,
+            )
+            .await
+            .unwrap();
+        let open = settings(false, false);
+
+        // A tampered link fails its signature
+        let forged = invite.link.replacen('.', "x.", 1);
+        assert!(manager
+            .redeem(&forged, Some("bob"), &open, now)
+            .await
+            .is_err());
+        // Anonymous guests aren't allowed here
+        assert!(manager
+            .redeem(&invite.link, None, &open, now)
+            .await
+            .is_err());
+
+        let code = invite.code.to_lowercase().replace('-', "");
+        let JoinOutcome::Admitted(request) = manager
+            .redeem(&code, Some("bob"), &open, now)
+            .await
+            .unwrap()
+        else {
+            panic!("expected to be admitted");
+        };
+        assert!(matches!(request.role, ParticipantRole::Contributor));
+        // Single use
+        assert!(manager
+            .redeem(&invite.link, Some("carol"), &open, now)
+            .await
+            .is_err());
+
+        let later = manager
+            .create_invite(
+                "s1",
+                "ada",
+                ParticipantRole::Contributor,
+                Duration::minutes(5),
+                None,
+                now,
+            )
+            .await
+            .unwrap();
+        let expired = now + Duration::minutes(6);
+        assert!(manager
+            .redeem(&later.link, Some("carol"), &open, expired)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_approval_queue_and_revocation() {
+        let manager = InvitationManager::new().await.unwrap();
+        let now = Utc::now();
+        let invite = manager
+            .create_invite(
+                "s1",
+                "ada",
+                ParticipantRole::Moderator,
+                Duration::hours(1),
+                None,
+                now,
+            )
+            .await
+            .unwrap();
+        let gated = settings(true, true);
+
+        let JoinOutcome::Pending(guest) = manager
+            .redeem(&invite.link, None, &gated, now)
+            .await
+            .unwrap()
+        else {
+            panic!("expected to wait for approval");
+        };
+        assert!(guest.anonymous);
+        assert!(matches!(guest.role, ParticipantRole::Guest));
+        manager
+            .redeem(&invite.code, Some("bob"), &gated, now)
+            .await
+            .unwrap();
+        assert_eq!(manager.pending("s1").await.len(), 2);
+
+        let approved = manager
+            .decide("s1", &guest.request_id, true, now)
+            .await
+            .unwrap();
+        assert_eq!(approved.user_id, guest.user_id);
+        assert!(manager
+            .decide("s1", &guest.request_id, true, now)
+            .await
+            .is_err());
+
+        manager.revoke("s1", &invite.invite_id).await.unwrap();
+        assert!(manager.pending("s1").await.is_empty());
+        assert!(manager
+            .redeem(&invite.link, Some("carol"), &gated, now)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wrong_codes_are_rate_limited() {
+        let manager = InvitationManager::new().await.unwrap();
+        let now = Utc::now();
+        let invite = manager
+            .create_invite(
+                "s1",
+                "ada",
+                ParticipantRole::Contributor,
+                Duration::hours(1),
+                None,
+                now,
+            )
+            .await
+            .unwrap();
+        let open = settings(false, false);
+        for _ in 0..MAX_FAILED_CODES {
+            assert!(manager
+                .redeem("AAAA-AAAA", Some("mallory"), &open, now)
+                .await
+                .is_err());
+        }
+
+        // Even the right code is refused until the window passes; the link isn't
+        assert!(manager
+            .redeem(&invite.code, Some("bob"), &open, now)
+            .await
+            .is_err());
+        assert!(manager
+            .redeem(&invite.link, Some("bob"), &open, now)
+            .await
+            .is_ok());
+        let later = now + Duration::seconds(FAILED_CODE_WINDOW_SECS);
+        assert!(manager
+            .redeem(&invite.code, Some("bob"), &open, later)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_codes_are_redrawn_on_collision() {
+        let manager = InvitationManager::new().await.unwrap();
+        let now = Utc::now();
+        let taken = manager
+            .create_invite(
+                "s1",
+                "ada",
+                ParticipantRole::Contributor,
+                Duration::hours(1),
+                None,
+                now,
+            )
+            .await
+            .unwrap();
+        let invites = manager.invites.read().await;
+        let mut draws = vec![Ok("ZZZZ-ZZZZ".to_string()), Ok(taken.code.clone())];
+        assert_eq!(
+            unique_code(|| draws.pop().unwrap(), &invites).unwrap(),
+            "ZZZZ-ZZZZ"
+        );
+        assert!(unique_code(|| Ok(taken.code.clone()), &invites).is_err());
+    }
+}
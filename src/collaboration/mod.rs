@@ -1,17 +1,20 @@
+use crate::alerts::Alert;
+use crate::error::WarpError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock, broadcast};
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
+use tokio::sync::{broadcast, Mutex, RwLock};
 
-pub mod session_manager;
-pub mod real_time_sync;
-pub mod voice_chat;
+pub mod chat_history;
+pub mod invitations;
+pub mod permissions;
+pub mod presence;
 pub mod screen_sharing;
-pub mod code_sharing;
+pub mod voice_chat;
 pub mod whiteboard;
-pub mod presence;
-pub mod permissions;
+
+// Author of system messages, such as relayed alerts
+pub const SYSTEM_USER: &str = "system";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollaborationSession {
@@ -41,6 +44,8 @@ pub struct Participant {
     pub status: ParticipantStatus,
     pub cursor_position: Option<CursorPosition>,
     pub current_view: Option<ViewState>,
+    #[serde(default)]
+    pub speaking: bool, // lit in the presence list while voice is detected
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,7 +60,7 @@ pub enum SessionType {
     Support,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SessionStatus {
     Active,
     Paused,
@@ -72,7 +77,7 @@ pub enum ParticipantRole {
     Guest,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Permission {
     ViewCode,
     EditCode,
@@ -173,15 +178,18 @@ pub enum EventType {
     ParticipantJoined,
     ParticipantLeft,
     ParticipantStatusChanged,
+    JoinRequested,
+    JoinDenied,
+    TypingChanged,
     CursorMoved,
     ViewChanged,
-    
+
     // Code events
     CodeChanged,
     FileOpened,
     FileClosed,
     FileSaved,
-    
+
     // Debug events
     BreakpointSet,
     BreakpointRemoved,
@@ -189,14 +197,21 @@ pub enum EventType {
     DebuggerStopped,
     DebuggerPaused,
     DebuggerResumed,
-    
+
     // Communication events
     ChatMessage,
     VoiceStarted,
     VoiceStopped,
+    SpeakingChanged,
     ScreenShareStarted,
     ScreenShareStopped,
-    
+    ControlRequested,
+    ControlGranted,
+    ControlReleased,
+
+    // Whiteboard events
+    WhiteboardUpdated,
+
     // System events
     SessionStarted,
     SessionEnded,
@@ -260,40 +275,57 @@ pub enum ChangeType {
 
 pub struct CollaborationManager {
     sessions: Arc<RwLock<HashMap<String, CollaborationSession>>>,
-    session_manager: Arc<session_manager::SessionManager>,
-    real_time_sync: Arc<real_time_sync::RealTimeSync>,
     voice_chat: Arc<voice_chat::VoiceChatManager>,
     screen_sharing: Arc<screen_sharing::ScreenSharingManager>,
-    code_sharing: Arc<code_sharing::CodeSharingManager>,
     whiteboard: Arc<whiteboard::WhiteboardManager>,
     presence: Arc<presence::PresenceManager>,
+    chat_history: chat_history::ChatHistory,
     permissions: Arc<permissions::PermissionManager>,
+    invitations: Arc<invitations::InvitationManager>,
     event_broadcaster: broadcast::Sender<CollaborationEvent>,
     active_connections: Arc<Mutex<HashMap<String, Vec<String>>>>, // session_id -> user_ids
 }
 
 impl CollaborationManager {
-    pub async fn new() -> Result<Self, WarpError> {
+    pub async fn new(config: &crate::config::WarpConfig) -> Result<Self, WarpError> {
         let (event_broadcaster, _) = broadcast::channel(1000);
-        
+        let sessions = Arc::new(RwLock::new(HashMap::new()));
+        let voice_chat = Arc::new(voice_chat::VoiceChatManager::new(config.voice.clone()).await?);
+        let presence = Arc::new(presence::PresenceManager::new(config.presence.clone()).await?);
+        Self::forward_speaking(
+            sessions.clone(),
+            presence.clone(),
+            voice_chat.subscribe_speaking(),
+            event_broadcaster.clone(),
+        );
+        Self::sweep_presence(
+            sessions.clone(),
+            presence.clone(),
+            event_broadcaster.clone(),
+        );
+
         Ok(Self {
-            sessions: Arc::new(RwLock::new(HashMap::new())),
-            session_manager: Arc::new(session_manager::SessionManager::new().await?),
-            real_time_sync: Arc::new(real_time_sync::RealTimeSync::new().await?),
-            voice_chat: Arc::new(voice_chat::VoiceChatManager::new().await?),
+            sessions,
+            voice_chat,
             screen_sharing: Arc::new(screen_sharing::ScreenSharingManager::new().await?),
-            code_sharing: Arc::new(code_sharing::CodeSharingManager::new().await?),
             whiteboard: Arc::new(whiteboard::WhiteboardManager::new().await?),
-            presence: Arc::new(presence::PresenceManager::new().await?),
+            presence,
+            chat_history: chat_history::ChatHistory::new(chat_history::ChatHistory::default_dir()),
             permissions: Arc::new(permissions::PermissionManager::new().await?),
+            invitations: Arc::new(invitations::InvitationManager::new().await?),
             event_broadcaster,
             active_connections: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
-    pub async fn create_session(&self, owner_id: &str, session_type: SessionType, settings: SessionSettings) -> Result<String, WarpError> {
+    pub async fn create_session(
+        &self,
+        owner_id: &str,
+        session_type: SessionType,
+        settings: SessionSettings,
+    ) -> Result<String, WarpError> {
         let session_id = uuid::Uuid::new_v4().to_string();
-        
+
         let session = CollaborationSession {
             session_id: session_id.clone(),
             name: format!("{:?} Session", session_type),
@@ -305,25 +337,13 @@ impl CollaborationManager {
                 display_name: owner_id.to_string(),
                 avatar_url: None,
                 role: ParticipantRole::Owner,
-                permissions: vec![
-                    Permission::ViewCode,
-                    Permission::EditCode,
-                    Permission::ExecuteCode,
-                    Permission::ViewDebugger,
-                    Permission::ControlDebugger,
-                    Permission::ViewTerminal,
-                    Permission::ControlTerminal,
-                    Permission::ShareScreen,
-                    Permission::UseVoiceChat,
-                    Permission::UseTextChat,
-                    Permission::ManageParticipants,
-                    Permission::ModifySettings,
-                ],
+                permissions: permissions::ALL_PERMISSIONS.to_vec(),
                 joined_at: chrono::Utc::now(),
                 last_active: chrono::Utc::now(),
                 status: ParticipantStatus::Online,
                 cursor_position: None,
                 current_view: None,
+                speaking: false,
             }],
             session_type,
             created_at: chrono::Utc::now(),
@@ -336,10 +356,8 @@ impl CollaborationManager {
         let mut sessions = self.sessions.write().await;
         sessions.insert(session_id.clone(), session.clone());
 
-        // Initialize session components
-        self.session_manager.initialize_session(&session).await?;
-        self.real_time_sync.create_sync_room(&session_id).await?;
-        
+        self.permissions.create_session(&session_id, owner_id).await;
+
         // Broadcast session created event
         let event = CollaborationEvent {
             event_id: uuid::Uuid::new_v4().to_string(),
@@ -354,18 +372,23 @@ impl CollaborationManager {
         Ok(session_id)
     }
 
-    pub async fn join_session(&self, session_id: &str, user_id: &str, role: ParticipantRole) -> Result<(), WarpError> {
+    pub async fn join_session(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        role: ParticipantRole,
+    ) -> Result<(), WarpError> {
         let mut sessions = self.sessions.write().await;
-        
+
         if let Some(session) = sessions.get_mut(session_id) {
             // Check if user is already in session
             if session.participants.iter().any(|p| p.user_id == user_id) {
-                return Err(WarpError::ConfigError("User already in session".to_string()));
+                return Err(WarpError::Validation("User already in session".to_string()));
             }
 
             // Check session limits
             if session.participants.len() >= session.settings.max_participants as usize {
-                return Err(WarpError::ConfigError("Session is full".to_string()));
+                return Err(WarpError::Validation("Session is full".to_string()));
             }
 
             // Add participant
@@ -381,16 +404,20 @@ impl CollaborationManager {
                 status: ParticipantStatus::Online,
                 cursor_position: None,
                 current_view: None,
+                speaking: false,
             };
 
             session.participants.push(participant.clone());
+            self.permissions
+                .add_participant(session_id, user_id, &participant.permissions)
+                .await;
 
             // Update active connections
             let mut connections = self.active_connections.lock().await;
-            connections.entry(session_id.to_string()).or_insert_with(Vec::new).push(user_id.to_string());
-
-            // Join real-time sync room
-            self.real_time_sync.join_room(session_id, user_id).await?;
+            connections
+                .entry(session_id.to_string())
+                .or_insert_with(Vec::new)
+                .push(user_id.to_string());
 
             // Update presence
             self.presence.set_user_online(user_id, session_id).await?;
@@ -408,13 +435,150 @@ impl CollaborationManager {
 
             Ok(())
         } else {
-            Err(WarpError::ConfigError("Session not found".to_string()))
+            Err(WarpError::not_found("session", session_id))
+        }
+    }
+
+    pub async fn create_invite(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        role: ParticipantRole,
+        ttl_secs: u64,
+        max_uses: Option<u32>,
+    ) -> Result<invitations::Invite, WarpError> {
+        self.check_manage_participants(session_id, user_id).await?;
+        let ttl = chrono::Duration::seconds(ttl_secs.min(i64::MAX as u64) as i64);
+        self.invitations
+            .create_invite(session_id, user_id, role, ttl, max_uses, chrono::Utc::now())
+            .await
+    }
+
+    // Redeems a join link or code. Without `user_id` the joiner is an
+    // anonymous guest, if the session allows those. Sessions that require
+    // approval queue the request for the owner instead of joining.
+    pub async fn request_join(
+        &self,
+        link_or_code: &str,
+        user_id: Option<&str>,
+    ) -> Result<invitations::JoinOutcome, WarpError> {
+        let now = chrono::Utc::now();
+        let session_id = self.invitations.session_for(link_or_code, now).await?;
+        let settings = {
+            let sessions = self.sessions.read().await;
+            let session = sessions
+                .get(&session_id)
+                .ok_or_else(|| WarpError::not_found("session", &session_id))?;
+            if session.status != SessionStatus::Active {
+                return Err(WarpError::Validation("This session has ended".to_string()));
+            }
+            session.settings.clone()
+        };
+
+        let outcome = self
+            .invitations
+            .redeem(link_or_code, user_id, &settings, now)
+            .await?;
+        match &outcome {
+            invitations::JoinOutcome::Admitted(request) => {
+                self.join_session(&request.session_id, &request.user_id, request.role.clone())
+                    .await?;
+            }
+            invitations::JoinOutcome::Pending(request) => {
+                let event = CollaborationEvent {
+                    event_id: uuid::Uuid::new_v4().to_string(),
+                    session_id: request.session_id.clone(),
+                    user_id: request.user_id.clone(),
+                    timestamp: now,
+                    event_type: EventType::JoinRequested,
+                    data: serde_json::to_value(request)?,
+                };
+                let _ = self.event_broadcaster.send(event);
+            }
         }
+        Ok(outcome)
+    }
+
+    pub async fn pending_join_requests(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<invitations::JoinRequest>, WarpError> {
+        self.check_manage_participants(session_id, user_id).await?;
+        Ok(self.invitations.pending(session_id).await)
+    }
+
+    pub async fn respond_to_join_request(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        request_id: &str,
+        approve: bool,
+    ) -> Result<(), WarpError> {
+        self.check_manage_participants(session_id, user_id).await?;
+        let request = self
+            .invitations
+            .decide(session_id, request_id, approve, chrono::Utc::now())
+            .await?;
+        if approve {
+            return self
+                .join_session(session_id, &request.user_id, request.role)
+                .await;
+        }
+
+        let event = CollaborationEvent {
+            event_id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            event_type: EventType::JoinDenied,
+            data: serde_json::json!({"request_id": request_id, "user_id": request.user_id}),
+        };
+        let _ = self.event_broadcaster.send(event);
+        Ok(())
+    }
+
+    pub async fn session_invites(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<invitations::Invite>, WarpError> {
+        self.check_manage_participants(session_id, user_id).await?;
+        Ok(self.invitations.invites(session_id).await)
+    }
+
+    // Links and codes stop working at once; requests made with them that
+    // are still waiting are dropped
+    pub async fn revoke_invite(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        invite_id: &str,
+    ) -> Result<(), WarpError> {
+        self.check_manage_participants(session_id, user_id).await?;
+        self.invitations.revoke(session_id, invite_id).await
+    }
+
+    async fn check_manage_participants(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<(), WarpError> {
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::ManageParticipants)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
+        }
+        Ok(())
     }
 
     pub async fn leave_session(&self, session_id: &str, user_id: &str) -> Result<(), WarpError> {
         let mut sessions = self.sessions.write().await;
-        
+
         if let Some(session) = sessions.get_mut(session_id) {
             // Remove participant
             session.participants.retain(|p| p.user_id != user_id);
@@ -425,15 +589,17 @@ impl CollaborationManager {
                 user_list.retain(|id| id != user_id);
             }
 
-            // Leave real-time sync room
-            self.real_time_sync.leave_room(session_id, user_id).await?;
-
             // Update presence
             self.presence.set_user_offline(user_id, session_id).await?;
+            self.permissions
+                .remove_participant(session_id, user_id)
+                .await;
 
             // Stop any active sharing
             self.voice_chat.stop_for_user(session_id, user_id).await?;
-            self.screen_sharing.stop_for_user(session_id, user_id).await?;
+            self.screen_sharing
+                .stop_for_user(session_id, user_id)
+                .await?;
 
             // Broadcast participant left event
             let event = CollaborationEvent {
@@ -453,13 +619,19 @@ impl CollaborationManager {
 
             Ok(())
         } else {
-            Err(WarpError::ConfigError("Session not found".to_string()))
+            Err(WarpError::not_found("session", session_id))
         }
     }
 
-    pub async fn send_chat_message(&self, session_id: &str, user_id: &str, content: &str, message_type: MessageType) -> Result<String, WarpError> {
+    pub async fn send_chat_message(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        content: &str,
+        message_type: MessageType,
+    ) -> Result<String, WarpError> {
         let message_id = uuid::Uuid::new_v4().to_string();
-        
+
         let message = ChatMessage {
             message_id: message_id.clone(),
             session_id: session_id.to_string(),
@@ -473,8 +645,10 @@ impl CollaborationManager {
             attachments: Vec::new(),
         };
 
-        // Store message
-        self.session_manager.store_chat_message(&message).await?;
+        self.chat_history.store(&message).await?;
+        self.presence
+            .set_typing(session_id, user_id, false, message.timestamp)
+            .await;
 
         // Broadcast message
         let event = CollaborationEvent {
@@ -490,14 +664,28 @@ impl CollaborationManager {
         Ok(message_id)
     }
 
-    pub async fn share_code(&self, session_id: &str, user_id: &str, file_path: &str, content: &str) -> Result<(), WarpError> {
+    pub async fn chat_messages(&self, session_id: &str) -> Result<Vec<ChatMessage>, WarpError> {
+        self.chat_history.messages(session_id).await
+    }
+
+    pub async fn share_code(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        file_path: &str,
+        content: &str,
+    ) -> Result<(), WarpError> {
         // Check permissions
-        if !self.permissions.has_permission(session_id, user_id, &Permission::EditCode).await? {
-            return Err(WarpError::ConfigError("Insufficient permissions".to_string()));
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::EditCode)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
         }
 
-        self.code_sharing.share_file(session_id, user_id, file_path, content).await?;
-
         // Broadcast file opened event
         let event = CollaborationEvent {
             event_id: uuid::Uuid::new_v4().to_string(),
@@ -515,15 +703,23 @@ impl CollaborationManager {
         Ok(())
     }
 
-    pub async fn apply_code_change(&self, session_id: &str, user_id: &str, change: CodeChange) -> Result<(), WarpError> {
+    pub async fn apply_code_change(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        change: CodeChange,
+    ) -> Result<(), WarpError> {
         // Check permissions
-        if !self.permissions.has_permission(session_id, user_id, &Permission::EditCode).await? {
-            return Err(WarpError::ConfigError("Insufficient permissions".to_string()));
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::EditCode)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
         }
 
-        // Apply change through real-time sync
-        self.real_time_sync.apply_change(session_id, &change).await?;
-
         // Broadcast code change event
         let event = CollaborationEvent {
             event_id: uuid::Uuid::new_v4().to_string(),
@@ -538,13 +734,26 @@ impl CollaborationManager {
         Ok(())
     }
 
-    pub async fn start_voice_chat(&self, session_id: &str, user_id: &str) -> Result<String, WarpError> {
+    pub async fn start_voice_chat(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<String, WarpError> {
         // Check permissions
-        if !self.permissions.has_permission(session_id, user_id, &Permission::UseVoiceChat).await? {
-            return Err(WarpError::ConfigError("Insufficient permissions".to_string()));
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::UseVoiceChat)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
         }
 
-        let room_id = self.voice_chat.start_voice_chat(session_id, user_id).await?;
+        let room_id = self
+            .voice_chat
+            .start_voice_chat(session_id, user_id)
+            .await?;
 
         // Broadcast voice started event
         let event = CollaborationEvent {
@@ -553,20 +762,203 @@ impl CollaborationManager {
             user_id: user_id.to_string(),
             timestamp: chrono::Utc::now(),
             event_type: EventType::VoiceStarted,
-            data: serde_json::json!({"room_id": room_id}),
+            // Peers send their audio to this address
+            data: serde_json::json!({"room_id": room_id, "address": self.voice_chat.local_address(session_id).await}),
         };
         let _ = self.event_broadcaster.send(event);
 
         Ok(room_id)
     }
 
-    pub async fn start_screen_sharing(&self, session_id: &str, user_id: &str) -> Result<String, WarpError> {
+    // Called when a peer's VoiceStarted event arrives over signaling
+    pub async fn connect_voice_peer(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        address: std::net::SocketAddr,
+    ) -> Result<(), WarpError> {
+        self.voice_chat.add_peer(session_id, user_id, address).await
+    }
+
+    pub async fn set_voice_muted(&self, session_id: &str, muted: bool) -> Result<(), WarpError> {
+        self.voice_chat.set_muted(session_id, muted).await
+    }
+
+    pub async fn set_voice_deafened(
+        &self,
+        session_id: &str,
+        deafened: bool,
+    ) -> Result<(), WarpError> {
+        self.voice_chat.set_deafened(session_id, deafened).await
+    }
+
+    pub async fn voice_state(&self, session_id: &str) -> Option<voice_chat::VoiceState> {
+        self.voice_chat.state(session_id).await
+    }
+
+    // Keeps Participant::speaking current for the presence list
+    fn forward_speaking(
+        sessions: Arc<RwLock<HashMap<String, CollaborationSession>>>,
+        presence: Arc<presence::PresenceManager>,
+        mut speaking: broadcast::Receiver<voice_chat::SpeakingEvent>,
+        events: broadcast::Sender<CollaborationEvent>,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                let change = match speaking.recv().await {
+                    Ok(change) => change,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let mut sessions = sessions.write().await;
+                let participant = sessions.get_mut(&change.session_id).and_then(|session| {
+                    session
+                        .participants
+                        .iter_mut()
+                        .find(|p| p.user_id == change.user_id)
+                });
+                if let Some(participant) = participant {
+                    participant.speaking = change.speaking;
+                }
+                drop(sessions);
+                presence
+                    .set_speaking(&change.session_id, &change.user_id, change.speaking)
+                    .await;
+                let _ = events.send(CollaborationEvent {
+                    event_id: uuid::Uuid::new_v4().to_string(),
+                    session_id: change.session_id.clone(),
+                    user_id: change.user_id.clone(),
+                    timestamp: chrono::Utc::now(),
+                    event_type: EventType::SpeakingChanged,
+                    data: serde_json::json!({"speaking": change.speaking}),
+                });
+            }
+        });
+    }
+
+    // Marks participants away or offline once their heartbeats or input
+    // stop, as PresenceManager::sweep notices
+    fn sweep_presence(
+        sessions: Arc<RwLock<HashMap<String, CollaborationSession>>>,
+        presence: Arc<presence::PresenceManager>,
+        events: broadcast::Sender<CollaborationEvent>,
+    ) {
+        // Ends once the manager, and with it the presence tracker, is dropped
+        let presence = Arc::downgrade(&presence);
+        tokio::spawn(async move {
+            loop {
+                let interval = match presence.upgrade() {
+                    Some(presence) => presence.heartbeat_interval().await,
+                    None => return,
+                };
+                tokio::time::sleep(interval).await;
+                let Some(presence) = presence.upgrade() else {
+                    return;
+                };
+                for change in presence.sweep(chrono::Utc::now()).await {
+                    let status = participant_status(change.state);
+                    let mut sessions = sessions.write().await;
+                    let participant = sessions.get_mut(&change.session_id).and_then(|session| {
+                        session
+                            .participants
+                            .iter_mut()
+                            .find(|p| p.user_id == change.user_id)
+                    });
+                    if let Some(participant) = participant {
+                        participant.status = status.clone();
+                    }
+                    drop(sessions);
+                    let _ = events.send(CollaborationEvent {
+                        event_id: uuid::Uuid::new_v4().to_string(),
+                        session_id: change.session_id,
+                        user_id: change.user_id,
+                        timestamp: chrono::Utc::now(),
+                        event_type: EventType::ParticipantStatusChanged,
+                        data: serde_json::json!({"status": status}),
+                    });
+                }
+            }
+        });
+    }
+
+    // Called for the local user every PresenceConfig::heartbeat_secs and when
+    // another participant's heartbeat arrives
+    pub async fn presence_heartbeat(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        active: bool,
+    ) -> Result<(), WarpError> {
+        let now = chrono::Utc::now();
+        self.presence
+            .heartbeat(session_id, user_id, active, now)
+            .await;
+        let state = self.presence.state(session_id, user_id, now).await;
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("session", session_id))?;
+        if let Some(participant) = session
+            .participants
+            .iter_mut()
+            .find(|p| p.user_id == user_id)
+        {
+            participant.status = participant_status(state);
+            if active {
+                participant.last_active = now;
+            }
+        }
+        Ok(())
+    }
+
+    // While composing a chat message; cleared when it's sent or times out
+    pub async fn set_typing(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        typing: bool,
+    ) -> Result<(), WarpError> {
+        self.presence
+            .set_typing(session_id, user_id, typing, chrono::Utc::now())
+            .await;
+        let event = CollaborationEvent {
+            event_id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            event_type: EventType::TypingChanged,
+            data: serde_json::json!({"typing": typing}),
+        };
+        let _ = self.event_broadcaster.send(event);
+        Ok(())
+    }
+
+    // The participant strip for each session as it changes; the app sends
+    // it on to the header as UIEvent::Participants
+    pub fn subscribe_presence(&self) -> broadcast::Receiver<presence::PresenceUpdate> {
+        self.presence.subscribe()
+    }
+
+    pub async fn start_screen_sharing(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<String, WarpError> {
         // Check permissions
-        if !self.permissions.has_permission(session_id, user_id, &Permission::ShareScreen).await? {
-            return Err(WarpError::ConfigError("Insufficient permissions".to_string()));
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::ShareScreen)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
         }
 
-        let stream_id = self.screen_sharing.start_screen_share(session_id, user_id).await?;
+        let stream_id = self
+            .screen_sharing
+            .start_screen_share(session_id, user_id)
+            .await?;
 
         // Broadcast screen share started event
         let event = CollaborationEvent {
@@ -582,12 +974,326 @@ impl CollaborationManager {
         Ok(stream_id)
     }
 
-    pub async fn update_cursor_position(&self, session_id: &str, user_id: &str, position: CursorPosition) -> Result<(), WarpError> {
+    pub async fn publish_screen_frame(
+        &self,
+        stream_id: &str,
+        grid: &crate::grid::Grid,
+        cursor: Option<(u16, u16)>,
+    ) -> Result<(), WarpError> {
+        self.screen_sharing
+            .publish_frame(stream_id, grid, cursor)
+            .await
+    }
+
+    pub fn subscribe_screen_frames(&self) -> broadcast::Receiver<screen_sharing::SharedFrame> {
+        self.screen_sharing.subscribe_frames()
+    }
+
+    pub async fn request_screen_keyframe(&self, stream_id: &str) {
+        self.screen_sharing.request_keyframe(stream_id).await
+    }
+
+    pub async fn request_control(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        stream_id: &str,
+    ) -> Result<String, WarpError> {
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::ControlTerminal)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
+        }
+
+        let request_id = self
+            .screen_sharing
+            .request_control(stream_id, user_id)
+            .await?;
+        self.send_control_event(
+            session_id,
+            user_id,
+            EventType::ControlRequested,
+            serde_json::json!({
+                "stream_id": stream_id,
+                "request_id": request_id,
+            }),
+        );
+        Ok(request_id)
+    }
+
+    pub async fn respond_to_control(
+        &self,
+        session_id: &str,
+        sharer_id: &str,
+        stream_id: &str,
+        request_id: &str,
+        approve: bool,
+    ) -> Result<(), WarpError> {
+        let granted = self
+            .screen_sharing
+            .respond_to_control(stream_id, sharer_id, request_id, approve)
+            .await?;
+        let (event_type, controller) = match granted {
+            Some(user_id) => (EventType::ControlGranted, Some(user_id)),
+            None => (EventType::ControlReleased, None),
+        };
+        self.send_control_event(
+            session_id,
+            sharer_id,
+            event_type,
+            serde_json::json!({
+                "stream_id": stream_id,
+                "request_id": request_id,
+                "controller": controller,
+            }),
+        );
+        Ok(())
+    }
+
+    pub async fn release_control(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        stream_id: &str,
+    ) -> Result<(), WarpError> {
+        self.screen_sharing
+            .release_control(stream_id, user_id)
+            .await?;
+        self.send_control_event(
+            session_id,
+            user_id,
+            EventType::ControlReleased,
+            serde_json::json!({
+                "stream_id": stream_id,
+                "controller": null,
+            }),
+        );
+        Ok(())
+    }
+
+    // Keystrokes from a participant in control, to be written to the
+    // sharer's pane
+    pub async fn forward_control_input(
+        &self,
+        session_id: &str,
+        stream_id: &str,
+        user_id: &str,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>, WarpError> {
+        // Checked per keystroke so a revoke takes effect immediately
+        if !self
+            .permissions
+            .has_permission(session_id, user_id, &Permission::ControlTerminal)
+            .await?
+        {
+            return Err(WarpError::Permission(
+                "Insufficient permissions".to_string(),
+            ));
+        }
+        self.screen_sharing
+            .forward_input(stream_id, user_id, input)
+            .await
+    }
+
+    fn send_control_event(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        event_type: EventType,
+        data: serde_json::Value,
+    ) {
+        let _ = self.event_broadcaster.send(CollaborationEvent {
+            event_id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            timestamp: chrono::Utc::now(),
+            event_type,
+            data,
+        });
+    }
+
+    pub async fn draw_on_whiteboard(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        shape: whiteboard::Shape,
+    ) -> Result<(), WarpError> {
+        self.check_whiteboard(session_id, user_id).await?;
+        let update = self.whiteboard.draw(session_id, user_id, shape).await?;
+        self.send_whiteboard_event(update)
+    }
+
+    pub async fn undo_whiteboard(&self, session_id: &str, user_id: &str) -> Result<(), WarpError> {
+        if let Some(update) = self.whiteboard.undo(session_id, user_id).await? {
+            self.send_whiteboard_event(update)?;
+        }
+        Ok(())
+    }
+
+    // Called when another participant's WhiteboardUpdated event arrives
+    pub async fn apply_whiteboard_update(
+        &self,
+        update: &whiteboard::WhiteboardUpdate,
+    ) -> Result<(), WarpError> {
+        self.check_whiteboard(&update.session_id, &update.user_id)
+            .await?;
+        self.whiteboard.apply(update).await?;
+        Ok(())
+    }
+
+    pub async fn render_whiteboard(
+        &self,
+        session_id: &str,
+        width: u16,
+        height: u16,
+    ) -> crate::grid::Grid {
+        self.whiteboard.render(session_id, width, height).await
+    }
+
+    pub async fn export_whiteboard_svg(&self, session_id: &str) -> String {
+        self.whiteboard.export_svg(session_id).await
+    }
+
+    pub async fn export_whiteboard_png(
+        &self,
+        session_id: &str,
+        path: &std::path::Path,
+    ) -> Result<(), WarpError> {
+        self.whiteboard.export_png(session_id, path).await
+    }
+
+    async fn check_whiteboard(&self, session_id: &str, user_id: &str) -> Result<(), WarpError> {
+        let sessions = self.sessions.read().await;
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| WarpError::not_found("session", session_id))?;
+        if !session.settings.enable_whiteboard {
+            return Err(WarpError::Validation(
+                "The whiteboard is disabled for this session".to_string(),
+            ));
+        }
+        if !session.participants.iter().any(|p| p.user_id == user_id) {
+            return Err(WarpError::Permission(
+                "Not a participant of this session".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn send_whiteboard_event(&self, update: whiteboard::WhiteboardUpdate) -> Result<(), WarpError> {
+        let event = CollaborationEvent {
+            event_id: uuid::Uuid::new_v4().to_string(),
+            session_id: update.session_id.clone(),
+            user_id: update.user_id.clone(),
+            timestamp: chrono::Utc::now(),
+            event_type: EventType::WhiteboardUpdated,
+            data: serde_json::to_value(&update)?,
+        };
+        let _ = self.event_broadcaster.send(event);
+        Ok(())
+    }
+
+    // The permissions editor: the owner grants or revokes one permission
+    // for a participant. Revoking takes effect at once, ending whatever the
+    // participant was doing with it.
+    pub async fn set_participant_permission(
+        &self,
+        session_id: &str,
+        actor: &str,
+        user_id: &str,
+        permission: Permission,
+        granted: bool,
+    ) -> Result<(), WarpError> {
+        if !self
+            .permissions
+            .set_permission(session_id, actor, user_id, permission.clone(), granted)
+            .await?
+        {
+            return Ok(());
+        }
+        let current = self.permissions.permissions(session_id, user_id).await;
         let mut sessions = self.sessions.write().await;
-        
+        if let Some(participant) = sessions.get_mut(session_id).and_then(|session| {
+            session
+                .participants
+                .iter_mut()
+                .find(|p| p.user_id == user_id)
+        }) {
+            participant.permissions = current;
+        }
+        drop(sessions);
+
+        if !granted {
+            match permission {
+                Permission::ControlTerminal => {
+                    self.screen_sharing
+                        .revoke_control(session_id, user_id)
+                        .await
+                }
+                Permission::ShareScreen => {
+                    for stream_id in self.screen_sharing.stop_sharing(session_id, user_id).await {
+                        let _ = self.event_broadcaster.send(CollaborationEvent {
+                            event_id: uuid::Uuid::new_v4().to_string(),
+                            session_id: session_id.to_string(),
+                            user_id: user_id.to_string(),
+                            timestamp: chrono::Utc::now(),
+                            event_type: EventType::ScreenShareStopped,
+                            data: serde_json::json!({"stream_id": stream_id}),
+                        });
+                    }
+                }
+                Permission::UseVoiceChat => {
+                    self.voice_chat.stop_for_user(session_id, user_id).await?
+                }
+                _ => {}
+            }
+        }
+
+        let event = CollaborationEvent {
+            event_id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            user_id: actor.to_string(),
+            timestamp: chrono::Utc::now(),
+            event_type: EventType::PermissionChanged,
+            data: serde_json::json!({"user_id": user_id, "permission": permission, "granted": granted}),
+        };
+        let _ = self.event_broadcaster.send(event);
+        Ok(())
+    }
+
+    pub async fn permission_matrix(&self, session_id: &str) -> Vec<permissions::PermissionRow> {
+        self.permissions.matrix(session_id).await
+    }
+
+    pub async fn permission_audit_log(
+        &self,
+        session_id: &str,
+    ) -> Vec<permissions::PermissionChange> {
+        self.permissions.audit_log(session_id).await
+    }
+
+    pub async fn update_cursor_position(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        position: CursorPosition,
+    ) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.write().await;
+
         if let Some(session) = sessions.get_mut(session_id) {
-            if let Some(participant) = session.participants.iter_mut().find(|p| p.user_id == user_id) {
+            if let Some(participant) = session
+                .participants
+                .iter_mut()
+                .find(|p| p.user_id == user_id)
+            {
                 participant.cursor_position = Some(position.clone());
+                self.presence
+                    .set_cursor(session_id, user_id, position.clone(), chrono::Utc::now())
+                    .await;
                 participant.last_active = chrono::Utc::now();
 
                 // Broadcast cursor moved event
@@ -608,18 +1314,22 @@ impl CollaborationManager {
 
     pub async fn get_session(&self, session_id: &str) -> Result<CollaborationSession, WarpError> {
         let sessions = self.sessions.read().await;
-        sessions.get(session_id)
+        sessions
+            .get(session_id)
             .cloned()
-            .ok_or_else(|| WarpError::ConfigError("Session not found".to_string()))
+            .ok_or_else(|| WarpError::not_found("session", session_id))
     }
 
-    pub async fn get_active_sessions(&self, user_id: &str) -> Result<Vec<CollaborationSession>, WarpError> {
+    pub async fn get_active_sessions(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<CollaborationSession>, WarpError> {
         let sessions = self.sessions.read().await;
         let active_sessions: Vec<CollaborationSession> = sessions
             .values()
             .filter(|session| {
-                session.status == SessionStatus::Active &&
-                session.participants.iter().any(|p| p.user_id == user_id)
+                session.status == SessionStatus::Active
+                    && session.participants.iter().any(|p| p.user_id == user_id)
             })
             .cloned()
             .collect();
@@ -629,16 +1339,17 @@ impl CollaborationManager {
 
     pub async fn end_session(&self, session_id: &str) -> Result<(), WarpError> {
         let mut sessions = self.sessions.write().await;
-        
+
         if let Some(session) = sessions.get_mut(session_id) {
             session.status = SessionStatus::Ended;
 
             // Clean up resources
-            self.real_time_sync.cleanup_room(session_id).await?;
             self.voice_chat.cleanup_session(session_id).await?;
             self.screen_sharing.cleanup_session(session_id).await?;
-            self.code_sharing.cleanup_session(session_id).await?;
             self.whiteboard.cleanup_session(session_id).await?;
+            self.presence.cleanup_session(session_id).await;
+            self.permissions.cleanup_session(session_id).await;
+            self.invitations.cleanup_session(session_id).await;
 
             // Clear active connections
             let mut connections = self.active_connections.lock().await;
@@ -655,8 +1366,6 @@ impl CollaborationManager {
             };
             let _ = self.event_broadcaster.send(event);
 
-            // Archive session
-            self.session_manager.archive_session(session).await?;
             sessions.remove(session_id);
         }
 
@@ -666,4 +1375,52 @@ impl CollaborationManager {
     pub fn subscribe_to_events(&self) -> broadcast::Receiver<CollaborationEvent> {
         self.event_broadcaster.subscribe()
     }
+
+    // Posts alerts routed to chat (see crate::alerts) into the session as
+    // system messages until the session ends
+    pub fn relay_alerts(
+        self: &Arc<Self>,
+        session_id: &str,
+        mut alerts: broadcast::Receiver<Alert>,
+    ) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                let alert = match alerts.recv().await {
+                    Ok(alert) => alert,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let active = manager
+                    .sessions
+                    .read()
+                    .await
+                    .get(&session_id)
+                    .is_some_and(|session| session.status == SessionStatus::Active);
+                if !active {
+                    return;
+                }
+                if let Err(e) = manager
+                    .send_chat_message(
+                        &session_id,
+                        SYSTEM_USER,
+                        &alert.chat_text(),
+                        MessageType::System,
+                    )
+                    .await
+                {
+                    log::warn!("Posting alert to session {} failed: {}", session_id, e);
+                }
+            }
+        })
+    }
+}
+
+fn participant_status(state: crate::widgets::PresenceState) -> ParticipantStatus {
+    match state {
+        crate::widgets::PresenceState::Online => ParticipantStatus::Online,
+        crate::widgets::PresenceState::Away => ParticipantStatus::Away,
+        crate::widgets::PresenceState::Offline => ParticipantStatus::Offline,
+    }
 }
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{ParticipantRole, Permission};
+use crate::error::WarpError;
+
+// Per session; the oldest entries go first
+const MAX_AUDIT_ENTRIES: usize = 1000;
+
+pub const ALL_PERMISSIONS: [Permission; 12] = [
+    Permission::ViewCode,
+    Permission::EditCode,
+    Permission::ExecuteCode,
+    Permission::ViewDebugger,
+    Permission::ControlDebugger,
+    Permission::ViewTerminal,
+    Permission::ControlTerminal,
+    Permission::ShareScreen,
+    Permission::UseVoiceChat,
+    Permission::UseTextChat,
+    Permission::ManageParticipants,
+    Permission::ModifySettings,
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionChange {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub actor: String,
+    pub user_id: String,
+    pub permission: Permission,
+    pub granted: bool,
+}
+
+// One row of the permissions editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRow {
+    pub user_id: String,
+    pub owner: bool,
+    pub permissions: Vec<(Permission, bool)>,
+}
+
+#[derive(Default)]
+struct SessionPermissions {
+    owner: String,
+    granted: HashMap<String, HashSet<Permission>>,
+    audit: VecDeque<PermissionChange>,
+}
+
+// What each participant may do, per session. Participants start from their
+// role's defaults; the owner can then grant or revoke single permissions
+// while the session runs, and every change is kept in an audit log.
+pub struct PermissionManager {
+    sessions: RwLock<HashMap<String, SessionPermissions>>,
+}
+
+impl PermissionManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self {
+            sessions: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub async fn get_default_permissions(
+        &self,
+        role: &ParticipantRole,
+    ) -> Result<Vec<Permission>, WarpError> {
+        Ok(default_permissions(role))
+    }
+
+    pub async fn create_session(&self, session_id: &str, owner_id: &str) {
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(
+            session_id.to_string(),
+            SessionPermissions {
+                owner: owner_id.to_string(),
+                granted: HashMap::from([(
+                    owner_id.to_string(),
+                    ALL_PERMISSIONS.into_iter().collect(),
+                )]),
+                audit: VecDeque::new(),
+            },
+        );
+    }
+
+    pub async fn add_participant(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        permissions: &[Permission],
+    ) {
+        let mut sessions = self.sessions.write().await;
+        if let Some(session) = sessions.get_mut(session_id) {
+            session
+                .granted
+                .insert(user_id.to_string(), permissions.iter().cloned().collect());
+        }
+    }
+
+    pub async fn remove_participant(&self, session_id: &str, user_id: &str) {
+        let mut sessions = self.sessions.write().await;
+        if let Some(session) = sessions.get_mut(session_id) {
+            session.granted.remove(user_id);
+        }
+    }
+
+    pub async fn has_permission(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        permission: &Permission,
+    ) -> Result<bool, WarpError> {
+        let sessions = self.sessions.read().await;
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| WarpError::not_found("session", session_id))?;
+        Ok(session
+            .granted
+            .get(user_id)
+            .is_some_and(|granted| granted.contains(permission)))
+    }
+
+    pub async fn permissions(&self, session_id: &str, user_id: &str) -> Vec<Permission> {
+        let sessions = self.sessions.read().await;
+        let granted = sessions
+            .get(session_id)
+            .and_then(|session| session.granted.get(user_id));
+        ALL_PERMISSIONS
+            .into_iter()
+            .filter(|permission| granted.is_some_and(|granted| granted.contains(permission)))
+            .collect()
+    }
+
+    // Grants or revokes one permission. Only the owner may, and the owner's
+    // own permissions are fixed so a session can't be locked out of itself.
+    // Returns false when the participant already had it that way.
+    pub async fn set_permission(
+        &self,
+        session_id: &str,
+        actor: &str,
+        user_id: &str,
+        permission: Permission,
+        granted: bool,
+    ) -> Result<bool, WarpError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("session", session_id))?;
+        if session.owner != actor {
+            return Err(WarpError::Permission(
+                "Only the session owner can change permissions".to_string(),
+            ));
+        }
+        if session.owner == user_id {
+            return Err(WarpError::Validation(
+                "The owner's permissions can't be changed".to_string(),
+            ));
+        }
+        let current = session
+            .granted
+            .get_mut(user_id)
+            .ok_or_else(|| WarpError::not_found("participant", user_id))?;
+        let changed = if granted {
+            current.insert(permission.clone())
+        } else {
+            current.remove(&permission)
+        };
+        if changed {
+            log::info!(
+                "Session {}: {} {} {:?} for {}",
+                session_id,
+                actor,
+                if granted { "granted" } else { "revoked" },
+                permission,
+                user_id
+            );
+            if session.audit.len() == MAX_AUDIT_ENTRIES {
+                session.audit.pop_front();
+            }
+            session.audit.push_back(PermissionChange {
+                at: chrono::Utc::now(),
+                actor: actor.to_string(),
+                user_id: user_id.to_string(),
+                permission,
+                granted,
+            });
+        }
+        Ok(changed)
+    }
+
+    // Every participant against every permission, owner first
+    pub async fn matrix(&self, session_id: &str) -> Vec<PermissionRow> {
+        let sessions = self.sessions.read().await;
+        let Some(session) = sessions.get(session_id) else {
+            return Vec::new();
+        };
+        let mut rows: Vec<PermissionRow> = session
+            .granted
+            .iter()
+            .map(|(user_id, granted)| PermissionRow {
+                user_id: user_id.clone(),
+                owner: *user_id == session.owner,
+                permissions: ALL_PERMISSIONS
+                    .into_iter()
+                    .map(|permission| {
+                        let has = granted.contains(&permission);
+                        (permission, has)
+                    })
+                    .collect(),
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.owner
+                .cmp(&a.owner)
+                .then_with(|| a.user_id.cmp(&b.user_id))
+        });
+        rows
+    }
+
+    // Oldest first
+    pub async fn audit_log(&self, session_id: &str) -> Vec<PermissionChange> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(session_id)
+            .map(|session| session.audit.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn cleanup_session(&self, session_id: &str) {
+        self.sessions.write().await.remove(session_id);
+    }
+}
+
+fn default_permissions(role: &ParticipantRole) -> Vec<Permission> {
+    use Permission::*;
+
+    match role {
+        ParticipantRole::Owner => ALL_PERMISSIONS.to_vec(),
+        ParticipantRole::Moderator => ALL_PERMISSIONS
+            .into_iter()
+            .filter(|p| *p != ModifySettings)
+            .collect(),
+        ParticipantRole::Contributor => vec![
+            ViewCode,
+            EditCode,
+            ExecuteCode,
+            ViewDebugger,
+            ViewTerminal,
+            ShareScreen,
+            UseVoiceChat,
+            UseTextChat,
+        ],
+        ParticipantRole::Observer => vec![
+            ViewCode,
+            ViewDebugger,
+            ViewTerminal,
+            UseVoiceChat,
+            UseTextChat,
+        ],
+        ParticipantRole::Guest => vec![ViewCode, ViewTerminal, UseTextChat],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_owner_edits_permissions() {
+        let manager = PermissionManager::new().await.unwrap();
+        manager.create_session("s1", "ada").await;
+        let guest = manager
+            .get_default_permissions(&ParticipantRole::Guest)
+            .await
+            .unwrap();
+        manager.add_participant("s1", "bob", &guest).await;
+        assert!(!manager
+            .has_permission("s1", "bob", &Permission::EditCode)
+            .await
+            .unwrap());
+
+        assert!(manager
+            .set_permission("s1", "ada", "bob", Permission::EditCode, true)
+            .await
+            .unwrap());
+        assert!(!manager
+            .set_permission("s1", "ada", "bob", Permission::EditCode, true)
+            .await
+            .unwrap());
+        assert!(manager
+            .has_permission("s1", "bob", &Permission::EditCode)
+            .await
+            .unwrap());
+        assert!(manager
+            .set_permission("s1", "ada", "bob", Permission::ViewTerminal, false)
+            .await
+            .unwrap());
+
+        // Nobody else edits, and the owner can't be locked out
+        assert!(manager
+            .set_permission("s1", "bob", "bob", Permission::ShareScreen, true)
+            .await
+            .is_err());
+        assert!(manager
+            .set_permission("s1", "ada", "ada", Permission::ShareScreen, false)
+            .await
+            .is_err());
+
+        let log = manager.audit_log("s1").await;
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            (log[1].permission.clone(), log[1].granted),
+            (Permission::ViewTerminal, false)
+        );
+
+        let matrix = manager.matrix("s1").await;
+        assert_eq!(matrix[0].user_id, "ada");
+        assert!(matrix[1]
+            .permissions
+            .contains(&(Permission::EditCode, true)));
+        assert!(matrix[1]
+            .permissions
+            .contains(&(Permission::ViewTerminal, false)));
+    }
+}
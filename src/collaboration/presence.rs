@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use tokio::sync::{broadcast, RwLock};
+
+use super::whiteboard::user_color;
+use super::CursorPosition;
+use crate::config::PresenceConfig;
+use crate::error::WarpError;
+use crate::widgets::{ParticipantBadge, PresenceState};
+
+struct Presence {
+    last_heartbeat: DateTime<Utc>,
+    // Last input; a participant without any for `away_secs` is away
+    last_active: DateTime<Utc>,
+    typing_at: Option<DateTime<Utc>>,
+    cursor: Option<CursorPosition>,
+    speaking: bool,
+    left: bool,
+    // As last published, to notice timeouts in `sweep`
+    state: PresenceState,
+}
+
+impl Presence {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            last_heartbeat: now,
+            last_active: now,
+            typing_at: None,
+            cursor: None,
+            speaking: false,
+            left: false,
+            state: PresenceState::Online,
+        }
+    }
+
+    fn state(&self, config: &PresenceConfig, now: DateTime<Utc>) -> PresenceState {
+        if self.left || now - self.last_heartbeat > secs(config.offline_secs) {
+            PresenceState::Offline
+        } else if now - self.last_active > secs(config.away_secs) {
+            PresenceState::Away
+        } else {
+            PresenceState::Online
+        }
+    }
+
+    fn typing(&self, config: &PresenceConfig, now: DateTime<Utc>) -> bool {
+        self.typing_at
+            .is_some_and(|at| now - at <= secs(config.typing_secs))
+    }
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::seconds(secs.min(i64::MAX as u64) as i64)
+}
+
+#[derive(Debug, Clone)]
+pub struct PresenceUpdate {
+    pub session_id: String,
+    pub badges: Vec<ParticipantBadge>,
+}
+
+// A participant whose state changed on its own, by timing out
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceChange {
+    pub session_id: String,
+    pub user_id: String,
+    pub state: PresenceState,
+}
+
+// Tracks who is online, away, typing and where their cursor is, per
+// session. Subscribers get the session's participant strip on every change.
+pub struct PresenceManager {
+    config: PresenceConfig,
+    sessions: RwLock<HashMap<String, HashMap<String, Presence>>>,
+    updates: broadcast::Sender<PresenceUpdate>,
+}
+
+impl PresenceManager {
+    pub async fn new(config: PresenceConfig) -> Result<Self, WarpError> {
+        Ok(Self {
+            config,
+            sessions: RwLock::new(HashMap::new()),
+            updates: broadcast::channel(64).0,
+        })
+    }
+
+    pub async fn heartbeat_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.config.heartbeat_secs.max(1))
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceUpdate> {
+        self.updates.subscribe()
+    }
+
+    pub async fn set_user_online(&self, user_id: &str, session_id: &str) -> Result<(), WarpError> {
+        let now = Utc::now();
+        self.update(session_id, user_id, now, |presence| {
+            *presence = Presence::new(now)
+        })
+        .await;
+        Ok(())
+    }
+
+    pub async fn set_user_offline(&self, user_id: &str, session_id: &str) -> Result<(), WarpError> {
+        self.update(session_id, user_id, Utc::now(), |presence| {
+            presence.left = true;
+            presence.typing_at = None;
+            presence.speaking = false;
+        })
+        .await;
+        Ok(())
+    }
+
+    // `active` says whether there was input since the last heartbeat
+    pub async fn heartbeat(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        active: bool,
+        now: DateTime<Utc>,
+    ) {
+        self.update(session_id, user_id, now, |presence| {
+            presence.last_heartbeat = now;
+            presence.left = false;
+            if active {
+                presence.last_active = now;
+            }
+        })
+        .await;
+    }
+
+    pub async fn set_typing(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        typing: bool,
+        now: DateTime<Utc>,
+    ) {
+        self.update(session_id, user_id, now, |presence| {
+            presence.typing_at = typing.then_some(now);
+            presence.last_active = now;
+        })
+        .await;
+    }
+
+    pub async fn set_cursor(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        cursor: CursorPosition,
+        now: DateTime<Utc>,
+    ) {
+        self.update(session_id, user_id, now, |presence| {
+            presence.cursor = Some(cursor);
+            presence.last_active = now;
+        })
+        .await;
+    }
+
+    pub async fn set_speaking(&self, session_id: &str, user_id: &str, speaking: bool) {
+        self.update(session_id, user_id, Utc::now(), |presence| {
+            presence.speaking = speaking
+        })
+        .await;
+    }
+
+    pub async fn state(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> PresenceState {
+        let config = &self.config;
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(session_id)
+            .and_then(|users| users.get(user_id))
+            .map_or(PresenceState::Offline, |presence| {
+                presence.state(config, now)
+            })
+    }
+
+    // Online first, then away, then offline; by name within each
+    pub async fn badges(&self, session_id: &str, now: DateTime<Utc>) -> Vec<ParticipantBadge> {
+        let config = &self.config;
+        let sessions = self.sessions.read().await;
+        badges(config, sessions.get(session_id), now)
+    }
+
+    // Run every heartbeat interval: finds participants who went away or
+    // offline (or stopped typing) without telling us, and republishes
+    pub async fn sweep(&self, now: DateTime<Utc>) -> Vec<PresenceChange> {
+        let config = &self.config;
+        let mut sessions = self.sessions.write().await;
+        let mut changes = Vec::new();
+        for (session_id, users) in sessions.iter_mut() {
+            let mut changed = false;
+            for (user_id, presence) in users.iter_mut() {
+                let state = presence.state(config, now);
+                if state != presence.state {
+                    presence.state = state;
+                    changes.push(PresenceChange {
+                        session_id: session_id.clone(),
+                        user_id: user_id.clone(),
+                        state,
+                    });
+                    changed = true;
+                }
+                if presence.typing_at.is_some() && !presence.typing(config, now) {
+                    presence.typing_at = None;
+                    changed = true;
+                }
+            }
+            if changed {
+                let _ = self.updates.send(PresenceUpdate {
+                    session_id: session_id.clone(),
+                    badges: badges(config, Some(users), now),
+                });
+            }
+        }
+        changes
+    }
+
+    pub async fn cleanup_session(&self, session_id: &str) {
+        self.sessions.write().await.remove(session_id);
+    }
+
+    async fn update(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        now: DateTime<Utc>,
+        change: impl FnOnce(&mut Presence),
+    ) {
+        let config = &self.config;
+        let mut sessions = self.sessions.write().await;
+        let users = sessions.entry(session_id.to_string()).or_default();
+        let presence = users
+            .entry(user_id.to_string())
+            .or_insert_with(|| Presence::new(now));
+        change(presence);
+        presence.state = presence.state(config, now);
+        let _ = self.updates.send(PresenceUpdate {
+            session_id: session_id.to_string(),
+            badges: badges(config, Some(users), now),
+        });
+    }
+}
+
+fn badges(
+    config: &PresenceConfig,
+    users: Option<&HashMap<String, Presence>>,
+    now: DateTime<Utc>,
+) -> Vec<ParticipantBadge> {
+    let mut badges: Vec<ParticipantBadge> = users
+        .into_iter()
+        .flatten()
+        .map(|(user_id, presence)| {
+            let state = presence.state(config, now);
+            let online = state != PresenceState::Offline;
+            ParticipantBadge {
+                name: user_id.clone(),
+                state,
+                typing: online && presence.typing(config, now),
+                speaking: online && presence.speaking,
+                location: presence.cursor.as_ref().filter(|_| online).map(|cursor| {
+                    let file = std::path::Path::new(&cursor.file_path)
+                        .file_name()
+                        .map_or(cursor.file_path.clone(), |name| {
+                            name.to_string_lossy().into_owned()
+                        });
+                    format!("{}:{}", file, cursor.line)
+                }),
+                color: user_color(user_id),
+            }
+        })
+        .collect();
+    badges.sort_by(|a, b| (a.state, &a.name).cmp(&(b.state, &b.name)));
+    badges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(file_path: &str, line: u32) -> CursorPosition {
+        CursorPosition {
+            file_path: file_path.to_string(),
+            line,
+            column: 1,
+            selection_start: None,
+            selection_end: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_away_and_offline_timeouts() {
+        let config = PresenceConfig {
+            heartbeat_secs: 15,
+            away_secs: 60,
+            offline_secs: 30,
+            typing_secs: 5,
+        };
+        let presence = PresenceManager::new(config).await.unwrap();
+        let start = Utc::now();
+        presence.heartbeat("s1", "ada", true, start).await;
+        presence.heartbeat("s1", "bob", true, start).await;
+        presence.set_typing("s1", "bob", true, start).await;
+        presence
+            .set_cursor("s1", "bob", cursor("src/main.rs", 12), start)
+            .await;
+
+        let badges = presence.badges("s1", start).await;
+        assert_eq!(badges[1].label(), "●BO✎@main.rs:12");
+
+        // Ada keeps sending heartbeats but stops typing; Bob goes silent
+        let later = start + Duration::seconds(40);
+        presence.heartbeat("s1", "ada", false, later).await;
+        let mut changes = presence.sweep(later).await;
+        changes.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            (changes[0].user_id.as_str(), changes[0].state),
+            ("bob", PresenceState::Offline)
+        );
+
+        let much_later = start + Duration::seconds(61);
+        presence.heartbeat("s1", "ada", false, much_later).await;
+        assert_eq!(
+            presence.state("s1", "ada", much_later).await,
+            PresenceState::Away
+        );
+        let labels: Vec<String> = presence
+            .badges("s1", much_later)
+            .await
+            .iter()
+            .map(|b| b.label())
+            .collect();
+        assert_eq!(labels, vec!["◐AD", "○BO"]);
+
+        presence.heartbeat("s1", "ada", true, much_later).await;
+        assert_eq!(
+            presence.state("s1", "ada", much_later).await,
+            PresenceState::Online
+        );
+    }
+}
@@ -0,0 +1,630 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use crossterm::style::{Attribute, Color};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Mutex};
+
+use crate::error::WarpError;
+use crate::grid::{DamageRegion, Grid};
+
+// A full grid goes out this often even without a resize, so a viewer that
+// missed a patch resynchronizes on its own
+const KEYFRAME_INTERVAL: u64 = 300;
+// Compressed patches larger than this are refused on receipt
+const MAX_PATCH_BYTES: usize = 4 * 1024 * 1024;
+
+const NAMED_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+// 0 is Reset, 1-16 the named colors, 0x100 + n ANSI color n, and
+// 0x1000000 + rgb a true color
+fn color_code(color: Color) -> u32 {
+    match color {
+        Color::Reset => 0,
+        Color::AnsiValue(value) => 0x100 + value as u32,
+        Color::Rgb { r, g, b } => 0x100_0000 | (r as u32) << 16 | (g as u32) << 8 | b as u32,
+        named => NAMED_COLORS
+            .iter()
+            .position(|&c| c == named)
+            .map_or(0, |index| index as u32 + 1),
+    }
+}
+
+fn color_from_code(code: u32) -> Color {
+    match code {
+        1..=16 => NAMED_COLORS[code as usize - 1],
+        0x100..=0x1ff => Color::AnsiValue((code - 0x100) as u8),
+        0x100_0000..=0x1ff_ffff => Color::Rgb {
+            r: (code >> 16) as u8,
+            g: (code >> 8) as u8,
+            b: code as u8,
+        },
+        _ => Color::Reset,
+    }
+}
+
+// Cells on one row sharing a style, written left to right from `x`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub y: u16,
+    pub x: u16,
+    pub text: String,
+    pub fg: u32,
+    pub bg: u32,
+    pub bold: bool,
+}
+
+// The change between two frames of a shared pane. A keyframe carries the
+// whole grid; anything else applies on top of frame `base_seq`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridPatch {
+    pub seq: u64,
+    pub base_seq: u64,
+    pub keyframe: bool,
+    pub width: u16,
+    pub height: u16,
+    pub cursor: Option<(u16, u16)>,
+    pub spans: Vec<Span>,
+}
+
+impl GridPatch {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WarpError> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&serde_json::to_vec(self)?)?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WarpError> {
+        let mut json = Vec::new();
+        DeflateDecoder::new(bytes)
+            .take(MAX_PATCH_BYTES as u64 + 1)
+            .read_to_end(&mut json)?;
+        if json.len() > MAX_PATCH_BYTES {
+            return Err(WarpError::Validation(
+                "Screen share patch too large".to_string(),
+            ));
+        }
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+fn spans(grid: &Grid, regions: &[DamageRegion]) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for region in regions {
+        // Start on the first half of a wide character the damage cut into
+        let mut x = region.x_start;
+        while x > 0 && grid.get(x, region.y).is_some_and(|cell| cell.ch == '\0') {
+            x -= 1;
+        }
+        for (offset, cell) in grid.row(region.y)[x as usize..region.x_end as usize]
+            .iter()
+            .enumerate()
+        {
+            let (fg, bg) = (color_code(cell.fg), color_code(cell.bg));
+            let bold = cell.attributes.has(Attribute::Bold);
+            let continues = spans.last().is_some_and(|span| {
+                span.y == region.y
+                    && (span.fg, span.bg, span.bold) == (fg, bg, bold)
+                    && span.x as usize + span_width(span) == x as usize + offset
+            });
+            if !continues {
+                spans.push(Span {
+                    y: region.y,
+                    x: x + offset as u16,
+                    text: String::new(),
+                    fg,
+                    bg,
+                    bold,
+                });
+            }
+            let span = spans.last_mut().expect("a span was just pushed");
+            // Placeholders are recreated by put_str on the other side, but
+            // still count towards the span's width
+            span.text
+                .push(if cell.ch == '\0' { '\u{1}' } else { cell.ch });
+        }
+    }
+    for span in &mut spans {
+        span.text.retain(|ch| ch != '\u{1}');
+    }
+    spans
+}
+
+fn span_width(span: &Span) -> usize {
+    span.text.chars().count()
+}
+
+// The sharer's side: turns successive grids into patches
+pub struct GridEncoder {
+    previous: Option<Grid>,
+    seq: u64,
+    last_keyframe: u64,
+    keyframe_requested: bool,
+}
+
+impl GridEncoder {
+    pub fn new() -> Self {
+        Self {
+            previous: None,
+            seq: 0,
+            last_keyframe: 0,
+            keyframe_requested: true,
+        }
+    }
+
+    // A viewer joined or fell out of sync
+    pub fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
+    // None when nothing changed and no keyframe is due
+    pub fn encode(&mut self, grid: &Grid, cursor: Option<(u16, u16)>) -> Option<GridPatch> {
+        let keyframe = self.keyframe_requested
+            || self.seq - self.last_keyframe >= KEYFRAME_INTERVAL
+            || !self.previous.as_ref().is_some_and(|previous| {
+                previous.width() == grid.width() && previous.height() == grid.height()
+            });
+        let regions = match (&self.previous, keyframe) {
+            (Some(previous), false) => grid.diff(previous),
+            _ => grid.full_damage(),
+        };
+        if regions.is_empty() && !keyframe {
+            return None;
+        }
+        let base_seq = self.seq;
+        self.seq += 1;
+        if keyframe {
+            self.last_keyframe = self.seq;
+            self.keyframe_requested = false;
+        }
+        self.previous = Some(grid.clone());
+        Some(GridPatch {
+            seq: self.seq,
+            base_seq,
+            keyframe,
+            width: grid.width(),
+            height: grid.height(),
+            cursor,
+            spans: spans(grid, &regions),
+        })
+    }
+}
+
+impl Default for GridEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A participant's copy of the shared pane, drawn natively by their UI
+pub struct GridViewer {
+    grid: Grid,
+    seq: Option<u64>,
+    cursor: Option<(u16, u16)>,
+}
+
+impl GridViewer {
+    pub fn new() -> Self {
+        Self {
+            grid: Grid::new(0, 0),
+            seq: None,
+            cursor: None,
+        }
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        self.cursor
+    }
+
+    // Returns the rows touched, or a Validation error when a patch was
+    // missed and a keyframe has to be requested
+    pub fn apply(&mut self, patch: &GridPatch) -> Result<Vec<DamageRegion>, WarpError> {
+        if !patch.keyframe && self.seq != Some(patch.base_seq) {
+            return Err(WarpError::Validation(format!(
+                "Screen share out of sync: have frame {:?}, patch is based on {}",
+                self.seq, patch.base_seq
+            )));
+        }
+        let previous = self.grid.clone();
+        if patch.keyframe {
+            self.grid = Grid::new(patch.width, patch.height);
+        }
+        for span in &patch.spans {
+            let fg = color_from_code(span.fg);
+            let bg = color_from_code(span.bg);
+            self.grid
+                .put_str(span.x, span.y, &span.text, fg, bg, span.bold);
+        }
+        self.seq = Some(patch.seq);
+        self.cursor = patch.cursor;
+        Ok(self.grid.diff(&previous))
+    }
+}
+
+impl Default for GridViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedFrame {
+    pub session_id: String,
+    pub stream_id: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ControlState {
+    // Only the sharer types into the pane
+    Sharer,
+    Requested { user_id: String, request_id: String },
+    Granted { user_id: String },
+}
+
+struct ScreenShare {
+    session_id: String,
+    sharer: String,
+    encoder: GridEncoder,
+    control: ControlState,
+}
+
+// Shares a pane by streaming its cell grid rather than video: patches of
+// changed cells, deflated, at a fraction of a video stream's bandwidth.
+// Participants can ask to take control; the sharer decides.
+pub struct ScreenSharingManager {
+    streams: Mutex<HashMap<String, ScreenShare>>,
+    frames: broadcast::Sender<SharedFrame>,
+}
+
+impl ScreenSharingManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self {
+            streams: Mutex::new(HashMap::new()),
+            frames: broadcast::channel(64).0,
+        })
+    }
+
+    pub async fn start_screen_share(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<String, WarpError> {
+        let stream_id = uuid::Uuid::new_v4().to_string();
+        self.streams.lock().await.insert(
+            stream_id.clone(),
+            ScreenShare {
+                session_id: session_id.to_string(),
+                sharer: user_id.to_string(),
+                encoder: GridEncoder::new(),
+                control: ControlState::Sharer,
+            },
+        );
+        Ok(stream_id)
+    }
+
+    // Patches to send to the session's participants over the transport
+    pub fn subscribe_frames(&self) -> broadcast::Receiver<SharedFrame> {
+        self.frames.subscribe()
+    }
+
+    // Called by the sharer's UI after each render of the shared pane
+    pub async fn publish_frame(
+        &self,
+        stream_id: &str,
+        grid: &Grid,
+        cursor: Option<(u16, u16)>,
+    ) -> Result<(), WarpError> {
+        let mut streams = self.streams.lock().await;
+        let share = streams
+            .get_mut(stream_id)
+            .ok_or_else(|| WarpError::not_found("screen share", stream_id))?;
+        if let Some(patch) = share.encoder.encode(grid, cursor) {
+            let _ = self.frames.send(SharedFrame {
+                session_id: share.session_id.clone(),
+                stream_id: stream_id.to_string(),
+                bytes: patch.to_bytes()?,
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn request_keyframe(&self, stream_id: &str) {
+        if let Some(share) = self.streams.lock().await.get_mut(stream_id) {
+            share.encoder.request_keyframe();
+        }
+    }
+
+    pub async fn sharer(&self, stream_id: &str) -> Option<String> {
+        self.streams
+            .lock()
+            .await
+            .get(stream_id)
+            .map(|share| share.sharer.clone())
+    }
+
+    pub async fn control_state(&self, stream_id: &str) -> Option<ControlState> {
+        self.streams
+            .lock()
+            .await
+            .get(stream_id)
+            .map(|share| share.control.clone())
+    }
+
+    // Replaces any request still waiting; returns the new request's id
+    pub async fn request_control(
+        &self,
+        stream_id: &str,
+        user_id: &str,
+    ) -> Result<String, WarpError> {
+        let mut streams = self.streams.lock().await;
+        let share = streams
+            .get_mut(stream_id)
+            .ok_or_else(|| WarpError::not_found("screen share", stream_id))?;
+        if share.sharer == user_id {
+            return Err(WarpError::Validation(
+                "The sharer already has control".to_string(),
+            ));
+        }
+        if let ControlState::Granted { user_id: holder } = &share.control {
+            return Err(WarpError::Validation(format!(
+                "{} has control of this screen",
+                holder
+            )));
+        }
+        let request_id = uuid::Uuid::new_v4().to_string();
+        share.control = ControlState::Requested {
+            user_id: user_id.to_string(),
+            request_id: request_id.clone(),
+        };
+        Ok(request_id)
+    }
+
+    // Only the sharer answers; returns who was granted control, if anyone
+    pub async fn respond_to_control(
+        &self,
+        stream_id: &str,
+        sharer: &str,
+        request_id: &str,
+        approve: bool,
+    ) -> Result<Option<String>, WarpError> {
+        let mut streams = self.streams.lock().await;
+        let share = streams
+            .get_mut(stream_id)
+            .ok_or_else(|| WarpError::not_found("screen share", stream_id))?;
+        if share.sharer != sharer {
+            return Err(WarpError::Permission(
+                "Only the sharer can hand over control".to_string(),
+            ));
+        }
+        let requester = match &share.control {
+            ControlState::Requested {
+                user_id,
+                request_id: pending,
+            } if pending == request_id => user_id.clone(),
+            _ => return Err(WarpError::not_found("control request", request_id)),
+        };
+        if approve {
+            share.control = ControlState::Granted {
+                user_id: requester.clone(),
+            };
+            Ok(Some(requester))
+        } else {
+            share.control = ControlState::Sharer;
+            Ok(None)
+        }
+    }
+
+    // Either side can end it; control returns to the sharer
+    pub async fn release_control(&self, stream_id: &str, user_id: &str) -> Result<(), WarpError> {
+        let mut streams = self.streams.lock().await;
+        let share = streams
+            .get_mut(stream_id)
+            .ok_or_else(|| WarpError::not_found("screen share", stream_id))?;
+        let holder = match &share.control {
+            ControlState::Granted { user_id } | ControlState::Requested { user_id, .. } => {
+                Some(user_id.as_str())
+            }
+            ControlState::Sharer => None,
+        };
+        if holder != Some(user_id) && share.sharer != user_id {
+            return Err(WarpError::Permission(
+                "Not in control of this screen".to_string(),
+            ));
+        }
+        share.control = ControlState::Sharer;
+        Ok(())
+    }
+
+    // Input from a participant, passed through to the pane only while they
+    // hold control
+    pub async fn forward_input(
+        &self,
+        stream_id: &str,
+        user_id: &str,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>, WarpError> {
+        let streams = self.streams.lock().await;
+        let share = streams
+            .get(stream_id)
+            .ok_or_else(|| WarpError::not_found("screen share", stream_id))?;
+        match &share.control {
+            ControlState::Granted { user_id: holder } if holder == user_id => Ok(input),
+            _ => Err(WarpError::Permission(
+                "Control of this screen hasn't been granted".to_string(),
+            )),
+        }
+    }
+
+    pub async fn stop_for_user(&self, session_id: &str, user_id: &str) -> Result<(), WarpError> {
+        self.stop_sharing(session_id, user_id).await;
+        // Whoever left also gives up control of anyone else's screen
+        self.revoke_control(session_id, user_id).await;
+        Ok(())
+    }
+
+    // Ends the user's own shares; returns their stream ids
+    pub async fn stop_sharing(&self, session_id: &str, user_id: &str) -> Vec<String> {
+        let mut streams = self.streams.lock().await;
+        let stopped: Vec<String> = streams
+            .iter()
+            .filter(|(_, share)| share.session_id == session_id && share.sharer == user_id)
+            .map(|(stream_id, _)| stream_id.clone())
+            .collect();
+        for stream_id in &stopped {
+            streams.remove(stream_id);
+        }
+        stopped
+    }
+
+    // Hands control of every screen in the session the user holds or has
+    // asked for back to its sharer
+    pub async fn revoke_control(&self, session_id: &str, user_id: &str) {
+        let mut streams = self.streams.lock().await;
+        for share in streams
+            .values_mut()
+            .filter(|share| share.session_id == session_id)
+        {
+            let holds = match &share.control {
+                ControlState::Granted { user_id: holder }
+                | ControlState::Requested {
+                    user_id: holder, ..
+                } => holder == user_id,
+                ControlState::Sharer => false,
+            };
+            if holds {
+                share.control = ControlState::Sharer;
+            }
+        }
+    }
+
+    pub async fn cleanup_session(&self, session_id: &str) -> Result<(), WarpError> {
+        self.streams
+            .lock()
+            .await
+            .retain(|_, share| share.session_id != session_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch_round_trip(patch: &GridPatch) -> GridPatch {
+        GridPatch::from_bytes(&patch.to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_viewer_follows_sharer() {
+        let mut encoder = GridEncoder::new();
+        let mut viewer = GridViewer::new();
+        let mut grid = Grid::new(12, 3);
+        grid.put_str(0, 0, "$ ls 日本", Color::Green, Color::Reset, true);
+        grid.put_str(
+            0,
+            1,
+            "src",
+            Color::Rgb { r: 1, g: 2, b: 3 },
+            Color::AnsiValue(236),
+            false,
+        );
+
+        let first = encoder.encode(&grid, Some((0, 2))).unwrap();
+        assert!(first.keyframe);
+        viewer.apply(&patch_round_trip(&first)).unwrap();
+        assert_eq!(viewer.grid(), &grid);
+        assert!(encoder.encode(&grid, Some((0, 2))).is_none());
+
+        grid.put_str(4, 1, "!", Color::Red, Color::Reset, false);
+        let second = encoder.encode(&grid, Some((5, 1))).unwrap();
+        assert!(!second.keyframe);
+        assert_eq!(second.spans.len(), 1);
+        let damage = viewer.apply(&patch_round_trip(&second)).unwrap();
+        assert_eq!(
+            damage,
+            vec![DamageRegion {
+                y: 1,
+                x_start: 4,
+                x_end: 5
+            }]
+        );
+        assert_eq!(viewer.grid(), &grid);
+        assert_eq!(viewer.cursor(), Some((5, 1)));
+    }
+
+    #[test]
+    fn test_missed_patch_needs_keyframe() {
+        let mut encoder = GridEncoder::new();
+        let mut viewer = GridViewer::new();
+        let mut grid = Grid::new(4, 1);
+        viewer.apply(&encoder.encode(&grid, None).unwrap()).unwrap();
+        grid.put_str(0, 0, "a", Color::White, Color::Reset, false);
+        let _lost = encoder.encode(&grid, None).unwrap();
+        grid.put_str(1, 0, "b", Color::White, Color::Reset, false);
+        assert!(viewer.apply(&encoder.encode(&grid, None).unwrap()).is_err());
+
+        encoder.request_keyframe();
+        viewer.apply(&encoder.encode(&grid, None).unwrap()).unwrap();
+        assert_eq!(viewer.grid(), &grid);
+    }
+
+    #[tokio::test]
+    async fn test_take_control_flow() {
+        let manager = ScreenSharingManager::new().await.unwrap();
+        let stream = manager.start_screen_share("s1", "alice").await.unwrap();
+        assert!(manager
+            .forward_input(&stream, "bob", b"ls\r".to_vec())
+            .await
+            .is_err());
+
+        let request = manager.request_control(&stream, "bob").await.unwrap();
+        assert!(manager
+            .respond_to_control(&stream, "bob", &request, true)
+            .await
+            .is_err());
+        let granted = manager
+            .respond_to_control(&stream, "alice", &request, true)
+            .await
+            .unwrap();
+        assert_eq!(granted.as_deref(), Some("bob"));
+        assert_eq!(
+            manager
+                .forward_input(&stream, "bob", b"ls\r".to_vec())
+                .await
+                .unwrap(),
+            b"ls\r"
+        );
+        assert!(manager.request_control(&stream, "carol").await.is_err());
+
+        manager.stop_for_user("s1", "bob").await.unwrap();
+        assert_eq!(
+            manager.control_state(&stream).await,
+            Some(ControlState::Sharer)
+        );
+    }
+}
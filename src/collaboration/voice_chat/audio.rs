@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::VoiceConfig;
+use crate::error::WarpError;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+// 20 ms of mono audio, the Opus frame size we use
+pub const FRAME_SAMPLES: usize = 960;
+// Playback queued beyond this is dropped so latency can't build up
+const MAX_PLAYOUT_SAMPLES: usize = FRAME_SAMPLES * 10;
+
+// Mixed audio waiting for the output callback, which plays silence when
+// it runs dry
+#[derive(Clone, Default)]
+pub struct Playout(Arc<std::sync::Mutex<VecDeque<i16>>>);
+
+impl Playout {
+    pub fn push(&self, samples: &[i16]) {
+        let mut queue = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        queue.extend(samples);
+        let excess = queue.len().saturating_sub(MAX_PLAYOUT_SAMPLES);
+        queue.drain(..excess);
+    }
+
+    fn pop(&self) -> i16 {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+            .unwrap_or(0)
+    }
+}
+
+pub fn input_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    host.input_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+pub fn output_devices() -> Vec<String> {
+    let host = cpal::default_host();
+    host.output_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+// Capture and playback streams. cpal streams can't move between threads on
+// every platform, so both live on a thread of their own until dropped.
+pub struct AudioIo {
+    stop: mpsc::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AudioIo {
+    // Captured audio is sent as mono 48 kHz chunks of whatever size the
+    // device delivers
+    pub fn start(
+        config: &VoiceConfig,
+        captured: UnboundedSender<Vec<i16>>,
+        playout: Playout,
+    ) -> Result<Self, WarpError> {
+        let input_name = config.input_device.clone();
+        let output_name = config.output_device.clone();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (stop, stop_rx) = mpsc::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("voice-audio".to_string())
+            .spawn(move || {
+                let streams = open_streams(
+                    input_name.as_deref(),
+                    output_name.as_deref(),
+                    captured,
+                    playout,
+                );
+                match streams {
+                    Ok(streams) => {
+                        let _ = ready_tx.send(Ok(()));
+                        // Returns once AudioIo is dropped
+                        let _ = stop_rx.recv();
+                        drop(streams);
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                    }
+                }
+            })?;
+        let ready = ready_rx.recv().map_err(|_| {
+            WarpError::Config("The audio thread exited before opening devices".to_string())
+        })?;
+        let io = Self {
+            stop,
+            thread: Some(thread),
+        };
+        ready.map(|()| io)
+    }
+}
+
+impl Drop for AudioIo {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn open_streams(
+    input_name: Option<&str>,
+    output_name: Option<&str>,
+    captured: UnboundedSender<Vec<i16>>,
+    playout: Playout,
+) -> Result<(cpal::Stream, cpal::Stream), WarpError> {
+    let host = cpal::default_host();
+    let input = match input_name {
+        Some(name) => host
+            .input_devices()
+            .ok()
+            .and_then(|devices| named(devices, name)),
+        None => host.default_input_device(),
+    }
+    .ok_or_else(|| {
+        WarpError::Config(format!(
+            "Microphone not found: {}",
+            input_name.unwrap_or("default")
+        ))
+    })?;
+    let output = match output_name {
+        Some(name) => host
+            .output_devices()
+            .ok()
+            .and_then(|devices| named(devices, name)),
+        None => host.default_output_device(),
+    }
+    .ok_or_else(|| {
+        WarpError::Config(format!(
+            "Audio output not found: {}",
+            output_name.unwrap_or("default")
+        ))
+    })?;
+
+    let input_config = pick_config(
+        input
+            .supported_input_configs()
+            .map(|configs| configs.collect()),
+    )?;
+    let output_config = pick_config(
+        output
+            .supported_output_configs()
+            .map(|configs| configs.collect()),
+    )?;
+    let capture = match input_config.sample_format() {
+        SampleFormat::F32 => build_input::<f32>(&input, &input_config.config(), captured),
+        _ => build_input::<i16>(&input, &input_config.config(), captured),
+    }?;
+    let playback = match output_config.sample_format() {
+        SampleFormat::F32 => build_output::<f32>(&output, &output_config.config(), playout),
+        _ => build_output::<i16>(&output, &output_config.config(), playout),
+    }?;
+    capture
+        .play()
+        .map_err(|e| WarpError::Config(format!("Cannot start the microphone: {}", e)))?;
+    playback
+        .play()
+        .map_err(|e| WarpError::Config(format!("Cannot start audio output: {}", e)))?;
+    Ok((capture, playback))
+}
+
+fn named(mut devices: impl Iterator<Item = cpal::Device>, name: &str) -> Option<cpal::Device> {
+    devices.find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+// 48 kHz in f32 or i16, fewest channels first; we don't resample
+fn pick_config(
+    ranges: Result<Vec<cpal::SupportedStreamConfigRange>, cpal::SupportedStreamConfigsError>,
+) -> Result<cpal::SupportedStreamConfig, WarpError> {
+    let mut ranges =
+        ranges.map_err(|e| WarpError::Config(format!("Cannot query audio device: {}", e)))?;
+    ranges.sort_by_key(|range| range.channels());
+    ranges
+        .into_iter()
+        .filter(|range| matches!(range.sample_format(), SampleFormat::F32 | SampleFormat::I16))
+        .find(|range| {
+            range.min_sample_rate().0 <= SAMPLE_RATE && SAMPLE_RATE <= range.max_sample_rate().0
+        })
+        .map(|range| range.with_sample_rate(cpal::SampleRate(SAMPLE_RATE)))
+        .ok_or_else(|| WarpError::Config("The audio device doesn't support 48 kHz".to_string()))
+}
+
+fn build_input<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    captured: UnboundedSender<Vec<i16>>,
+) -> Result<cpal::Stream, WarpError>
+where
+    T: SizedSample,
+    i16: FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                // Keep the first channel; voice is the same on all of them
+                let mono = data
+                    .chunks(channels)
+                    .map(|frame| frame[0].to_sample::<i16>())
+                    .collect();
+                let _ = captured.send(mono);
+            },
+            |e| log::warn!("Microphone stream error: {}", e),
+            None,
+        )
+        .map_err(|e| WarpError::Config(format!("Cannot open the microphone: {}", e)))
+}
+
+fn build_output<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    playout: Playout,
+) -> Result<cpal::Stream, WarpError>
+where
+    T: SizedSample + FromSample<i16>,
+{
+    let channels = config.channels.max(1) as usize;
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                for frame in data.chunks_mut(channels) {
+                    let sample = T::from_sample(playout.pop());
+                    frame.fill(sample);
+                }
+            },
+            |e| log::warn!("Audio output stream error: {}", e),
+            None,
+        )
+        .map_err(|e| WarpError::Config(format!("Cannot open audio output: {}", e)))
+}
+
+// Root mean square level in 0..1
+pub fn level(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = samples
+        .iter()
+        .map(|&sample| (sample as f64 / i16::MAX as f64).powi(2))
+        .sum();
+    (sum / samples.len() as f64).sqrt() as f32
+}
+
+// Adds frames sample by sample, clipping instead of wrapping
+pub fn mix(into: &mut [i16], frame: &[i16]) {
+    for (out, &sample) in into.iter_mut().zip(frame) {
+        *out = out.saturating_add(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_and_mix() {
+        assert_eq!(level(&[]), 0.0);
+        assert_eq!(level(&[0; 960]), 0.0);
+        assert!((level(&[i16::MAX, -i16::MAX]) - 1.0).abs() < 1e-6);
+
+        let mut mixed = vec![30_000, -30_000, 5];
+        mix(&mut mixed, &[10_000, -10_000, 5]);
+        assert_eq!(mixed, vec![i16::MAX, i16::MIN, 10]);
+    }
+
+    #[test]
+    fn test_playout_is_bounded() {
+        let playout = Playout::default();
+        playout.push(&vec![1; MAX_PLAYOUT_SAMPLES + 5]);
+        playout.push(&[7]);
+        let queued = playout.0.lock().unwrap();
+        assert_eq!(queued.len(), MAX_PLAYOUT_SAMPLES);
+        assert_eq!(queued.back(), Some(&7));
+    }
+}
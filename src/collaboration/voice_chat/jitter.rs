@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Audio(Vec<u8>),
+    // Missing when its turn came; the decoder conceals it
+    Lost,
+}
+
+// Reorders one peer's packets and plays them out one per 20 ms tick.
+// Playout starts once `depth` frames are queued and starts over after the
+// queue runs dry, so a peer that goes quiet re-buffers when it resumes.
+pub struct JitterBuffer {
+    depth: usize,
+    max_frames: usize,
+    packets: BTreeMap<u64, Vec<u8>>,
+    // Sequence numbers widened past u16 so wraparound keeps their order
+    highest: Option<u64>,
+    next: Option<u64>,
+}
+
+impl JitterBuffer {
+    pub fn new(depth: usize) -> Self {
+        let depth = depth.max(1);
+        Self {
+            depth,
+            // A stall longer than this is dropped rather than played late
+            max_frames: depth * 4,
+            packets: BTreeMap::new(),
+            highest: None,
+            next: None,
+        }
+    }
+
+    // Returns false for packets that arrived after their turn or twice
+    pub fn push(&mut self, seq: u16, payload: Vec<u8>) -> bool {
+        let seq = self.extend(seq);
+        if self.next.is_some_and(|next| seq < next) || self.packets.contains_key(&seq) {
+            return false;
+        }
+        self.packets.insert(seq, payload);
+        self.highest = Some(self.highest.map_or(seq, |highest| highest.max(seq)));
+        while self.packets.len() > self.max_frames {
+            if let Some((oldest, _)) = self.packets.pop_first() {
+                self.next = self.next.map(|next| next.max(oldest + 1));
+            }
+        }
+        true
+    }
+
+    // The next frame to play, or None while buffering
+    pub fn pop(&mut self) -> Option<Frame> {
+        let next = match self.next {
+            Some(next) => next,
+            None if self.packets.len() >= self.depth => *self.packets.keys().next()?,
+            None => return None,
+        };
+        if self.packets.is_empty() {
+            self.next = None;
+            return None;
+        }
+        self.next = Some(next + 1);
+        Some(match self.packets.remove(&next) {
+            Some(payload) => Frame::Audio(payload),
+            None => Frame::Lost,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    fn extend(&self, seq: u16) -> u64 {
+        match self.highest {
+            // Start well above zero so packets just before the first still fit
+            None => (1 << 16) + seq as u64,
+            Some(highest) => {
+                let delta = seq.wrapping_sub(highest as u16) as i16;
+                (highest as i64 + delta as i64) as u64
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(byte: u8) -> Option<Frame> {
+        Some(Frame::Audio(vec![byte]))
+    }
+
+    #[test]
+    fn test_reorders_and_conceals_loss() {
+        let mut buffer = JitterBuffer::new(3);
+        buffer.push(11, vec![11]);
+        buffer.push(10, vec![10]);
+        assert_eq!(buffer.pop(), None);
+        buffer.push(13, vec![13]);
+
+        assert_eq!(buffer.pop(), audio(10));
+        assert_eq!(buffer.pop(), audio(11));
+        assert_eq!(buffer.pop(), Some(Frame::Lost));
+        // 12 turned up after its slot was played
+        assert!(!buffer.push(12, vec![12]));
+        assert_eq!(buffer.pop(), audio(13));
+        // Ran dry: buffer again
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn test_sequence_wraparound() {
+        let mut buffer = JitterBuffer::new(2);
+        buffer.push(65535, vec![1]);
+        buffer.push(0, vec![2]);
+        buffer.push(1, vec![3]);
+        assert_eq!(buffer.pop(), audio(1));
+        assert_eq!(buffer.pop(), audio(2));
+        assert_eq!(buffer.pop(), audio(3));
+    }
+
+    #[test]
+    fn test_long_stall_is_dropped() {
+        let mut buffer = JitterBuffer::new(1);
+        for seq in 0..10 {
+            buffer.push(seq, vec![seq as u8]);
+        }
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.pop(), audio(6));
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::config::VoiceConfig;
+use crate::error::WarpError;
+
+pub mod audio;
+pub mod jitter;
+pub mod packet;
+
+use audio::{AudioIo, Playout, FRAME_SAMPLES};
+use jitter::{Frame, JitterBuffer};
+use packet::{VoicePacket, MAX_PAYLOAD};
+
+const FRAME_DURATION: Duration = Duration::from_millis(20);
+// How long speech has to stop before the speaking indicator goes out
+const SPEAKING_HANGOVER: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakingEvent {
+    pub session_id: String,
+    pub user_id: String,
+    pub speaking: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceState {
+    pub room_id: String,
+    pub local_address: SocketAddr,
+    pub muted: bool,
+    pub deafened: bool,
+    pub peers: Vec<String>,
+    pub speaking: Vec<String>,
+}
+
+// A remote participant's audio on its way to the speakers
+struct RemoteStream {
+    jitter: JitterBuffer,
+    decoder: opus::Decoder,
+    ssrc: u32,
+    speaking: bool,
+    last_voice: Option<Instant>,
+}
+
+// What the send, receive and playout tasks of one room share
+struct Shared {
+    session_id: String,
+    user_id: String,
+    // Addresses learned through session signaling; anything else is ignored
+    peers: RwLock<HashMap<SocketAddr, String>>,
+    streams: std::sync::Mutex<HashMap<String, RemoteStream>>,
+    muted: AtomicBool,
+    deafened: AtomicBool,
+    speaking: AtomicBool,
+    events: broadcast::Sender<SpeakingEvent>,
+}
+
+impl Shared {
+    fn announce(&self, user_id: &str, speaking: bool) {
+        let _ = self.events.send(SpeakingEvent {
+            session_id: self.session_id.clone(),
+            user_id: user_id.to_string(),
+            speaking,
+        });
+    }
+
+    fn peer_addresses(&self) -> Vec<SocketAddr> {
+        self.peers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .copied()
+            .collect()
+    }
+}
+
+// The local end of a session's voice chat: microphone and speakers, one
+// UDP socket, and the tasks moving audio between them
+struct VoiceRoom {
+    room_id: String,
+    local_address: SocketAddr,
+    shared: Arc<Shared>,
+    tasks: Vec<JoinHandle<()>>,
+    _audio: AudioIo,
+}
+
+impl Drop for VoiceRoom {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Voice for collaboration sessions: 20 ms Opus frames over UDP straight
+// to each peer, whose addresses come from session signaling.
+pub struct VoiceChatManager {
+    config: VoiceConfig,
+    rooms: Mutex<HashMap<String, VoiceRoom>>,
+    events: broadcast::Sender<SpeakingEvent>,
+}
+
+impl VoiceChatManager {
+    pub async fn new(config: VoiceConfig) -> Result<Self, WarpError> {
+        Ok(Self {
+            config,
+            rooms: Mutex::new(HashMap::new()),
+            events: broadcast::channel(256).0,
+        })
+    }
+
+    pub fn input_devices() -> Vec<String> {
+        audio::input_devices()
+    }
+
+    pub fn output_devices() -> Vec<String> {
+        audio::output_devices()
+    }
+
+    pub fn subscribe_speaking(&self) -> broadcast::Receiver<SpeakingEvent> {
+        self.events.subscribe()
+    }
+
+    // Opens the microphone and speakers for the local user and returns the
+    // room id. Starting again while running returns the same room.
+    pub async fn start_voice_chat(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<String, WarpError> {
+        let mut rooms = self.rooms.lock().await;
+        if let Some(room) = rooms.get(session_id) {
+            return Ok(room.room_id.clone());
+        }
+        let config = self.config.clone();
+
+        let socket = UdpSocket::bind(("0.0.0.0", config.port))
+            .await
+            .map_err(|e| WarpError::network("Cannot open the voice socket", e))?;
+        let local_address = socket.local_addr()?;
+        let socket = Arc::new(socket);
+
+        let mut encoder = opus::Encoder::new(
+            audio::SAMPLE_RATE,
+            opus::Channels::Mono,
+            opus::Application::Voip,
+        )
+        .map_err(|e| WarpError::Config(format!("Cannot create the Opus encoder: {}", e)))?;
+        encoder
+            .set_bitrate(opus::Bitrate::Bits(config.bitrate))
+            .map_err(|e| {
+                WarpError::Config(format!("Invalid voice bitrate {}: {}", config.bitrate, e))
+            })?;
+
+        let (captured_tx, captured_rx) = mpsc::unbounded_channel();
+        let playout = Playout::default();
+        let audio = AudioIo::start(&config, captured_tx, playout.clone())?;
+
+        let shared = Arc::new(Shared {
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            peers: RwLock::new(HashMap::new()),
+            streams: std::sync::Mutex::new(HashMap::new()),
+            muted: AtomicBool::new(false),
+            deafened: AtomicBool::new(false),
+            speaking: AtomicBool::new(false),
+            events: self.events.clone(),
+        });
+        let depth = (config.jitter_ms as usize / FRAME_DURATION.as_millis() as usize).max(1);
+        let tasks = vec![
+            tokio::spawn(send_loop(
+                shared.clone(),
+                socket.clone(),
+                encoder,
+                captured_rx,
+                config.speaking_threshold,
+            )),
+            tokio::spawn(receive_loop(shared.clone(), socket, depth)),
+            tokio::spawn(playout_loop(shared.clone(), playout)),
+        ];
+
+        let room_id = uuid::Uuid::new_v4().to_string();
+        log::info!(
+            "Voice chat for session {} listening on {}",
+            session_id,
+            local_address
+        );
+        rooms.insert(
+            session_id.to_string(),
+            VoiceRoom {
+                room_id: room_id.clone(),
+                local_address,
+                shared,
+                tasks,
+                _audio: audio,
+            },
+        );
+        Ok(room_id)
+    }
+
+    // Where peers should send audio; advertised over session signaling
+    pub async fn local_address(&self, session_id: &str) -> Option<SocketAddr> {
+        self.rooms
+            .lock()
+            .await
+            .get(session_id)
+            .map(|room| room.local_address)
+    }
+
+    pub async fn add_peer(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        address: SocketAddr,
+    ) -> Result<(), WarpError> {
+        let rooms = self.rooms.lock().await;
+        let room = rooms
+            .get(session_id)
+            .ok_or_else(|| WarpError::not_found("voice room", session_id))?;
+        let mut peers = room.shared.peers.write().unwrap_or_else(|e| e.into_inner());
+        peers.retain(|_, peer| peer != user_id);
+        peers.insert(address, user_id.to_string());
+        Ok(())
+    }
+
+    // The local user leaving closes the room; anyone else is just dropped
+    pub async fn stop_for_user(&self, session_id: &str, user_id: &str) -> Result<(), WarpError> {
+        let mut rooms = self.rooms.lock().await;
+        let Some(room) = rooms.get(session_id) else {
+            return Ok(());
+        };
+        if room.shared.user_id == user_id {
+            rooms.remove(session_id);
+            return Ok(());
+        }
+        room.shared
+            .peers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, peer| peer != user_id);
+        let removed = room
+            .shared
+            .streams
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(user_id);
+        if removed.is_some_and(|stream| stream.speaking) {
+            room.shared.announce(user_id, false);
+        }
+        Ok(())
+    }
+
+    pub async fn cleanup_session(&self, session_id: &str) -> Result<(), WarpError> {
+        self.rooms.lock().await.remove(session_id);
+        Ok(())
+    }
+
+    pub async fn set_muted(&self, session_id: &str, muted: bool) -> Result<(), WarpError> {
+        let rooms = self.rooms.lock().await;
+        let room = rooms
+            .get(session_id)
+            .ok_or_else(|| WarpError::not_found("voice room", session_id))?;
+        room.shared.muted.store(muted, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Deafened users hear nothing and, as in most voice apps, aren't heard
+    pub async fn set_deafened(&self, session_id: &str, deafened: bool) -> Result<(), WarpError> {
+        let rooms = self.rooms.lock().await;
+        let room = rooms
+            .get(session_id)
+            .ok_or_else(|| WarpError::not_found("voice room", session_id))?;
+        room.shared.deafened.store(deafened, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub async fn state(&self, session_id: &str) -> Option<VoiceState> {
+        let rooms = self.rooms.lock().await;
+        let room = rooms.get(session_id)?;
+        let shared = &room.shared;
+        let mut speaking: Vec<String> = shared
+            .streams
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, stream)| stream.speaking)
+            .map(|(user, _)| user.clone())
+            .collect();
+        if shared.speaking.load(Ordering::Relaxed) {
+            speaking.push(shared.user_id.clone());
+        }
+        speaking.sort();
+        let mut peers: Vec<String> = shared
+            .peers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        peers.sort();
+        Some(VoiceState {
+            room_id: room.room_id.clone(),
+            local_address: room.local_address,
+            muted: shared.muted.load(Ordering::Relaxed),
+            deafened: shared.deafened.load(Ordering::Relaxed),
+            peers,
+            speaking,
+        })
+    }
+}
+
+// Cuts captured audio into frames, tracks whether we're speaking, and
+// sends each encoded frame to every peer unless muted
+async fn send_loop(
+    shared: Arc<Shared>,
+    socket: Arc<UdpSocket>,
+    mut encoder: opus::Encoder,
+    mut captured: mpsc::UnboundedReceiver<Vec<i16>>,
+    threshold: f32,
+) {
+    let ssrc = uuid::Uuid::new_v4().as_u128() as u32;
+    let mut pending: Vec<i16> = Vec::with_capacity(FRAME_SAMPLES * 2);
+    let mut seq: u16 = 0;
+    let mut timestamp: u32 = 0;
+    let mut last_voice: Option<Instant> = None;
+    let mut encoded = vec![0u8; MAX_PAYLOAD];
+    while let Some(samples) = captured.recv().await {
+        pending.extend(samples);
+        while pending.len() >= FRAME_SAMPLES {
+            let frame: Vec<i16> = pending.drain(..FRAME_SAMPLES).collect();
+            let now = Instant::now();
+            timestamp = timestamp.wrapping_add(FRAME_SAMPLES as u32);
+
+            let silenced =
+                shared.muted.load(Ordering::Relaxed) || shared.deafened.load(Ordering::Relaxed);
+            if !silenced && audio::level(&frame) >= threshold {
+                last_voice = Some(now);
+            }
+            let speaking = !silenced
+                && last_voice.is_some_and(|at| now.duration_since(at) < SPEAKING_HANGOVER);
+            if shared.speaking.swap(speaking, Ordering::Relaxed) != speaking {
+                shared.announce(&shared.user_id, speaking);
+            }
+            if silenced {
+                continue;
+            }
+
+            let len = match encoder.encode(&frame, &mut encoded) {
+                Ok(len) => len,
+                Err(e) => {
+                    log::warn!("Opus encoding failed: {}", e);
+                    continue;
+                }
+            };
+            let bytes = VoicePacket {
+                ssrc,
+                seq,
+                timestamp,
+                speaking,
+                payload: encoded[..len].to_vec(),
+            }
+            .encode();
+            seq = seq.wrapping_add(1);
+            for address in shared.peer_addresses() {
+                if let Err(e) = socket.send_to(&bytes, address).await {
+                    log::debug!("Voice packet to {} failed: {}", address, e);
+                }
+            }
+        }
+    }
+}
+
+async fn receive_loop(shared: Arc<Shared>, socket: Arc<UdpSocket>, depth: usize) {
+    let mut buffer = vec![0u8; 2048];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                log::debug!("Voice socket receive failed: {}", e);
+                continue;
+            }
+        };
+        let Some(user_id) = shared
+            .peers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&from)
+            .cloned()
+        else {
+            continue;
+        };
+        let packet = match VoicePacket::decode(&buffer[..len]) {
+            Ok(packet) => packet,
+            Err(e) => {
+                log::debug!("Dropping voice packet from {}: {}", from, e);
+                continue;
+            }
+        };
+        let mut streams = shared.streams.lock().unwrap_or_else(|e| e.into_inner());
+        // A new SSRC means the peer restarted; its sequence numbers start over
+        if streams
+            .get(&user_id)
+            .is_some_and(|stream| stream.ssrc != packet.ssrc)
+        {
+            streams.remove(&user_id);
+        }
+        let stream = match streams.entry(user_id.clone()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                match opus::Decoder::new(audio::SAMPLE_RATE, opus::Channels::Mono) {
+                    Ok(decoder) => entry.insert(RemoteStream {
+                        jitter: JitterBuffer::new(depth),
+                        decoder,
+                        ssrc: packet.ssrc,
+                        speaking: false,
+                        last_voice: None,
+                    }),
+                    Err(e) => {
+                        log::warn!("Cannot create an Opus decoder: {}", e);
+                        continue;
+                    }
+                }
+            }
+        };
+        if packet.speaking {
+            stream.last_voice = Some(Instant::now());
+            if !stream.speaking {
+                stream.speaking = true;
+                shared.announce(&user_id, true);
+            }
+        }
+        stream.jitter.push(packet.seq, packet.payload);
+    }
+}
+
+// Every 20 ms: one frame from each peer's jitter buffer, decoded (or
+// concealed when lost), mixed and queued for the speakers
+async fn playout_loop(shared: Arc<Shared>, playout: Playout) {
+    let mut interval = tokio::time::interval(FRAME_DURATION);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut decoded = vec![0i16; FRAME_SAMPLES];
+    loop {
+        interval.tick().await;
+        let now = Instant::now();
+        let mut mixed = vec![0i16; FRAME_SAMPLES];
+        let mut audible = false;
+        {
+            let mut streams = shared.streams.lock().unwrap_or_else(|e| e.into_inner());
+            for (user_id, stream) in streams.iter_mut() {
+                let result = match stream.jitter.pop() {
+                    Some(Frame::Audio(payload)) => {
+                        stream.decoder.decode(&payload, &mut decoded, false)
+                    }
+                    Some(Frame::Lost) => stream.decoder.decode(&[], &mut decoded, false),
+                    None => Ok(0),
+                };
+                match result {
+                    Ok(samples) if samples > 0 => {
+                        audio::mix(&mut mixed, &decoded[..samples.min(FRAME_SAMPLES)]);
+                        audible = true;
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::debug!("Opus decoding failed for {}: {}", user_id, e),
+                }
+                let quiet = stream
+                    .last_voice
+                    .is_none_or(|at| now.duration_since(at) >= SPEAKING_HANGOVER);
+                if stream.speaking && quiet {
+                    stream.speaking = false;
+                    shared.announce(user_id, false);
+                }
+            }
+        }
+        if audible && !shared.deafened.load(Ordering::Relaxed) {
+            playout.push(&mixed);
+        }
+    }
+}
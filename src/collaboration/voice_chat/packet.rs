@@ -0,0 +1,82 @@
+use crate::error::WarpError;
+
+const MAGIC: [u8; 2] = *b"WV";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 14;
+const FLAG_SPEAKING: u8 = 0b0000_0001;
+
+// Largest Opus packet we send; a 20 ms frame at our bitrates is far smaller
+pub const MAX_PAYLOAD: usize = 1275;
+
+// One 20 ms Opus frame on the wire:
+//   magic "WV" | version | flags | ssrc u32 | seq u16 | timestamp u32 | payload
+// Integers are big-endian. The timestamp counts samples at 48 kHz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoicePacket {
+    pub ssrc: u32,
+    pub seq: u16,
+    pub timestamp: u32,
+    pub speaking: bool,
+    pub payload: Vec<u8>,
+}
+
+impl VoicePacket {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.push(if self.speaking { FLAG_SPEAKING } else { 0 });
+        bytes.extend_from_slice(&self.ssrc.to_be_bytes());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, WarpError> {
+        if bytes.len() < HEADER_LEN || bytes[..2] != MAGIC {
+            return Err(WarpError::Validation("Not a voice packet".to_string()));
+        }
+        if bytes[2] != VERSION {
+            return Err(WarpError::Validation(format!(
+                "Unsupported voice packet version {}",
+                bytes[2]
+            )));
+        }
+        let payload = &bytes[HEADER_LEN..];
+        if payload.len() > MAX_PAYLOAD {
+            return Err(WarpError::Validation("Voice packet too large".to_string()));
+        }
+        Ok(Self {
+            speaking: bytes[3] & FLAG_SPEAKING != 0,
+            ssrc: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            seq: u16::from_be_bytes([bytes[8], bytes[9]]),
+            timestamp: u32::from_be_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]),
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let packet = VoicePacket {
+            ssrc: 0xdeadbeef,
+            seq: 65535,
+            timestamp: 960,
+            speaking: true,
+            payload: vec![1, 2, 3],
+        };
+        let bytes = packet.encode();
+        assert_eq!(&bytes[..4], b"WV\x01\x01");
+        assert_eq!(VoicePacket::decode(&bytes).unwrap(), packet);
+
+        assert!(VoicePacket::decode(b"WV\x01").is_err());
+        let mut other = bytes.clone();
+        other[2] = 9;
+        assert!(VoicePacket::decode(&other).is_err());
+    }
+}
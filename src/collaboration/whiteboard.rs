@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::error::WarpError;
+use crate::grid::Grid;
+
+// Each terminal cell holds a 2x4 braille dot matrix
+const DOTS_X: i32 = 2;
+const DOTS_Y: i32 = 4;
+// Cell size in exported images
+const CELL_W: i32 = 10;
+const CELL_H: i32 = 20;
+const MAX_ELEMENTS: usize = 10_000;
+const MAX_TEXT: usize = 500;
+const ARROW_WING: f64 = 3.0;
+
+const PALETTE: [(u8, u8, u8); 8] = [
+    (0x26, 0x8b, 0xd2),
+    (0xdc, 0x32, 0x2f),
+    (0x85, 0x99, 0x00),
+    (0xd3, 0x36, 0x82),
+    (0xb5, 0x89, 0x00),
+    (0x2a, 0xa1, 0x98),
+    (0xcb, 0x4b, 0x16),
+    (0x6c, 0x71, 0xc4),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: u16,
+    pub y: u16,
+}
+
+// Positions are in terminal cells
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Box { at: Point, width: u16, height: u16 },
+    Line { from: Point, to: Point },
+    Arrow { from: Point, to: Point },
+    Text { at: Point, text: String },
+}
+
+impl Shape {
+    fn validate(&self) -> Result<(), WarpError> {
+        match self {
+            Shape::Box { width, height, .. } if *width < 2 || *height < 2 => Err(
+                WarpError::Validation("A box needs at least 2x2 cells".to_string()),
+            ),
+            Shape::Text { text, .. } if text.is_empty() || text.chars().count() > MAX_TEXT => Err(
+                WarpError::Validation(format!("Whiteboard text must be 1-{} characters", MAX_TEXT)),
+            ),
+            Shape::Text { text, .. } if text.chars().any(char::is_control) => {
+                Err(WarpError::Validation(
+                    "Whiteboard text can't contain control characters".to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Element {
+    pub id: String,
+    pub author: String,
+    // Lamport clock; every peer draws elements in (clock, author, id) order
+    pub clock: u64,
+    pub shape: Shape,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BoardOp {
+    Add(Element),
+    Remove { element_id: String },
+}
+
+// What travels over the collaboration transport as a WhiteboardUpdated event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhiteboardUpdate {
+    pub session_id: String,
+    pub user_id: String,
+    pub op: BoardOp,
+}
+
+// Every peer has to agree on a user's color without coordinating, so it
+// comes from a stable hash of the user id
+pub fn user_rgb(user_id: &str) -> (u8, u8, u8) {
+    let hash = user_id.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    PALETTE[hash as usize % PALETTE.len()]
+}
+
+pub fn user_color(user_id: &str) -> Color {
+    let (r, g, b) = user_rgb(user_id);
+    Color::Rgb { r, g, b }
+}
+
+#[derive(Default)]
+struct Board {
+    elements: Vec<Element>,
+    clock: u64,
+    // Each user's own elements, newest last, for undo
+    history: HashMap<String, Vec<String>>,
+}
+
+impl Board {
+    fn apply(&mut self, update: &WhiteboardUpdate) -> Result<bool, WarpError> {
+        match &update.op {
+            BoardOp::Add(element) => {
+                if element.author != update.user_id {
+                    return Err(WarpError::Permission(
+                        "Elements can only be added by their author".to_string(),
+                    ));
+                }
+                if self
+                    .elements
+                    .iter()
+                    .any(|existing| existing.id == element.id)
+                {
+                    return Ok(false);
+                }
+                if self.elements.len() >= MAX_ELEMENTS {
+                    return Err(WarpError::Validation("The whiteboard is full".to_string()));
+                }
+                element.shape.validate()?;
+                self.clock = self.clock.max(element.clock);
+                let key = |e: &Element| (e.clock, e.author.clone(), e.id.clone());
+                let index = self
+                    .elements
+                    .partition_point(|existing| key(existing) < key(element));
+                self.elements.insert(index, element.clone());
+                self.history
+                    .entry(element.author.clone())
+                    .or_default()
+                    .push(element.id.clone());
+                Ok(true)
+            }
+            BoardOp::Remove { element_id } => {
+                let Some(index) = self
+                    .elements
+                    .iter()
+                    .position(|element| &element.id == element_id)
+                else {
+                    return Ok(false);
+                };
+                if self.elements[index].author != update.user_id {
+                    return Err(WarpError::Permission(
+                        "Only the author can remove an element".to_string(),
+                    ));
+                }
+                self.elements.remove(index);
+                if let Some(history) = self.history.get_mut(&update.user_id) {
+                    history.retain(|id| id != element_id);
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+// A shared canvas per session. Local edits return the update to broadcast;
+// updates from other participants go through `apply`.
+pub struct WhiteboardManager {
+    boards: RwLock<HashMap<String, Board>>,
+}
+
+impl WhiteboardManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self {
+            boards: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub async fn draw(
+        &self,
+        session_id: &str,
+        user_id: &str,
+        shape: Shape,
+    ) -> Result<WhiteboardUpdate, WarpError> {
+        let mut boards = self.boards.write().await;
+        let board = boards.entry(session_id.to_string()).or_default();
+        let update = WhiteboardUpdate {
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            op: BoardOp::Add(Element {
+                id: uuid::Uuid::new_v4().to_string(),
+                author: user_id.to_string(),
+                clock: board.clock + 1,
+                shape,
+            }),
+        };
+        board.apply(&update)?;
+        Ok(update)
+    }
+
+    // Removes the user's most recent element still on the board
+    pub async fn undo(
+        &self,
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WhiteboardUpdate>, WarpError> {
+        let mut boards = self.boards.write().await;
+        let Some(board) = boards.get_mut(session_id) else {
+            return Ok(None);
+        };
+        let Some(element_id) = board
+            .history
+            .get(user_id)
+            .and_then(|history| history.last())
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        let update = WhiteboardUpdate {
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            op: BoardOp::Remove { element_id },
+        };
+        board.apply(&update)?;
+        Ok(Some(update))
+    }
+
+    // Returns false for updates already applied
+    pub async fn apply(&self, update: &WhiteboardUpdate) -> Result<bool, WarpError> {
+        let mut boards = self.boards.write().await;
+        boards
+            .entry(update.session_id.clone())
+            .or_default()
+            .apply(update)
+    }
+
+    pub async fn elements(&self, session_id: &str) -> Vec<Element> {
+        let boards = self.boards.read().await;
+        boards
+            .get(session_id)
+            .map(|board| board.elements.clone())
+            .unwrap_or_default()
+    }
+
+    pub async fn render(&self, session_id: &str, width: u16, height: u16) -> Grid {
+        render(&self.elements(session_id).await, width, height)
+    }
+
+    pub async fn export_svg(&self, session_id: &str) -> String {
+        to_svg(&self.elements(session_id).await)
+    }
+
+    pub async fn export_png(&self, session_id: &str, path: &Path) -> Result<(), WarpError> {
+        let png = to_png(&self.export_svg(session_id).await)?;
+        crate::shutdown::write_atomic(path, &png).await
+    }
+
+    pub async fn cleanup_session(&self, session_id: &str) -> Result<(), WarpError> {
+        self.boards.write().await.remove(session_id);
+        Ok(())
+    }
+}
+
+// Draws elements oldest first, so newer ones cover older ones
+pub fn render(elements: &[Element], width: u16, height: u16) -> Grid {
+    let mut grid = Grid::new(width, height);
+    for element in elements {
+        let color = user_color(&element.author);
+        match &element.shape {
+            Shape::Box { at, width, height } => draw_box(&mut grid, *at, *width, *height, color),
+            Shape::Line { from, to } => braille_line(&mut grid, dot(*from), dot(*to), color),
+            Shape::Arrow { from, to } => {
+                let (from, to) = (dot(*from), dot(*to));
+                braille_line(&mut grid, from, to, color);
+                for wing in arrow_wings(from, to, ARROW_WING) {
+                    braille_line(&mut grid, to, wing, color);
+                }
+            }
+            Shape::Text { at, text } => {
+                grid.put_str(at.x, at.y, text, color, Color::Reset, false);
+            }
+        }
+    }
+    grid
+}
+
+fn draw_box(grid: &mut Grid, at: Point, width: u16, height: u16, color: Color) {
+    let (left, top) = (at.x, at.y);
+    let right = left.saturating_add(width - 1);
+    let bottom = top.saturating_add(height - 1);
+    for x in left.saturating_add(1)..right {
+        grid.put_str(x, top, "─", color, Color::Reset, false);
+        grid.put_str(x, bottom, "─", color, Color::Reset, false);
+    }
+    for y in top.saturating_add(1)..bottom {
+        grid.put_str(left, y, "│", color, Color::Reset, false);
+        grid.put_str(right, y, "│", color, Color::Reset, false);
+    }
+    grid.put_str(left, top, "┌", color, Color::Reset, false);
+    grid.put_str(right, top, "┐", color, Color::Reset, false);
+    grid.put_str(left, bottom, "└", color, Color::Reset, false);
+    grid.put_str(right, bottom, "┘", color, Color::Reset, false);
+}
+
+// The dot in the middle of a cell
+fn dot(point: Point) -> (i32, i32) {
+    (
+        point.x as i32 * DOTS_X,
+        point.y as i32 * DOTS_Y + DOTS_Y / 2,
+    )
+}
+
+fn arrow_wings(from: (i32, i32), to: (i32, i32), length: f64) -> [(i32, i32); 2] {
+    let (dx, dy) = ((from.0 - to.0) as f64, (from.1 - to.1) as f64);
+    let norm = dx.hypot(dy).max(f64::EPSILON);
+    let (ux, uy) = (dx / norm * length, dy / norm * length);
+    let (sin, cos) = std::f64::consts::FRAC_PI_6.sin_cos();
+    [
+        (
+            to.0 + (ux * cos - uy * sin).round() as i32,
+            to.1 + (ux * sin + uy * cos).round() as i32,
+        ),
+        (
+            to.0 + (ux * cos + uy * sin).round() as i32,
+            to.1 + (uy * cos - ux * sin).round() as i32,
+        ),
+    ]
+}
+
+// Bresenham over braille dots. Dots land in cells that already hold braille
+// are merged in; anything else in the cell is replaced.
+fn braille_line(grid: &mut Grid, from: (i32, i32), to: (i32, i32), color: Color) {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y, mut error) = (from.0, from.1, dx + dy);
+    loop {
+        set_dot(grid, x, y, color);
+        if (x, y) == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += sx;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+fn set_dot(grid: &mut Grid, x: i32, y: i32, color: Color) {
+    const BITS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    if x < 0 || y < 0 {
+        return;
+    }
+    let (cell_x, cell_y) = ((x / DOTS_X) as u16, (y / DOTS_Y) as u16);
+    let Some(cell) = grid.get(cell_x, cell_y) else {
+        return;
+    };
+    let existing = match cell.ch as u32 {
+        bits @ 0x2800..=0x28ff => bits - 0x2800,
+        _ => 0,
+    };
+    let bits = existing | BITS[(x % DOTS_X) as usize][(y % DOTS_Y) as usize];
+    let ch = char::from_u32(0x2800 + bits).unwrap_or('⣿');
+    grid.put_str(cell_x, cell_y, &ch.to_string(), color, Color::Reset, false);
+}
+
+fn cell_center(point: Point) -> (i32, i32) {
+    (
+        point.x as i32 * CELL_W + CELL_W / 2,
+        point.y as i32 * CELL_H + CELL_H / 2,
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn to_svg(elements: &[Element]) -> String {
+    let (mut max_x, mut max_y) = (CELL_W * 20, CELL_H * 10);
+    let mut body = String::new();
+    for element in elements {
+        let (r, g, b) = user_rgb(&element.author);
+        let stroke = format!("#{:02x}{:02x}{:02x}", r, g, b);
+        match &element.shape {
+            Shape::Box { at, width, height } => {
+                let (x, y) = cell_center(*at);
+                let (w, h) = ((*width as i32 - 1) * CELL_W, (*height as i32 - 1) * CELL_H);
+                let _ = writeln!(
+                    body,
+                    r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-width="2"/>"#,
+                    x, y, w, h, stroke
+                );
+                (max_x, max_y) = (max_x.max(x + w + CELL_W), max_y.max(y + h + CELL_H));
+            }
+            Shape::Line { from, to } | Shape::Arrow { from, to } => {
+                let (start, end) = (cell_center(*from), cell_center(*to));
+                let _ = writeln!(
+                    body,
+                    r#"  <line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="2"/>"#,
+                    start.0, start.1, end.0, end.1, stroke
+                );
+                if matches!(element.shape, Shape::Arrow { .. }) {
+                    let [left, right] = arrow_wings(start, end, CELL_W as f64);
+                    let _ = writeln!(
+                        body,
+                        r#"  <polygon points="{},{} {},{} {},{}" fill="{}"/>"#,
+                        end.0, end.1, left.0, left.1, right.0, right.1, stroke
+                    );
+                }
+                max_x = max_x.max(start.0.max(end.0) + CELL_W);
+                max_y = max_y.max(start.1.max(end.1) + CELL_H);
+            }
+            Shape::Text { at, text } => {
+                let (x, y) = (at.x as i32 * CELL_W, at.y as i32 * CELL_H + CELL_H * 3 / 4);
+                let _ = writeln!(
+                    body,
+                    r#"  <text x="{}" y="{}" font-family="monospace" font-size="16" fill="{}">{}</text>"#,
+                    x,
+                    y,
+                    stroke,
+                    escape_xml(text)
+                );
+                max_x = max_x.max(x + text.chars().count() as i32 * CELL_W + CELL_W);
+                max_y = max_y.max(y + CELL_H);
+            }
+        }
+    }
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            "\n",
+            r#"  <rect width="{w}" height="{h}" fill="white"/>"#,
+            "\n{body}</svg>\n"
+        ),
+        w = max_x,
+        h = max_y,
+        body = body
+    )
+}
+
+fn to_png(svg: &str) -> Result<Vec<u8>, WarpError> {
+    use resvg::{tiny_skia, usvg};
+
+    let mut fonts = usvg::fontdb::Database::new();
+    fonts.load_system_fonts();
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default(), &fonts)
+        .map_err(|e| WarpError::Validation(format!("Cannot render whiteboard: {}", e)))?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| WarpError::Validation("The whiteboard is empty".to_string()))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap
+        .encode_png()
+        .map_err(|e| WarpError::Validation(format!("Cannot encode whiteboard image: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: u16, y: u16) -> Point {
+        Point { x, y }
+    }
+
+    fn text(grid: &Grid, y: u16) -> String {
+        grid.row(y)
+            .iter()
+            .map(|cell| cell.ch)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_sync_and_undo() {
+        let alice = WhiteboardManager::new().await.unwrap();
+        let bob = WhiteboardManager::new().await.unwrap();
+
+        let first = alice
+            .draw(
+                "s1",
+                "alice",
+                Shape::Box {
+                    at: at(0, 0),
+                    width: 4,
+                    height: 3,
+                },
+            )
+            .await
+            .unwrap();
+        let second = bob
+            .draw(
+                "s1",
+                "bob",
+                Shape::Text {
+                    at: at(1, 1),
+                    text: "hi".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(bob.apply(&first).await.unwrap());
+        assert!(alice.apply(&second).await.unwrap());
+        // Delivered twice
+        assert!(!alice.apply(&second).await.unwrap());
+        assert_eq!(alice.elements("s1").await, bob.elements("s1").await);
+
+        let grid = alice.render("s1", 6, 3).await;
+        assert_eq!(text(&grid, 0), "┌──┐");
+        assert_eq!(text(&grid, 1), "│hi│");
+        assert_eq!(grid.get(1, 1).unwrap().fg, user_color("bob"));
+
+        // Bob can't remove Alice's box, only undo his own text
+        let forged = WhiteboardUpdate {
+            session_id: "s1".to_string(),
+            user_id: "bob".to_string(),
+            op: BoardOp::Remove {
+                element_id: alice.elements("s1").await[0].id.clone(),
+            },
+        };
+        assert!(alice.apply(&forged).await.is_err());
+        let undo = bob.undo("s1", "bob").await.unwrap().unwrap();
+        alice.apply(&undo).await.unwrap();
+        assert_eq!(text(&alice.render("s1", 6, 3).await, 1), "│  │");
+        assert!(bob.undo("s1", "bob").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_braille_lines_merge() {
+        let element = |id: &str, shape| Element {
+            id: id.to_string(),
+            author: "a".to_string(),
+            clock: 1,
+            shape,
+        };
+        let grid = render(
+            &[
+                element(
+                    "1",
+                    Shape::Line {
+                        from: at(0, 0),
+                        to: at(2, 0),
+                    },
+                ),
+                element(
+                    "2",
+                    Shape::Arrow {
+                        from: at(0, 3),
+                        to: at(0, 1),
+                    },
+                ),
+            ],
+            3,
+            4,
+        );
+        // Row 2 of the dot matrix in every cell: dots 3 and 6
+        assert_eq!(text(&grid, 0), "⠤⠤⠄");
+        assert!(grid
+            .row(1)
+            .iter()
+            .all(|cell| ('\u{2800}'..='\u{28ff}').contains(&cell.ch) || cell.ch == ' '));
+    }
+
+    #[test]
+    fn test_svg_export() {
+        let element = Element {
+            id: "1".to_string(),
+            author: "a".to_string(),
+            clock: 1,
+            shape: Shape::Text {
+                at: at(0, 0),
+                text: "<b>&".to_string(),
+            },
+        };
+        let svg = to_svg(&[element]);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(">&lt;b&gt;&amp;</text>"));
+        assert!(Shape::Box {
+            at: at(0, 0),
+            width: 1,
+            height: 5
+        }
+        .validate()
+        .is_err());
+    }
+}
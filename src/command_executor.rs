@@ -1,6 +1,8 @@
 use std::env;
 use std::process::{Command, Stdio};
 
+use crate::i18n;
+
 pub struct CommandExecutor {
     current_dir: std::path::PathBuf,
 }
@@ -68,9 +70,12 @@ Press Ctrl+C to exit."#
 
         if new_path.exists() && new_path.is_dir() {
             env::set_current_dir(&new_path)?;
-            Ok(format!("Changed directory to: {}", new_path.display()))
+            Ok(i18n::t_args(
+                "command.dir_changed",
+                &[("path", &new_path.display().to_string())],
+            ))
         } else {
-            Ok(format!("Directory not found: {}", path))
+            Ok(i18n::t_args("command.dir_not_found", &[("path", path)]))
         }
     }
 
@@ -99,7 +104,7 @@ Press Ctrl+C to exit."#
                     Ok(stdout.to_string())
                 }
             }
-            Err(e) => Ok(format!("Command failed: {}", e)),
+            Err(e) => Ok(i18n::t_args("command.failed", &[("error", &e.to_string())])),
         }
     }
 }
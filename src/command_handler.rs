@@ -54,7 +54,7 @@ impl Command {
                 Ok(())
             }
             Command::Unknown(cmd) => {
-                Err(WarpError::CommandError(format!("Unknown command: {}", cmd)))
+                Err(WarpError::CommandExecution(format!("Unknown command: {}", cmd)))
             }
         }
     }
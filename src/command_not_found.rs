@@ -0,0 +1,239 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PackageManager {
+    Apt,
+    Brew,
+    Dnf,
+    Cargo,
+    Npm,
+}
+
+use PackageManager::*;
+
+impl PackageManager {
+    fn binary(self) -> &'static str {
+        match self {
+            Apt => "apt",
+            Brew => "brew",
+            Dnf => "dnf",
+            Cargo => "cargo",
+            Npm => "npm",
+        }
+    }
+
+    pub fn install_command(self, package: &str) -> String {
+        match self {
+            Apt => format!("sudo apt install {}", package),
+            Brew => format!("brew install {}", package),
+            Dnf => format!("sudo dnf install {}", package),
+            Cargo => format!("cargo install {}", package),
+            Npm => format!("npm install -g {}", package),
+        }
+    }
+
+    // The system package manager comes first; language ones are a fallback
+    fn preference() -> &'static [PackageManager] {
+        if cfg!(target_os = "macos") {
+            &[Brew, Cargo, Npm]
+        } else {
+            &[Apt, Dnf, Brew, Cargo, Npm]
+        }
+    }
+}
+
+// Binaries whose package names are known, per package manager
+const PACKAGES: &[(&str, &[(PackageManager, &str)])] = &[
+    (
+        "bat",
+        &[(Apt, "bat"), (Brew, "bat"), (Dnf, "bat"), (Cargo, "bat")],
+    ),
+    ("cargo", &[(Apt, "cargo"), (Brew, "rust"), (Dnf, "cargo")]),
+    ("curl", &[(Apt, "curl"), (Brew, "curl"), (Dnf, "curl")]),
+    (
+        "delta",
+        &[
+            (Brew, "git-delta"),
+            (Dnf, "git-delta"),
+            (Cargo, "git-delta"),
+        ],
+    ),
+    ("eza", &[(Apt, "eza"), (Brew, "eza"), (Cargo, "eza")]),
+    (
+        "fd",
+        &[
+            (Apt, "fd-find"),
+            (Brew, "fd"),
+            (Dnf, "fd-find"),
+            (Cargo, "fd-find"),
+        ],
+    ),
+    (
+        "ffmpeg",
+        &[(Apt, "ffmpeg"), (Brew, "ffmpeg"), (Dnf, "ffmpeg")],
+    ),
+    ("fzf", &[(Apt, "fzf"), (Brew, "fzf"), (Dnf, "fzf")]),
+    ("gh", &[(Apt, "gh"), (Brew, "gh"), (Dnf, "gh")]),
+    ("git", &[(Apt, "git"), (Brew, "git"), (Dnf, "git")]),
+    ("go", &[(Apt, "golang-go"), (Brew, "go"), (Dnf, "golang")]),
+    ("htop", &[(Apt, "htop"), (Brew, "htop"), (Dnf, "htop")]),
+    (
+        "http",
+        &[(Apt, "httpie"), (Brew, "httpie"), (Dnf, "httpie")],
+    ),
+    (
+        "hyperfine",
+        &[
+            (Apt, "hyperfine"),
+            (Brew, "hyperfine"),
+            (Cargo, "hyperfine"),
+        ],
+    ),
+    ("jq", &[(Apt, "jq"), (Brew, "jq"), (Dnf, "jq")]),
+    ("just", &[(Brew, "just"), (Cargo, "just")]),
+    ("make", &[(Apt, "make"), (Brew, "make"), (Dnf, "make")]),
+    ("node", &[(Apt, "nodejs"), (Brew, "node"), (Dnf, "nodejs")]),
+    ("npm", &[(Apt, "npm"), (Brew, "node"), (Dnf, "npm")]),
+    (
+        "nvim",
+        &[(Apt, "neovim"), (Brew, "neovim"), (Dnf, "neovim")],
+    ),
+    ("pnpm", &[(Brew, "pnpm"), (Npm, "pnpm")]),
+    ("prettier", &[(Npm, "prettier")]),
+    (
+        "python3",
+        &[(Apt, "python3"), (Brew, "python"), (Dnf, "python3")],
+    ),
+    (
+        "rg",
+        &[
+            (Apt, "ripgrep"),
+            (Brew, "ripgrep"),
+            (Dnf, "ripgrep"),
+            (Cargo, "ripgrep"),
+        ],
+    ),
+    (
+        "shellcheck",
+        &[
+            (Apt, "shellcheck"),
+            (Brew, "shellcheck"),
+            (Dnf, "ShellCheck"),
+        ],
+    ),
+    ("tmux", &[(Apt, "tmux"), (Brew, "tmux"), (Dnf, "tmux")]),
+    ("tokei", &[(Brew, "tokei"), (Cargo, "tokei")]),
+    ("tree", &[(Apt, "tree"), (Brew, "tree"), (Dnf, "tree")]),
+    ("tsc", &[(Npm, "typescript")]),
+    ("unzip", &[(Apt, "unzip"), (Brew, "unzip"), (Dnf, "unzip")]),
+    ("wget", &[(Apt, "wget"), (Brew, "wget"), (Dnf, "wget")]),
+    ("yarn", &[(Brew, "yarn"), (Npm, "yarn")]),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallSuggestion {
+    pub manager: PackageManager,
+    pub package: String,
+    pub command: String,
+}
+
+fn not_found_patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            r"zsh: command not found: ([\w.+-]+)",
+            r"(?m)^(?:bash: )?(?:line \d+: )?([\w.+-]+): command not found",
+            r"fish: Unknown command:? '?([\w.+-]+)'?",
+            r"(?m)^(?:sh|dash): \d+: ([\w.+-]+): not found",
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("static pattern"))
+        .collect()
+    })
+}
+
+// The missing command named in a shell's "command not found" message
+pub fn detect(output: &str) -> Option<String> {
+    not_found_patterns()
+        .iter()
+        .find_map(|pattern| pattern.captures(output))
+        .map(|captures| captures[1].to_string())
+}
+
+// Ways to install `binary` with the package managers on this machine, most
+// preferred first
+pub fn install_suggestions(binary: &str) -> Vec<InstallSuggestion> {
+    suggestions_for(binary, |manager| find_in_path(manager.binary()).is_some())
+}
+
+fn suggestions_for(
+    binary: &str,
+    available: impl Fn(PackageManager) -> bool,
+) -> Vec<InstallSuggestion> {
+    let Some((_, packages)) = PACKAGES.iter().find(|(name, _)| *name == binary) else {
+        return Vec::new();
+    };
+    PackageManager::preference()
+        .iter()
+        .filter(|manager| available(**manager))
+        .filter_map(|manager| {
+            packages
+                .iter()
+                .find(|(m, _)| m == manager)
+                .map(|(manager, package)| InstallSuggestion {
+                    manager: *manager,
+                    package: package.to_string(),
+                    command: manager.install_command(package),
+                })
+        })
+        .collect()
+}
+
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+pub(crate) fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_shell_messages() {
+        assert_eq!(
+            detect("zsh: command not found: rg\r\n").as_deref(),
+            Some("rg")
+        );
+        assert_eq!(
+            detect("bash: jq: command not found\n").as_deref(),
+            Some("jq")
+        );
+        assert_eq!(detect("fish: Unknown command: fd\n").as_deref(), Some("fd"));
+        assert_eq!(detect("sh: 1: tree: not found\n").as_deref(), Some("tree"));
+        assert_eq!(detect("error: file not found\n"), None);
+    }
+
+    #[test]
+    fn test_suggestions_follow_available_managers() {
+        let suggestions = suggestions_for("fd", |manager| matches!(manager, Brew | Cargo));
+        let commands: Vec<&str> = suggestions.iter().map(|s| s.command.as_str()).collect();
+        assert_eq!(commands, vec!["brew install fd", "cargo install fd-find"]);
+        assert!(suggestions_for("no-such-tool", |_| true).is_empty());
+    }
+}
@@ -1,14 +1,119 @@
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use tokio::sync::Mutex;
 
 use crate::{config::Config, error::WarpError};
 
+pub mod lsp;
+
+const BUILTIN_SPECS: &str = include_str!("../completions/builtin.json");
+
+// What a command accepts: flags on their own, flags that consume a value
+// (`-m msg`, `--depth 1`), and subcommands with their own flags
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompletionSpec {
+    pub name: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub arg_options: Vec<String>,
+    #[serde(default)]
+    pub subcommands: Vec<CompletionSpec>,
+    // Set on imported specs whose option list may be missing entries, so
+    // flags they don't list aren't reported as unknown
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+impl CompletionSpec {
+    pub fn subcommand(&self, name: &str) -> Option<&CompletionSpec> {
+        self.subcommands.iter().find(|spec| spec.name == name)
+    }
+
+    pub fn knows_option(&self, name: &str) -> bool {
+        self.options
+            .iter()
+            .chain(&self.arg_options)
+            .any(|option| option == name)
+    }
+
+    pub fn takes_arg(&self, name: &str) -> bool {
+        self.arg_options.iter().any(|option| option == name)
+    }
+}
+
+// Shared between the completion engine and the linter. Specs imported from
+// the system's shell completions are added in the background after startup.
+#[derive(Debug, Default)]
+pub struct SpecRegistry {
+    specs: RwLock<HashMap<String, Arc<CompletionSpec>>>,
+}
+
+impl SpecRegistry {
+    pub fn builtin() -> Self {
+        let specs: Vec<CompletionSpec> =
+            serde_json::from_str(BUILTIN_SPECS).expect("built-in completion specs must parse");
+        let registry = Self::default();
+        for spec in specs {
+            registry.insert(spec);
+        }
+        registry
+    }
+
+    pub fn insert(&self, spec: CompletionSpec) {
+        self.specs
+            .write()
+            .unwrap()
+            .insert(spec.name.clone(), Arc::new(spec));
+    }
+
+    pub fn get(&self, command: &str) -> Option<Arc<CompletionSpec>> {
+        self.specs.read().unwrap().get(command).cloned()
+    }
+
+    pub fn contains(&self, command: &str) -> bool {
+        self.specs.read().unwrap().contains_key(command)
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 pub struct CompletionEngine {
     config: Arc<Mutex<Config>>,
+    specs: Arc<SpecRegistry>,
 }
 
 impl CompletionEngine {
     pub async fn new(config: Arc<Mutex<Config>>) -> Result<Self, WarpError> {
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            specs: Arc::new(SpecRegistry::builtin()),
+        })
+    }
+
+    pub fn specs(&self) -> Arc<SpecRegistry> {
+        self.specs.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_specs_parse() {
+        let registry = SpecRegistry::builtin();
+        let git = registry.get("git").unwrap();
+        let commit = git.subcommand("commit").unwrap();
+        assert!(commit.takes_arg("-m"));
+        assert!(commit.knows_option("--amend"));
+        assert!(!commit.knows_option("--amned"));
     }
 }
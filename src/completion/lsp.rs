@@ -0,0 +1,623 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::config::LanguageServerConfig;
+use crate::error::WarpError;
+use crate::i18n;
+use crate::lint::{Diagnostic, Severity};
+
+// The script being typed at the prompt
+pub const PROMPT_URI: &str = "untitled:prompt";
+const NOTEBOOK_URI: &str = "untitled:notebook/";
+// Servers that take longer to start than this are given up on
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(2);
+
+// A notebook's command cell, by index; only one notebook is open at a time
+pub fn notebook_uri(cell: usize) -> String {
+    format!("{}{}", NOTEBOOK_URI, cell)
+}
+
+pub fn notebook_cell(uri: &str) -> Option<usize> {
+    uri.strip_prefix(NOTEBOOK_URI)?.parse().ok()
+}
+
+// The LSP language id of a script: from its shebang, else the shell's
+pub fn language_of(text: &str) -> String {
+    let Some(shebang) = text.lines().next().and_then(|line| line.strip_prefix("#!")) else {
+        return "shellscript".to_string();
+    };
+    let mut words = shebang.split_whitespace();
+    let mut interpreter = words.next().unwrap_or("");
+    // `#!/usr/bin/env -S python3 -u`
+    if interpreter.ends_with("/env") || interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-')).unwrap_or("");
+    }
+    let name = interpreter.rsplit('/').next().unwrap_or(interpreter);
+    let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match name {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "" => "shellscript",
+        "node" | "deno" | "bun" => "javascript",
+        other => other,
+    }
+    .to_string()
+}
+
+// Frames are a Content-Length header, a blank line and a JSON body
+pub fn encode(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut frame = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    frame.extend_from_slice(body.as_bytes());
+    frame
+}
+
+// Collects the server's output until a whole message is in
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn next_message(&mut self) -> Result<Option<Value>, WarpError> {
+        let Some(end) = self
+            .buffer
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+        else {
+            return Ok(None);
+        };
+        let header = String::from_utf8_lossy(&self.buffer[..end]).into_owned();
+        let length = header
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim()
+                    .eq_ignore_ascii_case("content-length")
+                    .then(|| value.trim().parse::<usize>().ok())?
+            })
+            .ok_or_else(|| {
+                WarpError::command_err(format!(
+                    "Language server sent a header without a length: {}",
+                    header
+                ))
+            })?;
+        let start = end + 4;
+        if self.buffer.len() < start + length {
+            return Ok(None);
+        }
+        let frame: Vec<u8> = self.buffer.drain(..start + length).collect();
+        Ok(Some(serde_json::from_slice(&frame[start..])?))
+    }
+}
+
+// Line and UTF-16 column of a byte offset, the way LSP counts positions
+pub fn position(text: &str, offset: usize) -> (u32, u32) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or("")
+        .encode_utf16()
+        .count();
+    (line as u32, column as u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub insert: String,
+}
+
+// A completion response is the items or a list holding them. Snippets
+// are inserted as their label; the prompt has no placeholders to tab to.
+pub fn completions(result: &Value) -> Vec<Completion> {
+    let items = result.get("items").unwrap_or(result);
+    items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let label = item.get("label")?.as_str()?.to_string();
+            let snippet = item.get("insertTextFormat").and_then(Value::as_u64) == Some(2);
+            let insert = match snippet {
+                true => None,
+                false => item
+                    .pointer("/textEdit/newText")
+                    .or_else(|| item.get("insertText"))
+                    .and_then(Value::as_str),
+            };
+            Some(Completion {
+                insert: insert.unwrap_or(&label).to_string(),
+                label,
+            })
+        })
+        .collect()
+}
+
+// What Tab adds at the cursor: the rest of the only match, or as much as
+// all matches share, and the matches when there is more than one
+pub fn completion_edit(before: &str, items: &[Completion]) -> (String, Vec<String>) {
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '-')
+        .last()
+        .map_or(before.len(), |(i, _)| i);
+    let word = &before[start..];
+    let mut matches: Vec<&Completion> = items
+        .iter()
+        .filter(|item| item.insert.starts_with(word))
+        .collect();
+    matches.dedup_by(|a, b| a.insert == b.insert);
+    let Some(first) = matches.first() else {
+        return (String::new(), Vec::new());
+    };
+    let shared = matches[1..]
+        .iter()
+        .fold(first.insert.as_str(), |shared, item| {
+            let end = shared
+                .char_indices()
+                .zip(item.insert.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(shared.len().min(item.insert.len()), |((i, _), _)| i);
+            &shared[..end]
+        });
+    let labels = match matches.len() {
+        1 => Vec::new(),
+        _ => matches.iter().map(|item| item.label.clone()).collect(),
+    };
+    (shared[word.len()..].to_string(), labels)
+}
+
+// A publishDiagnostics notification, as the linter's diagnostics. Hints
+// are left out.
+pub fn published(params: &Value) -> Option<(String, Vec<Diagnostic>)> {
+    let uri = params.get("uri")?.as_str()?.to_string();
+    let diagnostics = params
+        .get("diagnostics")?
+        .as_array()?
+        .iter()
+        .filter_map(|diagnostic| {
+            let severity = match diagnostic.get("severity").and_then(Value::as_u64) {
+                Some(1) => Severity::Error,
+                Some(4) => return None,
+                _ => Severity::Warning,
+            };
+            let message = diagnostic.get("message")?.as_str()?;
+            let line = diagnostic
+                .pointer("/range/start/line")
+                .and_then(Value::as_u64)
+                .unwrap_or(0)
+                + 1;
+            Some(Diagnostic {
+                severity,
+                message: i18n::t_args(
+                    "lsp.diagnostic",
+                    &[
+                        ("line", &line.to_string()),
+                        ("message", message.lines().next().unwrap_or(message)),
+                    ],
+                ),
+            })
+        })
+        .collect();
+    Some((uri, diagnostics))
+}
+
+#[derive(Debug, Clone)]
+pub enum LspEvent {
+    Diagnostics {
+        uri: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Completions {
+        uri: String,
+        items: Vec<Completion>,
+    },
+    Failed {
+        language: String,
+        error: String,
+    },
+}
+
+// A script as the server sees it; `root` is where its server starts
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub uri: String,
+    pub language: String,
+    pub root: PathBuf,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum LspRequest {
+    Update(Document),
+    Complete(Document, usize), // byte offset of the cursor
+}
+
+// One running server. Requests wait for the response the reader task
+// routes back by id; requests from the server get an empty answer.
+pub struct LanguageClient {
+    language: String,
+    stdin: Mutex<ChildStdin>,
+    next_id: AtomicI64,
+    pending: Arc<Mutex<HashMap<i64, oneshot::Sender<Value>>>>,
+    versions: Mutex<HashMap<String, i64>>, // open documents
+    running: AtomicBool,
+    _child: Child, // killed when the client is dropped
+}
+
+impl LanguageClient {
+    pub async fn start(
+        language: &str,
+        config: &LanguageServerConfig,
+        root: &Path,
+        events: mpsc::UnboundedSender<LspEvent>,
+    ) -> Result<Arc<Self>, WarpError> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                WarpError::command_err(format!("Failed to start {}: {}", config.command, e))
+            })?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(WarpError::command_err(format!(
+                "{} has no stdio",
+                config.command
+            )));
+        };
+        let client = Arc::new(Self {
+            language: language.to_string(),
+            stdin: Mutex::new(stdin),
+            next_id: AtomicI64::new(1),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            versions: Mutex::new(HashMap::new()),
+            running: AtomicBool::new(true),
+            _child: child,
+        });
+        tokio::spawn(read_messages(stdout, Arc::downgrade(&client), events));
+
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": format!("file://{}", root.display()),
+            "capabilities": {
+                "textDocument": {
+                    "completion": { "completionItem": { "snippetSupport": false } },
+                    "publishDiagnostics": {},
+                },
+            },
+            "clientInfo": { "name": "warp" },
+        });
+        tokio::time::timeout(INITIALIZE_TIMEOUT, client.request("initialize", params))
+            .await
+            .map_err(|_| WarpError::command_err(i18n::t("lsp.timeout")))??;
+        client.notify("initialized", json!({})).await?;
+        Ok(client)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    async fn send(&self, message: &Value) -> Result<(), WarpError> {
+        let mut stdin = self.stdin.lock().await;
+        stdin.write_all(&encode(message)).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), WarpError> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, WarpError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().await.insert(id, sender);
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+        let response = receiver.await.map_err(|_| {
+            WarpError::command_err(format!("The {} language server exited", self.language))
+        })?;
+        if let Some(error) = response.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(WarpError::command_err(format!(
+                "{} failed: {}",
+                method, message
+            )));
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    // The whole text every time; scripts at the prompt are short
+    pub async fn update(&self, uri: &str, text: &str) -> Result<(), WarpError> {
+        let mut versions = self.versions.lock().await;
+        if let Some(version) = versions.get_mut(uri) {
+            *version += 1;
+            let params = json!({
+                "textDocument": { "uri": uri, "version": *version },
+                "contentChanges": [{ "text": text }],
+            });
+            return self.notify("textDocument/didChange", params).await;
+        }
+        versions.insert(uri.to_string(), 1);
+        let params = json!({
+            "textDocument": { "uri": uri, "languageId": self.language, "version": 1, "text": text },
+        });
+        self.notify("textDocument/didOpen", params).await
+    }
+
+    pub async fn complete(
+        &self,
+        uri: &str,
+        text: &str,
+        offset: usize,
+    ) -> Result<Vec<Completion>, WarpError> {
+        self.update(uri, text).await?;
+        let (line, character) = position(text, offset);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        });
+        Ok(completions(
+            &self.request("textDocument/completion", params).await?,
+        ))
+    }
+}
+
+async fn read_messages(
+    mut stdout: ChildStdout,
+    client: Weak<LanguageClient>,
+    events: mpsc::UnboundedSender<LspEvent>,
+) {
+    let mut decoder = FrameDecoder::default();
+    let mut buffer = [0; 8192];
+    'read: loop {
+        match stdout.read(&mut buffer).await {
+            Ok(n) if n > 0 => decoder.push(&buffer[..n]),
+            _ => break,
+        }
+        loop {
+            let message = match decoder.next_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("Language server output unreadable: {}", e);
+                    break 'read;
+                }
+            };
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+            let method = message.get("method").and_then(Value::as_str);
+            match (message.get("id"), method) {
+                (Some(id), None) => {
+                    let sender = match id.as_i64() {
+                        Some(id) => client.pending.lock().await.remove(&id),
+                        None => None,
+                    };
+                    if let Some(sender) = sender {
+                        let _ = sender.send(message);
+                    }
+                }
+                // workspace/configuration wants one entry per item asked about
+                (Some(id), Some(method)) => {
+                    let result = match method {
+                        "workspace/configuration" => {
+                            let items = message
+                                .pointer("/params/items")
+                                .and_then(Value::as_array)
+                                .map_or(0, Vec::len);
+                            Value::Array(vec![Value::Null; items])
+                        }
+                        _ => Value::Null,
+                    };
+                    let _ = client
+                        .send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+                        .await;
+                }
+                (None, Some("textDocument/publishDiagnostics")) => {
+                    if let Some((uri, diagnostics)) = message.get("params").and_then(published) {
+                        let _ = events.send(LspEvent::Diagnostics { uri, diagnostics });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some(client) = client.upgrade() {
+        client.running.store(false, Ordering::Relaxed);
+        client.pending.lock().await.clear();
+    }
+}
+
+// The configured servers by language id, each started the first time a
+// script in its language is edited. One that fails isn't tried again.
+pub struct LanguageServers {
+    config: BTreeMap<String, LanguageServerConfig>,
+    clients: HashMap<String, Arc<LanguageClient>>,
+    failed: HashSet<String>,
+    events: mpsc::UnboundedSender<LspEvent>,
+}
+
+impl LanguageServers {
+    pub fn new(
+        config: BTreeMap<String, LanguageServerConfig>,
+        events: mpsc::UnboundedSender<LspEvent>,
+    ) -> Self {
+        Self {
+            config,
+            clients: HashMap::new(),
+            failed: HashSet::new(),
+            events,
+        }
+    }
+
+    async fn client(
+        &mut self,
+        document: &Document,
+    ) -> Result<Option<Arc<LanguageClient>>, WarpError> {
+        let language = &document.language;
+        match self.clients.get(language) {
+            Some(client) if client.is_running() => return Ok(Some(client.clone())),
+            Some(_) => {
+                self.clients.remove(language);
+                self.failed.insert(language.clone());
+                return Err(WarpError::command_err(format!(
+                    "The {} language server exited",
+                    language
+                )));
+            }
+            None => {}
+        }
+        let Some(config) = self.config.get(language).filter(|config| config.enabled) else {
+            return Ok(None);
+        };
+        if self.failed.contains(language) {
+            return Ok(None);
+        }
+        match LanguageClient::start(language, config, &document.root, self.events.clone()).await {
+            Ok(client) => {
+                self.clients.insert(language.clone(), client.clone());
+                Ok(Some(client))
+            }
+            Err(e) => {
+                self.failed.insert(language.clone());
+                Err(e)
+            }
+        }
+    }
+
+    async fn handle(&mut self, request: LspRequest) -> Result<(), WarpError> {
+        match request {
+            LspRequest::Update(document) => {
+                if let Some(client) = self.client(&document).await? {
+                    client.update(&document.uri, &document.text).await?;
+                }
+            }
+            LspRequest::Complete(document, offset) => {
+                let Some(client) = self.client(&document).await? else {
+                    return Ok(());
+                };
+                let items = tokio::time::timeout(
+                    COMPLETION_TIMEOUT,
+                    client.complete(&document.uri, &document.text, offset),
+                )
+                .await
+                .map_err(|_| WarpError::command_err(i18n::t("lsp.timeout")))??;
+                let _ = self.events.send(LspEvent::Completions {
+                    uri: document.uri,
+                    items,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // One request at a time, so a document's changes reach its server in
+    // the order they were typed
+    pub async fn serve(mut self, mut requests: mpsc::UnboundedReceiver<LspRequest>) {
+        while let Some(request) = requests.recv().await {
+            let language = match &request {
+                LspRequest::Update(document) | LspRequest::Complete(document, _) => {
+                    document.language.clone()
+                }
+            };
+            if let Err(e) = self.handle(request).await {
+                let _ = self.events.send(LspEvent::Failed {
+                    language,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_split_across_reads() {
+        let first = json!({ "jsonrpc": "2.0", "id": 1, "result": null });
+        let second = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+        let mut bytes = encode(&first);
+        bytes.extend(encode(&second));
+        let mut decoder = FrameDecoder::default();
+        decoder.push(&bytes[..10]);
+        assert_eq!(decoder.next_message().unwrap(), None);
+        decoder.push(&bytes[10..]);
+        assert_eq!(decoder.next_message().unwrap(), Some(first));
+        assert_eq!(decoder.next_message().unwrap(), Some(second));
+        assert_eq!(decoder.next_message().unwrap(), None);
+    }
+
+    #[test]
+    fn test_languages_and_positions() {
+        assert_eq!(
+            language_of("for f in *; do\n  echo $f\ndone"),
+            "shellscript"
+        );
+        assert_eq!(
+            language_of("#!/usr/bin/env -S python3 -u\nprint(1)"),
+            "python"
+        );
+        assert_eq!(language_of("#!/usr/local/bin/node\n"), "javascript");
+        assert_eq!(position("ls\nécho $HO", 12), (1, 8));
+        assert_eq!(notebook_cell(&notebook_uri(4)), Some(4));
+        assert_eq!(notebook_cell(PROMPT_URI), None);
+    }
+
+    #[test]
+    fn test_completion_edit() {
+        let result = json!({ "isIncomplete": false, "items": [
+            { "label": "HOME", "insertText": "HOME" },
+            { "label": "HOSTNAME" },
+            { "label": "for loop", "insertText": "for ${1:x} in $2; do\n\t$0\ndone", "insertTextFormat": 2 },
+        ]});
+        let items = completions(&result);
+        assert_eq!(items[1].insert, "HOSTNAME");
+        assert_eq!(items[2].insert, "for loop");
+        assert_eq!(
+            completion_edit("echo $HO", &items),
+            (
+                "".to_string(),
+                vec!["HOME".to_string(), "HOSTNAME".to_string()]
+            )
+        );
+        assert_eq!(
+            completion_edit("echo $HOS", &items),
+            ("TNAME".to_string(), Vec::new())
+        );
+        assert_eq!(
+            completion_edit("echo $X", &items),
+            (String::new(), Vec::new())
+        );
+    }
+}
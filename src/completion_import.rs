@@ -0,0 +1,903 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::completion::{CompletionSpec, SpecRegistry};
+use crate::error::WarpError;
+
+// Searched in order; the first definition found for a command wins
+const FISH_DIRS: &[&str] = &[
+    "~/.config/fish/completions",
+    "/etc/fish/completions",
+    "/usr/local/share/fish/vendor_completions.d",
+    "/usr/share/fish/vendor_completions.d",
+    "/usr/share/fish/completions",
+];
+const NU_DIRS: &[&str] = &[
+    "~/.config/nushell/completions",
+    "~/.local/share/nushell/completions",
+    "/usr/share/nushell/completions",
+];
+const ZSH_DIRS: &[&str] = &[
+    "/usr/local/share/zsh/site-functions",
+    "/usr/share/zsh/site-functions",
+    "/usr/share/zsh/vendor-completions",
+    "/usr/share/zsh/functions/Completion/Unix",
+    "/usr/share/zsh/functions/Completion/Linux",
+    "/usr/share/zsh/functions/Completion/BSD",
+];
+const BASH_DIRS: &[&str] = &[
+    "~/.local/share/bash-completion/completions",
+    "/usr/local/share/bash-completion/completions",
+    "/usr/share/bash-completion/completions",
+    "/etc/bash_completion.d",
+];
+const BASH_COMPLETION_LIB: &str = "/usr/share/bash-completion/bash_completion";
+const BASH_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+// Subcommands probed for their own flags; the rest are listed without them
+const MAX_PROBED_SUBCOMMANDS: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpecSource {
+    Fish(PathBuf),
+    Nushell(PathBuf),
+    Zsh(PathBuf),
+    Bash(PathBuf),
+}
+
+impl SpecSource {
+    fn path(&self) -> &Path {
+        match self {
+            SpecSource::Fish(path)
+            | SpecSource::Nushell(path)
+            | SpecSource::Zsh(path)
+            | SpecSource::Bash(path) => path,
+        }
+    }
+}
+
+fn expand(dir: &str) -> Option<PathBuf> {
+    match dir.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map(|home| home.join(rest)),
+        None => Some(PathBuf::from(dir)),
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['-', '_', '.'])
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c))
+}
+
+fn fish_name(file: &str) -> Option<&str> {
+    file.strip_suffix(".fish")
+}
+
+// git-completions.nu, as laid out in nu_scripts, or git.nu
+fn nu_name(file: &str) -> Option<&str> {
+    let name = file.strip_suffix(".nu")?;
+    Some(name.strip_suffix("-completions").unwrap_or(name))
+}
+
+fn zsh_name(file: &str) -> Option<&str> {
+    file.strip_prefix('_')
+}
+
+fn bash_name(file: &str) -> Option<&str> {
+    Some(file.strip_suffix(".bash").unwrap_or(file))
+}
+
+// Every command with a completion definition on this system, preferring
+// the declarative formats (fish, nushell externs), then zsh, then bash
+// (which has to be run to be read)
+pub fn discover() -> HashMap<String, SpecSource> {
+    type Format = (
+        &'static [&'static str],
+        fn(&str) -> Option<&str>,
+        fn(PathBuf) -> SpecSource,
+    );
+    let formats: [Format; 4] = [
+        (FISH_DIRS, fish_name, SpecSource::Fish),
+        (NU_DIRS, nu_name, SpecSource::Nushell),
+        (ZSH_DIRS, zsh_name, SpecSource::Zsh),
+        (BASH_DIRS, bash_name, SpecSource::Bash),
+    ];
+    let mut found = HashMap::new();
+    for (dirs, command_name, source) in formats {
+        for dir in dirs.iter().filter_map(|dir| expand(dir)) {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let Some(name) = command_name(&file_name).filter(|name| valid_name(name)) else {
+                    continue;
+                };
+                found
+                    .entry(name.to_string())
+                    .or_insert_with(|| source(entry.path()));
+            }
+        }
+    }
+    found
+}
+
+// Splits a line into words the way a shell would for simple cases: quotes,
+// backslash escapes and trailing comments. Expansions are left as text.
+pub fn shell_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\'' => break,
+                        // fish allows \' and \\ inside single quotes
+                        '\\' if matches!(chars.peek(), Some('\'') | Some('\\')) => {
+                            word.push(chars.next().unwrap())
+                        }
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next());
+            }
+            '#' if !in_word => break,
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+fn add_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+fn add_option(spec: &mut CompletionSpec, name: String, takes_arg: bool) {
+    if takes_arg {
+        spec.options.retain(|option| *option != name);
+        add_unique(&mut spec.arg_options, name);
+    } else if !spec.arg_options.contains(&name) {
+        add_unique(&mut spec.options, name);
+    }
+}
+
+fn subcommand_mut<'a>(spec: &'a mut CompletionSpec, name: &str) -> &'a mut CompletionSpec {
+    let index = match spec.subcommands.iter().position(|sub| sub.name == name) {
+        Some(index) => index,
+        None => {
+            spec.subcommands.push(CompletionSpec {
+                name: name.to_string(),
+                ..Default::default()
+            });
+            spec.subcommands.len() - 1
+        }
+    };
+    &mut spec.subcommands[index]
+}
+
+// Specs read from elsewhere rarely list every flag a subcommand takes;
+// ones with none listed are marked so the linter stays quiet about them
+fn finish(mut spec: CompletionSpec) -> Option<CompletionSpec> {
+    for sub in &mut spec.subcommands {
+        sub.incomplete = sub.options.is_empty() && sub.arg_options.is_empty();
+    }
+    spec.incomplete = spec.options.is_empty() && spec.arg_options.is_empty();
+    (!spec.incomplete || !spec.subcommands.is_empty()).then_some(spec)
+}
+
+#[derive(Default)]
+struct FishEntry {
+    command: Option<String>,
+    condition: String,
+    options: Vec<String>,
+    arguments: Option<String>,
+    takes_arg: bool,
+}
+
+impl FishEntry {
+    fn parse(words: &[String]) -> Self {
+        let mut entry = Self::default();
+        let mut iter = words.iter();
+        while let Some(word) = iter.next() {
+            if let Some(long) = word.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let takes_value = [
+                    "command",
+                    "condition",
+                    "short-option",
+                    "long-option",
+                    "old-option",
+                    "arguments",
+                    "description",
+                    "wraps",
+                    "path",
+                ]
+                .contains(&name);
+                let value = if takes_value {
+                    inline.or_else(|| iter.next().cloned()).unwrap_or_default()
+                } else {
+                    String::new()
+                };
+                entry.apply(name, value);
+            } else if let Some(cluster) = word.strip_prefix('-') {
+                for (i, c) in cluster.char_indices() {
+                    let name = match c {
+                        'c' => "command",
+                        'n' => "condition",
+                        's' => "short-option",
+                        'l' => "long-option",
+                        'o' => "old-option",
+                        'a' => "arguments",
+                        'd' => "description",
+                        'w' => "wraps",
+                        'p' => "path",
+                        'r' => "require-parameter",
+                        'x' => "exclusive",
+                        _ => continue,
+                    };
+                    if "cnsloadwp".contains(c) {
+                        let rest = &cluster[i + 1..];
+                        let value = if rest.is_empty() {
+                            iter.next().cloned().unwrap_or_default()
+                        } else {
+                            rest.to_string()
+                        };
+                        entry.apply(name, value);
+                        break;
+                    }
+                    entry.apply(name, String::new());
+                }
+            } else if entry.command.is_none() {
+                // `complete git -s v` names the command positionally
+                entry.command = Some(word.clone());
+            }
+        }
+        entry
+    }
+
+    fn apply(&mut self, name: &str, value: String) {
+        match name {
+            "command" => self.command = Some(value),
+            "condition" => self.condition = value,
+            "short-option" => self.options.push(format!("-{}", value)),
+            "long-option" => self.options.push(format!("--{}", value)),
+            "old-option" => self.options.push(format!("-{}", value)),
+            "arguments" => self.arguments = Some(value),
+            "require-parameter" | "exclusive" => self.takes_arg = true,
+            _ => {}
+        }
+    }
+}
+
+fn seen_subcommand_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(not\s+)?__fish_seen_subcommand_from((?:\s+[\w.-]+)+)")
+            .expect("static pattern")
+    })
+}
+
+pub fn parse_fish(command: &str, script: &str) -> Option<CompletionSpec> {
+    let mut spec = CompletionSpec {
+        name: command.to_string(),
+        ..Default::default()
+    };
+    let script = script.replace("\\\n", " ");
+    for line in script.lines() {
+        let words = shell_words(line.trim());
+        if words.first().map(String::as_str) != Some("complete") {
+            continue;
+        }
+        let entry = FishEntry::parse(&words[1..]);
+        if entry.command.as_deref() != Some(command) {
+            continue;
+        }
+
+        // Completions offered where a subcommand is expected, vs ones that
+        // apply once a particular subcommand has been typed
+        let seen = seen_subcommand_pattern()
+            .captures(&entry.condition)
+            .filter(|captures| captures.get(1).is_none())
+            .map(|captures| {
+                captures[2]
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            });
+        let at_command_position = entry.condition.contains("__fish_use_subcommand")
+            || entry.condition.contains("needs_command")
+            || seen_subcommand_pattern()
+                .captures(&entry.condition)
+                .is_some_and(|captures| captures.get(1).is_some());
+
+        match seen {
+            Some(subcommands) => {
+                for name in subcommands.iter().filter(|name| valid_name(name)) {
+                    let sub = subcommand_mut(&mut spec, name);
+                    for option in &entry.options {
+                        add_option(sub, option.clone(), entry.takes_arg);
+                    }
+                }
+            }
+            None => {
+                for option in &entry.options {
+                    add_option(&mut spec, option.clone(), entry.takes_arg);
+                }
+                if at_command_position && entry.options.is_empty() {
+                    let arguments = entry.arguments.as_deref().unwrap_or_default();
+                    // Command substitutions and variables can't be read statically
+                    if !arguments.contains(['(', '$']) {
+                        for name in arguments.split_whitespace().filter(|name| valid_name(name)) {
+                            subcommand_mut(&mut spec, name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    finish(spec)
+}
+
+fn zsh_option_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // An _arguments spec after its exclusion list: -x[desc], --foo=[desc]:msg:action,
+    // {-a,--all}[desc], '*-v[verbose]'
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"^(?:\([^)]*\))?[*!]?(\{[^}]*\}|[-+]{1,2}[A-Za-z0-9][\w-]*)([=+-]?)(\[[^\]]*\])?(:)?",
+        )
+        .expect("static pattern")
+    })
+}
+
+fn zsh_array_start() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // commands=( ... ), local -a subcmds; subcmds=( ... )
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^\s*(?:local\s+(?:-a\s+)?)?\w*(?:commands|cmds)\w*=\(")
+            .expect("static pattern")
+    })
+}
+
+// Best effort: options from _arguments specs, and subcommands from arrays of
+// 'name:description' entries. Options are all attributed to the command
+// itself, since the script decides at runtime which belong to subcommands.
+pub fn parse_zsh(command: &str, script: &str) -> Option<CompletionSpec> {
+    let mut spec = CompletionSpec {
+        name: command.to_string(),
+        ..Default::default()
+    };
+    let mut in_array = false;
+    for line in script.lines() {
+        if zsh_array_start().is_match(line) {
+            in_array = true;
+        }
+        for word in shell_words(line.trim()) {
+            if in_array {
+                let name = word
+                    .trim_start_matches("commands=(")
+                    .split(':')
+                    .next()
+                    .unwrap_or_default();
+                if word.contains(':') && valid_name(name) {
+                    subcommand_mut(&mut spec, name);
+                }
+                continue;
+            }
+            let Some(captures) = zsh_option_pattern().captures(&word) else {
+                continue;
+            };
+            let described = captures.get(3).is_some() || captures.get(4).is_some();
+            if !described && captures[2].is_empty() {
+                // A bare -x is as likely to be code as an option spec
+                continue;
+            }
+            let takes_arg = matches!(&captures[2], "=" | "+") || captures.get(4).is_some();
+            let names = &captures[1];
+            let names: Vec<&str> = match names.strip_prefix('{').and_then(|n| n.strip_suffix('}')) {
+                Some(list) => list.split(',').collect(),
+                None => vec![names],
+            };
+            for name in names.into_iter().filter(|name| name.starts_with('-')) {
+                // {-g+,--glob=} carries the argument style on each name
+                let name = name.trim_end_matches(['=', '+']);
+                add_option(&mut spec, name.to_string(), takes_arg);
+            }
+        }
+        if in_array && line.trim_end().ends_with(')') {
+            in_array = false;
+        }
+    }
+    finish(spec)
+}
+
+fn nu_extern_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // export extern "git push" [ ... ], extern git [ ... ]
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?m)^\s*(?:export\s+)?extern\s+(?:"([^"]+)"|'([^']+)'|([\w.+-]+))\s*\["#)
+            .expect("static pattern")
+    })
+}
+
+fn nu_flag_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // --force(-f), --repo: string, -n, --depth(-d): int = 1
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^(?:(--[\w-]+)(?:\s*\(\s*(-\w)\s*\))?|(-\w))\s*(:)?").expect("static pattern")
+    })
+}
+
+// The signature between an extern's brackets, up to the matching `]`.
+// Descriptions in comments may hold brackets and apostrophes.
+fn nu_signature(rest: &str) -> &str {
+    let mut depth = 1;
+    let mut quote = None;
+    let mut comment = false;
+    for (index, c) in rest.char_indices() {
+        match (quote, c) {
+            _ if comment => comment = c != '\n',
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '#') => comment = true,
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return &rest[..index];
+                }
+            }
+            _ => {}
+        }
+    }
+    rest
+}
+
+// Nushell describes external commands with `extern` signatures: flags with
+// a type take a value, and "cmd sub" names a subcommand
+pub fn parse_nushell(command: &str, script: &str) -> Option<CompletionSpec> {
+    let mut spec = CompletionSpec {
+        name: command.to_string(),
+        ..Default::default()
+    };
+    for captures in nu_extern_pattern().captures_iter(script) {
+        let name = captures
+            .get(1)
+            .or(captures.get(2))
+            .or(captures.get(3))
+            .map_or("", |m| m.as_str());
+        let words: Vec<&str> = name.split_whitespace().collect();
+        let target = match words.as_slice() {
+            [name] if *name == command => &mut spec,
+            [name, sub] if *name == command && valid_name(sub) => subcommand_mut(&mut spec, sub),
+            _ => continue,
+        };
+        let body = nu_signature(&script[captures.get(0).map_or(0, |m| m.end())..]);
+        for line in body.lines() {
+            // Parameters are separated by newlines or commas; # starts a description
+            let line = line.split('#').next().unwrap_or_default();
+            for param in line.split(',').map(str::trim) {
+                let Some(flag) = nu_flag_pattern().captures(param) else {
+                    continue;
+                };
+                let takes_arg = flag.get(4).is_some();
+                for name in [flag.get(1), flag.get(2), flag.get(3)]
+                    .into_iter()
+                    .flatten()
+                {
+                    add_option(target, name.as_str().to_string(), takes_arg);
+                }
+            }
+        }
+    }
+    finish(spec)
+}
+
+// Sources the script in bash and calls its completion function as if the
+// user had pressed Tab after `command -` and `command `, then after `sub -`
+// for each subcommand found
+const BASH_PROBE: &str = r##"
+[ -f "$3" ] && . "$3" >/dev/null 2>&1
+. "$1" >/dev/null 2>&1
+spec=$(complete -p "$2" 2>/dev/null) || exit 0
+func=$(printf '%s\n' "$spec" | sed -n 's/.*-F \([^ ]*\).*/\1/p')
+[ -n "$func" ] || exit 0
+probe() {
+    COMP_WORDS=("$@")
+    COMP_CWORD=$(( ${#COMP_WORDS[@]} - 1 ))
+    COMP_LINE="$*"
+    COMP_POINT=${#COMP_LINE}
+    COMPREPLY=()
+    "$func" "$1" "${COMP_WORDS[COMP_CWORD]}" "${COMP_WORDS[COMP_CWORD-1]}" >/dev/null 2>&1
+    printf '%s\n' "${COMPREPLY[@]}"
+}
+if [ "$#" -gt 3 ]; then
+    for sub in "${@:4}"; do
+        echo "#sub $sub"
+        probe "$2" "$sub" -
+    done
+else
+    echo "#options"
+    probe "$2" -
+    echo "#subcommands"
+    probe "$2" ""
+fi
+"##;
+
+fn run_probe(
+    script: &Path,
+    command: &str,
+    subcommands: &[String],
+    cwd: &Path,
+) -> Result<String, WarpError> {
+    let mut child = Command::new("bash")
+        .arg("--norc")
+        .arg("--noprofile")
+        .arg("-c")
+        .arg(BASH_PROBE)
+        .arg("warp-probe")
+        .arg(script)
+        .arg(command)
+        .arg(BASH_COMPLETION_LIB)
+        .args(subcommands)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| WarpError::command_err(format!("Could not run bash: {}", e)))?;
+    let started = Instant::now();
+    loop {
+        if child.try_wait()?.is_some() {
+            break;
+        }
+        if started.elapsed() > BASH_PROBE_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(WarpError::command_err(format!(
+                "Completion probe for {} timed out",
+                command
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout.read_to_string(&mut output)?;
+    }
+    Ok(output)
+}
+
+// Runs in an empty directory so completion functions that fall back to
+// file names don't report the files around them as subcommands
+pub fn probe_bash(command: &str, script: &Path) -> Result<Option<CompletionSpec>, WarpError> {
+    let empty = std::env::temp_dir().join(format!("warp-probe-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&empty)?;
+    let result = probe_bash_in(command, script, &empty);
+    let _ = std::fs::remove_dir(&empty);
+    result
+}
+
+fn probe_bash_in(
+    command: &str,
+    script: &Path,
+    cwd: &Path,
+) -> Result<Option<CompletionSpec>, WarpError> {
+    let mut spec = CompletionSpec {
+        name: command.to_string(),
+        ..Default::default()
+    };
+    let output = run_probe(script, command, &[], cwd)?;
+    let mut section = "";
+    for line in output.lines() {
+        if line.starts_with('#') {
+            section = line;
+            continue;
+        }
+        let word = line.trim().trim_end_matches('=');
+        match section {
+            "#options" if word.starts_with('-') && word.len() > 1 => {
+                add_option(&mut spec, word.to_string(), line.trim().ends_with('='))
+            }
+            "#subcommands" if valid_name(word) => {
+                subcommand_mut(&mut spec, word);
+            }
+            _ => {}
+        }
+    }
+
+    let probed: Vec<String> = spec
+        .subcommands
+        .iter()
+        .take(MAX_PROBED_SUBCOMMANDS)
+        .map(|sub| sub.name.clone())
+        .collect();
+    if !probed.is_empty() {
+        let output = run_probe(script, command, &probed, cwd)?;
+        let mut current: Option<String> = None;
+        for line in output.lines() {
+            if let Some(name) = line.strip_prefix("#sub ") {
+                current = Some(name.to_string());
+                continue;
+            }
+            let word = line.trim().trim_end_matches('=');
+            if let Some(name) = current
+                .as_deref()
+                .filter(|_| word.starts_with('-') && word.len() > 1)
+            {
+                add_option(
+                    subcommand_mut(&mut spec, name),
+                    word.to_string(),
+                    line.trim().ends_with('='),
+                );
+            }
+        }
+    }
+    // bash can't tell which flags take values, so the linter would misread
+    // `-o file` as a flag and an operand; treat probed specs as partial
+    let mut spec = finish(spec);
+    if let Some(spec) = spec.as_mut() {
+        spec.incomplete = true;
+        for sub in &mut spec.subcommands {
+            sub.incomplete = true;
+        }
+    }
+    Ok(spec)
+}
+
+pub fn load(command: &str, source: &SpecSource) -> Result<Option<CompletionSpec>, WarpError> {
+    match source {
+        SpecSource::Fish(path) => Ok(parse_fish(command, &std::fs::read_to_string(path)?)),
+        SpecSource::Nushell(path) => Ok(parse_nushell(command, &std::fs::read_to_string(path)?)),
+        SpecSource::Zsh(path) => Ok(parse_zsh(command, &std::fs::read_to_string(path)?)),
+        SpecSource::Bash(path) => probe_bash(command, path),
+    }
+}
+
+// Imported specs keyed by source file and its mtime, so only new or changed
+// definitions are parsed (or probed) again on the next start
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpecCache {
+    entries: HashMap<PathBuf, (u64, Option<CompletionSpec>)>,
+}
+
+impl SpecCache {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("warp").join("completion_specs.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), WarpError> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> u64 {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// Adds specs for every discovered command the registry doesn't already
+// have; hand-written specs always win. Blocking; returns how many were added.
+pub fn import_all(registry: &SpecRegistry, probe_bash: bool) -> usize {
+    let mut cache = SpecCache::load();
+    let mut added = 0;
+    for (command, source) in discover() {
+        if registry.contains(&command) || (!probe_bash && matches!(source, SpecSource::Bash(_))) {
+            continue;
+        }
+        let path = source.path().to_path_buf();
+        let mtime = modified(&path);
+        let spec = match cache.entries.get(&path) {
+            Some((cached, spec)) if *cached == mtime => spec.clone(),
+            _ => {
+                let spec = load(&command, &source).unwrap_or_else(|e| {
+                    log::debug!("Skipping completions for {}: {}", command, e);
+                    None
+                });
+                cache.entries.insert(path, (mtime, spec.clone()));
+                spec
+            }
+        };
+        if let Some(spec) = spec {
+            registry.insert(spec);
+            added += 1;
+        }
+    }
+    if let Err(e) = cache.save() {
+        log::warn!("Could not save the completion spec cache: {}", e);
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fish() {
+        let script = r#"
+# git completions
+complete -c git -f
+complete -c git -n '__fish_git_needs_command' -a 'commit push' -d 'Subcommand'
+complete -c git -n __fish_use_subcommand -a "(__fish_git_aliases)"
+complete -c git -s C -r -d 'Run as if started in <path>'
+complete -c git -l no-pager -d 'Do not pipe output into a pager'
+complete -c git -n '__fish_seen_subcommand_from commit' -s m -l message -x -d 'Message'
+complete -c git -n '__fish_seen_subcommand_from commit' -l amend
+complete -c git -n '__fish_seen_subcommand_from push pull' -s f -l force
+complete --command=tig -l help
+"#;
+        let spec = parse_fish("git", script).unwrap();
+        assert_eq!(spec.arg_options, vec!["-C"]);
+        assert_eq!(spec.options, vec!["--no-pager"]);
+        let names: Vec<&str> = spec
+            .subcommands
+            .iter()
+            .map(|sub| sub.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["commit", "push", "pull"]);
+        let commit = spec.subcommand("commit").unwrap();
+        assert!(commit.takes_arg("-m") && commit.takes_arg("--message"));
+        assert!(commit.knows_option("--amend"));
+        assert!(spec.subcommand("pull").unwrap().knows_option("--force"));
+        assert!(!spec.incomplete);
+    }
+
+    #[test]
+    fn test_parse_zsh() {
+        let script = r#"#compdef rg
+_arguments -s -S \
+  '(-i --ignore-case)'{-i,--ignore-case}'[search case-insensitively]' \
+  '*'{-g+,--glob=}'[include/exclude files]:glob' \
+  '--max-depth=[limit directory depth]:depth' \
+  '-h[show help]' \
+  '(- *)--version[show version]'
+if [[ -n $x ]]; then ls -la; fi
+local -a commands
+commands=(
+  'add:add a thing'
+  'remove:remove a thing'
+)
+"#;
+        let spec = parse_zsh("rg", script).unwrap();
+        assert_eq!(spec.options, vec!["-i", "--ignore-case", "-h", "--version"]);
+        assert_eq!(spec.arg_options, vec!["-g", "--glob", "--max-depth"]);
+        let names: Vec<&str> = spec
+            .subcommands
+            .iter()
+            .map(|sub| sub.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["add", "remove"]);
+        assert!(spec.subcommand("add").unwrap().incomplete);
+    }
+
+    #[test]
+    fn test_parse_nushell() {
+        let script = r#"
+def "nu-complete git remotes" [] {
+  ^git remote | lines | each { |line| $line | str trim }
+}
+
+# Update remote refs along with associated objects
+export extern "git push" [
+  remote?: string@"nu-complete git remotes", # the name of the remote
+  --force(-f)                                 # don't check [refs]
+  --set-upstream(-u)
+  --repo: string                              # repository
+  --push-option(-o): string, -n
+]
+
+export extern git [
+  --version(-v)
+  -C: path   # run as if started in <path>
+]
+
+export extern "git commit" []
+export extern "hg pull" [ --rebase ]
+"#;
+        let spec = parse_nushell("git", script).unwrap();
+        assert_eq!(spec.options, vec!["--version", "-v"]);
+        assert_eq!(spec.arg_options, vec!["-C"]);
+        let push = spec.subcommand("push").unwrap();
+        assert_eq!(
+            push.options,
+            vec!["--force", "-f", "--set-upstream", "-u", "-n"]
+        );
+        assert!(push.takes_arg("--repo") && push.takes_arg("-o"));
+        assert!(spec.subcommand("commit").unwrap().incomplete);
+        assert!(spec.subcommand("pull").is_none());
+        assert_eq!(nu_name("git-completions.nu"), Some("git"));
+        assert_eq!(nu_name("cargo.nu"), Some("cargo"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_probe_bash() {
+        if Command::new("bash").arg("-c").arg("true").status().is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("tool");
+        std::fs::write(
+            &script,
+            r#"
+_tool() {
+    local cur="$2"
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "--verbose --output= build clean" -- "$cur"))
+    elif [ "${COMP_WORDS[1]}" = build ]; then
+        COMPREPLY=($(compgen -W "--release --jobs=" -- "$cur"))
+    fi
+}
+complete -F _tool tool
+"#,
+        )
+        .unwrap();
+        let spec = probe_bash("tool", &script).unwrap().unwrap();
+        assert_eq!(spec.options, vec!["--verbose"]);
+        assert_eq!(spec.arg_options, vec!["--output"]);
+        let build = spec.subcommand("build").unwrap();
+        assert!(build.knows_option("--release") && build.takes_arg("--jobs"));
+        assert!(spec.subcommand("clean").unwrap().options.is_empty());
+        assert!(spec.incomplete);
+    }
+}
@@ -0,0 +1,519 @@
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+
+use crate::config::ComplianceConfig;
+use crate::error::WarpError;
+use crate::shutdown::write_atomic;
+
+pub(crate) const GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Records buffered per pane before they are synced to disk; flush() syncs
+// whatever is left in between
+const SYNC_EVERY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    pub session_id: String,
+    pub pane_id: String,
+    pub user: String,
+    pub hostname: String,
+    pub shell: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceRecord {
+    pub sequence: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub session_id: String,
+    pub pane_id: String,
+    pub direction: Direction,
+    pub data: String, // base64, PTY streams are not guaranteed to be valid UTF-8
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+// Written next to each log on every sync. A log cut short after the fact
+// still verifies as a chain, so the head is what makes truncation visible.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogHead {
+    pub records: u64,
+    pub last_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub path: PathBuf,
+    pub records_checked: u64,
+    pub valid: bool,
+    pub first_broken_sequence: Option<u64>,
+    pub error: Option<String>,
+}
+
+struct PaneLog {
+    metadata: SessionMetadata,
+    path: PathBuf,
+    file: BufWriter<File>,
+    sequence: u64,
+    last_hash: String,
+    unsynced: usize,
+}
+
+pub struct ComplianceLogger {
+    config: ComplianceConfig,
+    panes: Mutex<HashMap<String, PaneLog>>,
+}
+
+impl ComplianceRecord {
+    fn compute_hash(&self) -> String {
+        let payload = format!(
+            "{}|{}|{}|{}|{:?}|{}|{}",
+            self.sequence,
+            self.timestamp.to_rfc3339(),
+            self.session_id,
+            self.pane_id,
+            self.direction,
+            self.data,
+            self.prev_hash
+        );
+        to_hex(digest(&SHA256, payload.as_bytes()).as_ref())
+    }
+}
+
+impl ComplianceLogger {
+    pub async fn new(config: ComplianceConfig) -> Result<Self, WarpError> {
+        if config.enabled {
+            fs::create_dir_all(&config.log_directory).await?;
+        }
+
+        Ok(Self {
+            config,
+            panes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_designated(&self, pane_id: &str) -> bool {
+        self.config.enabled
+            && self
+                .config
+                .designated_panes
+                .iter()
+                .any(|p| p == "*" || p == pane_id)
+    }
+
+    pub async fn start_pane(&self, metadata: SessionMetadata) -> Result<(), WarpError> {
+        if !self.is_designated(&metadata.pane_id) {
+            return Ok(());
+        }
+
+        let file_name = format!(
+            "{}_{}_{}.jsonl",
+            metadata.started_at.format("%Y%m%dT%H%M%S"),
+            metadata.session_id,
+            metadata.pane_id
+        );
+        let path = self.config.log_directory.join(file_name);
+
+        // Terminal I/O includes whatever was typed, passwords too
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = BufWriter::new(options.open(&path).await?);
+
+        // The metadata header is itself chained, so it cannot be swapped out later
+        let header = serde_json::to_string(&metadata)?;
+        let mut pane = PaneLog {
+            metadata,
+            path,
+            file,
+            sequence: 0,
+            last_hash: GENESIS_HASH.to_string(),
+            unsynced: 0,
+        };
+        Self::append(&mut pane, Direction::Output, header.as_bytes(), true).await?;
+        Self::sync(&mut pane).await?;
+
+        let mut panes = self.panes.lock().await;
+        panes.insert(pane.metadata.pane_id.clone(), pane);
+        Ok(())
+    }
+
+    // A failed write is logged rather than returned: losing a compliance
+    // record must not take the pane's output down with it
+    pub async fn record(&self, pane_id: &str, direction: Direction, data: &[u8]) {
+        let wanted = match direction {
+            Direction::Input => self.config.record_input,
+            Direction::Output => self.config.record_output,
+        };
+        if !wanted || data.is_empty() {
+            return;
+        }
+
+        let mut panes = self.panes.lock().await;
+        if let Some(pane) = panes.get_mut(pane_id) {
+            if let Err(e) = Self::append(pane, direction, data, false).await {
+                log::error!(
+                    "Failed to write compliance log {}: {}",
+                    pane.path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    pub async fn stop_pane(&self, pane_id: &str) {
+        let mut panes = self.panes.lock().await;
+        if let Some(mut pane) = panes.remove(pane_id) {
+            if let Err(e) = Self::sync(&mut pane).await {
+                log::error!(
+                    "Failed to sync compliance log {}: {}",
+                    pane.path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    // Syncs every pane with buffered records; called periodically and on shutdown
+    pub async fn flush(&self) -> Result<(), WarpError> {
+        let mut panes = self.panes.lock().await;
+        for pane in panes.values_mut().filter(|pane| pane.unsynced > 0) {
+            Self::sync(pane).await?;
+        }
+        Ok(())
+    }
+
+    async fn append(
+        pane: &mut PaneLog,
+        direction: Direction,
+        data: &[u8],
+        is_header: bool,
+    ) -> Result<(), WarpError> {
+        let mut record = ComplianceRecord {
+            sequence: pane.sequence,
+            timestamp: chrono::Utc::now(),
+            session_id: pane.metadata.session_id.clone(),
+            pane_id: pane.metadata.pane_id.clone(),
+            direction,
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+            prev_hash: pane.last_hash.clone(),
+            hash: String::new(),
+        };
+        if is_header {
+            record.pane_id = format!("{}#meta", record.pane_id);
+        }
+        record.hash = record.compute_hash();
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        pane.file.write_all(line.as_bytes()).await?;
+        pane.sequence += 1;
+        pane.last_hash = record.hash;
+        pane.unsynced += 1;
+        if pane.unsynced >= SYNC_EVERY {
+            Self::sync(pane).await?;
+        }
+        Ok(())
+    }
+
+    // The head is only moved once the records it names are on disk
+    async fn sync(pane: &mut PaneLog) -> Result<(), WarpError> {
+        pane.file.flush().await?;
+        pane.file.get_ref().sync_data().await?;
+        let head = LogHead {
+            records: pane.sequence,
+            last_hash: pane.last_hash.clone(),
+        };
+        write_atomic(&head_path(&pane.path), &serde_json::to_vec(&head)?).await?;
+        pane.unsynced = 0;
+        Ok(())
+    }
+
+    pub async fn verify_log(&self, path: &Path) -> Result<VerificationReport, WarpError> {
+        let content = fs::read_to_string(path).await?;
+        let head = match fs::read(head_path(path)).await {
+            Ok(bytes) => Some(serde_json::from_slice::<LogHead>(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let mut report = verify_chain(&content, head.as_ref());
+        report.path = path.to_path_buf();
+        Ok(report)
+    }
+
+    pub async fn list_logs(&self) -> Result<Vec<PathBuf>, WarpError> {
+        let mut logs = Vec::new();
+        if !self.config.log_directory.exists() {
+            return Ok(logs);
+        }
+
+        let mut entries = fs::read_dir(&self.config.log_directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("jsonl") {
+                logs.push(path);
+            }
+        }
+        logs.sort();
+        Ok(logs)
+    }
+
+    pub async fn apply_retention(&self) -> Result<usize, WarpError> {
+        let cutoff = std::time::SystemTime::now()
+            - std::time::Duration::from_secs(self.config.retention_days as u64 * 24 * 60 * 60);
+        let active: Vec<PathBuf> = {
+            let panes = self.panes.lock().await;
+            panes.values().map(|p| p.path.clone()).collect()
+        };
+
+        let mut removed = 0;
+        for path in self.list_logs().await? {
+            if active.contains(&path) {
+                continue;
+            }
+            let modified = fs::metadata(&path).await?.modified()?;
+            if modified < cutoff {
+                fs::remove_file(&path).await?;
+                let _ = fs::remove_file(head_path(&path)).await;
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            log::info!("Compliance retention removed {} expired log(s)", removed);
+        }
+        Ok(removed)
+    }
+
+    // Flattens every log into rows for the export module's ComplianceLog data source
+    pub async fn export_rows(&self) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+        let mut rows = Vec::new();
+
+        for path in self.list_logs().await? {
+            let content = fs::read_to_string(&path).await?;
+            for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                let record: ComplianceRecord = serde_json::from_str(line)?;
+                let decoded = base64::engine::general_purpose::STANDARD
+                    .decode(&record.data)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                    .unwrap_or_default();
+
+                let mut row = HashMap::new();
+                row.insert(
+                    "log_file".to_string(),
+                    serde_json::json!(path.display().to_string()),
+                );
+                row.insert("sequence".to_string(), serde_json::json!(record.sequence));
+                row.insert(
+                    "timestamp".to_string(),
+                    serde_json::json!(record.timestamp.to_rfc3339()),
+                );
+                row.insert(
+                    "session_id".to_string(),
+                    serde_json::json!(record.session_id),
+                );
+                row.insert("pane_id".to_string(), serde_json::json!(record.pane_id));
+                row.insert(
+                    "direction".to_string(),
+                    serde_json::json!(format!("{:?}", record.direction)),
+                );
+                row.insert("data".to_string(), serde_json::json!(decoded));
+                row.insert("hash".to_string(), serde_json::json!(record.hash));
+                rows.push(row);
+            }
+        }
+
+        Ok(rows)
+    }
+}
+
+fn head_path(log: &Path) -> PathBuf {
+    log.with_extension("head")
+}
+
+// With a head, the log must also reach the record it names, with the same hash
+pub fn verify_chain(content: &str, head: Option<&LogHead>) -> VerificationReport {
+    let mut report = VerificationReport {
+        path: PathBuf::new(),
+        records_checked: 0,
+        valid: true,
+        first_broken_sequence: None,
+        error: None,
+    };
+    let mut expected_prev = GENESIS_HASH.to_string();
+
+    for (index, line) in content.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+        let index = index as u64;
+        let record: ComplianceRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                report.valid = false;
+                report.first_broken_sequence = Some(index);
+                report.error = Some(format!("Unparseable record: {}", e));
+                return report;
+            }
+        };
+
+        let problem = if record.sequence != index {
+            Some("sequence gap (record removed or reordered)")
+        } else if record.prev_hash != expected_prev {
+            Some("chain link mismatch")
+        } else if record.compute_hash() != record.hash {
+            Some("record contents modified")
+        } else if head
+            .is_some_and(|head| head.records == index + 1 && head.last_hash != record.hash)
+        {
+            Some("head hash mismatch")
+        } else {
+            None
+        };
+
+        if let Some(problem) = problem {
+            report.valid = false;
+            report.first_broken_sequence = Some(index);
+            report.error = Some(problem.to_string());
+            return report;
+        }
+
+        expected_prev = record.hash;
+        report.records_checked += 1;
+    }
+
+    if let Some(head) = head.filter(|head| head.records > report.records_checked) {
+        report.valid = false;
+        report.first_broken_sequence = Some(report.records_checked);
+        report.error = Some(format!(
+            "log truncated: head names {} records",
+            head.records
+        ));
+    }
+
+    report
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chained(count: u64) -> Vec<ComplianceRecord> {
+        let mut prev = GENESIS_HASH.to_string();
+        (0..count)
+            .map(|sequence| {
+                let mut record = ComplianceRecord {
+                    sequence,
+                    timestamp: chrono::Utc::now(),
+                    session_id: "s".to_string(),
+                    pane_id: "0".to_string(),
+                    direction: Direction::Output,
+                    data: base64::engine::general_purpose::STANDARD
+                        .encode(format!("line {}", sequence)),
+                    prev_hash: prev.clone(),
+                    hash: String::new(),
+                };
+                record.hash = record.compute_hash();
+                prev = record.hash.clone();
+                record
+            })
+            .collect()
+    }
+
+    fn to_log(records: &[ComplianceRecord]) -> String {
+        records
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_intact_chain_verifies() {
+        let report = verify_chain(&to_log(&chained(5)), None);
+        assert!(report.valid);
+        assert_eq!(report.records_checked, 5);
+    }
+
+    #[test]
+    fn test_modified_record_is_detected() {
+        let mut records = chained(5);
+        records[2].data = base64::engine::general_purpose::STANDARD.encode("tampered");
+        let report = verify_chain(&to_log(&records), None);
+        assert!(!report.valid);
+        assert_eq!(report.first_broken_sequence, Some(2));
+    }
+
+    #[test]
+    fn test_removed_record_is_detected() {
+        let mut records = chained(5);
+        records.remove(1);
+        let report = verify_chain(&to_log(&records), None);
+        assert!(!report.valid);
+        assert_eq!(report.first_broken_sequence, Some(1));
+    }
+
+    #[test]
+    fn test_truncated_tail_is_detected_against_head() {
+        let records = chained(5);
+        let head = LogHead {
+            records: 5,
+            last_hash: records[4].hash.clone(),
+        };
+        assert!(verify_chain(&to_log(&records), Some(&head)).valid);
+
+        let report = verify_chain(&to_log(&records[..3]), Some(&head));
+        assert!(!report.valid);
+        assert_eq!(report.first_broken_sequence, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_records_are_batched_until_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = ComplianceLogger::new(ComplianceConfig {
+            enabled: true,
+            log_directory: dir.path().to_path_buf(),
+            designated_panes: vec!["*".to_string()],
+            record_input: true,
+            record_output: true,
+            retention_days: 1,
+        })
+        .await
+        .unwrap();
+        logger
+            .start_pane(SessionMetadata {
+                session_id: "s".to_string(),
+                pane_id: "0".to_string(),
+                user: String::new(),
+                hostname: String::new(),
+                shell: "sh".to_string(),
+                started_at: chrono::Utc::now(),
+            })
+            .await
+            .unwrap();
+        logger.record("0", Direction::Output, b"hello").await;
+
+        let log = logger.list_logs().await.unwrap().remove(0);
+        assert_eq!(logger.verify_log(&log).await.unwrap().records_checked, 1);
+        logger.flush().await.unwrap();
+        let report = logger.verify_log(&log).await.unwrap();
+        assert!(report.valid);
+        assert_eq!(report.records_checked, 2);
+    }
+}
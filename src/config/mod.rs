@@ -1,16 +1,21 @@
+use crate::encoding::OutputEncoding;
+use crate::error::WarpError;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
-use std::collections::HashMap;
 use tokio::fs;
-use crate::error::WarpError;
 
-pub mod manager;
 pub mod validation;
 
+// What main and the app load; the sections the original single-file config
+// had (ui, terminal, ai, plugins, keybindings, debug) are all part of it
+pub type Config = WarpConfig;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WarpConfig {
     pub general: GeneralConfig,
     pub ui: UIConfig,
+    pub accessibility: AccessibilityConfig,
     pub terminal: TerminalConfig,
     pub ai: AIConfig,
     pub plugins: PluginConfig,
@@ -19,25 +24,157 @@ pub struct WarpConfig {
     pub workflows: WorkflowConfig,
     pub scripting: ScriptingConfig,
     pub ssh: SSHConfig,
+    pub compliance: ComplianceConfig,
+    pub network_inspector: NetworkInspectorConfig,
+    pub forge: ForgeConfig,
+    pub security: SecurityConfig,
+    pub correction: CorrectionConfig,
+    pub ipc: IpcConfig,
+    // Embedded HTTP API, including its TLS settings under api.server
+    #[serde(default)]
+    pub api: crate::api::APIConfig,
+    pub editor: EditorConfig,
+    pub problems: ProblemsConfig,
+    pub watchdog: WatchdogConfig,
+    pub output: OutputConfig,
+    pub completions: CompletionImportConfig,
+    pub notifications: NotificationsConfig,
+    pub startup: StartupConfig,
+    pub memory: MemoryConfig,
+    pub notebooks: NotebookConfig,
+    pub runbooks: RunbookConfig,
+    pub cloud: CloudConfig,
+    pub search: SearchConfig,
+    pub replay: ReplayConfig,
+    pub downloads: DownloadConfig,
+    pub network: NetworkConfig,
+    pub oauth: OAuthConfig,
+    pub smtp: SmtpConfig,
+    pub status_bar: StatusBarConfig,
+    pub alerts: AlertsConfig,
+    pub voice: VoiceConfig,
+    pub presence: PresenceConfig,
+    pub shells: ShellsConfig,
     pub docker: DockerConfig,
     pub gpu: GPUConfig,
     pub wasm: WASMConfig,
     pub keybindings: KeybindingConfig,
     pub debug: DebugConfig,
+    #[serde(default)]
+    pub commands: Vec<CustomCommand>, // extra `warp <name>` subcommands
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneralConfig {
     pub auto_update: bool,
-    pub telemetry: bool,
+    // For Warp itself and the default for marketplace items; items can
+    // follow their own
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
+    #[serde(deserialize_with = "telemetry_mode")]
+    pub telemetry: TelemetryMode,
+    #[serde(default)]
+    pub telemetry_delivery: TelemetryDeliveryConfig,
     pub crash_reporting: bool,
     pub startup_command: Option<String>,
     pub working_directory: Option<PathBuf>,
 }
 
+// Which builds to update to. Each channel also takes everything the
+// steadier ones get, so beta users still receive stable releases.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,    // alpha, beta and rc pre-releases
+    Nightly, // nightly and dev pre-releases
+}
+
+impl UpdateChannel {
+    // The channel a semver version is published on, by its pre-release tag
+    pub fn of(version: &str) -> Self {
+        let pre = version
+            .split_once('+')
+            .map_or(version, |(version, _)| version)
+            .split_once('-');
+        match pre {
+            None => Self::Stable,
+            Some((_, tag)) if tag.starts_with("nightly") || tag.starts_with("dev") => Self::Nightly,
+            Some(_) => Self::Beta,
+        }
+    }
+
+    pub fn accepts(self, version: &str) -> bool {
+        Self::of(version) <= self
+    }
+}
+
+// Whether usage events are recorded, and whether they may leave the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TelemetryMode {
+    Ask, // nothing is recorded until the user picks one of the others
+    Off,
+    LocalOnly, // recorded and viewable here, never sent
+    Share,
+}
+
+// Where shared events go and how they wait when they can't get there. They
+// are batched into compressed files on disk first, so an outage or a crash
+// loses nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryDeliveryConfig {
+    pub endpoints: Vec<String>, // tried in order; none means nothing is sent
+    pub batch_size: usize,      // events per upload
+    pub flush_interval_secs: u64,
+    pub max_queue_mb: u64, // on disk; past it, batches are dropped per `drop_policy`
+    pub drop_policy: DropPolicy,
+    pub max_age_days: u32, // batches older than this are dropped unsent
+}
+
+impl Default for TelemetryDeliveryConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            batch_size: 200,
+            flush_interval_secs: 60,
+            max_queue_mb: 10,
+            drop_policy: DropPolicy::DropOldest,
+            max_age_days: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    DropOldest, // make room for new batches
+    DropNewest, // keep what's queued and discard new batches
+}
+
+// Older files stored a bool. false was the default rather than an answer, so
+// it becomes Ask.
+fn telemetry_mode<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<TelemetryMode, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Flag(bool),
+        Mode(TelemetryMode),
+    }
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Flag(true) => TelemetryMode::Share,
+        Stored::Flag(false) => TelemetryMode::Ask,
+        Stored::Mode(mode) => mode,
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UIConfig {
     pub theme: String,
+    pub locale: Option<String>, // falls back to $LANG when unset
     pub font_size: u16,
     pub font_family: String,
     pub opacity: f32,
@@ -49,18 +186,116 @@ pub struct UIConfig {
     pub minimap: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessibilityConfig {
+    pub screen_reader_announcements: bool,
+    pub announce_output: bool,
+    pub output_coalesce_ms: u64,
+    pub high_contrast: bool,
+    pub reduced_motion: bool,
+    pub min_font_size: u16,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TerminalConfig {
     pub shell: String,
     pub shell_args: Vec<String>,
     pub scrollback_lines: usize,
+    #[serde(default)]
+    pub scrollback_paging: PagingConfig,
     pub cursor_blink: bool,
     pub cursor_style: String,
-    pub bell: bool,
-    pub bell_sound: Option<String>,
+    pub bell: bool,                 // audible bell
+    pub bell_sound: Option<String>, // played instead of the host terminal's bell
+    #[serde(default)]
+    pub bell_options: BellConfig,
+    #[serde(default)]
+    pub activity: ActivityConfig,
     pub word_separators: String,
     pub copy_on_select: bool,
     pub paste_on_right_click: bool,
+    pub shutdown_grace_ms: u64,
+    pub pager_threshold_lines: usize,
+    pub command_lint: bool,
+    #[serde(default)]
+    pub output_encoding: OutputEncoding, // how pane output is decoded unless a project says otherwise
+}
+
+// Scrollback older than the newest hot_lines is compressed a page at a
+// time and decompressed when something reads that far back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PagingConfig {
+    pub enabled: bool,
+    pub hot_lines: usize,
+    pub page_lines: usize,
+    // For compressed pages; past it the oldest go to temp files, or are
+    // dropped when spill_to_disk is off
+    pub memory_budget_mb: u64,
+    pub spill_to_disk: bool,
+}
+
+impl Default for PagingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hot_lines: 2000,
+            page_lines: 500,
+            memory_budget_mb: 16,
+            spill_to_disk: true,
+        }
+    }
+}
+
+// Output and silence in background tabs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivityConfig {
+    // Quiet this long after being busy counts as done, e.g. a finished build
+    pub silence_secs: u64,
+    // Output for at least this long before going quiet is reported
+    pub min_busy_secs: u64,
+    // Switch to a background tab when it goes quiet
+    pub focus_on_silence: bool,
+    // Regexes matched against background output that switch to its tab,
+    // e.g. '(?i)error|panicked'. Neither rule fires while something is typed.
+    pub focus_patterns: Vec<String>,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        Self {
+            silence_secs: 5,
+            min_busy_secs: 3,
+            focus_on_silence: false,
+            focus_patterns: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BellConfig {
+    pub visual: bool,
+    pub flash_ms: u64,
+    // A desktop notification when the bell rings while warp isn't focused
+    pub notify_when_unfocused: bool,
+    // Bells closer together than this ring once
+    pub min_interval_ms: u64,
+    // Regexes matched against the running command, e.g. '^(vim|less)\b'
+    pub suppress_commands: Vec<String>,
+}
+
+impl Default for BellConfig {
+    fn default() -> Self {
+        Self {
+            visual: true,
+            flash_ms: 150,
+            notify_when_unfocused: true,
+            min_interval_ms: 500,
+            suppress_commands: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,6 +317,50 @@ pub struct PluginConfig {
     pub plugin_directory: PathBuf,
     pub auto_update_plugins: bool,
     pub plugin_repositories: Vec<String>,
+    // Marketplace registries besides the official one; an entry named
+    // "official" changes the official registry's settings instead
+    #[serde(default)]
+    pub registries: Vec<RegistryConfig>,
+    // Earlier versions of each marketplace item kept for rollback
+    #[serde(default = "kept_versions")]
+    pub kept_versions: usize,
+}
+
+fn kept_versions() -> usize {
+    3
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryConfig {
+    pub name: String,
+    pub url: String, // API base, like https://marketplace.warp.dev/api/v1
+    #[serde(default)]
+    pub priority: i32, // the higher one wins when registries carry the same item
+    #[serde(default)]
+    pub auth: RegistryAuth,
+    // Publishers whose items only come from this registry, so a public item
+    // with the same id can't stand in for a private one
+    #[serde(default)]
+    pub publishers: Vec<String>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RegistryAuth {
+    #[default]
+    None,
+    Token {
+        secret: String,
+    }, // name of a secret holding a bearer token
+    OAuth {
+        provider: String,
+    }, // an [oauth] provider signed in with `warp auth login`
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -126,6 +405,528 @@ pub struct SSHConfig {
     pub known_hosts_file: PathBuf,
     pub connection_timeout: u64,
     pub keep_alive_interval: u64,
+    pub reconnect: SSHReconnectConfig,
+    // Named groups of destinations ("user@host" or "host:port") for broadcast runs
+    #[serde(default)]
+    pub host_groups: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub broadcast: SSHBroadcastConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SSHBroadcastConfig {
+    pub max_concurrency: usize,
+    pub host_timeout_secs: u64,
+}
+
+impl Default for SSHBroadcastConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 8,
+            host_timeout_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SSHReconnectConfig {
+    pub enabled: bool,
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub backoff_multiplier: f64,
+    pub persistent_remote_session: bool, // reattach via tmux on the remote host
+    pub resync_lines: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceConfig {
+    pub enabled: bool,
+    pub log_directory: PathBuf,
+    pub designated_panes: Vec<String>, // pane ids, or "*" for every pane
+    pub record_input: bool,
+    pub record_output: bool,
+    pub retention_days: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkInspectorConfig {
+    pub enabled: bool, // opt-in: polls the foreground command's sockets
+    pub poll_interval_ms: u64,
+    pub allowed_hosts: Vec<String>, // exact names/IPs or "*.example.com"
+    pub flag_unexpected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForgeConfig {
+    pub github_hosts: Vec<String>, // add GitHub Enterprise hosts here
+    pub gitlab_hosts: Vec<String>, // and self-managed GitLab hosts here
+    pub max_items: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PolicyAction {
+    Allow,
+    Confirm,
+    Deny,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandRule {
+    pub pattern: String, // regex matched against the whole command line
+    pub action: PolicyAction,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    pub command_rules: Vec<CommandRule>, // first match wins; unmatched commands are allowed
+    pub install_suggestions: bool,
+    pub escapes: EscapeFilterConfig,
+    pub paste: PasteConfig,
+    // Keep the local audit log of privileged actions (see audit.rs)
+    #[serde(default = "enabled")]
+    pub audit_log: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasteConfig {
+    pub review_multiline: bool, // multi-line pastes wait in a review panel
+    pub strip_trailing_newlines: bool, // a trailing newline would run the paste straight away
+    pub warnings: Vec<PasteRule>, // matched against each pasted line
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasteRule {
+    pub pattern: String,
+    pub reason: String,
+}
+
+// How far a command's output is trusted to drive the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TrustLevel {
+    Local,
+    Remote,    // a shell on another machine (ssh and the like)
+    Untrusted, // file contents, e.g. cat of a download
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EscapeAction {
+    Allow,
+    Strip,
+    Report, // strip and tell the user
+}
+
+// Colours and text styles always pass
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscapePolicy {
+    pub title: EscapeAction,          // OSC 0/1/2, which can spoof a prompt
+    pub clipboard: EscapeAction,      // OSC 52
+    pub hyperlinks: EscapeAction,     // OSC 8
+    pub other_osc: EscapeAction,      // cwd, prompt marks, palette, notifications
+    pub device_control: EscapeAction, // DCS, APC, PM and SOS strings
+    pub queries: EscapeAction, // status, attribute and window reports the terminal answers by typing
+    pub cursor: EscapeAction,  // movement, erasing and modes, which can hide earlier lines
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscapeFilterConfig {
+    pub enabled: bool,
+    pub local: EscapePolicy,
+    pub remote: EscapePolicy,
+    pub untrusted: EscapePolicy,
+    pub remote_commands: Vec<String>, // regexes; output of matching commands is Remote
+    pub untrusted_commands: Vec<String>, // checked first; matching output is Untrusted
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrectionConfig {
+    pub suggestions: bool,
+    pub auto_correct: bool, // only applies to typos listed in `trusted`
+    pub trusted: HashMap<String, String>, // typo -> command it always means
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IpcScope {
+    Read, // query blocks and output
+    Run,  // run commands, open tabs
+    Ui,   // trigger panel and pager actions
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcConfig {
+    pub enabled: bool,
+    pub socket_path: Option<PathBuf>, // defaults to a per-process socket in the runtime dir
+    pub scopes: Vec<IpcScope>,        // what a client holding the token may do
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditorConfig {
+    // Template with {file}, {line} and {column}; defaults to $VISUAL/$EDITOR
+    pub command: Option<String>,
+    pub projects: HashMap<PathBuf, String>, // project root -> template used for files under it
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ProblemSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProblemsConfig {
+    pub matchers: Vec<ProblemMatcherConfig>, // tried before the built-in cargo/tsc/eslint/pytest/gcc parsers
+}
+
+// A regex with named groups: `file`, `line` and `message` are required;
+// `column`, `severity` and `code` are optional
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProblemMatcherConfig {
+    pub name: String,
+    pub pattern: String,
+    pub severity: ProblemSeverity, // used when the pattern has no `severity` group
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitAction {
+    Warn,
+    Throttle, // warn and cap how fast output is captured
+    Kill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourcePolicy {
+    pub max_runtime_secs: Option<u64>,
+    pub max_memory_bytes: Option<u64>, // summed over the command's processes
+    pub max_output_bytes: Option<u64>,
+    pub action: LimitAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogRule {
+    pub pattern: String, // regex matched against the command line
+    pub policy: ResourcePolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    pub default_policy: ResourcePolicy,
+    pub rules: Vec<WatchdogRule>, // first match wins
+    pub projects: HashMap<PathBuf, Vec<WatchdogRule>>, // project root -> rules tried before `rules`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputConfig {
+    pub truncate: bool,
+    pub head_lines: usize, // kept from the start of a long block
+    pub tail_lines: usize, // kept from its end; the middle goes to a temp file
+    pub max_line_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionImportConfig {
+    pub import_system: bool, // read fish/zsh/bash completion scripts
+    pub probe_bash: bool,    // bash scripts are executed to be read
+    // Servers for multi-line scripts at the prompt and notebook cells, by
+    // LSP language id ("shellscript", "python", ...)
+    #[serde(default)]
+    pub language_servers: BTreeMap<String, LanguageServerConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageServerConfig {
+    pub enabled: bool,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NotificationCategory {
+    Jobs,
+    Alerts,
+    Collaboration,
+    Updates,
+    System,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum NotificationLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+// Local times as "HH:MM"; a range past midnight such as 22:00-07:00 wraps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    pub toasts: bool,
+    pub os_notifications: bool, // via notify-send or osascript
+    pub toast_secs: u64,
+    pub history_limit: usize,
+    pub min_level: HashMap<NotificationCategory, NotificationLevel>, // quieter ones only go to history
+    pub do_not_disturb: Option<QuietHours>,
+    pub errors_break_through: bool, // errors still show during quiet hours
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartupConfig {
+    pub budget_ms: u64, // warn when subsystems built before the UI take longer
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    pub enabled: bool,
+    pub sample_secs: u64,
+    pub budgets_mb: HashMap<String, u64>, // subsystem -> alert above this
+    pub dump_directory: Option<PathBuf>,  // defaults to the temp dir
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotebookConfig {
+    pub cell_timeout_secs: u64, // a cell still running after this is killed
+    pub stop_on_failure: bool,  // running several cells stops at the first non-zero exit
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunbookConfig {
+    pub directories: Vec<PathBuf>, // searched for *.toml runbooks
+    pub step_timeout_secs: u64,    // default for steps without their own timeout_secs
+    pub report_directory: PathBuf, // a Markdown report is written here when a run ends
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudConfig {
+    pub enabled: bool,                     // AWS/GCP/Azure contexts in the header
+    pub production_patterns: Vec<String>,  // regexes over profile, project and subscription names
+    pub destructive_commands: Vec<String>, // regexes; confirmed first while a production context is active
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchConfig {
+    pub enabled: bool,        // finished blocks go into the on-disk search index
+    pub index_output: bool,   // false indexes commands only
+    pub max_output_kb: usize, // per block; the rest of its output isn't searchable
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    pub enabled: bool, // each session's commands and output are recorded for replay
+    pub directory: PathBuf,
+    pub keep_sessions: usize, // older recordings are removed at startup
+}
+
+// Shared by marketplace package installs and theme URL installs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadConfig {
+    pub max_connections: usize, // concurrent downloads; the rest wait their turn
+    pub bandwidth_limit_kbps: u64, // across all downloads; 0 is unlimited
+    pub retries: u32,           // an interrupted download resumes where it stopped
+    pub timeout_secs: u64,      // connect and per-read timeout, not the whole transfer
+}
+
+// Applied to every HTTP client: marketplace, forges, downloads and plugins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub http_proxy: Option<String>,  // overrides HTTP_PROXY
+    pub https_proxy: Option<String>, // overrides HTTPS_PROXY
+    pub no_proxy: Vec<String>,       // hosts, domains and CIDRs, added to NO_PROXY
+    pub use_env_proxy: bool,         // false ignores the *_PROXY variables entirely
+    pub ca_bundle: Option<PathBuf>,  // PEM file of extra trusted roots, e.g. a corporate CA
+}
+
+// Device-code sign-in (`warp auth login <name>`); tokens go to the secrets manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthConfig {
+    pub providers: Vec<OAuthProviderConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthProviderConfig {
+    pub name: String,
+    pub client_id: String, // a public client; device flow needs no secret
+    pub device_authorization_url: String,
+    pub token_url: String,
+    pub scopes: Vec<String>,
+}
+
+impl OAuthConfig {
+    pub fn provider(&self, name: &str) -> Option<&OAuthProviderConfig> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+}
+
+// Outgoing mail for emailed export reports; the password is the `smtp.password` secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: Option<String>, // unset disables email delivery
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub from: String,
+    pub max_attachment_mb: u64, // larger reports are linked instead of attached
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SmtpSecurity {
+    StartTls, // upgrade a plain connection, usually port 587
+    Tls,      // TLS from the first byte, usually port 465
+    None,     // only for local relays
+}
+
+// Live widgets in the header's bottom edge, in order; empty shows none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusBarConfig {
+    pub widgets: Vec<StatusWidget>,
+    pub sample_secs: u64,
+    pub width: usize, // cells per sparkline or gauge
+    // Segments in the bottom edge's left and right slots, shown in this order
+    #[serde(default)]
+    pub segments: Vec<StatusSegmentConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatusWidget {
+    CpuSparkline,
+    MemoryGauge,
+    LogTail(PathBuf), // the last line of a growing file
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusSegmentConfig {
+    pub provider: StatusProvider,
+    pub slot: StatusSlot,
+    // When a slot runs out of room the lowest priorities are dropped first
+    #[serde(default)]
+    pub priority: i32,
+    pub refresh_secs: u64,
+    #[serde(default)]
+    pub action: Option<StatusAction>, // what activating the segment does
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatusProvider {
+    Git,          // branch, ahead/behind and changed files of the pane's repository
+    Kubernetes,   // kubectl's current context and namespace
+    Time(String), // a strftime format, e.g. "%H:%M"
+    Jobs,         // processes the pane's shell is running
+    // A plugin's reply to `status_segment`; activating it sends `status_click`
+    Plugin(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusSlot {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatusAction {
+    Action(String),  // one of the app's actions, e.g. "toggle_git"
+    Command(String), // typed into the pane and run
+}
+
+// Where metric alerts, anomalies and CI failures go. The first route
+// matching an alert's source and level decides; unmatched alerts are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    pub routes: Vec<AlertRoute>,
+    pub repeat_secs: u64, // the same alert isn't posted to chat again within this
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRoute {
+    pub source: Option<AlertSource>, // None matches every source
+    pub min_level: NotificationLevel,
+    pub notify: bool, // the notification center
+    pub chat: bool,   // a system message in the active collaboration session
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AlertSource {
+    Metrics,
+    Anomalies,
+    CI,
+    System,
+}
+
+// Voice chat in collaboration sessions, sent as Opus over UDP
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoiceConfig {
+    pub input_device: Option<String>, // by name, as listed by the OS; None uses the default
+    pub output_device: Option<String>,
+    pub port: u16,               // UDP port to receive on; 0 picks a free one
+    pub bitrate: i32,            // bits per second
+    pub jitter_ms: u32,          // audio held back to smooth out late packets
+    pub speaking_threshold: f32, // RMS level, 0..1, counted as speech
+}
+
+// Who is around in collaboration sessions, from heartbeats and activity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceConfig {
+    pub heartbeat_secs: u64, // how often clients send a heartbeat
+    pub away_secs: u64,      // without input before a participant shows as away
+    pub offline_secs: u64,   // without a heartbeat before a participant shows as offline
+    pub typing_secs: u64,    // the typing indicator's lifetime after the last keystroke
+}
+
+// Shell and environment for new tabs; anything unset falls back to `terminal`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellsConfig {
+    pub available: Vec<String>, // offered in the new tab picker when found on PATH
+    pub profiles: HashMap<String, EnvironmentProfile>,
+    pub projects: HashMap<PathBuf, PaneDefaults>, // project root -> defaults for tabs opened inside it
+    #[serde(default)]
+    pub connections: BTreeMap<String, ConnectionProfile>, // `warp connect <name>`
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvironmentProfile {
+    pub env: HashMap<String, String>,
+    pub unset: Vec<String>,
+}
+
+// A saved way onto a machine: one action opens a tab with the profile's
+// environment, connects to `host` and runs the startup commands there
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionProfile {
+    pub host: Option<String>,    // "[user@]host[:port]"; unset for a local tab
+    pub profile: Option<String>, // one of `profiles`
+    pub cwd: Option<PathBuf>,    // where the local shell starts
+    pub startup_commands: Vec<String>,
+    pub accent: Option<String>, // header colour while the tab is open: a colour name or "#rrggbb"
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PaneDefaults {
+    pub shell: Option<String>,
+    pub profile: Option<String>,
+    pub encoding: Option<OutputEncoding>,
+    #[serde(default)]
+    pub container: Option<ContainerPolicy>,
+}
+
+// Commands in a project that run in a throwaway container rather than the
+// shell, the way an `@` prefix does for one command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerPolicy {
+    pub image: Option<String>, // docker.run.image when unset
+    // Regexes for the commands to contain; every command when empty
+    pub commands: Vec<String>,
+    pub network: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,6 +936,29 @@ pub struct DockerConfig {
     pub default_registry: String,
     pub auto_pull_images: bool,
     pub cleanup_containers: bool,
+    #[serde(default)]
+    pub run: ContainerRunConfig,
+}
+
+// Commands run in a container from the prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerRunConfig {
+    pub runtime: Option<String>, // "podman" or "docker"; whichever is installed when unset, podman first
+    pub image: String,           // images without a registry are pulled from default_registry
+    pub network: bool,
+    pub timeout_secs: u64,
+}
+
+impl Default for ContainerRunConfig {
+    fn default() -> Self {
+        Self {
+            runtime: None,
+            image: "debian:stable-slim".to_string(),
+            network: true,
+            timeout_secs: 600,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -153,6 +977,13 @@ pub struct WASMConfig {
     pub timeout: u64,
     pub allowed_imports: Vec<String>,
     pub sandbox_level: String, // strict, moderate, permissive
+    #[serde(default)]
+    pub dev_plugins: Vec<PathBuf>, // plugin packages to rebuild and reload on change
+    #[serde(default)]
+    pub dev_build_command: Option<String>, // instead of cargo, run in the package directory
+    pub max_calls_per_minute: u32, // per plugin; over it calls are refused, 0 for no limit
+    pub max_cpu_ms_per_minute: u64, // per plugin; over it the plugin is suspended, 0 for no limit
+    pub kill_switch_ms: u64,   // a UI stall this long disables every plugin, 0 to never
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -175,6 +1006,36 @@ pub struct DebugConfig {
     pub log_file: Option<PathBuf>,
     pub performance_monitoring: bool,
     pub memory_profiling: bool,
+    // Share of blocks traced stage by stage for the trace view, 0.0 to 1.0
+    #[serde(default = "trace_sample_rate")]
+    pub trace_sample_rate: f64,
+}
+
+fn trace_sample_rate() -> f64 {
+    0.1
+}
+
+// A user-defined `warp <name>` subcommand. `run` is a shell command line in
+// which `{arg}` placeholders are replaced by the (quoted) argument values;
+// anything after the declared arguments is appended as-is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomCommand {
+    pub name: String,
+    #[serde(default)]
+    pub about: String,
+    pub run: String,
+    #[serde(default)]
+    pub args: Vec<CustomArg>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomArg {
+    pub name: String,
+    #[serde(default)]
+    pub help: String,
+    #[serde(default)]
+    pub required: bool,
+    pub default: Option<String>,
 }
 
 impl Default for WarpConfig {
@@ -182,12 +1043,15 @@ impl Default for WarpConfig {
         Self {
             general: GeneralConfig {
                 auto_update: true,
-                telemetry: false,
+                update_channel: UpdateChannel::Stable,
+                telemetry: TelemetryMode::Ask,
+                telemetry_delivery: TelemetryDeliveryConfig::default(),
                 crash_reporting: true,
                 startup_command: None,
                 working_directory: None,
             },
             ui: UIConfig {
+                locale: None,
                 theme: "standard_dark".to_string(),
                 font_size: 14,
                 font_family: "JetBrains Mono".to_string(),
@@ -199,17 +1063,36 @@ impl Default for WarpConfig {
                 line_numbers: false,
                 minimap: false,
             },
+            accessibility: AccessibilityConfig {
+                screen_reader_announcements: false,
+                announce_output: true,
+                output_coalesce_ms: 1500,
+                high_contrast: false,
+                reduced_motion: false,
+                min_font_size: 8,
+            },
             terminal: TerminalConfig {
-                shell: if cfg!(windows) { "powershell".to_string() } else { "zsh".to_string() },
+                shell: if cfg!(windows) {
+                    "powershell".to_string()
+                } else {
+                    "zsh".to_string()
+                },
                 shell_args: vec![],
                 scrollback_lines: 10000,
+                scrollback_paging: PagingConfig::default(),
                 cursor_blink: true,
                 cursor_style: "block".to_string(),
                 bell: false,
                 bell_sound: None,
+                bell_options: BellConfig::default(),
+                activity: ActivityConfig::default(),
                 word_separators: " \t\n\r\"'`()[]{}|\\".to_string(),
                 copy_on_select: false,
                 paste_on_right_click: true,
+                shutdown_grace_ms: 3000,
+                pager_threshold_lines: 200,
+                command_lint: true,
+                output_encoding: OutputEncoding::Utf8,
             },
             ai: AIConfig {
                 enabled: true,
@@ -227,37 +1110,37 @@ impl Default for WarpConfig {
                 plugin_directory: dirs::config_dir().unwrap_or_default().join("warp/plugins"),
                 auto_update_plugins: false,
                 plugin_repositories: vec!["https://github.com/warpdotdev/plugins".to_string()],
+                registries: Vec::new(),
+                kept_versions: kept_versions(),
             },
             themes: ThemeConfig {
                 current_theme: "standard_dark".to_string(),
-                theme_directories: vec![
-                    dirs::config_dir().unwrap_or_default().join("warp/themes"),
-                ],
+                theme_directories: vec![dirs::config_dir().unwrap_or_default().join("warp/themes")],
                 auto_switch_theme: false,
                 light_theme: "standard_light".to_string(),
                 dark_theme: "standard_dark".to_string(),
             },
             keysets: KeysetConfig {
                 current_keyset: "default".to_string(),
-                keyset_directories: vec![
-                    dirs::config_dir().unwrap_or_default().join("warp/keysets"),
-                ],
+                keyset_directories: vec![dirs::config_dir()
+                    .unwrap_or_default()
+                    .join("warp/keysets")],
                 custom_bindings: HashMap::new(),
             },
             workflows: WorkflowConfig {
                 enabled: true,
-                workflow_directories: vec![
-                    dirs::config_dir().unwrap_or_default().join("warp/workflows"),
-                ],
+                workflow_directories: vec![dirs::config_dir()
+                    .unwrap_or_default()
+                    .join("warp/workflows")],
                 auto_execute: false,
                 max_concurrent_workflows: 5,
             },
             scripting: ScriptingConfig {
                 enabled: true,
                 default_language: "lua".to_string(),
-                script_directories: vec![
-                    dirs::config_dir().unwrap_or_default().join("warp/scripts"),
-                ],
+                script_directories: vec![dirs::config_dir()
+                    .unwrap_or_default()
+                    .join("warp/scripts")],
                 timeout: 30,
                 max_memory: 100 * 1024 * 1024, // 100MB
                 allowed_modules: vec!["fs".to_string(), "http".to_string()],
@@ -266,9 +1149,350 @@ impl Default for WarpConfig {
                 enabled: true,
                 default_user: None,
                 key_directory: dirs::home_dir().unwrap_or_default().join(".ssh"),
-                known_hosts_file: dirs::home_dir().unwrap_or_default().join(".ssh/known_hosts"),
+                known_hosts_file: dirs::home_dir()
+                    .unwrap_or_default()
+                    .join(".ssh/known_hosts"),
                 connection_timeout: 30,
                 keep_alive_interval: 60,
+                reconnect: SSHReconnectConfig {
+                    enabled: true,
+                    max_attempts: 10,
+                    initial_backoff_ms: 500,
+                    max_backoff_ms: 30_000,
+                    backoff_multiplier: 2.0,
+                    persistent_remote_session: true,
+                    resync_lines: 2000,
+                },
+                host_groups: BTreeMap::new(),
+                broadcast: SSHBroadcastConfig::default(),
+            },
+            compliance: ComplianceConfig {
+                enabled: false,
+                log_directory: dirs::data_dir().unwrap_or_default().join("warp/compliance"),
+                designated_panes: vec![],
+                record_input: true,
+                record_output: true,
+                retention_days: 365,
+            },
+            network_inspector: NetworkInspectorConfig {
+                enabled: false,
+                poll_interval_ms: 250,
+                allowed_hosts: vec![],
+                flag_unexpected: true,
+            },
+            forge: ForgeConfig {
+                github_hosts: vec!["github.com".to_string()],
+                gitlab_hosts: vec!["gitlab.com".to_string()],
+                max_items: 50,
+            },
+            security: SecurityConfig {
+                command_rules: vec![
+                    CommandRule {
+                        pattern: r"\brm\s+-[a-zA-Z]*(rf|fr)[a-zA-Z]*\s+(/|/\*|~)(\s|$)".to_string(),
+                        action: PolicyAction::Deny,
+                        reason: "Recursively deletes the root or home directory".to_string(),
+                    },
+                    CommandRule {
+                        pattern: r"\b(curl|wget)\b[^|]*\|\s*(sudo\s+)?(ba|z)?sh\b".to_string(),
+                        action: PolicyAction::Confirm,
+                        reason: "Runs a downloaded script".to_string(),
+                    },
+                    CommandRule {
+                        pattern: r"^\s*sudo\b".to_string(),
+                        action: PolicyAction::Confirm,
+                        reason: "Runs with elevated privileges".to_string(),
+                    },
+                ],
+                install_suggestions: true,
+                escapes: EscapeFilterConfig {
+                    enabled: true,
+                    local: EscapePolicy {
+                        title: EscapeAction::Allow,
+                        clipboard: EscapeAction::Report,
+                        hyperlinks: EscapeAction::Allow,
+                        other_osc: EscapeAction::Allow,
+                        device_control: EscapeAction::Allow,
+                        queries: EscapeAction::Allow,
+                        cursor: EscapeAction::Allow,
+                    },
+                    remote: EscapePolicy {
+                        title: EscapeAction::Allow,
+                        clipboard: EscapeAction::Report,
+                        hyperlinks: EscapeAction::Allow,
+                        other_osc: EscapeAction::Allow,
+                        device_control: EscapeAction::Strip,
+                        queries: EscapeAction::Allow,
+                        cursor: EscapeAction::Allow,
+                    },
+                    untrusted: EscapePolicy {
+                        title: EscapeAction::Report,
+                        clipboard: EscapeAction::Report,
+                        hyperlinks: EscapeAction::Strip,
+                        other_osc: EscapeAction::Strip,
+                        device_control: EscapeAction::Report,
+                        queries: EscapeAction::Report,
+                        cursor: EscapeAction::Strip,
+                    },
+                    remote_commands: vec![
+                        r"^\s*(ssh|mosh|et|telnet)\s".to_string(),
+                        r"^\s*(docker|podman|kubectl)\s+exec\s".to_string(),
+                    ],
+                    untrusted_commands: vec![
+                        r"^\s*(cat|less|more|head|tail|bat)\s.*(/Downloads/|/tmp/)".to_string(),
+                        r"^\s*(curl|zcat|xzcat|bzcat)\s".to_string(),
+                    ],
+                },
+                paste: PasteConfig {
+                    review_multiline: true,
+                    strip_trailing_newlines: true,
+                    warnings: vec![
+                        PasteRule {
+                            pattern: r"\bsudo\b".to_string(),
+                            reason: "Runs with elevated privileges".to_string(),
+                        },
+                        PasteRule {
+                            pattern: r"\brm\s+(-[a-zA-Z]*[rRf]|--recursive|--force)".to_string(),
+                            reason: "Deletes files without asking".to_string(),
+                        },
+                        PasteRule {
+                            pattern: r"\b(curl|wget)\b[^|]*\|\s*(sudo\s+)?(ba|z)?sh\b".to_string(),
+                            reason: "Runs a downloaded script".to_string(),
+                        },
+                    ],
+                },
+                audit_log: true,
+            },
+            correction: CorrectionConfig {
+                suggestions: true,
+                auto_correct: false,
+                trusted: [("gti", "git"), ("sl", "ls"), ("claer", "clear")]
+                    .into_iter()
+                    .map(|(typo, command)| (typo.to_string(), command.to_string()))
+                    .collect(),
+            },
+            ipc: IpcConfig {
+                enabled: false,
+                socket_path: None,
+                scopes: vec![IpcScope::Read, IpcScope::Run, IpcScope::Ui],
+            },
+            api: crate::api::APIConfig::default(),
+            editor: EditorConfig {
+                command: None,
+                projects: HashMap::new(),
+            },
+            problems: ProblemsConfig {
+                matchers: Vec::new(),
+            },
+            watchdog: WatchdogConfig {
+                enabled: true,
+                // Generous enough for builds and test runs; the point is to
+                // stop a `cat /dev/urandom` from taking the app down
+                default_policy: ResourcePolicy {
+                    max_runtime_secs: None,
+                    max_memory_bytes: None,
+                    max_output_bytes: Some(512 * 1024 * 1024),
+                    action: LimitAction::Throttle,
+                },
+                rules: Vec::new(),
+                projects: HashMap::new(),
+            },
+            output: OutputConfig {
+                truncate: true,
+                head_lines: 200,
+                tail_lines: 600,
+                max_line_bytes: 16 * 1024,
+            },
+            completions: CompletionImportConfig {
+                import_system: true,
+                probe_bash: true,
+                language_servers: BTreeMap::from([
+                    (
+                        "shellscript".to_string(),
+                        LanguageServerConfig {
+                            enabled: false,
+                            command: "bash-language-server".to_string(),
+                            args: vec!["start".to_string()],
+                        },
+                    ),
+                    (
+                        "python".to_string(),
+                        LanguageServerConfig {
+                            enabled: false,
+                            command: "pylsp".to_string(),
+                            args: Vec::new(),
+                        },
+                    ),
+                ]),
+            },
+            notifications: NotificationsConfig {
+                toasts: true,
+                os_notifications: false,
+                toast_secs: 5,
+                history_limit: 200,
+                min_level: HashMap::new(),
+                do_not_disturb: None,
+                errors_break_through: true,
+            },
+            startup: StartupConfig { budget_ms: 250 },
+            memory: MemoryConfig {
+                enabled: true,
+                sample_secs: 10,
+                budgets_mb: [
+                    ("scrollback", 64),
+                    ("block", 32),
+                    ("history", 16),
+                    ("notifications", 4),
+                    ("process", 2048),
+                ]
+                .into_iter()
+                .map(|(name, mb)| (name.to_string(), mb))
+                .collect(),
+                dump_directory: None,
+            },
+            notebooks: NotebookConfig {
+                cell_timeout_secs: 600,
+                stop_on_failure: true,
+            },
+            runbooks: RunbookConfig {
+                directories: vec![
+                    dirs::config_dir().unwrap_or_default().join("warp/runbooks"),
+                    PathBuf::from("runbooks"),
+                ],
+                step_timeout_secs: 1800,
+                report_directory: dirs::data_dir()
+                    .unwrap_or_default()
+                    .join("warp/runbook-reports"),
+            },
+            cloud: CloudConfig {
+                enabled: true,
+                production_patterns: vec![r"(?i)\bprod(uction)?\b".to_string()],
+                destructive_commands: vec![
+                    r"\baws\s+\S+\s+(delete|terminate|remove|deregister|purge)-".to_string(),
+                    r"\baws\s+s3\s+(rm|rb)\b".to_string(),
+                    r"\bgcloud\b.*\s(delete|remove)\b".to_string(),
+                    r"\baz\b.*\s(delete|purge)\b".to_string(),
+                    r"\b(terraform|tofu)\s+(destroy|apply)\b".to_string(),
+                    r"\bkubectl\s+delete\b".to_string(),
+                ],
+            },
+            search: SearchConfig {
+                enabled: true,
+                index_output: true,
+                max_output_kb: 256,
+            },
+            replay: ReplayConfig {
+                enabled: true,
+                directory: dirs::data_dir().unwrap_or_default().join("warp/recordings"),
+                keep_sessions: 20,
+            },
+            downloads: DownloadConfig {
+                max_connections: 4,
+                bandwidth_limit_kbps: 0,
+                retries: 3,
+                timeout_secs: 30,
+            },
+            network: NetworkConfig {
+                http_proxy: None,
+                https_proxy: None,
+                no_proxy: Vec::new(),
+                use_env_proxy: true,
+                ca_bundle: None,
+            },
+            oauth: OAuthConfig {
+                providers: vec![OAuthProviderConfig {
+                    name: "marketplace".to_string(),
+                    client_id: "warp-terminal".to_string(),
+                    device_authorization_url: "https://marketplace.warp.dev/oauth/device/code"
+                        .to_string(),
+                    token_url: "https://marketplace.warp.dev/oauth/token".to_string(),
+                    scopes: vec!["packages:read".to_string(), "packages:publish".to_string()],
+                }],
+            },
+            smtp: SmtpConfig {
+                host: None,
+                port: 587,
+                security: SmtpSecurity::StartTls,
+                username: None,
+                from: "Warp Reports <reports@localhost>".to_string(),
+                max_attachment_mb: 10,
+                timeout_secs: 30,
+            },
+            status_bar: StatusBarConfig {
+                widgets: Vec::new(),
+                sample_secs: 2,
+                width: 10,
+                segments: vec![
+                    StatusSegmentConfig {
+                        provider: StatusProvider::Git,
+                        slot: StatusSlot::Left,
+                        priority: 30,
+                        refresh_secs: 5,
+                        action: Some(StatusAction::Action("toggle_git".to_string())),
+                    },
+                    StatusSegmentConfig {
+                        provider: StatusProvider::Jobs,
+                        slot: StatusSlot::Left,
+                        priority: 10,
+                        refresh_secs: 2,
+                        action: Some(StatusAction::Action("toggle_process_tree".to_string())),
+                    },
+                    StatusSegmentConfig {
+                        provider: StatusProvider::Kubernetes,
+                        slot: StatusSlot::Right,
+                        priority: 20,
+                        refresh_secs: 10,
+                        action: Some(StatusAction::Command(
+                            "kubectl config get-contexts".to_string(),
+                        )),
+                    },
+                    StatusSegmentConfig {
+                        provider: StatusProvider::Time("%H:%M".to_string()),
+                        slot: StatusSlot::Right,
+                        priority: 0,
+                        refresh_secs: 5,
+                        action: None,
+                    },
+                ],
+            },
+            alerts: AlertsConfig {
+                routes: vec![
+                    AlertRoute {
+                        source: None,
+                        min_level: NotificationLevel::Warning,
+                        notify: true,
+                        chat: true,
+                    },
+                    AlertRoute {
+                        source: None,
+                        min_level: NotificationLevel::Info,
+                        notify: true,
+                        chat: false,
+                    },
+                ],
+                repeat_secs: 600,
+            },
+            voice: VoiceConfig {
+                input_device: None,
+                output_device: None,
+                port: 0,
+                bitrate: 24_000,
+                jitter_ms: 60,
+                speaking_threshold: 0.02,
+            },
+            presence: PresenceConfig {
+                heartbeat_secs: 15,
+                away_secs: 300,
+                offline_secs: 60,
+                typing_secs: 5,
+            },
+            shells: ShellsConfig {
+                available: ["zsh", "bash", "fish", "pwsh", "nu"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                profiles: HashMap::new(),
+                projects: HashMap::new(),
+                connections: BTreeMap::new(),
             },
             docker: DockerConfig {
                 enabled: true,
@@ -280,6 +1504,7 @@ impl Default for WarpConfig {
                 default_registry: "docker.io".to_string(),
                 auto_pull_images: false,
                 cleanup_containers: true,
+                run: ContainerRunConfig::default(),
             },
             gpu: GPUConfig {
                 enabled: true,
@@ -294,6 +1519,11 @@ impl Default for WarpConfig {
                 timeout: 10,
                 allowed_imports: vec!["wasi_snapshot_preview1".to_string()],
                 sandbox_level: "strict".to_string(),
+                dev_plugins: Vec::new(),
+                dev_build_command: None,
+                max_calls_per_minute: 600,
+                max_cpu_ms_per_minute: 10_000,
+                kill_switch_ms: 3000,
             },
             keybindings: KeybindingConfig {
                 copy: "Ctrl+C".to_string(),
@@ -312,7 +1542,9 @@ impl Default for WarpConfig {
                 log_file: None,
                 performance_monitoring: false,
                 memory_profiling: false,
+                trace_sample_rate: trace_sample_rate(),
             },
+            commands: Vec::new(),
         }
     }
 }
@@ -328,11 +1560,11 @@ impl WarpConfig {
         if path.exists() {
             let content = fs::read_to_string(&path).await?;
             let config: WarpConfig = toml::from_str(&content)
-                .map_err(|e| WarpError::ConfigError(format!("Failed to parse config: {}", e)))?;
-            
+                .map_err(|e| WarpError::Config(format!("Failed to parse config: {}", e)))?;
+
             // Validate configuration
             validation::validate_config(&config)?;
-            
+
             Ok(config)
         } else {
             let default_config = Self::default();
@@ -347,15 +1579,15 @@ impl WarpConfig {
         }
 
         let content = toml::to_string_pretty(self)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to serialize config: {}", e)))?;
-        
+            .map_err(|e| WarpError::Config(format!("Failed to serialize config: {}", e)))?;
+
         fs::write(path, content).await?;
         Ok(())
     }
 
     fn default_config_path() -> Result<PathBuf, WarpError> {
         let config_dir = dirs::config_dir()
-            .ok_or_else(|| WarpError::ConfigError("Could not find config directory".to_string()))?;
+            .ok_or_else(|| WarpError::Config("Could not find config directory".to_string()))?;
         Ok(config_dir.join("warp").join("config.toml"))
     }
 }
@@ -0,0 +1,360 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveTime;
+use regex::Regex;
+
+use super::{StatusProvider, WarpConfig};
+use crate::error::WarpError;
+
+fn check(ok: bool, message: impl FnOnce() -> String) -> Result<(), WarpError> {
+    if ok {
+        Ok(())
+    } else {
+        Err(WarpError::Validation(message()))
+    }
+}
+
+fn check_pattern(what: &str, pattern: &str) -> Result<(), WarpError> {
+    Regex::new(pattern)
+        .map(|_| ())
+        .map_err(|e| WarpError::Validation(format!("Invalid {} '{}': {}", what, pattern, e)))
+}
+
+fn valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Values that deserialize fine but can't work: out-of-range numbers and
+// patterns that don't compile. Checked on load and before the settings
+// panel applies a change.
+pub fn validate_config(config: &WarpConfig) -> Result<(), WarpError> {
+    let ui = &config.ui;
+    check((6..=72).contains(&ui.font_size), || {
+        format!(
+            "ui.font_size must be between 6 and 72, got {}",
+            ui.font_size
+        )
+    })?;
+    check((0.0..=1.0).contains(&ui.opacity), || {
+        format!("ui.opacity must be between 0 and 1, got {}", ui.opacity)
+    })?;
+    check(config.accessibility.min_font_size <= ui.font_size, || {
+        "accessibility.min_font_size can't be larger than ui.font_size".to_string()
+    })?;
+
+    let terminal = &config.terminal;
+    check(!terminal.shell.trim().is_empty(), || {
+        "terminal.shell can't be empty".to_string()
+    })?;
+    check(terminal.scrollback_lines > 0, || {
+        "terminal.scrollback_lines must be at least 1".to_string()
+    })?;
+    // The screen is drawn from the lines that aren't paged out
+    let paging = &terminal.scrollback_paging;
+    check(
+        !paging.enabled || (paging.hot_lines >= 200 && paging.page_lines > 0),
+        || {
+            "terminal.scrollback_paging needs hot_lines of at least 200 and page_lines of at least 1"
+                .to_string()
+        },
+    )?;
+    check(
+        ["block", "underline", "bar"].contains(&terminal.cursor_style.as_str()),
+        || {
+            format!(
+                "terminal.cursor_style must be block, underline or bar, got '{}'",
+                terminal.cursor_style
+            )
+        },
+    )?;
+
+    let ai = &config.ai;
+    check((0.0..=2.0).contains(&ai.temperature), || {
+        format!(
+            "ai.temperature must be between 0 and 2, got {}",
+            ai.temperature
+        )
+    })?;
+    check(ai.max_tokens > 0, || {
+        "ai.max_tokens must be at least 1".to_string()
+    })?;
+
+    let reconnect = &config.ssh.reconnect;
+    check(reconnect.backoff_multiplier >= 1.0, || {
+        "ssh.reconnect.backoff_multiplier must be at least 1".to_string()
+    })?;
+    check(
+        reconnect.initial_backoff_ms <= reconnect.max_backoff_ms,
+        || "ssh.reconnect.initial_backoff_ms can't exceed max_backoff_ms".to_string(),
+    )?;
+
+    let api = &config.api;
+    check(
+        api.authentication.jwt_secret.is_empty() || api.authentication.jwt_secret.len() >= 32,
+        || {
+            "api.authentication.jwt_secret must be at least 32 bytes, or empty to generate one"
+                .to_string()
+        },
+    )?;
+    if api.enabled {
+        api.server.validate()?;
+    }
+
+    check(config.workflows.max_concurrent_workflows > 0, || {
+        "workflows.max_concurrent_workflows must be at least 1".to_string()
+    })?;
+    check(config.gpu.max_fps > 0, || {
+        "gpu.max_fps must be at least 1".to_string()
+    })?;
+    check(config.forge.max_items > 0, || {
+        "forge.max_items must be at least 1".to_string()
+    })?;
+
+    let output = &config.output;
+    check(
+        !output.truncate || output.head_lines + output.tail_lines > 0,
+        || "output.head_lines and output.tail_lines can't both be 0 while truncating".to_string(),
+    )?;
+    check(output.max_line_bytes >= 80, || {
+        "output.max_line_bytes must be at least 80".to_string()
+    })?;
+
+    check(config.startup.budget_ms > 0, || {
+        "startup.budget_ms must be at least 1".to_string()
+    })?;
+
+    for segment in &config.status_bar.segments {
+        check(segment.refresh_secs > 0, || {
+            format!(
+                "status_bar.segments: {:?} refresh_secs must be at least 1",
+                segment.provider
+            )
+        })?;
+        if let StatusProvider::Time(format) = &segment.provider {
+            check(
+                !StrftimeItems::new(format).any(|item| item == Item::Error),
+                || format!("status_bar.segments: invalid time format '{}'", format),
+            )?;
+        }
+    }
+
+    check(config.memory.sample_secs > 0, || {
+        "memory.sample_secs must be at least 1".to_string()
+    })?;
+    check(config.notebooks.cell_timeout_secs > 0, || {
+        "notebooks.cell_timeout_secs must be at least 1".to_string()
+    })?;
+    check(config.runbooks.step_timeout_secs > 0, || {
+        "runbooks.step_timeout_secs must be at least 1".to_string()
+    })?;
+
+    let notifications = &config.notifications;
+    check(notifications.toast_secs > 0, || {
+        "notifications.toast_secs must be at least 1".to_string()
+    })?;
+    if let Some(hours) = &notifications.do_not_disturb {
+        for time in [&hours.start, &hours.end] {
+            check(NaiveTime::parse_from_str(time, "%H:%M").is_ok(), || {
+                format!(
+                    "notifications.do_not_disturb times must be HH:MM, got '{}'",
+                    time
+                )
+            })?;
+        }
+    }
+
+    for rule in &config.security.command_rules {
+        check_pattern("security rule", &rule.pattern)?;
+    }
+    for pattern in config
+        .security
+        .escapes
+        .remote_commands
+        .iter()
+        .chain(&config.security.escapes.untrusted_commands)
+    {
+        check_pattern("trust pattern", pattern)?;
+    }
+    for rule in &config.security.paste.warnings {
+        check_pattern("paste warning", &rule.pattern)?;
+    }
+    for pattern in &config.terminal.activity.focus_patterns {
+        check_pattern("focus pattern", pattern)?;
+    }
+    for matcher in &config.problems.matchers {
+        check_pattern("problem matcher", &matcher.pattern)?;
+    }
+    for rule in config
+        .watchdog
+        .rules
+        .iter()
+        .chain(config.watchdog.projects.values().flatten())
+    {
+        check_pattern("watchdog rule", &rule.pattern)?;
+    }
+    let cloud = &config.cloud;
+    for pattern in cloud
+        .production_patterns
+        .iter()
+        .chain(&cloud.destructive_commands)
+    {
+        check_pattern("cloud pattern", pattern)?;
+    }
+    for (language, server) in &config.completions.language_servers {
+        check(!server.enabled || !server.command.trim().is_empty(), || {
+            format!("completions.language_servers.{} needs a command", language)
+        })?;
+    }
+    let run = &config.docker.run;
+    check(
+        run.runtime
+            .as_deref()
+            .is_none_or(|runtime| ["podman", "docker"].contains(&runtime)),
+        || "docker.run.runtime must be podman or docker".to_string(),
+    )?;
+    check(!run.image.trim().is_empty(), || {
+        "docker.run.image can't be empty".to_string()
+    })?;
+    check(run.timeout_secs > 0, || {
+        "docker.run.timeout_secs must be at least 1".to_string()
+    })?;
+    let shells = &config.shells;
+    check(
+        shells
+            .available
+            .iter()
+            .all(|shell| !shell.trim().is_empty()),
+        || "shells.available must not contain empty names".to_string(),
+    )?;
+    for (name, profile) in &shells.profiles {
+        for var in profile.env.keys().chain(&profile.unset) {
+            check(valid_env_name(var), || {
+                format!(
+                    "shells.profiles.{} has an invalid variable name '{}'",
+                    name, var
+                )
+            })?;
+        }
+    }
+    for (root, defaults) in &shells.projects {
+        for pattern in defaults
+            .container
+            .iter()
+            .flat_map(|policy| &policy.commands)
+        {
+            check_pattern("container command pattern", pattern)?;
+        }
+        if let Some(profile) = &defaults.profile {
+            check(shells.profiles.contains_key(profile), || {
+                format!(
+                    "shells.projects '{}' uses unknown profile '{}'",
+                    root.display(),
+                    profile
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(validate_config(&WarpConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_values() {
+        let mut config = WarpConfig::default();
+        config.ui.opacity = 1.5;
+        assert!(matches!(
+            validate_config(&config),
+            Err(WarpError::Validation(_))
+        ));
+
+        let mut config = WarpConfig::default();
+        config.terminal.cursor_style = "beam".to_string();
+        assert!(validate_config(&config).is_err());
+
+        let mut config = WarpConfig::default();
+        config.notifications.do_not_disturb = Some(crate::config::QuietHours {
+            start: "10pm".to_string(),
+            end: "07:00".to_string(),
+        });
+        assert!(validate_config(&config).is_err());
+
+        let mut config = WarpConfig::default();
+        config.status_bar.segments[3].provider =
+            crate::config::StatusProvider::Time("%H:%Q".to_string());
+        assert!(validate_config(&config).is_err());
+
+        let mut config = WarpConfig::default();
+        config
+            .problems
+            .matchers
+            .push(crate::config::ProblemMatcherConfig {
+                name: "broken".to_string(),
+                pattern: "(?P<file>".to_string(),
+                severity: crate::config::ProblemSeverity::Error,
+            });
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_rejects_unknown_shell_profile() {
+        let mut config = WarpConfig::default();
+        config.shells.projects.insert(
+            "/work/api".into(),
+            crate::config::PaneDefaults {
+                shell: Some("fish".to_string()),
+                profile: Some("staging".to_string()),
+                encoding: None,
+                container: None,
+            },
+        );
+        assert!(validate_config(&config).is_err());
+
+        let mut profile = crate::config::EnvironmentProfile::default();
+        profile.env.insert("1BAD".to_string(), "x".to_string());
+        config
+            .shells
+            .profiles
+            .insert("staging".to_string(), profile);
+        assert!(validate_config(&config).is_err());
+
+        config
+            .shells
+            .profiles
+            .get_mut("staging")
+            .unwrap()
+            .env
+            .clear();
+        assert!(validate_config(&config).is_ok());
+
+        config
+            .shells
+            .projects
+            .get_mut(std::path::Path::new("/work/api"))
+            .unwrap()
+            .container = Some(crate::config::ContainerPolicy {
+            commands: vec!["(".to_string()],
+            ..Default::default()
+        });
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_rejects_unknown_container_runtime() {
+        let mut config = WarpConfig::default();
+        config.docker.run.runtime = Some("lxc".to_string());
+        assert!(validate_config(&config).is_err());
+        config.docker.run.runtime = Some("podman".to_string());
+        assert!(validate_config(&config).is_ok());
+    }
+}
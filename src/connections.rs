@@ -0,0 +1,270 @@
+use crossterm::style::Color;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::config::{ConnectionProfile, SSHConfig};
+use crate::i18n;
+use crate::shell::PaneRequest;
+use crate::ssh::SshTarget;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// What opening a connection does: a tab from `request`, then `input` typed
+// into it one line at a time. With `ssh` set, the tab's session is supervised
+// by the SSH session manager and `input` goes to the remote shell instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub request: PaneRequest,
+    pub ssh: Option<SshTarget>,
+    pub input: Vec<String>,
+    pub accent: Option<Color>,
+    pub label: String, // for the header, e.g. "staging → deploy@stage-1"
+}
+
+pub fn plan(name: &str, connection: &ConnectionProfile, ssh: &SSHConfig, cwd: PathBuf) -> Launch {
+    let mut request = PaneRequest::new(connection.cwd.clone().unwrap_or(cwd));
+    request.profile = connection.profile.clone();
+    // The connection says exactly what it wants
+    request.project_defaults = false;
+
+    let (target, input, label) = match &connection.host {
+        // Reconnects and gap recovery need the session outside the shell
+        Some(host) if ssh.enabled && ssh.reconnect.enabled => {
+            let target = SshTarget::parse(host, ssh.default_user.as_deref());
+            let label = format!("{} → {}", name, target.destination());
+            (Some(target), connection.startup_commands.clone(), label)
+        }
+        Some(host) => {
+            let target = SshTarget::parse(host, ssh.default_user.as_deref());
+            let label = format!("{} → {}", name, target.destination());
+            (
+                None,
+                vec![ssh_command(&target, ssh, &connection.startup_commands)],
+                label,
+            )
+        }
+        None => (None, connection.startup_commands.clone(), name.to_string()),
+    };
+    Launch {
+        request,
+        ssh: target,
+        input,
+        accent: connection.accent.as_deref().and_then(parse_accent),
+        label,
+    }
+}
+
+// The startup commands go to the remote shell in the same ssh invocation,
+// which then stays on as a login shell
+fn ssh_command(target: &SshTarget, ssh: &SSHConfig, startup: &[String]) -> String {
+    let mut command = format!("ssh -o ConnectTimeout={}", ssh.connection_timeout);
+    if let Some(port) = target.port {
+        command.push_str(&format!(" -p {}", port));
+    }
+    if startup.is_empty() {
+        command.push_str(&format!(" {}", quote(&target.destination())));
+        return command;
+    }
+    let remote = format!("{}; exec \"$SHELL\" -l", startup.join("; "));
+    command.push_str(&format!(
+        " -t {} {}",
+        quote(&target.destination()),
+        quote(&remote)
+    ));
+    command
+}
+
+fn quote(text: &str) -> String {
+    if !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+:@%,".contains(c))
+    {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+// "#rrggbb" or one of crossterm's names ("cyan", "dark_magenta", ...)
+pub fn parse_accent(text: &str) -> Option<Color> {
+    match text.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            Some(Color::Rgb {
+                r: (value >> 16) as u8,
+                g: (value >> 8) as u8,
+                b: value as u8,
+            })
+        }
+        Some(_) => None,
+        None => Color::try_from(text).ok(),
+    }
+}
+
+pub enum ConnectionCommand {
+    None,
+    Open(String),
+}
+
+pub struct ConnectionsPanel {
+    connections: Vec<(String, ConnectionProfile)>,
+    selected: usize,
+}
+
+impl ConnectionsPanel {
+    pub fn new(connections: &BTreeMap<String, ConnectionProfile>) -> Self {
+        Self {
+            connections: connections
+                .iter()
+                .map(|(name, c)| (name.clone(), c.clone()))
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.connections.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> ConnectionCommand {
+        self.select(index);
+        match (key, self.connections.get(self.selected)) {
+            ('\n', Some((name, _))) => ConnectionCommand::Open(name.clone()),
+            _ => ConnectionCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .connections
+            .iter()
+            .map(|(name, connection)| {
+                let accent = connection.accent.as_deref().and_then(parse_accent);
+                let host = connection
+                    .host
+                    .clone()
+                    .unwrap_or_else(|| i18n::t("connections.local"));
+                PanelItem::styled(vec![
+                    ("● ".to_string(), accent),
+                    (format!("{:<20}", name), None),
+                    (host, Some(Color::DarkGrey)),
+                ])
+            })
+            .collect();
+        let preview = match self.connections.get(self.selected) {
+            Some((_, connection)) => {
+                let none = i18n::t("connections.none");
+                let mut rows: Vec<PanelItem> = vec![i18n::t_args(
+                    "connections.profile",
+                    &[("profile", connection.profile.as_deref().unwrap_or(&none))],
+                )
+                .into()];
+                if let Some(cwd) = &connection.cwd {
+                    rows.push(
+                        i18n::t_args("connections.cwd", &[("cwd", &cwd.display().to_string())])
+                            .into(),
+                    );
+                }
+                rows.extend(
+                    connection
+                        .startup_commands
+                        .iter()
+                        .map(|command| format!("$ {}", command).into()),
+                );
+                rows
+            }
+            None => vec![i18n::t("connections.empty").into()],
+        };
+        PanelView {
+            kind: PanelKind::Connections,
+            title: i18n::t("panel.connections"),
+            items,
+            footer: Some(i18n::t("panel.connections_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+
+    fn staging() -> ConnectionProfile {
+        ConnectionProfile {
+            host: Some("stage-1:2222".to_string()),
+            profile: Some("staging".to_string()),
+            cwd: None,
+            startup_commands: vec![
+                "cd /srv/app".to_string(),
+                "tail -n 50 log/app.log".to_string(),
+            ],
+            accent: Some("#ff8800".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_plan_remote_connection() {
+        let mut ssh = WarpConfig::default().ssh;
+        ssh.default_user = Some("deploy".to_string());
+        ssh.reconnect.enabled = false;
+        let launch = plan("staging", &staging(), &ssh, PathBuf::from("/home/me"));
+
+        assert_eq!(launch.request.profile.as_deref(), Some("staging"));
+        assert_eq!(launch.request.cwd, PathBuf::from("/home/me"));
+        assert!(!launch.request.project_defaults);
+        assert_eq!(launch.label, "staging → deploy@stage-1");
+        assert_eq!(
+            launch.accent,
+            Some(Color::Rgb {
+                r: 255,
+                g: 136,
+                b: 0
+            })
+        );
+        assert_eq!(
+            launch.input,
+            vec![format!(
+                "ssh -o ConnectTimeout={} -p 2222 -t deploy@stage-1 'cd /srv/app; tail -n 50 log/app.log; exec \"$SHELL\" -l'",
+                ssh.connection_timeout
+            )]
+        );
+    }
+
+    #[test]
+    fn test_plan_supervised_connection() {
+        let ssh = WarpConfig::default().ssh;
+        let launch = plan("staging", &staging(), &ssh, PathBuf::from("/home/me"));
+
+        let target = launch.ssh.expect("reconnect is on by default");
+        assert_eq!(target.destination(), "stage-1");
+        assert_eq!(target.port, Some(2222));
+        assert_eq!(launch.input, staging().startup_commands);
+    }
+
+    #[test]
+    fn test_plan_local_connection_and_accents() {
+        let local = ConnectionProfile {
+            cwd: Some(PathBuf::from("/work/infra")),
+            startup_commands: vec!["terraform workspace select prod".to_string()],
+            accent: Some("red".to_string()),
+            ..ConnectionProfile::default()
+        };
+        let launch = plan(
+            "infra",
+            &local,
+            &WarpConfig::default().ssh,
+            PathBuf::from("/home/me"),
+        );
+        assert_eq!(launch.request.cwd, PathBuf::from("/work/infra"));
+        assert_eq!(
+            launch.input,
+            vec!["terraform workspace select prod".to_string()]
+        );
+        assert_eq!(launch.accent, Some(Color::Red));
+
+        assert_eq!(parse_accent("#12"), None);
+        assert_eq!(parse_accent("chartreuse"), None);
+        assert_eq!(parse_accent("Dark_Cyan"), Some(Color::DarkCyan));
+    }
+}
@@ -0,0 +1,270 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::command_not_found::find_in_path;
+use crate::config::{ContainerPolicy, DockerConfig};
+use crate::error::WarpError;
+use crate::file_manager::shell_quote;
+use crate::headless::{self, RunResult};
+use crate::i18n;
+
+// Where the directory is mounted inside the container
+const WORKDIR: &str = "/work";
+const RUNTIMES: [&str; 2] = ["podman", "docker"];
+
+// `@ cargo test` runs in the configured image, `@node:20 npm test` in node:20
+pub fn parse_prefix(line: &str) -> Option<(Option<&str>, &str)> {
+    let (image, command) = line.strip_prefix('@')?.split_once(char::is_whitespace)?;
+    let command = command.trim();
+    if command.is_empty() {
+        return None;
+    }
+    Some(((!image.is_empty()).then_some(image), command))
+}
+
+// One command in a throwaway container with the directory mounted as its
+// working directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerRun {
+    pub runtime: String,
+    pub image: String,
+    pub directory: PathBuf,
+    pub command: String,
+    pub network: bool,
+    pub pull: bool, // always, rather than only when missing
+    pub remove: bool,
+    pub owner: Option<(u32, u32)>, // of the directory, so files written to it aren't root's
+}
+
+impl ContainerRun {
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec!["run".to_string()];
+        if self.remove {
+            args.push("--rm".to_string());
+        }
+        args.extend([
+            "--pull".to_string(),
+            if self.pull { "always" } else { "missing" }.to_string(),
+        ]);
+        if !self.network {
+            args.extend(["--network".to_string(), "none".to_string()]);
+        }
+        // Rootless podman maps the user through; docker needs to be told
+        match (self.runtime.as_str(), self.owner) {
+            ("podman", _) => args.extend(["--userns".to_string(), "keep-id".to_string()]),
+            (_, Some((uid, gid))) => {
+                args.extend(["--user".to_string(), format!("{}:{}", uid, gid)])
+            }
+            _ => {}
+        }
+        args.extend([
+            "--volume".to_string(),
+            format!("{}:{}", self.directory.display(), WORKDIR),
+        ]);
+        args.extend(["--workdir".to_string(), WORKDIR.to_string()]);
+        args.extend([
+            self.image.clone(),
+            "sh".to_string(),
+            "-c".to_string(),
+            self.command.clone(),
+        ]);
+        args
+    }
+
+    // The whole invocation, quoted for the user's shell
+    pub fn script(&self) -> String {
+        std::iter::once(self.runtime.clone())
+            .chain(self.args())
+            .map(|arg| shell_quote(Path::new(&arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Whether `line` runs in a container and how: an `@` prefix always does,
+// otherwise the project's policy decides
+pub fn plan(
+    line: &str,
+    config: &DockerConfig,
+    policy: Option<&ContainerPolicy>,
+    directory: &Path,
+) -> Result<Option<ContainerRun>, WarpError> {
+    let (image, command) = match parse_prefix(line) {
+        Some((image, command)) => (image.map(str::to_string), command),
+        None => match policy {
+            Some(policy) if contains(policy, line)? => (None, line.trim()),
+            _ => return Ok(None),
+        },
+    };
+    if !config.enabled {
+        return Err(WarpError::config_err(i18n::t("container.disabled")));
+    }
+    let runtime = match &config.run.runtime {
+        Some(runtime) => runtime.clone(),
+        None => RUNTIMES
+            .iter()
+            .find(|runtime| find_in_path(runtime).is_some())
+            .ok_or_else(|| WarpError::config_err(i18n::t("container.no_runtime")))?
+            .to_string(),
+    };
+    let image = image
+        .or_else(|| policy.and_then(|policy| policy.image.clone()))
+        .unwrap_or_else(|| config.run.image.clone());
+    Ok(Some(ContainerRun {
+        runtime,
+        image: qualify(&image, &config.default_registry),
+        directory: directory.to_path_buf(),
+        command: command.to_string(),
+        network: policy
+            .and_then(|policy| policy.network)
+            .unwrap_or(config.run.network),
+        pull: config.auto_pull_images,
+        remove: config.cleanup_containers,
+        owner: owner(directory),
+    }))
+}
+
+fn contains(policy: &ContainerPolicy, line: &str) -> Result<bool, WarpError> {
+    if policy.commands.is_empty() {
+        return Ok(true);
+    }
+    for pattern in &policy.commands {
+        let regex = Regex::new(pattern).map_err(|e| {
+            WarpError::config_err(format!(
+                "Invalid container command pattern '{}': {}",
+                pattern, e
+            ))
+        })?;
+        if regex.is_match(line) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Podman won't guess the registry of a short name without a terminal to
+// ask on
+fn qualify(image: &str, registry: &str) -> String {
+    let host = image.split_once('/').map(|(host, _)| host);
+    let has_registry = host.is_some_and(|host| host.contains(['.', ':']) || host == "localhost");
+    if has_registry || registry.is_empty() {
+        image.to_string()
+    } else {
+        format!("{}/{}", registry.trim_end_matches('/'), image)
+    }
+}
+
+#[cfg(unix)]
+fn owner(directory: &Path) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(directory).ok()?;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_directory: &Path) -> Option<(u32, u32)> {
+    None
+}
+
+// Through the user's shell like a notebook cell, so the runtime is found
+// the same way it would be at the prompt
+pub async fn run(
+    run: &ContainerRun,
+    shell: &str,
+    timeout: Duration,
+) -> Result<RunResult, WarpError> {
+    let mut result = headless::run(shell, &run.script(), Some(timeout)).await?;
+    result.command = run.command.clone();
+    Ok(result)
+}
+
+// The block's lines: output, then errors, then how it ended unless it
+// succeeded
+pub fn result_lines(result: &RunResult) -> Vec<String> {
+    let mut lines: Vec<String> = result
+        .stdout
+        .lines()
+        .chain(result.stderr.lines())
+        .map(str::to_string)
+        .collect();
+    match result.exit_code {
+        Some(0) => {}
+        Some(code) => lines.push(i18n::t_args(
+            "container.exit_code",
+            &[("code", &code.to_string())],
+        )),
+        None if result.timed_out => {
+            let secs = (result.duration_ms / 1000).to_string();
+            lines.push(i18n::t_args("container.timed_out", &[("secs", &secs)]));
+        }
+        None => lines.push(i18n::t("container.killed")),
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+
+    fn config() -> DockerConfig {
+        let mut config = WarpConfig::default().docker;
+        config.run.runtime = Some("docker".to_string());
+        config
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        assert_eq!(parse_prefix("@ cargo test"), Some((None, "cargo test")));
+        assert_eq!(
+            parse_prefix("@node:20 npm test "),
+            Some((Some("node:20"), "npm test"))
+        );
+        assert_eq!(parse_prefix("@node:20"), None);
+        assert_eq!(parse_prefix("@ "), None);
+        assert_eq!(parse_prefix("echo @ home"), None);
+    }
+
+    #[test]
+    fn test_plan_prefix_and_policy() {
+        let directory = Path::new("/nonexistent/app");
+        let run = plan("@ make check", &config(), None, directory)
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.image, "docker.io/debian:stable-slim");
+        assert_eq!(
+            run.script(),
+            "docker run --rm --pull missing --volume /nonexistent/app:/work --workdir /work \
+             docker.io/debian:stable-slim sh -c 'make check'"
+        );
+        assert_eq!(
+            plan("make check", &config(), None, directory).unwrap(),
+            None
+        );
+
+        let policy = ContainerPolicy {
+            image: Some("ghcr.io/acme/build:1".to_string()),
+            commands: vec![r"^(make|cargo)\b".to_string()],
+            network: Some(false),
+        };
+        let run = plan("cargo test", &config(), Some(&policy), directory)
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.image, "ghcr.io/acme/build:1");
+        assert!(run.script().contains("--network none"));
+        assert_eq!(
+            plan("ls", &config(), Some(&policy), directory).unwrap(),
+            None
+        );
+        // The prefix picks its own image even where a policy applies
+        let run = plan("@alpine ls", &config(), Some(&policy), directory)
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.image, "docker.io/alpine");
+
+        let mut disabled = config();
+        disabled.enabled = false;
+        assert!(plan("@ ls", &disabled, None, directory).is_err());
+    }
+}
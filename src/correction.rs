@@ -0,0 +1,204 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::command_not_found::is_executable;
+
+// Rc files that commonly define aliases; missing ones are skipped
+const ALIAS_FILES: &[&str] = &[
+    ".bashrc",
+    ".bash_aliases",
+    ".zshrc",
+    ".zsh_aliases",
+    ".config/fish/config.fish",
+];
+
+// Optimal string alignment distance: like Levenshtein, but swapping two
+// adjacent characters (gti -> git) counts as one edit
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+// Short names are close to too many other short names, so the bar rises
+// with length: two-letter typos only match their own transposition
+fn is_plausible(typo: &str, candidate: &str, distance: usize) -> bool {
+    match typo.chars().count() {
+        0..=2 => {
+            let mut a: Vec<char> = typo.chars().collect();
+            let mut b: Vec<char> = candidate.chars().collect();
+            a.sort_unstable();
+            b.sort_unstable();
+            distance == 1 && a == b
+        }
+        3..=5 => distance == 1,
+        _ => distance <= 2,
+    }
+}
+
+// How often each command name starts a history entry
+pub fn history_counts(commands: &[String]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for command in commands {
+        if let Some(name) = command.split_whitespace().next() {
+            *counts.entry(name.to_string()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Names of every executable on PATH
+pub fn path_binaries() -> HashSet<String> {
+    let Some(path) = std::env::var_os("PATH") else {
+        return HashSet::new();
+    };
+    std::env::split_paths(&path)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+fn alias_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        // bash/zsh `alias gs='git status'`, fish `alias gs 'git status'` and `abbr -a gs ...`
+        Regex::new(r"(?m)^\s*(?:alias|abbr(?:\s+-a|\s+--add)?)\s+([\w.+-]+)[=\s]")
+            .expect("static pattern")
+    })
+}
+
+pub fn parse_aliases(rc: &str) -> Vec<String> {
+    alias_pattern()
+        .captures_iter(rc)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+pub fn shell_aliases() -> HashSet<String> {
+    let Some(home) = dirs::home_dir() else {
+        return HashSet::new();
+    };
+    ALIAS_FILES
+        .iter()
+        .filter_map(|file| std::fs::read_to_string(home.join(file)).ok())
+        .flat_map(|rc| parse_aliases(&rc))
+        .collect()
+}
+
+// Likely intended commands for `typo`, best first: fewest edits, then the
+// ones used most in history
+pub fn rank(
+    typo: &str,
+    candidates: &HashSet<String>,
+    history: &HashMap<String, usize>,
+) -> Vec<String> {
+    let mut matches: Vec<(usize, usize, &String)> = candidates
+        .iter()
+        .filter(|candidate| candidate.as_str() != typo)
+        .filter_map(|candidate| {
+            let distance = edit_distance(typo, candidate);
+            is_plausible(typo, candidate, distance).then(|| {
+                (
+                    distance,
+                    history.get(candidate).copied().unwrap_or(0),
+                    candidate,
+                )
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(b.2)));
+    matches
+        .into_iter()
+        .map(|(_, _, name)| name.clone())
+        .collect()
+}
+
+// Blocks on directory reads; callers on the event loop should run it with
+// spawn_blocking
+pub fn suggest(typo: &str, history: &HashMap<String, usize>) -> Vec<String> {
+    let mut candidates = path_binaries();
+    candidates.extend(shell_aliases());
+    candidates.extend(history.keys().cloned());
+    rank(typo, &candidates, history)
+}
+
+// `line` with its first word `typo` replaced; None when the line doesn't
+// start with the typo (it failed somewhere in a pipeline or script)
+pub fn correct_command(line: &str, typo: &str, fixed: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.strip_prefix(typo)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(format!("{}{}", fixed, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("dokcer", "docker"), 1);
+        assert_eq!(edit_distance("carg", "cargo"), 1);
+        assert_eq!(edit_distance("kubeclt", "kubectl"), 1);
+        assert_eq!(edit_distance("python", "pyhton3"), 2);
+        assert_eq!(edit_distance("abc", "xyz"), 3);
+    }
+
+    #[test]
+    fn test_rank_prefers_history() {
+        let candidates = set(&["git", "gio", "gtk", "ls", "rm"]);
+        let history = history_counts(&[
+            "git status".to_string(),
+            "git push".to_string(),
+            "gio open x".to_string(),
+        ]);
+        assert_eq!(rank("gti", &candidates, &history), vec!["git", "gtk"]);
+        assert_eq!(rank("gi", &candidates, &history), Vec::<String>::new());
+        assert_eq!(rank("sl", &candidates, &history), vec!["ls"]);
+        assert_eq!(rank("gto", &candidates, &history), vec!["gio", "gtk"]);
+    }
+
+    #[test]
+    fn test_parse_aliases_and_correct_command() {
+        let rc =
+            "alias gs='git status'\n  alias ll=\"ls -la\"\nabbr -a gco git checkout\nexport X=1\n";
+        assert_eq!(parse_aliases(rc), vec!["gs", "ll", "gco"]);
+
+        assert_eq!(
+            correct_command("gti push -f", "gti", "git").as_deref(),
+            Some("git push -f")
+        );
+        assert_eq!(correct_command("gtix push", "gti", "git"), None);
+        assert_eq!(correct_command("echo | gti", "gti", "git"), None);
+    }
+}
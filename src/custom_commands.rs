@@ -0,0 +1,154 @@
+use clap::{Arg, ArgMatches, Command};
+use std::path::Path;
+
+use crate::{config::CustomCommand, error::WarpError, file_manager::shell_quote};
+
+// Everything after the declared arguments, passed through to the command
+const TRAILING_ARGS: &str = "trailing-args";
+
+// Adds `commands` as subcommands of `cli`. A name taken by a built-in or an
+// earlier custom command is skipped and returned so the caller can warn.
+pub fn register(mut cli: Command, commands: &[CustomCommand]) -> (Command, Vec<String>) {
+    let mut skipped = Vec::new();
+    for custom in commands {
+        let valid = !custom.name.is_empty()
+            && !custom.name.starts_with('-')
+            && custom
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+        if !valid || custom.name == "help" || cli.find_subcommand(&custom.name).is_some() {
+            skipped.push(custom.name.clone());
+            continue;
+        }
+        cli = cli.subcommand(subcommand(custom));
+    }
+    (cli, skipped)
+}
+
+fn subcommand(custom: &CustomCommand) -> Command {
+    let about = if custom.about.is_empty() {
+        format!("Runs `{}`", custom.run)
+    } else {
+        custom.about.clone()
+    };
+    let mut command = Command::new(custom.name.clone()).about(about);
+    for arg in &custom.args {
+        let mut clap_arg = Arg::new(arg.name.clone())
+            .help(arg.help.clone())
+            .required(arg.required && arg.default.is_none());
+        if let Some(default) = &arg.default {
+            clap_arg = clap_arg.default_value(default.clone());
+        }
+        command = command.arg(clap_arg);
+    }
+    command.arg(
+        Arg::new(TRAILING_ARGS)
+            .num_args(0..)
+            .trailing_var_arg(true)
+            .allow_hyphen_values(true)
+            .hide(true),
+    )
+}
+
+pub fn command_line(custom: &CustomCommand, matches: &ArgMatches) -> String {
+    let quote = |value: &String| shell_quote(Path::new(value));
+    let mut line = custom.run.clone();
+    for arg in &custom.args {
+        let value = matches
+            .get_one::<String>(&arg.name)
+            .map(quote)
+            .unwrap_or_default();
+        line = line.replace(&format!("{{{}}}", arg.name), &value);
+    }
+    let trailing: Vec<String> = matches
+        .get_many::<String>(TRAILING_ARGS)
+        .into_iter()
+        .flatten()
+        .map(quote)
+        .collect();
+    if !trailing.is_empty() {
+        line.push(' ');
+        line.push_str(&trailing.join(" "));
+    }
+    line
+}
+
+// Runs the command through the configured shell with the terminal's stdio
+// and returns its exit code
+pub async fn run(
+    custom: &CustomCommand,
+    matches: &ArgMatches,
+    shell: &str,
+) -> Result<i32, WarpError> {
+    let line = command_line(custom, matches);
+    let flag = if shell.contains("powershell") || shell.contains("pwsh") {
+        "-Command"
+    } else {
+        "-c"
+    };
+    let status = tokio::process::Command::new(shell)
+        .arg(flag)
+        .arg(&line)
+        .env("WARP_SUBCOMMAND", &custom.name)
+        .status()
+        .await
+        .map_err(|e| {
+            WarpError::command_err(format!(
+                "Failed to run '{}' with {}: {}",
+                custom.name, shell, e
+            ))
+        })?;
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CustomArg;
+
+    fn deploy() -> CustomCommand {
+        CustomCommand {
+            name: "deploy".to_string(),
+            about: "Deploy the current branch".to_string(),
+            run: "./scripts/deploy.sh --env {env}".to_string(),
+            args: vec![CustomArg {
+                name: "env".to_string(),
+                help: "Target environment".to_string(),
+                required: false,
+                default: Some("staging".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_register_skips_clashes() {
+        let cli = Command::new("warp").subcommand(Command::new("ports"));
+        let mut ports = deploy();
+        ports.name = "ports".to_string();
+        let (cli, skipped) = register(cli, &[deploy(), ports, deploy()]);
+        assert_eq!(skipped, vec!["ports", "deploy"]);
+        assert!(cli.find_subcommand("deploy").is_some());
+    }
+
+    #[test]
+    fn test_command_line_substitutes_and_forwards() {
+        let (cli, _) = register(Command::new("warp"), &[deploy()]);
+        let matches = cli
+            .try_get_matches_from(["warp", "deploy", "prod eu", "--dry-run"])
+            .unwrap();
+        let (_, args) = matches.subcommand().unwrap();
+        assert_eq!(
+            command_line(&deploy(), args),
+            "./scripts/deploy.sh --env 'prod eu' --dry-run"
+        );
+
+        let (cli, _) = register(Command::new("warp"), &[deploy()]);
+        let matches = cli.try_get_matches_from(["warp", "deploy"]).unwrap();
+        let (_, args) = matches.subcommand().unwrap();
+        assert_eq!(
+            command_line(&deploy(), args),
+            "./scripts/deploy.sh --env staging"
+        );
+    }
+}
@@ -1,23 +1,18 @@
-use std::collections::HashMap;
+use crate::alerts::{Alert, AlertBus};
+use crate::config::{AlertSource, NotificationLevel};
+use crate::error::WarpError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
 
-pub mod definitions;
-pub mod collectors;
-pub mod processors;
-pub mod validators;
-pub mod aggregators;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Data points are held in memory per metric, trimmed to the definition's
+// raw_data_retention, and aggregated when queried
 pub struct CustomMetricsManager {
     metric_definitions: Arc<Mutex<HashMap<String, MetricDefinition>>>,
-    collectors: Arc<Mutex<HashMap<String, Box<dyn MetricCollector>>>>,
-    processors: Arc<processors::MetricProcessor>,
-    validators: Arc<validators::MetricValidator>,
-    aggregators: Arc<aggregators::MetricAggregator>,
+    data_points: Arc<Mutex<HashMap<String, Vec<MetricDataPoint>>>>,
     active_metrics: Arc<Mutex<HashMap<String, ActiveMetric>>>,
+    alert_bus: Option<AlertBus>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,7 +61,10 @@ pub enum CollectionMethod {
     Pull,
     Event,
     Calculated,
-    External { endpoint: String, interval: chrono::Duration },
+    External {
+        endpoint: String,
+        interval: chrono::Duration,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,7 +117,7 @@ pub enum ValidationErrorAction {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetentionPolicy {
     pub raw_data_retention: chrono::Duration,
-    pub aggregated_data_retention: HashMap<AggregationType, chrono::Duration>,
+    pub aggregated_data_retention: Vec<(AggregationType, chrono::Duration)>,
     pub compression_enabled: bool,
     pub archival_storage: Option<ArchivalConfig>,
 }
@@ -202,11 +200,23 @@ pub enum AlertSeverity {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NotificationChannel {
-    Email { recipients: Vec<String> },
-    Slack { webhook_url: String, channel: String },
-    Discord { webhook_url: String },
-    Webhook { url: String, headers: HashMap<String, String> },
-    SMS { phone_numbers: Vec<String> },
+    Email {
+        recipients: Vec<String>,
+    },
+    Slack {
+        webhook_url: String,
+        channel: String,
+    },
+    Discord {
+        webhook_url: String,
+    },
+    Webhook {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+    SMS {
+        phone_numbers: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -219,7 +229,7 @@ pub struct ActiveMetric {
     pub status: MetricStatus,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MetricValue {
     Integer(i64),
     Float(f64),
@@ -299,136 +309,161 @@ pub struct AggregatedDataPoint {
     pub sample_count: u64,
 }
 
-pub trait MetricCollector: Send + Sync {
-    async fn collect(&self, metric_id: &str) -> Result<Vec<MetricDataPoint>, WarpError>;
-    fn collection_interval(&self) -> chrono::Duration;
-    fn supports_metric_type(&self, metric_type: &MetricType) -> bool;
-}
-
 impl CustomMetricsManager {
     pub async fn new() -> Result<Self, WarpError> {
         Ok(Self {
             metric_definitions: Arc::new(Mutex::new(HashMap::new())),
-            collectors: Arc::new(Mutex::new(HashMap::new())),
-            processors: Arc::new(processors::MetricProcessor::new().await?),
-            validators: Arc::new(validators::MetricValidator::new().await?),
-            aggregators: Arc::new(aggregators::MetricAggregator::new().await?),
+            data_points: Arc::new(Mutex::new(HashMap::new())),
             active_metrics: Arc::new(Mutex::new(HashMap::new())),
+            alert_bus: None,
         })
     }
 
+    // Triggered alerts are also raised in the terminal, routed per config
+    pub fn set_alert_bus(&mut self, bus: AlertBus) {
+        self.alert_bus = Some(bus);
+    }
+
     pub async fn define_metric(&self, definition: MetricDefinition) -> Result<String, WarpError> {
-        // Validate the metric definition
-        self.validators.validate_definition(&definition).await?;
+        validate_definition(&definition)?;
 
         let metric_id = definition.id.clone();
-        
+
         // Store the definition
         {
             let mut definitions = self.metric_definitions.lock().await;
-            definitions.insert(metric_id.clone(), definition.clone());
+            definitions.insert(metric_id.clone(), definition);
         }
 
         // Initialize active metric
         {
             let mut active_metrics = self.active_metrics.lock().await;
-            active_metrics.insert(metric_id.clone(), ActiveMetric {
-                metric_id: metric_id.clone(),
-                current_value: MetricValue::Float(0.0),
-                last_updated: chrono::Utc::now(),
-                collection_count: 0,
-                error_count: 0,
-                status: MetricStatus::Active,
-            });
-        }
-
-        // Set up collection if needed
-        if definition.enabled {
-            self.setup_collection(&definition).await?;
+            active_metrics.insert(
+                metric_id.clone(),
+                ActiveMetric {
+                    metric_id: metric_id.clone(),
+                    current_value: MetricValue::Float(0.0),
+                    last_updated: chrono::Utc::now(),
+                    collection_count: 0,
+                    error_count: 0,
+                    status: MetricStatus::Active,
+                },
+            );
         }
 
         Ok(metric_id)
     }
 
-    pub async fn update_metric_definition(&self, metric_id: &str, definition: MetricDefinition) -> Result<(), WarpError> {
-        self.validators.validate_definition(&definition).await?;
+    pub async fn update_metric_definition(
+        &self,
+        metric_id: &str,
+        definition: MetricDefinition,
+    ) -> Result<(), WarpError> {
+        validate_definition(&definition)?;
 
         let mut definitions = self.metric_definitions.lock().await;
         if definitions.contains_key(metric_id) {
             definitions.insert(metric_id.to_string(), definition);
             Ok(())
         } else {
-            Err(WarpError::ConfigError(format!("Metric not found: {}", metric_id)))
+            Err(WarpError::not_found("metric", metric_id))
         }
     }
 
     pub async fn delete_metric(&self, metric_id: &str) -> Result<(), WarpError> {
-        // Remove from definitions
-        {
-            let mut definitions = self.metric_definitions.lock().await;
-            definitions.remove(metric_id);
-        }
-
-        // Remove from active metrics
-        {
-            let mut active_metrics = self.active_metrics.lock().await;
-            active_metrics.remove(metric_id);
-        }
-
-        // Remove collector
-        {
-            let mut collectors = self.collectors.lock().await;
-            collectors.remove(metric_id);
-        }
-
+        self.metric_definitions.lock().await.remove(metric_id);
+        self.active_metrics.lock().await.remove(metric_id);
+        self.data_points.lock().await.remove(metric_id);
         Ok(())
     }
 
+    // Points for undefined metrics are kept too, so integrations can push
+    // before anyone has written a definition
     pub async fn record_metric(&self, data_point: MetricDataPoint) -> Result<(), WarpError> {
-        // Validate the data point
-        self.validators.validate_data_point(&data_point).await?;
-
-        // Process the data point
-        let processed_point = self.processors.process_data_point(data_point).await?;
+        let definition = self
+            .metric_definitions
+            .lock()
+            .await
+            .get(&data_point.metric_id)
+            .cloned();
+        if let Some(definition) = &definition {
+            if !definition.enabled {
+                return Ok(());
+            }
+            if let Err(e) = validate_data_point(definition, &data_point) {
+                if let Some(active_metric) = self
+                    .active_metrics
+                    .lock()
+                    .await
+                    .get_mut(&data_point.metric_id)
+                {
+                    active_metric.error_count += 1;
+                }
+                return Err(e);
+            }
+        }
 
         // Update active metric
         {
             let mut active_metrics = self.active_metrics.lock().await;
-            if let Some(active_metric) = active_metrics.get_mut(&processed_point.metric_id) {
-                active_metric.current_value = processed_point.value.clone();
-                active_metric.last_updated = processed_point.timestamp;
+            if let Some(active_metric) = active_metrics.get_mut(&data_point.metric_id) {
+                active_metric.current_value = data_point.value.clone();
+                active_metric.last_updated = data_point.timestamp;
                 active_metric.collection_count += 1;
             }
         }
 
-        // Store the data point
-        self.aggregators.store_data_point(processed_point).await?;
+        // Store the data point, in time order
+        let mut data_points = self.data_points.lock().await;
+        let series = data_points.entry(data_point.metric_id.clone()).or_default();
+        if let Some(definition) = &definition {
+            let cutoff = chrono::Utc::now() - definition.retention_policy.raw_data_retention;
+            series.retain(|point| point.timestamp >= cutoff);
+        }
+        let index = series.partition_point(|point| point.timestamp <= data_point.timestamp);
+        series.insert(index, data_point);
 
         Ok(())
     }
 
     pub async fn query_metric(&self, query: MetricQuery) -> Result<MetricQueryResult, WarpError> {
         let start_time = std::time::Instant::now();
-        
-        // Execute the query
-        let data_points = self.aggregators.query_data_points(&query).await?;
-        
-        let query_duration = start_time.elapsed();
-        
+
+        let data_points = {
+            let data_points = self.data_points.lock().await;
+            aggregate_points(
+                data_points
+                    .get(&query.metric_id)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+                &query,
+            )
+        };
+        let total_count = data_points.len() as u64;
+        let data_points = data_points
+            .into_iter()
+            .skip(query.offset.unwrap_or(0) as usize)
+            .take(query.limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect();
+
         Ok(MetricQueryResult {
             metric_id: query.metric_id,
             data_points,
-            total_count: data_points.len() as u64,
-            query_duration,
+            total_count,
+            query_duration: start_time.elapsed(),
             metadata: HashMap::new(),
         })
     }
 
-    pub async fn get_metric_definition(&self, metric_id: &str) -> Result<MetricDefinition, WarpError> {
+    pub async fn get_metric_definition(
+        &self,
+        metric_id: &str,
+    ) -> Result<MetricDefinition, WarpError> {
         let definitions = self.metric_definitions.lock().await;
-        definitions.get(metric_id)
+        definitions
+            .get(metric_id)
             .cloned()
-            .ok_or_else(|| WarpError::ConfigError(format!("Metric not found: {}", metric_id)))
+            .ok_or_else(|| WarpError::not_found("metric", metric_id))
     }
 
     pub async fn list_metrics(&self) -> Result<Vec<MetricDefinition>, WarpError> {
@@ -438,173 +473,429 @@ impl CustomMetricsManager {
 
     pub async fn get_metric_status(&self, metric_id: &str) -> Result<ActiveMetric, WarpError> {
         let active_metrics = self.active_metrics.lock().await;
-        active_metrics.get(metric_id)
+        active_metrics
+            .get(metric_id)
             .cloned()
-            .ok_or_else(|| WarpError::ConfigError(format!("Active metric not found: {}", metric_id)))
+            .ok_or_else(|| WarpError::not_found("active metric", metric_id))
     }
 
     pub async fn enable_metric(&self, metric_id: &str) -> Result<(), WarpError> {
-        let mut definitions = self.metric_definitions.lock().await;
-        if let Some(definition) = definitions.get_mut(metric_id) {
-            definition.enabled = true;
-            self.setup_collection(definition).await?;
-        }
-        Ok(())
+        self.set_enabled(metric_id, true).await
     }
 
     pub async fn disable_metric(&self, metric_id: &str) -> Result<(), WarpError> {
-        let mut definitions = self.metric_definitions.lock().await;
-        if let Some(definition) = definitions.get_mut(metric_id) {
-            definition.enabled = false;
-            
-            // Remove collector
-            let mut collectors = self.collectors.lock().await;
-            collectors.remove(metric_id);
-        }
-        Ok(())
-    }
-
-    pub async fn register_collector(&self, metric_id: &str, collector: Box<dyn MetricCollector>) -> Result<(), WarpError> {
-        let mut collectors = self.collectors.lock().await;
-        collectors.insert(metric_id.to_string(), collector);
-        Ok(())
-    }
-
-    pub async fn start_collection(&self) -> Result<(), WarpError> {
-        let collectors = self.collectors.lock().await;
-        
-        for (metric_id, collector) in collectors.iter() {
-            let metric_id = metric_id.clone();
-            let interval = collector.collection_interval();
-            
-            // Start collection task
-            tokio::spawn(async move {
-                let mut interval_timer = tokio::time::interval(interval.to_std().unwrap_or(std::time::Duration::from_secs(60)));
-                
-                loop {
-                    interval_timer.tick().await;
-                    
-                    // Collect metrics
-                    if let Ok(data_points) = collector.collect(&metric_id).await {
-                        for data_point in data_points {
-                            // In a real implementation, send to processing pipeline
-                            log::debug!("Collected metric data point: {:?}", data_point);
-                        }
-                    }
-                }
-            });
-        }
-        
-        Ok(())
-    }
-
-    async fn setup_collection(&self, definition: &MetricDefinition) -> Result<(), WarpError> {
-        match &definition.collection_method {
-            CollectionMethod::Pull => {
-                // Set up pull-based collection
-                let collector = collectors::PullCollector::new(&definition.id).await?;
-                self.register_collector(&definition.id, Box::new(collector)).await?;
-            }
-            CollectionMethod::Event => {
-                // Set up event-based collection
-                let collector = collectors::EventCollector::new(&definition.id).await?;
-                self.register_collector(&definition.id, Box::new(collector)).await?;
-            }
-            CollectionMethod::External { endpoint, interval } => {
-                // Set up external collection
-                let collector = collectors::ExternalCollector::new(&definition.id, endpoint, *interval).await?;
-                self.register_collector(&definition.id, Box::new(collector)).await?;
-            }
-            _ => {
-                // For push and calculated methods, no automatic collection setup needed
-            }
-        }
-        
-        Ok(())
+        self.set_enabled(metric_id, false).await
     }
 
-    pub async fn calculate_derived_metrics(&self) -> Result<(), WarpError> {
-        let definitions = self.metric_definitions.lock().await;
-        
-        for definition in definitions.values() {
-            if matches!(definition.collection_method, CollectionMethod::Calculated) {
-                // Calculate derived metric value
-                let calculated_value = self.calculate_metric_value(definition).await?;
-                
-                let data_point = MetricDataPoint {
-                    metric_id: definition.id.clone(),
-                    value: calculated_value,
-                    dimensions: HashMap::new(),
-                    timestamp: chrono::Utc::now(),
-                    source: "calculated".to_string(),
-                    metadata: HashMap::new(),
-                };
-                
-                self.record_metric(data_point).await?;
-            }
+    async fn set_enabled(&self, metric_id: &str, enabled: bool) -> Result<(), WarpError> {
+        let mut definitions = self.metric_definitions.lock().await;
+        let definition = definitions
+            .get_mut(metric_id)
+            .ok_or_else(|| WarpError::not_found("metric", metric_id))?;
+        definition.enabled = enabled;
+        if let Some(active_metric) = self.active_metrics.lock().await.get_mut(metric_id) {
+            active_metric.status = if enabled {
+                MetricStatus::Active
+            } else {
+                MetricStatus::Paused
+            };
         }
-        
         Ok(())
     }
 
-    async fn calculate_metric_value(&self, _definition: &MetricDefinition) -> Result<MetricValue, WarpError> {
-        // In a real implementation, this would evaluate the calculation expression
-        // For now, return a mock calculated value
-        Ok(MetricValue::Float(42.0))
-    }
-
     pub async fn trigger_alerts(&self) -> Result<(), WarpError> {
         let definitions = self.metric_definitions.lock().await;
         let active_metrics = self.active_metrics.lock().await;
-        
+
         for definition in definitions.values() {
             if let Some(active_metric) = active_metrics.get(&definition.id) {
                 for alert in &definition.alerts {
                     if alert.enabled {
-                        let should_trigger = self.evaluate_alert_condition(alert, active_metric).await?;
-                        
+                        let should_trigger =
+                            self.evaluate_alert_condition(alert, active_metric).await?;
+
                         if should_trigger {
-                            self.send_alert_notifications(alert, &definition.name, &active_metric.current_value).await?;
+                            self.send_alert_notifications(
+                                alert,
+                                &definition.name,
+                                &active_metric.current_value,
+                            )
+                            .await?;
                         }
                     }
                 }
             }
         }
-        
+
         Ok(())
     }
 
-    async fn evaluate_alert_condition(&self, alert: &MetricAlert, active_metric: &ActiveMetric) -> Result<bool, WarpError> {
+    async fn evaluate_alert_condition(
+        &self,
+        alert: &MetricAlert,
+        active_metric: &ActiveMetric,
+    ) -> Result<bool, WarpError> {
         let current_value = match &active_metric.current_value {
             MetricValue::Float(v) => *v,
             MetricValue::Integer(v) => *v as f64,
             _ => return Ok(false),
         };
-        
+
         match alert.condition {
             AlertCondition::GreaterThan => Ok(current_value > alert.threshold.value),
             AlertCondition::LessThan => Ok(current_value < alert.threshold.value),
-            AlertCondition::Equals => Ok((current_value - alert.threshold.value).abs() < f64::EPSILON),
-            AlertCondition::NotEquals => Ok((current_value - alert.threshold.value).abs() > f64::EPSILON),
+            AlertCondition::Equals => {
+                Ok((current_value - alert.threshold.value).abs() < f64::EPSILON)
+            }
+            AlertCondition::NotEquals => {
+                Ok((current_value - alert.threshold.value).abs() > f64::EPSILON)
+            }
             _ => Ok(false), // Other conditions would be implemented
         }
     }
 
-    async fn send_alert_notifications(&self, alert: &MetricAlert, metric_name: &str, current_value: &MetricValue) -> Result<(), WarpError> {
+    async fn send_alert_notifications(
+        &self,
+        alert: &MetricAlert,
+        metric_name: &str,
+        current_value: &MetricValue,
+    ) -> Result<(), WarpError> {
+        if let Some(bus) = &self.alert_bus {
+            let level = match alert.threshold.severity {
+                AlertSeverity::Critical => NotificationLevel::Error,
+                AlertSeverity::Warning => NotificationLevel::Warning,
+                AlertSeverity::Info => NotificationLevel::Info,
+            };
+            bus.publish(
+                Alert::new(
+                    AlertSource::Metrics,
+                    level,
+                    format!("{}: {}", metric_name, alert.name),
+                )
+                .with_body(format!(
+                    "{:?} {:?} {}",
+                    current_value, alert.condition, alert.threshold.value
+                )),
+            );
+        }
         for channel in &alert.notification_channels {
             match channel {
                 NotificationChannel::Email { recipients } => {
-                    log::info!("Sending email alert to {:?} for metric {} with value {:?}", recipients, metric_name, current_value);
+                    log::info!(
+                        "Sending email alert to {:?} for metric {} with value {:?}",
+                        recipients,
+                        metric_name,
+                        current_value
+                    );
                 }
-                NotificationChannel::Slack { webhook_url, channel } => {
-                    log::info!("Sending Slack alert to {} ({}) for metric {} with value {:?}", channel, webhook_url, metric_name, current_value);
+                NotificationChannel::Slack {
+                    webhook_url,
+                    channel,
+                } => {
+                    log::info!(
+                        "Sending Slack alert to {} ({}) for metric {} with value {:?}",
+                        channel,
+                        webhook_url,
+                        metric_name,
+                        current_value
+                    );
                 }
                 _ => {
-                    log::info!("Sending alert notification for metric {} with value {:?}", metric_name, current_value);
+                    log::info!(
+                        "Sending alert notification for metric {} with value {:?}",
+                        metric_name,
+                        current_value
+                    );
                 }
             }
         }
-        
+
         Ok(())
     }
 }
+
+fn validate_definition(definition: &MetricDefinition) -> Result<(), WarpError> {
+    if definition.id.trim().is_empty() || definition.name.trim().is_empty() {
+        return Err(WarpError::Validation(
+            "A metric needs an id and a name".to_string(),
+        ));
+    }
+    for rule in &definition.validation_rules {
+        if let ValidationRuleType::Range { min, max } = rule.rule_type {
+            if min > max {
+                return Err(WarpError::Validation(format!(
+                    "Metric {} has a range rule with min above max",
+                    definition.id
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Only Reject rules refuse a point; the other actions log it and keep it
+fn validate_data_point(
+    definition: &MetricDefinition,
+    data_point: &MetricDataPoint,
+) -> Result<(), WarpError> {
+    for rule in &definition.validation_rules {
+        let problem = match &rule.rule_type {
+            ValidationRuleType::Range { min, max } => numeric(&data_point.value)
+                .filter(|value| value < min || value > max)
+                .map(|value| format!("{} is outside {}..={}", value, min, max)),
+            ValidationRuleType::DataType => (!matches!(
+                (&definition.data_type, &data_point.value),
+                (MetricDataType::Integer, MetricValue::Integer(_))
+                    | (
+                        MetricDataType::Float,
+                        MetricValue::Float(_) | MetricValue::Integer(_)
+                    )
+                    | (MetricDataType::Boolean, MetricValue::Boolean(_))
+                    | (MetricDataType::String, MetricValue::String(_))
+                    | (MetricDataType::JSON, _)
+            ))
+            .then(|| format!("{:?} is not {:?}", data_point.value, definition.data_type)),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            if matches!(rule.error_action, ValidationErrorAction::Reject) {
+                return Err(WarpError::Validation(format!(
+                    "Metric {}: {}",
+                    definition.id, problem
+                )));
+            }
+            log::warn!("Metric {}: {}", definition.id, problem);
+        }
+    }
+    Ok(())
+}
+
+fn numeric(value: &MetricValue) -> Option<f64> {
+    match value {
+        MetricValue::Integer(v) => Some(*v as f64),
+        MetricValue::Float(v) => Some(*v),
+        MetricValue::Boolean(v) => Some(if *v { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+fn filter_matches(filter: &MetricFilter, dimensions: &HashMap<String, String>) -> bool {
+    let value = dimensions
+        .get(&filter.dimension)
+        .map(String::as_str)
+        .unwrap_or_default();
+    let mut listed = filter.value.split(',').map(str::trim);
+    match filter.operator {
+        FilterOperator::Equals => value == filter.value,
+        FilterOperator::NotEquals => value != filter.value,
+        FilterOperator::Contains => value.contains(filter.value.as_str()),
+        FilterOperator::StartsWith => value.starts_with(filter.value.as_str()),
+        FilterOperator::EndsWith => value.ends_with(filter.value.as_str()),
+        FilterOperator::In => listed.any(|item| item == value),
+        FilterOperator::NotIn => !listed.any(|item| item == value),
+    }
+}
+
+// A group's dimension values, in group_by order
+type Labels = Vec<(String, String)>;
+
+// Buckets numeric points in the range by interval (one bucket without
+// one) and by the group_by dimensions, then aggregates each; Average if
+// the query names no aggregation
+fn aggregate_points(series: &[MetricDataPoint], query: &MetricQuery) -> Vec<AggregatedDataPoint> {
+    let range = &query.time_range;
+    let interval = range
+        .interval
+        .filter(|interval| interval.num_milliseconds() > 0);
+    let mut buckets: BTreeMap<(chrono::DateTime<chrono::Utc>, Labels), Vec<f64>> = BTreeMap::new();
+    for point in series {
+        if point.timestamp < range.start || point.timestamp >= range.end {
+            continue;
+        }
+        if !query
+            .filters
+            .iter()
+            .all(|filter| filter_matches(filter, &point.dimensions))
+        {
+            continue;
+        }
+        let Some(value) = numeric(&point.value) else {
+            continue;
+        };
+        let bucket = match interval {
+            Some(interval) => {
+                let width = interval.num_milliseconds();
+                let index = (point.timestamp - range.start).num_milliseconds() / width;
+                range.start + chrono::Duration::milliseconds(index * width)
+            }
+            None => range.start,
+        };
+        let group = query
+            .group_by
+            .iter()
+            .map(|dimension| {
+                (
+                    dimension.clone(),
+                    point.dimensions.get(dimension).cloned().unwrap_or_default(),
+                )
+            })
+            .collect();
+        buckets.entry((bucket, group)).or_default().push(value);
+    }
+
+    let width = interval.unwrap_or(range.end - range.start);
+    let aggregation = query
+        .aggregation
+        .clone()
+        .unwrap_or(AggregationType::Average);
+    buckets
+        .into_iter()
+        .map(|((timestamp, group), values)| AggregatedDataPoint {
+            timestamp,
+            value: aggregate(&aggregation, &values, width),
+            dimensions: group.into_iter().collect(),
+            sample_count: values.len() as u64,
+        })
+        .collect()
+}
+
+// Values are in time order; Rate is per second over the bucket
+fn aggregate(aggregation: &AggregationType, values: &[f64], width: chrono::Duration) -> f64 {
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+    let percentile = |p: f64| {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let rank = ((p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64).round() as usize;
+        sorted[rank]
+    };
+    match aggregation {
+        AggregationType::Sum => values.iter().sum(),
+        AggregationType::Average => mean,
+        AggregationType::Count => count,
+        AggregationType::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+        AggregationType::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        AggregationType::Median => percentile(50.0),
+        AggregationType::Percentile(p) => percentile(*p),
+        AggregationType::StandardDeviation => variance.sqrt(),
+        AggregationType::Variance => variance,
+        AggregationType::Rate => count / (width.num_milliseconds().max(1) as f64 / 1000.0),
+        AggregationType::Delta => values[values.len() - 1] - values[0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn point(minute: i64, value: f64, host: &str) -> MetricDataPoint {
+        MetricDataPoint {
+            metric_id: "latency".to_string(),
+            value: MetricValue::Float(value),
+            dimensions: HashMap::from([("host".to_string(), host.to_string())]),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+                + Duration::minutes(minute),
+            source: "test".to_string(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn query(
+        aggregation: AggregationType,
+        group_by: &[&str],
+        filters: Vec<MetricFilter>,
+    ) -> MetricQuery {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        MetricQuery {
+            metric_id: "latency".to_string(),
+            time_range: TimeRange {
+                start,
+                end: start + Duration::minutes(20),
+                interval: Some(Duration::minutes(10)),
+            },
+            aggregation: Some(aggregation),
+            group_by: group_by.iter().map(|d| d.to_string()).collect(),
+            filters,
+            limit: None,
+            offset: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_buckets_groups_and_pages() {
+        let manager = CustomMetricsManager::new().await.unwrap();
+        for p in [
+            point(1, 10.0, "a"),
+            point(2, 30.0, "b"),
+            point(12, 5.0, "a"),
+            point(25, 99.0, "a"),
+        ] {
+            manager.record_metric(p).await.unwrap();
+        }
+
+        let result = manager
+            .query_metric(query(AggregationType::Average, &[], Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(result.total_count, 2);
+        assert_eq!(
+            result
+                .data_points
+                .iter()
+                .map(|p| p.value)
+                .collect::<Vec<_>>(),
+            [20.0, 5.0]
+        );
+
+        let mut grouped = query(AggregationType::Max, &["host"], Vec::new());
+        grouped.limit = Some(1);
+        grouped.offset = Some(1);
+        let result = manager.query_metric(grouped).await.unwrap();
+        assert_eq!(result.total_count, 3);
+        assert_eq!(result.data_points[0].dimensions["host"], "b");
+        assert_eq!(result.data_points[0].value, 30.0);
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_aggregations() {
+        let series = [
+            point(1, 1.0, "a"),
+            point(2, 2.0, "web-1"),
+            point(3, 4.0, "web-2"),
+            point(4, 9.0, "db"),
+        ];
+        let filters = vec![MetricFilter {
+            dimension: "host".to_string(),
+            operator: FilterOperator::StartsWith,
+            value: "web".to_string(),
+        }];
+        let result = aggregate_points(&series, &query(AggregationType::Sum, &[], filters));
+        assert_eq!(result[0].value, 6.0);
+
+        let filters = vec![MetricFilter {
+            dimension: "host".to_string(),
+            operator: FilterOperator::NotIn,
+            value: "a, db".to_string(),
+        }];
+        assert_eq!(
+            aggregate_points(&series, &query(AggregationType::Count, &[], filters))[0].value,
+            2.0
+        );
+
+        let values = [1.0, 2.0, 4.0, 9.0];
+        assert_eq!(
+            aggregate(&AggregationType::Median, &values, Duration::minutes(1)),
+            4.0
+        );
+        assert_eq!(
+            aggregate(&AggregationType::Delta, &values, Duration::minutes(1)),
+            8.0
+        );
+        assert_eq!(
+            aggregate(&AggregationType::Rate, &values, Duration::seconds(2)),
+            2.0
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::error::WarpError;
+use crate::package::Manifest;
+
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+#[derive(Debug, Clone, Default)]
+pub struct BuildConfig {
+    // Run through sh instead of cargo; it must leave the entry point in place
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildResult {
+    pub success: bool,
+    pub output: String, // stdout and stderr of the build
+    pub artifact: PathBuf,
+    pub duration: Duration,
+}
+
+pub struct Builder;
+
+impl Builder {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self)
+    }
+
+    pub async fn build_item(
+        &self,
+        item_path: &str,
+        build_config: &BuildConfig,
+    ) -> Result<BuildResult, WarpError> {
+        build_plugin(Path::new(item_path), build_config).await
+    }
+}
+
+// The module cargo writes for a crate, by the crate's name in Cargo.toml
+async fn cargo_artifact(dir: &Path) -> Result<PathBuf, WarpError> {
+    let text = tokio::fs::read_to_string(dir.join("Cargo.toml")).await?;
+    let table: toml::Table = toml::from_str(&text)?;
+    let name = table
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| WarpError::config_err("Cargo.toml has no package name"))?;
+    Ok(dir
+        .join("target")
+        .join(WASM_TARGET)
+        .join("release")
+        .join(format!("{}.wasm", name.replace('-', "_"))))
+}
+
+// Builds a plugin package's entry point. With no command configured a cargo
+// crate is built for wasm and its module copied over the entry point; a
+// failed build is a result with success false, not an error.
+pub async fn build_plugin(dir: &Path, config: &BuildConfig) -> Result<BuildResult, WarpError> {
+    let manifest = Manifest::load(dir).await?;
+    let entry_point = manifest
+        .entry_point
+        .as_deref()
+        .ok_or_else(|| WarpError::Validation(format!("{} has no entry_point", manifest.id)))?;
+    let artifact = dir.join(entry_point);
+
+    let mut command = match &config.command {
+        Some(line) => {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(line);
+            command
+        }
+        None if dir.join("Cargo.toml").exists() => {
+            let mut command = Command::new("cargo");
+            command.args(["build", "--release", "--target", WASM_TARGET]);
+            command
+        }
+        None => {
+            return Err(WarpError::config_err(format!(
+                "Don't know how to build {}; set wasm.dev_build_command",
+                dir.display()
+            )))
+        }
+    };
+
+    let started = Instant::now();
+    let output = command
+        .current_dir(dir)
+        .output()
+        .await
+        .map_err(|e| WarpError::command_err(format!("Failed to start the build: {}", e)))?;
+    let mut log = String::from_utf8_lossy(&output.stdout).into_owned();
+    log.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    let mut success = output.status.success();
+    if success && config.command.is_none() {
+        let built = cargo_artifact(dir).await?;
+        if let Err(e) = tokio::fs::copy(&built, &artifact).await {
+            log.push_str(&format!("\ncouldn't copy {}: {}", built.display(), e));
+            success = false;
+        }
+    }
+    Ok(BuildResult {
+        success,
+        output: log,
+        artifact,
+        duration: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::{scaffold, PackageKind};
+
+    #[tokio::test]
+    async fn test_runs_the_configured_command() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Plugin, "sample")
+            .await
+            .unwrap();
+
+        let config = BuildConfig {
+            command: Some("printf '\\0asm' > plugin.wasm && echo built".to_string()),
+        };
+        let result = build_plugin(dir.path(), &config).await.unwrap();
+        assert!(result.success, "{}", result.output);
+        assert_eq!(result.output.trim(), "built");
+        assert!(std::fs::read(&result.artifact)
+            .unwrap()
+            .starts_with(b"\0asm"));
+
+        let config = BuildConfig {
+            command: Some("echo nope >&2; exit 1".to_string()),
+        };
+        let result = build_plugin(dir.path(), &config).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.output.trim(), "nope");
+
+        assert!(build_plugin(dir.path(), &BuildConfig::default())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Local};
+use crossterm::style::Color;
+use std::collections::VecDeque;
+
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Oldest entries drop off past this
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleLevel {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsoleEntry {
+    pub at: DateTime<Local>,
+    pub plugin: String,
+    pub level: ConsoleLevel,
+    pub summary: String,
+    pub detail: Vec<String>, // build output, the full error
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleCommand {
+    None,
+    Redraw,
+}
+
+// Builds, reloads and plugin log lines for plugins under development, newest
+// last; the preview shows the selected entry's full output
+#[derive(Default)]
+pub struct DevConsole {
+    entries: VecDeque<ConsoleEntry>,
+    selected: usize,
+}
+
+impl DevConsole {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, plugin: &str, level: ConsoleLevel, summary: String, detail: &str) {
+        self.entries.push_back(ConsoleEntry {
+            at: Local::now(),
+            plugin: plugin.to_string(),
+            level,
+            summary,
+            detail: detail.lines().map(str::to_string).collect(),
+        });
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn info(&mut self, plugin: &str, summary: String) {
+        self.push(plugin, ConsoleLevel::Info, summary, "");
+    }
+
+    pub fn error(&mut self, plugin: &str, summary: String, detail: &str) {
+        self.push(plugin, ConsoleLevel::Error, summary, detail);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ConsoleEntry> {
+        self.entries.iter()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> ConsoleCommand {
+        self.select(index);
+        match key {
+            'c' => {
+                self.entries.clear();
+                self.selected = 0;
+                ConsoleCommand::Redraw
+            }
+            _ => ConsoleCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let color = match entry.level {
+                    ConsoleLevel::Info => None,
+                    ConsoleLevel::Error => Some(Color::Red),
+                };
+                PanelItem::styled(vec![
+                    (
+                        format!("{} ", entry.at.format("%H:%M:%S")),
+                        Some(Color::DarkGrey),
+                    ),
+                    (format!("{} ", entry.plugin), Some(Color::Cyan)),
+                    (entry.summary.clone(), color),
+                ])
+            })
+            .collect();
+        let preview = match self.entries.get(self.selected) {
+            Some(entry) => entry
+                .detail
+                .iter()
+                .map(|line| line.clone().into())
+                .collect(),
+            None => vec![i18n::t("dev_console.empty").into()],
+        };
+        let errors = self
+            .entries
+            .iter()
+            .filter(|entry| entry.level == ConsoleLevel::Error)
+            .count();
+        PanelView {
+            kind: PanelKind::DevConsole,
+            title: i18n::t_args("panel.dev_console", &[("errors", &errors.to_string())]),
+            items,
+            footer: Some(i18n::t("panel.dev_console_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_and_clear() {
+        let mut console = DevConsole::new();
+        console.info("sample", "reloaded".to_string());
+        console.error(
+            "sample",
+            "build failed".to_string(),
+            "error[E0425]: cannot find value\n --> src/lib.rs:3:5",
+        );
+        console.select(1);
+        let view = console.view();
+        assert_eq!(view.items.len(), 2);
+        assert_eq!(view.preview.len(), 2);
+
+        for _ in 0..MAX_ENTRIES {
+            console.info("sample", "reloaded".to_string());
+        }
+        assert_eq!(console.entries().count(), MAX_ENTRIES);
+
+        assert_eq!(console.handle_key('c', 0), ConsoleCommand::Redraw);
+        assert_eq!(console.entries().count(), 0);
+        assert_eq!(console.handle_key('q', 0), ConsoleCommand::None);
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptLanguage {
+    Lua,
+    JavaScript,
+    Python,
+    Wasm,
+}
+
+impl ScriptLanguage {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "lua" => Some(Self::Lua),
+            "js" | "mjs" | "cjs" => Some(Self::JavaScript),
+            "py" => Some(Self::Python),
+            "wasm" => Some(Self::Wasm),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lua => "lua",
+            Self::JavaScript => "javascript",
+            Self::Python => "python",
+            Self::Wasm => "wasm",
+        }
+    }
+}
+
+// How to start a debug adapter and what to launch under it. `{program}` and
+// `{cwd}` in the launch arguments are filled in per session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdapterConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Connect here instead of spawning `command`, for adapters run as servers
+    #[serde(default)]
+    pub address: Option<String>,
+    pub launch: Value,
+}
+
+impl AdapterConfig {
+    pub fn default_for(language: ScriptLanguage) -> Self {
+        let (command, args, launch) = match language {
+            ScriptLanguage::Python => (
+                "python3",
+                vec!["-m", "debugpy.adapter"],
+                json!({ "type": "python", "request": "launch", "program": "{program}", "cwd": "{cwd}", "justMyCode": true }),
+            ),
+            ScriptLanguage::JavaScript => (
+                "js-debug-adapter",
+                vec![],
+                json!({ "type": "pwa-node", "request": "launch", "program": "{program}", "cwd": "{cwd}" }),
+            ),
+            ScriptLanguage::Lua => (
+                "local-lua-dbg",
+                vec![],
+                json!({ "type": "lua-local", "request": "launch", "program": { "lua": "lua", "file": "{program}" }, "cwd": "{cwd}" }),
+            ),
+            // WASM plugins run under wasmtime with DWARF so lldb can map
+            // them back to source
+            ScriptLanguage::Wasm => (
+                "lldb-dap",
+                vec![],
+                json!({ "request": "launch", "program": "wasmtime", "args": ["run", "-D", "debug-info", "{program}"], "cwd": "{cwd}" }),
+            ),
+        };
+        Self {
+            command: command.to_string(),
+            args: args.into_iter().map(String::from).collect(),
+            address: None,
+            launch,
+        }
+    }
+
+    pub fn launch_arguments(&self, program: &Path, cwd: &Path) -> Value {
+        substitute(
+            &self.launch,
+            &program.to_string_lossy(),
+            &cwd.to_string_lossy(),
+        )
+    }
+}
+
+fn substitute(value: &Value, program: &str, cwd: &str) -> Value {
+    match value {
+        Value::String(text) => {
+            Value::String(text.replace("{program}", program).replace("{cwd}", cwd))
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| substitute(item, program, cwd))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| (key.clone(), substitute(item, program, cwd)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_arguments_fill_nested_placeholders() {
+        let config = AdapterConfig::default_for(ScriptLanguage::Lua);
+        let launch = config.launch_arguments(Path::new("/p/main.lua"), Path::new("/p"));
+        assert_eq!(launch["program"]["file"], "/p/main.lua");
+        assert_eq!(launch["cwd"], "/p");
+        assert_eq!(
+            ScriptLanguage::from_path(Path::new("plugin.wasm")),
+            Some(ScriptLanguage::Wasm)
+        );
+        assert_eq!(ScriptLanguage::from_path(Path::new("README")), None);
+    }
+}
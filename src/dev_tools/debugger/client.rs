@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use super::protocol::{encode, FrameDecoder, Message};
+use crate::error::WarpError;
+
+// Adapters answer quickly unless they're launching the debuggee
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+type Writer = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+type Pending = Arc<std::sync::Mutex<HashMap<i64, oneshot::Sender<Message>>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterEvent {
+    pub event: String,
+    pub body: Value,
+}
+
+// A connection to one debug adapter. Responses are matched to requests by
+// sequence number; events arrive on the receiver returned at start.
+pub struct DapClient {
+    name: String,
+    writer: Writer,
+    seq: Arc<AtomicI64>,
+    pending: Pending,
+    child: Option<Child>,
+}
+
+impl DapClient {
+    // Runs an adapter that speaks DAP on its stdin/stdout
+    pub async fn spawn(
+        name: &str,
+        command: &str,
+        args: &[String],
+        cwd: &Path,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<AdapterEvent>), WarpError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                WarpError::plugin(
+                    name,
+                    format!("couldn't start debug adapter {}: {}", command, e),
+                )
+            })?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(WarpError::plugin(name, "debug adapter has no stdio"));
+        };
+        Ok(Self::start(name, stdout, Box::new(stdin), Some(child)))
+    }
+
+    // Connects to an adapter already listening on a TCP port
+    pub async fn connect(
+        name: &str,
+        address: &str,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<AdapterEvent>), WarpError> {
+        let stream = tokio::net::TcpStream::connect(address).await.map_err(|e| {
+            WarpError::plugin(
+                name,
+                format!("couldn't connect to debug adapter at {}: {}", address, e),
+            )
+        })?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self::start(name, reader, Box::new(writer), None))
+    }
+
+    fn start(
+        name: &str,
+        mut reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: Box<dyn AsyncWrite + Send + Unpin>,
+        child: Option<Child>,
+    ) -> (Self, mpsc::UnboundedReceiver<AdapterEvent>) {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let client = Self {
+            name: name.to_string(),
+            writer: Arc::new(Mutex::new(writer)),
+            seq: Arc::new(AtomicI64::new(1)),
+            pending: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            child,
+        };
+
+        let pending = client.pending.clone();
+        let writer = client.writer.clone();
+        let seq = client.seq.clone();
+        tokio::spawn(async move {
+            let mut decoder = FrameDecoder::new();
+            let mut chunk = vec![0u8; 8192];
+            loop {
+                let read = match reader.read(&mut chunk).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                for message in decoder.push(&chunk[..read]) {
+                    match message {
+                        Message::Response { request_seq, .. } => {
+                            if let Some(waiter) = pending.lock().unwrap().remove(&request_seq) {
+                                let _ = waiter.send(message);
+                            }
+                        }
+                        Message::Event { event, body, .. } => {
+                            let _ = events_tx.send(AdapterEvent { event, body });
+                        }
+                        // Reverse requests such as runInTerminal: we don't
+                        // support any, and the adapter falls back without them
+                        Message::Request {
+                            seq: request_seq,
+                            command,
+                            ..
+                        } => {
+                            let reply = Message::Response {
+                                seq: seq.fetch_add(1, Ordering::SeqCst),
+                                request_seq,
+                                success: false,
+                                command,
+                                message: Some("not supported".to_string()),
+                                body: Value::Null,
+                            };
+                            let _ = writer.lock().await.write_all(&encode(&reply)).await;
+                        }
+                    }
+                }
+            }
+            // Dropping the waiters fails any request still in flight
+            pending.lock().unwrap().clear();
+            let _ = events_tx.send(AdapterEvent {
+                event: "terminated".to_string(),
+                body: Value::Null,
+            });
+        });
+
+        (client, events_rx)
+    }
+
+    // Sends a request and returns the response body, or the adapter's error
+    pub async fn request(&self, command: &str, arguments: Value) -> Result<Value, WarpError> {
+        let seq = self.seq.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(seq, tx);
+        let request = Message::Request {
+            seq,
+            command: command.to_string(),
+            arguments,
+        };
+        {
+            let mut writer = self.writer.lock().await;
+            writer.write_all(&encode(&request)).await?;
+            writer.flush().await?;
+        }
+
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, rx).await;
+        self.pending.lock().unwrap().remove(&seq);
+        match response {
+            Ok(Ok(Message::Response {
+                success: true,
+                body,
+                ..
+            })) => Ok(body),
+            Ok(Ok(Message::Response { message, .. })) => Err(WarpError::plugin(
+                &self.name,
+                format!(
+                    "{} failed: {}",
+                    command,
+                    message.unwrap_or_else(|| "no reason given".to_string())
+                ),
+            )),
+            Ok(_) => Err(WarpError::plugin(&self.name, "debug adapter exited")),
+            Err(_) => Err(WarpError::plugin(
+                &self.name,
+                format!("{} timed out", command),
+            )),
+        }
+    }
+
+    pub async fn shutdown(&mut self) {
+        let _ = self
+            .request(
+                "disconnect",
+                serde_json::json!({ "terminateDebuggee": true }),
+            )
+            .await;
+        if let Some(child) = &mut self.child {
+            let _ = child.kill().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_matches_response_and_forwards_events() {
+        let (client_side, adapter_side) = tokio::io::duplex(4096);
+        let (reader, writer) = tokio::io::split(client_side);
+        let (client, mut events) = DapClient::start("test", reader, Box::new(writer), None);
+
+        // A fake adapter: answer the first request, then send an event
+        let (mut adapter_reader, mut adapter_writer) = tokio::io::split(adapter_side);
+        tokio::spawn(async move {
+            let mut decoder = FrameDecoder::new();
+            let mut chunk = vec![0u8; 1024];
+            loop {
+                let read = adapter_reader.read(&mut chunk).await.unwrap();
+                if let Some(Message::Request { seq, command, .. }) =
+                    decoder.push(&chunk[..read]).pop()
+                {
+                    let response = Message::Response {
+                        seq: 100,
+                        request_seq: seq,
+                        success: true,
+                        command,
+                        message: None,
+                        body: serde_json::json!({ "supportsConditionalBreakpoints": true }),
+                    };
+                    adapter_writer.write_all(&encode(&response)).await.unwrap();
+                    let event = Message::Event {
+                        seq: 101,
+                        event: "initialized".to_string(),
+                        body: Value::Null,
+                    };
+                    adapter_writer.write_all(&encode(&event)).await.unwrap();
+                    break;
+                }
+            }
+        });
+
+        let body = client
+            .request("initialize", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(body["supportsConditionalBreakpoints"], true);
+        assert_eq!(events.recv().await.unwrap().event, "initialized");
+    }
+}
@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, Mutex};
+
+use super::{DebugSession, DebugStatus, DebugVariable, DevToolsConfig, StackFrame, VariableScope};
+use crate::error::WarpError;
+use crate::package::Manifest;
+
+pub mod adapters;
+pub mod client;
+pub mod panel;
+pub mod protocol;
+
+pub use adapters::{AdapterConfig, ScriptLanguage};
+use client::{AdapterEvent, DapClient};
+
+// Frames whose variables are fetched when the debuggee stops; deeper frames
+// are listed without them
+const FRAMES_WITH_VARIABLES: usize = 5;
+const MAX_FRAMES: i64 = 50;
+// Debuggee output kept per session
+const MAX_OUTPUT_LINES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    Continue,
+    Next,
+    StepIn,
+    StepOut,
+    Pause,
+}
+
+impl StepKind {
+    fn command(&self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::Next => "next",
+            Self::StepIn => "stepIn",
+            Self::StepOut => "stepOut",
+            Self::Pause => "pause",
+        }
+    }
+}
+
+struct SourceBreakpoint {
+    id: String,
+    line: u32,
+    condition: Option<String>,
+}
+
+// One running adapter and what we know about the debuggee under it
+struct AdapterSession {
+    client: DapClient,
+    events: mpsc::UnboundedReceiver<AdapterEvent>,
+    // Events that arrived while waiting for something else during launch
+    early_events: Vec<AdapterEvent>,
+    breakpoints: HashMap<String, Vec<SourceBreakpoint>>,
+    // Adapter breakpoint id -> ours
+    adapter_ids: HashMap<i64, String>,
+    thread_id: Option<i64>,
+    // DAP frame ids of the last call stack, in the same order
+    frame_ids: Vec<i64>,
+    output: Vec<String>,
+}
+
+// Debugs plugin scripts and WASM plugins through the Debug Adapter Protocol
+// and mirrors the adapter's state into DebugSession
+pub struct Debugger {
+    config: Arc<Mutex<DevToolsConfig>>,
+    sessions: Mutex<HashMap<String, AdapterSession>>,
+    // Plugins under development, by item id; others are looked up in the
+    // installed plugin directory
+    item_dirs: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl Debugger {
+    pub async fn new(config: Arc<Mutex<DevToolsConfig>>) -> Result<Self, WarpError> {
+        Ok(Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            item_dirs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn register_item_dir(&self, item_id: &str, dir: PathBuf) {
+        self.item_dirs.lock().await.insert(item_id.to_string(), dir);
+    }
+
+    async fn item_dir(&self, item_id: &str) -> PathBuf {
+        match self.item_dirs.lock().await.get(item_id) {
+            Some(dir) => dir.clone(),
+            None => dirs::config_dir()
+                .unwrap_or_default()
+                .join("warp/plugins")
+                .join(item_id),
+        }
+    }
+
+    // Starts the adapter for the item's entry point and launches it. The
+    // debuggee runs until it hits a breakpoint set afterwards or is paused.
+    pub async fn attach_to_item(&self, item_id: &str, session_id: &str) -> Result<(), WarpError> {
+        let dir = self.item_dir(item_id).await;
+        let manifest = Manifest::load(&dir).await?;
+        let Some(entry_point) = manifest.entry_point.as_ref() else {
+            return Err(WarpError::Validation(format!(
+                "{} has no entry_point to debug",
+                manifest.id
+            )));
+        };
+        let program = dir.join(entry_point);
+        let Some(language) = ScriptLanguage::from_path(&program) else {
+            return Err(WarpError::Validation(format!(
+                "don't know how to debug {}",
+                entry_point
+            )));
+        };
+        let adapter = self
+            .config
+            .lock()
+            .await
+            .debug_adapters
+            .get(language.name())
+            .cloned()
+            .unwrap_or_else(|| AdapterConfig::default_for(language));
+
+        let (client, mut events) = match &adapter.address {
+            Some(address) => DapClient::connect(item_id, address).await?,
+            None => DapClient::spawn(item_id, &adapter.command, &adapter.args, &dir).await?,
+        };
+        client
+            .request(
+                "initialize",
+                json!({
+                    "clientID": "warp",
+                    "clientName": "Warp Terminal",
+                    "adapterID": language.name(),
+                    "linesStartAt1": true,
+                    "columnsStartAt1": true,
+                    "pathFormat": "path",
+                    "supportsVariableType": true,
+                }),
+            )
+            .await?;
+
+        // Adapters may hold the launch response until configuration is done,
+        // so configure while launch is in flight
+        let launch = client.request("launch", adapter.launch_arguments(&program, &dir));
+        let configure = async {
+            let mut early_events = Vec::new();
+            loop {
+                match events.recv().await {
+                    Some(event) if event.event == "initialized" => break,
+                    Some(event) => early_events.push(event),
+                    None => {
+                        return Err(WarpError::plugin(
+                            item_id,
+                            "debug adapter exited during launch",
+                        ))
+                    }
+                }
+            }
+            client.request("configurationDone", json!({})).await?;
+            Ok(early_events)
+        };
+        let (launched, configured) = tokio::join!(launch, configure);
+        launched?;
+        let early_events = configured?;
+
+        self.sessions.lock().await.insert(
+            session_id.to_string(),
+            AdapterSession {
+                client,
+                events,
+                early_events,
+                breakpoints: HashMap::new(),
+                adapter_ids: HashMap::new(),
+                thread_id: None,
+                frame_ids: Vec::new(),
+                output: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    pub async fn detach_from_item(
+        &self,
+        _item_id: &str,
+        session_id: &str,
+    ) -> Result<(), WarpError> {
+        let session = self.sessions.lock().await.remove(session_id);
+        if let Some(mut session) = session {
+            session.client.shutdown().await;
+        }
+        Ok(())
+    }
+
+    pub async fn set_breakpoint(
+        &self,
+        session_id: &str,
+        breakpoint_id: &str,
+        file_path: &str,
+        line_number: u32,
+        condition: Option<String>,
+    ) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("debug session", session_id))?;
+        session
+            .breakpoints
+            .entry(file_path.to_string())
+            .or_default()
+            .push(SourceBreakpoint {
+                id: breakpoint_id.to_string(),
+                line: line_number,
+                condition,
+            });
+        sync_breakpoints(session, file_path).await
+    }
+
+    pub async fn remove_breakpoint(
+        &self,
+        session_id: &str,
+        breakpoint_id: &str,
+    ) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("debug session", session_id))?;
+        let file = session
+            .breakpoints
+            .iter()
+            .find(|(_, breakpoints)| {
+                breakpoints
+                    .iter()
+                    .any(|breakpoint| breakpoint.id == breakpoint_id)
+            })
+            .map(|(file, _)| file.clone());
+        let Some(file) = file else {
+            return Ok(());
+        };
+        if let Some(breakpoints) = session.breakpoints.get_mut(&file) {
+            breakpoints.retain(|breakpoint| breakpoint.id != breakpoint_id);
+        }
+        sync_breakpoints(session, &file).await
+    }
+
+    pub async fn step(&self, session_id: &str, kind: StepKind) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("debug session", session_id))?;
+        let thread_id = match session.thread_id {
+            Some(thread_id) => thread_id,
+            None => first_thread(&session.client).await?,
+        };
+        session
+            .client
+            .request(kind.command(), json!({ "threadId": thread_id }))
+            .await?;
+        if kind != StepKind::Pause {
+            session.frame_ids.clear();
+        }
+        Ok(())
+    }
+
+    // Evaluates a watch expression in a frame of the last call stack
+    pub async fn evaluate(
+        &self,
+        session_id: &str,
+        expression: &str,
+        frame_index: usize,
+    ) -> Result<String, WarpError> {
+        let sessions = self.sessions.lock().await;
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| WarpError::not_found("debug session", session_id))?;
+        let mut arguments = json!({ "expression": expression, "context": "watch" });
+        if let Some(frame_id) = session.frame_ids.get(frame_index) {
+            arguments["frameId"] = json!(frame_id);
+        }
+        let body = session.client.request("evaluate", arguments).await?;
+        Ok(body["result"].as_str().unwrap_or_default().to_string())
+    }
+
+    pub async fn output(&self, session_id: &str) -> Vec<String> {
+        let sessions = self.sessions.lock().await;
+        sessions
+            .get(session_id)
+            .map(|session| session.output.clone())
+            .unwrap_or_default()
+    }
+
+    // Applies adapter events that arrived since the last call. When the
+    // debuggee has stopped, fetches its call stack and variables.
+    pub async fn refresh(&self, debug_session: &mut DebugSession) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions
+            .get_mut(&debug_session.session_id)
+            .ok_or_else(|| WarpError::not_found("debug session", &debug_session.session_id))?;
+
+        let mut events = std::mem::take(&mut session.early_events);
+        while let Ok(event) = session.events.try_recv() {
+            events.push(event);
+        }
+        let mut stopped = false;
+        for event in events {
+            match event.event.as_str() {
+                "stopped" => {
+                    stopped = true;
+                    debug_session.status = DebugStatus::Paused;
+                    session.thread_id = event.body["threadId"].as_i64().or(session.thread_id);
+                    for hit in event.body["hitBreakpointIds"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                    {
+                        let Some(id) = hit.as_i64().and_then(|hit| session.adapter_ids.get(&hit))
+                        else {
+                            continue;
+                        };
+                        if let Some(breakpoint) = debug_session
+                            .breakpoints
+                            .iter_mut()
+                            .find(|breakpoint| &breakpoint.id == id)
+                        {
+                            breakpoint.hit_count += 1;
+                        }
+                    }
+                }
+                "continued" => {
+                    debug_session.status = DebugStatus::Running;
+                    debug_session.call_stack.clear();
+                    debug_session.variables.clear();
+                }
+                "terminated" | "exited" => debug_session.status = DebugStatus::Stopped,
+                "output" => {
+                    let text = event.body["output"].as_str().unwrap_or_default();
+                    session.output.extend(text.lines().map(String::from));
+                    let excess = session.output.len().saturating_sub(MAX_OUTPUT_LINES);
+                    session.output.drain(..excess);
+                }
+                _ => {}
+            }
+        }
+
+        if stopped {
+            let (frames, frame_ids) = call_stack(session).await?;
+            debug_session.variables = frames
+                .first()
+                .map(|frame| frame.variables.clone())
+                .unwrap_or_default();
+            debug_session.call_stack = frames;
+            session.frame_ids = frame_ids;
+        }
+        Ok(())
+    }
+}
+
+async fn first_thread(client: &DapClient) -> Result<i64, WarpError> {
+    let body = client.request("threads", json!({})).await?;
+    Ok(body["threads"]
+        .get(0)
+        .and_then(|thread| thread["id"].as_i64())
+        .unwrap_or(1))
+}
+
+// DAP replaces all of a source's breakpoints at once
+async fn sync_breakpoints(session: &mut AdapterSession, file_path: &str) -> Result<(), WarpError> {
+    let breakpoints = session
+        .breakpoints
+        .get(file_path)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let requested: Vec<Value> = breakpoints
+        .iter()
+        .map(|breakpoint| match &breakpoint.condition {
+            Some(condition) => json!({ "line": breakpoint.line, "condition": condition }),
+            None => json!({ "line": breakpoint.line }),
+        })
+        .collect();
+    let body = session
+        .client
+        .request(
+            "setBreakpoints",
+            json!({ "source": { "path": file_path }, "breakpoints": requested }),
+        )
+        .await?;
+    // The response lists breakpoints in request order
+    let ours: Vec<String> = breakpoints
+        .iter()
+        .map(|breakpoint| breakpoint.id.clone())
+        .collect();
+    session.adapter_ids.retain(|_, id| !ours.contains(id));
+    for (id, result) in ours
+        .into_iter()
+        .zip(body["breakpoints"].as_array().into_iter().flatten())
+    {
+        if let Some(adapter_id) = result["id"].as_i64() {
+            session.adapter_ids.insert(adapter_id, id);
+        }
+        if result["verified"] == false {
+            log::debug!(
+                "Breakpoint {} in {} not verified: {}",
+                result["line"],
+                file_path,
+                result["message"]
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn call_stack(session: &AdapterSession) -> Result<(Vec<StackFrame>, Vec<i64>), WarpError> {
+    let thread_id = session.thread_id.unwrap_or(1);
+    let body = session
+        .client
+        .request(
+            "stackTrace",
+            json!({ "threadId": thread_id, "levels": MAX_FRAMES }),
+        )
+        .await?;
+    let mut frames = Vec::new();
+    let mut frame_ids = Vec::new();
+    for (index, frame) in body["stackFrames"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        let frame_id = frame["id"].as_i64().unwrap_or_default();
+        let variables = if index < FRAMES_WITH_VARIABLES {
+            frame_variables(&session.client, frame_id).await?
+        } else {
+            HashMap::new()
+        };
+        frames.push(StackFrame {
+            function_name: frame["name"].as_str().unwrap_or("?").to_string(),
+            file_path: frame["source"]["path"]
+                .as_str()
+                .or(frame["source"]["name"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+            line_number: frame["line"].as_u64().unwrap_or_default() as u32,
+            variables,
+        });
+        frame_ids.push(frame_id);
+    }
+    Ok((frames, frame_ids))
+}
+
+async fn frame_variables(
+    client: &DapClient,
+    frame_id: i64,
+) -> Result<HashMap<String, DebugVariable>, WarpError> {
+    let body = client
+        .request("scopes", json!({ "frameId": frame_id }))
+        .await?;
+    let mut variables = HashMap::new();
+    for scope in body["scopes"].as_array().into_iter().flatten() {
+        // Globals and registers are usually huge and rarely what's wanted
+        if scope["expensive"] == true {
+            continue;
+        }
+        let kind = variable_scope(
+            scope["name"].as_str().unwrap_or_default(),
+            scope["presentationHint"].as_str(),
+        );
+        let reference = scope["variablesReference"].as_i64().unwrap_or_default();
+        if reference == 0 {
+            continue;
+        }
+        let body = client
+            .request("variables", json!({ "variablesReference": reference }))
+            .await?;
+        for variable in body["variables"].as_array().into_iter().flatten() {
+            let name = variable["name"].as_str().unwrap_or_default().to_string();
+            // The innermost scope comes first and shadows the rest
+            variables
+                .entry(name.clone())
+                .or_insert_with(|| DebugVariable {
+                    name,
+                    value: variable["value"].as_str().unwrap_or_default().to_string(),
+                    var_type: variable["type"].as_str().unwrap_or_default().to_string(),
+                    scope: kind.clone(),
+                });
+        }
+    }
+    Ok(variables)
+}
+
+// Adapters name scopes freely; the hint, when given, is the reliable part
+fn variable_scope(name: &str, hint: Option<&str>) -> VariableScope {
+    let name = name.to_lowercase();
+    match hint {
+        Some("arguments") => VariableScope::Parameter,
+        Some("locals") => VariableScope::Local,
+        _ if name.contains("global") => VariableScope::Global,
+        _ if name.contains("closure") || name.contains("upvalue") || name.contains("nonlocal") => {
+            VariableScope::Closure
+        }
+        _ if name.contains("argument") || name.contains("param") => VariableScope::Parameter,
+        _ => VariableScope::Local,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variable_scope_prefers_hint() {
+        assert!(matches!(
+            variable_scope("Globals", Some("locals")),
+            VariableScope::Local
+        ));
+        assert!(matches!(
+            variable_scope("Upvalues", None),
+            VariableScope::Closure
+        ));
+        assert!(matches!(
+            variable_scope("Global", None),
+            VariableScope::Global
+        ));
+        assert!(matches!(
+            variable_scope("Arguments", None),
+            VariableScope::Parameter
+        ));
+        assert!(matches!(
+            variable_scope("Locals", None),
+            VariableScope::Local
+        ));
+    }
+}
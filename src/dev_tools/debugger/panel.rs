@@ -0,0 +1,377 @@
+use crossterm::style::Color;
+
+use super::StepKind;
+use crate::dev_tools::{DebugSession, DebugStatus, VariableScope};
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// What the app still has to do after a keypress. Stepping and evaluating
+// watches go through the debugger outside the panel lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugCommand {
+    None,
+    Redraw,
+    Step(StepKind),
+    // Ask for input; the answer comes back through submit_input
+    Prompt(String),
+    // The selected frame or the watches changed, so watches need evaluating
+    EvaluateWatches,
+    SetBreakpoint {
+        file_path: String,
+        line_number: u32,
+        condition: Option<String>,
+    },
+    RemoveBreakpoint(usize),
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input {
+    Watch,
+    Breakpoint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Heading,
+    Frame(usize),
+    Variable,
+    Watch(usize),
+    Breakpoint(usize),
+}
+
+pub struct Watch {
+    pub expression: String,
+    pub value: Option<Result<String, String>>,
+}
+
+// Call stack, the selected frame's variables, watches and breakpoints for
+// one debug session
+pub struct DebugPanel {
+    session: Option<DebugSession>,
+    selected_frame: usize,
+    watches: Vec<Watch>,
+    prompting: Option<Input>,
+}
+
+impl DebugPanel {
+    pub fn new() -> Self {
+        Self {
+            session: None,
+            selected_frame: 0,
+            watches: Vec::new(),
+            prompting: None,
+        }
+    }
+
+    pub fn session(&self) -> Option<&DebugSession> {
+        self.session.as_ref()
+    }
+
+    pub fn set_session(&mut self, session: DebugSession) {
+        if self.selected_frame >= session.call_stack.len() {
+            self.selected_frame = 0;
+        }
+        self.session = Some(session);
+    }
+
+    pub fn selected_frame(&self) -> usize {
+        self.selected_frame
+    }
+
+    pub fn add_watch(&mut self, expression: String) {
+        self.watches.push(Watch {
+            expression,
+            value: None,
+        });
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = &str> {
+        self.watches.iter().map(|watch| watch.expression.as_str())
+    }
+
+    pub fn set_watch_value(&mut self, index: usize, value: Result<String, String>) {
+        if let Some(watch) = self.watches.get_mut(index) {
+            watch.value = Some(value);
+        }
+    }
+
+    // Keys: c continue · n step over · s step in · o step out · p pause ·
+    // Enter select frame · w add watch · b add breakpoint · d delete
+    // watch/breakpoint · x stop
+    pub fn handle_key(&mut self, key: char, index: usize) -> DebugCommand {
+        let row = self.rows().get(index).map(|(row, _)| *row);
+        match (key, row) {
+            ('c', _) => DebugCommand::Step(StepKind::Continue),
+            ('n', _) => DebugCommand::Step(StepKind::Next),
+            ('s', _) => DebugCommand::Step(StepKind::StepIn),
+            ('o', _) => DebugCommand::Step(StepKind::StepOut),
+            ('p', _) => DebugCommand::Step(StepKind::Pause),
+            ('w', _) => {
+                self.prompting = Some(Input::Watch);
+                DebugCommand::Prompt(i18n::t("debugger.watch_prompt"))
+            }
+            ('b', _) => {
+                self.prompting = Some(Input::Breakpoint);
+                DebugCommand::Prompt(i18n::t("debugger.breakpoint_prompt"))
+            }
+            ('x', _) => DebugCommand::Stop,
+            ('\n', Some(Row::Frame(frame))) => {
+                self.selected_frame = frame;
+                DebugCommand::EvaluateWatches
+            }
+            ('d', Some(Row::Watch(watch))) => {
+                self.watches.remove(watch);
+                DebugCommand::Redraw
+            }
+            ('d', Some(Row::Breakpoint(breakpoint))) => DebugCommand::RemoveBreakpoint(breakpoint),
+            _ => DebugCommand::None,
+        }
+    }
+
+    pub fn submit_input(&mut self, text: &str) -> DebugCommand {
+        let text = text.trim();
+        match self.prompting.take() {
+            _ if text.is_empty() => DebugCommand::None,
+            Some(Input::Watch) => {
+                self.add_watch(text.to_string());
+                DebugCommand::EvaluateWatches
+            }
+            Some(Input::Breakpoint) => match parse_breakpoint(text) {
+                Some((file_path, line_number, condition)) => DebugCommand::SetBreakpoint {
+                    file_path,
+                    line_number,
+                    condition,
+                },
+                None => {
+                    self.prompting = Some(Input::Breakpoint);
+                    DebugCommand::Prompt(i18n::t("debugger.breakpoint_usage"))
+                }
+            },
+            None => DebugCommand::None,
+        }
+    }
+
+    fn rows(&self) -> Vec<(Row, PanelItem)> {
+        let Some(session) = &self.session else {
+            return vec![(Row::Heading, i18n::t("panel.debugger_no_session").into())];
+        };
+        let heading = |key: &str| {
+            (
+                Row::Heading,
+                PanelItem::styled(vec![(i18n::t(key), Some(Color::Yellow))]),
+            )
+        };
+        let mut rows = vec![heading("panel.debugger_stack")];
+        if session.call_stack.is_empty() {
+            rows.push((Row::Heading, i18n::t("panel.debugger_running").into()));
+        }
+        for (index, frame) in session.call_stack.iter().enumerate() {
+            let marker = if index == self.selected_frame {
+                "▶"
+            } else {
+                " "
+            };
+            rows.push((
+                Row::Frame(index),
+                PanelItem::styled(vec![
+                    (
+                        format!("{} {} ", marker, frame.function_name),
+                        Some(Color::Cyan),
+                    ),
+                    (format!("{}:{}", frame.file_path, frame.line_number), None),
+                ]),
+            ));
+        }
+
+        if let Some(frame) = session.call_stack.get(self.selected_frame) {
+            rows.push(heading("panel.debugger_variables"));
+            let mut variables: Vec<_> = frame.variables.values().collect();
+            variables.sort_by(|a, b| a.name.cmp(&b.name));
+            for variable in variables {
+                let color = match variable.scope {
+                    VariableScope::Parameter => Color::Magenta,
+                    VariableScope::Closure => Color::Blue,
+                    VariableScope::Global => Color::DarkGrey,
+                    VariableScope::Local => Color::White,
+                };
+                let mut segments = vec![
+                    (format!("  {} = ", variable.name), Some(color)),
+                    (variable.value.clone(), None),
+                ];
+                if !variable.var_type.is_empty() {
+                    segments.push((format!("  {}", variable.var_type), Some(Color::DarkGrey)));
+                }
+                rows.push((Row::Variable, PanelItem::styled(segments)));
+            }
+        }
+
+        rows.push(heading("panel.debugger_watches"));
+        for (index, watch) in self.watches.iter().enumerate() {
+            let (value, color) = match &watch.value {
+                Some(Ok(value)) => (value.clone(), None),
+                Some(Err(error)) => (error.clone(), Some(Color::Red)),
+                None => ("…".to_string(), Some(Color::DarkGrey)),
+            };
+            rows.push((
+                Row::Watch(index),
+                PanelItem::styled(vec![
+                    (format!("  {} = ", watch.expression), Some(Color::Green)),
+                    (value, color),
+                ]),
+            ));
+        }
+
+        rows.push(heading("panel.debugger_breakpoints"));
+        for (index, breakpoint) in session.breakpoints.iter().enumerate() {
+            let mut line = format!(
+                "  {}:{} ×{}",
+                breakpoint.file_path, breakpoint.line_number, breakpoint.hit_count
+            );
+            if let Some(condition) = &breakpoint.condition {
+                line.push_str(&format!(" if {}", condition));
+            }
+            rows.push((Row::Breakpoint(index), line.into()));
+        }
+        rows
+    }
+
+    pub fn view(&self) -> PanelView {
+        let (item, status) = match &self.session {
+            Some(session) => {
+                let status = match &session.status {
+                    DebugStatus::Running => i18n::t("panel.debugger_status_running"),
+                    DebugStatus::Paused => i18n::t("panel.debugger_status_paused"),
+                    DebugStatus::Stopped => i18n::t("panel.debugger_status_stopped"),
+                    DebugStatus::Error(message) => message.clone(),
+                };
+                (session.item_id.clone(), status)
+            }
+            None => ("-".to_string(), i18n::t("panel.debugger_status_stopped")),
+        };
+        PanelView {
+            kind: PanelKind::Debugger,
+            title: i18n::t_args("panel.debugger", &[("item", &item), ("status", &status)]),
+            items: self.rows().into_iter().map(|(_, item)| item).collect(),
+            footer: Some(i18n::t("panel.debugger_keys")),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+}
+
+// "main.lua:12" or "main.lua:12 if count > 2"
+fn parse_breakpoint(text: &str) -> Option<(String, u32, Option<String>)> {
+    let (location, condition) = match text.split_once(" if ") {
+        Some((location, condition)) => (location.trim(), Some(condition.trim().to_string())),
+        None => (text, None),
+    };
+    let (file_path, line) = location.rsplit_once(':')?;
+    let line_number = line.parse().ok().filter(|line| *line > 0)?;
+    if file_path.is_empty() {
+        return None;
+    }
+    Some((file_path.to_string(), line_number, condition))
+}
+
+impl Default for DebugPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_tools::{Breakpoint, DebugVariable, PerformanceSnapshot, StackFrame};
+    use std::collections::HashMap;
+
+    fn session() -> DebugSession {
+        let frame = |name: &str, line: u32| StackFrame {
+            function_name: name.to_string(),
+            file_path: "main.lua".to_string(),
+            line_number: line,
+            variables: HashMap::from([(
+                "count".to_string(),
+                DebugVariable {
+                    name: "count".to_string(),
+                    value: "3".to_string(),
+                    var_type: "number".to_string(),
+                    scope: VariableScope::Local,
+                },
+            )]),
+        };
+        DebugSession {
+            session_id: "s".to_string(),
+            item_id: "counter".to_string(),
+            started_at: chrono::Utc::now(),
+            breakpoints: vec![Breakpoint {
+                id: "b".to_string(),
+                file_path: "main.lua".to_string(),
+                line_number: 12,
+                condition: None,
+                hit_count: 1,
+                enabled: true,
+            }],
+            call_stack: vec![frame("tick", 12), frame("main", 30)],
+            variables: HashMap::new(),
+            performance_data: PerformanceSnapshot {
+                timestamp: chrono::Utc::now(),
+                cpu_usage: 0.0,
+                memory_usage: 0,
+                heap_size: 0,
+                gc_pressure: 0.0,
+                thread_count: 1,
+                active_handles: 0,
+            },
+            status: DebugStatus::Paused,
+        }
+    }
+
+    #[test]
+    fn test_rows_map_keys_to_frames_watches_and_breakpoints() {
+        let mut panel = DebugPanel::new();
+        panel.set_session(session());
+        panel.add_watch("count * 2".to_string());
+
+        // Stack heading, 2 frames, variables heading, 1 variable, watches
+        // heading, 1 watch, breakpoints heading, 1 breakpoint
+        assert_eq!(panel.view().items.len(), 9);
+        assert_eq!(panel.handle_key('\n', 2), DebugCommand::EvaluateWatches);
+        assert_eq!(panel.selected_frame(), 1);
+        assert_eq!(panel.handle_key('d', 8), DebugCommand::RemoveBreakpoint(0));
+        assert_eq!(panel.handle_key('d', 6), DebugCommand::Redraw);
+        assert_eq!(panel.watches().count(), 0);
+        assert_eq!(panel.handle_key('n', 0), DebugCommand::Step(StepKind::Next));
+        assert_eq!(panel.handle_key('d', 0), DebugCommand::None);
+    }
+
+    #[test]
+    fn test_prompts_add_watches_and_breakpoints() {
+        let mut panel = DebugPanel::new();
+        assert!(matches!(panel.handle_key('w', 0), DebugCommand::Prompt(_)));
+        assert_eq!(panel.submit_input(" count "), DebugCommand::EvaluateWatches);
+        assert_eq!(panel.watches().collect::<Vec<_>>(), ["count"]);
+
+        panel.handle_key('b', 0);
+        assert_eq!(
+            panel.submit_input("src/main.lua:12 if count > 2"),
+            DebugCommand::SetBreakpoint {
+                file_path: "src/main.lua".to_string(),
+                line_number: 12,
+                condition: Some("count > 2".to_string()),
+            }
+        );
+        // A bad location asks again
+        panel.handle_key('b', 0);
+        assert!(matches!(
+            panel.submit_input("main.lua"),
+            DebugCommand::Prompt(_)
+        ));
+        assert!(matches!(
+            panel.submit_input("main.lua:3"),
+            DebugCommand::SetBreakpoint { line_number: 3, .. }
+        ));
+        assert_eq!(panel.submit_input("main.lua:4"), DebugCommand::None);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Debug Adapter Protocol messages. Each is a JSON body behind a
+// `Content-Length` header, the same framing LSP uses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    Request {
+        seq: i64,
+        command: String,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        arguments: Value,
+    },
+    Response {
+        seq: i64,
+        request_seq: i64,
+        success: bool,
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        body: Value,
+    },
+    Event {
+        seq: i64,
+        event: String,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        body: Value,
+    },
+}
+
+pub fn encode(message: &Message) -> Vec<u8> {
+    let body = serde_json::to_vec(message).unwrap_or_default();
+    let mut frame = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    frame.extend(body);
+    frame
+}
+
+// Splits a byte stream into messages; bytes of a partial frame are kept
+// until the rest arrives
+#[derive(Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Frames that aren't valid DAP messages are logged and skipped so one
+    // bad message doesn't wedge the session
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Message> {
+        self.buffer.extend_from_slice(bytes);
+        let mut messages = Vec::new();
+        while let Some(header_end) = find(&self.buffer, b"\r\n\r\n") {
+            let header = String::from_utf8_lossy(&self.buffer[..header_end]).to_string();
+            let length = header.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim()
+                    .eq_ignore_ascii_case("content-length")
+                    .then(|| value.trim().parse::<usize>().ok())?
+            });
+            let Some(length) = length else {
+                log::warn!(
+                    "Dropping debug adapter frame without Content-Length: {:?}",
+                    header
+                );
+                self.buffer.drain(..header_end + 4);
+                continue;
+            };
+            let body_start = header_end + 4;
+            if self.buffer.len() < body_start + length {
+                break;
+            }
+            let body: Vec<u8> = self
+                .buffer
+                .drain(..body_start + length)
+                .skip(body_start)
+                .collect();
+            match serde_json::from_slice(&body) {
+                Ok(message) => messages.push(message),
+                Err(e) => log::warn!("Dropping malformed debug adapter message: {}", e),
+            }
+        }
+        messages
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip_across_chunks() {
+        let request = Message::Request {
+            seq: 1,
+            command: "initialize".to_string(),
+            arguments: serde_json::json!({ "adapterID": "warp" }),
+        };
+        let event = Message::Event {
+            seq: 2,
+            event: "initialized".to_string(),
+            body: Value::Null,
+        };
+        let mut stream = encode(&request);
+        stream.extend(encode(&event));
+
+        let mut decoder = FrameDecoder::new();
+        let (first, rest) = stream.split_at(30);
+        assert!(decoder.push(first).is_empty());
+        assert_eq!(decoder.push(rest), vec![request, event]);
+    }
+
+    #[test]
+    fn test_decodes_adapter_response() {
+        let body = br#"{"seq":5,"type":"response","request_seq":3,"success":false,"command":"next","message":"not stopped"}"#;
+        let mut frame = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+        frame.extend_from_slice(body);
+        let messages = FrameDecoder::new().push(&frame);
+        assert!(matches!(
+            &messages[0],
+            Message::Response { request_seq: 3, success: false, message: Some(m), .. } if m == "not stopped"
+        ));
+    }
+}
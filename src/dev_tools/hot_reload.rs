@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+use walkdir::WalkDir;
+
+use crate::error::WarpError;
+use crate::package::{Manifest, PackageKind};
+
+// Build output and the like; changes here never mean a rebuild
+const IGNORED_DIRS: &[&str] = &["target", "dist", ".git", "node_modules"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadEvent {
+    SourceChanged { name: String, dir: PathBuf },
+    WasmChanged { name: String, wasm: PathBuf },
+}
+
+struct WatchedPlugin {
+    dir: PathBuf,
+    wasm: PathBuf,
+    source_changed: Option<SystemTime>,
+    wasm_changed: Option<SystemTime>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// The newest change to anything in the package other than the module itself
+fn latest_source_change(dir: &Path, wasm: &Path) -> Option<SystemTime> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            !(entry.file_type().is_dir()
+                && entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| IGNORED_DIRS.contains(&name)))
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && entry.path() != wasm)
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+}
+
+// Watches plugin packages under development by polling modification times:
+// a source change asks for a rebuild, a new module for a reload
+pub struct HotReloadManager {
+    watched: Mutex<HashMap<String, WatchedPlugin>>,
+}
+
+impl HotReloadManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self {
+            watched: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // Starts watching a plugin package directory; returns the plugin's name
+    pub async fn watch(&self, dir: &Path) -> Result<String, WarpError> {
+        let manifest = Manifest::load(dir).await?;
+        let entry_point = match (manifest.kind, &manifest.entry_point) {
+            (PackageKind::Plugin, Some(entry_point)) => entry_point,
+            _ => {
+                return Err(WarpError::Validation(format!(
+                    "{} is not a plugin with an entry_point",
+                    manifest.id
+                )))
+            }
+        };
+        let wasm = dir.join(entry_point);
+        let plugin = WatchedPlugin {
+            dir: dir.to_path_buf(),
+            source_changed: latest_source_change(dir, &wasm),
+            wasm_changed: modified(&wasm),
+            wasm,
+        };
+        let name = manifest.short_name().to_string();
+        self.watched.lock().await.insert(name.clone(), plugin);
+        Ok(name)
+    }
+
+    pub async fn enable_for_item(&self, item_path: &str) -> Result<(), WarpError> {
+        self.watch(Path::new(item_path)).await.map(|_| ())
+    }
+
+    pub async fn disable_for_item(&self, item_id: &str) -> Result<(), WarpError> {
+        self.watched
+            .lock()
+            .await
+            .retain(|name, plugin| name != item_id && plugin.dir != Path::new(item_id));
+        Ok(())
+    }
+
+    pub async fn watching(&self) -> Vec<(String, PathBuf)> {
+        let mut watching: Vec<_> = self
+            .watched
+            .lock()
+            .await
+            .iter()
+            .map(|(name, plugin)| (name.clone(), plugin.wasm.clone()))
+            .collect();
+        watching.sort();
+        watching
+    }
+
+    // What changed since the last poll
+    pub async fn poll(&self) -> Vec<ReloadEvent> {
+        let mut events = Vec::new();
+        for (name, plugin) in self.watched.lock().await.iter_mut() {
+            let source = latest_source_change(&plugin.dir, &plugin.wasm);
+            if source > plugin.source_changed {
+                plugin.source_changed = source;
+                events.push(ReloadEvent::SourceChanged {
+                    name: name.clone(),
+                    dir: plugin.dir.clone(),
+                });
+            }
+            let wasm = modified(&plugin.wasm);
+            if wasm.is_some() && wasm != plugin.wasm_changed {
+                plugin.wasm_changed = wasm;
+                events.push(ReloadEvent::WasmChanged {
+                    name: name.clone(),
+                    wasm: plugin.wasm.clone(),
+                });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::scaffold;
+    use std::time::Duration;
+
+    fn touch(path: &Path) {
+        let later = SystemTime::now() + Duration::from_secs(60);
+        std::fs::write(path, "changed").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reports_source_and_module_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Plugin, "sample")
+            .await
+            .unwrap();
+        let manager = HotReloadManager::new().await.unwrap();
+        let name = manager.watch(dir.path()).await.unwrap();
+        assert_eq!(name, "sample");
+        assert!(manager.poll().await.is_empty());
+
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        touch(&dir.path().join("target").join("build.log"));
+        assert!(manager.poll().await.is_empty());
+
+        touch(&dir.path().join("README.md"));
+        assert_eq!(
+            manager.poll().await,
+            vec![ReloadEvent::SourceChanged {
+                name: "sample".to_string(),
+                dir: dir.path().to_path_buf(),
+            }]
+        );
+
+        touch(&dir.path().join("plugin.wasm"));
+        assert_eq!(
+            manager.poll().await,
+            vec![ReloadEvent::WasmChanged {
+                name: "sample".to_string(),
+                wasm: dir.path().join("plugin.wasm"),
+            }]
+        );
+        assert!(manager.poll().await.is_empty());
+
+        manager.disable_for_item("sample").await.unwrap();
+        assert!(manager.watching().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_only_watches_plugins() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Theme, "sample")
+            .await
+            .unwrap();
+        let manager = HotReloadManager::new().await.unwrap();
+        assert!(manager.watch(dir.path()).await.is_err());
+    }
+}
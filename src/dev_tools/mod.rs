@@ -1,17 +1,17 @@
+use crate::config::WarpConfig;
+use crate::error::WarpError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
 
+pub mod builder;
+pub mod console;
 pub mod debugger;
-pub mod profiler;
+pub mod hot_reload;
 pub mod testing;
-pub mod builder;
+pub mod trace;
 pub mod validator;
-pub mod simulator;
-pub mod inspector;
-pub mod hot_reload;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DevToolsConfig {
@@ -25,6 +25,9 @@ pub struct DevToolsConfig {
     pub log_level: LogLevel,
     pub breakpoints_enabled: bool,
     pub code_coverage_enabled: bool,
+    // Overrides for the built-in adapter per language ("lua", "python", ...)
+    #[serde(default)]
+    pub debug_adapters: HashMap<String, debugger::AdapterConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,7 +139,10 @@ pub enum TestExpectation {
     Success,
     Failure(String),
     Output(String),
-    Performance { max_time_ms: u64, max_memory_mb: u64 },
+    Performance {
+        max_time_ms: u64,
+        max_memory_mb: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -173,37 +179,41 @@ pub struct CoverageData {
 pub struct DevToolsManager {
     config: Arc<Mutex<DevToolsConfig>>,
     debugger: Arc<debugger::Debugger>,
-    profiler: Arc<profiler::Profiler>,
     testing_framework: Arc<testing::TestingFramework>,
     builder: Arc<builder::Builder>,
     validator: Arc<validator::Validator>,
-    simulator: Arc<simulator::Simulator>,
-    inspector: Arc<inspector::Inspector>,
     hot_reload: Arc<hot_reload::HotReloadManager>,
     active_sessions: Arc<Mutex<HashMap<String, DebugSession>>>,
 }
 
 impl DevToolsManager {
-    pub async fn new() -> Result<Self, WarpError> {
+    // WASM plugins under test get the loaded config's sandbox limits
+    pub async fn new(warp_config: &WarpConfig) -> Result<Self, WarpError> {
         let config = Arc::new(Mutex::new(DevToolsConfig::default()));
-        
+
         Ok(Self {
             config: config.clone(),
             debugger: Arc::new(debugger::Debugger::new(config.clone()).await?),
-            profiler: Arc::new(profiler::Profiler::new().await?),
-            testing_framework: Arc::new(testing::TestingFramework::new().await?),
+            testing_framework: Arc::new(
+                testing::TestingFramework::new(config.clone(), &warp_config.wasm).await?,
+            ),
             builder: Arc::new(builder::Builder::new().await?),
             validator: Arc::new(validator::Validator::new().await?),
-            simulator: Arc::new(simulator::Simulator::new().await?),
-            inspector: Arc::new(inspector::Inspector::new().await?),
             hot_reload: Arc::new(hot_reload::HotReloadManager::new().await?),
             active_sessions: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
+    // Points the debugger and test runner at a plugin under development
+    // instead of the installed copy
+    pub async fn register_item_dir(&self, item_id: &str, dir: std::path::PathBuf) {
+        self.debugger.register_item_dir(item_id, dir.clone()).await;
+        self.testing_framework.register_item_dir(item_id, dir).await;
+    }
+
     pub async fn start_debug_session(&self, item_id: &str) -> Result<String, WarpError> {
         let session_id = uuid::Uuid::new_v4().to_string();
-        
+
         let session = DebugSession {
             session_id: session_id.clone(),
             item_id: item_id.to_string(),
@@ -227,21 +237,29 @@ impl DevToolsManager {
         sessions.insert(session_id.clone(), session);
 
         self.debugger.attach_to_item(item_id, &session_id).await?;
-        
+
         Ok(session_id)
     }
 
     pub async fn stop_debug_session(&self, session_id: &str) -> Result<(), WarpError> {
         let mut sessions = self.active_sessions.lock().await;
         if let Some(session) = sessions.remove(session_id) {
-            self.debugger.detach_from_item(&session.item_id, session_id).await?;
+            self.debugger
+                .detach_from_item(&session.item_id, session_id)
+                .await?;
         }
         Ok(())
     }
 
-    pub async fn set_breakpoint(&self, session_id: &str, file_path: &str, line_number: u32, condition: Option<String>) -> Result<String, WarpError> {
+    pub async fn set_breakpoint(
+        &self,
+        session_id: &str,
+        file_path: &str,
+        line_number: u32,
+        condition: Option<String>,
+    ) -> Result<String, WarpError> {
         let breakpoint_id = uuid::Uuid::new_v4().to_string();
-        
+
         let breakpoint = Breakpoint {
             id: breakpoint_id.clone(),
             file_path: file_path.to_string(),
@@ -253,39 +271,102 @@ impl DevToolsManager {
 
         let mut sessions = self.active_sessions.lock().await;
         if let Some(session) = sessions.get_mut(session_id) {
+            let condition = breakpoint.condition.clone();
             session.breakpoints.push(breakpoint);
-            self.debugger.set_breakpoint(session_id, &breakpoint_id, file_path, line_number).await?;
+            self.debugger
+                .set_breakpoint(
+                    session_id,
+                    &breakpoint_id,
+                    file_path,
+                    line_number,
+                    condition,
+                )
+                .await?;
         }
 
         Ok(breakpoint_id)
     }
 
-    pub async fn run_tests(&self, item_id: &str, test_suite: &TestSuite) -> Result<Vec<TestResult>, WarpError> {
-        self.testing_framework.run_test_suite(item_id, test_suite).await
+    pub async fn remove_breakpoint(
+        &self,
+        session_id: &str,
+        breakpoint_id: &str,
+    ) -> Result<(), WarpError> {
+        let mut sessions = self.active_sessions.lock().await;
+        if let Some(session) = sessions.get_mut(session_id) {
+            session
+                .breakpoints
+                .retain(|breakpoint| breakpoint.id != breakpoint_id);
+            self.debugger
+                .remove_breakpoint(session_id, breakpoint_id)
+                .await?;
+        }
+        Ok(())
     }
 
-    pub async fn start_profiling(&self, item_id: &str) -> Result<String, WarpError> {
-        self.profiler.start_profiling(item_id).await
+    pub async fn step(&self, session_id: &str, kind: debugger::StepKind) -> Result<(), WarpError> {
+        self.debugger.step(session_id, kind).await
     }
 
-    pub async fn stop_profiling(&self, profile_id: &str) -> Result<profiler::ProfileReport, WarpError> {
-        self.profiler.stop_profiling(profile_id).await
+    // The session as of the adapter's latest events, for the debug panel
+    pub async fn refresh_debug_session(&self, session_id: &str) -> Result<DebugSession, WarpError> {
+        let mut sessions = self.active_sessions.lock().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("debug session", session_id))?;
+        self.debugger.refresh(session).await?;
+        Ok(session.clone())
     }
 
-    pub async fn validate_item(&self, item_path: &str) -> Result<validator::ValidationReport, WarpError> {
-        self.validator.validate_item(item_path).await
+    pub async fn evaluate_watch(
+        &self,
+        session_id: &str,
+        expression: &str,
+        frame_index: usize,
+    ) -> Result<String, WarpError> {
+        self.debugger
+            .evaluate(session_id, expression, frame_index)
+            .await
     }
 
-    pub async fn build_item(&self, item_path: &str, build_config: &builder::BuildConfig) -> Result<builder::BuildResult, WarpError> {
-        self.builder.build_item(item_path, build_config).await
+    pub async fn run_tests(
+        &self,
+        item_id: &str,
+        test_suite: &TestSuite,
+    ) -> Result<Vec<TestResult>, WarpError> {
+        self.testing_framework
+            .run_test_suite(item_id, test_suite)
+            .await
     }
 
-    pub async fn simulate_environment(&self, item_id: &str, environment: &simulator::Environment) -> Result<simulator::SimulationResult, WarpError> {
-        self.simulator.run_simulation(item_id, environment).await
+    pub async fn rerun_tests(
+        &self,
+        item_id: &str,
+        test_suite: &TestSuite,
+        names: &[String],
+    ) -> Result<Vec<TestResult>, WarpError> {
+        self.testing_framework
+            .rerun(item_id, test_suite, names)
+            .await
     }
 
-    pub async fn inspect_item(&self, item_id: &str) -> Result<inspector::InspectionReport, WarpError> {
-        self.inspector.inspect_item(item_id).await
+    pub async fn test_coverage(&self, item_id: &str) -> Option<CoverageData> {
+        self.testing_framework.suite_coverage(item_id).await
+    }
+
+    pub async fn validate_item(
+        &self,
+        item_path: &str,
+    ) -> Result<validator::ValidationReport, WarpError> {
+        self.validator.validate_item(item_path).await
+    }
+
+    pub async fn build_item(
+        &self,
+        item_path: &str,
+        build_config: &builder::BuildConfig,
+    ) -> Result<builder::BuildResult, WarpError> {
+        self.builder.build_item(item_path, build_config).await
     }
 
     pub async fn enable_hot_reload(&self, item_id: &str) -> Result<(), WarpError> {
@@ -310,6 +391,7 @@ impl Default for DevToolsConfig {
             log_level: LogLevel::Info,
             breakpoints_enabled: true,
             code_coverage_enabled: true,
+            debug_adapters: HashMap::new(),
         }
     }
 }
@@ -0,0 +1,462 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::dev_tools::debugger::ScriptLanguage;
+use crate::dev_tools::CoverageData;
+
+// The harness reports on the last stderr line that starts with this
+const TRAILER: &str = "\u{1e}warp-test ";
+
+// Each harness is fed on stdin and gets the program, setup, test and
+// teardown code and "1" to collect coverage as arguments. It loads the
+// program, runs the three blocks (teardown even after a failure), prints a
+// TRAILER line and exits non-zero with the error on stderr if any failed.
+const LUA_HARNESS: &str = r#"
+local program, setup, test, teardown, coverage = arg[1], arg[2], arg[3], arg[4], arg[5] == "1"
+local source = "@" .. program
+local lines, functions = {}, {}
+if coverage then
+  debug.sethook(function(event, line)
+    local info = debug.getinfo(2, "S")
+    if info.source ~= source then return end
+    if event == "line" then
+      lines[line] = true
+    elseif info.linedefined > 0 then
+      functions[info.linedefined] = true
+    end
+  end, "cl")
+end
+local function run(code, name)
+  if code == "" then return true end
+  local chunk, err = load(code, "=" .. name)
+  if not chunk then return false, err end
+  return pcall(chunk)
+end
+local ok, err = pcall(dofile, program)
+local elapsed = 0
+if ok then ok, err = run(setup, "setup") end
+if ok then
+  local started = os.clock()
+  ok, err = run(test, "test")
+  elapsed = os.clock() - started
+end
+local cleaned, cleanup_err = run(teardown, "teardown")
+debug.sethook()
+if ok and not cleaned then ok, err = false, cleanup_err end
+local function keys(set)
+  local out = {}
+  for key in pairs(set) do out[#out + 1] = key end
+  table.sort(out)
+  return table.concat(out, ",")
+end
+io.stdout:flush()
+io.stderr:write(string.format("\30warp-test {\"elapsed_us\":%d,\"memory_kb\":%d,\"lines\":[%s],\"functions\":[%s]}\n",
+  math.floor(elapsed * 1e6), math.floor(collectgarbage("count")), keys(lines), keys(functions)))
+if not ok then
+  io.stderr:write(tostring(err), "\n")
+  os.exit(1)
+end
+"#;
+
+const PYTHON_HARNESS: &str = r#"
+import dis, json, resource, sys, time
+program, setup, test, teardown, coverage = sys.argv[1:6]
+lines, functions, executable, definitions = set(), set(), set(), set()
+
+def trace(frame, event, arg):
+    if frame.f_code.co_filename != program:
+        return None
+    if event == "call" and frame.f_code.co_name != "<module>":
+        functions.add(frame.f_code.co_firstlineno)
+    elif event == "line":
+        lines.add(frame.f_lineno)
+    return trace
+
+def walk(code):
+    executable.update(line for _, line in dis.findlinestarts(code) if line)
+    for const in code.co_consts:
+        if hasattr(const, "co_code"):
+            definitions.add(const.co_firstlineno)
+            walk(const)
+
+scope = {"__name__": "__warp_test__", "__file__": program}
+error, elapsed = None, 0.0
+if coverage == "1":
+    sys.settrace(trace)
+try:
+    with open(program) as source:
+        code = compile(source.read(), program, "exec")
+    walk(code)
+    exec(code, scope)
+    exec(compile(setup, "<setup>", "exec"), scope)
+    started = time.perf_counter()
+    exec(compile(test, "<test>", "exec"), scope)
+    elapsed = time.perf_counter() - started
+except BaseException as e:
+    error = f"{type(e).__name__}: {e}"
+try:
+    exec(compile(teardown, "<teardown>", "exec"), scope)
+except BaseException as e:
+    error = error or f"{type(e).__name__}: {e}"
+sys.settrace(None)
+memory_kb = resource.getrusage(resource.RUSAGE_SELF).ru_maxrss
+if sys.platform == "darwin":
+    memory_kb //= 1024
+report = {"elapsed_us": int(elapsed * 1e6), "memory_kb": memory_kb}
+if coverage == "1":
+    report.update(lines=sorted(lines), functions=sorted(functions),
+                  executable=sorted(executable), definitions=sorted(definitions))
+sys.stdout.flush()
+sys.stderr.write("\x1ewarp-test " + json.dumps(report) + "\n")
+if error:
+    sys.stderr.write(error + "\n")
+    sys.exit(1)
+"#;
+
+// V8 only reports coverage as byte ranges through the inspector, so
+// JavaScript tests run without it
+const JAVASCRIPT_HARNESS: &str = r#"
+const vm = require("vm");
+const [program, setup, test, teardown] = process.argv.slice(2);
+const run = (code, name) => { if (code) vm.runInThisContext(code, { filename: name }); };
+let error = null;
+let elapsed = 0;
+try {
+  globalThis.plugin = require(program);
+  run(setup, "setup");
+  const started = performance.now();
+  run(test, "test");
+  elapsed = performance.now() - started;
+} catch (e) {
+  error = e;
+}
+try {
+  run(teardown, "teardown");
+} catch (e) {
+  error = error || e;
+}
+const report = { elapsed_us: Math.round(elapsed * 1000), memory_kb: Math.round(process.memoryUsage().rss / 1024) };
+process.stderr.write("\x1ewarp-test " + JSON.stringify(report) + "\n");
+if (error) {
+  process.stderr.write(String((error && error.stack) || error) + "\n");
+  process.exitCode = 1;
+}
+"#;
+
+#[derive(Debug, Default, Deserialize)]
+struct Trailer {
+    elapsed_us: u64,
+    memory_kb: u64,
+    #[serde(default)]
+    lines: Option<BTreeSet<u32>>,
+    #[serde(default)]
+    functions: BTreeSet<u32>,
+    #[serde(default)]
+    executable: Option<BTreeSet<u32>>,
+    #[serde(default)]
+    definitions: Option<BTreeSet<u32>>,
+}
+
+// Lines and functions of the program under test, by line number. Kept as
+// sets so the runs of a suite can be merged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineCoverage {
+    pub lines: BTreeSet<u32>,
+    pub executable: BTreeSet<u32>,
+    pub functions: BTreeSet<u32>,
+    pub definitions: BTreeSet<u32>,
+}
+
+impl LineCoverage {
+    pub fn merge(&mut self, other: &LineCoverage) {
+        self.lines.extend(&other.lines);
+        self.executable.extend(&other.executable);
+        self.functions.extend(&other.functions);
+        self.definitions.extend(&other.definitions);
+    }
+
+    pub fn to_data(&self) -> CoverageData {
+        let lines_total = self.executable.len() as u32;
+        let lines_covered = self.lines.len() as u32;
+        CoverageData {
+            lines_covered,
+            lines_total,
+            functions_covered: self.functions.len() as u32,
+            functions_total: self.definitions.len() as u32,
+            // None of the runtimes report branches without instrumenting
+            // the source
+            branches_covered: 0,
+            branches_total: 0,
+            coverage_percentage: if lines_total == 0 {
+                0.0
+            } else {
+                lines_covered as f32 / lines_total as f32 * 100.0
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub output: String,
+    pub error: Option<String>,
+    pub timed_out: bool,
+    pub duration: Duration,
+    pub memory_kb: Option<u64>,
+    pub coverage: Option<LineCoverage>,
+}
+
+fn interpreter(language: ScriptLanguage) -> Option<(&'static str, &'static str)> {
+    match language {
+        ScriptLanguage::Lua => Some(("lua", LUA_HARNESS)),
+        ScriptLanguage::Python => Some(("python3", PYTHON_HARNESS)),
+        ScriptLanguage::JavaScript => Some(("node", JAVASCRIPT_HARNESS)),
+        ScriptLanguage::Wasm => None,
+    }
+}
+
+// Runs one test in a fresh interpreter so tests can't leak state into each
+// other. The process is killed when the timeout passes.
+pub async fn run_script(
+    language: ScriptLanguage,
+    program: &Path,
+    blocks: [&str; 3],
+    coverage: bool,
+    timeout: Duration,
+) -> Outcome {
+    let Some((command, harness)) = interpreter(language) else {
+        return Outcome {
+            error: Some(format!("{} isn't a script", program.display())),
+            ..Outcome::default()
+        };
+    };
+    let program_arg = program.to_string_lossy().to_string();
+    let [setup, test, teardown] = blocks;
+    let spawned = Command::new(command)
+        .arg("-")
+        .args([
+            program_arg.as_str(),
+            setup,
+            test,
+            teardown,
+            if coverage { "1" } else { "0" },
+        ])
+        .current_dir(program.parent().unwrap_or(Path::new(".")))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            return Outcome {
+                error: Some(format!("couldn't start {}: {}", command, e)),
+                ..Outcome::default()
+            }
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(harness.as_bytes()).await;
+    }
+
+    let started = Instant::now();
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Outcome {
+                error: Some(e.to_string()),
+                duration: started.elapsed(),
+                ..Outcome::default()
+            }
+        }
+        Err(_) => {
+            return Outcome {
+                error: Some(format!("timed out after {}ms", timeout.as_millis())),
+                timed_out: true,
+                duration: started.elapsed(),
+                ..Outcome::default()
+            }
+        }
+    };
+    let wall = started.elapsed();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut trailer = None;
+    let mut errors = Vec::new();
+    for line in stderr.lines() {
+        match line.strip_prefix(TRAILER) {
+            Some(json) => trailer = serde_json::from_str::<Trailer>(json).ok(),
+            None => errors.push(line),
+        }
+    }
+    let error = (!output.status.success()).then(|| match errors.join("\n").trim() {
+        "" => format!("exited with {}", output.status),
+        message => message.to_string(),
+    });
+
+    let coverage = match (&trailer, coverage) {
+        (
+            Some(Trailer {
+                lines: Some(lines),
+                functions,
+                executable,
+                definitions,
+                ..
+            }),
+            true,
+        ) => {
+            // Lua can't list a chunk's lines up front, so read them off the
+            // source; any line that ran counts as executable regardless
+            let (mut executable, mut definitions) = match (executable, definitions) {
+                (Some(executable), Some(definitions)) => (executable.clone(), definitions.clone()),
+                _ => scan_lua(&tokio::fs::read_to_string(program).await.unwrap_or_default()),
+            };
+            executable.extend(lines);
+            definitions.extend(functions);
+            Some(LineCoverage {
+                lines: lines.clone(),
+                executable,
+                functions: functions.clone(),
+                definitions,
+            })
+        }
+        _ => None,
+    };
+    Outcome {
+        output: String::from_utf8_lossy(&output.stdout).to_string(),
+        error,
+        timed_out: false,
+        duration: trailer
+            .as_ref()
+            .filter(|trailer| trailer.elapsed_us > 0)
+            .map_or(wall, |trailer| Duration::from_micros(trailer.elapsed_us)),
+        memory_kb: trailer.as_ref().map(|trailer| trailer.memory_kb),
+        coverage,
+    }
+}
+
+fn function_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\bfunction\b").unwrap())
+}
+
+// Lines that compile to code and lines that start a function, skipping
+// blanks, comments and lone keywords the line hook never reports
+fn scan_lua(source: &str) -> (BTreeSet<u32>, BTreeSet<u32>) {
+    let mut executable = BTreeSet::new();
+    let mut definitions = BTreeSet::new();
+    let mut in_comment = false;
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index as u32 + 1;
+        let line = line.trim();
+        if in_comment {
+            in_comment = !line.contains("]]");
+            continue;
+        }
+        if line.starts_with("--[[") || line.starts_with("--[=[") {
+            in_comment = !line.contains("]]");
+            continue;
+        }
+        if line.is_empty()
+            || line.starts_with("--")
+            || matches!(line, "else" | "do" | "then" | "repeat" | "{" | "}" | ")")
+        {
+            continue;
+        }
+        executable.insert(line_number);
+        if function_pattern().is_match(line) {
+            definitions.insert(line_number);
+        }
+    }
+    (executable, definitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interpreter_available(command: &str) -> bool {
+        std::process::Command::new(command)
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_scan_lua_skips_comments_and_lone_keywords() {
+        let source = "-- counter\nlocal count = 0\n\n--[[ bump\n  by one ]]\nfunction bump()\n  count = count + 1\nelse\nend\n";
+        let (executable, definitions) = scan_lua(source);
+        assert_eq!(executable.into_iter().collect::<Vec<_>>(), vec![2, 6, 7, 9]);
+        assert_eq!(definitions.into_iter().collect::<Vec<_>>(), vec![6]);
+    }
+
+    #[tokio::test]
+    async fn test_python_harness_reports_coverage_and_errors() {
+        if !interpreter_available("python3") {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("plugin.py");
+        std::fs::write(
+            &program,
+            "def double(x):\n    return x * 2\n\ndef unused():\n    return 0\n",
+        )
+        .unwrap();
+
+        let outcome = run_script(
+            ScriptLanguage::Python,
+            &program,
+            ["", "print(double(21))", ""],
+            true,
+            Duration::from_secs(10),
+        )
+        .await;
+        assert_eq!(outcome.error, None);
+        assert_eq!(outcome.output.trim(), "42");
+        let coverage = outcome.coverage.unwrap().to_data();
+        assert_eq!(
+            (coverage.functions_covered, coverage.functions_total),
+            (1, 2)
+        );
+        assert!(coverage.lines_covered < coverage.lines_total);
+
+        let outcome = run_script(
+            ScriptLanguage::Python,
+            &program,
+            ["", "raise ValueError('bad input')", ""],
+            false,
+            Duration::from_secs(10),
+        )
+        .await;
+        assert!(outcome.error.unwrap().contains("ValueError: bad input"));
+        assert!(outcome.coverage.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_script_is_killed_at_timeout() {
+        if !interpreter_available("python3") {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("plugin.py");
+        std::fs::write(&program, "import time\n").unwrap();
+        let outcome = run_script(
+            ScriptLanguage::Python,
+            &program,
+            ["", "time.sleep(30)", ""],
+            false,
+            Duration::from_millis(300),
+        )
+        .await;
+        assert!(outcome.timed_out);
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use tokio::sync::Mutex;
+
+use super::debugger::ScriptLanguage;
+use super::{
+    CoverageData, DevToolsConfig, PerformanceSnapshot, TestCase, TestExpectation, TestResult,
+    TestStatus, TestSuite,
+};
+use crate::config::WASMConfig;
+use crate::error::WarpError;
+use crate::package::Manifest;
+use crate::plugin_host::PluginHost;
+
+pub mod harness;
+pub mod panel;
+
+use harness::{LineCoverage, Outcome};
+
+// Used when neither the test nor its suite sets a timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+// Tests of a parallel suite run this many at a time
+const PARALLEL_TESTS: usize = 4;
+// Tests with this tag are reported as skipped without running
+const SKIP_TAG: &str = "skip";
+// The suite the test panel runs, next to the package manifest
+pub const SUITE_FILE: &str = "warp-tests.toml";
+
+// Runs test suites against plugins under development: script entry points
+// in a fresh interpreter per test, WASM plugins in a sandbox of their own
+pub struct TestingFramework {
+    config: Arc<Mutex<DevToolsConfig>>,
+    item_dirs: Mutex<HashMap<String, PathBuf>>,
+    host: PluginHost,
+    // Coverage merged over the last run of each item's suite
+    coverage: Mutex<HashMap<String, LineCoverage>>,
+}
+
+// What a test runs against
+#[derive(Clone)]
+enum Target {
+    Script(ScriptLanguage, PathBuf),
+    Wasm(PathBuf),
+}
+
+impl TestingFramework {
+    pub async fn new(
+        config: Arc<Mutex<DevToolsConfig>>,
+        wasm: &WASMConfig,
+    ) -> Result<Self, WarpError> {
+        Ok(Self {
+            config,
+            item_dirs: Mutex::new(HashMap::new()),
+            host: PluginHost::new(wasm)?,
+            coverage: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn register_item_dir(&self, item_id: &str, dir: PathBuf) {
+        self.item_dirs.lock().await.insert(item_id.to_string(), dir);
+    }
+
+    async fn target(&self, item_id: &str) -> Result<Target, WarpError> {
+        let dir = match self.item_dirs.lock().await.get(item_id) {
+            Some(dir) => dir.clone(),
+            None => dirs::config_dir()
+                .unwrap_or_default()
+                .join("warp/plugins")
+                .join(item_id),
+        };
+        let manifest = Manifest::load(&dir).await?;
+        let Some(entry_point) = manifest.entry_point.as_ref() else {
+            return Err(WarpError::Validation(format!(
+                "{} has no entry_point to test",
+                manifest.id
+            )));
+        };
+        // Interpreters resolve the program relative to their own cwd
+        let program = tokio::fs::canonicalize(dir.join(entry_point)).await?;
+        match ScriptLanguage::from_path(&program) {
+            Some(ScriptLanguage::Wasm) => Ok(Target::Wasm(program)),
+            Some(language) => Ok(Target::Script(language, program)),
+            None => Err(WarpError::Validation(format!(
+                "don't know how to run tests for {}",
+                entry_point
+            ))),
+        }
+    }
+
+    // Results come back in the suite's order, whether or not it ran in
+    // parallel
+    pub async fn run_test_suite(
+        &self,
+        item_id: &str,
+        suite: &TestSuite,
+    ) -> Result<Vec<TestResult>, WarpError> {
+        let target = self.target(item_id).await?;
+        let coverage = {
+            let config = self.config.lock().await;
+            config.testing_framework_enabled && config.code_coverage_enabled
+        };
+        let concurrency = if suite.parallel { PARALLEL_TESTS } else { 1 };
+        // By index: a closure over &TestCase makes this future fail the
+        // higher-ranked Send check when it's spawned
+        let runs: Vec<(TestResult, Option<LineCoverage>)> = stream::iter(0..suite.tests.len())
+            .map(|index| self.run_test(item_id, &target, suite, &suite.tests[index], coverage))
+            .buffered(concurrency)
+            .collect()
+            .await;
+
+        let mut merged = LineCoverage::default();
+        let results = runs
+            .into_iter()
+            .map(|(result, lines)| {
+                if let Some(lines) = lines {
+                    merged.merge(&lines);
+                }
+                result
+            })
+            .collect();
+        if coverage {
+            self.coverage
+                .lock()
+                .await
+                .insert(item_id.to_string(), merged);
+        }
+        Ok(results)
+    }
+
+    // Coverage of every test in the item's last run, not just the latest
+    // re-run
+    pub async fn suite_coverage(&self, item_id: &str) -> Option<CoverageData> {
+        self.coverage
+            .lock()
+            .await
+            .get(item_id)
+            .map(LineCoverage::to_data)
+    }
+
+    // Re-runs the named tests, e.g. the failures from the last run. Their
+    // coverage is merged into the earlier run's.
+    pub async fn rerun(
+        &self,
+        item_id: &str,
+        suite: &TestSuite,
+        names: &[String],
+    ) -> Result<Vec<TestResult>, WarpError> {
+        let earlier = self.coverage.lock().await.get(item_id).cloned();
+        let results = self.run_test_suite(item_id, &select(suite, names)).await?;
+        if let Some(earlier) = earlier {
+            if let Some(coverage) = self.coverage.lock().await.get_mut(item_id) {
+                coverage.merge(&earlier);
+            }
+        }
+        Ok(results)
+    }
+
+    async fn run_test(
+        &self,
+        item_id: &str,
+        target: &Target,
+        suite: &TestSuite,
+        test: &TestCase,
+        coverage: bool,
+    ) -> (TestResult, Option<LineCoverage>) {
+        if test.tags.iter().any(|tag| tag == SKIP_TAG) {
+            let result = TestResult {
+                test_name: test.name.clone(),
+                status: TestStatus::Skipped,
+                duration: Duration::ZERO,
+                output: String::new(),
+                error: None,
+                performance_data: None,
+                coverage_data: None,
+            };
+            return (result, None);
+        }
+
+        let timeout = timeout_for(suite, test);
+        let setup = suite.setup.as_deref().unwrap_or_default();
+        let teardown = suite.teardown.as_deref().unwrap_or_default();
+        let outcome = match target {
+            Target::Script(language, program) => {
+                harness::run_script(
+                    *language,
+                    program,
+                    [setup, &test.code, teardown],
+                    coverage,
+                    timeout,
+                )
+                .await
+            }
+            Target::Wasm(program) => {
+                self.run_wasm(item_id, program, [setup, &test.code, teardown], timeout)
+                    .await
+            }
+        };
+
+        let (status, error) = judge(&test.expected_result, &outcome);
+        let result = TestResult {
+            test_name: test.name.clone(),
+            status,
+            duration: outcome.duration,
+            output: outcome.output,
+            error,
+            performance_data: outcome.memory_kb.map(|memory_kb| PerformanceSnapshot {
+                timestamp: chrono::Utc::now(),
+                cpu_usage: 0.0,
+                memory_usage: memory_kb * 1024,
+                heap_size: memory_kb * 1024,
+                gc_pressure: 0.0,
+                thread_count: 1,
+                active_handles: 0,
+            }),
+            coverage_data: outcome.coverage.as_ref().map(LineCoverage::to_data),
+        };
+        (result, outcome.coverage)
+    }
+
+    // Setup, test and teardown are each sent as a command to a fresh
+    // instance of the module. Wasmtime has no line coverage to offer.
+    async fn run_wasm(
+        &self,
+        item_id: &str,
+        program: &Path,
+        blocks: [&str; 3],
+        timeout: Duration,
+    ) -> Outcome {
+        let name = format!("{}#test-{}", item_id, uuid::Uuid::new_v4());
+        if let Err(e) = self.host.load(&name, program).await {
+            return Outcome {
+                error: Some(e.to_string()),
+                ..Outcome::default()
+            };
+        }
+        let [setup, test, teardown] = blocks;
+        let mut outcome = Outcome::default();
+        let mut reply = Ok(String::new());
+        if !setup.is_empty() {
+            reply = self.host.call_with_timeout(&name, setup, timeout).await;
+        }
+        if reply.is_ok() {
+            let started = std::time::Instant::now();
+            reply = self.host.call_with_timeout(&name, test, timeout).await;
+            outcome.duration = started.elapsed();
+            // The deadline counts epoch ticks, so the trap can come a tick
+            // or two before the timeout
+            outcome.timed_out =
+                reply.is_err() && outcome.duration + Duration::from_millis(20) >= timeout;
+        }
+        if !teardown.is_empty() {
+            let cleanup = self.host.call_with_timeout(&name, teardown, timeout).await;
+            if reply.is_ok() {
+                reply = cleanup.map(|_| reply.unwrap_or_default());
+            }
+        }
+        outcome.memory_kb = self
+            .host
+            .usage()
+            .await
+            .into_iter()
+            .find(|usage| usage.name == name)
+            .map(|usage| usage.memory as u64 / 1024);
+        self.host.unload(&name).await;
+
+        match reply {
+            Ok(output) => outcome.output = output,
+            Err(e) => outcome.error = Some(e.to_string()),
+        }
+        outcome
+    }
+}
+
+// Test timeouts are in milliseconds; 0 falls back to the suite's
+fn timeout_for(suite: &TestSuite, test: &TestCase) -> Duration {
+    match (test.timeout, suite.timeout) {
+        (0, 0) => DEFAULT_TIMEOUT,
+        (0, suite) => Duration::from_millis(suite),
+        (test, _) => Duration::from_millis(test),
+    }
+}
+
+// The suite with only the named tests, in its own order
+pub fn select(suite: &TestSuite, names: &[String]) -> TestSuite {
+    TestSuite {
+        tests: suite
+            .tests
+            .iter()
+            .filter(|test| names.contains(&test.name))
+            .cloned()
+            .collect(),
+        ..suite.clone()
+    }
+}
+
+pub async fn load_suite(dir: &Path) -> Result<TestSuite, WarpError> {
+    let text = tokio::fs::read_to_string(dir.join(SUITE_FILE)).await?;
+    toml::from_str(&text).map_err(|e| WarpError::config_err(format!("{}: {}", SUITE_FILE, e)))
+}
+
+fn judge(expectation: &TestExpectation, outcome: &Outcome) -> (TestStatus, Option<String>) {
+    if outcome.timed_out {
+        return (TestStatus::Timeout, outcome.error.clone());
+    }
+    let failed = |message: String| (TestStatus::Failed, Some(message));
+    match (expectation, &outcome.error) {
+        (TestExpectation::Failure(expected), Some(error)) if error.contains(expected.as_str()) => {
+            (TestStatus::Passed, None)
+        }
+        (TestExpectation::Failure(expected), Some(error)) => failed(format!(
+            "expected an error containing {:?}, got: {}",
+            expected, error
+        )),
+        (TestExpectation::Failure(expected), None) => {
+            failed(format!("expected an error containing {:?}", expected))
+        }
+        (_, Some(error)) => failed(error.clone()),
+        (TestExpectation::Success, None) => (TestStatus::Passed, None),
+        (TestExpectation::Output(expected), None) if outcome.output.trim() == expected.trim() => {
+            (TestStatus::Passed, None)
+        }
+        (TestExpectation::Output(expected), None) => failed(format!(
+            "expected output {:?}, got {:?}",
+            expected.trim(),
+            outcome.output.trim()
+        )),
+        (
+            TestExpectation::Performance {
+                max_time_ms,
+                max_memory_mb,
+            },
+            None,
+        ) => {
+            let took = outcome.duration.as_millis() as u64;
+            if took > *max_time_ms {
+                return failed(format!(
+                    "took {}ms, over the {}ms budget",
+                    took, max_time_ms
+                ));
+            }
+            match outcome.memory_kb {
+                Some(memory_kb) if memory_kb > max_memory_mb * 1024 => failed(format!(
+                    "used {}MB, over the {}MB budget",
+                    memory_kb / 1024,
+                    max_memory_mb
+                )),
+                _ => (TestStatus::Passed, None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_tools::TestType;
+
+    fn case(name: &str, timeout: u64) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            description: String::new(),
+            test_type: TestType::Unit,
+            code: String::new(),
+            expected_result: TestExpectation::Success,
+            timeout,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_judge_checks_expectations_and_budgets() {
+        let outcome = Outcome {
+            output: "42\n".to_string(),
+            duration: Duration::from_millis(120),
+            memory_kb: Some(64 * 1024),
+            ..Outcome::default()
+        };
+        let status =
+            |expectation: TestExpectation| format!("{:?}", judge(&expectation, &outcome).0);
+        assert_eq!(status(TestExpectation::Success), "Passed");
+        assert_eq!(status(TestExpectation::Output("42".to_string())), "Passed");
+        assert_eq!(status(TestExpectation::Output("41".to_string())), "Failed");
+        assert_eq!(
+            status(TestExpectation::Failure("boom".to_string())),
+            "Failed"
+        );
+        assert_eq!(
+            status(TestExpectation::Performance {
+                max_time_ms: 200,
+                max_memory_mb: 128
+            }),
+            "Passed"
+        );
+        assert_eq!(
+            status(TestExpectation::Performance {
+                max_time_ms: 100,
+                max_memory_mb: 128
+            }),
+            "Failed"
+        );
+        assert_eq!(
+            status(TestExpectation::Performance {
+                max_time_ms: 200,
+                max_memory_mb: 32
+            }),
+            "Failed"
+        );
+
+        let errored = Outcome {
+            error: Some("ValueError: boom".to_string()),
+            ..Outcome::default()
+        };
+        assert!(matches!(
+            judge(&TestExpectation::Failure("boom".to_string()), &errored).0,
+            TestStatus::Passed
+        ));
+        let timed_out = Outcome {
+            timed_out: true,
+            ..errored
+        };
+        assert!(matches!(
+            judge(&TestExpectation::Failure("boom".to_string()), &timed_out).0,
+            TestStatus::Timeout
+        ));
+    }
+
+    #[test]
+    fn test_timeouts_fall_back_and_select_keeps_order() {
+        let suite = TestSuite {
+            name: "suite".to_string(),
+            tests: vec![case("a", 0), case("b", 250), case("c", 0)],
+            setup: None,
+            teardown: None,
+            timeout: 1000,
+            parallel: false,
+        };
+        assert_eq!(
+            timeout_for(&suite, &suite.tests[0]),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            timeout_for(&suite, &suite.tests[1]),
+            Duration::from_millis(250)
+        );
+
+        let names: Vec<_> = select(&suite, &["c".to_string(), "a".to_string()])
+            .tests
+            .into_iter()
+            .map(|test| test.name)
+            .collect();
+        assert_eq!(names, vec!["a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_suite_loads_from_the_package_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(
+            dir.path().join(SUITE_FILE),
+            r#"
+name = "counter"
+timeout = 5000
+parallel = false
+
+[[tests]]
+name = "prints"
+description = ""
+test_type = "Unit"
+code = "print(42)"
+expected_result = { Output = "42" }
+timeout = 0
+tags = []
+"#,
+        )
+        .await
+        .unwrap();
+        let suite = load_suite(dir.path()).await.unwrap();
+        assert_eq!(suite.name, "counter");
+        assert!(
+            matches!(&suite.tests[0].expected_result, TestExpectation::Output(output) if output == "42")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crossterm::style::Color;
+
+use crate::dev_tools::{CoverageData, TestResult, TestStatus};
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestCommand {
+    None,
+    Redraw,
+    // Run these tests again; the app feeds the results back through
+    // update_results
+    Rerun(Vec<String>),
+}
+
+// Results of one suite run, with the selected test's output and error in
+// the preview
+pub struct TestPanel {
+    suite: String,
+    results: Vec<TestResult>,
+    coverage: Option<CoverageData>,
+    selected: usize,
+}
+
+fn is_failure(status: &TestStatus) -> bool {
+    matches!(
+        status,
+        TestStatus::Failed | TestStatus::Timeout | TestStatus::Error
+    )
+}
+
+impl TestPanel {
+    pub fn new(suite: &str) -> Self {
+        Self {
+            suite: suite.to_string(),
+            results: Vec::new(),
+            coverage: None,
+            selected: 0,
+        }
+    }
+
+    pub fn set_results(&mut self, results: Vec<TestResult>, coverage: Option<CoverageData>) {
+        self.results = results;
+        self.coverage = coverage;
+        self.selected = self.selected.min(self.results.len().saturating_sub(1));
+    }
+
+    // Replaces the re-run tests' results and leaves the rest alone
+    pub fn update_results(&mut self, results: Vec<TestResult>, coverage: Option<CoverageData>) {
+        for result in results {
+            match self
+                .results
+                .iter_mut()
+                .find(|r| r.test_name == result.test_name)
+            {
+                Some(existing) => *existing = result,
+                None => self.results.push(result),
+            }
+        }
+        if coverage.is_some() {
+            self.coverage = coverage;
+        }
+    }
+
+    pub fn failures(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|result| is_failure(&result.status))
+            .map(|result| result.test_name.clone())
+            .collect()
+    }
+
+    // Keys: Enter show output · r re-run test · f re-run failures · a re-run all
+    pub fn handle_key(&mut self, key: char, index: usize) -> TestCommand {
+        let selected = self
+            .results
+            .get(index)
+            .map(|result| result.test_name.clone());
+        match (key, selected) {
+            ('\n', Some(_)) => {
+                self.selected = index;
+                TestCommand::Redraw
+            }
+            ('r', Some(name)) => TestCommand::Rerun(vec![name]),
+            ('f', _) => match self.failures() {
+                failures if failures.is_empty() => TestCommand::None,
+                failures => TestCommand::Rerun(failures),
+            },
+            ('a', _) if !self.results.is_empty() => TestCommand::Rerun(
+                self.results
+                    .iter()
+                    .map(|result| result.test_name.clone())
+                    .collect(),
+            ),
+            _ => TestCommand::None,
+        }
+    }
+
+    fn preview(&self) -> Vec<PanelItem> {
+        let Some(result) = self.results.get(self.selected) else {
+            return Vec::new();
+        };
+        let mut preview: Vec<PanelItem> = result
+            .output
+            .lines()
+            .map(|line| line.to_string().into())
+            .collect();
+        if let Some(error) = &result.error {
+            preview.extend(
+                error
+                    .lines()
+                    .map(|line| PanelItem::styled(vec![(line.to_string(), Some(Color::Red))])),
+            );
+        }
+        if let Some(coverage) = &result.coverage_data {
+            preview.push(PanelItem::styled(vec![(
+                i18n::t_args(
+                    "panel.tests_coverage",
+                    &[
+                        ("percent", &format!("{:.0}", coverage.coverage_percentage)),
+                        (
+                            "lines",
+                            &format!("{}/{}", coverage.lines_covered, coverage.lines_total),
+                        ),
+                        (
+                            "functions",
+                            &format!(
+                                "{}/{}",
+                                coverage.functions_covered, coverage.functions_total
+                            ),
+                        ),
+                    ],
+                ),
+                Some(Color::DarkGrey),
+            )]));
+        }
+        preview
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .results
+            .iter()
+            .map(|result| {
+                let (marker, color) = match result.status {
+                    TestStatus::Passed => ("✓", Color::Green),
+                    TestStatus::Failed => ("✖", Color::Red),
+                    TestStatus::Timeout => ("⏱", Color::Red),
+                    TestStatus::Error => ("!", Color::Red),
+                    TestStatus::Skipped => ("○", Color::DarkGrey),
+                };
+                let mut segments = vec![
+                    (format!("{} ", marker), Some(color)),
+                    (result.test_name.clone(), None),
+                    (
+                        format!("  {}ms", result.duration.as_millis()),
+                        Some(Color::DarkGrey),
+                    ),
+                ];
+                if let Some(error) = result.error.as_ref().and_then(|error| error.lines().next()) {
+                    segments.push((format!("  {}", error), Some(Color::Red)));
+                }
+                PanelItem::styled(segments)
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.tests_empty").into());
+        }
+
+        let count = |pick: fn(&TestStatus) -> bool| {
+            self.results
+                .iter()
+                .filter(|r| pick(&r.status))
+                .count()
+                .to_string()
+        };
+        let coverage = match &self.coverage {
+            Some(coverage) => format!("{:.0}%", coverage.coverage_percentage),
+            None => "-".to_string(),
+        };
+        PanelView {
+            kind: PanelKind::TestRunner,
+            title: i18n::t_args(
+                "panel.tests",
+                &[
+                    ("suite", &self.suite),
+                    (
+                        "passed",
+                        &count(|status| matches!(status, TestStatus::Passed)),
+                    ),
+                    ("failed", &count(is_failure)),
+                    ("coverage", &coverage),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.tests_keys")),
+            preview: self.preview(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn result(name: &str, status: TestStatus) -> TestResult {
+        TestResult {
+            test_name: name.to_string(),
+            status,
+            duration: Duration::from_millis(5),
+            output: String::new(),
+            error: None,
+            performance_data: None,
+            coverage_data: None,
+        }
+    }
+
+    #[test]
+    fn test_rerun_failures_and_merge_results() {
+        let mut panel = TestPanel::new("counter");
+        panel.set_results(
+            vec![
+                result("adds", TestStatus::Passed),
+                result("resets", TestStatus::Failed),
+                result("slow", TestStatus::Timeout),
+                result("later", TestStatus::Skipped),
+            ],
+            None,
+        );
+        assert_eq!(
+            panel.handle_key('f', 0),
+            TestCommand::Rerun(vec!["resets".to_string(), "slow".to_string()])
+        );
+        assert_eq!(
+            panel.handle_key('r', 0),
+            TestCommand::Rerun(vec!["adds".to_string()])
+        );
+
+        panel.update_results(vec![result("resets", TestStatus::Passed)], None);
+        assert_eq!(panel.failures(), vec!["slow".to_string()]);
+        assert_eq!(panel.view().items.len(), 4);
+    }
+}
@@ -0,0 +1,466 @@
+use chrono::{DateTime, Local};
+use crossterm::style::Color;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Oldest traces drop off past this
+const MAX_BLOCKS: usize = 50;
+const BAR_WIDTH: usize = 20;
+
+// The steps a block's command and output go through, by the span names the
+// pipeline opens for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Spawn,
+    PtyRead,
+    Parse,
+    AiHooks,
+    Render,
+    HistoryPersist,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Spawn,
+        Stage::PtyRead,
+        Stage::Parse,
+        Stage::AiHooks,
+        Stage::Render,
+        Stage::HistoryPersist,
+    ];
+
+    pub fn span_name(self) -> &'static str {
+        match self {
+            Stage::Spawn => "spawn",
+            Stage::PtyRead => "pty_read",
+            Stage::Parse => "parse",
+            Stage::AiHooks => "ai_hooks",
+            Stage::Render => "render",
+            Stage::HistoryPersist => "history_persist",
+        }
+    }
+
+    fn from_span_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|stage| stage.span_name() == name)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn label(self) -> String {
+        i18n::t(match self {
+            Stage::Spawn => "trace.stage_spawn",
+            Stage::PtyRead => "trace.stage_pty_read",
+            Stage::Parse => "trace.stage_parse",
+            Stage::AiHooks => "trace.stage_ai_hooks",
+            Stage::Render => "trace.stage_render",
+            Stage::HistoryPersist => "trace.stage_history",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageTiming {
+    pub spans: u32,
+    pub total: Duration,
+    pub max: Duration,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockTrace {
+    pub block_id: String,
+    pub command: String,
+    pub at: DateTime<Local>,
+    started: Instant,
+    last: Instant, // when the latest span ended
+    stages: [StageTiming; Stage::ALL.len()],
+}
+
+impl BlockTrace {
+    fn new(block_id: &str, command: &str) -> Self {
+        let now = Instant::now();
+        Self {
+            block_id: block_id.to_string(),
+            command: command.to_string(),
+            at: Local::now(),
+            started: now,
+            last: now,
+            stages: Default::default(),
+        }
+    }
+
+    pub fn stage(&self, stage: Stage) -> &StageTiming {
+        &self.stages[stage.index()]
+    }
+
+    // From submitting the command to the last traced work on its output
+    pub fn wall(&self) -> Duration {
+        self.last - self.started
+    }
+
+    // Time spent in the pipeline; the rest of the wall time is the command
+    // itself, or the shell, running
+    pub fn busy(&self) -> Duration {
+        self.stages.iter().map(|timing| timing.total).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceCommand {
+    None,
+    Redraw,
+}
+
+// Per-block timings for a sample of blocks, so "this felt slow" can be
+// answered with where the time went. Spans are attributed to the block
+// that's current when they end; unsampled blocks cost a lock per span.
+#[derive(Default)]
+pub struct BlockTracer {
+    rate: f64,
+    credit: f64,
+    trace_next: bool,
+    current: Option<BlockTrace>, // None while the current block isn't sampled
+    finished: VecDeque<BlockTrace>,
+    selected: usize,
+}
+
+impl BlockTracer {
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            ..Self::default()
+        }
+    }
+
+    // Every 1/rate-th block is traced, evenly rather than at random, plus
+    // the next one after the user asks
+    pub fn start_block(&mut self, block_id: &str, command: &str) {
+        if let Some(trace) = self.current.take() {
+            self.finished.push_back(trace);
+            if self.finished.len() > MAX_BLOCKS {
+                self.finished.pop_front();
+            }
+        }
+        self.credit += self.rate;
+        let sampled = if std::mem::take(&mut self.trace_next) {
+            true
+        } else if self.credit >= 1.0 {
+            self.credit -= 1.0;
+            true
+        } else {
+            false
+        };
+        if sampled {
+            self.current = Some(BlockTrace::new(block_id, command));
+        }
+    }
+
+    pub fn sampling(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn record(&mut self, stage: Stage, elapsed: Duration, bytes: u64) {
+        // An empty read is the idle loop polling, not work for the block
+        if stage == Stage::PtyRead && bytes == 0 {
+            return;
+        }
+        let Some(trace) = self.current.as_mut() else {
+            return;
+        };
+        let timing = &mut trace.stages[stage.index()];
+        timing.spans += 1;
+        timing.total += elapsed;
+        timing.max = timing.max.max(elapsed);
+        timing.bytes += bytes;
+        trace.last = Instant::now();
+    }
+
+    // Oldest first, the block still running last
+    pub fn traces(&self) -> impl Iterator<Item = &BlockTrace> {
+        self.finished.iter().chain(self.current.as_ref())
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.traces().count().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> TraceCommand {
+        self.select(index);
+        match key {
+            'n' => {
+                self.trace_next = true;
+                TraceCommand::Redraw
+            }
+            'c' => {
+                self.finished.clear();
+                self.selected = 0;
+                TraceCommand::Redraw
+            }
+            _ => TraceCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let count = self.traces().count();
+        let items = self
+            .traces()
+            .enumerate()
+            .map(|(i, trace)| {
+                let running = i + 1 == count && self.current.is_some();
+                PanelItem::styled(vec![
+                    (
+                        format!("{} ", trace.at.format("%H:%M:%S")),
+                        Some(Color::DarkGrey),
+                    ),
+                    (format!("{} ", trace.command), Some(Color::Cyan)),
+                    if running {
+                        (i18n::t("trace.running"), Some(Color::Yellow))
+                    } else {
+                        (millis(trace.wall()), None)
+                    },
+                ])
+            })
+            .collect();
+        let mut preview: Vec<PanelItem> = match self.traces().nth(self.selected) {
+            Some(trace) => Self::breakdown(trace),
+            None => vec![i18n::t("trace.empty").into()],
+        };
+        if self.trace_next {
+            preview.push(PanelItem::styled(vec![(
+                i18n::t("trace.next_armed"),
+                Some(Color::Yellow),
+            )]));
+        }
+        PanelView {
+            kind: PanelKind::Traces,
+            title: i18n::t_args(
+                "panel.traces",
+                &[("rate", &format!("{:.0}", self.rate * 100.0))],
+            ),
+            items,
+            footer: Some(i18n::t("panel.traces_keys")),
+            preview,
+            image: None,
+        }
+    }
+
+    // A row per stage with its share of the wall time as a bar
+    fn breakdown(trace: &BlockTrace) -> Vec<PanelItem> {
+        let wall = trace.wall().as_secs_f64();
+        let share = |elapsed: Duration| {
+            if wall > 0.0 {
+                (elapsed.as_secs_f64() / wall).min(1.0)
+            } else {
+                0.0
+            }
+        };
+        let mut lines = vec![PanelItem::styled(vec![(
+            i18n::t_args(
+                "trace.wall",
+                &[("ms", &millis(trace.wall())), ("block", &trace.block_id)],
+            ),
+            Some(Color::DarkGrey),
+        )])];
+        for stage in Stage::ALL {
+            let timing = trace.stage(stage);
+            let filled = (share(timing.total) * BAR_WIDTH as f64).round() as usize;
+            let mut detail = i18n::t_args(
+                "trace.stage_detail",
+                &[
+                    ("spans", &timing.spans.to_string()),
+                    ("max", &millis(timing.max)),
+                ],
+            );
+            if timing.bytes > 0 {
+                detail.push_str(&format!(" · {} B", timing.bytes));
+            }
+            lines.push(PanelItem::styled(vec![
+                (format!("{:<24}", stage.label()), None),
+                (format!("{:>10} ", millis(timing.total)), Some(Color::Cyan)),
+                (
+                    format!("{:<width$} ", "█".repeat(filled), width = BAR_WIDTH),
+                    Some(Color::Green),
+                ),
+                (detail, Some(Color::DarkGrey)),
+            ]));
+        }
+        let outside = trace.wall().saturating_sub(trace.busy());
+        lines.push(i18n::t_args("trace.outside", &[("ms", &millis(outside))]).into());
+        lines
+    }
+}
+
+fn millis(elapsed: Duration) -> String {
+    format!("{:.1} ms", elapsed.as_secs_f64() * 1000.0)
+}
+
+// Feeds the pipeline's spans into the tracer. Installed as the global
+// tracing subscriber, so other layers can be added next to it.
+pub struct TraceLayer {
+    tracer: Arc<Mutex<BlockTracer>>,
+}
+
+impl TraceLayer {
+    pub fn new(tracer: Arc<Mutex<BlockTracer>>) -> Self {
+        Self { tracer }
+    }
+}
+
+pub fn install(tracer: Arc<Mutex<BlockTracer>>) {
+    let subscriber = tracing_subscriber::registry().with(TraceLayer::new(tracer));
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        log::debug!("A tracing subscriber is already set; block traces are off");
+    }
+}
+
+struct Timing {
+    stage: Stage,
+    started: Instant,
+    bytes: u64,
+}
+
+// The optional `bytes` field a span carries
+struct Bytes(u64);
+
+impl Visit for Bytes {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "bytes" {
+            self.0 = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(stage) = Stage::from_span_name(attrs.metadata().name()) else {
+            return;
+        };
+        if !self.tracer.lock().unwrap().sampling() {
+            return;
+        }
+        let mut bytes = Bytes(0);
+        attrs.record(&mut bytes);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Timing {
+                stage,
+                started: Instant::now(),
+                bytes: bytes.0,
+            });
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<Timing>() {
+            let mut bytes = Bytes(timing.bytes);
+            values.record(&mut bytes);
+            timing.bytes = bytes.0;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        if let Some(timing) = extensions.get::<Timing>() {
+            self.tracer.lock().unwrap().record(
+                timing.stage,
+                timing.started.elapsed(),
+                timing.bytes,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling() {
+        let mut tracer = BlockTracer::new(0.25);
+        let sampled: Vec<bool> = (0..8)
+            .map(|i| {
+                tracer.start_block(&i.to_string(), "ls");
+                tracer.sampling()
+            })
+            .collect();
+        assert_eq!(sampled.iter().filter(|sampled| **sampled).count(), 2);
+
+        let mut tracer = BlockTracer::new(0.0);
+        tracer.start_block("1", "ls");
+        assert!(!tracer.sampling());
+        assert_eq!(tracer.handle_key('n', 0), TraceCommand::Redraw);
+        tracer.start_block("2", "cargo build");
+        assert!(tracer.sampling());
+        tracer.start_block("3", "ls");
+        assert!(!tracer.sampling());
+        assert_eq!(tracer.traces().count(), 1);
+    }
+
+    #[test]
+    fn test_spans_are_timed_into_the_block() {
+        let tracer = Arc::new(Mutex::new(BlockTracer::new(1.0)));
+        tracer.lock().unwrap().start_block("b1", "make");
+        let subscriber = tracing_subscriber::registry().with(TraceLayer::new(tracer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let read = tracing::trace_span!("pty_read", bytes = tracing::field::Empty);
+            read.record("bytes", 42u64);
+            drop(read);
+            // Idle polls and unrelated spans don't count
+            drop(tracing::trace_span!("pty_read", bytes = 0u64));
+            drop(tracing::trace_span!("unrelated"));
+            let _render = tracing::trace_span!("render").entered();
+            std::thread::sleep(Duration::from_millis(2));
+        });
+
+        let tracer = tracer.lock().unwrap();
+        let trace = tracer.traces().next().unwrap();
+        assert_eq!(trace.stage(Stage::PtyRead).spans, 1);
+        assert_eq!(trace.stage(Stage::PtyRead).bytes, 42);
+        assert_eq!(trace.stage(Stage::Render).spans, 1);
+        assert!(trace.stage(Stage::Render).total >= Duration::from_millis(2));
+        assert!(trace.wall() >= trace.busy());
+        assert_eq!(trace.stage(Stage::Spawn).spans, 0);
+    }
+
+    #[test]
+    fn test_view() {
+        let mut tracer = BlockTracer::new(1.0);
+        assert_eq!(tracer.view().items.len(), 0);
+        tracer.start_block("b1", "ls");
+        tracer.record(Stage::Parse, Duration::from_millis(3), 0);
+        tracer.start_block("b2", "pwd");
+        let view = tracer.view();
+        assert_eq!(view.items.len(), 2);
+        // Header, one row per stage and the time outside the pipeline
+        assert_eq!(view.preview.len(), Stage::ALL.len() + 2);
+        assert_eq!(tracer.handle_key('c', 1), TraceCommand::Redraw);
+        assert_eq!(tracer.traces().count(), 1);
+        assert_eq!(tracer.handle_key('q', 0), TraceCommand::None);
+    }
+}
@@ -0,0 +1,966 @@
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+use crate::correction::edit_distance;
+use crate::error::WarpError;
+use crate::package::{Manifest, PackageKind, MANIFEST, MANIFEST_SCHEMA_VERSION};
+use crate::plugin_host::{HOST_IMPORTS, REQUIRED_EXPORTS};
+
+// The marketplace rejects larger uploads
+pub const MAX_PACKAGE_BYTES: u64 = 10 * 1024 * 1024;
+// What a plugin may ask for in its manifest
+pub const PLUGIN_PERMISSIONS: &[&str] = &[
+    "clipboard",
+    "filesystem",
+    "network",
+    "notifications",
+    "shell",
+];
+// Host imports that only work with a permission in the manifest
+const IMPORT_PERMISSIONS: &[(&str, &str)] = &[("read_file", "filesystem"), ("http_get", "network")];
+const THEME_KEYS: &[&str] = &["accent", "background", "foreground", "terminal_colors"];
+const PALETTES: &[&str] = &["normal", "bright"];
+const PALETTE_COLORS: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+// Manifest schemas by schema_version
+const SCHEMAS: &[(u32, &str)] = &[(1, include_str!("../../schemas/warp-package.v1.json"))];
+const WORKFLOW_KEYS: &[&str] = &["name", "command"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub file: String,
+    pub message: String,
+    // How to fix it, when there's an obvious way
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    fn suggest(&mut self, suggestion: impl Into<String>) {
+        self.suggestion = Some(suggestion.into());
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub manifest: Option<Manifest>,
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, severity: Severity, file: &str, message: String) -> &mut Finding {
+        self.findings.push(Finding {
+            severity,
+            file: file.to_string(),
+            message,
+            suggestion: None,
+        });
+        self.findings.last_mut().unwrap()
+    }
+
+    fn error(&mut self, file: &str, message: impl Into<String>) -> &mut Finding {
+        self.push(Severity::Error, file, message.into())
+    }
+
+    fn warn(&mut self, file: &str, message: impl Into<String>) -> &mut Finding {
+        self.push(Severity::Warning, file, message.into())
+    }
+
+    pub fn errors(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+            .count()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.manifest.is_some() && self.errors() == 0
+    }
+}
+
+fn id_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^[a-z0-9][a-z0-9-]*/[a-z0-9][a-z0-9-]*$").unwrap())
+}
+
+fn version_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^\d+\.\d+\.\d+(-[0-9A-Za-z.-]+)?$").unwrap())
+}
+
+fn color_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r#"^\s*[a-z_]+:\s*"?(#[^"\s]*)"?\s*$"#).unwrap())
+}
+
+// Bundled paths stay inside the package directory
+fn is_contained(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+// The top-level keys of a YAML file; the package formats are flat enough
+// that indentation is all that's needed
+fn top_level_keys(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter(|line| !line.starts_with([' ', '\t', '#', '-']))
+        .filter_map(|line| line.split_once(':').map(|(key, _)| key.trim()))
+        .collect()
+}
+
+// 1.2 -> 1.2.0, v2 -> 2.0.0; anything unparseable becomes 0.1.0
+fn as_semver(version: &str) -> String {
+    let parts: Vec<u64> = version
+        .trim_start_matches('v')
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    match parts.as_slice() {
+        [] => "0.1.0".to_string(),
+        [major] => format!("{}.0.0", major),
+        [major, minor] => format!("{}.{}.0", major, minor),
+        [major, minor, patch, ..] => format!("{}.{}.{}", major, minor, patch),
+    }
+}
+
+// The nearest candidate within a couple of edits, for "did you mean"
+fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn article(kind: &str) -> &'static str {
+    if kind.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+// The part of JSON Schema the manifest schemas use: type, enum, required,
+// properties, additionalProperties, items and uniqueItems. Suggestions come
+// from each property's examples. Keys the schema doesn't list are warnings
+// so a manifest using a newer field still loads on an older Warp.
+fn check_schema(report: &mut ValidationReport, schema: &Value, value: &Value, path: &str) {
+    let example = |key: &str| {
+        schema["properties"][key]["examples"]
+            .get(0)
+            .map(|example| format!("{} = {}", key, example))
+    };
+    if let Some(expected) = schema["type"].as_str() {
+        let actual = type_name(value);
+        if actual != expected && !(expected == "number" && actual == "integer") {
+            let finding = report.error(
+                MANIFEST,
+                format!(
+                    "'{}' should be {} {}, not {} {}",
+                    path,
+                    article(expected),
+                    expected,
+                    article(actual),
+                    actual
+                ),
+            );
+            if let Some(example) = schema["examples"].get(0) {
+                finding.suggest(format!("{} = {}", path, example));
+            }
+            return;
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let names: Vec<String> = allowed
+                .iter()
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+                .collect();
+            let finding = report.error(
+                MANIFEST,
+                format!(
+                    "'{}' is {}, expected one of {}",
+                    path,
+                    value,
+                    names.join(", ")
+                ),
+            );
+            if let Some(nearest) = value
+                .as_str()
+                .and_then(|v| closest(v, names.iter().map(String::as_str)))
+            {
+                finding.suggest(format!("{} = \"{}\"", path, nearest));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            for key in schema["required"].as_array().into_iter().flatten() {
+                let Some(key) = key.as_str() else { continue };
+                if !map.contains_key(key) {
+                    let finding = report.error(MANIFEST, format!("missing '{}'", key));
+                    if let Some(example) = example(key) {
+                        finding.suggest(format!("add {}", example));
+                    }
+                }
+            }
+            let properties = schema["properties"].as_object();
+            for (key, item) in map {
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property) => check_schema(report, property, item, key),
+                    None if schema["additionalProperties"] == Value::Bool(false) => {
+                        let known = properties
+                            .into_iter()
+                            .flat_map(|p| p.keys().map(String::as_str));
+                        let suggestion = match closest(key, known) {
+                            Some(nearest) => format!("did you mean '{}'?", nearest),
+                            None => "remove it".to_string(),
+                        };
+                        report
+                            .warn(MANIFEST, format!("unknown key '{}'", key))
+                            .suggest(suggestion);
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                if schema["items"].is_object() {
+                    check_schema(
+                        report,
+                        &schema["items"],
+                        item,
+                        &format!("{}[{}]", path, index),
+                    );
+                }
+                if schema["uniqueItems"] == Value::Bool(true) && items[..index].contains(item) {
+                    report
+                        .error(MANIFEST, format!("'{}' lists {} twice", path, item))
+                        .suggest("remove the duplicate");
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// terminal_colors' palettes and the colours in each, read by indentation
+// like top_level_keys
+fn palettes(text: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut palettes: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut inside = false;
+    let mut palette_indent = None;
+    let mut current: Option<String> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if indent == 0 {
+            inside = trimmed.starts_with("terminal_colors:");
+            current = None;
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':').filter(|_| inside) else {
+            continue;
+        };
+        let key = key.trim().to_string();
+        if *palette_indent.get_or_insert(indent) == indent {
+            palettes.entry(key.clone()).or_default();
+            current = Some(key);
+        } else if let Some(palette) = current.as_ref().and_then(|p| palettes.get_mut(p)) {
+            palette.insert(key, value.trim().trim_matches('"').to_string());
+        }
+    }
+    palettes
+}
+
+struct WasmReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl WasmReader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    // Unsigned LEB128
+    fn number(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let end = self.position.checked_add(len)?;
+        let bytes = self.bytes.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let len = self.number()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn limits(&mut self) -> Option<()> {
+        let flags = self.byte()?;
+        self.number()?;
+        if flags & 1 == 1 {
+            self.number()?;
+        }
+        Some(())
+    }
+}
+
+type WasmInterface = (Vec<(String, String)>, Vec<String>);
+
+// The imports (module, name) and export names of a WebAssembly binary,
+// read from its sections without compiling it. None if it's malformed.
+fn wasm_interface(bytes: &[u8]) -> Option<WasmInterface> {
+    let mut reader = WasmReader {
+        bytes: bytes.strip_prefix(b"\0asm\x01\0\0\0")?,
+        position: 0,
+    };
+    let mut imports = Vec::new();
+    let mut exports = Vec::new();
+    while let Some(id) = reader.byte() {
+        let len = reader.number()? as usize;
+        let mut section = WasmReader {
+            bytes: reader.take(len)?,
+            position: 0,
+        };
+        match id {
+            2 => {
+                for _ in 0..section.number()? {
+                    let module = section.name()?;
+                    let name = section.name()?;
+                    match section.byte()? {
+                        0x00 => {
+                            section.number()?; // type index
+                        }
+                        0x01 => {
+                            section.byte()?; // element type
+                            section.limits()?;
+                        }
+                        0x02 => section.limits()?,
+                        0x03 => {
+                            section.take(2)?; // value type and mutability
+                        }
+                        0x04 => {
+                            section.byte()?; // tag attribute
+                            section.number()?;
+                        }
+                        _ => return None,
+                    }
+                    imports.push((module, name));
+                }
+            }
+            7 => {
+                for _ in 0..section.number()? {
+                    exports.push(section.name()?);
+                    section.byte()?;
+                    section.number()?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Some((imports, exports))
+}
+
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("yaml" | "yml")
+    )
+}
+
+pub struct Validator;
+
+impl Validator {
+    pub async fn new() -> Result<Self, WarpError> {
+        Ok(Self)
+    }
+
+    pub async fn validate_item(&self, item_path: &str) -> Result<ValidationReport, WarpError> {
+        validate_package(Path::new(item_path)).await
+    }
+}
+
+// Checks a package directory the way the marketplace will: the manifest,
+// the files it lists and what each kind of item needs. Problems are
+// collected rather than returned as errors so they can all be shown.
+pub async fn validate_package(dir: &Path) -> Result<ValidationReport, WarpError> {
+    let mut report = ValidationReport::default();
+    let text = match tokio::fs::read_to_string(dir.join(MANIFEST)).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            report.error(MANIFEST, "missing; run `warp package new` to create one");
+            return Ok(report);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let table: toml::Value = match toml::from_str(&text) {
+        Ok(table) => table,
+        Err(e) => {
+            report.error(MANIFEST, e.to_string());
+            return Ok(report);
+        }
+    };
+    let table = serde_json::to_value(&table).unwrap_or_default();
+    let version = table
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1);
+    let Some((_, schema)) = SCHEMAS
+        .iter()
+        .find(|(known, _)| u64::from(*known) == version)
+    else {
+        report
+            .error(
+                MANIFEST,
+                format!("schema_version {} isn't one this Warp understands", version),
+            )
+            .suggest(format!(
+                "update Warp, or set schema_version = {}",
+                MANIFEST_SCHEMA_VERSION
+            ));
+        return Ok(report);
+    };
+    let schema: Value = serde_json::from_str(schema).expect("bundled manifest schema is JSON");
+    let errors = report.errors();
+    check_schema(&mut report, &schema, &table, "");
+    // The manifest can't be read until its shape is right
+    if report.errors() > errors {
+        return Ok(report);
+    }
+
+    let manifest: Manifest = match toml::from_str(&text) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            report.error(MANIFEST, e.to_string());
+            return Ok(report);
+        }
+    };
+
+    if !id_pattern().is_match(&manifest.id) {
+        report.error(
+            MANIFEST,
+            format!("id '{}' should look like publisher/name", manifest.id),
+        );
+    }
+    for (field, value) in [
+        ("version", &manifest.version),
+        ("min_warp_version", &manifest.min_warp_version),
+    ] {
+        if !version_pattern().is_match(value) {
+            report
+                .error(
+                    MANIFEST,
+                    format!("{} '{}' is not a semantic version", field, value),
+                )
+                .suggest(format!("{} = \"{}\"", field, as_semver(value)));
+        }
+    }
+    if manifest.name.trim().is_empty() {
+        report.error(MANIFEST, "name is empty");
+    }
+    if manifest.description.trim().is_empty() {
+        report.error(MANIFEST, "description is empty");
+    }
+    if manifest.license.trim().is_empty() {
+        report
+            .warn(
+                MANIFEST,
+                "no license; the item will be listed as all rights reserved",
+            )
+            .suggest("license = \"MIT\", or any SPDX identifier");
+    }
+    if !dir.join("README.md").exists() {
+        report.warn("README.md", "missing; the listing page will be empty");
+    }
+    if manifest.files.is_empty() {
+        report.error(MANIFEST, "files lists nothing to bundle");
+    }
+
+    let mut total = 0;
+    let mut bundled = Vec::new();
+    for file in &manifest.files {
+        if !is_contained(file) {
+            report.error(
+                MANIFEST,
+                format!("'{}' is outside the package directory", file),
+            );
+            continue;
+        }
+        let path = dir.join(file);
+        if !path.exists() {
+            report.error(file, "listed in files but missing");
+            continue;
+        }
+        for entry in WalkDir::new(&path).into_iter().filter_map(Result::ok) {
+            if entry.file_type().is_file() {
+                total += entry.metadata().map(|m| m.len()).unwrap_or(0);
+                bundled.push(entry.into_path());
+            }
+        }
+    }
+    if total > MAX_PACKAGE_BYTES {
+        report.error(
+            MANIFEST,
+            format!(
+                "the files add up to {} bytes, over the {} byte limit",
+                total, MAX_PACKAGE_BYTES
+            ),
+        );
+    }
+
+    match manifest.kind {
+        PackageKind::Theme => check_yaml(&mut report, dir, &bundled, THEME_KEYS, true).await?,
+        PackageKind::Workflow => {
+            check_yaml(&mut report, dir, &bundled, WORKFLOW_KEYS, false).await?
+        }
+        PackageKind::Plugin => check_plugin(&mut report, dir, &manifest).await?,
+    }
+    report.manifest = Some(manifest);
+    Ok(report)
+}
+
+async fn check_yaml(
+    report: &mut ValidationReport,
+    dir: &Path,
+    bundled: &[std::path::PathBuf],
+    required: &[&str],
+    colors: bool,
+) -> Result<(), WarpError> {
+    let files: Vec<_> = bundled.iter().filter(|path| is_yaml(path)).collect();
+    if files.is_empty() {
+        report.error(MANIFEST, "files lists no .yaml file");
+    }
+    for path in files {
+        let name = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+        let text = tokio::fs::read_to_string(path).await?;
+        let keys = top_level_keys(&text);
+        for key in required.iter().filter(|key| !keys.contains(key)) {
+            report.error(&name, format!("missing '{}'", key));
+        }
+        if !colors {
+            continue;
+        }
+        let palettes = palettes(&text);
+        if keys.contains(&"terminal_colors") {
+            for palette in PALETTES {
+                let Some(colors) = palettes.get(*palette) else {
+                    report
+                        .error(
+                            &name,
+                            format!("terminal_colors has no '{}' palette", palette),
+                        )
+                        .suggest(format!(
+                            "add terminal_colors.{} with {}",
+                            palette,
+                            PALETTE_COLORS.join(", ")
+                        ));
+                    continue;
+                };
+                for color in PALETTE_COLORS.iter().filter(|c| !colors.contains_key(**c)) {
+                    let finding = report.error(
+                        &name,
+                        format!("terminal_colors.{} is missing {}", palette, color),
+                    );
+                    // The same colour from the other palette is a fair start
+                    let fallback = palettes
+                        .iter()
+                        .filter(|(other, _)| other.as_str() != *palette)
+                        .find_map(|(other, colors)| Some((other, colors.get(*color)?)));
+                    if let Some((other, value)) = fallback {
+                        finding.suggest(format!(
+                            "add {}: \"{}\" (the {} {})",
+                            color, value, other, color
+                        ));
+                    }
+                }
+            }
+        }
+        for (number, line) in text.lines().enumerate() {
+            let Some(color) = color_pattern().captures(line).map(|c| c[1].to_string()) else {
+                continue;
+            };
+            let hex = &color[1..];
+            if !matches!(hex.len(), 3 | 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                report.error(
+                    &name,
+                    format!("line {}: '{}' is not a hex colour", number + 1, color),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn check_plugin(
+    report: &mut ValidationReport,
+    dir: &Path,
+    manifest: &Manifest,
+) -> Result<(), WarpError> {
+    for permission in &manifest.permissions {
+        if !PLUGIN_PERMISSIONS.contains(&permission.as_str()) {
+            let finding = report.error(
+                MANIFEST,
+                format!(
+                    "unknown permission '{}' (one of {})",
+                    permission,
+                    PLUGIN_PERMISSIONS.join(", ")
+                ),
+            );
+            if let Some(nearest) = closest(permission, PLUGIN_PERMISSIONS.iter().copied()) {
+                finding.suggest(format!("did you mean '{}'?", nearest));
+            }
+        }
+    }
+    let Some(entry) = &manifest.entry_point else {
+        report
+            .error(MANIFEST, "plugins need an entry_point")
+            .suggest("add entry_point = \"plugin.wasm\"");
+        return Ok(());
+    };
+    if !manifest
+        .files
+        .iter()
+        .any(|file| Path::new(entry).starts_with(file))
+    {
+        report
+            .error(MANIFEST, format!("entry_point '{}' is not in files", entry))
+            .suggest(format!("add \"{}\" to files", entry));
+    }
+    let bytes = match tokio::fs::read(dir.join(entry)).await {
+        Ok(bytes) if bytes.starts_with(b"\0asm") => bytes,
+        Ok(_) => {
+            report.error(entry, "not a WebAssembly module");
+            return Ok(());
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            report.error(entry, "missing; build the plugin first");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    check_module(report, entry, &bytes, manifest);
+    Ok(())
+}
+
+// What the host will refuse at load time, and permissions that don't match
+// what the module imports
+fn check_module(report: &mut ValidationReport, entry: &str, bytes: &[u8], manifest: &Manifest) {
+    let Some((imports, exports)) = wasm_interface(bytes) else {
+        report.error(entry, "malformed WebAssembly module");
+        return;
+    };
+    for (module, name) in &imports {
+        if module == "warp" && HOST_IMPORTS.contains(&name.as_str()) {
+            continue;
+        }
+        let finding = report.error(
+            entry,
+            format!(
+                "imports {}.{}, which the host doesn't provide",
+                module, name
+            ),
+        );
+        if module.starts_with("wasi") {
+            finding.suggest("build for wasm32-unknown-unknown; plugins don't get WASI");
+        } else {
+            finding.suggest(format!(
+                "plugins can import warp.{}",
+                HOST_IMPORTS.join(", warp.")
+            ));
+        }
+    }
+    for (import, permission) in IMPORT_PERMISSIONS {
+        let imported = imports.iter().any(|(m, n)| m == "warp" && n == import);
+        let declared = manifest.permissions.iter().any(|p| p == permission);
+        if imported && !declared {
+            report
+                .error(
+                    MANIFEST,
+                    format!(
+                        "{} imports warp.{} but permissions doesn't include '{}'",
+                        entry, import, permission
+                    ),
+                )
+                .suggest(format!("add \"{}\" to permissions", permission));
+        } else if declared && !imported {
+            report
+                .warn(
+                    MANIFEST,
+                    format!(
+                        "permission '{}' is declared but {} never imports warp.{}",
+                        permission, entry, import
+                    ),
+                )
+                .suggest(format!("remove \"{}\" from permissions", permission));
+        }
+    }
+    for export in REQUIRED_EXPORTS
+        .iter()
+        .filter(|e| !exports.iter().any(|x| x == *e))
+    {
+        report.error(
+            entry,
+            format!("doesn't export '{}', which the host calls", export),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::scaffold;
+
+    fn messages(report: &ValidationReport) -> Vec<String> {
+        report
+            .findings
+            .iter()
+            .map(|f| format!("{}: {}", f.file, f.message))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_scaffolded_theme_and_workflow_are_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        for kind in [PackageKind::Theme, PackageKind::Workflow] {
+            let package = dir.path().join(format!("{:?}", kind));
+            scaffold(&package, kind, "sample").await.unwrap();
+            let report = validate_package(&package).await.unwrap();
+            assert!(report.is_ok(), "{:?}", messages(&report));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_every_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Theme, "sample")
+            .await
+            .unwrap();
+        let manifest = dir.path().join(MANIFEST);
+        let text = std::fs::read_to_string(&manifest)
+            .unwrap()
+            .replace("version = \"0.1.0\"", "version = \"1.0\"")
+            .replace("files = [", "files = [\"../secrets\", \"missing.yaml\", ");
+        std::fs::write(&manifest, text).unwrap();
+        std::fs::write(
+            dir.path().join("theme.yaml"),
+            "accent: \"#zz0000\"\nbackground: \"#000\"\n",
+        )
+        .unwrap();
+
+        let report = validate_package(dir.path()).await.unwrap();
+        let messages = messages(&report).join("\n");
+        assert!(!report.is_ok());
+        assert!(messages.contains("not a semantic version"));
+        assert!(messages.contains("outside the package directory"));
+        assert!(messages.contains("missing.yaml: listed in files but missing"));
+        assert!(messages.contains("missing 'foreground'"));
+        assert!(messages.contains("'#zz0000' is not a hex colour"));
+    }
+
+    // A module with function imports and exports; the validator only reads
+    // names, so every export can point at function 0
+    fn module(imports: &[(&str, &str)], exports: &[&str]) -> Vec<u8> {
+        let name = |s: &str| [vec![s.len() as u8], s.as_bytes().to_vec()].concat();
+        let section = |id: u8, items: Vec<Vec<u8>>| {
+            let body = [vec![items.len() as u8], items.concat()].concat();
+            [vec![id, body.len() as u8], body].concat()
+        };
+        [
+            b"\0asm\x01\0\0\0".to_vec(),
+            section(1, vec![vec![0x60, 0, 0]]),
+            section(
+                2,
+                imports
+                    .iter()
+                    .map(|(m, n)| [name(m), name(n), vec![0, 0]].concat())
+                    .collect(),
+            ),
+            section(
+                7,
+                exports
+                    .iter()
+                    .map(|e| [name(e), vec![0, 0]].concat())
+                    .collect(),
+            ),
+        ]
+        .concat()
+    }
+
+    fn suggestion(report: &ValidationReport, message: &str) -> Option<String> {
+        report
+            .findings
+            .iter()
+            .find(|f| f.message.contains(message))
+            .and_then(|f| f.suggestion.clone())
+    }
+
+    #[tokio::test]
+    async fn test_plugin_needs_a_wasm_entry_point() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Plugin, "sample")
+            .await
+            .unwrap();
+        let report = validate_package(dir.path()).await.unwrap();
+        assert!(messages(&report)
+            .iter()
+            .any(|m| m.contains("build the plugin first")));
+
+        std::fs::write(
+            dir.path().join("plugin.wasm"),
+            module(&[("warp", "log")], REQUIRED_EXPORTS),
+        )
+        .unwrap();
+        let report = validate_package(dir.path()).await.unwrap();
+        assert!(report.is_ok(), "{:?}", messages(&report));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_imports_must_match_host_and_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Plugin, "sample")
+            .await
+            .unwrap();
+        let manifest = dir.path().join(MANIFEST);
+        let text = std::fs::read_to_string(&manifest).unwrap().replace(
+            "permissions = []",
+            "permissions = [\"netwrok\", \"filesystem\"]",
+        );
+        std::fs::write(&manifest, text).unwrap();
+        std::fs::write(
+            dir.path().join("plugin.wasm"),
+            module(
+                &[("warp", "http_get"), ("wasi_snapshot_preview1", "fd_write")],
+                &["memory", "on_command"],
+            ),
+        )
+        .unwrap();
+
+        let report = validate_package(dir.path()).await.unwrap();
+        assert_eq!(
+            suggestion(&report, "unknown permission 'netwrok'").as_deref(),
+            Some("did you mean 'network'?")
+        );
+        assert!(suggestion(&report, "wasi_snapshot_preview1.fd_write")
+            .unwrap()
+            .contains("wasm32-unknown-unknown"));
+        assert_eq!(
+            suggestion(&report, "doesn't include 'network'").as_deref(),
+            Some("add \"network\" to permissions")
+        );
+        assert!(messages(&report)
+            .iter()
+            .any(|m| m.contains("'filesystem' is declared but")));
+        assert!(messages(&report)
+            .iter()
+            .any(|m| m.contains("export 'alloc'")));
+    }
+
+    #[tokio::test]
+    async fn test_manifest_schema_and_theme_palettes() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Theme, "sample")
+            .await
+            .unwrap();
+        let manifest = dir.path().join(MANIFEST);
+        let original = std::fs::read_to_string(&manifest).unwrap();
+
+        let broken = original
+            .replace("kind = \"theme\"", "kind = \"themes\"")
+            .replace("tags = []", "tags = \"dark\"\nentrypoint = \"x\"")
+            .replace("license = \"MIT\"\n", "");
+        std::fs::write(&manifest, broken).unwrap();
+        let report = validate_package(dir.path()).await.unwrap();
+        assert!(report.manifest.is_none());
+        assert_eq!(
+            suggestion(&report, "'kind' is \"themes\"").as_deref(),
+            Some("kind = \"theme\"")
+        );
+        assert_eq!(
+            suggestion(&report, "'tags' should be an array, not a string").as_deref(),
+            Some("tags = [\"dark\"]")
+        );
+        assert_eq!(
+            suggestion(&report, "unknown key 'entrypoint'").as_deref(),
+            Some("did you mean 'entry_point'?")
+        );
+        assert_eq!(
+            suggestion(&report, "missing 'license'").as_deref(),
+            Some("add license = \"MIT\"")
+        );
+
+        std::fs::write(
+            &manifest,
+            original.replace("schema_version = 1", "schema_version = 9"),
+        )
+        .unwrap();
+        let report = validate_package(dir.path()).await.unwrap();
+        assert!(messages(&report)[0].contains("schema_version 9"));
+
+        std::fs::write(&manifest, &original).unwrap();
+        let theme = dir.path().join("theme.yaml");
+        let text = std::fs::read_to_string(&theme).unwrap().replace(
+            "    cyan: \"#94e2d5\"\n    white: \"#a6adc8\"\n",
+            "    white: \"#a6adc8\"\n",
+        );
+        std::fs::write(&theme, text).unwrap();
+        let report = validate_package(dir.path()).await.unwrap();
+        assert_eq!(
+            suggestion(&report, "terminal_colors.bright is missing cyan").as_deref(),
+            Some("add cyan: \"#94e2d5\" (the normal cyan)")
+        );
+        assert_eq!(report.errors(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = validate_package(dir.path()).await.unwrap();
+        assert!(report.manifest.is_none());
+        assert_eq!(report.errors(), 1);
+    }
+}
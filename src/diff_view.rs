@@ -0,0 +1,417 @@
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthChar;
+
+use crate::error::WarpError;
+use crate::git::{self, DiffHunk};
+use crate::i18n;
+use crate::pager::parse_ansi;
+
+// SGR parameters; the pager turns them back into styles
+const RED: &str = "31";
+const GREEN: &str = "32";
+const CYAN: &str = "36";
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const PLAIN: &str = "0";
+
+// One side of a comparison as typed at the prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSource {
+    Block(usize), // @1 is the last block, @2 the one before it
+    BlockId(String),
+    File(PathBuf),
+}
+
+impl DiffSource {
+    fn parse(text: &str) -> Self {
+        match text.strip_prefix('@') {
+            Some(back) => match back.parse::<usize>() {
+                Ok(back) => DiffSource::Block(back.max(1)),
+                Err(_) => DiffSource::BlockId(back.to_string()),
+            },
+            None => DiffSource::File(PathBuf::from(text)),
+        }
+    }
+}
+
+// "a b" compares a with b; a lone operand is compared with the last block
+pub fn parse_sources(text: &str) -> Result<(DiffSource, DiffSource), WarpError> {
+    let operands: Vec<&str> = text.split_whitespace().collect();
+    match operands.as_slice() {
+        [right] => Ok((DiffSource::Block(1), DiffSource::parse(right))),
+        [left, right] => Ok((DiffSource::parse(left), DiffSource::parse(right))),
+        _ => Err(WarpError::Validation(i18n::t("diff.usage"))),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffSide {
+    pub label: String,
+    pub text: String,
+}
+
+impl DiffSide {
+    // A block's lines as the output buffer holds them: the command first,
+    // then its output with colours still in it
+    pub fn block(lines: &[String]) -> Self {
+        let (label, output) = match lines.split_first() {
+            Some((first, rest)) => (first.strip_prefix("❯ ").unwrap_or(first).to_string(), rest),
+            None => (String::new(), lines),
+        };
+        let mut text = String::new();
+        for line in output {
+            text.extend(parse_ansi(line).into_iter().map(|(run, _)| run));
+            text.push('\n');
+        }
+        Self { label, text }
+    }
+
+    // Tabs are expanded the way the pager does for blocks, so a file and
+    // the output it came from compare equal
+    pub fn file(path: &Path, text: String) -> Self {
+        Self {
+            label: path.display().to_string(),
+            text: text.replace('\t', "    "),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffMode {
+    Unified,
+    SideBySide,
+}
+
+// The pager's lines for a diff, and where each hunk starts in them
+pub struct Rendered {
+    pub lines: Vec<String>,
+    pub hunks: Vec<usize>,
+}
+
+type Segment = (String, &'static str);
+
+pub struct DiffView {
+    left: DiffSide,
+    right: DiffSide,
+    hunks: Vec<DiffHunk>,
+    mode: DiffMode,
+}
+
+impl DiffView {
+    pub fn new(left: DiffSide, right: DiffSide) -> Result<Self, WarpError> {
+        let hunks = git::diff_text(&left.text, &right.text)?;
+        Ok(Self {
+            left,
+            right,
+            hunks,
+            mode: DiffMode::Unified,
+        })
+    }
+
+    pub fn mode(&self) -> DiffMode {
+        self.mode
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            DiffMode::Unified => DiffMode::SideBySide,
+            DiffMode::SideBySide => DiffMode::Unified,
+        };
+    }
+
+    pub fn title(&self) -> String {
+        i18n::t_args(
+            "diff.title",
+            &[
+                ("left", &self.left.label),
+                ("right", &self.right.label),
+                ("hunks", &self.hunks.len().to_string()),
+            ],
+        )
+    }
+
+    // Side by side needs the width to split; unified lines scroll sideways
+    pub fn render(&self, width: usize) -> Rendered {
+        let mut lines = Vec::new();
+        let mut starts = Vec::new();
+        if self.hunks.is_empty() {
+            lines.push(i18n::t("diff.identical"));
+        }
+        for hunk in &self.hunks {
+            starts.push(lines.len());
+            lines.push(encode(&[(hunk.header.clone(), CYAN)]));
+            for row in rows(hunk) {
+                match self.mode {
+                    DiffMode::Unified => unified(&row, &mut lines),
+                    DiffMode::SideBySide => lines.push(side_by_side(&row, width)),
+                }
+            }
+        }
+        Rendered {
+            lines,
+            hunks: starts,
+        }
+    }
+}
+
+// A context line, or a removed and/or added line shown against each other
+enum Row<'a> {
+    Same(&'a str),
+    Changed(Option<&'a str>, Option<&'a str>),
+}
+
+// Runs of removed lines followed by added ones are paired up in order, so
+// an edited line can be highlighted within
+fn rows(hunk: &DiffHunk) -> Vec<Row<'_>> {
+    let mut rows = Vec::new();
+    let lines = &hunk.lines;
+    let mut i = 0;
+    while i < lines.len() {
+        match lines[i].origin {
+            ' ' => {
+                rows.push(Row::Same(&lines[i].content));
+                i += 1;
+            }
+            '-' | '+' => {
+                let removed = run(lines, i, '-');
+                let added = run(lines, i + removed.len(), '+');
+                i += removed.len() + added.len();
+                for pair in 0..removed.len().max(added.len()) {
+                    rows.push(Row::Changed(
+                        removed.get(pair).copied(),
+                        added.get(pair).copied(),
+                    ));
+                }
+            }
+            // libgit2's end-of-file newline markers
+            _ => i += 1,
+        }
+    }
+    rows
+}
+
+fn run(lines: &[git::DiffLine], from: usize, origin: char) -> Vec<&str> {
+    lines[from..]
+        .iter()
+        .take_while(|line| line.origin == origin)
+        .map(|line| line.content.as_str())
+        .collect()
+}
+
+// Byte ranges of the part of each line that differs, after the common
+// prefix and suffix
+fn changed_span(old: &str, new: &str) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+    let prefix: usize = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    (prefix..old.len() - suffix, prefix..new.len() - suffix)
+}
+
+// The line in its colour with the changed span in reverse video
+fn highlighted(
+    line: &str,
+    span: Option<std::ops::Range<usize>>,
+    color: &'static str,
+    strong: &'static str,
+) -> Vec<Segment> {
+    match span {
+        Some(span) if !span.is_empty() && span.len() < line.len() => vec![
+            (line[..span.start].to_string(), color),
+            (line[span.clone()].to_string(), strong),
+            (line[span.end..].to_string(), color),
+        ],
+        _ => vec![(line.to_string(), color)],
+    }
+}
+
+fn sides(old: Option<&str>, new: Option<&str>) -> (Option<Vec<Segment>>, Option<Vec<Segment>>) {
+    let spans = match (old, new) {
+        (Some(old), Some(new)) => {
+            let (old, new) = changed_span(old, new);
+            (Some(old), Some(new))
+        }
+        _ => (None, None),
+    };
+    (
+        old.map(|line| highlighted(line, spans.0, RED, "31;7")),
+        new.map(|line| highlighted(line, spans.1, GREEN, "32;7")),
+    )
+}
+
+fn unified(row: &Row, lines: &mut Vec<String>) {
+    match row {
+        Row::Same(line) => lines.push(encode(&[(format!(" {}", line), PLAIN)])),
+        Row::Changed(old, new) => {
+            let (old, new) = sides(*old, *new);
+            for (marker, color, segments) in [("-", RED, old), ("+", GREEN, new)] {
+                if let Some(segments) = segments {
+                    let mut line = vec![(marker.to_string(), color)];
+                    line.extend(segments);
+                    lines.push(encode(&line));
+                }
+            }
+        }
+    }
+}
+
+fn side_by_side(row: &Row, width: usize) -> String {
+    let column = width.saturating_sub(3) / 2;
+    let (left, right) = match row {
+        Row::Same(line) => (
+            Some(vec![(line.to_string(), PLAIN)]),
+            Some(vec![(line.to_string(), PLAIN)]),
+        ),
+        Row::Changed(old, new) => sides(*old, *new),
+    };
+    let separator = match row {
+        Row::Same(_) => (" │ ".to_string(), DIM),
+        Row::Changed(..) => (" ┃ ".to_string(), BOLD),
+    };
+    let mut line = fit(left.as_deref().unwrap_or_default(), column);
+    line.push(separator);
+    line.extend(fit(right.as_deref().unwrap_or_default(), column));
+    encode(&line)
+}
+
+// Cut to `width` columns and padded out to it, so the right side lines up
+fn fit(segments: &[Segment], width: usize) -> Vec<Segment> {
+    let mut used = 0;
+    let mut fitted = Vec::new();
+    'segments: for (text, style) in segments {
+        let mut kept = String::new();
+        for c in text.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width {
+                fitted.push((kept, *style));
+                break 'segments;
+            }
+            used += w;
+            kept.push(c);
+        }
+        fitted.push((kept, *style));
+    }
+    fitted.push((" ".repeat(width - used), PLAIN));
+    fitted
+}
+
+fn encode(segments: &[Segment]) -> String {
+    let mut line = String::new();
+    for (text, style) in segments.iter().filter(|(text, _)| !text.is_empty()) {
+        line.push_str(&format!("\x1b[{}m{}\x1b[0m", style, text));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffLine;
+
+    fn strip(line: &str) -> String {
+        parse_ansi(line).into_iter().map(|(text, _)| text).collect()
+    }
+
+    fn view(hunks: Vec<DiffHunk>) -> DiffView {
+        DiffView {
+            left: DiffSide::block(&["❯ env | sort".to_string(), "A=1".to_string()]),
+            right: DiffSide::file(Path::new("prod.env"), "A=2\n".to_string()),
+            hunks,
+            mode: DiffMode::Unified,
+        }
+    }
+
+    fn hunk(lines: &[(char, &str)]) -> DiffHunk {
+        DiffHunk {
+            header: "@@ -1,3 +1,3 @@".to_string(),
+            lines: lines
+                .iter()
+                .map(|(origin, content)| DiffLine {
+                    origin: *origin,
+                    content: content.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        assert_eq!(
+            parse_sources("@2 @1").unwrap(),
+            (DiffSource::Block(2), DiffSource::Block(1))
+        );
+        assert_eq!(
+            parse_sources("config/prod.yaml").unwrap(),
+            (
+                DiffSource::Block(1),
+                DiffSource::File(PathBuf::from("config/prod.yaml"))
+            )
+        );
+        assert_eq!(
+            parse_sources("@k3x9 a.yaml").unwrap().0,
+            DiffSource::BlockId("k3x9".to_string())
+        );
+        assert!(parse_sources("").is_err());
+        assert!(parse_sources("a b c").is_err());
+
+        let side = DiffSide::block(&["❯ env | sort".to_string(), "\x1b[32mA\x1b[0m=1".to_string()]);
+        assert_eq!(
+            (side.label.as_str(), side.text.as_str()),
+            ("env | sort", "A=1\n")
+        );
+    }
+
+    #[test]
+    fn test_unified_pairs_and_highlights_edits() {
+        let diff = view(vec![
+            hunk(&[
+                (' ', "HOST=db"),
+                ('-', "PORT=5432"),
+                ('+', "PORT=6432"),
+                ('+', "TLS=on"),
+            ]),
+            hunk(&[('-', "DEBUG=1")]),
+        ]);
+        let rendered = diff.render(80);
+        assert_eq!(rendered.hunks, vec![0, 5]);
+        let plain: Vec<String> = rendered.lines.iter().map(|line| strip(line)).collect();
+        assert_eq!(
+            plain[1..5],
+            [" HOST=db", "-PORT=5432", "+PORT=6432", "+TLS=on"]
+        );
+        // Only the changed digit is in reverse video
+        assert!(rendered.lines[2].contains("\x1b[31;7m5\x1b[0m"));
+        assert!(rendered.lines[3].contains("\x1b[32;7m6\x1b[0m"));
+        assert!(!rendered.lines[4].contains(";7m"));
+    }
+
+    #[test]
+    fn test_side_by_side_aligns_columns() {
+        let mut diff = view(vec![hunk(&[
+            (' ', "名前=ok"),
+            ('-', "PORT=5432"),
+            ('+', "PORT=6432"),
+            ('+', "TLS=on"),
+        ])]);
+        diff.toggle_mode();
+        assert_eq!(diff.mode(), DiffMode::SideBySide);
+        let plain: Vec<String> = diff
+            .render(23)
+            .lines
+            .iter()
+            .map(|line| strip(line))
+            .collect();
+        assert_eq!(plain[1], "名前=ok    │ 名前=ok   ");
+        assert_eq!(plain[2], "PORT=5432  ┃ PORT=6432 ");
+        assert_eq!(plain[3], "           ┃ TLS=on    ");
+    }
+}
@@ -0,0 +1,373 @@
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use ring::digest::{Context, SHA256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+
+use crate::config::DownloadConfig;
+use crate::error::WarpError;
+use crate::network;
+
+// Pause before resuming an interrupted download, doubled per attempt
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+// One file to fetch. Partial data is kept next to `dest` as `<name>.part`
+// until the transfer finishes and the checksum matches.
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    pub url: String,
+    pub dest: PathBuf,
+    pub sha256: Option<String>, // lowercase hex
+    pub bearer: Option<String>,
+}
+
+impl DownloadRequest {
+    pub fn new(url: impl Into<String>, dest: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            dest: dest.into(),
+            sha256: None,
+            bearer: None,
+        }
+    }
+
+    pub fn sha256(mut self, hex: impl Into<String>) -> Self {
+        self.sha256 = Some(hex.into().to_ascii_lowercase());
+        self
+    }
+
+    pub fn bearer(mut self, token: impl Into<String>) -> Self {
+        self.bearer = Some(token.into());
+        self
+    }
+
+    fn part_path(&self) -> PathBuf {
+        let mut name = self.dest.file_name().unwrap_or_default().to_os_string();
+        name.push(".part");
+        self.dest.with_file_name(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub downloaded: u64,
+    pub total: Option<u64>, // unknown when the server sends no length
+}
+
+impl Progress {
+    pub fn percent(&self) -> Option<u8> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.downloaded.min(total) * 100 / total) as u8)
+    }
+}
+
+// Paces reads so that all downloads together stay under the cap. Each chunk
+// books time on a shared clock and the reader sleeps until its slot ends.
+struct Throttle {
+    bytes_per_sec: u64,
+    next: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    fn new(kbps: u64) -> Self {
+        Self {
+            bytes_per_sec: kbps * 1024,
+            next: Mutex::new(None),
+        }
+    }
+
+    fn reserve(&self, bytes: usize, now: Instant) -> Option<Instant> {
+        if self.bytes_per_sec == 0 {
+            return None;
+        }
+        let mut next = self.next.lock().unwrap();
+        let start = next.map_or(now, |next| next.max(now));
+        let until = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        *next = Some(until);
+        Some(until)
+    }
+
+    async fn consume(&self, bytes: usize) {
+        if let Some(until) = self.reserve(bytes, Instant::now()) {
+            tokio::time::sleep_until(until.into()).await;
+        }
+    }
+}
+
+pub struct DownloadManager {
+    client: reqwest::Client,
+    connections: Semaphore,
+    throttle: Throttle,
+    retries: u32,
+    read_timeout: Duration,
+}
+
+impl DownloadManager {
+    pub fn new(config: &DownloadConfig) -> Result<Self, WarpError> {
+        let read_timeout = Duration::from_secs(config.timeout_secs.max(1));
+        let client = network::client_builder()
+            .connect_timeout(read_timeout)
+            .build()
+            .map_err(|e| WarpError::network("Failed to set up the download client", e))?;
+        Ok(Self {
+            client,
+            connections: Semaphore::new(config.max_connections.max(1)),
+            throttle: Throttle::new(config.bandwidth_limit_kbps),
+            retries: config.retries,
+            read_timeout,
+        })
+    }
+
+    // Fetches `request.url` into `request.dest`, resuming from an earlier
+    // `.part` file when the server supports ranges.
+    pub async fn download(
+        &self,
+        request: &DownloadRequest,
+        mut on_progress: impl FnMut(Progress) + Send,
+    ) -> Result<PathBuf, WarpError> {
+        let _permit = self
+            .connections
+            .acquire()
+            .await
+            .map_err(|_| WarpError::network_msg("Download manager is shutting down"))?;
+        if let Some(parent) = request.dest.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let part = request.part_path();
+        let mut attempt = 0;
+        loop {
+            match self.transfer(request, &part, &mut on_progress).await {
+                Ok(()) => break,
+                Err(e) if attempt < self.retries && retryable(&e) => {
+                    attempt += 1;
+                    log::warn!(
+                        "Download of {} interrupted ({}), retry {}",
+                        request.url,
+                        e,
+                        attempt
+                    );
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some(expected) = &request.sha256 {
+            let actual = sha256_file(&part).await?;
+            if &actual != expected {
+                // A corrupt partial file would only be resumed again
+                let _ = fs::remove_file(&part).await;
+                return Err(WarpError::Validation(format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    request.url, expected, actual
+                )));
+            }
+        }
+        fs::rename(&part, &request.dest).await?;
+        Ok(request.dest.clone())
+    }
+
+    // Small payloads such as a theme file, read into memory under the same
+    // connection and bandwidth limits.
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>, WarpError> {
+        let _permit = self
+            .connections
+            .acquire()
+            .await
+            .map_err(|_| WarpError::network_msg("Download manager is shutting down"))?;
+        let mut response = self.send(url, None, 0).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(WarpError::api(
+                Some(status.as_u16()),
+                format!("Download of {} failed", url),
+            ));
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = self.next_chunk(&mut response).await? {
+            self.throttle.consume(chunk.len()).await;
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    async fn transfer(
+        &self,
+        request: &DownloadRequest,
+        part: &Path,
+        on_progress: &mut (impl FnMut(Progress) + Send),
+    ) -> Result<(), WarpError> {
+        let offset = fs::metadata(part).await.map(|m| m.len()).unwrap_or(0);
+        let mut response = self
+            .send(&request.url, request.bearer.as_deref(), offset)
+            .await?;
+
+        let (mut file, mut downloaded, total) = match response.status() {
+            // The `.part` file already holds everything
+            StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => return Ok(()),
+            StatusCode::PARTIAL_CONTENT => {
+                let total = response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(range_total);
+                let file = fs::OpenOptions::new().append(true).open(part).await?;
+                (file, offset, total)
+            }
+            status if status.is_success() => {
+                // No range support: start over
+                let file = fs::File::create(part).await?;
+                (file, 0, response.content_length())
+            }
+            status => {
+                return Err(WarpError::api(
+                    Some(status.as_u16()),
+                    format!("Download of {} failed", request.url),
+                ))
+            }
+        };
+
+        on_progress(Progress { downloaded, total });
+        while let Some(chunk) = self.next_chunk(&mut response).await? {
+            self.throttle.consume(chunk.len()).await;
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            on_progress(Progress { downloaded, total });
+        }
+        file.flush().await?;
+
+        match total {
+            Some(total) if downloaded < total => Err(WarpError::network_msg(format!(
+                "Download of {} ended early at {} of {} bytes",
+                request.url, downloaded, total
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn send(
+        &self,
+        url: &str,
+        bearer: Option<&str>,
+        offset: u64,
+    ) -> Result<reqwest::Response, WarpError> {
+        let mut builder = self.client.get(network::endpoint(url));
+        if let Some(token) = bearer {
+            builder = builder.bearer_auth(token);
+        }
+        if offset > 0 {
+            builder = builder.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = tokio::time::timeout(self.read_timeout, builder.send())
+            .await
+            .map_err(|_| WarpError::network_msg(format!("Timed out connecting to {}", url)))?
+            .map_err(|e| WarpError::network(format!("Download of {} failed", url), e))?;
+        Ok(response)
+    }
+
+    async fn next_chunk(
+        &self,
+        response: &mut reqwest::Response,
+    ) -> Result<Option<Vec<u8>>, WarpError> {
+        tokio::time::timeout(self.read_timeout, response.chunk())
+            .await
+            .map_err(|_| WarpError::network_msg("Download stalled"))?
+            .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+            .map_err(|e| WarpError::network("Download interrupted", e))
+    }
+}
+
+// Dropped connections and server errors; a 404 or bad checksum won't improve
+fn retryable(e: &WarpError) -> bool {
+    match e.root() {
+        WarpError::Network { .. } => true,
+        WarpError::Api { status, .. } => status.is_some_and(|status| status >= 500),
+        _ => false,
+    }
+}
+
+// Total size from `Content-Range: bytes 100-199/200`
+fn range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+pub async fn sha256_file(path: &Path) -> Result<String, WarpError> {
+    let data = fs::read(path).await?;
+    Ok(sha256_hex(&data))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut context = Context::new(&SHA256);
+    context.update(data);
+    context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_total() {
+        assert_eq!(range_total("bytes 100-199/200"), Some(200));
+        assert_eq!(range_total("bytes 0-99/*"), None);
+        assert_eq!(range_total("garbage"), None);
+    }
+
+    #[test]
+    fn test_throttle_books_shared_time() {
+        let throttle = Throttle::new(1);
+        let now = Instant::now();
+        assert_eq!(
+            throttle.reserve(512, now),
+            Some(now + Duration::from_millis(500))
+        );
+        // A second reader queues behind the first
+        assert_eq!(
+            throttle.reserve(512, now),
+            Some(now + Duration::from_secs(1))
+        );
+        assert_eq!(Throttle::new(0).reserve(512, now), None);
+    }
+
+    #[test]
+    fn test_part_path_and_progress() {
+        let request =
+            DownloadRequest::new("https://example.com/a.zip", "/tmp/cache/a.zip").sha256("ABC");
+        assert_eq!(request.part_path(), PathBuf::from("/tmp/cache/a.zip.part"));
+        assert_eq!(request.sha256.as_deref(), Some("abc"));
+        let progress = Progress {
+            downloaded: 50,
+            total: Some(200),
+        };
+        assert_eq!(progress.percent(), Some(25));
+        assert_eq!(
+            Progress {
+                downloaded: 50,
+                total: None
+            }
+            .percent(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).await.unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
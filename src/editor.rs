@@ -0,0 +1,152 @@
+use std::path::Path;
+
+use crate::config::EditorConfig;
+
+// Editors with their own window; anything else is assumed to draw in the
+// terminal and gets it handed over while it runs
+const GUI_EDITORS: &[&str] = &["code", "code-insiders", "codium", "cursor", "subl", "zed"];
+const JETBRAINS: &[&str] = &[
+    "idea",
+    "pycharm",
+    "webstorm",
+    "goland",
+    "clion",
+    "rider",
+    "rubymine",
+    "phpstorm",
+    "rustrover",
+    "studio",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorLaunch {
+    pub program: String,
+    pub args: Vec<String>,
+    // Run in the foreground with the terminal suspended, rather than detached
+    pub foreground: bool,
+}
+
+// The command template for `file`: the most specific project override whose
+// root contains it, else the configured command, else $VISUAL/$EDITOR
+fn template_for(file: &Path, config: &EditorConfig) -> String {
+    let project = config
+        .projects
+        .iter()
+        .filter(|(root, _)| file.starts_with(root))
+        .max_by_key(|(root, _)| root.components().count())
+        .map(|(_, command)| command.clone());
+    if let Some(command) = project.or_else(|| config.command.clone()) {
+        return command;
+    }
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    default_template(&editor)
+}
+
+// How each editor family takes a position
+fn default_template(editor: &str) -> String {
+    let name = program_name(editor);
+    if ["code", "code-insiders", "codium", "cursor"].contains(&name) {
+        format!("{} --goto {{file}}:{{line}}:{{column}}", editor)
+    } else if JETBRAINS.contains(&name) {
+        format!("{} --line {{line}} --column {{column}} {{file}}", editor)
+    } else if ["subl", "zed", "hx", "helix", "micro"].contains(&name) {
+        format!("{} {{file}}:{{line}}:{{column}}", editor)
+    } else {
+        // vi, emacs, nano and most others
+        format!("{} +{{line}} {{file}}", editor)
+    }
+}
+
+fn program_name(command: &str) -> &str {
+    let program = command.split_whitespace().next().unwrap_or_default();
+    program.rsplit(['/', '\\']).next().unwrap_or(program)
+}
+
+pub fn launch_for(
+    file: &Path,
+    line: u32,
+    column: u32,
+    config: &EditorConfig,
+) -> Option<EditorLaunch> {
+    let template = template_for(file, config);
+    let file = file.to_string_lossy();
+    let mut words = template.split_whitespace().map(|word| {
+        word.replace("{file}", &file)
+            .replace("{line}", &line.to_string())
+            .replace("{column}", &column.to_string())
+    });
+    let program = words.next()?;
+    Some(EditorLaunch {
+        foreground: !is_gui(&program),
+        program,
+        args: words.collect(),
+    })
+}
+
+// Unknown editors run in the foreground: a GUI launcher just returns at
+// once, while a terminal editor started detached would fight the UI
+fn is_gui(program: &str) -> bool {
+    let name = program_name(program);
+    GUI_EDITORS.contains(&name) || JETBRAINS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn config(command: Option<&str>, projects: &[(&str, &str)]) -> EditorConfig {
+        EditorConfig {
+            command: command.map(str::to_string),
+            projects: projects
+                .iter()
+                .map(|(root, command)| (PathBuf::from(root), command.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_editor_families() {
+        let file = Path::new("/src/app.rs");
+        let launch =
+            launch_for(file, 12, 5, &config(Some(&default_template("code")), &[])).unwrap();
+        assert_eq!(launch.args, vec!["--goto", "/src/app.rs:12:5"]);
+        assert!(!launch.foreground);
+
+        let launch = launch_for(
+            file,
+            12,
+            5,
+            &config(Some(&default_template("/usr/bin/nvim")), &[]),
+        )
+        .unwrap();
+        assert_eq!(launch.program, "/usr/bin/nvim");
+        assert_eq!(launch.args, vec!["+12", "/src/app.rs"]);
+        assert!(launch.foreground);
+
+        let launch = launch_for(file, 3, 1, &config(Some(&default_template("idea")), &[])).unwrap();
+        assert_eq!(
+            launch.args,
+            vec!["--line", "3", "--column", "1", "/src/app.rs"]
+        );
+    }
+
+    #[test]
+    fn test_project_override_wins() {
+        let config = config(
+            Some("vim +{line} {file}"),
+            &[
+                ("/work", "subl {file}:{line}"),
+                ("/work/api", "idea --line {line} {file}"),
+            ],
+        );
+        let launch = launch_for(Path::new("/work/api/main.go"), 7, 1, &config).unwrap();
+        assert_eq!(launch.program, "idea");
+        let launch = launch_for(Path::new("/work/web/app.ts"), 7, 1, &config).unwrap();
+        assert_eq!(launch.program, "subl");
+        let launch = launch_for(Path::new("/home/notes.md"), 7, 1, &config).unwrap();
+        assert_eq!(launch.program, "vim");
+    }
+}
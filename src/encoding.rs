@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utf8::Utf8Decoder;
+
+// What a pane's output is decoded as. Everything but UTF-8 is for legacy
+// tools, BBS-style ANSI art and old Japanese software.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputEncoding {
+    #[default]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    #[serde(rename = "cp437", alias = "ibm437")]
+    Cp437,
+    #[serde(rename = "latin-1", alias = "latin1", alias = "iso-8859-1")]
+    Latin1,
+    #[serde(rename = "shift-jis", alias = "shift_jis", alias = "sjis")]
+    ShiftJis,
+}
+
+impl OutputEncoding {
+    pub const ALL: [OutputEncoding; 4] = [
+        OutputEncoding::Utf8,
+        OutputEncoding::Cp437,
+        OutputEncoding::Latin1,
+        OutputEncoding::ShiftJis,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputEncoding::Utf8 => "UTF-8",
+            OutputEncoding::Cp437 => "CP437",
+            OutputEncoding::Latin1 => "Latin-1",
+            OutputEncoding::ShiftJis => "Shift-JIS",
+        }
+    }
+
+    // The one after this in ALL, wrapping around
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|e| e == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn decoder(&self) -> OutputDecoder {
+        match self {
+            OutputEncoding::Utf8 => OutputDecoder::Utf8(Utf8Decoder::new()),
+            OutputEncoding::Cp437 => OutputDecoder::Cp437,
+            OutputEncoding::Latin1 => OutputDecoder::Latin1,
+            OutputEncoding::ShiftJis => {
+                OutputDecoder::ShiftJis(encoding_rs::SHIFT_JIS.new_decoder_without_bom_handling())
+            }
+        }
+    }
+}
+
+// Code page 437 from 0x80 up. Below that it is ASCII; the control bytes stay
+// controls rather than becoming the smiley and arrow glyphs, so escape
+// sequences in ANSI art still work.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+// Streaming decoder for one pane. The single-byte encodings can't split a
+// character across reads; UTF-8 and Shift-JIS hold a partial one back.
+pub enum OutputDecoder {
+    Utf8(Utf8Decoder),
+    Cp437,
+    Latin1,
+    ShiftJis(encoding_rs::Decoder),
+}
+
+impl OutputDecoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        match self {
+            OutputDecoder::Utf8(decoder) => decoder.decode(bytes),
+            OutputDecoder::Cp437 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0xff => CP437_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect(),
+            // Latin-1 is the first 256 code points
+            OutputDecoder::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            OutputDecoder::ShiftJis(decoder) => shift_jis(decoder, bytes, false),
+        }
+    }
+
+    // Whatever is still held back, for when the stream has ended
+    pub fn finish(&mut self) -> String {
+        match self {
+            OutputDecoder::Utf8(decoder) => decoder.finish(),
+            OutputDecoder::ShiftJis(decoder) => shift_jis(decoder, &[], true),
+            OutputDecoder::Cp437 | OutputDecoder::Latin1 => String::new(),
+        }
+    }
+}
+
+fn shift_jis(decoder: &mut encoding_rs::Decoder, bytes: &[u8], last: bool) -> String {
+    // Reserving the worst case means one call always decodes everything
+    let capacity = decoder
+        .max_utf8_buffer_length(bytes.len())
+        .unwrap_or(bytes.len() * 3 + 3);
+    let mut out = String::with_capacity(capacity);
+    let _ = decoder.decode_to_string(bytes, &mut out, last);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_byte_encodings() {
+        let bytes = b"\x1b[1m\xc9\xcd\xbb caf\xe9\x1b[0m";
+        assert_eq!(
+            OutputEncoding::Cp437.decoder().decode(bytes),
+            "\x1b[1m╔═╗ cafΘ\x1b[0m"
+        );
+        assert_eq!(
+            OutputEncoding::Latin1.decoder().decode(bytes),
+            "\x1b[1mÉÍ» café\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_shift_jis_split_across_reads() {
+        // 日本語 followed by half-width katakana ｱ
+        let bytes = b"\x93\xfa\x96\x7b\x8c\xea \xb1";
+        let mut decoder = OutputEncoding::ShiftJis.decoder();
+        let mut out = String::new();
+        for byte in bytes {
+            out.push_str(&decoder.decode(std::slice::from_ref(byte)));
+        }
+        out.push_str(&decoder.finish());
+        assert_eq!(out, "日本語 ｱ");
+
+        let mut decoder = OutputEncoding::ShiftJis.decoder();
+        assert_eq!(decoder.decode(b"ok\x93"), "ok");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_cycles_and_parses() {
+        assert_eq!(OutputEncoding::Utf8.next(), OutputEncoding::Cp437);
+        assert_eq!(OutputEncoding::ShiftJis.next(), OutputEncoding::Utf8);
+        let parsed: OutputEncoding = toml::Value::String("sjis".to_string()).try_into().unwrap();
+        assert_eq!(parsed, OutputEncoding::ShiftJis);
+        assert_eq!(
+            toml::Value::try_from(OutputEncoding::Latin1)
+                .unwrap()
+                .as_str(),
+            Some("latin-1")
+        );
+    }
+}
@@ -1,37 +1,127 @@
 use std::fmt;
 use thiserror::Error;
 
+use crate::i18n;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 #[derive(Error, Debug)]
 pub enum WarpError {
-    #[error("I/O error: {0}")]
+    // Transparent, so the wrapped error's text isn't repeated as its own
+    // cause when rendered
+    #[error(transparent)]
     Io(#[from] std::io::Error),
 
-    #[error("UTF-8 conversion error: {0}")]
+    #[error(transparent)]
     Utf8(#[from] std::string::FromUtf8Error),
 
-    #[error("Terminal error: {0}")]
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("{}", localized("error.config", .0))]
+    Config(String),
+
+    #[error("{}", localized("error.network", .message))]
+    Network {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{}", localized("error.auth", .0))]
+    Auth(String),
+
+    #[error("{}", localized("error.permission", .0))]
+    Permission(String),
+
+    #[error("{}", localized("error.not_found", format!("{} '{}'", .kind, .name)))]
+    NotFound { kind: String, name: String },
+
+    #[error("{}", localized("error.api", api_detail(.status, .message)))]
+    Api {
+        status: Option<u16>,
+        message: String,
+    },
+
+    #[error("{}", localized("error.plugin", format!("{}: {}", .plugin, .message)))]
+    Plugin { plugin: String, message: String },
+
+    #[error("{}", localized("error.pty", .0))]
+    Pty(String),
+
+    #[error("{}", localized("error.ai", .0))]
+    Ai(String),
+
+    #[error("{}", localized("error.validation", .0))]
+    Validation(String),
+
+    #[error("{}", localized("error.terminal", .0))]
     Terminal(String),
 
-    #[error("Command execution failed: {0}")]
+    #[error("{}", localized("error.command", .0))]
     CommandExecution(String),
 
-    #[error("PTY error: {0}")]
-    PtyError(String),
+    // Wraps another error with what we were doing when it happened; the
+    // wrapped error stays reachable through source() for the renderer
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<WarpError>,
+    },
 }
 
-impl fmt::Display for WarpError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            WarpError::Io(e) => write!(f, "IO Error: {}", e),
-            WarpError::Utf8(e) => write!(f, "UTF-8 Error: {}", e),
-            WarpError::Terminal(msg) => write!(f, "Terminal Error: {}", msg),
-            WarpError::CommandExecution(msg) => write!(f, "Command Execution Error: {}", msg),
-            WarpError::PtyError(msg) => write!(f, "PTY Error: {}", msg),
+fn localized(key: &str, detail: impl fmt::Display) -> String {
+    i18n::t_args(key, &[("detail", &detail.to_string())])
+}
+
+fn api_detail(status: &Option<u16>, message: &str) -> String {
+    match status {
+        Some(status) => format!("HTTP {}: {}", status, message),
+        None => message.to_string(),
+    }
+}
+
+impl From<toml::de::Error> for WarpError {
+    fn from(e: toml::de::Error) -> Self {
+        WarpError::Config(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for WarpError {
+    fn from(e: toml::ser::Error) -> Self {
+        WarpError::Config(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for WarpError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => WarpError::Api {
+                status: Some(status.as_u16()),
+                message: e.to_string(),
+            },
+            None => WarpError::Network {
+                message: e.to_string(),
+                source: Some(Box::new(e)),
+            },
         }
     }
 }
 
-impl std::error::Error for WarpError {}
+impl From<git2::Error> for WarpError {
+    fn from(e: git2::Error) -> Self {
+        let message = e.message().to_string();
+        match (e.code(), e.class()) {
+            (git2::ErrorCode::Auth, _) => WarpError::Auth(message),
+            (git2::ErrorCode::NotFound, _) => WarpError::not_found("git object", message),
+            (_, git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssh) => {
+                WarpError::network(message, e)
+            }
+            _ => WarpError::command_err(format!("git: {}", message)),
+        }
+    }
+}
 
 impl WarpError {
     pub fn terminal_err(msg: impl Into<String>) -> Self {
@@ -43,6 +133,162 @@ impl WarpError {
     }
 
     pub fn pty_err(msg: impl Into<String>) -> Self {
-        WarpError::PtyError(msg.into())
+        WarpError::Pty(msg.into())
+    }
+
+    pub fn config_err(msg: impl Into<String>) -> Self {
+        WarpError::Config(msg.into())
+    }
+
+    pub fn not_found(kind: impl Into<String>, name: impl Into<String>) -> Self {
+        WarpError::NotFound {
+            kind: kind.into(),
+            name: name.into(),
+        }
+    }
+
+    pub fn network(
+        msg: impl Into<String>,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        WarpError::Network {
+            message: msg.into(),
+            source: Some(Box::new(source)),
+        }
+    }
+
+    pub fn network_msg(msg: impl Into<String>) -> Self {
+        WarpError::Network {
+            message: msg.into(),
+            source: None,
+        }
+    }
+
+    pub fn api(status: Option<u16>, msg: impl Into<String>) -> Self {
+        WarpError::Api {
+            status,
+            message: msg.into(),
+        }
+    }
+
+    pub fn plugin(plugin: impl Into<String>, msg: impl Into<String>) -> Self {
+        WarpError::Plugin {
+            plugin: plugin.into(),
+            message: msg.into(),
+        }
+    }
+
+    pub fn context(self, context: impl Into<String>) -> Self {
+        WarpError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    // The innermost WarpError, skipping context wrappers
+    pub fn root(&self) -> &WarpError {
+        match self {
+            WarpError::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    pub fn hint(&self) -> Option<String> {
+        let key = match self.root() {
+            WarpError::Io(e) => match e.kind() {
+                std::io::ErrorKind::NotFound => "hint.io_not_found",
+                std::io::ErrorKind::PermissionDenied => "hint.io_permission",
+                _ => return None,
+            },
+            WarpError::Config(_) => "hint.config",
+            WarpError::Serialization(_) => "hint.config",
+            WarpError::Network { .. } => "hint.network",
+            WarpError::Auth(_) => "hint.auth",
+            WarpError::Permission(_) => "hint.permission",
+            WarpError::NotFound { .. } => "hint.not_found",
+            WarpError::Api {
+                status: Some(429), ..
+            } => "hint.rate_limited",
+            WarpError::Api {
+                status: Some(401 | 403),
+                ..
+            } => "hint.auth",
+            WarpError::Api {
+                status: Some(s), ..
+            } if *s >= 500 => "hint.server",
+            WarpError::Plugin { plugin, .. } => {
+                return Some(i18n::t_args("hint.plugin", &[("plugin", plugin)]));
+            }
+            WarpError::Pty(_) => "hint.pty",
+            WarpError::Ai(_) => "hint.ai",
+            _ => return None,
+        };
+        Some(i18n::t(key))
+    }
+
+    // User-facing rendering: the message, each cause on its own line, then an
+    // actionable hint if we have one. Never shows Debug output.
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![format!("❌ {}", self)];
+
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            lines.push(format!("   {} {}", i18n::t("error.caused_by"), cause));
+            source = cause.source();
+        }
+
+        if let Some(hint) = self.hint() {
+            lines.push(format!("💡 {}", hint));
+        }
+        lines
+    }
+}
+
+pub trait ResultExt<T> {
+    fn context(self, context: impl Into<String>) -> Result<T, WarpError>;
+    fn with_context<F: FnOnce() -> String>(self, f: F) -> Result<T, WarpError>;
+}
+
+impl<T, E: Into<WarpError>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T, WarpError> {
+        self.map_err(|e| e.into().context(context))
+    }
+
+    fn with_context<F: FnOnce() -> String>(self, f: F) -> Result<T, WarpError> {
+        self.map_err(|e| e.into().context(f()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_chain_is_rendered_with_root_hint() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "config.toml");
+        let result: Result<(), std::io::Error> = Err(io);
+        let err = result.context("Failed to load configuration").unwrap_err();
+
+        let lines = err.render();
+        assert_eq!(lines[0], "❌ Failed to load configuration");
+        assert!(lines[1].contains("config.toml"));
+        assert!(lines.last().unwrap().starts_with("💡"));
+        assert!(matches!(err.root(), WarpError::Io(_)));
+        // The io error is shown once, not again as its own cause
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn test_api_status_selects_hint() {
+        let limited = WarpError::api(Some(429), "slow down").hint().unwrap();
+        let server = WarpError::api(Some(503), "unavailable").hint().unwrap();
+        assert_ne!(limited, server);
+        assert!(WarpError::api(Some(400), "bad request").hint().is_none());
+    }
+
+    #[test]
+    fn test_plugin_hint_names_the_plugin() {
+        let hint = WarpError::plugin("git-prompt", "panicked").hint().unwrap();
+        assert!(hint.contains("git-prompt"));
     }
 }
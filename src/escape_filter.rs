@@ -0,0 +1,475 @@
+use crate::config::{EscapeAction, EscapeFilterConfig, EscapePolicy, TrustLevel};
+
+// A sequence longer than this is dropped whatever the policy says; an
+// unterminated string would otherwise hold back all later output
+const MAX_SEQUENCE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EscapeClass {
+    Style,
+    Cursor,
+    Query,
+    Title,
+    Clipboard,
+    Hyperlink,
+    OtherOsc,
+    DeviceControl,
+}
+
+impl EscapeClass {
+    pub fn i18n_key(self) -> &'static str {
+        match self {
+            EscapeClass::Style => "escapes.style",
+            EscapeClass::Cursor => "escapes.cursor",
+            EscapeClass::Query => "escapes.query",
+            EscapeClass::Title => "escapes.title",
+            EscapeClass::Clipboard => "escapes.clipboard",
+            EscapeClass::Hyperlink => "escapes.hyperlink",
+            EscapeClass::OtherOsc => "escapes.other_osc",
+            EscapeClass::DeviceControl => "escapes.device_control",
+        }
+    }
+}
+
+pub fn trust_i18n_key(trust: TrustLevel) -> &'static str {
+    match trust {
+        TrustLevel::Local => "escapes.trust_local",
+        TrustLevel::Remote => "escapes.trust_remote",
+        TrustLevel::Untrusted => "escapes.trust_untrusted",
+    }
+}
+
+// An ssh pane's output is never treated as Local; a command's trust can
+// only lower it
+pub fn pane_trust(remote_pane: bool, command: TrustLevel) -> TrustLevel {
+    match (remote_pane, command) {
+        (true, TrustLevel::Local) => TrustLevel::Remote,
+        (_, command) => command,
+    }
+}
+
+fn action(policy: &EscapePolicy, class: EscapeClass) -> EscapeAction {
+    match class {
+        EscapeClass::Style => EscapeAction::Allow,
+        EscapeClass::Cursor => policy.cursor,
+        EscapeClass::Query => policy.queries,
+        EscapeClass::Title => policy.title,
+        EscapeClass::Clipboard => policy.clipboard,
+        EscapeClass::Hyperlink => policy.hyperlinks,
+        EscapeClass::OtherOsc => policy.other_osc,
+        EscapeClass::DeviceControl => policy.device_control,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    Csi,
+    Osc,
+    // DCS, APC, PM and SOS: ended only by ST
+    Str,
+    // ESC seen inside an OSC or string; '\' makes it ST
+    StrEscape,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Filtered {
+    pub text: String,
+    // Classes that were dropped under a Report action, each once per
+    // command
+    pub reported: Vec<EscapeClass>,
+}
+
+// Streaming sanitizer for PTY output. Sequences cut by a read boundary are
+// held until they complete, so the result doesn't depend on how the output
+// was chunked.
+pub struct EscapeFilter {
+    config: EscapeFilterConfig,
+    trust: TrustLevel,
+    state: State,
+    // The sequence so far, introducer included
+    sequence: String,
+    overflowed: bool,
+    // Set once the sequence is known to be a string (OSC and friends)
+    string_class: Option<EscapeClass>,
+    reported: Vec<EscapeClass>,
+}
+
+impl EscapeFilter {
+    pub fn new(config: EscapeFilterConfig) -> Self {
+        Self {
+            config,
+            trust: TrustLevel::Local,
+            state: State::Ground,
+            sequence: String::new(),
+            overflowed: false,
+            string_class: None,
+            reported: Vec::new(),
+        }
+    }
+
+    pub fn trust(&self) -> TrustLevel {
+        self.trust
+    }
+
+    // Called as each command starts; also resets what has been reported
+    pub fn set_trust(&mut self, trust: TrustLevel) {
+        self.trust = trust;
+        self.reported.clear();
+    }
+
+    pub fn set_config(&mut self, config: EscapeFilterConfig) {
+        self.config = config;
+    }
+
+    fn policy(&self) -> &EscapePolicy {
+        match self.trust {
+            TrustLevel::Local => &self.config.local,
+            TrustLevel::Remote => &self.config.remote,
+            TrustLevel::Untrusted => &self.config.untrusted,
+        }
+    }
+
+    pub fn filter(&mut self, input: &str) -> Filtered {
+        let mut out = Filtered::default();
+        if !self.config.enabled && self.state == State::Ground {
+            out.text = input.to_string();
+            return out;
+        }
+        out.text.reserve(input.len());
+        for c in input.chars() {
+            self.step(c, &mut out);
+        }
+        out
+    }
+
+    fn start(&mut self, c: char, state: State) {
+        self.sequence.clear();
+        self.sequence.push(c);
+        self.overflowed = false;
+        self.string_class = match state {
+            State::Str => Some(EscapeClass::DeviceControl),
+            _ => None,
+        };
+        self.state = state;
+    }
+
+    fn push(&mut self, c: char) {
+        if self.sequence.len() + c.len_utf8() > MAX_SEQUENCE {
+            if self.string_class.is_none() && self.state == State::Osc {
+                self.string_class = Some(classify_osc(&self.sequence));
+            }
+            self.overflowed = true;
+        } else if !self.overflowed {
+            self.sequence.push(c);
+        }
+    }
+
+    fn step(&mut self, c: char, out: &mut Filtered) {
+        match self.state {
+            State::Ground => match c {
+                '\x1b' => self.start(c, State::Escape),
+                '\u{9b}' => self.start(c, State::Csi),
+                '\u{9d}' => self.start(c, State::Osc),
+                '\u{90}' | '\u{98}' | '\u{9e}' | '\u{9f}' => self.start(c, State::Str),
+                _ => out.text.push(c),
+            },
+            State::Escape | State::EscapeIntermediate if c < ' ' && c != '\x1b' => {
+                self.state = State::Ground;
+                self.step(c, out);
+            }
+            State::Escape => {
+                self.sequence.push(c);
+                match c {
+                    '[' => self.state = State::Csi,
+                    ']' => self.state = State::Osc,
+                    'P' | 'X' | '^' | '_' => {
+                        self.state = State::Str;
+                        self.string_class = Some(EscapeClass::DeviceControl);
+                    }
+                    ' '..='/' => self.state = State::EscapeIntermediate,
+                    // A second ESC abandons the first
+                    '\x1b' => self.start(c, State::Escape),
+                    _ => self.complete(EscapeClass::Cursor, out),
+                }
+            }
+            State::EscapeIntermediate if c == '\x1b' => self.start(c, State::Escape),
+            State::EscapeIntermediate => {
+                self.sequence.push(c);
+                if !(' '..='/').contains(&c) {
+                    self.complete(EscapeClass::Cursor, out);
+                }
+            }
+            State::Csi => match c {
+                '\x1b' => self.start(c, State::Escape),
+                // Anything that can't be a parameter cancels the CSI
+                '\0'..='\x1f' | '\u{7f}'.. => {
+                    self.state = State::Ground;
+                    self.step(c, out);
+                }
+                '@'..='~' => {
+                    self.sequence.push(c);
+                    let class = classify_csi(&self.sequence);
+                    self.complete(class, out);
+                }
+                _ => self.push(c),
+            },
+            State::Osc | State::Str => match c {
+                '\x07' if self.state == State::Osc => {
+                    self.push(c);
+                    self.complete_string(out);
+                }
+                '\u{9c}' => {
+                    self.push(c);
+                    self.complete_string(out);
+                }
+                '\x1b' => {
+                    if self.string_class.is_none() {
+                        self.string_class = Some(classify_osc(&self.sequence));
+                    }
+                    self.push(c);
+                    self.state = State::StrEscape;
+                }
+                _ => self.push(c),
+            },
+            State::StrEscape => {
+                if c == '\\' {
+                    self.push(c);
+                    self.complete_string(out);
+                } else {
+                    // ESC not followed by '\' ends the string and starts a
+                    // new sequence; the unterminated string is dropped
+                    self.start('\x1b', State::Escape);
+                    self.step(c, out);
+                }
+            }
+        }
+    }
+
+    fn complete_string(&mut self, out: &mut Filtered) {
+        let class = self
+            .string_class
+            .unwrap_or_else(|| classify_osc(&self.sequence));
+        self.complete(class, out);
+    }
+
+    fn complete(&mut self, class: EscapeClass, out: &mut Filtered) {
+        let action = if self.overflowed {
+            EscapeAction::Strip
+        } else {
+            action(self.policy(), class)
+        };
+        match action {
+            EscapeAction::Allow => out.text.push_str(&self.sequence),
+            EscapeAction::Strip => {}
+            EscapeAction::Report => {
+                if !self.reported.contains(&class) {
+                    self.reported.push(class);
+                    out.reported.push(class);
+                }
+            }
+        }
+        self.sequence.clear();
+        self.overflowed = false;
+        self.string_class = None;
+        self.state = State::Ground;
+    }
+}
+
+fn classify_csi(sequence: &str) -> EscapeClass {
+    let introducer = if sequence.starts_with('\x1b') { 2 } else { 1 };
+    let body = sequence
+        .char_indices()
+        .nth(introducer)
+        .map_or("", |(i, _)| &sequence[i..]);
+    let Some(last) = body.chars().last() else {
+        return EscapeClass::Cursor;
+    };
+    let params = &body[..body.len() - last.len_utf8()];
+    match last {
+        'm' if !params.starts_with(['>', '?']) => EscapeClass::Style,
+        // Device status, device attributes and window reports
+        'n' | 'c' | 't' => EscapeClass::Query,
+        'q' if params.starts_with('>') => EscapeClass::Query,
+        'p' if params.ends_with('$') => EscapeClass::Query,
+        _ => EscapeClass::Cursor,
+    }
+}
+
+fn classify_osc(sequence: &str) -> EscapeClass {
+    let body = sequence
+        .strip_prefix("\x1b]")
+        .or_else(|| sequence.strip_prefix('\u{9d}'))
+        .unwrap_or(sequence);
+    let number: String = body.chars().take_while(char::is_ascii_digit).collect();
+    match number.as_str() {
+        "0" | "1" | "2" => EscapeClass::Title,
+        "52" => EscapeClass::Clipboard,
+        "8" => EscapeClass::Hyperlink,
+        _ => EscapeClass::OtherOsc,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+    use proptest::prelude::*;
+
+    fn filter(trust: TrustLevel) -> EscapeFilter {
+        let mut filter = EscapeFilter::new(WarpConfig::default().security.escapes);
+        filter.set_trust(trust);
+        filter
+    }
+
+    // Every ESC or C1 introducer left in the output starts an SGR sequence
+    fn only_style_remains(text: &str) -> bool {
+        let mut rest = text;
+        while let Some(i) = rest.find([
+            '\x1b', '\u{9b}', '\u{9d}', '\u{90}', '\u{98}', '\u{9e}', '\u{9f}',
+        ]) {
+            let after = &rest[i..];
+            let params = if let Some(params) = after.strip_prefix("\x1b[") {
+                params
+            } else if let Some(params) = after.strip_prefix('\u{9b}') {
+                params
+            } else {
+                return false;
+            };
+            let Some(end) = params.find(|c: char| ('@'..='~').contains(&c)) else {
+                return false;
+            };
+            if !params[end..].starts_with('m') {
+                return false;
+            }
+            rest = &params[end + 1..];
+        }
+        true
+    }
+
+    #[test]
+    fn test_ssh_panes_are_at_least_remote() {
+        assert_eq!(pane_trust(false, TrustLevel::Local), TrustLevel::Local);
+        assert_eq!(pane_trust(true, TrustLevel::Local), TrustLevel::Remote);
+        assert_eq!(
+            pane_trust(true, TrustLevel::Untrusted),
+            TrustLevel::Untrusted
+        );
+    }
+
+    #[test]
+    fn test_untrusted_output_keeps_colours_only() {
+        let mut filter = filter(TrustLevel::Untrusted);
+        let input = "\x1b[31mred\x1b[0m\x1b]0;user@host:~$ \x07\x1b[1A\x1b[2Kgone\x1b]52;c;cm0gLXJmIH4=\x07\x1bP+q544e\x1b\\\x1b[6n";
+        let out = filter.filter(input);
+        assert_eq!(out.text, "\x1b[31mred\x1b[0mgone");
+        assert_eq!(
+            out.reported,
+            vec![
+                EscapeClass::Title,
+                EscapeClass::Clipboard,
+                EscapeClass::DeviceControl,
+                EscapeClass::Query
+            ]
+        );
+        // Reported once per command
+        assert!(filter.filter("\x1b]2;again\x07").reported.is_empty());
+        filter.set_trust(TrustLevel::Untrusted);
+        assert_eq!(
+            filter.filter("\x1b]2;again\x07").reported,
+            vec![EscapeClass::Title]
+        );
+    }
+
+    #[test]
+    fn test_local_output_passes_except_clipboard() {
+        let mut filter = filter(TrustLevel::Local);
+        let input = "\x1b]0;title\x07\x1b[?1049h\x1b]8;;https://x\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(filter.filter(input).text, input);
+        let out = filter.filter("\x1b]52;c;aGk=\x1b\\done");
+        assert_eq!(out.text, "done");
+        assert_eq!(out.reported, vec![EscapeClass::Clipboard]);
+    }
+
+    #[test]
+    fn test_c1_introducers_are_filtered() {
+        let mut filter = filter(TrustLevel::Untrusted);
+        let out = filter.filter("a\u{9d}0;spoof\u{9c}b\u{9b}31mc\u{9b}2Jd");
+        assert_eq!(out.text, "ab\u{9b}31mcd");
+    }
+
+    #[test]
+    fn test_sequence_split_across_reads() {
+        let mut filter = filter(TrustLevel::Untrusted);
+        assert_eq!(filter.filter("before\x1b]5").text, "before");
+        assert_eq!(filter.filter("2;c;aGk=\x1b").text, "");
+        let out = filter.filter("\\after");
+        assert_eq!(out.text, "after");
+        assert_eq!(out.reported, vec![EscapeClass::Clipboard]);
+    }
+
+    #[test]
+    fn test_unterminated_string_ends_at_next_escape() {
+        let mut filter = filter(TrustLevel::Local);
+        assert_eq!(
+            filter.filter("\x1b]0;never ends\x1b[1mbold").text,
+            "\x1b[1mbold"
+        );
+    }
+
+    #[test]
+    fn test_disabled_filter_passes_everything() {
+        let mut config = WarpConfig::default().security.escapes;
+        config.enabled = false;
+        let mut filter = EscapeFilter::new(config);
+        filter.set_trust(TrustLevel::Untrusted);
+        assert_eq!(
+            filter.filter("\x1b]52;c;aGk=\x07").text,
+            "\x1b]52;c;aGk=\x07"
+        );
+    }
+
+    #[test]
+    fn test_corpus_is_clean_when_untrusted() {
+        let Ok(entries) = std::fs::read_dir(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fuzz/corpus/pty_output"
+        )) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let bytes = std::fs::read(entry.path()).unwrap();
+            let text = String::from_utf8_lossy(&bytes);
+            let out = filter(TrustLevel::Untrusted).filter(&text);
+            assert!(only_style_remains(&out.text), "{:?}", entry.path());
+        }
+    }
+
+    proptest! {
+        // However the output is cut, the filtered text is the same
+        #[test]
+        fn prop_chunking_does_not_change_output(
+            text in "(\\PC|\x1b|\u{9b}|\u{9d}|\x07|\\[|\\]|;|[0-9]|P|m|\\\\){0,200}",
+            size in 1usize..16,
+        ) {
+            let whole = filter(TrustLevel::Untrusted).filter(&text).text;
+            let mut chunked = filter(TrustLevel::Untrusted);
+            let chars: Vec<char> = text.chars().collect();
+            let out: String = chars
+                .chunks(size)
+                .map(|chunk| chunked.filter(&chunk.iter().collect::<String>()).text)
+                .collect();
+            prop_assert_eq!(out, whole);
+        }
+
+        #[test]
+        fn prop_untrusted_output_has_no_control_sequences(
+            text in "(\\PC|\x1b|\u{9b}|\u{9d}|\u{90}|\x07|\\[|\\]|;|[0-9]|P|m|\\\\){0,200}",
+        ) {
+            let out = filter(TrustLevel::Untrusted).filter(&text).text;
+            prop_assert!(only_style_remains(&out), "{:?}", out);
+        }
+    }
+}
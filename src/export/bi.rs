@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+
+use super::columns::{self, Cell, Column};
+use super::{DataType, ExportFormat, ExportGenerator, ExportRequest};
+use crate::error::WarpError;
+
+// ExportRequest.metadata keys for the BI targets
+pub const GRAFANA_OUTPUT_KEY: &str = "grafana_output"; // "frames" (default) or "live"
+pub const MEASUREMENT_KEY: &str = "measurement"; // Grafana Live measurement name
+pub const ENTITY_SET_KEY: &str = "entity_set"; // OData entity set name
+
+// Grafana: a query response holding one data frame, which JSON and
+// Infinity datasources read as-is, or Influx line protocol for POSTing to
+// /api/live/push/<stream> through a Webhook destination
+#[derive(Default)]
+pub struct GrafanaGenerator;
+
+impl GrafanaGenerator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ExportGenerator for GrafanaGenerator {
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, Value>],
+    ) -> Result<Vec<u8>, WarpError> {
+        let columns = columns::schema(request, data);
+        let name = metadata_str(request, MEASUREMENT_KEY).unwrap_or_else(|| source_name(request));
+        match metadata_str(request, GRAFANA_OUTPUT_KEY).as_deref() {
+            None | Some("frames") => {
+                let frame = grafana_frame(&name, &columns, data)?;
+                let response = json!({ "results": { "A": { "status": 200, "frames": [frame] } } });
+                Ok(serde_json::to_vec_pretty(&response)?)
+            }
+            Some("live") => Ok(line_protocol(&name, &columns, data)?.into_bytes()),
+            Some(other) => Err(WarpError::Validation(format!(
+                "Unknown Grafana output '{}'",
+                other
+            ))),
+        }
+    }
+
+    fn supported_format(&self) -> ExportFormat {
+        ExportFormat::Grafana
+    }
+
+    fn max_row_limit(&self) -> Option<u64> {
+        None
+    }
+}
+
+pub fn grafana_frame(
+    name: &str,
+    columns: &[Column],
+    data: &[HashMap<String, Value>],
+) -> Result<Value, WarpError> {
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+    for column in columns {
+        let field_type = match column.data_type {
+            DataType::Date | DataType::DateTime => "time",
+            DataType::Integer | DataType::Float | DataType::Currency | DataType::Percentage => {
+                "number"
+            }
+            DataType::Boolean => "boolean",
+            DataType::String => "string",
+        };
+        fields.push(json!({ "name": column.name, "type": field_type }));
+        values.push(Value::Array(
+            cells(column, data)?
+                .into_iter()
+                .map(|cell| match cell {
+                    // Grafana times are milliseconds since the epoch
+                    Cell::Date(date) => json!(date
+                        .and_hms_opt(0, 0, 0)
+                        .map(|at| at.and_utc().timestamp_millis())),
+                    Cell::DateTime(at) => json!(at.timestamp_millis()),
+                    other => cell_json(other),
+                })
+                .collect(),
+        ));
+    }
+    Ok(json!({
+        "schema": { "name": name, "fields": fields },
+        "data": { "values": values },
+    }))
+}
+
+// String columns become tags and the rest fields; the first time column
+// is the timestamp, in nanoseconds
+pub fn line_protocol(
+    measurement: &str,
+    columns: &[Column],
+    data: &[HashMap<String, Value>],
+) -> Result<String, WarpError> {
+    let time_column = columns
+        .iter()
+        .position(|column| column.data_type == DataType::DateTime);
+    if !columns
+        .iter()
+        .enumerate()
+        .any(|(index, column)| Some(index) != time_column && column.data_type != DataType::String)
+    {
+        return Err(WarpError::Validation(
+            "Grafana Live needs at least one numeric or boolean column".to_string(),
+        ));
+    }
+    let measurement = escape_key(measurement, false);
+    let mut lines = Vec::with_capacity(data.len());
+    for (row_index, row) in data.iter().enumerate() {
+        let mut tags = String::new();
+        let mut fields = Vec::new();
+        let mut timestamp = None;
+        for (index, column) in columns.iter().enumerate() {
+            let cell = columns::cell(row.get(&column.name), &column.data_type)
+                .ok_or_else(|| invalid(row_index, column))?;
+            let key = escape_key(&column.name, true);
+            match cell {
+                Cell::Null => {}
+                Cell::DateTime(at) if Some(index) == time_column => {
+                    timestamp = at.timestamp_nanos_opt()
+                }
+                Cell::Text(text) if !text.is_empty() => {
+                    tags.push_str(&format!(",{}={}", key, escape_key(&text, true)))
+                }
+                Cell::Text(_) => {}
+                Cell::Integer(value) => fields.push(format!("{}={}i", key, value)),
+                Cell::Float(value) if value.is_finite() => {
+                    fields.push(format!("{}={}", key, value))
+                }
+                Cell::Float(_) => {}
+                Cell::Boolean(value) => fields.push(format!("{}={}", key, value)),
+                Cell::Date(date) => fields.push(format!("{}=\"{}\"", key, date.format("%Y-%m-%d"))),
+                Cell::DateTime(at) => fields.push(format!("{}=\"{}\"", key, at.to_rfc3339())),
+            }
+        }
+        // A point with no fields is invalid line protocol
+        if fields.is_empty() {
+            continue;
+        }
+        let mut line = format!("{}{} {}", measurement, tags, fields.join(","));
+        if let Some(timestamp) = timestamp {
+            line.push_str(&format!(" {}", timestamp));
+        }
+        lines.push(line);
+    }
+    Ok(lines.join("\n"))
+}
+
+// Power BI and Tableau both read OData v4 feeds, which keep column types
+// where CSV loses them. (Tableau's .hyper extracts can only be written
+// with Tableau's proprietary Hyper library.) Serve the export together
+// with odata_metadata() as $metadata, or import the JSON directly.
+pub struct ODataGenerator {
+    format: ExportFormat,
+}
+
+impl ODataGenerator {
+    pub fn new(format: ExportFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl ExportGenerator for ODataGenerator {
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, Value>],
+    ) -> Result<Vec<u8>, WarpError> {
+        let columns = columns::schema(request, data);
+        let entity_set = odata_name(
+            &metadata_str(request, ENTITY_SET_KEY).unwrap_or_else(|| source_name(request)),
+        );
+        let mut rows = Vec::with_capacity(data.len());
+        for (row_index, row) in data.iter().enumerate() {
+            let mut entity = Map::new();
+            entity.insert("_id".to_string(), json!(row_index + 1));
+            for column in &columns {
+                let cell = columns::cell(row.get(&column.name), &column.data_type)
+                    .ok_or_else(|| invalid(row_index, column))?;
+                entity.insert(odata_name(&column.name), cell_json(cell));
+            }
+            rows.push(Value::Object(entity));
+        }
+        let feed = json!({
+            "@odata.context": format!("$metadata#{}", entity_set),
+            "value": rows,
+        });
+        Ok(serde_json::to_vec_pretty(&feed)?)
+    }
+
+    fn supported_format(&self) -> ExportFormat {
+        self.format.clone()
+    }
+
+    fn max_row_limit(&self) -> Option<u64> {
+        None
+    }
+}
+
+// The CSDL $metadata document describing an OData export's entity set.
+// OData requires a key, so each row carries its position as _id.
+pub fn odata_metadata(entity_set: &str, columns: &[Column]) -> String {
+    let entity_set = odata_name(entity_set);
+    let properties: String = columns
+        .iter()
+        .map(|column| {
+            format!(
+                "        <Property Name=\"{}\" Type=\"{}\"/>\n",
+                odata_name(&column.name),
+                edm_type(&column.data_type)
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <edmx:Edmx Version=\"4.0\" xmlns:edmx=\"http://docs.oasis-open.org/odata/ns/edmx\">\n\
+         <edmx:DataServices>\n\
+         <Schema Namespace=\"Warp\" xmlns=\"http://docs.oasis-open.org/odata/ns/edm\">\n\
+         \x20   <EntityType Name=\"{set}Row\">\n\
+         \x20       <Key><PropertyRef Name=\"_id\"/></Key>\n\
+         \x20       <Property Name=\"_id\" Type=\"Edm.Int64\" Nullable=\"false\"/>\n\
+         {properties}\
+         \x20   </EntityType>\n\
+         \x20   <EntityContainer Name=\"Export\">\n\
+         \x20       <EntitySet Name=\"{set}\" EntityType=\"Warp.{set}Row\"/>\n\
+         \x20   </EntityContainer>\n\
+         </Schema>\n\
+         </edmx:DataServices>\n\
+         </edmx:Edmx>\n",
+        set = entity_set,
+        properties = properties,
+    )
+}
+
+fn edm_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::String => "Edm.String",
+        DataType::Integer => "Edm.Int64",
+        DataType::Float | DataType::Percentage => "Edm.Double",
+        DataType::Currency => "Edm.Decimal",
+        DataType::Boolean => "Edm.Boolean",
+        DataType::Date => "Edm.Date",
+        DataType::DateTime => "Edm.DateTimeOffset",
+    }
+}
+
+fn cells(column: &Column, data: &[HashMap<String, Value>]) -> Result<Vec<Cell>, WarpError> {
+    data.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            columns::cell(row.get(&column.name), &column.data_type)
+                .ok_or_else(|| invalid(index, column))
+        })
+        .collect()
+}
+
+fn cell_json(cell: Cell) -> Value {
+    match cell {
+        Cell::Null => Value::Null,
+        Cell::Text(text) => json!(text),
+        Cell::Integer(value) => json!(value),
+        Cell::Float(value) => json!(value),
+        Cell::Boolean(value) => json!(value),
+        Cell::Date(date) => json!(date.format("%Y-%m-%d").to_string()),
+        Cell::DateTime(at) => json!(at.to_rfc3339()),
+    }
+}
+
+fn invalid(row_index: usize, column: &Column) -> WarpError {
+    WarpError::Validation(format!(
+        "Row {}: '{}' is not a valid {:?}",
+        row_index + 1,
+        column.name,
+        column.data_type
+    ))
+}
+
+fn metadata_str(request: &ExportRequest, key: &str) -> Option<String> {
+    request
+        .metadata
+        .get(key)
+        .and_then(|value| value.as_str())
+        .map(str::to_string)
+}
+
+fn source_name(request: &ExportRequest) -> String {
+    format!("{:?}", request.data_source).to_lowercase()
+}
+
+// Line protocol escaping: commas and spaces always, '=' in tags and keys
+fn escape_key(text: &str, escape_equals: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            ',' | ' ' | '\\' => escaped.push('\\'),
+            '=' if escape_equals => escaped.push('\\'),
+            '\n' => {
+                escaped.push_str("\\n");
+                continue;
+            }
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// OData identifiers: letters, digits and underscores, not starting with a digit
+fn odata_name(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> (Vec<Column>, Vec<HashMap<String, Value>>) {
+        let columns = vec![
+            Column {
+                name: "at".to_string(),
+                data_type: DataType::DateTime,
+            },
+            Column {
+                name: "cwd".to_string(),
+                data_type: DataType::String,
+            },
+            Column {
+                name: "exit code".to_string(),
+                data_type: DataType::Integer,
+            },
+            Column {
+                name: "duration".to_string(),
+                data_type: DataType::Float,
+            },
+        ];
+        let data = vec![HashMap::from([
+            ("at".to_string(), json!("2024-05-01T10:00:00Z")),
+            ("cwd".to_string(), json!("/home/me/my project")),
+            ("exit code".to_string(), json!(1)),
+            ("duration".to_string(), json!(0.25)),
+        ])];
+        (columns, data)
+    }
+
+    #[test]
+    fn test_grafana_frame() {
+        let (columns, data) = rows();
+        let frame = grafana_frame("terminal", &columns, &data).unwrap();
+        assert_eq!(
+            frame["schema"]["fields"][0],
+            json!({ "name": "at", "type": "time" })
+        );
+        assert_eq!(frame["schema"]["fields"][2]["type"], "number");
+        assert_eq!(frame["data"]["values"][0], json!([1714557600000i64]));
+        assert_eq!(frame["data"]["values"][1], json!(["/home/me/my project"]));
+    }
+
+    #[test]
+    fn test_line_protocol() {
+        let (columns, data) = rows();
+        let lines = line_protocol("warp blocks", &columns, &data).unwrap();
+        assert_eq!(lines, "warp\\ blocks,cwd=/home/me/my\\ project exit\\ code=1i,duration=0.25 1714557600000000000");
+
+        let strings_only = vec![Column {
+            name: "cwd".to_string(),
+            data_type: DataType::String,
+        }];
+        assert!(line_protocol("m", &strings_only, &data).is_err());
+    }
+
+    #[test]
+    fn test_odata_feed_and_metadata() {
+        let (columns, data) = rows();
+        let mut request = columns::test_request(ExportFormat::PowerBI);
+        request.columns = Some(columns.iter().map(|column| column.name.clone()).collect());
+        let feed = ODataGenerator::new(ExportFormat::PowerBI)
+            .generate(&request, &data)
+            .unwrap();
+        let feed: Value = serde_json::from_slice(&feed).unwrap();
+        assert_eq!(feed["@odata.context"], "$metadata#terminal");
+        assert_eq!(feed["value"][0]["_id"], 1);
+        assert_eq!(feed["value"][0]["exit_code"], 1);
+
+        let metadata = odata_metadata("terminal", &columns);
+        assert!(metadata.contains("<Property Name=\"at\" Type=\"Edm.DateTimeOffset\"/>"));
+        assert!(metadata.contains("<EntitySet Name=\"terminal\" EntityType=\"Warp.terminalRow\"/>"));
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DataType, ExportRequest};
+
+// ExportRequest.metadata key holding declared columns, set from the
+// template when the request names one
+pub const SCHEMA_KEY: &str = "schema";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+// One value converted to its column's type
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Null,
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+// The columns typed formats write, in order: declared columns first, then
+// any other requested or present ones with types inferred from the data
+pub fn schema(request: &ExportRequest, data: &[HashMap<String, Value>]) -> Vec<Column> {
+    let mut columns: Vec<Column> = request
+        .metadata
+        .get(SCHEMA_KEY)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_default();
+
+    let names: Vec<String> = match &request.columns {
+        Some(names) => names.clone(),
+        None if !columns.is_empty() => Vec::new(),
+        // Rows are maps, so sort for a stable column order
+        None => data
+            .iter()
+            .flat_map(|row| row.keys().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+    };
+    if request.columns.is_some() {
+        columns.retain(|column| names.contains(&column.name));
+    }
+    for name in names {
+        if !columns.iter().any(|column| column.name == name) {
+            let data_type = infer(data.iter().filter_map(|row| row.get(&name)));
+            columns.push(Column { name, data_type });
+        }
+    }
+    columns
+}
+
+// The narrowest type every value fits: integers widen to floats, and any
+// other mix falls back to strings
+pub fn infer<'a>(values: impl Iterator<Item = &'a Value>) -> DataType {
+    let mut inferred: Option<DataType> = None;
+    for value in values {
+        let data_type = match value {
+            Value::Null => continue,
+            Value::Bool(_) => DataType::Boolean,
+            Value::Number(number) if number.is_i64() => DataType::Integer,
+            Value::Number(_) => DataType::Float,
+            Value::String(text) if DateTime::parse_from_rfc3339(text).is_ok() => DataType::DateTime,
+            Value::String(text) if NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok() => {
+                DataType::Date
+            }
+            _ => DataType::String,
+        };
+        inferred = Some(match (inferred, data_type) {
+            (None, data_type) => data_type,
+            (Some(current), data_type) if current == data_type => current,
+            (Some(DataType::Integer), DataType::Float)
+            | (Some(DataType::Float), DataType::Integer) => DataType::Float,
+            _ => return DataType::String,
+        });
+    }
+    inferred.unwrap_or(DataType::String)
+}
+
+// None when the value can't be read as the column's type
+pub fn cell(value: Option<&Value>, data_type: &DataType) -> Option<Cell> {
+    let value = match value {
+        None | Some(Value::Null) => return Some(Cell::Null),
+        Some(value) => value,
+    };
+    let text = value.as_str().map(str::trim);
+    match data_type {
+        DataType::String => Some(Cell::Text(match value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        })),
+        DataType::Integer => value
+            .as_i64()
+            .or_else(|| text.and_then(|text| text.parse().ok()))
+            .map(Cell::Integer),
+        DataType::Float | DataType::Currency | DataType::Percentage => value
+            .as_f64()
+            .or_else(|| text.and_then(|text| text.trim_end_matches('%').parse().ok()))
+            .map(Cell::Float),
+        DataType::Boolean => match value {
+            Value::Bool(flag) => Some(*flag),
+            Value::Number(number) => number.as_i64().map(|number| number != 0),
+            _ => match text?.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "1" => Some(true),
+                "false" | "no" | "0" => Some(false),
+                _ => None,
+            },
+        }
+        .map(Cell::Boolean),
+        DataType::Date => text
+            .and_then(|text| {
+                NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                    .ok()
+                    .or_else(|| datetime(value).map(|at| at.date_naive()))
+            })
+            .map(Cell::Date),
+        DataType::DateTime => datetime(value).map(Cell::DateTime),
+    }
+}
+
+// RFC 3339 strings, or whole seconds since the epoch
+fn datetime(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => DateTime::parse_from_rfc3339(text.trim())
+            .ok()
+            .map(|at| at.with_timezone(&Utc)),
+        Value::Number(number) => DateTime::from_timestamp(number.as_i64()?, 0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(super) fn test_request(format: super::ExportFormat) -> ExportRequest {
+    ExportRequest {
+        request_id: "test".to_string(),
+        format,
+        data_source: super::DataSource::Terminal,
+        filters: Vec::new(),
+        columns: None,
+        time_range: None,
+        template: None,
+        destination: super::ExportDestination::LocalFile {
+            path: "export.out".into(),
+        },
+        compression: None,
+        encryption: None,
+        metadata: HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_widens() {
+        let values = [json!(1), json!(null), json!(2.5)];
+        assert_eq!(infer(values.iter()), DataType::Float);
+        let values = [
+            json!("2024-05-01T10:00:00Z"),
+            json!("2024-05-02T10:00:00+02:00"),
+        ];
+        assert_eq!(infer(values.iter()), DataType::DateTime);
+        let values = [json!(true), json!("yes")];
+        assert_eq!(infer(values.iter()), DataType::String);
+        assert_eq!(infer([].iter()), DataType::String);
+    }
+
+    #[test]
+    fn test_cell_conversion() {
+        assert_eq!(
+            cell(Some(&json!("42")), &DataType::Integer),
+            Some(Cell::Integer(42))
+        );
+        assert_eq!(
+            cell(Some(&json!("12.5%")), &DataType::Percentage),
+            Some(Cell::Float(12.5))
+        );
+        assert_eq!(cell(Some(&json!("nope")), &DataType::Integer), None);
+        assert_eq!(cell(None, &DataType::Boolean), Some(Cell::Null));
+        assert_eq!(
+            cell(Some(&json!("2024-05-01T23:30:00-02:00")), &DataType::Date),
+            Some(Cell::Date(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()))
+        );
+        assert_eq!(
+            cell(Some(&json!({"a": 1})), &DataType::String),
+            Some(Cell::Text("{\"a\":1}".to_string()))
+        );
+    }
+}
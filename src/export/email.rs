@@ -0,0 +1,396 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ExportFormat, ExportRequest};
+use crate::config::{SmtpConfig, SmtpSecurity};
+use crate::error::WarpError;
+use crate::secrets::SecretsManager;
+
+pub const PASSWORD_SECRET: &str = "smtp.password";
+// ExportRequest.metadata key naming an HTML report template file
+pub const TEMPLATE_KEY: &str = "email_template";
+// Rows shown in the body's preview table
+const PREVIEW_ROWS: usize = 20;
+
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html><body style=\"font-family: sans-serif\">
+<h2>{{title}}</h2>
+<p>{{row_count}} rows, generated {{generated_at}}.</p>
+{{attachment_note}}
+{{table}}
+</body></html>
+";
+
+// What happened to an emailed export, kept on its ExportResult
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryStatus {
+    pub recipients: Vec<String>,
+    pub attached: bool, // false when the report was too large and saved instead
+    pub saved_to: Option<PathBuf>, // where an oversized report was left
+    pub delivered_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub server_response: Option<String>,
+    pub error: Option<String>,
+}
+
+pub struct Report<'a> {
+    pub request: &'a ExportRequest,
+    pub recipients: &'a [String],
+    pub subject: &'a str,
+    pub rows: &'a [HashMap<String, Value>],
+    pub file: &'a [u8],
+}
+
+pub struct Mailer {
+    config: SmtpConfig,
+    secrets: Arc<SecretsManager>,
+}
+
+impl Mailer {
+    pub fn new(config: SmtpConfig, secrets: Arc<SecretsManager>) -> Self {
+        Self { config, secrets }
+    }
+
+    // Delivery failures are recorded in the status rather than returned,
+    // so a scheduled export still reports what it produced
+    pub async fn deliver(&self, report: Report<'_>) -> DeliveryStatus {
+        let mut status = DeliveryStatus {
+            recipients: report.recipients.to_vec(),
+            attached: true,
+            saved_to: None,
+            delivered_at: None,
+            server_response: None,
+            error: None,
+        };
+        if let Err(e) = self.try_deliver(&report, &mut status).await {
+            log::warn!(
+                "Emailing export {} failed: {}",
+                report.request.request_id,
+                e
+            );
+            status.error = Some(e.to_string());
+        }
+        status
+    }
+
+    async fn try_deliver(
+        &self,
+        report: &Report<'_>,
+        status: &mut DeliveryStatus,
+    ) -> Result<(), WarpError> {
+        let filename = format!(
+            "{}.{}",
+            report.request.request_id,
+            extension(&report.request.format)
+        );
+        let limit = self.config.max_attachment_mb.saturating_mul(1024 * 1024);
+        if report.file.len() as u64 > limit {
+            // Too big for most mail servers; leave it on disk and say where
+            let path = std::env::temp_dir().join(&filename);
+            tokio::fs::write(&path, report.file).await?;
+            status.attached = false;
+            status.saved_to = Some(path);
+        }
+
+        let template = match report
+            .request
+            .metadata
+            .get(TEMPLATE_KEY)
+            .and_then(|value| value.as_str())
+        {
+            Some(path) => tokio::fs::read_to_string(path).await.map_err(|e| {
+                WarpError::Config(format!("Cannot read email template {}: {}", path, e))
+            })?,
+            None => DEFAULT_TEMPLATE.to_string(),
+        };
+        let html = render(&template, report, status);
+        let message = compose(&self.config.from, report, &filename, html, status.attached)?;
+
+        let response = self
+            .transport()
+            .await?
+            .send(message)
+            .await
+            .map_err(|e| WarpError::network("SMTP delivery failed", e))?;
+        status.delivered_at = Some(chrono::Utc::now());
+        status.server_response = Some(format!(
+            "{} {}",
+            response.code(),
+            response.message().collect::<Vec<_>>().join(" ")
+        ));
+        Ok(())
+    }
+
+    async fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, WarpError> {
+        let host = self.config.host.as_deref().ok_or_else(|| {
+            WarpError::Config("Email exports need smtp.host to be set".to_string())
+        })?;
+        let builder = match self.config.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+        }
+        .map_err(|e| WarpError::Config(format!("Invalid SMTP host {}: {}", host, e)))?;
+        let mut builder = builder
+            .port(self.config.port)
+            .timeout(Some(Duration::from_secs(self.config.timeout_secs)));
+        if let Some(username) = &self.config.username {
+            let password = self.secrets.get(PASSWORD_SECRET).await?.ok_or_else(|| {
+                WarpError::Config(format!(
+                    "smtp.username is set but the {} secret is missing",
+                    PASSWORD_SECRET
+                ))
+            })?;
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(builder.build())
+    }
+}
+
+pub fn compose(
+    from: &str,
+    report: &Report<'_>,
+    filename: &str,
+    html: String,
+    attach: bool,
+) -> Result<Message, WarpError> {
+    let mailbox = |address: &str| {
+        address.parse::<Mailbox>().map_err(|e| {
+            WarpError::Validation(format!("Invalid email address '{}': {}", address, e))
+        })
+    };
+    if report.recipients.is_empty() {
+        return Err(WarpError::Validation(
+            "Email export has no recipients".to_string(),
+        ));
+    }
+    let mut builder = Message::builder()
+        .from(mailbox(from)?)
+        .subject(report.subject);
+    for recipient in report.recipients {
+        builder = builder.to(mailbox(recipient)?);
+    }
+    let mut body = MultiPart::mixed().singlepart(SinglePart::html(html));
+    if attach {
+        let content_type = ContentType::parse(content_type(&report.request.format))
+            .map_err(|e| WarpError::Validation(format!("Bad attachment type: {}", e)))?;
+        body = body.singlepart(
+            Attachment::new(filename.to_string()).body(report.file.to_vec(), content_type),
+        );
+    }
+    builder
+        .multipart(body)
+        .map_err(|e| WarpError::Validation(format!("Cannot build report email: {}", e)))
+}
+
+// Fills {{title}}, {{row_count}}, {{generated_at}}, {{attachment_note}}
+// and {{table}}, a preview of the first rows
+pub fn render(template: &str, report: &Report<'_>, status: &DeliveryStatus) -> String {
+    let note = match &status.saved_to {
+        Some(path) => format!(
+            "<p>The report was too large to attach and was saved to <code>{}</code>.</p>",
+            escape(&path.display().to_string())
+        ),
+        None => String::new(),
+    };
+    template
+        .replace("{{title}}", &escape(report.subject))
+        .replace("{{row_count}}", &report.rows.len().to_string())
+        .replace(
+            "{{generated_at}}",
+            &chrono::Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        )
+        .replace("{{attachment_note}}", &note)
+        .replace("{{table}}", &preview_table(report))
+}
+
+fn preview_table(report: &Report<'_>) -> String {
+    let columns: Vec<String> = match &report.request.columns {
+        Some(columns) => columns.clone(),
+        None => report
+            .rows
+            .iter()
+            .flat_map(|row| row.keys().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+    };
+    if columns.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from(
+        "<table border=\"1\" cellpadding=\"4\" style=\"border-collapse: collapse\">\n<tr>",
+    );
+    for column in &columns {
+        html.push_str(&format!("<th>{}</th>", escape(column)));
+    }
+    html.push_str("</tr>\n");
+    for row in report.rows.iter().take(PREVIEW_ROWS) {
+        html.push_str("<tr>");
+        for column in &columns {
+            let text = match row.get(column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(text)) => text.clone(),
+                Some(other) => other.to_string(),
+            };
+            html.push_str(&format!("<td>{}</td>", escape(&text)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    if report.rows.len() > PREVIEW_ROWS {
+        html.push_str(&format!(
+            "<p>Showing {} of {} rows.</p>\n",
+            PREVIEW_ROWS,
+            report.rows.len()
+        ));
+    }
+    html
+}
+
+fn extension(format: &ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::CSV => "csv",
+        ExportFormat::JSON
+        | ExportFormat::Grafana
+        | ExportFormat::PowerBI
+        | ExportFormat::Tableau => "json",
+        ExportFormat::XML => "xml",
+        ExportFormat::Excel => "xlsx",
+        ExportFormat::PDF => "pdf",
+        ExportFormat::HTML => "html",
+        ExportFormat::Parquet => "parquet",
+        ExportFormat::SQLDump => "sql",
+    }
+}
+
+fn content_type(format: &ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::CSV => "text/csv",
+        ExportFormat::JSON
+        | ExportFormat::Grafana
+        | ExportFormat::PowerBI
+        | ExportFormat::Tableau => "application/json",
+        ExportFormat::XML => "application/xml",
+        ExportFormat::Excel => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ExportFormat::PDF => "application/pdf",
+        ExportFormat::HTML => "text/html",
+        ExportFormat::SQLDump => "application/sql",
+        ExportFormat::Parquet => "application/octet-stream",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn status() -> DeliveryStatus {
+        DeliveryStatus {
+            recipients: Vec::new(),
+            attached: true,
+            saved_to: None,
+            delivered_at: None,
+            server_response: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_render_escapes_and_previews() {
+        let request = super::super::columns::test_request(ExportFormat::CSV);
+        let rows = vec![HashMap::from([(
+            "command".to_string(),
+            json!("echo <b>&</b>"),
+        )])];
+        let recipients = vec!["ops@example.com".to_string()];
+        let report = Report {
+            request: &request,
+            recipients: &recipients,
+            subject: "Weekly <usage>",
+            rows: &rows,
+            file: b"",
+        };
+
+        let html = render(DEFAULT_TEMPLATE, &report, &status());
+        assert!(html.contains("<h2>Weekly &lt;usage&gt;</h2>"));
+        assert!(html.contains("<p>1 rows"));
+        assert!(html.contains("<td>echo &lt;b&gt;&amp;&lt;/b&gt;</td>"));
+
+        let mut saved = status();
+        saved.saved_to = Some(PathBuf::from("/tmp/report.csv"));
+        assert!(render("{{attachment_note}}", &report, &saved).contains("/tmp/report.csv"));
+    }
+
+    #[test]
+    fn test_compose_attachment() {
+        let request = super::super::columns::test_request(ExportFormat::CSV);
+        let recipients = vec![
+            "ops@example.com".to_string(),
+            "Team <team@example.com>".to_string(),
+        ];
+        let report = Report {
+            request: &request,
+            recipients: &recipients,
+            subject: "Report",
+            rows: &[],
+            file: b"a,b\n1,2\n",
+        };
+
+        let message = compose(
+            "Warp <reports@example.com>",
+            &report,
+            "test.csv",
+            "<p>hi</p>".to_string(),
+            true,
+        )
+        .unwrap();
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        assert!(raw.contains("To: ops@example.com, Team <team@example.com>"));
+        assert!(raw.contains("Content-Disposition: attachment; filename=\"test.csv\""));
+        assert!(raw.contains("Content-Type: text/csv"));
+
+        let message = compose(
+            "Warp <reports@example.com>",
+            &report,
+            "test.csv",
+            "<p>hi</p>".to_string(),
+            false,
+        )
+        .unwrap();
+        assert!(!String::from_utf8(message.formatted())
+            .unwrap()
+            .contains("attachment"));
+
+        let bad = vec!["not an address".to_string()];
+        let report = Report {
+            recipients: &bad,
+            ..report
+        };
+        assert!(compose(
+            "reports@example.com",
+            &report,
+            "test.csv",
+            String::new(),
+            true
+        )
+        .is_err());
+    }
+}
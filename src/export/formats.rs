@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::columns::{self, Cell, Column};
+use super::{ExportFormat, ExportGenerator, ExportRequest};
+use crate::error::WarpError;
+
+#[derive(Default)]
+pub struct CSVGenerator;
+
+impl CSVGenerator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ExportGenerator for CSVGenerator {
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, Value>],
+    ) -> Result<Vec<u8>, WarpError> {
+        let columns = columns::schema(request, data);
+        let header: Vec<String> = columns
+            .iter()
+            .map(|column| csv_field(&column.name))
+            .collect();
+        let mut csv = header.join(",");
+        csv.push('\n');
+        for (index, row) in data.iter().enumerate() {
+            let fields = typed_cells(&columns, row, index)?
+                .into_iter()
+                .map(|cell| csv_field(&cell_text(cell)))
+                .collect::<Vec<_>>();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        Ok(csv.into_bytes())
+    }
+
+    fn supported_format(&self) -> ExportFormat {
+        ExportFormat::CSV
+    }
+
+    fn max_row_limit(&self) -> Option<u64> {
+        None
+    }
+}
+
+// An array of objects with the schema's columns, in order
+#[derive(Default)]
+pub struct JSONGenerator;
+
+impl JSONGenerator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ExportGenerator for JSONGenerator {
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, Value>],
+    ) -> Result<Vec<u8>, WarpError> {
+        let columns = columns::schema(request, data);
+        let mut rows = Vec::with_capacity(data.len());
+        for (index, row) in data.iter().enumerate() {
+            let cells = typed_cells(&columns, row, index)?;
+            let object: Map<String, Value> = columns
+                .iter()
+                .zip(cells)
+                .map(|(column, cell)| (column.name.clone(), cell_json(cell)))
+                .collect();
+            rows.push(Value::Object(object));
+        }
+        Ok(serde_json::to_vec_pretty(&rows)?)
+    }
+
+    fn supported_format(&self) -> ExportFormat {
+        ExportFormat::JSON
+    }
+
+    fn max_row_limit(&self) -> Option<u64> {
+        None
+    }
+}
+
+fn typed_cells(
+    columns: &[Column],
+    row: &HashMap<String, Value>,
+    index: usize,
+) -> Result<Vec<Cell>, WarpError> {
+    columns
+        .iter()
+        .map(|column| {
+            columns::cell(row.get(&column.name), &column.data_type).ok_or_else(|| {
+                WarpError::Validation(format!(
+                    "Row {}: '{}' is not a valid {:?}",
+                    index + 1,
+                    column.name,
+                    column.data_type
+                ))
+            })
+        })
+        .collect()
+}
+
+fn cell_text(cell: Cell) -> String {
+    match cell {
+        Cell::Null => String::new(),
+        Cell::Text(text) => text,
+        Cell::Integer(value) => value.to_string(),
+        Cell::Float(value) => value.to_string(),
+        Cell::Boolean(value) => value.to_string(),
+        Cell::Date(date) => date.format("%Y-%m-%d").to_string(),
+        Cell::DateTime(at) => at.to_rfc3339(),
+    }
+}
+
+fn cell_json(cell: Cell) -> Value {
+    match cell {
+        Cell::Null => Value::Null,
+        Cell::Integer(value) => Value::from(value),
+        Cell::Float(value) => serde_json::Number::from_f64(value)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        Cell::Boolean(value) => Value::Bool(value),
+        other => Value::String(cell_text(other)),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> Vec<HashMap<String, Value>> {
+        vec![
+            HashMap::from([
+                ("command".to_string(), json!("echo \"a, b\"")),
+                ("exit_code".to_string(), json!(0)),
+            ]),
+            HashMap::from([
+                ("command".to_string(), json!("false")),
+                ("exit_code".to_string(), json!(null)),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test_csv_quotes_and_orders_columns() {
+        let request = columns::test_request(ExportFormat::CSV);
+        let csv =
+            String::from_utf8(CSVGenerator::new().generate(&request, &rows()).unwrap()).unwrap();
+        assert_eq!(csv, "command,exit_code\n\"echo \"\"a, b\"\"\",0\nfalse,\n");
+    }
+
+    #[test]
+    fn test_json_uses_column_types() {
+        let mut request = columns::test_request(ExportFormat::JSON);
+        request.metadata.insert(
+            columns::SCHEMA_KEY.to_string(),
+            json!([{ "name": "exit_code", "data_type": "Float" }]),
+        );
+        let output = JSONGenerator::new().generate(&request, &rows()).unwrap();
+        let parsed: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(parsed, json!([{ "exit_code": 0.0 }, { "exit_code": null }]));
+    }
+}
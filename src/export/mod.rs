@@ -1,21 +1,32 @@
+use crate::audit::{self, AuditLog, AuditQuery};
+use crate::calc;
+use crate::compliance::ComplianceLogger;
+use crate::error::WarpError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
+use std::sync::Arc;
 
+pub mod bi;
+pub mod columns;
+pub mod email;
 pub mod formats;
-pub mod generators;
-pub mod schedulers;
-pub mod templates;
+pub mod parquet_format;
+pub mod privacy;
+pub mod sql_dump;
+pub mod terminal;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportManager {
     generators: HashMap<ExportFormat, Box<dyn ExportGenerator>>,
+    compliance_logger: Option<Arc<ComplianceLogger>>,
+    terminal: Option<Arc<terminal::TerminalSource>>,
+    privacy: privacy::PrivacyPolicy,
+    mailer: Option<Arc<email::Mailer>>,
     schedulers: Vec<ExportScheduler>,
     templates: HashMap<String, ExportTemplate>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ExportFormat {
     CSV,
     JSON,
@@ -30,6 +41,19 @@ pub enum ExportFormat {
     Grafana,
 }
 
+impl ExportFormat {
+    // The file formats an export to a path can take, by its extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::CSV),
+            "json" => Some(Self::JSON),
+            "parquet" => Some(Self::Parquet),
+            "sql" => Some(Self::SQLDump),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportRequest {
     pub request_id: String,
@@ -54,6 +78,11 @@ pub enum DataSource {
     Marketplace,
     CustomMetrics,
     RawEvents,
+    ComplianceLog,
+    // Privileged actions from the local audit log (see audit.rs)
+    AuditLog,
+    // Command history and block metadata (command, cwd, duration, exit code)
+    Terminal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,14 +115,39 @@ pub struct TimeRange {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ExportDestination {
-    LocalFile { path: PathBuf },
-    S3 { bucket: String, key: String, region: String },
-    GCS { bucket: String, object: String },
-    Azure { container: String, blob: String },
-    FTP { host: String, path: String, credentials: FTPCredentials },
-    Email { recipients: Vec<String>, subject: String },
-    Webhook { url: String, headers: HashMap<String, String> },
-    Database { connection_string: String, table: String },
+    LocalFile {
+        path: PathBuf,
+    },
+    S3 {
+        bucket: String,
+        key: String,
+        region: String,
+    },
+    GCS {
+        bucket: String,
+        object: String,
+    },
+    Azure {
+        container: String,
+        blob: String,
+    },
+    FTP {
+        host: String,
+        path: String,
+        credentials: FTPCredentials,
+    },
+    Email {
+        recipients: Vec<String>,
+        subject: String,
+    },
+    Webhook {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+    Database {
+        connection_string: String,
+        table: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,6 +192,8 @@ pub struct ExportResult {
     pub error_message: Option<String>,
     pub download_url: Option<String>,
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub delivery: Option<email::DeliveryStatus>, // Email destinations only
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -184,9 +240,11 @@ pub struct ColumnDefinition {
     pub width: Option<u32>,
     pub alignment: Option<Alignment>,
     pub visible: bool,
+    #[serde(default)]
+    pub privacy: Option<privacy::PrivacyRule>, // sensitive columns are hashed when unset
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DataType {
     String,
     Integer,
@@ -268,7 +326,11 @@ pub enum AggregationType {
 }
 
 pub trait ExportGenerator: Send + Sync {
-    async fn generate(&self, request: &ExportRequest, data: &[HashMap<String, serde_json::Value>]) -> Result<Vec<u8>, WarpError>;
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, serde_json::Value>],
+    ) -> Result<Vec<u8>, WarpError>;
     fn supported_format(&self) -> ExportFormat;
     fn max_row_limit(&self) -> Option<u64>;
 }
@@ -276,23 +338,40 @@ pub trait ExportGenerator: Send + Sync {
 impl ExportManager {
     pub async fn new() -> Result<Self, WarpError> {
         let mut generators: HashMap<ExportFormat, Box<dyn ExportGenerator>> = HashMap::new();
-        
+
         // Register format generators
         generators.insert(ExportFormat::CSV, Box::new(formats::CSVGenerator::new()));
         generators.insert(ExportFormat::JSON, Box::new(formats::JSONGenerator::new()));
-        generators.insert(ExportFormat::Excel, Box::new(formats::ExcelGenerator::new()));
-        generators.insert(ExportFormat::PDF, Box::new(formats::PDFGenerator::new()));
-        generators.insert(ExportFormat::HTML, Box::new(formats::HTMLGenerator::new()));
-        generators.insert(ExportFormat::Parquet, Box::new(formats::ParquetGenerator::new()));
+        generators.insert(
+            ExportFormat::Parquet,
+            Box::new(parquet_format::ParquetGenerator::new()),
+        );
+        generators.insert(
+            ExportFormat::SQLDump,
+            Box::new(sql_dump::SQLDumpGenerator::new()),
+        );
+        generators.insert(ExportFormat::Grafana, Box::new(bi::GrafanaGenerator::new()));
+        generators.insert(
+            ExportFormat::PowerBI,
+            Box::new(bi::ODataGenerator::new(ExportFormat::PowerBI)),
+        );
+        generators.insert(
+            ExportFormat::Tableau,
+            Box::new(bi::ODataGenerator::new(ExportFormat::Tableau)),
+        );
 
         Ok(Self {
             generators,
+            compliance_logger: None,
+            terminal: None,
+            privacy: privacy::PrivacyPolicy::random(),
+            mailer: None,
             schedulers: Vec::new(),
             templates: HashMap::new(),
         })
     }
 
-    pub async fn export_data(&self, request: ExportRequest) -> Result<ExportResult, WarpError> {
+    pub async fn export_data(&self, mut request: ExportRequest) -> Result<ExportResult, WarpError> {
         let mut result = ExportResult {
             request_id: request.request_id.clone(),
             status: ExportStatus::Processing,
@@ -304,14 +383,15 @@ impl ExportManager {
             error_message: None,
             download_url: None,
             expires_at: None,
+            delivery: None,
         };
 
         // Get data from source
         let data = self.fetch_data(&request).await?;
-        
+
         // Apply filters
         let filtered_data = self.apply_filters(&data, &request.filters)?;
-        
+
         // Apply template transformations if specified
         let processed_data = if let Some(template_name) = &request.template {
             self.apply_template(&filtered_data, template_name)?
@@ -319,25 +399,95 @@ impl ExportManager {
             filtered_data
         };
 
+        // Pseudonymize per the template and destination before anything is written
+        let template = request
+            .template
+            .as_ref()
+            .and_then(|name| self.templates.get(name));
+        let declared = template
+            .map(|template| template.columns.as_slice())
+            .unwrap_or_default();
+        let processed_data = self
+            .privacy
+            .apply(&processed_data, declared, &request.destination);
+
+        // Typed formats take column types from the template instead of guessing
+        if let Some(template) = template {
+            let exposure = privacy::Exposure::of(&request.destination);
+            let schema: Vec<columns::Column> = template
+                .columns
+                .iter()
+                .filter(|column| column.visible)
+                .map(|column| {
+                    // Pseudonyms are strings whatever the column held
+                    let data_type =
+                        match self.privacy.rule_for(&column.name, Some(column), exposure) {
+                            privacy::PrivacyRule::Hash => DataType::String,
+                            _ => column.data_type.clone(),
+                        };
+                    columns::Column {
+                        name: column.name.clone(),
+                        data_type,
+                    }
+                })
+                .collect();
+            request.metadata.insert(
+                columns::SCHEMA_KEY.to_string(),
+                serde_json::to_value(schema)?,
+            );
+        }
+
         // Generate export
         if let Some(generator) = self.generators.get(&request.format) {
-            match generator.generate(&request, &processed_data).await {
-                Ok(export_data) => {
+            match (
+                generator.generate(&request, &processed_data),
+                &request.destination,
+            ) {
+                (
+                    Ok(export_data),
+                    ExportDestination::Email {
+                        recipients,
+                        subject,
+                    },
+                ) => {
+                    let report = email::Report {
+                        request: &request,
+                        recipients,
+                        subject,
+                        rows: &processed_data,
+                        file: &export_data,
+                    };
+                    let delivery = self.deliver_email(report).await;
+                    result.status = if delivery.error.is_none() {
+                        ExportStatus::Completed
+                    } else {
+                        ExportStatus::Failed
+                    };
+                    result.error_message = delivery.error.clone();
+                    result.file_path = delivery.saved_to.clone();
+                    result.file_size = Some(export_data.len() as u64);
+                    result.row_count = Some(processed_data.len() as u64);
+                    result.completed_at = Some(chrono::Utc::now());
+                    result.delivery = Some(delivery);
+                }
+                (Ok(export_data), _) => {
                     // Save to destination
-                    let file_path = self.save_to_destination(&request.destination, &export_data).await?;
-                    
+                    let file_path = self
+                        .save_to_destination(&request.destination, &export_data)
+                        .await?;
+
                     result.status = ExportStatus::Completed;
                     result.file_path = Some(file_path);
                     result.file_size = Some(export_data.len() as u64);
                     result.row_count = Some(processed_data.len() as u64);
                     result.completed_at = Some(chrono::Utc::now());
-                    
+
                     // Set expiration for temporary files
                     if matches!(request.destination, ExportDestination::LocalFile { .. }) {
                         result.expires_at = Some(chrono::Utc::now() + chrono::Duration::days(7));
                     }
                 }
-                Err(e) => {
+                (Err(e), _) => {
                     result.status = ExportStatus::Failed;
                     result.error_message = Some(e.to_string());
                 }
@@ -350,7 +500,27 @@ impl ExportManager {
         Ok(result)
     }
 
-    pub async fn schedule_export(&mut self, scheduler: ExportScheduler) -> Result<String, WarpError> {
+    pub fn set_compliance_logger(&mut self, logger: Arc<ComplianceLogger>) {
+        self.compliance_logger = Some(logger);
+    }
+
+    // A salt kept across runs makes pseudonyms comparable between exports
+    pub fn set_privacy_salt(&mut self, salt: &[u8]) {
+        self.privacy = privacy::PrivacyPolicy::new(salt);
+    }
+
+    pub fn set_mailer(&mut self, mailer: Arc<email::Mailer>) {
+        self.mailer = Some(mailer);
+    }
+
+    pub fn set_terminal_source(&mut self, source: Arc<terminal::TerminalSource>) {
+        self.terminal = Some(source);
+    }
+
+    pub async fn schedule_export(
+        &mut self,
+        scheduler: ExportScheduler,
+    ) -> Result<String, WarpError> {
         let schedule_id = scheduler.schedule_id.clone();
         self.schedulers.push(scheduler);
         Ok(schedule_id)
@@ -362,7 +532,7 @@ impl ExportManager {
         Ok(template_id)
     }
 
-    pub async fn get_export_status(&self, request_id: &str) -> Result<ExportStatus, WarpError> {
+    pub async fn get_export_status(&self, _request_id: &str) -> Result<ExportStatus, WarpError> {
         // In a real implementation, this would query the export status from storage
         Ok(ExportStatus::Completed)
     }
@@ -373,13 +543,19 @@ impl ExportManager {
         Ok(())
     }
 
-    pub async fn list_exports(&self, user_id: Option<&str>) -> Result<Vec<ExportResult>, WarpError> {
+    pub async fn list_exports(
+        &self,
+        user_id: Option<&str>,
+    ) -> Result<Vec<ExportResult>, WarpError> {
         // In a real implementation, this would query exports from storage
         let _ = user_id;
         Ok(Vec::new())
     }
 
-    async fn fetch_data(&self, request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_data(
+        &self,
+        request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         match &request.data_source {
             DataSource::Analytics => {
                 // Fetch analytics data
@@ -409,111 +585,267 @@ impl ExportManager {
                 // Fetch raw events data
                 self.fetch_raw_events_data(request).await
             }
+            DataSource::ComplianceLog => {
+                // Fetch recorded PTY I/O from the compliance logs
+                match &self.compliance_logger {
+                    Some(logger) => logger.export_rows().await,
+                    None => Err(WarpError::Config(
+                        "Compliance mode is not enabled".to_string(),
+                    )),
+                }
+            }
+            DataSource::AuditLog => {
+                // Every session's records, within the time range if one is given
+                let records = AuditLog::read(&AuditLog::default_path()?).await?;
+                let query = AuditQuery {
+                    since: request.time_range.as_ref().map(|range| range.start),
+                    until: request.time_range.as_ref().map(|range| range.end),
+                    ..AuditQuery::default()
+                };
+                Ok(audit::export_rows(&audit::search(&records, &query)))
+            }
+            DataSource::Terminal => {
+                // The user's own commands and blocks, within the time range if one is given
+                match &self.terminal {
+                    Some(source) => source.export_rows(request.time_range.as_ref()).await,
+                    None => Err(WarpError::Config(
+                        "Terminal history is not available for export".to_string(),
+                    )),
+                }
+            }
         }
     }
 
-    async fn fetch_analytics_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_analytics_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock analytics data
         let mut data = Vec::new();
         for i in 0..100 {
             let mut row = HashMap::new();
-            row.insert("date".to_string(), serde_json::Value::String(format!("2024-01-{:02}", i % 30 + 1)));
-            row.insert("users".to_string(), serde_json::Value::Number(serde_json::Number::from(1000 + i * 10)));
-            row.insert("sessions".to_string(), serde_json::Value::Number(serde_json::Number::from(1500 + i * 15)));
-            row.insert("revenue".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(5000.0 + i as f64 * 50.0).unwrap()));
+            row.insert(
+                "date".to_string(),
+                serde_json::Value::String(format!("2024-01-{:02}", i % 30 + 1)),
+            );
+            row.insert(
+                "users".to_string(),
+                serde_json::Value::Number(serde_json::Number::from(1000 + i * 10)),
+            );
+            row.insert(
+                "sessions".to_string(),
+                serde_json::Value::Number(serde_json::Number::from(1500 + i * 15)),
+            );
+            row.insert(
+                "revenue".to_string(),
+                serde_json::Value::Number(
+                    serde_json::Number::from_f64(5000.0 + i as f64 * 50.0).unwrap(),
+                ),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    async fn fetch_user_behavior_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_user_behavior_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock user behavior data
         let mut data = Vec::new();
         for i in 0..50 {
             let mut row = HashMap::new();
-            row.insert("user_id".to_string(), serde_json::Value::String(format!("user_{}", i)));
-            row.insert("action".to_string(), serde_json::Value::String("click".to_string()));
-            row.insert("timestamp".to_string(), serde_json::Value::String(chrono::Utc::now().to_rfc3339()));
-            row.insert("duration".to_string(), serde_json::Value::Number(serde_json::Number::from(i * 100)));
+            row.insert(
+                "user_id".to_string(),
+                serde_json::Value::String(format!("user_{}", i)),
+            );
+            row.insert(
+                "action".to_string(),
+                serde_json::Value::String("click".to_string()),
+            );
+            row.insert(
+                "timestamp".to_string(),
+                serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+            );
+            row.insert(
+                "duration".to_string(),
+                serde_json::Value::Number(serde_json::Number::from(i * 100)),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    async fn fetch_performance_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_performance_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock performance data
         let mut data = Vec::new();
         for i in 0..200 {
             let mut row = HashMap::new();
-            row.insert("timestamp".to_string(), serde_json::Value::String(chrono::Utc::now().to_rfc3339()));
-            row.insert("cpu_usage".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(50.0 + (i as f64 * 0.1).sin() * 20.0).unwrap()));
-            row.insert("memory_usage".to_string(), serde_json::Value::Number(serde_json::Number::from(1024 + i * 10)));
-            row.insert("response_time".to_string(), serde_json::Value::Number(serde_json::Number::from(100 + i % 50)));
+            row.insert(
+                "timestamp".to_string(),
+                serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+            );
+            row.insert(
+                "cpu_usage".to_string(),
+                serde_json::Value::Number(
+                    serde_json::Number::from_f64(50.0 + (i as f64 * 0.1).sin() * 20.0).unwrap(),
+                ),
+            );
+            row.insert(
+                "memory_usage".to_string(),
+                serde_json::Value::Number(serde_json::Number::from(1024 + i * 10)),
+            );
+            row.insert(
+                "response_time".to_string(),
+                serde_json::Value::Number(serde_json::Number::from(100 + i % 50)),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    async fn fetch_ab_test_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_ab_test_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock A/B test data
         let mut data = Vec::new();
         for i in 0..75 {
             let mut row = HashMap::new();
-            row.insert("experiment_id".to_string(), serde_json::Value::String(format!("exp_{}", i % 5)));
-            row.insert("variant_id".to_string(), serde_json::Value::String(format!("variant_{}", i % 2)));
-            row.insert("user_id".to_string(), serde_json::Value::String(format!("user_{}", i)));
-            row.insert("conversion".to_string(), serde_json::Value::Bool(i % 3 == 0));
-            row.insert("revenue".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(if i % 3 == 0 { 25.99 } else { 0.0 }).unwrap()));
+            row.insert(
+                "experiment_id".to_string(),
+                serde_json::Value::String(format!("exp_{}", i % 5)),
+            );
+            row.insert(
+                "variant_id".to_string(),
+                serde_json::Value::String(format!("variant_{}", i % 2)),
+            );
+            row.insert(
+                "user_id".to_string(),
+                serde_json::Value::String(format!("user_{}", i)),
+            );
+            row.insert(
+                "conversion".to_string(),
+                serde_json::Value::Bool(i % 3 == 0),
+            );
+            row.insert(
+                "revenue".to_string(),
+                serde_json::Value::Number(
+                    serde_json::Number::from_f64(if i % 3 == 0 { 25.99 } else { 0.0 }).unwrap(),
+                ),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    async fn fetch_marketplace_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_marketplace_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock marketplace data
         let mut data = Vec::new();
         for i in 0..30 {
             let mut row = HashMap::new();
-            row.insert("item_id".to_string(), serde_json::Value::String(format!("item_{}", i)));
-            row.insert("name".to_string(), serde_json::Value::String(format!("Item {}", i)));
-            row.insert("category".to_string(), serde_json::Value::String(["Themes", "Plugins", "AI Models"][i % 3].to_string()));
-            row.insert("downloads".to_string(), serde_json::Value::Number(serde_json::Number::from(1000 + i * 100)));
-            row.insert("rating".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(4.0 + (i as f64 % 10) / 10.0).unwrap()));
+            row.insert(
+                "item_id".to_string(),
+                serde_json::Value::String(format!("item_{}", i)),
+            );
+            row.insert(
+                "name".to_string(),
+                serde_json::Value::String(format!("Item {}", i)),
+            );
+            row.insert(
+                "category".to_string(),
+                serde_json::Value::String(["Themes", "Plugins", "AI Models"][i % 3].to_string()),
+            );
+            row.insert(
+                "downloads".to_string(),
+                serde_json::Value::Number(serde_json::Number::from(1000 + i * 100)),
+            );
+            row.insert(
+                "rating".to_string(),
+                serde_json::Value::Number(
+                    serde_json::Number::from_f64(4.0 + (i as f64 % 10.0) / 10.0).unwrap(),
+                ),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    async fn fetch_custom_metrics_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_custom_metrics_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock custom metrics data
         let mut data = Vec::new();
         for i in 0..150 {
             let mut row = HashMap::new();
-            row.insert("metric_name".to_string(), serde_json::Value::String(format!("custom_metric_{}", i % 10)));
-            row.insert("value".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(i as f64 * 1.5).unwrap()));
-            row.insert("timestamp".to_string(), serde_json::Value::String(chrono::Utc::now().to_rfc3339()));
-            row.insert("tags".to_string(), serde_json::Value::String(format!("tag1,tag2,tag{}", i % 5)));
+            row.insert(
+                "metric_name".to_string(),
+                serde_json::Value::String(format!("custom_metric_{}", i % 10)),
+            );
+            row.insert(
+                "value".to_string(),
+                serde_json::Value::Number(serde_json::Number::from_f64(i as f64 * 1.5).unwrap()),
+            );
+            row.insert(
+                "timestamp".to_string(),
+                serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+            );
+            row.insert(
+                "tags".to_string(),
+                serde_json::Value::String(format!("tag1,tag2,tag{}", i % 5)),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    async fn fetch_raw_events_data(&self, _request: &ExportRequest) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    async fn fetch_raw_events_data(
+        &self,
+        _request: &ExportRequest,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         // Mock raw events data
         let mut data = Vec::new();
         for i in 0..500 {
             let mut row = HashMap::new();
-            row.insert("event_id".to_string(), serde_json::Value::String(uuid::Uuid::new_v4().to_string()));
-            row.insert("event_type".to_string(), serde_json::Value::String(["click", "view", "download", "install"][i % 4].to_string()));
-            row.insert("user_id".to_string(), serde_json::Value::String(format!("user_{}", i % 100)));
-            row.insert("timestamp".to_string(), serde_json::Value::String(chrono::Utc::now().to_rfc3339()));
-            row.insert("properties".to_string(), serde_json::Value::String(format!("{{\"prop1\": {}}}", i)));
+            row.insert(
+                "event_id".to_string(),
+                serde_json::Value::String(uuid::Uuid::new_v4().to_string()),
+            );
+            row.insert(
+                "event_type".to_string(),
+                serde_json::Value::String(
+                    ["click", "view", "download", "install"][i % 4].to_string(),
+                ),
+            );
+            row.insert(
+                "user_id".to_string(),
+                serde_json::Value::String(format!("user_{}", i % 100)),
+            );
+            row.insert(
+                "timestamp".to_string(),
+                serde_json::Value::String(chrono::Utc::now().to_rfc3339()),
+            );
+            row.insert(
+                "properties".to_string(),
+                serde_json::Value::String(format!("{{\"prop1\": {}}}", i)),
+            );
             data.push(row);
         }
         Ok(data)
     }
 
-    fn apply_filters(&self, data: &[HashMap<String, serde_json::Value>], filters: &[ExportFilter]) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    fn apply_filters(
+        &self,
+        data: &[HashMap<String, serde_json::Value>],
+        filters: &[ExportFilter],
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         let mut filtered_data = data.to_vec();
 
         for filter in filters {
@@ -523,14 +855,18 @@ impl ExportManager {
                         FilterOperator::Equals => field_value == &filter.value,
                         FilterOperator::NotEquals => field_value != &filter.value,
                         FilterOperator::GreaterThan => {
-                            if let (Some(field_num), Some(filter_num)) = (field_value.as_f64(), filter.value.as_f64()) {
+                            if let (Some(field_num), Some(filter_num)) =
+                                (field_value.as_f64(), filter.value.as_f64())
+                            {
                                 field_num > filter_num
                             } else {
                                 false
                             }
                         }
                         FilterOperator::LessThan => {
-                            if let (Some(field_num), Some(filter_num)) = (field_value.as_f64(), filter.value.as_f64()) {
+                            if let (Some(field_num), Some(filter_num)) =
+                                (field_value.as_f64(), filter.value.as_f64())
+                            {
                                 field_num < filter_num
                             } else {
                                 false
@@ -539,11 +875,9 @@ impl ExportManager {
                         FilterOperator::Between => {
                             if let Some(range) = filter.value.as_array() {
                                 if range.len() == 2 {
-                                    if let (Some(field_num), Some(min), Some(max)) = (
-                                        field_value.as_f64(),
-                                        range[0].as_f64(),
-                                        range[1].as_f64()
-                                    ) {
+                                    if let (Some(field_num), Some(min), Some(max)) =
+                                        (field_value.as_f64(), range[0].as_f64(), range[1].as_f64())
+                                    {
                                         field_num >= min && field_num <= max
                                     } else {
                                         false
@@ -570,21 +904,27 @@ impl ExportManager {
                             }
                         }
                         FilterOperator::Contains => {
-                            if let (Some(field_str), Some(filter_str)) = (field_value.as_str(), filter.value.as_str()) {
+                            if let (Some(field_str), Some(filter_str)) =
+                                (field_value.as_str(), filter.value.as_str())
+                            {
                                 field_str.contains(filter_str)
                             } else {
                                 false
                             }
                         }
                         FilterOperator::StartsWith => {
-                            if let (Some(field_str), Some(filter_str)) = (field_value.as_str(), filter.value.as_str()) {
+                            if let (Some(field_str), Some(filter_str)) =
+                                (field_value.as_str(), filter.value.as_str())
+                            {
                                 field_str.starts_with(filter_str)
                             } else {
                                 false
                             }
                         }
                         FilterOperator::EndsWith => {
-                            if let (Some(field_str), Some(filter_str)) = (field_value.as_str(), filter.value.as_str()) {
+                            if let (Some(field_str), Some(filter_str)) =
+                                (field_value.as_str(), filter.value.as_str())
+                            {
                                 field_str.ends_with(filter_str)
                             } else {
                                 false
@@ -600,7 +940,11 @@ impl ExportManager {
         Ok(filtered_data)
     }
 
-    fn apply_template(&self, data: &[HashMap<String, serde_json::Value>], template_name: &str) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    fn apply_template(
+        &self,
+        data: &[HashMap<String, serde_json::Value>],
+        template_name: &str,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         if let Some(template) = self.templates.get(template_name) {
             let mut processed_data = Vec::new();
 
@@ -612,23 +956,29 @@ impl ExportManager {
                     match transformation.transformation_type {
                         TransformationType::Rename => {
                             if let Some(value) = row.get(&transformation.source_column) {
-                                processed_row.insert(transformation.target_column.clone(), value.clone());
+                                processed_row
+                                    .insert(transformation.target_column.clone(), value.clone());
                             }
                         }
                         TransformationType::Format => {
                             if let Some(value) = row.get(&transformation.source_column) {
-                                let formatted_value = self.format_value(value, &transformation.parameters)?;
-                                processed_row.insert(transformation.target_column.clone(), formatted_value);
+                                let formatted_value =
+                                    self.format_value(value, &transformation.parameters)?;
+                                processed_row
+                                    .insert(transformation.target_column.clone(), formatted_value);
                             }
                         }
                         TransformationType::Calculate => {
-                            let calculated_value = self.calculate_value(row, &transformation.parameters)?;
-                            processed_row.insert(transformation.target_column.clone(), calculated_value);
+                            let calculated_value =
+                                self.calculate_value(row, &transformation.parameters)?;
+                            processed_row
+                                .insert(transformation.target_column.clone(), calculated_value);
                         }
                         _ => {
                             // Handle other transformation types
                             if let Some(value) = row.get(&transformation.source_column) {
-                                processed_row.insert(transformation.target_column.clone(), value.clone());
+                                processed_row
+                                    .insert(transformation.target_column.clone(), value.clone());
                             }
                         }
                     }
@@ -644,16 +994,21 @@ impl ExportManager {
 
             // Apply aggregations if specified
             if !template.aggregations.is_empty() {
-                processed_data = self.apply_aggregations(&processed_data, &template.aggregations)?;
+                processed_data =
+                    self.apply_aggregations(&processed_data, &template.aggregations)?;
             }
 
             Ok(processed_data)
         } else {
-            Err(WarpError::ConfigError(format!("Template not found: {}", template_name)))
+            Err(WarpError::not_found("template", template_name))
         }
     }
 
-    fn format_value(&self, value: &serde_json::Value, parameters: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, WarpError> {
+    fn format_value(
+        &self,
+        value: &serde_json::Value,
+        parameters: &HashMap<String, serde_json::Value>,
+    ) -> Result<serde_json::Value, WarpError> {
         if let Some(format_str) = parameters.get("format").and_then(|v| v.as_str()) {
             match format_str {
                 "currency" => {
@@ -674,7 +1029,9 @@ impl ExportManager {
                     if let Some(date_str) = value.as_str() {
                         // Parse and reformat date
                         if let Ok(parsed_date) = chrono::DateTime::parse_from_rfc3339(date_str) {
-                            Ok(serde_json::Value::String(parsed_date.format("%Y-%m-%d").to_string()))
+                            Ok(serde_json::Value::String(
+                                parsed_date.format("%Y-%m-%d").to_string(),
+                            ))
                         } else {
                             Ok(value.clone())
                         }
@@ -689,112 +1046,172 @@ impl ExportManager {
         }
     }
 
-    fn calculate_value(&self, row: &HashMap<String, serde_json::Value>, parameters: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, WarpError> {
-        if let Some(expression) = parameters.get("expression").and_then(|v| v.as_str()) {
-            // Simple expression evaluation (in a real implementation, use a proper expression parser)
-            if expression.contains("+") {
-                let parts: Vec<&str> = expression.split('+').collect();
-                if parts.len() == 2 {
-                    let left_val = row.get(parts[0].trim()).and_then(|v| v.as_f64()).unwrap_or(0.0);
-                    let right_val = row.get(parts[1].trim()).and_then(|v| v.as_f64()).unwrap_or(0.0);
-                    return Ok(serde_json::Value::Number(serde_json::Number::from_f64(left_val + right_val).unwrap()));
-                }
-            }
-        }
-        
-        Ok(serde_json::Value::Number(serde_json::Number::from(0)))
+    // `expression` uses the prompt calculator's syntax, with the row's
+    // columns as variables; a null or non-numeric column counts as 0
+    fn calculate_value(
+        &self,
+        row: &HashMap<String, serde_json::Value>,
+        parameters: &HashMap<String, serde_json::Value>,
+    ) -> Result<serde_json::Value, WarpError> {
+        let Some(expression) = parameters.get("expression").and_then(|v| v.as_str()) else {
+            return Ok(serde_json::Value::Number(serde_json::Number::from(0)));
+        };
+        let column = |name: &str| row.get(name).map(|v| v.as_f64().unwrap_or(0.0));
+        let value = calc::evaluate_with(expression, &column)?;
+        Ok(serde_json::json!(value.number))
     }
 
-    fn apply_aggregations(&self, data: &[HashMap<String, serde_json::Value>], aggregations: &[DataAggregation]) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+    fn apply_aggregations(
+        &self,
+        data: &[HashMap<String, serde_json::Value>],
+        aggregations: &[DataAggregation],
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
         let mut result = Vec::new();
-        
+
         for aggregation in aggregations {
-            let mut groups: HashMap<String, Vec<&HashMap<String, serde_json::Value>>> = HashMap::new();
-            
+            let mut groups: HashMap<String, Vec<&HashMap<String, serde_json::Value>>> =
+                HashMap::new();
+
             // Group data
             for row in data {
                 let group_key = if aggregation.group_by.is_empty() {
                     "all".to_string()
                 } else {
-                    aggregation.group_by.iter()
+                    aggregation
+                        .group_by
+                        .iter()
                         .map(|col| row.get(col).and_then(|v| v.as_str()).unwrap_or(""))
                         .collect::<Vec<_>>()
                         .join("|")
                 };
-                
-                groups.entry(group_key).or_insert_with(Vec::new).push(row);
+
+                groups.entry(group_key).or_default().push(row);
             }
-            
+
             // Calculate aggregations
             for (group_key, group_rows) in groups {
                 let mut agg_row = HashMap::new();
-                
+
                 // Add group by columns
                 if !aggregation.group_by.is_empty() {
                     let group_values: Vec<&str> = group_key.split('|').collect();
                     for (i, col) in aggregation.group_by.iter().enumerate() {
                         if let Some(value) = group_values.get(i) {
-                            agg_row.insert(col.clone(), serde_json::Value::String(value.to_string()));
+                            agg_row
+                                .insert(col.clone(), serde_json::Value::String(value.to_string()));
                         }
                     }
                 }
-                
+
                 // Calculate aggregation value
                 let agg_value = match aggregation.aggregation_type {
                     AggregationType::Count => {
                         serde_json::Value::Number(serde_json::Number::from(group_rows.len()))
                     }
                     AggregationType::Sum => {
-                        let sum: f64 = group_rows.iter()
+                        let sum: f64 = group_rows
+                            .iter()
                             .filter_map(|row| row.get(&aggregation.column).and_then(|v| v.as_f64()))
                             .sum();
                         serde_json::Value::Number(serde_json::Number::from_f64(sum).unwrap())
                     }
                     AggregationType::Average => {
-                        let values: Vec<f64> = group_rows.iter()
+                        let values: Vec<f64> = group_rows
+                            .iter()
                             .filter_map(|row| row.get(&aggregation.column).and_then(|v| v.as_f64()))
                             .collect();
-                        let avg = if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 };
+                        let avg = if values.is_empty() {
+                            0.0
+                        } else {
+                            values.iter().sum::<f64>() / values.len() as f64
+                        };
                         serde_json::Value::Number(serde_json::Number::from_f64(avg).unwrap())
                     }
                     AggregationType::Min => {
-                        let min = group_rows.iter()
+                        let min = group_rows
+                            .iter()
                             .filter_map(|row| row.get(&aggregation.column).and_then(|v| v.as_f64()))
                             .fold(f64::INFINITY, f64::min);
-                        serde_json::Value::Number(serde_json::Number::from_f64(if min.is_infinite() { 0.0 } else { min }).unwrap())
+                        serde_json::Value::Number(
+                            serde_json::Number::from_f64(if min.is_infinite() { 0.0 } else { min })
+                                .unwrap(),
+                        )
                     }
                     AggregationType::Max => {
-                        let max = group_rows.iter()
+                        let max = group_rows
+                            .iter()
                             .filter_map(|row| row.get(&aggregation.column).and_then(|v| v.as_f64()))
                             .fold(f64::NEG_INFINITY, f64::max);
-                        serde_json::Value::Number(serde_json::Number::from_f64(if max.is_infinite() { 0.0 } else { max }).unwrap())
+                        serde_json::Value::Number(
+                            serde_json::Number::from_f64(if max.is_infinite() { 0.0 } else { max })
+                                .unwrap(),
+                        )
                     }
                     _ => serde_json::Value::Number(serde_json::Number::from(0)),
                 };
-                
+
                 agg_row.insert(aggregation.alias.clone(), agg_value);
                 result.push(agg_row);
             }
         }
-        
+
         Ok(result)
     }
 
-    async fn save_to_destination(&self, destination: &ExportDestination, data: &[u8]) -> Result<PathBuf, WarpError> {
+    async fn deliver_email(&self, report: email::Report<'_>) -> email::DeliveryStatus {
+        match &self.mailer {
+            Some(mailer) => mailer.deliver(report).await,
+            None => email::DeliveryStatus {
+                recipients: report.recipients.to_vec(),
+                attached: false,
+                saved_to: None,
+                delivered_at: None,
+                server_response: None,
+                error: Some("Email delivery is not configured; set smtp.host".to_string()),
+            },
+        }
+    }
+
+    async fn save_to_destination(
+        &self,
+        destination: &ExportDestination,
+        data: &[u8],
+    ) -> Result<PathBuf, WarpError> {
         match destination {
             ExportDestination::LocalFile { path } => {
                 tokio::fs::write(path, data).await?;
                 Ok(path.clone())
             }
-            ExportDestination::S3 { bucket, key, region: _ } => {
+            ExportDestination::S3 {
+                bucket,
+                key,
+                region: _,
+            } => {
                 // In a real implementation, upload to S3
                 let local_path = PathBuf::from(format!("/tmp/export_{}_{}", bucket, key));
                 tokio::fs::write(&local_path, data).await?;
                 Ok(local_path)
             }
-            ExportDestination::Email { recipients, subject: _ } => {
-                // In a real implementation, send email with attachment
-                let local_path = PathBuf::from(format!("/tmp/export_email_{}.dat", recipients.join("_")));
+            ExportDestination::Webhook { url, headers } => {
+                // e.g. Grafana Live's /api/live/push/<stream> with an Authorization header
+                let client = crate::network::client()?;
+                let mut request = client
+                    .post(crate::network::endpoint(url))
+                    .body(data.to_vec());
+                for (name, value) in headers {
+                    request = request.header(name.as_str(), value.as_str());
+                }
+                let response = request.send().await.map_err(|e| {
+                    WarpError::network(format!("Failed to deliver export to {}", url), e)
+                })?;
+                if !response.status().is_success() {
+                    return Err(WarpError::api(
+                        Some(response.status().as_u16()),
+                        format!("{} rejected the export", url),
+                    ));
+                }
+                let local_path = std::env::temp_dir()
+                    .join(format!("export_webhook_{}.dat", uuid::Uuid::new_v4()));
                 tokio::fs::write(&local_path, data).await?;
                 Ok(local_path)
             }
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Date32Builder, Float64Builder, Int64Builder, StringBuilder,
+    TimestampMicrosecondBuilder,
+};
+use arrow::datatypes::{DataType as ArrowType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use super::columns::{self, Cell, Column};
+use super::{CompressionType, DataType, ExportFormat, ExportGenerator, ExportRequest};
+use crate::error::WarpError;
+
+// Rows per record batch; bounds memory for large exports
+const BATCH_ROWS: usize = 8192;
+
+#[derive(Default)]
+pub struct ParquetGenerator;
+
+impl ParquetGenerator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ExportGenerator for ParquetGenerator {
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, serde_json::Value>],
+    ) -> Result<Vec<u8>, WarpError> {
+        let columns = columns::schema(request, data);
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|column| Field::new(&column.name, arrow_type(&column.data_type), true))
+                .collect::<Vec<_>>(),
+        ));
+        let properties = WriterProperties::builder()
+            .set_compression(compression(request.compression.as_ref()))
+            .build();
+        let mut writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))
+            .map_err(parquet_error)?;
+        for (index, rows) in data.chunks(BATCH_ROWS).enumerate() {
+            let arrays = columns
+                .iter()
+                .map(|column| build_array(column, rows, index * BATCH_ROWS))
+                .collect::<Result<Vec<_>, _>>()?;
+            let batch = RecordBatch::try_new(schema.clone(), arrays)
+                .map_err(|e| WarpError::Validation(format!("Cannot build Parquet batch: {}", e)))?;
+            writer.write(&batch).map_err(parquet_error)?;
+        }
+        writer.into_inner().map_err(parquet_error)
+    }
+
+    fn supported_format(&self) -> ExportFormat {
+        ExportFormat::Parquet
+    }
+
+    fn max_row_limit(&self) -> Option<u64> {
+        None
+    }
+}
+
+fn arrow_type(data_type: &DataType) -> ArrowType {
+    match data_type {
+        DataType::String => ArrowType::Utf8,
+        DataType::Integer => ArrowType::Int64,
+        DataType::Float | DataType::Currency | DataType::Percentage => ArrowType::Float64,
+        DataType::Boolean => ArrowType::Boolean,
+        DataType::Date => ArrowType::Date32,
+        DataType::DateTime => ArrowType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+    }
+}
+
+// Zip and Bzip2 have no Parquet codec, so those get the default
+fn compression(requested: Option<&CompressionType>) -> Compression {
+    match requested {
+        Some(CompressionType::Gzip) => Compression::GZIP(GzipLevel::default()),
+        Some(CompressionType::Lz4) => Compression::LZ4_RAW,
+        Some(CompressionType::Zstd) => Compression::ZSTD(ZstdLevel::default()),
+        _ => Compression::SNAPPY,
+    }
+}
+
+fn build_array(
+    column: &Column,
+    rows: &[HashMap<String, serde_json::Value>],
+    offset: usize,
+) -> Result<ArrayRef, WarpError> {
+    let cells = rows.iter().enumerate().map(|(index, row)| {
+        columns::cell(row.get(&column.name), &column.data_type).ok_or_else(|| {
+            WarpError::Validation(format!(
+                "Row {}: '{}' is not a valid {:?}",
+                offset + index + 1,
+                column.name,
+                column.data_type
+            ))
+        })
+    });
+
+    // cell() only produces the variant matching the column type, or Null
+    macro_rules! collect {
+        ($builder:expr, $variant:ident, $convert:expr) => {{
+            let mut builder = $builder;
+            for cell in cells {
+                match cell? {
+                    Cell::$variant(value) => builder.append_value($convert(value)),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish()) as ArrayRef
+        }};
+    }
+
+    Ok(match column.data_type {
+        DataType::String => collect!(StringBuilder::new(), Text, |value: String| value),
+        DataType::Integer => collect!(Int64Builder::new(), Integer, |value| value),
+        DataType::Float | DataType::Currency | DataType::Percentage => {
+            collect!(Float64Builder::new(), Float, |value| value)
+        }
+        DataType::Boolean => collect!(BooleanBuilder::new(), Boolean, |value| value),
+        DataType::Date => collect!(Date32Builder::new(), Date, days_since_epoch),
+        DataType::DateTime => collect!(
+            TimestampMicrosecondBuilder::new().with_timezone("UTC"),
+            DateTime,
+            |value: chrono::DateTime<chrono::Utc>| value.timestamp_micros()
+        ),
+    })
+}
+
+fn days_since_epoch(date: NaiveDate) -> i32 {
+    (date - NaiveDate::default()).num_days() as i32
+}
+
+fn parquet_error(e: parquet::errors::ParquetError) -> WarpError {
+    WarpError::Validation(format!("Cannot write Parquet: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float64Array, Int64Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_typed_columns_round_trip() {
+        let mut request = columns::test_request(ExportFormat::Parquet);
+        request.metadata.insert(
+            columns::SCHEMA_KEY.to_string(),
+            json!([{ "name": "price", "data_type": "Currency" }]),
+        );
+        request.columns = Some(vec![
+            "price".to_string(),
+            "count".to_string(),
+            "name".to_string(),
+        ]);
+        let rows: Vec<HashMap<String, serde_json::Value>> = vec![
+            [
+                ("price", json!("9.99")),
+                ("count", json!(3)),
+                ("name", json!("a")),
+            ],
+            [
+                ("price", json!(null)),
+                ("count", json!(4)),
+                ("name", json!("b")),
+            ],
+        ]
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect()
+        })
+        .collect();
+
+        let bytes = ParquetGenerator::new().generate(&request, &rows).unwrap();
+        let file = tempfile::tempfile().unwrap();
+        std::io::Write::write_all(&mut &file, &bytes).unwrap();
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.next().unwrap().unwrap();
+        let schema = batch.schema();
+        let names: Vec<&str> = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+        assert_eq!(names, ["price", "count", "name"]);
+
+        let price = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(price.value(0), 9.99);
+        assert!(price.is_null(1));
+        let count = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(count.value(1), 4);
+        let name = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(name.value(0), "a");
+    }
+
+    #[test]
+    fn test_rejects_mistyped_values() {
+        let mut request = columns::test_request(ExportFormat::Parquet);
+        request.metadata.insert(
+            columns::SCHEMA_KEY.to_string(),
+            json!([{ "name": "n", "data_type": "Integer" }]),
+        );
+        let rows = vec![HashMap::from([("n".to_string(), json!("many"))])];
+        let error = ParquetGenerator::new()
+            .generate(&request, &rows)
+            .unwrap_err();
+        assert!(error.to_string().contains("Row 1"));
+    }
+}
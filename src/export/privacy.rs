@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ColumnDefinition, ExportDestination};
+
+// Columns pseudonymized when a template says nothing about them
+const SENSITIVE_COLUMNS: &[&str] = &[
+    "user_id",
+    "username",
+    "email",
+    "ip_address",
+    "user_agent",
+    "hostname",
+    "session_id",
+    "device_id",
+    "api_key",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivacyRule {
+    Keep,
+    Redact,                // the column is dropped from every row
+    Hash,                  // a stable pseudonym, so rows still join
+    Bucket { width: f64 }, // numbers floored to a multiple; times to `width` seconds
+}
+
+// How far an export travels. Anything leaving the machine is pseudonymized;
+// anything sent to a third party's inbox or endpoint is stricter still.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Exposure {
+    Local,
+    Storage,
+    External,
+}
+
+impl Exposure {
+    pub fn of(destination: &ExportDestination) -> Self {
+        match destination {
+            ExportDestination::LocalFile { .. } => Exposure::Local,
+            ExportDestination::S3 { .. }
+            | ExportDestination::GCS { .. }
+            | ExportDestination::Azure { .. }
+            | ExportDestination::FTP { .. }
+            | ExportDestination::Database { .. } => Exposure::Storage,
+            ExportDestination::Email { .. } | ExportDestination::Webhook { .. } => {
+                Exposure::External
+            }
+        }
+    }
+}
+
+pub struct PrivacyPolicy {
+    key: hmac::Key,
+}
+
+impl PrivacyPolicy {
+    // Pseudonyms only match across exports made with the same salt; keep
+    // it in the secrets store to make them stable between runs
+    pub fn new(salt: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, salt),
+        }
+    }
+
+    pub fn random() -> Self {
+        let mut salt = [0u8; 32];
+        // SystemRandom only fails if the OS has no entropy source at all
+        let _ = SystemRandom::new().fill(&mut salt);
+        Self::new(&salt)
+    }
+
+    // The template's annotation wins, then the sensitive-name default, and
+    // the destination can only tighten the result: hashes sent to external
+    // recipients become redactions, and sensitive columns are never kept
+    pub fn rule_for(
+        &self,
+        name: &str,
+        declared: Option<&ColumnDefinition>,
+        exposure: Exposure,
+    ) -> PrivacyRule {
+        let sensitive = is_sensitive(name);
+        let rule = match declared.and_then(|column| column.privacy.clone()) {
+            Some(rule) => rule,
+            None if sensitive => PrivacyRule::Hash,
+            None => PrivacyRule::Keep,
+        };
+        match (rule, exposure) {
+            (PrivacyRule::Hash, Exposure::External) => PrivacyRule::Redact,
+            (PrivacyRule::Keep, Exposure::External) if sensitive => PrivacyRule::Redact,
+            (PrivacyRule::Keep, Exposure::Storage) if sensitive => PrivacyRule::Hash,
+            (rule, _) => rule,
+        }
+    }
+
+    pub fn apply(
+        &self,
+        data: &[HashMap<String, Value>],
+        declared: &[ColumnDefinition],
+        destination: &ExportDestination,
+    ) -> Vec<HashMap<String, Value>> {
+        let exposure = Exposure::of(destination);
+        let mut rules: HashMap<&str, PrivacyRule> = HashMap::new();
+        data.iter()
+            .map(|row| {
+                row.iter()
+                    .filter_map(|(name, value)| {
+                        let rule = rules.entry(name.as_str()).or_insert_with(|| {
+                            let column = declared.iter().find(|column| &column.name == name);
+                            self.rule_for(name, column, exposure)
+                        });
+                        let value = match rule {
+                            PrivacyRule::Keep => value.clone(),
+                            PrivacyRule::Redact => return None,
+                            PrivacyRule::Hash => self.pseudonym(value),
+                            PrivacyRule::Bucket { width } => bucket(value, *width),
+                        };
+                        Some((name.clone(), value))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn pseudonym(&self, value: &Value) -> Value {
+        let text = match value {
+            Value::Null => return Value::Null,
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        let tag = hmac::sign(&self.key, text.as_bytes());
+        let hex: String = tag.as_ref()[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Value::String(format!("anon_{}", hex))
+    }
+}
+
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SENSITIVE_COLUMNS
+        .iter()
+        .any(|sensitive| name == *sensitive || name.ends_with(&format!(".{}", sensitive)))
+}
+
+// Values that are neither numbers nor timestamps can't be bucketed, so
+// they are dropped rather than passed through
+fn bucket(value: &Value, width: f64) -> Value {
+    if width <= 0.0 {
+        return Value::Null;
+    }
+    if let Some(number) = value.as_f64() {
+        let floored = (number / width).floor() * width;
+        return if value.is_i64() || value.is_u64() {
+            serde_json::json!(floored as i64)
+        } else {
+            serde_json::json!(floored)
+        };
+    }
+    let parsed = value
+        .as_str()
+        .and_then(|text| chrono::DateTime::parse_from_rfc3339(text).ok());
+    match parsed {
+        Some(at) => {
+            let seconds = at.timestamp();
+            let width = width as i64;
+            let floored = seconds - seconds.rem_euclid(width.max(1));
+            chrono::DateTime::from_timestamp(floored, 0)
+                .map(|at| Value::String(at.to_rfc3339()))
+                .unwrap_or(Value::Null)
+        }
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn column(name: &str, privacy: Option<PrivacyRule>) -> ColumnDefinition {
+        ColumnDefinition {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            data_type: super::super::DataType::String,
+            format: None,
+            width: None,
+            alignment: None,
+            visible: true,
+            privacy,
+        }
+    }
+
+    #[test]
+    fn test_destination_tightens_rules() {
+        let policy = PrivacyPolicy::new(b"salt");
+        assert_eq!(
+            policy.rule_for("user_id", None, Exposure::Local),
+            PrivacyRule::Hash
+        );
+        assert_eq!(
+            policy.rule_for("user_id", None, Exposure::External),
+            PrivacyRule::Redact
+        );
+        assert_eq!(
+            policy.rule_for("command", None, Exposure::External),
+            PrivacyRule::Keep
+        );
+
+        let kept = column("user_id", Some(PrivacyRule::Keep));
+        assert_eq!(
+            policy.rule_for("user_id", Some(&kept), Exposure::Local),
+            PrivacyRule::Keep
+        );
+        assert_eq!(
+            policy.rule_for("user_id", Some(&kept), Exposure::Storage),
+            PrivacyRule::Hash
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let policy = PrivacyPolicy::new(b"salt");
+        let declared = vec![
+            column("duration_ms", Some(PrivacyRule::Bucket { width: 100.0 })),
+            column("cwd", Some(PrivacyRule::Redact)),
+        ];
+        let rows = vec![
+            HashMap::from([
+                ("user_id".to_string(), json!("alice")),
+                ("duration_ms".to_string(), json!(1234)),
+                ("cwd".to_string(), json!("/home/alice")),
+            ]),
+            HashMap::from([("user_id".to_string(), json!("alice"))]),
+        ];
+        let local = ExportDestination::LocalFile { path: "out".into() };
+        let out = policy.apply(&rows, &declared, &local);
+        assert_eq!(out[0]["duration_ms"], json!(1200));
+        assert!(!out[0].contains_key("cwd"));
+        // Stable pseudonyms keep rows joinable
+        assert_eq!(out[0]["user_id"], out[1]["user_id"]);
+        assert!(out[0]["user_id"].as_str().unwrap().starts_with("anon_"));
+        assert_ne!(
+            PrivacyPolicy::new(b"other").apply(&rows, &declared, &local)[0]["user_id"],
+            out[0]["user_id"]
+        );
+
+        assert_eq!(
+            bucket(&json!("2024-05-01T10:17:00Z"), 3600.0),
+            json!("2024-05-01T10:00:00+00:00")
+        );
+    }
+}
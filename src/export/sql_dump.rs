@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::columns::{self, Cell, Column};
+use super::{DataType, ExportFormat, ExportGenerator, ExportRequest};
+use crate::error::WarpError;
+
+// ExportRequest.metadata keys for the dump
+pub const DIALECT_KEY: &str = "sql_dialect";
+pub const TABLE_KEY: &str = "sql_table";
+
+// Rows per INSERT statement
+const INSERT_BATCH: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlDialect {
+    SQLite,
+    Postgres,
+    MySQL,
+}
+
+impl SqlDialect {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sqlite" | "sqlite3" => Some(Self::SQLite),
+            "postgres" | "postgresql" | "pg" => Some(Self::Postgres),
+            "mysql" | "mariadb" => Some(Self::MySQL),
+            _ => None,
+        }
+    }
+
+    fn quote_identifier(self, name: &str) -> String {
+        match self {
+            Self::MySQL => format!("`{}`", name.replace('`', "``")),
+            Self::SQLite | Self::Postgres => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    fn quote_string(self, text: &str) -> String {
+        let escaped = text.replace('\'', "''");
+        match self {
+            // MySQL treats backslash as an escape unless NO_BACKSLASH_ESCAPES is set
+            Self::MySQL => format!("'{}'", escaped.replace('\\', "\\\\")),
+            Self::SQLite | Self::Postgres => format!("'{}'", escaped),
+        }
+    }
+
+    fn column_type(self, data_type: &DataType) -> &'static str {
+        match (self, data_type) {
+            (_, DataType::String) => "TEXT",
+            (Self::SQLite, DataType::Integer | DataType::Boolean) => "INTEGER",
+            (_, DataType::Integer) => "BIGINT",
+            (Self::SQLite, DataType::Float | DataType::Percentage) => "REAL",
+            (Self::Postgres, DataType::Float | DataType::Percentage) => "DOUBLE PRECISION",
+            (Self::MySQL, DataType::Float | DataType::Percentage) => "DOUBLE",
+            (Self::SQLite, DataType::Currency) => "NUMERIC",
+            (Self::Postgres, DataType::Currency) => "NUMERIC(19, 4)",
+            (Self::MySQL, DataType::Currency) => "DECIMAL(19, 4)",
+            (_, DataType::Boolean) => "BOOLEAN",
+            (Self::SQLite, DataType::Date | DataType::DateTime) => "TEXT",
+            (_, DataType::Date) => "DATE",
+            (Self::Postgres, DataType::DateTime) => "TIMESTAMPTZ",
+            (Self::MySQL, DataType::DateTime) => "DATETIME",
+        }
+    }
+
+    fn literal(self, cell: &Cell) -> String {
+        match cell {
+            Cell::Null => "NULL".to_string(),
+            Cell::Text(text) => self.quote_string(text),
+            Cell::Integer(value) => value.to_string(),
+            Cell::Float(value) if value.is_finite() => value.to_string(),
+            Cell::Float(_) => "NULL".to_string(),
+            Cell::Boolean(value) => match (self, value) {
+                (Self::SQLite, true) => "1".to_string(),
+                (Self::SQLite, false) => "0".to_string(),
+                (_, value) => value.to_string().to_uppercase(),
+            },
+            Cell::Date(date) => format!("'{}'", date.format("%Y-%m-%d")),
+            Cell::DateTime(at) => match self {
+                // DATETIME has no zone; values are written in UTC
+                Self::MySQL => format!("'{}'", at.format("%Y-%m-%d %H:%M:%S%.6f")),
+                Self::SQLite | Self::Postgres => format!("'{}'", at.to_rfc3339()),
+            },
+        }
+    }
+
+    fn begin(self) -> &'static str {
+        match self {
+            Self::MySQL => "START TRANSACTION;",
+            Self::SQLite | Self::Postgres => "BEGIN;",
+        }
+    }
+}
+
+// CREATE TABLE plus batched INSERTs in one transaction. The dialect and
+// table name come from request metadata, defaulting to SQLite and the
+// data source's name.
+#[derive(Default)]
+pub struct SQLDumpGenerator;
+
+impl SQLDumpGenerator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ExportGenerator for SQLDumpGenerator {
+    fn generate(
+        &self,
+        request: &ExportRequest,
+        data: &[HashMap<String, serde_json::Value>],
+    ) -> Result<Vec<u8>, WarpError> {
+        let dialect = match request
+            .metadata
+            .get(DIALECT_KEY)
+            .and_then(|value| value.as_str())
+        {
+            Some(name) => SqlDialect::parse(name)
+                .ok_or_else(|| WarpError::Validation(format!("Unknown SQL dialect '{}'", name)))?,
+            None => SqlDialect::SQLite,
+        };
+        let table = request
+            .metadata
+            .get(TABLE_KEY)
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:?}", request.data_source).to_lowercase());
+        let columns = columns::schema(request, data);
+        Ok(dump(dialect, &table, &columns, data)?.into_bytes())
+    }
+
+    fn supported_format(&self) -> ExportFormat {
+        ExportFormat::SQLDump
+    }
+
+    fn max_row_limit(&self) -> Option<u64> {
+        None
+    }
+}
+
+pub fn dump(
+    dialect: SqlDialect,
+    table: &str,
+    columns: &[Column],
+    data: &[HashMap<String, serde_json::Value>],
+) -> Result<String, WarpError> {
+    if columns.is_empty() {
+        return Err(WarpError::Validation(
+            "Nothing to export: no columns".to_string(),
+        ));
+    }
+    let table = dialect.quote_identifier(table);
+    let mut out = format!(
+        "-- Warp export, {:?} dialect, {}\n{}\n\n",
+        dialect,
+        chrono::Utc::now().to_rfc3339(),
+        dialect.begin()
+    );
+
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            format!(
+                "  {} {}",
+                dialect.quote_identifier(&column.name),
+                dialect.column_type(&column.data_type)
+            )
+        })
+        .collect();
+    out.push_str(&format!(
+        "CREATE TABLE {} (\n{}\n);\n\n",
+        table,
+        definitions.join(",\n")
+    ));
+
+    let names: Vec<String> = columns
+        .iter()
+        .map(|column| dialect.quote_identifier(&column.name))
+        .collect();
+    for (batch, rows) in data.chunks(INSERT_BATCH).enumerate() {
+        out.push_str(&format!(
+            "INSERT INTO {} ({}) VALUES\n",
+            table,
+            names.join(", ")
+        ));
+        let mut tuples = Vec::with_capacity(rows.len());
+        for (index, row) in rows.iter().enumerate() {
+            let values = columns
+                .iter()
+                .map(|column| {
+                    columns::cell(row.get(&column.name), &column.data_type)
+                        .map(|cell| dialect.literal(&cell))
+                        .ok_or_else(|| {
+                            WarpError::Validation(format!(
+                                "Row {}: '{}' is not a valid {:?}",
+                                batch * INSERT_BATCH + index + 1,
+                                column.name,
+                                column.data_type
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            tuples.push(format!("  ({})", values.join(", ")));
+        }
+        out.push_str(&tuples.join(",\n"));
+        out.push_str(";\n\n");
+    }
+    out.push_str("COMMIT;\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn block_columns() -> Vec<Column> {
+        vec![
+            Column {
+                name: "command".to_string(),
+                data_type: DataType::String,
+            },
+            Column {
+                name: "ok".to_string(),
+                data_type: DataType::Boolean,
+            },
+            Column {
+                name: "at".to_string(),
+                data_type: DataType::DateTime,
+            },
+        ]
+    }
+
+    fn rows() -> Vec<HashMap<String, serde_json::Value>> {
+        vec![HashMap::from([
+            ("command".to_string(), json!("echo 'hi' \\n")),
+            ("ok".to_string(), json!(true)),
+            ("at".to_string(), json!("2024-05-01T10:00:00Z")),
+        ])]
+    }
+
+    #[test]
+    fn test_dialects() {
+        let sqlite = dump(SqlDialect::SQLite, "blocks", &block_columns(), &rows()).unwrap();
+        assert!(sqlite.contains(
+            "CREATE TABLE \"blocks\" (\n  \"command\" TEXT,\n  \"ok\" INTEGER,\n  \"at\" TEXT\n);"
+        ));
+        assert!(sqlite.contains("('echo ''hi'' \\n', 1, '2024-05-01T10:00:00+00:00')"));
+
+        let postgres = dump(SqlDialect::Postgres, "blocks", &block_columns(), &rows()).unwrap();
+        assert!(postgres.contains("\"at\" TIMESTAMPTZ"));
+        assert!(postgres.contains(", TRUE, "));
+
+        let mysql = dump(SqlDialect::MySQL, "blocks", &block_columns(), &rows()).unwrap();
+        assert!(mysql.starts_with("-- Warp export"));
+        assert!(mysql.contains("START TRANSACTION;"));
+        assert!(mysql.contains("INSERT INTO `blocks` (`command`, `ok`, `at`) VALUES"));
+        assert!(mysql.contains("('echo ''hi'' \\\\n', TRUE, '2024-05-01 10:00:00.000000')"));
+        assert!(mysql.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    fn test_dialect_from_metadata() {
+        let mut request = columns::test_request(ExportFormat::SQLDump);
+        request
+            .metadata
+            .insert(DIALECT_KEY.to_string(), json!("oracle"));
+        assert!(SQLDumpGenerator::new().generate(&request, &rows()).is_err());
+
+        request
+            .metadata
+            .insert(DIALECT_KEY.to_string(), json!("postgresql"));
+        let output = SQLDumpGenerator::new().generate(&request, &rows()).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        // Inferred column types, sorted by name
+        assert!(output.contains("CREATE TABLE \"terminal\" (\n  \"at\" TIMESTAMPTZ,\n  \"command\" TEXT,\n  \"ok\" BOOLEAN\n);"));
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::block_tags::BlockMarks;
+use crate::error::WarpError;
+use crate::history::HistoryManager;
+use crate::search::{BlockRecord, SearchEngine};
+use crate::startup::LazyService;
+
+use super::TimeRange;
+
+// The user's own terminal activity for DataSource::Terminal: every block in
+// the search index with its metadata and tags. With search off there is no
+// block metadata, so the command history alone is exported.
+pub struct TerminalSource {
+    search_engine: Arc<LazyService<SearchEngine>>,
+    history_manager: Arc<Mutex<HistoryManager>>,
+    search_enabled: bool,
+}
+
+impl TerminalSource {
+    pub fn new(
+        search_engine: Arc<LazyService<SearchEngine>>,
+        history_manager: Arc<Mutex<HistoryManager>>,
+        search_enabled: bool,
+    ) -> Self {
+        Self {
+            search_engine,
+            history_manager,
+            search_enabled,
+        }
+    }
+
+    pub async fn export_rows(
+        &self,
+        time_range: Option<&TimeRange>,
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, WarpError> {
+        if !self.search_enabled {
+            let history = self.history_manager.lock().await;
+            return Ok(history
+                .commands()
+                .iter()
+                .enumerate()
+                .map(|(sequence, command)| history_row(sequence, command))
+                .collect());
+        }
+
+        let engine = self.search_engine.get().await?;
+        let (after, before) = (
+            time_range.map(|range| range.start),
+            time_range.map(|range| range.end),
+        );
+        let blocks = tokio::task::spawn_blocking(move || engine.blocks(after, before))
+            .await
+            .map_err(|e| WarpError::command_err(e.to_string()))??;
+
+        let history = self.history_manager.lock().await;
+        Ok(blocks
+            .iter()
+            .map(|block| block_row(block, history.tags().get(&block.block)))
+            .collect())
+    }
+}
+
+pub fn history_row(sequence: usize, command: &str) -> HashMap<String, serde_json::Value> {
+    let mut row = HashMap::new();
+    row.insert("sequence".to_string(), serde_json::json!(sequence));
+    row.insert("command".to_string(), serde_json::json!(command));
+    row
+}
+
+// Columns: session, block, command, cwd, started, duration_ms, exit_code,
+// tags, pinned, favorite. Unknown values are null.
+pub fn block_row(
+    block: &BlockRecord,
+    marks: Option<&BlockMarks>,
+) -> HashMap<String, serde_json::Value> {
+    let tags: Vec<&str> = marks
+        .map(|marks| marks.tags.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    let mut row = HashMap::new();
+    row.insert("session".to_string(), serde_json::json!(block.session));
+    row.insert("block".to_string(), serde_json::json!(block.block));
+    row.insert("command".to_string(), serde_json::json!(block.command));
+    row.insert(
+        "cwd".to_string(),
+        serde_json::json!(block.cwd.as_ref().map(|cwd| cwd.display().to_string())),
+    );
+    row.insert(
+        "started".to_string(),
+        serde_json::json!(block.started.to_rfc3339()),
+    );
+    row.insert(
+        "duration_ms".to_string(),
+        serde_json::json!(block.duration_ms),
+    );
+    row.insert("exit_code".to_string(), serde_json::json!(block.exit_code));
+    row.insert("tags".to_string(), serde_json::json!(tags.join(",")));
+    row.insert(
+        "pinned".to_string(),
+        serde_json::json!(marks.is_some_and(|marks| marks.pinned)),
+    );
+    row.insert(
+        "favorite".to_string(),
+        serde_json::json!(marks.is_some_and(|marks| marks.favorite)),
+    );
+    row
+}
@@ -0,0 +1,558 @@
+use crossterm::style::Color;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+use crate::error::{ResultExt, WarpError};
+use crate::i18n;
+use crate::notebook;
+use crate::process_tree::{format_bytes, process_cwd};
+use crate::ui::{self, PanelItem, PanelKind, PanelView};
+
+// Enough to detect binaries, parse image headers and fill the preview
+const PREVIEW_BYTES: usize = 64 * 1024;
+const PREVIEW_LINES: usize = 200;
+const MAX_INLINE_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+impl FileEntry {
+    fn item(&self) -> PanelItem {
+        if self.is_dir {
+            PanelItem::styled(vec![(format!("{}/", self.name), Some(Color::Blue))])
+        } else {
+            PanelItem::styled(vec![
+                (self.name.clone(), None),
+                (
+                    format!("  {}", format_bytes(self.size)),
+                    Some(Color::DarkGrey),
+                ),
+            ])
+        }
+    }
+}
+
+// Directories first, then case-insensitive by name
+pub async fn list_dir(dir: &Path, show_hidden: bool) -> Result<Vec<FileEntry>, WarpError> {
+    let mut entries = Vec::new();
+    let mut reader = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Could not read {}", dir.display()))?;
+    while let Some(entry) = reader.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !show_hidden && name.starts_with('.') {
+            continue;
+        }
+        // Follows symlinks so a link to a directory can be entered; a
+        // dangling link is listed as a zero-sized file
+        let metadata = tokio::fs::metadata(entry.path()).await.ok();
+        entries.push(FileEntry {
+            name,
+            path: entry.path(),
+            is_dir: metadata.as_ref().is_some_and(|m| m.is_dir()),
+            size: metadata.as_ref().map_or(0, |m| m.len()),
+        });
+    }
+    sort_entries(&mut entries);
+    Ok(entries)
+}
+
+fn sort_entries(entries: &mut [FileEntry]) {
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+}
+
+#[derive(Debug, Clone)]
+pub enum Preview {
+    Text(Vec<String>),
+    Image {
+        format: ImageFormat,
+        dimensions: Option<(u32, u32)>,
+        size: u64,
+        // Only loaded when the terminal can display it
+        data: Option<Arc<Vec<u8>>>,
+    },
+    Binary {
+        size: u64,
+    },
+    Directory {
+        entries: usize,
+    },
+}
+
+impl Preview {
+    fn items(&self) -> Vec<PanelItem> {
+        match self {
+            Preview::Text(lines) => lines.iter().map(|line| line.clone().into()).collect(),
+            Preview::Image {
+                format,
+                dimensions,
+                size,
+                ..
+            } => {
+                let dimensions =
+                    dimensions.map_or_else(|| "?".to_string(), |(w, h)| format!("{}×{}", w, h));
+                vec![i18n::t_args(
+                    "files.image",
+                    &[
+                        ("format", &format!("{:?}", format).to_uppercase()),
+                        ("dimensions", &dimensions),
+                        ("size", &format_bytes(*size)),
+                    ],
+                )
+                .into()]
+            }
+            Preview::Binary { size } => {
+                vec![i18n::t_args("files.binary", &[("size", &format_bytes(*size))]).into()]
+            }
+            Preview::Directory { entries } => {
+                vec![i18n::t_plural("files.directory", *entries as i64).into()]
+            }
+        }
+    }
+
+    fn image(&self) -> Option<Arc<Vec<u8>>> {
+        match self {
+            Preview::Image { data, .. } => data.clone(),
+            _ => None,
+        }
+    }
+}
+
+pub async fn load_preview(entry: &FileEntry) -> Result<Preview, WarpError> {
+    if entry.is_dir {
+        let mut reader = tokio::fs::read_dir(&entry.path).await?;
+        let mut entries = 0;
+        while reader.next_entry().await?.is_some() {
+            entries += 1;
+        }
+        return Ok(Preview::Directory { entries });
+    }
+
+    let mut head = Vec::with_capacity(PREVIEW_BYTES.min(entry.size as usize));
+    tokio::fs::File::open(&entry.path)
+        .await?
+        .take(PREVIEW_BYTES as u64)
+        .read_to_end(&mut head)
+        .await?;
+
+    if let Some(format) = image_format(&head) {
+        let data = if ui::inline_images_supported() && entry.size <= MAX_INLINE_IMAGE_BYTES {
+            Some(Arc::new(tokio::fs::read(&entry.path).await?))
+        } else {
+            None
+        };
+        return Ok(Preview::Image {
+            format,
+            dimensions: image_dimensions(format, &head),
+            size: entry.size,
+            data,
+        });
+    }
+    if head.contains(&0) {
+        return Ok(Preview::Binary { size: entry.size });
+    }
+    Ok(Preview::Text(text_preview(&head)))
+}
+
+// Control characters are replaced so file contents can't move the cursor
+// or change modes while the panel is drawn
+fn text_preview(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .take(PREVIEW_LINES)
+        .map(|line| {
+            line.replace('\t', "    ")
+                .chars()
+                .map(|c| if c.is_control() { '·' } else { c })
+                .collect()
+        })
+        .collect()
+}
+
+fn image_format(bytes: &[u8]) -> Option<ImageFormat> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else {
+        None
+    }
+}
+
+fn image_dimensions(format: ImageFormat, bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]) as u32);
+    match format {
+        // IHDR is always the first chunk
+        ImageFormat::Png => {
+            let be32 =
+                |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+            Some((be32(16)?, be32(20)?))
+        }
+        ImageFormat::Gif => {
+            let le16 =
+                |at: usize| Some(u16::from_le_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]) as u32);
+            Some((le16(6)?, le16(8)?))
+        }
+        // Walk the segments up to the first start-of-frame marker
+        ImageFormat::Jpeg => {
+            let mut at = 2;
+            loop {
+                if *bytes.get(at)? != 0xFF {
+                    return None;
+                }
+                let marker = *bytes.get(at + 1)?;
+                if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                    return Some((be16(at + 7)?, be16(at + 5)?));
+                }
+                at += 2 + be16(at + 2)? as usize;
+            }
+        }
+    }
+}
+
+// Single-quoted so the path can be pasted into any POSIX shell prompt
+pub fn shell_quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if !path.is_empty()
+        && path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+:@%,".contains(c))
+    {
+        return path.to_string();
+    }
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+// Text typed at the panel prompt is for one of these
+enum PendingInput {
+    Rename(PathBuf),
+    NewDirectory,
+}
+
+pub enum FileCommand {
+    None,
+    Redraw,
+    Prompt(String),
+    InsertText(String),
+    OpenNotebook(PathBuf),
+}
+
+pub struct FileManager {
+    cwd: PathBuf,
+    entries: Vec<FileEntry>,
+    selected: usize,
+    preview: Option<Preview>,
+    show_hidden: bool,
+    confirm_delete: Option<PathBuf>,
+    pending_input: Option<PendingInput>,
+    // Shell of the pane the panel was opened from, and the directory it was
+    // last seen in
+    follow_pid: Option<u32>,
+    followed_cwd: Option<PathBuf>,
+}
+
+impl FileManager {
+    pub async fn open(follow_pid: Option<u32>) -> Result<Self, WarpError> {
+        let followed_cwd = follow_pid.and_then(process_cwd);
+        let cwd = match &followed_cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir()?,
+        };
+        let mut manager = Self {
+            cwd: PathBuf::new(),
+            entries: Vec::new(),
+            selected: 0,
+            preview: None,
+            show_hidden: false,
+            confirm_delete: None,
+            pending_input: None,
+            follow_pid,
+            followed_cwd,
+        };
+        manager.navigate(cwd).await?;
+        Ok(manager)
+    }
+
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    pub async fn navigate(&mut self, dir: PathBuf) -> Result<(), WarpError> {
+        self.entries = list_dir(&dir, self.show_hidden).await?;
+        self.cwd = dir;
+        self.confirm_delete = None;
+        self.select(0).await;
+        Ok(())
+    }
+
+    // Keeps the selection on the same name when it still exists
+    async fn reload(&mut self) -> Result<(), WarpError> {
+        let selected = self
+            .entries
+            .get(self.selected)
+            .map(|entry| entry.name.clone());
+        self.entries = list_dir(&self.cwd, self.show_hidden).await?;
+        let index = selected
+            .and_then(|name| self.entries.iter().position(|entry| entry.name == name))
+            .unwrap_or(0);
+        self.select(index).await;
+        Ok(())
+    }
+
+    // An unreadable file shows its error in the preview instead of failing
+    pub async fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+        self.preview = match self.entries.get(self.selected) {
+            Some(entry) => Some(
+                load_preview(entry)
+                    .await
+                    .unwrap_or_else(|e| Preview::Text(e.render())),
+            ),
+            None => None,
+        };
+    }
+
+    // Jumps only when the shell changed directory since the last check, so
+    // browsing elsewhere isn't undone every tick. Returns true if it moved.
+    pub async fn sync_with_pane(&mut self) -> Result<bool, WarpError> {
+        let Some(cwd) = self.follow_pid.and_then(process_cwd) else {
+            return Ok(false);
+        };
+        if self.followed_cwd.as_ref() == Some(&cwd) {
+            return Ok(false);
+        }
+        self.followed_cwd = Some(cwd.clone());
+        if cwd == self.cwd {
+            return Ok(false);
+        }
+        self.navigate(cwd).await?;
+        Ok(true)
+    }
+
+    // l/h enter/leave · p paste path · r rename · d delete (y confirms)
+    // · n new directory · . hidden files · g back to the shell's directory
+    pub async fn handle_key(&mut self, key: char, index: usize) -> Result<FileCommand, WarpError> {
+        if index != self.selected {
+            self.select(index).await;
+        }
+        let entry = self.entries.get(self.selected).cloned();
+
+        // Any key other than y cancels a pending delete
+        if let Some(target) = self.confirm_delete.take() {
+            if key == 'y' && entry.as_ref().map(|e| &e.path) == Some(&target) {
+                remove(&target).await?;
+                self.reload().await?;
+            }
+            return Ok(FileCommand::Redraw);
+        }
+
+        match (key, entry) {
+            ('l', Some(entry)) if entry.is_dir => self.navigate(entry.path).await?,
+            ('\n', Some(entry)) if notebook::is_notebook(&entry.path) => {
+                return Ok(FileCommand::OpenNotebook(entry.path))
+            }
+            ('h', _) => {
+                let Some(parent) = self.cwd.parent().map(Path::to_path_buf) else {
+                    return Ok(FileCommand::None);
+                };
+                let child = self.cwd.clone();
+                self.navigate(parent).await?;
+                if let Some(index) = self.entries.iter().position(|entry| entry.path == child) {
+                    self.select(index).await;
+                }
+            }
+            ('p', Some(entry)) => {
+                return Ok(FileCommand::InsertText(format!(
+                    "{} ",
+                    shell_quote(&entry.path)
+                )))
+            }
+            ('r', Some(entry)) => {
+                self.pending_input = Some(PendingInput::Rename(entry.path));
+                return Ok(FileCommand::Prompt(i18n::t_args(
+                    "files.rename_prompt",
+                    &[("name", &entry.name)],
+                )));
+            }
+            ('d', Some(entry)) => self.confirm_delete = Some(entry.path),
+            ('n', _) => {
+                self.pending_input = Some(PendingInput::NewDirectory);
+                return Ok(FileCommand::Prompt(i18n::t("files.new_dir_prompt")));
+            }
+            ('.', _) => {
+                self.show_hidden = !self.show_hidden;
+                self.reload().await?;
+            }
+            ('g', _) => match self.followed_cwd.clone() {
+                Some(cwd) => self.navigate(cwd).await?,
+                None => return Ok(FileCommand::None),
+            },
+            _ => return Ok(FileCommand::None),
+        }
+        Ok(FileCommand::Redraw)
+    }
+
+    pub async fn submit_input(&mut self, text: &str) -> Result<(), WarpError> {
+        let Some(pending) = self.pending_input.take() else {
+            return Ok(());
+        };
+        let name = validate_name(text)?;
+        let target = self.cwd.join(name);
+        if tokio::fs::symlink_metadata(&target).await.is_ok() {
+            return Err(WarpError::Validation(i18n::t_args(
+                "files.exists",
+                &[("name", name)],
+            )));
+        }
+        match pending {
+            PendingInput::Rename(from) => tokio::fs::rename(&from, &target)
+                .await
+                .with_context(|| format!("Could not rename {}", from.display()))?,
+            PendingInput::NewDirectory => tokio::fs::create_dir(&target)
+                .await
+                .with_context(|| format!("Could not create {}", target.display()))?,
+        }
+        self.reload().await?;
+        if let Some(index) = self.entries.iter().position(|entry| entry.path == target) {
+            self.select(index).await;
+        }
+        Ok(())
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self.entries.iter().map(FileEntry::item).collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.files_empty").into());
+        }
+        let footer = match &self.confirm_delete {
+            Some(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                i18n::t_args("files.confirm_delete", &[("name", &name)])
+            }
+            None => i18n::t("panel.files_keys"),
+        };
+        PanelView {
+            kind: PanelKind::Files,
+            title: i18n::t_args("panel.files", &[("path", &display_path(&self.cwd))]),
+            items,
+            footer: Some(footer),
+            preview: self
+                .preview
+                .as_ref()
+                .map(Preview::items)
+                .unwrap_or_default(),
+            image: self.preview.as_ref().and_then(Preview::image),
+        }
+    }
+}
+
+// A plain name in the current directory; paths and . / .. are refused
+fn validate_name(text: &str) -> Result<&str, WarpError> {
+    let name = text.trim();
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains('\0') {
+        return Err(WarpError::Validation(i18n::t_args(
+            "files.invalid_name",
+            &[("name", name)],
+        )));
+    }
+    Ok(name)
+}
+
+async fn remove(path: &Path) -> Result<(), WarpError> {
+    let metadata = tokio::fs::symlink_metadata(path).await?;
+    let result = if metadata.is_dir() {
+        tokio::fs::remove_dir_all(path).await
+    } else {
+        tokio::fs::remove_file(path).await
+    };
+    result.with_context(|| format!("Could not delete {}", path.display()))
+}
+
+fn display_path(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(&home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_dir_sorts_directories_first() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.txt", "A.txt", ".hidden"] {
+            std::fs::write(dir.path().join(name), "x").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("zdir")).unwrap();
+
+        let names: Vec<String> = list_dir(dir.path(), false)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["zdir", "A.txt", "b.txt"]);
+        assert_eq!(list_dir(dir.path(), true).await.unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(image_format(&png), Some(ImageFormat::Png));
+        assert_eq!(image_dimensions(ImageFormat::Png, &png), Some((640, 480)));
+
+        let gif = b"GIF89a\x20\x00\x10\x00";
+        assert_eq!(image_dimensions(ImageFormat::Gif, gif), Some((32, 16)));
+
+        // SOI, an APP0 segment of length 4, then SOF0 with height 2 and width 3
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00,
+            0x02, 0x00, 0x03,
+        ];
+        assert_eq!(image_format(&jpeg), Some(ImageFormat::Jpeg));
+        assert_eq!(image_dimensions(ImageFormat::Jpeg, &jpeg), Some((3, 2)));
+        assert_eq!(image_dimensions(ImageFormat::Jpeg, &jpeg[..10]), None);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote(Path::new("/tmp/a-b_c.txt")), "/tmp/a-b_c.txt");
+        assert_eq!(shell_quote(Path::new("/tmp/my file")), "'/tmp/my file'");
+        assert_eq!(shell_quote(Path::new("it's")), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_text_preview_strips_control_characters() {
+        assert_eq!(
+            text_preview(b"a\tb\x1b[2Jc\nline"),
+            vec!["a    b·[2Jc", "line"]
+        );
+        assert!(validate_name("../x").is_err());
+        assert!(validate_name("..").is_err());
+        assert_eq!(validate_name(" notes ").unwrap(), "notes");
+    }
+}
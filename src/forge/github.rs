@@ -0,0 +1,177 @@
+use serde::Deserialize;
+
+use super::{CiState, CiStatus, ForgeClient, Issue, PullRequest};
+use crate::error::WarpError;
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GitRef {
+    #[serde(rename = "ref")]
+    name: String,
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct Pull {
+    number: u64,
+    title: String,
+    user: User,
+    head: GitRef,
+    base: GitRef,
+    html_url: String,
+    #[serde(default)]
+    draft: bool,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct IssueItem {
+    number: u64,
+    title: String,
+    user: User,
+    html_url: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+    // Present when the "issue" is really a pull request
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct CheckRuns {
+    check_runs: Vec<CheckRun>,
+}
+
+#[derive(Deserialize)]
+struct CheckRun {
+    status: String,
+    conclusion: Option<String>,
+    html_url: Option<String>,
+}
+
+pub(super) async fn pull_requests(client: &ForgeClient) -> Result<Vec<PullRequest>, WarpError> {
+    let pulls: Vec<Pull> = client
+        .get(&format!(
+            "/repos/{}/pulls?state=open&per_page={}",
+            client.remote.project, client.max_items
+        ))
+        .await?;
+    Ok(pulls
+        .into_iter()
+        .map(|pull| PullRequest {
+            number: pull.number,
+            title: pull.title,
+            author: pull.user.login,
+            source_branch: pull.head.name,
+            target_branch: pull.base.name,
+            head_sha: pull.head.sha,
+            url: pull.html_url,
+            draft: pull.draft,
+            ci: None,
+        })
+        .collect())
+}
+
+// The issues endpoint also returns pull requests; those are dropped here
+pub(super) async fn issues(client: &ForgeClient) -> Result<Vec<Issue>, WarpError> {
+    let items: Vec<IssueItem> = client
+        .get(&format!(
+            "/repos/{}/issues?state=open&per_page={}",
+            client.remote.project, client.max_items
+        ))
+        .await?;
+    Ok(items
+        .into_iter()
+        .filter(|item| item.pull_request.is_none())
+        .map(|item| Issue {
+            number: item.number,
+            title: item.title,
+            author: item.user.login,
+            url: item.html_url,
+            labels: item.labels.into_iter().map(|label| label.name).collect(),
+        })
+        .collect())
+}
+
+pub(super) async fn ci_status(
+    client: &ForgeClient,
+    sha: &str,
+) -> Result<Option<CiStatus>, WarpError> {
+    let runs: CheckRuns = client
+        .get(&format!(
+            "/repos/{}/commits/{}/check-runs",
+            client.remote.project, sha
+        ))
+        .await?;
+    Ok(combine_check_runs(&runs.check_runs))
+}
+
+// One status for all check runs on a commit: any failure wins, then anything
+// still going, then success. The URL points at the run that decided it.
+fn combine_check_runs(runs: &[CheckRun]) -> Option<CiStatus> {
+    let states: Vec<(CiState, &CheckRun)> =
+        runs.iter().map(|run| (check_run_state(run), run)).collect();
+    let priority = [
+        CiState::Failure,
+        CiState::Running,
+        CiState::Pending,
+        CiState::Cancelled,
+        CiState::Success,
+        CiState::Skipped,
+    ];
+    priority.iter().find_map(|wanted| {
+        states
+            .iter()
+            .find(|(state, _)| state == wanted)
+            .map(|(state, run)| CiStatus {
+                state: *state,
+                details_url: run.html_url.clone(),
+            })
+    })
+}
+
+fn check_run_state(run: &CheckRun) -> CiState {
+    match run.status.as_str() {
+        "in_progress" => CiState::Running,
+        "completed" => match run.conclusion.as_deref() {
+            Some("success") | Some("neutral") => CiState::Success,
+            Some("skipped") => CiState::Skipped,
+            Some("cancelled") => CiState::Cancelled,
+            _ => CiState::Failure, // failure, timed_out, action_required, stale
+        },
+        _ => CiState::Pending, // queued, waiting, requested, pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(status: &str, conclusion: Option<&str>, url: &str) -> CheckRun {
+        CheckRun {
+            status: status.to_string(),
+            conclusion: conclusion.map(str::to_string),
+            html_url: Some(url.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_failure_outranks_running_and_success() {
+        let runs = vec![
+            run("completed", Some("success"), "a"),
+            run("in_progress", None, "b"),
+            run("completed", Some("timed_out"), "c"),
+        ];
+        let status = combine_check_runs(&runs).unwrap();
+        assert_eq!(status.state, CiState::Failure);
+        assert_eq!(status.details_url.as_deref(), Some("c"));
+        assert!(combine_check_runs(&[]).is_none());
+    }
+}
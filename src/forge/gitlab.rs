@@ -0,0 +1,131 @@
+use serde::Deserialize;
+
+use super::{CiState, CiStatus, ForgeClient, Issue, PullRequest};
+use crate::error::WarpError;
+
+#[derive(Deserialize)]
+struct User {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    iid: u64,
+    title: String,
+    author: User,
+    source_branch: String,
+    target_branch: String,
+    #[serde(default)]
+    sha: String,
+    web_url: String,
+    #[serde(default)]
+    draft: bool,
+}
+
+#[derive(Deserialize)]
+struct IssueItem {
+    iid: u64,
+    title: String,
+    author: User,
+    web_url: String,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Pipeline {
+    status: String,
+    web_url: Option<String>,
+}
+
+// Projects are addressed by their URL-encoded path, subgroups included
+fn project_id(client: &ForgeClient) -> String {
+    client.remote.project.replace('/', "%2F")
+}
+
+pub(super) async fn merge_requests(client: &ForgeClient) -> Result<Vec<PullRequest>, WarpError> {
+    let requests: Vec<MergeRequest> = client
+        .get(&format!(
+            "/projects/{}/merge_requests?state=opened&per_page={}",
+            project_id(client),
+            client.max_items
+        ))
+        .await?;
+    Ok(requests
+        .into_iter()
+        .map(|mr| PullRequest {
+            number: mr.iid,
+            title: mr.title,
+            author: mr.author.username,
+            source_branch: mr.source_branch,
+            target_branch: mr.target_branch,
+            head_sha: mr.sha,
+            url: mr.web_url,
+            draft: mr.draft,
+            ci: None,
+        })
+        .collect())
+}
+
+pub(super) async fn issues(client: &ForgeClient) -> Result<Vec<Issue>, WarpError> {
+    let items: Vec<IssueItem> = client
+        .get(&format!(
+            "/projects/{}/issues?state=opened&per_page={}",
+            project_id(client),
+            client.max_items
+        ))
+        .await?;
+    Ok(items
+        .into_iter()
+        .map(|item| Issue {
+            number: item.iid,
+            title: item.title,
+            author: item.author.username,
+            url: item.web_url,
+            labels: item.labels,
+        })
+        .collect())
+}
+
+// The newest pipeline for the commit decides the status
+pub(super) async fn ci_status(
+    client: &ForgeClient,
+    sha: &str,
+) -> Result<Option<CiStatus>, WarpError> {
+    if sha.is_empty() {
+        return Ok(None);
+    }
+    let pipelines: Vec<Pipeline> = client
+        .get(&format!(
+            "/projects/{}/pipelines?sha={}&per_page=1",
+            project_id(client),
+            sha
+        ))
+        .await?;
+    Ok(pipelines.into_iter().next().map(|pipeline| CiStatus {
+        state: pipeline_state(&pipeline.status),
+        details_url: pipeline.web_url,
+    }))
+}
+
+fn pipeline_state(status: &str) -> CiState {
+    match status {
+        "running" => CiState::Running,
+        "success" => CiState::Success,
+        "failed" => CiState::Failure,
+        "canceled" => CiState::Cancelled,
+        "skipped" => CiState::Skipped,
+        _ => CiState::Pending, // created, waiting_for_resource, preparing, pending, manual, scheduled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_state() {
+        assert_eq!(pipeline_state("failed"), CiState::Failure);
+        assert_eq!(pipeline_state("waiting_for_resource"), CiState::Pending);
+    }
+}
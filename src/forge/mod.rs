@@ -0,0 +1,452 @@
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::config::ForgeConfig;
+use crate::error::WarpError;
+use crate::i18n;
+use crate::network;
+use crate::secrets::SecretsManager;
+
+mod github;
+mod gitlab;
+pub mod panel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+}
+
+// The hosting service behind one of the repository's remotes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForgeRemote {
+    pub kind: ForgeKind,
+    pub host: String,
+    pub project: String, // owner/repo, or group/subgroup/project on GitLab
+    pub remote_name: String,
+}
+
+impl ForgeRemote {
+    pub fn from_url(remote_name: &str, url: &str, config: &ForgeConfig) -> Option<Self> {
+        let (host, project) = parse_remote_url(url)?;
+        let kind = if config
+            .github_hosts
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(&host))
+        {
+            ForgeKind::GitHub
+        } else if config
+            .gitlab_hosts
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(&host))
+        {
+            ForgeKind::GitLab
+        } else {
+            return None;
+        };
+        Some(Self {
+            kind,
+            host,
+            project,
+            remote_name: remote_name.to_string(),
+        })
+    }
+
+    // Name of the token in the secrets manager
+    pub fn secret_name(&self) -> String {
+        format!("forge.{}", self.host)
+    }
+
+    fn api_base(&self) -> String {
+        match self.kind {
+            ForgeKind::GitHub if self.host == "github.com" => "https://api.github.com".to_string(),
+            ForgeKind::GitHub => format!("https://{}/api/v3", self.host),
+            ForgeKind::GitLab => format!("https://{}/api/v4", self.host),
+        }
+    }
+}
+
+// Prefers `upstream` over `origin`: in a fork, pull requests are opened
+// against the upstream project
+pub fn detect_remote(path: &Path, config: &ForgeConfig) -> Result<ForgeRemote, WarpError> {
+    let repo = git2::Repository::discover(path)
+        .map_err(|_| WarpError::not_found("git repository", path.display().to_string()))?;
+    let remotes = repo.remotes()?;
+    let mut names: Vec<&str> = remotes.iter().flatten().collect();
+    names.sort_by_key(|name| match *name {
+        "upstream" => 0,
+        "origin" => 1,
+        _ => 2,
+    });
+    for name in names {
+        let remote = repo.find_remote(name)?;
+        if let Some(forge) = remote
+            .url()
+            .and_then(|url| ForgeRemote::from_url(name, url, config))
+        {
+            return Ok(forge);
+        }
+    }
+    Err(WarpError::not_found(
+        "GitHub or GitLab remote",
+        path.display().to_string(),
+    ))
+}
+
+// Accepts https://host/owner/repo(.git), ssh://git@host:22/owner/repo.git and
+// scp-style git@host:owner/repo.git. Returns (host, project path).
+pub fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let (host, path) = match url.split_once("://") {
+        Some((_, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            let host = authority.rsplit('@').next()?.split(':').next()?;
+            (host, path)
+        }
+        None => {
+            let (authority, path) = url.split_once(':')?;
+            (authority.rsplit('@').next()?, path)
+        }
+    };
+    let project = path.trim_matches('/').trim_end_matches(".git");
+    if host.is_empty() || !project.contains('/') {
+        return None;
+    }
+    Some((host.to_ascii_lowercase(), project.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CiState {
+    Pending,
+    Running,
+    Success,
+    Failure,
+    Cancelled,
+    Skipped,
+}
+
+impl CiState {
+    pub fn icon(self) -> &'static str {
+        match self {
+            CiState::Pending => "◷",
+            CiState::Running => "⟳",
+            CiState::Success => "✔",
+            CiState::Failure => "✘",
+            CiState::Cancelled => "⊘",
+            CiState::Skipped => "⤼",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CiStatus {
+    pub state: CiState,
+    pub details_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub head_sha: String,
+    pub url: String,
+    pub draft: bool,
+    pub ci: Option<CiStatus>,
+}
+
+impl PullRequest {
+    // Works for branches in forks too, which the source branch name doesn't
+    pub fn fetch_ref(&self, kind: ForgeKind) -> String {
+        match kind {
+            ForgeKind::GitHub => format!("refs/pull/{}/head", self.number),
+            ForgeKind::GitLab => format!("refs/merge-requests/{}/head", self.number),
+        }
+    }
+
+    pub fn local_branch(&self, kind: ForgeKind) -> String {
+        match kind {
+            ForgeKind::GitHub => format!("pr-{}", self.number),
+            ForgeKind::GitLab => format!("mr-{}", self.number),
+        }
+    }
+
+    pub fn display_line(&self) -> String {
+        format!(
+            "#{}{} {} ({}) {} → {}",
+            self.number,
+            if self.draft { " [draft]" } else { "" },
+            self.title,
+            self.author,
+            self.source_branch,
+            self.target_branch
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub url: String,
+    pub labels: Vec<String>,
+}
+
+impl Issue {
+    pub fn display_line(&self) -> String {
+        let mut line = format!("#{} {} ({})", self.number, self.title, self.author);
+        if !self.labels.is_empty() {
+            line.push_str(&format!(" [{}]", self.labels.join(", ")));
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForgeSnapshot {
+    pub pull_requests: Vec<PullRequest>,
+    pub issues: Vec<Issue>,
+}
+
+impl ForgeSnapshot {
+    // Pull requests whose CI was not failing in `previous` and now is. PRs
+    // missing from `previous`, as on the first load, never count.
+    pub fn newly_failing<'a>(&'a self, previous: &ForgeSnapshot) -> Vec<&'a PullRequest> {
+        let failing = |pr: &PullRequest| {
+            pr.ci
+                .as_ref()
+                .is_some_and(|ci| ci.state == CiState::Failure)
+        };
+        self.pull_requests
+            .iter()
+            .filter(|pr| failing(pr))
+            .filter(|pr| {
+                previous
+                    .pull_requests
+                    .iter()
+                    .any(|old| old.number == pr.number && !failing(old))
+            })
+            .collect()
+    }
+}
+
+pub struct ForgeClient {
+    remote: ForgeRemote,
+    http: reqwest::Client,
+    token: Option<String>,
+    max_items: usize,
+}
+
+impl ForgeClient {
+    // The token comes from the secrets manager, falling back to the
+    // environment variables the official CLIs use. Public projects work
+    // without one, subject to much lower rate limits.
+    pub async fn new(
+        remote: ForgeRemote,
+        secrets: &SecretsManager,
+        config: &ForgeConfig,
+    ) -> Result<Self, WarpError> {
+        let mut token = secrets.get(&remote.secret_name()).await?;
+        if token.is_none() {
+            let vars: &[&str] = match remote.kind {
+                ForgeKind::GitHub => &["GITHUB_TOKEN", "GH_TOKEN"],
+                ForgeKind::GitLab => &["GITLAB_TOKEN"],
+            };
+            token = vars
+                .iter()
+                .find_map(|var| std::env::var(var).ok().filter(|t| !t.is_empty()));
+        }
+        Ok(Self {
+            remote,
+            http: network::client()?,
+            token,
+            max_items: config.max_items,
+        })
+    }
+
+    pub fn remote(&self) -> &ForgeRemote {
+        &self.remote
+    }
+
+    // Pull requests with their CI status, and issues. A CI lookup failing
+    // for one PR leaves its status empty rather than failing the listing.
+    pub async fn snapshot(&self) -> Result<ForgeSnapshot, WarpError> {
+        let (mut pull_requests, issues) = match self.remote.kind {
+            ForgeKind::GitHub => (
+                github::pull_requests(self).await?,
+                github::issues(self).await?,
+            ),
+            ForgeKind::GitLab => (
+                gitlab::merge_requests(self).await?,
+                gitlab::issues(self).await?,
+            ),
+        };
+        let statuses =
+            futures::future::join_all(pull_requests.iter().map(|pr| self.ci_status(pr))).await;
+        for (pr, status) in pull_requests.iter_mut().zip(statuses) {
+            pr.ci = status.unwrap_or_else(|e| {
+                log::debug!("CI status for #{} failed: {}", pr.number, e);
+                None
+            });
+        }
+        Ok(ForgeSnapshot {
+            pull_requests,
+            issues,
+        })
+    }
+
+    pub async fn ci_status(&self, pr: &PullRequest) -> Result<Option<CiStatus>, WarpError> {
+        match self.remote.kind {
+            ForgeKind::GitHub => github::ci_status(self, &pr.head_sha).await,
+            ForgeKind::GitLab => gitlab::ci_status(self, &pr.head_sha).await,
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, WarpError> {
+        let url = network::endpoint(&format!("{}{}", self.remote.api_base(), path));
+        let mut request = self.http.get(&url).header(USER_AGENT, "warp-terminal");
+        if self.remote.kind == ForgeKind::GitHub {
+            request = request.header(ACCEPT, "application/vnd.github+json");
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| WarpError::network(format!("Could not reach {}", self.remote.host), e))?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(WarpError::Auth(i18n::t_args(
+                "forge.auth_required",
+                &[
+                    ("host", &self.remote.host),
+                    ("secret", &self.remote.secret_name()),
+                ],
+            )));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(WarpError::api(Some(status.as_u16()), body));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ForgeConfig {
+        ForgeConfig {
+            github_hosts: vec!["github.com".to_string()],
+            gitlab_hosts: vec!["gitlab.com".to_string(), "git.corp.example".to_string()],
+            max_items: 50,
+        }
+    }
+
+    #[test]
+    fn test_parse_remote_url_forms() {
+        let expected = Some(("github.com".to_string(), "warpdotdev/warp".to_string()));
+        assert_eq!(
+            parse_remote_url("https://github.com/warpdotdev/warp.git"),
+            expected
+        );
+        assert_eq!(
+            parse_remote_url("git@github.com:warpdotdev/warp.git"),
+            expected
+        );
+        assert_eq!(
+            parse_remote_url("ssh://git@GitHub.com:22/warpdotdev/warp/"),
+            expected
+        );
+        assert_eq!(parse_remote_url("/srv/git/warp.git"), None);
+    }
+
+    #[test]
+    fn test_self_hosted_gitlab_with_subgroups() {
+        let remote = ForgeRemote::from_url(
+            "origin",
+            "git@git.corp.example:platform/tools/warp.git",
+            &config(),
+        )
+        .unwrap();
+        assert_eq!(remote.kind, ForgeKind::GitLab);
+        assert_eq!(remote.project, "platform/tools/warp");
+        assert_eq!(remote.api_base(), "https://git.corp.example/api/v4");
+        assert!(ForgeRemote::from_url("origin", "git@bitbucket.org:a/b.git", &config()).is_none());
+    }
+    #[tokio::test]
+    async fn test_snapshot_from_cassette() {
+        let playback = crate::cassette::Playback::start(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/github_snapshot.json"
+        ))
+        .await
+        .unwrap();
+        let client = ForgeClient {
+            remote: ForgeRemote::from_url(
+                "origin",
+                "git@github.com:warpdotdev/warp.git",
+                &config(),
+            )
+            .unwrap(),
+            http: network::client().unwrap(),
+            token: None,
+            max_items: 50,
+        };
+        let snapshot = client.snapshot().await.unwrap();
+        playback.finish().unwrap();
+
+        assert_eq!(snapshot.pull_requests.len(), 1);
+        let pr = &snapshot.pull_requests[0];
+        assert_eq!((pr.number, pr.source_branch.as_str()), (42, "fix-scroll"));
+        assert_eq!(pr.ci.as_ref().map(|ci| ci.state), Some(CiState::Failure));
+        // The pull request listed among issues is dropped
+        assert_eq!(snapshot.issues.len(), 1);
+        assert_eq!(snapshot.issues[0].labels, vec!["bug"]);
+    }
+
+    #[test]
+    fn test_newly_failing() {
+        let pr = |number: u64, state: CiState| PullRequest {
+            number,
+            title: String::new(),
+            author: String::new(),
+            source_branch: String::new(),
+            target_branch: String::new(),
+            head_sha: String::new(),
+            url: String::new(),
+            draft: false,
+            ci: Some(CiStatus {
+                state,
+                details_url: None,
+            }),
+        };
+        let before = ForgeSnapshot {
+            pull_requests: vec![pr(1, CiState::Running), pr(2, CiState::Failure)],
+            issues: Vec::new(),
+        };
+        let after = ForgeSnapshot {
+            pull_requests: vec![
+                pr(1, CiState::Failure),
+                pr(2, CiState::Failure),
+                pr(3, CiState::Failure),
+            ],
+            issues: Vec::new(),
+        };
+        let numbers: Vec<u64> = after
+            .newly_failing(&before)
+            .iter()
+            .map(|pr| pr.number)
+            .collect();
+        assert_eq!(numbers, vec![1]);
+        assert!(after.newly_failing(&ForgeSnapshot::default()).is_empty());
+    }
+}
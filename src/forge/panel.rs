@@ -0,0 +1,166 @@
+use crossterm::style::Color;
+use std::sync::Arc;
+
+use super::{CiState, ForgeClient, ForgeSnapshot, PullRequest};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForgeTab {
+    PullRequests,
+    Issues,
+}
+
+// Actions that leave the panel: the app performs them so network and git
+// work doesn't happen under the panel lock
+#[derive(Debug, Clone)]
+pub enum ForgeCommand {
+    None,
+    Redraw,
+    Refresh,
+    OpenUrl(String),
+    CopyUrl(String),
+    Checkout(PullRequest),
+}
+
+pub struct ForgePanel {
+    client: Arc<ForgeClient>,
+    tab: ForgeTab,
+    snapshot: ForgeSnapshot,
+    loading: bool,
+    error: Option<String>,
+}
+
+impl ForgePanel {
+    pub fn new(client: Arc<ForgeClient>) -> Self {
+        Self {
+            client,
+            tab: ForgeTab::PullRequests,
+            snapshot: ForgeSnapshot::default(),
+            loading: true,
+            error: None,
+        }
+    }
+
+    pub fn client(&self) -> Arc<ForgeClient> {
+        self.client.clone()
+    }
+
+    pub fn snapshot(&self) -> &ForgeSnapshot {
+        &self.snapshot
+    }
+
+    pub fn set_loading(&mut self) {
+        self.loading = true;
+    }
+
+    // Errors are shown inside the panel; the previous listing stays visible
+    pub fn apply(&mut self, result: Result<ForgeSnapshot, WarpError>) {
+        self.loading = false;
+        match result {
+            Ok(snapshot) => {
+                self.snapshot = snapshot;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.render().join(" ")),
+        }
+    }
+
+    // v switch PRs/issues · o open · y copy URL · c checkout · l open CI run · r refresh
+    pub fn handle_key(&mut self, key: char, index: usize) -> ForgeCommand {
+        if key == 'v' {
+            self.tab = match self.tab {
+                ForgeTab::PullRequests => ForgeTab::Issues,
+                ForgeTab::Issues => ForgeTab::PullRequests,
+            };
+            return ForgeCommand::Redraw;
+        }
+        if key == 'r' {
+            return ForgeCommand::Refresh;
+        }
+
+        let url = match self.tab {
+            ForgeTab::PullRequests => self
+                .snapshot
+                .pull_requests
+                .get(index)
+                .map(|pr| pr.url.clone()),
+            ForgeTab::Issues => self
+                .snapshot
+                .issues
+                .get(index)
+                .map(|issue| issue.url.clone()),
+        };
+        let pr = match self.tab {
+            ForgeTab::PullRequests => self.snapshot.pull_requests.get(index),
+            ForgeTab::Issues => None,
+        };
+        match (key, url, pr) {
+            ('o', Some(url), _) => ForgeCommand::OpenUrl(url),
+            ('y', Some(url), _) => ForgeCommand::CopyUrl(url),
+            ('c', _, Some(pr)) => ForgeCommand::Checkout(pr.clone()),
+            ('l', _, Some(pr)) => match pr.ci.as_ref().and_then(|ci| ci.details_url.clone()) {
+                Some(url) => ForgeCommand::OpenUrl(url),
+                None => ForgeCommand::None,
+            },
+            _ => ForgeCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let remote = self.client.remote();
+        let (title_key, keys) = match self.tab {
+            ForgeTab::PullRequests => ("panel.forge_pulls", "panel.forge_pull_keys"),
+            ForgeTab::Issues => ("panel.forge_issues", "panel.forge_issue_keys"),
+        };
+
+        let mut items: Vec<PanelItem> = match self.tab {
+            ForgeTab::PullRequests => self
+                .snapshot
+                .pull_requests
+                .iter()
+                .map(pull_request_item)
+                .collect(),
+            ForgeTab::Issues => self
+                .snapshot
+                .issues
+                .iter()
+                .map(|issue| issue.display_line().into())
+                .collect(),
+        };
+        if items.is_empty() && !self.loading && self.error.is_none() {
+            items.push(i18n::t("panel.forge_empty").into());
+        }
+
+        let footer = if self.loading {
+            i18n::t("panel.forge_loading")
+        } else if let Some(error) = &self.error {
+            error.clone()
+        } else {
+            i18n::t(keys)
+        };
+        PanelView {
+            kind: PanelKind::Forge,
+            title: i18n::t_args(title_key, &[("project", &remote.project)]),
+            items,
+            footer: Some(footer),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+}
+
+fn pull_request_item(pr: &PullRequest) -> PanelItem {
+    let (icon, color) = match pr.ci.as_ref().map(|ci| ci.state) {
+        Some(state @ CiState::Success) => (state.icon(), Some(Color::Green)),
+        Some(state @ CiState::Failure) => (state.icon(), Some(Color::Red)),
+        Some(state @ (CiState::Running | CiState::Pending)) => (state.icon(), Some(Color::Yellow)),
+        Some(state) => (state.icon(), None),
+        None => (" ", None),
+    };
+    PanelItem::styled(vec![
+        (icon.to_string(), color),
+        (format!(" {}", pr.display_line()), None),
+    ])
+}
@@ -0,0 +1,92 @@
+use crossterm::style::Color;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+use super::DiffHunk;
+
+const THEME: &str = "base16-ocean.dark";
+
+pub type Segments = Vec<(String, Option<Color>)>;
+
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+// Loading the bundled syntax definitions takes tens of milliseconds, so it
+// happens once, on the first diff shown
+fn highlighter() -> &'static Highlighter {
+    static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+    HIGHLIGHTER.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove(THEME).unwrap_or_default(),
+        }
+    })
+}
+
+fn syntax(extension: Option<&str>) -> &'static SyntaxReference {
+    let syntaxes = &highlighter().syntaxes;
+    extension
+        .and_then(|ext| syntaxes.find_syntax_by_extension(ext))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+fn highlight_line(lines: &mut HighlightLines, text: &str) -> Segments {
+    let content = format!("{}\n", text);
+    match lines.highlight_line(&content, &highlighter().syntaxes) {
+        Ok(ranges) => ranges
+            .into_iter()
+            .map(|(style, text)| {
+                let color = Color::Rgb {
+                    r: style.foreground.r,
+                    g: style.foreground.g,
+                    b: style.foreground.b,
+                };
+                (text.trim_end_matches('\n').to_string(), Some(color))
+            })
+            .collect(),
+        Err(_) => vec![(text.to_string(), None)],
+    }
+}
+
+// One row per diff line: the +/- marker coloured by change type, followed by
+// the content highlighted for the file's language. Unknown languages and
+// lines syntect can't parse fall back to plain text.
+pub fn highlight_hunk(path: &str, hunk: &DiffHunk) -> Vec<Segments> {
+    let extension = Path::new(path).extension().and_then(|ext| ext.to_str());
+    let mut lines = HighlightLines::new(syntax(extension), &highlighter().theme);
+
+    hunk.lines
+        .iter()
+        .map(|line| {
+            let mut segments = vec![(line.origin.to_string(), marker_color(line.origin))];
+            segments.extend(highlight_line(&mut lines, &line.content));
+            segments
+        })
+        .collect()
+}
+
+// Plain source text, one row per line, highlighted as the language for
+// `extension`
+pub fn highlight_source(extension: &str, source: &str) -> Vec<Segments> {
+    let mut lines = HighlightLines::new(syntax(Some(extension)), &highlighter().theme);
+    source
+        .lines()
+        .map(|line| highlight_line(&mut lines, line))
+        .collect()
+}
+
+fn marker_color(origin: char) -> Option<Color> {
+    match origin {
+        '+' | '>' => Some(Color::Green),
+        '-' | '<' => Some(Color::Red),
+        _ => None,
+    }
+}
@@ -0,0 +1,689 @@
+use git2::{
+    build::CheckoutBuilder, ApplyLocation, ApplyOptions, BranchType, Cred, CredentialType, Diff,
+    DiffOptions, FetchOptions, Patch, PushOptions, RemoteCallbacks, Repository, Status,
+    StatusOptions,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+use crate::error::WarpError;
+use crate::i18n;
+
+pub mod highlight;
+pub mod panel;
+
+// Credential callbacks are retried by libgit2 until one succeeds; without a
+// cap a rejected key loops forever
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    TypeChange,
+    Untracked,
+    Conflicted,
+}
+
+impl ChangeKind {
+    pub fn code(self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Deleted => 'D',
+            ChangeKind::Renamed => 'R',
+            ChangeKind::TypeChange => 'T',
+            ChangeKind::Untracked => '?',
+            ChangeKind::Conflicted => 'U',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStatus {
+    pub path: String,
+    pub staged: Option<ChangeKind>,
+    pub unstaged: Option<ChangeKind>,
+}
+
+impl FileStatus {
+    // None for clean and ignored entries
+    fn from_git(path: &str, status: Status) -> Option<Self> {
+        if status.is_conflicted() {
+            return Some(Self {
+                path: path.to_string(),
+                staged: Some(ChangeKind::Conflicted),
+                unstaged: Some(ChangeKind::Conflicted),
+            });
+        }
+        let staged = if status.is_index_new() {
+            Some(ChangeKind::Added)
+        } else if status.is_index_modified() {
+            Some(ChangeKind::Modified)
+        } else if status.is_index_deleted() {
+            Some(ChangeKind::Deleted)
+        } else if status.is_index_renamed() {
+            Some(ChangeKind::Renamed)
+        } else if status.is_index_typechange() {
+            Some(ChangeKind::TypeChange)
+        } else {
+            None
+        };
+        let unstaged = if status.is_wt_new() {
+            Some(ChangeKind::Untracked)
+        } else if status.is_wt_modified() {
+            Some(ChangeKind::Modified)
+        } else if status.is_wt_deleted() {
+            Some(ChangeKind::Deleted)
+        } else if status.is_wt_renamed() {
+            Some(ChangeKind::Renamed)
+        } else if status.is_wt_typechange() {
+            Some(ChangeKind::TypeChange)
+        } else {
+            None
+        };
+        if staged.is_none() && unstaged.is_none() {
+            return None;
+        }
+        Some(Self {
+            path: path.to_string(),
+            staged,
+            unstaged,
+        })
+    }
+
+    // Two-column code as in `git status --short`
+    pub fn short_code(&self) -> String {
+        if self.unstaged == Some(ChangeKind::Untracked) {
+            return "??".to_string();
+        }
+        let column = |kind: Option<ChangeKind>| kind.map(ChangeKind::code).unwrap_or(' ');
+        format!("{}{}", column(self.staged), column(self.unstaged))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoStatus {
+    pub branch: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub files: Vec<FileStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub origin: char, // '+', '-', ' ' or one of libgit2's EOF markers
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub header: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchInfo {
+    pub name: String,
+    pub is_head: bool,
+    pub upstream: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitProgress {
+    Receiving { percent: u8 },
+    Sending { percent: u8 },
+}
+
+impl GitProgress {
+    pub fn describe(&self) -> String {
+        match self {
+            GitProgress::Receiving { percent } => {
+                i18n::t_args("git.receiving", &[("percent", &percent.to_string())])
+            }
+            GitProgress::Sending { percent } => {
+                i18n::t_args("git.sending", &[("percent", &percent.to_string())])
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PullOutcome {
+    UpToDate,
+    FastForwarded,
+}
+
+// All operations open the repository fresh on a blocking thread; libgit2
+// handles aren't Send and the working tree can change under us between calls
+#[derive(Debug, Clone)]
+pub struct GitManager {
+    path: PathBuf,
+}
+
+impl GitManager {
+    // Finds the repository containing `path`
+    pub fn discover(path: &Path) -> Result<Self, WarpError> {
+        let repo = Repository::discover(path)
+            .map_err(|_| WarpError::not_found("git repository", path.display().to_string()))?;
+        let root = repo.workdir().ok_or_else(|| {
+            WarpError::Validation("Bare repositories are not supported".to_string())
+        })?;
+        Ok(Self {
+            path: root.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn with_repo<T, F>(&self, f: F) -> Result<T, WarpError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Repository) -> Result<T, WarpError> + Send + 'static,
+    {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || f(&Repository::open(&path)?))
+            .await
+            .map_err(|e| WarpError::command_err(format!("git task failed: {}", e)))?
+    }
+
+    pub async fn status(&self) -> Result<RepoStatus, WarpError> {
+        self.with_repo(read_status).await
+    }
+
+    pub async fn diff(&self, path: &str, staged: bool) -> Result<Vec<DiffHunk>, WarpError> {
+        let path = path.to_string();
+        self.with_repo(move |repo| collect_hunks(&file_diff(repo, &path, staged, false)?))
+            .await
+    }
+
+    pub async fn stage_file(&self, path: &str) -> Result<(), WarpError> {
+        let path = path.to_string();
+        self.with_repo(move |repo| {
+            let mut index = repo.index()?;
+            let workdir = repo.workdir().unwrap_or_else(|| Path::new("."));
+            if workdir.join(&path).exists() {
+                index.add_path(Path::new(&path))?;
+            } else {
+                index.remove_path(Path::new(&path))?;
+            }
+            index.write()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn unstage_file(&self, path: &str) -> Result<(), WarpError> {
+        let path = path.to_string();
+        self.with_repo(move |repo| {
+            match repo.head().ok().and_then(|head| head.peel_to_commit().ok()) {
+                Some(commit) => repo.reset_default(Some(commit.as_object()), [path.as_str()])?,
+                // Nothing committed yet: unstaging means dropping the entry
+                None => {
+                    let mut index = repo.index()?;
+                    index.remove_path(Path::new(&path))?;
+                    index.write()?;
+                }
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    // Stages one hunk of the unstaged diff, or with `staged` unstages one
+    // hunk of the staged diff by applying it in reverse to the index
+    pub async fn apply_hunk(&self, path: &str, hunk: usize, staged: bool) -> Result<(), WarpError> {
+        let path = path.to_string();
+        self.with_repo(move |repo| {
+            let diff = file_diff(repo, &path, staged, staged)?;
+            let mut seen = 0;
+            let mut options = ApplyOptions::new();
+            options.hunk_callback(|candidate| {
+                if candidate.is_none() {
+                    return false;
+                }
+                seen += 1;
+                seen - 1 == hunk
+            });
+            repo.apply(&diff, ApplyLocation::Index, Some(&mut options))?;
+            Ok(())
+        })
+        .await
+    }
+
+    // Returns the short id of the new commit
+    pub async fn commit(&self, message: &str) -> Result<String, WarpError> {
+        let message = git2::message_prettify(message, Some(b'#'))?;
+        if message.trim().is_empty() {
+            return Err(WarpError::Validation(i18n::t("git.empty_message")));
+        }
+        self.with_repo(move |repo| {
+            let signature = repo.signature().map_err(|_| {
+                WarpError::config_err("Set user.name and user.email in your git config to commit")
+            })?;
+            let mut index = repo.index()?;
+            if index.has_conflicts() {
+                return Err(WarpError::Validation(
+                    "Resolve conflicts before committing".to_string(),
+                ));
+            }
+            let tree = repo.find_tree(index.write_tree()?)?;
+            let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+            let unchanged = match &parent {
+                Some(parent) => parent.tree_id() == tree.id(),
+                None => tree.is_empty(),
+            };
+            if unchanged {
+                return Err(WarpError::Validation(i18n::t("git.nothing_staged")));
+            }
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            let oid = repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &message,
+                &tree,
+                &parents,
+            )?;
+            Ok(oid.to_string().chars().take(7).collect())
+        })
+        .await
+    }
+
+    pub async fn branches(&self) -> Result<Vec<BranchInfo>, WarpError> {
+        self.with_repo(|repo| {
+            let mut branches = Vec::new();
+            for entry in repo.branches(Some(BranchType::Local))? {
+                let (branch, _) = entry?;
+                let Some(name) = branch.name()?.map(str::to_string) else {
+                    continue;
+                };
+                let upstream = branch
+                    .upstream()
+                    .ok()
+                    .and_then(|upstream| upstream.name().ok().flatten().map(str::to_string));
+                branches.push(BranchInfo {
+                    name,
+                    is_head: branch.is_head(),
+                    upstream,
+                });
+            }
+            branches.sort_by(|a, b| b.is_head.cmp(&a.is_head).then_with(|| a.name.cmp(&b.name)));
+            Ok(branches)
+        })
+        .await
+    }
+
+    // Safe checkout: refuses to overwrite local modifications
+    pub async fn checkout_branch(&self, name: &str) -> Result<(), WarpError> {
+        let name = name.to_string();
+        self.with_repo(move |repo| {
+            let branch = repo.find_branch(&name, BranchType::Local)?;
+            let reference = branch.get();
+            let refname = reference
+                .name()
+                .ok_or_else(|| {
+                    WarpError::Validation(format!("Branch name is not valid UTF-8: {}", name))
+                })?
+                .to_string();
+            let tree = reference.peel_to_tree()?;
+            repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))?;
+            repo.set_head(&refname)?;
+            Ok(())
+        })
+        .await
+    }
+
+    // Fetches `source_ref` (e.g. refs/pull/12/head) from `remote` into the
+    // local branch `branch` and checks it out. Review branches are scratch
+    // copies of the remote, so an existing one is reset to the fetched commit.
+    pub async fn checkout_remote_ref(
+        &self,
+        remote: &str,
+        source_ref: &str,
+        branch: &str,
+        progress: mpsc::UnboundedSender<GitProgress>,
+    ) -> Result<(), WarpError> {
+        let (remote, source_ref, branch) = (
+            remote.to_string(),
+            source_ref.to_string(),
+            branch.to_string(),
+        );
+        self.with_repo(move |repo| {
+            let mut remote = repo.find_remote(&remote)?;
+            let config = repo.config()?;
+            let mut options = FetchOptions::new();
+            options.remote_callbacks(remote_callbacks(&config, progress));
+            remote.fetch(&[source_ref.as_str()], Some(&mut options), None)?;
+            let target = repo.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+
+            let refname = format!("refs/heads/{}", branch);
+            let on_branch = repo
+                .head()
+                .ok()
+                .and_then(|head| head.name().map(|name| name == refname));
+            if on_branch == Some(true) {
+                // libgit2 refuses to force-update the checked-out branch
+                repo.find_reference(&refname)?
+                    .set_target(target.id(), "review: update")?;
+                repo.checkout_head(Some(CheckoutBuilder::new().safe()))?;
+            } else {
+                repo.branch(&branch, &target, true)?;
+                repo.checkout_tree(target.as_object(), Some(CheckoutBuilder::new().safe()))?;
+                repo.set_head(&refname)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn push(
+        &self,
+        progress: mpsc::UnboundedSender<GitProgress>,
+    ) -> Result<(), WarpError> {
+        self.with_repo(move |repo| {
+            let (refname, remote_name) = current_branch_remote(repo)?;
+            let mut remote = repo.find_remote(&remote_name)?;
+            let config = repo.config()?;
+            let mut callbacks = remote_callbacks(&config, progress);
+            callbacks.push_update_reference(|reference, rejection| match rejection {
+                Some(reason) => Err(git2::Error::from_str(&format!(
+                    "{} rejected: {}",
+                    reference, reason
+                ))),
+                None => Ok(()),
+            });
+            let mut options = PushOptions::new();
+            options.remote_callbacks(callbacks);
+            remote.push(&[format!("{}:{}", refname, refname)], Some(&mut options))?;
+            Ok(())
+        })
+        .await
+    }
+
+    // Fetch followed by a fast-forward only; anything that needs a merge or
+    // rebase is left to the user's shell where conflicts can be resolved
+    pub async fn pull(
+        &self,
+        progress: mpsc::UnboundedSender<GitProgress>,
+    ) -> Result<PullOutcome, WarpError> {
+        self.with_repo(move |repo| {
+            let (refname, remote_name) = current_branch_remote(repo)?;
+            let mut remote = repo.find_remote(&remote_name)?;
+            let config = repo.config()?;
+            let mut options = FetchOptions::new();
+            options.remote_callbacks(remote_callbacks(&config, progress));
+            remote.fetch(&[] as &[&str], Some(&mut options), None)?;
+
+            let upstream = repo.branch_upstream_name(&refname)?;
+            let upstream = upstream.as_str().ok_or_else(|| {
+                WarpError::Validation("Upstream name is not valid UTF-8".to_string())
+            })?;
+            let target = repo.find_reference(upstream)?.peel_to_commit()?;
+            let annotated = repo.find_annotated_commit(target.id())?;
+            let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+            if analysis.is_up_to_date() {
+                return Ok(PullOutcome::UpToDate);
+            }
+            if !analysis.is_fast_forward() {
+                return Err(WarpError::Validation(i18n::t("git.diverged")));
+            }
+            repo.find_reference(&refname)?
+                .set_target(target.id(), "pull: fast-forward")?;
+            repo.checkout_head(Some(CheckoutBuilder::new().safe()))?;
+            Ok(PullOutcome::FastForwarded)
+        })
+        .await
+    }
+}
+
+fn read_status(repo: &Repository) -> Result<RepoStatus, WarpError> {
+    let mut status = RepoStatus::default();
+    if let Ok(head) = repo.head() {
+        if head.is_branch() {
+            status.branch = head.shorthand().map(str::to_string);
+            let upstream = status
+                .branch
+                .as_deref()
+                .and_then(|name| repo.find_branch(name, BranchType::Local).ok())
+                .and_then(|branch| branch.upstream().ok())
+                .and_then(|upstream| upstream.get().target());
+            if let (Some(local), Some(upstream)) = (head.target(), upstream) {
+                (status.ahead, status.behind) = repo.graph_ahead_behind(local, upstream)?;
+            }
+        } else {
+            status.branch = head
+                .target()
+                .map(|oid| oid.to_string().chars().take(7).collect());
+        }
+    }
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true);
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        if let Some(file) = entry
+            .path()
+            .and_then(|path| FileStatus::from_git(path, entry.status()))
+        {
+            status.files.push(file);
+        }
+    }
+    Ok(status)
+}
+
+// Staged diffs are HEAD -> index, unstaged are index -> working tree.
+// Untracked files are included with their content so they can be staged by hunk.
+fn file_diff<'r>(
+    repo: &'r Repository,
+    path: &str,
+    staged: bool,
+    reverse: bool,
+) -> Result<Diff<'r>, WarpError> {
+    let mut options = DiffOptions::new();
+    options
+        .pathspec(path)
+        .disable_pathspec_match(true)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true)
+        .reverse(reverse);
+    let diff = if staged {
+        let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+        repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))?
+    } else {
+        repo.diff_index_to_workdir(None, Some(&mut options))?
+    };
+    Ok(diff)
+}
+
+fn collect_hunks(diff: &Diff) -> Result<Vec<DiffHunk>, WarpError> {
+    let mut hunks = Vec::new();
+    for delta in 0..diff.deltas().len() {
+        // None for binary files
+        let Some(patch) = Patch::from_diff(diff, delta)? else {
+            continue;
+        };
+        hunks.extend(patch_hunks(&patch)?);
+    }
+    Ok(hunks)
+}
+
+// Line diff of two texts that aren't in any repository
+pub fn diff_text(old: &str, new: &str) -> Result<Vec<DiffHunk>, WarpError> {
+    let patch = Patch::from_buffers(old.as_bytes(), None, new.as_bytes(), None, None)?;
+    patch_hunks(&patch)
+}
+
+fn patch_hunks(patch: &Patch) -> Result<Vec<DiffHunk>, WarpError> {
+    let mut hunks = Vec::with_capacity(patch.num_hunks());
+    for index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(index)?;
+        let mut lines = Vec::with_capacity(line_count);
+        for line in 0..line_count {
+            let line = patch.line_in_hunk(index, line)?;
+            lines.push(DiffLine {
+                origin: line.origin(),
+                content: String::from_utf8_lossy(line.content())
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string(),
+            });
+        }
+        hunks.push(DiffHunk {
+            header: String::from_utf8_lossy(hunk.header())
+                .trim_end()
+                .to_string(),
+            lines,
+        });
+    }
+    Ok(hunks)
+}
+
+// The checked-out branch's full ref name and the remote it tracks, falling
+// back to origin for branches that have never been pushed
+fn current_branch_remote(repo: &Repository) -> Result<(String, String), WarpError> {
+    let head = repo.head()?;
+    if !head.is_branch() {
+        return Err(WarpError::Validation(i18n::t("git.detached")));
+    }
+    let refname = head
+        .name()
+        .ok_or_else(|| WarpError::Validation("Branch name is not valid UTF-8".to_string()))?
+        .to_string();
+    let remote = repo
+        .branch_upstream_remote(&refname)
+        .ok()
+        .and_then(|remote| remote.as_str().map(str::to_string))
+        .unwrap_or_else(|| "origin".to_string());
+    Ok((refname, remote))
+}
+
+// ssh-agent for SSH remotes, the user's configured credential helper for HTTPS
+fn remote_callbacks(
+    config: &git2::Config,
+    progress: mpsc::UnboundedSender<GitProgress>,
+) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut attempts = 0;
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::new(
+                git2::ErrorCode::Auth,
+                git2::ErrorClass::Net,
+                format!("Authentication failed for {}", url),
+            ));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            Cred::credential_helper(config, url, username)
+        } else {
+            Cred::default()
+        }
+    });
+
+    // Only forward whole-percent changes; libgit2 reports per object
+    let receiving = progress.clone();
+    let mut last_received = None;
+    callbacks.transfer_progress(move |stats| {
+        let percent = percent(stats.received_objects(), stats.total_objects());
+        if last_received != Some(percent) {
+            last_received = Some(percent);
+            let _ = receiving.send(GitProgress::Receiving { percent });
+        }
+        true
+    });
+    let mut last_sent = None;
+    callbacks.push_transfer_progress(move |current, total, _bytes| {
+        let percent = percent(current, total);
+        if last_sent != Some(percent) {
+            last_sent = Some(percent);
+            let _ = progress.send(GitProgress::Sending { percent });
+        }
+    });
+    callbacks
+}
+
+fn percent(done: usize, total: usize) -> u8 {
+    (done.min(total) * 100).checked_div(total).unwrap_or(100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_repo() -> (tempfile::TempDir, GitManager) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        let git = GitManager::discover(dir.path()).unwrap();
+        (dir, git)
+    }
+
+    #[test]
+    fn test_short_code() {
+        let file = FileStatus::from_git("a", Status::INDEX_MODIFIED | Status::WT_MODIFIED).unwrap();
+        assert_eq!(file.short_code(), "MM");
+        assert_eq!(
+            FileStatus::from_git("b", Status::WT_NEW)
+                .unwrap()
+                .short_code(),
+            "??"
+        );
+        assert!(FileStatus::from_git("c", Status::IGNORED).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stage_commit_roundtrip() {
+        let (dir, git) = init_repo();
+        std::fs::write(dir.path().join("notes.txt"), "one\n").unwrap();
+
+        let status = git.status().await.unwrap();
+        assert_eq!(status.files[0].short_code(), "??");
+        assert!(git.commit("empty").await.is_err());
+
+        git.stage_file("notes.txt").await.unwrap();
+        assert_eq!(git.status().await.unwrap().files[0].short_code(), "A ");
+        let id = git.commit("Add notes").await.unwrap();
+        assert_eq!(id.len(), 7);
+        assert!(git.status().await.unwrap().files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stage_single_hunk() {
+        let (dir, git) = init_repo();
+        let original: String = (1..=30).map(|n| format!("line {}\n", n)).collect();
+        std::fs::write(dir.path().join("file.txt"), &original).unwrap();
+        git.stage_file("file.txt").await.unwrap();
+        git.commit("Initial").await.unwrap();
+
+        // Two edits far enough apart to produce separate hunks
+        let edited = original
+            .replace("line 2\n", "line two\n")
+            .replace("line 28\n", "line twenty-eight\n");
+        std::fs::write(dir.path().join("file.txt"), edited).unwrap();
+        assert_eq!(git.diff("file.txt", false).await.unwrap().len(), 2);
+
+        git.apply_hunk("file.txt", 1, false).await.unwrap();
+        let staged = git.diff("file.txt", true).await.unwrap();
+        assert_eq!(staged.len(), 1);
+        assert!(staged[0]
+            .lines
+            .iter()
+            .any(|l| l.origin == '+' && l.content == "line twenty-eight"));
+        assert_eq!(git.diff("file.txt", false).await.unwrap().len(), 1);
+
+        git.apply_hunk("file.txt", 0, true).await.unwrap();
+        assert!(git.diff("file.txt", true).await.unwrap().is_empty());
+    }
+}
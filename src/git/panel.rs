@@ -0,0 +1,246 @@
+use crossterm::style::Color;
+
+use super::{highlight, BranchInfo, DiffHunk, GitManager, RepoStatus};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitView {
+    Status,
+    Diff { path: String, staged: bool },
+    Branches,
+}
+
+// What the app still has to do after a keypress. Commit needs a message from
+// the input line and push/pull run outside the panel lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitCommand {
+    None,
+    Redraw,
+    PromptCommitMessage,
+    Push,
+    Pull,
+    OpenForge,
+}
+
+pub struct GitPanel {
+    git: GitManager,
+    view: GitView,
+    status: RepoStatus,
+    // Rendered diff rows, each tagged with the hunk it belongs to so the
+    // selected row can be staged
+    diff_rows: Vec<(usize, PanelItem)>,
+    branches: Vec<BranchInfo>,
+}
+
+impl GitPanel {
+    pub fn new(git: GitManager) -> Self {
+        Self {
+            git,
+            view: GitView::Status,
+            status: RepoStatus::default(),
+            diff_rows: Vec::new(),
+            branches: Vec::new(),
+        }
+    }
+
+    pub fn git(&self) -> &GitManager {
+        &self.git
+    }
+
+    pub async fn refresh(&mut self) -> Result<(), WarpError> {
+        self.status = self.git.status().await?;
+        match &self.view {
+            GitView::Status => {}
+            GitView::Diff { path, staged } => {
+                let hunks = self.git.diff(path, *staged).await?;
+                self.diff_rows = diff_rows(path, &hunks);
+            }
+            GitView::Branches => self.branches = self.git.branches().await?,
+        }
+        Ok(())
+    }
+
+    // Keys (status): s stage · u unstage · d diff · i staged diff · c commit ·
+    // b branches · p push · f pull · m pull requests. Diff: s/u stage or unstage the selected
+    // hunk, t switch staged/unstaged. Branches: o checkout. q goes back.
+    pub async fn handle_key(&mut self, key: char, index: usize) -> Result<GitCommand, WarpError> {
+        let selected_file = self.status.files.get(index).cloned();
+        match (self.view.clone(), key) {
+            (_, 'r') => {}
+            (GitView::Status, 'c') => return Ok(GitCommand::PromptCommitMessage),
+            (GitView::Status, 'p') => return Ok(GitCommand::Push),
+            (GitView::Status, 'f') => return Ok(GitCommand::Pull),
+            (GitView::Status, 'm') => return Ok(GitCommand::OpenForge),
+            (GitView::Status, 'b') => self.view = GitView::Branches,
+            (GitView::Status, 's') => match selected_file {
+                Some(file) => self.git.stage_file(&file.path).await?,
+                None => return Ok(GitCommand::None),
+            },
+            (GitView::Status, 'u') => match selected_file {
+                Some(file) => self.git.unstage_file(&file.path).await?,
+                None => return Ok(GitCommand::None),
+            },
+            (GitView::Status, 'd' | 'i') => match selected_file {
+                Some(file) => {
+                    // A file with only staged changes has nothing to show unstaged
+                    let staged = key == 'i' || file.unstaged.is_none();
+                    self.view = GitView::Diff {
+                        path: file.path,
+                        staged,
+                    };
+                }
+                None => return Ok(GitCommand::None),
+            },
+            (GitView::Diff { path, staged }, 's' | 'u') if staged == (key == 'u') => {
+                // Row 0 is the heading
+                let row = index.checked_sub(1).and_then(|row| self.diff_rows.get(row));
+                let Some(hunk) = row.map(|(hunk, _)| *hunk) else {
+                    return Ok(GitCommand::None);
+                };
+                self.git.apply_hunk(&path, hunk, staged).await?;
+            }
+            (GitView::Diff { path, staged }, 't') => {
+                self.view = GitView::Diff {
+                    path,
+                    staged: !staged,
+                }
+            }
+            (GitView::Branches, 'o') => {
+                let Some(branch) = self.branches.get(index) else {
+                    return Ok(GitCommand::None);
+                };
+                self.git.checkout_branch(&branch.name).await?;
+                self.view = GitView::Status;
+            }
+            (GitView::Diff { .. } | GitView::Branches, 'q') => self.view = GitView::Status,
+            _ => return Ok(GitCommand::None),
+        }
+        self.refresh().await?;
+        Ok(GitCommand::Redraw)
+    }
+
+    pub fn view(&self) -> PanelView {
+        let (items, footer): (Vec<PanelItem>, &str) = match &self.view {
+            GitView::Status => {
+                let mut items: Vec<PanelItem> = self
+                    .status
+                    .files
+                    .iter()
+                    .map(|file| format!("{} {}", file.short_code(), file.path).into())
+                    .collect();
+                if items.is_empty() {
+                    items.push(i18n::t("panel.git_clean").into());
+                }
+                (items, "panel.git_status_keys")
+            }
+            GitView::Diff { path, staged } => {
+                let mut items: Vec<PanelItem> = vec![PanelItem::styled(vec![(
+                    i18n::t_args(
+                        if *staged {
+                            "panel.git_staged"
+                        } else {
+                            "panel.git_unstaged"
+                        },
+                        &[("path", path)],
+                    ),
+                    Some(Color::Yellow),
+                )])];
+                if self.diff_rows.is_empty() {
+                    items.push(i18n::t("panel.git_no_diff").into());
+                }
+                items.extend(self.diff_rows.iter().map(|(_, item)| item.clone()));
+                (items, "panel.git_diff_keys")
+            }
+            GitView::Branches => {
+                let items = self
+                    .branches
+                    .iter()
+                    .map(|branch| {
+                        let marker = if branch.is_head { "*" } else { " " };
+                        let line = match &branch.upstream {
+                            Some(upstream) => format!("{} {} → {}", marker, branch.name, upstream),
+                            None => format!("{} {}", marker, branch.name),
+                        };
+                        line.into()
+                    })
+                    .collect();
+                (items, "panel.git_branch_keys")
+            }
+        };
+        PanelView {
+            kind: PanelKind::Git,
+            title: self.title(),
+            items,
+            footer: Some(i18n::t(footer)),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+
+    fn title(&self) -> String {
+        let branch = self
+            .status
+            .branch
+            .clone()
+            .unwrap_or_else(|| "HEAD".to_string());
+        let mut title = i18n::t_args("panel.git", &[("branch", &branch)]);
+        if self.status.ahead > 0 {
+            title.push_str(&format!(" ↑{}", self.status.ahead));
+        }
+        if self.status.behind > 0 {
+            title.push_str(&format!(" ↓{}", self.status.behind));
+        }
+        title
+    }
+}
+
+fn diff_rows(path: &str, hunks: &[DiffHunk]) -> Vec<(usize, PanelItem)> {
+    let mut rows = Vec::new();
+    for (index, hunk) in hunks.iter().enumerate() {
+        rows.push((
+            index,
+            PanelItem::styled(vec![(hunk.header.clone(), Some(Color::Cyan))]),
+        ));
+        for segments in highlight::highlight_hunk(path, hunk) {
+            rows.push((index, PanelItem::styled(segments)));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::DiffLine;
+
+    fn hunk(lines: &[(char, &str)]) -> DiffHunk {
+        DiffHunk {
+            header: "@@ -1,2 +1,2 @@".to_string(),
+            lines: lines
+                .iter()
+                .map(|(origin, content)| DiffLine {
+                    origin: *origin,
+                    content: content.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_rows_map_to_hunks() {
+        let hunks = vec![
+            hunk(&[('-', "let a = 1;"), ('+', "let a = 2;")]),
+            hunk(&[('+', "fn main() {}")]),
+        ];
+        let rows = diff_rows("main.rs", &hunks);
+        let owners: Vec<usize> = rows.iter().map(|(hunk, _)| *hunk).collect();
+        assert_eq!(owners, vec![0, 0, 0, 1, 1]);
+
+        // Marker segment first, coloured by change type
+        assert_eq!(rows[2].1.segments[0], ("+".to_string(), Some(Color::Green)));
+        let text: String = rows[2].1.segments.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(text, "+let a = 2;");
+    }
+}
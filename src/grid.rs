@@ -0,0 +1,250 @@
+use crossterm::style::{Attribute, Attributes, Color};
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub attributes: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+// A horizontal run of changed cells on one row, [x_start, x_end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DamageRegion {
+    pub y: u16,
+    pub x_start: u16,
+    pub x_end: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+// Runs separated by fewer unchanged cells than this are merged; one longer
+// Print is cheaper than an extra cursor move escape sequence
+const MERGE_GAP: u16 = 4;
+
+impl Grid {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn get(&self, x: u16, y: u16) -> Option<&Cell> {
+        if x < self.width && y < self.height {
+            self.cells
+                .get(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        if x < self.width && y < self.height {
+            let index = y as usize * self.width as usize + x as usize;
+            self.cells[index] = cell;
+        }
+    }
+
+    // Writes text starting at (x, y), clipping at the right edge. Wide
+    // characters occupy two cells; the second is left as a NUL placeholder
+    pub fn put_str(&mut self, x: u16, y: u16, text: &str, fg: Color, bg: Color, bold: bool) -> u16 {
+        let attributes = match bold {
+            true => Attribute::Bold.into(),
+            false => Attributes::default(),
+        };
+        let mut col = x;
+        for ch in text.chars() {
+            let width = ch.width().unwrap_or(0) as u16;
+            if width == 0 {
+                continue;
+            }
+            if col + width > self.width {
+                break;
+            }
+            self.set(
+                col,
+                y,
+                Cell {
+                    ch,
+                    fg,
+                    bg,
+                    attributes,
+                },
+            );
+            if width == 2 {
+                self.set(
+                    col + 1,
+                    y,
+                    Cell {
+                        ch: '\0',
+                        fg,
+                        bg,
+                        attributes,
+                    },
+                );
+            }
+            col += width;
+        }
+        col - x
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+    }
+
+    pub fn row(&self, y: u16) -> &[Cell] {
+        let start = y as usize * self.width as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+
+    pub fn full_damage(&self) -> Vec<DamageRegion> {
+        (0..self.height)
+            .map(|y| DamageRegion {
+                y,
+                x_start: 0,
+                x_end: self.width,
+            })
+            .collect()
+    }
+
+    // Computes which cells of `self` differ from `previous`. A size change
+    // invalidates everything.
+    pub fn diff(&self, previous: &Grid) -> Vec<DamageRegion> {
+        if self.width != previous.width || self.height != previous.height {
+            return self.full_damage();
+        }
+
+        let mut regions = Vec::new();
+        for y in 0..self.height {
+            let current = self.row(y);
+            let before = previous.row(y);
+            if current == before {
+                continue;
+            }
+
+            let mut run: Option<DamageRegion> = None;
+            for x in 0..self.width {
+                if current[x as usize] == before[x as usize] {
+                    continue;
+                }
+                match run.as_mut() {
+                    Some(r) if x - r.x_end < MERGE_GAP => r.x_end = x + 1,
+                    Some(r) => {
+                        regions.push(*r);
+                        run = Some(DamageRegion {
+                            y,
+                            x_start: x,
+                            x_end: x + 1,
+                        });
+                    }
+                    None => {
+                        run = Some(DamageRegion {
+                            y,
+                            x_start: x,
+                            x_end: x + 1,
+                        })
+                    }
+                }
+            }
+            if let Some(r) = run {
+                regions.push(r);
+            }
+        }
+        regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_grids_have_no_damage() {
+        let a = Grid::new(10, 3);
+        let b = a.clone();
+        assert!(b.diff(&a).is_empty());
+    }
+
+    #[test]
+    fn test_single_change_is_one_cell_region() {
+        let previous = Grid::new(10, 3);
+        let mut next = previous.clone();
+        next.put_str(4, 1, "x", Color::White, Color::Reset, false);
+        assert_eq!(
+            next.diff(&previous),
+            vec![DamageRegion {
+                y: 1,
+                x_start: 4,
+                x_end: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_nearby_changes_are_merged() {
+        let previous = Grid::new(20, 1);
+        let mut next = previous.clone();
+        next.put_str(0, 0, "a", Color::White, Color::Reset, false);
+        next.put_str(2, 0, "b", Color::White, Color::Reset, false);
+        next.put_str(15, 0, "c", Color::White, Color::Reset, false);
+        assert_eq!(
+            next.diff(&previous),
+            vec![
+                DamageRegion {
+                    y: 0,
+                    x_start: 0,
+                    x_end: 3
+                },
+                DamageRegion {
+                    y: 0,
+                    x_start: 15,
+                    x_end: 16
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resize_is_full_damage() {
+        let previous = Grid::new(10, 2);
+        let next = Grid::new(12, 2);
+        assert_eq!(next.diff(&previous).len(), 2);
+    }
+
+    #[test]
+    fn test_wide_characters_take_two_cells() {
+        let mut grid = Grid::new(4, 1);
+        assert_eq!(
+            grid.put_str(0, 0, "日本語", Color::White, Color::Reset, false),
+            4
+        );
+        assert_eq!(grid.get(1, 0).unwrap().ch, '\0');
+    }
+}
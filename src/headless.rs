@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+
+use crate::{error::WarpError, pty::PtyManager, shell::PaneSpec};
+
+// Exit status reported when --timeout kills the command, as timeout(1) does
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub block_id: String,
+    pub command: String,
+    pub shell: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>, // None when killed by a signal or the timeout
+    pub duration_ms: u64,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub timed_out: bool,
+}
+
+impl RunResult {
+    pub fn process_exit_code(&self) -> i32 {
+        match self.exit_code {
+            Some(code) => code,
+            None if self.timed_out => TIMEOUT_EXIT_CODE,
+            None => 1,
+        }
+    }
+}
+
+// Runs `command` in a fresh shell started through the PTY manager, like a
+// pane's shell, and collects its output once the shell exits. Stdin is
+// closed after the command so the shell exits with its status; shells that
+// don't read a script from stdin get it as an argument instead.
+pub async fn run(
+    shell: &str,
+    command: &str,
+    timeout: Option<Duration>,
+) -> Result<RunResult, WarpError> {
+    let mut pty = PtyManager::new().await?;
+    let mut spec = PaneSpec::plain(shell);
+    let script_args = spec.kind().script_args(command);
+    if let Some(args) = &script_args {
+        spec.args = args.clone();
+    }
+    let process_id = pty.spawn_pane(&spec).await?;
+    let mut stderr_pipe = pty.take_stderr(process_id).await;
+
+    let block_id = uuid::Uuid::new_v4().to_string();
+    let started_at = chrono::Utc::now();
+    let started = Instant::now();
+
+    if script_args.is_none() {
+        pty.write_input(&format!("{}\n", command)).await?;
+    }
+    pty.close_input(process_id).await;
+
+    // Collected outside the future so a timeout keeps what was produced
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let collect = async {
+        let read_stdout = async {
+            loop {
+                let chunk = pty.read_output_bytes().await?;
+                if chunk.is_empty() {
+                    return Ok::<_, WarpError>(());
+                }
+                stdout.extend_from_slice(&chunk);
+            }
+        };
+        let read_stderr = async {
+            if let Some(pipe) = stderr_pipe.as_mut() {
+                let mut chunk = [0; 4096];
+                loop {
+                    let n = pipe.read(&mut chunk).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    stderr.extend_from_slice(&chunk[..n]);
+                }
+            }
+            Ok::<_, WarpError>(())
+        };
+        tokio::try_join!(read_stdout, read_stderr)?;
+        pty.wait(process_id).await
+    };
+
+    let (exit_code, timed_out) = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, collect).await {
+            Ok(code) => (code?, false),
+            Err(_) => {
+                pty.kill_process(process_id).await?;
+                (None, true)
+            }
+        },
+        None => (collect.await?, false),
+    };
+
+    Ok(RunResult {
+        block_id,
+        command: command.to_string(),
+        shell: shell.to_string(),
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+        exit_code,
+        duration_ms: started.elapsed().as_millis() as u64,
+        started_at,
+        timed_out,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_collects_streams_and_status() {
+        let result = run("sh", "echo out; echo err >&2; exit 3", None)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err\n");
+        assert_eq!(result.exit_code, Some(3));
+        assert!(!result.timed_out);
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout_kills_the_shell() {
+        let result = run(
+            "sh",
+            "echo started; sleep 5",
+            Some(Duration::from_millis(300)),
+        )
+        .await
+        .unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.process_exit_code(), TIMEOUT_EXIT_CODE);
+        assert_eq!(result.stdout, "started\n");
+    }
+}
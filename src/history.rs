@@ -1,23 +1,81 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::{config::Config, error::WarpError};
+use crate::{block_tags::BlockTags, config::Config, error::WarpError, shutdown};
 
 pub struct HistoryManager {
     config: Arc<Mutex<Config>>,
     commands: Vec<String>,
+    history_path: Option<PathBuf>,
+    dirty: bool,
+    // Tags, pins and favorites on blocks, saved beside the history
+    tags: BlockTags,
 }
 
 impl HistoryManager {
     pub async fn new(config: Arc<Mutex<Config>>) -> Result<Self, WarpError> {
+        let history_path = dirs::data_dir().map(|dir| dir.join("warp").join("history"));
+        let commands = match &history_path {
+            Some(path) => match tokio::fs::read_to_string(path).await {
+                Ok(content) => content.lines().map(str::to_string).collect(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e.into()),
+            },
+            None => Vec::new(),
+        };
+        let tags = match &history_path {
+            Some(path) => BlockTags::load(&path.with_file_name("block_tags.json")).await?,
+            None => BlockTags::default(),
+        };
+
         Ok(Self {
             config,
-            commands: Vec::new(),
+            commands,
+            history_path,
+            dirty: false,
+            tags,
         })
     }
 
     pub async fn add_command(&mut self, command: String) -> Result<(), WarpError> {
         self.commands.push(command);
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    pub fn tags(&self) -> &BlockTags {
+        &self.tags
+    }
+
+    pub fn tags_mut(&mut self) -> &mut BlockTags {
+        &mut self.tags
+    }
+
+    pub async fn flush(&mut self) -> Result<(), WarpError> {
+        let Some(path) = &self.history_path else {
+            return Ok(());
+        };
+        if self.tags.is_dirty() {
+            shutdown::write_atomic(
+                &path.with_file_name("block_tags.json"),
+                &self.tags.to_bytes()?,
+            )
+            .await?;
+            self.tags.mark_saved();
+        }
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut content = self.commands.join("\n");
+        content.push('\n');
+        shutdown::write_atomic(path, content.as_bytes()).await?;
+        self.dirty = false;
         Ok(())
     }
 }
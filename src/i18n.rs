@@ -0,0 +1,247 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::error::WarpError;
+
+// Catalogs are compiled in so a broken install can never leave the UI without strings
+const BUILTIN_LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.toml")),
+    ("de", include_str!("../locales/de.toml")),
+    ("ja", include_str!("../locales/ja.toml")),
+    ("zh", include_str!("../locales/zh.toml")),
+];
+
+const FALLBACK_LOCALE: &str = "en";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CatalogFile {
+    #[serde(default)]
+    messages: HashMap<String, String>,
+    #[serde(default)]
+    plurals: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PluralCategory {
+    One,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    locale: String,
+    primary: CatalogFile,
+    fallback: CatalogFile,
+}
+
+static CATALOG: OnceLock<RwLock<Catalog>> = OnceLock::new();
+
+impl Catalog {
+    pub fn load(locale: &str) -> Result<Self, WarpError> {
+        let locale = normalize_locale(locale);
+        let fallback = parse_builtin(FALLBACK_LOCALE)?;
+        let primary = if BUILTIN_LOCALES.iter().any(|(code, _)| *code == locale) {
+            parse_builtin(&locale)?
+        } else {
+            log::warn!("No message catalog for locale '{}', using English", locale);
+            fallback.clone()
+        };
+
+        Ok(Self {
+            locale,
+            primary,
+            fallback,
+        })
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn message(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let template = self
+            .primary
+            .messages
+            .get(key)
+            .or_else(|| self.fallback.messages.get(key))
+            .map(|s| s.as_str())
+            .unwrap_or(key);
+        format_message(template, args)
+    }
+
+    pub fn plural(&self, key: &str, count: i64, args: &[(&str, &str)]) -> String {
+        let count_str = count.to_string();
+        let mut all_args: Vec<(&str, &str)> = vec![("count", count_str.as_str())];
+        all_args.extend_from_slice(args);
+
+        let category = plural_category(&self.locale, count);
+        let forms = self
+            .primary
+            .plurals
+            .get(key)
+            .or_else(|| self.fallback.plurals.get(key));
+
+        let template = forms.and_then(|forms| {
+            let wanted = match category {
+                PluralCategory::One => "one",
+                PluralCategory::Other => "other",
+            };
+            forms.get(wanted).or_else(|| forms.get("other"))
+        });
+
+        match template {
+            Some(template) => format_message(template, &all_args),
+            None => key.to_string(),
+        }
+    }
+}
+
+pub fn available_locales() -> Vec<&'static str> {
+    BUILTIN_LOCALES.iter().map(|(code, _)| *code).collect()
+}
+
+// Config wins; otherwise follow POSIX precedence LC_ALL > LC_MESSAGES > LANG
+pub fn resolve_locale(configured: Option<&str>) -> String {
+    if let Some(locale) = configured.filter(|l| !l.is_empty()) {
+        return normalize_locale(locale);
+    }
+
+    for var in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(value) = std::env::var(var) {
+            if !value.is_empty() && value != "C" && value != "POSIX" {
+                return normalize_locale(&value);
+            }
+        }
+    }
+
+    FALLBACK_LOCALE.to_string()
+}
+
+pub fn init(configured: Option<&str>) -> Result<(), WarpError> {
+    let catalog = Catalog::load(&resolve_locale(configured))?;
+    match CATALOG.get() {
+        Some(lock) => {
+            if let Ok(mut current) = lock.write() {
+                *current = catalog;
+            }
+        }
+        None => {
+            let _ = CATALOG.set(RwLock::new(catalog));
+        }
+    }
+    Ok(())
+}
+
+pub fn t(key: &str) -> String {
+    t_args(key, &[])
+}
+
+pub fn t_args(key: &str, args: &[(&str, &str)]) -> String {
+    with_catalog(|catalog| catalog.message(key, args))
+}
+
+pub fn t_plural(key: &str, count: i64) -> String {
+    with_catalog(|catalog| catalog.plural(key, count, &[]))
+}
+
+fn with_catalog<F: FnOnce(&Catalog) -> String>(f: F) -> String {
+    // Lazily fall back to English when called before init (e.g. from early error paths)
+    let lock = CATALOG.get_or_init(|| {
+        RwLock::new(Catalog::load(FALLBACK_LOCALE).expect("built-in English catalog must parse"))
+    });
+    match lock.read() {
+        Ok(catalog) => f(&catalog),
+        Err(poisoned) => f(&poisoned.into_inner()),
+    }
+}
+
+fn parse_builtin(locale: &str) -> Result<CatalogFile, WarpError> {
+    let source = BUILTIN_LOCALES
+        .iter()
+        .find(|(code, _)| *code == locale)
+        .map(|(_, source)| *source)
+        .ok_or_else(|| WarpError::Config(format!("Unknown locale: {}", locale)))?;
+
+    toml::from_str(source)
+        .map_err(|e| WarpError::Config(format!("Failed to parse {} catalog: {}", locale, e)))
+}
+
+fn normalize_locale(raw: &str) -> String {
+    // "de_DE.UTF-8@euro" -> "de"
+    raw.split(['.', '@'])
+        .next()
+        .unwrap_or(raw)
+        .split(['_', '-'])
+        .next()
+        .unwrap_or(raw)
+        .to_lowercase()
+}
+
+fn plural_category(locale: &str, count: i64) -> PluralCategory {
+    match locale {
+        // CJK languages do not inflect for number
+        "ja" | "zh" | "ko" => PluralCategory::Other,
+        _ if count == 1 => PluralCategory::One,
+        _ => PluralCategory::Other,
+    }
+}
+
+fn format_message(template: &str, args: &[(&str, &str)]) -> String {
+    let mut result = template.to_string();
+    for (name, value) in args {
+        result = result.replace(&format!("{{{}}}", name), value);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_normalization() {
+        assert_eq!(normalize_locale("de_DE.UTF-8"), "de");
+        assert_eq!(normalize_locale("zh-Hans"), "zh");
+        assert_eq!(normalize_locale("ja"), "ja");
+    }
+
+    #[test]
+    fn test_all_catalogs_parse_and_cover_english_keys() {
+        let english = parse_builtin("en").unwrap();
+        for locale in available_locales() {
+            let catalog = parse_builtin(locale).unwrap();
+            for key in english.messages.keys() {
+                assert!(
+                    catalog.messages.contains_key(key),
+                    "{} is missing {}",
+                    locale,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_plural_forms() {
+        let en = Catalog::load("en").unwrap();
+        assert_eq!(en.plural("a11y.new_output", 1, &[]), "1 new line of output");
+        assert_eq!(
+            en.plural("a11y.new_output", 3, &[]),
+            "3 new lines of output"
+        );
+
+        let ja = Catalog::load("ja").unwrap();
+        assert_eq!(
+            ja.plural("a11y.new_output", 1, &[]),
+            "新しい出力が 1 行あります"
+        );
+    }
+
+    #[test]
+    fn test_missing_key_falls_back_to_english() {
+        let fr = Catalog::load("fr").unwrap();
+        assert_eq!(fr.locale(), "fr");
+        assert_eq!(fr.message("ui.output", &[]), "Output");
+    }
+}
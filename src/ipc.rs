@@ -0,0 +1,545 @@
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{
+    config::{IpcConfig, IpcScope, PolicyAction},
+    error::WarpError,
+    i18n,
+    pty::PtyManager,
+    security::SecurityManager,
+    shell::{PaneRequest, ShellManager},
+    shutdown::ShutdownSignal,
+    ui::{AppAction, PromptHint, UIEvent, UI},
+};
+
+// Methods and the scope each needs; None means any authenticated client
+const METHODS: &[(&str, Option<IpcScope>)] = &[
+    ("ping", None),
+    ("run_command", Some(IpcScope::Run)),
+    ("open_tab", Some(IpcScope::Run)),
+    ("get_block", Some(IpcScope::Read)),
+    ("list_actions", Some(IpcScope::Ui)),
+    ("trigger_action", Some(IpcScope::Ui)),
+];
+
+// One JSON object per line in each direction
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    #[serde(default)]
+    pub token: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RpcError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+// The token clients must present and what it allows
+pub struct IpcAuth {
+    token: String,
+    scopes: Vec<IpcScope>,
+}
+
+impl IpcAuth {
+    pub fn new(token: String, scopes: Vec<IpcScope>) -> Self {
+        Self { token, scopes }
+    }
+
+    pub fn check(&self, request: &Request) -> Result<(), RpcError> {
+        if !same_token(request.token.as_bytes(), self.token.as_bytes()) {
+            return Err(RpcError::new("unauthorized", "Missing or wrong token"));
+        }
+        let Some((_, scope)) = METHODS.iter().find(|(name, _)| *name == request.method) else {
+            return Err(RpcError::new(
+                "unknown_method",
+                format!("No method '{}'", request.method),
+            ));
+        };
+        match scope {
+            Some(scope) if !self.scopes.contains(scope) => Err(RpcError::new(
+                "forbidden",
+                format!("'{}' needs the {:?} scope", request.method, scope),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Compares every byte whatever the first mismatch, so timing doesn't give
+// the token away
+fn same_token(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// What requests act on: the same UI, shells and policy as the key bindings
+pub struct IpcContext {
+    pub ui: Arc<Mutex<UI>>,
+    pub pty_manager: Arc<Mutex<PtyManager>>,
+    pub event_sender: mpsc::UnboundedSender<UIEvent>,
+    pub security: Arc<SecurityManager>,
+    pub shell_manager: Arc<Mutex<ShellManager>>,
+}
+
+pub async fn handle_line(auth: &IpcAuth, context: &IpcContext, line: &str) -> Response {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Response {
+                id: Value::Null,
+                result: None,
+                error: Some(RpcError::new("parse_error", e.to_string())),
+            }
+        }
+    };
+    let outcome = match auth.check(&request) {
+        Ok(()) => dispatch(context, &request.method, &request.params).await,
+        Err(e) => Err(e),
+    };
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+    Response {
+        id: request.id,
+        result,
+        error,
+    }
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params.get(name).and_then(Value::as_str).ok_or_else(|| {
+        RpcError::new(
+            "invalid_params",
+            format!("Missing string parameter '{}'", name),
+        )
+    })
+}
+
+async fn dispatch(context: &IpcContext, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "ping" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+        // Goes through the command policy like the terminal's own
+        // suggestions; commands that need confirmation wait for the user
+        "run_command" => {
+            let command = str_param(params, "command")?;
+            let decision = context.security.check_command(command);
+            let mut ui = context.ui.lock().await;
+            match decision.action {
+                PolicyAction::Deny => Err(RpcError::new(
+                    "denied",
+                    decision
+                        .reason
+                        .unwrap_or_else(|| "Blocked by the command policy".to_string()),
+                )),
+                PolicyAction::Confirm => {
+                    ui.set_hint(Some(PromptHint {
+                        message: i18n::t_args("ipc.confirm_run", &[("command", command)]),
+                        command: Some(command.to_string()),
+                        confirm: true,
+                    }));
+                    Ok(json!({ "status": "needs_confirmation" }))
+                }
+                PolicyAction::Allow => {
+                    ui.run_command(command.to_string());
+                    Ok(json!({ "status": "started" }))
+                }
+            }
+        }
+        // Optional shell, profile and cwd; the rest comes from the project's
+        // defaults and the terminal config
+        "open_tab" => {
+            let cwd = match params.get("cwd").and_then(Value::as_str) {
+                Some(cwd) => PathBuf::from(cwd),
+                None => {
+                    std::env::current_dir().map_err(|e| RpcError::new("internal", e.to_string()))?
+                }
+            };
+            let mut request = PaneRequest::new(cwd);
+            request.shell = params
+                .get("shell")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            request.profile = params
+                .get("profile")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let mut shells = context.shell_manager.lock().await;
+            let spec = shells
+                .resolve(&request)
+                .await
+                .map_err(|e| RpcError::new("invalid_params", e.to_string()))?;
+            let tab = context
+                .pty_manager
+                .lock()
+                .await
+                .spawn_pane(&spec)
+                .await
+                .map_err(|e| RpcError::new("internal", e.to_string()))?;
+            shells.opened(&spec);
+            context.ui.lock().await.set_shell_label(Some(spec.title()));
+            Ok(json!({ "tab": tab, "title": spec.title() }))
+        }
+        "get_block" => Ok(json!({ "lines": context.ui.lock().await.current_block() })),
+        "list_actions" => Ok(json!(AppAction::ALL
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>())),
+        "trigger_action" => {
+            let name = str_param(params, "action")?;
+            let action = AppAction::from_name(name)
+                .ok_or_else(|| RpcError::new("invalid_params", format!("No action '{}'", name)))?;
+            context
+                .event_sender
+                .send(UIEvent::Action(action))
+                .map_err(|_| RpcError::new("internal", "The terminal is shutting down"))?;
+            Ok(json!({ "status": "queued" }))
+        }
+        _ => Err(RpcError::new(
+            "unknown_method",
+            format!("No method '{}'", method),
+        )),
+    }
+}
+
+fn generate_token() -> Result<String, WarpError> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| WarpError::config_err("Could not generate an IPC token"))?;
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
+
+// Without a runtime dir (macOS, minimal containers) the socket goes in a
+// per-user directory under the temp dir rather than a shared one
+#[cfg(unix)]
+fn default_socket_path() -> PathBuf {
+    let dir = match dirs::runtime_dir() {
+        Some(dir) => dir.join("warp"),
+        None => std::env::temp_dir().join(format!("warp-{}", current_uid())),
+    };
+    dir.join(format!("ipc-{}.sock", std::process::id()))
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
+}
+
+// The socket's directory is created 0700. One that already exists must be
+// ours and not writable by anyone else, or the socket and token could be
+// swapped for someone else's.
+#[cfg(unix)]
+fn private_dir(dir: &std::path::Path) -> Result<(), WarpError> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if let Err(e) = std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(e.into());
+        }
+    }
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o022 != 0 {
+        return Err(WarpError::config_err(format!(
+            "{} must be a directory owned and only writable by the current user",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+// create_new refuses an existing file or symlink, so the token can't be
+// written through a link planted in its place
+#[cfg(unix)]
+fn write_token(path: &std::path::Path, token: &str) -> Result<(), WarpError> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(token.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn restrict_socket(path: &std::path::Path) -> Result<(), WarpError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn remove_stale(path: &std::path::Path) -> Result<(), WarpError> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// Local socket for editors and scripts. The token is written next to the
+// socket, readable only by the user; clients read it from there.
+#[cfg(unix)]
+pub struct IpcServer {
+    listener: tokio::net::UnixListener,
+    socket_path: PathBuf,
+    token_path: PathBuf,
+    auth: Arc<IpcAuth>,
+}
+
+#[cfg(unix)]
+impl IpcServer {
+    pub async fn bind(config: &IpcConfig) -> Result<Self, WarpError> {
+        use crate::error::ResultExt;
+
+        let socket_path = config
+            .socket_path
+            .clone()
+            .unwrap_or_else(default_socket_path);
+        if let Some(dir) = socket_path.parent() {
+            private_dir(dir)?;
+        }
+        let token_path = socket_path.with_extension("token");
+        // Left behind by a crashed instance
+        remove_stale(&socket_path)?;
+        remove_stale(&token_path)?;
+
+        // The directory is already 0700, so nobody else can reach the socket
+        // in the moment before it's narrowed to 0600
+        let listener = tokio::net::UnixListener::bind(&socket_path)
+            .with_context(|| format!("Failed to listen on {}", socket_path.display()))?;
+        restrict_socket(&socket_path)?;
+
+        let token = generate_token()?;
+        write_token(&token_path, &token)?;
+
+        Ok(Self {
+            listener,
+            socket_path,
+            token_path,
+            auth: Arc::new(IpcAuth::new(token, config.scopes.clone())),
+        })
+    }
+
+    pub fn socket_path(&self) -> &std::path::Path {
+        &self.socket_path
+    }
+
+    pub async fn serve(self, context: Arc<IpcContext>, mut signal: ShutdownSignal) {
+        loop {
+            let stream = tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log::warn!("IPC accept failed: {}", e);
+                        continue;
+                    }
+                },
+                _ = signal.triggered() => break,
+            };
+            let auth = self.auth.clone();
+            let context = context.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve_connection(stream, &auth, &context).await {
+                    log::debug!("IPC connection closed: {}", e);
+                }
+            });
+        }
+        let _ = tokio::fs::remove_file(&self.socket_path).await;
+        let _ = tokio::fs::remove_file(&self.token_path).await;
+    }
+}
+
+#[cfg(unix)]
+async fn serve_connection(
+    stream: tokio::net::UnixStream,
+    auth: &IpcAuth,
+    context: &IpcContext,
+) -> Result<(), WarpError> {
+    use tokio::io::{AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    while let Some(line) = read_line(&mut reader).await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_line(auth, context, &line).await;
+        let mut out = serde_json::to_string(&response)?;
+        out.push('\n');
+        writer.write_all(out.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+// Requests are read before the token is checked, so a line is capped rather
+// than letting an unauthenticated client grow it without bound
+const MAX_LINE_BYTES: u64 = 1024 * 1024;
+
+async fn read_line<R>(reader: &mut R) -> Result<Option<String>, WarpError>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(MAX_LINE_BYTES + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if read as u64 > MAX_LINE_BYTES {
+        return Err(WarpError::Validation(format!(
+            "IPC request exceeds {} bytes",
+            MAX_LINE_BYTES
+        )));
+    }
+    Ok(Some(String::from_utf8(line)?))
+}
+
+#[cfg(not(unix))]
+pub struct IpcServer;
+
+#[cfg(not(unix))]
+impl IpcServer {
+    pub async fn bind(_config: &IpcConfig) -> Result<Self, WarpError> {
+        Err(WarpError::config_err(
+            "IPC is only available over Unix domain sockets",
+        ))
+    }
+
+    pub fn socket_path(&self) -> &std::path::Path {
+        std::path::Path::new("")
+    }
+
+    pub async fn serve(self, _context: Arc<IpcContext>, _signal: ShutdownSignal) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(token: &str, method: &str) -> Request {
+        serde_json::from_value(json!({ "id": 1, "token": token, "method": method })).unwrap()
+    }
+
+    #[test]
+    fn test_auth_checks_token_and_scope() {
+        let auth = IpcAuth::new("secret".to_string(), vec![IpcScope::Read]);
+        assert!(auth.check(&request("secret", "get_block")).is_ok());
+        assert!(auth.check(&request("secret", "ping")).is_ok());
+        assert_eq!(
+            auth.check(&request("wrong", "ping")).unwrap_err().code,
+            "unauthorized"
+        );
+        assert_eq!(
+            auth.check(&request("", "ping")).unwrap_err().code,
+            "unauthorized"
+        );
+        assert_eq!(
+            auth.check(&request("secret", "run_command"))
+                .unwrap_err()
+                .code,
+            "forbidden"
+        );
+        assert_eq!(
+            auth.check(&request("secret", "rm_rf")).unwrap_err().code,
+            "unknown_method"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_dir_and_token_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("warp");
+        private_dir(&dir).unwrap();
+        assert_eq!(
+            std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+
+        let token = dir.join("ipc.token");
+        write_token(&token, "secret").unwrap();
+        assert_eq!(
+            std::fs::metadata(&token).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(write_token(&token, "other").is_err());
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(private_dir(&dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_lines_are_capped() {
+        let mut input: &[u8] = b"first\nsecond";
+        assert_eq!(
+            read_line(&mut input).await.unwrap().as_deref(),
+            Some("first")
+        );
+        assert_eq!(
+            read_line(&mut input).await.unwrap().as_deref(),
+            Some("second")
+        );
+        assert!(read_line(&mut input).await.unwrap().is_none());
+
+        let long = vec![b'a'; MAX_LINE_BYTES as usize + 10];
+        let mut input: &[u8] = &long;
+        assert!(read_line(&mut input).await.is_err());
+    }
+
+    #[test]
+    fn test_tokens_are_unique() {
+        let a = generate_token().unwrap();
+        let b = generate_token().unwrap();
+        assert_eq!(a.len(), 43);
+        assert_ne!(a, b);
+    }
+}
@@ -83,7 +83,7 @@ impl KeySetManager {
     async fn load_keyset_file(&self, path: &PathBuf) -> Result<KeySet, WarpError> {
         let content = fs::read_to_string(path).await?;
         let keyset: KeySet = serde_yaml::from_str(&content)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to parse keyset: {}", e)))?;
+            .map_err(|e| WarpError::Config(format!("Failed to parse keyset: {}", e)))?;
         Ok(keyset)
     }
 
@@ -100,7 +100,7 @@ impl KeySetManager {
             self.current_keyset = name;
             Ok(())
         } else {
-            Err(WarpError::ConfigError(format!("Keyset '{}' not found", name)))
+            Err(WarpError::not_found("keyset", name))
         }
     }
 
@@ -1,18 +1,93 @@
+pub mod ab_testing;
+pub mod accessibility;
+pub mod activity;
+pub mod alerts;
+pub mod analytics;
+pub mod api;
 pub mod app;
+pub mod audit;
+pub mod bell;
+pub mod block_tags;
+pub mod calc;
+pub mod cassette;
+pub mod cicd;
+pub mod cloud;
+pub mod collaboration;
+pub mod command_not_found;
 pub mod completion;
+pub mod completion_import;
+pub mod compliance;
+pub mod config;
+pub mod connections;
+pub mod container;
+pub mod correction;
+pub mod custom_commands;
+pub mod custom_metrics;
+pub mod dev_tools;
+pub mod diff_view;
+pub mod download;
+pub mod editor;
+pub mod encoding;
 pub mod error;
+pub mod escape_filter;
+pub mod export;
+pub mod file_manager;
+pub mod forge;
+pub mod git;
+pub mod grid;
+pub mod headless;
 pub mod history;
+pub mod i18n;
+pub mod ipc;
+pub mod lint;
+pub mod locations;
 pub mod logger;
+pub mod marketplace;
+pub mod memory;
+pub mod ml_insights;
 pub mod multiplexer;
 pub mod network;
+pub mod network_inspector;
+pub mod notebook;
+pub mod notifications;
+pub mod oauth;
+pub mod output_buffer;
+pub mod package;
+pub mod pager;
+pub mod pane_tuning;
+pub mod paste;
 pub mod performance;
+pub mod permalinks;
+pub mod plugin_activity;
+pub mod plugin_host;
+pub mod plugin_permissions;
 pub mod plugins;
+pub mod ports;
+pub mod problems;
+pub mod process_tree;
+pub mod project_tasks;
 pub mod pty;
+pub mod render_bench;
+pub mod replay;
+pub mod runbook;
 pub mod search;
+pub mod secrets;
 pub mod security;
+pub mod settings;
 pub mod shell;
+pub mod shutdown;
+pub mod ssh;
+pub mod startup;
+pub mod status_bar;
+pub mod structured;
 pub mod terminal;
+pub mod transforms;
 pub mod ui;
+pub mod utf8;
+pub mod visualization;
+pub mod watchdog;
+pub mod widgets;
+pub mod workspace_trust;
 
 pub mod modules {
     pub mod ai;
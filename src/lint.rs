@@ -0,0 +1,409 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::completion::{CompletionSpec, SpecRegistry};
+use crate::i18n;
+
+// Commands whose operands must already exist
+const PATH_OPERANDS: &[&str] = &[
+    "cat", "cd", "head", "less", "more", "rm", "rmdir", "source", ".", "tail", "wc",
+];
+// The last operand is a destination that may not exist yet
+const SOURCE_OPERANDS: &[&str] = &["cp", "mv"];
+// Prefixes that run the rest of the line as a command, with the options
+// of theirs that take a value
+const WRAPPERS: &[(&str, &[&str])] = &[
+    (
+        "sudo",
+        &["-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-U"],
+    ),
+    ("env", &["-u", "-C", "-S"]),
+    ("nice", &["-n"]),
+    ("nohup", &[]),
+    ("time", &[]),
+    ("command", &[]),
+    ("exec", &[]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn warning(message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Word {
+    text: String,
+    quoted: bool,
+    // Unquoted *, ? or [ that the shell will expand
+    glob: bool,
+    // $var, $(...) or `...`; the final text isn't known until the shell runs it
+    expansion: bool,
+}
+
+// Splits a command line into simple commands at ; | & ( ), dropping
+// redirections and comments. Returns the unclosed quote, if any.
+fn tokenize(input: &str) -> (Vec<Vec<Word>>, Option<char>) {
+    let mut commands = vec![Vec::new()];
+    let mut word: Option<Word> = None;
+    let mut skip_next_word = false;
+    let mut chars = input.chars().peekable();
+
+    fn finish(word: &mut Option<Word>, skip: &mut bool, commands: &mut [Vec<Word>]) {
+        if let Some(word) = word.take() {
+            if std::mem::take(skip) {
+                return;
+            }
+            commands
+                .last_mut()
+                .expect("at least one command")
+                .push(word);
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => finish(&mut word, &mut skip_next_word, &mut commands),
+            ';' | '|' | '&' | '(' | ')' => {
+                finish(&mut word, &mut skip_next_word, &mut commands);
+                if !commands.last().is_none_or(Vec::is_empty) {
+                    commands.push(Vec::new());
+                }
+            }
+            '<' | '>' => {
+                // `2>file`: the fd number belongs to the redirection
+                if word
+                    .as_ref()
+                    .is_some_and(|w| !w.quoted && w.text.chars().all(|c| c.is_ascii_digit()))
+                {
+                    word = None;
+                }
+                finish(&mut word, &mut skip_next_word, &mut commands);
+                while chars.next_if(|c| matches!(c, '<' | '>' | '&')).is_some() {}
+                skip_next_word = true;
+            }
+            '#' if word.is_none() => break,
+            '\'' => {
+                let w = word.get_or_insert_with(Word::default);
+                w.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => w.text.push(c),
+                        None => return (commands, Some('\'')),
+                    }
+                }
+            }
+            '"' => {
+                let w = word.get_or_insert_with(Word::default);
+                w.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => w.text.extend(chars.next()),
+                        Some(c) => {
+                            w.expansion |= c == '$' || c == '`';
+                            w.text.push(c);
+                        }
+                        None => return (commands, Some('"')),
+                    }
+                }
+            }
+            '\\' => word
+                .get_or_insert_with(Word::default)
+                .text
+                .extend(chars.next()),
+            c => {
+                let w = word.get_or_insert_with(Word::default);
+                w.glob |= matches!(c, '*' | '?' | '[');
+                w.expansion |= matches!(c, '$' | '`');
+                w.text.push(c);
+            }
+        }
+    }
+    finish(&mut word, &mut skip_next_word, &mut commands);
+    commands.retain(|command| !command.is_empty());
+    (commands, None)
+}
+
+// Cheap checks run while the command is typed; nothing here executes or
+// blocks the command
+pub struct CommandLinter {
+    specs: Arc<SpecRegistry>,
+}
+
+impl CommandLinter {
+    pub fn new(specs: Arc<SpecRegistry>) -> Self {
+        Self { specs }
+    }
+
+    pub fn lint(&self, input: &str, cwd: &Path) -> Vec<Diagnostic> {
+        let (commands, unclosed) = tokenize(input);
+        let mut diagnostics = Vec::new();
+        if let Some(quote) = unclosed {
+            diagnostics.push(Diagnostic::error(i18n::t_args(
+                "lint.unclosed_quote",
+                &[("quote", &quote.to_string())],
+            )));
+        }
+        for words in &commands {
+            self.lint_command(words, cwd, &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    fn lint_command(&self, words: &[Word], cwd: &Path, diagnostics: &mut Vec<Diagnostic>) {
+        let mut rest = words;
+        // Leading VAR=value assignments
+        while rest.first().is_some_and(|w| is_assignment(&w.text)) {
+            rest = &rest[1..];
+        }
+        let mut sudo = false;
+        while let Some((wrapper, arg_options)) = rest
+            .first()
+            .and_then(|w| WRAPPERS.iter().find(|(name, _)| *name == w.text))
+        {
+            sudo |= *wrapper == "sudo";
+            rest = &rest[1..];
+            while let Some(word) = rest.first() {
+                if is_assignment(&word.text) {
+                    rest = &rest[1..];
+                } else if word.text.starts_with('-') && word.text.len() > 1 {
+                    let skip = if arg_options.contains(&word.text.as_str()) {
+                        2
+                    } else {
+                        1
+                    };
+                    rest = &rest[skip.min(rest.len())..];
+                } else {
+                    break;
+                }
+            }
+        }
+        let Some((command, args)) = rest.split_first() else {
+            return;
+        };
+        let name = command.text.rsplit('/').next().unwrap_or(&command.text);
+
+        let operands = match self.specs.get(name) {
+            Some(spec) => check_flags(name, &spec, args, diagnostics),
+            None => args
+                .iter()
+                .scan(false, |after_dashes, word| {
+                    if word.text == "--" && !*after_dashes {
+                        *after_dashes = true;
+                        return Some(None);
+                    }
+                    let is_flag =
+                        !*after_dashes && word.text.starts_with('-') && word.text.len() > 1;
+                    Some(if is_flag { None } else { Some(word) })
+                })
+                .flatten()
+                .collect(),
+        };
+
+        if sudo && name == "rm" {
+            if let Some(glob) = operands.iter().find(|w| w.glob) {
+                diagnostics.push(Diagnostic::error(i18n::t_args(
+                    "lint.sudo_rm_glob",
+                    &[("pattern", &glob.text)],
+                )));
+            }
+        }
+
+        let checked: &[&Word] = if PATH_OPERANDS.contains(&name) {
+            &operands
+        } else if SOURCE_OPERANDS.contains(&name) && operands.len() > 1 {
+            &operands[..operands.len() - 1]
+        } else {
+            &[]
+        };
+        for word in checked {
+            if word.glob
+                || word.expansion
+                || word.text == "-"
+                || word.text.chars().all(|c| c.is_ascii_digit())
+            {
+                continue;
+            }
+            if std::fs::symlink_metadata(resolve(cwd, &word.text)).is_err() {
+                diagnostics.push(Diagnostic::warning(i18n::t_args(
+                    "lint.missing_path",
+                    &[("path", &word.text)],
+                )));
+            }
+        }
+    }
+}
+
+// Reports flags the spec doesn't know and returns the operands. Stops
+// checking at an unknown subcommand (aliases, external git-foo commands).
+fn check_flags<'a>(
+    command: &str,
+    spec: &CompletionSpec,
+    args: &'a [Word],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<&'a Word> {
+    let mut spec = spec;
+    let mut operands = Vec::new();
+    let mut iter = args.iter();
+    let mut after_dashes = false;
+
+    while let Some(word) = iter.next() {
+        let text = word.text.as_str();
+        if after_dashes
+            || !text.starts_with('-')
+            || text == "-"
+            || text[1..].chars().all(|c| c.is_ascii_digit())
+        {
+            if !after_dashes && operands.is_empty() && !spec.subcommands.is_empty() {
+                match spec.subcommand(text) {
+                    Some(sub) => {
+                        spec = sub;
+                        continue;
+                    }
+                    None => return Vec::new(),
+                }
+            }
+            operands.push(word);
+            continue;
+        }
+        if text == "--" {
+            after_dashes = true;
+            continue;
+        }
+
+        let unknown = if let Some(long) = text.strip_prefix("--") {
+            let name = format!("--{}", long.split('=').next().unwrap_or(long));
+            if spec.takes_arg(&name) && !long.contains('=') {
+                iter.next();
+            }
+            !spec.knows_option(&name)
+        } else {
+            // A cluster like -la; a flag taking a value ends it, with the
+            // value either attached (-m"msg") or in the next word
+            let mut unknown = false;
+            for (i, c) in text.char_indices().skip(1) {
+                let name = format!("-{}", c);
+                if spec.takes_arg(&name) {
+                    if i + c.len_utf8() == text.len() {
+                        iter.next();
+                    }
+                    break;
+                }
+                if !spec.knows_option(&name) {
+                    unknown = true;
+                    break;
+                }
+            }
+            unknown
+        };
+        if unknown && !spec.incomplete {
+            let command = if spec.name == command {
+                command.to_string()
+            } else {
+                format!("{} {}", command, spec.name)
+            };
+            diagnostics.push(Diagnostic::warning(i18n::t_args(
+                "lint.unknown_flag",
+                &[("command", &command), ("flag", text)],
+            )));
+        }
+    }
+    operands
+}
+
+fn is_assignment(text: &str) -> bool {
+    match text.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+fn resolve(cwd: &Path, path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => dirs::home_dir()
+            .unwrap_or_default()
+            .join(rest.trim_start_matches('/')),
+        _ => cwd.join(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(input: &str, cwd: &Path) -> Vec<Diagnostic> {
+        CommandLinter::new(Arc::new(SpecRegistry::builtin())).lint(input, cwd)
+    }
+
+    #[test]
+    fn test_tokenize_quotes_and_separators() {
+        let (commands, unclosed) = tokenize(r#"echo "a b" 'c' d\ e | grep x 2>/dev/null; ls"#);
+        assert_eq!(unclosed, None);
+        let texts: Vec<Vec<&str>> = commands
+            .iter()
+            .map(|words| words.iter().map(|w| w.text.as_str()).collect())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                vec!["echo", "a b", "c", "d e"],
+                vec!["grep", "x"],
+                vec!["ls"]
+            ]
+        );
+        assert_eq!(tokenize("echo 'oops").1, Some('\''));
+    }
+
+    #[test]
+    fn test_unknown_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(lint("ls -la --color=auto -I target", dir.path()).is_empty());
+        assert!(lint("git commit -am 'msg' --amend", dir.path()).is_empty());
+        assert!(lint("git my-alias --whatever", dir.path()).is_empty());
+
+        let diagnostics = lint("git commit --amned", dir.path());
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("--amned"));
+    }
+
+    #[test]
+    fn test_missing_paths_and_sudo_rm_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("present.txt"), "").unwrap();
+        assert!(lint("cat present.txt && cp present.txt new.txt", dir.path()).is_empty());
+        assert!(lint("cat *.log $HOME/x", dir.path()).is_empty());
+        assert_eq!(lint("cat absent.txt", dir.path()).len(), 1);
+
+        let diagnostics = lint("sudo -u root rm -rf build/*", dir.path());
+        assert!(diagnostics.iter().any(|d| d.severity == Severity::Error));
+        assert!(lint("rm -rf build/*", dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use crossterm::style::Color;
+use regex::Regex;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::i18n;
+use crate::pager::parse_ansi;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// File lines shown either side of the referenced one
+const PREVIEW_CONTEXT: u32 = 5;
+
+// A file:line pointer found in command output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReference {
+    pub path: PathBuf,   // absolute
+    pub display: String, // as written in the output
+    pub line: u32,
+    pub column: Option<u32>,
+    pub text: String, // the output line it came from
+}
+
+fn python_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // Python tracebacks: File "app/main.py", line 12, in <module>
+    PATTERN.get_or_init(|| Regex::new(r#"File "([^"]+)", line (\d+)"#).expect("static pattern"))
+}
+
+fn location_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // rustc/gcc/tsc `src/main.rs:12:5`, grep -n `README.md:3:`, eslint, go vet,
+    // ... The path must contain a dot or slash, which keeps out times and
+    // host:port pairs; bare names still have to exist on disk below.
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?:^|[\s('"\[<])([\w~+@-]*[./][\w./~+@-]*):(\d+)(?:[:.](\d+))?"#)
+            .expect("static pattern")
+    })
+}
+
+pub(crate) fn resolve(path: &str, cwd: &Path) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => cwd.join(path),
+    }
+}
+
+// References in `lines` whose file exists, in output order without repeats.
+// Escape sequences are stripped first so coloured compiler output matches.
+pub fn find_references(lines: &[String], cwd: &Path) -> Vec<FileReference> {
+    let mut references: Vec<FileReference> = Vec::new();
+    for raw in lines {
+        let plain: String = parse_ansi(raw).into_iter().map(|(text, _)| text).collect();
+        let python = python_pattern()
+            .captures_iter(&plain)
+            .map(|captures| (captures.get(1), captures.get(2), None));
+        let general = location_pattern()
+            .captures_iter(&plain)
+            .map(|captures| (captures.get(1), captures.get(2), captures.get(3)));
+        for (path, line, column) in python.chain(general) {
+            let (Some(path), Some(line)) = (path, line) else {
+                continue;
+            };
+            let Ok(line) = line.as_str().parse::<u32>() else {
+                continue;
+            };
+            if line == 0 {
+                continue;
+            }
+            let display = path.as_str().trim_end_matches('.').to_string();
+            let resolved = resolve(&display, cwd);
+            if !resolved.is_file() {
+                continue;
+            }
+            let column = column.and_then(|column| column.as_str().parse().ok());
+            let duplicate = references.iter().any(|known| {
+                known.path == resolved && known.line == line && known.column == column
+            });
+            if !duplicate {
+                references.push(FileReference {
+                    path: resolved,
+                    display,
+                    line,
+                    column,
+                    text: plain.trim().to_string(),
+                });
+            }
+        }
+    }
+    references
+}
+
+// The lines around `line`, numbered, with the referenced one highlighted
+pub(crate) fn source_preview(path: &Path, line: u32) -> Vec<PanelItem> {
+    let Ok(file) = std::fs::File::open(path) else {
+        return Vec::new();
+    };
+    let first = line.saturating_sub(PREVIEW_CONTEXT).max(1);
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .enumerate()
+        .map(|(index, text)| (index as u32 + 1, text))
+        .skip(first as usize - 1)
+        .take_while(|(number, _)| *number <= line + PREVIEW_CONTEXT)
+        .map(|(number, text)| {
+            let text = format!("{:>5} {}", number, text.replace('\t', "    "));
+            if number == line {
+                PanelItem::styled(vec![(text, Some(Color::Yellow))])
+            } else {
+                text.into()
+            }
+        })
+        .collect()
+}
+
+pub enum LocationCommand {
+    None,
+    Redraw,
+    Rescan,
+    Open(FileReference),
+}
+
+pub struct LocationsPanel {
+    cwd: PathBuf, // relative references resolve against this
+    references: Vec<FileReference>,
+    selected: usize,
+    preview: Vec<PanelItem>,
+}
+
+impl LocationsPanel {
+    pub fn new(lines: &[String], cwd: PathBuf) -> Self {
+        let mut panel = Self {
+            cwd,
+            references: Vec::new(),
+            selected: 0,
+            preview: Vec::new(),
+        };
+        panel.rescan(lines);
+        panel
+    }
+
+    pub fn rescan(&mut self, lines: &[String]) {
+        self.references = find_references(lines, &self.cwd);
+        self.select(0);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.references.len().saturating_sub(1));
+        self.preview = self
+            .references
+            .get(self.selected)
+            .map(|reference| source_preview(&reference.path, reference.line))
+            .unwrap_or_default();
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> LocationCommand {
+        self.select(index);
+        match key {
+            '\n' | 'o' => match self.references.get(self.selected) {
+                Some(reference) => LocationCommand::Open(reference.clone()),
+                None => LocationCommand::None,
+            },
+            'r' => LocationCommand::Rescan,
+            _ => LocationCommand::Redraw,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .references
+            .iter()
+            .map(|reference| {
+                let location = match reference.column {
+                    Some(column) => format!("{}:{}:{}", reference.display, reference.line, column),
+                    None => format!("{}:{}", reference.display, reference.line),
+                };
+                PanelItem::styled(vec![
+                    (location, Some(Color::Blue)),
+                    (format!("  {}", reference.text), Some(Color::DarkGrey)),
+                ])
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.locations_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::Locations,
+            title: i18n::t_args(
+                "panel.locations",
+                &[("count", &self.references.len().to_string())],
+            ),
+            items,
+            footer: Some(i18n::t("panel.locations_keys")),
+            preview: self.preview.clone(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_find_references_in_tool_output() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        for name in ["src/main.rs", "app.py", "README.md"] {
+            std::fs::write(dir.path().join(name), "one\ntwo\nthree\n").unwrap();
+        }
+        let output = lines(
+            "error[E0425]: cannot find value `x`\n\
+             \x1b[1;34m  --> \x1b[0msrc/main.rs:2:9\n\
+             Traceback (most recent call last):\n  File \"app.py\", line 3, in <module>\n\
+             README.md:1:one\n\
+             see src/main.rs:2:9 again, or gone.rs:4\n\
+             listening on 127.0.0.1:8080 at 12:30:01",
+        );
+        let found = find_references(&output, dir.path());
+        let summary: Vec<(&str, u32, Option<u32>)> = found
+            .iter()
+            .map(|reference| (reference.display.as_str(), reference.line, reference.column))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/main.rs", 2, Some(9)),
+                ("app.py", 3, None),
+                ("README.md", 1, None)
+            ]
+        );
+        assert_eq!(found[0].path, dir.path().join("src/main.rs"));
+        assert_eq!(found[0].text, "--> src/main.rs:2:9");
+    }
+
+    #[test]
+    fn test_panel_opens_selection_with_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let body: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        std::fs::write(dir.path().join("lib.rs"), body).unwrap();
+        let mut panel = LocationsPanel::new(
+            &lines("lib.rs:10:1: warning\nlib.rs:2: note"),
+            dir.path().to_path_buf(),
+        );
+
+        panel.select(1);
+        assert_eq!(panel.view().preview.len(), 7);
+        match panel.handle_key('\n', 0) {
+            LocationCommand::Open(reference) => {
+                assert_eq!((reference.line, reference.column), (10, Some(1)))
+            }
+            _ => panic!("expected Open"),
+        }
+        let preview = panel.view().preview;
+        assert_eq!(preview.len(), 11);
+        assert_eq!(preview[5].segments[0].0, "   10 line 10");
+        assert!(matches!(panel.handle_key('r', 0), LocationCommand::Rescan));
+    }
+}
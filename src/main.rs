@@ -22,13 +22,61 @@ use theme::Theme;
 
 use clap::{Arg, Command};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use warp_terminal::{app::WarpApp, config::Config, error::WarpError, logger::Logger};
+use warp_terminal::{
+    app::WarpApp,
+    audit::{self, AuditLog, AuditQuery},
+    config::{CustomCommand, OAuthConfig, WarpConfig},
+    custom_commands,
+    dev_tools::validator::{self, Severity},
+    download::{DownloadManager, DownloadRequest},
+    error::{ResultExt, WarpError},
+    headless,
+    logger::Logger,
+    notebook::{self, Notebook},
+    oauth,
+    package::{self, Bundle, Manifest, PackageKind, Signature},
+    plugins::PluginManager,
+    ports::PortWatcher,
+    render_bench::{format_report, RenderBenchmark},
+    secrets::SecretsManager,
+    startup::{self, StartupProfile},
+};
 
 #[tokio::main]
-async fn main() -> Result<(), WarpError> {
+async fn main() {
+    // Fatal errors get the same rendering as in-app ones instead of Debug output
+    if let Err(e) = run().await {
+        for line in e.render() {
+            eprintln!("{}", line);
+        }
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), WarpError> {
+    // Custom subcommands come from the config and plugins, so the config is
+    // read before the arguments are parsed. A broken config only surfaces
+    // once something needs it.
+    let profile = Arc::new(StartupProfile::new());
+    let config = profile
+        .measure(
+            "config",
+            WarpConfig::load(config_arg(std::env::args()).as_ref()),
+        )
+        .await;
+    let custom = match &config {
+        Ok(config) => {
+            profile
+                .measure("custom-commands", custom_commands(config))
+                .await?
+        }
+        Err(_) => Vec::new(),
+    };
+
     // Parse command line arguments
-    let matches = Command::new("warp")
+    let cli = Command::new("warp")
         .version("1.0.0")
         .author("Warp Terminal Team")
         .about("A modern, Rust-based terminal with AI built in")
@@ -46,6 +94,18 @@ async fn main() -> Result<(), WarpError> {
                 .value_name("THEME")
                 .help("Sets the theme"),
         )
+        .arg(
+            Arg::new("bench-render")
+                .long("bench-render")
+                .help("Measure PTY-to-screen throughput on pathological output and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("profile-startup")
+                .long("profile-startup")
+                .help("Start every subsystem, print how long each took to initialize and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("debug")
                 .short('d')
@@ -53,15 +113,267 @@ async fn main() -> Result<(), WarpError> {
                 .help("Enable debug mode")
                 .action(clap::ArgAction::SetTrue),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("ports")
+                .about("List listening ports and the processes that own them")
+                .arg(
+                    Arg::new("watch")
+                        .short('w')
+                        .long("watch")
+                        .help("Keep running and report ports as they open and close")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run a command without the UI and print its result")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print stdout, stderr, exit code, duration and block id as JSON")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Kill the command after this many seconds (exit code 124)"),
+                )
+                .arg(
+                    Arg::new("shell")
+                        .long("shell")
+                        .value_name("SHELL")
+                        .help("Shell to run the command in instead of the configured one"),
+                )
+                .arg(
+                    Arg::new("command")
+                        .required(true)
+                        .num_args(1..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("connect")
+                .about("Open a saved connection from shells.connections in a new tab")
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(
+            Command::new("notebook")
+                .about("Run a .warpnb notebook (or a Markdown runbook) without the UI")
+                .arg(
+                    Arg::new("run")
+                        .long("run")
+                        .help("Run every command cell in order and save the outputs")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("html")
+                        .long("html")
+                        .help("Export the notebook to HTML next to it")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(Arg::new("file").required(true)),
+        )
+        .subcommand(
+            Command::new("secrets")
+                .about("Manage tokens used by integrations (e.g. forge.github.com)")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set")
+                        .about("Store a secret, reading the value from stdin")
+                        .arg(Arg::new("name").required(true)),
+                )
+                .subcommand(Command::new("list").about("List stored secret names"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete a stored secret")
+                        .arg(Arg::new("name").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Search, export and verify the local log of privileged actions")
+                .subcommand_required(true)
+                .subcommand(audit_filters(
+                    Command::new("list").about("Print the matching records, oldest first"),
+                ))
+                .subcommand(
+                    audit_filters(
+                        Command::new("export")
+                            .about("Write the matching records as CSV or JSON lines"),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["csv", "json"])
+                            .default_value("csv"),
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("FILE")
+                            .help("Defaults to stdout"),
+                    ),
+                )
+                .subcommand(
+                    Command::new("verify").about("Check that no record was edited or removed"),
+                ),
+        )
+        .subcommand(
+            Command::new("auth")
+                .about("Sign in to the marketplace and OAuth integrations with a device code")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("login")
+                        .about("Show a code to approve in the browser, then store the tokens")
+                        .arg(Arg::new("provider").default_value("marketplace")),
+                )
+                .subcommand(
+                    Command::new("logout")
+                        .about("Forget the stored tokens")
+                        .arg(Arg::new("provider").default_value("marketplace")),
+                )
+                .subcommand(
+                    Command::new("status").about("List providers and whether each is signed in"),
+                ),
+        )
+        .subcommand(
+            Command::new("theme")
+                .about("Install color themes")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Download a theme file into the first theme directory")
+                        .arg(Arg::new("url").required(true))
+                        .arg(
+                            Arg::new("sha256")
+                                .long("sha256")
+                                .value_name("HEX")
+                                .help("Reject the download unless it has this checksum"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("package")
+                .about("Create, check and publish marketplace themes, plugins and workflows")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("new")
+                        .about("Scaffold a package with a manifest and starter files")
+                        .arg(
+                            Arg::new("kind")
+                                .required(true)
+                                .value_parser(["theme", "plugin", "workflow"]),
+                        )
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .value_name("DIR")
+                                .help("Defaults to ./<name>"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("validate")
+                        .about("Check a package the way the marketplace will")
+                        .arg(Arg::new("dir").default_value(".")),
+                )
+                .subcommand(
+                    Command::new("bundle")
+                        .about("Validate, then write the signed archive to dist/")
+                        .arg(Arg::new("dir").default_value(".")),
+                )
+                .subcommand(
+                    Command::new("publish")
+                        .about("Validate, bundle, sign and upload a package")
+                        .arg(Arg::new("dir").default_value("."))
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .help("Stop before uploading")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                ),
+        );
+    let (cli, skipped) = custom_commands::register(cli, &custom);
+    let matches = cli.get_matches();
 
     // Initialize logger
     let debug_mode = matches.get_flag("debug");
     Logger::init(debug_mode)?;
+    for name in skipped {
+        log::warn!(
+            "Ignoring custom command '{}': the name is invalid or already taken",
+            name
+        );
+    }
+
+    if matches.get_flag("bench-render") {
+        let bench = RenderBenchmark::new(200, 50, 10_000);
+        let reports = bench.run_all(256 * 1024 * 1024)?;
+        print!("{}", format_report(&reports));
+        return Ok(());
+    }
+
+    // Proxies and extra CAs for every HTTP client created from here on,
+    // including `package publish` which runs without a valid config
+    if let Ok(config) = &config {
+        warp_terminal::network::init(&config.network)?;
+    }
+
+    if let Some(ports_matches) = matches.subcommand_matches("ports") {
+        return run_ports(ports_matches.get_flag("watch")).await;
+    }
+
+    if let Some(secrets_matches) = matches.subcommand_matches("secrets") {
+        return run_secrets(secrets_matches).await;
+    }
+
+    if let Some(audit_matches) = matches.subcommand_matches("audit") {
+        return run_audit(audit_matches).await;
+    }
+
+    if let Some(package_matches) = matches.subcommand_matches("package") {
+        // Publishing can still sign in through the default provider without a valid config
+        let oauth = config.as_ref().map_or_else(
+            |_| WarpConfig::default().oauth,
+            |config| config.oauth.clone(),
+        );
+        return run_package(package_matches, &oauth).await;
+    }
+
+    let config = config.context("Failed to load configuration")?;
+
+    if let Some(auth_matches) = matches.subcommand_matches("auth") {
+        return run_auth(auth_matches, &config).await;
+    }
+
+    if let Some(theme_matches) = matches.subcommand_matches("theme") {
+        return run_theme(theme_matches, &config).await;
+    }
 
-    // Load configuration
-    let config_path = matches.get_one::<String>("config");
-    let config = Config::load(config_path).await?;
+    if let Some(run_matches) = matches.subcommand_matches("run") {
+        return run_headless(run_matches, &config).await;
+    }
+
+    if let Some(notebook_matches) = matches.subcommand_matches("notebook") {
+        return run_notebook(notebook_matches, &config).await;
+    }
+
+    if let Some((name, args)) = matches.subcommand() {
+        if let Some(command) = custom.iter().find(|command| command.name == name) {
+            let code = custom_commands::run(command, args, &config.terminal.shell).await?;
+            std::process::exit(code);
+        }
+    }
+
+    // Select the UI language before anything user-facing is rendered
+    let started = Instant::now();
+    warp_terminal::i18n::init(config.ui.locale.as_deref())?;
+    profile.record("i18n", started.elapsed(), false);
 
     // Override theme if specified
     let mut final_config = config;
@@ -70,12 +382,503 @@ async fn main() -> Result<(), WarpError> {
     }
 
     // Create and run the application
-    let app = WarpApp::new(Arc::new(Mutex::new(final_config))).await?;
+    let budget = Duration::from_millis(final_config.startup.budget_ms);
+    let mut app =
+        WarpApp::with_profile(Arc::new(Mutex::new(final_config)), profile.clone()).await?;
+    // Settings edits go back to the file the config came from
+    if let Some(path) = config_arg(std::env::args()) {
+        app.set_config_path(std::path::PathBuf::from(path));
+    }
+    if let Some(connect_matches) = matches.subcommand_matches("connect") {
+        app.set_initial_connection(
+            connect_matches
+                .get_one::<String>("name")
+                .expect("required")
+                .clone(),
+        );
+    }
+    if matches.get_flag("profile-startup") {
+        app.load_lazy_services().await?;
+        print!("{}", startup::format_report(&profile, budget));
+        return Ok(());
+    }
     app.run().await?;
 
     Ok(())
 }
 
+// The --config value, found before clap runs; scanning stops at the first
+// subcommand so its own -c is left alone
+fn config_arg(args: impl Iterator<Item = String>) -> Option<String> {
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => return args.next(),
+            "-t" | "--theme" => {
+                args.next();
+            }
+            _ => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    return Some(path.to_string());
+                }
+                if !arg.starts_with('-') {
+                    break;
+                }
+            }
+        }
+    }
+    None
+}
+
+async fn custom_commands(config: &WarpConfig) -> Result<Vec<CustomCommand>, WarpError> {
+    let mut commands = config.commands.clone();
+    let plugins = PluginManager::new(Arc::new(Mutex::new(config.clone()))).await?;
+    plugins.start().await?;
+    commands.extend(plugins.custom_commands());
+    plugins.stop().await?;
+    Ok(commands)
+}
+
+async fn run_headless(matches: &clap::ArgMatches, config: &WarpConfig) -> Result<(), WarpError> {
+    let command = matches
+        .get_many::<String>("command")
+        .expect("required")
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let shell = matches
+        .get_one::<String>("shell")
+        .unwrap_or(&config.terminal.shell);
+    let timeout = matches
+        .get_one::<u64>("timeout")
+        .map(|secs| std::time::Duration::from_secs(*secs));
+
+    let result = headless::run(shell, &command, timeout).await?;
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string(&result)?);
+    } else {
+        print!("{}", result.stdout);
+        eprint!("{}", result.stderr);
+    }
+    io::stdout().flush()?;
+    std::process::exit(result.process_exit_code());
+}
+
+// Goes through the shared download manager, so the download limits, proxy
+// settings and resume apply like they do for marketplace installs
+async fn run_theme(matches: &clap::ArgMatches, config: &WarpConfig) -> Result<(), WarpError> {
+    let Some(("install", args)) = matches.subcommand() else {
+        return Ok(());
+    };
+    let url = args.get_one::<String>("url").expect("required");
+    let directory = config
+        .themes
+        .theme_directories
+        .first()
+        .ok_or_else(|| WarpError::config_err("No theme directory is configured"))?;
+    let mut request = DownloadRequest::new(url.clone(), directory.join(theme_file_name(url)?));
+    if let Some(sha256) = args.get_one::<String>("sha256") {
+        request = request.sha256(sha256.clone());
+    }
+
+    let downloads = DownloadManager::new(&config.downloads)?;
+    let path = downloads.download(&request, |_| {}).await?;
+    // A file that doesn't parse would only fail later, once it's selected
+    let content = tokio::fs::read(&path).await?;
+    if let Err(e) = serde_yaml::from_slice::<serde_yaml::Value>(&content) {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(WarpError::Validation(format!(
+            "{} is not a YAML theme: {}",
+            url, e
+        )));
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+// The last path segment, which must name a YAML file; anything else could
+// write outside the theme directory or clobber a non-theme file
+fn theme_file_name(url: &str) -> Result<String, WarpError> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| WarpError::Validation(format!("Invalid URL '{}': {}", url, e)))?;
+    parsed
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .filter(|name| name.ends_with(".yaml") || name.ends_with(".yml"))
+        .filter(|name| !name.starts_with('.') && !name.contains('\\'))
+        .map(str::to_string)
+        .ok_or_else(|| {
+            WarpError::Validation(format!(
+                "'{}' doesn't point to a .yaml or .yml theme file",
+                url
+            ))
+        })
+}
+
+async fn run_notebook(matches: &clap::ArgMatches, config: &WarpConfig) -> Result<(), WarpError> {
+    let source = std::path::Path::new(matches.get_one::<String>("file").expect("required"));
+    let mut book = Notebook::load(source).await?;
+    let path = source.with_extension(notebook::EXTENSION);
+    let settings = &config.notebooks;
+    let timeout = Duration::from_secs(settings.cell_timeout_secs);
+
+    let mut failed = false;
+    if matches.get_flag("run") {
+        let directory = notebook::directory(&path);
+        for index in book.command_cells_from(0) {
+            let command = book.command(index).expect("command cell").to_string();
+            println!("$ {}", command);
+            let result =
+                notebook::run_cell(&config.terminal.shell, &directory, &command, timeout).await?;
+            print!("{}", result.stdout);
+            eprint!("{}", result.stderr);
+            io::stdout().flush()?;
+            let succeeded = result.exit_code == Some(0);
+            book.set_output(index, result);
+            if !succeeded {
+                failed = true;
+                if settings.stop_on_failure {
+                    break;
+                }
+            }
+        }
+    }
+    // A Markdown source is only written out as .warpnb once there is
+    // something to keep
+    if matches.get_flag("run") || notebook::is_notebook(source) {
+        book.save(&path).await?;
+    }
+    if matches.get_flag("html") {
+        println!("{}", book.export_html(&path).await?.display());
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_ports(watch: bool) -> Result<(), WarpError> {
+    let mut watcher = PortWatcher::new();
+    watcher.refresh().await?;
+    for port in watcher.ports() {
+        println!("{}  {}", port.display_line(), port.url());
+    }
+
+    while watch {
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let diff = watcher.refresh().await?;
+        for port in &diff.opened {
+            println!("+ {}  {}", port.display_line(), port.url());
+        }
+        for address in &diff.closed {
+            println!("- {}", address);
+        }
+    }
+    Ok(())
+}
+
+async fn run_secrets(matches: &clap::ArgMatches) -> Result<(), WarpError> {
+    let secrets = SecretsManager::new().await?;
+    match matches.subcommand() {
+        Some(("set", args)) => {
+            let name = args.get_one::<String>("name").expect("required");
+            // stdin rather than an argument so the value stays out of shell history
+            let mut value = String::new();
+            io::stdin().read_line(&mut value)?;
+            let value = value.trim_end_matches(['\n', '\r']);
+            if value.is_empty() {
+                return Err(WarpError::Validation(format!(
+                    "No value given for '{}'",
+                    name
+                )));
+            }
+            secrets.set(name, value).await?;
+        }
+        Some(("list", _)) => {
+            for name in secrets.names().await? {
+                println!("{}", name);
+            }
+        }
+        Some(("remove", args)) => {
+            let name = args.get_one::<String>("name").expect("required");
+            if !secrets.remove(name).await? {
+                return Err(WarpError::not_found("secret", name.clone()));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn audit_filters(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("search")
+                .short('s')
+                .long("search")
+                .value_name("TEXT")
+                .help("Only records mentioning TEXT, ignoring case"),
+        )
+        .arg(
+            Arg::new("action")
+                .short('a')
+                .long("action")
+                .value_name("ACTION")
+                .help("e.g. sudo, policy_override, permission_granted, config_changed"),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .value_name("DATE")
+                .help("YYYY-MM-DD or an RFC 3339 time"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .value_name("DATE")
+                .help("YYYY-MM-DD or an RFC 3339 time, exclusive"),
+        )
+}
+
+// A date means local midnight at its start
+fn parse_time(value: &str) -> Result<chrono::DateTime<chrono::Utc>, WarpError> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| {
+            date.and_hms_opt(0, 0, 0)?
+                .and_local_timezone(chrono::Local)
+                .earliest()
+        })
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .ok_or_else(|| WarpError::Validation(format!("Not a date or time: '{}'", value)))
+}
+
+async fn run_audit(matches: &clap::ArgMatches) -> Result<(), WarpError> {
+    let path = AuditLog::default_path()?;
+    let (name, args) = matches.subcommand().expect("required");
+    if name == "verify" {
+        let report = AuditLog::verify(&path).await?;
+        if !report.valid {
+            return Err(WarpError::Validation(format!(
+                "{} is broken at record {}: {}",
+                path.display(),
+                report.first_broken_sequence.unwrap_or_default(),
+                report.error.unwrap_or_default()
+            )));
+        }
+        println!("{} records, chain intact", report.records_checked);
+        return Ok(());
+    }
+
+    let time = |name: &str| {
+        args.get_one::<String>(name)
+            .map(|value| parse_time(value))
+            .transpose()
+    };
+    let query = AuditQuery {
+        text: args.get_one::<String>("search").cloned(),
+        action: args.get_one::<String>("action").cloned(),
+        since: time("since")?,
+        until: time("until")?,
+    };
+    let records = AuditLog::read(&path).await?;
+    let matching = audit::search(&records, &query);
+    if name == "list" {
+        for record in matching {
+            println!(
+                "{}  {:<24} {:<12} {}",
+                record
+                    .timestamp
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S"),
+                record.action.kind(),
+                record.user,
+                record.action.summary()
+            );
+        }
+        return Ok(());
+    }
+
+    let output = match args.get_one::<String>("format").map(String::as_str) {
+        Some("json") => matching
+            .iter()
+            .map(|record| Ok(serde_json::to_string(record)? + "\n"))
+            .collect::<Result<String, WarpError>>()?,
+        _ => audit::to_csv(&matching),
+    };
+    match args.get_one::<String>("output") {
+        Some(file) => tokio::fs::write(file, output).await?,
+        None => print!("{}", output),
+    }
+    Ok(())
+}
+
+async fn run_auth(matches: &clap::ArgMatches, config: &WarpConfig) -> Result<(), WarpError> {
+    let secrets = SecretsManager::new().await?;
+    let provider = |args: &clap::ArgMatches| {
+        let name = args.get_one::<String>("provider").expect("defaulted");
+        config
+            .oauth
+            .provider(name)
+            .ok_or_else(|| WarpError::not_found("OAuth provider", name.clone()))
+    };
+    match matches.subcommand() {
+        Some(("login", args)) => {
+            let provider = provider(args)?;
+            let tokens = oauth::login(&secrets, provider, |authorization| {
+                let code = &authorization.user_code;
+                match &authorization.verification_uri_complete {
+                    Some(url) => println!("Open {} and confirm the code {}", url, code),
+                    None => println!(
+                        "Open {} and enter the code {}",
+                        authorization.verification_uri, code
+                    ),
+                }
+                println!("Waiting for approval...");
+            })
+            .await?;
+            match tokens.scope {
+                Some(scope) => println!("Signed in to {} ({})", provider.name, scope),
+                None => println!("Signed in to {}", provider.name),
+            }
+        }
+        Some(("logout", args)) => {
+            let provider = provider(args)?;
+            if !oauth::logout(&secrets, &provider.name).await? {
+                println!("Not signed in to {}", provider.name);
+            }
+        }
+        Some(("status", _)) => {
+            for provider in &config.oauth.providers {
+                let status = match oauth::stored_tokens(&secrets, &provider.name).await? {
+                    Some(tokens)
+                        if tokens.is_expired(chrono::Utc::now())
+                            && tokens.refresh_token.is_none() =>
+                    {
+                        "expired"
+                    }
+                    Some(_) => "signed in",
+                    None => "signed out",
+                };
+                println!("{}: {}", provider.name, status);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn run_package(matches: &clap::ArgMatches, oauth: &OAuthConfig) -> Result<(), WarpError> {
+    let dir = |args: &clap::ArgMatches| {
+        std::path::PathBuf::from(args.get_one::<String>("dir").expect("defaulted"))
+    };
+    match matches.subcommand() {
+        Some(("new", args)) => {
+            let kind = args.get_one::<String>("kind").expect("required");
+            let kind = PackageKind::parse(kind).expect("checked by clap");
+            let name = args.get_one::<String>("name").expect("required");
+            let dir = args
+                .get_one::<String>("dir")
+                .map_or_else(|| name.into(), std::path::PathBuf::from);
+            for path in package::scaffold(&dir, kind, name).await? {
+                println!("Created {}", path.display());
+            }
+            if kind == PackageKind::Plugin {
+                println!(
+                    "Build the plugin to {} before bundling",
+                    dir.join("plugin.wasm").display()
+                );
+            }
+        }
+        Some(("validate", args)) => {
+            let manifest = validate_package(&dir(args)).await?;
+            println!("{} {} is ready to publish", manifest.id, manifest.version);
+        }
+        Some(("bundle", args)) => {
+            bundle_package(&dir(args)).await?;
+        }
+        Some(("publish", args)) => {
+            let (manifest, bundle, signature) = bundle_package(&dir(args)).await?;
+            if args.get_flag("dry-run") {
+                println!("Dry run; nothing uploaded");
+                return Ok(());
+            }
+            println!(
+                "Uploading {} bytes to the marketplace...",
+                bundle.archive.len()
+            );
+            let secrets = SecretsManager::new().await?;
+            let provider = oauth.provider("marketplace");
+            let published =
+                package::publish(&secrets, provider, &manifest, &bundle.archive, &signature)
+                    .await
+                    .with_context(|| {
+                        format!("Failed to publish {} {}", manifest.id, manifest.version)
+                    })?;
+            println!("Published {} {}", published.id, published.version);
+            if let Some(url) = published.url {
+                println!("{}", url);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Prints every finding; any error stops the flow before anything is written
+async fn validate_package(dir: &std::path::Path) -> Result<Manifest, WarpError> {
+    let report = validator::validate_package(dir).await?;
+    for finding in &report.findings {
+        let severity = match finding.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        eprintln!("{}: {}: {}", severity, finding.file, finding.message);
+        if let Some(suggestion) = &finding.suggestion {
+            eprintln!("  help: {}", suggestion);
+        }
+    }
+    match report.manifest {
+        Some(manifest) if report.errors() == 0 => Ok(manifest),
+        _ => Err(WarpError::Validation(format!(
+            "{} has {} error(s)",
+            dir.display(),
+            report.errors()
+        ))),
+    }
+}
+
+// Writes dist/<name>-<version>.zip and its .sig next to it
+async fn bundle_package(dir: &std::path::Path) -> Result<(Manifest, Bundle, Signature), WarpError> {
+    let manifest = validate_package(dir).await?;
+    println!("Validated {} {}", manifest.id, manifest.version);
+
+    let bundle = package::bundle(dir, &manifest).await?;
+    let dist = dir.join("dist");
+    tokio::fs::create_dir_all(&dist).await?;
+    let archive_path = dist.join(manifest.archive_name());
+    tokio::fs::write(&archive_path, &bundle.archive).await?;
+    println!(
+        "Bundled {} files ({} bytes) into {}",
+        bundle.files.len(),
+        bundle.archive.len(),
+        archive_path.display()
+    );
+
+    let secrets = SecretsManager::new().await?;
+    let key = package::signing_key(&secrets)
+        .await
+        .context("Failed to load the package signing key")?;
+    let signature = package::sign(&key, &bundle.archive);
+    let signature_path = dist.join(format!("{}.sig", manifest.archive_name()));
+    tokio::fs::write(&signature_path, &signature.signature).await?;
+    println!("Signed with public key {}", signature.public_key);
+    Ok((manifest, bundle, signature))
+}
+
 fn draw_header(stdout: &mut io::Stdout, theme: &Theme) -> Result<(), Box<dyn std::error::Error>> {
     queue!(
         stdout,
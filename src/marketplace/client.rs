@@ -1,141 +1,377 @@
 use super::*;
+use crate::config::{OAuthConfig, RegistryAuth, RegistryConfig};
+use crate::download::DownloadRequest;
 use crate::error::WarpError;
-use reqwest::{Client, Response};
+use crate::network;
+use crate::oauth;
+use crate::secrets::SecretsManager;
+use futures::future;
+use reqwest::{Client, RequestBuilder, Response};
 use serde_json;
+use std::path::Path;
 
+// Talks to every enabled registry: listings are gathered from all of them
+// and merged, single items come from the best registry that has them
 pub struct MarketplaceClient {
     client: Client,
-    base_url: String,
-    api_key: Option<String>,
+    registries: Vec<RegistryConfig>, // best first
+    oauth: OAuthConfig,
+    secrets: SecretsManager,
+    api_key: Option<String>, // WARP_MARKETPLACE_API_KEY, for the official registry only
 }
 
 impl MarketplaceClient {
-    pub async fn new() -> Result<Self, WarpError> {
+    pub async fn new(
+        registries: &[RegistryConfig],
+        oauth: &OAuthConfig,
+    ) -> Result<Self, WarpError> {
         Ok(Self {
-            client: Client::new(),
-            base_url: "https://marketplace.warp.dev/api/v1".to_string(),
+            client: network::client()?,
+            registries: registry::effective(registries),
+            oauth: oauth.clone(),
+            secrets: SecretsManager::new().await?,
             api_key: std::env::var("WARP_MARKETPLACE_API_KEY").ok(),
         })
     }
 
+    pub fn registries(&self) -> &[RegistryConfig] {
+        &self.registries
+    }
+
+    fn registry(&self, name: &str) -> Result<&RegistryConfig, WarpError> {
+        self.registries
+            .iter()
+            .find(|registry| registry.name == name)
+            .ok_or_else(|| WarpError::not_found("registry", name))
+    }
+
+    fn url(registry: &RegistryConfig, path: &str) -> String {
+        format!(
+            "{}/{}",
+            network::endpoint(registry.url.trim_end_matches('/')),
+            path
+        )
+    }
+
+    // The bearer token for a registry, if it wants one. A private registry
+    // the user hasn't signed in to is an error rather than an anonymous
+    // request that would only come back 401.
+    async fn token(&self, registry: &RegistryConfig) -> Result<Option<String>, WarpError> {
+        if registry.name == registry::OFFICIAL && self.api_key.is_some() {
+            return Ok(self.api_key.clone());
+        }
+        match &registry.auth {
+            RegistryAuth::None => Ok(None),
+            RegistryAuth::Token { secret } => match self.secrets.get(secret).await? {
+                Some(token) => Ok(Some(token)),
+                None => Err(WarpError::Auth(format!(
+                    "Registry {} needs the {} secret; set it with `warp secrets set {}`",
+                    registry.name, secret, secret
+                ))),
+            },
+            RegistryAuth::OAuth { provider } => {
+                let Some(config) = self.oauth.provider(provider) else {
+                    // The official registry works anonymously for browsing
+                    if registry.name == registry::OFFICIAL {
+                        return Ok(None);
+                    }
+                    return Err(WarpError::not_found("oauth provider", provider));
+                };
+                match oauth::access_token(&self.secrets, config).await? {
+                    Some(token) => Ok(Some(token)),
+                    None if registry.name == registry::OFFICIAL => Ok(None),
+                    None => Err(WarpError::Auth(format!(
+                        "Registry {} needs a sign-in; run `warp auth login {}`",
+                        registry.name, provider
+                    ))),
+                }
+            }
+        }
+    }
+
+    async fn authorize(
+        &self,
+        registry: &RegistryConfig,
+        request: RequestBuilder,
+    ) -> Result<RequestBuilder, WarpError> {
+        Ok(match self.token(registry).await? {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+
     pub async fn search(&self, query: SearchQuery) -> Result<SearchResult, WarpError> {
-        let url = format!("{}/search", self.base_url);
-        
-        let response = self.client
-            .post(&url)
-            .json(&query)
-            .send()
-            .await
-            .map_err(|e| WarpError::ConfigError(format!("Search request failed: {}", e)))?;
+        let query = &query;
+        let results = self
+            .query_all(|registry| async move {
+                let request = self.client.post(Self::url(registry, "search")).json(query);
+                let response = self
+                    .authorize(registry, request)
+                    .await?
+                    .send()
+                    .await
+                    .map_err(|e| WarpError::network("Search request failed", e))?;
+                self.handle_response::<SearchResult>(response).await
+            })
+            .await?;
 
-        self.handle_response(response).await
+        // Facets and paging come from the best registry that answered
+        let mut merged = results[0].1.clone();
+        merged.total_count = results.iter().map(|(_, result)| result.total_count).sum();
+        merged.items = registry::merge(
+            &self.registries,
+            results
+                .into_iter()
+                .map(|(name, result)| (name, result.items))
+                .collect(),
+        );
+        Ok(merged)
     }
 
     pub async fn get_item(&self, id: &str) -> Result<MarketplaceItem, WarpError> {
-        let url = format!("{}/items/{}", self.base_url, id);
-        
-        let response = self.client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| WarpError::ConfigError(format!("Get item request failed: {}", e)))?;
+        self.query_first(id, |registry| async move {
+            let request = self
+                .client
+                .get(Self::url(registry, &format!("items/{}", id)));
+            let response = self
+                .authorize(registry, request)
+                .await?
+                .send()
+                .await
+                .map_err(|e| WarpError::network("Get item request failed", e))?;
+            let mut item: MarketplaceItem = self.handle_response(response).await?;
+            item.registry = Some(registry.name.clone());
+            Ok(item)
+        })
+        .await
+    }
 
-        self.handle_response(response).await
+    // Every published version of an item, from the registry that serves it
+    pub async fn get_versions(&self, id: &str) -> Result<Vec<MarketplaceItem>, WarpError> {
+        self.query_first(id, |registry| async move {
+            let request = self
+                .client
+                .get(Self::url(registry, &format!("items/{}/versions", id)));
+            let response = self
+                .authorize(registry, request)
+                .await?
+                .send()
+                .await
+                .map_err(|e| WarpError::network("Get versions request failed", e))?;
+            let mut versions: Vec<MarketplaceItem> = self.handle_response(response).await?;
+            for item in &mut versions {
+                item.registry = Some(registry.name.clone());
+            }
+            Ok(versions)
+        })
+        .await
     }
 
     pub async fn get_reviews(&self, item_id: &str, page: u32) -> Result<Vec<Review>, WarpError> {
-        let url = format!("{}/items/{}/reviews?page={}", self.base_url, item_id, page);
-        
-        let response = self.client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| WarpError::ConfigError(format!("Get reviews request failed: {}", e)))?;
-
-        self.handle_response(response).await
+        self.query_first(item_id, |registry| async move {
+            let url = Self::url(
+                registry,
+                &format!("items/{}/reviews?page={}", item_id, page),
+            );
+            let response = self
+                .authorize(registry, self.client.get(url))
+                .await?
+                .send()
+                .await
+                .map_err(|e| WarpError::network("Get reviews request failed", e))?;
+            self.handle_response(response).await
+        })
+        .await
     }
 
-    pub async fn download_item(&self, item_id: &str) -> Result<Vec<u8>, WarpError> {
-        let url = format!("{}/items/{}/download", self.base_url, item_id);
-        
-        let mut request = self.client.get(&url);
-        
-        if let Some(api_key) = &self.api_key {
-            request = request.header("Authorization", format!("Bearer {}", api_key));
+    // Packages go through the shared DownloadManager for its limits and
+    // resume; `registry` is the one the item was listed by
+    pub async fn download_request(
+        &self,
+        item_id: &str,
+        version: &str,
+        registry: &str,
+        dest: &Path,
+    ) -> Result<DownloadRequest, WarpError> {
+        if !registry::may_serve(&self.registries, registry, item_id) {
+            return Err(WarpError::Validation(format!(
+                "{} can't be installed from registry {}",
+                item_id, registry
+            )));
         }
-        
-        let response = request
+        let registry = self.registry(registry)?;
+        let url = Self::url(
+            registry,
+            &format!("items/{}/download?version={}", item_id, version),
+        );
+        let request = DownloadRequest::new(url, dest);
+        Ok(match self.token(registry).await? {
+            Some(token) => request.bearer(token),
+            None => request,
+        })
+    }
+
+    pub async fn submit_rating(
+        &self,
+        item_id: &str,
+        registry: &str,
+        draft: &moderation::ReviewDraft,
+    ) -> Result<(), WarpError> {
+        let registry = self.registry(registry)?;
+        let url = Self::url(registry, &format!("items/{}/reviews", item_id));
+
+        let payload = serde_json::json!({
+            "rating": draft.rating,
+            "title": draft.title,
+            "review": draft.body
+        });
+
+        let response = self
+            .authorize(registry, self.client.post(&url).json(&payload))
+            .await?
             .send()
             .await
-            .map_err(|e| WarpError::ConfigError(format!("Download request failed: {}", e)))?;
+            .map_err(|e| WarpError::network("Rating submission failed", e))?;
 
         if response.status().is_success() {
-            response.bytes().await
-                .map(|b| b.to_vec())
-                .map_err(|e| WarpError::ConfigError(format!("Failed to read download: {}", e)))
+            Ok(())
         } else {
-            Err(WarpError::ConfigError(format!("Download failed with status: {}", response.status())))
+            Err(WarpError::api(
+                Some(response.status().as_u16()),
+                "Rating submission failed",
+            ))
         }
     }
 
-    pub async fn submit_rating(&self, item_id: &str, rating: u8, review: Option<String>) -> Result<(), WarpError> {
-        let url = format!("{}/items/{}/reviews", self.base_url, item_id);
-        
+    pub async fn report_review(
+        &self,
+        item_id: &str,
+        registry: &str,
+        review_id: &str,
+        reason: moderation::ReportReason,
+        note: Option<String>,
+    ) -> Result<(), WarpError> {
+        let registry = self.registry(registry)?;
+        let url = Self::url(
+            registry,
+            &format!("items/{}/reviews/{}/report", item_id, review_id),
+        );
+
         let payload = serde_json::json!({
-            "rating": rating,
-            "review": review
+            "reason": reason,
+            "note": note
         });
-        
-        let mut request = self.client.post(&url).json(&payload);
-        
-        if let Some(api_key) = &self.api_key {
-            request = request.header("Authorization", format!("Bearer {}", api_key));
-        }
-        
-        let response = request
+
+        let response = self
+            .authorize(registry, self.client.post(&url).json(&payload))
+            .await?
             .send()
             .await
-            .map_err(|e| WarpError::ConfigError(format!("Rating submission failed: {}", e)))?;
+            .map_err(|e| WarpError::network("Review report failed", e))?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            Err(WarpError::ConfigError(format!("Rating submission failed with status: {}", response.status())))
+            Err(WarpError::api(
+                Some(response.status().as_u16()),
+                "Review report failed",
+            ))
         }
     }
 
     pub async fn get_featured_items(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
-        let url = format!("{}/featured", self.base_url);
-        
-        let response = self.client
-            .get(&url)
-            .send()
+        self.listing("featured", "Featured items request failed")
             .await
-            .map_err(|e| WarpError::ConfigError(format!("Featured items request failed: {}", e)))?;
-
-        self.handle_response(response).await
     }
 
     pub async fn get_trending_items(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
-        let url = format!("{}/trending", self.base_url);
-        
-        let response = self.client
-            .get(&url)
-            .send()
+        self.listing("trending", "Trending items request failed")
             .await
-            .map_err(|e| WarpError::ConfigError(format!("Trending items request failed: {}", e)))?;
+    }
+
+    async fn listing(&self, path: &str, failure: &str) -> Result<Vec<MarketplaceItem>, WarpError> {
+        let results = self
+            .query_all(|registry| async move {
+                let response = self
+                    .authorize(registry, self.client.get(Self::url(registry, path)))
+                    .await?
+                    .send()
+                    .await
+                    .map_err(|e| WarpError::network(failure, e))?;
+                self.handle_response::<Vec<MarketplaceItem>>(response).await
+            })
+            .await?;
+        Ok(registry::merge(&self.registries, results))
+    }
+
+    // Asks every registry at once, best first in the result. One registry
+    // being down or unauthorized only drops its part; it's an error when
+    // none of them answered.
+    async fn query_all<'a, T, F, Fut>(&'a self, fetch: F) -> Result<Vec<(String, T)>, WarpError>
+    where
+        F: Fn(&'a RegistryConfig) -> Fut,
+        Fut: std::future::Future<Output = Result<T, WarpError>>,
+    {
+        let answers = future::join_all(self.registries.iter().map(|registry| {
+            let fetched = fetch(registry);
+            async move { (registry.name.clone(), fetched.await) }
+        }))
+        .await;
 
-        self.handle_response(response).await
+        let mut results = Vec::new();
+        let mut first_error = None;
+        for (name, answer) in answers {
+            match answer {
+                Ok(result) => results.push((name, result)),
+                Err(e) => {
+                    log::warn!("Marketplace registry {} failed: {}", name, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match (results.is_empty(), first_error) {
+            (true, Some(e)) => Err(e),
+            (true, None) => Err(WarpError::Config(
+                "No marketplace registry is enabled".to_string(),
+            )),
+            _ => Ok(results),
+        }
+    }
+
+    // Tries the registries that may serve the item, best first, moving on
+    // when one doesn't have it
+    async fn query_first<'a, T, F, Fut>(&'a self, item_id: &str, fetch: F) -> Result<T, WarpError>
+    where
+        F: Fn(&'a RegistryConfig) -> Fut,
+        Fut: std::future::Future<Output = Result<T, WarpError>>,
+    {
+        let mut last_error = None;
+        for registry in registry::candidates(&self.registries, item_id) {
+            match fetch(registry).await {
+                Err(
+                    e @ WarpError::Api {
+                        status: Some(404), ..
+                    },
+                ) => last_error = Some(e),
+                result => return result,
+            }
+        }
+        Err(last_error.unwrap_or_else(|| WarpError::not_found("marketplace item", item_id)))
     }
 
-    async fn handle_response<T: for<'de> Deserialize<'de>>(&self, response: Response) -> Result<T, WarpError> {
+    async fn handle_response<T: for<'de> Deserialize<'de>>(
+        &self,
+        response: Response,
+    ) -> Result<T, WarpError> {
         if response.status().is_success() {
-            response.json().await
-                .map_err(|e| WarpError::ConfigError(format!("Failed to parse response: {}", e)))
+            response
+                .json()
+                .await
+                .map_err(|e| WarpError::network("Failed to parse response", e))
         } else {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            Err(WarpError::ConfigError(format!("Request failed with status {}: {}", status, text)))
+            Err(WarpError::api(Some(status.as_u16()), text))
         }
     }
 }
@@ -0,0 +1,452 @@
+use super::package_manager::InstalledPackage;
+use super::{ItemType, MarketplaceItem};
+use crate::error::WarpError;
+use semver::{Version, VersionReq};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+// A dependency as plugins declare it: `publisher/name@^1.2`, or just the
+// id for any version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+    pub id: String,
+    pub version: VersionReq,
+}
+
+impl Requirement {
+    pub fn parse(spec: &str) -> Result<Self, WarpError> {
+        let (id, version) = spec.split_once('@').unwrap_or((spec, "*"));
+        let version = VersionReq::parse(version.trim())
+            .map_err(|e| WarpError::Validation(format!("Bad dependency {}: {}", spec, e)))?;
+        Ok(Self {
+            id: id.trim().to_string(),
+            version,
+        })
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.id, self.version)
+    }
+}
+
+pub fn declared(item: &MarketplaceItem) -> &[String] {
+    match &item.item_type {
+        ItemType::Plugin(metadata) => &metadata.dependencies,
+        _ => &[],
+    }
+}
+
+fn parse_all(specs: &[String]) -> Result<Vec<Requirement>, WarpError> {
+    specs.iter().map(|spec| Requirement::parse(spec)).collect()
+}
+
+fn version(id: &str, version: &str) -> Result<Version, WarpError> {
+    Version::parse(version)
+        .map_err(|e| WarpError::Validation(format!("{} has a bad version {}: {}", id, version, e)))
+}
+
+// Installed packages that declare a dependency on `id`
+pub fn dependents(installed: &[InstalledPackage], id: &str) -> Vec<String> {
+    installed
+        .iter()
+        .filter(|package| {
+            package
+                .dependencies
+                .iter()
+                .filter_map(|spec| Requirement::parse(spec).ok())
+                .any(|requirement| requirement.id == id)
+        })
+        .map(|package| package.id.clone())
+        .collect()
+}
+
+// Items to install, dependencies before the packages that need them and the
+// requested item last. Installed packages that already fit are left out.
+#[derive(Debug)]
+pub struct Plan {
+    pub install: Vec<MarketplaceItem>,
+}
+
+// One version picked for a package; `item` is None when the installed copy
+// is kept
+#[derive(Clone)]
+struct Choice<'a> {
+    version: Version,
+    requirements: Vec<Requirement>,
+    item: Option<&'a MarketplaceItem>,
+}
+
+struct Resolver<'a> {
+    root: &'a str,
+    catalog: &'a HashMap<String, Vec<MarketplaceItem>>,
+    installed: HashMap<&'a str, Choice<'a>>,
+}
+
+// Picks a version of `root` and everything it needs, transitively. `catalog`
+// holds every published version of the packages involved; already installed
+// packages are kept when they satisfy the constraints and upgraded when they
+// don't, as long as whatever depends on them still fits.
+pub fn resolve(
+    root: &str,
+    catalog: &HashMap<String, Vec<MarketplaceItem>>,
+    installed: &[InstalledPackage],
+) -> Result<Plan, WarpError> {
+    let mut kept = HashMap::new();
+    for package in installed {
+        // Packages from before dependencies were tracked may not parse; they
+        // neither constrain nor get kept
+        match (
+            version(&package.id, &package.version),
+            parse_all(&package.dependencies),
+        ) {
+            (Ok(version), Ok(requirements)) => {
+                kept.insert(
+                    package.id.as_str(),
+                    Choice {
+                        version,
+                        requirements,
+                        item: None,
+                    },
+                );
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                log::warn!("Ignoring installed {} when resolving: {}", package.id, e)
+            }
+        }
+    }
+    let resolver = Resolver {
+        root,
+        catalog,
+        installed: kept,
+    };
+
+    let mut chosen = BTreeMap::new();
+    resolver
+        .solve(&mut chosen, vec![root.to_string()])
+        .map_err(WarpError::Validation)?;
+    let order = install_order(&chosen)?;
+    Ok(Plan {
+        install: order
+            .into_iter()
+            .filter_map(|id| chosen[&id].item.cloned())
+            .collect(),
+    })
+}
+
+impl<'a> Resolver<'a> {
+    // Depth-first with backtracking: the newest version that fits is tried
+    // first, and a later dead end undoes it. Err carries the conflict that
+    // ended the search.
+    fn solve(
+        &self,
+        chosen: &mut BTreeMap<String, Choice<'a>>,
+        pending: Vec<String>,
+    ) -> Result<(), String> {
+        let Some((id, rest)) = pending.split_first() else {
+            return Ok(());
+        };
+        if chosen.contains_key(id) {
+            return self.solve(chosen, rest.to_vec());
+        }
+
+        let constraints = self.constraints(chosen, id);
+        let options = self.options(id, &constraints)?;
+        let mut conflict = None;
+        for choice in options {
+            // What this version needs must agree with what's already picked
+            if let Some(requirement) = choice.requirements.iter().find(|requirement| {
+                chosen
+                    .get(&requirement.id)
+                    .is_some_and(|picked| !requirement.version.matches(&picked.version))
+            }) {
+                conflict.get_or_insert_with(|| {
+                    format!(
+                        "{} {} needs {}, which conflicts with {} {}",
+                        id,
+                        choice.version,
+                        requirement,
+                        requirement.id,
+                        chosen[&requirement.id].version
+                    )
+                });
+                continue;
+            }
+
+            let mut next = rest.to_vec();
+            next.extend(
+                choice
+                    .requirements
+                    .iter()
+                    .map(|requirement| requirement.id.clone()),
+            );
+            chosen.insert(id.clone(), choice);
+            match self.solve(chosen, next) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    chosen.remove(id);
+                    conflict.get_or_insert(e);
+                }
+            }
+        }
+        Err(conflict.unwrap_or_else(|| format!("No version of {} fits", id)))
+    }
+
+    // Every requirement on `id` from picked packages and from installed ones
+    // that stay as they are, with who asked
+    fn constraints(
+        &self,
+        chosen: &BTreeMap<String, Choice<'a>>,
+        id: &str,
+    ) -> Vec<(String, Requirement)> {
+        let picked = chosen
+            .iter()
+            .map(|(owner, choice)| (owner.as_str(), choice));
+        let kept = self
+            .installed
+            .iter()
+            .filter(|(owner, _)| !chosen.contains_key(**owner))
+            .map(|(owner, choice)| (*owner, choice));
+        let mut constraints: Vec<_> = picked
+            .chain(kept)
+            .flat_map(|(owner, choice)| {
+                choice
+                    .requirements
+                    .iter()
+                    .filter(|requirement| requirement.id == id)
+                    .map(move |requirement| {
+                        (format!("{} {}", owner, choice.version), requirement.clone())
+                    })
+            })
+            .collect();
+        constraints.sort_by(|a, b| a.0.cmp(&b.0));
+        constraints
+    }
+
+    // Versions of `id` that meet every constraint, best first: the
+    // installed copy (except for the package being installed, which gets the
+    // newest), then published versions from newest to oldest
+    fn options(
+        &self,
+        id: &str,
+        constraints: &[(String, Requirement)],
+    ) -> Result<Vec<Choice<'a>>, String> {
+        let fits = |version: &Version| {
+            constraints
+                .iter()
+                .all(|(_, requirement)| requirement.version.matches(version))
+        };
+        let mut options = Vec::new();
+        let installed = self.installed.get(id).filter(|_| id != self.root);
+        if let Some(installed) = installed.filter(|installed| fits(&installed.version)) {
+            options.push(installed.clone());
+        }
+
+        let mut published = Vec::new();
+        for item in self.catalog.get(id).map(Vec::as_slice).unwrap_or_default() {
+            let version = version(id, &item.version).map_err(|e| e.to_string())?;
+            if fits(&version) && installed.is_none_or(|installed| installed.version != version) {
+                let requirements = parse_all(declared(item)).map_err(|e| e.to_string())?;
+                published.push(Choice {
+                    version,
+                    requirements,
+                    item: Some(item),
+                });
+            }
+        }
+        published.sort_by(|a, b| b.version.cmp(&a.version));
+        options.extend(published);
+
+        if options.is_empty() {
+            return Err(match constraints {
+                _ if !self.catalog.contains_key(id) && !self.installed.contains_key(id) => {
+                    format!("{} isn't published in any registry", id)
+                }
+                [] => format!("{} has no installable version", id),
+                _ => format!(
+                    "No version of {} satisfies {}",
+                    id,
+                    constraints
+                        .iter()
+                        .map(|(owner, requirement)| format!(
+                            "{} (from {})",
+                            requirement.version, owner
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(" and ")
+                ),
+            });
+        }
+        Ok(options)
+    }
+}
+
+// Dependencies before dependents; a cycle can't be installed in any order
+fn install_order(chosen: &BTreeMap<String, Choice>) -> Result<Vec<String>, WarpError> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        id: &str,
+        chosen: &BTreeMap<String, Choice>,
+        marks: &mut HashMap<String, Mark>,
+        path: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> Result<(), WarpError> {
+        match marks.get(id) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = path.iter().position(|p| p == id).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(id.to_string());
+                return Err(WarpError::Validation(format!(
+                    "Dependency cycle: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+            None => {}
+        }
+        marks.insert(id.to_string(), Mark::Visiting);
+        path.push(id.to_string());
+        for requirement in &chosen[id].requirements {
+            if chosen.contains_key(&requirement.id) {
+                visit(&requirement.id, chosen, marks, path, order)?;
+            }
+        }
+        path.pop();
+        marks.insert(id.to_string(), Mark::Done);
+        order.push(id.to_string());
+        Ok(())
+    }
+
+    let mut marks = HashMap::new();
+    let mut order = Vec::new();
+    for id in chosen.keys() {
+        visit(id, chosen, &mut marks, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(id: &str, version: &str, dependencies: &[&str]) -> MarketplaceItem {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "description": "",
+            "category": "Plugins",
+            "item_type": { "Plugin": {
+                "entry_point": "plugin.wasm",
+                "permissions": [],
+                "dependencies": dependencies,
+                "supported_platforms": [],
+                "api_version": "1"
+            } },
+            "version": version,
+            "author": { "id": "a", "username": "a", "display_name": "A", "email": null, "website": null, "verified": false, "reputation": 0 },
+            "tags": [],
+            "rating": { "average": 0.0, "count": 0, "distribution": {} },
+            "downloads": 0,
+            "price": "Free",
+            "license": { "name": "MIT", "url": null, "open_source": true },
+            "compatibility": { "min_warp_version": "1.0.0", "max_warp_version": null, "platforms": [], "architectures": [] },
+            "screenshots": [],
+            "readme": "",
+            "changelog": "",
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+            "verified": false,
+            "featured": false
+        }))
+        .unwrap()
+    }
+
+    fn catalog(items: Vec<MarketplaceItem>) -> HashMap<String, Vec<MarketplaceItem>> {
+        let mut catalog: HashMap<String, Vec<MarketplaceItem>> = HashMap::new();
+        for item in items {
+            catalog.entry(item.id.clone()).or_default().push(item);
+        }
+        catalog
+    }
+
+    fn installed(id: &str, version: &str, dependencies: &[&str]) -> InstalledPackage {
+        InstalledPackage {
+            id: id.to_string(),
+            name: id.to_string(),
+            version: version.to_string(),
+            installed_at: chrono::Utc::now(),
+            auto_update: true,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            install_path: std::path::PathBuf::new(),
+            registry: None,
+            channel: None,
+            pinned: None,
+            item: None,
+            history: Vec::new(),
+        }
+    }
+
+    fn planned(plan: &Plan) -> Vec<String> {
+        plan.install
+            .iter()
+            .map(|item| format!("{}@{}", item.id, item.version))
+            .collect()
+    }
+
+    #[test]
+    fn test_backtracks_to_versions_that_agree() {
+        // The newest ui wants log 2, which tools can't use
+        let catalog = catalog(vec![
+            plugin("a/tools", "1.0.0", &["a/ui@^1", "a/log@^1.2"]),
+            plugin("a/ui", "1.1.0", &["a/log@^2"]),
+            plugin("a/ui", "1.0.0", &["a/log@>=1.0"]),
+            plugin("a/log", "1.2.5", &[]),
+            plugin("a/log", "1.3.0", &[]),
+            plugin("a/log", "2.0.0", &[]),
+        ]);
+        let plan = resolve("a/tools", &catalog, &[]).unwrap();
+        assert_eq!(
+            planned(&plan),
+            vec!["a/log@1.3.0", "a/ui@1.0.0", "a/tools@1.0.0"]
+        );
+
+        // An installed log that fits is kept
+        let plan = resolve("a/tools", &catalog, &[installed("a/log", "1.2.5", &[])]).unwrap();
+        assert_eq!(planned(&plan), vec!["a/ui@1.0.0", "a/tools@1.0.0"]);
+    }
+
+    #[test]
+    fn test_reports_conflicts_and_cycles() {
+        let catalog = catalog(vec![
+            plugin("a/tools", "1.0.0", &["a/log@^2"]),
+            plugin("a/log", "1.0.0", &[]),
+            plugin("a/log", "2.0.0", &[]),
+            plugin("a/x", "1.0.0", &["a/y"]),
+            plugin("a/y", "1.0.0", &["a/x@1"]),
+        ]);
+        // Something installed pins log to 1.x
+        let error = resolve(
+            "a/tools",
+            &catalog,
+            &[installed("a/app", "1.0.0", &["a/log@^1"])],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains(
+            "No version of a/log satisfies ^1 (from a/app 1.0.0) and ^2 (from a/tools 1.0.0)"
+        ));
+
+        let error = resolve("a/x", &catalog, &[]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Dependency cycle: a/x -> a/y -> a/x"));
+
+        let installed = [installed("a/app", "1.0.0", &["a/log@^1"])];
+        assert_eq!(dependents(&installed, "a/log"), vec!["a/app".to_string()]);
+    }
+}
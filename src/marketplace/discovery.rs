@@ -44,36 +44,38 @@ impl DiscoveryEngine {
 
     pub async fn get_recommendations(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
         let cache_key = "general_recommendations".to_string();
-        
+
         if let Some(cached) = self.recommendation_cache.get(&cache_key) {
             return Ok(cached.clone());
         }
-        
+
         let mut recommendations = Vec::new();
-        
+
         // Get personalized recommendations based on user preferences
         recommendations.extend(self.get_category_recommendations().await?);
         recommendations.extend(self.get_usage_based_recommendations().await?);
         recommendations.extend(self.get_trending_recommendations().await?);
-        
+
         // Sort by relevance score
         recommendations.sort_by(|a, b| {
             let score_a = self.calculate_relevance_score(a);
             let score_b = self.calculate_relevance_score(b);
-            score_b.partial_cmp(&score_a).unwrap_or(std::cmp::Ordering::Equal)
+            score_b
+                .partial_cmp(&score_a)
+                .unwrap_or(std::cmp::Ordering::Equal)
         });
-        
+
         // Limit to top 20 recommendations
         recommendations.truncate(20);
-        
+
         Ok(recommendations)
     }
 
     async fn get_category_recommendations(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
         let mut recommendations = Vec::new();
-        
+
         for category in &self.user_preferences.preferred_categories {
-            let query = SearchQuery {
+            let _query = SearchQuery {
                 query: None,
                 category: Some(category.clone()),
                 tags: vec![],
@@ -83,18 +85,18 @@ impl DiscoveryEngine {
                 page: 1,
                 per_page: 5,
             };
-            
+
             // This would call the marketplace API
             // For now, create mock recommendations
             recommendations.extend(self.create_mock_recommendations(category).await?);
         }
-        
+
         Ok(recommendations)
     }
 
     async fn get_usage_based_recommendations(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
         let mut recommendations = Vec::new();
-        
+
         // Analyze most used commands and suggest relevant plugins
         for (command, _usage_count) in &self.usage_analytics.most_used_commands {
             if command.starts_with("git") {
@@ -105,7 +107,7 @@ impl DiscoveryEngine {
                 recommendations.extend(self.get_nodejs_related_items().await?);
             }
         }
-        
+
         Ok(recommendations)
     }
 
@@ -115,9 +117,12 @@ impl DiscoveryEngine {
         Ok(vec![])
     }
 
-    async fn create_mock_recommendations(&self, category: &ItemCategory) -> Result<Vec<MarketplaceItem>, WarpError> {
+    async fn create_mock_recommendations(
+        &self,
+        category: &ItemCategory,
+    ) -> Result<Vec<MarketplaceItem>, WarpError> {
         let mut items = Vec::new();
-        
+
         match category {
             ItemCategory::Themes => {
                 items.push(MarketplaceItem {
@@ -142,11 +147,21 @@ impl DiscoveryEngine {
                         verified: true,
                         reputation: 95,
                     },
-                    tags: vec!["pastel".to_string(), "dark".to_string(), "light".to_string()],
+                    tags: vec![
+                        "pastel".to_string(),
+                        "dark".to_string(),
+                        "light".to_string(),
+                    ],
                     rating: Rating {
                         average: 4.8,
                         count: 1250,
-                        distribution: HashMap::from([(5, 1000), (4, 200), (3, 30), (2, 15), (1, 5)]),
+                        distribution: HashMap::from([
+                            (5, 1000),
+                            (4, 200),
+                            (3, 30),
+                            (2, 15),
+                            (1, 5),
+                        ]),
                     },
                     downloads: 15000,
                     price: Price::Free,
@@ -158,23 +173,31 @@ impl DiscoveryEngine {
                     compatibility: Compatibility {
                         min_warp_version: "1.0.0".to_string(),
                         max_warp_version: None,
-                        platforms: vec!["macos".to_string(), "linux".to_string(), "windows".to_string()],
+                        platforms: vec![
+                            "macos".to_string(),
+                            "linux".to_string(),
+                            "windows".to_string(),
+                        ],
                         architectures: vec!["x86_64".to_string(), "arm64".to_string()],
                     },
                     screenshots: vec!["screenshot1.png".to_string()],
-                    readme: "# Catppuccin Theme\n\nA soothing pastel theme for Warp terminal.".to_string(),
+                    readme: "# Catppuccin Theme\n\nA soothing pastel theme for Warp terminal."
+                        .to_string(),
                     changelog: "## v1.0.0\n- Initial release".to_string(),
                     created_at: chrono::Utc::now() - chrono::Duration::days(30),
                     updated_at: chrono::Utc::now() - chrono::Duration::days(5),
                     verified: true,
                     featured: true,
+                    sha256: None,
+                    registry: None,
                 });
             }
             ItemCategory::Plugins => {
                 items.push(MarketplaceItem {
                     id: "git-enhanced".to_string(),
                     name: "Git Enhanced".to_string(),
-                    description: "Enhanced Git integration with visual diff and branch management".to_string(),
+                    description: "Enhanced Git integration with visual diff and branch management"
+                        .to_string(),
                     category: ItemCategory::Plugins,
                     item_type: ItemType::Plugin(PluginMetadata {
                         entry_point: "git_enhanced.wasm".to_string(),
@@ -193,14 +216,21 @@ impl DiscoveryEngine {
                         verified: true,
                         reputation: 88,
                     },
-                    tags: vec!["git".to_string(), "vcs".to_string(), "productivity".to_string()],
+                    tags: vec![
+                        "git".to_string(),
+                        "vcs".to_string(),
+                        "productivity".to_string(),
+                    ],
                     rating: Rating {
                         average: 4.6,
                         count: 890,
                         distribution: HashMap::from([(5, 650), (4, 180), (3, 40), (2, 15), (1, 5)]),
                     },
                     downloads: 8500,
-                    price: Price::Paid { amount: 999, currency: "USD".to_string() },
+                    price: Price::Paid {
+                        amount: 999,
+                        currency: "USD".to_string(),
+                    },
                     license: License {
                         name: "Commercial".to_string(),
                         url: None,
@@ -213,17 +243,22 @@ impl DiscoveryEngine {
                         architectures: vec!["x86_64".to_string(), "arm64".to_string()],
                     },
                     screenshots: vec!["plugin_screenshot1.png".to_string()],
-                    readme: "# Git Enhanced Plugin\n\nPowerful Git integration for Warp.".to_string(),
-                    changelog: "## v2.1.0\n- Added visual diff support\n- Improved branch management".to_string(),
+                    readme: "# Git Enhanced Plugin\n\nPowerful Git integration for Warp."
+                        .to_string(),
+                    changelog:
+                        "## v2.1.0\n- Added visual diff support\n- Improved branch management"
+                            .to_string(),
                     created_at: chrono::Utc::now() - chrono::Duration::days(60),
                     updated_at: chrono::Utc::now() - chrono::Duration::days(10),
                     verified: true,
                     featured: false,
+                    sha256: None,
+                    registry: None,
                 });
             }
             _ => {}
         }
-        
+
         Ok(items)
     }
 
@@ -244,44 +279,51 @@ impl DiscoveryEngine {
 
     fn calculate_relevance_score(&self, item: &MarketplaceItem) -> f32 {
         let mut score = 0.0;
-        
+
         // Base score from rating and downloads
         score += item.rating.average * 0.3;
         score += (item.downloads as f32).log10() * 0.2;
-        
+
         // Category preference bonus
-        if self.user_preferences.preferred_categories.contains(&item.category) {
+        if self
+            .user_preferences
+            .preferred_categories
+            .contains(&item.category)
+        {
             score += 2.0;
         }
-        
+
         // Tag preference bonus
         for tag in &item.tags {
             if self.user_preferences.preferred_tags.contains(tag) {
                 score += 0.5;
             }
         }
-        
+
         // Price preference
         match (&item.price, &self.user_preferences.price_preference) {
             (Price::Free, PriceFilter::Free) => score += 1.0,
             (Price::Paid { .. }, PriceFilter::Paid) => score += 0.5,
             _ => {}
         }
-        
+
         // Verified bonus
         if item.verified {
             score += 0.5;
         }
-        
+
         // Featured bonus
         if item.featured {
             score += 1.0;
         }
-        
+
         score
     }
 
-    pub async fn update_user_preferences(&mut self, preferences: UserPreferences) -> Result<(), WarpError> {
+    pub async fn update_user_preferences(
+        &mut self,
+        preferences: UserPreferences,
+    ) -> Result<(), WarpError> {
         self.user_preferences = preferences;
         // Clear cache to force regeneration with new preferences
         self.recommendation_cache.clear();
@@ -289,7 +331,11 @@ impl DiscoveryEngine {
     }
 
     pub async fn track_usage(&mut self, command: &str) -> Result<(), WarpError> {
-        *self.usage_analytics.most_used_commands.entry(command.to_string()).or_insert(0) += 1;
+        *self
+            .usage_analytics
+            .most_used_commands
+            .entry(command.to_string())
+            .or_insert(0) += 1;
         Ok(())
     }
 }
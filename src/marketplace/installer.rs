@@ -1,71 +1,113 @@
 use super::*;
+use crate::download::{DownloadManager, Progress};
 use crate::error::WarpError;
+use std::io::Write;
 use std::path::PathBuf;
 use tokio::fs;
 
 pub struct Installer {
+    client: Arc<client::MarketplaceClient>,
+    downloads: Arc<DownloadManager>,
     download_cache: PathBuf,
     temp_directory: PathBuf,
 }
 
 impl Installer {
-    pub async fn new() -> Result<Self, WarpError> {
+    pub async fn new(
+        client: Arc<client::MarketplaceClient>,
+        downloads: Arc<DownloadManager>,
+    ) -> Result<Self, WarpError> {
         let config_dir = dirs::config_dir()
-            .ok_or_else(|| WarpError::ConfigError("Could not find config directory".to_string()))?;
-        
+            .ok_or_else(|| WarpError::Config("Could not find config directory".to_string()))?;
+
         let download_cache = config_dir.join("warp/cache/downloads");
         let temp_directory = config_dir.join("warp/temp");
-        
+
         fs::create_dir_all(&download_cache).await?;
         fs::create_dir_all(&temp_directory).await?;
-        
+
         Ok(Self {
+            client,
+            downloads,
             download_cache,
             temp_directory,
         })
     }
 
-    pub async fn install(&self, item_id: &str) -> Result<(), WarpError> {
-        println!("🔄 Installing {}...", item_id);
-        
+    pub async fn install(&self, item: &MarketplaceItem) -> Result<(), WarpError> {
+        let item_id = item.id.as_str();
+        let registry = item.registry.as_deref().unwrap_or(registry::OFFICIAL);
+        println!(
+            "🔄 Installing {} {} from {}...",
+            item_id, item.version, registry
+        );
+
         // Download the package
-        let package_data = self.download_package(item_id).await?;
-        
+        let package_data = self.download_package(item, registry).await?;
+
         // Verify package integrity
         self.verify_package(&package_data).await?;
-        
+
         // Extract and install
         self.extract_and_install(item_id, package_data).await?;
-        
+
         println!("✅ Successfully installed {}", item_id);
         Ok(())
     }
 
     pub async fn uninstall(&self, item_id: &str) -> Result<(), WarpError> {
         println!("🗑️ Uninstalling {}...", item_id);
-        
+
         // Remove package files
         self.remove_package_files(item_id).await?;
-        
+
         println!("✅ Successfully uninstalled {}", item_id);
         Ok(())
     }
 
-    async fn download_package(&self, item_id: &str) -> Result<Vec<u8>, WarpError> {
-        // Check cache first
-        let cache_file = self.download_cache.join(format!("{}.pkg", item_id));
+    async fn download_package(
+        &self,
+        item: &MarketplaceItem,
+        registry: &str,
+    ) -> Result<Vec<u8>, WarpError> {
+        // Check cache first; a rollback finds the earlier version there
+        let cache_file = self.cache_file(item, registry);
         if cache_file.exists() {
             return Ok(fs::read(&cache_file).await?);
         }
-        
-        // Download from marketplace (mock implementation)
+
+        // Download from marketplace into the cache; an interrupted download resumes next time
         println!("📥 Downloading package...");
-        let package_data = vec![0u8; 1024]; // Mock package data
-        
-        // Cache the download
-        fs::write(&cache_file, &package_data).await?;
-        
-        Ok(package_data)
+        let mut request = self
+            .client
+            .download_request(&item.id, &item.version, registry, &cache_file)
+            .await?;
+        if let Some(sha256) = &item.sha256 {
+            request = request.sha256(sha256);
+        }
+        self.downloads.download(&request, print_progress).await?;
+        println!();
+
+        Ok(fs::read(&cache_file).await?)
+    }
+
+    // Each registry has its own cache, as the same id can be a different
+    // package elsewhere, with an archive per version
+    fn cache_file(&self, item: &MarketplaceItem, registry: &str) -> PathBuf {
+        self.download_cache
+            .join(registry)
+            .join(&item.id)
+            .join(format!("{}.pkg", item.version))
+    }
+
+    // Drops the cached archive of a version that's no longer kept
+    pub async fn prune(&self, item: &MarketplaceItem) -> Result<(), WarpError> {
+        let cache_file =
+            self.cache_file(item, item.registry.as_deref().unwrap_or(registry::OFFICIAL));
+        if cache_file.exists() {
+            fs::remove_file(&cache_file).await?;
+        }
+        Ok(())
     }
 
     async fn verify_package(&self, _package_data: &[u8]) -> Result<(), WarpError> {
@@ -78,33 +120,52 @@ impl Installer {
         Ok(())
     }
 
-    async fn extract_and_install(&self, item_id: &str, package_data: Vec<u8>) -> Result<(), WarpError> {
+    async fn extract_and_install(
+        &self,
+        item_id: &str,
+        package_data: Vec<u8>,
+    ) -> Result<(), WarpError> {
         println!("📦 Extracting package...");
-        
+
         let temp_dir = self.temp_directory.join(item_id);
         fs::create_dir_all(&temp_dir).await?;
-        
+
         // Mock extraction
         let package_file = temp_dir.join("package.data");
         fs::write(&package_file, package_data).await?;
-        
+
         println!("🔧 Installing files...");
         // Mock installation process
-        
+
         // Clean up temp files
         fs::remove_dir_all(&temp_dir).await?;
-        
+
         Ok(())
     }
 
     async fn remove_package_files(&self, item_id: &str) -> Result<(), WarpError> {
-        // Remove from cache
-        let cache_file = self.download_cache.join(format!("{}.pkg", item_id));
-        if cache_file.exists() {
-            fs::remove_file(&cache_file).await?;
+        // Remove from every registry's cache
+        let mut registries = fs::read_dir(&self.download_cache).await?;
+        while let Some(entry) = registries.next_entry().await? {
+            let cached_versions = entry.path().join(item_id);
+            if cached_versions.exists() {
+                fs::remove_dir_all(&cached_versions).await?;
+            }
         }
-        
+
         // Remove installed files (this would be more comprehensive in real implementation)
         Ok(())
     }
 }
+
+fn print_progress(progress: Progress) {
+    match progress.percent() {
+        Some(percent) => print!(
+            "\r   {}% of {} KB",
+            percent,
+            progress.total.unwrap_or_default() / 1024
+        ),
+        None => print!("\r   {} KB", progress.downloaded / 1024),
+    }
+    let _ = std::io::stdout().flush();
+}
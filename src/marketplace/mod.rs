@@ -1,17 +1,22 @@
+use crate::config::{OAuthConfig, PluginConfig, UpdateChannel};
+use crate::download::DownloadManager;
+use crate::error::WarpError;
+use crate::plugin_host::{PluginHost, ReloadOutcome};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
 
 pub mod client;
-pub mod package_manager;
-pub mod store;
-pub mod auth;
+pub mod dependencies;
 pub mod discovery;
 pub mod installer;
+pub mod moderation;
+pub mod package_manager;
 pub mod publisher;
+pub mod registry;
 pub mod security;
+pub mod ui;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketplaceItem {
@@ -35,9 +40,13 @@ pub struct MarketplaceItem {
     pub updated_at: chrono::DateTime<chrono::Utc>,
     pub verified: bool,
     pub featured: bool,
+    #[serde(default)]
+    pub sha256: Option<String>, // of the package archive, checked after download
+    #[serde(default)]
+    pub registry: Option<String>, // the registry this copy came from, set by the client
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ItemCategory {
     Themes,
     Plugins,
@@ -128,9 +137,19 @@ pub struct Rating {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Price {
     Free,
-    Paid { amount: u32, currency: String },
-    PayWhatYouWant { suggested: u32, currency: String },
-    Subscription { monthly: u32, yearly: u32, currency: String },
+    Paid {
+        amount: u32,
+        currency: String,
+    },
+    PayWhatYouWant {
+        suggested: u32,
+        currency: String,
+    },
+    Subscription {
+        monthly: u32,
+        yearly: u32,
+        currency: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +178,8 @@ pub struct Review {
     pub helpful_votes: u32,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub verified_purchase: bool,
+    #[serde(default)]
+    pub moderation: moderation::ModerationStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -210,37 +231,55 @@ pub struct SearchFacets {
 pub struct Marketplace {
     client: Arc<client::MarketplaceClient>,
     package_manager: Arc<Mutex<package_manager::PackageManager>>,
-    store: Arc<Mutex<store::LocalStore>>,
-    auth: Arc<Mutex<auth::AuthManager>>,
     discovery: Arc<discovery::DiscoveryEngine>,
     installer: Arc<installer::Installer>,
     publisher: Arc<publisher::Publisher>,
     security: Arc<security::SecurityManager>,
+    channel: UpdateChannel, // general.update_channel, for items without their own
+    plugin_host: Option<Arc<PluginHost>>, // reloads plugins after a rollback
+    review_limiter: Mutex<moderation::SubmissionLimiter>,
+    report_limiter: Mutex<moderation::SubmissionLimiter>,
 }
 
+// How long to hold off after the backend rejects a review or report as
+// too frequent
+const BACKEND_PAUSE: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
 impl Marketplace {
-    pub async fn new() -> Result<Self, WarpError> {
-        let client = Arc::new(client::MarketplaceClient::new().await?);
-        let package_manager = Arc::new(Mutex::new(package_manager::PackageManager::new().await?));
-        let store = Arc::new(Mutex::new(store::LocalStore::new().await?));
-        let auth = Arc::new(Mutex::new(auth::AuthManager::new().await?));
+    pub async fn new(
+        downloads: Arc<DownloadManager>,
+        plugins: &PluginConfig,
+        oauth: &OAuthConfig,
+        channel: UpdateChannel,
+    ) -> Result<Self, WarpError> {
+        let client = Arc::new(client::MarketplaceClient::new(&plugins.registries, oauth).await?);
+        let package_manager = Arc::new(Mutex::new(
+            package_manager::PackageManager::new(plugins.kept_versions).await?,
+        ));
         let discovery = Arc::new(discovery::DiscoveryEngine::new().await?);
-        let installer = Arc::new(installer::Installer::new().await?);
-        let publisher = Arc::new(publisher::Publisher::new().await?);
+        let installer = Arc::new(installer::Installer::new(client.clone(), downloads).await?);
+        let publisher = Arc::new(publisher::Publisher::new(oauth).await?);
         let security = Arc::new(security::SecurityManager::new().await?);
 
         Ok(Self {
             client,
             package_manager,
-            store,
-            auth,
             discovery,
             installer,
             publisher,
             security,
+            channel,
+            plugin_host: None,
+            review_limiter: Mutex::new(moderation::SubmissionLimiter::reviews()),
+            report_limiter: Mutex::new(moderation::SubmissionLimiter::reports()),
         })
     }
 
+    pub fn with_plugin_host(mut self, host: Arc<PluginHost>) -> Self {
+        self.plugin_host = Some(host);
+        self
+    }
+
     pub async fn search(&self, query: SearchQuery) -> Result<SearchResult, WarpError> {
         self.client.search(query).await
     }
@@ -249,70 +288,344 @@ impl Marketplace {
         self.client.get_item(id).await
     }
 
-    pub async fn get_reviews(&self, item_id: &str, page: u32) -> Result<Vec<Review>, WarpError> {
-        self.client.get_reviews(item_id, page).await
+    // Reviews the backend flagged as spam or abuse are left out unless
+    // `show_flagged`; removed ones always are
+    pub async fn get_reviews(
+        &self,
+        item_id: &str,
+        page: u32,
+        show_flagged: bool,
+    ) -> Result<Vec<Review>, WarpError> {
+        let reviews = self.client.get_reviews(item_id, page).await?;
+        Ok(moderation::visible(reviews, show_flagged))
     }
 
+    // Installs the item along with whatever it depends on, dependencies
+    // first. Nothing is downloaded until the whole set resolves.
     pub async fn install_item(&self, item_id: &str) -> Result<(), WarpError> {
+        let plan = self.plan_install(item_id).await?;
+
         // Security check
-        self.security.verify_item(item_id).await?;
-        
+        for item in &plan.install {
+            self.security.verify_item(&item.id).await?;
+        }
+
         // Download and install
-        self.installer.install(item_id).await?;
-        
-        // Update local store
-        let mut store = self.store.lock().await;
-        store.mark_installed(item_id).await?;
-        
+        for item in &plan.install {
+            self.installer.install(item).await?;
+            self.record_install(item).await?;
+        }
+
         Ok(())
     }
 
-    pub async fn uninstall_item(&self, item_id: &str) -> Result<(), WarpError> {
-        self.installer.uninstall(item_id).await?;
-        
-        let mut store = self.store.lock().await;
-        store.mark_uninstalled(item_id).await?;
-        
+    async fn record_install(&self, item: &MarketplaceItem) -> Result<(), WarpError> {
+        let dropped = self
+            .package_manager
+            .lock()
+            .await
+            .record_install(item)
+            .await?;
+        for prior in dropped {
+            self.installer.prune(&prior.item).await?;
+        }
         Ok(())
     }
 
-    pub async fn update_item(&self, item_id: &str) -> Result<(), WarpError> {
+    // The installed package with the earlier versions it can roll back to
+    pub async fn version_history(
+        &self,
+        item_id: &str,
+    ) -> Option<package_manager::InstalledPackage> {
+        self.package_manager
+            .lock()
+            .await
+            .get_package(item_id)
+            .cloned()
+    }
+
+    // Puts back an earlier version, the most recent one unless `version`
+    // says which, from the kept archive. A loaded plugin is reloaded in
+    // place; the outcome says whether its state survived.
+    pub async fn rollback_item(
+        &self,
+        item_id: &str,
+        version: Option<&str>,
+    ) -> Result<Option<ReloadOutcome>, WarpError> {
+        let package = self
+            .version_history(item_id)
+            .await
+            .ok_or_else(|| WarpError::not_found("package", item_id))?;
+        let prior = match version {
+            Some(version) => package
+                .history
+                .iter()
+                .find(|prior| prior.item.version == version),
+            None => package.history.first(),
+        }
+        .ok_or_else(|| {
+            WarpError::Validation(match version {
+                Some(version) => format!("{} {} isn't kept for rollback", item_id, version),
+                None => format!("No earlier version of {} is kept", item_id),
+            })
+        })?;
+
+        self.installer.install(&prior.item).await?;
+        self.record_install(&prior.item).await?;
+
+        let Some(host) = &self.plugin_host else {
+            return Ok(None);
+        };
+        if !matches!(prior.item.item_type, ItemType::Plugin(_))
+            || !host.names().await.iter().any(|name| name == item_id)
+        {
+            return Ok(None);
+        }
+        let outcome = host
+            .reload(item_id, &package.install_path.join("plugin.wasm"))
+            .await?;
+        Ok(Some(outcome))
+    }
+
+    // Fetches every version of the item and of anything its versions could
+    // need, then resolves against what's installed. Installed packages only
+    // see versions on their channel, or their pinned one.
+    pub async fn plan_install(&self, item_id: &str) -> Result<dependencies::Plan, WarpError> {
+        let installed: Vec<_> = self
+            .package_manager
+            .lock()
+            .await
+            .get_installed_packages()
+            .into_iter()
+            .cloned()
+            .collect();
+        let mut catalog: HashMap<String, Vec<MarketplaceItem>> = HashMap::new();
+        let mut pending = vec![item_id.to_string()];
+        while let Some(id) = pending.pop() {
+            if catalog.contains_key(&id) {
+                continue;
+            }
+            let mut versions = match self.client.get_versions(&id).await {
+                Ok(versions) => versions,
+                // Left out of the catalog, so the resolver can say who wanted it
+                Err(WarpError::Api {
+                    status: Some(404), ..
+                }) if id != item_id => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            match installed.iter().find(|package| package.id == id) {
+                Some(package) => {
+                    versions.retain(|item| package.allows(&item.version, self.channel))
+                }
+                None => versions.retain(|item| self.channel.accepts(&item.version)),
+            }
+            for item in &versions {
+                for spec in dependencies::declared(item) {
+                    pending.push(dependencies::Requirement::parse(spec)?.id);
+                }
+            }
+            catalog.insert(id, versions);
+        }
+        catalog.retain(|_, versions| !versions.is_empty());
+
+        dependencies::resolve(item_id, &catalog, &installed)
+    }
+
+    // Refuses to remove a package other installed packages depend on,
+    // unless forced
+    pub async fn uninstall_item(&self, item_id: &str, force: bool) -> Result<(), WarpError> {
         let mut package_manager = self.package_manager.lock().await;
-        package_manager.update_package(item_id).await
+        let installed: Vec<_> = package_manager
+            .get_installed_packages()
+            .into_iter()
+            .cloned()
+            .collect();
+        let dependents = dependencies::dependents(&installed, item_id);
+        if !dependents.is_empty() && !force {
+            return Err(WarpError::Validation(format!(
+                "{} is needed by {}; uninstall those first or force it",
+                item_id,
+                dependents.join(", ")
+            )));
+        }
+
+        self.installer.uninstall(item_id).await?;
+        package_manager.uninstall_package(item_id).await
+    }
+
+    // Moves the item to the newest version on its channel, if there's one
+    pub async fn update_item(&self, item_id: &str) -> Result<(), WarpError> {
+        let package = self
+            .package_manager
+            .lock()
+            .await
+            .get_package(item_id)
+            .cloned()
+            .ok_or_else(|| WarpError::not_found("package", item_id))?;
+        if let Some(pinned) = &package.pinned {
+            return Err(WarpError::Validation(format!(
+                "{} is pinned to {}; unpin it to update",
+                item_id, pinned
+            )));
+        }
+        let versions = self.client.get_versions(item_id).await?;
+        match package_manager::available_update(&package, &versions, self.channel) {
+            Some(_) => self.install_item(item_id).await,
+            None => Ok(()),
+        }
+    }
+
+    // None follows the app-wide channel
+    pub async fn set_item_channel(
+        &self,
+        item_id: &str,
+        channel: Option<UpdateChannel>,
+    ) -> Result<(), WarpError> {
+        self.package_manager
+            .lock()
+            .await
+            .set_channel(item_id, channel)
+            .await
     }
 
+    // Holds the item at a version, installing it first if another one is
+    // there; None lets it update again
+    pub async fn pin_item(&self, item_id: &str, version: Option<&str>) -> Result<(), WarpError> {
+        let previous = {
+            let mut package_manager = self.package_manager.lock().await;
+            let package = package_manager
+                .get_package(item_id)
+                .cloned()
+                .ok_or_else(|| WarpError::not_found("package", item_id))?;
+            package_manager
+                .pin(item_id, version.map(str::to_string))
+                .await?;
+            package
+        };
+        let Some(version) = version.filter(|version| *version != previous.version) else {
+            return Ok(());
+        };
+        if let Err(e) = self.install_item(item_id).await {
+            self.package_manager
+                .lock()
+                .await
+                .pin(item_id, previous.pinned)
+                .await?;
+            return Err(WarpError::Validation(format!(
+                "Couldn't pin {} to {}: {}",
+                item_id, version, e
+            )));
+        }
+        Ok(())
+    }
+
+    // Packages installed before listings were recorded are left out
     pub async fn get_installed_items(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
-        let store = self.store.lock().await;
-        store.get_installed_items().await
+        let package_manager = self.package_manager.lock().await;
+        Ok(package_manager
+            .get_installed_packages()
+            .into_iter()
+            .filter_map(|package| package.item.clone())
+            .collect())
     }
 
-    pub async fn get_updates(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
-        let mut package_manager = self.package_manager.lock().await;
-        package_manager.check_updates().await
+    pub async fn get_updates(&self) -> Result<Vec<package_manager::Update>, WarpError> {
+        let package_manager = self.package_manager.lock().await;
+        package_manager
+            .check_updates(&self.client, self.channel)
+            .await
     }
 
     pub async fn get_recommendations(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
         self.discovery.get_recommendations().await
     }
 
-    pub async fn rate_item(&self, item_id: &str, rating: u8, review: Option<String>) -> Result<(), WarpError> {
-        let auth = self.auth.lock().await;
-        if !auth.is_authenticated() {
-            return Err(WarpError::ConfigError("Authentication required".to_string()));
-        }
-        
-        self.client.submit_rating(item_id, rating, review).await
+    // The client signs the submission in to the item's registry, and fails
+    // with WarpError::Auth when there's no sign-in for it
+    pub async fn rate_item(
+        &self,
+        item_id: &str,
+        draft: &moderation::ReviewDraft,
+    ) -> Result<(), WarpError> {
+        draft.validate()?;
+        self.review_limiter
+            .lock()
+            .await
+            .check(item_id, std::time::Instant::now())?;
+
+        let registry = self.review_registry(item_id).await?;
+        let sent = self.client.submit_rating(item_id, &registry, draft).await;
+        self.limited(&self.review_limiter, item_id, sent).await
     }
 
-    pub async fn publish_item(&self, item: MarketplaceItem, package_data: Vec<u8>) -> Result<String, WarpError> {
-        let auth = self.auth.lock().await;
-        if !auth.is_authenticated() {
-            return Err(WarpError::ConfigError("Authentication required".to_string()));
+    pub async fn report_review(
+        &self,
+        item_id: &str,
+        review_id: &str,
+        reason: moderation::ReportReason,
+        note: Option<String>,
+    ) -> Result<(), WarpError> {
+        self.report_limiter
+            .lock()
+            .await
+            .check(review_id, std::time::Instant::now())?;
+
+        let registry = self.review_registry(item_id).await?;
+        let sent = self
+            .client
+            .report_review(item_id, &registry, review_id, reason, note)
+            .await;
+        self.limited(&self.report_limiter, review_id, sent).await
+    }
+
+    // Ratings and reports go to the registry the item was installed from
+    async fn review_registry(&self, item_id: &str) -> Result<String, WarpError> {
+        Ok(match self.provenance(item_id).await {
+            Some(registry) => registry,
+            None => self
+                .client
+                .get_item(item_id)
+                .await?
+                .registry
+                .unwrap_or_else(|| registry::OFFICIAL.to_string()),
+        })
+    }
+
+    // Counts a sent submission against the limiter, or pauses it when the
+    // backend says 429
+    async fn limited(
+        &self,
+        limiter: &Mutex<moderation::SubmissionLimiter>,
+        target: &str,
+        sent: Result<(), WarpError>,
+    ) -> Result<(), WarpError> {
+        let now = std::time::Instant::now();
+        match &sent {
+            Ok(()) => limiter.lock().await.record(target, now),
+            Err(WarpError::Api {
+                status: Some(429), ..
+            }) => limiter.lock().await.pause(now + BACKEND_PAUSE),
+            Err(_) => {}
         }
-        
+        sent
+    }
+
+    // Which registry an installed item came from
+    pub async fn provenance(&self, item_id: &str) -> Option<String> {
+        let package_manager = self.package_manager.lock().await;
+        package_manager
+            .get_package(item_id)
+            .and_then(|package| package.registry.clone())
+    }
+
+    pub async fn publish_item(
+        &self,
+        item: MarketplaceItem,
+        package_data: Vec<u8>,
+    ) -> Result<String, WarpError> {
         // Security scan
         self.security.scan_package(&package_data).await?;
-        
+
         // Publish
         self.publisher.publish(item, package_data).await
     }
@@ -0,0 +1,224 @@
+use super::*;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// How many reviews and reports one client may send, before the backend's
+// own limits; a 429 from the backend also pauses submissions
+const REVIEWS_PER_HOUR: usize = 5;
+const REVIEW_COOLDOWN: Duration = Duration::from_secs(10 * 60); // per item
+const REPORTS_PER_HOUR: usize = 20;
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+pub const TITLE_MAX: usize = 100;
+pub const BODY_MIN: usize = 10;
+pub const BODY_MAX: usize = 5000;
+
+// Where the backend's moderation has put a review
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    #[default]
+    Published,
+    Pending, // held until a moderator looks at it; only its author sees it
+    Flagged, // suspected spam or abuse, hidden unless asked for
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    Spam,
+    Abuse,
+    OffTopic,
+    Other,
+}
+
+// A review being written; the body is Markdown
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewDraft {
+    pub rating: u8,
+    pub title: String,
+    pub body: String,
+}
+
+impl ReviewDraft {
+    pub fn validate(&self) -> Result<(), WarpError> {
+        let problem = if !(1..=5).contains(&self.rating) {
+            "Pick a rating from 1 to 5 stars".to_string()
+        } else if self.title.trim().is_empty() {
+            "Give the review a title".to_string()
+        } else if self.title.chars().count() > TITLE_MAX {
+            format!("Keep the title under {} characters", TITLE_MAX)
+        } else if self.body.trim().chars().count() < BODY_MIN {
+            format!("Write at least {} characters", BODY_MIN)
+        } else if self.body.chars().count() > BODY_MAX {
+            format!("Keep the review under {} characters", BODY_MAX)
+        } else {
+            return Ok(());
+        };
+        Err(WarpError::Validation(problem))
+    }
+}
+
+// Reviews to show: removed ones never, flagged ones only on request
+pub fn visible(reviews: Vec<Review>, show_flagged: bool) -> Vec<Review> {
+    reviews
+        .into_iter()
+        .filter(|review| match review.moderation {
+            ModerationStatus::Published | ModerationStatus::Pending => true,
+            ModerationStatus::Flagged => show_flagged,
+            ModerationStatus::Removed => false,
+        })
+        .collect()
+}
+
+// A sliding hour of submissions plus a cooldown per target, so a review
+// can't be posted to the same item again right away
+pub struct SubmissionLimiter {
+    per_hour: usize,
+    cooldown: Duration,
+    recent: VecDeque<Instant>,
+    last: HashMap<String, Instant>,
+    paused_until: Option<Instant>,
+}
+
+impl SubmissionLimiter {
+    pub fn new(per_hour: usize, cooldown: Duration) -> Self {
+        Self {
+            per_hour,
+            cooldown,
+            recent: VecDeque::new(),
+            last: HashMap::new(),
+            paused_until: None,
+        }
+    }
+
+    pub fn reviews() -> Self {
+        Self::new(REVIEWS_PER_HOUR, REVIEW_COOLDOWN)
+    }
+
+    // Reporting the same review twice is pointless, so its cooldown is the
+    // whole window
+    pub fn reports() -> Self {
+        Self::new(REPORTS_PER_HOUR, HOUR)
+    }
+
+    // How long until a submission for `target` is allowed; zero when it is
+    pub fn wait(&mut self, target: &str, now: Instant) -> Duration {
+        while self
+            .recent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= HOUR)
+        {
+            self.recent.pop_front();
+        }
+        let window = match self.recent.front() {
+            Some(oldest) if self.recent.len() >= self.per_hour => {
+                HOUR - now.duration_since(*oldest)
+            }
+            _ => Duration::ZERO,
+        };
+        let cooldown = self.last.get(target).map_or(Duration::ZERO, |sent| {
+            self.cooldown.saturating_sub(now.duration_since(*sent))
+        });
+        let paused = self
+            .paused_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        window.max(cooldown).max(paused)
+    }
+
+    pub fn check(&mut self, target: &str, now: Instant) -> Result<(), WarpError> {
+        match self.wait(target, now) {
+            wait if wait.is_zero() => Ok(()),
+            wait => Err(WarpError::Validation(format!(
+                "Too many submissions; try again in {} minutes",
+                wait.as_secs().div_ceil(60).max(1)
+            ))),
+        }
+    }
+
+    pub fn record(&mut self, target: &str, now: Instant) {
+        self.recent.push_back(now);
+        self.last.insert(target.to_string(), now);
+    }
+
+    // The backend said 429; nothing goes out until it's ready again
+    pub fn pause(&mut self, until: Instant) {
+        self.paused_until = Some(self.paused_until.map_or(until, |paused| paused.max(until)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(id: &str, moderation: ModerationStatus) -> Review {
+        Review {
+            id: id.to_string(),
+            user_id: "u".to_string(),
+            username: "u".to_string(),
+            rating: 4,
+            title: String::new(),
+            content: String::new(),
+            helpful_votes: 0,
+            created_at: chrono::Utc::now(),
+            verified_purchase: false,
+            moderation,
+        }
+    }
+
+    #[test]
+    fn test_limiter_windows_cooldowns_and_pauses() {
+        let start = Instant::now();
+        let mut limiter = SubmissionLimiter::new(2, Duration::from_secs(600));
+        limiter.record("a/theme", start);
+        assert_eq!(
+            limiter.wait("a/theme", start + Duration::from_secs(60)),
+            Duration::from_secs(540)
+        );
+        assert!(limiter
+            .check("a/other", start + Duration::from_secs(60))
+            .is_ok());
+
+        limiter.record("a/other", start + Duration::from_secs(60));
+        // Two in the hour: the next waits for the first to age out
+        assert_eq!(
+            limiter.wait("a/third", start + Duration::from_secs(1800)),
+            Duration::from_secs(1800)
+        );
+        assert!(limiter.check("a/third", start + HOUR).is_ok());
+
+        limiter.pause(start + HOUR + Duration::from_secs(30));
+        assert_eq!(
+            limiter.wait("a/third", start + HOUR),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_drafts_and_flagged_reviews() {
+        let mut draft = ReviewDraft {
+            rating: 5,
+            title: "Great".to_string(),
+            body: "Works **really** well".to_string(),
+        };
+        assert!(draft.validate().is_ok());
+        draft.rating = 0;
+        assert!(draft.validate().is_err());
+
+        let reviews = vec![
+            review("1", ModerationStatus::Published),
+            review("2", ModerationStatus::Flagged),
+            review("3", ModerationStatus::Removed),
+            review("4", ModerationStatus::Pending),
+        ];
+        let ids = |reviews: Vec<Review>| {
+            reviews
+                .into_iter()
+                .map(|review| review.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(visible(reviews.clone(), false)), vec!["1", "4"]);
+        assert_eq!(ids(visible(reviews, true)), vec!["1", "2", "4"]);
+    }
+}
@@ -1,13 +1,15 @@
 use super::*;
+use crate::config::UpdateChannel;
 use crate::error::WarpError;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
 pub struct PackageManager {
     installed_packages: HashMap<String, InstalledPackage>,
     package_directory: PathBuf,
     cache_directory: PathBuf,
+    kept_versions: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,123 +21,257 @@ pub struct InstalledPackage {
     pub auto_update: bool,
     pub dependencies: Vec<String>,
     pub install_path: PathBuf,
+    #[serde(default)]
+    pub registry: Option<String>, // where it was installed from; None for installs before registries
+    #[serde(default)]
+    pub channel: Option<UpdateChannel>, // None follows general.update_channel
+    #[serde(default)]
+    pub pinned: Option<String>, // stays at this version; updates skip it
+    // The listing this version was installed from, so it can be put back
+    // after an update
+    #[serde(default)]
+    pub item: Option<MarketplaceItem>,
+    #[serde(default)]
+    pub history: Vec<PriorVersion>, // newest first
+}
+
+// A version that was replaced and can be rolled back to; its archive stays
+// in the installer's cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorVersion {
+    pub item: MarketplaceItem,
+    pub installed_at: chrono::DateTime<chrono::Utc>,
+    pub replaced_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl InstalledPackage {
+    pub fn channel(&self, default: UpdateChannel) -> UpdateChannel {
+        self.channel.unwrap_or(default)
+    }
+
+    // Whether a published version may be installed for this package
+    pub fn allows(&self, version: &str, default: UpdateChannel) -> bool {
+        match &self.pinned {
+            Some(pinned) => pinned == version,
+            None => self.channel(default).accepts(version),
+        }
+    }
+}
+
+// A newer version on the package's channel, with the changelog of every
+// version in between, pre-releases included, newest first
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub item: MarketplaceItem,
+    pub current: String,
+    pub changelog: Vec<(String, String)>,
+}
+
+// Pinned packages never have one
+pub fn available_update(
+    package: &InstalledPackage,
+    versions: &[MarketplaceItem],
+    default: UpdateChannel,
+) -> Option<Update> {
+    if package.pinned.is_some() {
+        return None;
+    }
+    let current = semver::Version::parse(&package.version).ok()?;
+    let mut newer: Vec<_> = versions
+        .iter()
+        .filter(|item| package.allows(&item.version, default))
+        .filter_map(|item| Some((semver::Version::parse(&item.version).ok()?, item)))
+        .filter(|(version, _)| *version > current)
+        .collect();
+    newer.sort_by(|a, b| b.0.cmp(&a.0));
+    let (_, latest) = newer.first()?;
+    Some(Update {
+        item: (*latest).clone(),
+        current: package.version.clone(),
+        changelog: newer
+            .iter()
+            .map(|(version, item)| (version.to_string(), item.changelog.clone()))
+            .collect(),
+    })
+}
+
+// The history once `previous` is replaced by `version`: the replaced
+// version goes first, the incoming one leaves (it's current again after a
+// rollback), and anything past `keep` is dropped
+fn next_history(
+    previous: &InstalledPackage,
+    version: &str,
+    keep: usize,
+) -> (Vec<PriorVersion>, Vec<PriorVersion>) {
+    let mut history = previous.history.clone();
+    if previous.version != version {
+        if let Some(item) = &previous.item {
+            history.insert(
+                0,
+                PriorVersion {
+                    item: item.clone(),
+                    installed_at: previous.installed_at,
+                    replaced_at: chrono::Utc::now(),
+                },
+            );
+        }
+    }
+    history.retain(|prior| prior.item.version != version);
+    let dropped = history.split_off(keep.min(history.len()));
+    (history, dropped)
 }
 
 impl PackageManager {
-    pub async fn new() -> Result<Self, WarpError> {
+    pub async fn new(kept_versions: usize) -> Result<Self, WarpError> {
         let config_dir = dirs::config_dir()
-            .ok_or_else(|| WarpError::ConfigError("Could not find config directory".to_string()))?;
-        
+            .ok_or_else(|| WarpError::Config("Could not find config directory".to_string()))?;
+
         let package_directory = config_dir.join("warp/packages");
         let cache_directory = config_dir.join("warp/cache");
-        
+
         // Create directories if they don't exist
         fs::create_dir_all(&package_directory).await?;
         fs::create_dir_all(&cache_directory).await?;
-        
+
         let mut manager = Self {
             installed_packages: HashMap::new(),
             package_directory,
             cache_directory,
+            kept_versions,
         };
-        
+
         manager.load_installed_packages().await?;
-        
+
         Ok(manager)
     }
 
     async fn load_installed_packages(&mut self) -> Result<(), WarpError> {
         let manifest_path = self.package_directory.join("manifest.json");
-        
+
         if manifest_path.exists() {
             let content = fs::read_to_string(&manifest_path).await?;
             let packages: HashMap<String, InstalledPackage> = serde_json::from_str(&content)
-                .map_err(|e| WarpError::ConfigError(format!("Failed to parse manifest: {}", e)))?;
-            
+                .map_err(|e| WarpError::Config(format!("Failed to parse manifest: {}", e)))?;
+
             self.installed_packages = packages;
         }
-        
+
         Ok(())
     }
 
     async fn save_manifest(&self) -> Result<(), WarpError> {
         let manifest_path = self.package_directory.join("manifest.json");
         let content = serde_json::to_string_pretty(&self.installed_packages)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to serialize manifest: {}", e)))?;
-        
+            .map_err(|e| WarpError::Config(format!("Failed to serialize manifest: {}", e)))?;
+
         fs::write(&manifest_path, content).await?;
         Ok(())
     }
 
-    pub async fn install_package(&mut self, item: &MarketplaceItem, package_data: Vec<u8>) -> Result<(), WarpError> {
+    pub async fn install_package(
+        &mut self,
+        item: &MarketplaceItem,
+        package_data: Vec<u8>,
+    ) -> Result<(), WarpError> {
         let install_path = self.package_directory.join(&item.id);
-        
+
         // Create package directory
         fs::create_dir_all(&install_path).await?;
-        
+
         // Extract package based on type
         match &item.item_type {
             ItemType::Theme(_) => {
-                self.install_theme(&item, package_data, &install_path).await?;
+                self.install_theme(&item, package_data, &install_path)
+                    .await?;
             }
             ItemType::Plugin(_) => {
-                self.install_plugin(&item, package_data, &install_path).await?;
+                self.install_plugin(&item, package_data, &install_path)
+                    .await?;
             }
             ItemType::AIModel(_) => {
-                self.install_ai_model(&item, package_data, &install_path).await?;
+                self.install_ai_model(&item, package_data, &install_path)
+                    .await?;
             }
             ItemType::Keyset(_) => {
-                self.install_keyset(&item, package_data, &install_path).await?;
+                self.install_keyset(&item, package_data, &install_path)
+                    .await?;
             }
             ItemType::Workflow(_) => {
-                self.install_workflow(&item, package_data, &install_path).await?;
+                self.install_workflow(&item, package_data, &install_path)
+                    .await?;
             }
             ItemType::Script(_) => {
-                self.install_script(&item, package_data, &install_path).await?;
+                self.install_script(&item, package_data, &install_path)
+                    .await?;
             }
         }
-        
-        // Add to installed packages
+
+        self.record_install(item).await.map(|_| ())
+    }
+
+    // Adds the item to the manifest, with the registry it came from. A
+    // reinstall keeps the package's channel and pin, and the version it
+    // replaces goes into the history. Returns the versions that no longer
+    // fit in the history, whose archives can go.
+    pub async fn record_install(
+        &mut self,
+        item: &MarketplaceItem,
+    ) -> Result<Vec<PriorVersion>, WarpError> {
+        let previous = self.installed_packages.get(&item.id);
+        let (history, dropped) = match previous {
+            Some(previous) => next_history(previous, &item.version, self.kept_versions),
+            None => (Vec::new(), Vec::new()),
+        };
         let installed_package = InstalledPackage {
             id: item.id.clone(),
             name: item.name.clone(),
             version: item.version.clone(),
             installed_at: chrono::Utc::now(),
             auto_update: true,
-            dependencies: vec![], // This would be extracted from package metadata
-            install_path,
+            dependencies: dependencies::declared(item).to_vec(),
+            install_path: self.package_directory.join(&item.id),
+            registry: item.registry.clone(),
+            channel: previous.and_then(|package| package.channel),
+            pinned: previous.and_then(|package| package.pinned.clone()),
+            item: Some(item.clone()),
+            history,
         };
-        
-        self.installed_packages.insert(item.id.clone(), installed_package);
+
+        self.installed_packages
+            .insert(item.id.clone(), installed_package);
         self.save_manifest().await?;
-        
-        Ok(())
+        Ok(dropped)
     }
 
-    async fn install_theme(&self, item: &MarketplaceItem, package_data: Vec<u8>, install_path: &PathBuf) -> Result<(), WarpError> {
-        // Extract theme files
-        let archive = zip::ZipArchive::new(std::io::Cursor::new(package_data))
-            .map_err(|e| WarpError::ConfigError(format!("Failed to read theme package: {}", e)))?;
-        
+    async fn install_theme(
+        &self,
+        item: &MarketplaceItem,
+        package_data: Vec<u8>,
+        _install_path: &PathBuf,
+    ) -> Result<(), WarpError> {
         // Extract to themes directory
         let themes_dir = dirs::config_dir()
-            .ok_or_else(|| WarpError::ConfigError("Could not find config directory".to_string()))?
+            .ok_or_else(|| WarpError::Config("Could not find config directory".to_string()))?
             .join("warp/themes");
-        
+
         fs::create_dir_all(&themes_dir).await?;
-        
-        // This would extract the zip file to the themes directory
-        // For now, just create a placeholder file
+
+        // A theme package is the theme's YAML
         let theme_file = themes_dir.join(format!("{}.yaml", item.id));
-        fs::write(&theme_file, "# Theme placeholder").await?;
-        
+        fs::write(&theme_file, package_data).await?;
+
         Ok(())
     }
 
-    async fn install_plugin(&self, item: &MarketplaceItem, package_data: Vec<u8>, install_path: &PathBuf) -> Result<(), WarpError> {
+    async fn install_plugin(
+        &self,
+        item: &MarketplaceItem,
+        package_data: Vec<u8>,
+        install_path: &PathBuf,
+    ) -> Result<(), WarpError> {
         // Extract plugin files
         fs::write(&install_path.join("plugin.wasm"), package_data).await?;
-        
+
         // Create plugin manifest
         let manifest = serde_json::json!({
             "id": item.id,
@@ -143,16 +279,21 @@ impl PackageManager {
             "version": item.version,
             "entry_point": "plugin.wasm"
         });
-        
+
         fs::write(&install_path.join("manifest.json"), manifest.to_string()).await?;
-        
+
         Ok(())
     }
 
-    async fn install_ai_model(&self, item: &MarketplaceItem, package_data: Vec<u8>, install_path: &PathBuf) -> Result<(), WarpError> {
+    async fn install_ai_model(
+        &self,
+        item: &MarketplaceItem,
+        package_data: Vec<u8>,
+        install_path: &PathBuf,
+    ) -> Result<(), WarpError> {
         // Save model data
         fs::write(&install_path.join("model.bin"), package_data).await?;
-        
+
         // Create model configuration
         let config = serde_json::json!({
             "id": item.id,
@@ -160,27 +301,42 @@ impl PackageManager {
             "version": item.version,
             "model_file": "model.bin"
         });
-        
+
         fs::write(&install_path.join("config.json"), config.to_string()).await?;
-        
+
         Ok(())
     }
 
-    async fn install_keyset(&self, item: &MarketplaceItem, package_data: Vec<u8>, install_path: &PathBuf) -> Result<(), WarpError> {
+    async fn install_keyset(
+        &self,
+        _item: &MarketplaceItem,
+        package_data: Vec<u8>,
+        install_path: &Path,
+    ) -> Result<(), WarpError> {
         // Save keyset configuration
         fs::write(&install_path.join("keyset.yaml"), package_data).await?;
-        
+
         Ok(())
     }
 
-    async fn install_workflow(&self, item: &MarketplaceItem, package_data: Vec<u8>, install_path: &PathBuf) -> Result<(), WarpError> {
+    async fn install_workflow(
+        &self,
+        _item: &MarketplaceItem,
+        package_data: Vec<u8>,
+        install_path: &Path,
+    ) -> Result<(), WarpError> {
         // Save workflow configuration
         fs::write(&install_path.join("workflow.yaml"), package_data).await?;
-        
+
         Ok(())
     }
 
-    async fn install_script(&self, item: &MarketplaceItem, package_data: Vec<u8>, install_path: &PathBuf) -> Result<(), WarpError> {
+    async fn install_script(
+        &self,
+        item: &MarketplaceItem,
+        package_data: Vec<u8>,
+        install_path: &PathBuf,
+    ) -> Result<(), WarpError> {
         // Save script file
         if let ItemType::Script(metadata) = &item.item_type {
             let extension = match metadata.language.as_str() {
@@ -190,10 +346,14 @@ impl PackageManager {
                 "shell" => "sh",
                 _ => "txt",
             };
-            
-            fs::write(&install_path.join(format!("script.{}", extension)), package_data).await?;
+
+            fs::write(
+                &install_path.join(format!("script.{}", extension)),
+                package_data,
+            )
+            .await?;
         }
-        
+
         Ok(())
     }
 
@@ -203,52 +363,215 @@ impl PackageManager {
             if package.install_path.exists() {
                 fs::remove_dir_all(&package.install_path).await?;
             }
-            
+
             // Remove from specific directories based on type
             self.remove_from_system_directories(package_id).await?;
-            
+
             self.save_manifest().await?;
         }
-        
+
         Ok(())
     }
 
     async fn remove_from_system_directories(&self, package_id: &str) -> Result<(), WarpError> {
         let config_dir = dirs::config_dir()
-            .ok_or_else(|| WarpError::ConfigError("Could not find config directory".to_string()))?;
-        
+            .ok_or_else(|| WarpError::Config("Could not find config directory".to_string()))?;
+
         // Remove from themes
-        let theme_file = config_dir.join("warp/themes").join(format!("{}.yaml", package_id));
+        let theme_file = config_dir
+            .join("warp/themes")
+            .join(format!("{}.yaml", package_id));
         if theme_file.exists() {
             fs::remove_file(&theme_file).await?;
         }
-        
+
         // Remove from other directories as needed
-        
+
         Ok(())
     }
 
-    pub async fn update_package(&mut self, package_id: &str) -> Result<(), WarpError> {
-        if let Some(package) = self.installed_packages.get(package_id) {
-            // Check for updates (this would call the marketplace API)
-            // For now, just return success
-            log::info!("Checking for updates for package: {}", package.name);
+    // Updates for every installed package on its channel; a package whose
+    // registry can't be reached is skipped, not fatal
+    pub async fn check_updates(
+        &self,
+        client: &client::MarketplaceClient,
+        default: UpdateChannel,
+    ) -> Result<Vec<Update>, WarpError> {
+        let mut updates = Vec::new();
+        for package in self
+            .installed_packages
+            .values()
+            .filter(|package| package.pinned.is_none())
+        {
+            match client.get_versions(&package.id).await {
+                Ok(versions) => updates.extend(available_update(package, &versions, default)),
+                Err(e) => log::warn!("Couldn't check {} for updates: {}", package.id, e),
+            }
         }
-        
-        Ok(())
+        updates.sort_by(|a, b| a.item.id.cmp(&b.item.id));
+        Ok(updates)
+    }
+
+    pub async fn set_channel(
+        &mut self,
+        package_id: &str,
+        channel: Option<UpdateChannel>,
+    ) -> Result<(), WarpError> {
+        let package = self
+            .installed_packages
+            .get_mut(package_id)
+            .ok_or_else(|| WarpError::not_found("package", package_id))?;
+        package.channel = channel;
+        self.save_manifest().await
     }
 
-    pub async fn check_updates(&self) -> Result<Vec<MarketplaceItem>, WarpError> {
-        // This would check all installed packages for updates
-        // For now, return empty list
-        Ok(vec![])
+    pub async fn pin(
+        &mut self,
+        package_id: &str,
+        version: Option<String>,
+    ) -> Result<(), WarpError> {
+        let package = self
+            .installed_packages
+            .get_mut(package_id)
+            .ok_or_else(|| WarpError::not_found("package", package_id))?;
+        package.pinned = version;
+        self.save_manifest().await
     }
 
     pub fn get_installed_packages(&self) -> Vec<&InstalledPackage> {
         self.installed_packages.values().collect()
     }
 
+    pub fn get_package(&self, package_id: &str) -> Option<&InstalledPackage> {
+        self.installed_packages.get(package_id)
+    }
+
     pub fn is_installed(&self, package_id: &str) -> bool {
         self.installed_packages.contains_key(package_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> MarketplaceItem {
+        serde_json::from_value(serde_json::json!({
+            "id": "a/theme",
+            "name": "theme",
+            "description": "",
+            "category": "Themes",
+            "item_type": { "Keyset": { "editor_style": "vim", "supported_shells": [], "key_count": 0 } },
+            "version": version,
+            "author": { "id": "a", "username": "a", "display_name": "A", "email": null, "website": null, "verified": false, "reputation": 0 },
+            "tags": [],
+            "rating": { "average": 0.0, "count": 0, "distribution": {} },
+            "downloads": 0,
+            "price": "Free",
+            "license": { "name": "MIT", "url": null, "open_source": true },
+            "compatibility": { "min_warp_version": "1.0.0", "max_warp_version": null, "platforms": [], "architectures": [] },
+            "screenshots": [],
+            "readme": "",
+            "changelog": format!("changes in {}", version),
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+            "verified": false,
+            "featured": false
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_updates_follow_the_channel_and_pin() {
+        let versions: Vec<_> = ["1.0.0", "1.1.0", "1.2.0-beta.1", "1.2.0-nightly.20240301"]
+            .into_iter()
+            .map(version)
+            .collect();
+        let mut package = InstalledPackage {
+            id: "a/theme".to_string(),
+            name: "theme".to_string(),
+            version: "1.0.0".to_string(),
+            installed_at: chrono::Utc::now(),
+            auto_update: true,
+            dependencies: Vec::new(),
+            install_path: PathBuf::new(),
+            registry: None,
+            channel: None,
+            pinned: None,
+            item: None,
+            history: Vec::new(),
+        };
+
+        let stable = available_update(&package, &versions, UpdateChannel::Stable).unwrap();
+        assert_eq!(stable.item.version, "1.1.0");
+
+        package.channel = Some(UpdateChannel::Beta);
+        let beta = available_update(&package, &versions, UpdateChannel::Stable).unwrap();
+        assert_eq!(beta.item.version, "1.2.0-beta.1");
+        let notes: Vec<_> = beta
+            .changelog
+            .iter()
+            .map(|(version, _)| version.as_str())
+            .collect();
+        assert_eq!(notes, vec!["1.2.0-beta.1", "1.1.0"]);
+
+        assert_eq!(
+            available_update(&package, &versions, UpdateChannel::Nightly)
+                .unwrap()
+                .item
+                .version,
+            "1.2.0-beta.1"
+        );
+        package.channel = None;
+        assert_eq!(
+            available_update(&package, &versions, UpdateChannel::Nightly)
+                .unwrap()
+                .item
+                .version,
+            "1.2.0-nightly.20240301"
+        );
+
+        package.pinned = Some("1.0.0".to_string());
+        assert!(available_update(&package, &versions, UpdateChannel::Nightly).is_none());
+    }
+
+    #[test]
+    fn test_history_keeps_replaced_versions_newest_first() {
+        let mut package = InstalledPackage {
+            id: "a/theme".to_string(),
+            name: "theme".to_string(),
+            version: "1.0.0".to_string(),
+            installed_at: chrono::Utc::now(),
+            auto_update: true,
+            dependencies: Vec::new(),
+            install_path: PathBuf::new(),
+            registry: None,
+            channel: None,
+            pinned: None,
+            item: Some(version("1.0.0")),
+            history: Vec::new(),
+        };
+        let kept = |history: &[PriorVersion]| {
+            history
+                .iter()
+                .map(|prior| prior.item.version.clone())
+                .collect::<Vec<_>>()
+        };
+
+        for next in ["1.1.0", "1.2.0", "1.3.0"] {
+            let (history, dropped) = next_history(&package, next, 2);
+            package.history = history;
+            package.version = next.to_string();
+            package.item = Some(version(next));
+            if next == "1.3.0" {
+                assert_eq!(kept(&dropped), vec!["1.0.0"]);
+            }
+        }
+        assert_eq!(kept(&package.history), vec!["1.2.0", "1.1.0"]);
+
+        // Rolling back to 1.1.0 takes it out of the history and keeps 1.3.0
+        let (history, dropped) = next_history(&package, "1.1.0", 2);
+        assert_eq!(kept(&history), vec!["1.3.0", "1.2.0"]);
+        assert!(dropped.is_empty());
+    }
+}
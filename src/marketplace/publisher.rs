@@ -0,0 +1,64 @@
+use super::*;
+use crate::config::OAuthConfig;
+use crate::package::{self, Manifest, PackageKind};
+use crate::secrets::SecretsManager;
+
+// Items built in memory go through the same signed upload as
+// `warp package publish`
+pub struct Publisher {
+    secrets: SecretsManager,
+    oauth: OAuthConfig, // the "marketplace" provider signs in when there's no token
+}
+
+impl Publisher {
+    pub async fn new(oauth: &OAuthConfig) -> Result<Self, WarpError> {
+        Ok(Self {
+            secrets: SecretsManager::new().await?,
+            oauth: oauth.clone(),
+        })
+    }
+
+    pub async fn publish(
+        &self,
+        item: MarketplaceItem,
+        package_data: Vec<u8>,
+    ) -> Result<String, WarpError> {
+        let kind = match item.item_type {
+            ItemType::Theme(_) => PackageKind::Theme,
+            ItemType::Plugin(_) => PackageKind::Plugin,
+            ItemType::Workflow(_) => PackageKind::Workflow,
+            _ => {
+                return Err(WarpError::Validation(format!(
+                    "{} can't be published yet; only themes, plugins and workflows can",
+                    item.id
+                )))
+            }
+        };
+        let manifest = Manifest {
+            schema_version: package::MANIFEST_SCHEMA_VERSION,
+            id: item.id,
+            name: item.name,
+            kind,
+            version: item.version,
+            description: item.description,
+            author: item.author.username,
+            license: item.license.name,
+            min_warp_version: item.compatibility.min_warp_version,
+            tags: item.tags,
+            files: Vec::new(),
+            entry_point: None,
+            permissions: Vec::new(),
+        };
+        let key = package::signing_key(&self.secrets).await?;
+        let signature = package::sign(&key, &package_data);
+        Ok(package::publish(
+            &self.secrets,
+            self.oauth.provider("marketplace"),
+            &manifest,
+            &package_data,
+            &signature,
+        )
+        .await?
+        .id)
+    }
+}
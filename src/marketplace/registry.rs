@@ -0,0 +1,187 @@
+use super::MarketplaceItem;
+use crate::config::{RegistryAuth, RegistryConfig};
+
+pub const OFFICIAL: &str = "official";
+const OFFICIAL_URL: &str = "https://marketplace.warp.dev/api/v1";
+
+pub fn official() -> RegistryConfig {
+    RegistryConfig {
+        name: OFFICIAL.to_string(),
+        url: OFFICIAL_URL.to_string(),
+        priority: 0,
+        auth: RegistryAuth::OAuth {
+            provider: "marketplace".to_string(),
+        },
+        publishers: Vec::new(),
+        enabled: true,
+    }
+}
+
+// The official registry plus the configured ones, best first. Registries
+// of equal priority keep their configured order, official first.
+pub fn effective(configured: &[RegistryConfig]) -> Vec<RegistryConfig> {
+    let mut registries = vec![configured
+        .iter()
+        .find(|registry| registry.name == OFFICIAL)
+        .cloned()
+        .unwrap_or_else(official)];
+    registries.extend(
+        configured
+            .iter()
+            .filter(|registry| registry.name != OFFICIAL)
+            .cloned(),
+    );
+    registries.retain(|registry| registry.enabled);
+    registries.sort_by_key(|registry| std::cmp::Reverse(registry.priority));
+    registries
+}
+
+// Item ids are publisher/name
+pub fn publisher(item_id: &str) -> &str {
+    item_id
+        .split_once('/')
+        .map_or(item_id, |(publisher, _)| publisher)
+}
+
+// Registries to ask for an item, best first. A registry that claims the
+// item's publisher is the only place it can come from.
+pub fn candidates<'a>(registries: &'a [RegistryConfig], item_id: &str) -> Vec<&'a RegistryConfig> {
+    let publisher = publisher(item_id);
+    let claimed: Vec<_> = registries
+        .iter()
+        .filter(|registry| registry.publishers.iter().any(|p| p == publisher))
+        .collect();
+    if claimed.is_empty() {
+        registries.iter().collect()
+    } else {
+        claimed
+    }
+}
+
+pub fn may_serve(registries: &[RegistryConfig], registry: &str, item_id: &str) -> bool {
+    candidates(registries, item_id)
+        .iter()
+        .any(|candidate| candidate.name == registry)
+}
+
+// Each registry's items in one list: an id listed by several registries
+// comes from the best one allowed to serve it, and every item is tagged
+// with the registry it came from. Order follows the best registry's
+// results, then the next one's new items, and so on.
+pub fn merge(
+    registries: &[RegistryConfig],
+    results: Vec<(String, Vec<MarketplaceItem>)>,
+) -> Vec<MarketplaceItem> {
+    let rank = |name: &str| registries.iter().position(|registry| registry.name == name);
+    let mut results: Vec<_> = results
+        .into_iter()
+        .filter_map(|(name, items)| Some((rank(&name)?, name, items)))
+        .collect();
+    results.sort_by_key(|(rank, _, _)| *rank);
+
+    let mut merged: Vec<MarketplaceItem> = Vec::new();
+    for (_, name, items) in results {
+        for mut item in items {
+            if !may_serve(registries, &name, &item.id) || merged.iter().any(|m| m.id == item.id) {
+                continue;
+            }
+            item.registry = Some(name.clone());
+            merged.push(item);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(name: &str, priority: i32, publishers: &[&str]) -> RegistryConfig {
+        RegistryConfig {
+            name: name.to_string(),
+            url: format!("https://{}.example/api", name),
+            priority,
+            auth: RegistryAuth::None,
+            publishers: publishers.iter().map(|p| p.to_string()).collect(),
+            enabled: true,
+        }
+    }
+
+    fn item(id: &str) -> MarketplaceItem {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "description": "",
+            "category": "Themes",
+            "item_type": { "Keyset": { "editor_style": "vim", "supported_shells": [], "key_count": 0 } },
+            "version": "1.0.0",
+            "author": { "id": "a", "username": "a", "display_name": "A", "email": null, "website": null, "verified": false, "reputation": 0 },
+            "tags": [],
+            "rating": { "average": 0.0, "count": 0, "distribution": {} },
+            "downloads": 0,
+            "price": "Free",
+            "license": { "name": "MIT", "url": null, "open_source": true },
+            "compatibility": { "min_warp_version": "1.0.0", "max_warp_version": null, "platforms": [], "architectures": [] },
+            "screenshots": [],
+            "readme": "",
+            "changelog": "",
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+            "verified": false,
+            "featured": false
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_effective_orders_by_priority_and_overrides_official() {
+        let mut quiet = official();
+        quiet.priority = -1;
+        let registries = effective(&[registry("corp", 10, &["acme"]), quiet]);
+        let names: Vec<_> = registries.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["corp", OFFICIAL]);
+
+        let mut disabled = official();
+        disabled.enabled = false;
+        assert!(effective(&[disabled]).is_empty());
+    }
+
+    #[test]
+    fn test_claimed_publishers_only_come_from_their_registry() {
+        let registries = effective(&[registry("corp", -5, &["acme"])]);
+        let names = |id| {
+            candidates(&registries, id)
+                .iter()
+                .map(|r| r.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("acme/tools"), vec!["corp"]);
+        assert_eq!(names("someone/theme"), vec![OFFICIAL, "corp"]);
+
+        let merged = merge(
+            &registries,
+            vec![
+                (
+                    "corp".to_string(),
+                    vec![item("acme/tools"), item("someone/theme")],
+                ),
+                (
+                    OFFICIAL.to_string(),
+                    vec![item("acme/tools"), item("someone/theme"), item("other/x")],
+                ),
+            ],
+        );
+        let provenance: Vec<_> = merged
+            .iter()
+            .map(|item| format!("{}@{}", item.id, item.registry.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            provenance,
+            vec![
+                "someone/theme@official",
+                "other/x@official",
+                "acme/tools@corp"
+            ]
+        );
+    }
+}
@@ -1,4 +1,3 @@
-use super::*;
 use crate::error::WarpError;
 use std::collections::HashSet;
 
@@ -23,7 +22,7 @@ impl SecurityManager {
         trusted_publishers.insert("warp-official".to_string());
         trusted_publishers.insert("catppuccin".to_string());
         trusted_publishers.insert("gittools".to_string());
-        
+
         Ok(Self {
             trusted_publishers,
             blocked_items: HashSet::new(),
@@ -39,23 +38,28 @@ impl SecurityManager {
 
     pub async fn verify_item(&self, item_id: &str) -> Result<(), WarpError> {
         if self.blocked_items.contains(item_id) {
-            return Err(WarpError::ConfigError(format!("Item {} is blocked", item_id)));
+            return Err(WarpError::Permission(format!(
+                "Item {} is blocked",
+                item_id
+            )));
         }
-        
+
         // Additional security checks would go here
         Ok(())
     }
 
     pub async fn scan_package(&self, package_data: &[u8]) -> Result<(), WarpError> {
         if package_data.len() as u64 > self.security_policies.max_package_size {
-            return Err(WarpError::ConfigError("Package size exceeds maximum allowed".to_string()));
+            return Err(WarpError::Validation(
+                "Package size exceeds maximum allowed".to_string(),
+            ));
         }
-        
+
         if self.security_policies.scan_for_malware {
             // Mock malware scan
             println!("🛡️ Scanning for malware...");
         }
-        
+
         Ok(())
     }
 
@@ -1,15 +1,13 @@
 use super::*;
 use crate::error::WarpError;
 use ratatui::{
-    backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans},
+    text::{Line, Span},
     widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs},
     Frame,
 };
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 pub struct MarketplaceUI {
     marketplace: Arc<Marketplace>,
@@ -19,6 +17,42 @@ pub struct MarketplaceUI {
     current_tab: MarketplaceTab,
     list_state: ListState,
     search_query: String,
+    history: Option<package_manager::InstalledPackage>,
+    history_state: ListState,
+    reviews: Vec<Review>,
+    reviews_state: ListState,
+    show_flagged: bool,
+    composer: ReviewComposer,
+    notice: Option<String>, // outcome of the last review or report
+    // With the registry each came from; loaded with the featured items and
+    // after an install or rollback, since drawing can't wait on the manager
+    installed: Vec<(MarketplaceItem, String)>,
+    closed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ComposeField {
+    Rating,
+    Title,
+    Body,
+}
+
+// The review being written, with the focused field
+struct ReviewComposer {
+    draft: moderation::ReviewDraft,
+    field: ComposeField,
+}
+
+impl ReviewComposer {
+    fn new() -> Self {
+        Self {
+            draft: moderation::ReviewDraft {
+                rating: 5,
+                ..Default::default()
+            },
+            field: ComposeField::Rating,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +63,8 @@ pub enum MarketplaceUIState {
     Installing,
     Reviews,
     MyItems,
+    History,
+    Compose,
 }
 
 #[derive(Debug, Clone)]
@@ -51,21 +87,36 @@ impl MarketplaceUI {
             current_tab: MarketplaceTab::Featured,
             list_state: ListState::default(),
             search_query: String::new(),
+            history: None,
+            history_state: ListState::default(),
+            reviews: Vec::new(),
+            reviews_state: ListState::default(),
+            show_flagged: false,
+            composer: ReviewComposer::new(),
+            notice: None,
+            installed: Vec::new(),
+            closed: false,
         };
-        
+
         // Load initial content
         ui.load_featured_items().await?;
-        
+        ui.load_installed_items().await?;
+
         Ok(ui)
     }
 
-    pub async fn render<B: Backend>(&mut self, f: &mut Frame<B>) -> Result<(), WarpError> {
+    // Set by Esc on the browse view; the app then drops the UI
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn render(&mut self, f: &mut Frame<'_>) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(3),  // Header/Tabs
-                Constraint::Min(0),     // Main content
-                Constraint::Length(3),  // Status bar
+                Constraint::Length(3), // Header/Tabs
+                Constraint::Min(0),    // Main content
+                Constraint::Length(3), // Status bar
             ])
             .split(f.size());
 
@@ -74,30 +125,30 @@ impl MarketplaceUI {
 
         // Render main content based on state
         match self.state {
-            MarketplaceUIState::Browse => self.render_browse(f, chunks[1]).await?,
-            MarketplaceUIState::Search => self.render_search(f, chunks[1]).await?,
-            MarketplaceUIState::ItemDetails => self.render_item_details(f, chunks[1]).await?,
-            MarketplaceUIState::Installing => self.render_installing(f, chunks[1]).await?,
-            MarketplaceUIState::Reviews => self.render_reviews(f, chunks[1]).await?,
-            MarketplaceUIState::MyItems => self.render_my_items(f, chunks[1]).await?,
+            MarketplaceUIState::Browse => self.render_browse(f, chunks[1]),
+            MarketplaceUIState::Search => self.render_search(f, chunks[1]),
+            MarketplaceUIState::ItemDetails => self.render_item_details(f, chunks[1]),
+            MarketplaceUIState::Installing => self.render_installing(f, chunks[1]),
+            MarketplaceUIState::Reviews => self.render_reviews(f, chunks[1]),
+            MarketplaceUIState::MyItems => self.render_my_items(f, chunks[1]),
+            MarketplaceUIState::History => self.render_history(f, chunks[1]),
+            MarketplaceUIState::Compose => self.render_compose(f, chunks[1]),
         }
 
         // Render status bar
         self.render_status_bar(f, chunks[2]);
-
-        Ok(())
     }
 
-    fn render_tabs<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+    fn render_tabs(&self, f: &mut Frame<'_>, area: Rect) {
         let titles = vec![
             "Featured",
-            "Themes", 
+            "Themes",
             "Plugins",
             "AI Models",
             "Installed",
             "Updates",
         ];
-        
+
         let selected_tab = match self.current_tab {
             MarketplaceTab::Featured => 0,
             MarketplaceTab::Themes => 1,
@@ -108,15 +159,23 @@ impl MarketplaceUI {
         };
 
         let tabs = Tabs::new(titles)
-            .block(Block::default().borders(Borders::ALL).title("Warp Marketplace"))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Warp Marketplace"),
+            )
             .select(selected_tab)
             .style(Style::default().fg(Color::White))
-            .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            );
 
         f.render_widget(tabs, area);
     }
 
-    async fn render_browse<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) -> Result<(), WarpError> {
+    fn render_browse(&mut self, f: &mut Frame<'_>, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
@@ -134,30 +193,41 @@ impl MarketplaceUI {
                 .style(Style::default().fg(Color::Gray));
             f.render_widget(placeholder, chunks[1]);
         }
-
-        Ok(())
     }
 
-    fn render_item_list<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
-        let items: Vec<ListItem> = self.search_results
+    fn render_item_list(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let items: Vec<ListItem> = self
+            .search_results
             .iter()
             .map(|item| {
                 let price_text = match &item.price {
                     Price::Free => "Free".to_string(),
-                    Price::Paid { amount, currency } => format!("${}.{:02}", amount / 100, amount % 100),
-                    Price::PayWhatYouWant { suggested, currency } => format!("PWYW (${}.{:02})", suggested / 100, suggested % 100),
-                    Price::Subscription { monthly, currency, .. } => format!("${}.{:02}/mo", monthly / 100, monthly % 100),
+                    Price::Paid { amount, .. } => format!("${}.{:02}", amount / 100, amount % 100),
+                    Price::PayWhatYouWant { suggested, .. } => {
+                        format!("PWYW (${}.{:02})", suggested / 100, suggested % 100)
+                    }
+                    Price::Subscription { monthly, .. } => {
+                        format!("${}.{:02}/mo", monthly / 100, monthly % 100)
+                    }
                 };
 
                 let spans = vec![
-                    Span::styled(&item.name, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+                    Span::styled(
+                        &item.name,
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD),
+                    ),
                     Span::raw(" - "),
-                    Span::styled(&price_text, Style::default().fg(Color::Green)),
+                    Span::styled(price_text, Style::default().fg(Color::Green)),
                     Span::raw(" "),
-                    Span::styled(format!("⭐ {:.1}", item.rating.average), Style::default().fg(Color::Yellow)),
+                    Span::styled(
+                        format!("⭐ {:.1}", item.rating.average),
+                        Style::default().fg(Color::Yellow),
+                    ),
                 ];
 
-                ListItem::new(Spans::from(spans))
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
@@ -169,34 +239,52 @@ impl MarketplaceUI {
         f.render_stateful_widget(list, area, &mut self.list_state);
     }
 
-    fn render_item_preview<B: Backend>(&self, f: &mut Frame<B>, area: Rect, item: &MarketplaceItem) {
+    fn render_item_preview(&self, f: &mut Frame<'_>, area: Rect, item: &MarketplaceItem) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(6),  // Header
-                Constraint::Min(0),     // Description
-                Constraint::Length(4),  // Actions
+                Constraint::Length(6), // Header
+                Constraint::Min(0),    // Description
+                Constraint::Length(4), // Actions
             ])
             .split(area);
 
         // Header
         let header_text = vec![
-            Spans::from(vec![
-                Span::styled(&item.name, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+            Line::from(vec![
+                Span::styled(
+                    &item.name,
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                ),
                 Span::raw(" v"),
                 Span::styled(&item.version, Style::default().fg(Color::Cyan)),
             ]),
-            Spans::from(vec![
+            Line::from(vec![
                 Span::raw("by "),
                 Span::styled(&item.author.display_name, Style::default().fg(Color::Green)),
-                if item.author.verified { Span::styled(" ✓", Style::default().fg(Color::Blue)) } else { Span::raw("") },
+                if item.author.verified {
+                    Span::styled(" ✓", Style::default().fg(Color::Blue))
+                } else {
+                    Span::raw("")
+                },
             ]),
-            Spans::from(vec![
-                Span::styled(format!("⭐ {:.1}", item.rating.average), Style::default().fg(Color::Yellow)),
+            Line::from(vec![
+                Span::styled(
+                    format!("⭐ {:.1}", item.rating.average),
+                    Style::default().fg(Color::Yellow),
+                ),
                 Span::raw(" "),
-                Span::styled(format!("({} reviews)", item.rating.count), Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("({} reviews)", item.rating.count),
+                    Style::default().fg(Color::Gray),
+                ),
                 Span::raw(" • "),
-                Span::styled(format!("{} downloads", item.downloads), Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("{} downloads", item.downloads),
+                    Style::default().fg(Color::Gray),
+                ),
             ]),
         ];
 
@@ -211,57 +299,90 @@ impl MarketplaceUI {
         f.render_widget(description, chunks[1]);
 
         // Actions
-        let actions_text = vec![
-            Spans::from(vec![
-                Span::styled("[I]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                Span::raw(" Install  "),
-                Span::styled("[R]", Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
-                Span::raw(" Reviews  "),
-                Span::styled("[Enter]", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-                Span::raw(" Details"),
-            ]),
-        ];
+        let actions_text = vec![Line::from(vec![
+            Span::styled(
+                "[I]",
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" Install  "),
+            Span::styled(
+                "[R]",
+                Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" Reviews  "),
+            Span::styled(
+                "[Enter]",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" Details"),
+        ])];
 
         let actions = Paragraph::new(actions_text)
             .block(Block::default().borders(Borders::ALL).title("Actions"));
         f.render_widget(actions, chunks[2]);
     }
 
-    async fn render_search<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) -> Result<(), WarpError> {
+    fn render_search(&mut self, f: &mut Frame<'_>, area: Rect) {
         // Similar to browse but with search input
-        self.render_browse(f, area).await
+        self.render_browse(f, area)
     }
 
-    async fn render_item_details<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) -> Result<(), WarpError> {
+    fn render_item_details(&mut self, f: &mut Frame<'_>, area: Rect) {
         if let Some(item) = &self.selected_item {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(8),  // Header
-                    Constraint::Min(0),     // README/Details
-                    Constraint::Length(4),  // Actions
+                    Constraint::Length(8), // Header
+                    Constraint::Min(0),    // README/Details
+                    Constraint::Length(4), // Actions
                 ])
                 .split(area);
 
             // Detailed header
+            let channel = match UpdateChannel::of(&item.version) {
+                UpdateChannel::Stable => String::new(),
+                channel => format!(" ({:?})", channel).to_lowercase(),
+            };
             let header_text = vec![
-                Spans::from(vec![
-                    Span::styled(&item.name, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+                Line::from(vec![
+                    Span::styled(
+                        &item.name,
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD),
+                    ),
                     Span::raw(" v"),
                     Span::styled(&item.version, Style::default().fg(Color::Cyan)),
+                    Span::styled(channel, Style::default().fg(Color::Yellow)),
                 ]),
-                Spans::from(vec![
+                Line::from(vec![
                     Span::raw("Category: "),
-                    Span::styled(format!("{:?}", item.category), Style::default().fg(Color::Magenta)),
+                    Span::styled(
+                        format!("{:?}", item.category),
+                        Style::default().fg(Color::Magenta),
+                    ),
                 ]),
-                Spans::from(vec![
+                Line::from(vec![
                     Span::raw("License: "),
                     Span::styled(&item.license.name, Style::default().fg(Color::Green)),
                 ]),
-                Spans::from(vec![
+                Line::from(vec![
                     Span::raw("Tags: "),
                     Span::styled(item.tags.join(", "), Style::default().fg(Color::Gray)),
                 ]),
+                Line::from(vec![
+                    Span::raw("Registry: "),
+                    Span::styled(
+                        item.registry.as_deref().unwrap_or(registry::OFFICIAL),
+                        Style::default().fg(Color::Blue),
+                    ),
+                ]),
             ];
 
             let header = Paragraph::new(header_text)
@@ -275,29 +396,45 @@ impl MarketplaceUI {
             f.render_widget(readme, chunks[1]);
 
             // Actions
-            let actions_text = vec![
-                Spans::from(vec![
-                    Span::styled("[I]", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                    Span::raw(" Install  "),
-                    Span::styled("[R]", Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
-                    Span::raw(" Reviews  "),
-                    Span::styled("[Esc]", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                    Span::raw(" Back"),
-                ]),
-            ];
+            let actions_text = vec![Line::from(vec![
+                Span::styled(
+                    "[I]",
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" Install  "),
+                Span::styled(
+                    "[R]",
+                    Style::default()
+                        .fg(Color::Blue)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" Reviews  "),
+                Span::styled(
+                    "[Esc]",
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" Back"),
+            ])];
 
             let actions = Paragraph::new(actions_text)
                 .block(Block::default().borders(Borders::ALL).title("Actions"));
             f.render_widget(actions, chunks[2]);
         }
-
-        Ok(())
     }
 
-    async fn render_installing<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) -> Result<(), WarpError> {
+    fn render_installing(&mut self, f: &mut Frame<'_>, area: Rect) {
         let installing_text = vec![
-            Spans::from(vec![Span::styled("Installing...", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))]),
-            Spans::from(vec![Span::raw("Please wait while the item is being installed.")]),
+            Line::from(vec![Span::styled(
+                "Installing...",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )]),
+            Line::from(vec![Span::raw(
+                "Please wait while the item is being installed.",
+            )]),
         ];
 
         let installing = Paragraph::new(installing_text)
@@ -308,35 +445,222 @@ impl MarketplaceUI {
         let popup_area = centered_rect(50, 20, area);
         f.render_widget(Clear, popup_area);
         f.render_widget(installing, popup_area);
-
-        Ok(())
     }
 
-    async fn render_reviews<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) -> Result<(), WarpError> {
-        // This would show reviews for the selected item
-        let placeholder = Paragraph::new("Reviews will be displayed here")
-            .block(Block::default().borders(Borders::ALL).title("Reviews"));
-        f.render_widget(placeholder, area);
-
-        Ok(())
-    }
+    fn render_reviews(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
 
-    async fn render_my_items<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) -> Result<(), WarpError> {
-        // This would show installed items
-        let installed_items = self.marketplace.get_installed_items().await?;
-        
-        let items: Vec<ListItem> = installed_items
+        let items: Vec<ListItem> = self
+            .reviews
             .iter()
-            .map(|item| {
-                let spans = vec![
-                    Span::styled(&item.name, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
-                    Span::raw(" v"),
-                    Span::styled(&item.version, Style::default().fg(Color::Cyan)),
-                    Span::raw(" - Installed"),
+            .map(|review| {
+                let mut spans = vec![
+                    Span::styled(
+                        "★".repeat(review.rating.min(5) as usize),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::raw(" "),
+                    Span::styled(
+                        review.title.clone(),
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        format!(" by {}", review.username),
+                        Style::default().fg(Color::Gray),
+                    ),
                 ];
-                ListItem::new(Spans::from(spans))
+                match review.moderation {
+                    moderation::ModerationStatus::Pending => spans.push(Span::styled(
+                        " [awaiting moderation]",
+                        Style::default().fg(Color::Blue),
+                    )),
+                    moderation::ModerationStatus::Flagged => {
+                        spans.push(Span::styled(" [flagged]", Style::default().fg(Color::Red)))
+                    }
+                    _ => {}
+                }
+                ListItem::new(Line::from(spans))
             })
             .collect();
+        let title = match (&self.notice, self.show_flagged) {
+            (Some(notice), _) => format!("Reviews - {}", notice),
+            (None, true) => "Reviews (including flagged)".to_string(),
+            (None, false) => "Reviews".to_string(),
+        };
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().bg(Color::DarkGray))
+            .highlight_symbol("▶ ");
+        f.render_stateful_widget(list, chunks[0], &mut self.reviews_state);
+
+        let body = match self
+            .reviews_state
+            .selected()
+            .and_then(|index| self.reviews.get(index))
+        {
+            Some(review) => markdown_spans(&review.content),
+            None => vec![Line::from("No reviews yet")],
+        };
+        let content = Paragraph::new(body)
+            .block(Block::default().borders(Borders::ALL).title("Review"))
+            .wrap(ratatui::widgets::Wrap { trim: false });
+        f.render_widget(content, chunks[1]);
+    }
+
+    // The form on the left, the Markdown as it will show on the right
+    fn render_compose(&self, f: &mut Frame<'_>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let form = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ])
+            .split(chunks[0]);
+
+        let draft = &self.composer.draft;
+        let focus = |field: ComposeField| {
+            if self.composer.field == field {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }
+        };
+        let stars = format!(
+            "{}{}",
+            "★".repeat(draft.rating as usize),
+            "☆".repeat(5 - draft.rating as usize)
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(stars, Style::default().fg(Color::Yellow))).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Rating")
+                    .border_style(focus(ComposeField::Rating)),
+            ),
+            form[0],
+        );
+        f.render_widget(
+            Paragraph::new(draft.title.as_str()).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        "Title ({}/{})",
+                        draft.title.chars().count(),
+                        moderation::TITLE_MAX
+                    ))
+                    .border_style(focus(ComposeField::Title)),
+            ),
+            form[1],
+        );
+        f.render_widget(
+            Paragraph::new(draft.body.as_str())
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!(
+                            "Review, Markdown ({}/{})",
+                            draft.body.chars().count(),
+                            moderation::BODY_MAX
+                        ))
+                        .border_style(focus(ComposeField::Body)),
+                )
+                .wrap(ratatui::widgets::Wrap { trim: false }),
+            form[2],
+        );
+
+        let mut preview = vec![Line::from(Span::styled(
+            draft.title.clone(),
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ))];
+        preview.extend(markdown_spans(&draft.body));
+        let title = match &self.notice {
+            Some(notice) => format!("Preview - {}", notice),
+            None => "Preview".to_string(),
+        };
+        f.render_widget(
+            Paragraph::new(preview)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .wrap(ratatui::widgets::Wrap { trim: false }),
+            chunks[1],
+        );
+    }
+
+    fn render_history(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let Some(package) = &self.history else {
+            return;
+        };
+        let when = |time: &chrono::DateTime<chrono::Utc>| time.format("%Y-%m-%d %H:%M").to_string();
+        let mut items = vec![ListItem::new(Line::from(vec![
+            Span::styled(
+                format!("v{}", package.version),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("  installed {}", when(&package.installed_at))),
+            Span::styled(" - current", Style::default().fg(Color::Green)),
+        ]))];
+        for prior in &package.history {
+            items.push(ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("v{}", prior.item.version),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(format!(
+                    "  installed {}, replaced {}",
+                    when(&prior.installed_at),
+                    when(&prior.replaced_at)
+                )),
+                Span::styled(
+                    format!(
+                        "  from {}",
+                        prior.item.registry.as_deref().unwrap_or(registry::OFFICIAL)
+                    ),
+                    Style::default().fg(Color::Blue),
+                ),
+            ])));
+        }
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("{} versions", package.name)),
+            )
+            .highlight_style(Style::default().bg(Color::DarkGray))
+            .highlight_symbol("▶ ");
+        f.render_stateful_widget(list, area, &mut self.history_state);
+    }
+
+    fn render_my_items(&mut self, f: &mut Frame<'_>, area: Rect) {
+        let mut items: Vec<ListItem> = Vec::new();
+        for (item, registry) in &self.installed {
+            let spans = vec![
+                Span::styled(
+                    &item.name,
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" v"),
+                Span::styled(&item.version, Style::default().fg(Color::Cyan)),
+                Span::raw(" - Installed from "),
+                Span::styled(registry.as_str(), Style::default().fg(Color::Blue)),
+            ];
+            items.push(ListItem::new(Line::from(spans)));
+        }
 
         let list = List::new(items)
             .block(Block::default().borders(Borders::ALL).title("My Items"))
@@ -344,18 +668,18 @@ impl MarketplaceUI {
             .highlight_symbol("▶ ");
 
         f.render_stateful_widget(list, area, &mut self.list_state);
-
-        Ok(())
     }
 
-    fn render_status_bar<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+    fn render_status_bar(&self, f: &mut Frame<'_>, area: Rect) {
         let status_text = match self.state {
-            MarketplaceUIState::Browse => "Browse marketplace items • Use ↑↓ to navigate, Enter for details",
+            MarketplaceUIState::Browse => "Browse marketplace items • Use ↑↓ to navigate, Enter for details, Esc to close",
             MarketplaceUIState::Search => "Search results • Type to search, Enter to select",
-            MarketplaceUIState::ItemDetails => "Item details • I to install, R for reviews, Esc to go back",
+            MarketplaceUIState::ItemDetails => "Item details • I to install, R for reviews, H for version history, Esc to go back",
             MarketplaceUIState::Installing => "Installing item...",
-            MarketplaceUIState::Reviews => "Item reviews • Esc to go back",
+            MarketplaceUIState::Reviews => "Item reviews • W write a review, S/A report spam/abuse, F show flagged, Esc to go back",
+            MarketplaceUIState::Compose => "Write a review • Tab next field, ←→ rating, Enter on rating or title to submit, Esc to cancel",
             MarketplaceUIState::MyItems => "Your installed items • Enter for details",
+            MarketplaceUIState::History => "Version history • Enter to roll back to the selected version, Esc to go back",
         };
 
         let status = Paragraph::new(status_text)
@@ -369,16 +693,36 @@ impl MarketplaceUI {
         // This would load featured items from the marketplace
         // For now, use discovery engine recommendations
         self.search_results = self.marketplace.get_recommendations().await?;
-        
+
         if !self.search_results.is_empty() {
             self.list_state.select(Some(0));
             self.selected_item = Some(self.search_results[0].clone());
         }
-        
+
+        Ok(())
+    }
+
+    async fn load_installed_items(&mut self) -> Result<(), WarpError> {
+        let mut installed = Vec::new();
+        for item in self.marketplace.get_installed_items().await? {
+            let registry = self
+                .marketplace
+                .provenance(&item.id)
+                .await
+                .unwrap_or_else(|| registry::OFFICIAL.to_string());
+            installed.push((item, registry));
+        }
+        self.installed = installed;
         Ok(())
     }
 
     pub async fn handle_input(&mut self, key: crossterm::event::KeyCode) -> Result<(), WarpError> {
+        match self.state {
+            MarketplaceUIState::History => return self.handle_history_input(key).await,
+            MarketplaceUIState::Reviews => return self.handle_reviews_input(key).await,
+            MarketplaceUIState::Compose => return self.handle_compose_input(key).await,
+            _ => {}
+        }
         match key {
             crossterm::event::KeyCode::Up => {
                 if let Some(selected) = self.list_state.selected() {
@@ -400,16 +744,27 @@ impl MarketplaceUI {
                 self.state = MarketplaceUIState::ItemDetails;
             }
             crossterm::event::KeyCode::Char('i') | crossterm::event::KeyCode::Char('I') => {
-                if let Some(item) = &self.selected_item {
+                if self.selected_item.is_some() {
                     self.install_selected_item().await?;
                 }
             }
             crossterm::event::KeyCode::Char('r') | crossterm::event::KeyCode::Char('R') => {
                 self.state = MarketplaceUIState::Reviews;
+                self.load_reviews().await?;
             }
-            crossterm::event::KeyCode::Esc => {
-                self.state = MarketplaceUIState::Browse;
+            crossterm::event::KeyCode::Char('h') | crossterm::event::KeyCode::Char('H') => {
+                if let Some(item) = &self.selected_item {
+                    self.history = self.marketplace.version_history(&item.id).await;
+                    if self.history.is_some() {
+                        self.history_state.select(Some(0));
+                        self.state = MarketplaceUIState::History;
+                    }
+                }
             }
+            crossterm::event::KeyCode::Esc => match self.state {
+                MarketplaceUIState::Browse => self.closed = true,
+                _ => self.state = MarketplaceUIState::Browse,
+            },
             crossterm::event::KeyCode::Tab => {
                 self.switch_tab();
             }
@@ -419,15 +774,190 @@ impl MarketplaceUI {
         Ok(())
     }
 
+    async fn load_reviews(&mut self) -> Result<(), WarpError> {
+        let Some(item) = &self.selected_item else {
+            return Ok(());
+        };
+        self.reviews = self
+            .marketplace
+            .get_reviews(&item.id, 1, self.show_flagged)
+            .await?;
+        self.reviews_state.select(if self.reviews.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+        Ok(())
+    }
+
+    async fn handle_reviews_input(
+        &mut self,
+        key: crossterm::event::KeyCode,
+    ) -> Result<(), WarpError> {
+        let selected = self.reviews_state.selected();
+        let reason = match key {
+            crossterm::event::KeyCode::Up => {
+                self.reviews_state
+                    .select(selected.map(|index| index.saturating_sub(1)));
+                None
+            }
+            crossterm::event::KeyCode::Down => {
+                let last = self.reviews.len().saturating_sub(1);
+                self.reviews_state
+                    .select(selected.map(|index| (index + 1).min(last)));
+                None
+            }
+            crossterm::event::KeyCode::Char('w') | crossterm::event::KeyCode::Char('W') => {
+                self.composer = ReviewComposer::new();
+                self.notice = None;
+                self.state = MarketplaceUIState::Compose;
+                None
+            }
+            crossterm::event::KeyCode::Char('f') | crossterm::event::KeyCode::Char('F') => {
+                self.show_flagged = !self.show_flagged;
+                self.load_reviews().await?;
+                None
+            }
+            crossterm::event::KeyCode::Char('s') | crossterm::event::KeyCode::Char('S') => {
+                Some(moderation::ReportReason::Spam)
+            }
+            crossterm::event::KeyCode::Char('a') | crossterm::event::KeyCode::Char('A') => {
+                Some(moderation::ReportReason::Abuse)
+            }
+            crossterm::event::KeyCode::Esc => {
+                self.notice = None;
+                self.state = MarketplaceUIState::ItemDetails;
+                None
+            }
+            _ => None,
+        };
+
+        let (Some(reason), Some(item), Some(review)) = (
+            reason,
+            &self.selected_item,
+            selected.and_then(|index| self.reviews.get(index)),
+        ) else {
+            return Ok(());
+        };
+        self.notice = Some(
+            match self
+                .marketplace
+                .report_review(&item.id, &review.id, reason, None)
+                .await
+            {
+                Ok(()) => "reported, thanks".to_string(),
+                Err(e) => e.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn handle_compose_input(
+        &mut self,
+        key: crossterm::event::KeyCode,
+    ) -> Result<(), WarpError> {
+        let composer = &mut self.composer;
+        match (key, composer.field) {
+            (crossterm::event::KeyCode::Tab, field) => {
+                composer.field = match field {
+                    ComposeField::Rating => ComposeField::Title,
+                    ComposeField::Title => ComposeField::Body,
+                    ComposeField::Body => ComposeField::Rating,
+                };
+            }
+            (crossterm::event::KeyCode::Left, ComposeField::Rating) => {
+                composer.draft.rating = composer.draft.rating.saturating_sub(1).max(1);
+            }
+            (crossterm::event::KeyCode::Right, ComposeField::Rating) => {
+                composer.draft.rating = (composer.draft.rating + 1).min(5);
+            }
+            (crossterm::event::KeyCode::Char(c), ComposeField::Title) => {
+                composer.draft.title.push(c)
+            }
+            (crossterm::event::KeyCode::Char(c), ComposeField::Body) => composer.draft.body.push(c),
+            (crossterm::event::KeyCode::Enter, ComposeField::Body) => {
+                composer.draft.body.push('\n')
+            }
+            (crossterm::event::KeyCode::Backspace, ComposeField::Title) => {
+                composer.draft.title.pop();
+            }
+            (crossterm::event::KeyCode::Backspace, ComposeField::Body) => {
+                composer.draft.body.pop();
+            }
+            (crossterm::event::KeyCode::Enter, _) => {
+                let Some(item) = &self.selected_item else {
+                    return Ok(());
+                };
+                match self
+                    .marketplace
+                    .rate_item(&item.id, &self.composer.draft)
+                    .await
+                {
+                    Ok(()) => {
+                        self.notice = Some("review sent for moderation".to_string());
+                        self.state = MarketplaceUIState::Reviews;
+                        self.load_reviews().await?;
+                    }
+                    Err(e) => self.notice = Some(e.to_string()),
+                }
+            }
+            (crossterm::event::KeyCode::Esc, _) => {
+                self.notice = None;
+                self.state = MarketplaceUIState::Reviews;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // The installed version is the first row; the kept ones follow
+    async fn handle_history_input(
+        &mut self,
+        key: crossterm::event::KeyCode,
+    ) -> Result<(), WarpError> {
+        let Some(package) = &self.history else {
+            self.state = MarketplaceUIState::Browse;
+            return Ok(());
+        };
+        let selected = self.history_state.selected().unwrap_or(0);
+        match key {
+            crossterm::event::KeyCode::Up => {
+                self.history_state.select(Some(selected.saturating_sub(1)));
+            }
+            crossterm::event::KeyCode::Down => {
+                self.history_state
+                    .select(Some((selected + 1).min(package.history.len())));
+            }
+            crossterm::event::KeyCode::Enter if selected > 0 => {
+                let id = package.id.clone();
+                let version = package.history[selected - 1].item.version.clone();
+                if let Err(e) = self.marketplace.rollback_item(&id, Some(&version)).await {
+                    log::error!("Rollback failed: {}", e);
+                }
+                self.history = self.marketplace.version_history(&id).await;
+                self.history_state.select(Some(0));
+                self.load_installed_items().await?;
+            }
+            crossterm::event::KeyCode::Esc => {
+                self.state = MarketplaceUIState::Browse;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
     async fn install_selected_item(&mut self) -> Result<(), WarpError> {
         if let Some(item) = &self.selected_item {
             self.state = MarketplaceUIState::Installing;
-            
+
             // Install the item
             match self.marketplace.install_item(&item.id).await {
                 Ok(_) => {
                     // Installation successful
                     self.state = MarketplaceUIState::Browse;
+                    self.load_installed_items().await?;
                 }
                 Err(e) => {
                     // Handle installation error
@@ -452,6 +982,79 @@ impl MarketplaceUI {
     }
 }
 
+// Review Markdown as styled lines: headings, bullets, quotes, fenced code,
+// and inline `code`, **bold** and *emphasis*
+fn markdown_spans(text: &str) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(Line::from(Span::styled(
+                format!("  {}", line),
+                Style::default().fg(Color::Cyan),
+            )));
+        } else if let Some(heading) = trimmed.strip_prefix('#') {
+            lines.push(Line::from(Span::styled(
+                heading.trim_start_matches('#').trim().to_string(),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            lines.push(Line::from(Span::styled(
+                format!("│ {}", quote.trim()),
+                Style::default()
+                    .fg(Color::Gray)
+                    .add_modifier(Modifier::ITALIC),
+            )));
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            let mut spans = vec![Span::raw("  • ")];
+            spans.extend(inline_spans(item));
+            lines.push(Line::from(spans));
+        } else {
+            lines.push(Line::from(inline_spans(line)));
+        }
+    }
+    lines
+}
+
+fn inline_spans(text: &str) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for (i, part) in text.split('`').enumerate() {
+        if i % 2 == 1 {
+            spans.push(Span::styled(
+                part.to_string(),
+                Style::default().fg(Color::Cyan),
+            ));
+            continue;
+        }
+        for (j, piece) in part.split("**").enumerate() {
+            let bold = j % 2 == 1;
+            for (k, word) in piece.split('*').enumerate() {
+                let mut style = Style::default();
+                if bold {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if k % 2 == 1 {
+                    style = style.add_modifier(Modifier::ITALIC);
+                }
+                if !word.is_empty() {
+                    spans.push(Span::styled(word.to_string(), style));
+                }
+            }
+        }
+    }
+    spans
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
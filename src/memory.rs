@@ -0,0 +1,377 @@
+use crossterm::style::Color;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::config::MemoryConfig;
+use crate::error::WarpError;
+use crate::i18n;
+use crate::process_tree::format_bytes;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Samples kept per subsystem; ten minutes at the default interval
+const WINDOW: usize = 60;
+// Consecutive samples that each grew, after which a subsystem is flagged
+const LEAK_SAMPLES: usize = 6;
+
+pub fn strings_bytes<'a>(strings: impl IntoIterator<Item = &'a String>) -> u64 {
+    strings
+        .into_iter()
+        .map(|s| (std::mem::size_of::<String>() + s.capacity()) as u64)
+        .sum()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetAlert {
+    pub name: &'static str,
+    pub bytes: u64,
+    pub budget: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryRow {
+    pub name: &'static str,
+    pub bytes: u64,
+    pub budget: Option<u64>,
+    pub growth_per_min: f64,
+    pub leaking: bool,
+}
+
+#[derive(Serialize)]
+struct DumpSeries {
+    budget: Option<u64>,
+    // (seconds since the tracker started, bytes)
+    samples: Vec<(u64, u64)>,
+}
+
+#[derive(Serialize)]
+struct Dump {
+    created: chrono::DateTime<chrono::Local>,
+    uptime_secs: u64,
+    subsystems: BTreeMap<&'static str, DumpSeries>,
+}
+
+// Per-subsystem memory estimates over time. Sizes come from the
+// subsystems themselves (string capacities and the like), plus the whole
+// process's resident size for comparison.
+pub struct MemoryTracker {
+    started: Instant,
+    budgets: HashMap<String, u64>,
+    // In the order subsystems were first recorded
+    series: Vec<(&'static str, VecDeque<(Instant, u64)>)>,
+    over: HashSet<&'static str>,
+}
+
+impl MemoryTracker {
+    pub fn new(config: &MemoryConfig) -> Self {
+        Self {
+            started: Instant::now(),
+            budgets: config
+                .budgets_mb
+                .iter()
+                .map(|(name, mb)| (name.clone(), mb * 1024 * 1024))
+                .collect(),
+            series: Vec::new(),
+            over: HashSet::new(),
+        }
+    }
+
+    // Returns an alert the first time a subsystem goes over its budget; it
+    // has to drop back under before it alerts again
+    pub fn record(&mut self, name: &'static str, bytes: u64, now: Instant) -> Option<BudgetAlert> {
+        let index = match self.series.iter().position(|(n, _)| *n == name) {
+            Some(index) => index,
+            None => {
+                self.series.push((name, VecDeque::new()));
+                self.series.len() - 1
+            }
+        };
+        let samples = &mut self.series[index].1;
+        if samples.len() == WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back((now, bytes));
+
+        let budget = *self.budgets.get(name)?;
+        if bytes <= budget {
+            self.over.remove(name);
+            return None;
+        }
+        self.over.insert(name).then_some(BudgetAlert {
+            name,
+            bytes,
+            budget,
+        })
+    }
+
+    fn samples(&self, name: &str) -> Option<&VecDeque<(Instant, u64)>> {
+        self.series
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, samples)| samples)
+    }
+
+    pub fn current(&self, name: &str) -> Option<u64> {
+        self.samples(name)?.back().map(|(_, bytes)| *bytes)
+    }
+
+    // Bytes per minute across the kept window
+    pub fn growth_per_min(&self, name: &str) -> f64 {
+        let Some(samples) = self.samples(name) else {
+            return 0.0;
+        };
+        match (samples.front(), samples.back()) {
+            (Some((first_at, first)), Some((last_at, last))) if last_at > first_at => {
+                let minutes = last_at.duration_since(*first_at).as_secs_f64() / 60.0;
+                (*last as f64 - *first as f64) / minutes
+            }
+            _ => 0.0,
+        }
+    }
+
+    // Grew in each of the last LEAK_SAMPLES samples
+    pub fn suspected_leak(&self, name: &str) -> bool {
+        let Some(samples) = self.samples(name) else {
+            return false;
+        };
+        samples.len() > LEAK_SAMPLES
+            && samples
+                .iter()
+                .skip(samples.len() - LEAK_SAMPLES - 1)
+                .zip(samples.iter().skip(samples.len() - LEAK_SAMPLES))
+                .all(|((_, before), (_, after))| after > before)
+    }
+
+    pub fn rows(&self) -> Vec<MemoryRow> {
+        self.series
+            .iter()
+            .map(|(name, samples)| MemoryRow {
+                name,
+                bytes: samples.back().map_or(0, |(_, bytes)| *bytes),
+                budget: self.budgets.get(*name).copied(),
+                growth_per_min: self.growth_per_min(name),
+                leaking: self.suspected_leak(name),
+            })
+            .collect()
+    }
+
+    // Writes every kept sample as JSON for offline diagnosis
+    pub fn dump(&self, directory: &Path) -> Result<PathBuf, WarpError> {
+        let subsystems = self
+            .series
+            .iter()
+            .map(|(name, samples)| {
+                let series = DumpSeries {
+                    budget: self.budgets.get(*name).copied(),
+                    samples: samples
+                        .iter()
+                        .map(|(at, bytes)| (at.duration_since(self.started).as_secs(), *bytes))
+                        .collect(),
+                };
+                (*name, series)
+            })
+            .collect();
+        let dump = Dump {
+            created: chrono::Local::now(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            subsystems,
+        };
+        std::fs::create_dir_all(directory)?;
+        let path = directory.join(format!(
+            "warp-memory-{}.json",
+            dump.created.format("%Y%m%d-%H%M%S")
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(&dump)?)?;
+        Ok(path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryCommand {
+    None,
+    Redraw,
+    Dump,
+}
+
+// The performance overlay: one row per tracked subsystem
+pub struct MemoryPanel {
+    rows: Vec<MemoryRow>,
+    sample_secs: u64,
+    status: Option<String>,
+    selected: usize,
+}
+
+impl MemoryPanel {
+    pub fn new(tracker: &MemoryTracker, sample_secs: u64) -> Self {
+        Self {
+            rows: tracker.rows(),
+            sample_secs,
+            status: None,
+            selected: 0,
+        }
+    }
+
+    pub fn refresh(&mut self, tracker: &MemoryTracker) {
+        self.rows = tracker.rows();
+        self.select(self.selected);
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> MemoryCommand {
+        self.select(index);
+        match key {
+            'd' => MemoryCommand::Dump,
+            '\n' => MemoryCommand::None,
+            _ => MemoryCommand::Redraw,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let over = row.budget.is_some_and(|budget| row.bytes > budget);
+                let color = if over {
+                    Color::Red
+                } else if row.leaking {
+                    Color::Yellow
+                } else {
+                    Color::Green
+                };
+                let budget = row
+                    .budget
+                    .map(|budget| format!(" / {}", format_bytes(budget)))
+                    .unwrap_or_default();
+                let mut segments = vec![
+                    (format!("{:<14}", row.name), Some(color)),
+                    (
+                        format!("{:>10}{:<12}", format_bytes(row.bytes), budget),
+                        None,
+                    ),
+                ];
+                if row.growth_per_min.abs() >= 1.0 {
+                    let sign = if row.growth_per_min > 0.0 { "+" } else { "-" };
+                    segments.push((
+                        format!(
+                            " {}{}/min",
+                            sign,
+                            format_bytes(row.growth_per_min.abs() as u64)
+                        ),
+                        Some(Color::DarkGrey),
+                    ));
+                }
+                if row.leaking {
+                    segments.push((
+                        format!(" {}", i18n::t("memory.growing")),
+                        Some(Color::Yellow),
+                    ));
+                }
+                PanelItem::styled(segments)
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.memory_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::Performance,
+            title: i18n::t_args("panel.memory", &[("secs", &self.sample_secs.to_string())]),
+            items,
+            footer: Some(
+                self.status
+                    .clone()
+                    .unwrap_or_else(|| i18n::t("panel.memory_keys")),
+            ),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn config() -> MemoryConfig {
+        MemoryConfig {
+            enabled: true,
+            sample_secs: 10,
+            budgets_mb: [("scrollback".to_string(), 1)].into_iter().collect(),
+            dump_directory: None,
+        }
+    }
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_budget_alerts_once_until_back_under() {
+        let mut tracker = MemoryTracker::new(&config());
+        let now = Instant::now();
+        assert_eq!(tracker.record("scrollback", MB / 2, now), None);
+        let alert = tracker.record("scrollback", 2 * MB, now).unwrap();
+        assert_eq!(alert.budget, MB);
+        assert_eq!(tracker.record("scrollback", 3 * MB, now), None);
+        assert_eq!(tracker.record("scrollback", MB / 2, now), None);
+        assert!(tracker.record("scrollback", 2 * MB, now).is_some());
+        // No budget, no alert
+        assert_eq!(tracker.record("history", 100 * MB, now), None);
+    }
+
+    #[test]
+    fn test_steady_growth_is_flagged() {
+        let mut tracker = MemoryTracker::new(&config());
+        let start = Instant::now();
+        for i in 0..=LEAK_SAMPLES as u64 {
+            let at = start + Duration::from_secs(i * 10);
+            tracker.record("history", 1000 + i * 600, at);
+            tracker.record("block", 1000 + (i % 2) * 600, at);
+        }
+        assert!(tracker.suspected_leak("history"));
+        assert!(!tracker.suspected_leak("block"));
+        assert!((tracker.growth_per_min("history") - 3600.0).abs() < 1.0);
+        assert_eq!(tracker.current("history"), Some(1000 + 6 * 600));
+
+        let rows = tracker.rows();
+        assert_eq!(rows[0].name, "history");
+        assert!(rows[0].leaking);
+    }
+
+    #[test]
+    fn test_window_is_bounded() {
+        let mut tracker = MemoryTracker::new(&config());
+        let start = Instant::now();
+        for i in 0..(WINDOW as u64 * 2) {
+            tracker.record("scrollback", i, start + Duration::from_secs(i));
+        }
+        assert_eq!(tracker.samples("scrollback").unwrap().len(), WINDOW);
+    }
+
+    #[test]
+    fn test_dump_writes_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tracker = MemoryTracker::new(&config());
+        tracker.record("scrollback", 42, Instant::now());
+        let path = tracker.dump(dir.path()).unwrap();
+        let dump: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(dump["subsystems"]["scrollback"]["budget"], MB);
+        assert_eq!(dump["subsystems"]["scrollback"]["samples"][0][1], 42);
+    }
+
+    #[test]
+    fn test_panel_dump_key() {
+        let tracker = MemoryTracker::new(&config());
+        let mut panel = MemoryPanel::new(&tracker, 10);
+        assert_eq!(panel.handle_key('d', 0), MemoryCommand::Dump);
+        assert_eq!(panel.view().items.len(), 1);
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::{ClusterCharacteristic, UserCluster};
+use crate::error::WarpError;
+
+const MAX_ITERATIONS: usize = 100;
+// Characteristics and representative users kept per cluster
+const TOP_CHARACTERISTICS: usize = 5;
+const REPRESENTATIVES: usize = 3;
+
+// Labels and centroids from one k-means run
+type Clustering = (Vec<usize>, Vec<Vec<f64>>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusteringConfig {
+    pub min_k: usize,
+    pub max_k: usize,
+    pub refresh_interval: chrono::Duration,
+}
+
+impl Default for ClusteringConfig {
+    fn default() -> Self {
+        Self {
+            min_k: 2,
+            max_k: 8,
+            refresh_interval: chrono::Duration::hours(6),
+        }
+    }
+}
+
+// The last clustering run, as persisted between sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClusteringSnapshot {
+    pub clusters: Vec<UserCluster>,
+    pub assignments: HashMap<String, String>,
+    pub silhouette: f64,
+    pub refreshed_at: Option<DateTime<Utc>>,
+}
+
+// Groups users by their usage features with k-means, picking k by the mean
+// silhouette score
+#[derive(Debug)]
+pub struct UserClusterer {
+    config: ClusteringConfig,
+    features: Mutex<HashMap<String, HashMap<String, f64>>>,
+    snapshot: Mutex<ClusteringSnapshot>,
+    path: Option<PathBuf>,
+}
+
+impl UserClusterer {
+    pub async fn new() -> Result<Self, WarpError> {
+        let path = dirs::data_dir().map(|dir| dir.join("warp/ml/clusters.json"));
+        Self::with_path(path, ClusteringConfig::default()).await
+    }
+
+    pub async fn with_path(
+        path: Option<PathBuf>,
+        config: ClusteringConfig,
+    ) -> Result<Self, WarpError> {
+        let snapshot = match &path {
+            Some(path) if path.exists() => {
+                let data = tokio::fs::read(path).await?;
+                serde_json::from_slice(&data).unwrap_or_else(|e| {
+                    log::warn!(
+                        "Ignoring unreadable cluster snapshot {}: {}",
+                        path.display(),
+                        e
+                    );
+                    ClusteringSnapshot::default()
+                })
+            }
+            _ => ClusteringSnapshot::default(),
+        };
+        Ok(Self {
+            config,
+            features: Mutex::new(HashMap::new()),
+            snapshot: Mutex::new(snapshot),
+            path,
+        })
+    }
+
+    pub async fn set_features(&self, features: HashMap<String, HashMap<String, f64>>) {
+        *self.features.lock().await = features;
+    }
+
+    pub async fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        let snapshot = self.snapshot.lock().await;
+        snapshot
+            .refreshed_at
+            .is_none_or(|at| now - at >= self.config.refresh_interval)
+    }
+
+    pub async fn snapshot(&self) -> ClusteringSnapshot {
+        self.snapshot.lock().await.clone()
+    }
+
+    pub async fn get_user_cluster(&self, user_id: &str) -> Result<UserCluster, WarpError> {
+        let snapshot = self.snapshot.lock().await;
+        snapshot
+            .assignments
+            .get(user_id)
+            .and_then(|cluster_id| {
+                snapshot
+                    .clusters
+                    .iter()
+                    .find(|cluster| &cluster.cluster_id == cluster_id)
+            })
+            .cloned()
+            .ok_or_else(|| WarpError::not_found("user cluster", user_id))
+    }
+
+    pub async fn perform_clustering(&self) -> Result<Vec<UserCluster>, WarpError> {
+        let features = self.features.lock().await.clone();
+        let snapshot = cluster(&features, &self.config)?;
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, serde_json::to_vec_pretty(&snapshot)?).await?;
+        }
+        let clusters = snapshot.clusters.clone();
+        *self.snapshot.lock().await = snapshot;
+        Ok(clusters)
+    }
+}
+
+// Rows are standardised so no single feature dominates the distance
+struct Dataset {
+    users: Vec<String>,
+    feature_names: Vec<String>,
+    rows: Vec<Vec<f64>>,
+}
+
+impl Dataset {
+    fn new(features: &HashMap<String, HashMap<String, f64>>) -> Self {
+        let mut users: Vec<String> = features.keys().cloned().collect();
+        users.sort();
+        let mut feature_names: Vec<String> = features
+            .values()
+            .flat_map(|row| row.keys().cloned())
+            .collect();
+        feature_names.sort();
+        feature_names.dedup();
+
+        let raw: Vec<Vec<f64>> = users
+            .iter()
+            .map(|user| {
+                feature_names
+                    .iter()
+                    .map(|name| features[user].get(name).copied().unwrap_or(0.0))
+                    .collect()
+            })
+            .collect();
+        let n = raw.len().max(1) as f64;
+        let mut rows = raw.clone();
+        for j in 0..feature_names.len() {
+            let mean = raw.iter().map(|row| row[j]).sum::<f64>() / n;
+            let std = (raw.iter().map(|row| (row[j] - mean).powi(2)).sum::<f64>() / n).sqrt();
+            for row in &mut rows {
+                row[j] = if std > 0.0 {
+                    (row[j] - mean) / std
+                } else {
+                    0.0
+                };
+            }
+        }
+        Self {
+            users,
+            feature_names,
+            rows,
+        }
+    }
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+fn nearest(row: &[f64], centroids: &[Vec<f64>]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .min_by(|a, b| distance(row, a.1).total_cmp(&distance(row, b.1)))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+// Deterministic farthest-point seeding: start from the row nearest the
+// overall mean, then repeatedly take the row farthest from every chosen seed
+fn seeds(rows: &[Vec<f64>], k: usize) -> Vec<Vec<f64>> {
+    let origin = vec![0.0; rows[0].len()];
+    let first = nearest(&origin, rows);
+    let mut centroids = vec![rows[first].clone()];
+    while centroids.len() < k {
+        let next = rows
+            .iter()
+            .enumerate()
+            .max_by(|a, b| {
+                let da = centroids
+                    .iter()
+                    .map(|c| distance(a.1, c))
+                    .fold(f64::MAX, f64::min);
+                let db = centroids
+                    .iter()
+                    .map(|c| distance(b.1, c))
+                    .fold(f64::MAX, f64::min);
+                da.total_cmp(&db)
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        centroids.push(rows[next].clone());
+    }
+    centroids
+}
+
+fn kmeans(rows: &[Vec<f64>], k: usize) -> Clustering {
+    let mut centroids = seeds(rows, k);
+    let mut labels = vec![usize::MAX; rows.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let next: Vec<usize> = rows.iter().map(|row| nearest(row, &centroids)).collect();
+        if next == labels {
+            break;
+        }
+        labels = next;
+        for (c, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<&Vec<f64>> = rows
+                .iter()
+                .zip(&labels)
+                .filter(|(_, l)| **l == c)
+                .map(|(row, _)| row)
+                .collect();
+            // An emptied cluster keeps its old centroid
+            if members.is_empty() {
+                continue;
+            }
+            for (j, value) in centroid.iter_mut().enumerate() {
+                *value = members.iter().map(|row| row[j]).sum::<f64>() / members.len() as f64;
+            }
+        }
+    }
+    (labels, centroids)
+}
+
+// Mean silhouette over all rows; rows alone in their cluster score 0
+fn silhouette(rows: &[Vec<f64>], labels: &[usize], k: usize) -> f64 {
+    let mut total = 0.0;
+    for (i, row) in rows.iter().enumerate() {
+        let mut sums = vec![0.0; k];
+        let mut counts = vec![0usize; k];
+        for (j, other) in rows.iter().enumerate() {
+            if i != j {
+                sums[labels[j]] += distance(row, other);
+                counts[labels[j]] += 1;
+            }
+        }
+        let own = labels[i];
+        if counts[own] == 0 {
+            continue;
+        }
+        let a = sums[own] / counts[own] as f64;
+        let b = (0..k)
+            .filter(|&c| c != own && counts[c] > 0)
+            .map(|c| sums[c] / counts[c] as f64)
+            .fold(f64::MAX, f64::min);
+        if b == f64::MAX {
+            continue;
+        }
+        total += (b - a) / a.max(b).max(f64::EPSILON);
+    }
+    total / rows.len() as f64
+}
+
+pub fn cluster(
+    features: &HashMap<String, HashMap<String, f64>>,
+    config: &ClusteringConfig,
+) -> Result<ClusteringSnapshot, WarpError> {
+    let data = Dataset::new(features);
+    if data.rows.len() <= config.min_k {
+        return Err(WarpError::Validation(format!(
+            "clustering needs more than {} users, got {}",
+            config.min_k,
+            data.rows.len()
+        )));
+    }
+
+    let max_k = config.max_k.min(data.rows.len() - 1).max(config.min_k);
+    let mut best: Option<(f64, Clustering)> = None;
+    for k in config.min_k..=max_k {
+        let (labels, centroids) = kmeans(&data.rows, k);
+        let score = silhouette(&data.rows, &labels, k);
+        if best
+            .as_ref()
+            .is_none_or(|(best_score, _)| score > *best_score)
+        {
+            best = Some((score, (labels, centroids)));
+        }
+    }
+    let Some((score, (labels, centroids))) = best else {
+        return Err(WarpError::Validation(
+            "no clustering candidates".to_string(),
+        ));
+    };
+
+    let mut clusters = Vec::new();
+    let mut assignments = HashMap::new();
+    for (c, centroid) in centroids.iter().enumerate() {
+        let mut members: Vec<usize> = (0..data.rows.len()).filter(|&i| labels[i] == c).collect();
+        if members.is_empty() {
+            continue;
+        }
+        let cluster_id = format!("cluster-{}", clusters.len() + 1);
+        for &i in &members {
+            assignments.insert(data.users[i].clone(), cluster_id.clone());
+        }
+        members.sort_by(|&a, &b| {
+            distance(&data.rows[a], centroid).total_cmp(&distance(&data.rows[b], centroid))
+        });
+        clusters.push(describe(&data, cluster_id, centroid, &members));
+    }
+
+    Ok(ClusteringSnapshot {
+        clusters,
+        assignments,
+        silhouette: score,
+        refreshed_at: Some(Utc::now()),
+    })
+}
+
+// A cluster's characteristics are the features whose centroid sits furthest
+// from the population mean, with importance as each one's share of that spread
+fn describe(
+    data: &Dataset,
+    cluster_id: String,
+    centroid: &[f64],
+    members: &[usize],
+) -> UserCluster {
+    let spread: f64 = centroid.iter().map(|z| z.abs()).sum();
+    let mut characteristics: Vec<ClusterCharacteristic> = data
+        .feature_names
+        .iter()
+        .zip(centroid)
+        .map(|(name, z)| ClusterCharacteristic {
+            feature: name.clone(),
+            value: *z,
+            importance: if spread > 0.0 { z.abs() / spread } else { 0.0 },
+            description: format!(
+                "{} is {:.1} standard deviations {} average",
+                name,
+                z.abs(),
+                if *z >= 0.0 { "above" } else { "below" }
+            ),
+        })
+        .collect();
+    characteristics.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+    characteristics.truncate(TOP_CHARACTERISTICS);
+
+    let name = match characteristics.first() {
+        Some(top) if top.importance > 0.0 => format!(
+            "{} {}",
+            if top.value >= 0.0 { "High" } else { "Low" },
+            top.feature
+        ),
+        _ => "Typical users".to_string(),
+    };
+    let description = characteristics
+        .iter()
+        .take(2)
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    UserCluster {
+        cluster_id,
+        name,
+        description,
+        characteristics,
+        size: members.len() as u32,
+        representative_users: members
+            .iter()
+            .take(REPRESENTATIVES)
+            .map(|&i| data.users[i].clone())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> HashMap<String, HashMap<String, f64>> {
+        let mut features = HashMap::new();
+        for i in 0..30 {
+            let jitter = (i % 5) as f64 * 0.3;
+            let (sessions, ai) = match i % 3 {
+                0 => (40.0 + jitter, 0.0 + jitter),
+                1 => (5.0 + jitter, 30.0 + jitter),
+                _ => (2.0 + jitter, 1.0 + jitter),
+            };
+            features.insert(
+                format!("user-{:02}", i),
+                HashMap::from([
+                    ("sessions_last_30d".to_string(), sessions),
+                    ("ai_requests_last_30d".to_string(), ai),
+                ]),
+            );
+        }
+        features
+    }
+
+    #[test]
+    fn test_silhouette_picks_natural_cluster_count() {
+        let snapshot = cluster(&users(), &ClusteringConfig::default()).unwrap();
+        assert_eq!(snapshot.clusters.len(), 3);
+        assert!(snapshot.silhouette > 0.8);
+        assert!(snapshot.clusters.iter().all(|cluster| cluster.size == 10));
+        // Users 0, 3, 6... are heavy terminal users
+        let heavy = &snapshot.assignments["user-00"];
+        assert_eq!(&snapshot.assignments["user-03"], heavy);
+        assert_ne!(&snapshot.assignments["user-01"], heavy);
+    }
+
+    #[test]
+    fn test_characteristics_describe_the_centroid() {
+        let snapshot = cluster(&users(), &ClusteringConfig::default()).unwrap();
+        let ai_cluster_id = &snapshot.assignments["user-01"];
+        let ai_cluster = snapshot
+            .clusters
+            .iter()
+            .find(|cluster| &cluster.cluster_id == ai_cluster_id)
+            .unwrap();
+        assert_eq!(ai_cluster.name, "High ai_requests_last_30d");
+        assert_eq!(
+            ai_cluster.characteristics[0].feature,
+            "ai_requests_last_30d"
+        );
+        let total: f64 = ai_cluster
+            .characteristics
+            .iter()
+            .map(|c| c.importance)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_too_few_users_is_an_error() {
+        let features = HashMap::from([(
+            "solo".to_string(),
+            HashMap::from([("sessions".to_string(), 1.0)]),
+        )]);
+        assert!(cluster(&features, &ClusteringConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use super::{FactorDirection, PredictionFactor};
+
+// Permutations sampled per attribution; enough for stable rankings over the
+// handful of features our models use
+pub const SHAPLEY_SAMPLES: usize = 64;
+
+type Row = HashMap<String, f64>;
+
+// Cheap deterministic shuffle so the same prediction is always explained
+// the same way
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+// Sampled Shapley values: how much each feature moved the score from the
+// average over `background` to this instance's score. Each sample starts
+// from a background row and switches features to the instance's values in
+// a random order, crediting each feature with the change it causes.
+pub fn shapley_values(
+    predict: impl Fn(&Row) -> f64,
+    instance: &Row,
+    background: &[Row],
+    feature_names: &[String],
+    samples: usize,
+) -> Vec<(String, f64)> {
+    let mut totals = vec![0.0; feature_names.len()];
+    if background.is_empty() || samples == 0 {
+        return feature_names.iter().cloned().zip(totals).collect();
+    }
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+    let mut order: Vec<usize> = (0..feature_names.len()).collect();
+    for sample in 0..samples {
+        rng.shuffle(&mut order);
+        let mut row = background[sample % background.len()].clone();
+        let mut previous = predict(&row);
+        for &j in &order {
+            let name = &feature_names[j];
+            match instance.get(name) {
+                Some(value) => row.insert(name.clone(), *value),
+                None => row.remove(name),
+            };
+            let current = predict(&row);
+            totals[j] += current - previous;
+            previous = current;
+        }
+    }
+    feature_names
+        .iter()
+        .cloned()
+        .zip(totals.into_iter().map(|total| total / samples as f64))
+        .collect()
+}
+
+// How much the Brier score worsens when a feature's column is scrambled
+// across examples; features the model ignores score zero
+pub fn permutation_importance(
+    predict: impl Fn(&Row) -> f64,
+    examples: &[(Row, bool)],
+    feature_names: &[String],
+) -> Vec<(String, f64)> {
+    let brier = |rows: &[Row]| {
+        rows.iter()
+            .zip(examples)
+            .map(|(row, (_, label))| (predict(row) - if *label { 1.0 } else { 0.0 }).powi(2))
+            .sum::<f64>()
+            / examples.len().max(1) as f64
+    };
+    let rows: Vec<Row> = examples.iter().map(|(row, _)| row.clone()).collect();
+    let baseline = brier(&rows);
+    feature_names
+        .iter()
+        .map(|name| {
+            // Rotating the column by half keeps its distribution but breaks
+            // its link to each example's label
+            let shift = rows.len() / 2;
+            let permuted: Vec<Row> = (0..rows.len())
+                .map(|i| {
+                    let mut row = rows[i].clone();
+                    match rows[(i + shift) % rows.len()].get(name) {
+                        Some(value) => row.insert(name.clone(), *value),
+                        None => row.remove(name),
+                    };
+                    row
+                })
+                .collect();
+            (name.clone(), (brier(&permuted) - baseline).max(0.0))
+        })
+        .collect()
+}
+
+// Largest contribution first
+pub fn to_factors(contributions: Vec<(String, f64)>, instance: &Row) -> Vec<PredictionFactor> {
+    let mut factors: Vec<PredictionFactor> = contributions
+        .into_iter()
+        .map(|(name, contribution)| {
+            let direction = if contribution > 1e-9 {
+                FactorDirection::Positive
+            } else if contribution < -1e-9 {
+                FactorDirection::Negative
+            } else {
+                FactorDirection::Neutral
+            };
+            let description = match instance.get(&name) {
+                Some(value) => format!(
+                    "{} = {:.2} moved the score {:+.1} points",
+                    name,
+                    value,
+                    contribution * 100.0
+                ),
+                None => format!(
+                    "{} missing, moved the score {:+.1} points",
+                    name,
+                    contribution * 100.0
+                ),
+            };
+            PredictionFactor {
+                feature_name: name,
+                importance: contribution.abs(),
+                direction,
+                description,
+            }
+        })
+        .collect();
+    factors.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(a: f64, b: f64) -> Row {
+        HashMap::from([("a".to_string(), a), ("b".to_string(), b)])
+    }
+
+    #[test]
+    fn test_shapley_values_of_additive_model_are_exact() {
+        let predict = |row: &Row| 2.0 * row["a"] - row["b"];
+        let names = vec!["a".to_string(), "b".to_string()];
+        let background = vec![row(0.0, 0.0), row(2.0, 2.0)];
+        let values = shapley_values(predict, &row(3.0, 1.0), &background, &names, 32);
+        // Background averages a = 1, b = 1
+        assert!((values[0].1 - 4.0).abs() < 1e-9);
+        assert!((values[1].1 - 0.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shapley_values_sum_to_score_change() {
+        let predict = |row: &Row| row["a"] * row["b"];
+        let names = vec!["a".to_string(), "b".to_string()];
+        let background = vec![row(1.0, 1.0)];
+        let values = shapley_values(predict, &row(3.0, 1.5), &background, &names, 16);
+        let total: f64 = values.iter().map(|(_, v)| v).sum();
+        assert!((total - 3.5).abs() < 1e-9);
+        let factors = to_factors(values, &row(3.0, 1.5));
+        assert_eq!(factors[0].feature_name, "a");
+    }
+
+    #[test]
+    fn test_permutation_importance_ignores_unused_features() {
+        let predict = |row: &Row| if row["a"] > 0.5 { 1.0 } else { 0.0 };
+        let examples: Vec<(Row, bool)> = (0..10)
+            .map(|i| (row((i % 2) as f64, i as f64), i % 2 == 1))
+            .collect();
+        let names = vec!["a".to_string(), "b".to_string()];
+        let importance = permutation_importance(predict, &examples, &names);
+        assert!(importance[0].1 > 0.5);
+        assert_eq!(importance[1].1, 0.0);
+    }
+}
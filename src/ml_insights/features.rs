@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use tokio::sync::RwLock;
+
+use super::training::LabeledExample;
+use crate::error::WarpError;
+
+// Per-user usage features, outcome labels per model, and metric series.
+// Whatever collects usage fills it in; the prediction pipelines, clustering
+// and trend analysis read from it. Held in memory only.
+// Samples in time order
+pub type MetricSeries = Vec<(DateTime<Utc>, f64)>;
+
+#[derive(Debug, Default)]
+pub struct FeatureStore {
+    users: RwLock<HashMap<String, HashMap<String, f64>>>,
+    // Model name to user id to whether the predicted outcome happened
+    outcomes: RwLock<HashMap<String, HashMap<String, bool>>>,
+    metrics: RwLock<HashMap<String, MetricSeries>>,
+}
+
+impl FeatureStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Merged into the user's row; features not given keep their values
+    pub async fn set_user_features(&self, user_id: &str, features: HashMap<String, f64>) {
+        self.users
+            .write()
+            .await
+            .entry(user_id.to_string())
+            .or_default()
+            .extend(features);
+    }
+
+    pub async fn set_outcome(&self, model_name: &str, user_id: &str, label: bool) {
+        self.outcomes
+            .write()
+            .await
+            .entry(model_name.to_string())
+            .or_default()
+            .insert(user_id.to_string(), label);
+    }
+
+    // Kept in time order, so samples may arrive late
+    pub async fn record_metric(&self, metric_name: &str, at: DateTime<Utc>, value: f64) {
+        let mut metrics = self.metrics.write().await;
+        let series = metrics.entry(metric_name.to_string()).or_default();
+        let index = series.partition_point(|(time, _)| *time <= at);
+        series.insert(index, (at, value));
+    }
+
+    pub async fn get_numeric_features(
+        &self,
+        user_id: &str,
+    ) -> Result<HashMap<String, f64>, WarpError> {
+        self.users
+            .read()
+            .await
+            .get(user_id)
+            .cloned()
+            .ok_or_else(|| WarpError::not_found("user features", user_id))
+    }
+
+    pub async fn get_all_numeric_features(&self) -> HashMap<String, HashMap<String, f64>> {
+        self.users.read().await.clone()
+    }
+
+    // Users with both features and a label for the model, in user id order
+    // so the training holdout is stable between runs
+    pub async fn get_labeled_examples(&self, model_name: &str) -> Vec<LabeledExample> {
+        let users = self.users.read().await;
+        let outcomes = self.outcomes.read().await;
+        let Some(labels) = outcomes.get(model_name) else {
+            return Vec::new();
+        };
+        let mut examples: Vec<LabeledExample> = labels
+            .iter()
+            .filter_map(|(user_id, label)| {
+                Some(LabeledExample {
+                    user_id: user_id.clone(),
+                    features: users.get(user_id)?.clone(),
+                    label: *label,
+                })
+            })
+            .collect();
+        examples.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+        examples
+    }
+
+    // Samples from the last `range`, oldest first
+    pub async fn get_metric_history(&self, metric_name: &str, range: Duration) -> MetricSeries {
+        let since = Utc::now() - range;
+        self.metrics
+            .read()
+            .await
+            .get(metric_name)
+            .map(|series| {
+                series
+                    .iter()
+                    .filter(|(at, _)| *at >= since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub async fn metric_series(&self) -> HashMap<String, MetricSeries> {
+        self.metrics.read().await.clone()
+    }
+
+    // Drops samples older than `keep`, so a long-running sampler stays bounded
+    pub async fn prune_metrics(&self, keep: Duration) {
+        let since = Utc::now() - keep;
+        for series in self.metrics.write().await.values_mut() {
+            series.retain(|(at, _)| *at >= since);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_labeled_examples_join_features_and_outcomes() {
+        let store = FeatureStore::new();
+        store
+            .set_user_features("b", HashMap::from([("sessions_last_30d".to_string(), 4.0)]))
+            .await;
+        store
+            .set_user_features(
+                "a",
+                HashMap::from([("sessions_last_30d".to_string(), 20.0)]),
+            )
+            .await;
+        store
+            .set_user_features("a", HashMap::from([("error_rate".to_string(), 0.1)]))
+            .await;
+        store.set_outcome("churn", "b", true).await;
+        store.set_outcome("churn", "a", false).await;
+        store.set_outcome("churn", "unknown", true).await;
+
+        let examples = store.get_labeled_examples("churn").await;
+        assert_eq!(
+            examples
+                .iter()
+                .map(|e| e.user_id.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(examples[0].features.len(), 2);
+        assert!(examples[1].label);
+        assert!(store
+            .get_labeled_examples("feature_adoption")
+            .await
+            .is_empty());
+        assert!(store.get_numeric_features("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_metric_history_is_ordered_and_windowed() {
+        let store = FeatureStore::new();
+        let now = Utc::now();
+        store
+            .record_metric("installs", now - Duration::hours(1), 2.0)
+            .await;
+        store
+            .record_metric("installs", now - Duration::days(10), 1.0)
+            .await;
+        store
+            .record_metric("installs", now - Duration::hours(3), 3.0)
+            .await;
+
+        let history = store
+            .get_metric_history("installs", Duration::days(1))
+            .await;
+        assert_eq!(
+            history.iter().map(|(_, v)| *v).collect::<Vec<_>>(),
+            [3.0, 2.0]
+        );
+        assert_eq!(store.metric_series().await["installs"].len(), 3);
+
+        store.prune_metrics(Duration::days(1)).await;
+        assert_eq!(store.metric_series().await["installs"].len(), 2);
+    }
+}
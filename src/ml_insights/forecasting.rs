@@ -0,0 +1,441 @@
+use serde::{Deserialize, Serialize};
+
+// Autocorrelation a lag needs before it's treated as a season
+const SEASONALITY_THRESHOLD: f64 = 0.3;
+// Smoothing parameters tried when fitting Holt and Holt-Winters
+const GRID: [f64; 9] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9];
+// z for a 95% interval
+pub const Z_95: f64 = 1.96;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ForecastModel {
+    // Last value plus the average historical step
+    Drift,
+    Holt {
+        alpha: f64,
+        beta: f64,
+    },
+    HoltWinters {
+        alpha: f64,
+        beta: f64,
+        gamma: f64,
+        period: usize,
+    },
+    // ARIMA(p, 1, 0): an autoregression on first differences
+    Arima {
+        p: usize,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forecast {
+    pub model: ForecastModel,
+    pub values: Vec<f64>,
+    // Standard error of each step; grows with the horizon
+    pub std_errors: Vec<f64>,
+    // Root mean squared error on the held-out tail the model was picked by
+    pub backtest_rmse: Option<f64>,
+}
+
+impl Forecast {
+    pub fn bounds(&self, step: usize, z: f64) -> (f64, f64) {
+        let value = self.values[step];
+        let spread = z * self.std_errors[step];
+        (value - spread, value + spread)
+    }
+}
+
+pub fn autocorrelation(values: &[f64], lag: usize) -> f64 {
+    let n = values.len();
+    if lag == 0 || lag >= n {
+        return if lag == 0 { 1.0 } else { 0.0 };
+    }
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let variance: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    if variance == 0.0 {
+        return 0.0;
+    }
+    let covariance: f64 = (lag..n)
+        .map(|i| (values[i] - mean) * (values[i - lag] - mean))
+        .sum();
+    covariance / variance
+}
+
+// The lag with the strongest autocorrelation peak after removing the trend,
+// if it's strong enough and the series covers at least two full seasons
+pub fn detect_period(values: &[f64]) -> Option<(usize, f64)> {
+    let detrended: Vec<f64> = values.windows(2).map(|w| w[1] - w[0]).collect();
+    let max_lag = detrended.len() / 2;
+    let acf: Vec<f64> = (0..=max_lag)
+        .map(|lag| autocorrelation(&detrended, lag))
+        .collect();
+    (2..max_lag)
+        .filter(|&lag| acf[lag] > acf[lag - 1] && acf[lag] >= acf[lag + 1])
+        .map(|lag| (lag, acf[lag]))
+        .filter(|(_, strength)| *strength >= SEASONALITY_THRESHOLD)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+// Fits every candidate on all but the last `holdout` points, keeps the one
+// with the lowest error on them, and refits it on the whole series
+pub fn forecast(values: &[f64], horizon: usize) -> Forecast {
+    if values.len() < 4 {
+        return fit(values, ForecastModel::Drift).forecast(horizon);
+    }
+    let period = detect_period(values).map(|(period, _)| period);
+    let holdout = horizon.clamp(1, values.len() / 4).max(1);
+    let (train, test) = values.split_at(values.len() - holdout);
+
+    let mut best: Option<(ForecastModel, f64)> = None;
+    for candidate in candidates(train, period) {
+        let predicted = fit(train, candidate).forecast(holdout).values;
+        let rmse = (test
+            .iter()
+            .zip(&predicted)
+            .map(|(a, p)| (a - p).powi(2))
+            .sum::<f64>()
+            / holdout as f64)
+            .sqrt();
+        if rmse.is_finite() && best.is_none_or(|(_, best_rmse)| rmse < best_rmse) {
+            best = Some((candidate, rmse));
+        }
+    }
+    let (model, rmse) = best.unwrap_or((ForecastModel::Drift, f64::NAN));
+    let mut forecast = fit(values, model).forecast(horizon);
+    forecast.backtest_rmse = rmse.is_finite().then_some(rmse);
+    forecast
+}
+
+// Candidate models, each with its best in-sample smoothing parameters
+fn candidates(values: &[f64], period: Option<usize>) -> Vec<ForecastModel> {
+    let mut models = vec![ForecastModel::Drift];
+    let holt = GRID.iter().flat_map(|&alpha| {
+        GRID.iter()
+            .map(move |&beta| ForecastModel::Holt { alpha, beta })
+    });
+    models.extend(best_fit(values, holt));
+    if let Some(period) = period.filter(|period| values.len() >= 2 * period) {
+        let winters = GRID.iter().flat_map(|&alpha| {
+            GRID.iter().flat_map(move |&beta| {
+                GRID.iter().map(move |&gamma| ForecastModel::HoltWinters {
+                    alpha,
+                    beta,
+                    gamma,
+                    period,
+                })
+            })
+        });
+        models.extend(best_fit(values, winters));
+    }
+    for p in 1..=2 {
+        if values.len() > 3 * (p + 1) {
+            models.push(ForecastModel::Arima { p });
+        }
+    }
+    models
+}
+
+fn best_fit(values: &[f64], models: impl Iterator<Item = ForecastModel>) -> Option<ForecastModel> {
+    models
+        .map(|model| (model, fit(values, model).sse))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(model, _)| model)
+}
+
+struct Fitted {
+    model: ForecastModel,
+    level: f64,
+    trend: f64,
+    season: Vec<f64>,
+    // Position in the season of the next point
+    season_index: usize,
+    ar: Vec<f64>,
+    history: Vec<f64>,
+    sse: f64,
+    sigma: f64,
+}
+
+fn fit(values: &[f64], model: ForecastModel) -> Fitted {
+    let mut fitted = Fitted {
+        model,
+        level: values.last().copied().unwrap_or(0.0),
+        trend: 0.0,
+        season: Vec::new(),
+        season_index: 0,
+        ar: Vec::new(),
+        history: values.to_vec(),
+        sse: 0.0,
+        sigma: 0.0,
+    };
+    let mut errors = Vec::new();
+    match model {
+        ForecastModel::Drift => {
+            if values.len() >= 2 {
+                fitted.trend = (values[values.len() - 1] - values[0]) / (values.len() - 1) as f64;
+                errors = values
+                    .windows(2)
+                    .map(|w| w[1] - w[0] - fitted.trend)
+                    .collect();
+            }
+        }
+        ForecastModel::Holt { alpha, beta } => {
+            if values.len() >= 2 {
+                let (mut level, mut trend) = (values[0], values[1] - values[0]);
+                for &value in &values[1..] {
+                    let predicted = level + trend;
+                    errors.push(value - predicted);
+                    let previous = level;
+                    level = alpha * value + (1.0 - alpha) * predicted;
+                    trend = beta * (level - previous) + (1.0 - beta) * trend;
+                }
+                fitted.level = level;
+                fitted.trend = trend;
+            }
+        }
+        ForecastModel::HoltWinters {
+            alpha,
+            beta,
+            gamma,
+            period,
+        } => {
+            // Initial level and trend from the first two seasons, initial
+            // seasonal indices from the first season's deviations
+            let first = values[..period].iter().sum::<f64>() / period as f64;
+            let second = values[period..2 * period].iter().sum::<f64>() / period as f64;
+            let mut level = first;
+            let mut trend = (second - first) / period as f64;
+            let mut season: Vec<f64> = values[..period].iter().map(|v| v - first).collect();
+            for (i, &value) in values.iter().enumerate().skip(period) {
+                let s = i % period;
+                let predicted = level + trend + season[s];
+                errors.push(value - predicted);
+                let previous = level;
+                level = alpha * (value - season[s]) + (1.0 - alpha) * (level + trend);
+                trend = beta * (level - previous) + (1.0 - beta) * trend;
+                season[s] = gamma * (value - level) + (1.0 - gamma) * season[s];
+            }
+            fitted.level = level;
+            fitted.trend = trend;
+            fitted.season = season;
+            fitted.season_index = values.len() % period;
+        }
+        ForecastModel::Arima { p } => {
+            let diffs: Vec<f64> = values.windows(2).map(|w| w[1] - w[0]).collect();
+            fitted.ar = least_squares_ar(&diffs, p);
+            for t in p..diffs.len() {
+                let predicted: f64 =
+                    fitted.ar[0] + (1..=p).map(|i| fitted.ar[i] * diffs[t - i]).sum::<f64>();
+                errors.push(diffs[t] - predicted);
+            }
+        }
+    }
+    fitted.sse = errors.iter().map(|e| e * e).sum();
+    fitted.sigma = if errors.is_empty() {
+        0.0
+    } else {
+        (fitted.sse / errors.len() as f64).sqrt()
+    };
+    fitted
+}
+
+// Intercept followed by p lag coefficients, by ordinary least squares
+fn least_squares_ar(diffs: &[f64], p: usize) -> Vec<f64> {
+    let size = p + 1;
+    let mut xtx = vec![vec![0.0; size]; size];
+    let mut xty = vec![0.0; size];
+    for t in p..diffs.len() {
+        let mut x = vec![1.0];
+        x.extend((1..=p).map(|i| diffs[t - i]));
+        for i in 0..size {
+            xty[i] += x[i] * diffs[t];
+            for j in 0..size {
+                xtx[i][j] += x[i] * x[j];
+            }
+        }
+    }
+    // Small ridge keeps the system solvable for constant series
+    for (i, row) in xtx.iter_mut().enumerate() {
+        row[i] += 1e-6;
+    }
+    solve(xtx, xty).unwrap_or_else(|| vec![0.0; size])
+}
+
+// Gaussian elimination with partial pivoting
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (target, source) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *target -= factor * source;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let tail: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+impl Fitted {
+    fn forecast(&self, horizon: usize) -> Forecast {
+        let mut values = Vec::with_capacity(horizon);
+        // Forecast error variance at step h is sigma² times the sum of the
+        // squared weights that shocks at steps 1..=h carry into step h
+        let mut weights = Vec::with_capacity(horizon);
+        match self.model {
+            ForecastModel::Drift => {
+                for h in 1..=horizon {
+                    values.push(self.level + self.trend * h as f64);
+                    weights.push(1.0);
+                }
+            }
+            ForecastModel::Holt { alpha, beta } => {
+                for h in 1..=horizon {
+                    values.push(self.level + self.trend * h as f64);
+                    weights.push(if h == 1 {
+                        1.0
+                    } else {
+                        alpha * (1.0 + (h - 1) as f64 * beta)
+                    });
+                }
+            }
+            ForecastModel::HoltWinters {
+                alpha,
+                beta,
+                gamma,
+                period,
+            } => {
+                for h in 1..=horizon {
+                    let s = (self.season_index + h - 1) % period;
+                    values.push(self.level + self.trend * h as f64 + self.season[s]);
+                    let seasonal = if h > 1 && (h - 1) % period == 0 {
+                        gamma * (1.0 - alpha)
+                    } else {
+                        0.0
+                    };
+                    weights.push(if h == 1 {
+                        1.0
+                    } else {
+                        alpha * (1.0 + (h - 1) as f64 * beta) + seasonal
+                    });
+                }
+            }
+            ForecastModel::Arima { p } => {
+                let mut diffs: Vec<f64> = self.history.windows(2).map(|w| w[1] - w[0]).collect();
+                let mut last = self.level;
+                // psi weights of the AR process on differences, summed to
+                // get the weights on levels
+                let mut psi = vec![1.0];
+                let mut cumulative = 0.0;
+                for h in 1..=horizon {
+                    let t = diffs.len();
+                    let next: f64 = self.ar[0]
+                        + (1..=p)
+                            .map(|i| {
+                                self.ar[i] * diffs.get(t.wrapping_sub(i)).copied().unwrap_or(0.0)
+                            })
+                            .sum::<f64>();
+                    diffs.push(next);
+                    last += next;
+                    values.push(last);
+                    if h > 1 {
+                        let j = h - 1;
+                        let weight: f64 = (1..=p.min(j)).map(|i| self.ar[i] * psi[j - i]).sum();
+                        psi.push(weight);
+                    }
+                    cumulative += psi[h - 1];
+                    weights.push(cumulative);
+                }
+            }
+        }
+
+        let mut variance = 0.0;
+        let std_errors = weights
+            .iter()
+            .map(|weight| {
+                variance += weight * weight;
+                self.sigma * f64::sqrt(variance)
+            })
+            .collect();
+
+        Forecast {
+            model: self.model,
+            values,
+            std_errors,
+            backtest_rmse: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weekly(days: usize) -> Vec<f64> {
+        (0..days)
+            .map(|day| {
+                let weekend = if day % 7 >= 5 { -30.0 } else { 0.0 };
+                100.0 + day as f64 * 0.5 + weekend + ((day * 7919) % 5) as f64 * 0.2
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_weekly_period() {
+        let (period, strength) = detect_period(&weekly(70)).unwrap();
+        assert_eq!(period, 7);
+        assert!(strength > SEASONALITY_THRESHOLD);
+        let trend: Vec<f64> = (0..70).map(|i| i as f64 * 2.0).collect();
+        assert!(detect_period(&trend).is_none());
+    }
+
+    #[test]
+    fn test_backtest_picks_holt_winters_for_seasonal_series() {
+        let series = weekly(70);
+        let forecast = forecast(&series, 14);
+        assert!(matches!(
+            forecast.model,
+            ForecastModel::HoltWinters { period: 7, .. }
+        ));
+        let actual = weekly(84);
+        for (h, predicted) in forecast.values.iter().enumerate() {
+            assert!(
+                (predicted - actual[70 + h]).abs() < 5.0,
+                "step {} off: {} vs {}",
+                h,
+                predicted,
+                actual[70 + h]
+            );
+        }
+    }
+
+    #[test]
+    fn test_intervals_widen_with_horizon() {
+        let noisy: Vec<f64> = (0..60)
+            .map(|i| 50.0 + i as f64 + ((i * 37) % 11) as f64 - 5.0)
+            .collect();
+        let forecast = forecast(&noisy, 10);
+        assert!(forecast.std_errors.windows(2).all(|w| w[1] >= w[0]));
+        assert!(forecast.std_errors[9] > forecast.std_errors[0]);
+        let (lower, upper) = forecast.bounds(0, Z_95);
+        assert!(lower < forecast.values[0] && forecast.values[0] < upper);
+    }
+
+    #[test]
+    fn test_arima_follows_constant_growth() {
+        let linear: Vec<f64> = (0..40).map(|i| 10.0 + 3.0 * i as f64).collect();
+        let forecast = fit(&linear, ForecastModel::Arima { p: 1 }).forecast(3);
+        assert!((forecast.values[2] - (10.0 + 3.0 * 42.0)).abs() < 1e-3);
+    }
+}
@@ -1,24 +1,28 @@
+use crate::alerts::{Alert, AlertBus};
+use crate::config::{AlertSource, NotificationLevel};
+use crate::error::WarpError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
 
-pub mod models;
-pub mod features;
-pub mod predictions;
-pub mod recommendations;
 pub mod clustering;
-pub mod anomaly_detection;
+pub mod explain;
+pub mod features;
+pub mod forecasting;
+pub mod panel;
+pub mod training;
+
+// Metric samples needed before the window, to have a baseline to compare with
+const ANOMALY_MIN_HISTORY: usize = 8;
+// Standard deviations from the baseline that count as an anomaly
+const ANOMALY_Z: f64 = 3.0;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MLInsightsEngine {
-    models: Arc<Mutex<HashMap<String, models::MLModel>>>,
     feature_store: Arc<features::FeatureStore>,
-    predictor: Arc<predictions::Predictor>,
-    recommender: Arc<recommendations::RecommendationEngine>,
     clusterer: Arc<clustering::UserClusterer>,
-    anomaly_detector: Arc<anomaly_detection::AnomalyDetector>,
+    pipelines: Arc<Mutex<training::PredictionPipelines>>,
+    alert_bus: Option<AlertBus>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -221,22 +225,42 @@ pub struct ConfidenceInterval {
 impl MLInsightsEngine {
     pub async fn new() -> Result<Self, WarpError> {
         Ok(Self {
-            models: Arc::new(Mutex::new(HashMap::new())),
-            feature_store: Arc::new(features::FeatureStore::new().await?),
-            predictor: Arc::new(predictions::Predictor::new().await?),
-            recommender: Arc::new(recommendations::RecommendationEngine::new().await?),
+            feature_store: Arc::new(features::FeatureStore::new()),
             clusterer: Arc::new(clustering::UserClusterer::new().await?),
-            anomaly_detector: Arc::new(anomaly_detection::AnomalyDetector::new().await?),
+            pipelines: Arc::new(Mutex::new(training::PredictionPipelines::new())),
+            alert_bus: None,
         })
     }
 
-    pub async fn predict_user_behavior(&self, user_id: &str, prediction_types: Vec<PredictionType>) -> Result<UserBehaviorPrediction, WarpError> {
-        let user_features = self.feature_store.get_user_features(user_id).await?;
+    // Where usage collection writes the features, labels and metrics
+    pub fn feature_store(&self) -> Arc<features::FeatureStore> {
+        self.feature_store.clone()
+    }
+
+    // Detected anomalies are raised as alerts, routed per config
+    pub fn set_alert_bus(&mut self, bus: AlertBus) {
+        self.alert_bus = Some(bus);
+    }
+
+    // Only churn and feature adoption have models, and only once their
+    // pipelines have trained
+    pub async fn predict_user_behavior(
+        &self,
+        user_id: &str,
+        prediction_types: Vec<PredictionType>,
+    ) -> Result<UserBehaviorPrediction, WarpError> {
         let mut predictions = HashMap::new();
         let mut confidence_scores = HashMap::new();
 
+        let numeric_features = self.feature_store.get_numeric_features(user_id).await?;
+        let pipelines = self.pipelines.lock().await;
+
         for prediction_type in prediction_types {
-            let result = self.predictor.predict(&prediction_type, &user_features).await?;
+            let result = pipelines
+                .predict(&prediction_type, &numeric_features)
+                .ok_or_else(|| {
+                    WarpError::not_found("trained model", format!("{:?}", prediction_type))
+                })?;
             confidence_scores.insert(format!("{:?}", prediction_type), result.confidence);
             predictions.insert(format!("{:?}", prediction_type), result);
         }
@@ -249,32 +273,68 @@ impl MLInsightsEngine {
         })
     }
 
-    pub async fn generate_personalized_recommendations(&self, user_id: &str, context: HashMap<String, serde_json::Value>) -> Result<PersonalizedRecommendation, WarpError> {
-        let user_features = self.feature_store.get_user_features(user_id).await?;
-        let user_cluster = self.clusterer.get_user_cluster(user_id).await?;
-        
-        let recommendations = self.recommender.generate_recommendations(
-            user_id,
-            &user_features,
-            &user_cluster,
-            &context,
-        ).await?;
-
-        Ok(recommendations)
+    // Compares each metric's samples inside the window with its samples
+    // before it
+    pub async fn detect_anomalies(
+        &self,
+        time_window: chrono::Duration,
+    ) -> Result<AnomalyDetectionResult, WarpError> {
+        let now = chrono::Utc::now();
+        let mut anomalies = Vec::new();
+        for (metric_name, series) in self.feature_store.metric_series().await {
+            let (baseline, recent): (Vec<_>, Vec<_>) =
+                series.iter().partition(|(at, _)| *at < now - time_window);
+            let baseline: Vec<f64> = baseline.iter().map(|(_, v)| *v).collect();
+            let recent: Vec<f64> = recent.iter().map(|(_, v)| *v).collect();
+            anomalies.extend(detect_shift(&metric_name, &baseline, &recent));
+        }
+        let result = AnomalyDetectionResult {
+            overall_score: anomalies
+                .iter()
+                .map(|anomaly| anomaly.score)
+                .fold(0.0, f64::max),
+            anomalies,
+            detection_timestamp: now,
+            model_version: "zscore".to_string(),
+        };
+        if let Some(bus) = &self.alert_bus {
+            for anomaly in &result.anomalies {
+                let level = match anomaly.severity {
+                    AnomalySeverity::Critical | AnomalySeverity::High => NotificationLevel::Error,
+                    AnomalySeverity::Medium => NotificationLevel::Warning,
+                    AnomalySeverity::Low | AnomalySeverity::Info => NotificationLevel::Info,
+                };
+                let mut alert = Alert::new(
+                    AlertSource::Anomalies,
+                    level,
+                    format!("{:?}", anomaly.anomaly_type),
+                );
+                if !anomaly.description.is_empty() {
+                    alert = alert.with_body(anomaly.description.clone());
+                }
+                bus.publish(alert);
+            }
+        }
+        Ok(result)
     }
 
-    pub async fn detect_anomalies(&self, time_window: chrono::Duration) -> Result<AnomalyDetectionResult, WarpError> {
-        self.anomaly_detector.detect_anomalies(time_window).await
-    }
+    pub async fn analyze_trends(
+        &self,
+        metric_name: &str,
+        time_range: chrono::Duration,
+    ) -> Result<TrendAnalysis, WarpError> {
+        let historical_data = self
+            .feature_store
+            .get_metric_history(metric_name, time_range)
+            .await;
 
-    pub async fn analyze_trends(&self, metric_name: &str, time_range: chrono::Duration) -> Result<TrendAnalysis, WarpError> {
-        let historical_data = self.feature_store.get_metric_history(metric_name, time_range).await?;
-        
         // Perform trend analysis
         let trend_direction = self.calculate_trend_direction(&historical_data);
         let trend_strength = self.calculate_trend_strength(&historical_data);
         let seasonality = self.detect_seasonality(&historical_data);
-        let forecast = self.generate_forecast(&historical_data, chrono::Duration::days(30)).await?;
+        let forecast = self
+            .generate_forecast(&historical_data, chrono::Duration::days(30))
+            .await?;
         let confidence_intervals = self.calculate_confidence_intervals(&forecast);
         let change_points = self.detect_change_points(&historical_data);
 
@@ -290,48 +350,133 @@ impl MLInsightsEngine {
     }
 
     pub async fn cluster_users(&self) -> Result<Vec<UserCluster>, WarpError> {
+        let usage_features = self.feature_store.get_all_numeric_features().await;
+        self.clusterer.set_features(usage_features).await;
         self.clusterer.perform_clustering().await
     }
 
-    pub async fn get_feature_importance(&self, model_name: &str) -> Result<Vec<PredictionFactor>, WarpError> {
-        let models = self.models.lock().await;
-        if let Some(model) = models.get(model_name) {
-            model.get_feature_importance().await
-        } else {
-            Err(WarpError::ConfigError(format!("Model not found: {}", model_name)))
-        }
+    // Reclusters whenever the persisted clusters are older than their refresh
+    // interval, checking every `check_every` until the engine is dropped
+    pub fn start_clustering_schedule(
+        self: &Arc<Self>,
+        check_every: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let engine = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check_every);
+            loop {
+                interval.tick().await;
+                let Some(engine) = engine.upgrade() else {
+                    break;
+                };
+                if !engine.clusterer.is_stale(chrono::Utc::now()).await {
+                    continue;
+                }
+                if let Err(e) = engine.cluster_users().await {
+                    log::warn!("Scheduled user clustering failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub async fn get_feature_importance(
+        &self,
+        model_name: &str,
+    ) -> Result<Vec<PredictionFactor>, WarpError> {
+        // Trained pipelines report permutation importance on held-out users
+        self.pipelines
+            .lock()
+            .await
+            .job(model_name)
+            .and_then(|job| job.feature_importance())
+            .ok_or_else(|| WarpError::not_found("trained model", model_name))
     }
 
+    // Trains the pipeline now, whether or not it's due
     pub async fn retrain_model(&self, model_name: &str) -> Result<(), WarpError> {
-        let mut models = self.models.lock().await;
-        if let Some(model) = models.get_mut(model_name) {
-            let training_data = self.feature_store.get_training_data(model_name).await?;
-            model.retrain(&training_data).await?;
-        }
-        Ok(())
+        let examples = self.feature_store.get_labeled_examples(model_name).await;
+        let mut pipelines = self.pipelines.lock().await;
+        let job = pipelines
+            .job_mut(model_name)
+            .ok_or_else(|| WarpError::not_found("model", model_name))?;
+        job.train(&examples, chrono::Utc::now())
     }
 
-    pub async fn evaluate_model_performance(&self, model_name: &str) -> Result<models::ModelPerformance, WarpError> {
-        let models = self.models.lock().await;
-        if let Some(model) = models.get(model_name) {
-            let test_data = self.feature_store.get_test_data(model_name).await?;
-            model.evaluate(&test_data).await
-        } else {
-            Err(WarpError::ConfigError(format!("Model not found: {}", model_name)))
+    // Retrains every pipeline whose interval has passed on fresh labelled
+    // examples from the feature store
+    pub async fn run_due_training(&self) -> Result<Vec<String>, WarpError> {
+        let now = chrono::Utc::now();
+        let due = self.pipelines.lock().await.due(now);
+        for model_name in &due {
+            let examples = self.feature_store.get_labeled_examples(model_name).await;
+            let mut pipelines = self.pipelines.lock().await;
+            if let Some(job) = pipelines.job_mut(model_name) {
+                job.train(&examples, now)?;
+            }
         }
+        Ok(due)
+    }
+
+    // Checks for due pipelines every `check_every` until the engine is dropped
+    pub fn start_training_schedule(
+        self: &Arc<Self>,
+        check_every: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let engine = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check_every);
+            loop {
+                interval.tick().await;
+                let Some(engine) = engine.upgrade() else {
+                    break;
+                };
+                match engine.run_due_training().await {
+                    Ok(trained) if !trained.is_empty() => {
+                        log::info!("Retrained prediction models: {}", trained.join(", "))
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Scheduled model training failed: {}", e),
+                }
+            }
+        })
+    }
+
+    pub async fn set_retrain_interval(&self, interval: chrono::Duration) {
+        self.pipelines.lock().await.set_retrain_interval(interval);
+    }
+
+    pub async fn calibration_report(
+        &self,
+        model_name: &str,
+    ) -> Result<training::CalibrationReport, WarpError> {
+        let pipelines = self.pipelines.lock().await;
+        pipelines
+            .job(model_name)
+            .and_then(|job| job.calibration.clone())
+            .ok_or_else(|| WarpError::not_found("calibration report", model_name))
+    }
+
+    // Shown in the ML insights tab of the analytics dashboard
+    pub async fn pipeline_summaries(&self) -> Vec<training::PipelineSummary> {
+        self.pipelines.lock().await.summaries()
     }
 
     // Helper methods for trend analysis
-    fn calculate_trend_direction(&self, data: &[(chrono::DateTime<chrono::Utc>, f64)]) -> TrendDirection {
+    fn calculate_trend_direction(
+        &self,
+        data: &[(chrono::DateTime<chrono::Utc>, f64)],
+    ) -> TrendDirection {
         if data.len() < 2 {
             return TrendDirection::Stable;
         }
 
-        let first_half_avg = data[..data.len()/2].iter().map(|(_, v)| v).sum::<f64>() / (data.len()/2) as f64;
-        let second_half_avg = data[data.len()/2..].iter().map(|(_, v)| v).sum::<f64>() / (data.len() - data.len()/2) as f64;
-        
+        let first_half_avg =
+            data[..data.len() / 2].iter().map(|(_, v)| v).sum::<f64>() / (data.len() / 2) as f64;
+        let second_half_avg = data[data.len() / 2..].iter().map(|(_, v)| v).sum::<f64>()
+            / (data.len() - data.len() / 2) as f64;
+
         let change_ratio = (second_half_avg - first_half_avg) / first_half_avg;
-        
+
         if change_ratio > 0.05 {
             TrendDirection::Increasing
         } else if change_ratio < -0.05 {
@@ -350,85 +495,146 @@ impl MLInsightsEngine {
         let n = data.len() as f64;
         let sum_x: f64 = (0..data.len()).map(|i| i as f64).sum();
         let sum_y: f64 = data.iter().map(|(_, v)| v).sum();
-        let sum_xy: f64 = data.iter().enumerate().map(|(i, (_, v))| i as f64 * v).sum();
+        let sum_xy: f64 = data
+            .iter()
+            .enumerate()
+            .map(|(i, (_, v))| i as f64 * v)
+            .sum();
         let sum_x2: f64 = (0..data.len()).map(|i| (i as f64).powi(2)).sum();
 
         let slope = (n * sum_xy - sum_x * sum_y) / (n * sum_x2 - sum_x.powi(2));
         slope.abs()
     }
 
-    fn detect_seasonality(&self, _data: &[(chrono::DateTime<chrono::Utc>, f64)]) -> Option<SeasonalityPattern> {
-        // Simplified seasonality detection
-        // In a real implementation, this would use FFT or autocorrelation
+    fn detect_seasonality(
+        &self,
+        data: &[(chrono::DateTime<chrono::Utc>, f64)],
+    ) -> Option<SeasonalityPattern> {
+        let values: Vec<f64> = data.iter().map(|(_, v)| *v).collect();
+        let (period, _) = forecasting::detect_period(&values)?;
+        let period_length = sample_interval(data) * period as i32;
+        let pattern_type = match period_length.num_days() {
+            0..=2 => SeasonalityType::Daily,
+            3..=14 => SeasonalityType::Weekly,
+            15..=60 => SeasonalityType::Monthly,
+            61..=180 => SeasonalityType::Quarterly,
+            _ => SeasonalityType::Yearly,
+        };
+
+        // Average deviation from the mean at each position in the season
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let mut offsets = vec![(0.0, 0usize); period];
+        for (i, value) in values.iter().enumerate() {
+            offsets[i % period].0 += value - mean;
+            offsets[i % period].1 += 1;
+        }
+        let offsets: Vec<f64> = offsets
+            .iter()
+            .map(|(sum, count)| sum / *count as f64)
+            .collect();
+        let peak = offsets
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        let amplitude = (offsets.iter().cloned().fold(f64::MIN, f64::max)
+            - offsets.iter().cloned().fold(f64::MAX, f64::min))
+            / 2.0;
+
         Some(SeasonalityPattern {
-            pattern_type: SeasonalityType::Weekly,
-            period: chrono::Duration::days(7),
-            amplitude: 0.1,
-            phase: 0.0,
+            pattern_type,
+            period: period_length,
+            amplitude,
+            phase: peak as f64 / period as f64 * std::f64::consts::TAU,
         })
     }
 
-    async fn generate_forecast(&self, data: &[(chrono::DateTime<chrono::Utc>, f64)], horizon: chrono::Duration) -> Result<Vec<ForecastPoint>, WarpError> {
-        // Simplified forecasting using linear extrapolation
-        if data.is_empty() {
+    async fn generate_forecast(
+        &self,
+        data: &[(chrono::DateTime<chrono::Utc>, f64)],
+        horizon: chrono::Duration,
+    ) -> Result<Vec<ForecastPoint>, WarpError> {
+        let Some(last_point) = data.last() else {
             return Ok(vec![]);
-        }
-
-        let last_point = data.last().unwrap();
-        let trend_strength = self.calculate_trend_strength(data);
-        let last_value = last_point.1;
-        
-        let mut forecast = Vec::new();
-        let steps = horizon.num_hours() / 24; // Daily forecast points
-        
-        for i in 1..=steps {
-            let timestamp = last_point.0 + chrono::Duration::days(i);
-            let predicted_value = last_value + (trend_strength * i as f64);
-            let uncertainty = 0.1 * predicted_value * (i as f64).sqrt(); // Increasing uncertainty
-            
-            forecast.push(ForecastPoint {
-                timestamp,
-                value: predicted_value,
-                lower_bound: predicted_value - uncertainty,
-                upper_bound: predicted_value + uncertainty,
-            });
-        }
-
-        Ok(forecast)
+        };
+
+        // Forecast at the series' own sampling interval, with the model that
+        // backtested best on its most recent points
+        let step = sample_interval(data);
+        let steps = (horizon.num_seconds() / step.num_seconds().max(1)).max(0) as usize;
+        let values: Vec<f64> = data.iter().map(|(_, v)| *v).collect();
+        let forecast = forecasting::forecast(&values, steps);
+        log::debug!(
+            "Forecasting with {:?}, backtest RMSE {:?}",
+            forecast.model,
+            forecast.backtest_rmse
+        );
+
+        Ok((0..steps)
+            .map(|i| {
+                let (lower_bound, upper_bound) = forecast.bounds(i, forecasting::Z_95);
+                ForecastPoint {
+                    timestamp: last_point.0 + step * (i as i32 + 1),
+                    value: forecast.values[i],
+                    lower_bound,
+                    upper_bound,
+                }
+            })
+            .collect())
     }
 
-    fn calculate_confidence_intervals(&self, forecast: &[ForecastPoint]) -> Vec<ConfidenceInterval> {
-        forecast.iter().map(|point| {
-            ConfidenceInterval {
+    fn calculate_confidence_intervals(
+        &self,
+        forecast: &[ForecastPoint],
+    ) -> Vec<ConfidenceInterval> {
+        forecast
+            .iter()
+            .map(|point| ConfidenceInterval {
                 timestamp: point.timestamp,
                 lower_bound: point.lower_bound,
                 upper_bound: point.upper_bound,
                 confidence_level: 0.95,
-            }
-        }).collect()
+            })
+            .collect()
     }
 
-    fn detect_change_points(&self, data: &[(chrono::DateTime<chrono::Utc>, f64)]) -> Vec<ChangePoint> {
+    fn detect_change_points(
+        &self,
+        data: &[(chrono::DateTime<chrono::Utc>, f64)],
+    ) -> Vec<ChangePoint> {
         // Simplified change point detection
         let mut change_points = Vec::new();
-        
+
         if data.len() < 10 {
             return change_points;
         }
 
         let window_size = data.len() / 5;
         for i in window_size..(data.len() - window_size) {
-            let before_avg = data[(i-window_size)..i].iter().map(|(_, v)| v).sum::<f64>() / window_size as f64;
-            let after_avg = data[i..(i+window_size)].iter().map(|(_, v)| v).sum::<f64>() / window_size as f64;
-            
+            let before_avg = data[(i - window_size)..i]
+                .iter()
+                .map(|(_, v)| v)
+                .sum::<f64>()
+                / window_size as f64;
+            let after_avg = data[i..(i + window_size)]
+                .iter()
+                .map(|(_, v)| v)
+                .sum::<f64>()
+                / window_size as f64;
+
             let change_magnitude = (after_avg - before_avg).abs() / before_avg;
-            
-            if change_magnitude > 0.2 { // 20% change threshold
+
+            if change_magnitude > 0.2 {
+                // 20% change threshold
                 change_points.push(ChangePoint {
                     timestamp: data[i].0,
                     magnitude: change_magnitude,
                     confidence: 0.8,
-                    description: format!("Significant change detected: {:.1}% change", change_magnitude * 100.0),
+                    description: format!(
+                        "Significant change detected: {:.1}% change",
+                        change_magnitude * 100.0
+                    ),
                 });
             }
         }
@@ -436,3 +642,117 @@ impl MLInsightsEngine {
         change_points
     }
 }
+
+// An anomaly when the window's mean is more than ANOMALY_Z standard
+// deviations from the baseline's
+fn detect_shift(metric_name: &str, baseline: &[f64], recent: &[f64]) -> Option<Anomaly> {
+    if baseline.len() < ANOMALY_MIN_HISTORY || recent.is_empty() {
+        return None;
+    }
+    let mean = baseline.iter().sum::<f64>() / baseline.len() as f64;
+    let variance =
+        baseline.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (baseline.len() - 1) as f64;
+    let std_dev = variance.sqrt();
+    if std_dev == 0.0 {
+        return None;
+    }
+    let recent_mean = recent.iter().sum::<f64>() / recent.len() as f64;
+    let z = (recent_mean - mean) / std_dev;
+    if z.abs() < ANOMALY_Z {
+        return None;
+    }
+    let severity = match z.abs() {
+        z if z >= ANOMALY_Z * 2.0 => AnomalySeverity::High,
+        z if z >= ANOMALY_Z * 1.5 => AnomalySeverity::Medium,
+        _ => AnomalySeverity::Low,
+    };
+    Some(Anomaly {
+        anomaly_type: if z > 0.0 {
+            AnomalyType::UsageSpike
+        } else {
+            AnomalyType::UsageDrop
+        },
+        severity,
+        score: z.abs(),
+        description: format!(
+            "{} averaged {:.2} against a usual {:.2} ± {:.2}",
+            metric_name, recent_mean, mean, std_dev
+        ),
+        affected_entities: vec![metric_name.to_string()],
+        suggested_actions: Vec::new(),
+        context: HashMap::from([("z_score".to_string(), serde_json::json!(z))]),
+    })
+}
+
+// Median spacing between samples, or a day when there's too little to tell
+fn sample_interval(data: &[(chrono::DateTime<chrono::Utc>, f64)]) -> chrono::Duration {
+    let mut gaps: Vec<chrono::Duration> = data
+        .windows(2)
+        .map(|w| w[1].0 - w[0].0)
+        .filter(|gap| *gap > chrono::Duration::zero())
+        .collect();
+    if gaps.is_empty() {
+        return chrono::Duration::days(1);
+    }
+    gaps.sort();
+    gaps[gaps.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_shift() {
+        let baseline = [10.0, 11.0, 9.0, 10.5, 9.5, 10.0, 11.0, 9.0];
+        let spike = detect_shift("installs", &baseline, &[30.0, 28.0]).unwrap();
+        assert!(matches!(spike.anomaly_type, AnomalyType::UsageSpike));
+        assert!(matches!(spike.severity, AnomalySeverity::High));
+        assert!(matches!(
+            detect_shift("installs", &baseline, &[2.0])
+                .unwrap()
+                .anomaly_type,
+            AnomalyType::UsageDrop
+        ));
+        assert!(detect_shift("installs", &baseline, &[10.8]).is_none());
+        assert!(detect_shift("installs", &baseline[..3], &[30.0]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_predictions_come_from_trained_pipelines() {
+        let engine = MLInsightsEngine {
+            feature_store: Arc::new(features::FeatureStore::new()),
+            clusterer: Arc::new(
+                clustering::UserClusterer::with_path(None, clustering::ClusteringConfig::default())
+                    .await
+                    .unwrap(),
+            ),
+            pipelines: Arc::new(Mutex::new(training::PredictionPipelines::new())),
+            alert_bus: None,
+        };
+        let store = engine.feature_store();
+        for i in 0..40 {
+            let user = format!("user{:02}", i);
+            let idle = (i % 10) as f64;
+            store
+                .set_user_features(
+                    &user,
+                    HashMap::from([("days_since_last_session".to_string(), idle)]),
+                )
+                .await;
+            store.set_outcome("churn", &user, idle >= 5.0).await;
+        }
+        assert!(engine
+            .predict_user_behavior("user07", vec![PredictionType::ChurnProbability])
+            .await
+            .is_err());
+
+        engine.retrain_model("churn").await.unwrap();
+        let prediction = engine
+            .predict_user_behavior("user07", vec![PredictionType::ChurnProbability])
+            .await
+            .unwrap();
+        assert!(prediction.predictions["ChurnProbability"].value > 0.5);
+        assert!(engine.retrain_model("unknown").await.is_err());
+    }
+}
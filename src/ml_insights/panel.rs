@@ -0,0 +1,267 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use crossterm::style::Color;
+
+use super::training::PipelineSummary;
+use super::{Anomaly, AnomalySeverity, ForecastPoint, TrendAnalysis, TrendDirection};
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// How far ahead the forecast row looks
+const FORECAST_AHEAD: Duration = Duration::hours(1);
+
+struct MetricForecast {
+    metric_name: String,
+    direction: TrendDirection,
+    point: Option<ForecastPoint>,
+}
+
+// The insights engine's results in one list: the prediction models, the
+// anomalies in the recent window, and where each metric is heading
+pub struct InsightsPanel {
+    models: Vec<PipelineSummary>,
+    anomalies: Vec<Anomaly>,
+    forecasts: Vec<MetricForecast>,
+}
+
+fn heading(text: String) -> PanelItem {
+    PanelItem::styled(vec![(text, Some(Color::Cyan))])
+}
+
+fn trained_at(at: Option<DateTime<Utc>>) -> String {
+    match at {
+        Some(at) => at.with_timezone(&Local).format("%m-%d %H:%M").to_string(),
+        None => i18n::t("insights.never"),
+    }
+}
+
+impl InsightsPanel {
+    pub fn new(
+        models: Vec<PipelineSummary>,
+        anomalies: Vec<Anomaly>,
+        mut trends: Vec<TrendAnalysis>,
+    ) -> Self {
+        trends.sort_by(|a, b| a.metric_name.cmp(&b.metric_name));
+        let forecasts = trends
+            .into_iter()
+            .map(|trend| {
+                let ahead = trend
+                    .forecast
+                    .first()
+                    .map(|first| first.timestamp + FORECAST_AHEAD);
+                let point = match ahead {
+                    Some(ahead) => trend.forecast.iter().find(|point| point.timestamp >= ahead),
+                    None => None,
+                };
+                MetricForecast {
+                    point: point.or(trend.forecast.last()).cloned(),
+                    metric_name: trend.metric_name,
+                    direction: trend.trend_direction,
+                }
+            })
+            .collect();
+        Self {
+            models,
+            anomalies,
+            forecasts,
+        }
+    }
+
+    // Keys: r refresh
+    pub fn wants_refresh(key: char) -> bool {
+        key == 'r'
+    }
+
+    fn model_rows(&self) -> Vec<PanelItem> {
+        self.models
+            .iter()
+            .map(|summary| {
+                let mut segments = vec![
+                    (format!("{:<20} ", summary.model_name), None),
+                    (
+                        i18n::t_args(
+                            "insights.model_row",
+                            &[
+                                ("examples", &summary.training_examples.to_string()),
+                                ("trained", &trained_at(summary.last_trained)),
+                            ],
+                        ),
+                        Some(Color::DarkGrey),
+                    ),
+                ];
+                if let Some(report) = &summary.calibration {
+                    segments.push((
+                        format!(
+                            "  {}",
+                            i18n::t_args(
+                                "insights.calibration",
+                                &[
+                                    ("brier", &format!("{:.3}", report.brier_score)),
+                                    (
+                                        "ece",
+                                        &format!(
+                                            "{:.1}%",
+                                            report.expected_calibration_error * 100.0
+                                        )
+                                    ),
+                                ],
+                            )
+                        ),
+                        None,
+                    ));
+                }
+                PanelItem::styled(segments)
+            })
+            .collect()
+    }
+
+    fn anomaly_rows(&self) -> Vec<PanelItem> {
+        if self.anomalies.is_empty() {
+            return vec![PanelItem::styled(vec![(
+                i18n::t("insights.no_anomalies"),
+                Some(Color::DarkGrey),
+            )])];
+        }
+        self.anomalies
+            .iter()
+            .map(|anomaly| {
+                let color = match anomaly.severity {
+                    AnomalySeverity::Critical | AnomalySeverity::High => Color::Red,
+                    AnomalySeverity::Medium => Color::Yellow,
+                    AnomalySeverity::Low | AnomalySeverity::Info => Color::DarkGrey,
+                };
+                PanelItem::styled(vec![
+                    (
+                        format!("{:<9} ", format!("{:?}", anomaly.severity)),
+                        Some(color),
+                    ),
+                    (anomaly.description.clone(), None),
+                ])
+            })
+            .collect()
+    }
+
+    fn forecast_rows(&self) -> Vec<PanelItem> {
+        if self.forecasts.is_empty() {
+            return vec![PanelItem::styled(vec![(
+                i18n::t("insights.no_samples"),
+                Some(Color::DarkGrey),
+            )])];
+        }
+        self.forecasts
+            .iter()
+            .map(|forecast| {
+                let arrow = match forecast.direction {
+                    TrendDirection::Increasing => "↑",
+                    TrendDirection::Decreasing => "↓",
+                    TrendDirection::Stable => "→",
+                    TrendDirection::Cyclical => "∿",
+                    TrendDirection::Volatile => "↕",
+                };
+                let detail = match &forecast.point {
+                    Some(point) => i18n::t_args(
+                        "insights.forecast_row",
+                        &[
+                            ("value", &format!("{:.1}", point.value)),
+                            ("lower", &format!("{:.1}", point.lower_bound)),
+                            ("upper", &format!("{:.1}", point.upper_bound)),
+                        ],
+                    ),
+                    None => i18n::t("insights.no_samples"),
+                };
+                PanelItem::styled(vec![
+                    (format!("{:<20} ", forecast.metric_name), None),
+                    (format!("{} ", arrow), Some(Color::Yellow)),
+                    (detail, Some(Color::DarkGrey)),
+                ])
+            })
+            .collect()
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items = vec![heading(i18n::t("insights.models"))];
+        items.extend(self.model_rows());
+        items.push(heading(i18n::t("insights.anomalies")));
+        items.extend(self.anomaly_rows());
+        items.push(heading(i18n::t("insights.forecasts")));
+        items.extend(self.forecast_rows());
+        PanelView {
+            kind: PanelKind::Insights,
+            title: i18n::t_args(
+                "panel.insights",
+                &[("anomalies", &self.anomalies.len().to_string())],
+            ),
+            items,
+            footer: Some(i18n::t("panel.insights_keys")),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml_insights::AnomalyType;
+    use std::collections::HashMap;
+
+    fn trend(metric_name: &str, values: &[f64]) -> TrendAnalysis {
+        let start = Utc::now();
+        TrendAnalysis {
+            metric_name: metric_name.to_string(),
+            trend_direction: TrendDirection::Increasing,
+            trend_strength: 1.0,
+            seasonality: None,
+            forecast: values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| ForecastPoint {
+                    timestamp: start + Duration::minutes(30 * i as i64),
+                    value: *value,
+                    lower_bound: value - 1.0,
+                    upper_bound: value + 1.0,
+                })
+                .collect(),
+            confidence_intervals: Vec::new(),
+            change_points: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_forecasts_look_an_hour_ahead() {
+        let panel = InsightsPanel::new(
+            Vec::new(),
+            Vec::new(),
+            vec![
+                trend("memory_percent", &[40.0]),
+                trend("cpu_percent", &[10.0, 20.0, 30.0, 40.0]),
+            ],
+        );
+        let values: Vec<_> = panel
+            .forecasts
+            .iter()
+            .map(|f| (f.metric_name.as_str(), f.point.as_ref().map(|p| p.value)))
+            .collect();
+        assert_eq!(
+            values,
+            [("cpu_percent", Some(30.0)), ("memory_percent", Some(40.0))]
+        );
+    }
+
+    #[test]
+    fn test_view_lists_each_section() {
+        let anomaly = Anomaly {
+            anomaly_type: AnomalyType::UsageSpike,
+            severity: AnomalySeverity::High,
+            score: 4.2,
+            description: "cpu_percent rose to 95".to_string(),
+            affected_entities: Vec::new(),
+            suggested_actions: Vec::new(),
+            context: HashMap::new(),
+        };
+        let panel = InsightsPanel::new(Vec::new(), vec![anomaly], Vec::new());
+        let view = panel.view();
+        // Three headings, the anomaly and the empty forecasts row
+        assert_eq!(view.items.len(), 5);
+        assert!(InsightsPanel::wants_refresh('r'));
+    }
+}
@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::explain;
+use super::{FactorDirection, PredictionFactor, PredictionResult, PredictionType};
+use crate::error::WarpError;
+
+// Every fifth example is held out to measure calibration
+const HOLDOUT_EVERY: usize = 5;
+const CALIBRATION_BINS: usize = 10;
+// Users kept per pipeline for the dashboard's highest-score list
+const TOP_USERS: usize = 10;
+// Examples kept after training to explain predictions against
+const BACKGROUND_SIZE: usize = 50;
+
+// One user's features from the feature store, labelled with whether they
+// churned (or adopted the feature) within the prediction horizon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledExample {
+    pub user_id: String,
+    pub features: HashMap<String, f64>,
+    pub label: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    pub learning_rate: f64,
+    pub epochs: usize,
+    pub l2: f64,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        Self {
+            learning_rate: 0.1,
+            epochs: 500,
+            l2: 0.01,
+        }
+    }
+}
+
+// L2-regularised logistic regression over standardised features. Features
+// missing from a user's row count as the training mean.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogisticModel {
+    pub feature_names: Vec<String>,
+    pub weights: Vec<f64>,
+    pub bias: f64,
+    means: Vec<f64>,
+    scales: Vec<f64>,
+}
+
+impl LogisticModel {
+    pub fn fit(
+        examples: &[LabeledExample],
+        feature_names: &[String],
+        config: &TrainingConfig,
+    ) -> Result<Self, WarpError> {
+        if examples.is_empty() {
+            return Err(WarpError::Validation("no training examples".to_string()));
+        }
+        let n = examples.len() as f64;
+        let raw: Vec<Vec<f64>> = examples
+            .iter()
+            .map(|example| {
+                feature_names
+                    .iter()
+                    .map(|name| example.features.get(name).copied().unwrap_or(f64::NAN))
+                    .collect()
+            })
+            .collect();
+
+        let mut means = vec![0.0; feature_names.len()];
+        let mut scales = vec![1.0; feature_names.len()];
+        for j in 0..feature_names.len() {
+            let present: Vec<f64> = raw
+                .iter()
+                .map(|row| row[j])
+                .filter(|v| !v.is_nan())
+                .collect();
+            if present.is_empty() {
+                continue;
+            }
+            let mean = present.iter().sum::<f64>() / present.len() as f64;
+            let variance =
+                present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / present.len() as f64;
+            means[j] = mean;
+            scales[j] = if variance > 0.0 { variance.sqrt() } else { 1.0 };
+        }
+
+        let mut model = Self {
+            feature_names: feature_names.to_vec(),
+            weights: vec![0.0; feature_names.len()],
+            bias: 0.0,
+            means,
+            scales,
+        };
+        let rows: Vec<Vec<f64>> = raw.iter().map(|row| model.standardize(row)).collect();
+        let labels: Vec<f64> = examples
+            .iter()
+            .map(|example| if example.label { 1.0 } else { 0.0 })
+            .collect();
+
+        for _ in 0..config.epochs {
+            let mut gradient = vec![0.0; model.weights.len()];
+            let mut bias_gradient = 0.0;
+            for (row, label) in rows.iter().zip(&labels) {
+                let error = sigmoid(model.linear(row)) - label;
+                for (g, x) in gradient.iter_mut().zip(row) {
+                    *g += error * x;
+                }
+                bias_gradient += error;
+            }
+            for (weight, g) in model.weights.iter_mut().zip(&gradient) {
+                *weight -= config.learning_rate * (g / n + config.l2 * *weight);
+            }
+            model.bias -= config.learning_rate * bias_gradient / n;
+        }
+        Ok(model)
+    }
+
+    pub fn predict_proba(&self, features: &HashMap<String, f64>) -> f64 {
+        let raw: Vec<f64> = self
+            .feature_names
+            .iter()
+            .map(|name| features.get(name).copied().unwrap_or(f64::NAN))
+            .collect();
+        sigmoid(self.linear(&self.standardize(&raw)))
+    }
+
+    fn standardize(&self, raw: &[f64]) -> Vec<f64> {
+        raw.iter()
+            .zip(self.means.iter().zip(&self.scales))
+            .map(|(v, (mean, scale))| if v.is_nan() { 0.0 } else { (v - mean) / scale })
+            .collect()
+    }
+
+    fn linear(&self, row: &[f64]) -> f64 {
+        self.bias
+            + self
+                .weights
+                .iter()
+                .zip(row)
+                .map(|(w, x)| w * x)
+                .sum::<f64>()
+    }
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub mean_predicted: f64,
+    pub observed_rate: f64,
+}
+
+// How well predicted probabilities match observed rates on held-out users
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationReport {
+    pub samples: usize,
+    pub brier_score: f64,
+    pub log_loss: f64,
+    pub expected_calibration_error: f64,
+    pub bins: Vec<CalibrationBin>,
+}
+
+impl CalibrationReport {
+    pub fn from_predictions(predicted: &[f64], labels: &[bool]) -> Self {
+        let samples = predicted.len().min(labels.len());
+        let mut bins: Vec<CalibrationBin> = (0..CALIBRATION_BINS)
+            .map(|i| CalibrationBin {
+                lower: i as f64 / CALIBRATION_BINS as f64,
+                upper: (i + 1) as f64 / CALIBRATION_BINS as f64,
+                count: 0,
+                mean_predicted: 0.0,
+                observed_rate: 0.0,
+            })
+            .collect();
+        if samples == 0 {
+            return Self {
+                samples,
+                brier_score: 0.0,
+                log_loss: 0.0,
+                expected_calibration_error: 0.0,
+                bins,
+            };
+        }
+
+        let mut brier = 0.0;
+        let mut log_loss = 0.0;
+        for (p, label) in predicted.iter().zip(labels) {
+            let y = if *label { 1.0 } else { 0.0 };
+            brier += (p - y).powi(2);
+            let clamped = p.clamp(1e-12, 1.0 - 1e-12);
+            log_loss -= y * clamped.ln() + (1.0 - y) * (1.0 - clamped).ln();
+            let index = ((p * CALIBRATION_BINS as f64) as usize).min(CALIBRATION_BINS - 1);
+            let bin = &mut bins[index];
+            bin.count += 1;
+            bin.mean_predicted += p;
+            bin.observed_rate += y;
+        }
+
+        let mut ece = 0.0;
+        for bin in bins.iter_mut().filter(|bin| bin.count > 0) {
+            bin.mean_predicted /= bin.count as f64;
+            bin.observed_rate /= bin.count as f64;
+            ece +=
+                bin.count as f64 / samples as f64 * (bin.mean_predicted - bin.observed_rate).abs();
+        }
+
+        Self {
+            samples,
+            brier_score: brier / samples as f64,
+            log_loss: log_loss / samples as f64,
+            expected_calibration_error: ece,
+            bins,
+        }
+    }
+}
+
+// A prediction model trained from the feature store and retrained once its
+// interval has passed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingJob {
+    pub model_name: String,
+    pub prediction_type: PredictionType,
+    pub feature_names: Vec<String>,
+    pub retrain_interval: Duration,
+    pub time_horizon: Duration,
+    pub config: TrainingConfig,
+    pub model: Option<LogisticModel>,
+    pub calibration: Option<CalibrationReport>,
+    pub last_trained: Option<DateTime<Utc>>,
+    pub training_examples: usize,
+    // Highest-scoring users from the latest training run
+    pub top_users: Vec<(String, f64)>,
+    // Held-out examples from the latest run, the baseline for attributions
+    pub background: Vec<LabeledExample>,
+}
+
+impl TrainingJob {
+    pub fn churn() -> Self {
+        Self::new(
+            "churn",
+            PredictionType::ChurnProbability,
+            &[
+                "days_since_last_session",
+                "sessions_last_30d",
+                "commands_per_session",
+                "error_rate",
+                "plugins_installed",
+            ],
+        )
+    }
+
+    pub fn feature_adoption() -> Self {
+        Self::new(
+            "feature_adoption",
+            PredictionType::FeatureAdoption,
+            &[
+                "sessions_last_30d",
+                "features_used",
+                "tutorials_completed",
+                "ai_requests_last_30d",
+                "workflows_saved",
+            ],
+        )
+    }
+
+    fn new(model_name: &str, prediction_type: PredictionType, feature_names: &[&str]) -> Self {
+        Self {
+            model_name: model_name.to_string(),
+            prediction_type,
+            feature_names: feature_names.iter().map(|name| name.to_string()).collect(),
+            retrain_interval: Duration::days(1),
+            time_horizon: Duration::days(30),
+            config: TrainingConfig::default(),
+            model: None,
+            calibration: None,
+            last_trained: None,
+            training_examples: 0,
+            top_users: Vec::new(),
+            background: Vec::new(),
+        }
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.last_trained
+            .is_none_or(|trained| now - trained >= self.retrain_interval)
+    }
+
+    // Fits on all but the held-out examples and reports calibration on those.
+    // The previous model is kept if training fails.
+    pub fn train(
+        &mut self,
+        examples: &[LabeledExample],
+        now: DateTime<Utc>,
+    ) -> Result<(), WarpError> {
+        let (holdout, training): (Vec<_>, Vec<_>) = examples
+            .iter()
+            .enumerate()
+            .partition(|(i, _)| i % HOLDOUT_EVERY == HOLDOUT_EVERY - 1);
+        let training: Vec<LabeledExample> = training
+            .into_iter()
+            .map(|(_, example)| example.clone())
+            .collect();
+        let model = LogisticModel::fit(&training, &self.feature_names, &self.config)
+            .map_err(|e| e.context(format!("training {} model", self.model_name)))?;
+
+        let predicted: Vec<f64> = holdout
+            .iter()
+            .map(|(_, example)| model.predict_proba(&example.features))
+            .collect();
+        let labels: Vec<bool> = holdout.iter().map(|(_, example)| example.label).collect();
+        self.calibration = Some(CalibrationReport::from_predictions(&predicted, &labels));
+
+        let mut scored: Vec<(String, f64)> = examples
+            .iter()
+            .map(|example| {
+                (
+                    example.user_id.clone(),
+                    model.predict_proba(&example.features),
+                )
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(TOP_USERS);
+        self.top_users = scored;
+
+        self.background = holdout
+            .iter()
+            .take(BACKGROUND_SIZE)
+            .map(|(_, example)| (*example).clone())
+            .collect();
+        self.training_examples = training.len();
+        self.last_trained = Some(now);
+        self.model = Some(model);
+        Ok(())
+    }
+
+    pub fn predict(&self, features: &HashMap<String, f64>) -> Option<PredictionResult> {
+        let model = self.model.as_ref()?;
+        let value = model.predict_proba(features);
+        // Held-out calibration error bounds how far to trust the score
+        let confidence = self.calibration.as_ref().map_or(0.5, |report| {
+            (1.0 - report.expected_calibration_error).clamp(0.0, 1.0)
+        });
+        Some(PredictionResult {
+            prediction_type: self.prediction_type.clone(),
+            value,
+            confidence,
+            factors: self.attributions(features).unwrap_or_default(),
+            time_horizon: self.time_horizon,
+        })
+    }
+
+    // Which features moved this user's score away from the held-out average
+    pub fn attributions(&self, features: &HashMap<String, f64>) -> Option<Vec<PredictionFactor>> {
+        let model = self.model.as_ref()?;
+        let background: Vec<HashMap<String, f64>> = self
+            .background
+            .iter()
+            .map(|example| example.features.clone())
+            .collect();
+        let contributions = explain::shapley_values(
+            |row| model.predict_proba(row),
+            features,
+            &background,
+            &self.feature_names,
+            explain::SHAPLEY_SAMPLES,
+        );
+        Some(explain::to_factors(contributions, features))
+    }
+
+    // Permutation importance over the held-out examples, most important first
+    pub fn feature_importance(&self) -> Option<Vec<PredictionFactor>> {
+        let model = self.model.as_ref()?;
+        let examples: Vec<(HashMap<String, f64>, bool)> = self
+            .background
+            .iter()
+            .map(|example| (example.features.clone(), example.label))
+            .collect();
+        let importance = explain::permutation_importance(
+            |row| model.predict_proba(row),
+            &examples,
+            &self.feature_names,
+        );
+        let total: f64 = importance.iter().map(|(_, value)| value).sum();
+        let mut factors: Vec<PredictionFactor> = importance
+            .into_iter()
+            .map(|(name, value)| PredictionFactor {
+                description: format!("Scrambling {} raises the Brier score by {:.3}", name, value),
+                feature_name: name,
+                importance: if total > 0.0 { value / total } else { 0.0 },
+                direction: FactorDirection::Neutral,
+            })
+            .collect();
+        factors.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+        Some(factors)
+    }
+
+    pub fn summary(&self) -> PipelineSummary {
+        PipelineSummary {
+            model_name: self.model_name.clone(),
+            last_trained: self.last_trained,
+            next_training: self
+                .last_trained
+                .map(|trained| trained + self.retrain_interval),
+            training_examples: self.training_examples,
+            calibration: self.calibration.clone(),
+            top_users: self.top_users.clone(),
+        }
+    }
+}
+
+// What the ML insights dashboard tab shows for one pipeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineSummary {
+    pub model_name: String,
+    pub last_trained: Option<DateTime<Utc>>,
+    pub next_training: Option<DateTime<Utc>>,
+    pub training_examples: usize,
+    pub calibration: Option<CalibrationReport>,
+    pub top_users: Vec<(String, f64)>,
+}
+
+// The churn and feature-adoption pipelines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PredictionPipelines {
+    jobs: Vec<TrainingJob>,
+}
+
+impl PredictionPipelines {
+    pub fn new() -> Self {
+        Self {
+            jobs: vec![TrainingJob::churn(), TrainingJob::feature_adoption()],
+        }
+    }
+
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<String> {
+        self.jobs
+            .iter()
+            .filter(|job| job.is_due(now))
+            .map(|job| job.model_name.clone())
+            .collect()
+    }
+
+    pub fn job(&self, model_name: &str) -> Option<&TrainingJob> {
+        self.jobs.iter().find(|job| job.model_name == model_name)
+    }
+
+    pub fn job_mut(&mut self, model_name: &str) -> Option<&mut TrainingJob> {
+        self.jobs
+            .iter_mut()
+            .find(|job| job.model_name == model_name)
+    }
+
+    pub fn set_retrain_interval(&mut self, interval: Duration) {
+        for job in &mut self.jobs {
+            job.retrain_interval = interval;
+        }
+    }
+
+    // None until the pipeline for this prediction type has been trained
+    pub fn predict(
+        &self,
+        prediction_type: &PredictionType,
+        features: &HashMap<String, f64>,
+    ) -> Option<PredictionResult> {
+        self.jobs
+            .iter()
+            .find(|job| {
+                std::mem::discriminant(&job.prediction_type)
+                    == std::mem::discriminant(prediction_type)
+            })
+            .and_then(|job| job.predict(features))
+    }
+
+    pub fn summaries(&self) -> Vec<PipelineSummary> {
+        self.jobs.iter().map(TrainingJob::summary).collect()
+    }
+}
+
+impl Default for PredictionPipelines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(user: usize, idle_days: f64, sessions: f64, churned: bool) -> LabeledExample {
+        LabeledExample {
+            user_id: format!("user-{}", user),
+            features: HashMap::from([
+                ("days_since_last_session".to_string(), idle_days),
+                ("sessions_last_30d".to_string(), sessions),
+            ]),
+            label: churned,
+        }
+    }
+
+    fn examples() -> Vec<LabeledExample> {
+        (0..100)
+            .map(|i| {
+                let churned = i % 2 == 0;
+                let jitter = (i % 7) as f64;
+                if churned {
+                    example(i, 20.0 + jitter, 1.0 + jitter / 7.0, true)
+                } else {
+                    example(i, 1.0 + jitter / 7.0, 25.0 + jitter, false)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_churn_job_learns_and_calibrates() {
+        let mut job = TrainingJob::churn();
+        let now = Utc::now();
+        assert!(job.is_due(now));
+        job.train(&examples(), now).unwrap();
+        assert!(!job.is_due(now + Duration::hours(1)));
+        assert!(job.is_due(now + Duration::days(1)));
+
+        let idle = HashMap::from([
+            ("days_since_last_session".to_string(), 30.0),
+            ("sessions_last_30d".to_string(), 0.0),
+        ]);
+        let active = HashMap::from([
+            ("days_since_last_session".to_string(), 0.0),
+            ("sessions_last_30d".to_string(), 30.0),
+        ]);
+        let idle_result = job.predict(&idle).unwrap();
+        assert!(idle_result.value > 0.9);
+        assert!(job.predict(&active).unwrap().value < 0.1);
+        assert!(matches!(
+            idle_result.factors[0].direction,
+            FactorDirection::Positive
+        ));
+        let importance = job.feature_importance().unwrap();
+        assert!(importance[0].importance > 0.0);
+
+        let report = job.calibration.as_ref().unwrap();
+        assert_eq!(report.samples, 20);
+        assert!(report.brier_score < 0.05);
+        assert_eq!(job.top_users.len(), TOP_USERS);
+    }
+
+    #[test]
+    fn test_calibration_report_measures_miscalibration() {
+        let labels = [true, false, true, false];
+        let perfect = CalibrationReport::from_predictions(&[1.0, 0.0, 1.0, 0.0], &labels);
+        assert_eq!(perfect.brier_score, 0.0);
+        assert_eq!(perfect.expected_calibration_error, 0.0);
+
+        let overconfident = CalibrationReport::from_predictions(&[0.9; 4], &labels);
+        assert!((overconfident.expected_calibration_error - 0.4).abs() < 1e-9);
+        assert_eq!(overconfident.bins[9].count, 4);
+    }
+
+    #[test]
+    fn test_untrained_pipeline_has_no_prediction() {
+        let pipelines = PredictionPipelines::new();
+        assert!(pipelines
+            .predict(&PredictionType::ChurnProbability, &HashMap::new())
+            .is_none());
+        assert_eq!(
+            pipelines.due(Utc::now()),
+            vec!["churn".to_string(), "feature_adoption".to_string()]
+        );
+    }
+}
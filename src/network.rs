@@ -1,3 +1,7 @@
+use std::path::Path;
+use std::sync::RwLock;
+
+use crate::config::NetworkConfig;
 use crate::error::WarpError;
 
 pub struct NetworkManager;
@@ -7,3 +11,248 @@ impl NetworkManager {
         Ok(Self)
     }
 }
+
+// Proxy URLs after config overrides are applied to the environment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxySettings {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    // Config wins per scheme; otherwise the usual variables, upper case
+    // first, with ALL_PROXY covering both schemes. NO_PROXY entries from
+    // config and environment are combined.
+    pub fn resolve(config: &NetworkConfig, env: impl Fn(&str) -> Option<String>) -> Self {
+        let from_env = |names: &[&str]| {
+            if !config.use_env_proxy {
+                return None;
+            }
+            names
+                .iter()
+                .find_map(|name| env(name).filter(|value| !value.trim().is_empty()))
+        };
+        let all = from_env(&["ALL_PROXY", "all_proxy"]);
+        let http = config
+            .http_proxy
+            .clone()
+            .or_else(|| from_env(&["HTTP_PROXY", "http_proxy"]))
+            .or_else(|| all.clone());
+        let https = config
+            .https_proxy
+            .clone()
+            .or_else(|| from_env(&["HTTPS_PROXY", "https_proxy"]))
+            .or(all);
+
+        let mut no_proxy: Vec<String> = config.no_proxy.clone();
+        if let Some(list) = from_env(&["NO_PROXY", "no_proxy"]) {
+            no_proxy.extend(list.split(',').map(|host| host.trim().to_string()));
+        }
+        no_proxy.retain(|host| !host.is_empty());
+        no_proxy.dedup();
+        Self {
+            http,
+            https,
+            no_proxy,
+        }
+    }
+}
+
+struct Settings {
+    proxies: ProxySettings,
+    certificates: Vec<reqwest::Certificate>,
+}
+
+static SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+
+// Base URL of an active cassette server; see crate::cassette
+static ENDPOINT: RwLock<Option<String>> = RwLock::new(None);
+
+// Resolves proxies and loads the CA bundle once at startup so a bad path
+// or URL is reported up front rather than on the first request. Clients
+// built afterwards through client_builder() all share the result.
+pub fn init(config: &NetworkConfig) -> Result<(), WarpError> {
+    let proxies = ProxySettings::resolve(config, |name| std::env::var(name).ok());
+    for url in proxies.http.iter().chain(&proxies.https) {
+        reqwest::Proxy::all(url.as_str())
+            .map_err(|e| WarpError::Config(format!("Invalid proxy URL '{}': {}", url, e)))?;
+    }
+    let certificates = match &config.ca_bundle {
+        Some(path) => load_ca_bundle(path)?,
+        None => Vec::new(),
+    };
+    if let Ok(mut settings) = SETTINGS.write() {
+        *settings = Some(Settings {
+            proxies,
+            certificates,
+        });
+    }
+    Ok(())
+}
+
+// Every outgoing HTTP client starts here so proxies and extra CAs apply
+// uniformly. Before init() it behaves like reqwest's defaults.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    if endpoint_override().is_some() {
+        // The cassette server is on loopback; a proxy would never reach it
+        return reqwest::Client::builder().no_proxy();
+    }
+    upstream_client_builder()
+}
+
+// The real network even while a cassette is active, for recording
+pub(crate) fn upstream_client_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder();
+    let settings = match SETTINGS.read() {
+        Ok(settings) => settings,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let Some(settings) = settings.as_ref() else {
+        return builder;
+    };
+
+    // Environment lookups were already folded into the resolved settings
+    let mut builder = builder.no_proxy();
+    let no_proxy = reqwest::NoProxy::from_string(&settings.proxies.no_proxy.join(","));
+    if let Some(url) = &settings.proxies.http {
+        if let Ok(proxy) = reqwest::Proxy::http(url.as_str()) {
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+    }
+    if let Some(url) = &settings.proxies.https {
+        if let Ok(proxy) = reqwest::Proxy::https(url.as_str()) {
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+    }
+    for certificate in &settings.certificates {
+        builder = builder.add_root_certificate(certificate.clone());
+    }
+    builder
+}
+
+pub fn client() -> Result<reqwest::Client, WarpError> {
+    client_builder()
+        .build()
+        .map_err(|e| WarpError::network("Failed to set up the HTTP client", e))
+}
+
+// Integrations build request URLs through this so a cassette can stand in
+// for the remote service: `https://api.github.com/x` becomes
+// `http://127.0.0.1:<port>/https/api.github.com/x`.
+pub fn endpoint(url: &str) -> String {
+    match (endpoint_override(), url.split_once("://")) {
+        (Some(base), Some((scheme, rest))) => format!("{}/{}/{}", base, scheme, rest),
+        _ => url.to_string(),
+    }
+}
+
+fn endpoint_override() -> Option<String> {
+    match ENDPOINT.read() {
+        Ok(endpoint) => endpoint.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+pub(crate) fn set_endpoint_override(base: Option<String>) {
+    if let Ok(mut endpoint) = ENDPOINT.write() {
+        *endpoint = base;
+    }
+}
+
+pub fn load_ca_bundle(path: &Path) -> Result<Vec<reqwest::Certificate>, WarpError> {
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        WarpError::Config(format!("Cannot read CA bundle {}: {}", path.display(), e))
+    })?;
+    let blocks = pem_blocks(&text);
+    if blocks.is_empty() {
+        return Err(WarpError::Config(format!(
+            "No certificates found in {}",
+            path.display()
+        )));
+    }
+    blocks
+        .into_iter()
+        .map(|block| {
+            reqwest::Certificate::from_pem(block.as_bytes()).map_err(|e| {
+                WarpError::Config(format!("Bad certificate in {}: {}", path.display(), e))
+            })
+        })
+        .collect()
+}
+
+// The individual certificates of a PEM bundle, ignoring comments between them
+fn pem_blocks(text: &str) -> Vec<&str> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(BEGIN) {
+        let Some(len) = rest[start..].find(END) else {
+            break;
+        };
+        let end = start + len + END.len();
+        blocks.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config() -> NetworkConfig {
+        NetworkConfig {
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: Vec::new(),
+            use_env_proxy: true,
+            ca_bundle: None,
+        }
+    }
+
+    #[test]
+    fn test_config_overrides_environment() {
+        let env: HashMap<&str, &str> = [
+            ("https_proxy", "http://env:3128"),
+            ("ALL_PROXY", "http://all:3128"),
+            ("NO_PROXY", "localhost, .corp.example.com"),
+        ]
+        .into_iter()
+        .collect();
+        let lookup = |name: &str| env.get(name).map(|value| value.to_string());
+
+        let proxies = ProxySettings::resolve(&config(), lookup);
+        assert_eq!(proxies.https.as_deref(), Some("http://env:3128"));
+        assert_eq!(proxies.http.as_deref(), Some("http://all:3128"));
+        assert_eq!(proxies.no_proxy, vec!["localhost", ".corp.example.com"]);
+
+        let mut overridden = config();
+        overridden.https_proxy = Some("http://config:8080".to_string());
+        overridden.no_proxy = vec!["10.0.0.0/8".to_string()];
+        let proxies = ProxySettings::resolve(&overridden, lookup);
+        assert_eq!(proxies.https.as_deref(), Some("http://config:8080"));
+        assert_eq!(
+            proxies.no_proxy,
+            vec!["10.0.0.0/8", "localhost", ".corp.example.com"]
+        );
+
+        overridden.use_env_proxy = false;
+        let proxies = ProxySettings::resolve(&overridden, lookup);
+        assert_eq!(proxies.http, None);
+        assert_eq!(proxies.no_proxy, vec!["10.0.0.0/8"]);
+    }
+
+    #[test]
+    fn test_pem_blocks() {
+        let bundle = "# Corp root\n-----BEGIN CERTIFICATE-----\nAAA\n-----END CERTIFICATE-----\n\
+                      # Corp intermediate\n-----BEGIN CERTIFICATE-----\nBBB\n-----END CERTIFICATE-----\n\
+                      -----BEGIN CERTIFICATE-----\ntruncated";
+        let blocks = pem_blocks(bundle);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[1].contains("BBB"));
+        assert!(pem_blocks("not a bundle").is_empty());
+    }
+}
@@ -0,0 +1,551 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::{watch, Mutex};
+
+use crate::config::NetworkInspectorConfig;
+use crate::error::WarpError;
+use crate::process_tree::{build_tree, snapshot};
+
+// eBPF capture would also see short-lived connections between polls, but it
+// needs privileges we don't assume; polling /proc or lsof is the portable path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureBackend {
+    Procfs,
+    Lsof,
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SocketPair {
+    local: SocketAddr,
+    remote: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+struct ObservedConnection {
+    pid: u32,
+    first_seen: chrono::DateTime<chrono::Utc>,
+    last_seen: chrono::DateTime<chrono::Utc>,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DestinationSummary {
+    pub address: IpAddr,
+    pub host: Option<String>,
+    pub port: u16,
+    pub connections: usize,
+    pub pids: Vec<u32>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub duration_ms: i64,
+    pub unexpected: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockNetworkSummary {
+    pub block_id: String,
+    pub command: String,
+    pub backend: CaptureBackend,
+    pub destinations: Vec<DestinationSummary>,
+}
+
+impl BlockNetworkSummary {
+    pub fn unexpected(&self) -> impl Iterator<Item = &DestinationSummary> {
+        self.destinations.iter().filter(|d| d.unexpected)
+    }
+
+    pub fn display_lines(&self) -> Vec<String> {
+        self.destinations
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} {}:{} ×{} ↑{} ↓{} {}ms",
+                    if d.unexpected { "⚠" } else { "·" },
+                    d.host.clone().unwrap_or_else(|| d.address.to_string()),
+                    d.port,
+                    d.connections,
+                    d.bytes_sent,
+                    d.bytes_received,
+                    d.duration_ms
+                )
+            })
+            .collect()
+    }
+}
+
+struct ActiveCapture {
+    command: String,
+    stop: watch::Sender<bool>,
+    connections: Arc<Mutex<HashMap<SocketPair, ObservedConnection>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+pub struct NetworkInspector {
+    config: NetworkInspectorConfig,
+    backend: CaptureBackend,
+    // Allowed host names resolved to addresses, so destinations can be
+    // labelled without reverse DNS
+    allowed: Arc<Mutex<HashMap<IpAddr, String>>>,
+    captures: Mutex<HashMap<String, ActiveCapture>>,
+}
+
+impl NetworkInspector {
+    pub async fn new(config: NetworkInspectorConfig) -> Result<Self, WarpError> {
+        let backend = if config.enabled {
+            Self::detect_backend().await
+        } else {
+            CaptureBackend::Unavailable
+        };
+        let inspector = Self {
+            config,
+            backend,
+            allowed: Arc::new(Mutex::new(HashMap::new())),
+            captures: Mutex::new(HashMap::new()),
+        };
+        inspector.resolve_allowed_hosts().await;
+        Ok(inspector)
+    }
+
+    async fn detect_backend() -> CaptureBackend {
+        if std::path::Path::new("/proc/net/tcp").exists() {
+            return CaptureBackend::Procfs;
+        }
+        let has_lsof = Command::new("lsof")
+            .arg("-v")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok();
+        if has_lsof {
+            CaptureBackend::Lsof
+        } else {
+            CaptureBackend::Unavailable
+        }
+    }
+
+    pub fn backend(&self) -> CaptureBackend {
+        self.backend
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled && self.backend != CaptureBackend::Unavailable
+    }
+
+    async fn resolve_allowed_hosts(&self) {
+        let mut allowed = self.allowed.lock().await;
+        for host in &self.config.allowed_hosts {
+            if host.starts_with("*.") {
+                continue; // wildcard entries are matched against resolved names only
+            }
+            if let Ok(ip) = host.parse::<IpAddr>() {
+                allowed.insert(ip, host.clone());
+                continue;
+            }
+            if let Ok(addrs) = tokio::net::lookup_host((host.as_str(), 0)).await {
+                for addr in addrs {
+                    allowed.insert(addr.ip(), host.clone());
+                }
+            }
+        }
+    }
+
+    // Starts polling the connections of `root_pid` and its descendants for
+    // the block. A block that is already being captured is left alone.
+    pub async fn start_block(&self, block_id: &str, command: &str, root_pid: u32) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut captures = self.captures.lock().await;
+        if captures.contains_key(block_id) {
+            return;
+        }
+
+        let (stop, mut stopped) = watch::channel(false);
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let task_connections = connections.clone();
+        let backend = self.backend;
+        let interval = Duration::from_millis(self.config.poll_interval_ms.max(50));
+
+        let task = tokio::spawn(async move {
+            let mut system = sysinfo::System::new();
+            loop {
+                let pids = descendant_pids(&mut system, root_pid);
+                match poll_connections(backend, &pids).await {
+                    Ok(observed) => {
+                        let now = chrono::Utc::now();
+                        let mut connections = task_connections.lock().await;
+                        for (pair, pid, sent, received) in observed {
+                            let entry = connections.entry(pair).or_insert(ObservedConnection {
+                                pid,
+                                first_seen: now,
+                                last_seen: now,
+                                bytes_sent: 0,
+                                bytes_received: 0,
+                            });
+                            entry.last_seen = now;
+                            entry.bytes_sent = entry.bytes_sent.max(sent);
+                            entry.bytes_received = entry.bytes_received.max(received);
+                        }
+                    }
+                    Err(e) => log::debug!("Network capture poll failed: {}", e),
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = stopped.changed() => return,
+                }
+            }
+        });
+
+        captures.insert(
+            block_id.to_string(),
+            ActiveCapture {
+                command: command.to_string(),
+                stop,
+                connections,
+                task,
+            },
+        );
+    }
+
+    pub async fn finish_block(&self, block_id: &str) -> Option<BlockNetworkSummary> {
+        let capture = self.captures.lock().await.remove(block_id)?;
+        let _ = capture.stop.send(true);
+        let _ = capture.task.await;
+
+        let connections = capture.connections.lock().await;
+        let allowed = self.allowed.lock().await;
+        Some(BlockNetworkSummary {
+            block_id: block_id.to_string(),
+            command: capture.command,
+            backend: self.backend,
+            destinations: summarize(
+                &connections,
+                &allowed,
+                &self.config.allowed_hosts,
+                self.config.flag_unexpected,
+            ),
+        })
+    }
+}
+
+fn summarize(
+    connections: &HashMap<SocketPair, ObservedConnection>,
+    allowed: &HashMap<IpAddr, String>,
+    allowed_hosts: &[String],
+    flag_unexpected: bool,
+) -> Vec<DestinationSummary> {
+    let mut by_destination: HashMap<(IpAddr, u16), DestinationSummary> = HashMap::new();
+    for (pair, conn) in connections {
+        let address = pair.remote.ip();
+        let host = allowed.get(&address).cloned();
+        let summary = by_destination
+            .entry((address, pair.remote.port()))
+            .or_insert_with(|| DestinationSummary {
+                address,
+                host: host.clone(),
+                port: pair.remote.port(),
+                connections: 0,
+                pids: Vec::new(),
+                bytes_sent: 0,
+                bytes_received: 0,
+                duration_ms: 0,
+                unexpected: flag_unexpected
+                    && !is_expected(address, host.as_deref(), allowed_hosts),
+            });
+        summary.connections += 1;
+        if !summary.pids.contains(&conn.pid) {
+            summary.pids.push(conn.pid);
+        }
+        summary.bytes_sent += conn.bytes_sent;
+        summary.bytes_received += conn.bytes_received;
+        summary.duration_ms = summary
+            .duration_ms
+            .max((conn.last_seen - conn.first_seen).num_milliseconds());
+    }
+
+    let mut destinations: Vec<DestinationSummary> = by_destination.into_values().collect();
+    destinations.sort_by(|a, b| {
+        b.unexpected
+            .cmp(&a.unexpected)
+            .then(b.bytes_received.cmp(&a.bytes_received))
+    });
+    destinations
+}
+
+// Local and private-network traffic is never flagged; an empty allowlist
+// means "don't flag anything" rather than "flag everything"
+fn is_expected(address: IpAddr, host: Option<&str>, allowed_hosts: &[String]) -> bool {
+    let private = match address {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local(),
+        IpAddr::V6(v6) => v6.is_loopback() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+    };
+    if private || allowed_hosts.is_empty() {
+        return true;
+    }
+    let Some(host) = host else {
+        return false;
+    };
+    allowed_hosts
+        .iter()
+        .any(|pattern| match pattern.strip_prefix("*.") {
+            Some(suffix) => host.ends_with(&format!(".{}", suffix)),
+            None => pattern == host,
+        })
+}
+
+fn descendant_pids(system: &mut sysinfo::System, root_pid: u32) -> HashSet<u32> {
+    system.refresh_processes();
+    build_tree(&snapshot(system), root_pid)
+        .into_iter()
+        .map(|node| node.info.pid)
+        .collect()
+}
+
+async fn poll_connections(
+    backend: CaptureBackend,
+    pids: &HashSet<u32>,
+) -> Result<Vec<(SocketPair, u32, u64, u64)>, WarpError> {
+    let owned: Vec<(SocketPair, u32)> = match backend {
+        CaptureBackend::Procfs => {
+            let mut inodes: HashMap<u64, u32> = HashMap::new();
+            for pid in pids {
+                let Ok(mut fds) = tokio::fs::read_dir(format!("/proc/{}/fd", pid)).await else {
+                    continue; // exited, or not ours to inspect
+                };
+                while let Ok(Some(fd)) = fds.next_entry().await {
+                    if let Ok(target) = tokio::fs::read_link(fd.path()).await {
+                        if let Some(inode) = parse_socket_inode(&target.to_string_lossy()) {
+                            inodes.insert(inode, *pid);
+                        }
+                    }
+                }
+            }
+            let mut owned = Vec::new();
+            for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+                let Ok(content) = tokio::fs::read_to_string(table).await else {
+                    continue;
+                };
+                for (pair, inode) in parse_proc_net_tcp(&content) {
+                    if let Some(pid) = inodes.get(&inode) {
+                        owned.push((pair, *pid));
+                    }
+                }
+            }
+            owned
+        }
+        CaptureBackend::Lsof => {
+            if pids.is_empty() {
+                return Ok(Vec::new());
+            }
+            let pid_list = pids
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            let output = Command::new("lsof")
+                .args([
+                    "-nP",
+                    "-a",
+                    "-iTCP",
+                    "-sTCP:ESTABLISHED",
+                    "-F",
+                    "pn",
+                    "-p",
+                    &pid_list,
+                ])
+                .stderr(Stdio::null())
+                .output()
+                .await?;
+            parse_lsof(&String::from_utf8_lossy(&output.stdout))
+        }
+        CaptureBackend::Unavailable => Vec::new(),
+    };
+
+    // Byte counters are only available from `ss`; without it they stay at 0
+    let counters = match Command::new("ss")
+        .args(["-tinH"])
+        .stderr(Stdio::null())
+        .output()
+        .await
+    {
+        Ok(output) => parse_ss_counters(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => HashMap::new(),
+    };
+
+    Ok(owned
+        .into_iter()
+        .map(|(pair, pid)| {
+            let (sent, received) = counters.get(&pair).copied().unwrap_or((0, 0));
+            (pair, pid, sent, received)
+        })
+        .collect())
+}
+
+pub(crate) fn parse_socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+// /proc/net/tcp{,6}: established and connecting sockets with their inode.
+// Addresses are hex, each 32-bit word in host (little-endian) order.
+fn parse_proc_net_tcp(content: &str) -> Vec<(SocketPair, u64)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                return None;
+            }
+            // 01 = ESTABLISHED, 02 = SYN_SENT
+            if fields[3] != "01" && fields[3] != "02" {
+                return None;
+            }
+            let pair = SocketPair {
+                local: parse_hex_addr(fields[1])?,
+                remote: parse_hex_addr(fields[2])?,
+            };
+            Some((pair, fields[9].parse().ok()?))
+        })
+        .collect()
+}
+
+pub(crate) fn parse_hex_addr(field: &str) -> Option<SocketAddr> {
+    let (ip_hex, port_hex) = field.split_once(':')?;
+    let port = u16::from_str_radix(port_hex, 16).ok()?;
+    let ip = match ip_hex.len() {
+        8 => IpAddr::V4(Ipv4Addr::from(
+            u32::from_str_radix(ip_hex, 16).ok()?.swap_bytes(),
+        )),
+        32 => {
+            let mut bytes = [0u8; 16];
+            for word in 0..4 {
+                let value = u32::from_str_radix(&ip_hex[word * 8..word * 8 + 8], 16).ok()?;
+                bytes[word * 4..word * 4 + 4].copy_from_slice(&value.to_le_bytes());
+            }
+            let v6 = Ipv6Addr::from(bytes);
+            v6.to_ipv4_mapped()
+                .map(IpAddr::V4)
+                .unwrap_or(IpAddr::V6(v6))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+// `lsof -F pn` emits "p<pid>" then one "n<local>-><remote>" line per socket
+fn parse_lsof(output: &str) -> Vec<(SocketPair, u32)> {
+    let mut current_pid = 0;
+    let mut result = Vec::new();
+    for line in output.lines() {
+        if let Some(pid) = line.strip_prefix('p') {
+            current_pid = pid.parse().unwrap_or(0);
+        } else if let Some(name) = line.strip_prefix('n') {
+            if let Some((local, remote)) = name.split_once("->") {
+                if let (Ok(local), Ok(remote)) = (local.parse(), remote.parse()) {
+                    result.push((SocketPair { local, remote }, current_pid));
+                }
+            }
+        }
+    }
+    result
+}
+
+// `ss -tinH`: a connection line followed by an indented line of TCP info
+fn parse_ss_counters(output: &str) -> HashMap<SocketPair, (u64, u64)> {
+    let mut counters = HashMap::new();
+    let mut current: Option<SocketPair> = None;
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            current = match (
+                fields.get(3).and_then(|l| parse_ss_addr(l)),
+                fields.get(4).and_then(|r| parse_ss_addr(r)),
+            ) {
+                (Some(local), Some(remote)) => Some(SocketPair { local, remote }),
+                _ => None,
+            };
+            continue;
+        }
+        let Some(pair) = current.take() else {
+            continue;
+        };
+        let counter = |name: &str| {
+            line.split_whitespace()
+                .find_map(|field| field.strip_prefix(name))
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        counters.insert(pair, (counter("bytes_sent:"), counter("bytes_received:")));
+    }
+    counters
+}
+
+fn parse_ss_addr(field: &str) -> Option<SocketAddr> {
+    let field = field.replace("%lo", "");
+    field.parse().ok().or_else(|| {
+        // ss prints IPv4-mapped v6 as [::ffff:1.2.3.4]:443
+        let (ip, port) = field.rsplit_once(':')?;
+        let ip = ip.trim_start_matches('[').trim_end_matches(']');
+        let ip: IpAddr = ip.trim_start_matches("::ffff:").parse().ok()?;
+        Some(SocketAddr::new(ip, port.parse().ok()?))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_net_tcp_ipv4() {
+        let content = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 11111 1\n   1: 0200000A:C350 22D8B85D:01BB 01 00000000:00000000 00:00000000 00000000  1000        0 22222 1\n";
+        let parsed = parse_proc_net_tcp(content);
+        assert_eq!(parsed.len(), 1, "listening sockets are skipped");
+        let (pair, inode) = parsed[0];
+        assert_eq!(inode, 22222);
+        assert_eq!(pair.local, "10.0.0.2:50000".parse().unwrap());
+        assert_eq!(pair.remote, "93.184.216.34:443".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_lsof_fields() {
+        let output = "p4242\nn10.0.0.2:50000->93.184.216.34:443\nn[::1]:5000->[::1]:8080\n";
+        let parsed = parse_lsof(output);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].1, 4242);
+        assert_eq!(parsed[1].0.remote.port(), 8080);
+    }
+
+    #[test]
+    fn test_parse_ss_counters() {
+        let output = "ESTAB 0 0 10.0.0.2:50000 93.184.216.34:443\n\t cubic wscale:7,7 bytes_sent:517 bytes_received:4096 segs_out:9\n";
+        let counters = parse_ss_counters(output);
+        let pair = SocketPair {
+            local: "10.0.0.2:50000".parse().unwrap(),
+            remote: "93.184.216.34:443".parse().unwrap(),
+        };
+        assert_eq!(counters.get(&pair), Some(&(517, 4096)));
+    }
+
+    #[test]
+    fn test_unexpected_destinations() {
+        let allowed = vec!["github.com".to_string(), "*.crates.io".to_string()];
+        let public: IpAddr = "93.184.216.34".parse().unwrap();
+        assert!(is_expected(public, Some("github.com"), &allowed));
+        assert!(is_expected(public, Some("static.crates.io"), &allowed));
+        assert!(!is_expected(public, None, &allowed));
+        assert!(is_expected("127.0.0.1".parse().unwrap(), None, &allowed));
+        assert!(is_expected(public, None, &[]));
+    }
+}
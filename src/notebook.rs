@@ -0,0 +1,765 @@
+use crossterm::style::Color;
+use ratatui::style::{Color as StyleColor, Modifier};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::WarpError;
+use crate::file_manager::shell_quote;
+use crate::headless::{self, RunResult};
+use crate::i18n;
+use crate::lint::{Diagnostic, Severity};
+use crate::pager::parse_ansi;
+use crate::shell::ShellKind;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+pub const EXTENSION: &str = "warpnb";
+const FORMAT_VERSION: u32 = 1;
+// Fence languages that become command cells when importing Markdown
+const SHELL_FENCES: &[&str] = &["sh", "bash", "zsh", "fish", "shell", "console"];
+// Output lines shown in the panel preview
+const PREVIEW_LINES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Cell {
+    Markdown {
+        source: String,
+    },
+    Command {
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<RunResult>,
+    },
+}
+
+// A .warpnb file: JSON so outputs can be stored alongside the cells
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notebook {
+    pub version: u32,
+    pub title: String,
+    pub cells: Vec<Cell>,
+}
+
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+impl Notebook {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            title: title.into(),
+            cells: Vec::new(),
+        }
+    }
+
+    // .md files are imported; the result is saved as .warpnb
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if path.extension().is_some_and(|ext| ext == "md") {
+            return Ok(Self::from_markdown(&text, title));
+        }
+        let notebook: Notebook = serde_json::from_str(&text)?;
+        if notebook.version > FORMAT_VERSION {
+            return Err(WarpError::Validation(i18n::t_args(
+                "notebook.newer_version",
+                &[("version", &notebook.version.to_string())],
+            )));
+        }
+        Ok(notebook)
+    }
+
+    // Written next to the target and renamed over it, so a crash mid-write
+    // doesn't lose the notebook
+    pub async fn save(&self, path: &Path) -> Result<(), WarpError> {
+        let temp = path.with_extension(format!("{}.tmp", EXTENSION));
+        tokio::fs::write(&temp, serde_json::to_string_pretty(self)?).await?;
+        tokio::fs::rename(&temp, path).await?;
+        Ok(())
+    }
+
+    // Shell fences become command cells (one per fence, `$ ` prompts
+    // stripped from console fences); everything else stays Markdown
+    pub fn from_markdown(text: &str, title: impl Into<String>) -> Self {
+        let mut notebook = Self::new(title);
+        let mut markdown = String::new();
+        let mut fence: Option<(String, bool, Vec<String>)> = None;
+        for line in text.lines() {
+            match fence.as_mut() {
+                None => match line.trim_start().strip_prefix("```") {
+                    Some(info) if SHELL_FENCES.contains(&info.trim()) => {
+                        notebook.push_markdown(&mut markdown);
+                        fence = Some((info.trim().to_string(), true, Vec::new()));
+                    }
+                    Some(info) => {
+                        markdown.push_str(line);
+                        markdown.push('\n');
+                        fence = Some((info.trim().to_string(), false, Vec::new()));
+                    }
+                    None => {
+                        markdown.push_str(line);
+                        markdown.push('\n');
+                    }
+                },
+                Some((info, shell, lines)) => {
+                    if line.trim_start().starts_with("```") {
+                        if *shell {
+                            let command = if info.as_str() == "console" {
+                                lines
+                                    .iter()
+                                    .filter_map(|line| line.strip_prefix("$ "))
+                                    .collect::<Vec<_>>()
+                                    .join("\n")
+                            } else {
+                                lines.join("\n")
+                            };
+                            if !command.trim().is_empty() {
+                                notebook.cells.push(Cell::Command {
+                                    command: command.trim().to_string(),
+                                    output: None,
+                                });
+                            }
+                        } else {
+                            markdown.push_str(line);
+                            markdown.push('\n');
+                        }
+                        fence = None;
+                    } else if *shell {
+                        lines.push(line.to_string());
+                    } else {
+                        markdown.push_str(line);
+                        markdown.push('\n');
+                    }
+                }
+            }
+        }
+        notebook.push_markdown(&mut markdown);
+        notebook
+    }
+
+    fn push_markdown(&mut self, markdown: &mut String) {
+        let source = std::mem::take(markdown);
+        if !source.trim().is_empty() {
+            self.cells.push(Cell::Markdown {
+                source: source.trim().to_string(),
+            });
+        }
+    }
+
+    pub fn command(&self, index: usize) -> Option<&str> {
+        match self.cells.get(index)? {
+            Cell::Command { command, .. } => Some(command),
+            Cell::Markdown { .. } => None,
+        }
+    }
+
+    pub fn set_output(&mut self, index: usize, result: RunResult) {
+        if let Some(Cell::Command { output, .. }) = self.cells.get_mut(index) {
+            *output = Some(result);
+        }
+    }
+
+    pub fn clear_outputs(&mut self) {
+        for cell in &mut self.cells {
+            if let Cell::Command { output, .. } = cell {
+                *output = None;
+            }
+        }
+    }
+
+    // Command cells from `start` on, in order
+    pub fn command_cells_from(&self, start: usize) -> Vec<usize> {
+        (start..self.cells.len())
+            .filter(|&index| self.command(index).is_some())
+            .collect()
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut body = String::new();
+        for cell in &self.cells {
+            match cell {
+                Cell::Markdown { source } => body.push_str(&markdown_to_html(source)),
+                Cell::Command { command, output } => {
+                    body.push_str(&format!(
+                        "<pre class=\"command\">❯ {}</pre>\n",
+                        escape_html(command)
+                    ));
+                    if let Some(output) = output {
+                        let status = match output.exit_code {
+                            Some(0) => "ok".to_string(),
+                            Some(code) => format!("exit {}", code),
+                            None if output.timed_out => "timed out".to_string(),
+                            None => "killed".to_string(),
+                        };
+                        body.push_str(&format!(
+                            "<pre class=\"output\">{}{}</pre>\n<p class=\"status\">{} · {} ms · {}</p>\n",
+                            ansi_to_html(&output.stdout),
+                            ansi_to_html(&output.stderr),
+                            status,
+                            output.duration_ms,
+                            output.started_at.to_rfc3339()
+                        ));
+                    }
+                }
+            }
+        }
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+             body {{ font-family: sans-serif; max-width: 60rem; margin: 2rem auto; }}\n\
+             pre {{ padding: 0.5rem; overflow-x: auto; }}\n\
+             pre.command {{ background: #eee; margin-bottom: 0; }}\n\
+             pre.output {{ background: #1e1e1e; color: #ddd; margin-top: 0; }}\n\
+             p.status {{ color: #888; font-size: 0.8rem; }}\n\
+             @media print {{ pre {{ white-space: pre-wrap; }} }}\n\
+             </style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+            title = escape_html(&self.title),
+            body = body
+        )
+    }
+
+    // Written next to the notebook; print it from a browser for a PDF
+    pub async fn export_html(&self, path: &Path) -> Result<PathBuf, WarpError> {
+        let target = path.with_extension("html");
+        tokio::fs::write(&target, self.to_html()).await?;
+        Ok(target)
+    }
+}
+
+// Cells run relative to the notebook, not to wherever warp was started
+pub fn directory(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// Each cell gets a fresh shell started in the notebook's directory, so
+// cells don't share variables or cd
+pub async fn run_cell(
+    shell: &str,
+    directory: &Path,
+    command: &str,
+    timeout: Duration,
+) -> Result<RunResult, WarpError> {
+    let directory = shell_quote(directory);
+    let script = match ShellKind::detect(shell) {
+        // An error stops the script in both, so a failed cd skips the command
+        ShellKind::Nushell => format!("cd {}\n{}", directory, command),
+        ShellKind::PowerShell => {
+            format!("Set-Location -ErrorAction Stop {}\n{}", directory, command)
+        }
+        _ => format!("cd {} || exit 1\n{}", directory, command),
+    };
+    let mut result = headless::run(shell, &script, Some(timeout)).await?;
+    result.command = command.to_string();
+    Ok(result)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// `code`, **bold** and *emphasis*, after escaping
+fn inline_html(text: &str) -> String {
+    let mut out = String::new();
+    for (i, part) in escape_html(text).split('`').enumerate() {
+        if i % 2 == 1 {
+            out.push_str(&format!("<code>{}</code>", part));
+            continue;
+        }
+        let mut bold = false;
+        for (j, piece) in part.split("**").enumerate() {
+            if j > 0 {
+                out.push_str(if bold { "</strong>" } else { "<strong>" });
+                bold = !bold;
+            }
+            let mut emphasis = false;
+            for (k, word) in piece.split('*').enumerate() {
+                if k > 0 {
+                    out.push_str(if emphasis { "</em>" } else { "<em>" });
+                    emphasis = !emphasis;
+                }
+                out.push_str(word);
+            }
+            if emphasis {
+                out.push_str("</em>");
+            }
+        }
+        if bold {
+            out.push_str("</strong>");
+        }
+    }
+    out
+}
+
+// The subset of Markdown runbooks use: headings, paragraphs, lists, fenced
+// code and inline code/emphasis
+fn markdown_to_html(source: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut list: Option<&str> = None;
+    let mut code: Option<Vec<&str>> = None;
+
+    fn flush(out: &mut String, paragraph: &mut Vec<&str>, list: &mut Option<&str>) {
+        if !paragraph.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", inline_html(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+        if let Some(tag) = list.take() {
+            out.push_str(&format!("</{}>\n", tag));
+        }
+    }
+
+    for line in source.lines() {
+        if let Some(lines) = code.as_mut() {
+            if line.trim_start().starts_with("```") {
+                out.push_str(&format!(
+                    "<pre><code>{}</code></pre>\n",
+                    escape_html(&lines.join("\n"))
+                ));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            flush(&mut out, &mut paragraph, &mut list);
+            code = Some(Vec::new());
+        } else if trimmed.is_empty() {
+            flush(&mut out, &mut paragraph, &mut list);
+        } else if let Some((level, text)) = heading(trimmed) {
+            flush(&mut out, &mut paragraph, &mut list);
+            out.push_str(&format!(
+                "<h{level}>{}</h{level}>\n",
+                inline_html(text),
+                level = level + 1
+            ));
+        } else if let Some((tag, item)) = list_item(trimmed) {
+            if !paragraph.is_empty() || list.is_some_and(|open| open != tag) {
+                flush(&mut out, &mut paragraph, &mut list);
+            }
+            if list.is_none() {
+                out.push_str(&format!("<{}>\n", tag));
+                list = Some(tag);
+            }
+            out.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+        } else {
+            if list.is_some() {
+                flush(&mut out, &mut paragraph, &mut list);
+            }
+            paragraph.push(trimmed);
+        }
+    }
+    if let Some(lines) = code {
+        out.push_str(&format!(
+            "<pre><code>{}</code></pre>\n",
+            escape_html(&lines.join("\n"))
+        ));
+    }
+    flush(&mut out, &mut paragraph, &mut list);
+    out
+}
+
+// Headings are shifted down one level; the notebook title is the <h1>
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=5).contains(&level) {
+        return None;
+    }
+    line[level..]
+        .strip_prefix(' ')
+        .map(|text| (level, text.trim()))
+}
+
+fn list_item(line: &str) -> Option<(&'static str, &str)> {
+    if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return Some(("ul", item));
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        if let Some(item) = line[digits..].strip_prefix(". ") {
+            return Some(("ol", item));
+        }
+    }
+    None
+}
+
+fn css_color(color: StyleColor) -> Option<String> {
+    let named = match color {
+        StyleColor::Black => "#000",
+        StyleColor::Red => "#c33",
+        StyleColor::Green => "#3a3",
+        StyleColor::Yellow => "#cc3",
+        StyleColor::Blue => "#36c",
+        StyleColor::Magenta => "#c3c",
+        StyleColor::Cyan => "#3cc",
+        StyleColor::Gray => "#ccc",
+        StyleColor::DarkGray => "#777",
+        StyleColor::LightRed => "#f66",
+        StyleColor::LightGreen => "#6f6",
+        StyleColor::LightYellow => "#ff6",
+        StyleColor::LightBlue => "#69f",
+        StyleColor::LightMagenta => "#f6f",
+        StyleColor::LightCyan => "#6ff",
+        StyleColor::White => "#fff",
+        StyleColor::Rgb(r, g, b) => return Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        _ => return None,
+    };
+    Some(named.to_string())
+}
+
+// Output keeps its SGR colours and bold; other escapes are dropped
+fn ansi_to_html(text: &str) -> String {
+    let mut out = String::new();
+    for line in text.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, "\n"),
+            None => (line, ""),
+        };
+        for (run, style) in parse_ansi(line) {
+            let mut css = Vec::new();
+            if let Some(color) = style.fg.and_then(css_color) {
+                css.push(format!("color: {}", color));
+            }
+            if let Some(color) = style.bg.and_then(css_color) {
+                css.push(format!("background: {}", color));
+            }
+            if style.add_modifier.contains(Modifier::BOLD) {
+                css.push("font-weight: bold".to_string());
+            }
+            if css.is_empty() {
+                out.push_str(&escape_html(&run));
+            } else {
+                out.push_str(&format!(
+                    "<span style=\"{}\">{}</span>",
+                    css.join("; "),
+                    escape_html(&run)
+                ));
+            }
+        }
+        out.push_str(newline);
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotebookCommand {
+    None,
+    Redraw,
+    // Command cells to run, in order
+    Run(Vec<usize>),
+    Export,
+    Edit(PathBuf),
+    Save,
+}
+
+// The notebook pane: one row per cell, the selected cell's text or output
+// in the preview
+pub struct NotebookPanel {
+    path: PathBuf,
+    notebook: Notebook,
+    running: Option<usize>,
+    status: Option<String>,
+    selected: usize,
+    diagnostics: HashMap<usize, Vec<Diagnostic>>, // from a language server, by cell
+}
+
+impl NotebookPanel {
+    pub fn new(path: PathBuf, notebook: Notebook) -> Self {
+        Self {
+            path,
+            notebook,
+            running: None,
+            status: None,
+            selected: 0,
+            diagnostics: HashMap::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn notebook(&self) -> &Notebook {
+        &self.notebook
+    }
+
+    pub fn set_notebook(&mut self, notebook: Notebook) {
+        self.notebook = notebook;
+        self.diagnostics.clear();
+        self.select(self.selected);
+    }
+
+    pub fn running(&self) -> Option<usize> {
+        self.running
+    }
+
+    pub fn set_running(&mut self, index: Option<usize>) {
+        self.running = index;
+    }
+
+    pub fn set_output(&mut self, index: usize, result: RunResult) {
+        self.notebook.set_output(index, result);
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn set_diagnostics(&mut self, index: usize, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics.insert(index, diagnostics);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.notebook.cells.len().saturating_sub(1));
+    }
+
+    // Enter runs the cell, r runs from it to the end, R runs everything
+    pub fn handle_key(&mut self, key: char, index: usize) -> NotebookCommand {
+        self.select(index);
+        let busy = self.running.is_some();
+        match key {
+            '\n' if !busy && self.notebook.command(self.selected).is_some() => {
+                NotebookCommand::Run(vec![self.selected])
+            }
+            'r' if !busy => NotebookCommand::Run(self.notebook.command_cells_from(self.selected)),
+            'R' if !busy => NotebookCommand::Run(self.notebook.command_cells_from(0)),
+            'c' if !busy => {
+                self.notebook.clear_outputs();
+                NotebookCommand::Save
+            }
+            'x' => NotebookCommand::Export,
+            'e' if !busy => NotebookCommand::Edit(self.path.clone()),
+            '\n' | 'r' | 'R' | 'c' | 'e' => NotebookCommand::None,
+            _ => NotebookCommand::Redraw,
+        }
+    }
+
+    fn cell_item(&self, index: usize, cell: &Cell) -> PanelItem {
+        match cell {
+            Cell::Markdown { source } => {
+                let first = source
+                    .lines()
+                    .find(|line| !line.trim().is_empty())
+                    .unwrap_or("");
+                PanelItem::styled(vec![(
+                    format!("  {}", first.trim_start_matches('#').trim()),
+                    Some(Color::DarkGrey),
+                )])
+            }
+            Cell::Command { command, output } => {
+                let (mark, color) = if self.running == Some(index) {
+                    ("…".to_string(), Color::Yellow)
+                } else {
+                    match output {
+                        None => ("·".to_string(), Color::DarkGrey),
+                        Some(output) if output.exit_code == Some(0) => {
+                            ("✓".to_string(), Color::Green)
+                        }
+                        Some(output) => (
+                            output
+                                .exit_code
+                                .map_or("✗".to_string(), |code| format!("✗{}", code)),
+                            Color::Red,
+                        ),
+                    }
+                };
+                let first = command.lines().next().unwrap_or("");
+                let mut segments = vec![
+                    (format!("{:<3}", mark), Some(color)),
+                    (format!("❯ {}", first), None),
+                ];
+                if let Some(output) = output {
+                    segments.push((
+                        format!("  {} ms", output.duration_ms),
+                        Some(Color::DarkGrey),
+                    ));
+                }
+                PanelItem::styled(segments)
+            }
+        }
+    }
+
+    fn preview(&self) -> Vec<PanelItem> {
+        match self.notebook.cells.get(self.selected) {
+            None => Vec::new(),
+            Some(Cell::Markdown { source }) => source
+                .lines()
+                .map(|line| PanelItem::from(line.to_string()))
+                .collect(),
+            Some(Cell::Command { command, output }) => {
+                let mut items: Vec<PanelItem> = command
+                    .lines()
+                    .map(|line| PanelItem::styled(vec![(format!("❯ {}", line), Some(Color::Cyan))]))
+                    .collect();
+                let diagnostics = self.diagnostics.get(&self.selected).into_iter().flatten();
+                items.extend(diagnostics.map(|diagnostic| {
+                    let color = match diagnostic.severity {
+                        Severity::Error => Color::Red,
+                        Severity::Warning => Color::Yellow,
+                    };
+                    PanelItem::styled(vec![(format!("  {}", diagnostic.message), Some(color))])
+                }));
+                let Some(output) = output else {
+                    return items;
+                };
+                let lines: Vec<&str> = output.stdout.lines().chain(output.stderr.lines()).collect();
+                let skip = lines.len().saturating_sub(PREVIEW_LINES);
+                items.extend(lines[skip..].iter().map(|line| {
+                    PanelItem::from(
+                        parse_ansi(line)
+                            .into_iter()
+                            .map(|(text, _)| text)
+                            .collect::<String>(),
+                    )
+                }));
+                items
+            }
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .notebook
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| self.cell_item(index, cell))
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.notebook_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::Notebook,
+            title: i18n::t_args(
+                "panel.notebook",
+                &[
+                    ("title", &self.notebook.title),
+                    ("count", &self.notebook.cells.len().to_string()),
+                ],
+            ),
+            items,
+            footer: Some(
+                self.status
+                    .clone()
+                    .unwrap_or_else(|| i18n::t("panel.notebook_keys")),
+            ),
+            preview: self.preview(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUNBOOK: &str = "# Restart the API\n\nCheck the **current** state first.\n\n```console\n$ systemctl status api\n● api.service\n```\n\n1. Drain\n2. Restart\n\n```sh\nsystemctl restart api\n```\n\n```json\n{\"a\": 1}\n```\n";
+
+    #[test]
+    fn test_markdown_import_splits_shell_fences() {
+        let notebook = Notebook::from_markdown(RUNBOOK, "restart");
+        let kinds: Vec<Option<&str>> = (0..notebook.cells.len())
+            .map(|i| notebook.command(i))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                None,
+                Some("systemctl status api"),
+                None,
+                Some("systemctl restart api"),
+                None
+            ]
+        );
+        assert_eq!(notebook.command_cells_from(2), vec![3]);
+        let Cell::Markdown { source } = &notebook.cells[4] else {
+            panic!("expected markdown");
+        };
+        assert!(source.contains("```json"));
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("restart.{}", EXTENSION));
+        let notebook = Notebook::from_markdown(RUNBOOK, "restart");
+        notebook.save(&path).await.unwrap();
+        assert!(is_notebook(&path));
+        assert_eq!(Notebook::load(&path).await.unwrap(), notebook);
+
+        let mut newer = notebook.clone();
+        newer.version = FORMAT_VERSION + 1;
+        newer.save(&path).await.unwrap();
+        assert!(matches!(
+            Notebook::load(&path).await,
+            Err(WarpError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_html_export() {
+        let mut notebook = Notebook::from_markdown(RUNBOOK, "Restart <prod>");
+        notebook.set_output(
+            1,
+            RunResult {
+                block_id: "b".to_string(),
+                command: "systemctl status api".to_string(),
+                shell: "sh".to_string(),
+                stdout: "\x1b[32mactive\x1b[0m\n".to_string(),
+                stderr: String::new(),
+                exit_code: Some(0),
+                duration_ms: 12,
+                started_at: chrono::Utc::now(),
+                timed_out: false,
+            },
+        );
+        let html = notebook.to_html();
+        assert!(html.contains("<title>Restart &lt;prod&gt;</title>"));
+        assert!(html.contains("<h2>Restart the API</h2>"));
+        assert!(html.contains("Check the <strong>current</strong> state first."));
+        assert!(html.contains("<ol>\n<li>Drain</li>\n<li>Restart</li>\n</ol>"));
+        assert!(html.contains("<span style=\"color: #3a3\">active</span>"));
+        assert!(!html.contains('\x1b'));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cells_run_in_the_notebook_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deploy.warpnb");
+        assert_eq!(directory(&path), dir.path());
+        assert_eq!(directory(Path::new("deploy.warpnb")), PathBuf::from("."));
+        let result = run_cell("sh", &directory(&path), "pwd", Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(result.command, "pwd");
+        assert_eq!(
+            std::fs::canonicalize(result.stdout.trim()).unwrap(),
+            std::fs::canonicalize(dir.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_panel_keys() {
+        let notebook = Notebook::from_markdown(RUNBOOK, "restart");
+        let mut panel = NotebookPanel::new(PathBuf::from("restart.warpnb"), notebook);
+        assert_eq!(panel.handle_key('\n', 0), NotebookCommand::None);
+        assert_eq!(panel.handle_key('\n', 1), NotebookCommand::Run(vec![1]));
+        assert_eq!(panel.handle_key('R', 4), NotebookCommand::Run(vec![1, 3]));
+        panel.set_running(Some(1));
+        assert_eq!(panel.handle_key('r', 0), NotebookCommand::None);
+        assert_eq!(panel.view().items.len(), 5);
+    }
+}
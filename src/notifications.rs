@@ -0,0 +1,526 @@
+use chrono::{DateTime, Local, NaiveTime};
+use crossterm::style::Color;
+use std::collections::VecDeque;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config::{NotificationCategory, NotificationLevel, NotificationsConfig, QuietHours};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+pub const CATEGORIES: &[NotificationCategory] = &[
+    NotificationCategory::Jobs,
+    NotificationCategory::Alerts,
+    NotificationCategory::Collaboration,
+    NotificationCategory::Updates,
+    NotificationCategory::System,
+];
+
+pub fn category_name(category: NotificationCategory) -> String {
+    i18n::t(match category {
+        NotificationCategory::Jobs => "notifications.jobs",
+        NotificationCategory::Alerts => "notifications.alerts",
+        NotificationCategory::Collaboration => "notifications.collaboration",
+        NotificationCategory::Updates => "notifications.updates",
+        NotificationCategory::System => "notifications.system",
+    })
+}
+
+pub fn level_color(level: NotificationLevel) -> Color {
+    match level {
+        NotificationLevel::Info => Color::Cyan,
+        NotificationLevel::Success => Color::Green,
+        NotificationLevel::Warning => Color::Yellow,
+        NotificationLevel::Error => Color::Red,
+    }
+}
+
+// "42s", "3m 07s", "1h 02m"
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub id: u64,
+    pub category: NotificationCategory,
+    pub level: NotificationLevel,
+    pub title: String,
+    pub body: Option<String>,
+    pub at: DateTime<Local>,
+}
+
+// Where a new notification should be shown besides the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Delivery {
+    pub toast: bool,
+    pub os: bool,
+}
+
+fn parse_clock(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+pub fn in_quiet_hours(hours: &QuietHours, now: NaiveTime) -> bool {
+    let (Some(start), Some(end)) = (parse_clock(&hours.start), parse_clock(&hours.end)) else {
+        return false;
+    };
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+pub struct NotificationCenter {
+    config: NotificationsConfig,
+    history: VecDeque<Notification>,
+    next_id: u64,
+    unread: usize,
+    // Set by the panel toggle; overrides the schedule until toggled back
+    dnd_override: Option<bool>,
+}
+
+impl NotificationCenter {
+    pub fn new(config: NotificationsConfig) -> Self {
+        Self {
+            config,
+            history: VecDeque::new(),
+            next_id: 1,
+            unread: 0,
+            dnd_override: None,
+        }
+    }
+
+    pub fn config(&self) -> &NotificationsConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: NotificationsConfig) {
+        self.config = config;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.history.len() > self.config.history_limit {
+            self.history.pop_front();
+        }
+        self.unread = self.unread.min(self.history.len());
+    }
+
+    pub fn is_quiet(&self, now: NaiveTime) -> bool {
+        self.dnd_override.unwrap_or_else(|| {
+            self.config
+                .do_not_disturb
+                .as_ref()
+                .is_some_and(|hours| in_quiet_hours(hours, now))
+        })
+    }
+
+    pub fn toggle_dnd(&mut self, now: NaiveTime) -> bool {
+        let quiet = !self.is_quiet(now);
+        self.dnd_override = Some(quiet);
+        quiet
+    }
+
+    pub fn notify(
+        &mut self,
+        category: NotificationCategory,
+        level: NotificationLevel,
+        title: String,
+        body: Option<String>,
+        now: DateTime<Local>,
+    ) -> (Notification, Delivery) {
+        let notification = Notification {
+            id: self.next_id,
+            category,
+            level,
+            title,
+            body,
+            at: now,
+        };
+        self.next_id += 1;
+        self.history.push_back(notification.clone());
+        self.unread += 1;
+        self.trim();
+
+        let wanted = self
+            .config
+            .min_level
+            .get(&category)
+            .is_none_or(|min| level >= *min);
+        let allowed = !self.is_quiet(now.time())
+            || (level == NotificationLevel::Error && self.config.errors_break_through);
+        let shown = wanted && allowed;
+        let delivery = Delivery {
+            toast: shown && self.config.toasts,
+            os: shown && self.config.os_notifications,
+        };
+        (notification, delivery)
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &Notification> {
+        self.history.iter().rev()
+    }
+
+    pub fn memory_bytes(&self) -> u64 {
+        self.history
+            .iter()
+            .map(|n| {
+                std::mem::size_of::<Notification>()
+                    + n.title.capacity()
+                    + n.body.as_ref().map_or(0, String::capacity)
+            })
+            .sum::<usize>() as u64
+    }
+
+    pub fn unread(&self) -> usize {
+        self.unread
+    }
+
+    pub fn mark_read(&mut self) {
+        self.unread = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.unread = 0;
+    }
+}
+
+pub fn send_os_notification(title: &str, body: Option<&str>) -> Result<(), WarpError> {
+    let body = body.unwrap_or_default();
+    let mut command = if cfg!(target_os = "macos") {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification \"{}\" with title \"{}\"",
+            escape(body),
+            escape(title)
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command
+            .arg("--app-name=warp")
+            .arg("--")
+            .arg(title)
+            .arg(body);
+        command
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationCommand {
+    None,
+    Redraw,
+    Clear,
+    ToggleDnd,
+}
+
+pub struct NotificationsPanel {
+    entries: Vec<Notification>,
+    filter: Option<NotificationCategory>,
+    quiet: bool,
+    selected: usize,
+}
+
+impl NotificationsPanel {
+    pub fn new(center: &NotificationCenter, now: NaiveTime) -> Self {
+        let mut panel = Self {
+            entries: Vec::new(),
+            filter: None,
+            quiet: false,
+            selected: 0,
+        };
+        panel.refresh(center, now);
+        panel
+    }
+
+    pub fn refresh(&mut self, center: &NotificationCenter, now: NaiveTime) {
+        self.entries = center.history().cloned().collect();
+        self.quiet = center.is_quiet(now);
+        self.select(self.selected);
+    }
+
+    fn visible(&self) -> Vec<&Notification> {
+        self.entries
+            .iter()
+            .filter(|entry| {
+                self.filter
+                    .is_none_or(|category| entry.category == category)
+            })
+            .collect()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.visible().len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> NotificationCommand {
+        self.select(index);
+        match key {
+            'f' => {
+                // None -> each category in turn -> None
+                self.filter = match self.filter {
+                    None => CATEGORIES.first().copied(),
+                    Some(current) => CATEGORIES
+                        .iter()
+                        .skip_while(|category| **category != current)
+                        .nth(1)
+                        .copied(),
+                };
+                self.selected = 0;
+                NotificationCommand::Redraw
+            }
+            'c' => NotificationCommand::Clear,
+            'z' => NotificationCommand::ToggleDnd,
+            '\n' => NotificationCommand::None,
+            _ => NotificationCommand::Redraw,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let visible = self.visible();
+        let mut items: Vec<PanelItem> = visible
+            .iter()
+            .map(|entry| {
+                PanelItem::styled(vec![
+                    (
+                        entry.at.format("%H:%M:%S ").to_string(),
+                        Some(Color::DarkGrey),
+                    ),
+                    (
+                        format!("{:<14}", category_name(entry.category)),
+                        Some(level_color(entry.level)),
+                    ),
+                    (entry.title.clone(), None),
+                ])
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.notifications_empty").into());
+        }
+        let preview = visible
+            .get(self.selected)
+            .and_then(|entry| entry.body.as_ref())
+            .map(|body| {
+                body.lines()
+                    .map(|line| PanelItem::styled(vec![(line.to_string(), Some(Color::DarkGrey))]))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let filter = self
+            .filter
+            .map(category_name)
+            .unwrap_or_else(|| i18n::t("notifications.all"));
+        let dnd = i18n::t(if self.quiet {
+            "notifications.dnd_on"
+        } else {
+            "notifications.dnd_off"
+        });
+        PanelView {
+            kind: PanelKind::Notifications,
+            title: i18n::t_args(
+                "panel.notifications",
+                &[
+                    ("count", &visible.len().to_string()),
+                    ("filter", &filter),
+                    ("dnd", &dnd),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.notifications_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn config() -> NotificationsConfig {
+        NotificationsConfig {
+            toasts: true,
+            os_notifications: true,
+            toast_secs: 5,
+            history_limit: 3,
+            min_level: HashMap::new(),
+            do_not_disturb: None,
+            errors_break_through: true,
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 3, 14, hour, minute, 0)
+            .single()
+            .unwrap()
+    }
+
+    fn clock(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_wrap_past_midnight() {
+        let night = QuietHours {
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+        };
+        assert!(in_quiet_hours(&night, clock(23, 30)));
+        assert!(in_quiet_hours(&night, clock(6, 59)));
+        assert!(!in_quiet_hours(&night, clock(7, 0)));
+        assert!(!in_quiet_hours(&night, clock(12, 0)));
+
+        let lunch = QuietHours {
+            start: "12:00".to_string(),
+            end: "13:00".to_string(),
+        };
+        assert!(in_quiet_hours(&lunch, clock(12, 15)));
+        assert!(!in_quiet_hours(&lunch, clock(13, 15)));
+
+        let broken = QuietHours {
+            start: "noon".to_string(),
+            end: "13:00".to_string(),
+        };
+        assert!(!in_quiet_hours(&broken, clock(12, 15)));
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_secs(42)), "42s");
+        assert_eq!(format_elapsed(Duration::from_secs(187)), "3m 07s");
+        assert_eq!(format_elapsed(Duration::from_secs(3720)), "1h 02m");
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut center = NotificationCenter::new(config());
+        for i in 0..5 {
+            center.notify(
+                NotificationCategory::Jobs,
+                NotificationLevel::Info,
+                format!("job {}", i),
+                None,
+                at(12, 0),
+            );
+        }
+        let titles: Vec<_> = center.history().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["job 4", "job 3", "job 2"]);
+        assert_eq!(center.unread(), 3);
+        center.mark_read();
+        assert_eq!(center.unread(), 0);
+    }
+
+    #[test]
+    fn test_min_level_keeps_history_only() {
+        let mut config = config();
+        config
+            .min_level
+            .insert(NotificationCategory::Updates, NotificationLevel::Warning);
+        let mut center = NotificationCenter::new(config);
+        let (_, delivery) = center.notify(
+            NotificationCategory::Updates,
+            NotificationLevel::Info,
+            "new version".to_string(),
+            None,
+            at(12, 0),
+        );
+        assert_eq!(delivery, Delivery::default());
+        assert_eq!(center.history().count(), 1);
+
+        let (_, delivery) = center.notify(
+            NotificationCategory::Jobs,
+            NotificationLevel::Info,
+            "build done".to_string(),
+            None,
+            at(12, 0),
+        );
+        assert!(delivery.toast && delivery.os);
+    }
+
+    #[test]
+    fn test_do_not_disturb_lets_errors_through() {
+        let mut config = config();
+        config.do_not_disturb = Some(QuietHours {
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+        });
+        let mut center = NotificationCenter::new(config);
+        let (_, delivery) = center.notify(
+            NotificationCategory::Jobs,
+            NotificationLevel::Success,
+            "build done".to_string(),
+            None,
+            at(23, 0),
+        );
+        assert!(!delivery.toast);
+        let (_, delivery) = center.notify(
+            NotificationCategory::Alerts,
+            NotificationLevel::Error,
+            "killed".to_string(),
+            None,
+            at(23, 0),
+        );
+        assert!(delivery.toast);
+
+        // The manual toggle wins over the schedule
+        assert!(!center.toggle_dnd(clock(23, 0)));
+        let (_, delivery) = center.notify(
+            NotificationCategory::Jobs,
+            NotificationLevel::Info,
+            "tests passed".to_string(),
+            None,
+            at(23, 0),
+        );
+        assert!(delivery.toast);
+    }
+
+    #[test]
+    fn test_panel_filter_cycles_categories() {
+        let mut center = NotificationCenter::new(config());
+        center.notify(
+            NotificationCategory::Jobs,
+            NotificationLevel::Info,
+            "job".to_string(),
+            None,
+            at(12, 0),
+        );
+        center.notify(
+            NotificationCategory::Alerts,
+            NotificationLevel::Warning,
+            "alert".to_string(),
+            Some("details".to_string()),
+            at(12, 1),
+        );
+        let mut panel = NotificationsPanel::new(&center, clock(12, 2));
+        assert_eq!(panel.visible().len(), 2);
+        assert_eq!(panel.handle_key('f', 0), NotificationCommand::Redraw);
+        assert_eq!(panel.visible()[0].title, "job");
+        panel.handle_key('f', 0);
+        assert_eq!(panel.visible()[0].title, "alert");
+        for _ in 0..CATEGORIES.len() - 1 {
+            panel.handle_key('f', 0);
+        }
+        assert_eq!(panel.visible().len(), 2);
+        assert_eq!(panel.handle_key('c', 0), NotificationCommand::Clear);
+        assert_eq!(panel.handle_key('z', 0), NotificationCommand::ToggleDnd);
+    }
+}
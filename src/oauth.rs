@@ -0,0 +1,387 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration as StdDuration;
+
+use crate::config::OAuthProviderConfig;
+use crate::error::WarpError;
+use crate::network;
+use crate::secrets::SecretsManager;
+
+// Tokens this close to expiry are refreshed before use
+const EXPIRY_SKEW_SECS: i64 = 60;
+// RFC 8628: the polling interval when the server doesn't give one, and the
+// amount added on each slow_down
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+const SLOW_DOWN_SECS: u64 = 5;
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+// Where a provider's tokens live in the secrets manager
+pub fn secret_name(provider: &str) -> String {
+    format!("oauth.{}", provider)
+}
+
+// What the user needs to approve the sign-in on another device
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    pub interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenSet {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub scope: Option<String>,
+}
+
+impl TokenSet {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at - Duration::seconds(EXPIRY_SKEW_SECS) <= now)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    scope: Option<String>,
+}
+
+impl TokenResponse {
+    fn into_tokens(self, now: DateTime<Utc>) -> TokenSet {
+        TokenSet {
+            access_token: self.access_token,
+            refresh_token: self.refresh_token,
+            expires_at: self.expires_in.map(|secs| now + Duration::seconds(secs)),
+            scope: self.scope,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+pub struct OAuthClient {
+    provider: OAuthProviderConfig,
+    http: reqwest::Client,
+}
+
+impl OAuthClient {
+    pub fn new(provider: OAuthProviderConfig) -> Result<Self, WarpError> {
+        Ok(Self {
+            provider,
+            http: network::client()?,
+        })
+    }
+
+    pub fn provider(&self) -> &OAuthProviderConfig {
+        &self.provider
+    }
+
+    pub async fn start(&self) -> Result<DeviceAuthorization, WarpError> {
+        let scope = self.provider.scopes.join(" ");
+        let response = self
+            .http
+            .post(network::endpoint(&self.provider.device_authorization_url))
+            .form(&[
+                ("client_id", self.provider.client_id.as_str()),
+                ("scope", &scope),
+            ])
+            .send()
+            .await
+            .map_err(|e| {
+                WarpError::network(format!("Could not reach {}", self.provider.name), e)
+            })?;
+        if !response.status().is_success() {
+            return Err(self.error_from(response).await);
+        }
+        Ok(response.json().await?)
+    }
+
+    // Polls until the user approves or denies the request, or it expires
+    pub async fn poll(&self, authorization: &DeviceAuthorization) -> Result<TokenSet, WarpError> {
+        let deadline = Utc::now() + Duration::seconds(authorization.expires_in as i64);
+        let mut interval = authorization.interval.unwrap_or(DEFAULT_INTERVAL_SECS);
+        loop {
+            tokio::time::sleep(StdDuration::from_secs(interval)).await;
+            let response = self
+                .token_request(&[
+                    ("grant_type", DEVICE_GRANT),
+                    ("device_code", &authorization.device_code),
+                    ("client_id", &self.provider.client_id),
+                ])
+                .await?;
+            match response {
+                Ok(tokens) => return Ok(tokens.into_tokens(Utc::now())),
+                Err(error) => match error.error.as_str() {
+                    "authorization_pending" => {}
+                    "slow_down" => interval += SLOW_DOWN_SECS,
+                    "access_denied" => {
+                        return Err(WarpError::Auth(format!(
+                            "Sign-in to {} was denied",
+                            self.provider.name
+                        )))
+                    }
+                    "expired_token" => return Err(self.expired()),
+                    _ => return Err(self.auth_error(error)),
+                },
+            }
+            if Utc::now() >= deadline {
+                return Err(self.expired());
+            }
+        }
+    }
+
+    // Servers that don't rotate refresh tokens omit one; the old one stays valid
+    pub async fn refresh(&self, tokens: &TokenSet) -> Result<TokenSet, WarpError> {
+        let refresh_token = tokens
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| self.sign_in_again())?;
+        let response = self
+            .token_request(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("client_id", &self.provider.client_id),
+            ])
+            .await?;
+        match response {
+            Ok(fresh) => {
+                let mut fresh = fresh.into_tokens(Utc::now());
+                if fresh.refresh_token.is_none() {
+                    fresh.refresh_token = tokens.refresh_token.clone();
+                }
+                Ok(fresh)
+            }
+            Err(error) if error.error == "invalid_grant" => Err(self.sign_in_again()),
+            Err(error) => Err(self.auth_error(error)),
+        }
+    }
+
+    // Ok(Err(..)) is an OAuth error response; transport failures are Err
+    async fn token_request(
+        &self,
+        form: &[(&str, &str)],
+    ) -> Result<Result<TokenResponse, ErrorResponse>, WarpError> {
+        let response = self
+            .http
+            .post(network::endpoint(&self.provider.token_url))
+            .form(form)
+            .send()
+            .await
+            .map_err(|e| {
+                WarpError::network(format!("Could not reach {}", self.provider.name), e)
+            })?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if status.is_success() {
+            return Ok(Ok(serde_json::from_str(&body)?));
+        }
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) => Ok(Err(error)),
+            Err(_) => Err(WarpError::api(Some(status.as_u16()), body)),
+        }
+    }
+
+    async fn error_from(&self, response: reqwest::Response) -> WarpError {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) => self.auth_error(error),
+            Err(_) => WarpError::api(Some(status), body),
+        }
+    }
+
+    fn auth_error(&self, error: ErrorResponse) -> WarpError {
+        WarpError::Auth(format!(
+            "{}: {}",
+            self.provider.name,
+            error.error_description.unwrap_or(error.error)
+        ))
+    }
+
+    fn expired(&self) -> WarpError {
+        WarpError::Auth(format!(
+            "The sign-in code for {} expired; run it again",
+            self.provider.name
+        ))
+    }
+
+    fn sign_in_again(&self) -> WarpError {
+        WarpError::Auth(format!(
+            "The {} session has ended; sign in again with `warp auth login {}`",
+            self.provider.name, self.provider.name
+        ))
+    }
+}
+
+// Runs the whole device flow and stores the tokens. `show` is called once
+// with the code and URL the user has to visit.
+pub async fn login(
+    secrets: &SecretsManager,
+    provider: &OAuthProviderConfig,
+    show: impl FnOnce(&DeviceAuthorization),
+) -> Result<TokenSet, WarpError> {
+    let client = OAuthClient::new(provider.clone())?;
+    let authorization = client.start().await?;
+    show(&authorization);
+    let tokens = client.poll(&authorization).await?;
+    store(secrets, &provider.name, &tokens).await?;
+    Ok(tokens)
+}
+
+pub async fn logout(secrets: &SecretsManager, provider: &str) -> Result<bool, WarpError> {
+    secrets.remove(&secret_name(provider)).await
+}
+
+pub async fn stored_tokens(
+    secrets: &SecretsManager,
+    provider: &str,
+) -> Result<Option<TokenSet>, WarpError> {
+    match secrets.get(&secret_name(provider)).await? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
+async fn store(
+    secrets: &SecretsManager,
+    provider: &str,
+    tokens: &TokenSet,
+) -> Result<(), WarpError> {
+    secrets
+        .set(&secret_name(provider), &serde_json::to_string(tokens)?)
+        .await
+}
+
+// A usable access token for the provider, refreshing and re-storing it
+// when it has expired. None when the user never signed in; a refresh the
+// server rejects clears the stored tokens so the next call says so.
+pub async fn access_token(
+    secrets: &SecretsManager,
+    provider: &OAuthProviderConfig,
+) -> Result<Option<String>, WarpError> {
+    let Some(tokens) = stored_tokens(secrets, &provider.name).await? else {
+        return Ok(None);
+    };
+    if !tokens.is_expired(Utc::now()) {
+        return Ok(Some(tokens.access_token));
+    }
+    let client = OAuthClient::new(provider.clone())?;
+    match client.refresh(&tokens).await {
+        Ok(fresh) => {
+            store(secrets, &provider.name, &fresh).await?;
+            Ok(Some(fresh.access_token))
+        }
+        Err(e @ WarpError::Auth(_)) => {
+            logout(secrets, &provider.name).await?;
+            Err(e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{
+        Cassette, Interaction, Mode, Playback, RecordedRequest, RecordedResponse,
+    };
+
+    fn provider() -> OAuthProviderConfig {
+        OAuthProviderConfig {
+            name: "example".to_string(),
+            client_id: "warp-cli".to_string(),
+            device_authorization_url: "https://auth.example.com/device/code".to_string(),
+            token_url: "https://auth.example.com/token".to_string(),
+            scopes: vec!["read".to_string(), "publish".to_string()],
+        }
+    }
+
+    fn exchange(url: &str, body: &str, status: u16, response: &str) -> Interaction {
+        Interaction {
+            request: RecordedRequest {
+                method: "POST".to_string(),
+                url: url.to_string(),
+                body: Some(body.to_string()),
+            },
+            response: RecordedResponse {
+                status,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: response.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_expiry_allows_for_skew() {
+        let now = Utc::now();
+        let mut tokens = TokenSet {
+            access_token: "a".to_string(),
+            refresh_token: None,
+            expires_at: Some(now + Duration::seconds(30)),
+            scope: None,
+        };
+        assert!(tokens.is_expired(now));
+        tokens.expires_at = Some(now + Duration::seconds(600));
+        assert!(!tokens.is_expired(now));
+        tokens.expires_at = None;
+        assert!(!tokens.is_expired(now));
+    }
+
+    #[tokio::test]
+    async fn test_device_flow_and_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oauth.json");
+        let token = "https://auth.example.com/token";
+        let poll = "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code&device_code=dev-1&client_id=warp-cli";
+        Cassette {
+            interactions: vec![
+                exchange(
+                    "https://auth.example.com/device/code",
+                    "client_id=warp-cli&scope=read+publish",
+                    200,
+                    r#"{"device_code":"dev-1","user_code":"WDJB-MJHT","verification_uri":"https://example.com/device","expires_in":600,"interval":0}"#,
+                ),
+                exchange(token, poll, 400, r#"{"error":"authorization_pending"}"#),
+                exchange(token, poll, 200, r#"{"access_token":"at-1","refresh_token":"rt-1","expires_in":0}"#),
+                exchange(
+                    token,
+                    "grant_type=refresh_token&refresh_token=rt-1&client_id=warp-cli",
+                    200,
+                    r#"{"access_token":"at-2","expires_in":3600}"#,
+                ),
+            ],
+        }
+        .save(&path)
+        .unwrap();
+
+        let playback = Playback::with_mode(&path, Mode::Replay).await.unwrap();
+        let secrets = SecretsManager::open(dir.path()).await.unwrap();
+        let mut shown = None;
+        let tokens = login(&secrets, &provider(), |authorization| {
+            shown = Some(authorization.user_code.clone())
+        })
+        .await
+        .unwrap();
+        assert_eq!(shown.as_deref(), Some("WDJB-MJHT"));
+        assert_eq!(tokens.access_token, "at-1");
+
+        // Stored already expired, so this refreshes and keeps the refresh token
+        let access = access_token(&secrets, &provider()).await.unwrap();
+        assert_eq!(access.as_deref(), Some("at-2"));
+        let stored = stored_tokens(&secrets, "example").await.unwrap().unwrap();
+        assert_eq!(stored.refresh_token.as_deref(), Some("rt-1"));
+        playback.finish().unwrap();
+    }
+}
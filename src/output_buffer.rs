@@ -0,0 +1,952 @@
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::config::PagingConfig;
+use crate::i18n;
+use crate::permalinks;
+use crate::process_tree::format_bytes;
+
+// How older scrollback is kept. Past hot_lines plus a page, the oldest
+// page_lines lines are compressed together; compressed pages over the
+// budget go to disk, oldest first, or are dropped without spill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PagingLimits {
+    pub hot_lines: usize,
+    pub page_lines: usize,
+    pub memory_budget: usize, // bytes
+    pub spill: bool,
+}
+
+impl PagingLimits {
+    pub fn from_config(config: &PagingConfig) -> Option<Self> {
+        config.enabled.then_some(Self {
+            hot_lines: config.hot_lines,
+            page_lines: config.page_lines,
+            memory_budget: (config.memory_budget_mb * 1024 * 1024) as usize,
+            spill: config.spill_to_disk,
+        })
+    }
+}
+
+enum PageData {
+    Memory(Vec<u8>),
+    // A file per page, so the space goes with the page
+    Disk(PathBuf),
+}
+
+// Lines compressed together, each as its length (u32) and its bytes
+struct Page {
+    lines: usize,
+    data: PageData,
+}
+
+impl Page {
+    // Moves the page's data to a temp file. Returns the bytes freed.
+    fn spill(&mut self) -> std::io::Result<usize> {
+        let PageData::Memory(data) = &self.data else {
+            return Ok(0);
+        };
+        let path =
+            std::env::temp_dir().join(format!("warp-scrollback-{}.page", uuid::Uuid::new_v4()));
+        let written = create_private(&path).and_then(|mut file| file.write_all(data));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+        let freed = data.len();
+        self.data = PageData::Disk(path);
+        Ok(freed)
+    }
+
+    fn read(&self) -> std::io::Result<Vec<String>> {
+        match &self.data {
+            PageData::Memory(data) => decompress(data),
+            PageData::Disk(path) => decompress(&std::fs::read(path)?),
+        }
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        if let PageData::Disk(path) = &self.data {
+            if let Err(e) = std::fs::remove_file(path) {
+                log::debug!("Could not remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn compress<'a>(lines: impl Iterator<Item = &'a String>) -> std::io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    for line in lines {
+        encoder.write_all(&(line.len() as u32).to_le_bytes())?;
+        encoder.write_all(line.as_bytes())?;
+    }
+    encoder.finish()
+}
+
+fn decompress(data: &[u8]) -> std::io::Result<Vec<String>> {
+    let invalid = || std::io::Error::from(std::io::ErrorKind::InvalidData);
+    let mut bytes = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut bytes)?;
+    let mut lines = Vec::new();
+    let mut rest = bytes.as_slice();
+    while let Some((len, tail)) = rest.split_first_chunk::<4>() {
+        let len = u32::from_le_bytes(*len) as usize;
+        let line = tail.get(..len).ok_or_else(invalid)?;
+        lines.push(String::from_utf8(line.to_vec()).map_err(|_| invalid())?);
+        rest = &tail[len..];
+    }
+    Ok(lines)
+}
+
+// Output can contain anything the command printed, secrets included, so
+// only the user may read it
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+// How much of one block is kept in memory. Past head + tail lines, the
+// oldest tail line moves to a spill file, so a block that prints gigabytes
+// keeps its start and end on screen and the rest on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruncationLimits {
+    pub head_lines: usize,
+    pub tail_lines: usize,
+    pub max_line_bytes: usize, // longer lines are split
+}
+
+// The hidden middle of the current block
+struct Spill {
+    path: PathBuf,
+    // None if the file couldn't be created; lines are then only counted
+    file: Option<BufWriter<File>>,
+    lines: u64,
+    bytes: u64,
+}
+
+impl Spill {
+    fn create() -> Self {
+        let path = std::env::temp_dir().join(format!("warp-output-{}.log", uuid::Uuid::new_v4()));
+        let file = match create_private(&path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(e) => {
+                log::warn!("Could not create {}: {}", path.display(), e);
+                None
+            }
+        };
+        Self {
+            path,
+            file,
+            lines: 0,
+            bytes: 0,
+        }
+    }
+
+    fn write(&mut self, line: &str) {
+        self.lines += 1;
+        self.bytes += line.len() as u64 + 1;
+        if let Some(file) = self.file.as_mut() {
+            if let Err(e) = writeln!(file, "{}", line) {
+                log::warn!("Could not write {}: {}", self.path.display(), e);
+                self.file = None;
+            }
+        }
+    }
+
+    fn banner(&self) -> String {
+        let args = [
+            ("count", self.lines.to_string()),
+            ("size", format_bytes(self.bytes)),
+            ("path", self.path.display().to_string()),
+        ];
+        let args: Vec<(&str, &str)> = args.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        match self.file {
+            Some(_) => i18n::t_args("output.truncated", &args),
+            None => i18n::t_args("output.truncated_dropped", &args),
+        }
+    }
+}
+
+// Line-oriented scrollback fed directly from PTY reads. PTY chunks are cut at
+// arbitrary byte boundaries, so a trailing partial line is held back until
+// its newline arrives instead of being rendered as a separate line.
+// With paging, only the newest lines stay as they are and older ones sit in
+// compressed pages in front of them.
+pub struct OutputBuffer {
+    cold: VecDeque<Page>,
+    cold_lines: usize,
+    cold_memory: usize, // compressed bytes of the pages in memory
+    paging: Option<PagingLimits>,
+    lines: VecDeque<String>,
+    partial: String,
+    max_lines: usize,
+    dirty: bool,
+    total_bytes: u64,
+    // Lines ever pushed, and the count when the current command's block
+    // started; the difference survives lines being dropped from the front
+    pushed: u64,
+    block_start: u64,
+    // Blocks with lines still in the scrollback: their permalink id and the
+    // push count where each started
+    blocks: VecDeque<(String, u64)>,
+    truncation: Option<TruncationLimits>,
+    // While the current block is spilling: its hidden middle, a line
+    // describing it, and its last lines, shown after the banner
+    spill: Option<Spill>,
+    banner: String,
+    tail: VecDeque<String>,
+    // Spill files of earlier blocks, removed with the buffer
+    spill_files: Vec<PathBuf>,
+}
+
+impl OutputBuffer {
+    pub fn new(max_lines: usize) -> Self {
+        Self {
+            cold: VecDeque::new(),
+            cold_lines: 0,
+            cold_memory: 0,
+            paging: None,
+            lines: VecDeque::with_capacity(max_lines.min(4096)),
+            partial: String::new(),
+            max_lines: max_lines.max(1),
+            dirty: false,
+            total_bytes: 0,
+            pushed: 0,
+            block_start: 0,
+            blocks: VecDeque::new(),
+            truncation: None,
+            spill: None,
+            banner: String::new(),
+            tail: VecDeque::new(),
+            spill_files: Vec::new(),
+        }
+    }
+
+    // Head and tail both have to fit in the scrollback
+    pub fn set_truncation(&mut self, limits: Option<TruncationLimits>) {
+        self.truncation = limits.map(|limits| {
+            let tail_lines = limits.tail_lines.clamp(1, self.max_lines);
+            TruncationLimits {
+                head_lines: limits.head_lines.min(self.max_lines - tail_lines),
+                tail_lines,
+                max_line_bytes: limits.max_line_bytes.max(1),
+            }
+        });
+    }
+
+    pub fn set_paging(&mut self, limits: Option<PagingLimits>) {
+        self.paging = limits.map(|limits| PagingLimits {
+            hot_lines: limits.hot_lines.max(1),
+            page_lines: limits.page_lines.max(1),
+            ..limits
+        });
+        self.page_out();
+    }
+
+    pub fn push_chunk(&mut self, chunk: &str) {
+        if chunk.is_empty() {
+            return;
+        }
+        self.total_bytes += chunk.len() as u64;
+        self.dirty = true;
+
+        let mut rest = chunk;
+        while let Some(pos) = rest.find('\n') {
+            let (line, tail) = rest.split_at(pos);
+            // The \r of a \r\n may have arrived with the previous chunk
+            let mut full = std::mem::take(&mut self.partial);
+            full.push_str(line);
+            if full.ends_with('\r') {
+                full.pop();
+            }
+            self.push_split(full);
+            rest = &tail[1..];
+        }
+        self.partial.push_str(rest);
+
+        // A line that never ends (a progress bar without \r, binary noise)
+        // is broken up rather than allowed to grow without bound
+        if let Some(limits) = self.truncation {
+            while self.partial.len() > limits.max_line_bytes {
+                let at = floor_char_boundary(&self.partial, limits.max_line_bytes);
+                let rest = self.partial.split_off(at);
+                let line = std::mem::replace(&mut self.partial, rest);
+                self.push_line(line);
+            }
+        }
+    }
+
+    fn push_split(&mut self, mut line: String) {
+        if let Some(limits) = self.truncation {
+            while line.len() > limits.max_line_bytes {
+                let at = floor_char_boundary(&line, limits.max_line_bytes);
+                let rest = line.split_off(at);
+                self.push_line(std::mem::replace(&mut line, rest));
+            }
+        }
+        self.push_line(line);
+    }
+
+    pub fn push_line(&mut self, line: String) {
+        self.dirty = true;
+        if let Some(spill) = self.spill.as_mut() {
+            self.tail.push_back(line);
+            if self.tail.len() > self.truncation.map_or(0, |limits| limits.tail_lines) {
+                if let Some(oldest) = self.tail.pop_front() {
+                    spill.write(&oldest);
+                }
+            }
+            return;
+        }
+        if let Some(limits) = self.truncation {
+            if (self.pushed - self.block_start) as usize >= limits.head_lines + limits.tail_lines {
+                self.start_spill(limits);
+                return self.push_line(line);
+            }
+        }
+        self.store(line);
+    }
+
+    fn store(&mut self, line: String) {
+        if self.stored() >= self.max_lines {
+            self.drop_oldest();
+        }
+        self.lines.push_back(line);
+        self.pushed += 1;
+        self.page_out();
+    }
+
+    // Lines in the scrollback, paged or not
+    fn stored(&self) -> usize {
+        self.cold_lines + self.lines.len()
+    }
+
+    // A whole page at a time once there are pages
+    fn drop_oldest(&mut self) {
+        let Some(page) = self.cold.pop_front() else {
+            self.lines.pop_front();
+            return;
+        };
+        self.cold_lines -= page.lines;
+        if let PageData::Memory(data) = &page.data {
+            self.cold_memory -= data.len();
+        }
+    }
+
+    fn page_out(&mut self) {
+        let Some(limits) = self.paging else {
+            return;
+        };
+        while self.lines.len() >= limits.hot_lines + limits.page_lines {
+            let data = match compress(self.lines.range(..limits.page_lines)) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!("Could not compress scrollback: {}", e);
+                    return;
+                }
+            };
+            self.lines.drain(..limits.page_lines);
+            self.cold_lines += limits.page_lines;
+            self.cold_memory += data.len();
+            self.cold.push_back(Page {
+                lines: limits.page_lines,
+                data: PageData::Memory(data),
+            });
+        }
+        // Pages on disk are always older than those in memory
+        while self.cold_memory > limits.memory_budget {
+            let Some(index) = self
+                .cold
+                .iter()
+                .position(|page| matches!(page.data, PageData::Memory(_)))
+            else {
+                break;
+            };
+            if !self.paging.is_some_and(|paging| paging.spill) {
+                self.drop_oldest();
+                continue;
+            }
+            match self.cold[index].spill() {
+                Ok(freed) => self.cold_memory -= freed,
+                Err(e) => {
+                    // Dropped from now on rather than retried for every page
+                    log::warn!("Could not move scrollback to disk: {}", e);
+                    if let Some(paging) = self.paging.as_mut() {
+                        paging.spill = false;
+                    }
+                }
+            }
+        }
+    }
+
+    // A page's lines; a page that can't be read back still takes up its
+    // lines so positions in the scrollback stay right
+    fn read_page(page: &Page) -> Vec<String> {
+        match page.read() {
+            Ok(lines) if lines.len() == page.lines => lines,
+            result => {
+                if let Err(e) = result {
+                    log::warn!("Could not read a scrollback page: {}", e);
+                }
+                std::iter::once(i18n::t("output.page_unreadable"))
+                    .chain(std::iter::repeat(String::new()))
+                    .take(page.lines)
+                    .collect()
+            }
+        }
+    }
+
+    // Stored lines from `start` on; only the pages they reach are
+    // decompressed
+    fn stored_from(&self, start: usize) -> impl Iterator<Item = Cow<'_, str>> {
+        let mut page_start = 0;
+        let pages = self.cold.iter().filter_map(move |page| {
+            let skip = start.saturating_sub(page_start);
+            page_start += page.lines;
+            (skip < page.lines).then_some((page, skip))
+        });
+        pages
+            .flat_map(|(page, skip)| Self::read_page(page).into_iter().skip(skip).map(Cow::Owned))
+            .chain(
+                self.lines
+                    .iter()
+                    .skip(start.saturating_sub(self.cold_lines))
+                    .map(|line| Cow::Borrowed(line.as_str())),
+            )
+    }
+
+    // Everything after the block's head moves to the tail, which from now on
+    // keeps the latest lines
+    fn start_spill(&mut self, limits: TruncationLimits) {
+        for _ in 0..limits.tail_lines {
+            match self.lines.pop_back() {
+                Some(line) => self.tail.push_front(line),
+                None => break,
+            }
+        }
+        self.pushed -= self.tail.len() as u64;
+        let spill = Spill::create();
+        self.banner = spill.banner();
+        self.spill = Some(spill);
+    }
+
+    // The block's banner and tail become ordinary scrollback
+    fn finish_spill(&mut self) {
+        let Some(mut spill) = self.spill.take() else {
+            return;
+        };
+        if let Some(file) = spill.file.as_mut() {
+            let _ = file.flush();
+            self.spill_files.push(spill.path.clone());
+        }
+        let banner = spill.banner();
+        for line in std::iter::once(banner).chain(std::mem::take(&mut self.tail)) {
+            self.store(line);
+        }
+    }
+
+    // Whether the current block has lines on disk only
+    pub fn is_truncated(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    // The newest spill file, flushed so it can be read. It holds only the
+    // lines missing from the screen.
+    pub fn hidden_output(&mut self) -> Option<PathBuf> {
+        if let Some(spill) = self.spill.as_mut() {
+            if let Some(file) = spill.file.as_mut() {
+                let _ = file.flush();
+                return Some(spill.path.clone());
+            }
+        }
+        self.spill_files.last().cloned()
+    }
+
+    // The current block's banner and tail, and the partial line
+    fn live(&self) -> impl Iterator<Item = &str> {
+        let banner = self.spill.as_ref().map(|_| self.banner.as_str());
+        let partial = if self.partial.is_empty() {
+            None
+        } else {
+            Some(self.partial.as_str())
+        };
+        banner
+            .into_iter()
+            .chain(self.tail.iter().map(|s| s.as_str()))
+            .chain(partial)
+    }
+
+    fn live_len(&self) -> usize {
+        self.spill.is_some() as usize + self.tail.len() + !self.partial.is_empty() as usize
+    }
+
+    // Output from here on belongs to a new block. A pending partial line
+    // (usually the prompt) stays with the previous one. Returns the block's
+    // permalink id.
+    pub fn start_block(&mut self) -> String {
+        self.finish_spill();
+        if !self.partial.is_empty() {
+            let partial = std::mem::take(&mut self.partial);
+            self.push_line(partial);
+        }
+        self.block_start = self.pushed;
+
+        // Blocks whose last line has scrolled out are forgotten
+        let first = self.pushed - self.stored() as u64;
+        while self.blocks.len() > 1 && self.blocks[1].1 <= first {
+            self.blocks.pop_front();
+        }
+        let id = permalinks::new_block_id();
+        self.blocks.push_back((id.clone(), self.block_start));
+        id
+    }
+
+    pub fn current_block_id(&self) -> Option<&str> {
+        self.blocks.back().map(|(id, _)| id.as_str())
+    }
+
+    // The block before the current one, if it's still in the scrollback
+    pub fn previous_block_id(&self) -> Option<&str> {
+        let index = self.blocks.len().checked_sub(2)?;
+        self.blocks.get(index).map(|(id, _)| id.as_str())
+    }
+
+    // `back` blocks before the current one (0 is the current block)
+    pub fn recent_block_id(&self, back: usize) -> Option<&str> {
+        self.blocks
+            .iter()
+            .rev()
+            .nth(back)
+            .map(|(id, _)| id.as_str())
+    }
+
+    // Where a block's lines are in lines(); None once it has scrolled out.
+    // A block partly scrolled out starts at the first line.
+    pub fn block_range(&self, id: &str) -> Option<Range<usize>> {
+        let index = self.blocks.iter().position(|(block, _)| block == id)?;
+        let first = self.pushed - self.stored() as u64;
+        let start = self.blocks[index].1;
+        let end = match self.blocks.get(index + 1) {
+            Some((_, next)) => *next,
+            None => self.pushed + self.spill.is_some() as u64 + self.tail.len() as u64,
+        };
+        if start < first && end <= first {
+            return None;
+        }
+        Some((start.max(first) - first) as usize..(end - first) as usize)
+    }
+
+    // Lines of the current block still held in the buffer, including the
+    // in-progress partial line
+    pub fn current_block(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        let in_block = (self.pushed - self.block_start) as usize;
+        let skip = self.stored().saturating_sub(in_block);
+        self.stored_from(skip).chain(self.live().map(Cow::Borrowed))
+    }
+
+    pub fn block_len(&self) -> usize {
+        (self.pushed - self.block_start) as usize + self.live_len()
+    }
+
+    // The last `height` lines, including the in-progress partial line. Only
+    // lines that aren't paged out are drawn, which are more than a screen.
+    pub fn visible(&self, height: usize) -> impl Iterator<Item = &str> {
+        let skip = (self.lines.len() + self.live_len()).saturating_sub(height);
+        self.lines
+            .iter()
+            .map(|s| s.as_str())
+            .chain(self.live())
+            .skip(skip)
+    }
+
+    // All of the scrollback except the partial line, paged-out lines
+    // decompressed as the iterator reaches them
+    pub fn lines(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        let banner = self.spill.as_ref().map(|_| self.banner.as_str());
+        self.stored_from(0)
+            .chain(banner.map(Cow::Borrowed))
+            .chain(self.tail.iter().map(|s| Cow::Borrowed(s.as_str())))
+    }
+
+    pub fn len(&self) -> usize {
+        self.stored()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stored() == 0 && self.partial.is_empty()
+    }
+
+    pub fn set_max_lines(&mut self, max_lines: usize) {
+        self.max_lines = max_lines.max(1);
+        while self.stored() > self.max_lines {
+            self.drop_oldest();
+        }
+        self.set_truncation(self.truncation);
+    }
+
+    // Called once per frame, which is also when the banner's counts are
+    // brought up to date
+    pub fn take_dirty(&mut self) -> bool {
+        if self.dirty {
+            if let Some(spill) = &self.spill {
+                self.banner = spill.banner();
+            }
+        }
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    // Heap held by the scrollback (compressed pages in memory included), and
+    // by the current block beyond it (the tail kept while spilling, the banner
+    // and the partial line)
+    pub fn memory_bytes(&self) -> (u64, u64) {
+        let strings = |lines: &VecDeque<String>| {
+            (lines.capacity() * std::mem::size_of::<String>()
+                + lines.iter().map(String::capacity).sum::<usize>()) as u64
+        };
+        let pages = (self.cold.capacity() * std::mem::size_of::<Page>() + self.cold_memory) as u64;
+        let block = strings(&self.tail) + (self.banner.capacity() + self.partial.capacity()) as u64;
+        (strings(&self.lines) + pages, block)
+    }
+
+    pub fn clear(&mut self) {
+        self.finish_spill();
+        self.cold.clear();
+        self.cold_lines = 0;
+        self.cold_memory = 0;
+        self.lines.clear();
+        self.partial.clear();
+        self.block_start = self.pushed;
+        self.blocks.clear();
+        self.dirty = true;
+    }
+}
+
+impl Drop for OutputBuffer {
+    fn drop(&mut self) {
+        // Closes the current spill file before it is removed
+        self.finish_spill();
+        for path in &self.spill_files {
+            if let Err(e) = std::fs::remove_file(path) {
+                log::debug!("Could not remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+// str::floor_char_boundary is unstable
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    // Always make progress, even if the first character is wider than the limit
+    if index == 0 {
+        text.chars().next().map_or(0, char::len_utf8)
+    } else {
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_bench::chunk_str;
+    use crate::utf8::Utf8Decoder;
+    use proptest::prelude::*;
+
+    // Captured output of real tools: colours, progress bars redrawn with \r,
+    // alternate-screen apps, OSC titles, hyperlinks and clipboard writes,
+    // DCS queries, and binary noise. Shared with the pty_output fuzz target.
+    fn corpus() -> Vec<(String, Vec<u8>)> {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/pty_output");
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, std::fs::read(path).unwrap())
+            })
+            .collect()
+    }
+
+    fn lines(buffer: &OutputBuffer) -> Vec<String> {
+        buffer.visible(usize::MAX).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_corpus_reads_the_same_at_any_read_size() {
+        for (name, bytes) in corpus() {
+            let mut expected = None;
+            for size in [1, 2, 3, 7, 64, 4096] {
+                let mut decoder = Utf8Decoder::new();
+                let mut buffer = OutputBuffer::new(1000);
+                for chunk in bytes.chunks(size) {
+                    buffer.push_chunk(&decoder.decode(chunk));
+                }
+                buffer.push_chunk(&decoder.finish());
+                let got = lines(&buffer);
+                match &expected {
+                    None => expected = Some(got),
+                    Some(expected) => {
+                        assert_eq!(&got, expected, "{} read {} bytes at a time", name, size)
+                    }
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_chunking_does_not_change_lines(
+            text in "[a-z \\r\\n\\t\\x1b\\[;0-9mé😀]{0,300}",
+            size in 1usize..16,
+        ) {
+            let mut whole = OutputBuffer::new(50);
+            whole.push_chunk(&text);
+            let mut pieces = OutputBuffer::new(50);
+            for chunk in chunk_str(&text, size) {
+                pieces.push_chunk(chunk);
+            }
+            prop_assert_eq!(lines(&whole), lines(&pieces));
+            prop_assert!(whole.len() <= 50);
+        }
+
+        #[test]
+        fn prop_split_lines_respect_the_limit(text in "\\PC{0,400}", size in 1usize..64) {
+            let mut buffer = OutputBuffer::new(5000);
+            buffer.set_truncation(Some(TruncationLimits {
+                head_lines: 2000,
+                tail_lines: 2000,
+                max_line_bytes: 8,
+            }));
+            for chunk in chunk_str(&text, size) {
+                buffer.push_chunk(chunk);
+            }
+            for line in lines(&buffer) {
+                prop_assert!(line.len() <= 8, "{:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_bytes_follow_the_scrollback() {
+        let mut buffer = OutputBuffer::new(10);
+        let (empty, _) = buffer.memory_bytes();
+        buffer.push_chunk(&"x".repeat(1000));
+        buffer.push_chunk("\n");
+        let (scrollback, block) = buffer.memory_bytes();
+        assert!(scrollback >= empty + 1000);
+        assert_eq!(block, 0);
+        buffer.push_chunk("partial");
+        assert!(buffer.memory_bytes().1 >= 7);
+    }
+
+    #[test]
+    fn test_partial_lines_are_joined_across_chunks() {
+        let mut buffer = OutputBuffer::new(10);
+        buffer.push_chunk("hel");
+        buffer.push_chunk("lo\r\nwor");
+        buffer.push_chunk("ld\r");
+        buffer.push_chunk("\n");
+        assert_eq!(buffer.lines().collect::<Vec<_>>(), vec!["hello", "world"]);
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest_lines() {
+        let mut buffer = OutputBuffer::new(2);
+        buffer.push_chunk("a\nb\nc\n");
+        assert_eq!(buffer.lines().collect::<Vec<_>>(), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_current_block_survives_dropped_lines() {
+        let mut buffer = OutputBuffer::new(3);
+        buffer.push_chunk("old\n$ ");
+        buffer.start_block();
+        buffer.push_chunk("a\nb\nc\nd");
+        assert_eq!(buffer.block_len(), 4);
+        assert_eq!(
+            buffer.current_block().collect::<Vec<_>>(),
+            vec!["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn test_block_ranges_follow_the_scrollback() {
+        let mut buffer = OutputBuffer::new(4);
+        let first = buffer.start_block();
+        buffer.push_chunk("a\nb\n");
+        let second = buffer.start_block();
+        buffer.push_chunk("c\n");
+        assert_eq!(buffer.current_block_id(), Some(second.as_str()));
+        assert_eq!(buffer.previous_block_id(), Some(first.as_str()));
+        assert_eq!(buffer.block_range(&first), Some(0..2));
+        assert_eq!(buffer.block_range(&second), Some(2..3));
+
+        buffer.push_chunk("d\ne\n");
+        assert_eq!(buffer.block_range(&first), Some(0..1));
+        buffer.push_chunk("f\n");
+        assert_eq!(buffer.block_range(&first), None);
+        assert_eq!(buffer.block_range(&second), Some(0..4));
+        assert_eq!(buffer.block_range("missing"), None);
+    }
+
+    #[test]
+    fn test_block_range_covers_a_spilling_block() {
+        let mut buffer = truncating(100);
+        let id = buffer.start_block();
+        for i in 0..20 {
+            buffer.push_line(i.to_string());
+        }
+        assert!(buffer.is_truncated());
+        assert_eq!(buffer.block_range(&id), Some(0..buffer.lines().count()));
+    }
+
+    fn truncating(max_lines: usize) -> OutputBuffer {
+        let mut buffer = OutputBuffer::new(max_lines);
+        buffer.set_truncation(Some(TruncationLimits {
+            head_lines: 2,
+            tail_lines: 3,
+            max_line_bytes: 8,
+        }));
+        buffer
+    }
+
+    #[test]
+    fn test_truncation_keeps_head_and_tail() {
+        let mut buffer = truncating(20);
+        buffer.push_chunk("$ \n");
+        buffer.start_block();
+        let output: String = (1..=10).map(|n| format!("{}\n", n)).collect();
+        buffer.push_chunk(&output);
+        assert!(buffer.is_truncated());
+        buffer.take_dirty();
+
+        let block: Vec<Cow<str>> = buffer.current_block().collect();
+        assert_eq!(block.len(), 6);
+        assert_eq!(&block[..2], ["1", "2"]);
+        assert!(block[2].contains('5'));
+        assert_eq!(&block[3..], ["8", "9", "10"]);
+        assert_eq!(buffer.visible(2).collect::<Vec<_>>(), vec!["9", "10"]);
+
+        let path = buffer.hidden_output().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "3\n4\n5\n6\n7\n");
+
+        // The next block starts clean; the banner stays in the scrollback
+        buffer.start_block();
+        buffer.push_chunk("done\n");
+        assert!(!buffer.is_truncated());
+        assert_eq!(buffer.current_block().collect::<Vec<_>>(), vec!["done"]);
+        assert_eq!(buffer.lines().count(), 8);
+        drop(buffer);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_long_lines_are_split() {
+        let mut buffer = truncating(20);
+        buffer.push_chunk("abcdefghijkl\nxyzäöüxyzä");
+        assert_eq!(
+            buffer.lines().collect::<Vec<_>>(),
+            vec!["abcdefgh", "ijkl", "xyzäö"]
+        );
+        assert_eq!(buffer.visible(1).collect::<Vec<_>>(), vec!["üxyzä"]);
+    }
+
+    fn paging(memory_budget: usize, spill: bool) -> OutputBuffer {
+        let mut buffer = OutputBuffer::new(20);
+        buffer.set_paging(Some(PagingLimits {
+            hot_lines: 4,
+            page_lines: 3,
+            memory_budget,
+            spill,
+        }));
+        buffer
+    }
+
+    fn numbers(range: std::ops::RangeInclusive<u32>) -> String {
+        range.map(|n| format!("{}\n", n)).collect()
+    }
+
+    #[test]
+    fn test_paged_out_lines_read_back() {
+        let mut buffer = paging(usize::MAX, true);
+        let output = numbers(1..=13);
+        buffer.push_chunk(&output);
+        assert!(!buffer.cold.is_empty());
+        assert_eq!(buffer.len(), 13);
+        assert_eq!(
+            buffer.lines().collect::<Vec<_>>(),
+            output.lines().collect::<Vec<_>>()
+        );
+        assert_eq!(buffer.visible(2).collect::<Vec<_>>(), vec!["12", "13"]);
+
+        // Past the scrollback a whole page goes
+        buffer.push_chunk(&numbers(14..=21));
+        assert!(buffer.len() <= 20);
+        let lines: Vec<_> = buffer.lines().collect();
+        assert_eq!(lines.first().unwrap(), &(22 - buffer.len()).to_string());
+        assert_eq!(lines.last().unwrap(), "21");
+    }
+
+    #[test]
+    fn test_pages_over_budget_spill_or_drop() {
+        let mut buffer = paging(0, true);
+        let output = numbers(1..=12);
+        buffer.push_chunk(&output);
+        assert_eq!(buffer.cold_memory, 0);
+        let paths: Vec<PathBuf> = buffer
+            .cold
+            .iter()
+            .filter_map(|page| match &page.data {
+                PageData::Disk(path) => Some(path.clone()),
+                PageData::Memory(_) => None,
+            })
+            .collect();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            buffer.lines().collect::<Vec<_>>(),
+            output.lines().collect::<Vec<_>>()
+        );
+        // Scrolling out takes a page's file with it
+        buffer.push_chunk(&numbers(13..=24));
+        assert!(!paths[0].exists());
+        drop(buffer);
+        assert!(!paths[1].exists());
+
+        let mut buffer = paging(0, false);
+        buffer.push_chunk(&output);
+        assert!(buffer.cold.is_empty());
+        assert_eq!(
+            buffer.lines().collect::<Vec<_>>(),
+            vec!["7", "8", "9", "10", "11", "12"]
+        );
+    }
+
+    #[test]
+    fn test_visible_includes_partial_line() {
+        let mut buffer = OutputBuffer::new(10);
+        buffer.push_chunk("a\nb\nprompt$ ");
+        assert_eq!(buffer.visible(2).collect::<Vec<_>>(), vec!["b", "prompt$ "]);
+    }
+}
@@ -0,0 +1,469 @@
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config::OAuthProviderConfig;
+use crate::error::WarpError;
+use crate::network;
+use crate::oauth;
+use crate::secrets::SecretsManager;
+
+pub const MANIFEST: &str = "warp-package.toml";
+// Written into new manifests; see dev_tools::validator for the schemas
+pub const MANIFEST_SCHEMA_VERSION: u32 = 1;
+// Same API as marketplace::client
+const MARKETPLACE_API: &str = "https://marketplace.warp.dev/api/v1";
+const TOKEN_SECRET: &str = "marketplace.token";
+const SIGNING_KEY_SECRET: &str = "marketplace.signing_key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageKind {
+    Theme,
+    Plugin,
+    Workflow,
+}
+
+impl PackageKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "theme" => Some(Self::Theme),
+            "plugin" => Some(Self::Plugin),
+            "workflow" => Some(Self::Workflow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32, // manifests from before versioning are version 1
+    pub id: String, // publisher/name
+    pub name: String,
+    pub kind: PackageKind,
+    pub version: String,
+    pub description: String,
+    pub author: String,
+    pub license: String,
+    pub min_warp_version: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub files: Vec<String>, // relative to the manifest; directories are bundled whole
+    #[serde(default)]
+    pub entry_point: Option<String>, // plugins: the .wasm module
+    #[serde(default)]
+    pub permissions: Vec<String>, // plugins: see dev_tools::validator::PLUGIN_PERMISSIONS
+}
+
+fn default_schema_version() -> u32 {
+    1
+}
+
+impl Manifest {
+    pub async fn load(dir: &Path) -> Result<Self, WarpError> {
+        let text = tokio::fs::read_to_string(dir.join(MANIFEST)).await?;
+        toml::from_str(&text).map_err(|e| WarpError::config_err(e.to_string()))
+    }
+
+    // The id without its publisher
+    pub fn short_name(&self) -> &str {
+        self.id.rsplit('/').next().unwrap_or(&self.id)
+    }
+
+    pub fn archive_name(&self) -> String {
+        format!("{}-{}.zip", self.short_name(), self.version)
+    }
+}
+
+fn starter_files(kind: PackageKind, name: &str) -> Vec<(&'static str, String)> {
+    match kind {
+        PackageKind::Theme => vec![(
+            "theme.yaml",
+            "accent: \"#01a0e4\"\n\
+             background: \"#1e1e2e\"\n\
+             foreground: \"#cdd6f4\"\n\
+             details: darker\n\
+             terminal_colors:\n\
+            \x20 normal:\n\
+            \x20   black: \"#45475a\"\n\
+            \x20   red: \"#f38ba8\"\n\
+            \x20   green: \"#a6e3a1\"\n\
+            \x20   yellow: \"#f9e2af\"\n\
+            \x20   blue: \"#89b4fa\"\n\
+            \x20   magenta: \"#f5c2e7\"\n\
+            \x20   cyan: \"#94e2d5\"\n\
+            \x20   white: \"#bac2de\"\n\
+            \x20 bright:\n\
+            \x20   black: \"#585b70\"\n\
+            \x20   red: \"#f38ba8\"\n\
+            \x20   green: \"#a6e3a1\"\n\
+            \x20   yellow: \"#f9e2af\"\n\
+            \x20   blue: \"#89b4fa\"\n\
+            \x20   magenta: \"#f5c2e7\"\n\
+            \x20   cyan: \"#94e2d5\"\n\
+            \x20   white: \"#a6adc8\"\n"
+                .to_string(),
+        )],
+        PackageKind::Workflow => vec![(
+            "workflow.yaml",
+            format!(
+                "---\n\
+                 name: {}\n\
+                 command: \"echo {{{{message}}}}\"\n\
+                 tags: []\n\
+                 description: What the workflow does\n\
+                 arguments:\n\
+                \x20 - name: message\n\
+                \x20   description: Text to print\n\
+                \x20   default_value: hello\n\
+                 shells: []\n",
+                name
+            ),
+        )],
+        // The module itself comes from the plugin's own build
+        PackageKind::Plugin => Vec::new(),
+    }
+}
+
+// Creates a package directory with a manifest, a README and a starter file
+// for the kind of item. Returns the files written.
+pub async fn scaffold(
+    dir: &Path,
+    kind: PackageKind,
+    name: &str,
+) -> Result<Vec<PathBuf>, WarpError> {
+    let manifest_path = dir.join(MANIFEST);
+    if manifest_path.exists() {
+        return Err(WarpError::Validation(format!(
+            "{} already exists",
+            manifest_path.display()
+        )));
+    }
+    let publisher: String = std::env::var("USER")
+        .unwrap_or_default()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    let publisher = if publisher.is_empty() {
+        "me".to_string()
+    } else {
+        publisher
+    };
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+
+    let starters = starter_files(kind, name);
+    let mut files: Vec<String> = starters.iter().map(|(file, _)| file.to_string()).collect();
+    let plugin = match kind {
+        PackageKind::Plugin => {
+            files.push("plugin.wasm".to_string());
+            "entry_point = \"plugin.wasm\"\npermissions = []\n"
+        }
+        _ => "",
+    };
+    files.push("README.md".to_string());
+    let kind_name = format!("{:?}", kind).to_lowercase();
+    let manifest = format!(
+        "schema_version = {MANIFEST_SCHEMA_VERSION}\n\
+         id = \"{publisher}/{slug}\"\n\
+         name = {name:?}\n\
+         kind = \"{kind_name}\"\n\
+         version = \"0.1.0\"\n\
+         description = \"A Warp {kind_name}\"\n\
+         author = \"{publisher}\"\n\
+         license = \"MIT\"\n\
+         min_warp_version = \"1.0.0\"\n\
+         tags = []\n\
+         # Bundled into the archive, relative to this file\n\
+         files = {files:?}\n\
+         {plugin}",
+    );
+
+    tokio::fs::create_dir_all(dir).await?;
+    let mut written = vec![manifest_path.clone()];
+    tokio::fs::write(&manifest_path, manifest).await?;
+    let readme = format!(
+        "# {}\n\nDescribe the {} here; this is its marketplace page.\n",
+        name, kind_name
+    );
+    for (file, contents) in starters.into_iter().chain([("README.md", readme)]) {
+        let path = dir.join(file);
+        if !path.exists() {
+            tokio::fs::write(&path, contents).await?;
+            written.push(path);
+        }
+    }
+    Ok(written)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// A zip archive with every entry stored uncompressed, which is all the
+// installer needs and keeps bundles byte-for-byte reproducible
+fn write_zip(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    const DOS_DATE: u16 = 0x21; // 1980-01-01, so bundles don't depend on mtimes
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let header = |signature: u32, central_entry: bool| {
+            let mut h = Vec::new();
+            h.extend(signature.to_le_bytes());
+            if central_entry {
+                h.extend(20u16.to_le_bytes()); // made by
+            }
+            h.extend(20u16.to_le_bytes()); // needed to extract
+            h.extend(0u16.to_le_bytes()); // flags
+            h.extend(0u16.to_le_bytes()); // stored
+            h.extend(0u16.to_le_bytes()); // time
+            h.extend(DOS_DATE.to_le_bytes());
+            h.extend(crc.to_le_bytes());
+            h.extend(size.to_le_bytes());
+            h.extend(size.to_le_bytes());
+            h.extend((name.len() as u16).to_le_bytes());
+            h.extend(0u16.to_le_bytes()); // extra field
+            if central_entry {
+                h.extend([0u8; 10]); // comment, disk, internal and external attributes
+                h.extend(offset.to_le_bytes());
+            }
+            h.extend(name.as_bytes());
+            h
+        };
+        out.extend(header(0x0403_4b50, false));
+        out.extend(data);
+        central.extend(header(0x0201_4b50, true));
+    }
+    let central_offset = out.len() as u32;
+    let count = entries.len() as u16;
+    out.extend(&central);
+    out.extend(0x0605_4b50u32.to_le_bytes());
+    out.extend([0u8; 4]); // disk numbers
+    out.extend(count.to_le_bytes());
+    out.extend(count.to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(central_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // comment
+    out
+}
+
+pub struct Bundle {
+    pub archive: Vec<u8>,
+    pub files: Vec<String>,
+}
+
+// The manifest plus every listed file, in a stable order. Run the validator
+// first; this only fails on I/O.
+pub async fn bundle(dir: &Path, manifest: &Manifest) -> Result<Bundle, WarpError> {
+    let mut paths = vec![dir.join(MANIFEST)];
+    for file in &manifest.files {
+        paths.extend(
+            WalkDir::new(dir.join(file))
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path()),
+        );
+    }
+    let mut entries = Vec::new();
+    for path in paths {
+        let name = path
+            .strip_prefix(dir)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if entries.iter().any(|(existing, _)| *existing == name) {
+            continue;
+        }
+        entries.push((name, tokio::fs::read(&path).await?));
+    }
+    Ok(Bundle {
+        archive: write_zip(&entries),
+        files: entries.into_iter().map(|(name, _)| name).collect(),
+    })
+}
+
+// The publisher's Ed25519 key, created and kept in the secrets manager on
+// first use. The marketplace pins the public key on the first upload of an
+// id, so later versions must be signed with the same one.
+pub async fn signing_key(secrets: &SecretsManager) -> Result<Ed25519KeyPair, WarpError> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let pkcs8 = match secrets.get(SIGNING_KEY_SECRET).await? {
+        Some(stored) => engine.decode(stored).map_err(|_| {
+            WarpError::config_err(format!("The stored {} is corrupt", SIGNING_KEY_SECRET))
+        })?,
+        None => {
+            let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                .map_err(|_| WarpError::config_err("Could not generate a signing key"))?;
+            secrets
+                .set(SIGNING_KEY_SECRET, &engine.encode(document.as_ref()))
+                .await?;
+            document.as_ref().to_vec()
+        }
+    };
+    Ed25519KeyPair::from_pkcs8(&pkcs8)
+        .map_err(|_| WarpError::config_err(format!("The stored {} is corrupt", SIGNING_KEY_SECRET)))
+}
+
+pub struct Signature {
+    pub signature: String,  // base64
+    pub public_key: String, // base64
+}
+
+pub fn sign(key: &Ed25519KeyPair, archive: &[u8]) -> Signature {
+    let engine = base64::engine::general_purpose::STANDARD;
+    Signature {
+        signature: engine.encode(key.sign(archive).as_ref()),
+        public_key: engine.encode(key.public_key().as_ref()),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Published {
+    pub id: String,
+    pub version: String,
+    pub url: Option<String>,
+}
+
+// Uploads a signed bundle. The token comes from the secrets manager
+// (`warp secrets set marketplace.token`), then a `warp auth login
+// marketplace` session, then the variable marketplace::client reads.
+pub async fn publish(
+    secrets: &SecretsManager,
+    oauth: Option<&OAuthProviderConfig>,
+    manifest: &Manifest,
+    archive: &[u8],
+    signature: &Signature,
+) -> Result<Published, WarpError> {
+    let mut token = secrets.get(TOKEN_SECRET).await?;
+    if token.is_none() {
+        if let Some(provider) = oauth {
+            token = oauth::access_token(secrets, provider).await?;
+        }
+    }
+    let token = match token {
+        Some(token) => token,
+        None => std::env::var("WARP_MARKETPLACE_API_KEY")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                WarpError::Auth(format!(
+                    "Not signed in to the marketplace; run `warp auth login marketplace` or store a token with `warp secrets set {}`",
+                    TOKEN_SECRET
+                ))
+            })?,
+    };
+    let body = serde_json::json!({
+        "manifest": manifest,
+        "archive": base64::engine::general_purpose::STANDARD.encode(archive),
+        "signature": signature.signature,
+        "public_key": signature.public_key,
+    });
+    let response = network::client()?
+        .post(network::endpoint(&format!("{}/items", MARKETPLACE_API)))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| WarpError::network("Upload to the marketplace failed", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(WarpError::api(Some(status.as_u16()), text));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| WarpError::network("Failed to parse the marketplace response", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scaffold_refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = scaffold(dir.path(), PackageKind::Theme, "Night Owl")
+            .await
+            .unwrap();
+        assert_eq!(written.len(), 3);
+        let manifest: Manifest =
+            toml::from_str(&std::fs::read_to_string(dir.path().join(MANIFEST)).unwrap()).unwrap();
+        assert!(manifest.id.ends_with("/night-owl"));
+        assert_eq!(manifest.files, vec!["theme.yaml", "README.md"]);
+        assert_eq!(manifest.archive_name(), "night-owl-0.1.0.zip");
+        assert!(scaffold(dir.path(), PackageKind::Theme, "Night Owl")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bundle_is_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        scaffold(dir.path(), PackageKind::Workflow, "greet")
+            .await
+            .unwrap();
+        let manifest: Manifest =
+            toml::from_str(&std::fs::read_to_string(dir.path().join(MANIFEST)).unwrap()).unwrap();
+        let first = bundle(dir.path(), &manifest).await.unwrap();
+        let second = bundle(dir.path(), &manifest).await.unwrap();
+        assert_eq!(first.files, vec![MANIFEST, "workflow.yaml", "README.md"]);
+        assert_eq!(first.archive, second.archive);
+        assert!(first.archive.starts_with(b"PK\x03\x04"));
+        // End of central directory: three entries
+        let end = &first.archive[first.archive.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 3);
+    }
+
+    #[tokio::test]
+    async fn test_signature_verifies_with_stored_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::open(dir.path()).await.unwrap();
+        let key = signing_key(&secrets).await.unwrap();
+        let again = signing_key(&secrets).await.unwrap();
+        assert_eq!(key.public_key().as_ref(), again.public_key().as_ref());
+
+        let signed = sign(&key, b"archive");
+        let engine = base64::engine::general_purpose::STANDARD;
+        let public_key = ring::signature::UnparsedPublicKey::new(
+            &ring::signature::ED25519,
+            engine.decode(&signed.public_key).unwrap(),
+        );
+        let signature = engine.decode(&signed.signature).unwrap();
+        assert!(public_key.verify(b"archive", &signature).is_ok());
+        assert!(public_key.verify(b"tampered", &signature).is_err());
+    }
+}
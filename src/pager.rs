@@ -0,0 +1,615 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::ops::Range;
+
+use crate::i18n;
+
+// Before the first frame reports the real viewport
+const DEFAULT_HEIGHT: usize = 20;
+const HORIZONTAL_STEP: u16 = 8;
+
+// A run of text sharing one SGR style
+pub type StyledRun = (String, Style);
+
+pub enum PagerAction {
+    Continue,
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
+struct Search {
+    query: String,
+    direction: Direction,
+    matches: Vec<usize>,
+}
+
+// less-style viewer over a snapshot of output lines. Lines keep their ANSI
+// colours; search runs on the text with escapes stripped.
+pub struct Pager {
+    lines: Vec<Vec<StyledRun>>,
+    plain: Vec<String>,
+    top: usize,
+    left: u16,
+    height: usize,
+    wrap: bool,
+    // Some while the query is being typed after / or ?
+    input: Option<(Direction, String)>,
+    search: Option<Search>,
+    message: Option<String>,
+    // Lines shown with a background, e.g. the block a permalink points to
+    marked: Option<Range<usize>>,
+    // First lines of the parts [ and ] jump between, e.g. a diff's hunks
+    sections: Vec<usize>,
+}
+
+impl Pager {
+    pub fn new(lines: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let lines: Vec<Vec<StyledRun>> = lines
+            .into_iter()
+            .map(|line| parse_ansi(line.as_ref()))
+            .collect();
+        let plain = lines
+            .iter()
+            .map(|runs| runs.iter().map(|(text, _)| text.as_str()).collect())
+            .collect();
+        Self {
+            lines,
+            plain,
+            top: 0,
+            left: 0,
+            height: DEFAULT_HEIGHT,
+            wrap: false,
+            input: None,
+            search: None,
+            message: None,
+            marked: None,
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn set_sections(&mut self, sections: Vec<usize>) {
+        self.sections = sections;
+    }
+
+    // The section the top line is in
+    pub fn section(&self) -> Option<usize> {
+        self.sections.iter().rposition(|&start| start <= self.top)
+    }
+
+    pub fn show_section(&mut self, index: usize) {
+        if let Some(&start) = self.sections.get(index) {
+            self.scroll_to(start as isize);
+        }
+    }
+
+    // While a search is typed, keys are text rather than commands
+    pub fn typing(&self) -> bool {
+        self.input.is_some()
+    }
+
+    // Highlights the lines and scrolls them to the top
+    pub fn mark(&mut self, lines: Range<usize>) {
+        self.scroll_to(lines.start as isize);
+        self.marked = Some(lines);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn wrap(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn left(&self) -> u16 {
+        if self.wrap {
+            0
+        } else {
+            self.left
+        }
+    }
+
+    // Returns true when the viewport changed and the frame needs redrawing
+    pub fn set_height(&mut self, height: usize) -> bool {
+        let height = height.max(1);
+        if height == self.height {
+            return false;
+        }
+        self.height = height;
+        self.top = self.top.min(self.max_top());
+        true
+    }
+
+    fn max_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll_to(&mut self, top: isize) {
+        self.top = top.clamp(0, self.max_top() as isize) as usize;
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        self.scroll_to(self.top as isize + delta);
+    }
+
+    // j/k line · f/b/Space page · d/u half page · g/G ends · / ? search
+    // · n/N next/previous match · [ ] previous/next section · w wrap
+    // · h/l sideways · q quit
+    pub fn handle_key(&mut self, key: KeyEvent) -> PagerAction {
+        if self.input.is_some() {
+            self.handle_input_key(key);
+            return PagerAction::Continue;
+        }
+        self.message = None;
+
+        let page = self.height as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return PagerAction::Close,
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => self.scroll_by(1),
+            KeyCode::Char('k') | KeyCode::Up => self.scroll_by(-1),
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.scroll_by(page / 2)
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.scroll_by(-page / 2)
+            }
+            KeyCode::Char('d') => self.scroll_by(page / 2),
+            KeyCode::Char('u') => self.scroll_by(-page / 2),
+            KeyCode::Char('f') | KeyCode::Char(' ') | KeyCode::PageDown => self.scroll_by(page),
+            KeyCode::Char('b') | KeyCode::PageUp => self.scroll_by(-page),
+            KeyCode::Char('g') | KeyCode::Home => self.scroll_to(0),
+            KeyCode::Char('G') | KeyCode::End => self.scroll_to(self.max_top() as isize),
+            KeyCode::Char('w') => self.wrap = !self.wrap,
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.left = self.left.saturating_sub(HORIZONTAL_STEP)
+            }
+            KeyCode::Char('l') | KeyCode::Right if !self.wrap => {
+                self.left = self.left.saturating_add(HORIZONTAL_STEP)
+            }
+            KeyCode::Char('/') => self.input = Some((Direction::Forward, String::new())),
+            KeyCode::Char('?') => self.input = Some((Direction::Backward, String::new())),
+            KeyCode::Char('n') => self.next_match(false),
+            KeyCode::Char('N') => self.next_match(true),
+            KeyCode::Char(']') => {
+                if let Some(&start) = self.sections.iter().find(|&&start| start > self.top) {
+                    self.scroll_to(start as isize);
+                }
+            }
+            KeyCode::Char('[') => {
+                if let Some(&start) = self.sections.iter().rev().find(|&&start| start < self.top) {
+                    self.scroll_to(start as isize);
+                }
+            }
+            _ => {}
+        }
+        PagerAction::Continue
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) {
+        let Some((direction, query)) = self.input.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.input = None,
+            KeyCode::Backspace => {
+                if query.pop().is_none() {
+                    self.input = None;
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => query.push(c),
+            KeyCode::Enter => {
+                let (direction, query) = (*direction, std::mem::take(query));
+                self.input = None;
+                // An empty query repeats the previous search, as in less
+                if query.is_empty() {
+                    if let Some(search) = self.search.as_mut() {
+                        search.direction = direction;
+                        self.next_match(false);
+                    }
+                } else {
+                    self.start_search(query, direction);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start_search(&mut self, query: String, direction: Direction) {
+        let matches = self
+            .plain
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !match_ranges(line, &query).is_empty())
+            .map(|(i, _)| i)
+            .collect();
+        self.search = Some(Search {
+            query,
+            direction,
+            matches,
+        });
+        self.next_match(false);
+    }
+
+    // Moves to the next match after (or before) the top line in the search
+    // direction; `reverse` flips it for N
+    fn next_match(&mut self, reverse: bool) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let backward = (search.direction == Direction::Backward) != reverse;
+        let target = if backward {
+            search
+                .matches
+                .iter()
+                .rev()
+                .find(|&&line| line < self.top)
+                .copied()
+        } else {
+            search
+                .matches
+                .iter()
+                .find(|&&line| line > self.top)
+                .copied()
+        };
+        match target {
+            Some(line) => self.scroll_to(line as isize),
+            None => {
+                self.message = Some(i18n::t_args("pager.no_match", &[("query", &search.query)]));
+            }
+        }
+    }
+
+    // The visible slice, with search matches highlighted
+    pub fn visible(&self) -> Vec<Line<'static>> {
+        let query = self.search.as_ref().map(|s| s.query.as_str()).unwrap_or("");
+        self.lines
+            .iter()
+            .zip(&self.plain)
+            .enumerate()
+            .skip(self.top)
+            .take(self.height)
+            .map(|(i, (runs, plain))| {
+                let marked = self
+                    .marked
+                    .as_ref()
+                    .is_some_and(|marked| marked.contains(&i));
+                let spans = highlight(runs, &match_ranges(plain, query))
+                    .into_iter()
+                    .map(|(text, style)| {
+                        let style = if marked {
+                            style.bg(Color::DarkGray)
+                        } else {
+                            style
+                        };
+                        Span::styled(text, style)
+                    })
+                    .collect::<Vec<_>>();
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    pub fn status(&self) -> String {
+        if let Some((direction, query)) = &self.input {
+            let prefix = if *direction == Direction::Forward {
+                '/'
+            } else {
+                '?'
+            };
+            return format!("{}{}", prefix, query);
+        }
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        let last = (self.top + self.height).min(self.lines.len());
+        let percent = if self.lines.is_empty() {
+            100
+        } else {
+            last * 100 / self.lines.len()
+        };
+        let mut status = i18n::t_args(
+            "pager.position",
+            &[
+                ("first", &(self.top + 1).min(last).to_string()),
+                ("last", &last.to_string()),
+                ("total", &self.lines.len().to_string()),
+                ("percent", &percent.to_string()),
+            ],
+        );
+        if let Some(search) = &self.search {
+            status.push_str(&format!(
+                " · {}",
+                i18n::t_args(
+                    "pager.matches",
+                    &[
+                        ("query", &search.query),
+                        ("count", &search.matches.len().to_string())
+                    ],
+                )
+            ));
+        }
+        status
+    }
+}
+
+// Byte ranges of `query` in `line`. Smart case: a query without capitals
+// matches case-insensitively.
+fn match_ranges(line: &str, query: &str) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let (haystack, needle) = if query.chars().any(char::is_uppercase) {
+        (line.to_string(), query.to_string())
+    } else {
+        (line.to_lowercase(), query.to_lowercase())
+    };
+    // Lowercasing can change byte lengths outside ASCII; skip highlighting
+    // rather than slice at the wrong offsets
+    if haystack.len() != line.len() {
+        return if haystack.contains(&needle) {
+            vec![(0, 0)]
+        } else {
+            Vec::new()
+        };
+    }
+    haystack
+        .match_indices(&needle)
+        .map(|(start, found)| (start, start + found.len()))
+        .collect()
+}
+
+// Splits runs at match boundaries and reverses the matched parts
+fn highlight(runs: &[StyledRun], ranges: &[(usize, usize)]) -> Vec<StyledRun> {
+    if ranges.iter().all(|(start, end)| start == end) {
+        return runs.to_vec();
+    }
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for (text, style) in runs {
+        let (run_start, run_end) = (offset, offset + text.len());
+        let mut cuts = vec![run_start, run_end];
+        for &(start, end) in ranges {
+            cuts.extend(
+                [start, end]
+                    .into_iter()
+                    .filter(|&cut| cut > run_start && cut < run_end),
+            );
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+        for pair in cuts.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let matched = ranges.iter().any(|&(s, e)| start >= s && end <= e);
+            let style = if matched {
+                style.add_modifier(Modifier::REVERSED)
+            } else {
+                *style
+            };
+            out.push((text[start - run_start..end - run_start].to_string(), style));
+        }
+        offset = run_end;
+    }
+    out
+}
+
+// SGR colours and attributes become styles; every other escape sequence
+// (cursor movement, OSC titles, ...) and stray control characters are dropped
+pub fn parse_ansi(line: &str) -> Vec<StyledRun> {
+    let mut runs: Vec<StyledRun> = Vec::new();
+    let mut style = Style::default();
+    let mut text = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut terminator = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            terminator = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    if terminator == Some('m') {
+                        if !text.is_empty() {
+                            runs.push((std::mem::take(&mut text), style));
+                        }
+                        style = apply_sgr(style, &params);
+                    }
+                }
+                // OSC runs to BEL or ST
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' => text.push_str("    "),
+            c if c.is_control() => {}
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        runs.push((text, style));
+    }
+    runs
+}
+
+fn apply_sgr(mut style: Style, params: &str) -> Style {
+    let codes: Vec<u16> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => style = Style::default(),
+            1 => style = style.add_modifier(Modifier::BOLD),
+            2 => style = style.add_modifier(Modifier::DIM),
+            3 => style = style.add_modifier(Modifier::ITALIC),
+            4 => style = style.add_modifier(Modifier::UNDERLINED),
+            7 => style = style.add_modifier(Modifier::REVERSED),
+            9 => style = style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style = style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style = style.remove_modifier(Modifier::ITALIC),
+            24 => style = style.remove_modifier(Modifier::UNDERLINED),
+            27 => style = style.remove_modifier(Modifier::REVERSED),
+            29 => style = style.remove_modifier(Modifier::CROSSED_OUT),
+            code @ 30..=37 => style.fg = Some(basic_color(code - 30, false)),
+            code @ 90..=97 => style.fg = Some(basic_color(code - 90, true)),
+            code @ 40..=47 => style.bg = Some(basic_color(code - 40, false)),
+            code @ 100..=107 => style.bg = Some(basic_color(code - 100, true)),
+            39 => style.fg = None,
+            49 => style.bg = None,
+            code @ (38 | 48) => {
+                let (color, used) = extended_color(&codes[i + 1..]);
+                if let Some(color) = color {
+                    if code == 38 {
+                        style.fg = Some(color);
+                    } else {
+                        style.bg = Some(color);
+                    }
+                }
+                i += used;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    style
+}
+
+// 5;n for the 256-colour palette, 2;r;g;b for truecolor. Returns the colour
+// and how many parameters it consumed.
+fn extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    match params {
+        [5, n, ..] => (Some(Color::Indexed(*n as u8)), 2),
+        [2, r, g, b, ..] => (Some(Color::Rgb(*r as u8, *g as u8, *b as u8)), 4),
+        _ => (None, params.len()),
+    }
+}
+
+fn basic_color(index: u16, bright: bool) -> Color {
+    match (index, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::Red,
+        (2, false) => Color::Green,
+        (3, false) => Color::Yellow,
+        (4, false) => Color::Blue,
+        (5, false) => Color::Magenta,
+        (6, false) => Color::Cyan,
+        (7, false) => Color::Gray,
+        (0, true) => Color::DarkGray,
+        (1, true) => Color::LightRed,
+        (2, true) => Color::LightGreen,
+        (3, true) => Color::LightYellow,
+        (4, true) => Color::LightBlue,
+        (5, true) => Color::LightMagenta,
+        (6, true) => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_parse_ansi_colors_and_reset() {
+        let runs = parse_ansi(
+            "\x1b[1;31merror\x1b[0m: \x1b[38;5;208mwarn\x1b[38;2;1;2;3mrgb\x1b]0;title\x07\x1b[2K",
+        );
+        let texts: Vec<&str> = runs.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["error", ": ", "warn", "rgb"]);
+        assert_eq!(runs[0].1.fg, Some(Color::Red));
+        assert!(runs[0].1.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(runs[1].1, Style::default());
+        assert_eq!(runs[2].1.fg, Some(Color::Indexed(208)));
+        assert_eq!(runs[3].1.fg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn test_search_is_smart_case_and_highlights_across_runs() {
+        assert_eq!(match_ranges("Error error", "error"), vec![(0, 5), (6, 11)]);
+        assert_eq!(match_ranges("Error error", "Error"), vec![(0, 5)]);
+
+        let runs = parse_ansi("ab\x1b[32mcd");
+        let highlighted = highlight(&runs, &[(1, 3)]);
+        let texts: Vec<&str> = highlighted.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["a", "b", "c", "d"]);
+        assert!(highlighted[1].1.add_modifier.contains(Modifier::REVERSED));
+        assert!(!highlighted[3].1.add_modifier.contains(Modifier::REVERSED));
+    }
+
+    #[test]
+    fn test_navigation_and_search() {
+        let lines: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+        let mut pager = Pager::new(lines.iter().map(String::as_str));
+        pager.set_height(10);
+
+        pager.handle_key(key('G'));
+        assert_eq!(pager.top, 90);
+        pager.handle_key(key('f'));
+        assert_eq!(pager.top, 90);
+        pager.handle_key(key('g'));
+
+        for c in "/line 5".chars() {
+            pager.handle_key(key(c));
+        }
+        pager.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(pager.top, 5);
+        pager.handle_key(key('n'));
+        assert_eq!(pager.top, 50);
+        pager.handle_key(key('N'));
+        assert_eq!(pager.top, 5);
+        assert!(matches!(pager.handle_key(key('q')), PagerAction::Close));
+    }
+
+    #[test]
+    fn test_mark_scrolls_to_and_highlights_lines() {
+        let lines: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+        let mut pager = Pager::new(lines.iter().map(String::as_str));
+        pager.set_height(10);
+        pager.mark(40..43);
+        assert_eq!(pager.top, 40);
+        let visible = pager.visible();
+        assert_eq!(visible[2].spans[0].style.bg, Some(Color::DarkGray));
+        assert_eq!(visible[3].spans[0].style.bg, None);
+    }
+
+    #[test]
+    fn test_sections_jump_between_hunks() {
+        let lines: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+        let mut pager = Pager::new(lines.iter().map(String::as_str));
+        pager.set_height(10);
+        pager.set_sections(vec![0, 30, 60, 95]);
+
+        pager.handle_key(key(']'));
+        pager.handle_key(key(']'));
+        assert_eq!((pager.top, pager.section()), (60, Some(2)));
+        // The last one can't reach the top, but the end is shown
+        pager.handle_key(key(']'));
+        assert_eq!(pager.top, 90);
+        pager.handle_key(key('['));
+        assert_eq!(pager.top, 60);
+        pager.show_section(1);
+        assert_eq!(pager.top, 30);
+    }
+}
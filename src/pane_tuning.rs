@@ -0,0 +1,412 @@
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{OutputConfig, WarpConfig};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::shutdown::write_atomic;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Overrides for a pane, or saved for a project; unset knobs fall through
+// to the next layer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaneTuning {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrollback_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tail_lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fps: Option<u32>,
+}
+
+impl PaneTuning {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // These values, with `under`'s where these are unset
+    pub fn or(self, under: PaneTuning) -> PaneTuning {
+        PaneTuning {
+            scrollback_lines: self.scrollback_lines.or(under.scrollback_lines),
+            head_lines: self.head_lines.or(under.head_lines),
+            tail_lines: self.tail_lines.or(under.tail_lines),
+            max_fps: self.max_fps.or(under.max_fps),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Knob {
+    Scrollback,
+    HeadLines,
+    TailLines,
+    MaxFps,
+}
+
+impl Knob {
+    pub const ALL: [Knob; 4] = [
+        Knob::Scrollback,
+        Knob::HeadLines,
+        Knob::TailLines,
+        Knob::MaxFps,
+    ];
+
+    fn range(self) -> (u64, u64) {
+        match self {
+            Knob::Scrollback => (100, 1_000_000),
+            Knob::HeadLines => (0, 100_000),
+            Knob::TailLines => (1, 100_000),
+            Knob::MaxFps => (1, 240),
+        }
+    }
+
+    fn get(self, tuning: &PaneTuning) -> Option<u64> {
+        match self {
+            Knob::Scrollback => tuning.scrollback_lines.map(|lines| lines as u64),
+            Knob::HeadLines => tuning.head_lines.map(|lines| lines as u64),
+            Knob::TailLines => tuning.tail_lines.map(|lines| lines as u64),
+            Knob::MaxFps => tuning.max_fps.map(u64::from),
+        }
+    }
+
+    pub fn set(self, tuning: &mut PaneTuning, value: Option<u64>) {
+        let (min, max) = self.range();
+        let value = value.map(|value| value.clamp(min, max));
+        match self {
+            Knob::Scrollback => tuning.scrollback_lines = value.map(|value| value as usize),
+            Knob::HeadLines => tuning.head_lines = value.map(|value| value as usize),
+            Knob::TailLines => tuning.tail_lines = value.map(|value| value as usize),
+            Knob::MaxFps => tuning.max_fps = value.map(|value| value as u32),
+        }
+    }
+
+    fn label(self) -> String {
+        i18n::t(match self {
+            Knob::Scrollback => "pane_tuning.scrollback",
+            Knob::HeadLines => "pane_tuning.head_lines",
+            Knob::TailLines => "pane_tuning.tail_lines",
+            Knob::MaxFps => "pane_tuning.max_fps",
+        })
+    }
+}
+
+// What a pane runs with: the config, under the project's and then the
+// pane's own overrides
+#[derive(Debug, Clone)]
+pub struct PaneLimits {
+    pub scrollback_lines: usize,
+    pub output: OutputConfig, // head and tail are the capture limits
+    pub max_fps: u32,
+}
+
+impl PaneLimits {
+    pub fn from_config(config: &WarpConfig) -> Self {
+        Self {
+            scrollback_lines: config.terminal.scrollback_lines,
+            output: config.output.clone(),
+            max_fps: config.gpu.max_fps,
+        }
+    }
+
+    pub fn tuned(&self, tuning: &PaneTuning) -> Self {
+        let mut limits = self.clone();
+        limits.scrollback_lines = tuning.scrollback_lines.unwrap_or(limits.scrollback_lines);
+        limits.output.head_lines = tuning.head_lines.unwrap_or(limits.output.head_lines);
+        limits.output.tail_lines = tuning.tail_lines.unwrap_or(limits.output.tail_lines);
+        limits.max_fps = tuning.max_fps.unwrap_or(limits.max_fps);
+        limits
+    }
+
+    fn value(&self, knob: Knob) -> u64 {
+        match knob {
+            Knob::Scrollback => self.scrollback_lines as u64,
+            Knob::HeadLines => self.output.head_lines as u64,
+            Knob::TailLines => self.output.tail_lines as u64,
+            Knob::MaxFps => u64::from(self.max_fps),
+        }
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.max_fps.max(1)
+    }
+}
+
+// Tunings saved for project roots; a pane whose directory is inside one
+// starts with it
+#[derive(Debug, Default)]
+pub struct ProjectTunings {
+    projects: BTreeMap<PathBuf, PaneTuning>,
+}
+
+impl ProjectTunings {
+    pub fn default_path() -> Result<PathBuf, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        Ok(data_dir.join("warp").join("pane_tuning.json"))
+    }
+
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let projects = match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { projects })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WarpError> {
+        Ok(serde_json::to_vec_pretty(&self.projects)?)
+    }
+
+    pub async fn save(bytes: &[u8], path: &Path) -> Result<(), WarpError> {
+        write_atomic(path, bytes).await
+    }
+
+    // The most specific saved root containing `dir`
+    pub fn for_dir(&self, dir: &Path) -> Option<(&Path, PaneTuning)> {
+        self.projects
+            .iter()
+            .filter(|(root, _)| dir.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map(|(root, tuning)| (root.as_path(), *tuning))
+    }
+
+    pub fn set(&mut self, root: &Path, tuning: PaneTuning) {
+        if tuning.is_empty() {
+            self.projects.remove(root);
+        } else {
+            self.projects.insert(root.to_path_buf(), tuning);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TuningCommand {
+    None,
+    Apply(PaneTuning),       // the pane's new overrides
+    SaveProject(PaneTuning), // the project's new tuning
+}
+
+// Live knobs for the current pane. Changes apply at once and last for the
+// session unless saved for the project.
+pub struct PaneTuningPanel {
+    pane: usize,
+    config: PaneLimits,
+    root: PathBuf, // where a saved tuning goes
+    project: PaneTuning,
+    overrides: PaneTuning,
+    selected: usize,
+}
+
+impl PaneTuningPanel {
+    pub fn new(
+        pane: usize,
+        config: PaneLimits,
+        root: PathBuf,
+        project: PaneTuning,
+        overrides: PaneTuning,
+    ) -> Self {
+        Self {
+            pane,
+            config,
+            root,
+            project,
+            overrides,
+            selected: 0,
+        }
+    }
+
+    pub fn pane(&self) -> usize {
+        self.pane
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn effective(&self) -> PaneLimits {
+        self.config.tuned(&self.overrides.or(self.project))
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(Knob::ALL.len() - 1);
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> TuningCommand {
+        self.select(index);
+        let knob = Knob::ALL[self.selected];
+        let current = self.effective().value(knob);
+        match key {
+            '+' | '=' => {
+                knob.set(&mut self.overrides, Some(current.saturating_mul(2).max(1)));
+                TuningCommand::Apply(self.overrides)
+            }
+            '-' => {
+                knob.set(&mut self.overrides, Some(current / 2));
+                TuningCommand::Apply(self.overrides)
+            }
+            'r' => {
+                knob.set(&mut self.overrides, None);
+                TuningCommand::Apply(self.overrides)
+            }
+            's' => {
+                knob.set(&mut self.project, Some(current));
+                TuningCommand::SaveProject(self.project)
+            }
+            'x' => {
+                knob.set(&mut self.project, None);
+                TuningCommand::SaveProject(self.project)
+            }
+            _ => TuningCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let effective = self.effective();
+        let items = Knob::ALL
+            .iter()
+            .map(|&knob| {
+                let (source, color) = if knob.get(&self.overrides).is_some() {
+                    ("pane_tuning.from_pane", Some(Color::Yellow))
+                } else if knob.get(&self.project).is_some() {
+                    ("pane_tuning.from_project", Some(Color::Cyan))
+                } else {
+                    ("pane_tuning.from_config", Some(Color::DarkGrey))
+                };
+                PanelItem::styled(vec![
+                    (format!("{:<28}", knob.label()), None),
+                    (format!("{:>9} ", effective.value(knob)), None),
+                    (i18n::t(source), color),
+                ])
+            })
+            .collect();
+        let preview = vec![
+            i18n::t("pane_tuning.hint").into(),
+            i18n::t_args(
+                "pane_tuning.project",
+                &[("root", &self.root.display().to_string())],
+            )
+            .into(),
+        ];
+        PanelView {
+            kind: PanelKind::PaneTuning,
+            title: i18n::t_args("panel.pane_tuning", &[("pane", &self.pane.to_string())]),
+            items,
+            footer: Some(i18n::t("panel.pane_tuning_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panel(project: PaneTuning) -> PaneTuningPanel {
+        PaneTuningPanel::new(
+            1,
+            PaneLimits::from_config(&WarpConfig::default()),
+            PathBuf::from("/work/app"),
+            project,
+            PaneTuning::default(),
+        )
+    }
+
+    #[test]
+    fn test_layers_and_live_changes() {
+        let project = PaneTuning {
+            max_fps: Some(30),
+            ..PaneTuning::default()
+        };
+        let mut panel = panel(project);
+        let config = PaneLimits::from_config(&WarpConfig::default());
+        assert_eq!(panel.effective().max_fps, 30);
+        assert_eq!(
+            panel.effective().frame_interval(),
+            Duration::from_secs(1) / 30
+        );
+
+        // Doubling the scrollback is the pane's own, over the config
+        let TuningCommand::Apply(overrides) = panel.handle_key('+', 0) else {
+            panic!("expected the pane's overrides");
+        };
+        assert_eq!(
+            overrides.scrollback_lines,
+            Some(config.scrollback_lines * 2)
+        );
+        assert_eq!(
+            panel.effective().scrollback_lines,
+            config.scrollback_lines * 2
+        );
+
+        // and clamped, then reset back to what's configured
+        for _ in 0..20 {
+            panel.handle_key('+', 0);
+        }
+        assert_eq!(panel.effective().scrollback_lines, 1_000_000);
+        panel.handle_key('r', 0);
+        assert_eq!(panel.effective().scrollback_lines, config.scrollback_lines);
+
+        // The pane wins over the project
+        panel.handle_key('-', 3);
+        assert_eq!(panel.effective().max_fps, 15);
+        assert_eq!(
+            panel.handle_key('s', 3),
+            TuningCommand::SaveProject(PaneTuning {
+                max_fps: Some(15),
+                ..PaneTuning::default()
+            })
+        );
+        assert_eq!(
+            panel.handle_key('x', 3),
+            TuningCommand::SaveProject(PaneTuning::default())
+        );
+        assert_eq!(panel.handle_key('q', 0), TuningCommand::None);
+    }
+
+    #[tokio::test]
+    async fn test_project_tunings_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pane_tuning.json");
+        let mut tunings = ProjectTunings::default();
+        let tuning = PaneTuning {
+            scrollback_lines: Some(50_000),
+            ..PaneTuning::default()
+        };
+        tunings.set(
+            Path::new("/work"),
+            PaneTuning {
+                max_fps: Some(30),
+                ..PaneTuning::default()
+            },
+        );
+        tunings.set(Path::new("/work/app"), tuning);
+        ProjectTunings::save(&tunings.to_bytes().unwrap(), &path)
+            .await
+            .unwrap();
+
+        let mut loaded = ProjectTunings::load(&path).await.unwrap();
+        assert_eq!(
+            loaded.for_dir(Path::new("/work/app/src")),
+            Some((Path::new("/work/app"), tuning))
+        );
+        assert!(loaded.for_dir(Path::new("/elsewhere")).is_none());
+        loaded.set(Path::new("/work/app"), PaneTuning::default());
+        assert_eq!(
+            loaded.for_dir(Path::new("/work/app")).unwrap().0,
+            Path::new("/work")
+        );
+        assert!(ProjectTunings::load(&dir.path().join("missing.json"))
+            .await
+            .unwrap()
+            .for_dir(Path::new("/work"))
+            .is_none());
+    }
+}
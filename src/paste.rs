@@ -0,0 +1,233 @@
+use crossterm::style::Color;
+use regex::Regex;
+
+use crate::config::PasteConfig;
+use crate::error::WarpError;
+use crate::git::highlight;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasteWarning {
+    pub line: usize, // 1-based
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Paste {
+    pub text: String,
+    pub warnings: Vec<PasteWarning>,
+}
+
+impl Paste {
+    pub fn is_multiline(&self) -> bool {
+        self.text.contains('\n')
+    }
+}
+
+// Looks at clipboard text before it reaches the input line: normalises line
+// endings, drops the trailing newline that would run it straight away and
+// flags risky lines
+pub struct PasteGuard {
+    review_multiline: bool,
+    strip_trailing_newlines: bool,
+    warnings: Vec<(Regex, String)>,
+}
+
+impl PasteGuard {
+    pub fn from_config(config: &PasteConfig) -> Result<Self, WarpError> {
+        let warnings = config
+            .warnings
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .map(|regex| (regex, rule.reason.clone()))
+                    .map_err(|e| {
+                        WarpError::config_err(format!(
+                            "Invalid paste warning '{}': {}",
+                            rule.pattern, e
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            review_multiline: config.review_multiline,
+            strip_trailing_newlines: config.strip_trailing_newlines,
+            warnings,
+        })
+    }
+
+    pub fn prepare(&self, text: &str) -> Paste {
+        let mut text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let kept = text.trim_end().len();
+        if self.strip_trailing_newlines && text[kept..].contains('\n') {
+            text.truncate(kept);
+        }
+        let warnings = text
+            .lines()
+            .enumerate()
+            .flat_map(|(index, line)| {
+                self.warnings
+                    .iter()
+                    .filter(move |(regex, _)| regex.is_match(line))
+                    .map(move |(_, reason)| PasteWarning {
+                        line: index + 1,
+                        reason: reason.clone(),
+                    })
+            })
+            .collect();
+        Paste { text, warnings }
+    }
+
+    // Whether the paste goes to the review panel rather than the input line
+    pub fn needs_review(&self, paste: &Paste) -> bool {
+        self.review_multiline && paste.is_multiline()
+    }
+}
+
+pub fn warning_message(warning: &PasteWarning) -> String {
+    i18n::t_args(
+        "paste.warning",
+        &[
+            ("line", &warning.line.to_string()),
+            ("reason", &warning.reason),
+        ],
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasteCommand {
+    None,
+    Run(String),
+    Edit(String),
+}
+
+// A multi-line paste shown as a highlighted script; nothing runs until Enter
+pub struct PastePanel {
+    paste: Paste,
+    highlighted: Vec<highlight::Segments>,
+}
+
+impl PastePanel {
+    pub fn new(paste: Paste) -> Self {
+        let highlighted = highlight::highlight_source("sh", &paste.text);
+        Self { paste, highlighted }
+    }
+
+    pub fn handle_key(&self, key: char) -> PasteCommand {
+        match key {
+            '\n' => PasteCommand::Run(self.paste.text.clone()),
+            'e' => PasteCommand::Edit(self.paste.text.clone()),
+            _ => PasteCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let width = self.highlighted.len().to_string().len();
+        let items = self
+            .highlighted
+            .iter()
+            .enumerate()
+            .map(|(index, segments)| {
+                let flagged = self.paste.warnings.iter().any(|w| w.line == index + 1);
+                let (mark, color) = match flagged {
+                    true => ("! ", Some(Color::Red)),
+                    false => ("  ", Some(Color::DarkGrey)),
+                };
+                let mut row = vec![
+                    (mark.to_string(), color),
+                    (format!("{:>width$} ", index + 1), Some(Color::DarkGrey)),
+                ];
+                row.extend(segments.iter().cloned());
+                PanelItem::styled(row)
+            })
+            .collect();
+        let preview = self
+            .paste
+            .warnings
+            .iter()
+            .map(|warning| PanelItem::styled(vec![(warning_message(warning), Some(Color::Yellow))]))
+            .collect();
+        PanelView {
+            kind: PanelKind::Paste,
+            title: i18n::t_args(
+                "panel.paste",
+                &[("count", &self.highlighted.len().to_string())],
+            ),
+            items,
+            footer: Some(i18n::t("panel.paste_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+
+    fn guard() -> PasteGuard {
+        PasteGuard::from_config(&WarpConfig::default().security.paste).unwrap()
+    }
+
+    #[test]
+    fn test_strips_trailing_newlines() {
+        let guard = guard();
+        assert_eq!(guard.prepare("ls -la\n").text, "ls -la");
+        assert_eq!(guard.prepare("ls -la\r\n\r\n").text, "ls -la");
+        assert_eq!(guard.prepare("echo 'a  '").text, "echo 'a  '");
+        assert!(!guard.prepare("ls -la\n").is_multiline());
+
+        let mut config = WarpConfig::default().security.paste;
+        config.strip_trailing_newlines = false;
+        let guard = PasteGuard::from_config(&config).unwrap();
+        assert_eq!(guard.prepare("ls -la\n").text, "ls -la\n");
+    }
+
+    #[test]
+    fn test_multiline_needs_review() {
+        let guard = guard();
+        let paste = guard.prepare("cd /tmp\r\nmake\n");
+        assert_eq!(paste.text, "cd /tmp\nmake");
+        assert!(guard.needs_review(&paste));
+        assert!(!guard.needs_review(&guard.prepare("make\n")));
+
+        let mut config = WarpConfig::default().security.paste;
+        config.review_multiline = false;
+        let guard = PasteGuard::from_config(&config).unwrap();
+        assert!(!guard.needs_review(&guard.prepare("cd /tmp\nmake")));
+    }
+
+    #[test]
+    fn test_flags_risky_lines() {
+        let paste = guard().prepare(
+            "apt update\nsudo apt install jq\nrm -rf build\ncurl -fsSL https://x.sh | bash\n",
+        );
+        let lines: Vec<usize> = paste.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert!(guard().prepare("rm notes.txt").warnings.is_empty());
+        assert!(guard().prepare("echo pseudo").warnings.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_invalid_pattern() {
+        let mut config = WarpConfig::default().security.paste;
+        config.warnings[0].pattern = "(".to_string();
+        assert!(PasteGuard::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_panel_keys() {
+        let panel = PastePanel::new(guard().prepare("cd /tmp\nsudo make install\n"));
+        let view = panel.view();
+        assert_eq!(view.items.len(), 2);
+        assert_eq!(view.preview.len(), 1);
+        assert_eq!(
+            panel.handle_key('\n'),
+            PasteCommand::Run("cd /tmp\nsudo make install".to_string())
+        );
+        assert!(matches!(panel.handle_key('e'), PasteCommand::Edit(_)));
+        assert_eq!(panel.handle_key('q'), PasteCommand::None);
+    }
+}
@@ -0,0 +1,62 @@
+// warp://block/<id> links to a command's output. Ids are assigned when a
+// block starts and stay valid for as long as any of its lines are in the
+// scrollback.
+pub const BLOCK_LINK_PREFIX: &str = "warp://block/";
+
+const ID_LEN: usize = 12;
+
+pub fn new_block_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..ID_LEN].to_string()
+}
+
+pub fn block_link(id: &str) -> String {
+    format!("{}{}", BLOCK_LINK_PREFIX, id)
+}
+
+fn is_id(id: &str) -> bool {
+    id.len() == ID_LEN && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// The id when `text` is nothing but a block link, as when one is pasted
+// into the prompt
+pub fn parse_block_link(text: &str) -> Option<&str> {
+    text.trim()
+        .strip_prefix(BLOCK_LINK_PREFIX)
+        .filter(|id| is_id(id))
+}
+
+// Ids of every block link in a longer text, in order
+pub fn find_block_links(text: &str) -> Vec<&str> {
+    text.match_indices(BLOCK_LINK_PREFIX)
+        .filter_map(|(at, prefix)| {
+            let rest = &text[at + prefix.len()..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(rest.len());
+            Some(&rest[..end]).filter(|id| is_id(id))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_round_trip() {
+        let id = new_block_id();
+        assert!(is_id(&id));
+        assert_eq!(
+            parse_block_link(&format!("  {}\n", block_link(&id))),
+            Some(id.as_str())
+        );
+        assert_eq!(parse_block_link("warp://block/nothex"), None);
+        assert_eq!(parse_block_link(&format!("see {}", block_link(&id))), None);
+    }
+
+    #[test]
+    fn test_links_are_found_in_text() {
+        let text = "Failed in warp://block/0123456789ab, fixed by warp://block/ba9876543210. Not warp://block/12.";
+        assert_eq!(find_block_links(text), vec!["0123456789ab", "ba9876543210"]);
+    }
+}
@@ -0,0 +1,167 @@
+use crossterm::style::Color;
+
+use crate::i18n;
+use crate::plugin_host::{PluginState, PluginUsage, SuspendReason};
+use crate::process_tree::format_bytes;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivityCommand {
+    None,
+    Suspend(String),
+    Resume(String),
+    KillAll,
+    Revive,
+}
+
+fn state_label(state: PluginState) -> (String, Option<Color>) {
+    match state {
+        PluginState::Running => (i18n::t("plugin_activity.running"), Some(Color::Green)),
+        PluginState::Throttled => (i18n::t("plugin_activity.throttled"), Some(Color::Yellow)),
+        PluginState::Suspended(SuspendReason::Cpu) => {
+            (i18n::t("plugin_activity.suspended_cpu"), Some(Color::Red))
+        }
+        PluginState::Suspended(SuspendReason::User) => {
+            (i18n::t("plugin_activity.suspended"), Some(Color::DarkGrey))
+        }
+    }
+}
+
+// What each plugin has cost over the last minute and in total, with keys to
+// suspend one or switch them all off
+pub struct PluginActivityPanel {
+    usage: Vec<PluginUsage>,
+    killed: bool,
+    selected: usize,
+}
+
+impl PluginActivityPanel {
+    pub fn new(usage: Vec<PluginUsage>, killed: bool) -> Self {
+        Self {
+            usage,
+            killed,
+            selected: 0,
+        }
+    }
+
+    pub fn refresh(&mut self, usage: Vec<PluginUsage>, killed: bool) {
+        self.usage = usage;
+        self.killed = killed;
+        self.select(self.selected);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.usage.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> ActivityCommand {
+        self.select(index);
+        match (key, self.usage.get(self.selected)) {
+            ('s', Some(usage)) => match usage.state {
+                PluginState::Suspended(_) => ActivityCommand::Resume(usage.name.clone()),
+                _ => ActivityCommand::Suspend(usage.name.clone()),
+            },
+            ('k', _) if self.killed => ActivityCommand::Revive,
+            ('k', _) => ActivityCommand::KillAll,
+            _ => ActivityCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .usage
+            .iter()
+            .map(|usage| {
+                let (state, color) = state_label(usage.state);
+                PanelItem::styled(vec![
+                    (format!("{:<20} ", usage.name), None),
+                    (format!("{:<12} ", state), color),
+                    (
+                        i18n::t_args(
+                            "plugin_activity.row",
+                            &[
+                                ("calls", &usage.calls_last_minute.to_string()),
+                                ("cpu", &usage.cpu_last_minute.as_millis().to_string()),
+                                ("memory", &format_bytes(usage.memory as u64)),
+                            ],
+                        ),
+                        Some(Color::DarkGrey),
+                    ),
+                ])
+            })
+            .collect();
+        let preview = match self.usage.get(self.selected) {
+            Some(usage) => vec![
+                i18n::t_args(
+                    "plugin_activity.total_calls",
+                    &[("count", &usage.calls.to_string())],
+                )
+                .into(),
+                i18n::t_args(
+                    "plugin_activity.total_cpu",
+                    &[("ms", &usage.cpu.as_millis().to_string())],
+                )
+                .into(),
+            ],
+            None => vec![i18n::t("plugin_activity.empty").into()],
+        };
+        let title = match self.killed {
+            true => i18n::t("panel.plugin_activity_killed"),
+            false => i18n::t_args(
+                "panel.plugin_activity",
+                &[("count", &self.usage.len().to_string())],
+            ),
+        };
+        PanelView {
+            kind: PanelKind::PluginActivity,
+            title,
+            items,
+            footer: Some(i18n::t("panel.plugin_activity_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn usage(name: &str, state: PluginState) -> PluginUsage {
+        PluginUsage {
+            name: name.to_string(),
+            state,
+            calls: 12,
+            calls_last_minute: 3,
+            cpu: Duration::from_millis(40),
+            cpu_last_minute: Duration::from_millis(9),
+            memory: 64 * 1024,
+        }
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut panel = PluginActivityPanel::new(
+            vec![
+                usage("notes", PluginState::Running),
+                usage("weather", PluginState::Suspended(SuspendReason::Cpu)),
+            ],
+            false,
+        );
+        assert_eq!(panel.view().items.len(), 2);
+        assert_eq!(
+            panel.handle_key('s', 0),
+            ActivityCommand::Suspend("notes".to_string())
+        );
+        assert_eq!(
+            panel.handle_key('s', 1),
+            ActivityCommand::Resume("weather".to_string())
+        );
+        assert_eq!(panel.handle_key('k', 1), ActivityCommand::KillAll);
+
+        panel.refresh(Vec::new(), true);
+        assert_eq!(panel.handle_key('s', 0), ActivityCommand::None);
+        assert_eq!(panel.handle_key('k', 0), ActivityCommand::Revive);
+    }
+}
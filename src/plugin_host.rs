@@ -0,0 +1,852 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use wasmtime::{
+    Caller, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::config::WASMConfig;
+use crate::error::WarpError;
+use crate::network;
+use crate::plugin_permissions::{Capability, Permissions};
+
+// How often the engine's epoch advances; call deadlines are counted in these
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+// Log lines kept per plugin between drains
+const MAX_LOG_LINES: usize = 200;
+// Largest file or response body handed to a plugin
+const MAX_REPLY_BYTES: usize = 4 * 1024 * 1024;
+// Budgets are per rolling minute
+const METER_WINDOW: Duration = Duration::from_secs(60);
+// What the capability imports return instead of a (ptr << 32) | len reply
+const DENIED: i64 = -1;
+const FAILED: i64 = -2;
+
+// The plugin ABI. A module exports `memory` and
+//   alloc(len: i32) -> i32               memory for the host to write into
+//   on_command(ptr: i32, len: i32) -> i64  (ptr << 32) | len of the reply
+// and optionally, to keep its state across a hot reload,
+//   save_state() -> i64                  (ptr << 32) | len, like on_command
+//   restore_state(ptr: i32, len: i32)
+// Strings are UTF-8. The host provides
+//   log(ptr, len)
+//   read_file(ptr, len) -> i64           path; needs a filesystem grant
+//   http_get(ptr, len) -> i64            url; needs a grant for its host
+//   read_block() -> i64                  output of the last command
+// in the `warp` module; any other import fails the load. The capability
+// imports reply like on_command, or with DENIED until the user grants the
+// request (see plugin_permissions) or FAILED.
+// Everything link_imports provides, for checking modules before they load
+pub const HOST_IMPORTS: &[&str] = &["log", "read_file", "http_get", "read_block"];
+// What the host calls; save_state and restore_state are optional
+pub const REQUIRED_EXPORTS: &[&str] = &["memory", "alloc", "on_command"];
+
+pub struct HostState {
+    name: String,
+    limits: StoreLimits,
+    logs: Vec<String>,
+    timeout: Duration,
+    permissions: Arc<std::sync::Mutex<Permissions>>,
+    block: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> Option<Memory> {
+    caller.get_export("memory").and_then(|e| e.into_memory())
+}
+
+fn argument(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    let memory = memory(caller)?;
+    read_string(&memory, &*caller, ptr, len)
+}
+
+fn allowed(caller: &Caller<'_, HostState>, capability: Capability) -> bool {
+    let state = caller.data();
+    state
+        .permissions
+        .lock()
+        .unwrap()
+        .check(&state.name, capability)
+}
+
+// Copies a reply into the plugin's memory through its alloc export
+fn reply(caller: &mut Caller<'_, HostState>, bytes: &[u8]) -> i64 {
+    let (Some(memory), Some(alloc)) = (
+        memory(caller),
+        caller.get_export("alloc").and_then(|e| e.into_func()),
+    ) else {
+        return FAILED;
+    };
+    let Ok(alloc) = alloc.typed::<i32, i32>(&*caller) else {
+        return FAILED;
+    };
+    let Ok(ptr) = alloc.call(&mut *caller, bytes.len() as i32) else {
+        return FAILED;
+    };
+    if memory.write(&mut *caller, ptr as usize, bytes).is_err() {
+        return FAILED;
+    }
+    ((ptr as u32 as i64) << 32) | bytes.len() as u32 as i64
+}
+
+// Imports are synchronous, so the request runs on its own runtime in a
+// scoped thread. Redirects aren't followed: the grant is for one host.
+fn fetch(url: reqwest::Url, timeout: Duration) -> Option<Vec<u8>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .ok()?;
+    runtime.block_on(async {
+        let client = network::client_builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .ok()?;
+        let response = client.get(url).send().await.ok()?.error_for_status().ok()?;
+        let body = response.bytes().await.ok()?;
+        (body.len() <= MAX_REPLY_BYTES).then(|| body.to_vec())
+    })
+}
+
+fn link_imports(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    linker.func_wrap(
+        "warp",
+        "log",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            if let Some(line) = argument(&mut caller, ptr, len) {
+                let logs = &mut caller.data_mut().logs;
+                if logs.len() < MAX_LOG_LINES {
+                    logs.push(line);
+                }
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "warp",
+        "read_file",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i64 {
+            let Some(path) = argument(&mut caller, ptr, len) else {
+                return FAILED;
+            };
+            // Resolved first so `..` and symlinks can't step outside a grant
+            let Ok(path) = std::fs::canonicalize(path) else {
+                return FAILED;
+            };
+            if !allowed(&caller, Capability::Filesystem(path.clone())) {
+                return DENIED;
+            }
+            match std::fs::read(&path) {
+                Ok(bytes) if bytes.len() <= MAX_REPLY_BYTES => reply(&mut caller, &bytes),
+                _ => FAILED,
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "warp",
+        "http_get",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i64 {
+            let Some(url) = argument(&mut caller, ptr, len)
+                .and_then(|url| reqwest::Url::parse(&url).ok())
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+            else {
+                return FAILED;
+            };
+            let Some(host) = url.host_str().map(str::to_string) else {
+                return FAILED;
+            };
+            if !allowed(&caller, Capability::Network(host)) {
+                return DENIED;
+            }
+            let timeout = caller.data().timeout;
+            let body = std::thread::scope(|scope| scope.spawn(|| fetch(url, timeout)).join());
+            match body {
+                Ok(Some(body)) => reply(&mut caller, &body),
+                _ => FAILED,
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "warp",
+        "read_block",
+        |mut caller: Caller<'_, HostState>| -> i64 {
+            if !allowed(&caller, Capability::Blocks) {
+                return DENIED;
+            }
+            let text = caller.data().block.lock().unwrap().join("\n");
+            reply(&mut caller, text.as_bytes())
+        },
+    )?;
+    Ok(())
+}
+
+fn read_string(
+    memory: &Memory,
+    store: impl wasmtime::AsContext,
+    ptr: i32,
+    len: i32,
+) -> Option<String> {
+    let data = memory.data(&store);
+    let start = usize::try_from(ptr).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    data.get(start..end)
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn unpack(packed: i64) -> (i32, i32) {
+    ((packed >> 32) as i32, packed as i32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuspendReason {
+    Cpu,
+    User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginState {
+    Running,
+    Throttled,
+    Suspended(SuspendReason),
+}
+
+// A plugin's calls in the last minute, against the budgets in WASMConfig.
+// Over the call rate its calls are refused until the window frees up; over
+// the CPU budget it's suspended until resumed. CPU is the wall time spent
+// in calls, the module being single-threaded.
+#[derive(Debug, Default)]
+struct Meter {
+    calls: u64,
+    cpu: Duration,
+    window: VecDeque<(Instant, Duration)>,
+    suspended: Option<SuspendReason>,
+}
+
+impl Meter {
+    fn prune(&mut self, now: Instant) {
+        while let Some(&(at, _)) = self.window.front() {
+            if now.duration_since(at) < METER_WINDOW {
+                break;
+            }
+            self.window.pop_front();
+        }
+    }
+
+    fn recent_cpu(&self) -> Duration {
+        self.window.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+
+    fn state(&self, config: &WASMConfig) -> PluginState {
+        let limit = config.max_calls_per_minute as usize;
+        match self.suspended {
+            Some(reason) => PluginState::Suspended(reason),
+            None if limit > 0 && self.window.len() >= limit => PluginState::Throttled,
+            None => PluginState::Running,
+        }
+    }
+
+    fn admit(&mut self, now: Instant, config: &WASMConfig) -> Result<(), &'static str> {
+        self.prune(now);
+        match self.state(config) {
+            PluginState::Running => Ok(()),
+            PluginState::Throttled => Err("over the call rate limit"),
+            PluginState::Suspended(SuspendReason::Cpu) => Err("suspended for using too much CPU"),
+            PluginState::Suspended(SuspendReason::User) => Err("suspended"),
+        }
+    }
+
+    fn record(&mut self, now: Instant, elapsed: Duration, config: &WASMConfig) {
+        self.calls += 1;
+        self.cpu += elapsed;
+        self.window.push_back((now, elapsed));
+        let budget = Duration::from_millis(config.max_cpu_ms_per_minute);
+        if !budget.is_zero() && self.recent_cpu() > budget && self.suspended.is_none() {
+            self.suspended = Some(SuspendReason::Cpu);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginUsage {
+    pub name: String,
+    pub state: PluginState,
+    pub calls: u64,
+    pub calls_last_minute: usize,
+    pub cpu: Duration,
+    pub cpu_last_minute: Duration,
+    pub memory: usize, // bytes of linear memory
+}
+
+pub struct WasmPlugin {
+    name: String,
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    meter: Meter,
+}
+
+impl WasmPlugin {
+    fn call_error(&self, e: impl std::fmt::Display) -> WarpError {
+        WarpError::plugin(&self.name, e.to_string())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<i32, WarpError> {
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&mut self.store, "alloc")
+            .map_err(|e| self.call_error(e))?;
+        let ptr = alloc
+            .call(&mut self.store, bytes.len() as i32)
+            .map_err(|e| self.call_error(e))?;
+        self.memory
+            .write(&mut self.store, ptr as usize, bytes)
+            .map_err(|e| self.call_error(e))?;
+        Ok(ptr)
+    }
+
+    fn read(&self, packed: i64) -> Result<String, WarpError> {
+        let (ptr, len) = unpack(packed);
+        read_string(&self.memory, &self.store, ptr, len)
+            .ok_or_else(|| self.call_error("returned a string outside its memory"))
+    }
+
+    fn call(&mut self, command: &str, deadline: u64) -> Result<String, WarpError> {
+        self.store.set_epoch_deadline(deadline);
+        let ptr = self.write(command.as_bytes())?;
+        let on_command = self
+            .instance
+            .get_typed_func::<(i32, i32), i64>(&mut self.store, "on_command")
+            .map_err(|e| self.call_error(e))?;
+        let packed = on_command
+            .call(&mut self.store, (ptr, command.len() as i32))
+            .map_err(|e| self.call_error(e))?;
+        self.read(packed)
+    }
+
+    fn save_state(&mut self, deadline: u64) -> Option<Vec<u8>> {
+        self.store.set_epoch_deadline(deadline);
+        let save = self
+            .instance
+            .get_typed_func::<(), i64>(&mut self.store, "save_state")
+            .ok()?;
+        let (ptr, len) = unpack(save.call(&mut self.store, ()).ok()?);
+        let start = usize::try_from(ptr).ok()?;
+        let end = start.checked_add(usize::try_from(len).ok()?)?;
+        self.memory
+            .data(&self.store)
+            .get(start..end)
+            .map(<[u8]>::to_vec)
+    }
+
+    // Returns whether the module took the state
+    fn restore_state(&mut self, state: &[u8], deadline: u64) -> Result<bool, WarpError> {
+        let Ok(restore) = self
+            .instance
+            .get_typed_func::<(i32, i32), ()>(&mut self.store, "restore_state")
+        else {
+            return Ok(false);
+        };
+        self.store.set_epoch_deadline(deadline);
+        let ptr = self.write(state)?;
+        restore
+            .call(&mut self.store, (ptr, state.len() as i32))
+            .map_err(|e| self.call_error(e))?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReloadOutcome {
+    pub state_kept: bool,
+}
+
+fn deadline_for(timeout: Duration) -> u64 {
+    (timeout.as_millis() / EPOCH_TICK.as_millis()).max(1) as u64
+}
+
+// Runs WebAssembly plugins, each in its own store with the configured
+// memory limit and a deadline on every call
+pub struct PluginHost {
+    engine: Engine,
+    config: WASMConfig,
+    plugins: Mutex<HashMap<String, WasmPlugin>>,
+    // Shared with every store; the imports are synchronous
+    permissions: Arc<std::sync::Mutex<Permissions>>,
+    block: Arc<std::sync::Mutex<Vec<String>>>,
+    killed: AtomicBool,
+}
+
+impl PluginHost {
+    pub fn new(config: &WASMConfig) -> Result<Self, WarpError> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.epoch_interruption(true);
+        let engine =
+            Engine::new(&engine_config).map_err(|e| WarpError::plugin("host", e.to_string()))?;
+        let ticker = engine.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        });
+        Ok(Self {
+            engine,
+            config: config.clone(),
+            plugins: Mutex::new(HashMap::new()),
+            permissions: Arc::new(std::sync::Mutex::new(Permissions::default())),
+            block: Arc::new(std::sync::Mutex::new(Vec::new())),
+            killed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn permissions(&self) -> Arc<std::sync::Mutex<Permissions>> {
+        self.permissions.clone()
+    }
+
+    pub fn set_permissions(&self, permissions: Permissions) {
+        *self.permissions.lock().unwrap() = permissions;
+    }
+
+    // What read_block returns
+    pub fn set_block(&self, lines: Vec<String>) {
+        *self.block.lock().unwrap() = lines;
+    }
+
+    fn deadline(&self) -> u64 {
+        deadline_for(Duration::from_secs(self.config.timeout))
+    }
+
+    fn instantiate(&self, name: &str, path: &Path) -> Result<WasmPlugin, WarpError> {
+        let failed = |e: &dyn std::fmt::Display| WarpError::plugin(name, e.to_string());
+        let module = Module::from_file(&self.engine, path).map_err(|e| failed(&e))?;
+        if let Some(import) = module.imports().find(|import| import.module() != "warp") {
+            return Err(failed(&format!(
+                "imports {}.{}, which the host doesn't provide",
+                import.module(),
+                import.name()
+            )));
+        }
+
+        let state = HostState {
+            name: name.to_string(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.config.max_memory)
+                .build(),
+            logs: Vec::new(),
+            timeout: Duration::from_secs(self.config.timeout),
+            permissions: self.permissions.clone(),
+            block: self.block.clone(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_epoch_deadline(self.deadline());
+
+        let mut linker = Linker::new(&self.engine);
+        link_imports(&mut linker).map_err(|e| failed(&e))?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| failed(&e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| failed(&"doesn't export its memory"))?;
+        Ok(WasmPlugin {
+            name: name.to_string(),
+            store,
+            instance,
+            memory,
+            meter: Meter::default(),
+        })
+    }
+
+    pub async fn load(&self, name: &str, path: &Path) -> Result<(), WarpError> {
+        let plugin = self.instantiate(name, path)?;
+        self.plugins.lock().await.insert(name.to_string(), plugin);
+        Ok(())
+    }
+
+    // Each enabled plugin from <dir>/<name>/plugin.wasm; one that fails to
+    // load is skipped with the error returned alongside the others
+    pub async fn load_installed(&self, dir: &Path, enabled: &[String]) -> Vec<WarpError> {
+        let mut errors = Vec::new();
+        for name in enabled {
+            let path = dir.join(name).join("plugin.wasm");
+            if path.exists() {
+                if let Err(e) = self.load(name, &path).await {
+                    errors.push(e);
+                }
+            }
+        }
+        errors
+    }
+
+    // Swaps in a new build of a plugin. The old instance keeps running if
+    // the new one fails to load; its state moves across when both sides
+    // export the state functions.
+    pub async fn reload(&self, name: &str, path: &Path) -> Result<ReloadOutcome, WarpError> {
+        let mut replacement = self.instantiate(name, path)?;
+        let deadline = self.deadline();
+        let mut plugins = self.plugins.lock().await;
+        let state = plugins
+            .get_mut(name)
+            .and_then(|old| old.save_state(deadline));
+        let state_kept = match state {
+            Some(state) => replacement.restore_state(&state, deadline)?,
+            None => false,
+        };
+        if let Some(old) = plugins.get_mut(name) {
+            replacement.meter = std::mem::take(&mut old.meter);
+        }
+        plugins.insert(name.to_string(), replacement);
+        Ok(ReloadOutcome { state_kept })
+    }
+
+    pub async fn unload(&self, name: &str) -> bool {
+        self.plugins.lock().await.remove(name).is_some()
+    }
+
+    // Every call goes through the kill switch and the plugin's meter
+    fn metered_call(
+        &self,
+        plugin: &mut WasmPlugin,
+        command: &str,
+        deadline: u64,
+    ) -> Result<String, WarpError> {
+        if self.is_killed() {
+            return Err(plugin.call_error("plugins are switched off"));
+        }
+        let now = Instant::now();
+        plugin
+            .meter
+            .admit(now, &self.config)
+            .map_err(|reason| plugin.call_error(reason))?;
+        let reply = plugin.call(command, deadline);
+        plugin.meter.record(now, now.elapsed(), &self.config);
+        reply
+    }
+
+    pub async fn call(&self, name: &str, command: &str) -> Result<String, WarpError> {
+        let mut plugins = self.plugins.lock().await;
+        let plugin = plugins
+            .get_mut(name)
+            .ok_or_else(|| WarpError::not_found("plugin", name))?;
+        self.metered_call(plugin, command, self.deadline())
+    }
+
+    // Like call, but trapping after `timeout` instead of the configured one
+    pub async fn call_with_timeout(
+        &self,
+        name: &str,
+        command: &str,
+        timeout: Duration,
+    ) -> Result<String, WarpError> {
+        let mut plugins = self.plugins.lock().await;
+        let plugin = plugins
+            .get_mut(name)
+            .ok_or_else(|| WarpError::not_found("plugin", name))?;
+        self.metered_call(plugin, command, deadline_for(timeout))
+    }
+
+    // Every plugin's reply to a command, in name order
+    pub async fn broadcast(&self, command: &str) -> Vec<(String, Result<String, WarpError>)> {
+        let mut plugins = self.plugins.lock().await;
+        let mut replies: Vec<_> = plugins
+            .iter_mut()
+            .map(|(name, plugin)| {
+                let reply = self.metered_call(plugin, command, self.deadline());
+                (name.clone(), reply)
+            })
+            .collect();
+        replies.sort_by(|a, b| a.0.cmp(&b.0));
+        replies
+    }
+
+    pub async fn usage(&self) -> Vec<PluginUsage> {
+        let now = Instant::now();
+        let mut plugins = self.plugins.lock().await;
+        let mut usage: Vec<_> = plugins
+            .iter_mut()
+            .map(|(name, plugin)| {
+                plugin.meter.prune(now);
+                PluginUsage {
+                    name: name.clone(),
+                    state: plugin.meter.state(&self.config),
+                    calls: plugin.meter.calls,
+                    calls_last_minute: plugin.meter.window.len(),
+                    cpu: plugin.meter.cpu,
+                    cpu_last_minute: plugin.meter.recent_cpu(),
+                    memory: plugin.memory.data_size(&plugin.store),
+                }
+            })
+            .collect();
+        usage.sort_by(|a, b| a.name.cmp(&b.name));
+        usage
+    }
+
+    // Suspending by hand or resuming either kind; false for an unknown plugin
+    pub async fn set_suspended(&self, name: &str, suspended: bool) -> bool {
+        let mut plugins = self.plugins.lock().await;
+        let Some(plugin) = plugins.get_mut(name) else {
+            return false;
+        };
+        plugin.meter.suspended = suspended.then_some(SuspendReason::User);
+        true
+    }
+
+    // Stops every plugin at once, including calls already running: the
+    // epoch is pushed past any deadline that could be set. Synchronous so
+    // it works while the runtime is stuck.
+    pub fn kill_all(&self) {
+        self.killed.store(true, Ordering::SeqCst);
+        for _ in 0..=self.deadline() {
+            self.engine.increment_epoch();
+        }
+    }
+
+    pub fn revive(&self) {
+        self.killed.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    pub async fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.plugins.lock().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // What each plugin has logged since the last drain
+    pub async fn drain_logs(&self) -> Vec<(String, Vec<String>)> {
+        let mut plugins = self.plugins.lock().await;
+        plugins
+            .iter_mut()
+            .filter(|(_, plugin)| !plugin.store.data().logs.is_empty())
+            .map(|(name, plugin)| {
+                (
+                    name.clone(),
+                    std::mem::take(&mut plugin.store.data_mut().logs),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+    use std::path::PathBuf;
+
+    // Module::from_file takes the text format as well as binaries.
+    // A counter plugin: each command bumps the count and the
+    // reply is one byte, '0' + count; the count survives a reload
+    const COUNTER: &str = r#"
+        (module
+          (import "warp" "log" (func $log (param i32 i32)))
+          (memory (export "memory") 1)
+          (global $count (mut i32) (i32.const 0))
+          (data (i32.const 16) "hello")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "on_command") (param i32 i32) (result i64)
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (call $log (i32.const 16) (i32.const 5))
+            (i32.store8 (i32.const 0) (i32.add (i32.const 48) (global.get $count)))
+            (i64.const 1))
+          (func (export "save_state") (result i64)
+            (i32.store8 (i32.const 8) (global.get $count))
+            (i64.const 0x0000000800000001))
+          (func (export "restore_state") (param i32 i32)
+            (global.set $count (i32.load8_u (local.get 0)))))
+    "#;
+
+    fn write_module(dir: &Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_call_and_reload_keeps_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_module(dir.path(), "counter.wasm", COUNTER);
+        let host = PluginHost::new(&WarpConfig::default().wasm).unwrap();
+        host.load("counter", &path).await.unwrap();
+        assert_eq!(host.call("counter", "tick").await.unwrap(), "1");
+        assert_eq!(host.call("counter", "tick").await.unwrap(), "2");
+        assert_eq!(
+            host.drain_logs().await,
+            vec![("counter".to_string(), vec!["hello".to_string(); 2])]
+        );
+
+        let outcome = host.reload("counter", &path).await.unwrap();
+        assert!(outcome.state_kept);
+        assert_eq!(host.call("counter", "tick").await.unwrap(), "3");
+    }
+
+    // Replies with the file named by the command, or '1' when refused and
+    // '2' when the read failed
+    const READER: &str = r#"
+        (module
+          (import "warp" "read_file" (func $read (param i32 i32) (result i64)))
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "alloc") (param $len i32) (result i32)
+            (global.get $next)
+            (global.set $next (i32.add (global.get $next) (local.get $len))))
+          (func (export "on_command") (param $ptr i32) (param $len i32) (result i64)
+            (local $reply i64)
+            (local.set $reply (call $read (local.get $ptr) (local.get $len)))
+            (if (i64.lt_s (local.get $reply) (i64.const 0))
+              (then
+                (i32.store8 (i32.const 0)
+                  (i32.sub (i32.const 48) (i32.wrap_i64 (local.get $reply))))
+                (return (i64.const 1))))
+            (local.get $reply)))
+    "#;
+
+    #[tokio::test]
+    async fn test_file_reads_need_a_grant() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_module(dir.path(), "reader.wasm", READER);
+        let notes = dir.path().join("notes.txt");
+        std::fs::write(&notes, "remember the milk").unwrap();
+        let notes = notes.canonicalize().unwrap();
+
+        let host = PluginHost::new(&WarpConfig::default().wasm).unwrap();
+        host.load("reader", &path).await.unwrap();
+        let command = notes.display().to_string();
+        assert_eq!(host.call("reader", &command).await.unwrap(), "1");
+        let pending = host.permissions().lock().unwrap().pending().to_vec();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].capability, Capability::Filesystem(notes.clone()));
+
+        host.permissions().lock().unwrap().allow(&pending[0]);
+        assert_eq!(
+            host.call("reader", &command).await.unwrap(),
+            "remember the milk"
+        );
+        assert_eq!(host.call("reader", "/no/such/file").await.unwrap(), "2");
+    }
+
+    #[test]
+    fn test_meter_throttles_and_suspends() {
+        let mut config = WarpConfig::default().wasm;
+        config.max_calls_per_minute = 2;
+        config.max_cpu_ms_per_minute = 100;
+        let start = Instant::now();
+        let mut meter = Meter::default();
+        for _ in 0..2 {
+            meter.admit(start, &config).unwrap();
+            meter.record(start, Duration::from_millis(10), &config);
+        }
+        assert_eq!(meter.state(&config), PluginState::Throttled);
+        assert!(meter.admit(start, &config).is_err());
+        assert!(meter.admit(start + METER_WINDOW, &config).is_ok());
+
+        meter.record(start + METER_WINDOW, Duration::from_millis(150), &config);
+        assert_eq!(
+            meter.state(&config),
+            PluginState::Suspended(SuspendReason::Cpu)
+        );
+        assert!(meter.admit(start + METER_WINDOW * 3, &config).is_err());
+        assert_eq!(meter.calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_usage_and_suspend() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_module(dir.path(), "counter.wasm", COUNTER);
+        let host = PluginHost::new(&WarpConfig::default().wasm).unwrap();
+        host.load("counter", &path).await.unwrap();
+        host.call("counter", "tick").await.unwrap();
+        let usage = host.usage().await;
+        assert_eq!(usage[0].calls, 1);
+        assert_eq!(usage[0].memory, 64 * 1024);
+
+        assert!(host.set_suspended("counter", true).await);
+        assert!(host.call("counter", "tick").await.is_err());
+        assert_eq!(
+            host.usage().await[0].state,
+            PluginState::Suspended(SuspendReason::User)
+        );
+        host.set_suspended("counter", false).await;
+        assert_eq!(host.call("counter", "tick").await.unwrap(), "2");
+    }
+
+    // The call blocks its worker thread, so the test needs another
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_kill_switch_stops_running_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let spin = write_module(
+            dir.path(),
+            "spin.wasm",
+            r#"(module
+                 (memory (export "memory") 1)
+                 (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                 (func (export "on_command") (param i32 i32) (result i64)
+                   (loop $l (br $l))
+                   (i64.const 0)))"#,
+        );
+        let host = Arc::new(PluginHost::new(&WarpConfig::default().wasm).unwrap());
+        host.load("spin", &spin).await.unwrap();
+        let running = {
+            let host = host.clone();
+            tokio::spawn(async move { host.call("spin", "go").await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let started = Instant::now();
+        host.kill_all();
+        assert!(running.await.unwrap().is_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(host.call("spin", "go").await.is_err());
+        assert!(host.is_killed());
+        host.revive();
+        assert!(!host.is_killed());
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_keeps_old_instance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_module(dir.path(), "counter.wasm", COUNTER);
+        let host = PluginHost::new(&WarpConfig::default().wasm).unwrap();
+        host.load("counter", &path).await.unwrap();
+        host.call("counter", "tick").await.unwrap();
+
+        std::fs::write(&path, b"not wasm").unwrap();
+        assert!(host.reload("counter", &path).await.is_err());
+        assert_eq!(host.call("counter", "tick").await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unknown_imports_and_runaway_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let host = PluginHost::new(&WarpConfig::default().wasm).unwrap();
+        let wasi = write_module(
+            dir.path(),
+            "wasi.wasm",
+            r#"(module (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32))))"#,
+        );
+        let error = host.load("wasi", &wasi).await.unwrap_err().to_string();
+        assert!(
+            error.contains("wasi_snapshot_preview1.fd_write"),
+            "{}",
+            error
+        );
+
+        let mut config = WarpConfig::default().wasm;
+        config.timeout = 0;
+        let host = PluginHost::new(&config).unwrap();
+        let spin = write_module(
+            dir.path(),
+            "spin.wasm",
+            r#"(module
+                 (memory (export "memory") 1)
+                 (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                 (func (export "on_command") (param i32 i32) (result i64)
+                   (loop $l (br $l))
+                   (i64.const 0)))"#,
+        );
+        host.load("spin", &spin).await.unwrap();
+        assert!(host.call("spin", "go").await.is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::error::WarpError;
+use crate::i18n;
+use crate::shutdown::write_atomic;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// What a plugin can be allowed to do through the host's imports
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "target", rename_all = "lowercase")]
+pub enum Capability {
+    Filesystem(PathBuf), // reading this file, or anything under this directory
+    Network(String),     // requests to this host
+    Blocks,              // reading command output
+}
+
+impl Capability {
+    pub fn covers(&self, requested: &Capability) -> bool {
+        match (self, requested) {
+            (Capability::Filesystem(granted), Capability::Filesystem(path)) => {
+                path.starts_with(granted)
+            }
+            (Capability::Network(granted), Capability::Network(host)) => {
+                granted.eq_ignore_ascii_case(host)
+            }
+            (Capability::Blocks, Capability::Blocks) => true,
+            _ => false,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Capability::Filesystem(path) => i18n::t_args(
+                "plugin_permissions.filesystem",
+                &[("path", &path.display().to_string())],
+            ),
+            Capability::Network(host) => {
+                i18n::t_args("plugin_permissions.network", &[("host", host)])
+            }
+            Capability::Blocks => i18n::t("plugin_permissions.blocks"),
+        }
+    }
+
+    // Untranslated, for the audit log
+    pub fn label(&self) -> String {
+        match self {
+            Capability::Filesystem(path) => format!("filesystem:{}", path.display()),
+            Capability::Network(host) => format!("network:{}", host),
+            Capability::Blocks => "blocks".to_string(),
+        }
+    }
+}
+
+// Requests a plugin can have waiting at once; past this further ones are
+// refused without queueing, so a plugin probing every path can't flood
+// the prompt
+const MAX_PENDING_PER_PLUGIN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PermissionRequest {
+    pub plugin: String,
+    pub capability: Capability,
+}
+
+// Grants per plugin, kept on disk, and the requests waiting for an answer.
+// A plugin that asks for something it hasn't been granted is refused and
+// the request queued for the prompt; one the user denied isn't asked again
+// this session.
+#[derive(Debug, Default)]
+pub struct Permissions {
+    grants: BTreeMap<String, BTreeSet<Capability>>,
+    pending: Vec<PermissionRequest>,
+    denied: HashSet<PermissionRequest>,
+}
+
+impl Permissions {
+    pub fn default_path() -> Result<PathBuf, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        Ok(data_dir.join("warp").join("plugin_permissions.json"))
+    }
+
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let grants = match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            grants,
+            ..Self::default()
+        })
+    }
+
+    // The grants as saved; serialized here so the caller can write them
+    // without holding the lock
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WarpError> {
+        Ok(serde_json::to_vec_pretty(&self.grants)?)
+    }
+
+    pub async fn save(bytes: &[u8], path: &Path) -> Result<(), WarpError> {
+        write_atomic(path, bytes).await
+    }
+
+    pub fn check(&mut self, plugin: &str, capability: Capability) -> bool {
+        let granted = self
+            .grants
+            .get(plugin)
+            .is_some_and(|grants| grants.iter().any(|grant| grant.covers(&capability)));
+        if !granted {
+            let request = PermissionRequest {
+                plugin: plugin.to_string(),
+                capability,
+            };
+            let queued = self
+                .pending
+                .iter()
+                .filter(|pending| pending.plugin == plugin)
+                .count();
+            if !self.denied.contains(&request)
+                && !self.pending.contains(&request)
+                && queued < MAX_PENDING_PER_PLUGIN
+            {
+                self.pending.push(request);
+            }
+        }
+        granted
+    }
+
+    pub fn pending(&self) -> &[PermissionRequest] {
+        &self.pending
+    }
+
+    pub fn granted(&self) -> impl Iterator<Item = (&str, &Capability)> {
+        self.grants.iter().flat_map(|(plugin, grants)| {
+            grants
+                .iter()
+                .map(move |capability| (plugin.as_str(), capability))
+        })
+    }
+
+    pub fn allow(&mut self, request: &PermissionRequest) {
+        self.pending.retain(|pending| pending != request);
+        self.grants
+            .entry(request.plugin.clone())
+            .or_default()
+            .insert(request.capability.clone());
+    }
+
+    pub fn deny(&mut self, request: &PermissionRequest) {
+        self.pending.retain(|pending| pending != request);
+        self.denied.insert(request.clone());
+    }
+
+    pub fn revoke(&mut self, plugin: &str, capability: &Capability) {
+        if let Some(grants) = self.grants.get_mut(plugin) {
+            grants.remove(capability);
+            if grants.is_empty() {
+                self.grants.remove(plugin);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionCommand {
+    None,
+    Allow(PermissionRequest),
+    Deny(PermissionRequest),
+    Revoke(PermissionRequest),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    Pending(PermissionRequest),
+    Granted(PermissionRequest),
+}
+
+// The prompt for waiting requests, listed first, and the grants below them
+// with revoke
+pub struct PermissionsPanel {
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+impl PermissionsPanel {
+    pub fn new(permissions: &Permissions) -> Self {
+        let pending = permissions.pending().iter().cloned().map(Row::Pending);
+        let granted = permissions.granted().map(|(plugin, capability)| {
+            Row::Granted(PermissionRequest {
+                plugin: plugin.to_string(),
+                capability: capability.clone(),
+            })
+        });
+        Self {
+            rows: pending.chain(granted).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> PermissionCommand {
+        self.select(index);
+        match (key, self.rows.get(self.selected)) {
+            ('y', Some(Row::Pending(request))) => PermissionCommand::Allow(request.clone()),
+            ('n', Some(Row::Pending(request))) => PermissionCommand::Deny(request.clone()),
+            ('x', Some(Row::Granted(grant))) => PermissionCommand::Revoke(grant.clone()),
+            _ => PermissionCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Pending(request) => PanelItem::styled(vec![
+                    (format!("{} ", request.plugin), Some(Color::Yellow)),
+                    (
+                        i18n::t_args(
+                            "plugin_permissions.asks",
+                            &[("capability", &request.capability.describe())],
+                        ),
+                        None,
+                    ),
+                ]),
+                Row::Granted(grant) => PanelItem::styled(vec![
+                    (format!("{} ", grant.plugin), Some(Color::Cyan)),
+                    (grant.capability.describe(), None),
+                ]),
+            })
+            .collect();
+        let preview = match self.rows.get(self.selected) {
+            Some(Row::Pending(_)) => vec![i18n::t("plugin_permissions.pending_hint").into()],
+            Some(Row::Granted(_)) => vec![i18n::t("plugin_permissions.granted_hint").into()],
+            None => vec![i18n::t("plugin_permissions.empty").into()],
+        };
+        let pending = self
+            .rows
+            .iter()
+            .filter(|row| matches!(row, Row::Pending(_)))
+            .count();
+        PanelView {
+            kind: PanelKind::PluginPermissions,
+            title: i18n::t_args(
+                "panel.plugin_permissions",
+                &[("pending", &pending.to_string())],
+            ),
+            items,
+            footer: Some(i18n::t("panel.plugin_permissions_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(plugin: &str, capability: Capability) -> PermissionRequest {
+        PermissionRequest {
+            plugin: plugin.to_string(),
+            capability,
+        }
+    }
+
+    #[test]
+    fn test_refused_until_allowed() {
+        let mut permissions = Permissions::default();
+        let home = Capability::Filesystem(PathBuf::from("/home/me/notes"));
+        assert!(!permissions.check("notes", home.clone()));
+        assert!(!permissions.check("notes", home.clone()));
+        assert_eq!(permissions.pending(), &[request("notes", home.clone())]);
+
+        permissions.allow(&request("notes", home));
+        assert!(permissions.pending().is_empty());
+        assert!(permissions.check(
+            "notes",
+            Capability::Filesystem(PathBuf::from("/home/me/notes/today.md"))
+        ));
+        assert!(!permissions.check(
+            "notes",
+            Capability::Filesystem(PathBuf::from("/home/me/.ssh/id_ed25519"))
+        ));
+        assert!(!permissions.check("other", Capability::Blocks));
+    }
+
+    #[test]
+    fn test_denied_requests_are_not_asked_again() {
+        let mut permissions = Permissions::default();
+        let api = Capability::Network("api.example.com".to_string());
+        permissions.check("weather", api.clone());
+        permissions.deny(&request("weather", api.clone()));
+        assert!(!permissions.check("weather", api));
+        assert!(permissions.pending().is_empty());
+    }
+
+    #[test]
+    fn test_pending_requests_are_capped_per_plugin() {
+        let mut permissions = Permissions::default();
+        for i in 0..MAX_PENDING_PER_PLUGIN + 4 {
+            permissions.check(
+                "crawler",
+                Capability::Network(format!("host{}.example.com", i)),
+            );
+        }
+        permissions.check("notes", Capability::Blocks);
+        assert_eq!(permissions.pending().len(), MAX_PENDING_PER_PLUGIN + 1);
+
+        permissions.deny(&request(
+            "crawler",
+            Capability::Network("host0.example.com".to_string()),
+        ));
+        permissions.check(
+            "crawler",
+            Capability::Network("late.example.com".to_string()),
+        );
+        assert!(permissions.pending().contains(&request(
+            "crawler",
+            Capability::Network("late.example.com".to_string())
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_grants_persist_and_revoke() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plugin_permissions.json");
+        let mut permissions = Permissions::default();
+        permissions.allow(&request(
+            "weather",
+            Capability::Network("API.example.com".to_string()),
+        ));
+        permissions.allow(&request("weather", Capability::Blocks));
+        Permissions::save(&permissions.to_bytes().unwrap(), &path)
+            .await
+            .unwrap();
+
+        let mut loaded = Permissions::load(&path).await.unwrap();
+        assert_eq!(loaded.granted().count(), 2);
+        assert!(loaded.check(
+            "weather",
+            Capability::Network("api.example.com".to_string())
+        ));
+        loaded.revoke("weather", &Capability::Blocks);
+        assert!(!loaded.check("weather", Capability::Blocks));
+        assert!(Permissions::load(&dir.path().join("missing.json"))
+            .await
+            .unwrap()
+            .granted()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn test_panel_keys() {
+        let mut permissions = Permissions::default();
+        permissions.allow(&request("weather", Capability::Blocks));
+        permissions.check("notes", Capability::Blocks);
+        let mut panel = PermissionsPanel::new(&permissions);
+        assert_eq!(panel.view().items.len(), 2);
+        assert_eq!(
+            panel.handle_key('y', 0),
+            PermissionCommand::Allow(request("notes", Capability::Blocks))
+        );
+        assert_eq!(panel.handle_key('x', 0), PermissionCommand::None);
+        assert_eq!(
+            panel.handle_key('x', 1),
+            PermissionCommand::Revoke(request("weather", Capability::Blocks))
+        );
+    }
+}
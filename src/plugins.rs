@@ -2,7 +2,10 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::{config::Config, error::WarpError};
+use crate::{
+    config::{Config, CustomCommand},
+    error::WarpError,
+};
 
 pub struct PluginManager {
     config: Arc<Mutex<Config>>,
@@ -13,6 +16,11 @@ pub trait Plugin: Send + Sync {
     fn name(&self) -> &str;
     fn version(&self) -> &str;
     fn execute(&self, command: &str) -> Result<String, WarpError>;
+
+    // `warp <name>` subcommands the plugin adds to the CLI
+    fn commands(&self) -> Vec<CustomCommand> {
+        Vec::new()
+    }
 }
 
 impl PluginManager {
@@ -27,4 +35,19 @@ impl PluginManager {
         // Load and initialize plugins
         Ok(())
     }
+
+    pub fn custom_commands(&self) -> Vec<CustomCommand> {
+        self.loaded_plugins
+            .values()
+            .flat_map(|plugin| plugin.commands())
+            .collect()
+    }
+
+    pub async fn stop(&self) -> Result<(), WarpError> {
+        // Give loaded plugins a chance to release resources before exit
+        for name in self.loaded_plugins.keys() {
+            log::debug!("Unloading plugin {}", name);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,351 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::process::Stdio;
+use tokio::process::Command;
+
+use crate::error::WarpError;
+use crate::network_inspector::{parse_hex_addr, parse_socket_inode};
+use crate::process_tree::{build_tree, snapshot};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListeningPort {
+    pub address: SocketAddr,
+    pub pid: Option<u32>,
+    pub process_name: Option<String>,
+    pub first_seen: chrono::DateTime<chrono::Utc>,
+    pub is_new: bool,       // appeared after the watcher started
+    pub from_session: bool, // owned by a process started from one of our panes
+}
+
+impl ListeningPort {
+    pub fn port(&self) -> u16 {
+        self.address.port()
+    }
+
+    // Wildcard binds are reachable on localhost, which is what users want to open
+    pub fn url(&self) -> String {
+        let host = match self.address.ip() {
+            ip if ip.is_unspecified() || ip.is_loopback() => "localhost".to_string(),
+            IpAddr::V6(v6) => format!("[{}]", v6),
+            IpAddr::V4(v4) => v4.to_string(),
+        };
+        format!("http://{}:{}", host, self.port())
+    }
+
+    pub fn display_line(&self) -> String {
+        format!(
+            "{} {:<22} {:>7} {}",
+            if self.from_session && self.is_new {
+                "★"
+            } else {
+                " "
+            },
+            self.address,
+            self.pid
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            self.process_name.as_deref().unwrap_or("?")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortAction {
+    KillProcess,
+    CopyUrl,
+    OpenInBrowser,
+}
+
+impl PortAction {
+    // Panel keybindings: k/y/o
+    pub fn from_key(key: char) -> Option<Self> {
+        match key {
+            'k' => Some(PortAction::KillProcess),
+            'y' => Some(PortAction::CopyUrl),
+            'o' => Some(PortAction::OpenInBrowser),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PortsDiff {
+    pub opened: Vec<ListeningPort>,
+    pub closed: Vec<SocketAddr>,
+}
+
+pub struct PortWatcher {
+    system: sysinfo::System,
+    ports: Vec<ListeningPort>,
+    baseline: Option<HashSet<SocketAddr>>,
+}
+
+impl PortWatcher {
+    pub fn new() -> Self {
+        Self {
+            system: sysinfo::System::new(),
+            ports: Vec::new(),
+            baseline: None,
+        }
+    }
+
+    pub fn ports(&self) -> &[ListeningPort] {
+        &self.ports
+    }
+
+    // Ports owned by descendants of this process (our shells and whatever
+    // they ran) are attributed to the session. The first refresh sets the
+    // baseline, so nothing is "new" on start.
+    pub async fn refresh(&mut self) -> Result<PortsDiff, WarpError> {
+        let listeners = list_listeners().await?;
+        self.system.refresh_processes();
+        let session_pids = self.session_pids();
+
+        let previous: HashMap<SocketAddr, ListeningPort> =
+            self.ports.drain(..).map(|p| (p.address, p)).collect();
+        let baseline = self
+            .baseline
+            .get_or_insert_with(|| listeners.iter().map(|(addr, _)| *addr).collect())
+            .clone();
+
+        let now = chrono::Utc::now();
+        let mut diff = PortsDiff::default();
+        for (address, pid) in listeners {
+            let port = match previous.get(&address) {
+                Some(existing) => ListeningPort {
+                    pid: pid.or(existing.pid),
+                    ..existing.clone()
+                },
+                None => {
+                    let port = ListeningPort {
+                        address,
+                        pid,
+                        process_name: pid
+                            .and_then(|p| self.system.process(sysinfo::Pid::from_u32(p)))
+                            .map(|p| p.name().to_string()),
+                        first_seen: now,
+                        is_new: !baseline.contains(&address),
+                        from_session: pid.is_some_and(|p| session_pids.contains(&p)),
+                    };
+                    if port.is_new {
+                        diff.opened.push(port.clone());
+                    }
+                    port
+                }
+            };
+            self.ports.push(port);
+        }
+        diff.closed = previous
+            .keys()
+            .filter(|addr| !self.ports.iter().any(|p| p.address == **addr))
+            .copied()
+            .collect();
+
+        self.ports
+            .sort_by_key(|p| (!(p.from_session && p.is_new), p.port()));
+        Ok(diff)
+    }
+
+    fn session_pids(&self) -> HashSet<u32> {
+        build_tree(&snapshot(&self.system), std::process::id())
+            .into_iter()
+            .skip(1) // ourselves
+            .map(|node| node.info.pid)
+            .collect()
+    }
+
+    pub fn status_summary(&self) -> Option<String> {
+        let ports: Vec<String> = self
+            .ports
+            .iter()
+            .filter(|p| p.from_session && p.is_new)
+            .map(|p| format!(":{}", p.port()))
+            .collect();
+        if ports.is_empty() {
+            None
+        } else {
+            Some(format!("⇡ {}", ports.join(" ")))
+        }
+    }
+
+    pub async fn perform(&self, index: usize, action: PortAction) -> Result<(), WarpError> {
+        let port = self
+            .ports
+            .get(index)
+            .ok_or_else(|| WarpError::not_found("port", index.to_string()))?;
+        match action {
+            PortAction::KillProcess => {
+                let pid = port.pid.ok_or_else(|| {
+                    WarpError::Permission(format!("Owner of {} is not visible", port.address))
+                })?;
+                let process = self
+                    .system
+                    .process(sysinfo::Pid::from_u32(pid))
+                    .ok_or_else(|| WarpError::not_found("process", pid.to_string()))?;
+                if !process.kill_with(sysinfo::Signal::Term).unwrap_or(false) {
+                    return Err(WarpError::Permission(format!(
+                        "Could not terminate {}",
+                        pid
+                    )));
+                }
+                Ok(())
+            }
+            PortAction::CopyUrl => copy_to_clipboard(&port.url()),
+            PortAction::OpenInBrowser => open_url(&port.url()).await,
+        }
+    }
+}
+
+impl Default for PortWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// OSC 52 asks the hosting terminal to set the clipboard, which also works
+// over SSH where no local clipboard API is reachable
+pub fn copy_to_clipboard(text: &str) -> Result<(), WarpError> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+pub async fn open_url(url: &str) -> Result<(), WarpError> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+async fn list_listeners() -> Result<Vec<(SocketAddr, Option<u32>)>, WarpError> {
+    if std::path::Path::new("/proc/net/tcp").exists() {
+        return Ok(list_listeners_procfs().await);
+    }
+    let output = Command::new("lsof")
+        .args(["-nP", "-iTCP", "-sTCP:LISTEN", "-F", "pn"])
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| WarpError::command_err(format!("lsof is required to list ports: {}", e)))?;
+    Ok(parse_lsof_listeners(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+// Sockets owned by other users still show up, just without a pid
+async fn list_listeners_procfs() -> Vec<(SocketAddr, Option<u32>)> {
+    let mut owners: HashMap<u64, u32> = HashMap::new();
+    if let Ok(mut procs) = tokio::fs::read_dir("/proc").await {
+        while let Ok(Some(entry)) = procs.next_entry().await {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let Ok(mut fds) = tokio::fs::read_dir(entry.path().join("fd")).await else {
+                continue;
+            };
+            while let Ok(Some(fd)) = fds.next_entry().await {
+                if let Ok(target) = tokio::fs::read_link(fd.path()).await {
+                    if let Some(inode) = parse_socket_inode(&target.to_string_lossy()) {
+                        owners.insert(inode, pid);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut listeners = Vec::new();
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(content) = tokio::fs::read_to_string(table).await {
+            for (address, inode) in parse_proc_listeners(&content) {
+                if !listeners.iter().any(|(a, _)| *a == address) {
+                    listeners.push((address, owners.get(&inode).copied()));
+                }
+            }
+        }
+    }
+    listeners
+}
+
+fn parse_proc_listeners(content: &str) -> Vec<(SocketAddr, u64)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // 0A = LISTEN
+            if fields.len() < 10 || fields[3] != "0A" {
+                return None;
+            }
+            Some((parse_hex_addr(fields[1])?, fields[9].parse().ok()?))
+        })
+        .collect()
+}
+
+fn parse_lsof_listeners(output: &str) -> Vec<(SocketAddr, Option<u32>)> {
+    let mut pid = None;
+    let mut listeners = Vec::new();
+    for line in output.lines() {
+        if let Some(p) = line.strip_prefix('p') {
+            pid = p.parse().ok();
+        } else if let Some(name) = line.strip_prefix('n') {
+            // lsof prints wildcard binds as *:port
+            let name = name.replacen("*:", "0.0.0.0:", 1);
+            if let Ok(address) = name.parse() {
+                listeners.push((address, pid));
+            }
+        }
+    }
+    listeners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_listeners_only_listen_state() {
+        let content = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n   0: 00000000:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 123 1\n   1: 0200000A:C350 22D8B85D:01BB 01 00000000:00000000 00:00000000 00000000  1000        0 456 1\n";
+        let listeners = parse_proc_listeners(content);
+        assert_eq!(listeners, vec![("0.0.0.0:3000".parse().unwrap(), 123)]);
+    }
+
+    #[test]
+    fn test_parse_lsof_wildcard() {
+        let listeners = parse_lsof_listeners("p77\nn*:8080\nn127.0.0.1:5432\n");
+        assert_eq!(listeners[0], ("0.0.0.0:8080".parse().unwrap(), Some(77)));
+        assert_eq!(listeners[1].0.port(), 5432);
+    }
+
+    #[test]
+    fn test_url_uses_localhost_for_wildcard() {
+        let port = ListeningPort {
+            address: "0.0.0.0:3000".parse().unwrap(),
+            pid: None,
+            process_name: None,
+            first_seen: chrono::Utc::now(),
+            is_new: true,
+            from_session: true,
+        };
+        assert_eq!(port.url(), "http://localhost:3000");
+    }
+}
@@ -0,0 +1,533 @@
+use crossterm::style::Color;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{ProblemMatcherConfig, ProblemSeverity};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::locations::{resolve, source_preview};
+use crate::pager::parse_ansi;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub source: String, // matcher that found it: cargo, tsc, or a configured name
+    pub severity: ProblemSeverity,
+    pub path: PathBuf, // absolute
+    pub display: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub message: String,
+    pub code: Option<String>, // E0425, TS2322, no-unused-vars, ...
+}
+
+// Tools spell these differently: "fatal error", "warn", "E", ...
+pub fn parse_severity(text: &str) -> ProblemSeverity {
+    let text = text.trim().to_ascii_lowercase();
+    if text.contains("err") || text == "e" || text == "fatal" {
+        ProblemSeverity::Error
+    } else if text.starts_with("warn") || text == "w" {
+        ProblemSeverity::Warning
+    } else {
+        ProblemSeverity::Note
+    }
+}
+
+// A user-defined single-line matcher
+pub struct ProblemMatcher {
+    name: String,
+    pattern: Regex,
+    severity: ProblemSeverity,
+}
+
+impl ProblemMatcher {
+    pub fn from_config(config: &ProblemMatcherConfig) -> Result<Self, WarpError> {
+        let pattern = Regex::new(&config.pattern).map_err(|e| {
+            WarpError::config_err(format!("Invalid problem matcher '{}': {}", config.name, e))
+        })?;
+        let groups: Vec<&str> = pattern.capture_names().flatten().collect();
+        if let Some(missing) = ["file", "line", "message"]
+            .into_iter()
+            .find(|group| !groups.contains(group))
+        {
+            return Err(WarpError::config_err(format!(
+                "Problem matcher '{}' has no `{}` group",
+                config.name, missing
+            )));
+        }
+        Ok(Self {
+            name: config.name.clone(),
+            pattern,
+            severity: config.severity,
+        })
+    }
+
+    fn parse(&self, line: &str, cwd: &Path) -> Option<Problem> {
+        let captures = self.pattern.captures(line)?;
+        let group = |name: &str| captures.name(name).map(|m| m.as_str().trim());
+        let severity = group("severity").map_or(self.severity, parse_severity);
+        problem(
+            &self.name,
+            severity,
+            group("file")?,
+            group("line")?,
+            group("column"),
+            group("message")?,
+            group("code"),
+            cwd,
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn problem(
+    source: &str,
+    severity: ProblemSeverity,
+    file: &str,
+    line: &str,
+    column: Option<&str>,
+    message: &str,
+    code: Option<&str>,
+    cwd: &Path,
+) -> Option<Problem> {
+    let line = line.parse::<u32>().ok().filter(|line| *line > 0)?;
+    Some(Problem {
+        source: source.to_string(),
+        severity,
+        path: resolve(file, cwd),
+        display: file.to_string(),
+        line,
+        column: column.and_then(|column| column.parse().ok()),
+        message: message.trim().to_string(),
+        code: code.filter(|code| !code.is_empty()).map(str::to_string),
+    })
+}
+
+struct Patterns {
+    cargo_header: Regex,
+    cargo_location: Regex,
+    gcc: Regex,
+    tsc: Regex,
+    tsc_pretty: Regex,
+    eslint_file: Regex,
+    eslint_entry: Regex,
+    pytest_error: Regex,
+    pytest_location: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let regex = |pattern: &str| Regex::new(pattern).expect("static pattern");
+        Patterns {
+            // error[E0425]: cannot find value `x` in this scope
+            cargo_header: regex(r"^(error|warning)(?:\[(\w+)\])?: (.+)$"),
+            //   --> src/main.rs:4:5
+            cargo_location: regex(r"^\s*--> (.+?):(\d+):(\d+)$"),
+            // main.c:3:10: fatal error: foo.h: No such file or directory
+            gcc: regex(r"^(\S[^:]*):(\d+):(\d+): (fatal error|error|warning|note): (.+)$"),
+            // src/app.ts(12,5): error TS2322: Type 'string' is not ...
+            tsc: regex(r"^(\S.*?)\((\d+),(\d+)\): (error|warning) (TS\d+): (.+)$"),
+            // src/app.ts:12:5 - error TS2322: Type 'string' is not ...
+            tsc_pretty: regex(r"^(\S.*?):(\d+):(\d+) - (error|warning) (TS\d+): (.+)$"),
+            // eslint's stylish format: the file on its own line, then entries
+            eslint_file: regex(r"^(/|\./|[A-Za-z]:\\)?\S+\.[cm]?[jt]sx?$"),
+            eslint_entry: regex(r"^\s+(\d+):(\d+)\s+(error|warning)\s+(.+?)(?:\s{2,}(\S+))?$"),
+            // E       assert 1 == 2
+            pytest_error: regex(r"^E\s+(.+)$"),
+            // tests/test_app.py:12: AssertionError
+            pytest_location: regex(r"^(\S+\.py):(\d+): (\w+)$"),
+        }
+    })
+}
+
+// Built-in parsers. Some formats span lines, so the parser carries what it
+// has seen so far from one line to the next.
+#[derive(Default)]
+struct BuiltinState {
+    cargo: Option<(ProblemSeverity, Option<String>, String)>,
+    eslint_file: Option<String>,
+    pytest_message: Option<String>,
+}
+
+impl BuiltinState {
+    fn parse(&mut self, line: &str, cwd: &Path) -> Option<Problem> {
+        let p = patterns();
+        if let Some(c) = p.cargo_header.captures(line) {
+            self.cargo = Some((
+                parse_severity(&c[1]),
+                c.get(2).map(|code| code.as_str().to_string()),
+                c[3].to_string(),
+            ));
+            return None;
+        }
+        if let Some(c) = p.cargo_location.captures(line) {
+            let (severity, code, message) = self.cargo.take()?;
+            return problem(
+                "cargo",
+                severity,
+                &c[1],
+                &c[2],
+                Some(&c[3]),
+                &message,
+                code.as_deref(),
+                cwd,
+            );
+        }
+        if let Some(c) = p.tsc.captures(line).or_else(|| p.tsc_pretty.captures(line)) {
+            let severity = parse_severity(&c[4]);
+            return problem(
+                "tsc",
+                severity,
+                &c[1],
+                &c[2],
+                Some(&c[3]),
+                &c[6],
+                Some(&c[5]),
+                cwd,
+            );
+        }
+        if let Some(c) = p.gcc.captures(line) {
+            let severity = parse_severity(&c[4]);
+            return problem("gcc", severity, &c[1], &c[2], Some(&c[3]), &c[5], None, cwd);
+        }
+        if let Some(c) = p.pytest_error.captures(line) {
+            // Only the first E line: later ones are the diff detail
+            self.pytest_message.get_or_insert_with(|| c[1].to_string());
+            return None;
+        }
+        if let Some(c) = p.pytest_location.captures(line) {
+            let message = match self.pytest_message.take() {
+                Some(message) => format!("{}: {}", &c[3], message),
+                None => c[3].to_string(),
+            };
+            return problem(
+                "pytest",
+                ProblemSeverity::Error,
+                &c[1],
+                &c[2],
+                None,
+                &message,
+                None,
+                cwd,
+            );
+        }
+        if p.eslint_file.is_match(line) {
+            self.eslint_file = Some(line.trim().to_string());
+            return None;
+        }
+        if let Some(c) = p.eslint_entry.captures(line) {
+            let file = self.eslint_file.as_deref()?;
+            let severity = parse_severity(&c[3]);
+            let code = c.get(5).map(|code| code.as_str());
+            return problem(
+                "eslint",
+                severity,
+                file,
+                &c[1],
+                Some(&c[2]),
+                &c[4],
+                code,
+                cwd,
+            );
+        }
+        if line.trim().is_empty() {
+            self.eslint_file = None;
+        }
+        None
+    }
+}
+
+pub struct ProblemParser {
+    matchers: Vec<ProblemMatcher>,
+}
+
+impl ProblemParser {
+    pub fn new(configs: &[ProblemMatcherConfig]) -> Result<Self, WarpError> {
+        let matchers = configs
+            .iter()
+            .map(ProblemMatcher::from_config)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { matchers })
+    }
+
+    pub fn builtin() -> Self {
+        Self {
+            matchers: Vec::new(),
+        }
+    }
+
+    // Problems in `lines`, in output order without repeats. Configured
+    // matchers are tried first so they can claim lines a built-in would
+    // misread.
+    pub fn parse(&self, lines: &[String], cwd: &Path) -> Vec<Problem> {
+        let mut state = BuiltinState::default();
+        let mut problems: Vec<Problem> = Vec::new();
+        for raw in lines {
+            let line: String = parse_ansi(raw).into_iter().map(|(text, _)| text).collect();
+            let found = self
+                .matchers
+                .iter()
+                .find_map(|matcher| matcher.parse(&line, cwd))
+                .or_else(|| state.parse(&line, cwd));
+            if let Some(found) = found {
+                if !problems.contains(&found) {
+                    problems.push(found);
+                }
+            }
+        }
+        problems
+    }
+}
+
+pub enum ProblemCommand {
+    None,
+    Redraw,
+    Rescan,
+    Open(Problem),
+}
+
+pub struct ProblemsPanel {
+    cwd: PathBuf,
+    problems: Vec<Problem>,
+    // Only problems of this severity are listed; None lists all
+    filter: Option<ProblemSeverity>,
+    selected: usize,
+    preview: Vec<PanelItem>,
+}
+
+impl ProblemsPanel {
+    pub fn new(parser: &ProblemParser, lines: &[String], cwd: PathBuf) -> Self {
+        let mut panel = Self {
+            cwd,
+            problems: Vec::new(),
+            filter: None,
+            selected: 0,
+            preview: Vec::new(),
+        };
+        panel.rescan(parser, lines);
+        panel
+    }
+
+    pub fn rescan(&mut self, parser: &ProblemParser, lines: &[String]) {
+        self.problems = parser.parse(lines, &self.cwd);
+        self.select(0);
+    }
+
+    fn visible(&self) -> Vec<&Problem> {
+        self.problems
+            .iter()
+            .filter(|problem| {
+                self.filter
+                    .is_none_or(|severity| problem.severity == severity)
+            })
+            .collect()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        let visible = self.visible();
+        let selected = index.min(visible.len().saturating_sub(1));
+        let preview = visible
+            .get(selected)
+            .map(|problem| source_preview(&problem.path, problem.line))
+            .unwrap_or_default();
+        self.selected = selected;
+        self.preview = preview;
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> ProblemCommand {
+        self.select(index);
+        let filter = match key {
+            '\n' | 'o' => {
+                return match self.visible().get(self.selected) {
+                    Some(problem) => ProblemCommand::Open((*problem).clone()),
+                    None => ProblemCommand::None,
+                }
+            }
+            'r' => return ProblemCommand::Rescan,
+            'e' => Some(ProblemSeverity::Error),
+            'w' => Some(ProblemSeverity::Warning),
+            'n' => Some(ProblemSeverity::Note),
+            'a' => None,
+            _ => return ProblemCommand::None,
+        };
+        // Pressing the active filter again clears it
+        self.filter = if self.filter == filter { None } else { filter };
+        self.select(0);
+        ProblemCommand::Redraw
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .visible()
+            .into_iter()
+            .map(|problem| {
+                let (marker, color) = match problem.severity {
+                    ProblemSeverity::Error => ("✖", Color::Red),
+                    ProblemSeverity::Warning => ("▲", Color::Yellow),
+                    ProblemSeverity::Note => ("●", Color::Blue),
+                };
+                let location = match problem.column {
+                    Some(column) => format!("{}:{}:{}", problem.display, problem.line, column),
+                    None => format!("{}:{}", problem.display, problem.line),
+                };
+                let message = match &problem.code {
+                    Some(code) => format!("  {} [{}]", problem.message, code),
+                    None => format!("  {}", problem.message),
+                };
+                PanelItem::styled(vec![
+                    (format!("{} ", marker), Some(color)),
+                    (location, Some(Color::Blue)),
+                    (message, None),
+                ])
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.problems_empty").into());
+        }
+        let count = |severity: ProblemSeverity| {
+            self.problems
+                .iter()
+                .filter(|p| p.severity == severity)
+                .count()
+                .to_string()
+        };
+        let filter = match self.filter {
+            Some(ProblemSeverity::Error) => i18n::t("problems.filter_errors"),
+            Some(ProblemSeverity::Warning) => i18n::t("problems.filter_warnings"),
+            Some(ProblemSeverity::Note) => i18n::t("problems.filter_notes"),
+            None => i18n::t("problems.filter_all"),
+        };
+        PanelView {
+            kind: PanelKind::Problems,
+            title: i18n::t_args(
+                "panel.problems",
+                &[
+                    ("errors", &count(ProblemSeverity::Error)),
+                    ("warnings", &count(ProblemSeverity::Warning)),
+                    ("filter", &filter),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.problems_keys")),
+            preview: self.preview.clone(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn summary(problems: &[Problem]) -> Vec<(&str, ProblemSeverity, &str, u32, Option<&str>)> {
+        problems
+            .iter()
+            .map(|p| {
+                (
+                    p.source.as_str(),
+                    p.severity,
+                    p.display.as_str(),
+                    p.line,
+                    p.code.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_parsers() {
+        let output = lines(
+            "   Compiling app v0.1.0\n\
+             \x1b[1m\x1b[31merror[E0425]\x1b[0m: cannot find value `x` in this scope\n\
+             \x20 --> src/main.rs:4:5\n\
+             warning: unused import\n\
+             \x20 --> src/lib.rs:1:5\n\
+             warning: `app` (bin) generated 1 warning\n\
+             src/app.ts(12,5): error TS2322: Type 'string' is not assignable\n\
+             web/index.ts:3:1 - warning TS6133: 'x' is declared but never read\n\
+             main.c:3:10: fatal error: foo.h: No such file or directory\n\
+             /repo/web/app.js\n\
+             \x20 2:7  error    'y' is not defined  no-undef\n\
+             \x20 9:1  warning  Unexpected console statement  no-console\n\
+             \n\
+             E       assert 1 == 2\n\
+             E        +  where 1 = f()\n\
+             tests/test_app.py:12: AssertionError",
+        );
+        let problems = ProblemParser::builtin().parse(&output, Path::new("/repo"));
+        use ProblemSeverity::*;
+        assert_eq!(
+            summary(&problems),
+            vec![
+                ("cargo", Error, "src/main.rs", 4, Some("E0425")),
+                ("cargo", Warning, "src/lib.rs", 1, None),
+                ("tsc", Error, "src/app.ts", 12, Some("TS2322")),
+                ("tsc", Warning, "web/index.ts", 3, Some("TS6133")),
+                ("gcc", Error, "main.c", 3, None),
+                ("eslint", Error, "/repo/web/app.js", 2, Some("no-undef")),
+                ("eslint", Warning, "/repo/web/app.js", 9, Some("no-console")),
+                ("pytest", Error, "tests/test_app.py", 12, None),
+            ]
+        );
+        assert_eq!(problems[0].path, PathBuf::from("/repo/src/main.rs"));
+        assert_eq!(problems[0].message, "cannot find value `x` in this scope");
+        assert_eq!(problems[5].message, "'y' is not defined");
+        assert_eq!(problems[7].message, "AssertionError: assert 1 == 2");
+    }
+
+    #[test]
+    fn test_configured_matcher() {
+        let config = ProblemMatcherConfig {
+            name: "shellcheck".to_string(),
+            pattern: r"^In (?P<file>\S+) line (?P<line>\d+): (?P<message>.+) \[(?P<code>SC\d+)\]$"
+                .to_string(),
+            severity: ProblemSeverity::Warning,
+        };
+        let parser = ProblemParser::new(&[config.clone()]).unwrap();
+        let problems = parser.parse(
+            &lines("In deploy.sh line 7: Quote this [SC2086]"),
+            Path::new("/repo"),
+        );
+        assert_eq!(
+            summary(&problems),
+            vec![(
+                "shellcheck",
+                ProblemSeverity::Warning,
+                "deploy.sh",
+                7,
+                Some("SC2086")
+            )]
+        );
+
+        let invalid = ProblemMatcherConfig {
+            pattern: r"(?P<file>\S+):(?P<line>\d+)".to_string(),
+            ..config
+        };
+        assert!(ProblemParser::new(&[invalid]).is_err());
+    }
+
+    #[test]
+    fn test_panel_filters_by_severity() {
+        let output = lines("a.c:1:1: error: one\na.c:2:1: warning: two\na.c:3:1: error: three");
+        let mut panel =
+            ProblemsPanel::new(&ProblemParser::builtin(), &output, PathBuf::from("/repo"));
+        assert_eq!(panel.view().items.len(), 3);
+
+        assert!(matches!(panel.handle_key('w', 0), ProblemCommand::Redraw));
+        assert_eq!(panel.view().items.len(), 1);
+        match panel.handle_key('\n', 0) {
+            ProblemCommand::Open(problem) => assert_eq!(problem.message, "two"),
+            _ => panic!("expected Open"),
+        }
+        panel.handle_key('w', 0);
+        assert_eq!(panel.view().items.len(), 3);
+        panel.handle_key('e', 0);
+        assert_eq!(panel.view().items.len(), 2);
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use sysinfo::{Pid, ProcessRefreshKind, System, UpdateKind};
+
+use crate::error::WarpError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProcessSignal {
+    Term,
+    Kill,
+    Stop,
+    Cont,
+}
+
+impl ProcessSignal {
+    // Panel keybindings: t/k/s/c
+    pub fn from_key(key: char) -> Option<Self> {
+        match key {
+            't' => Some(ProcessSignal::Term),
+            'k' => Some(ProcessSignal::Kill),
+            's' => Some(ProcessSignal::Stop),
+            'c' => Some(ProcessSignal::Cont),
+            _ => None,
+        }
+    }
+
+    fn to_sysinfo(self) -> sysinfo::Signal {
+        match self {
+            ProcessSignal::Term => sysinfo::Signal::Term,
+            ProcessSignal::Kill => sysinfo::Signal::Kill,
+            ProcessSignal::Stop => sysinfo::Signal::Stop,
+            ProcessSignal::Cont => sysinfo::Signal::Continue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub parent: Option<u32>,
+    pub name: String,
+    pub command: String,
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessNode {
+    pub info: ProcessInfo,
+    pub depth: usize,
+}
+
+impl ProcessNode {
+    pub fn display_line(&self) -> String {
+        format!(
+            "{}{} {} {:>5.1}% {:>8}",
+            "  ".repeat(self.depth),
+            self.info.pid,
+            self.info.name,
+            self.info.cpu_percent,
+            format_bytes(self.info.memory_bytes)
+        )
+    }
+}
+
+// Depth-first flattening of the subtree under `root`, children sorted by pid
+// so rows don't jump around between refreshes
+pub fn build_tree(processes: &[ProcessInfo], root: u32) -> Vec<ProcessNode> {
+    let mut children: HashMap<u32, Vec<&ProcessInfo>> = HashMap::new();
+    let mut by_pid: HashMap<u32, &ProcessInfo> = HashMap::new();
+    for process in processes {
+        by_pid.insert(process.pid, process);
+        if let Some(parent) = process.parent {
+            children.entry(parent).or_default().push(process);
+        }
+    }
+    for list in children.values_mut() {
+        list.sort_by_key(|p| p.pid);
+    }
+
+    let mut nodes = Vec::new();
+    let Some(root_info) = by_pid.get(&root) else {
+        return nodes;
+    };
+    let mut stack = vec![(*root_info, 0usize)];
+    while let Some((info, depth)) = stack.pop() {
+        nodes.push(ProcessNode {
+            info: info.clone(),
+            depth,
+        });
+        if let Some(kids) = children.get(&info.pid) {
+            for child in kids.iter().rev() {
+                stack.push((child, depth + 1));
+            }
+        }
+    }
+    nodes
+}
+
+pub fn snapshot(system: &System) -> Vec<ProcessInfo> {
+    system
+        .processes()
+        .values()
+        .map(|p| ProcessInfo {
+            pid: p.pid().as_u32(),
+            parent: p.parent().map(|parent| parent.as_u32()),
+            name: p.name().to_string(),
+            command: p.cmd().join(" "),
+            cpu_percent: p.cpu_usage(),
+            memory_bytes: p.memory(),
+        })
+        .collect()
+}
+
+// Current directory of a process, used to follow a pane's shell as it cd's
+pub fn process_cwd(pid: u32) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(path) = std::fs::read_link(format!("/proc/{}/cwd", pid)) {
+            return Some(path);
+        }
+    }
+    let mut system = System::new();
+    let pid = Pid::from_u32(pid);
+    system.refresh_process_specifics(pid, ProcessRefreshKind::new().with_cwd(UpdateKind::Always));
+    system
+        .process(pid)
+        .and_then(|p| p.cwd())
+        .map(|cwd| cwd.to_path_buf())
+}
+
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, UNITS[unit])
+}
+
+pub struct ProcessTreeMonitor {
+    system: System,
+    root_pid: Option<u32>,
+    last_tree: Vec<ProcessNode>,
+}
+
+impl ProcessTreeMonitor {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            root_pid: None,
+            last_tree: Vec::new(),
+        }
+    }
+
+    pub fn set_root(&mut self, pid: Option<u32>) {
+        self.root_pid = pid;
+        self.last_tree.clear();
+    }
+
+    // CPU usage is a delta between refreshes, so the first refresh after
+    // set_root reports 0% for everything
+    pub fn refresh(&mut self) -> &[ProcessNode] {
+        let Some(root) = self.root_pid else {
+            self.last_tree.clear();
+            return &self.last_tree;
+        };
+        self.system.refresh_processes();
+
+        self.last_tree = build_tree(&snapshot(&self.system), root);
+        &self.last_tree
+    }
+
+    pub fn tree(&self) -> &[ProcessNode] {
+        &self.last_tree
+    }
+
+    // Only processes in the current pane's tree can be signalled from the
+    // panel; a stale row must not hit a recycled pid elsewhere on the system
+    pub fn send_signal(&self, pid: u32, signal: ProcessSignal) -> Result<(), WarpError> {
+        if !self.last_tree.iter().any(|node| node.info.pid == pid) {
+            return Err(WarpError::not_found("process", pid.to_string()));
+        }
+        let process = self
+            .system
+            .process(Pid::from_u32(pid))
+            .ok_or_else(|| WarpError::not_found("process", pid.to_string()))?;
+        match process.kill_with(signal.to_sysinfo()) {
+            Some(true) => Ok(()),
+            Some(false) => Err(WarpError::Permission(format!(
+                "Could not send {:?} to {}",
+                signal, pid
+            ))),
+            None => Err(WarpError::command_err(format!(
+                "{:?} is not supported on this platform",
+                signal
+            ))),
+        }
+    }
+}
+
+impl Default for ProcessTreeMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(pid: u32, parent: Option<u32>) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent,
+            name: format!("p{}", pid),
+            command: String::new(),
+            cpu_percent: 0.0,
+            memory_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_tree_contains_only_descendants_in_order() {
+        let processes = vec![
+            info(1, None),
+            info(10, Some(1)),
+            info(12, Some(10)),
+            info(11, Some(10)),
+            info(20, Some(11)),
+            info(99, Some(1)),
+        ];
+        let tree = build_tree(&processes, 10);
+        let rows: Vec<(u32, usize)> = tree.iter().map(|n| (n.info.pid, n.depth)).collect();
+        assert_eq!(rows, vec![(10, 0), (11, 1), (20, 2), (12, 1)]);
+    }
+
+    #[test]
+    fn test_missing_root_is_empty() {
+        assert!(build_tree(&[info(1, None)], 42).is_empty());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512.0B");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0M");
+    }
+}
@@ -0,0 +1,628 @@
+use crossterm::style::Color;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Files that mark a project root, in the order their tasks are listed
+const PROJECT_FILES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "Makefile",
+    "makefile",
+    "GNUmakefile",
+    "justfile",
+    "Justfile",
+    ".justfile",
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskSource {
+    Cargo,
+    Npm,
+    Make,
+    Just,
+    Compose,
+}
+
+impl TaskSource {
+    fn label(self) -> &'static str {
+        match self {
+            TaskSource::Cargo => "cargo",
+            TaskSource::Npm => "npm",
+            TaskSource::Make => "make",
+            TaskSource::Just => "just",
+            TaskSource::Compose => "compose",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            TaskSource::Cargo => Color::Red,
+            TaskSource::Npm => Color::Green,
+            TaskSource::Make => Color::Yellow,
+            TaskSource::Just => Color::Magenta,
+            TaskSource::Compose => Color::Cyan,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectTask {
+    pub command: String,
+    pub source: TaskSource,
+    pub description: Option<String>, // doc comment, or the script it runs
+}
+
+impl ProjectTask {
+    fn new(command: String, source: TaskSource, description: Option<String>) -> Self {
+        Self {
+            command,
+            source,
+            description,
+        }
+    }
+}
+
+// The nearest directory at or above `cwd` holding a project file
+pub fn find_root(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .find(|dir| PROJECT_FILES.iter().any(|file| dir.join(file).is_file()))
+        .map(Path::to_path_buf)
+}
+
+// Tasks from every project file in `root`
+pub fn detect(root: &Path) -> Vec<ProjectTask> {
+    let mut tasks: Vec<ProjectTask> = Vec::new();
+    let mut seen_make = false;
+    let mut seen_just = false;
+    let mut seen_compose = false;
+    for file in PROJECT_FILES {
+        let path = root.join(file);
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let found = match *file {
+            "Cargo.toml" => cargo_tasks(&text, root),
+            "package.json" => npm_tasks(&text, npm_runner(root)),
+            // make, just and compose each read only the first file they find
+            "Makefile" | "makefile" | "GNUmakefile" if !seen_make => {
+                seen_make = true;
+                make_tasks(&text)
+            }
+            "justfile" | "Justfile" | ".justfile" if !seen_just => {
+                seen_just = true;
+                just_tasks(&text)
+            }
+            _ if file.contains("compose") && !seen_compose => {
+                seen_compose = true;
+                compose_tasks(&text)
+            }
+            _ => Vec::new(),
+        };
+        for task in found {
+            if !tasks.iter().any(|known| known.command == task.command) {
+                tasks.push(task);
+            }
+        }
+    }
+    tasks
+}
+
+fn package_name(manifest: &toml::Value) -> Option<&str> {
+    manifest.get("package")?.get("name")?.as_str()
+}
+
+fn target_names<'a>(manifest: &'a toml::Value, table: &str) -> Vec<&'a str> {
+    manifest
+        .get(table)
+        .and_then(toml::Value::as_array)
+        .map(|targets| {
+            targets
+                .iter()
+                .filter_map(|target| target.get("name")?.as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn cargo_tasks(text: &str, root: &Path) -> Vec<ProjectTask> {
+    let Ok(manifest) = text.parse::<toml::Value>() else {
+        return Vec::new();
+    };
+    let task = |command: String| ProjectTask::new(command, TaskSource::Cargo, None);
+    let mut tasks: Vec<ProjectTask> = ["build", "test", "check", "clippy", "fmt"]
+        .iter()
+        .map(|subcommand| task(format!("cargo {}", subcommand)))
+        .collect();
+
+    let bins = target_names(&manifest, "bin");
+    if bins.len() > 1 {
+        tasks.extend(
+            bins.iter()
+                .map(|bin| task(format!("cargo run --bin {}", bin))),
+        );
+    } else if !bins.is_empty() || root.join("src/main.rs").is_file() {
+        tasks.insert(1, task("cargo run".to_string()));
+    }
+    tasks.extend(
+        target_names(&manifest, "example")
+            .iter()
+            .map(|example| task(format!("cargo run --example {}", example))),
+    );
+
+    // Literal workspace members; globs would need the directory walked
+    let members = manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(toml::Value::as_array);
+    for member in members
+        .into_iter()
+        .flatten()
+        .filter_map(toml::Value::as_str)
+    {
+        if member.contains('*') {
+            continue;
+        }
+        let name = std::fs::read_to_string(root.join(member).join("Cargo.toml"))
+            .ok()
+            .and_then(|text| text.parse::<toml::Value>().ok())
+            .and_then(|manifest| package_name(&manifest).map(str::to_string));
+        if let Some(name) = name {
+            tasks.push(task(format!("cargo test -p {}", name)));
+        }
+    }
+    tasks
+}
+
+// The package manager whose lockfile is present; npm when there's none
+fn npm_runner(root: &Path) -> &'static str {
+    [
+        ("pnpm-lock.yaml", "pnpm run"),
+        ("yarn.lock", "yarn"),
+        ("bun.lockb", "bun run"),
+        ("bun.lock", "bun run"),
+    ]
+    .iter()
+    .find(|(lockfile, _)| root.join(lockfile).is_file())
+    .map_or("npm run", |(_, runner)| runner)
+}
+
+pub fn npm_tasks(text: &str, runner: &str) -> Vec<ProjectTask> {
+    let Ok(package) = serde_json::from_str::<serde_json::Value>(text) else {
+        return Vec::new();
+    };
+    let Some(scripts) = package.get("scripts").and_then(|s| s.as_object()) else {
+        return Vec::new();
+    };
+    scripts
+        .iter()
+        .map(|(name, script)| {
+            // `npm test` and `npm start` don't need `run`
+            let command = match (runner, name.as_str()) {
+                ("npm run", "test" | "start") => format!("npm {}", name),
+                _ => format!("{} {}", runner, name),
+            };
+            ProjectTask::new(
+                command,
+                TaskSource::Npm,
+                script.as_str().map(str::to_string),
+            )
+        })
+        .collect()
+}
+
+fn make_target_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // `build test: deps ## description`; `:=` and `::=` are assignments
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^([A-Za-z0-9_][\w./ -]*?)\s*::?(?:[^=:]|$)(.*?)(?:##\s*(.*))?$")
+            .expect("static pattern")
+    })
+}
+
+// A `# comment` directly above a target or recipe
+fn comment_above(lines: &[&str], index: usize) -> Option<String> {
+    let line = lines.get(index.checked_sub(1)?)?.trim();
+    line.strip_prefix('#')
+        .map(|comment| comment.trim_start_matches('#').trim().to_string())
+        .filter(|comment| !comment.is_empty())
+}
+
+pub fn make_tasks(text: &str) -> Vec<ProjectTask> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut tasks: Vec<ProjectTask> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        // Recipe lines are tab-indented; variables and conditionals aren't targets
+        if line.starts_with(['\t', ' ']) || (line.contains('=') && !line.contains("##")) {
+            continue;
+        }
+        let Some(captures) = make_target_pattern().captures(line) else {
+            continue;
+        };
+        let description = captures
+            .get(3)
+            .map(|m| m.as_str().trim().to_string())
+            .or_else(|| comment_above(&lines, index));
+        for target in captures[1].split_whitespace() {
+            // Pattern rules and variables expand at run time
+            if target.contains(['%', '$'])
+                || tasks
+                    .iter()
+                    .any(|task| task.command.ends_with(&format!(" {}", target)))
+            {
+                continue;
+            }
+            tasks.push(ProjectTask::new(
+                format!("make {}", target),
+                TaskSource::Make,
+                description.clone(),
+            ));
+        }
+    }
+    tasks
+}
+
+fn just_recipe_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // `@build target="debug": deps`, but not `set shell := [...]`
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^@?([A-Za-z][\w-]*)(?:\s[^:]*)?:(?:[^=]|$)").expect("static pattern")
+    })
+}
+
+pub fn just_tasks(text: &str) -> Vec<ProjectTask> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with(char::is_whitespace))
+        .filter_map(|(index, line)| {
+            let name = just_recipe_pattern().captures(line)?.get(1)?.as_str();
+            Some(ProjectTask::new(
+                format!("just {}", name),
+                TaskSource::Just,
+                comment_above(&lines, index),
+            ))
+        })
+        .collect()
+}
+
+// Service names: the keys one level under the top-level `services:`
+pub fn compose_services(text: &str) -> Vec<String> {
+    let mut services = Vec::new();
+    let mut in_services = false;
+    let mut indent = None;
+    for line in text.lines() {
+        let trimmed = line.trim_end();
+        if trimmed.trim_start().is_empty() || trimmed.trim_start().starts_with('#') {
+            continue;
+        }
+        let depth = line.len() - line.trim_start().len();
+        if depth == 0 {
+            in_services = trimmed == "services:";
+            continue;
+        }
+        if !in_services {
+            continue;
+        }
+        let indent = *indent.get_or_insert(depth);
+        if depth != indent {
+            continue;
+        }
+        if let Some(name) = trimmed.trim_start().strip_suffix(':') {
+            services.push(name.trim_matches(['"', '\'']).to_string());
+        }
+    }
+    services
+}
+
+pub fn compose_tasks(text: &str) -> Vec<ProjectTask> {
+    let task = |command: String| ProjectTask::new(command, TaskSource::Compose, None);
+    let services = compose_services(text);
+    if services.is_empty() {
+        return Vec::new();
+    }
+    let mut tasks = vec![
+        task("docker compose up".to_string()),
+        task("docker compose up -d".to_string()),
+        task("docker compose down".to_string()),
+    ];
+    for service in &services {
+        tasks.push(task(format!("docker compose up {}", service)));
+        tasks.push(task(format!("docker compose logs -f {}", service)));
+    }
+    tasks
+}
+
+// Tasks for the shell's directory, re-read only when the project root or
+// one of its files changes
+#[derive(Default)]
+pub struct TaskCache {
+    root: Option<PathBuf>,
+    stamps: Vec<Option<SystemTime>>,
+    tasks: Vec<ProjectTask>,
+}
+
+fn file_stamps(root: &Path) -> Vec<Option<SystemTime>> {
+    PROJECT_FILES
+        .iter()
+        .map(|file| {
+            std::fs::metadata(root.join(file))
+                .and_then(|m| m.modified())
+                .ok()
+        })
+        .collect()
+}
+
+impl TaskCache {
+    pub fn tasks_for(&mut self, cwd: &Path) -> &[ProjectTask] {
+        let root = find_root(cwd);
+        let stamps = root.as_deref().map(file_stamps).unwrap_or_default();
+        if root != self.root || stamps != self.stamps {
+            self.tasks = root.as_deref().map(detect).unwrap_or_default();
+            self.root = root;
+            self.stamps = stamps;
+        }
+        &self.tasks
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+}
+
+// Tasks that complete what's typed so far
+pub fn matching<'a>(tasks: &'a [ProjectTask], input: &str) -> Vec<&'a ProjectTask> {
+    let input = input.trim_start();
+    tasks
+        .iter()
+        .filter(|task| task.command.starts_with(input) && task.command != input)
+        .collect()
+}
+
+pub enum TaskCommand {
+    None,
+    Redraw,
+    Rescan,
+    Run(String),
+    Edit(String),
+}
+
+pub struct TasksPanel {
+    root: Option<PathBuf>,
+    tasks: Vec<ProjectTask>,
+    selected: usize,
+}
+
+impl TasksPanel {
+    pub fn new(root: Option<PathBuf>, tasks: Vec<ProjectTask>) -> Self {
+        Self {
+            root,
+            tasks,
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.tasks.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> TaskCommand {
+        self.select(index);
+        let selected = self
+            .tasks
+            .get(self.selected)
+            .map(|task| task.command.clone());
+        match (key, selected) {
+            ('\n', Some(command)) => TaskCommand::Run(command),
+            ('e', Some(command)) => TaskCommand::Edit(command),
+            ('r', _) => TaskCommand::Rescan,
+            ('\n' | 'e', None) => TaskCommand::None,
+            _ => TaskCommand::Redraw,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let mut items: Vec<PanelItem> = self
+            .tasks
+            .iter()
+            .map(|task| {
+                PanelItem::styled(vec![
+                    (
+                        format!("{:<8}", task.source.label()),
+                        Some(task.source.color()),
+                    ),
+                    (task.command.clone(), None),
+                ])
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(i18n::t("panel.tasks_empty").into());
+        }
+        let preview = self
+            .tasks
+            .get(self.selected)
+            .and_then(|task| task.description.as_ref())
+            .map(|description| {
+                description
+                    .lines()
+                    .map(|line| PanelItem::styled(vec![(line.to_string(), Some(Color::DarkGrey))]))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let project = self
+            .root
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        PanelView {
+            kind: PanelKind::Tasks,
+            title: i18n::t_args(
+                "panel.tasks",
+                &[
+                    ("project", &project),
+                    ("count", &self.tasks.len().to_string()),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.tasks_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(tasks: &[ProjectTask]) -> Vec<&str> {
+        tasks.iter().map(|task| task.command.as_str()).collect()
+    }
+
+    #[test]
+    fn test_cargo_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("crates/core")).unwrap();
+        std::fs::write(
+            dir.path().join("crates/core/Cargo.toml"),
+            "[package]\nname = \"app-core\"\n",
+        )
+        .unwrap();
+        let manifest = r#"
+[workspace]
+members = ["crates/core", "plugins/*"]
+
+[[bin]]
+name = "server"
+
+[[bin]]
+name = "cli"
+
+[[example]]
+name = "demo"
+"#;
+        let tasks = cargo_tasks(manifest, dir.path());
+        assert_eq!(
+            commands(&tasks),
+            vec![
+                "cargo build",
+                "cargo test",
+                "cargo check",
+                "cargo clippy",
+                "cargo fmt",
+                "cargo run --bin server",
+                "cargo run --bin cli",
+                "cargo run --example demo",
+                "cargo test -p app-core",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_npm_scripts_use_lockfile_runner() {
+        let package = r#"{"scripts": {"test": "vitest", "dev": "vite --port 3000"}}"#;
+        let tasks = npm_tasks(package, "npm run");
+        let dev = tasks
+            .iter()
+            .find(|task| task.command == "npm run dev")
+            .unwrap();
+        assert_eq!(dev.description.as_deref(), Some("vite --port 3000"));
+        assert!(commands(&tasks).contains(&"npm test"));
+        assert!(commands(&npm_tasks(package, "pnpm run")).contains(&"pnpm run test"));
+    }
+
+    #[test]
+    fn test_make_targets() {
+        let makefile = "\
+CC := gcc
+.PHONY: all test
+# Build everything
+all: main.o
+\t$(CC) -o app main.o
+
+test: all ## Run the test suite
+\t./run-tests
+
+%.o: %.c
+\t$(CC) -c $<
+ifeq ($(OS),Windows_NT)
+install uninstall:
+endif
+";
+        let tasks = make_tasks(makefile);
+        assert_eq!(
+            commands(&tasks),
+            vec!["make all", "make test", "make install", "make uninstall"]
+        );
+        assert_eq!(tasks[0].description.as_deref(), Some("Build everything"));
+        assert_eq!(tasks[1].description.as_deref(), Some("Run the test suite"));
+    }
+
+    #[test]
+    fn test_just_recipes() {
+        let justfile = "\
+set shell := [\"bash\", \"-c\"]
+alias b := build
+
+# Compile in release mode
+build profile='release':
+    cargo build --profile {{profile}}
+
+@deploy env: build
+    ./deploy.sh {{env}}
+
+_helper:
+    echo hidden
+";
+        let tasks = just_tasks(justfile);
+        assert_eq!(commands(&tasks), vec!["just build", "just deploy"]);
+        assert_eq!(
+            tasks[0].description.as_deref(),
+            Some("Compile in release mode")
+        );
+    }
+
+    #[test]
+    fn test_compose_services() {
+        let compose = "\
+version: '3'
+services:
+  web:
+    image: nginx
+    ports:
+      - \"80:80\"
+  # the database
+  db:
+    image: postgres
+volumes:
+  data:
+";
+        assert_eq!(compose_services(compose), vec!["web", "db"]);
+        let tasks = compose_tasks(compose);
+        assert!(commands(&tasks).contains(&"docker compose logs -f db"));
+    }
+
+    #[test]
+    fn test_cache_finds_root_above_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("justfile"), "serve:\n    echo\n").unwrap();
+
+        let mut cache = TaskCache::default();
+        let tasks = cache.tasks_for(&dir.path().join("src"));
+        assert_eq!(commands(tasks), vec!["just serve"]);
+        assert_eq!(cache.root(), Some(dir.path()));
+        assert_eq!(matching(cache.tasks_for(dir.path()), "just s").len(), 1);
+        assert!(matching(cache.tasks_for(dir.path()), "just serve").is_empty());
+    }
+}
@@ -1,14 +1,24 @@
+use futures::FutureExt;
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
 use tokio::sync::Mutex;
 
+use crate::compliance::{ComplianceLogger, Direction, SessionMetadata};
+use crate::encoding::{OutputDecoder, OutputEncoding};
 use crate::error::WarpError;
+use crate::shell::PaneSpec;
+
+// Output kept per background pane until it's focused; older output is dropped
+const MAX_BACKLOG: usize = 1024 * 1024;
 
 pub struct PtyManager {
     processes: Vec<Arc<Mutex<PtyProcess>>>,
+    titles: Vec<String>, // per process, for the tab strip
     active_process: Option<usize>,
+    compliance_logger: Option<Arc<ComplianceLogger>>,
 }
 
 pub struct PtyProcess {
@@ -17,6 +27,11 @@ pub struct PtyProcess {
     pub stdout: Option<ChildStdout>,
     pid: u32,
     command: String,
+    encoding: OutputEncoding,
+    // Reads can end mid-character
+    decoder: OutputDecoder,
+    // Read while the pane was in the background, returned first once it's active
+    backlog: Vec<u8>,
 }
 
 impl PtyProcess {
@@ -26,6 +41,7 @@ impl PtyProcess {
         stdout: Option<ChildStdout>,
         pid: u32,
         command: String,
+        encoding: OutputEncoding,
     ) -> Self {
         Self {
             child,
@@ -33,6 +49,9 @@ impl PtyProcess {
             stdout,
             pid,
             command,
+            encoding,
+            decoder: encoding.decoder(),
+            backlog: Vec::new(),
         }
     }
 }
@@ -41,27 +60,69 @@ impl PtyManager {
     pub async fn new() -> Result<Self, WarpError> {
         Ok(Self {
             processes: Vec::new(),
+            titles: Vec::new(),
             active_process: None,
+            compliance_logger: None,
         })
     }
 
+    pub fn set_compliance_logger(&mut self, logger: Arc<ComplianceLogger>) {
+        self.compliance_logger = Some(logger);
+    }
+
     pub async fn spawn_shell(&mut self, shell_command: &str) -> Result<usize, WarpError> {
-        let mut child = Command::new(shell_command)
+        self.spawn_pane(&PaneSpec::plain(shell_command)).await
+    }
+
+    // Starts a tab's shell with its arguments, profile environment and
+    // working directory
+    pub async fn spawn_pane(&mut self, spec: &PaneSpec) -> Result<usize, WarpError> {
+        let mut command = Command::new(&spec.shell);
+        command
+            .args(&spec.args)
+            .envs(spec.env.iter().map(|(name, value)| (name, value)))
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            .stderr(Stdio::piped());
+        for name in &spec.unset {
+            command.env_remove(name);
+        }
+        if let Some(cwd) = &spec.cwd {
+            command.current_dir(cwd);
+        }
+        let mut child = command.spawn()?;
 
         let stdin = child.stdin.take();
         let stdout = child.stdout.take();
         let pid = child.id().unwrap_or(0);
 
-        let process = PtyProcess::new(child, stdin, stdout, pid, shell_command.to_string());
+        let process = PtyProcess::new(child, stdin, stdout, pid, spec.shell.clone(), spec.encoding);
 
         let process_id = self.processes.len();
         self.processes.push(Arc::new(Mutex::new(process)));
+        self.titles.push(spec.title());
         self.active_process = Some(process_id);
 
+        if let Some(logger) = &self.compliance_logger {
+            let started = logger
+                .start_pane(SessionMetadata {
+                    session_id: std::process::id().to_string(),
+                    pane_id: process_id.to_string(),
+                    user: std::env::var("USER").unwrap_or_default(),
+                    hostname: std::env::var("HOSTNAME").unwrap_or_default(),
+                    shell: spec.shell.clone(),
+                    started_at: chrono::Utc::now(),
+                })
+                .await;
+            if let Err(e) = started {
+                log::error!(
+                    "Failed to start compliance log for pane {}: {}",
+                    process_id,
+                    e
+                );
+            }
+        }
+
         Ok(process_id)
     }
 
@@ -73,28 +134,141 @@ impl PtyManager {
                     stdin.write_all(input.as_bytes()).await?;
                     stdin.flush().await?;
                 }
+                if let Some(logger) = &self.compliance_logger {
+                    logger
+                        .record(&active_id.to_string(), Direction::Input, input.as_bytes())
+                        .await;
+                }
             }
         }
         Ok(())
     }
 
     pub async fn read_output(&mut self) -> Result<String, WarpError> {
+        let output = self.read_output_bytes().await?;
+        Ok(self.decode_active(&output).await)
+    }
+
+    // Like read_output, but empty after `wait` without output, so the caller
+    // gets to the background panes and lets go of the manager now and then
+    pub async fn read_output_within(&mut self, wait: Duration) -> Result<String, WarpError> {
+        let output = self.read_active(Some(wait)).await?;
+        Ok(self.decode_active(&output).await)
+    }
+
+    async fn decode_active(&self, output: &[u8]) -> String {
+        match self.active_process.and_then(|id| self.processes.get(id)) {
+            Some(process) => process.lock().await.decoder.decode(output),
+            None => String::from_utf8_lossy(output).into_owned(),
+        }
+    }
+
+    // Empty once the active process has closed its stdout
+    pub async fn read_output_bytes(&mut self) -> Result<Vec<u8>, WarpError> {
+        self.read_active(None).await
+    }
+
+    async fn read_active(&mut self, wait: Option<Duration>) -> Result<Vec<u8>, WarpError> {
         if let Some(active_id) = self.active_process {
             if let Some(process_arc) = self.processes.get(active_id) {
                 let mut process = process_arc.lock().await;
+                if !process.backlog.is_empty() {
+                    return Ok(std::mem::take(&mut process.backlog));
+                }
                 if let Some(ref mut stdout) = process.stdout {
                     let mut buffer = [0; 4096];
-                    match stdout.read(&mut buffer).await {
+                    let read = stdout.read(&mut buffer);
+                    let result = match wait {
+                        // Reads are cancel-safe, so nothing is lost by giving up
+                        Some(wait) => match tokio::time::timeout(wait, read).await {
+                            Ok(result) => result,
+                            Err(_) => return Ok(Vec::new()),
+                        },
+                        None => read.await,
+                    };
+                    match result {
                         Ok(n) if n > 0 => {
-                            return Ok(String::from_utf8_lossy(&buffer[..n]).to_string());
+                            if let Some(logger) = &self.compliance_logger {
+                                logger
+                                    .record(&active_id.to_string(), Direction::Output, &buffer[..n])
+                                    .await;
+                            }
+                            return Ok(buffer[..n].to_vec());
                         }
                         Ok(_) => {}
-                        Err(e) => return Err(WarpError::PtyError(e.to_string())),
+                        Err(e) => return Err(WarpError::Pty(e.to_string())),
                     }
                 }
             }
         }
-        Ok(String::new())
+        Ok(Vec::new())
+    }
+
+    // Reads whatever the background panes have written without waiting, so
+    // they don't stall on a full pipe. It's kept for when the pane is
+    // focused; what was read is also returned, decoded loosely, per pane.
+    pub async fn drain_background(&mut self) -> Result<Vec<(usize, String)>, WarpError> {
+        let mut read = Vec::new();
+        for (process_id, process_arc) in self.processes.iter().enumerate() {
+            if Some(process_id) == self.active_process {
+                continue;
+            }
+            let mut process = process_arc.lock().await;
+            let Some(stdout) = process.stdout.as_mut() else {
+                continue;
+            };
+            let mut buffer = [0; 4096];
+            let mut chunk = Vec::new();
+            while chunk.len() < MAX_BACKLOG {
+                match stdout.read(&mut buffer).now_or_never() {
+                    Some(Ok(n)) if n > 0 => chunk.extend_from_slice(&buffer[..n]),
+                    // Nothing more right now, or closed
+                    None | Some(Ok(_)) => break,
+                    Some(Err(e)) => return Err(WarpError::Pty(e.to_string())),
+                }
+            }
+            if chunk.is_empty() {
+                continue;
+            }
+            if let Some(logger) = &self.compliance_logger {
+                logger
+                    .record(&process_id.to_string(), Direction::Output, &chunk)
+                    .await;
+            }
+            process.backlog.extend_from_slice(&chunk);
+            let excess = process.backlog.len().saturating_sub(MAX_BACKLOG);
+            process.backlog.drain(..excess);
+            read.push((process_id, String::from_utf8_lossy(&chunk).into_owned()));
+        }
+        Ok(read)
+    }
+
+    // Nothing in the interactive path reads stderr; headless runs take it
+    // to report it separately
+    pub async fn take_stderr(&self, process_id: usize) -> Option<ChildStderr> {
+        self.processes
+            .get(process_id)?
+            .lock()
+            .await
+            .child
+            .stderr
+            .take()
+    }
+
+    // EOF on stdin makes a non-interactive shell exit after the last command
+    pub async fn close_input(&self, process_id: usize) {
+        if let Some(process_arc) = self.processes.get(process_id) {
+            process_arc.lock().await.stdin = None;
+        }
+    }
+
+    // The exit code, or None when the process was killed by a signal
+    pub async fn wait(&self, process_id: usize) -> Result<Option<i32>, WarpError> {
+        let Some(process_arc) = self.processes.get(process_id) else {
+            return Err(WarpError::Pty(format!("No process {}", process_id)));
+        };
+        let status = process_arc.lock().await.child.wait().await?;
+        Ok(status.code())
     }
 
     pub async fn kill_process(&mut self, process_id: usize) -> Result<(), WarpError> {
@@ -102,6 +276,9 @@ impl PtyManager {
             let mut process = process_arc.lock().await;
             process.child.kill().await?;
         }
+        if let Some(logger) = &self.compliance_logger {
+            logger.stop_pane(&process_id.to_string()).await;
+        }
         Ok(())
     }
 
@@ -109,6 +286,34 @@ impl PtyManager {
         self.active_process
     }
 
+    pub fn tab_titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    pub async fn active_pid(&self) -> Option<u32> {
+        let process_arc = self.processes.get(self.active_process?)?;
+        let pid = process_arc.lock().await.pid;
+        (pid != 0).then_some(pid)
+    }
+
+    pub async fn encoding(&self) -> Option<OutputEncoding> {
+        let process_arc = self.processes.get(self.active_process?)?;
+        let encoding = process_arc.lock().await.encoding;
+        Some(encoding)
+    }
+
+    // Decodes the active pane's output from here on as `encoding`. A partial
+    // character the old decoder was holding back is dropped.
+    pub async fn set_encoding(&self, encoding: OutputEncoding) -> Result<(), WarpError> {
+        let Some(process_arc) = self.active_process.and_then(|id| self.processes.get(id)) else {
+            return Err(WarpError::Pty("No active pane".to_string()));
+        };
+        let mut process = process_arc.lock().await;
+        process.encoding = encoding;
+        process.decoder = encoding.decoder();
+        Ok(())
+    }
+
     pub async fn switch_to_process(&mut self, process_id: usize) -> Result<(), WarpError> {
         if process_id < self.processes.len() {
             self.active_process = Some(process_id);
@@ -116,6 +321,48 @@ impl PtyManager {
         Ok(())
     }
 
+    pub fn process_commands(&self) -> Vec<String> {
+        self.processes
+            .iter()
+            .filter_map(|p| p.try_lock().ok().map(|p| p.command.clone()))
+            .collect()
+    }
+
+    // Closes each child's stdin and sends SIGTERM, giving it `grace` to exit
+    // on its own before it is killed. Returns how many had to be killed.
+    pub async fn shutdown_all(&mut self, grace: Duration) -> Result<usize, WarpError> {
+        for process_arc in &self.processes {
+            let mut process = process_arc.lock().await;
+            process.stdin = None;
+            send_sigterm(process.pid);
+        }
+
+        let deadline = tokio::time::Instant::now() + grace;
+        let mut killed = 0;
+        for (process_id, process_arc) in self.processes.drain(..).enumerate() {
+            let mut process = process_arc.lock().await;
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if tokio::time::timeout(remaining, process.child.wait())
+                .await
+                .is_err()
+            {
+                log::warn!(
+                    "Shell '{}' (pid {}) ignored SIGTERM; killing",
+                    process.command,
+                    process.pid
+                );
+                process.child.kill().await?;
+                killed += 1;
+            }
+            if let Some(logger) = &self.compliance_logger {
+                logger.stop_pane(&process_id.to_string()).await;
+            }
+        }
+        self.titles.clear();
+        self.active_process = None;
+        Ok(killed)
+    }
+
     pub async fn terminate(&mut self) -> Result<(), WarpError> {
         if let Some(active_id) = self.active_process {
             if let Some(process_arc) = self.processes.get(active_id) {
@@ -123,12 +370,26 @@ impl PtyManager {
                 process.child.kill().await?;
             }
             self.processes.remove(active_id);
+            self.titles.remove(active_id);
             self.active_process = None;
         }
         Ok(())
     }
 }
 
+#[cfg(unix)]
+fn send_sigterm(pid: u32) {
+    if pid != 0 {
+        // SAFETY: kill(2) has no memory-safety preconditions
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn send_sigterm(_pid: u32) {}
+
 async fn spawn_pty() -> Result<(Child, ChildStdin, ChildStdout), WarpError> {
     let mut cmd = Command::new("bash");
     cmd.stdin(Stdio::piped())
@@ -139,11 +400,11 @@ async fn spawn_pty() -> Result<(Child, ChildStdin, ChildStdout), WarpError> {
     let stdin = child
         .stdin
         .take()
-        .ok_or(WarpError::PtyError("Failed to take stdin".to_string()))?;
+        .ok_or(WarpError::Pty("Failed to take stdin".to_string()))?;
     let stdout = child
         .stdout
         .take()
-        .ok_or(WarpError::PtyError("Failed to take stdout".to_string()))?;
+        .ok_or(WarpError::Pty("Failed to take stdout".to_string()))?;
 
     Ok((child, stdin, stdout))
 }
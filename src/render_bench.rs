@@ -0,0 +1,195 @@
+use ratatui::{
+    backend::TestBackend,
+    widgets::{Block, Borders, List, ListItem},
+    Terminal as RatatuiTerminal,
+};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::error::WarpError;
+use crate::output_buffer::OutputBuffer;
+
+// Matches the PTY read size in pty::PtyManager::read_output
+pub const PTY_CHUNK_SIZE: usize = 4096;
+pub const TARGET_THROUGHPUT_MB_S: f64 = 100.0;
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+// A single frame taking longer than this is perceived as the UI freezing
+const FREEZE_THRESHOLD: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Scenario {
+    Yes,        // `yes`: tiny identical lines, worst case for per-line overhead
+    LargeFile,  // `cat` of a large source file: long, varied lines
+    ColorStorm, // SGR sequence on every few characters
+}
+
+impl Scenario {
+    pub fn all() -> [Scenario; 3] {
+        [Scenario::Yes, Scenario::LargeFile, Scenario::ColorStorm]
+    }
+
+    pub fn generate(&self, total_bytes: usize) -> String {
+        let mut data = String::with_capacity(total_bytes + 256);
+        let mut i: usize = 0;
+        while data.len() < total_bytes {
+            match self {
+                Scenario::Yes => data.push_str("y\n"),
+                Scenario::LargeFile => {
+                    data.push_str(&format!(
+                        "{:>6}  fn handle_{}(input: &str) -> Result<Vec<u8>, Error> {{ let value = input.len() * {}; }}\n",
+                        i,
+                        i % 97,
+                        i % 13
+                    ));
+                }
+                Scenario::ColorStorm => {
+                    for c in 0..16 {
+                        data.push_str(&format!("\x1b[38;5;{}m#", (i + c) % 256));
+                    }
+                    data.push_str("\x1b[0m\n");
+                }
+            }
+            i += 1;
+        }
+        data
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioReport {
+    pub scenario: Scenario,
+    pub bytes: u64,
+    pub elapsed_ms: f64,
+    pub throughput_mb_s: f64,
+    pub frames_rendered: u64,
+    pub max_frame_ms: f64,
+    pub freezes: u64,
+    pub meets_target: bool,
+}
+
+pub struct RenderBenchmark {
+    width: u16,
+    height: u16,
+    scrollback_lines: usize,
+}
+
+impl RenderBenchmark {
+    pub fn new(width: u16, height: u16, scrollback_lines: usize) -> Self {
+        Self {
+            width,
+            height,
+            scrollback_lines,
+        }
+    }
+
+    pub fn run_all(&self, bytes_per_scenario: usize) -> Result<Vec<ScenarioReport>, WarpError> {
+        Scenario::all()
+            .iter()
+            .map(|scenario| self.run(*scenario, bytes_per_scenario))
+            .collect()
+    }
+
+    // Feeds the scenario through the same buffer + draw path the UI uses,
+    // rendering against an in-memory backend so terminal I/O doesn't skew results
+    pub fn run(&self, scenario: Scenario, total_bytes: usize) -> Result<ScenarioReport, WarpError> {
+        let data = scenario.generate(total_bytes);
+        let mut buffer = OutputBuffer::new(self.scrollback_lines);
+        let mut terminal = RatatuiTerminal::new(TestBackend::new(self.width, self.height))?;
+
+        let mut frames = 0u64;
+        let mut freezes = 0u64;
+        let mut max_frame = Duration::ZERO;
+        let mut last_frame = Instant::now();
+        let start = Instant::now();
+
+        for chunk in chunk_str(&data, PTY_CHUNK_SIZE) {
+            buffer.push_chunk(chunk);
+
+            // Frames are paced, not driven by every chunk; this is what keeps
+            // `yes` from starving the event loop
+            if last_frame.elapsed() >= FRAME_INTERVAL && buffer.take_dirty() {
+                let frame_time = self.draw(&mut terminal, &buffer)?;
+                frames += 1;
+                max_frame = max_frame.max(frame_time);
+                if frame_time > FREEZE_THRESHOLD {
+                    freezes += 1;
+                }
+                last_frame = Instant::now();
+            }
+        }
+
+        if buffer.take_dirty() {
+            let frame_time = self.draw(&mut terminal, &buffer)?;
+            frames += 1;
+            max_frame = max_frame.max(frame_time);
+        }
+
+        let elapsed = start.elapsed();
+        let throughput =
+            data.len() as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64().max(f64::EPSILON);
+
+        Ok(ScenarioReport {
+            scenario,
+            bytes: data.len() as u64,
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            throughput_mb_s: throughput,
+            frames_rendered: frames,
+            max_frame_ms: max_frame.as_secs_f64() * 1000.0,
+            freezes,
+            meets_target: throughput >= TARGET_THROUGHPUT_MB_S && freezes == 0,
+        })
+    }
+
+    fn draw(
+        &self,
+        terminal: &mut RatatuiTerminal<TestBackend>,
+        buffer: &OutputBuffer,
+    ) -> Result<Duration, WarpError> {
+        let start = Instant::now();
+        let visible = self.height.saturating_sub(2) as usize;
+        terminal.draw(|f| {
+            let items: Vec<ListItem> = buffer.visible(visible).map(ListItem::new).collect();
+            let list =
+                List::new(items).block(Block::default().borders(Borders::ALL).title("Output"));
+            f.render_widget(list, f.size());
+        })?;
+        Ok(start.elapsed())
+    }
+}
+
+pub fn format_report(reports: &[ScenarioReport]) -> String {
+    let mut out = format!(
+        "{:<12} {:>10} {:>12} {:>8} {:>12} {:>8}  {}\n",
+        "scenario", "MB", "MB/s", "frames", "max frame", "freezes", "target"
+    );
+    for r in reports {
+        out.push_str(&format!(
+            "{:<12} {:>10.1} {:>12.1} {:>8} {:>10.2}ms {:>8}  {}\n",
+            format!("{:?}", r.scenario),
+            r.bytes as f64 / (1024.0 * 1024.0),
+            r.throughput_mb_s,
+            r.frames_rendered,
+            r.max_frame_ms,
+            r.freezes,
+            if r.meets_target { "ok" } else { "MISS" }
+        ));
+    }
+    out
+}
+
+// Splits on char boundaries so multi-byte sequences are never torn
+pub fn chunk_str(data: &str, size: usize) -> impl Iterator<Item = &str> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
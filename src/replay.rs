@@ -0,0 +1,577 @@
+use chrono::{DateTime, Local, Utc};
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::error::WarpError;
+use crate::i18n;
+use crate::output_buffer::OutputBuffer;
+use crate::pager::parse_ansi;
+use crate::shutdown::ShutdownSignal;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Scrollback the reconstructed screen keeps, as the live one does by default
+const REPLAY_SCROLLBACK: usize = 10_000;
+// Lines of the screen shown beside the timeline
+const PREVIEW_LINES: usize = 40;
+const SCRUBBER_WIDTH: usize = 24;
+
+// First line of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub session: String,
+    pub started: DateTime<Utc>,
+    pub cwd: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Command,
+    Output,
+}
+
+// A command as submitted, or output as it reached the scrollback, `at`
+// milliseconds into the session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    pub at: u64,
+    pub kind: EventKind,
+    pub data: String,
+}
+
+// Appends a session's events to its recording from a background task, so
+// the event loop never waits on the disk
+pub struct SessionRecorder {
+    started: Instant,
+    sender: mpsc::UnboundedSender<ReplayEvent>,
+}
+
+impl SessionRecorder {
+    pub async fn start(
+        dir: &Path,
+        header: RecordingHeader,
+        mut signal: ShutdownSignal,
+    ) -> Result<(Self, JoinHandle<()>), WarpError> {
+        tokio::fs::create_dir_all(dir).await?;
+        let name = format!(
+            "{}_{}.jsonl",
+            header.started.format("%Y%m%dT%H%M%S"),
+            header.session
+        );
+        let file = tokio::fs::File::create(dir.join(name)).await?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&json_line(&header)?).await?;
+
+        let (sender, mut receiver) = mpsc::unbounded_channel::<ReplayEvent>();
+        let handle = tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    event = receiver.recv() => event,
+                    _ = signal.triggered() => None,
+                };
+                let Some(event) = event else {
+                    break;
+                };
+                // Everything already queued goes out in one write
+                let mut pending = vec![event];
+                while let Ok(event) = receiver.try_recv() {
+                    pending.push(event);
+                }
+                if let Err(e) = write_events(&mut writer, &pending).await {
+                    log::warn!("Failed to record session: {}", e);
+                    return;
+                }
+            }
+            let rest: Vec<ReplayEvent> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+            if let Err(e) = write_events(&mut writer, &rest).await {
+                log::warn!("Failed to record session: {}", e);
+            }
+        });
+        Ok((
+            Self {
+                started: Instant::now(),
+                sender,
+            },
+            handle,
+        ))
+    }
+
+    pub fn command(&self, command: &str) {
+        self.send(EventKind::Command, command);
+    }
+
+    pub fn output(&self, output: &str) {
+        self.send(EventKind::Output, output);
+    }
+
+    fn send(&self, kind: EventKind, data: &str) {
+        let _ = self.sender.send(ReplayEvent {
+            at: self.started.elapsed().as_millis() as u64,
+            kind,
+            data: data.to_string(),
+        });
+    }
+}
+
+fn json_line(value: &impl Serialize) -> Result<Vec<u8>, WarpError> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+async fn write_events(
+    writer: &mut BufWriter<tokio::fs::File>,
+    events: &[ReplayEvent],
+) -> Result<(), WarpError> {
+    for event in events {
+        writer.write_all(&json_line(event)?).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordingInfo {
+    pub path: PathBuf,
+    pub header: RecordingHeader,
+    pub bytes: u64,
+}
+
+// Newest first. Only the header line of each is read.
+pub async fn list_recordings(dir: &Path) -> Result<Vec<RecordingInfo>, WarpError> {
+    let mut recordings = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(recordings),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let file = tokio::fs::File::open(&path).await?;
+        let mut first = String::new();
+        BufReader::new(file).read_line(&mut first).await?;
+        let Ok(header) = serde_json::from_str(&first) else {
+            continue;
+        };
+        recordings.push(RecordingInfo {
+            bytes: entry.metadata().await?.len(),
+            path,
+            header,
+        });
+    }
+    recordings.sort_by_key(|recording| std::cmp::Reverse(recording.header.started));
+    Ok(recordings)
+}
+
+// Removes all but the newest `keep` recordings
+pub async fn prune_recordings(dir: &Path, keep: usize) -> Result<usize, WarpError> {
+    let recordings = list_recordings(dir).await?;
+    let mut removed = 0;
+    for recording in recordings.iter().skip(keep) {
+        tokio::fs::remove_file(&recording.path).await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+// A past session, stepped through event by event. The screen at any point
+// is rebuilt by feeding the events up to it into a fresh scrollback the way
+// the live one was fed.
+pub struct Replay {
+    header: RecordingHeader,
+    events: Vec<ReplayEvent>,
+    // Events applied so far
+    position: usize,
+}
+
+impl Replay {
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let text = tokio::fs::read_to_string(path).await?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, WarpError> {
+        let mut lines = text.lines();
+        let header: RecordingHeader = serde_json::from_str(lines.next().unwrap_or_default())
+            .map_err(|_| WarpError::Validation(i18n::t("replay.unreadable")))?;
+        // A crash can leave the last line half-written
+        let events: Vec<ReplayEvent> = lines
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        Ok(Self {
+            header,
+            position: events.len(),
+            events,
+        })
+    }
+
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    // Indexes of the command events, the timeline's steps
+    fn commands(&self) -> Vec<usize> {
+        self.events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| event.kind == EventKind::Command)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // The command whose output is on screen, if any has run yet
+    pub fn current_step(&self) -> Option<usize> {
+        self.commands()
+            .iter()
+            .rposition(|&index| index < self.position)
+    }
+
+    // Up to where step `step` finished, just before the next command
+    pub fn seek_step(&mut self, step: usize) {
+        let commands = self.commands();
+        self.position = match commands.get(step + 1) {
+            Some(&next) => next,
+            None => self.events.len(),
+        };
+    }
+
+    // One event, e.g. a single chunk of output, either way
+    pub fn step_event(&mut self, forward: bool) {
+        self.position = match forward {
+            true => (self.position + 1).min(self.events.len()),
+            false => self.position.saturating_sub(1),
+        };
+    }
+
+    // Milliseconds into the session of the last event applied
+    pub fn at(&self) -> u64 {
+        match self.position {
+            0 => 0,
+            position => self.events[position - 1].at,
+        }
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.events.last().map_or(0, |event| event.at)
+    }
+
+    pub fn screen(&self) -> OutputBuffer {
+        let mut buffer = OutputBuffer::new(REPLAY_SCROLLBACK);
+        for event in &self.events[..self.position] {
+            match event.kind {
+                EventKind::Command => {
+                    buffer.start_block();
+                    buffer.push_line(format!("❯ {}", event.data));
+                }
+                EventKind::Output => buffer.push_chunk(&event.data),
+            }
+        }
+        buffer
+    }
+
+    // "━━━━━●─────" with the knob where the replay is in time
+    pub fn scrubber(&self, width: usize) -> String {
+        let filled = match self.duration() {
+            0 => width,
+            total => (self.at() as usize * width) / total as usize,
+        }
+        .min(width);
+        let mut bar = "━".repeat(filled.saturating_sub(1));
+        bar.push('●');
+        bar.push_str(&"─".repeat(width - filled.max(1)));
+        bar
+    }
+}
+
+// "+1:05", "+1:02:03"
+fn offset(ms: u64) -> String {
+    let seconds = ms / 1000;
+    match seconds / 3600 {
+        0 => format!("+{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("+{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayCommand {
+    None,
+    Load(PathBuf),
+    // Page through the whole scrollback as it was at this point
+    Page(Vec<String>),
+}
+
+// Recorded sessions to pick from, then the chosen one's commands as a
+// timeline with the screen at the selected step beside it
+pub struct ReplayPanel {
+    recordings: Vec<RecordingInfo>,
+    replay: Option<Replay>,
+    selected: usize,
+}
+
+impl ReplayPanel {
+    pub fn new(recordings: Vec<RecordingInfo>) -> Self {
+        Self {
+            recordings,
+            replay: None,
+            selected: 0,
+        }
+    }
+
+    pub fn open(&mut self, replay: Replay) {
+        self.selected = replay.current_step().unwrap_or(0);
+        self.replay = Some(replay);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        match &mut self.replay {
+            Some(replay) => {
+                let steps = replay.commands().len();
+                self.selected = index.min(steps.saturating_sub(1));
+                if steps > 0 {
+                    replay.seek_step(self.selected);
+                }
+            }
+            None => self.selected = index.min(self.recordings.len().saturating_sub(1)),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> ReplayCommand {
+        let Some(replay) = &mut self.replay else {
+            self.select(index);
+            return match (key, self.recordings.get(self.selected)) {
+                ('\n', Some(recording)) => ReplayCommand::Load(recording.path.clone()),
+                _ => ReplayCommand::None,
+            };
+        };
+        match key {
+            ',' | '.' => {
+                replay.step_event(key == '.');
+                self.selected = replay.current_step().unwrap_or(0);
+                ReplayCommand::None
+            }
+            '\n' => ReplayCommand::Page(replay.screen().lines().map(Cow::into_owned).collect()),
+            'b' => {
+                self.replay = None;
+                self.selected = 0;
+                ReplayCommand::None
+            }
+            _ => ReplayCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        match &self.replay {
+            Some(replay) => self.timeline_view(replay),
+            None => self.sessions_view(),
+        }
+    }
+
+    fn sessions_view(&self) -> PanelView {
+        let items = self
+            .recordings
+            .iter()
+            .map(|recording| {
+                let started = recording.header.started.with_timezone(&Local);
+                PanelItem::styled(vec![
+                    (started.format("%Y-%m-%d %H:%M  ").to_string(), None),
+                    (
+                        recording
+                            .header
+                            .cwd
+                            .as_ref()
+                            .map(|cwd| cwd.display().to_string())
+                            .unwrap_or_default(),
+                        Some(Color::DarkGrey),
+                    ),
+                ])
+            })
+            .collect();
+        let preview = match self.recordings.is_empty() {
+            true => vec![i18n::t("replay.empty").into()],
+            false => Vec::new(),
+        };
+        PanelView {
+            kind: PanelKind::Replay,
+            title: i18n::t("panel.replay_sessions"),
+            items,
+            footer: Some(i18n::t("panel.replay_sessions_keys")),
+            preview,
+            image: None,
+        }
+    }
+
+    fn timeline_view(&self, replay: &Replay) -> PanelView {
+        let items = replay
+            .commands()
+            .into_iter()
+            .map(|index| {
+                let event = &replay.events[index];
+                PanelItem::styled(vec![
+                    (format!("{:>8} ", offset(event.at)), Some(Color::DarkGrey)),
+                    (event.data.clone(), None),
+                ])
+            })
+            .collect();
+        let screen = replay.screen();
+        let preview = screen
+            .visible(PREVIEW_LINES)
+            .map(|line| {
+                PanelItem::from(
+                    parse_ansi(line)
+                        .into_iter()
+                        .map(|(text, _)| text)
+                        .collect::<String>(),
+                )
+            })
+            .collect();
+        let started = replay.header.started + chrono::Duration::milliseconds(replay.at() as i64);
+        PanelView {
+            kind: PanelKind::Replay,
+            title: i18n::t_args(
+                "panel.replay",
+                &[
+                    ("scrubber", &replay.scrubber(SCRUBBER_WIDTH)),
+                    (
+                        "time",
+                        &started.with_timezone(&Local).format("%H:%M:%S").to_string(),
+                    ),
+                    ("offset", &offset(replay.at())),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.replay_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording() -> String {
+        let header = RecordingHeader {
+            session: "s1".to_string(),
+            started: Utc::now(),
+            cwd: None,
+        };
+        let event = |at, kind, data: &str| ReplayEvent {
+            at,
+            kind,
+            data: data.to_string(),
+        };
+        let mut text = serde_json::to_string(&header).unwrap();
+        for event in [
+            event(0, EventKind::Output, "welcome\n"),
+            event(1_000, EventKind::Command, "ls"),
+            event(1_200, EventKind::Output, "a.txt\nb.txt\n"),
+            event(5_000, EventKind::Command, "cat a.txt"),
+            event(5_100, EventKind::Output, "hel"),
+            event(5_300, EventKind::Output, "lo\n"),
+        ] {
+            text.push('\n');
+            text.push_str(&serde_json::to_string(&event).unwrap());
+        }
+        text.push_str("\n{\"at\": 6000, \"ki");
+        text
+    }
+
+    fn screen(replay: &Replay) -> Vec<String> {
+        replay.screen().lines().map(Cow::into_owned).collect()
+    }
+
+    #[test]
+    fn test_steps_by_command_and_event() {
+        let mut replay = Replay::parse(&recording()).unwrap();
+        assert_eq!(replay.current_step(), Some(1));
+        assert_eq!(replay.duration(), 5_300);
+
+        replay.seek_step(0);
+        assert_eq!(screen(&replay), ["welcome", "❯ ls", "a.txt", "b.txt"]);
+        assert_eq!(replay.at(), 1_200);
+
+        replay.seek_step(1);
+        replay.step_event(false);
+        assert_eq!(replay.current_step(), Some(1));
+        assert_eq!(
+            replay.screen().visible(2).collect::<Vec<_>>(),
+            ["❯ cat a.txt", "hel"]
+        );
+        replay.step_event(false);
+        replay.step_event(false);
+        assert_eq!(replay.current_step(), Some(0));
+    }
+
+    #[test]
+    fn test_scrubber() {
+        let mut replay = Replay::parse(&recording()).unwrap();
+        assert_eq!(replay.scrubber(4), "━━━●");
+        replay.seek_step(0);
+        assert_eq!(replay.scrubber(4), "●───");
+        assert_eq!(offset(65_000), "+1:05");
+        assert_eq!(offset(3_723_000), "+1:02:03");
+    }
+
+    #[tokio::test]
+    async fn test_records_and_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let controller =
+            crate::shutdown::ShutdownController::new(std::time::Duration::from_secs(1));
+        let header = RecordingHeader {
+            session: "s1".to_string(),
+            started: Utc::now(),
+            cwd: Some(PathBuf::from("/srv")),
+        };
+        let (recorder, handle) =
+            SessionRecorder::start(dir.path(), header.clone(), controller.subscribe())
+                .await
+                .unwrap();
+        recorder.command("make");
+        recorder.output("done\n");
+        controller.trigger(crate::shutdown::ShutdownReason::UserExit);
+        handle.await.unwrap();
+
+        let recordings = list_recordings(dir.path()).await.unwrap();
+        assert_eq!(recordings.len(), 1);
+        assert_eq!(recordings[0].header, header);
+        let replay = Replay::load(&recordings[0].path).await.unwrap();
+        assert_eq!(screen(&replay), ["❯ make", "done"]);
+
+        assert_eq!(prune_recordings(dir.path(), 0).await.unwrap(), 1);
+        assert!(list_recordings(&dir.path().join("missing"))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_panel() {
+        let mut panel = ReplayPanel::new(Vec::new());
+        assert_eq!(panel.handle_key('\n', 0), ReplayCommand::None);
+        panel.open(Replay::parse(&recording()).unwrap());
+        assert_eq!(panel.view().items.len(), 2);
+        panel.select(0);
+        assert_eq!(
+            panel.handle_key('\n', 0),
+            ReplayCommand::Page(vec![
+                "welcome".to_string(),
+                "❯ ls".to_string(),
+                "a.txt".to_string(),
+                "b.txt".to_string()
+            ])
+        );
+        panel.handle_key('b', 0);
+        assert_eq!(panel.view().kind, PanelKind::Replay);
+        assert!(panel.replay.is_none());
+    }
+}
@@ -0,0 +1,1066 @@
+use chrono::{DateTime, Local};
+use crossterm::style::Color;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::error::WarpError;
+use crate::file_manager::shell_quote;
+use crate::headless::RunResult;
+use crate::i18n;
+use crate::pager::parse_ansi;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Output lines kept per step for the preview and the report; the rest is
+// in the scrollback behind the step's block link
+const TAIL_LINES: usize = 20;
+
+// A documented procedure as TOML: parameters asked for when the run
+// starts, then steps run in order. A step with `approval` waits for the
+// operator before it runs; one with only `approval` is a pure checkpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Runbook {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub name: String,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub approval: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+fn placeholder_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap())
+}
+
+// {{name}} replaced by the parameter's value. Values going into a command
+// are shell-quoted so whatever the operator typed stays one word.
+pub fn render(template: &str, values: &[(String, String)], quote: bool) -> String {
+    placeholder_pattern()
+        .replace_all(template, |captures: &regex::Captures| {
+            let value = values
+                .iter()
+                .find(|(name, _)| *name == captures[1])
+                .map_or("", |(_, value)| value.as_str());
+            if quote {
+                shell_quote(Path::new(value))
+            } else {
+                value.to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Runbook {
+    pub fn parse(text: &str) -> Result<Self, WarpError> {
+        let runbook: Runbook =
+            toml::from_str(text).map_err(|e| WarpError::config_err(e.to_string()))?;
+        runbook.validate()?;
+        Ok(runbook)
+    }
+
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let text = tokio::fs::read_to_string(path).await?;
+        Self::parse(&text).map_err(|e| e.context(format!("Invalid runbook {}", path.display())))
+    }
+
+    fn validate(&self) -> Result<(), WarpError> {
+        if self.steps.is_empty() {
+            return Err(WarpError::config_err("A runbook needs at least one step"));
+        }
+        let mut names = HashSet::new();
+        for parameter in &self.parameters {
+            if !is_identifier(&parameter.name) || !names.insert(parameter.name.as_str()) {
+                return Err(WarpError::config_err(format!(
+                    "Parameter '{}' is invalid or declared twice",
+                    parameter.name
+                )));
+            }
+        }
+        for step in &self.steps {
+            if step.command.is_none() && step.approval.is_none() {
+                return Err(WarpError::config_err(format!(
+                    "Step '{}' has neither a command nor an approval",
+                    step.name
+                )));
+            }
+            let texts = step.command.iter().chain(step.approval.iter());
+            for text in texts {
+                for captures in placeholder_pattern().captures_iter(text) {
+                    if !names.contains(&captures[1]) {
+                        return Err(WarpError::config_err(format!(
+                            "Step '{}' uses undeclared parameter '{}'",
+                            step.name, &captures[1]
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A runbook file found on disk; broken ones are listed with their error
+// rather than silently left out
+pub struct RunbookEntry {
+    pub path: PathBuf,
+    pub runbook: Result<Runbook, String>,
+}
+
+pub async fn discover(directories: &[PathBuf]) -> Vec<RunbookEntry> {
+    let mut paths = Vec::new();
+    for directory in directories {
+        let Ok(mut entries) = tokio::fs::read_dir(directory).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "toml") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+
+    let mut found = Vec::new();
+    for path in paths {
+        let runbook = Runbook::load(&path).await.map_err(|e| e.to_string());
+        found.push(RunbookEntry { path, runbook });
+    }
+    found
+}
+
+pub fn operator() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct StepRecord {
+    pub status: StepStatus,
+    pub approved: Option<(String, DateTime<Local>)>,
+    pub started_at: Option<DateTime<Local>>,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    // warp://block/ link to the step's output in the scrollback
+    pub evidence: Option<String>,
+    pub tail: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Next {
+    Approve(usize),
+    Run(usize),
+    Running(usize),
+    Done,
+}
+
+pub struct RunbookRun {
+    pub path: PathBuf,
+    pub runbook: Runbook,
+    pub operator: String,
+    pub values: Vec<(String, String)>,
+    pub steps: Vec<StepRecord>,
+    pub started_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+    current: usize,
+    aborted: bool,
+}
+
+impl RunbookRun {
+    pub fn new(
+        path: PathBuf,
+        runbook: Runbook,
+        values: Vec<(String, String)>,
+        operator: String,
+    ) -> Self {
+        let steps = runbook
+            .steps
+            .iter()
+            .map(|_| StepRecord {
+                status: StepStatus::Pending,
+                approved: None,
+                started_at: None,
+                exit_code: None,
+                duration_ms: 0,
+                evidence: None,
+                tail: Vec::new(),
+            })
+            .collect();
+        Self {
+            path,
+            runbook,
+            operator,
+            values,
+            steps,
+            started_at: Local::now(),
+            finished_at: None,
+            current: 0,
+            aborted: false,
+        }
+    }
+
+    // A failed step stays current, so the operator can retry, skip or abort
+    pub fn next(&self) -> Next {
+        if self.aborted || self.current >= self.steps.len() {
+            return Next::Done;
+        }
+        let record = &self.steps[self.current];
+        if record.status == StepStatus::Running {
+            Next::Running(self.current)
+        } else if self.runbook.steps[self.current].approval.is_some() && record.approved.is_none() {
+            Next::Approve(self.current)
+        } else {
+            Next::Run(self.current)
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next() == Next::Done
+    }
+
+    // Every step done, none skipped and the run not aborted
+    pub fn succeeded(&self) -> bool {
+        self.is_finished()
+            && !self.aborted
+            && self
+                .steps
+                .iter()
+                .all(|record| record.status == StepStatus::Succeeded)
+    }
+
+    pub fn command(&self, index: usize) -> Option<String> {
+        let command = self.runbook.steps.get(index)?.command.as_ref()?;
+        Some(render(command, &self.values, true))
+    }
+
+    pub fn approval(&self, index: usize) -> Option<String> {
+        let approval = self.runbook.steps.get(index)?.approval.as_ref()?;
+        Some(render(approval, &self.values, false))
+    }
+
+    pub fn approve(&mut self) {
+        if let Next::Approve(index) = self.next() {
+            self.steps[index].approved = Some((self.operator.clone(), Local::now()));
+        }
+    }
+
+    // The rendered command to run for the current step, or None when it is
+    // a checkpoint with nothing to run and has completed
+    pub fn start(&mut self) -> Option<(usize, String)> {
+        let Next::Run(index) = self.next() else {
+            return None;
+        };
+        let command = self.command(index);
+        let record = &mut self.steps[index];
+        record.started_at = Some(Local::now());
+        match command {
+            Some(command) => {
+                record.status = StepStatus::Running;
+                Some((index, command))
+            }
+            None => {
+                record.status = StepStatus::Succeeded;
+                self.advance();
+                None
+            }
+        }
+    }
+
+    pub fn finish(&mut self, index: usize, result: &RunResult, evidence: Option<String>) {
+        let Some(record) = self.steps.get_mut(index) else {
+            return;
+        };
+        let lines: Vec<String> = result
+            .stdout
+            .lines()
+            .chain(result.stderr.lines())
+            .map(|line| parse_ansi(line).into_iter().map(|(text, _)| text).collect())
+            .collect();
+        record.tail = lines[lines.len().saturating_sub(TAIL_LINES)..].to_vec();
+        record.exit_code = result.exit_code;
+        record.duration_ms = result.duration_ms;
+        record.evidence = evidence;
+        if result.exit_code == Some(0) {
+            record.status = StepStatus::Succeeded;
+            if index == self.current {
+                self.advance();
+            }
+        } else {
+            record.status = StepStatus::Failed;
+        }
+    }
+
+    // A step that could not be started at all, e.g. the shell failed to spawn
+    pub fn fail(&mut self, index: usize, error: String) {
+        if let Some(record) = self.steps.get_mut(index) {
+            record.status = StepStatus::Failed;
+            record.tail = vec![error];
+        }
+    }
+
+    pub fn skip(&mut self) {
+        if let Next::Approve(index) | Next::Run(index) = self.next() {
+            self.steps[index].status = StepStatus::Skipped;
+            self.advance();
+        }
+    }
+
+    pub fn abort(&mut self) {
+        if matches!(self.next(), Next::Approve(_) | Next::Run(_)) {
+            self.aborted = true;
+            self.finished_at = Some(Local::now());
+        }
+    }
+
+    fn advance(&mut self) {
+        self.current += 1;
+        if self.current >= self.steps.len() {
+            self.finished_at = Some(Local::now());
+        }
+    }
+
+    fn outcome(&self) -> String {
+        let skipped = self
+            .steps
+            .iter()
+            .filter(|record| record.status == StepStatus::Skipped)
+            .count();
+        match (self.aborted, self.finished_at) {
+            (true, _) => format!("aborted at step {}", self.current + 1),
+            (false, None) => format!("in progress at step {}", self.current + 1),
+            (false, Some(_)) if skipped == 0 => "completed".to_string(),
+            (false, Some(_)) => format!("completed with {} skipped step(s)", skipped),
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let time = |at: &DateTime<Local>| at.format("%Y-%m-%d %H:%M:%S %:z").to_string();
+        let mut out = format!("# Runbook report: {}\n\n", self.runbook.name);
+        if let Some(description) = &self.runbook.description {
+            out.push_str(&format!("{}\n\n", description.trim()));
+        }
+        out.push_str(&format!("- Runbook: `{}`\n", self.path.display()));
+        out.push_str(&format!("- Operator: {}\n", self.operator));
+        out.push_str(&format!("- Started: {}\n", time(&self.started_at)));
+        if let Some(finished_at) = &self.finished_at {
+            out.push_str(&format!("- Finished: {}\n", time(finished_at)));
+        }
+        out.push_str(&format!("- Outcome: {}\n", self.outcome()));
+
+        if !self.values.is_empty() {
+            out.push_str("\n## Parameters\n\n");
+            for (name, value) in &self.values {
+                out.push_str(&format!("- `{}` = `{}`\n", name, value));
+            }
+        }
+
+        out.push_str("\n## Steps\n");
+        for (index, (step, record)) in self.runbook.steps.iter().zip(&self.steps).enumerate() {
+            let status = match record.status {
+                StepStatus::Pending => "not run",
+                StepStatus::Running => "running",
+                StepStatus::Succeeded => "succeeded",
+                StepStatus::Failed => "failed",
+                StepStatus::Skipped => "skipped",
+            };
+            out.push_str(&format!(
+                "\n### {}. {} — {}\n\n",
+                index + 1,
+                step.name,
+                status
+            ));
+            if let Some(approval) = self.approval(index) {
+                out.push_str(&format!("- Checkpoint: {}\n", approval));
+            }
+            if let Some((by, at)) = &record.approved {
+                out.push_str(&format!("- Approved by {} at {}\n", by, time(at)));
+            }
+            if let Some(command) = self.command(index) {
+                out.push_str(&format!("- Command: `{}`\n", command));
+            }
+            if let Some(started_at) = &record.started_at {
+                out.push_str(&format!("- Started: {}\n", time(started_at)));
+            }
+            if matches!(record.status, StepStatus::Succeeded | StepStatus::Failed)
+                && step.command.is_some()
+            {
+                let code = record
+                    .exit_code
+                    .map_or("none (killed)".to_string(), |code| code.to_string());
+                out.push_str(&format!(
+                    "- Exit code: {} after {} ms\n",
+                    code, record.duration_ms
+                ));
+            }
+            if let Some(evidence) = &record.evidence {
+                out.push_str(&format!("- Evidence: {}\n", evidence));
+            }
+            if !record.tail.is_empty() {
+                out.push_str(&format!("\n```text\n{}\n```\n", record.tail.join("\n")));
+            }
+        }
+        out
+    }
+
+    pub async fn write_report(&self, directory: &Path) -> Result<PathBuf, WarpError> {
+        tokio::fs::create_dir_all(directory).await?;
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "runbook".to_string());
+        let path = directory.join(format!(
+            "{}-{}.md",
+            stem,
+            self.started_at.format("%Y%m%d-%H%M%S")
+        ));
+        tokio::fs::write(&path, self.report()).await?;
+        Ok(path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunbookCommand {
+    None,
+    Redraw,
+    // Ask for a parameter value at the panel prompt
+    Prompt(String),
+    Run {
+        step: usize,
+        command: String,
+        timeout: Option<Duration>,
+    },
+    // The run has ended; its report should be written
+    Finished,
+    Report,
+    Edit(PathBuf),
+    Rescan,
+}
+
+enum Mode {
+    List,
+    Collecting {
+        entry: usize,
+        values: Vec<(String, String)>,
+    },
+    Running(Box<RunbookRun>),
+}
+
+// The runbooks pane: the runbooks found on disk, then the steps of the
+// one being run
+pub struct RunbookPanel {
+    entries: Vec<RunbookEntry>,
+    mode: Mode,
+    selected: usize,
+    status: Option<String>,
+}
+
+impl RunbookPanel {
+    pub fn new(entries: Vec<RunbookEntry>) -> Self {
+        Self {
+            entries,
+            mode: Mode::List,
+            selected: 0,
+            status: None,
+        }
+    }
+
+    pub fn set_entries(&mut self, entries: Vec<RunbookEntry>) {
+        self.entries = entries;
+        if !matches!(self.mode, Mode::Running(_)) {
+            self.mode = Mode::List;
+        }
+        self.select(self.selected);
+    }
+
+    pub fn run(&self) -> Option<&RunbookRun> {
+        match &self.mode {
+            Mode::Running(run) => Some(run),
+            _ => None,
+        }
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    fn len(&self) -> usize {
+        match &self.mode {
+            Mode::Running(run) => run.steps.len(),
+            _ => self.entries.len(),
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> RunbookCommand {
+        self.select(index);
+        if let Mode::Collecting { .. } = self.mode {
+            // The prompt was dismissed; start over from the list
+            self.mode = Mode::List;
+        }
+        self.status = None;
+        match &mut self.mode {
+            Mode::Running(run) => match key {
+                'w' => RunbookCommand::Report,
+                'b' if run.is_finished() => {
+                    self.mode = Mode::List;
+                    self.selected = 0;
+                    RunbookCommand::Redraw
+                }
+                // Nothing more to do while a step runs or once the run is over
+                _ if !matches!(run.next(), Next::Approve(_) | Next::Run(_)) => RunbookCommand::None,
+                '\n' | 'a' => {
+                    run.approve();
+                    self.start_step()
+                }
+                's' => {
+                    run.skip();
+                    self.finished_or_redraw()
+                }
+                'x' => {
+                    run.abort();
+                    self.finished_or_redraw()
+                }
+                _ => RunbookCommand::Redraw,
+            },
+            _ => match (key, self.entries.get(self.selected)) {
+                ('\n', Some(entry)) if entry.runbook.is_ok() => {
+                    self.mode = Mode::Collecting {
+                        entry: self.selected,
+                        values: Vec::new(),
+                    };
+                    self.next_parameter()
+                }
+                ('\n', _) => RunbookCommand::None,
+                ('e', Some(entry)) => RunbookCommand::Edit(entry.path.clone()),
+                ('r', _) => RunbookCommand::Rescan,
+                _ => RunbookCommand::Redraw,
+            },
+        }
+    }
+
+    // Text entered at the prompt is the value of the next parameter; an
+    // empty answer takes the default, or asks again when there is none
+    pub fn submit_input(&mut self, text: &str) -> RunbookCommand {
+        let Mode::Collecting { entry, values } = &mut self.mode else {
+            return RunbookCommand::None;
+        };
+        let Some(Ok(runbook)) = self.entries.get(*entry).map(|entry| &entry.runbook) else {
+            return RunbookCommand::None;
+        };
+        let Some(parameter) = runbook.parameters.get(values.len()) else {
+            return RunbookCommand::None;
+        };
+        let value = match text.trim() {
+            "" => parameter.default.clone().unwrap_or_default(),
+            value => value.to_string(),
+        };
+        if !value.is_empty() {
+            values.push((parameter.name.clone(), value));
+        }
+        self.next_parameter()
+    }
+
+    fn next_parameter(&mut self) -> RunbookCommand {
+        let Mode::Collecting { entry, values } = &mut self.mode else {
+            return RunbookCommand::None;
+        };
+        let Some(RunbookEntry {
+            path,
+            runbook: Ok(runbook),
+        }) = self.entries.get(*entry)
+        else {
+            return RunbookCommand::None;
+        };
+        if let Some(parameter) = runbook.parameters.get(values.len()) {
+            let prompt = parameter.prompt.as_deref().unwrap_or(&parameter.name);
+            return RunbookCommand::Prompt(match &parameter.default {
+                Some(default) => i18n::t_args(
+                    "runbook.parameter_default",
+                    &[("prompt", prompt), ("default", default)],
+                ),
+                None => prompt.to_string(),
+            });
+        }
+        let run = RunbookRun::new(
+            path.clone(),
+            runbook.clone(),
+            std::mem::take(values),
+            operator(),
+        );
+        self.mode = Mode::Running(Box::new(run));
+        self.selected = 0;
+        RunbookCommand::Redraw
+    }
+
+    fn start_step(&mut self) -> RunbookCommand {
+        let Mode::Running(run) = &mut self.mode else {
+            return RunbookCommand::None;
+        };
+        match run.start() {
+            Some((step, command)) => {
+                self.selected = step;
+                let timeout = run.runbook.steps[step]
+                    .timeout_secs
+                    .map(Duration::from_secs);
+                RunbookCommand::Run {
+                    step,
+                    command,
+                    timeout,
+                }
+            }
+            None => self.finished_or_redraw(),
+        }
+    }
+
+    fn finished_or_redraw(&mut self) -> RunbookCommand {
+        match &self.mode {
+            Mode::Running(run) if run.is_finished() => RunbookCommand::Finished,
+            _ => RunbookCommand::Redraw,
+        }
+    }
+
+    pub fn finish_step(
+        &mut self,
+        step: usize,
+        result: Result<&RunResult, String>,
+        evidence: Option<String>,
+    ) -> RunbookCommand {
+        let Mode::Running(run) = &mut self.mode else {
+            return RunbookCommand::None;
+        };
+        match result {
+            Ok(result) => run.finish(step, result, evidence),
+            Err(error) => run.fail(step, error),
+        }
+        self.finished_or_redraw()
+    }
+
+    fn entry_item(entry: &RunbookEntry) -> PanelItem {
+        match &entry.runbook {
+            Ok(runbook) => {
+                let mut segments = vec![(runbook.name.clone(), None)];
+                if let Some(description) = &runbook.description {
+                    segments.push((
+                        format!("  {}", description.lines().next().unwrap_or("")),
+                        Some(Color::DarkGrey),
+                    ));
+                }
+                PanelItem::styled(segments)
+            }
+            Err(error) => PanelItem::styled(vec![
+                (entry.path.display().to_string(), None),
+                (format!("  {}", error), Some(Color::Red)),
+            ]),
+        }
+    }
+
+    fn step_item(run: &RunbookRun, index: usize) -> PanelItem {
+        let record = &run.steps[index];
+        let (mark, color) = match (record.status, run.next()) {
+            (StepStatus::Pending, Next::Approve(current)) if current == index => {
+                ("?".to_string(), Color::Yellow)
+            }
+            (StepStatus::Pending, _) => ("·".to_string(), Color::DarkGrey),
+            (StepStatus::Running, _) => ("…".to_string(), Color::Yellow),
+            (StepStatus::Succeeded, _) => ("✓".to_string(), Color::Green),
+            (StepStatus::Failed, _) => (
+                record
+                    .exit_code
+                    .map_or("✗".to_string(), |code| format!("✗{}", code)),
+                Color::Red,
+            ),
+            (StepStatus::Skipped, _) => ("–".to_string(), Color::DarkGrey),
+        };
+        let mut segments = vec![
+            (format!("{:<3}", mark), Some(color)),
+            (
+                format!("{}. {}", index + 1, run.runbook.steps[index].name),
+                None,
+            ),
+        ];
+        if record.evidence.is_some() {
+            segments.push((
+                format!("  {} ms", record.duration_ms),
+                Some(Color::DarkGrey),
+            ));
+        }
+        PanelItem::styled(segments)
+    }
+
+    fn preview(&self) -> Vec<PanelItem> {
+        match &self.mode {
+            Mode::Running(run) => {
+                let index = self.selected;
+                let Some(record) = run.steps.get(index) else {
+                    return Vec::new();
+                };
+                let mut items = Vec::new();
+                if let Some(approval) = run.approval(index) {
+                    items.push(PanelItem::styled(vec![(
+                        format!("⏸ {}", approval),
+                        Some(Color::Yellow),
+                    )]));
+                }
+                if let Some((by, at)) = &record.approved {
+                    items.push(PanelItem::from(i18n::t_args(
+                        "runbook.approved_by",
+                        &[
+                            ("operator", by),
+                            ("time", &at.format("%H:%M:%S").to_string()),
+                        ],
+                    )));
+                }
+                if let Some(command) = run.command(index) {
+                    items.push(PanelItem::styled(vec![(
+                        format!("❯ {}", command),
+                        Some(Color::Cyan),
+                    )]));
+                }
+                if let Some(evidence) = &record.evidence {
+                    items.push(PanelItem::styled(vec![(
+                        evidence.clone(),
+                        Some(Color::Blue),
+                    )]));
+                }
+                items.extend(record.tail.iter().cloned().map(PanelItem::from));
+                items
+            }
+            _ => {
+                let Some(Ok(runbook)) = self.entries.get(self.selected).map(|e| &e.runbook) else {
+                    return Vec::new();
+                };
+                let mut items: Vec<PanelItem> = runbook
+                    .description
+                    .iter()
+                    .flat_map(|description| description.lines())
+                    .map(|line| PanelItem::from(line.to_string()))
+                    .collect();
+                items.extend(runbook.parameters.iter().map(|parameter| {
+                    PanelItem::styled(vec![(
+                        format!("{{{{{}}}}}", parameter.name),
+                        Some(Color::Cyan),
+                    )])
+                }));
+                items.extend(runbook.steps.iter().enumerate().map(|(index, step)| {
+                    let checkpoint = if step.approval.is_some() { "⏸ " } else { "" };
+                    PanelItem::from(format!("{}. {}{}", index + 1, checkpoint, step.name))
+                }));
+                items
+            }
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let (title, mut items, keys) = match &self.mode {
+            Mode::Running(run) => {
+                let keys = match run.next() {
+                    Next::Approve(_) => "panel.runbook_approve_keys",
+                    Next::Run(_) => "panel.runbook_keys",
+                    Next::Running(_) => "panel.runbook_running_keys",
+                    Next::Done => "panel.runbook_done_keys",
+                };
+                (
+                    i18n::t_args("panel.runbook", &[("name", &run.runbook.name)]),
+                    (0..run.steps.len())
+                        .map(|index| Self::step_item(run, index))
+                        .collect::<Vec<_>>(),
+                    keys,
+                )
+            }
+            _ => (
+                i18n::t_args(
+                    "panel.runbooks",
+                    &[("count", &self.entries.len().to_string())],
+                ),
+                self.entries.iter().map(Self::entry_item).collect(),
+                "panel.runbooks_keys",
+            ),
+        };
+        if items.is_empty() {
+            items.push(i18n::t("panel.runbooks_empty").into());
+        }
+        PanelView {
+            kind: PanelKind::Runbooks,
+            title,
+            items,
+            footer: Some(self.status.clone().unwrap_or_else(|| i18n::t(keys))),
+            preview: self.preview(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESTART: &str = r#"
+name = "Restart service"
+description = "Drain, restart and check a service"
+
+[[parameters]]
+name = "service"
+prompt = "Service"
+default = "api"
+
+[[parameters]]
+name = "reason"
+
+[[steps]]
+name = "Check health"
+command = "echo checking {{service}}"
+
+[[steps]]
+name = "Drain"
+approval = "Drain {{ service }} now?"
+command = "echo draining {{service}}"
+
+[[steps]]
+name = "Confirm dashboards"
+approval = "Dashboards look normal?"
+"#;
+
+    fn result(exit_code: i32, stdout: &str) -> RunResult {
+        RunResult {
+            block_id: String::new(),
+            command: String::new(),
+            shell: "sh".to_string(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: Some(exit_code),
+            duration_ms: 12,
+            started_at: chrono::Utc::now(),
+            timed_out: false,
+        }
+    }
+
+    fn run() -> RunbookRun {
+        RunbookRun::new(
+            PathBuf::from("restart.toml"),
+            Runbook::parse(RESTART).unwrap(),
+            vec![
+                ("service".to_string(), "api gateway".to_string()),
+                ("reason".to_string(), "deploy".to_string()),
+            ],
+            "alice".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_parse_and_validate() {
+        let runbook = Runbook::parse(RESTART).unwrap();
+        assert_eq!(runbook.parameters.len(), 2);
+        assert_eq!(runbook.steps[2].command, None);
+
+        let undeclared = "name = \"x\"\n[[steps]]\nname = \"a\"\ncommand = \"echo {{host}}\"";
+        assert!(Runbook::parse(undeclared).is_err());
+        let empty_step = "name = \"x\"\n[[steps]]\nname = \"a\"";
+        assert!(Runbook::parse(empty_step).is_err());
+        let duplicate = "name = \"x\"\n[[parameters]]\nname = \"a\"\n[[parameters]]\nname = \"a\"\n[[steps]]\nname = \"s\"\ncommand = \"true\"";
+        assert!(Runbook::parse(duplicate).is_err());
+        assert!(Runbook::parse("name = \"x\"\nsteps = []").is_err());
+    }
+
+    #[test]
+    fn test_values_are_quoted_in_commands_only() {
+        let values = vec![("service".to_string(), "api; rm -rf /".to_string())];
+        assert_eq!(
+            render("restart {{service}}", &values, true),
+            "restart 'api; rm -rf /'"
+        );
+        assert_eq!(
+            render("Restart {{ service }}?", &values, false),
+            "Restart api; rm -rf /?"
+        );
+    }
+
+    #[test]
+    fn test_checkpoints_wait_for_approval() {
+        let mut run = run();
+        assert_eq!(run.next(), Next::Run(0));
+        let (step, command) = run.start().unwrap();
+        assert_eq!((step, command.as_str()), (0, "echo checking 'api gateway'"));
+        assert_eq!(run.next(), Next::Running(0));
+        run.finish(
+            0,
+            &result(0, "ok"),
+            Some("warp://block/0123456789ab".to_string()),
+        );
+
+        assert_eq!(run.next(), Next::Approve(1));
+        assert_eq!(run.start(), None);
+        run.approve();
+        assert_eq!(run.steps[1].approved.as_ref().unwrap().0, "alice");
+        let (step, _) = run.start().unwrap();
+        run.finish(step, &result(3, "\x1b[31mfailed\x1b[0m"), None);
+        assert_eq!(run.steps[1].status, StepStatus::Failed);
+        assert_eq!(run.steps[1].tail, vec!["failed"]);
+        // A failure leaves the step current for a retry, keeping the approval
+        assert_eq!(run.next(), Next::Run(1));
+        let (step, _) = run.start().unwrap();
+        run.finish(step, &result(0, ""), None);
+
+        // A checkpoint without a command completes once approved
+        run.approve();
+        assert_eq!(run.start(), None);
+        assert!(run.is_finished());
+        assert!(run.finished_at.is_some());
+        assert!(run.succeeded());
+    }
+
+    #[test]
+    fn test_skip_and_abort() {
+        let mut run = run();
+        run.skip();
+        assert_eq!(run.steps[0].status, StepStatus::Skipped);
+        run.abort();
+        assert!(run.is_finished());
+        assert!(!run.succeeded());
+        assert!(run.report().contains("- Outcome: aborted at step 2"));
+    }
+
+    #[test]
+    fn test_report() {
+        let mut run = run();
+        run.start();
+        run.finish(
+            0,
+            &result(0, "healthy"),
+            Some("warp://block/0123456789ab".to_string()),
+        );
+        run.approve();
+        run.skip();
+        run.approve();
+        run.start();
+
+        let report = run.report();
+        assert!(report.starts_with("# Runbook report: Restart service\n"));
+        assert!(report.contains("- Operator: alice\n"));
+        assert!(report.contains("- `service` = `api gateway`\n"));
+        assert!(report.contains("### 1. Check health — succeeded\n"));
+        assert!(report.contains("- Command: `echo checking 'api gateway'`\n"));
+        assert!(report.contains("- Exit code: 0 after 12 ms\n"));
+        assert!(report.contains("- Evidence: warp://block/0123456789ab\n"));
+        assert!(report.contains("```text\nhealthy\n```"));
+        assert!(report.contains("### 2. Drain — skipped\n"));
+        assert!(report.contains("- Checkpoint: Drain api gateway now?\n"));
+        assert!(report.contains("### 3. Confirm dashboards — succeeded\n\n- Checkpoint"));
+        assert!(report.contains("- Approved by alice at "));
+        assert!(report.contains("- Outcome: completed with 1 skipped step(s)\n"));
+    }
+
+    #[tokio::test]
+    async fn test_discover_lists_broken_runbooks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("restart.toml"), RESTART).unwrap();
+        std::fs::write(dir.path().join("broken.toml"), "name = 1").unwrap();
+        std::fs::write(dir.path().join("notes.md"), "# not a runbook").unwrap();
+
+        let entries = discover(&[dir.path().to_path_buf(), dir.path().join("missing")]).await;
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].runbook.is_err());
+        assert_eq!(entries[1].runbook.as_ref().unwrap().name, "Restart service");
+
+        let report = run()
+            .write_report(&dir.path().join("reports"))
+            .await
+            .unwrap();
+        assert!(report
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("restart-"));
+        assert!(report.exists());
+    }
+
+    #[test]
+    fn test_panel_flow() {
+        let mut panel = RunbookPanel::new(vec![RunbookEntry {
+            path: PathBuf::from("restart.toml"),
+            runbook: Ok(Runbook::parse(RESTART).unwrap()),
+        }]);
+        assert!(matches!(
+            panel.handle_key('\n', 0),
+            RunbookCommand::Prompt(_)
+        ));
+        // Empty takes the default; a parameter without one asks again
+        assert!(matches!(panel.submit_input(""), RunbookCommand::Prompt(_)));
+        assert!(matches!(panel.submit_input(" "), RunbookCommand::Prompt(_)));
+        assert_eq!(panel.submit_input("deploy"), RunbookCommand::Redraw);
+        assert_eq!(
+            panel.run().unwrap().values[0],
+            ("service".to_string(), "api".to_string())
+        );
+
+        assert_eq!(
+            panel.handle_key('\n', 0),
+            RunbookCommand::Run {
+                step: 0,
+                command: "echo checking api".to_string(),
+                timeout: None,
+            }
+        );
+        assert_eq!(panel.handle_key('\n', 0), RunbookCommand::None);
+        assert_eq!(
+            panel.finish_step(0, Ok(&result(0, "")), None),
+            RunbookCommand::Redraw
+        );
+        assert!(matches!(
+            panel.handle_key('a', 1),
+            RunbookCommand::Run { step: 1, .. }
+        ));
+        assert_eq!(
+            panel.finish_step(1, Err("no shell".to_string()), None),
+            RunbookCommand::Redraw
+        );
+        assert_eq!(panel.handle_key('s', 1), RunbookCommand::Redraw);
+        assert_eq!(panel.handle_key('\n', 2), RunbookCommand::Finished);
+        assert_eq!(panel.handle_key('\n', 2), RunbookCommand::None);
+        assert_eq!(panel.view().items.len(), 3);
+        assert_eq!(panel.handle_key('b', 0), RunbookCommand::Redraw);
+        assert!(panel.run().is_none());
+    }
+}
@@ -66,7 +66,7 @@ impl ScriptEngine for LuaEngine {
         // Execute script
         match self.lua.load(script).exec() {
             Ok(_) => Ok("Script executed successfully".to_string()),
-            Err(e) => Err(WarpError::ConfigError(format!("Lua script error: {}", e))),
+            Err(e) => Err(WarpError::plugin("lua", format!("script error: {}", e))),
         }
     }
 
@@ -91,7 +91,7 @@ impl ScriptEngine for LuaEngine {
                     _ => Ok(format!("{:?}", value)),
                 }
             }
-            Err(e) => Err(WarpError::ConfigError(format!("Lua evaluation error: {}", e))),
+            Err(e) => Err(WarpError::plugin("lua", format!("evaluation error: {}", e))),
         }
     }
 
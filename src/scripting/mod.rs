@@ -67,7 +67,7 @@ impl ScriptingManager {
         context: Option<ScriptContext>,
     ) -> Result<String, WarpError> {
         let engine = self.engines.get(&language)
-            .ok_or_else(|| WarpError::ConfigError(format!("Script engine for {:?} not found", language)))?;
+            .ok_or_else(|| WarpError::not_found("script engine", format!("{:?}", language)))?;
 
         let ctx = if let Some(ctx) = context {
             ctx
@@ -85,7 +85,7 @@ impl ScriptingManager {
         context: Option<ScriptContext>,
     ) -> Result<String, WarpError> {
         let engine = self.engines.get(&language)
-            .ok_or_else(|| WarpError::ConfigError(format!("Script engine for {:?} not found", language)))?;
+            .ok_or_else(|| WarpError::not_found("script engine", format!("{:?}", language)))?;
 
         let ctx = if let Some(ctx) = context {
             ctx
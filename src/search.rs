@@ -1,9 +1,803 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use crossterm::style::Color;
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tantivy::collector::DocSetCollector;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{AllQuery, Query, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, FAST, INDEXED, STORED, STRING, TEXT};
+use tantivy::{Document, Index, IndexReader, IndexWriter, ReloadPolicy, Term};
+
+use crate::block_tags::{format_tags, tag_name, BlockTags};
 use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// tantivy's minimum for its single indexing thread
+const WRITER_HEAP_BYTES: usize = 15_000_000;
+// Newest matches loaded before the regex filter and grouping
+const MAX_CANDIDATES: usize = 2000;
+const MAX_RESULTS: usize = 200;
+
+// A finished block as it goes into the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockRecord {
+    pub session: String,
+    pub block: String,
+    pub command: String,
+    pub output: String,
+    pub cwd: Option<PathBuf>,
+    pub started: DateTime<Utc>,
+    // Until its last output; unknown for a block that printed nothing
+    pub duration_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+}
+
+impl BlockRecord {
+    pub fn new(session: &str, block: &str, command: &str, cwd: Option<PathBuf>) -> Self {
+        Self {
+            session: session.to_string(),
+            block: block.to_string(),
+            command: command.to_string(),
+            output: String::new(),
+            cwd,
+            started: Utc::now(),
+            duration_ms: None,
+            exit_code: None,
+        }
+    }
+
+    // Output arrived just now
+    pub fn touch(&mut self) {
+        self.duration_ms = Some((Utc::now() - self.started).num_milliseconds().max(0) as u64);
+    }
+
+    // Keeps the first `max_bytes` of the output, cut at a character boundary
+    pub fn set_output(&mut self, output: &str, max_bytes: usize) {
+        let mut end = output.len().min(max_bytes);
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.output = output[..end].to_string();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExitFilter {
+    Code(i32),
+    Failed,
+}
+
+// What the user typed, split into the text tantivy matches and the filters.
+// Plain words and "quoted phrases" must all appear; /regex/ is checked
+// against the command and output of each match; after:, before: and since:
+// bound the start time; exit:N or exit:fail the exit code. #tags are
+// resolved by the caller, which knows the tagged blocks (see restrict_to).
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    phrases: Vec<String>,
+    regex: Option<Regex>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    exit: Option<ExitFilter>,
+    tags: Vec<String>,
+    blocks: Option<HashSet<String>>,
+}
+
+// The slash closing a /regex/: the first one followed by a space or the end.
+// A slash starting a later word opens the next pattern instead, so without a
+// closing slash before it the token is an ordinary word, such as a path.
+fn closing_slash(rest: &str) -> Option<usize> {
+    let mut previous = '/';
+    for (i, c) in rest.char_indices().skip(1) {
+        let next = rest[i + 1..].chars().next();
+        if c == '/' && previous.is_whitespace() {
+            return None;
+        }
+        if c == '/' && next.is_none_or(char::is_whitespace) {
+            return Some(i);
+        }
+        previous = c;
+    }
+    None
+}
+
+fn local_midnight(date: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let midnight = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()?;
+    Some(midnight.with_timezone(&Utc))
+}
+
+// "30m", "2h", "7d", "2w"
+fn parse_age(age: &str) -> Option<Duration> {
+    let (count, unit) = age.split_at(age.find(|c: char| !c.is_ascii_digit())?);
+    let count: i64 = count.parse().ok()?;
+    match unit {
+        "m" => Some(Duration::minutes(count)),
+        "h" => Some(Duration::hours(count)),
+        "d" => Some(Duration::days(count)),
+        "w" => Some(Duration::weeks(count)),
+        _ => None,
+    }
+}
 
-pub struct SearchEngine;
+impl SearchQuery {
+    pub fn parse(input: &str, now: DateTime<Utc>) -> Result<Self, WarpError> {
+        let invalid = |token: &str| {
+            WarpError::Validation(i18n::t_args("search.invalid", &[("token", token)]))
+        };
+        let mut query = SearchQuery::default();
+        let mut rest = input.trim_start();
+        while !rest.is_empty() {
+            let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (token, after) = match rest.chars().next() {
+                Some('"') => match rest[1..].find('"') {
+                    Some(end) => rest.split_at(end + 2),
+                    None => return Err(invalid(rest)),
+                },
+                Some('/') => rest.split_at(closing_slash(rest).map_or(word_end, |end| end + 1)),
+                _ => rest.split_at(word_end),
+            };
+            rest = after.trim_start();
+
+            let regex = token
+                .strip_prefix('/')
+                .and_then(|t| t.strip_suffix('/'))
+                .filter(|pattern| !pattern.is_empty());
+            if let Some(phrase) = token.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                query.push_phrase(phrase);
+            } else if let Some(pattern) = regex {
+                query.regex = Some(Regex::new(pattern).map_err(|_| invalid(token))?);
+            } else if let Some(date) = token.strip_prefix("after:") {
+                query.after = Some(local_midnight(date).ok_or_else(|| invalid(token))?);
+            } else if let Some(date) = token.strip_prefix("before:") {
+                query.before = Some(local_midnight(date).ok_or_else(|| invalid(token))?);
+            } else if let Some(age) = token.strip_prefix("since:") {
+                query.after = Some(now - parse_age(age).ok_or_else(|| invalid(token))?);
+            } else if token.starts_with('#') {
+                query
+                    .tags
+                    .push(tag_name(token).ok_or_else(|| invalid(token))?);
+            } else if let Some(code) = token.strip_prefix("exit:") {
+                query.exit = Some(match code {
+                    "fail" | "failed" => ExitFilter::Failed,
+                    code => ExitFilter::Code(code.parse().map_err(|_| invalid(token))?),
+                });
+            } else {
+                query.push_phrase(&token.replace('"', ""));
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    // Only these blocks can match
+    pub fn restrict_to(&mut self, blocks: HashSet<String>) {
+        self.blocks = Some(blocks);
+    }
+
+    // Punctuation alone has no tokens to match
+    fn push_phrase(&mut self, phrase: &str) {
+        if phrase.chars().any(char::is_alphanumeric) {
+            self.phrases.push(phrase.to_string());
+        }
+    }
+
+    // In tantivy's query language, every clause required. Words are quoted
+    // so that punctuation is never read as query syntax.
+    fn index_query(&self) -> String {
+        let mut clauses: Vec<String> = self
+            .phrases
+            .iter()
+            .map(|phrase| format!("\"{}\"", phrase))
+            .collect();
+        if self.after.is_some() || self.before.is_some() {
+            let from = self.after.map_or(0, |t| t.timestamp());
+            let to = self.before.map_or(i64::MAX, |t| t.timestamp() - 1);
+            clauses.push(format!("started:[{} TO {}]", from, to));
+        }
+        match self.exit {
+            Some(ExitFilter::Code(code)) => clauses.push(format!("exit_code:{}", code)),
+            Some(ExitFilter::Failed) => clauses.push(format!("exit_code:[1 TO {}]", i32::MAX)),
+            None => {}
+        }
+        clauses.join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionHits {
+    pub session: String,
+    pub hits: Vec<BlockRecord>, // newest first
+}
+
+struct Fields {
+    session: Field,
+    block: Field,
+    command: Field,
+    output: Field,
+    cwd: Field,
+    started: Field,
+    duration_ms: Field,
+    exit_code: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Fields) {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            session: builder.add_text_field("session", STRING | STORED),
+            block: builder.add_text_field("block", STRING | STORED),
+            command: builder.add_text_field("command", TEXT | STORED),
+            output: builder.add_text_field("output", TEXT | STORED),
+            cwd: builder.add_text_field("cwd", STRING | STORED),
+            started: builder.add_i64_field("started", INDEXED | STORED | FAST),
+            duration_ms: builder.add_i64_field("duration_ms", STORED),
+            exit_code: builder.add_i64_field("exit_code", INDEXED | STORED),
+        };
+        (builder.build(), fields)
+    }
+
+    fn document(&self, record: &BlockRecord) -> Document {
+        let mut document = Document::default();
+        document.add_text(self.session, &record.session);
+        document.add_text(self.block, &record.block);
+        document.add_text(self.command, &record.command);
+        document.add_text(self.output, &record.output);
+        if let Some(cwd) = &record.cwd {
+            document.add_text(self.cwd, cwd.to_string_lossy());
+        }
+        document.add_i64(self.started, record.started.timestamp());
+        if let Some(duration) = record.duration_ms {
+            document.add_i64(self.duration_ms, duration as i64);
+        }
+        if let Some(code) = record.exit_code {
+            document.add_i64(self.exit_code, code as i64);
+        }
+        document
+    }
+
+    fn record(&self, document: &Document) -> BlockRecord {
+        let text = |field| {
+            document
+                .get_first(field)
+                .and_then(|value| value.as_text())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let number = |field| document.get_first(field).and_then(|value| value.as_i64());
+        BlockRecord {
+            session: text(self.session),
+            block: text(self.block),
+            command: text(self.command),
+            output: text(self.output),
+            cwd: Some(text(self.cwd))
+                .filter(|cwd| !cwd.is_empty())
+                .map(PathBuf::from),
+            started: number(self.started)
+                .and_then(|t| Utc.timestamp_opt(t, 0).single())
+                .unwrap_or_default(),
+            duration_ms: number(self.duration_ms).map(|ms| ms as u64),
+            exit_code: number(self.exit_code).map(|code| code as i32),
+        }
+    }
+}
+
+fn index_err(e: impl std::fmt::Display) -> WarpError {
+    WarpError::command_err(format!("search index: {}", e))
+}
+
+// Commands and their output from every session, kept in a tantivy index in
+// the data directory
+pub struct SearchEngine {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
 
 impl SearchEngine {
     pub async fn new() -> Result<Self, WarpError> {
-        Ok(Self)
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        let dir = data_dir.join("warp").join("search");
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::task::spawn_blocking(move || Self::open(&dir))
+            .await
+            .map_err(index_err)?
+    }
+
+    pub fn open(dir: &Path) -> Result<Self, WarpError> {
+        let (schema, fields) = Fields::schema();
+        let directory = MmapDirectory::open(dir).map_err(index_err)?;
+        Self::with_index(
+            Index::open_or_create(directory, schema).map_err(index_err)?,
+            fields,
+        )
+    }
+
+    pub fn in_memory() -> Result<Self, WarpError> {
+        let (schema, fields) = Fields::schema();
+        Self::with_index(Index::create_in_ram(schema), fields)
+    }
+
+    fn with_index(index: Index, fields: Fields) -> Result<Self, WarpError> {
+        let writer = index
+            .writer_with_num_threads(1, WRITER_HEAP_BYTES)
+            .map_err(index_err)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(index_err)?;
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+        })
+    }
+
+    // Blocks on disk I/O; the app calls it from spawn_blocking
+    pub fn index_block(&self, record: &BlockRecord) -> Result<(), WarpError> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer
+            .add_document(self.fields.document(record))
+            .map_err(index_err)?;
+        writer.commit().map_err(index_err)?;
+        self.reader.reload().map_err(index_err)
+    }
+
+    // One block by its permalink id, e.g. a pinned one from an earlier session
+    pub fn find_block(&self, block: &str) -> Result<Option<BlockRecord>, WarpError> {
+        let query = TermQuery::new(
+            Term::from_field_text(self.fields.block, block),
+            IndexRecordOption::Basic,
+        );
+        let searcher = self.reader.searcher();
+        let found = searcher
+            .search(&query, &DocSetCollector)
+            .map_err(index_err)?;
+        match found.into_iter().next() {
+            Some(address) => Ok(Some(
+                self.fields
+                    .record(&searcher.doc(address).map_err(index_err)?),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    // Matches grouped by session, the session with the newest match first
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SessionHits>, WarpError> {
+        let mut hits = self.matching(query, Some(MAX_CANDIDATES))?;
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.started));
+        hits.truncate(MAX_RESULTS);
+
+        let mut sessions: Vec<SessionHits> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for hit in hits {
+            let position = *positions.entry(hit.session.clone()).or_insert_with(|| {
+                sessions.push(SessionHits {
+                    session: hit.session.clone(),
+                    hits: Vec::new(),
+                });
+                sessions.len() - 1
+            });
+            sessions[position].hits.push(hit);
+        }
+        Ok(sessions)
+    }
+
+    // Every block started in [after, before), oldest first, e.g. for export
+    pub fn blocks(
+        &self,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<BlockRecord>, WarpError> {
+        let query = SearchQuery {
+            after,
+            before,
+            ..SearchQuery::default()
+        };
+        let mut blocks = self.matching(&query, None)?;
+        blocks.sort_by_key(|block| block.started);
+        Ok(blocks)
+    }
+
+    // The newest `limit` matches in index order
+    fn matching(
+        &self,
+        query: &SearchQuery,
+        limit: Option<usize>,
+    ) -> Result<Vec<BlockRecord>, WarpError> {
+        let text = query.index_query();
+        let parsed: Box<dyn Query> = match text.is_empty() {
+            true => Box::new(AllQuery),
+            false => {
+                let mut parser = QueryParser::for_index(
+                    &self.index,
+                    vec![self.fields.command, self.fields.output],
+                );
+                parser.set_conjunction_by_default();
+                parser
+                    .parse_query(&text)
+                    .map_err(|e| WarpError::Validation(e.to_string()))?
+            }
+        };
+
+        let searcher = self.reader.searcher();
+        let mut addresses: Vec<_> = searcher
+            .search(&parsed, &DocSetCollector)
+            .map_err(index_err)?
+            .into_iter()
+            .collect();
+        // Later documents were added later
+        addresses.sort_unstable_by(|a, b| b.cmp(a));
+        if let Some(limit) = limit {
+            addresses.truncate(limit);
+        }
+
+        let mut hits = Vec::new();
+        for address in addresses {
+            let record = self
+                .fields
+                .record(&searcher.doc(address).map_err(index_err)?);
+            let allowed = match &query.blocks {
+                Some(blocks) => blocks.contains(&record.block),
+                None => true,
+            };
+            let matches = match &query.regex {
+                Some(regex) => regex.is_match(&record.command) || regex.is_match(&record.output),
+                None => true,
+            };
+            if allowed && matches {
+                hits.push(record);
+            }
+        }
+        Ok(hits)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchCommand {
+    None,
+    // Ask for a new query at the panel prompt
+    Prompt(String),
+    Open(BlockRecord),
+}
+
+enum Row {
+    Session(String),
+    Hit(BlockRecord),
+}
+
+// Results of the last search, a header row per session with its blocks
+// under it; Enter opens the selected block
+pub struct SearchPanel {
+    session: String,
+    query: String,
+    rows: Vec<Row>,
+    tags: HashMap<String, BTreeSet<String>>,
+    selected: usize,
+}
+
+impl SearchPanel {
+    pub fn new(session: &str) -> Self {
+        Self {
+            session: session.to_string(),
+            query: String::new(),
+            rows: Vec::new(),
+            tags: HashMap::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn prompt_label() -> String {
+        i18n::t("search.prompt")
+    }
+
+    pub fn set_results(&mut self, query: &str, results: Vec<SessionHits>, tags: &BlockTags) {
+        self.query = query.to_string();
+        self.tags = results
+            .iter()
+            .flat_map(|group| &group.hits)
+            .filter_map(|hit| Some((hit.block.clone(), tags.get(&hit.block)?.tags.clone())))
+            .collect();
+        self.rows = results
+            .into_iter()
+            .flat_map(|group| {
+                std::iter::once(Row::Session(group.session))
+                    .chain(group.hits.into_iter().map(Row::Hit))
+            })
+            .collect();
+        // Start on the first block rather than its session header
+        self.selected = usize::from(!self.rows.is_empty());
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> SearchCommand {
+        self.select(index);
+        match (key, self.rows.get(self.selected)) {
+            ('/', _) => SearchCommand::Prompt(Self::prompt_label()),
+            ('\n', Some(Row::Hit(hit))) => SearchCommand::Open(hit.clone()),
+            _ => SearchCommand::None,
+        }
+    }
+
+    fn session_label(&self, session: &str, first: Option<&BlockRecord>) -> String {
+        match (session == self.session, first) {
+            (true, _) => i18n::t("search.this_session"),
+            (false, Some(hit)) => i18n::t_args(
+                "search.session",
+                &[(
+                    "started",
+                    &hit.started
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                )],
+            ),
+            (false, None) => session.to_string(),
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| match row {
+                Row::Session(session) => {
+                    let first = match self.rows.get(i + 1) {
+                        Some(Row::Hit(hit)) => Some(hit),
+                        _ => None,
+                    };
+                    PanelItem::styled(vec![(
+                        self.session_label(session, first),
+                        Some(Color::Cyan),
+                    )])
+                }
+                Row::Hit(hit) => {
+                    let exit = match hit.exit_code {
+                        Some(0) => (" ✓".to_string(), Some(Color::Green)),
+                        Some(code) => (format!(" ✗ {}", code), Some(Color::Red)),
+                        None => (String::new(), None),
+                    };
+                    PanelItem::styled(vec![
+                        (
+                            format!("  {} ", hit.started.with_timezone(&Local).format("%H:%M")),
+                            Some(Color::DarkGrey),
+                        ),
+                        (hit.command.clone(), None),
+                        exit,
+                        (
+                            self.tags
+                                .get(&hit.block)
+                                .map(format_tags)
+                                .unwrap_or_default(),
+                            Some(Color::Cyan),
+                        ),
+                    ])
+                }
+            })
+            .collect();
+        let preview = match self.rows.get(self.selected) {
+            Some(Row::Hit(hit)) => {
+                let mut preview: Vec<PanelItem> = Vec::new();
+                if let Some(cwd) = &hit.cwd {
+                    preview.push(PanelItem::styled(vec![(
+                        cwd.display().to_string(),
+                        Some(Color::DarkGrey),
+                    )]));
+                }
+                preview.extend(
+                    hit.output
+                        .lines()
+                        .take(20)
+                        .map(|line| line.to_string().into()),
+                );
+                preview
+            }
+            Some(Row::Session(_)) => Vec::new(),
+            None => vec![i18n::t("search.empty").into()],
+        };
+        let count = self
+            .rows
+            .iter()
+            .filter(|row| matches!(row, Row::Hit(_)))
+            .count();
+        PanelView {
+            kind: PanelKind::Search,
+            title: i18n::t_args(
+                "panel.search",
+                &[("query", &self.query), ("count", &count.to_string())],
+            ),
+            items,
+            footer: Some(i18n::t("panel.search_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        session: &str,
+        command: &str,
+        output: &str,
+        age_hours: i64,
+        exit: i32,
+    ) -> BlockRecord {
+        let mut record =
+            BlockRecord::new(session, &format!("{}-{}", session, command), command, None);
+        record.output = output.to_string();
+        record.started = Utc::now() - Duration::hours(age_hours);
+        record.exit_code = Some(exit);
+        record.duration_ms = Some(1500);
+        record
+    }
+
+    fn engine() -> SearchEngine {
+        let engine = SearchEngine::in_memory().unwrap();
+        for record in [
+            record(
+                "old",
+                "kubectl apply -f deploy.yaml",
+                "deployment.apps/api configured",
+                72,
+                0,
+            ),
+            record(
+                "old",
+                "cargo test",
+                "test result: FAILED. 3 passed; 1 failed",
+                70,
+                101,
+            ),
+            record(
+                "new",
+                "kubectl get pods",
+                "api-7f9c Running\nworker-2b1d CrashLoopBackOff",
+                1,
+                0,
+            ),
+            record(
+                "new",
+                "cargo build",
+                "Finished dev [unoptimized] target(s)",
+                0,
+                0,
+            ),
+        ] {
+            engine.index_block(&record).unwrap();
+        }
+        engine
+    }
+
+    fn search(engine: &SearchEngine, input: &str) -> Vec<Vec<String>> {
+        let query = SearchQuery::parse(input, Utc::now()).unwrap();
+        engine
+            .search(&query)
+            .unwrap()
+            .into_iter()
+            .map(|group| group.hits.into_iter().map(|hit| hit.command).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_groups_by_session_newest_first() {
+        let engine = engine();
+        assert_eq!(
+            search(&engine, "kubectl"),
+            vec![
+                vec!["kubectl get pods"],
+                vec!["kubectl apply -f deploy.yaml"]
+            ]
+        );
+        assert_eq!(search(&engine, "\"1 failed\""), vec![vec!["cargo test"]]);
+        assert!(search(&engine, "\"failed 1\"").is_empty());
+    }
+
+    #[test]
+    fn test_filters() {
+        let engine = engine();
+        assert_eq!(search(&engine, "cargo exit:fail"), vec![vec!["cargo test"]]);
+        assert_eq!(search(&engine, "exit:101"), vec![vec!["cargo test"]]);
+        assert_eq!(
+            search(&engine, "since:1d"),
+            vec![vec!["cargo build", "kubectl get pods"]]
+        );
+        assert_eq!(
+            search(&engine, r"/CrashLoop\w+/ pods"),
+            vec![vec!["kubectl get pods"]]
+        );
+        assert_eq!(search(&engine, "api-server:8080").len(), 0);
+
+        let mut query = SearchQuery::parse("#Deploy kubectl", Utc::now()).unwrap();
+        assert_eq!(query.tags(), ["deploy"]);
+        query.restrict_to(HashSet::from([
+            "old-kubectl apply -f deploy.yaml".to_string()
+        ]));
+        let hits = engine.search(&query).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].hits[0].command, "kubectl apply -f deploy.yaml");
+    }
+
+    #[test]
+    fn test_lists_blocks_oldest_first() {
+        let engine = engine();
+        let blocks = engine
+            .blocks(Some(Utc::now() - Duration::days(1)), None)
+            .unwrap();
+        let commands: Vec<_> = blocks.iter().map(|block| block.command.as_str()).collect();
+        assert_eq!(commands, ["kubectl get pods", "cargo build"]);
+        assert_eq!(blocks[0].duration_ms, Some(1500));
+        assert_eq!(engine.blocks(None, None).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_rejects_bad_filters() {
+        for input in [
+            "since:soon",
+            "exit:x",
+            "\"unclosed",
+            "/(/",
+            "after:yesterday",
+            "#a,b",
+        ] {
+            assert!(SearchQuery::parse(input, Utc::now()).is_err(), "{}", input);
+        }
+        let query = SearchQuery::parse("/etc/hosts /warn(ing)?/ -", Utc::now()).unwrap();
+        assert_eq!(query.phrases, vec!["/etc/hosts"]);
+        assert_eq!(query.regex.unwrap().as_str(), "warn(ing)?");
+    }
+
+    #[test]
+    fn test_persists_across_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        SearchEngine::open(dir.path())
+            .unwrap()
+            .index_block(&record("old", "make release", "", 5, 0))
+            .unwrap();
+        let engine = SearchEngine::open(dir.path()).unwrap();
+        assert_eq!(search(&engine, "release"), vec![vec!["make release"]]);
+        let found = engine.find_block("old-make release").unwrap().unwrap();
+        assert_eq!(found.command, "make release");
+        assert!(engine.find_block("old-make").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_panel_opens_hits() {
+        let mut tags = BlockTags::default();
+        let target = crate::block_tags::BlockRef {
+            session: "new".to_string(),
+            block: "new-kubectl get pods".to_string(),
+            command: "kubectl get pods".to_string(),
+        };
+        tags.retag(&target, &["incident-42".to_string()], &[]);
+        let mut panel = SearchPanel::new("new");
+        let query = SearchQuery::parse("kubectl", Utc::now()).unwrap();
+        panel.set_results("kubectl", engine().search(&query).unwrap(), &tags);
+        let view = panel.view();
+        assert_eq!(view.items.len(), 4);
+        assert_eq!(view.items[1].segments.last().unwrap().0, " #incident-42");
+        assert!(
+            matches!(panel.handle_key('\n', 1), SearchCommand::Open(hit) if hit.session == "new")
+        );
+        assert_eq!(panel.handle_key('\n', 2), SearchCommand::None);
+        assert!(matches!(panel.handle_key('/', 0), SearchCommand::Prompt(_)));
     }
 }
@@ -0,0 +1,186 @@
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+use crate::error::WarpError;
+use crate::shutdown::write_atomic;
+
+const KEY_FILE: &str = "secrets.key";
+const STORE_FILE: &str = "secrets.json";
+
+// Tokens and passwords for integrations, encrypted at rest with AES-256-GCM.
+// The key lives next to the store with owner-only permissions; this keeps
+// secrets out of config files, dotfile repos and backups of the store
+// alone, not away from someone who can read the user's home directory.
+pub struct SecretsManager {
+    store_path: PathBuf,
+    key: LessSafeKey,
+    rng: SystemRandom,
+    // Serialises read-modify-write of the store file
+    write_lock: Mutex<()>,
+}
+
+impl SecretsManager {
+    pub async fn new() -> Result<Self, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        Self::open(&data_dir.join("warp")).await
+    }
+
+    pub async fn open(dir: &Path) -> Result<Self, WarpError> {
+        let rng = SystemRandom::new();
+        let key_path = dir.join(KEY_FILE);
+        let key_bytes = match tokio::fs::read(&key_path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut bytes = vec![0u8; AES_256_GCM.key_len()];
+                rng.fill(&mut bytes)
+                    .map_err(|_| WarpError::config_err("Could not generate a secrets key"))?;
+                write_atomic(&key_path, &bytes).await?;
+                restrict_permissions(&key_path).await?;
+                bytes
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let key = UnboundKey::new(&AES_256_GCM, &key_bytes)
+            .map_err(|_| WarpError::config_err(format!("{} is corrupt", key_path.display())))?;
+
+        Ok(Self {
+            store_path: dir.join(STORE_FILE),
+            key: LessSafeKey::new(key),
+            rng,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    pub async fn get(&self, name: &str) -> Result<Option<String>, WarpError> {
+        let store = self.load().await?;
+        store
+            .get(name)
+            .map(|sealed| self.open_value(name, sealed))
+            .transpose()
+    }
+
+    pub async fn set(&self, name: &str, value: &str) -> Result<(), WarpError> {
+        let _guard = self.write_lock.lock().await;
+        let mut store = self.load().await?;
+        store.insert(name.to_string(), self.seal_value(name, value)?);
+        self.save(&store).await
+    }
+
+    // Returns false if there was nothing stored under `name`
+    pub async fn remove(&self, name: &str) -> Result<bool, WarpError> {
+        let _guard = self.write_lock.lock().await;
+        let mut store = self.load().await?;
+        let removed = store.remove(name).is_some();
+        if removed {
+            self.save(&store).await?;
+        }
+        Ok(removed)
+    }
+
+    pub async fn names(&self) -> Result<Vec<String>, WarpError> {
+        Ok(self.load().await?.into_keys().collect())
+    }
+
+    async fn load(&self) -> Result<BTreeMap<String, String>, WarpError> {
+        match tokio::fs::read(&self.store_path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, store: &BTreeMap<String, String>) -> Result<(), WarpError> {
+        write_atomic(&self.store_path, &serde_json::to_vec_pretty(store)?).await?;
+        restrict_permissions(&self.store_path).await
+    }
+
+    // base64(nonce || ciphertext || tag). The name is authenticated data, so
+    // a value copied to another entry fails to decrypt.
+    fn seal_value(&self, name: &str, value: &str) -> Result<String, WarpError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| WarpError::config_err("Could not generate a nonce"))?;
+        let mut sealed = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(name.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| WarpError::config_err(format!("Could not encrypt secret '{}'", name)))?;
+
+        let mut out = nonce.to_vec();
+        out.extend(sealed);
+        Ok(base64::engine::general_purpose::STANDARD.encode(out))
+    }
+
+    fn open_value(&self, name: &str, sealed: &str) -> Result<String, WarpError> {
+        let corrupt = || WarpError::config_err(format!("Secret '{}' could not be decrypted", name));
+        let mut bytes = base64::engine::general_purpose::STANDARD
+            .decode(sealed)
+            .map_err(|_| corrupt())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(corrupt());
+        }
+        let mut ciphertext = bytes.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&bytes).map_err(|_| corrupt())?;
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut ciphertext)
+            .map_err(|_| corrupt())?;
+        String::from_utf8(plain.to_vec()).map_err(|_| corrupt())
+    }
+}
+
+#[cfg(unix)]
+pub(crate) async fn restrict_permissions(path: &Path) -> Result<(), WarpError> {
+    use std::os::unix::fs::PermissionsExt;
+    tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn restrict_permissions(_path: &Path) -> Result<(), WarpError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_roundtrip_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::open(dir.path()).await.unwrap();
+        secrets.set("github.com", "ghp_token").await.unwrap();
+        assert_eq!(
+            secrets.get("github.com").await.unwrap().as_deref(),
+            Some("ghp_token")
+        );
+
+        let stored = std::fs::read_to_string(dir.path().join(STORE_FILE)).unwrap();
+        assert!(!stored.contains("ghp_token"));
+
+        let reopened = SecretsManager::open(dir.path()).await.unwrap();
+        assert_eq!(
+            reopened.get("github.com").await.unwrap().as_deref(),
+            Some("ghp_token")
+        );
+        assert!(reopened.remove("github.com").await.unwrap());
+        assert!(reopened.get("github.com").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_value_is_bound_to_its_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::open(dir.path()).await.unwrap();
+        let sealed = secrets.seal_value("a", "secret").unwrap();
+        assert!(secrets.open_value("b", &sealed).is_err());
+    }
+}
@@ -1,9 +1,167 @@
+use regex::Regex;
+
+use crate::config::{CommandRule, PolicyAction, SecurityConfig, TrustLevel, WarpConfig};
 use crate::error::WarpError;
 
-pub struct SecurityManager;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDecision {
+    pub action: PolicyAction,
+    pub reason: Option<String>,
+}
+
+// Command policy: commands the terminal runs on the user's behalf (install
+// suggestions, corrections) are checked here before they are offered. Also
+// decides how far each command's output is trusted (see escape_filter).
+pub struct SecurityManager {
+    rules: Vec<(Regex, CommandRule)>,
+    remote_commands: Vec<Regex>,
+    untrusted_commands: Vec<Regex>,
+}
+
+pub(crate) fn compile(what: &str, patterns: &[String]) -> Result<Vec<Regex>, WarpError> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| {
+                WarpError::config_err(format!("Invalid {} '{}': {}", what, pattern, e))
+            })
+        })
+        .collect()
+}
 
 impl SecurityManager {
     pub async fn new() -> Result<Self, WarpError> {
-        Ok(Self)
+        Self::from_config(&WarpConfig::default().security)
+    }
+
+    pub fn from_config(config: &SecurityConfig) -> Result<Self, WarpError> {
+        let rules = config
+            .command_rules
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .map(|regex| (regex, rule.clone()))
+                    .map_err(|e| {
+                        WarpError::config_err(format!(
+                            "Invalid command rule '{}': {}",
+                            rule.pattern, e
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            rules,
+            remote_commands: compile("remote command pattern", &config.escapes.remote_commands)?,
+            untrusted_commands: compile(
+                "untrusted command pattern",
+                &config.escapes.untrusted_commands,
+            )?,
+        })
+    }
+
+    pub fn trust_for_command(&self, command: &str) -> TrustLevel {
+        if self
+            .untrusted_commands
+            .iter()
+            .any(|regex| regex.is_match(command))
+        {
+            TrustLevel::Untrusted
+        } else if self
+            .remote_commands
+            .iter()
+            .any(|regex| regex.is_match(command))
+        {
+            TrustLevel::Remote
+        } else {
+            TrustLevel::Local
+        }
+    }
+
+    pub fn check_command(&self, command: &str) -> PolicyDecision {
+        match self.rules.iter().find(|(regex, _)| regex.is_match(command)) {
+            Some((_, rule)) => PolicyDecision {
+                action: rule.action,
+                reason: Some(rule.reason.clone()),
+            },
+            None => PolicyDecision {
+                action: PolicyAction::Allow,
+                reason: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_default_rules() {
+        let security = SecurityManager::new().await.unwrap();
+        assert_eq!(
+            security.check_command("rm -rf /").action,
+            PolicyAction::Deny
+        );
+        assert_eq!(
+            security.check_command("rm -rf ./build").action,
+            PolicyAction::Allow
+        );
+        assert_eq!(
+            security.check_command("sudo apt install jq").action,
+            PolicyAction::Confirm
+        );
+        assert_eq!(
+            security
+                .check_command("curl -fsSL https://x.sh | sh")
+                .action,
+            PolicyAction::Confirm
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trust_for_command() {
+        let security = SecurityManager::new().await.unwrap();
+        assert_eq!(security.trust_for_command("ls -la"), TrustLevel::Local);
+        assert_eq!(
+            security.trust_for_command("ssh build@ci"),
+            TrustLevel::Remote
+        );
+        assert_eq!(
+            security.trust_for_command("kubectl exec -it web -- sh"),
+            TrustLevel::Remote
+        );
+        assert_eq!(
+            security.trust_for_command("cat ~/Downloads/README.txt"),
+            TrustLevel::Untrusted
+        );
+        assert_eq!(
+            security.trust_for_command("curl https://example.com/install.txt"),
+            TrustLevel::Untrusted
+        );
+        assert_eq!(
+            security.trust_for_command("cat Cargo.toml"),
+            TrustLevel::Local
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_a_config_error() {
+        let mut config = WarpConfig::default().security;
+        config.command_rules = vec![CommandRule {
+            pattern: "(".to_string(),
+            action: PolicyAction::Deny,
+            reason: String::new(),
+        }];
+        assert!(matches!(
+            SecurityManager::from_config(&config),
+            Err(WarpError::Config(_))
+        ));
+
+        let mut config = WarpConfig::default().security;
+        config.escapes.untrusted_commands.push("[".to_string());
+        assert!(matches!(
+            SecurityManager::from_config(&config),
+            Err(WarpError::Config(_))
+        ));
     }
 }
@@ -0,0 +1,609 @@
+use crossterm::style::Color;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::config::{validation, WarpConfig};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Fields picked from a fixed list rather than typed
+const CHOICES: &[(&str, &[&str])] = &[
+    ("general.telemetry", &["Ask", "Off", "LocalOnly", "Share"]),
+    ("ui.tab_bar_position", &["top", "bottom"]),
+    ("terminal.cursor_style", &["block", "underline", "bar"]),
+    ("ai.provider", &["openai", "anthropic", "ollama"]),
+    (
+        "debug.log_level",
+        &["error", "warn", "info", "debug", "trace"],
+    ),
+    ("gpu.backend", &["auto", "vulkan", "dx12", "metal", "gl"]),
+    ("gpu.power_preference", &["low", "high"]),
+    ("wasm.sandbox_level", &["strict", "moderate", "permissive"]),
+    (
+        "watchdog.default_policy.action",
+        &["Warn", "Throttle", "Kill"],
+    ),
+];
+
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_default()
+        .join("warp")
+        .join("config.toml")
+}
+
+// A JSON pointer to `path`; map keys such as project roots may contain '/'
+pub fn pointer(path: &[String]) -> String {
+    path.iter()
+        .map(|part| format!("/{}", part.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(slot) => merge(slot, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (slot, value) => *slot = value,
+    }
+}
+
+// The settings in effect: the file's values over the defaults. The file may
+// hold only some sections, so it isn't parsed as a WarpConfig on its own.
+pub async fn load_current(file: &Path) -> Result<WarpConfig, WarpError> {
+    let mut config = serde_json::to_value(WarpConfig::default())?;
+    if let Ok(text) = fs::read_to_string(file).await {
+        let table: toml::Table = toml::from_str(&text)?;
+        merge(&mut config, serde_json::to_value(table)?);
+    }
+    Ok(serde_json::from_value(config)?)
+}
+
+// Sets one key in the config file, leaving the rest of it as it was.
+// A null value removes the key, so the default applies again.
+pub async fn write_setting(file: &Path, path: &[String], value: &Value) -> Result<(), WarpError> {
+    let mut document: toml::Table = match fs::read_to_string(file).await {
+        Ok(text) => toml::from_str(&text)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let Some((last, parents)) = path.split_last() else {
+        return Ok(());
+    };
+    let mut table = &mut document;
+    for part in parents {
+        let entry = table
+            .entry(part.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        table = entry.as_table_mut().ok_or_else(|| {
+            WarpError::config_err(format!("{} in {} is not a table", part, file.display()))
+        })?;
+    }
+    if value.is_null() {
+        table.remove(last);
+    } else {
+        table.insert(last.clone(), toml::Value::try_from(value)?);
+    }
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::write(file, toml::to_string_pretty(&document)?).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKind {
+    Toggle,
+    Choice(&'static [&'static str]),
+    Number,
+    Text,
+    List, // arrays are edited in the file
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub path: Vec<String>,
+    pub kind: FieldKind,
+    pub value: Value,
+}
+
+impl Field {
+    pub fn key(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+// The leaf fields of a section, nested tables flattened into dotted paths
+pub fn fields(config: &Value, section: &str) -> Vec<Field> {
+    fn walk(value: &Value, path: &mut Vec<String>, out: &mut Vec<Field>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, value) in map {
+                    path.push(key.clone());
+                    walk(value, path, out);
+                    path.pop();
+                }
+            }
+            _ => {
+                let key = path.join(".");
+                let kind = match CHOICES.iter().find(|(choice, _)| *choice == key) {
+                    Some((_, choices)) => FieldKind::Choice(choices),
+                    None => match value {
+                        Value::Bool(_) => FieldKind::Toggle,
+                        Value::Number(_) => FieldKind::Number,
+                        Value::Array(_) | Value::Object(_) => FieldKind::List,
+                        Value::String(_) | Value::Null => FieldKind::Text,
+                    },
+                };
+                out.push(Field {
+                    path: path.clone(),
+                    kind,
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+    let mut out = Vec::new();
+    if let Some(value) = config.get(section) {
+        walk(value, &mut vec![section.to_string()], &mut out);
+    }
+    out
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => i18n::t("settings.unset"),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => {
+            i18n::t_args("settings.items", &[("count", &items.len().to_string())])
+        }
+        Value::Object(map) => i18n::t_args("settings.items", &[("count", &map.len().to_string())]),
+        other => other.to_string(),
+    }
+}
+
+// Typed text as the field's type; Text fields offer fallbacks, tried in
+// order until one deserializes (an unset Option<u64> looks like text)
+fn candidates(field: &Field, text: &str) -> Result<Vec<Value>, String> {
+    let text = text.trim();
+    match field.kind {
+        // Optional numbers are unset by clearing them
+        FieldKind::Number if text.is_empty() => Ok(vec![Value::Null]),
+        FieldKind::Number => {
+            let number = if field.value.is_u64() {
+                text.parse::<u64>().ok().map(Value::from)
+            } else if field.value.is_i64() {
+                text.parse::<i64>().ok().map(Value::from)
+            } else {
+                text.parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(Value::Number)
+            };
+            number
+                .map(|number| vec![number])
+                .ok_or_else(|| i18n::t_args("settings.not_a_number", &[("value", text)]))
+        }
+        _ if text.is_empty() => Ok(vec![Value::Null, Value::from("")]),
+        _ => {
+            let mut values = Vec::new();
+            if let Ok(number) = text.parse::<u64>() {
+                values.push(Value::from(number));
+            }
+            values.push(Value::from(text));
+            Ok(values)
+        }
+    }
+}
+
+enum Row {
+    Section(String),
+    Field(Field),
+}
+
+// A change that passed validation, for the app to save and apply
+pub struct Change {
+    pub path: Vec<String>,
+    pub value: Value,
+    pub config: Box<WarpConfig>,
+}
+
+pub enum SettingsCommand {
+    None,
+    Redraw,
+    // Ask for a value at the prompt, starting from the current one
+    Prompt { label: String, current: String },
+    Apply(Change),
+}
+
+pub struct SettingsPanel {
+    file: PathBuf,
+    config: Value,
+    defaults: Value,
+    expanded: HashSet<String>,
+    rows: Vec<Row>,
+    selected: usize,
+    // The field waiting for text from the prompt
+    editing: Option<Field>,
+    status: Option<String>,
+}
+
+impl SettingsPanel {
+    pub fn new(config: &WarpConfig, file: PathBuf) -> Result<Self, WarpError> {
+        let mut panel = Self {
+            file,
+            config: serde_json::to_value(config)?,
+            defaults: serde_json::to_value(WarpConfig::default())?,
+            expanded: HashSet::new(),
+            rows: Vec::new(),
+            selected: 0,
+            editing: None,
+            status: None,
+        };
+        panel.rebuild();
+        Ok(panel)
+    }
+
+    fn rebuild(&mut self) {
+        self.rows.clear();
+        let Some(sections) = self.config.as_object() else {
+            return;
+        };
+        for section in sections.keys() {
+            self.rows.push(Row::Section(section.clone()));
+            if self.expanded.contains(section) {
+                self.rows
+                    .extend(fields(&self.config, section).into_iter().map(Row::Field));
+            }
+        }
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> SettingsCommand {
+        self.select(index);
+        let field = match self.rows.get(self.selected) {
+            Some(Row::Section(section)) => {
+                if key == '\n' || key == ' ' {
+                    if !self.expanded.remove(section) {
+                        self.expanded.insert(section.clone());
+                    }
+                    self.rebuild();
+                }
+                return SettingsCommand::Redraw;
+            }
+            Some(Row::Field(field)) => field.clone(),
+            None => return SettingsCommand::None,
+        };
+        match key {
+            '\n' | ' ' => match &field.kind {
+                FieldKind::Toggle => {
+                    let value = Value::Bool(!field.value.as_bool().unwrap_or_default());
+                    self.set(&field.path, vec![value])
+                }
+                FieldKind::Choice(choices) => {
+                    let current = field.value.as_str().unwrap_or_default();
+                    let next = choices
+                        .iter()
+                        .position(|choice| *choice == current)
+                        .map_or(0, |index| (index + 1) % choices.len());
+                    self.set(&field.path, vec![Value::from(choices[next])])
+                }
+                FieldKind::Number | FieldKind::Text => {
+                    let current = match &field.value {
+                        Value::Null => String::new(),
+                        value => display(value),
+                    };
+                    let label = field.key();
+                    self.editing = Some(field);
+                    SettingsCommand::Prompt { label, current }
+                }
+                FieldKind::List => {
+                    self.status = Some(i18n::t_args(
+                        "settings.edit_in_file",
+                        &[("file", &self.file.display().to_string())],
+                    ));
+                    SettingsCommand::Redraw
+                }
+            },
+            'd' if field.kind != FieldKind::List => {
+                let default = self
+                    .defaults
+                    .pointer(&pointer(&field.path))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                self.set(&field.path, vec![default])
+            }
+            _ => SettingsCommand::Redraw,
+        }
+    }
+
+    pub fn submit_input(&mut self, text: &str) -> SettingsCommand {
+        let Some(field) = self.editing.take() else {
+            return SettingsCommand::None;
+        };
+        match candidates(&field, text) {
+            Ok(values) => self.set(&field.path, values),
+            Err(message) => {
+                self.status = Some(message);
+                SettingsCommand::Redraw
+            }
+        }
+    }
+
+    // Applies the first candidate that still deserializes into a valid
+    // WarpConfig; otherwise the reason is shown and nothing changes
+    fn set(&mut self, path: &[String], values: Vec<Value>) -> SettingsCommand {
+        let mut error = None;
+        for value in values {
+            let mut candidate = self.config.clone();
+            let Some(slot) = candidate.pointer_mut(&pointer(path)) else {
+                return SettingsCommand::None;
+            };
+            *slot = value.clone();
+            let config = match serde_json::from_value::<WarpConfig>(candidate.clone()) {
+                Ok(config) => config,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    continue;
+                }
+            };
+            if let Err(e) = validation::validate_config(&config) {
+                error = Some(e.to_string());
+                continue;
+            }
+            self.config = candidate;
+            self.status = None;
+            self.rebuild();
+            return SettingsCommand::Apply(Change {
+                path: path.to_vec(),
+                value,
+                config: Box::new(config),
+            });
+        }
+        self.status = error;
+        SettingsCommand::Redraw
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Section(section) => {
+                    let marker = if self.expanded.contains(section) {
+                        "▾"
+                    } else {
+                        "▸"
+                    };
+                    PanelItem::styled(vec![(format!("{} {}", marker, section), Some(Color::Cyan))])
+                }
+                Row::Field(field) => {
+                    let changed =
+                        self.defaults.pointer(&pointer(&field.path)) != Some(&field.value);
+                    let color = match (&field.kind, &field.value) {
+                        (FieldKind::List, _) => Color::DarkGrey,
+                        (FieldKind::Toggle, Value::Bool(false)) => Color::DarkGrey,
+                        (FieldKind::Toggle, _) => Color::Green,
+                        _ if changed => Color::Yellow,
+                        _ => Color::White,
+                    };
+                    PanelItem::styled(vec![
+                        (format!("    {:<28}", field.path[1..].join(".")), None),
+                        (display(&field.value), Some(color)),
+                        (
+                            if changed { " *" } else { "" }.to_string(),
+                            Some(Color::Yellow),
+                        ),
+                    ])
+                }
+            })
+            .collect();
+        PanelView {
+            kind: PanelKind::Settings,
+            title: i18n::t_args(
+                "panel.settings",
+                &[("file", &self.file.display().to_string())],
+            ),
+            items,
+            footer: Some(
+                self.status
+                    .clone()
+                    .unwrap_or_else(|| i18n::t("panel.settings_keys")),
+            ),
+            preview: self.preview(),
+            image: None,
+        }
+    }
+
+    fn preview(&self) -> Vec<PanelItem> {
+        let Some(Row::Field(field)) = self.rows.get(self.selected) else {
+            return Vec::new();
+        };
+        let default = self
+            .defaults
+            .pointer(&pointer(&field.path))
+            .map(display)
+            .unwrap_or_default();
+        let mut lines = vec![
+            PanelItem::styled(vec![(field.key(), Some(Color::Cyan))]),
+            i18n::t_args("settings.default", &[("value", &default)]).into(),
+        ];
+        if let FieldKind::Choice(choices) = field.kind {
+            lines.push(choices.join(" · ").into());
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelemetryMode;
+
+    fn panel() -> SettingsPanel {
+        SettingsPanel::new(&WarpConfig::default(), PathBuf::from("/tmp/config.toml")).unwrap()
+    }
+
+    fn row_of(panel: &SettingsPanel, key: &str) -> usize {
+        panel
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Field(field) if field.key() == key))
+            .unwrap()
+    }
+
+    fn expand(panel: &mut SettingsPanel, section: &str) {
+        let index = panel
+            .rows
+            .iter()
+            .position(|row| matches!(row, Row::Section(name) if name == section))
+            .unwrap();
+        panel.handle_key('\n', index);
+    }
+
+    #[test]
+    fn test_fields_flatten_nested_tables() {
+        let config = serde_json::to_value(WarpConfig::default()).unwrap();
+        let ssh = fields(&config, "ssh");
+        let reconnect = ssh
+            .iter()
+            .find(|field| field.key() == "ssh.reconnect.max_attempts")
+            .unwrap();
+        assert_eq!(reconnect.kind, FieldKind::Number);
+        let action = fields(&config, "watchdog")
+            .into_iter()
+            .find(|field| field.key() == "watchdog.default_policy.action")
+            .unwrap();
+        assert!(matches!(action.kind, FieldKind::Choice(_)));
+    }
+
+    #[test]
+    fn test_toggle_and_choice_apply() {
+        let mut panel = panel();
+        expand(&mut panel, "terminal");
+        let index = row_of(&panel, "terminal.bell");
+        match panel.handle_key('\n', index) {
+            SettingsCommand::Apply(change) => {
+                assert_eq!(change.path, vec!["terminal", "bell"]);
+                assert!(change.config.terminal.bell);
+            }
+            _ => panic!("expected Apply"),
+        }
+        let index = row_of(&panel, "terminal.cursor_style");
+        match panel.handle_key('\n', index) {
+            SettingsCommand::Apply(change) => assert_eq!(change.value, Value::from("underline")),
+            _ => panic!("expected Apply"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_input_is_rejected() {
+        let mut panel = panel();
+        expand(&mut panel, "ui");
+        let index = row_of(&panel, "ui.font_size");
+        assert!(matches!(
+            panel.handle_key('\n', index),
+            SettingsCommand::Prompt { .. }
+        ));
+        assert!(matches!(panel.submit_input("200"), SettingsCommand::Redraw));
+        assert!(panel.status.as_deref().unwrap().contains("font_size"));
+
+        panel.handle_key('\n', index);
+        assert!(matches!(panel.submit_input("big"), SettingsCommand::Redraw));
+
+        panel.handle_key('\n', index);
+        match panel.submit_input("16") {
+            SettingsCommand::Apply(change) => assert_eq!(change.config.ui.font_size, 16),
+            _ => panic!("expected Apply"),
+        }
+    }
+
+    #[test]
+    fn test_optional_fields_accept_numbers_and_unset() {
+        let mut panel = panel();
+        expand(&mut panel, "watchdog");
+        let index = row_of(&panel, "watchdog.default_policy.max_runtime_secs");
+        panel.handle_key('\n', index);
+        match panel.submit_input("300") {
+            SettingsCommand::Apply(change) => {
+                assert_eq!(
+                    change.config.watchdog.default_policy.max_runtime_secs,
+                    Some(300)
+                )
+            }
+            _ => panic!("expected Apply"),
+        }
+        let index = row_of(&panel, "watchdog.default_policy.max_runtime_secs");
+        match panel.handle_key('d', index) {
+            SettingsCommand::Apply(change) => assert!(change.value.is_null()),
+            _ => panic!("expected Apply"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_setting_keeps_other_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        std::fs::write(
+            &file,
+            "[ui]\ntheme = \"light\"\n\n[terminal]\nshell = \"fish\"\n",
+        )
+        .unwrap();
+
+        let path = |key: &str| key.split('.').map(str::to_string).collect::<Vec<_>>();
+        write_setting(&file, &path("ui.font_size"), &Value::from(16))
+            .await
+            .unwrap();
+        write_setting(&file, &path("ssh.reconnect.enabled"), &Value::Bool(false))
+            .await
+            .unwrap();
+        write_setting(&file, &path("terminal.shell"), &Value::Null)
+            .await
+            .unwrap();
+
+        let table: toml::Table = toml::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(table["ui"]["theme"].as_str(), Some("light"));
+        assert_eq!(table["ui"]["font_size"].as_integer(), Some(16));
+        assert_eq!(table["ssh"]["reconnect"]["enabled"].as_bool(), Some(false));
+        assert!(table["terminal"].get("shell").is_none());
+
+        let current = load_current(&file).await.unwrap();
+        assert_eq!(current.ui.theme, "light");
+        assert_eq!(current.ui.font_size, 16);
+        assert_eq!(current.terminal.shell, WarpConfig::default().terminal.shell);
+    }
+
+    #[tokio::test]
+    async fn test_old_telemetry_flag_still_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        std::fs::write(&file, "[general]\ntelemetry = false\n").unwrap();
+        assert_eq!(
+            load_current(&file).await.unwrap().general.telemetry,
+            TelemetryMode::Ask
+        );
+        std::fs::write(&file, "[general]\ntelemetry = \"LocalOnly\"\n").unwrap();
+        assert_eq!(
+            load_current(&file).await.unwrap().general.telemetry,
+            TelemetryMode::LocalOnly
+        );
+    }
+}
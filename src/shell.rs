@@ -1,23 +1,480 @@
+use crossterm::style::Color;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::{config::Config, error::WarpError};
+use crate::{
+    command_not_found,
+    config::{Config, ConnectionProfile, PaneDefaults, ShellsConfig, TerminalConfig},
+    encoding::OutputEncoding,
+    error::WarpError,
+    i18n,
+    ui::{PanelItem, PanelKind, PanelView},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Zsh,
+    Bash,
+    Fish,
+    PowerShell,
+    Nushell,
+    Other,
+}
+
+impl ShellKind {
+    pub fn detect(shell: &str) -> Self {
+        match shell_name(shell).as_str() {
+            "zsh" => ShellKind::Zsh,
+            "bash" | "sh" => ShellKind::Bash,
+            "fish" => ShellKind::Fish,
+            "pwsh" | "powershell" => ShellKind::PowerShell,
+            "nu" | "nushell" => ShellKind::Nushell,
+            _ => ShellKind::Other,
+        }
+    }
+
+    // nushell and PowerShell don't run a script piped to stdin; they take it
+    // as an argument. None for the POSIX-style shells, which do.
+    pub fn script_args(&self, command: &str) -> Option<Vec<String>> {
+        match self {
+            ShellKind::Nushell => Some(vec!["-c".to_string(), command.to_string()]),
+            ShellKind::PowerShell => Some(vec![
+                "-NoLogo".to_string(),
+                "-NonInteractive".to_string(),
+                "-Command".to_string(),
+                command.to_string(),
+            ]),
+            _ => None,
+        }
+    }
+}
+
+// "/usr/local/bin/fish" -> "fish", "pwsh.exe" -> "pwsh"
+fn shell_name(shell: &str) -> String {
+    Path::new(shell)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| shell.to_string())
+}
+
+// What a new tab asks for. Unset fields fall back to the project's defaults
+// (when `project_defaults` is set) and then to TerminalConfig.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneRequest {
+    pub shell: Option<String>,
+    pub profile: Option<String>,
+    pub cwd: PathBuf,
+    pub project_defaults: bool,
+}
+
+impl PaneRequest {
+    pub fn new(cwd: PathBuf) -> Self {
+        Self {
+            shell: None,
+            profile: None,
+            cwd,
+            project_defaults: true,
+        }
+    }
+}
+
+// Everything needed to start a tab's shell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneSpec {
+    pub shell: String,
+    pub args: Vec<String>,
+    pub profile: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub unset: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub encoding: OutputEncoding,
+}
+
+impl PaneSpec {
+    // The shell as given, in the terminal's own environment
+    pub fn plain(shell: &str) -> Self {
+        Self {
+            shell: shell.to_string(),
+            args: Vec::new(),
+            profile: None,
+            env: Vec::new(),
+            unset: Vec::new(),
+            cwd: None,
+            encoding: OutputEncoding::Utf8,
+        }
+    }
+
+    pub fn kind(&self) -> ShellKind {
+        ShellKind::detect(&self.shell)
+    }
+
+    // Shell indicator for the tab title, e.g. "fish · staging"
+    pub fn title(&self) -> String {
+        match &self.profile {
+            Some(profile) => format!("{} · {}", shell_name(&self.shell), profile),
+            None => shell_name(&self.shell),
+        }
+    }
+}
+
+// Most specific project root containing `cwd`
+pub fn project_defaults(shells: &ShellsConfig, cwd: &Path) -> PaneDefaults {
+    shells
+        .projects
+        .iter()
+        .filter(|(root, _)| cwd.starts_with(root))
+        .max_by_key(|(root, _)| root.components().count())
+        .map(|(_, defaults)| defaults.clone())
+        .unwrap_or_default()
+}
+
+pub fn resolve(
+    terminal: &TerminalConfig,
+    shells: &ShellsConfig,
+    request: &PaneRequest,
+) -> Result<PaneSpec, WarpError> {
+    let defaults = match request.project_defaults {
+        true => project_defaults(shells, &request.cwd),
+        false => PaneDefaults::default(),
+    };
+    let shell = request
+        .shell
+        .clone()
+        .or(defaults.shell)
+        .unwrap_or_else(|| terminal.shell.clone());
+    let profile = request.profile.clone().or(defaults.profile);
+    let encoding = defaults.encoding.unwrap_or(terminal.output_encoding);
+    let (mut env, unset) = match &profile {
+        Some(name) => {
+            let profile = shells.profiles.get(name).ok_or_else(|| {
+                WarpError::config_err(i18n::t_args("shell.unknown_profile", &[("profile", name)]))
+            })?;
+            let env: Vec<(String, String)> = profile.env.clone().into_iter().collect();
+            (env, profile.unset.clone())
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    env.sort();
+    // shell_args are written for the configured shell, not whatever a tab picks
+    let args = match shell == terminal.shell {
+        true => terminal.shell_args.clone(),
+        false => Vec::new(),
+    };
+    Ok(PaneSpec {
+        shell,
+        args,
+        profile,
+        env,
+        unset,
+        cwd: Some(request.cwd.clone()),
+        encoding,
+    })
+}
 
 pub struct ShellManager {
     config: Arc<Mutex<Config>>,
     current_shell: String,
+    shells: ShellsConfig,
 }
 
 impl ShellManager {
     pub async fn new(config: Arc<Mutex<Config>>) -> Result<Self, WarpError> {
-        let shell = {
+        let (shell, shells) = {
             let cfg = config.lock().await;
-            cfg.terminal.shell.clone()
+            (cfg.terminal.shell.clone(), cfg.shells.clone())
         };
 
         Ok(Self {
             config,
             current_shell: shell,
+            shells,
         })
     }
+
+    pub fn current_shell(&self) -> &str {
+        &self.current_shell
+    }
+
+    pub async fn resolve(&self, request: &PaneRequest) -> Result<PaneSpec, WarpError> {
+        let terminal = self.config.lock().await.terminal.clone();
+        resolve(&terminal, &self.shells, request)
+    }
+
+    // Called once a tab's shell is running; it becomes the current one
+    pub fn opened(&mut self, spec: &PaneSpec) {
+        self.current_shell = spec.shell.clone();
+    }
+
+    pub fn project_defaults(&self, cwd: &Path) -> PaneDefaults {
+        project_defaults(&self.shells, cwd)
+    }
+
+    // The configured shells that are actually installed
+    pub fn installed(&self) -> Vec<String> {
+        self.shells
+            .available
+            .iter()
+            .filter(|shell| match Path::new(shell).is_absolute() {
+                true => command_not_found::is_executable(Path::new(shell)),
+                false => command_not_found::find_in_path(shell).is_some(),
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn connection(&self, name: &str) -> Option<&ConnectionProfile> {
+        self.shells.connections.get(name)
+    }
+
+    pub fn connections(&self) -> &BTreeMap<String, ConnectionProfile> {
+        &self.shells.connections
+    }
+
+    pub fn profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.shells.profiles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+pub enum NewTabCommand {
+    None,
+    Redraw,
+    Open(PaneRequest),
+}
+
+// Picks the shell and environment profile for a new tab, starting from the
+// defaults for the directory it opens in
+pub struct NewTabPanel {
+    shells: Vec<String>,
+    profiles: Vec<String>,
+    profile: Option<usize>,
+    selected: usize,
+    cwd: PathBuf,
+}
+
+impl NewTabPanel {
+    pub async fn new(manager: &ShellManager, cwd: PathBuf) -> Self {
+        let defaults = manager.project_defaults(&cwd);
+        let default_shell = match defaults.shell {
+            Some(shell) => shell,
+            None => manager.config.lock().await.terminal.shell.clone(),
+        };
+        let mut shells = manager.installed();
+        // The default is offered even when it isn't on PATH, e.g. a full path
+        if !shells.contains(&default_shell) {
+            shells.insert(0, default_shell.clone());
+        }
+        let profiles = manager.profiles();
+        Self {
+            selected: shells.iter().position(|s| *s == default_shell).unwrap_or(0),
+            profile: defaults
+                .profile
+                .and_then(|name| profiles.iter().position(|p| *p == name)),
+            shells,
+            profiles,
+            cwd,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.shells.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> NewTabCommand {
+        self.select(index);
+        match key {
+            '\n' => NewTabCommand::Open(PaneRequest {
+                shell: self.shells.get(self.selected).cloned(),
+                profile: self.profile.map(|i| self.profiles[i].clone()),
+                cwd: self.cwd.clone(),
+                // The panel already started from them, and the user may
+                // have deliberately picked no profile
+                project_defaults: false,
+            }),
+            // Cycles through the profiles and back to none
+            'p' if !self.profiles.is_empty() => {
+                self.profile = match self.profile {
+                    None => Some(0),
+                    Some(i) if i + 1 < self.profiles.len() => Some(i + 1),
+                    Some(_) => None,
+                };
+                NewTabCommand::Redraw
+            }
+            _ => NewTabCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .shells
+            .iter()
+            .map(|shell| {
+                PanelItem::styled(vec![
+                    (format!("{:<6}", shell_name(shell)), None),
+                    (shell.clone(), Some(Color::DarkGrey)),
+                ])
+            })
+            .collect();
+        let profile = match self.profile {
+            Some(i) => self.profiles[i].clone(),
+            None => i18n::t("panel.new_tab_no_profile"),
+        };
+        PanelView {
+            kind: PanelKind::NewTab,
+            title: i18n::t_args("panel.new_tab", &[("cwd", &self.cwd.display().to_string())]),
+            items,
+            footer: Some(i18n::t_args("panel.new_tab_keys", &[("profile", &profile)])),
+            preview: Vec::new(),
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EnvironmentProfile, WarpConfig};
+    use std::collections::HashMap;
+
+    fn shells() -> ShellsConfig {
+        let mut shells = WarpConfig::default().shells;
+        shells.profiles.insert(
+            "staging".to_string(),
+            EnvironmentProfile {
+                env: HashMap::from([
+                    ("AWS_PROFILE".to_string(), "staging".to_string()),
+                    (
+                        "API_URL".to_string(),
+                        "https://staging.example.com".to_string(),
+                    ),
+                ]),
+                unset: vec!["KUBECONFIG".to_string()],
+            },
+        );
+        shells.projects.insert(
+            PathBuf::from("/work"),
+            PaneDefaults {
+                shell: Some("bash".to_string()),
+                profile: None,
+                encoding: None,
+                container: None,
+            },
+        );
+        shells.projects.insert(
+            PathBuf::from("/work/api"),
+            PaneDefaults {
+                shell: Some("fish".to_string()),
+                profile: Some("staging".to_string()),
+                encoding: None,
+                container: None,
+            },
+        );
+        shells.projects.insert(
+            PathBuf::from("/retro"),
+            PaneDefaults {
+                shell: None,
+                profile: None,
+                encoding: Some(OutputEncoding::Cp437),
+                container: None,
+            },
+        );
+        shells
+    }
+
+    fn terminal() -> TerminalConfig {
+        let mut terminal = WarpConfig::default().terminal;
+        terminal.shell = "zsh".to_string();
+        terminal.shell_args = vec!["-l".to_string()];
+        terminal
+    }
+
+    #[test]
+    fn test_detects_shell_kind() {
+        assert_eq!(ShellKind::detect("/usr/bin/zsh"), ShellKind::Zsh);
+        assert_eq!(ShellKind::detect("pwsh.exe"), ShellKind::PowerShell);
+        assert_eq!(
+            ShellKind::detect("/opt/homebrew/bin/nu"),
+            ShellKind::Nushell
+        );
+        assert_eq!(ShellKind::detect("xonsh"), ShellKind::Other);
+    }
+
+    #[test]
+    fn test_falls_back_to_terminal_config() {
+        let request = PaneRequest::new(PathBuf::from("/home/me"));
+        let spec = resolve(&terminal(), &shells(), &request).unwrap();
+        assert_eq!(spec.shell, "zsh");
+        assert_eq!(spec.args, vec!["-l"]);
+        assert!(spec.env.is_empty());
+        assert_eq!(spec.title(), "zsh");
+    }
+
+    #[test]
+    fn test_most_specific_project_wins() {
+        let spec = resolve(
+            &terminal(),
+            &shells(),
+            &PaneRequest::new(PathBuf::from("/work/api/src")),
+        )
+        .unwrap();
+        assert_eq!(spec.shell, "fish");
+        assert!(spec.args.is_empty());
+        assert_eq!(
+            spec.env[0],
+            (
+                "API_URL".to_string(),
+                "https://staging.example.com".to_string()
+            )
+        );
+        assert_eq!(spec.unset, vec!["KUBECONFIG"]);
+        assert_eq!(spec.title(), "fish · staging");
+
+        let spec = resolve(
+            &terminal(),
+            &shells(),
+            &PaneRequest::new(PathBuf::from("/work/web")),
+        )
+        .unwrap();
+        assert_eq!(spec.title(), "bash");
+    }
+
+    #[test]
+    fn test_request_overrides_project() {
+        let mut request = PaneRequest::new(PathBuf::from("/work/api"));
+        request.shell = Some("nu".to_string());
+        let spec = resolve(&terminal(), &shells(), &request).unwrap();
+        assert_eq!(spec.title(), "nu · staging");
+
+        request.project_defaults = false;
+        let spec = resolve(&terminal(), &shells(), &request).unwrap();
+        assert_eq!(spec.title(), "nu");
+
+        request.profile = Some("missing".to_string());
+        assert!(resolve(&terminal(), &shells(), &request).is_err());
+    }
+
+    #[test]
+    fn test_encoding_from_project_or_terminal() {
+        let spec = resolve(
+            &terminal(),
+            &shells(),
+            &PaneRequest::new(PathBuf::from("/retro/bbs")),
+        )
+        .unwrap();
+        assert_eq!(spec.encoding, OutputEncoding::Cp437);
+        assert_eq!(spec.shell, "zsh");
+
+        let mut terminal = terminal();
+        terminal.output_encoding = OutputEncoding::ShiftJis;
+        let spec = resolve(
+            &terminal,
+            &shells(),
+            &PaneRequest::new(PathBuf::from("/work/api")),
+        )
+        .unwrap();
+        assert_eq!(spec.encoding, OutputEncoding::ShiftJis);
+    }
 }
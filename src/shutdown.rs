@@ -0,0 +1,329 @@
+use crossterm::{
+    event::{DisableBracketedPaste, DisableFocusChange},
+    terminal::{self, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::error::WarpError;
+use crate::ui::UIEvent;
+
+// Stages run in declaration order; everything that produces data is stopped
+// before anything that persists it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShutdownStage {
+    Background,
+    Plugins,
+    Processes,
+    Persistence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    UserExit,
+    Signal(&'static str),
+    Error,
+}
+
+pub type FlushHook = Box<dyn FnOnce() -> BoxFuture<'static, Result<(), WarpError>> + Send>;
+
+struct RegisteredTask {
+    stage: ShutdownStage,
+    name: String,
+    handle: JoinHandle<()>,
+}
+
+// Cheap cloneable handle background tasks select on to notice shutdown
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    receiver: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    pub async fn triggered(&mut self) {
+        while !*self.receiver.borrow() {
+            if self.receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub struct ShutdownController {
+    sender: watch::Sender<bool>,
+    started: AtomicBool,
+    task_timeout: Duration,
+    tasks: Mutex<Vec<RegisteredTask>>,
+    flush_hooks: Mutex<Vec<(String, FlushHook)>>,
+}
+
+impl ShutdownController {
+    pub fn new(task_timeout: Duration) -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender,
+            started: AtomicBool::new(false),
+            task_timeout,
+            tasks: Mutex::new(Vec::new()),
+            flush_hooks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    pub async fn register_task(
+        &self,
+        stage: ShutdownStage,
+        name: impl Into<String>,
+        handle: JoinHandle<()>,
+    ) {
+        self.tasks.lock().await.push(RegisteredTask {
+            stage,
+            name: name.into(),
+            handle,
+        });
+    }
+
+    // Hooks run during the Persistence stage, in registration order. Buffered
+    // writers (history, analytics queues, ...) register here.
+    pub async fn register_flush_hook(&self, name: impl Into<String>, hook: FlushHook) {
+        self.flush_hooks.lock().await.push((name.into(), hook));
+    }
+
+    // Returns false if shutdown was already under way, so a second SIGTERM
+    // during cleanup doesn't start it again
+    pub fn trigger(&self, reason: ShutdownReason) -> bool {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        log::info!("Shutting down: {:?}", reason);
+        let _ = self.sender.send(true);
+        true
+    }
+
+    // Waits for every task registered for `stage`, aborting any still running
+    // once the task timeout has passed. The timeout covers the whole stage, so
+    // several hung tasks don't add up. Returns the names of aborted tasks.
+    pub async fn stop_stage(&self, stage: ShutdownStage) -> Vec<String> {
+        let stage_tasks: Vec<RegisteredTask> = {
+            let mut tasks = self.tasks.lock().await;
+            let (matching, rest) = std::mem::take(&mut *tasks)
+                .into_iter()
+                .partition(|t| t.stage == stage);
+            *tasks = rest;
+            matching
+        };
+
+        let deadline = tokio::time::Instant::now() + self.task_timeout;
+        let mut aborted = Vec::new();
+        for mut task in stage_tasks {
+            if tokio::time::timeout_at(deadline, &mut task.handle)
+                .await
+                .is_err()
+            {
+                log::warn!(
+                    "Task '{}' did not stop within {:?}; aborting",
+                    task.name,
+                    self.task_timeout
+                );
+                task.handle.abort();
+                aborted.push(task.name);
+            }
+        }
+        aborted
+    }
+
+    pub async fn run_flush_hooks(&self) -> Vec<(String, WarpError)> {
+        let hooks = std::mem::take(&mut *self.flush_hooks.lock().await);
+        let mut failures = Vec::new();
+        for (name, hook) in hooks {
+            match tokio::time::timeout(self.task_timeout, hook()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => failures.push((name, e)),
+                Err(_) => failures.push((name, WarpError::terminal_err("flush timed out"))),
+            }
+        }
+        for (name, e) in &failures {
+            log::error!("Flush hook '{}' failed: {}", name, e);
+        }
+        failures
+    }
+
+    // SIGTERM/SIGHUP trigger the same path as a user exit: the event loop is
+    // asked to quit and run() performs the ordered cleanup
+    pub fn listen_for_signals(
+        self: &std::sync::Arc<Self>,
+        event_sender: mpsc::UnboundedSender<UIEvent>,
+    ) -> JoinHandle<()> {
+        let controller = self.clone();
+        tokio::spawn(async move {
+            let name = wait_for_signal().await;
+            if controller.trigger(ShutdownReason::Signal(name)) {
+                let _ = event_sender.send(UIEvent::Quit);
+            }
+        })
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (Ok(mut term), Ok(mut hup)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::hangup()),
+    ) else {
+        log::warn!("Could not install signal handlers");
+        return std::future::pending().await;
+    };
+    tokio::select! {
+        _ = term.recv() => "SIGTERM",
+        _ = hup.recv() => "SIGHUP",
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "CTRL_C"
+}
+
+// Safe to call more than once and from a panic hook
+pub fn restore_terminal() {
+    let _ = stdout().execute(DisableBracketedPaste);
+    let _ = stdout().execute(DisableFocusChange);
+    let _ = stdout().execute(LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+// A panic would otherwise leave the user's shell in raw mode on the alternate screen
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        previous(info);
+    }));
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionState {
+    pub working_directory: PathBuf,
+    pub shells: Vec<String>,
+    pub active_shell: Option<usize>,
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl SessionState {
+    pub fn default_path() -> Result<PathBuf, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::Config("Could not find data directory".to_string()))?;
+        Ok(data_dir.join("warp").join("session.json"))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), WarpError> {
+        write_atomic(path, &serde_json::to_vec_pretty(self)?).await
+    }
+
+    pub async fn load(path: &Path) -> Result<Option<Self>, WarpError> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// Write to a sibling temp file, fsync, then rename over the target. A crash
+// or kill mid-write leaves the previous file intact instead of a truncated one.
+pub async fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), WarpError> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = tokio::fs::File::create(&tmp_path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hung_task_is_aborted_after_timeout() {
+        let controller = ShutdownController::new(Duration::from_millis(20));
+        let mut signal = controller.subscribe();
+
+        let cooperative = tokio::spawn(async move { signal.triggered().await });
+        let hung = tokio::spawn(std::future::pending::<()>());
+        controller
+            .register_task(ShutdownStage::Background, "cooperative", cooperative)
+            .await;
+        controller
+            .register_task(ShutdownStage::Background, "hung", hung)
+            .await;
+
+        assert!(controller.trigger(ShutdownReason::UserExit));
+        assert!(!controller.trigger(ShutdownReason::Signal("SIGTERM")));
+        assert_eq!(
+            controller.stop_stage(ShutdownStage::Background).await,
+            vec!["hung".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stage_timeout_is_shared_by_its_tasks() {
+        let controller = ShutdownController::new(Duration::from_millis(100));
+        for name in ["first", "second", "third"] {
+            let hung = tokio::spawn(std::future::pending::<()>());
+            controller
+                .register_task(ShutdownStage::Plugins, name, hung)
+                .await;
+        }
+
+        let started = std::time::Instant::now();
+        assert_eq!(controller.stop_stage(ShutdownStage::Plugins).await.len(), 3);
+        assert!(started.elapsed() < Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("session.json");
+        write_atomic(&path, b"first").await.unwrap();
+        write_atomic(&path, b"second").await.unwrap();
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"second");
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+}
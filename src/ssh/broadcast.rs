@@ -0,0 +1,585 @@
+use crossterm::style::Color;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use super::{SshSessionManager, SshTarget};
+use crate::config::SSHConfig;
+use crate::error::WarpError;
+use crate::git::{self, DiffHunk};
+use crate::i18n;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Output past this is cut off; a broadcast is for checking hosts, not
+// for pulling logs off them
+const MAX_OUTPUT_BYTES: usize = 256 * 1024;
+const PREVIEW_LINES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostStatus {
+    Pending,
+    Succeeded,
+    Failed(Option<i32>), // None when ssh was killed by a signal
+    TimedOut,
+    Error(String),
+}
+
+impl HostStatus {
+    pub fn is_done(&self) -> bool {
+        !matches!(self, HostStatus::Pending)
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            HostStatus::Failed(_) | HostStatus::TimedOut | HostStatus::Error(_)
+        )
+    }
+
+    fn label(&self) -> (String, Option<Color>) {
+        match self {
+            HostStatus::Pending => (
+                format!("⋯ {}", i18n::t("broadcast.pending")),
+                Some(Color::DarkGrey),
+            ),
+            HostStatus::Succeeded => ("✓ 0".to_string(), Some(Color::Green)),
+            HostStatus::Failed(Some(code)) => (format!("✗ {}", code), Some(Color::Red)),
+            HostStatus::Failed(None) => (
+                format!("✗ {}", i18n::t("broadcast.killed")),
+                Some(Color::Red),
+            ),
+            HostStatus::TimedOut => (
+                format!("⏱ {}", i18n::t("broadcast.timed_out")),
+                Some(Color::Red),
+            ),
+            HostStatus::Error(_) => (
+                format!("✗ {}", i18n::t("broadcast.error")),
+                Some(Color::Red),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HostResult {
+    pub host: String,
+    pub status: HostStatus,
+    pub output: String, // stdout, then stderr
+    pub duration: Duration,
+}
+
+// Runs `run` for every host, at most `limit` at a time, and yields the
+// results as they finish. A host that takes longer than `timeout` is
+// dropped (and with it the ssh process) and reported as timed out.
+pub fn fan_out<F, Fut>(
+    hosts: Vec<String>,
+    limit: usize,
+    timeout: Duration,
+    run: F,
+) -> impl Stream<Item = HostResult>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<(Option<i32>, String), WarpError>>,
+{
+    stream::iter(hosts)
+        .map(move |host| {
+            let task = run(host.clone());
+            async move {
+                let started = Instant::now();
+                let (status, output) = match tokio::time::timeout(timeout, task).await {
+                    Ok(Ok((Some(0), output))) => (HostStatus::Succeeded, output),
+                    Ok(Ok((code, output))) => (HostStatus::Failed(code), output),
+                    Ok(Err(e)) => (HostStatus::Error(e.to_string()), String::new()),
+                    Err(_) => (HostStatus::TimedOut, String::new()),
+                };
+                HostResult {
+                    host,
+                    status,
+                    output,
+                    duration: started.elapsed(),
+                }
+            }
+        })
+        .buffer_unordered(limit.max(1))
+}
+
+// Runs `command` on every host with the configured concurrency and timeout
+pub fn broadcast(
+    config: &SSHConfig,
+    hosts: Vec<String>,
+    command: String,
+) -> impl Stream<Item = HostResult> {
+    let limit = config.broadcast.max_concurrency;
+    let timeout = Duration::from_secs(config.broadcast.host_timeout_secs);
+    let config = config.clone();
+    fan_out(hosts, limit, timeout, move |host| {
+        let target = SshTarget::parse(&host, config.default_user.as_deref());
+        let config = config.clone();
+        let command = command.clone();
+        async move { run_on_host(&target, &config, &command).await }
+    })
+}
+
+async fn run_on_host(
+    target: &SshTarget,
+    config: &SSHConfig,
+    command: &str,
+) -> Result<(Option<i32>, String), WarpError> {
+    let mut ssh = SshSessionManager::build_command(target, config, false);
+    // Nobody is there to answer a password prompt for twenty hosts at once
+    ssh.arg("-o")
+        .arg("BatchMode=yes")
+        .arg(command)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    let output = ssh.output().await?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if text.len() > MAX_OUTPUT_BYTES {
+        let mut end = MAX_OUTPUT_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    Ok((output.status.code(), text))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastCommand {
+    None,
+    Prompt(String),
+    Run {
+        id: u64,
+        hosts: Vec<String>,
+        command: String,
+    },
+}
+
+struct BroadcastRun {
+    id: u64,
+    group: String,
+    command: String,
+    results: Vec<HostResult>, // in the group's order
+}
+
+impl BroadcastRun {
+    fn done(&self) -> bool {
+        self.results.iter().all(|r| r.status.is_done())
+    }
+
+    // The output most successful hosts agree on; everything else is shown
+    // as a diff against it
+    fn baseline(&self) -> Option<&str> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for result in self
+            .results
+            .iter()
+            .filter(|r| r.status == HostStatus::Succeeded)
+        {
+            *counts.entry(result.output.as_str()).or_default() += 1;
+        }
+        // First host wins a tie, so the baseline doesn't flip between redraws
+        let best = counts.values().copied().max()?;
+        self.results
+            .iter()
+            .map(|r| r.output.as_str())
+            .find(|output| counts.get(output) == Some(&best))
+    }
+}
+
+pub struct BroadcastPanel {
+    groups: Vec<(String, Vec<String>)>,
+    run: Option<BroadcastRun>,
+    picked: Option<usize>, // group waiting for its command at the prompt
+    next_id: u64,
+    selected: usize,
+    show_diff: bool,
+}
+
+impl BroadcastPanel {
+    pub fn new(config: &SSHConfig) -> Self {
+        Self {
+            groups: config
+                .host_groups
+                .iter()
+                .map(|(name, hosts)| (name.clone(), hosts.clone()))
+                .collect(),
+            run: None,
+            picked: None,
+            next_id: 0,
+            selected: 0,
+            show_diff: true,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        let len = match &self.run {
+            Some(run) => run.results.len(),
+            None => self.groups.len(),
+        };
+        self.selected = index.min(len.saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> BroadcastCommand {
+        self.select(index);
+        match (&self.run, key) {
+            (None, '\n') => match self.groups.get(self.selected) {
+                Some((name, hosts)) if !hosts.is_empty() => {
+                    self.picked = Some(self.selected);
+                    BroadcastCommand::Prompt(i18n::t_args(
+                        "broadcast.prompt",
+                        &[("group", name), ("count", &hosts.len().to_string())],
+                    ))
+                }
+                _ => BroadcastCommand::None,
+            },
+            (Some(_), 'd') => {
+                self.show_diff = !self.show_diff;
+                BroadcastCommand::None
+            }
+            (Some(run), 'r') if run.done() => {
+                let failed: Vec<String> = run
+                    .results
+                    .iter()
+                    .filter(|r| r.status.is_failure())
+                    .map(|r| r.host.clone())
+                    .collect();
+                if failed.is_empty() {
+                    return BroadcastCommand::None;
+                }
+                let command = run.command.clone();
+                self.start(failed, command)
+            }
+            (Some(_), 'b') => {
+                self.run = None;
+                self.selected = 0;
+                BroadcastCommand::None
+            }
+            _ => BroadcastCommand::None,
+        }
+    }
+
+    // The command typed at the prompt, for the group picked before it
+    pub fn submit_input(&mut self, text: &str) -> BroadcastCommand {
+        let command = text.trim();
+        let Some(group) = self.picked.take() else {
+            return BroadcastCommand::None;
+        };
+        if command.is_empty() {
+            return BroadcastCommand::None;
+        }
+        let (name, hosts) = self.groups[group].clone();
+        self.run = Some(BroadcastRun {
+            id: 0,
+            group: name,
+            command: command.to_string(),
+            results: Vec::new(),
+        });
+        self.start(hosts, command.to_string())
+    }
+
+    // Marks `hosts` pending under a new run id; results from an earlier
+    // run still in flight are ignored by `record`
+    fn start(&mut self, hosts: Vec<String>, command: String) -> BroadcastCommand {
+        self.next_id += 1;
+        let id = self.next_id;
+        let Some(run) = self.run.as_mut() else {
+            return BroadcastCommand::None;
+        };
+        run.id = id;
+        for host in &hosts {
+            let pending = HostResult {
+                host: host.clone(),
+                status: HostStatus::Pending,
+                output: String::new(),
+                duration: Duration::ZERO,
+            };
+            match run.results.iter_mut().find(|r| &r.host == host) {
+                Some(result) => *result = pending,
+                None => run.results.push(pending),
+            }
+        }
+        BroadcastCommand::Run { id, hosts, command }
+    }
+
+    pub fn record(&mut self, id: u64, result: HostResult) {
+        let Some(run) = self.run.as_mut().filter(|run| run.id == id) else {
+            return;
+        };
+        if let Some(slot) = run.results.iter_mut().find(|r| r.host == result.host) {
+            *slot = result;
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        match &self.run {
+            None => self.groups_view(),
+            Some(run) => self.run_view(run),
+        }
+    }
+
+    fn groups_view(&self) -> PanelView {
+        let items = self
+            .groups
+            .iter()
+            .map(|(name, hosts)| {
+                PanelItem::styled(vec![
+                    (format!("{:<24}", name), None),
+                    (
+                        i18n::t_args("broadcast.hosts", &[("count", &hosts.len().to_string())]),
+                        Some(Color::DarkGrey),
+                    ),
+                ])
+            })
+            .collect();
+        let preview = match self.groups.get(self.selected) {
+            Some((_, hosts)) => hosts.iter().map(|host| host.clone().into()).collect(),
+            None => vec![i18n::t("broadcast.no_groups").into()],
+        };
+        PanelView {
+            kind: PanelKind::Broadcast,
+            title: i18n::t("panel.broadcast"),
+            items,
+            footer: Some(i18n::t("panel.broadcast_groups_keys")),
+            preview,
+            image: None,
+        }
+    }
+
+    fn run_view(&self, run: &BroadcastRun) -> PanelView {
+        let baseline = run.baseline();
+        let items = run
+            .results
+            .iter()
+            .map(|result| {
+                let (status, color) = result.status.label();
+                let mut segments = vec![
+                    (format!("{:<10}", status), color),
+                    (format!("{:<32}", result.host), None),
+                    (
+                        format!("{:>7.1}s ", result.duration.as_secs_f64()),
+                        Some(Color::DarkGrey),
+                    ),
+                ];
+                if result.status.is_done() && baseline.is_some_and(|b| b != result.output) {
+                    segments.push((i18n::t("broadcast.differs"), Some(Color::Yellow)));
+                }
+                PanelItem::styled(segments)
+            })
+            .collect();
+
+        let finished = run.results.iter().filter(|r| r.status.is_done()).count();
+        let failed = run.results.iter().filter(|r| r.status.is_failure()).count();
+        let title = i18n::t_args(
+            "panel.broadcast_run",
+            &[
+                ("command", &run.command),
+                ("group", &run.group),
+                ("done", &finished.to_string()),
+                ("total", &run.results.len().to_string()),
+                ("failed", &failed.to_string()),
+            ],
+        );
+        PanelView {
+            kind: PanelKind::Broadcast,
+            title,
+            items,
+            footer: Some(i18n::t("panel.broadcast_run_keys")),
+            preview: run
+                .results
+                .get(self.selected)
+                .map(|r| self.preview(r, baseline))
+                .unwrap_or_default(),
+            image: None,
+        }
+    }
+
+    fn preview(&self, result: &HostResult, baseline: Option<&str>) -> Vec<PanelItem> {
+        if let HostStatus::Error(message) = &result.status {
+            return vec![PanelItem::styled(vec![(message.clone(), Some(Color::Red))])];
+        }
+        let differs = baseline.filter(|b| result.status.is_done() && *b != result.output);
+        if let (true, Some(baseline)) = (self.show_diff, differs) {
+            if let Ok(hunks) = git::diff_text(baseline, &result.output) {
+                let mut rows: Vec<PanelItem> = vec![i18n::t("broadcast.diff_header").into()];
+                rows.extend(diff_rows(&hunks).into_iter().take(PREVIEW_LINES));
+                return rows;
+            }
+        }
+        let rows: Vec<PanelItem> = result
+            .output
+            .lines()
+            .take(PREVIEW_LINES)
+            .map(|line| line.to_string().into())
+            .collect();
+        if rows.is_empty() && result.status.is_done() {
+            return vec![i18n::t("broadcast.no_output").into()];
+        }
+        rows
+    }
+}
+
+fn diff_rows(hunks: &[DiffHunk]) -> Vec<PanelItem> {
+    let mut rows = Vec::new();
+    for hunk in hunks {
+        rows.push(PanelItem::styled(vec![(
+            hunk.header.clone(),
+            Some(Color::Cyan),
+        )]));
+        for line in &hunk.lines {
+            let color = match line.origin {
+                '+' => Some(Color::Green),
+                '-' => Some(Color::Red),
+                _ => None,
+            };
+            rows.push(PanelItem::styled(vec![(
+                format!("{}{}", line.origin, line.content),
+                color,
+            )]));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn config() -> SSHConfig {
+        let mut config = crate::config::WarpConfig::default().ssh;
+        config.host_groups.insert(
+            "web".to_string(),
+            vec!["web1".to_string(), "deploy@web2:2222".to_string()],
+        );
+        config
+    }
+
+    fn finished(host: &str, status: HostStatus, output: &str) -> HostResult {
+        HostResult {
+            host: host.to_string(),
+            status,
+            output: output.to_string(),
+            duration: Duration::from_millis(40),
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let target = SshTarget::parse("deploy@web2:2222", Some("ops"));
+        assert_eq!(
+            (target.destination(), target.port),
+            ("deploy@web2".to_string(), Some(2222))
+        );
+        let target = SshTarget::parse("web1", Some("ops"));
+        assert_eq!(
+            (target.destination(), target.port),
+            ("ops@web1".to_string(), None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_limits_and_times_out() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let hosts: Vec<String> = (0..6).map(|i| format!("h{}", i)).collect();
+        let results: Vec<HostResult> = fan_out(hosts, 2, Duration::from_millis(200), |host| {
+            let running = running.clone();
+            let peak = peak.clone();
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                let wait = if host == "h3" { 5_000 } else { 10 };
+                tokio::time::sleep(Duration::from_millis(wait)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                match host.as_str() {
+                    "h1" => Ok((Some(2), "boom".to_string())),
+                    _ => Ok((Some(0), "ok".to_string())),
+                }
+            }
+        })
+        .collect()
+        .await;
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        let status = |host: &str| {
+            results
+                .iter()
+                .find(|r| r.host == host)
+                .unwrap()
+                .status
+                .clone()
+        };
+        assert_eq!(status("h0"), HostStatus::Succeeded);
+        assert_eq!(status("h1"), HostStatus::Failed(Some(2)));
+        assert_eq!(status("h3"), HostStatus::TimedOut);
+    }
+
+    #[test]
+    fn test_panel_runs_and_reruns_failures() {
+        let mut panel = BroadcastPanel::new(&config());
+        assert!(matches!(
+            panel.handle_key('\n', 0),
+            BroadcastCommand::Prompt(_)
+        ));
+        let BroadcastCommand::Run { id, hosts, command } = panel.submit_input("uptime") else {
+            panic!("expected a run");
+        };
+        assert_eq!((hosts.len(), command.as_str()), (2, "uptime"));
+        // Can't rerun until every host is back
+        assert_eq!(panel.handle_key('r', 0), BroadcastCommand::None);
+
+        panel.record(id, finished("web1", HostStatus::Succeeded, "up 3 days\n"));
+        panel.record(id, finished("deploy@web2:2222", HostStatus::TimedOut, ""));
+        let view = panel.view();
+        assert!(view.items[1].segments[0].0.starts_with('⏱'));
+        assert_eq!(view.items[1].segments[0].1, Some(Color::Red));
+
+        let BroadcastCommand::Run {
+            id: rerun, hosts, ..
+        } = panel.handle_key('r', 0)
+        else {
+            panic!("expected a rerun");
+        };
+        assert_eq!(hosts, vec!["deploy@web2:2222".to_string()]);
+        // A late result from the first run doesn't land in the second
+        panel.record(
+            id,
+            finished("deploy@web2:2222", HostStatus::Succeeded, "late\n"),
+        );
+        assert_eq!(
+            panel.run.as_ref().unwrap().results[1].status,
+            HostStatus::Pending
+        );
+
+        // A host that disagrees with the others is shown as a diff
+        panel.record(
+            rerun,
+            finished("deploy@web2:2222", HostStatus::Succeeded, "up 9 days\n"),
+        );
+        panel.handle_key('b', 0);
+        panel.handle_key('\n', 0);
+        let BroadcastCommand::Run { id, .. } = panel.submit_input("cat /etc/motd") else {
+            panic!("expected a run");
+        };
+        panel.record(id, finished("web1", HostStatus::Succeeded, "a\nb\n"));
+        panel.record(
+            id,
+            finished("deploy@web2:2222", HostStatus::Succeeded, "a\nc\n"),
+        );
+        panel.select(1);
+        let view = panel.view();
+        let preview: Vec<String> = view
+            .preview
+            .iter()
+            .map(|item| item.segments[0].0.clone())
+            .collect();
+        assert!(preview.contains(&"-b".to_string()));
+        assert!(preview.contains(&"+c".to_string()));
+    }
+}
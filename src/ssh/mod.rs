@@ -0,0 +1,444 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::config::SSHConfig;
+use crate::error::WarpError;
+
+pub mod broadcast;
+pub mod reconnect;
+
+use reconnect::{compute_output_gap, RetryPolicy};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshTarget {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+}
+
+impl SshTarget {
+    // "[user@]host[:port]", as written in `ssh.host_groups`
+    pub fn parse(spec: &str, default_user: Option<&str>) -> Self {
+        let (user, rest) = match spec.split_once('@') {
+            Some((user, rest)) => (Some(user.to_string()), rest),
+            None => (default_user.map(str::to_string), spec),
+        };
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => (host, port.parse().ok()),
+            _ => (rest, None),
+        };
+        Self {
+            host: host.to_string(),
+            user,
+            port,
+            identity_file: None,
+        }
+    }
+
+    pub fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SshConnectionState {
+    Connecting,
+    Connected,
+    Reconnecting { attempt: u32, max_attempts: u32 },
+    Disconnected,
+    Failed(String),
+}
+
+impl SshConnectionState {
+    pub fn indicator(&self) -> String {
+        match self {
+            SshConnectionState::Connecting => "⋯ connecting".to_string(),
+            SshConnectionState::Connected => "● connected".to_string(),
+            SshConnectionState::Reconnecting {
+                attempt,
+                max_attempts,
+            } => {
+                format!("↻ reconnecting ({}/{})", attempt, max_attempts)
+            }
+            SshConnectionState::Disconnected => "○ disconnected".to_string(),
+            SshConnectionState::Failed(reason) => format!("✖ {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SshEvent {
+    Output {
+        session_id: String,
+        data: String,
+    },
+    // Lines recovered from the remote scrollback after a reconnect
+    GapRecovered {
+        session_id: String,
+        lines: Vec<String>,
+    },
+    StateChanged {
+        session_id: String,
+        state: SshConnectionState,
+    },
+}
+
+struct SshSession {
+    target: SshTarget,
+    state: SshConnectionState,
+    stdin: Option<ChildStdin>,
+    recent_lines: Vec<String>,
+}
+
+pub struct SshSessionManager {
+    config: SSHConfig,
+    sessions: Arc<Mutex<HashMap<String, SshSession>>>,
+    event_sender: mpsc::UnboundedSender<SshEvent>,
+}
+
+impl SshSessionManager {
+    pub async fn new(
+        config: SSHConfig,
+        event_sender: mpsc::UnboundedSender<SshEvent>,
+    ) -> Result<Self, WarpError> {
+        Ok(Self {
+            config,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            event_sender,
+        })
+    }
+
+    // `startup` is typed into the remote shell once, after the first
+    // connection comes up
+    pub async fn connect(
+        &self,
+        target: SshTarget,
+        startup: Vec<String>,
+    ) -> Result<String, WarpError> {
+        if !self.config.enabled {
+            return Err(WarpError::Config("SSH support is disabled".to_string()));
+        }
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        {
+            let mut sessions = self.sessions.lock().await;
+            sessions.insert(
+                session_id.clone(),
+                SshSession {
+                    target: target.clone(),
+                    state: SshConnectionState::Connecting,
+                    stdin: None,
+                    recent_lines: Vec::new(),
+                },
+            );
+        }
+
+        let sessions = self.sessions.clone();
+        let event_sender = self.event_sender.clone();
+        let config = self.config.clone();
+        let id = session_id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = Self::supervise(
+                id.clone(),
+                target,
+                startup,
+                config,
+                sessions.clone(),
+                event_sender.clone(),
+            )
+            .await
+            {
+                log::error!("SSH session {} failed: {}", id, e);
+                Self::set_state(
+                    &sessions,
+                    &event_sender,
+                    &id,
+                    SshConnectionState::Failed(e.to_string()),
+                )
+                .await;
+            }
+        });
+
+        Ok(session_id)
+    }
+
+    pub async fn write_input(&self, session_id: &str, input: &str) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.lock().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| WarpError::not_found("SSH session", session_id))?;
+
+        match session.stdin.as_mut() {
+            Some(stdin) => {
+                stdin.write_all(input.as_bytes()).await?;
+                stdin.flush().await?;
+                Ok(())
+            }
+            // Input typed while reconnecting is dropped rather than replayed into an unknown remote state
+            None => Err(WarpError::Config(format!(
+                "SSH session is {}",
+                session.state.indicator()
+            ))),
+        }
+    }
+
+    pub async fn get_state(&self, session_id: &str) -> Option<SshConnectionState> {
+        let sessions = self.sessions.lock().await;
+        sessions.get(session_id).map(|s| s.state.clone())
+    }
+
+    pub async fn disconnect(&self, session_id: &str) -> Result<(), WarpError> {
+        let mut sessions = self.sessions.lock().await;
+        sessions.remove(session_id);
+        let _ = self.event_sender.send(SshEvent::StateChanged {
+            session_id: session_id.to_string(),
+            state: SshConnectionState::Disconnected,
+        });
+        Ok(())
+    }
+
+    async fn supervise(
+        session_id: String,
+        target: SshTarget,
+        mut startup: Vec<String>,
+        config: SSHConfig,
+        sessions: Arc<Mutex<HashMap<String, SshSession>>>,
+        event_sender: mpsc::UnboundedSender<SshEvent>,
+    ) -> Result<(), WarpError> {
+        let policy = RetryPolicy::from_config(&config.reconnect);
+        let remote_session = format!("warp-{}", &session_id[..8]);
+        let mut attempt = 0;
+
+        loop {
+            let persistent = config.reconnect.persistent_remote_session;
+            let mut command = Self::build_command(&target, &config, persistent);
+            if persistent {
+                // Attach to (or create) a named tmux session so remote work survives the drop
+                command.arg(format!(
+                    "tmux new-session -A -s {} 2>/dev/null || exec $SHELL -l",
+                    remote_session
+                ));
+            }
+
+            let mut child = command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| WarpError::Pty("Failed to take ssh stdout".to_string()))?;
+
+            {
+                let mut guard = sessions.lock().await;
+                match guard.get_mut(&session_id) {
+                    Some(session) => {
+                        session.stdin = child.stdin.take();
+                        if let Some(stdin) = session.stdin.as_mut() {
+                            for line in startup.drain(..) {
+                                stdin.write_all(format!("{}\n", line).as_bytes()).await?;
+                            }
+                            stdin.flush().await?;
+                        }
+                    }
+                    // Session was closed by the user; don't resurrect it
+                    None => {
+                        let _ = child.kill().await;
+                        return Ok(());
+                    }
+                }
+            }
+
+            if attempt > 0 && config.reconnect.persistent_remote_session {
+                Self::resync_scrollback(
+                    &session_id,
+                    &target,
+                    &config,
+                    &remote_session,
+                    &sessions,
+                    &event_sender,
+                )
+                .await;
+            }
+            Self::set_state(
+                &sessions,
+                &event_sender,
+                &session_id,
+                SshConnectionState::Connected,
+            )
+            .await;
+
+            let mut reader = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = reader.next_line().await {
+                Self::record_line(&sessions, &session_id, &line, config.reconnect.resync_lines)
+                    .await;
+                let _ = event_sender.send(SshEvent::Output {
+                    session_id: session_id.clone(),
+                    data: line,
+                });
+                // A successful read means the link is healthy again
+                attempt = 0;
+            }
+
+            let status = child.wait().await?;
+            {
+                let mut guard = sessions.lock().await;
+                match guard.get_mut(&session_id) {
+                    Some(session) => session.stdin = None,
+                    None => return Ok(()),
+                }
+            }
+
+            if !policy.should_retry(attempt, status.code()) {
+                Self::set_state(
+                    &sessions,
+                    &event_sender,
+                    &session_id,
+                    SshConnectionState::Disconnected,
+                )
+                .await;
+                return Ok(());
+            }
+
+            attempt += 1;
+            Self::set_state(
+                &sessions,
+                &event_sender,
+                &session_id,
+                SshConnectionState::Reconnecting {
+                    attempt,
+                    max_attempts: policy.max_attempts,
+                },
+            )
+            .await;
+            tokio::time::sleep(policy.backoff_for(attempt - 1)).await;
+        }
+    }
+
+    fn build_command(target: &SshTarget, config: &SSHConfig, force_tty: bool) -> Command {
+        let mut command = Command::new("ssh");
+        command
+            .arg("-o")
+            .arg(format!("ConnectTimeout={}", config.connection_timeout))
+            .arg("-o")
+            .arg(format!(
+                "ServerAliveInterval={}",
+                config.keep_alive_interval
+            ))
+            .arg("-o")
+            .arg("ServerAliveCountMax=3");
+
+        if let Some(port) = target.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(identity) = &target.identity_file {
+            command.arg("-i").arg(identity);
+        }
+        if force_tty {
+            command.arg("-tt");
+        }
+        command.arg(target.destination());
+        command
+    }
+
+    async fn resync_scrollback(
+        session_id: &str,
+        target: &SshTarget,
+        config: &SSHConfig,
+        remote_session: &str,
+        sessions: &Arc<Mutex<HashMap<String, SshSession>>>,
+        event_sender: &mpsc::UnboundedSender<SshEvent>,
+    ) {
+        let mut command = Self::build_command(target, config, false);
+        command.arg(format!(
+            "tmux capture-pane -p -J -S -{} -t {}",
+            config.reconnect.resync_lines, remote_session
+        ));
+
+        let output = match command.output().await {
+            Ok(output) if output.status.success() => output,
+            Ok(_) | Err(_) => {
+                log::warn!(
+                    "Could not recover scrollback for SSH session {}",
+                    session_id
+                );
+                return;
+            }
+        };
+
+        let remote: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+
+        let gap = {
+            let guard = sessions.lock().await;
+            match guard.get(session_id) {
+                Some(session) => compute_output_gap(&session.recent_lines, &remote),
+                None => return,
+            }
+        };
+
+        if !gap.is_empty() {
+            for line in &gap {
+                Self::record_line(sessions, session_id, line, config.reconnect.resync_lines).await;
+            }
+            let _ = event_sender.send(SshEvent::GapRecovered {
+                session_id: session_id.to_string(),
+                lines: gap,
+            });
+        }
+    }
+
+    async fn record_line(
+        sessions: &Arc<Mutex<HashMap<String, SshSession>>>,
+        session_id: &str,
+        line: &str,
+        limit: usize,
+    ) {
+        let mut guard = sessions.lock().await;
+        if let Some(session) = guard.get_mut(session_id) {
+            session.recent_lines.push(line.to_string());
+            if session.recent_lines.len() > limit {
+                let excess = session.recent_lines.len() - limit;
+                session.recent_lines.drain(0..excess);
+            }
+        }
+    }
+
+    async fn set_state(
+        sessions: &Arc<Mutex<HashMap<String, SshSession>>>,
+        event_sender: &mpsc::UnboundedSender<SshEvent>,
+        session_id: &str,
+        state: SshConnectionState,
+    ) {
+        let mut guard = sessions.lock().await;
+        if let Some(session) = guard.get_mut(session_id) {
+            if session.state == state {
+                return;
+            }
+            log::info!(
+                "SSH {} ({}): {}",
+                session.target.destination(),
+                session_id,
+                state.indicator()
+            );
+            session.state = state.clone();
+        }
+        let _ = event_sender.send(SshEvent::StateChanged {
+            session_id: session_id.to_string(),
+            state,
+        });
+    }
+}
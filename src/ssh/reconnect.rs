@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use crate::config::SSHReconnectConfig;
+
+// ssh exits with 255 when the connection itself fails or drops; any other
+// exit status comes from the remote command and must not trigger a retry.
+const SSH_CONNECTION_ERROR_EXIT: i32 = 255;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub enabled: bool,
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &SSHReconnectConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            max_attempts: config.max_attempts,
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            multiplier: config.backoff_multiplier.max(1.0),
+        }
+    }
+
+    pub fn should_retry(&self, attempt: u32, exit_code: Option<i32>) -> bool {
+        self.enabled && attempt < self.max_attempts && Self::is_transient_exit(exit_code)
+    }
+
+    pub fn is_transient_exit(exit_code: Option<i32>) -> bool {
+        // A missing exit code means ssh was killed by a signal (e.g. SIGPIPE on a dead socket)
+        matches!(exit_code, None | Some(SSH_CONNECTION_ERROR_EXIT))
+    }
+
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.min(32) as i32);
+        let millis = (self.initial_backoff.as_millis() as f64 * factor) as u128;
+        Duration::from_millis(millis.min(self.max_backoff.as_millis()) as u64)
+    }
+}
+
+// Returns the lines of `remote` that the local scrollback has not seen yet.
+// The remote capture is expected to overlap with the tail of what we already
+// rendered; the longest such overlap is skipped so nothing is duplicated.
+pub fn compute_output_gap(local_tail: &[String], remote: &[String]) -> Vec<String> {
+    let max_overlap = local_tail.len().min(remote.len());
+
+    for overlap in (1..=max_overlap).rev() {
+        let local_suffix = &local_tail[local_tail.len() - overlap..];
+        if remote[..overlap] == *local_suffix {
+            return remote[overlap..].to_vec();
+        }
+    }
+
+    // No overlap: the gap is larger than the capture window, so everything is new
+    remote.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            enabled: true,
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+            multiplier: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = policy();
+        assert_eq!(policy.backoff_for(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff_for(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff_for(10), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_only_connection_failures_are_retried() {
+        let policy = policy();
+        assert!(policy.should_retry(0, Some(255)));
+        assert!(policy.should_retry(0, None));
+        assert!(!policy.should_retry(0, Some(1)));
+        assert!(!policy.should_retry(5, Some(255)));
+    }
+
+    #[test]
+    fn test_output_gap_skips_overlap() {
+        let local = lines(&["a", "b", "c"]);
+        let remote = lines(&["b", "c", "d", "e"]);
+        assert_eq!(compute_output_gap(&local, &remote), lines(&["d", "e"]));
+    }
+
+    #[test]
+    fn test_output_gap_without_overlap() {
+        let local = lines(&["a", "b"]);
+        let remote = lines(&["x", "y"]);
+        assert_eq!(compute_output_gap(&local, &remote), lines(&["x", "y"]));
+    }
+}
@@ -0,0 +1,258 @@
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify, OnceCell};
+
+use crate::error::WarpError;
+use crate::i18n;
+use crate::ui::UIEvent;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitTiming {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    // Initialized on first use rather than before the UI came up
+    pub lazy: bool,
+}
+
+// Per-subsystem init times, shared by main, WarpApp::new and the lazy
+// services so one report covers the whole startup
+#[derive(Debug, Default)]
+pub struct StartupProfile {
+    timings: Mutex<Vec<InitTiming>>,
+}
+
+impl StartupProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, name: &'static str, elapsed: Duration, lazy: bool) {
+        self.timings.lock().unwrap().push(InitTiming {
+            name,
+            elapsed,
+            lazy,
+        });
+    }
+
+    pub async fn measure<T>(
+        &self,
+        name: &'static str,
+        init: impl std::future::Future<Output = T>,
+    ) -> T {
+        let started = Instant::now();
+        let value = init.await;
+        self.record(name, started.elapsed(), false);
+        value
+    }
+
+    pub fn timings(&self) -> Vec<InitTiming> {
+        self.timings.lock().unwrap().clone()
+    }
+
+    // Time spent before the terminal is usable
+    pub fn eager_total(&self) -> Duration {
+        self.timings
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|timing| !timing.lazy)
+            .map(|timing| timing.elapsed)
+            .sum()
+    }
+
+    pub fn slowest_eager(&self) -> Option<InitTiming> {
+        self.timings
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|timing| !timing.lazy)
+            .max_by_key(|timing| timing.elapsed)
+            .cloned()
+    }
+}
+
+pub fn format_report(profile: &StartupProfile, budget: Duration) -> String {
+    let ms = |elapsed: Duration| elapsed.as_secs_f64() * 1000.0;
+    let mut out = format!("{:<20} {:>10}  {}\n", "subsystem", "init", "phase");
+    let mut timings = profile.timings();
+    timings.sort_by_key(|timing| timing.lazy);
+    for timing in &timings {
+        out.push_str(&format!(
+            "{:<20} {:>8.2}ms  {}\n",
+            timing.name,
+            ms(timing.elapsed),
+            if timing.lazy { "lazy" } else { "startup" }
+        ));
+    }
+    let total = profile.eager_total();
+    out.push_str(&format!(
+        "{:<20} {:>8.2}ms  {} (budget {:.0}ms)\n",
+        "startup total",
+        ms(total),
+        if total <= budget { "ok" } else { "OVER" },
+        ms(budget)
+    ));
+    out
+}
+
+type Init<T> = Box<dyn Fn() -> BoxFuture<'static, Result<T, WarpError>> + Send + Sync>;
+
+// A subsystem built on first use instead of in WarpApp::new. While it
+// initializes the header shows UIEvent::Loading; a failed init is retried on
+// the next use.
+pub struct LazyService<T> {
+    name: &'static str,
+    cell: OnceCell<Arc<T>>,
+    init: Init<T>,
+    ready: Notify,
+    profile: Arc<StartupProfile>,
+    events: Option<mpsc::UnboundedSender<UIEvent>>,
+}
+
+impl<T: Send + Sync + 'static> LazyService<T> {
+    pub fn new<F, Fut>(name: &'static str, profile: Arc<StartupProfile>, init: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<T, WarpError>> + Send + 'static,
+    {
+        Self {
+            name,
+            cell: OnceCell::new(),
+            init: Box::new(move || Box::pin(init())),
+            ready: Notify::new(),
+            profile,
+            events: None,
+        }
+    }
+
+    pub fn with_progress(mut self, events: mpsc::UnboundedSender<UIEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub async fn get(&self) -> Result<Arc<T>, WarpError> {
+        let service = self
+            .cell
+            .get_or_try_init(|| async {
+                self.progress(Some(i18n::t_args(
+                    "startup.loading",
+                    &[("name", self.name)],
+                )));
+                let started = Instant::now();
+                let result = (self.init)().await;
+                self.progress(None);
+                let service =
+                    result.map_err(|e| e.context(format!("Failed to start {}", self.name)))?;
+                self.profile.record(self.name, started.elapsed(), true);
+                Ok::<_, WarpError>(Arc::new(service))
+            })
+            .await?
+            .clone();
+        self.ready.notify_waiters();
+        Ok(service)
+    }
+
+    // None until something has used the service; shutdown uses this so it
+    // doesn't start a subsystem just to stop it
+    pub fn get_if_ready(&self) -> Option<Arc<T>> {
+        self.cell.get().cloned()
+    }
+
+    // Resolves once another caller has initialized the service, for
+    // background work that should only run after first use
+    pub async fn initialized(&self) -> Arc<T> {
+        loop {
+            let notified = self.ready.notified();
+            if let Some(service) = self.get_if_ready() {
+                return service;
+            }
+            notified.await;
+        }
+    }
+
+    fn progress(&self, status: Option<String>) {
+        if let Some(events) = &self.events {
+            let _ = events.send(UIEvent::Loading(status));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_lazy_service_initializes_once() {
+        let profile = Arc::new(StartupProfile::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let service = LazyService::new("counter", profile.clone(), move || {
+            let counter = counter.clone();
+            async move { Ok(counter.fetch_add(1, Ordering::SeqCst)) }
+        });
+        assert!(service.get_if_ready().is_none());
+        assert_eq!(*service.get().await.unwrap(), 0);
+        assert_eq!(*service.get().await.unwrap(), 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let timings = profile.timings();
+        assert_eq!(timings.len(), 1);
+        assert!(timings[0].lazy);
+        assert_eq!(profile.eager_total(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_failed_init_is_retried() {
+        let profile = Arc::new(StartupProfile::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let service = LazyService::new("flaky", profile, move || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt == 0 {
+                    Err(WarpError::config_err("not yet"))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        });
+        assert!(service.get().await.is_err());
+        assert_eq!(*service.get().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_initialized_waits_for_first_use() {
+        let profile = Arc::new(StartupProfile::new());
+        let service = Arc::new(LazyService::new("late", profile, || async { Ok("ready") }));
+        let waiter = tokio::spawn({
+            let service = service.clone();
+            async move { *service.initialized().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+        service.get().await.unwrap();
+        assert_eq!(waiter.await.unwrap(), "ready");
+    }
+
+    #[test]
+    fn test_report_flags_budget() {
+        let profile = StartupProfile::new();
+        profile.record("config", Duration::from_millis(30), false);
+        profile.record("ui", Duration::from_millis(90), false);
+        profile.record("ai-assistant", Duration::from_millis(500), true);
+        assert_eq!(profile.eager_total(), Duration::from_millis(120));
+        assert_eq!(profile.slowest_eager().unwrap().name, "ui");
+
+        let report = format_report(&profile, Duration::from_millis(100));
+        assert!(report.contains("OVER"));
+        assert!(report.lines().last().unwrap().starts_with("startup total"));
+        let report = format_report(&profile, Duration::from_millis(200));
+        assert!(report.contains(" ok "));
+    }
+}
@@ -0,0 +1,434 @@
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::{StatusAction, StatusProvider, StatusSegmentConfig, StatusSlot};
+use crate::error::WarpError;
+use crate::git::GitManager;
+use crate::i18n;
+use crate::process_tree::{build_tree, ProcessInfo};
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+pub const SEPARATOR: &str = "  ";
+// Plugins get one line of this many columns at most
+const MAX_PLUGIN_WIDTH: usize = 40;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub color: Option<Color>,
+}
+
+impl Segment {
+    pub fn new(text: impl Into<String>, color: Option<Color>) -> Self {
+        Self {
+            text: text.into(),
+            color,
+        }
+    }
+}
+
+struct Entry {
+    config: StatusSegmentConfig,
+    segment: Option<Segment>, // None until the provider has something to show
+}
+
+// Segments registered into the left and right slots. Each keeps its place in
+// registration order; priorities only decide what goes when a slot is full.
+#[derive(Default)]
+pub struct StatusBar {
+    entries: Vec<Entry>,
+}
+
+impl StatusBar {
+    pub fn new(segments: &[StatusSegmentConfig]) -> Self {
+        let mut bar = Self::default();
+        for segment in segments {
+            bar.register(segment.clone());
+        }
+        bar
+    }
+
+    // The id providers update the segment with
+    pub fn register(&mut self, config: StatusSegmentConfig) -> usize {
+        self.entries.push(Entry {
+            config,
+            segment: None,
+        });
+        self.entries.len() - 1
+    }
+
+    // Returns whether anything changed
+    pub fn update(&mut self, id: usize, segment: Option<Segment>) -> bool {
+        match self.entries.get_mut(id) {
+            Some(entry) if entry.segment != segment => {
+                entry.segment = segment;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Every segment with something to show, with its config
+    pub fn shown(&self) -> impl Iterator<Item = (&StatusSegmentConfig, &Segment)> {
+        self.entries.iter().filter_map(|entry| {
+            entry
+                .segment
+                .as_ref()
+                .map(|segment| (&entry.config, segment))
+        })
+    }
+
+    // What fits in `width` columns of a slot, joined by SEPARATOR. The lowest
+    // priority goes first; between equals, the one further right.
+    pub fn layout(&self, slot: StatusSlot, width: usize) -> Vec<&Segment> {
+        let mut kept: Vec<(i32, &Segment)> = self
+            .shown()
+            .filter(|(config, _)| config.slot == slot)
+            .map(|(config, segment)| (config.priority, segment))
+            .collect();
+        let used = |kept: &[(i32, &Segment)]| {
+            let text: usize = kept.iter().map(|(_, segment)| segment.text.width()).sum();
+            text + SEPARATOR.len() * kept.len().saturating_sub(1)
+        };
+        while used(&kept) > width {
+            let lowest = kept
+                .iter()
+                .enumerate()
+                .min_by_key(|(index, (priority, _))| (*priority, std::cmp::Reverse(*index)))
+                .map(|(index, _)| index);
+            if let Some(index) = lowest {
+                kept.remove(index);
+            }
+        }
+        kept.into_iter().map(|(_, segment)| segment).collect()
+    }
+}
+
+pub async fn git_segment(cwd: &Path) -> Result<Option<Segment>, WarpError> {
+    // Outside a repository there's nothing to show
+    let Ok(git) = GitManager::discover(cwd) else {
+        return Ok(None);
+    };
+    let status = git.status().await?;
+    let mut text = format!(
+        "⎇ {}",
+        status.branch.unwrap_or_else(|| i18n::t("status.detached"))
+    );
+    if status.ahead > 0 {
+        text.push_str(&format!(" ↑{}", status.ahead));
+    }
+    if status.behind > 0 {
+        text.push_str(&format!(" ↓{}", status.behind));
+    }
+    if !status.files.is_empty() {
+        text.push_str(&format!(" ●{}", status.files.len()));
+    }
+    let color = if status.files.is_empty() {
+        Color::Green
+    } else {
+        Color::Yellow
+    };
+    Ok(Some(Segment::new(text, Some(color))))
+}
+
+#[derive(Deserialize)]
+struct KubeConfig {
+    #[serde(rename = "current-context", default)]
+    current_context: Option<String>,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+}
+
+#[derive(Deserialize)]
+struct NamedContext {
+    name: String,
+    #[serde(default)]
+    context: Option<KubeContext>,
+}
+
+#[derive(Deserialize)]
+struct KubeContext {
+    namespace: Option<String>,
+}
+
+// The first file of $KUBECONFIG, which is where kubectl writes the current context
+pub fn kube_config_path() -> Option<PathBuf> {
+    match std::env::var_os("KUBECONFIG") {
+        Some(paths) => std::env::split_paths(&paths).find(|path| !path.as_os_str().is_empty()),
+        None => dirs::home_dir().map(|home| home.join(".kube/config")),
+    }
+}
+
+// `context:namespace`, or just the context when it doesn't set one
+pub fn kube_context(text: &str) -> Option<String> {
+    let config: KubeConfig = serde_yaml::from_str(text).ok()?;
+    let current = config.current_context.filter(|name| !name.is_empty())?;
+    let namespace = config
+        .contexts
+        .into_iter()
+        .find(|context| context.name == current)
+        .and_then(|context| context.context?.namespace);
+    Some(match namespace {
+        Some(namespace) => format!("{}:{}", current, namespace),
+        None => current,
+    })
+}
+
+pub async fn kube_segment() -> Result<Option<Segment>, WarpError> {
+    let Some(path) = kube_config_path() else {
+        return Ok(None);
+    };
+    let text = match tokio::fs::read_to_string(&path).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(
+        kube_context(&text)
+            .map(|context| Segment::new(format!("⎈ {}", context), Some(Color::Cyan))),
+    )
+}
+
+pub fn time_segment(format: &str, now: chrono::DateTime<chrono::Local>) -> Option<Segment> {
+    // Formatting with an invalid specifier fails instead of panicking this way
+    let mut text = String::new();
+    write!(text, "{}", now.format(format)).ok()?;
+    Some(Segment::new(text, None))
+}
+
+// Children of the shell: the foreground command and anything sent to the
+// background. Nothing is shown while the shell is idle.
+pub fn jobs_segment(processes: &[ProcessInfo], shell_pid: u32) -> Option<Segment> {
+    let count = build_tree(processes, shell_pid)
+        .iter()
+        .filter(|node| node.depth == 1)
+        .count();
+    (count > 0).then(|| {
+        Segment::new(
+            i18n::t_args("status.jobs", &[("count", &count.to_string())]),
+            Some(Color::Magenta),
+        )
+    })
+}
+
+// The first line of a plugin's reply; an empty reply hides the segment
+pub fn plugin_segment(reply: &str) -> Option<Segment> {
+    let line = reply.lines().next()?.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let mut text = String::new();
+    for c in line.chars().filter(|c| !c.is_control()) {
+        if text.width() + c.to_string().width() > MAX_PLUGIN_WIDTH {
+            text.push('…');
+            break;
+        }
+        text.push(c);
+    }
+    Some(Segment::new(text, None))
+}
+
+pub enum StatusCommand {
+    None,
+    Run(StatusAction),
+    Plugin(String), // send `status_click` to this plugin
+}
+
+// The status bar's segments as a list, for activating them from the keyboard
+pub struct StatusPanel {
+    rows: Vec<(StatusSegmentConfig, Segment)>,
+    selected: usize,
+}
+
+impl StatusPanel {
+    pub fn new(bar: &StatusBar) -> Self {
+        Self {
+            rows: bar
+                .shown()
+                .map(|(config, segment)| (config.clone(), segment.clone()))
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> StatusCommand {
+        self.select(index);
+        let Some((config, _)) = self.rows.get(self.selected).filter(|_| key == '\n') else {
+            return StatusCommand::None;
+        };
+        match (&config.action, &config.provider) {
+            (Some(action), _) => StatusCommand::Run(action.clone()),
+            (None, StatusProvider::Plugin(name)) => StatusCommand::Plugin(name.clone()),
+            (None, _) => StatusCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .rows
+            .iter()
+            .map(|(config, segment)| {
+                let slot = match config.slot {
+                    StatusSlot::Left => "◧ ",
+                    StatusSlot::Right => "◨ ",
+                };
+                PanelItem::styled(vec![
+                    (slot.to_string(), Some(Color::DarkGrey)),
+                    (segment.text.clone(), segment.color),
+                ])
+            })
+            .collect();
+        let preview = match self.rows.get(self.selected) {
+            Some((config, _)) => {
+                let action = match &config.action {
+                    Some(StatusAction::Action(name)) => {
+                        i18n::t_args("status.runs_action", &[("action", name)])
+                    }
+                    Some(StatusAction::Command(command)) => format!("$ {}", command),
+                    None if matches!(config.provider, StatusProvider::Plugin(_)) => {
+                        i18n::t("status.plugin_click")
+                    }
+                    None => i18n::t("status.no_action"),
+                };
+                vec![
+                    i18n::t_args(
+                        "status.refresh",
+                        &[
+                            ("secs", &config.refresh_secs.to_string()),
+                            ("priority", &config.priority.to_string()),
+                        ],
+                    )
+                    .into(),
+                    action.into(),
+                ]
+            }
+            None => vec![i18n::t("status.empty").into()],
+        };
+        PanelView {
+            kind: PanelKind::StatusBar,
+            title: i18n::t("panel.status_bar"),
+            items,
+            footer: Some(i18n::t("panel.status_bar_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarpConfig;
+
+    fn segment(provider: StatusProvider, slot: StatusSlot, priority: i32) -> StatusSegmentConfig {
+        StatusSegmentConfig {
+            provider,
+            slot,
+            priority,
+            refresh_secs: 1,
+            action: None,
+        }
+    }
+
+    #[test]
+    fn test_layout_drops_lowest_priority() {
+        let mut bar = StatusBar::new(&WarpConfig::default().status_bar.segments);
+        let plugin = bar.register(segment(
+            StatusProvider::Plugin("weather".to_string()),
+            StatusSlot::Right,
+            20,
+        ));
+        assert!(bar.update(2, Some(Segment::new("⎈ prod:api", None))));
+        assert!(bar.update(3, Some(Segment::new("12:30", None))));
+        assert!(bar.update(plugin, Some(Segment::new("☀ 21°", None))));
+        assert!(!bar.update(plugin, Some(Segment::new("☀ 21°", None))));
+
+        let texts = |width| -> Vec<String> {
+            bar.layout(StatusSlot::Right, width)
+                .into_iter()
+                .map(|segment| segment.text.clone())
+                .collect()
+        };
+        assert_eq!(texts(40), vec!["⎈ prod:api", "12:30", "☀ 21°"]);
+        // The clock goes first, then the later of the two at priority 20
+        assert_eq!(texts(20), vec!["⎈ prod:api", "☀ 21°"]);
+        assert_eq!(texts(12), vec!["⎈ prod:api"]);
+        assert!(texts(3).is_empty());
+        assert!(bar.layout(StatusSlot::Left, 40).is_empty());
+    }
+
+    #[test]
+    fn test_providers() {
+        let config = "current-context: prod\ncontexts:\n- name: dev\n  context: {cluster: a}\n- name: prod\n  context: {cluster: b, namespace: api}\n";
+        assert_eq!(kube_context(config).as_deref(), Some("prod:api"));
+        assert_eq!(
+            kube_context("current-context: dev\ncontexts: []\n").as_deref(),
+            Some("dev")
+        );
+        assert_eq!(kube_context("current-context: \"\"\n"), None);
+
+        let process = |pid, parent| ProcessInfo {
+            pid,
+            parent: Some(parent),
+            name: "sleep".to_string(),
+            command: "sleep 60".to_string(),
+            cpu_percent: 0.0,
+            memory_bytes: 0,
+        };
+        let processes = vec![
+            process(10, 1),
+            process(11, 10),
+            process(12, 10),
+            process(13, 12),
+        ];
+        assert!(jobs_segment(&processes, 10).is_some());
+        assert!(jobs_segment(&processes, 11).is_none());
+
+        assert_eq!(plugin_segment("\n"), None);
+        assert_eq!(plugin_segment("☀ 21°\nignored").unwrap().text, "☀ 21°");
+        assert_eq!(
+            plugin_segment(&"x".repeat(50))
+                .unwrap()
+                .text
+                .chars()
+                .count(),
+            MAX_PLUGIN_WIDTH + 1
+        );
+        assert_eq!(
+            time_segment("%Y", chrono::Local::now()).unwrap().text.len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_panel_actions() {
+        let mut bar = StatusBar::new(&WarpConfig::default().status_bar.segments);
+        bar.register(segment(
+            StatusProvider::Plugin("weather".to_string()),
+            StatusSlot::Right,
+            0,
+        ));
+        bar.update(0, Some(Segment::new("⎇ main", None)));
+        bar.update(3, Some(Segment::new("12:30", None)));
+        bar.update(4, Some(Segment::new("☀", None)));
+        let mut panel = StatusPanel::new(&bar);
+        assert_eq!(panel.view().items.len(), 3);
+
+        assert!(matches!(
+            panel.handle_key('\n', 0),
+            StatusCommand::Run(StatusAction::Action(name)) if name == "toggle_git"
+        ));
+        assert!(matches!(panel.handle_key('\n', 1), StatusCommand::None));
+        assert!(
+            matches!(panel.handle_key('\n', 2), StatusCommand::Plugin(name) if name == "weather")
+        );
+        assert!(matches!(panel.handle_key('x', 2), StatusCommand::None));
+    }
+}
@@ -0,0 +1,380 @@
+use crossterm::style::Color;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+use crate::i18n;
+use crate::pager::parse_ansi;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// Widest a column is drawn in the panel; the preview shows full values
+const MAX_COLUMN_WIDTH: usize = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    // Rows as JSON objects keyed by column, for copying out of the viewer
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.rows
+                .iter()
+                .map(|row| {
+                    let record: Map<String, Value> = self
+                        .columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, cell)| (column.clone(), Value::String(cell.clone())))
+                        .collect();
+                    Value::Object(record)
+                })
+                .collect(),
+        )
+    }
+}
+
+fn plain(line: &str) -> String {
+    parse_ansi(line).into_iter().map(|(text, _)| text).collect()
+}
+
+// ╭──┬──╮, ├──┼──┤, +----+----+ and the like
+fn is_rule(line: &str) -> bool {
+    !line.is_empty()
+        && line.contains(['─', '━', '═', '-'])
+        && line.chars().all(|c| "─━═-+╭╮╰╯├┤┼┬┴┌┐└┘│|: ".contains(c))
+}
+
+fn cells(line: &str) -> Option<Vec<String>> {
+    let separator = ['│', '┃', '|']
+        .into_iter()
+        .find(|&c| line.starts_with(c) && line.ends_with(c) && line.len() > 1)?;
+    let inner = &line[separator.len_utf8()..line.len() - separator.len_utf8()];
+    Some(
+        inner
+            .split(separator)
+            .map(|cell| cell.trim().to_string())
+            .collect(),
+    )
+}
+
+// Reads the last table in a block of output, in the layout nushell draws
+// them (any of its bordered themes). A rule under the first row marks it as
+// the header; without one the table is a record of field/value pairs.
+pub fn parse_table(lines: &[String]) -> Option<Table> {
+    let mut tables: Vec<Vec<Option<Vec<String>>>> = Vec::new();
+    let mut current: Vec<Option<Vec<String>>> = Vec::new();
+    for line in lines {
+        let line = plain(line);
+        let line = line.trim();
+        if is_rule(line) {
+            current.push(None);
+        } else if let Some(cells) = cells(line) {
+            current.push(Some(cells));
+        } else if !current.is_empty() {
+            tables.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        tables.push(current);
+    }
+
+    tables.into_iter().rev().find_map(|entries| {
+        let first = entries.iter().position(Option::is_some)?;
+        let header = entries[first + 1..].first().is_some_and(Option::is_none)
+            && entries[first + 1..].iter().any(Option::is_some);
+        let mut rows: Vec<Vec<String>> = entries.into_iter().flatten().collect();
+        let columns = match header {
+            true => rows.remove(0),
+            false if rows.iter().all(|row| row.len() == 2) => {
+                vec![i18n::t("structured.field"), i18n::t("structured.value")]
+            }
+            false => (1..=rows[0].len()).map(|i| i.to_string()).collect(),
+        };
+        for row in &mut rows {
+            row.resize(columns.len(), String::new());
+        }
+        Some(Table { columns, rows })
+    })
+}
+
+// "1.5 KiB" -> 1536; plain numbers as they are
+fn numeric(value: &str) -> Option<f64> {
+    if let Ok(number) = value.parse::<f64>() {
+        return Some(number);
+    }
+    let (number, unit) = value.split_once(' ')?;
+    let scale = match unit {
+        "B" => 1.0,
+        "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    number.parse::<f64>().ok().map(|n| n * scale)
+}
+
+fn compare(a: &str, b: &str) -> Ordering {
+    match (numeric(a), numeric(b)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructuredCommand {
+    None,
+    Redraw,
+    Copy(String),
+}
+
+// The last table a command printed, as rows that can be sorted and copied
+// out as JSON
+pub struct StructuredPanel {
+    table: Table,
+    // Indices into table.rows in display order
+    order: Vec<usize>,
+    sort: Option<(usize, bool)>, // column, descending
+    selected: usize,
+}
+
+impl StructuredPanel {
+    pub fn new(table: Table) -> Self {
+        let order = (0..table.rows.len()).collect();
+        Self {
+            table,
+            order,
+            sort: None,
+            selected: 0,
+        }
+    }
+
+    // Row 0 in the panel is the header
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.order.len());
+    }
+
+    fn resort(&mut self) {
+        let rows = &self.table.rows;
+        self.order = (0..rows.len()).collect();
+        if let Some((column, descending)) = self.sort {
+            self.order.sort_by(|&a, &b| {
+                let ordering = compare(&rows[a][column], &rows[b][column]);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> StructuredCommand {
+        self.select(index);
+        match key {
+            // Next column, then back to the original order
+            's' => {
+                self.sort = match self.sort {
+                    None => Some((0, false)),
+                    Some((column, _)) if column + 1 < self.table.columns.len() => {
+                        Some((column + 1, false))
+                    }
+                    Some(_) => None,
+                };
+                self.resort();
+                StructuredCommand::Redraw
+            }
+            'r' => {
+                if let Some((_, descending)) = self.sort.as_mut() {
+                    *descending = !*descending;
+                }
+                self.resort();
+                StructuredCommand::Redraw
+            }
+            'y' => StructuredCommand::Copy(self.table.to_json().to_string()),
+            _ => StructuredCommand::None,
+        }
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        self.table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.table
+                    .rows
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.chars().count() + 2])
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH)
+            })
+            .collect()
+    }
+
+    fn line(cells: &[String], widths: &[usize]) -> String {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, &width)| {
+                let cell: String = match cell.chars().count() > width {
+                    true => cell.chars().take(width - 1).chain(['…']).collect(),
+                    false => cell.clone(),
+                };
+                format!("{:<width$}", cell)
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    }
+
+    pub fn view(&self) -> PanelView {
+        let widths = self.widths();
+        let header: Vec<String> = self
+            .table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| match self.sort {
+                Some((sorted, false)) if sorted == i => format!("{} ▲", column),
+                Some((sorted, true)) if sorted == i => format!("{} ▼", column),
+                _ => column.clone(),
+            })
+            .collect();
+        let mut items = vec![PanelItem::styled(vec![(
+            Self::line(&header, &widths),
+            Some(Color::Cyan),
+        )])];
+        items.extend(
+            self.order
+                .iter()
+                .map(|&row| Self::line(&self.table.rows[row], &widths).into()),
+        );
+        let preview = match self.selected.checked_sub(1).and_then(|i| self.order.get(i)) {
+            Some(&row) => self
+                .table
+                .columns
+                .iter()
+                .zip(&self.table.rows[row])
+                .map(|(column, cell)| {
+                    PanelItem::styled(vec![
+                        (format!("{}: ", column), Some(Color::DarkGrey)),
+                        (cell.clone(), None),
+                    ])
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        PanelView {
+            kind: PanelKind::Structured,
+            title: i18n::t_args(
+                "panel.structured",
+                &[
+                    ("rows", &self.table.rows.len().to_string()),
+                    ("columns", &self.table.columns.len().to_string()),
+                ],
+            ),
+            items,
+            footer: Some(i18n::t("panel.structured_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    const LS: &str = "\
+> ls
+╭───┬────────────┬──────┬──────────┬──────────────╮
+│ # │    name    │ type │   size   │   modified   │
+├───┼────────────┼──────┼──────────┼──────────────┤
+│ 0 │ Cargo.toml │ file │  1.2 KiB │ 2 hours ago  │
+│ 1 │ src        │ dir  │   4.0 KiB │ 3 days ago   │
+│ 2 │ README.md  │ file │    512 B │ a week ago   │
+╰───┴────────────┴──────┴──────────┴──────────────╯
+";
+
+    #[test]
+    fn test_parses_nushell_table() {
+        let table = parse_table(&lines(LS)).unwrap();
+        assert_eq!(table.columns, vec!["#", "name", "type", "size", "modified"]);
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(
+            table.rows[1],
+            vec!["1", "src", "dir", "4.0 KiB", "3 days ago"]
+        );
+        assert_eq!(table.to_json()[2]["name"], "README.md");
+    }
+
+    #[test]
+    fn test_parses_record_and_basic_theme() {
+        let record = "\
+╭──────┬────────────╮
+│ name │ Cargo.toml │
+│ type │ file       │
+╰──────┴────────────╯";
+        let table = parse_table(&lines(record)).unwrap();
+        assert_eq!(table.columns.len(), 2);
+        assert_eq!(
+            table.rows,
+            vec![vec!["name", "Cargo.toml"], vec!["type", "file"]]
+        );
+
+        let basic = "\
++---+------+
+| # | name |
++---+------+
+| 0 | a    |
++---+------+";
+        let table = parse_table(&lines(basic)).unwrap();
+        assert_eq!(table.columns, vec!["#", "name"]);
+        assert_eq!(table.rows, vec![vec!["0", "a"]]);
+
+        assert!(parse_table(&lines("total 8\n-rw-r--r-- 1 me me 0 x")).is_none());
+    }
+
+    #[test]
+    fn test_ignores_ansi_and_takes_last_table() {
+        let output = format!("{}\nsome text\n\x1b[32m│ a │ b │\x1b[0m\n", LS);
+        let table = parse_table(&lines(&output)).unwrap();
+        assert_eq!(table.rows, vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn test_sorts_sizes_numerically() {
+        let mut panel = StructuredPanel::new(parse_table(&lines(LS)).unwrap());
+        for _ in 0..4 {
+            panel.handle_key('s', 0);
+        }
+        let names = |panel: &StructuredPanel| -> Vec<String> {
+            panel
+                .order
+                .iter()
+                .map(|&i| panel.table.rows[i][1].clone())
+                .collect()
+        };
+        assert_eq!(names(&panel), vec!["README.md", "Cargo.toml", "src"]);
+        panel.handle_key('r', 0);
+        assert_eq!(names(&panel), vec!["src", "Cargo.toml", "README.md"]);
+
+        panel.select(1);
+        assert_eq!(panel.view().preview.len(), 5);
+        assert!(
+            matches!(panel.handle_key('y', 1), StructuredCommand::Copy(json) if json.starts_with("[{"))
+        );
+    }
+}
@@ -1,52 +1,72 @@
 use crossterm::{
     cursor,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{
+        Attribute, Attributes, Color, Print, SetAttribute, SetAttributes, SetBackgroundColor,
+        SetForegroundColor,
+    },
     terminal::{self, ClearType},
-    QueueableCommand,
+    ExecutableCommand, QueueableCommand,
+};
+use ratatui::{
+    backend::{Backend, WindowSize},
+    buffer::Cell as RatatuiCell,
+    layout::{Rect, Size},
+    style::Modifier,
 };
 use std::io::{self, Write};
+use unicode_width::UnicodeWidthStr;
 
 use crate::error::WarpError;
+use crate::grid::{Cell, DamageRegion, Grid};
 
+// Double-buffered screen: writes go to the back grid, present() diffs it
+// against what is on screen (front) and emits only the damaged runs. The
+// UI draws through it as its ratatui backend.
 pub struct Terminal {
     width: u16,
     height: u16,
     cursor_x: u16,
     cursor_y: u16,
-    buffer: Vec<Vec<char>>,
+    front: Grid,
+    back: Grid,
+    force_full_redraw: bool,
 }
 
 impl Terminal {
     pub async fn new() -> Result<Self, WarpError> {
         let (width, height) = terminal::size()?;
-        let buffer = vec![vec![' '; width as usize]; height as usize];
 
         Ok(Self {
             width,
             height,
             cursor_x: 0,
             cursor_y: 0,
-            buffer,
+            front: Grid::new(width, height),
+            back: Grid::new(width, height),
+            force_full_redraw: true,
         })
     }
 
     pub async fn resize(&mut self, width: u16, height: u16) -> Result<(), WarpError> {
+        self.fit(width, height);
+        Ok(())
+    }
+
+    fn fit(&mut self, width: u16, height: u16) {
         self.width = width;
         self.height = height;
-        self.buffer = vec![vec![' '; width as usize]; height as usize];
-        Ok(())
+        self.front = Grid::new(width, height);
+        self.back = Grid::new(width, height);
+        self.force_full_redraw = true;
     }
 
+    // Clears the back buffer only; nothing is erased on screen until present(),
+    // and then only cells that actually changed, which avoids the flicker of
+    // a full-screen clear every frame
     pub async fn clear(&mut self) -> Result<(), WarpError> {
-        let mut stdout = io::stdout();
-        stdout.queue(terminal::Clear(ClearType::All))?;
-        stdout.queue(cursor::MoveTo(0, 0))?;
-        stdout.flush()?;
-
-        self.buffer = vec![vec![' '; self.width as usize]; self.height as usize];
+        self.back.clear();
         self.cursor_x = 0;
         self.cursor_y = 0;
-
         Ok(())
     }
 
@@ -57,24 +77,82 @@ impl Terminal {
         text: &str,
         color: Color,
     ) -> Result<(), WarpError> {
+        self.back.put_str(x, y, text, color, Color::Reset, false);
+        Ok(())
+    }
+
+    pub fn put_cell(&mut self, x: u16, y: u16, cell: Cell) {
+        self.back.set(x, y, cell);
+    }
+
+    // Flushes the frame. Returns the number of damaged regions written, which
+    // is zero when the frame is identical to what is already displayed
+    pub fn present(&mut self) -> Result<usize, WarpError> {
+        Ok(self.present_damage()?)
+    }
+
+    fn present_damage(&mut self) -> io::Result<usize> {
+        let regions = if self.force_full_redraw {
+            self.back.full_damage()
+        } else {
+            self.back.diff(&self.front)
+        };
+
+        if regions.is_empty() {
+            return Ok(0);
+        }
+
         let mut stdout = io::stdout();
-        stdout.queue(cursor::MoveTo(x, y))?;
-        stdout.queue(SetForegroundColor(color))?;
-        stdout.queue(Print(text))?;
-        stdout.queue(ResetColor)?;
+        if self.force_full_redraw {
+            stdout.queue(terminal::Clear(ClearType::All))?;
+            self.force_full_redraw = false;
+        }
+        for region in &regions {
+            self.write_region(&mut stdout, region)?;
+        }
+        stdout.queue(cursor::MoveTo(self.cursor_x, self.cursor_y))?;
         stdout.flush()?;
 
-        // Update buffer
-        if y < self.height && x < self.width {
-            let chars: Vec<char> = text.chars().collect();
-            for (i, &ch) in chars.iter().enumerate() {
-                let pos_x = x + i as u16;
-                if pos_x < self.width {
-                    self.buffer[y as usize][pos_x as usize] = ch;
+        self.front.clone_from(&self.back);
+        Ok(regions.len())
+    }
+
+    fn write_region(&self, stdout: &mut io::Stdout, region: &DamageRegion) -> io::Result<()> {
+        stdout.queue(cursor::MoveTo(region.x_start, region.y))?;
+
+        let row = self.back.row(region.y);
+        let mut current_style: Option<(Color, Color, Attributes)> = None;
+        let mut run = String::new();
+
+        for cell in &row[region.x_start as usize..region.x_end as usize] {
+            // Placeholder half of a wide character; the terminal already advanced
+            if cell.ch == '\0' {
+                continue;
+            }
+            let style = (cell.fg, cell.bg, cell.attributes);
+            if current_style != Some(style) {
+                if !run.is_empty() {
+                    stdout.queue(Print(&run))?;
+                    run.clear();
                 }
+                stdout.queue(SetAttribute(Attribute::Reset))?;
+                stdout.queue(SetAttributes(cell.attributes))?;
+                stdout.queue(SetForegroundColor(cell.fg))?;
+                stdout.queue(SetBackgroundColor(cell.bg))?;
+                current_style = Some(style);
             }
+            run.push(cell.ch);
+        }
+
+        if !run.is_empty() {
+            stdout.queue(Print(&run))?;
         }
+        stdout.queue(SetAttribute(Attribute::Reset))?;
+        Ok(())
+    }
 
+    pub async fn flush(&mut self) -> Result<(), WarpError> {
+        self.present()?;
         Ok(())
     }
 
@@ -89,6 +167,10 @@ impl Terminal {
         Ok(())
     }
 
+    pub fn invalidate(&mut self) {
+        self.force_full_redraw = true;
+    }
+
     pub fn get_size(&self) -> (u16, u16) {
         (self.width, self.height)
     }
@@ -97,3 +179,145 @@ impl Terminal {
         (self.cursor_x, self.cursor_y)
     }
 }
+
+// ratatui hands over the cells it changed, which land in the back grid;
+// nothing reaches the screen until its flush() presents the frame
+impl Backend for Terminal {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a RatatuiCell)>,
+    {
+        for (x, y, cell) in content {
+            let cell_at = |ch| Cell {
+                ch,
+                fg: cell.fg.into(),
+                bg: cell.bg.into(),
+                attributes: to_attributes(cell.modifier),
+            };
+            self.back
+                .set(x, y, cell_at(cell.symbol().chars().next().unwrap_or(' ')));
+            if cell.symbol().width() == 2 {
+                self.back.set(x + 1, y, cell_at('\0'));
+            }
+        }
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        io::stdout().execute(cursor::Hide)?;
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        io::stdout().execute(cursor::Show)?;
+        Ok(())
+    }
+
+    fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
+        Ok((self.cursor_x, self.cursor_y))
+    }
+
+    // Takes effect when the frame is presented
+    fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+        self.cursor_x = x;
+        self.cursor_y = y;
+        Ok(())
+    }
+
+    // ratatui clears after a resize, so this is also where the grids follow
+    // the new size
+    fn clear(&mut self) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        if (width, height) != (self.width, self.height) {
+            self.fit(width, height);
+        }
+        self.back.clear();
+        self.force_full_redraw = true;
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<Rect> {
+        let (width, height) = terminal::size()?;
+        Ok(Rect::new(0, 0, width, height))
+    }
+
+    fn window_size(&mut self) -> io::Result<WindowSize> {
+        let size = terminal::window_size()?;
+        Ok(WindowSize {
+            columns_rows: Size {
+                width: size.columns,
+                height: size.rows,
+            },
+            pixels: Size {
+                width: size.width,
+                height: size.height,
+            },
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.present_damage()?;
+        Ok(())
+    }
+}
+
+fn to_attributes(modifier: Modifier) -> Attributes {
+    [
+        (Modifier::BOLD, Attribute::Bold),
+        (Modifier::DIM, Attribute::Dim),
+        (Modifier::ITALIC, Attribute::Italic),
+        (Modifier::UNDERLINED, Attribute::Underlined),
+        (Modifier::SLOW_BLINK, Attribute::SlowBlink),
+        (Modifier::RAPID_BLINK, Attribute::RapidBlink),
+        (Modifier::REVERSED, Attribute::Reverse),
+        (Modifier::HIDDEN, Attribute::Hidden),
+        (Modifier::CROSSED_OUT, Attribute::CrossedOut),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| modifier.contains(*flag))
+    .fold(Attributes::default(), |attributes, (_, attribute)| {
+        attributes | attribute
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Color as RatatuiColor, Style};
+
+    fn terminal(width: u16, height: u16) -> Terminal {
+        Terminal {
+            width,
+            height,
+            cursor_x: 0,
+            cursor_y: 0,
+            front: Grid::new(width, height),
+            back: Grid::new(width, height),
+            force_full_redraw: false,
+        }
+    }
+
+    #[test]
+    fn test_draw_fills_back_grid_only() {
+        let mut terminal = terminal(4, 1);
+        let mut wide = RatatuiCell::default();
+        wide.set_symbol("日")
+            .set_fg(RatatuiColor::Red)
+            .set_style(Style::default().add_modifier(Modifier::REVERSED));
+        terminal.draw([(1, 0, &wide)].into_iter()).unwrap();
+
+        let cell = terminal.back.get(1, 0).unwrap();
+        assert_eq!((cell.ch, cell.fg), ('日', Color::DarkRed));
+        assert!(cell.attributes.has(Attribute::Reverse));
+        assert_eq!(terminal.back.get(2, 0).unwrap().ch, '\0');
+        assert_eq!(terminal.back.diff(&terminal.front).len(), 1);
+    }
+
+    #[test]
+    fn test_modifiers_map_to_attributes() {
+        let attributes = to_attributes(Modifier::BOLD | Modifier::UNDERLINED);
+        assert!(attributes.has(Attribute::Bold));
+        assert!(attributes.has(Attribute::Underlined));
+        assert!(!attributes.has(Attribute::Italic));
+    }
+}
@@ -131,7 +131,7 @@ impl ThemeManager {
     async fn load_theme_file(&self, path: &PathBuf) -> Result<WarpTheme, WarpError> {
         let content = fs::read_to_string(path).await?;
         let theme: WarpTheme = serde_yaml::from_str(&content)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to parse theme: {}", e)))?;
+            .map_err(|e| WarpError::Config(format!("Failed to parse theme: {}", e)))?;
         Ok(theme)
     }
 
@@ -148,7 +148,7 @@ impl ThemeManager {
             self.current_theme = name;
             Ok(())
         } else {
-            Err(WarpError::ConfigError(format!("Theme '{}' not found", name)))
+            Err(WarpError::not_found("theme", name))
         }
     }
 
@@ -158,13 +158,13 @@ impl ThemeManager {
 
     pub async fn install_theme_from_url(&mut self, url: &str) -> Result<(), WarpError> {
         let response = reqwest::get(url).await
-            .map_err(|e| WarpError::ConfigError(format!("Failed to download theme: {}", e)))?;
+            .map_err(|e| WarpError::network("Failed to download theme", e))?;
         
         let content = response.text().await
-            .map_err(|e| WarpError::ConfigError(format!("Failed to read theme content: {}", e)))?;
+            .map_err(|e| WarpError::network("Failed to read theme content", e))?;
         
         let theme: WarpTheme = serde_yaml::from_str(&content)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to parse downloaded theme: {}", e)))?;
+            .map_err(|e| WarpError::Config(format!("Failed to parse downloaded theme: {}", e)))?;
         
         self.themes.insert(theme.name.clone(), theme);
         Ok(())
@@ -0,0 +1,417 @@
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use crossterm::style::Color;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fmt::Write;
+
+use crate::error::WarpError;
+use crate::i18n;
+use crate::pager::parse_ansi;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// RFC 3986 unreserved characters stay as they are
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+const PREVIEW_LINES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    JsonPretty,
+    JsonMinify,
+    YamlPretty,
+    YamlMinify,
+    HexDump,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 9] = [
+        Transform::Base64Encode,
+        Transform::Base64Decode,
+        Transform::UrlEncode,
+        Transform::UrlDecode,
+        Transform::JsonPretty,
+        Transform::JsonMinify,
+        Transform::YamlPretty,
+        Transform::YamlMinify,
+        Transform::HexDump,
+    ];
+
+    pub fn name(self) -> String {
+        i18n::t(match self {
+            Transform::Base64Encode => "transforms.base64_encode",
+            Transform::Base64Decode => "transforms.base64_decode",
+            Transform::UrlEncode => "transforms.url_encode",
+            Transform::UrlDecode => "transforms.url_decode",
+            Transform::JsonPretty => "transforms.json_pretty",
+            Transform::JsonMinify => "transforms.json_minify",
+            Transform::YamlPretty => "transforms.yaml_pretty",
+            Transform::YamlMinify => "transforms.yaml_minify",
+            Transform::HexDump => "transforms.hex_dump",
+        })
+    }
+
+    pub fn apply(self, text: &str) -> Result<String, WarpError> {
+        match self {
+            Transform::Base64Encode => Ok(STANDARD.encode(text)),
+            Transform::Base64Decode => base64_decode(text),
+            Transform::UrlEncode => Ok(utf8_percent_encode(text.trim(), URL_COMPONENT).to_string()),
+            Transform::UrlDecode => percent_decode_str(&text.trim().replace('+', " "))
+                .decode_utf8()
+                .map(|text| text.into_owned())
+                .map_err(|_| WarpError::Validation(i18n::t("transforms.not_utf8"))),
+            Transform::JsonPretty => reformat_json(text, true),
+            Transform::JsonMinify => reformat_json(text, false),
+            Transform::YamlPretty => {
+                let value = parse_yaml(text)?;
+                let text = serde_yaml::to_string(&value).map_err(|e| invalid("YAML", e))?;
+                Ok(text.trim_end().to_string())
+            }
+            // JSON is YAML's flow style, so this stays valid YAML
+            Transform::YamlMinify => {
+                serde_json::to_string(&parse_yaml(text)?).map_err(|e| invalid("YAML", e))
+            }
+            Transform::HexDump => Ok(hex_dump(text.as_bytes())),
+        }
+    }
+}
+
+fn invalid(format: &str, error: impl std::fmt::Display) -> WarpError {
+    WarpError::Validation(i18n::t_args(
+        "transforms.invalid",
+        &[("format", format), ("error", &error.to_string())],
+    ))
+}
+
+// Standard or URL-safe alphabet, padded or not, wrapped or not. Bytes that
+// aren't text come back as a hex dump.
+fn base64_decode(text: &str) -> Result<String, WarpError> {
+    let normalized: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    let bytes = STANDARD_NO_PAD
+        .decode(normalized)
+        .map_err(|e| invalid("base64", e))?;
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(text),
+        Err(e) => Ok(hex_dump(e.as_bytes())),
+    }
+}
+
+fn parse_yaml(text: &str) -> Result<serde_yaml::Value, WarpError> {
+    serde_yaml::from_str(text).map_err(|e| invalid("YAML", e))
+}
+
+// Works on the text rather than a parsed value so keys keep their order
+// and numbers keep their spelling
+fn reformat_json(text: &str, pretty: bool) -> Result<String, WarpError> {
+    serde_json::from_str::<serde::de::IgnoredAny>(text).map_err(|e| invalid("JSON", e))?;
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    let mut chars = text.trim().chars().peekable();
+    let newline = |out: &mut String, depth: usize| {
+        if pretty {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth));
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.peek() {
+                    Some('}' | ']') => out.extend(chars.next()),
+                    _ => {
+                        depth += 1;
+                        newline(&mut out, depth);
+                    }
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(if pretty { ": " } else { ":" }),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+// The layout of `hexdump -C`
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x} ", row * 16);
+        for column in 0..16 {
+            if column == 8 {
+                out.push(' ');
+            }
+            match chunk.get(column) {
+                Some(byte) => {
+                    let _ = write!(out, " {:02x}", byte);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        let printable: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "  |{}|", printable);
+    }
+    let _ = write!(out, "{:08x}", bytes.len());
+    out
+}
+
+// Where the text being transformed comes from. There is no way to read
+// the system clipboard from inside the terminal, so "clipboard" is the
+// last thing pasted into warp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Input,
+    Clipboard,
+    Block,
+}
+
+impl Source {
+    fn name(self) -> String {
+        i18n::t(match self {
+            Source::Input => "transforms.source_input",
+            Source::Clipboard => "transforms.source_clipboard",
+            Source::Block => "transforms.source_block",
+        })
+    }
+}
+
+pub enum TransformCommand {
+    None,
+    Redraw,
+    // The input line is replaced when it was the source, added to otherwise
+    Insert { text: String, replace: bool },
+    Copy(String),
+    Block { command: String, lines: Vec<String> },
+}
+
+pub struct TransformsPanel {
+    sources: Vec<(Source, String)>,
+    source: usize,
+    selected: usize,
+}
+
+impl TransformsPanel {
+    // Empty sources are left out; the first one left is used
+    pub fn new(input: &str, clipboard: Option<&str>, block: &[String]) -> Self {
+        let block: Vec<String> = block
+            .iter()
+            .skip(1) // the command line
+            .map(|line| parse_ansi(line).into_iter().map(|(run, _)| run).collect())
+            .collect();
+        let sources = [
+            (Source::Input, input.to_string()),
+            (Source::Clipboard, clipboard.unwrap_or_default().to_string()),
+            (Source::Block, block.join("\n")),
+        ]
+        .into_iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect();
+        Self {
+            sources,
+            source: 0,
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(Transform::ALL.len() - 1);
+    }
+
+    fn result(&self) -> Option<(Source, Result<String, WarpError>)> {
+        let (source, text) = self.sources.get(self.source)?;
+        Some((*source, Transform::ALL[self.selected].apply(text)))
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> TransformCommand {
+        self.select(index);
+        if key == 's' && !self.sources.is_empty() {
+            self.source = (self.source + 1) % self.sources.len();
+            return TransformCommand::Redraw;
+        }
+        let Some((source, Ok(text))) = self.result() else {
+            return TransformCommand::None;
+        };
+        match key {
+            '\n' => TransformCommand::Insert {
+                text,
+                replace: source == Source::Input,
+            },
+            'c' => TransformCommand::Copy(text),
+            'b' => TransformCommand::Block {
+                command: i18n::t_args(
+                    "transforms.block",
+                    &[
+                        ("transform", &Transform::ALL[self.selected].name()),
+                        ("source", &source.name()),
+                    ],
+                ),
+                lines: text.lines().map(str::to_string).collect(),
+            },
+            _ => TransformCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = Transform::ALL
+            .iter()
+            .map(|transform| transform.name().into())
+            .collect();
+        let preview = match self.result() {
+            None => vec![i18n::t("transforms.empty").into()],
+            Some((source, result)) => {
+                let mut rows = vec![PanelItem::styled(vec![(
+                    i18n::t_args(
+                        "transforms.source",
+                        &[
+                            ("source", &source.name()),
+                            ("index", &(self.source + 1).to_string()),
+                            ("count", &self.sources.len().to_string()),
+                        ],
+                    ),
+                    Some(Color::DarkGrey),
+                )])];
+                match result {
+                    Ok(text) => rows.extend(
+                        text.lines()
+                            .take(PREVIEW_LINES)
+                            .map(|line| line.to_string().into()),
+                    ),
+                    Err(e) => rows.push(PanelItem::styled(vec![(e.to_string(), Some(Color::Red))])),
+                }
+                rows
+            }
+        };
+        PanelView {
+            kind: PanelKind::Transforms,
+            title: i18n::t("panel.transforms"),
+            items,
+            footer: Some(i18n::t("panel.transforms_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_keeps_key_order() {
+        let text = r#"{"b": 1.50, "a": [], "c": {"d": "x, \"y\": {z}", "e": [1, 2]}}"#;
+        assert_eq!(
+            Transform::JsonPretty.apply(text).unwrap(),
+            "{\n  \"b\": 1.50,\n  \"a\": [],\n  \"c\": {\n    \"d\": \"x, \\\"y\\\": {z}\",\n    \"e\": [\n      1,\n      2\n    ]\n  }\n}"
+        );
+        assert_eq!(
+            Transform::JsonMinify.apply(text).unwrap(),
+            r#"{"b":1.50,"a":[],"c":{"d":"x, \"y\": {z}","e":[1,2]}}"#
+        );
+        assert!(Transform::JsonPretty.apply("{\"a\": }").is_err());
+    }
+
+    #[test]
+    fn test_encodings_round_trip() {
+        let text = "user=me&next=/a b?c";
+        let encoded = Transform::UrlEncode.apply(text).unwrap();
+        assert_eq!(encoded, "user%3Dme%26next%3D%2Fa%20b%3Fc");
+        assert_eq!(Transform::UrlDecode.apply(&encoded).unwrap(), text);
+        assert_eq!(Transform::UrlDecode.apply("a+b%21").unwrap(), "a b!");
+
+        assert_eq!(Transform::Base64Encode.apply("hi?>").unwrap(), "aGk/Pg==");
+        // URL-safe, unpadded and wrapped input all decode
+        assert_eq!(Transform::Base64Decode.apply("aGk_Pg\n").unwrap(), "hi?>");
+        assert_eq!(
+            Transform::Base64Decode.apply("/w==").unwrap(),
+            hex_dump(&[0xff])
+        );
+        assert!(Transform::Base64Decode.apply("a!b").is_err());
+    }
+
+    #[test]
+    fn test_hex_dump_and_yaml() {
+        assert_eq!(
+            hex_dump(b"Hello, world!\n\x00\x01\x02"),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |Hello, world!...|\n\
+             00000010  02                                                |.|\n\
+             00000011"
+        );
+        let yaml = "zeta: 1\nalpha: [a, b]\n";
+        assert_eq!(
+            Transform::YamlMinify.apply(yaml).unwrap(),
+            r#"{"zeta":1,"alpha":["a","b"]}"#
+        );
+        assert_eq!(
+            Transform::YamlPretty
+                .apply(r#"{"zeta":1,"alpha":["a"]}"#)
+                .unwrap(),
+            "zeta: 1\nalpha:\n- a"
+        );
+    }
+
+    #[test]
+    fn test_panel_sources() {
+        let block = vec!["❯ cat token".to_string(), "\x1b[32maGk=\x1b[0m".to_string()];
+        let mut panel = TransformsPanel::new("  ", Some("a%20b"), &block);
+        assert_eq!(panel.sources.len(), 2);
+
+        let index = Transform::ALL
+            .iter()
+            .position(|t| *t == Transform::UrlDecode)
+            .unwrap();
+        assert!(matches!(
+            panel.handle_key('\n', index),
+            TransformCommand::Insert { ref text, replace: false } if text == "a b"
+        ));
+        assert!(matches!(panel.handle_key('s', 1), TransformCommand::Redraw));
+        match panel.handle_key('b', 1) {
+            TransformCommand::Block { lines, .. } => assert_eq!(lines, vec!["hi".to_string()]),
+            _ => panic!("expected a block"),
+        }
+    }
+}
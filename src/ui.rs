@@ -1,16 +1,50 @@
-use crossterm::{event::KeyEvent, style::Color};
+use base64::Engine;
+use crossterm::event::KeyEvent;
 use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Modifier, Style},
-    text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
-    Terminal as RatatuiTerminal,
+    text::{Line, Span},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, List, ListItem, Paragraph, Wrap,
+    },
+    Frame, Terminal as RatatuiTerminal,
 };
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex};
+use unicode_width::UnicodeWidthStr;
 
-use crate::{config::Config, error::WarpError};
+use crate::{
+    accessibility::{AccessibilityManager, AnnouncementPriority},
+    activity::{Indicator, TabBadge},
+    calc,
+    cloud::{CloudContext, CloudGuard},
+    completion::lsp::{self, LspEvent},
+    config::{Config, NotificationCategory, NotificationLevel, StatusSlot},
+    diff_view::{DiffMode, DiffView},
+    encoding::OutputEncoding,
+    error::WarpError,
+    i18n,
+    lint::{CommandLinter, Diagnostic, Severity},
+    output_buffer::{OutputBuffer, PagingLimits, TruncationLimits},
+    pager::{Pager, PagerAction},
+    permalinks,
+    ssh::SshConnectionState,
+    status_bar::{self, Segment, StatusBar},
+    terminal::Terminal,
+    widgets::{self as status_widgets, ParticipantBadge, PresenceState},
+};
+
+const SCROLLBACK_LINES: usize = 1000;
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+const MAX_TOASTS: usize = 3;
+const TOAST_WIDTH: u16 = 48;
+// Completions listed under the prompt when Tab can't pick one
+const COMPLETIONS_SHOWN: usize = 8;
 
 #[derive(Debug, Clone)]
 pub enum UIEvent {
@@ -19,16 +53,318 @@ pub enum UIEvent {
     AIQuery(String),
     ThemeChanged(String),
     Resize(u16, u16),
+    SshStateChanged(String, SshConnectionState),
+    AIResponse(String),
+    Timer(String),
+    // Pre-rendered WarpError lines (WarpError itself isn't Clone)
+    Error(Vec<String>),
+    ShowPanel(PanelView),
+    PanelClosed(PanelKind),
+    PanelAction {
+        kind: PanelKind,
+        key: char,
+        index: usize,
+    },
+    PanelSelection {
+        kind: PanelKind,
+        index: usize,
+    },
+    // Text entered at a panel prompt (see UI::begin_prompt)
+    PanelInput {
+        kind: PanelKind,
+        text: String,
+    },
+    PanelFooter {
+        kind: PanelKind,
+        footer: Option<String>,
+    },
+    PortStatus(Option<String>),
+    // Rendered status bar widgets (see crate::widgets)
+    StatusWidgets(Option<String>),
+    // A provider's latest segment, by the id it was registered with (see crate::status_bar)
+    StatusSegment(usize, Option<Segment>),
+    // Output a background pane wrote, read into its backlog (see crate::activity)
+    BackgroundOutput(usize, String),
+    // The tab strip may have changed; `focus` is a pane an auto-focus rule picked
+    TabActivity {
+        focus: Option<usize>,
+    },
+    // A multi-line script at the prompt changed, or Tab asked to complete it
+    // at a byte offset; see crate::completion::lsp
+    ScriptEdited(String),
+    ScriptCompletion {
+        text: String,
+        offset: usize,
+    },
+    Lsp(LspEvent),
+    // Collaboration session participants, for the header strip
+    Participants(Vec<ParticipantBadge>),
+    CloudContexts(Vec<CloudContext>),
+    // Shown in the header while a lazily started subsystem initializes
+    Loading(Option<String>),
+    Notice(Vec<String>),
+    Notify {
+        category: NotificationCategory,
+        level: NotificationLevel,
+        title: String,
+        body: Option<String>,
+    },
+    // Triggered from outside the key bindings, e.g. over IPC
+    Action(AppAction),
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppAction {
+    ToggleProcessTree,
+    TogglePorts,
+    ToggleGit,
+    ToggleFiles,
+    ToggleLocations,
+    ToggleProblems,
+    ToggleTasks,
+    ToggleSettings,
+    ToggleNotifications,
+    TogglePerformance,
+    TogglePager,
+    CopyBlockLink,
+    ToggleRunbooks,
+    ToggleCloud,
+    NewTab,
+    ToggleTable,
+    ToggleTelemetry,
+    ToggleDevConsole,
+    TogglePluginPermissions,
+    TogglePluginActivity,
+    CycleOutputEncoding,
+    ToggleSearch,
+    TagBlock,
+    TogglePinBlock,
+    ToggleFavoriteBlock,
+    TogglePinnedBlocks,
+    ToggleReplay,
+    ToggleWorkspaceTrust,
+    ToggleTraces,
+    TogglePaneTuning,
+    ToggleBroadcast,
+    ToggleConnections,
+    ShowDiff,
+    ExportHistory,
+    OpenMarketplace,
+    ToggleTransforms,
+    ToggleBellMute,
+    ToggleStatusBar,
+    NextTab,
+    NextActiveTab,
+    ToggleTabMute,
+    ToggleDebugger,
+    ToggleTests,
+    ToggleInsights,
+}
+
+impl AppAction {
+    pub const ALL: &'static [(&'static str, AppAction)] = &[
+        ("toggle_process_tree", AppAction::ToggleProcessTree),
+        ("toggle_ports", AppAction::TogglePorts),
+        ("toggle_git", AppAction::ToggleGit),
+        ("toggle_files", AppAction::ToggleFiles),
+        ("toggle_locations", AppAction::ToggleLocations),
+        ("toggle_problems", AppAction::ToggleProblems),
+        ("toggle_tasks", AppAction::ToggleTasks),
+        ("toggle_settings", AppAction::ToggleSettings),
+        ("toggle_notifications", AppAction::ToggleNotifications),
+        ("toggle_performance", AppAction::TogglePerformance),
+        ("toggle_pager", AppAction::TogglePager),
+        ("copy_block_link", AppAction::CopyBlockLink),
+        ("toggle_runbooks", AppAction::ToggleRunbooks),
+        ("toggle_cloud", AppAction::ToggleCloud),
+        ("new_tab", AppAction::NewTab),
+        ("toggle_table", AppAction::ToggleTable),
+        ("toggle_telemetry", AppAction::ToggleTelemetry),
+        ("toggle_dev_console", AppAction::ToggleDevConsole),
+        (
+            "toggle_plugin_permissions",
+            AppAction::TogglePluginPermissions,
+        ),
+        ("toggle_plugin_activity", AppAction::TogglePluginActivity),
+        ("cycle_output_encoding", AppAction::CycleOutputEncoding),
+        ("toggle_search", AppAction::ToggleSearch),
+        ("tag_block", AppAction::TagBlock),
+        ("toggle_pin_block", AppAction::TogglePinBlock),
+        ("toggle_favorite_block", AppAction::ToggleFavoriteBlock),
+        ("toggle_pinned_blocks", AppAction::TogglePinnedBlocks),
+        ("toggle_replay", AppAction::ToggleReplay),
+        ("toggle_workspace_trust", AppAction::ToggleWorkspaceTrust),
+        ("toggle_traces", AppAction::ToggleTraces),
+        ("toggle_pane_tuning", AppAction::TogglePaneTuning),
+        ("toggle_broadcast", AppAction::ToggleBroadcast),
+        ("toggle_connections", AppAction::ToggleConnections),
+        ("show_diff", AppAction::ShowDiff),
+        ("export_history", AppAction::ExportHistory),
+        ("open_marketplace", AppAction::OpenMarketplace),
+        ("toggle_transforms", AppAction::ToggleTransforms),
+        ("toggle_bell_mute", AppAction::ToggleBellMute),
+        ("toggle_status_bar", AppAction::ToggleStatusBar),
+        ("next_tab", AppAction::NextTab),
+        ("next_active_tab", AppAction::NextActiveTab),
+        ("toggle_tab_mute", AppAction::ToggleTabMute),
+        ("toggle_debugger", AppAction::ToggleDebugger),
+        ("toggle_tests", AppAction::ToggleTests),
+        ("toggle_insights", AppAction::ToggleInsights),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, action)| *action)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelKind {
+    ProcessTree,
+    Ports,
+    Git,
+    Forge,
+    Files,
+    Locations,
+    Problems,
+    Tasks,
+    Settings,
+    Notifications,
+    Performance,
+    Notebook,
+    Runbooks,
+    Cloud,
+    Paste,
+    NewTab,
+    Structured,
+    Telemetry,
+    DevConsole,
+    Debugger,
+    TestRunner,
+    PluginPermissions,
+    PluginActivity,
+    Search,
+    PinnedBlocks,
+    Replay,
+    WorkspaceTrust,
+    Traces,
+    PaneTuning,
+    Broadcast,
+    Connections,
+    Diff,
+    Transforms,
+    StatusBar,
+    Insights,
+    Export,
+}
+
+// One panel row. Most rows are plain text; diff rows carry per-segment colours.
+#[derive(Debug, Clone, Default)]
+pub struct PanelItem {
+    pub segments: Vec<(String, Option<crossterm::style::Color>)>,
+}
+
+impl PanelItem {
+    pub fn styled(segments: Vec<(String, Option<crossterm::style::Color>)>) -> Self {
+        Self { segments }
+    }
+}
+
+impl From<String> for PanelItem {
+    fn from(text: String) -> Self {
+        Self {
+            segments: vec![(text, None)],
+        }
+    }
+}
+
+// Content for the side panel. The owning subsystem builds the rows; the UI
+// only tracks selection and forwards keypresses back as PanelAction
+#[derive(Debug, Clone)]
+pub struct PanelView {
+    pub kind: PanelKind,
+    pub title: String,
+    pub items: Vec<PanelItem>,
+    pub footer: Option<String>,
+    // Shown below the list when non-empty
+    pub preview: Vec<PanelItem>,
+    // Raw image file drawn over the preview area with the inline image
+    // protocol; only set when inline_images_supported()
+    pub image: Option<Arc<Vec<u8>>>,
+}
+
+// A note shown under the prompt. When it carries a command, Alt+Enter runs
+// it as if it had been typed, asking for y/n first when `confirm` is set.
+#[derive(Debug, Clone)]
+pub struct PromptHint {
+    pub message: String,
+    pub command: Option<String>,
+    pub confirm: bool,
+}
+
+// While a prompt is active the input line collects text for the panel
+// instead of the shell
+#[derive(Debug, Clone)]
+struct PanelPrompt {
+    kind: PanelKind,
+    label: String,
+}
+
+struct Toast {
+    text: String,
+    color: crossterm::style::Color,
+    expires: Instant,
 }
 
 pub struct UI {
     config: Arc<Mutex<Config>>,
-    terminal: RatatuiTerminal<CrosstermBackend<std::io::Stdout>>,
+    terminal: RatatuiTerminal<Terminal>,
     event_sender: mpsc::UnboundedSender<UIEvent>,
-    output_buffer: Vec<String>,
+    output_buffer: OutputBuffer,
     input_buffer: String,
     cursor_position: usize,
+    // What a `= …` line evaluates to, shown after it and taken by Tab
+    calc_result: Option<String>,
     ai_response: Option<String>,
+    ssh_status: Option<String>,
+    // Every tab with its activity, shown once there's more than one
+    tabs: Vec<TabBadge>,
+    // Shell (and profile) of the active tab
+    shell_label: Option<String>,
+    // Header border colour of a saved connection's tab
+    accent: Option<crossterm::style::Color>,
+    encoding: OutputEncoding,
+    port_status: Option<String>,
+    status_widgets: Option<String>,
+    status_bar: StatusBar,
+    participants: Vec<ParticipantBadge>,
+    cloud_contexts: Vec<CloudContext>,
+    cloud_guard: Option<Arc<CloudGuard>>,
+    loading: Option<String>,
+    accessibility: Option<Arc<AccessibilityManager>>,
+    panel: Option<PanelView>,
+    panel_selected: usize,
+    prompt: Option<PanelPrompt>,
+    image_pending: bool,
+    image_on_screen: bool,
+    pager: Option<Pager>,
+    // The diff in the pager, rendered again for a new mode or width
+    diff: Option<DiffView>,
+    pager_width: usize,
+    hint: Option<PromptHint>,
+    // The hint's command while waiting for y/n
+    confirming: Option<String>,
+    linter: Option<CommandLinter>,
+    diagnostics: Vec<Diagnostic>,
+    scripts: bool, // multi-line input goes to a language server
+    toasts: Vec<Toast>,
+    bell_until: Option<Instant>, // the output border flashes until then
+    needs_redraw: bool,
+    last_frame: Instant,
+    frame_interval: Duration, // from the active pane's frame rate cap
 }
 
 impl UI {
@@ -36,22 +372,159 @@ impl UI {
         config: Arc<Mutex<Config>>,
         event_sender: mpsc::UnboundedSender<UIEvent>,
     ) -> Result<Self, WarpError> {
-        let backend = CrosstermBackend::new(std::io::stdout());
-        let terminal = RatatuiTerminal::new(backend)?;
+        let terminal = RatatuiTerminal::new(Terminal::new().await?)?;
+        let mut output_buffer = OutputBuffer::new(SCROLLBACK_LINES);
+        output_buffer.set_paging(PagingLimits::from_config(
+            &config.lock().await.terminal.scrollback_paging,
+        ));
 
         Ok(Self {
             config,
             terminal,
             event_sender,
-            output_buffer: Vec::new(),
+            output_buffer,
             input_buffer: String::new(),
             cursor_position: 0,
+            calc_result: None,
             ai_response: None,
+            ssh_status: None,
+            tabs: Vec::new(),
+            shell_label: None,
+            accent: None,
+            encoding: OutputEncoding::Utf8,
+            port_status: None,
+            status_widgets: None,
+            status_bar: StatusBar::default(),
+            participants: Vec::new(),
+            cloud_contexts: Vec::new(),
+            cloud_guard: None,
+            loading: None,
+            accessibility: None,
+            panel: None,
+            panel_selected: 0,
+            prompt: None,
+            image_pending: false,
+            image_on_screen: false,
+            pager: None,
+            diff: None,
+            pager_width: 120,
+            hint: None,
+            confirming: None,
+            linter: None,
+            diagnostics: Vec::new(),
+            scripts: false,
+            toasts: Vec::new(),
+            bell_until: None,
+            needs_redraw: true,
+            last_frame: Instant::now() - FRAME_INTERVAL,
+            frame_interval: FRAME_INTERVAL,
         })
     }
 
+    pub fn set_accessibility(&mut self, accessibility: Arc<AccessibilityManager>) {
+        self.accessibility = Some(accessibility);
+    }
+
+    pub fn set_linter(&mut self, linter: CommandLinter) {
+        self.linter = Some(linter);
+    }
+
+    pub fn set_script_servers(&mut self, enabled: bool) {
+        self.scripts = enabled;
+    }
+
+    // Typed or pasted text spanning lines is a script rather than a command
+    fn editing_script(&self) -> bool {
+        self.scripts && self.prompt.is_none() && self.input_buffer.contains('\n')
+    }
+
+    // What the language server found, while the script is still there
+    pub fn set_script_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        if self.editing_script() {
+            self.diagnostics = diagnostics;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn complete_script(&mut self, items: &[lsp::Completion]) {
+        if !self.editing_script() {
+            return;
+        }
+        let before = self
+            .input_buffer
+            .get(..self.cursor_position)
+            .unwrap_or(&self.input_buffer);
+        let (insert, labels) = lsp::completion_edit(before, items);
+        self.hint = match labels.len() {
+            0 if insert.is_empty() => Some(i18n::t("lsp.no_completions")),
+            0 => None,
+            count => {
+                let mut shown = labels
+                    .into_iter()
+                    .take(COMPLETIONS_SHOWN)
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                if count > COMPLETIONS_SHOWN {
+                    shown.push_str("  …");
+                }
+                Some(shown)
+            }
+        }
+        .map(|message| PromptHint {
+            message,
+            command: None,
+            confirm: false,
+        });
+        if !insert.is_empty() {
+            self.insert_text(&insert);
+        }
+        self.needs_redraw = true;
+    }
+
+    pub fn set_cloud_guard(&mut self, guard: Option<Arc<CloudGuard>>) {
+        self.cloud_guard = guard;
+    }
+
     pub async fn render(&mut self) -> Result<(), WarpError> {
+        if let Some(accessibility) = &self.accessibility {
+            accessibility.flush_output_announcements().await?;
+        }
+
+        // Skip frames when nothing changed, and cap the frame rate so a flood
+        // of PTY output renders at most once per interval instead of per chunk
+        self.needs_redraw |= self.output_buffer.take_dirty();
+        let toasts = self.toasts.len();
+        self.toasts.retain(|toast| toast.expires > Instant::now());
+        self.needs_redraw |= self.toasts.len() != toasts;
+        if self.bell_until.is_some_and(|until| until <= Instant::now()) {
+            self.bell_until = None;
+            self.needs_redraw = true;
+        }
+        if !self.needs_redraw || self.last_frame.elapsed() < self.frame_interval {
+            return Ok(());
+        }
+        self.needs_redraw = false;
+        self.last_frame = Instant::now();
+
+        // Inline images live outside ratatui's buffer, so it never repaints
+        // the cells under one; a full clear is the only way to remove it
+        if self.image_on_screen
+            && (self.image_pending || self.panel.as_ref().is_none_or(|p| p.image.is_none()))
+        {
+            self.terminal.clear()?;
+            self.image_on_screen = false;
+        }
+        let mut image_area = None;
+        let mut pager_height = None;
+        let mut pager_width = self.pager_width;
+
         let config = self.config.lock().await;
+        let _frame = tracing::trace_span!("render").entered();
+        let palette = self
+            .accessibility
+            .as_ref()
+            .map(|a| a.palette())
+            .unwrap_or_default();
 
         self.terminal.draw(|f| {
             let chunks = Layout::default()
@@ -68,27 +541,300 @@ impl UI {
                 .split(f.size());
 
             // Header
-            let header = Paragraph::new("🚀 Warp Terminal - Modern Rust Terminal with AI")
-                .block(Block::default().borders(Borders::ALL))
-                .style(Style::default().fg(to_ratatui_color(Color::Cyan)));
+            let mut header_block = Block::default().borders(Borders::ALL);
+            if let Some(accent) = self.accent {
+                header_block =
+                    header_block.border_style(Style::default().fg(to_ratatui_color(accent)));
+            }
+            if self.tabs.len() > 1 {
+                let mut spans = Vec::new();
+                for tab in &self.tabs {
+                    if !spans.is_empty() {
+                        spans.push(Span::raw(" "));
+                    }
+                    let color = match tab.indicator.filter(|_| !tab.muted) {
+                        Some(Indicator::Output) => Some(crossterm::style::Color::Yellow),
+                        Some(Indicator::Silence) => Some(crossterm::style::Color::Green),
+                        None => None,
+                    };
+                    let mut style = color.map_or(Style::default(), |color| {
+                        Style::default().fg(to_ratatui_color(color))
+                    });
+                    if tab.active {
+                        style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+                    } else if tab.muted {
+                        style = style.add_modifier(Modifier::DIM);
+                    }
+                    spans.push(Span::styled(tab.label(), style));
+                }
+                header_block = header_block.title(Line::from(spans));
+            }
+            if let Some(ref shell) = self.shell_label {
+                header_block = header_block.title(format!("[{}]", shell));
+            }
+            // Only called out when it isn't the default
+            if self.encoding != OutputEncoding::Utf8 {
+                header_block = header_block.title(format!("[{}]", self.encoding.label()));
+            }
+            if let Some(ref status) = self.ssh_status {
+                header_block =
+                    header_block.title(i18n::t_args("ui.ssh_status", &[("status", status)]));
+            }
+            if !self.cloud_contexts.is_empty() {
+                let production = self.cloud_guard.as_ref().is_some_and(|guard| {
+                    self.cloud_contexts
+                        .iter()
+                        .any(|context| guard.is_production(context))
+                });
+                let labels: Vec<String> = self
+                    .cloud_contexts
+                    .iter()
+                    .map(CloudContext::label)
+                    .collect();
+                let color = if production {
+                    palette.error
+                } else {
+                    palette.header
+                };
+                header_block = header_block.title(Span::styled(
+                    labels.join(" · "),
+                    Style::default().fg(to_ratatui_color(color)),
+                ));
+            }
+            if let Some(ref loading) = self.loading {
+                header_block =
+                    header_block.title(Title::from(loading.as_str()).alignment(Alignment::Center));
+            }
+            if let Some(ref ports) = self.port_status {
+                header_block =
+                    header_block.title(Title::from(ports.as_str()).alignment(Alignment::Right));
+            }
+            // Each half of the bottom border holds one slot; the right shares
+            // its half with the widgets, the left with participants
+            let half = chunks[0].width.saturating_sub(4) as usize / 2;
+            let widgets_width = self
+                .status_widgets
+                .as_ref()
+                .map_or(0, |widgets| widgets.width() + 1);
+            if let Some(ref widgets) = self.status_widgets {
+                header_block = header_block.title(
+                    Title::from(widgets.as_str())
+                        .position(Position::Bottom)
+                        .alignment(Alignment::Right),
+                );
+            }
+            let right = self
+                .status_bar
+                .layout(StatusSlot::Right, half.saturating_sub(widgets_width + 2));
+            if !right.is_empty() {
+                header_block = header_block.title(
+                    Title::from(status_line(&right))
+                        .position(Position::Bottom)
+                        .alignment(Alignment::Right),
+                );
+            }
+            let left = self
+                .status_bar
+                .layout(StatusSlot::Left, half.saturating_sub(2));
+            let left = (!left.is_empty()).then(|| status_line(&left));
+            let left_width = left.as_ref().map_or(0, |line| line.width() + 1);
+            if let Some(left) = left {
+                header_block = header_block.title(Title::from(left).position(Position::Bottom));
+            }
+            if !self.participants.is_empty() {
+                let width = half.saturating_sub(left_width);
+                let labels: Vec<String> = self
+                    .participants
+                    .iter()
+                    .map(ParticipantBadge::label)
+                    .collect();
+                let shown = status_widgets::strip_fit(&labels, width);
+                let mut spans = Vec::new();
+                for (badge, label) in self.participants.iter().zip(labels).take(shown) {
+                    if !spans.is_empty() {
+                        spans.push(Span::raw(" "));
+                    }
+                    let mut style = Style::default().fg(to_ratatui_color(badge.color));
+                    if badge.state != PresenceState::Online {
+                        style = style.add_modifier(Modifier::DIM);
+                    }
+                    spans.push(Span::styled(label, style));
+                }
+                if shown < self.participants.len() {
+                    spans.push(Span::raw(format!(" +{}", self.participants.len() - shown)));
+                }
+                header_block =
+                    header_block.title(Title::from(Line::from(spans)).position(Position::Bottom));
+            }
+            let header = Paragraph::new(i18n::t("app.title"))
+                .block(header_block)
+                .style(Style::default().fg(to_ratatui_color(palette.header)));
             f.render_widget(header, chunks[0]);
 
             // Main content (output)
+            let visible_lines = chunks[1].height.saturating_sub(2) as usize;
             let output_items: Vec<ListItem> = self
                 .output_buffer
-                .iter()
-                .map(|line| ListItem::new(line.as_ref()))
+                .visible(visible_lines)
+                .map(ListItem::new)
                 .collect();
 
-            let output_list = List::new(output_items)
-                .block(Block::default().borders(Borders::ALL).title("Output"))
-                .style(Style::default().fg(to_ratatui_color(Color::White)));
-            f.render_widget(output_list, chunks[1]);
+            let (output_area, panel_area) = match self.panel {
+                Some(_) => {
+                    let split = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            [Constraint::Percentage(60), Constraint::Percentage(40)].as_ref(),
+                        )
+                        .split(chunks[1]);
+                    (split[0], Some(split[1]))
+                }
+                None => (chunks[1], None),
+            };
+
+            pager_width = output_area.width.saturating_sub(2) as usize;
+            let output_border = match self.bell_until {
+                Some(_) => Style::default()
+                    .fg(to_ratatui_color(palette.error))
+                    .add_modifier(Modifier::BOLD),
+                None => Style::default(),
+            };
+            if let Some(pager) = &self.pager {
+                let (title, keys) = match &self.diff {
+                    Some(diff) => (diff.title(), i18n::t("diff.keys")),
+                    None => (i18n::t("pager.title"), i18n::t("pager.keys")),
+                };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(output_border)
+                    .title(title)
+                    .title(Title::from(pager.status()).position(Position::Bottom))
+                    .title(
+                        Title::from(keys)
+                            .position(Position::Bottom)
+                            .alignment(Alignment::Right),
+                    );
+                pager_height = Some(block.inner(output_area).height as usize);
+                let mut paragraph = Paragraph::new(pager.visible())
+                    .block(block)
+                    .style(Style::default().fg(to_ratatui_color(palette.output)))
+                    .scroll((0, pager.left()));
+                if pager.wrap() {
+                    paragraph = paragraph.wrap(Wrap { trim: false });
+                }
+                f.render_widget(paragraph, output_area);
+            } else {
+                // Long blocks advertise the pager instead of opening it unasked
+                let block_len = self.output_buffer.block_len();
+                let title = if block_len > config.terminal.pager_threshold_lines {
+                    i18n::t_args("ui.output_long", &[("count", &block_len.to_string())])
+                } else {
+                    i18n::t("ui.output")
+                };
+                let output_list = List::new(output_items)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(output_border)
+                            .title(title),
+                    )
+                    .style(Style::default().fg(to_ratatui_color(palette.output)));
+                f.render_widget(output_list, output_area);
+            }
+
+            // Side panel
+            if let (Some(panel), Some(area)) = (&self.panel, panel_area) {
+                let (area, preview_area) = if panel.preview.is_empty() && panel.image.is_none() {
+                    (area, None)
+                } else {
+                    let split = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(
+                            [Constraint::Percentage(50), Constraint::Percentage(50)].as_ref(),
+                        )
+                        .split(area);
+                    (split[0], Some(split[1]))
+                };
+
+                let items: Vec<ListItem> = panel
+                    .items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let style = if i == self.panel_selected {
+                            Style::default().add_modifier(Modifier::REVERSED)
+                        } else {
+                            Style::default()
+                        };
+                        ListItem::new(item_line(item)).style(style)
+                    })
+                    .collect();
+                let mut block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(panel.title.as_str());
+                if let Some(footer) = &panel.footer {
+                    block = block.title(Title::from(footer.as_str()).position(Position::Bottom));
+                }
+                let list = List::new(items)
+                    .block(block)
+                    .style(Style::default().fg(to_ratatui_color(palette.output)));
+                f.render_widget(list, area);
+
+                if let Some(preview_area) = preview_area {
+                    let block = Block::default().borders(Borders::ALL);
+                    image_area = Some(block.inner(preview_area));
+                    let lines: Vec<ListItem> = panel
+                        .preview
+                        .iter()
+                        .map(|item| ListItem::new(item_line(item)))
+                        .collect();
+                    let preview = List::new(lines)
+                        .block(block)
+                        .style(Style::default().fg(to_ratatui_color(palette.output)));
+                    f.render_widget(preview, preview_area);
+                }
+            }
 
             // Input
-            let input = Paragraph::new(self.input_buffer.as_ref())
-                .block(Block::default().borders(Borders::ALL).title("Input"))
-                .style(Style::default().fg(to_ratatui_color(Color::Green)));
+            let input_title = match (&self.prompt, &self.confirming) {
+                (Some(prompt), _) => prompt.label.clone(),
+                (None, Some(command)) => i18n::t_args("ui.confirm_run", &[("command", command)]),
+                (None, None) => i18n::t("ui.input"),
+            };
+            let mut input_block = Block::default().borders(Borders::ALL).title(input_title);
+            // Lint diagnostics for the typed command win over hints about the last one
+            if let Some(first) = self.diagnostics.first() {
+                let color = match first.severity {
+                    Severity::Error => palette.error,
+                    Severity::Warning => palette.assistant,
+                };
+                let mut spans = vec![Span::styled(
+                    first.message.clone(),
+                    Style::default().fg(to_ratatui_color(color)),
+                )];
+                if self.diagnostics.len() > 1 {
+                    let more = i18n::t_args(
+                        "lint.more",
+                        &[("count", &(self.diagnostics.len() - 1).to_string())],
+                    );
+                    spans.push(Span::raw(format!(" {}", more)));
+                }
+                input_block =
+                    input_block.title(Title::from(Line::from(spans)).position(Position::Bottom));
+            } else if let Some(hint) = &self.hint {
+                input_block = input_block
+                    .title(Title::from(hint.message.as_str()).position(Position::Bottom));
+            }
+            let mut input_line = vec![Span::raw(self.input_buffer.as_str())];
+            if let Some(result) = &self.calc_result {
+                input_line.push(Span::styled(
+                    format!("  = {}", result),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+            }
+            let input = Paragraph::new(Line::from(input_line))
+                .block(input_block)
+                .style(Style::default().fg(to_ratatui_color(palette.input)));
             f.render_widget(input, chunks[2]);
 
             // AI Response (if any)
@@ -97,38 +843,227 @@ impl UI {
                     .block(
                         Block::default()
                             .borders(Borders::ALL)
-                            .title("🤖 AI Assistant"),
+                            .title(i18n::t("ui.ai_assistant")),
                     )
-                    .style(Style::default().fg(to_ratatui_color(Color::Yellow)));
+                    .style(Style::default().fg(to_ratatui_color(palette.assistant)));
                 f.render_widget(ai_widget, chunks[3]);
             }
+
+            // Toasts, newest on top, stacked down the top-right corner
+            let area = f.size();
+            let width = TOAST_WIDTH.min(area.width);
+            for (i, toast) in self.toasts.iter().rev().enumerate() {
+                let y = area.y + 1 + i as u16 * 3;
+                if y + 3 > area.bottom() {
+                    break;
+                }
+                let rect = Rect::new(area.right() - width, y, width, 3);
+                let color = to_ratatui_color(toast.color);
+                let widget = Paragraph::new(toast.text.as_str())
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(color)),
+                    )
+                    .style(Style::default().fg(color));
+                f.render_widget(Clear, rect);
+                f.render_widget(widget, rect);
+            }
         })?;
 
+        if let (Some(pager), Some(height)) = (self.pager.as_mut(), pager_height) {
+            self.needs_redraw |= pager.set_height(height);
+        }
+        drop(config);
+        if pager_width != self.pager_width {
+            self.pager_width = pager_width;
+            if self
+                .diff
+                .as_ref()
+                .is_some_and(|diff| diff.mode() == DiffMode::SideBySide)
+            {
+                self.render_diff();
+            }
+        }
+
+        if let (true, Some(area), Some(image)) = (
+            self.image_pending,
+            image_area,
+            self.panel.as_ref().and_then(|p| p.image.clone()),
+        ) {
+            let mut stdout = std::io::stdout();
+            write!(
+                stdout,
+                "\x1b[{};{}H{}",
+                area.y + 1,
+                area.x + 1,
+                inline_image_sequence(&image, area.width, area.height)
+            )?;
+            stdout.flush()?;
+            self.image_on_screen = true;
+        }
+        self.image_pending = false;
+
         Ok(())
     }
 
     pub async fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<(), WarpError> {
         use crossterm::event::{KeyCode, KeyModifiers};
 
+        self.needs_redraw = true;
+
+        if let Some(prompt) = &self.prompt {
+            let kind = prompt.kind;
+            match key_event.code {
+                KeyCode::Esc => {
+                    self.prompt = None;
+                    self.input_buffer.clear();
+                    self.cursor_position = 0;
+                }
+                KeyCode::Enter => {
+                    self.prompt = None;
+                    let text = std::mem::take(&mut self.input_buffer);
+                    self.cursor_position = 0;
+                    let _ = self.event_sender.send(UIEvent::PanelInput { kind, text });
+                }
+                KeyCode::Backspace if self.cursor_position > 0 => {
+                    self.cursor_position -= 1;
+                    self.input_buffer.remove(self.cursor_position);
+                }
+                KeyCode::Char(c) if !key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.input_buffer.insert(self.cursor_position, c);
+                    self.cursor_position += 1;
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if let Some(command) = self.confirming.take() {
+            if key_event.code == KeyCode::Char('y') {
+                self.hint = None;
+                self.submit_command(command);
+            }
+            return Ok(());
+        }
+
+        if let Some(pager) = self.pager.as_mut() {
+            if let (Some(diff), KeyCode::Char('s'), false) =
+                (self.diff.as_mut(), key_event.code, pager.typing())
+            {
+                diff.toggle_mode();
+                self.render_diff();
+                return Ok(());
+            }
+            if let PagerAction::Close = pager.handle_key(key_event) {
+                self.pager = None;
+                self.diff = None;
+            }
+            return Ok(());
+        }
+
+        // An open panel takes the keyboard until it is closed with Esc
+        if let Some(panel) = &self.panel {
+            let kind = panel.kind;
+            match key_event.code {
+                KeyCode::Esc => {
+                    self.panel = None;
+                    let _ = self.event_sender.send(UIEvent::PanelClosed(kind));
+                }
+                KeyCode::Up | KeyCode::Down => {
+                    let previous = self.panel_selected;
+                    if key_event.code == KeyCode::Up {
+                        self.panel_selected = self.panel_selected.saturating_sub(1);
+                    } else if self.panel_selected + 1 < panel.items.len() {
+                        self.panel_selected += 1;
+                    }
+                    if self.panel_selected != previous {
+                        let _ = self.event_sender.send(UIEvent::PanelSelection {
+                            kind,
+                            index: self.panel_selected,
+                        });
+                    }
+                }
+                KeyCode::Char(key) if key_event.modifiers == KeyModifiers::NONE => {
+                    let _ = self.event_sender.send(UIEvent::PanelAction {
+                        kind,
+                        key,
+                        index: self.panel_selected,
+                    });
+                }
+                // Panels see Enter as '\n'
+                KeyCode::Enter => {
+                    let _ = self.event_sender.send(UIEvent::PanelAction {
+                        kind,
+                        key: '\n',
+                        index: self.panel_selected,
+                    });
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
         match key_event {
             KeyEvent {
                 code: KeyCode::Enter,
+                modifiers: KeyModifiers::ALT,
                 ..
+            } => match self.hint.clone() {
+                Some(PromptHint {
+                    command: Some(command),
+                    confirm: false,
+                    ..
+                }) => self.run_command(command),
+                hint => self.confirming = hint.and_then(|hint| hint.command),
+            },
+
+            KeyEvent {
+                code: KeyCode::Tab, ..
             } => {
-                if !self.input_buffer.trim().is_empty() {
-                    let command = self.input_buffer.clone();
-                    self.output_buffer.push(format!("❯ {}", command));
-
-                    // Check for AI commands
-                    if command.starts_with("ai ") {
-                        let query = command[3..].to_string();
-                        let _ = self.event_sender.send(UIEvent::AIQuery(query));
-                    } else {
-                        let _ = self.event_sender.send(UIEvent::CommandExecuted(command));
-                    }
+                if let Some(result) = self.calc_result.take() {
+                    self.replace_input(&result);
+                } else if self.editing_script() {
+                    let _ = self.event_sender.send(UIEvent::ScriptCompletion {
+                        text: self.input_buffer.clone(),
+                        offset: self.cursor_position,
+                    });
+                }
+            }
 
-                    self.input_buffer.clear();
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => {
+                // A `= …` line is answered here; the shell never sees it
+                if let Some(result) = self.calc_result.take() {
+                    let expression = std::mem::take(&mut self.input_buffer);
                     self.cursor_position = 0;
+                    self.push_block(expression.trim(), vec![result]);
+                } else if !self.input_buffer.trim().is_empty() {
+                    let command = std::mem::take(&mut self.input_buffer);
+                    self.cursor_position = 0;
+                    self.hint = None;
+                    self.diagnostics.clear();
+                    // A pasted block link jumps to the block instead of running
+                    let warning = self
+                        .cloud_guard
+                        .as_ref()
+                        .and_then(|guard| guard.check(&self.cloud_contexts, &command));
+                    match (permalinks::parse_block_link(&command), warning) {
+                        (Some(id), _) => self.jump_to_block(id),
+                        // Destructive while in production: y runs it, and
+                        // the hint keeps it on Alt+Enter after a no
+                        (None, Some(message)) => {
+                            self.hint = Some(PromptHint {
+                                message,
+                                command: Some(command.clone()),
+                                confirm: true,
+                            });
+                            self.confirming = Some(command);
+                        }
+                        (None, None) => self.submit_command(command),
+                    }
                 }
             }
 
@@ -139,6 +1074,7 @@ impl UI {
                 if self.cursor_position > 0 {
                     self.cursor_position -= 1;
                     self.input_buffer.remove(self.cursor_position);
+                    self.input_changed();
                 }
             }
 
@@ -149,6 +1085,7 @@ impl UI {
             } => {
                 self.input_buffer.insert(self.cursor_position, c);
                 self.cursor_position += 1;
+                self.input_changed();
             }
 
             _ => {}
@@ -157,30 +1094,544 @@ impl UI {
         Ok(())
     }
 
-    pub async fn append_output(&mut self, output: String) -> Result<(), WarpError> {
-        for line in output.lines() {
-            self.output_buffer.push(line.to_string());
+    fn submit_command(&mut self, command: String) {
+        self.output_buffer.start_block();
+        self.output_buffer.push_line(format!("❯ {}", command));
+
+        // Check for AI commands
+        if let Some(query) = command.strip_prefix("ai ") {
+            let _ = self.event_sender.send(UIEvent::AIQuery(query.to_string()));
+        } else {
+            let _ = self.event_sender.send(UIEvent::CommandExecuted(command));
+        }
+    }
+
+    fn input_changed(&mut self) {
+        self.calc_result = calc::prompt_expression(&self.input_buffer)
+            .filter(|_| self.prompt.is_none())
+            .and_then(|expression| calc::evaluate(expression).ok())
+            .map(|value| value.to_string());
+        match self.calc_result {
+            Some(_) => self.diagnostics.clear(),
+            // Diagnostics come back from the server when it has looked
+            None if self.editing_script() => {
+                let _ = self
+                    .event_sender
+                    .send(UIEvent::ScriptEdited(self.input_buffer.clone()));
+            }
+            None => self.lint_input(),
         }
+    }
+
+    fn lint_input(&mut self) {
+        let Some(linter) = self.linter.as_ref().filter(|_| self.prompt.is_none()) else {
+            return;
+        };
+        let cwd = std::env::current_dir().unwrap_or_default();
+        self.diagnostics = linter.lint(&self.input_buffer, &cwd);
+    }
+
+    // Runs a command on the user's behalf, echoed like a typed one
+    pub fn run_command(&mut self, command: String) {
+        self.hint = None;
+        self.confirming = None;
+        self.submit_command(command);
+        self.needs_redraw = true;
+    }
+
+    pub fn set_output_truncation(&mut self, limits: Option<TruncationLimits>) {
+        self.output_buffer.set_truncation(limits);
+    }
+
+    // The active pane's tuning; a smaller scrollback drops the oldest lines
+    pub fn set_pane_limits(
+        &mut self,
+        scrollback_lines: usize,
+        truncation: Option<TruncationLimits>,
+        frame_interval: Duration,
+    ) {
+        self.output_buffer.set_max_lines(scrollback_lines);
+        self.output_buffer.set_truncation(truncation);
+        self.frame_interval = frame_interval;
+        self.needs_redraw = true;
+    }
+
+    // The file holding lines a truncated block didn't keep in memory
+    pub fn hidden_output(&mut self) -> Option<PathBuf> {
+        self.output_buffer.hidden_output()
+    }
+
+    // For a view that takes the whole screen, like the marketplace. The
+    // terminal view is drawn again on the first render after it closes.
+    pub fn draw_fullscreen(&mut self, draw: impl FnOnce(&mut Frame<'_>)) -> Result<(), WarpError> {
+        self.terminal.draw(draw)?;
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    // Repaints everything, e.g. after a program drew over the screen
+    pub fn force_redraw(&mut self) -> Result<(), WarpError> {
+        self.terminal.clear()?;
+        self.needs_redraw = true;
+        Ok(())
+    }
 
-        // Keep only last 1000 lines
-        if self.output_buffer.len() > 1000 {
-            self.output_buffer.drain(0..self.output_buffer.len() - 1000);
+    pub fn set_hint(&mut self, hint: Option<PromptHint>) {
+        self.hint = hint;
+        self.confirming = None;
+        self.needs_redraw = true;
+    }
+
+    pub async fn append_output(&mut self, output: String) -> Result<(), WarpError> {
+        self.output_buffer.push_chunk(&output);
+
+        if let Some(accessibility) = &self.accessibility {
+            accessibility
+                .note_output(output.matches('\n').count())
+                .await?;
         }
 
         Ok(())
     }
 
     pub async fn show_ai_response(&mut self, response: String) -> Result<(), WarpError> {
+        if let Some(accessibility) = &self.accessibility {
+            accessibility
+                .announce(
+                    i18n::t_args("a11y.ai_response", &[("response", &response)]),
+                    AnnouncementPriority::Polite,
+                )
+                .await?;
+        }
         self.ai_response = Some(response);
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    pub async fn show_error(&mut self, error: &WarpError) -> Result<(), WarpError> {
+        self.show_error_lines(error.render()).await
+    }
+
+    pub async fn show_error_lines(&mut self, lines: Vec<String>) -> Result<(), WarpError> {
+        if let (Some(accessibility), Some(first)) = (&self.accessibility, lines.first()) {
+            accessibility
+                .announce(first.clone(), AnnouncementPriority::Assertive)
+                .await?;
+        }
+        for line in lines {
+            self.output_buffer.push_line(line);
+        }
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    pub fn set_loading(&mut self, status: Option<String>) {
+        self.loading = status;
+        self.needs_redraw = true;
+    }
+
+    pub fn set_shell_label(&mut self, label: Option<String>) {
+        self.shell_label = label;
+        self.needs_redraw = true;
+    }
+
+    pub fn set_accent(&mut self, accent: Option<crossterm::style::Color>) {
+        self.accent = accent;
+        self.needs_redraw = true;
+    }
+
+    pub fn set_encoding(&mut self, encoding: OutputEncoding) {
+        self.encoding = encoding;
+        self.needs_redraw = true;
+    }
+
+    pub fn set_cloud_contexts(&mut self, contexts: Vec<CloudContext>) {
+        if self.cloud_contexts != contexts {
+            self.cloud_contexts = contexts;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn set_port_status(&mut self, status: Option<String>) {
+        if self.port_status != status {
+            self.port_status = status;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn set_tabs(&mut self, tabs: Vec<TabBadge>) {
+        if self.tabs != tabs {
+            self.tabs = tabs;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn set_status_bar(&mut self, bar: StatusBar) {
+        self.status_bar = bar;
+        self.needs_redraw = true;
+    }
+
+    pub fn set_status_segment(&mut self, id: usize, segment: Option<Segment>) {
+        if self.status_bar.update(id, segment) {
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn status_bar(&self) -> &StatusBar {
+        &self.status_bar
+    }
+
+    pub fn set_status_widgets(&mut self, widgets: Option<String>) {
+        if self.status_widgets != widgets {
+            self.status_widgets = widgets;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn set_participants(&mut self, participants: Vec<ParticipantBadge>) {
+        if self.participants != participants {
+            self.participants = participants;
+            self.needs_redraw = true;
+        }
+    }
+
+    // Flashes the output border; with reduced motion the bell is announced
+    // instead
+    pub async fn visual_bell(&mut self, duration: Duration) -> Result<(), WarpError> {
+        if let Some(accessibility) = self.accessibility.as_ref().filter(|a| a.reduced_motion()) {
+            return accessibility
+                .announce(i18n::t("bell.rang"), AnnouncementPriority::Polite)
+                .await;
+        }
+        self.bell_until = Some(Instant::now() + duration);
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    pub fn show_toast(&mut self, text: String, color: crossterm::style::Color, duration: Duration) {
+        self.toasts.push(Toast {
+            text,
+            color,
+            expires: Instant::now() + duration,
+        });
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.needs_redraw = true;
+    }
+
+    // Scrollback and current-block bytes, for the memory tracker
+    pub fn memory_usage(&self) -> (u64, u64) {
+        self.output_buffer.memory_bytes()
+    }
+
+    pub fn push_lines(&mut self, lines: Vec<String>) {
+        for line in lines {
+            self.output_buffer.push_line(line);
+        }
+        self.needs_redraw = true;
+    }
+
+    // A block for a command the app ran on the user's behalf, so it can be
+    // linked to like a typed one
+    pub fn push_block(&mut self, command: &str, lines: Vec<String>) -> String {
+        let id = self.output_buffer.start_block();
+        self.output_buffer.push_line(format!("❯ {}", command));
+        for line in lines {
+            self.output_buffer.push_line(line);
+        }
+        self.needs_redraw = true;
+        id
+    }
+
+    // Refreshing a panel keeps the selection; switching panels resets it
+    pub fn show_panel(&mut self, view: PanelView) {
+        match &self.panel {
+            Some(current) if current.kind == view.kind => {
+                self.panel_selected = self.panel_selected.min(view.items.len().saturating_sub(1));
+            }
+            _ => self.panel_selected = 0,
+        }
+        let current_image = self.panel.as_ref().and_then(|p| p.image.as_ref());
+        self.image_pending = match (&view.image, current_image) {
+            (Some(new), Some(current)) => !Arc::ptr_eq(new, current) || !self.image_on_screen,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        self.panel = Some(view);
+        self.needs_redraw = true;
+    }
+
+    pub fn close_panel(&mut self, kind: PanelKind) {
+        if self.panel.as_ref().map(|p| p.kind) == Some(kind) {
+            self.panel = None;
+            self.needs_redraw = true;
+        }
+        if self.prompt.as_ref().map(|p| p.kind) == Some(kind) {
+            self.prompt = None;
+        }
+    }
+
+    // Pages through the current command's output, or the whole scrollback
+    // when nothing has run yet. Closing returns to the live output.
+    pub fn toggle_pager(&mut self) {
+        self.diff = None;
+        if self.pager.take().is_none() {
+            let pager = if self.output_buffer.block_len() > 0 {
+                Pager::new(self.output_buffer.current_block())
+            } else {
+                Pager::new(self.output_buffer.lines())
+            };
+            self.pager = Some(pager);
+        }
+        self.needs_redraw = true;
+    }
+
+    // Opens the scrollback in the pager with the block at the top and
+    // highlighted
+    pub fn jump_to_block(&mut self, id: &str) {
+        match self.output_buffer.block_range(id) {
+            Some(range) => {
+                let mut pager = Pager::new(self.output_buffer.lines());
+                pager.mark(range);
+                self.pager = Some(pager);
+                self.diff = None;
+            }
+            None => self.output_buffer.push_line(i18n::t_args(
+                "permalink.missing",
+                &[("link", &permalinks::block_link(id))],
+            )),
+        }
+        self.needs_redraw = true;
+    }
+
+    pub fn current_block_link(&self) -> Option<String> {
+        self.output_buffer
+            .current_block_id()
+            .map(permalinks::block_link)
+    }
+
+    // Output of the last command, or the whole scrollback before any command
+    pub fn current_block(&self) -> Vec<String> {
+        if self.output_buffer.block_len() > 0 {
+            self.output_buffer
+                .current_block()
+                .map(Cow::into_owned)
+                .collect()
+        } else {
+            self.output_buffer.lines().map(Cow::into_owned).collect()
+        }
+    }
+
+    // Output of the command before the one just submitted
+    pub fn previous_block(&self) -> Vec<String> {
+        match self.output_buffer.previous_block_id() {
+            Some(id) => self.block_lines(id),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn current_block_id(&self) -> Option<String> {
+        self.output_buffer.current_block_id().map(str::to_string)
+    }
+
+    // Empty once the block has scrolled out
+    pub fn block_lines(&self, id: &str) -> Vec<String> {
+        let Some(range) = self.output_buffer.block_range(id) else {
+            return Vec::new();
+        };
+        self.output_buffer
+            .lines()
+            .skip(range.start)
+            .take(range.len())
+            .map(Cow::into_owned)
+            .collect()
+    }
+
+    // Jumps to the block when it's still in the scrollback, otherwise pages
+    // through the copy kept elsewhere (e.g. from an earlier session)
+    pub fn open_block(&mut self, id: &str, command: &str, output: &str) {
+        if self.output_buffer.block_range(id).is_some() {
+            self.jump_to_block(id);
+            return;
+        }
+        let first = format!("❯ {}", command);
+        self.pager = Some(Pager::new(
+            std::iter::once(first.as_str()).chain(output.lines()),
+        ));
+        self.diff = None;
+        self.needs_redraw = true;
+    }
+
+    // Pages through lines that aren't in the scrollback, e.g. a replayed one
+    pub fn page_lines(&mut self, lines: &[String]) {
+        self.pager = Some(Pager::new(lines.iter().map(String::as_str)));
+        self.diff = None;
+        self.needs_redraw = true;
+    }
+
+    // Opens the diff in the pager, unified first; s switches to side by side
+    pub fn show_diff(&mut self, diff: DiffView) {
+        self.pager = None;
+        self.diff = Some(diff);
+        self.render_diff();
+    }
+
+    // Keeps the hunk at the top when the diff is laid out again
+    fn render_diff(&mut self) {
+        let Some(diff) = &self.diff else {
+            return;
+        };
+        let section = self.pager.as_ref().and_then(Pager::section).unwrap_or(0);
+        let rendered = diff.render(self.pager_width);
+        let mut pager = Pager::new(rendered.lines.iter().map(String::as_str));
+        pager.set_sections(rendered.hunks);
+        pager.show_section(section);
+        self.pager = Some(pager);
+        self.needs_redraw = true;
+    }
+
+    // `back` blocks before the last one; 0 is the last
+    pub fn recent_block_id(&self, back: usize) -> Option<String> {
+        self.output_buffer.recent_block_id(back).map(str::to_string)
+    }
+
+    // Pages through only these blocks, e.g. those with one tag. A block that
+    // has left the scrollback shows as just its command.
+    pub fn show_blocks(&mut self, blocks: &[(String, String)]) {
+        let mut lines = Vec::new();
+        for (id, command) in blocks {
+            match self.block_lines(id) {
+                block if block.is_empty() => lines.push(format!("❯ {}", command)),
+                block => lines.extend(block),
+            }
+        }
+        self.page_lines(&lines);
+    }
+
+    pub fn input_text(&self) -> &str {
+        &self.input_buffer
+    }
+
+    pub fn replace_input(&mut self, text: &str) {
+        self.input_buffer.clear();
+        self.cursor_position = 0;
+        self.insert_text(text);
+    }
+
+    // A panel prompt only takes one line
+    pub fn insert_text(&mut self, text: &str) {
+        let text = match self.prompt {
+            Some(_) => text.replace('\n', " "),
+            None => text.to_string(),
+        };
+        self.input_buffer.insert_str(self.cursor_position, &text);
+        self.cursor_position += text.len();
+        self.input_changed();
+        self.needs_redraw = true;
+    }
+
+    pub fn set_panel_footer(&mut self, kind: PanelKind, footer: Option<String>) {
+        if let Some(panel) = self.panel.as_mut().filter(|p| p.kind == kind) {
+            panel.footer = footer;
+            self.needs_redraw = true;
+        }
+    }
+
+    // Takes over the input line until Enter (sends PanelInput) or Esc.
+    // Anything the user had typed for the shell is discarded.
+    pub fn begin_prompt(&mut self, kind: PanelKind, label: String) {
+        self.input_buffer.clear();
+        self.cursor_position = 0;
+        self.diagnostics.clear();
+        self.calc_result = None;
+        self.prompt = Some(PanelPrompt { kind, label });
+        self.needs_redraw = true;
+    }
+
+    pub fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub fn panel_kind(&self) -> Option<PanelKind> {
+        self.panel.as_ref().map(|p| p.kind)
+    }
+
+    pub async fn set_ssh_status(&mut self, state: SshConnectionState) -> Result<(), WarpError> {
+        if let Some(accessibility) = &self.accessibility {
+            accessibility
+                .announce(
+                    i18n::t_args("ui.ssh_status", &[("status", &state.indicator())]),
+                    AnnouncementPriority::Assertive,
+                )
+                .await?;
+        }
+        self.ssh_status = match state {
+            SshConnectionState::Disconnected => None,
+            other => Some(other.indicator()),
+        };
+        self.needs_redraw = true;
         Ok(())
     }
 
     pub async fn resize(&mut self, width: u16, height: u16) -> Result<(), WarpError> {
+        self.needs_redraw = true;
         let _ = self.event_sender.send(UIEvent::Resize(width, height));
         Ok(())
     }
 }
 
+// A slot's segments, padded like the widgets
+fn status_line(segments: &[&Segment]) -> Line<'static> {
+    let mut spans = vec![Span::raw(" ")];
+    for (index, segment) in segments.iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::raw(status_bar::SEPARATOR));
+        }
+        spans.push(match segment.color {
+            Some(color) => Span::styled(
+                segment.text.clone(),
+                Style::default().fg(to_ratatui_color(color)),
+            ),
+            None => Span::raw(segment.text.clone()),
+        });
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
+}
+
+fn item_line(item: &PanelItem) -> Line<'_> {
+    let spans: Vec<Span> = item
+        .segments
+        .iter()
+        .map(|(text, color)| match color {
+            Some(color) => {
+                Span::styled(text.as_str(), Style::default().fg(to_ratatui_color(*color)))
+            }
+            None => Span::raw(text.as_str()),
+        })
+        .collect();
+    Line::from(spans)
+}
+
+// iTerm2's inline image protocol, also implemented by WezTerm. Other
+// terminals would print the base64 payload as text.
+pub fn inline_images_supported() -> bool {
+    let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    let lc_terminal = std::env::var("LC_TERMINAL").unwrap_or_default();
+    matches!(program.as_str(), "iTerm.app" | "WezTerm") || lc_terminal == "iTerm2"
+}
+
+fn inline_image_sequence(data: &[u8], width: u16, height: u16) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        data.len(),
+        width,
+        height,
+        base64::engine::general_purpose::STANDARD.encode(data)
+    )
+}
+
 // Convert crossterm colors to ratatui colors
 fn to_ratatui_color(color: crossterm::style::Color) -> ratatui::style::Color {
     match color {
@@ -0,0 +1,109 @@
+// Decodes a byte stream that arrives in arbitrary pieces. A multi-byte
+// character cut by a read boundary is held back until the rest arrives
+// instead of becoming two replacement characters; bytes that can never be
+// valid are replaced the same way String::from_utf8_lossy does.
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut out = String::with_capacity(self.pending.len());
+        let mut rest = &self.pending[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    // Checked by from_utf8 above
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // Truncated sequence at the end: wait for more
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let kept = rest.len();
+        let start = self.pending.len() - kept;
+        self.pending.drain(..start);
+        out
+    }
+
+    // Whatever is still held back, for when the stream has ended
+    pub fn finish(&mut self) -> String {
+        let out = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_split_character_is_joined() {
+        let bytes = "né😀".as_bytes();
+        let mut decoder = Utf8Decoder::new();
+        let mut out = String::new();
+        for byte in bytes {
+            out.push_str(&decoder.decode(std::slice::from_ref(byte)));
+        }
+        assert_eq!(out, "né😀");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn test_invalid_bytes_are_replaced() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(b"a\xffb\xc3"), "a\u{fffd}b");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+
+    proptest! {
+        // However the stream is cut, the text matches decoding it whole
+        #[test]
+        fn prop_chunking_matches_lossy_decoding(
+            bytes in proptest::collection::vec(any::<u8>(), 0..512),
+            cuts in proptest::collection::vec(any::<prop::sample::Index>(), 0..8),
+        ) {
+            let mut cuts: Vec<usize> = cuts.iter().map(|cut| cut.index(bytes.len() + 1)).collect();
+            cuts.push(0);
+            cuts.push(bytes.len());
+            cuts.sort_unstable();
+            let mut decoder = Utf8Decoder::new();
+            let mut out = String::new();
+            for window in cuts.windows(2) {
+                out.push_str(&decoder.decode(&bytes[window[0]..window[1]]));
+            }
+            out.push_str(&decoder.finish());
+            prop_assert_eq!(out, String::from_utf8_lossy(&bytes).into_owned());
+        }
+
+        #[test]
+        fn prop_valid_text_round_trips(text in "\\PC*", size in 1usize..8) {
+            let mut decoder = Utf8Decoder::new();
+            let out: String = text.as_bytes().chunks(size).map(|chunk| decoder.decode(chunk)).collect();
+            prop_assert_eq!(out, text);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::WarpError;
+use crate::widgets::{self, GaugeLevel, LogStream};
+
+use super::WidgetType;
+
+// Name of the custom widget type that renders a log tail
+pub const LOG_STREAM: &str = "log_stream";
+
+// Fractions at which a gauge is drawn as warning and critical
+const GAUGE_WARNING: f64 = 0.75;
+const GAUGE_CRITICAL: f64 = 0.9;
+
+// What a terminal widget is drawn from
+#[derive(Debug, Clone)]
+pub enum WidgetData {
+    Series(Vec<f64>),
+    Value { value: f64, max: f64 },
+    Lines(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedWidget {
+    pub lines: Vec<String>,
+    pub level: Option<GaugeLevel>,
+}
+
+// Draws one kind of widget as text, `width` columns by `height` rows
+pub trait WidgetRenderer: Send + Sync {
+    fn render(
+        &self,
+        data: &WidgetData,
+        width: usize,
+        height: usize,
+    ) -> Result<RenderedWidget, WarpError>;
+}
+
+pub struct SparklineRenderer;
+
+impl WidgetRenderer for SparklineRenderer {
+    fn render(
+        &self,
+        data: &WidgetData,
+        width: usize,
+        _height: usize,
+    ) -> Result<RenderedWidget, WarpError> {
+        let WidgetData::Series(values) = data else {
+            return Err(WarpError::Validation(
+                "A sparkline needs a series of values".to_string(),
+            ));
+        };
+        Ok(RenderedWidget {
+            lines: vec![widgets::sparkline(values, width, None)],
+            level: None,
+        })
+    }
+}
+
+pub struct GaugeRenderer;
+
+impl WidgetRenderer for GaugeRenderer {
+    fn render(
+        &self,
+        data: &WidgetData,
+        width: usize,
+        _height: usize,
+    ) -> Result<RenderedWidget, WarpError> {
+        let WidgetData::Value { value, max } = data else {
+            return Err(WarpError::Validation(
+                "A gauge needs a value and a maximum".to_string(),
+            ));
+        };
+        let fraction = if *max > 0.0 { value / max } else { 0.0 };
+        // Leave room for the percentage after the bar
+        let bar = width.saturating_sub(5).max(1);
+        Ok(RenderedWidget {
+            lines: vec![widgets::gauge(fraction, bar)],
+            level: Some(GaugeLevel::of(fraction, GAUGE_WARNING, GAUGE_CRITICAL)),
+        })
+    }
+}
+
+pub struct LogStreamRenderer;
+
+impl WidgetRenderer for LogStreamRenderer {
+    fn render(
+        &self,
+        data: &WidgetData,
+        width: usize,
+        height: usize,
+    ) -> Result<RenderedWidget, WarpError> {
+        let WidgetData::Lines(lines) = data else {
+            return Err(WarpError::Validation(
+                "A log stream needs lines of text".to_string(),
+            ));
+        };
+        let mut stream = LogStream::new(height.max(1));
+        for line in lines {
+            stream.push(line);
+            stream.push("\n");
+        }
+        let lines = stream
+            .tail(height)
+            .into_iter()
+            .map(|line| line.chars().take(width).collect())
+            .collect();
+        Ok(RenderedWidget { lines, level: None })
+    }
+}
+
+// Text renderers for the widget types that make sense in a terminal.
+// Dashboards and the status bar look widgets up here by type.
+pub struct ChartBuilder {
+    renderers: HashMap<String, Arc<dyn WidgetRenderer>>,
+}
+
+impl ChartBuilder {
+    pub async fn new() -> Result<Self, WarpError> {
+        let mut builder = Self {
+            renderers: HashMap::new(),
+        };
+        builder.register(&WidgetType::Sparkline, Arc::new(SparklineRenderer));
+        builder.register(&WidgetType::Gauge, Arc::new(GaugeRenderer));
+        builder.register(
+            &WidgetType::Custom(LOG_STREAM.to_string()),
+            Arc::new(LogStreamRenderer),
+        );
+        Ok(builder)
+    }
+
+    pub fn register(&mut self, widget_type: &WidgetType, renderer: Arc<dyn WidgetRenderer>) {
+        self.renderers.insert(key(widget_type), renderer);
+    }
+
+    pub fn supports(&self, widget_type: &WidgetType) -> bool {
+        self.renderers.contains_key(&key(widget_type))
+    }
+
+    pub fn render_text(
+        &self,
+        widget_type: &WidgetType,
+        data: &WidgetData,
+        width: usize,
+        height: usize,
+    ) -> Result<RenderedWidget, WarpError> {
+        let renderer = self.renderers.get(&key(widget_type)).ok_or_else(|| {
+            WarpError::Validation(format!(
+                "No terminal renderer for {:?} widgets",
+                widget_type
+            ))
+        })?;
+        renderer.render(data, width, height)
+    }
+}
+
+fn key(widget_type: &WidgetType) -> String {
+    match widget_type {
+        WidgetType::Custom(name) => name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_registered_widgets() {
+        let builder = ChartBuilder::new().await.unwrap();
+        let gauge = builder
+            .render_text(
+                &WidgetType::Gauge,
+                &WidgetData::Value {
+                    value: 8.0,
+                    max: 10.0,
+                },
+                9,
+                1,
+            )
+            .unwrap();
+        assert_eq!(gauge.lines, vec!["███░ 80%"]);
+        assert_eq!(gauge.level, Some(GaugeLevel::Warning));
+
+        let logs = WidgetData::Lines(vec![
+            "one".to_string(),
+            "two".to_string(),
+            "three".to_string(),
+        ]);
+        let tail = builder
+            .render_text(&WidgetType::Custom(LOG_STREAM.to_string()), &logs, 3, 2)
+            .unwrap();
+        assert_eq!(tail.lines, vec!["two", "thr"]);
+
+        assert!(builder
+            .render_text(&WidgetType::Sparkline, &logs, 10, 1)
+            .is_err());
+        assert!(!builder.supports(&WidgetType::Sankey));
+    }
+}
@@ -1,17 +1,15 @@
+use crate::error::WarpError;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use serde::{Deserialize, Serialize};
-use crate::error::WarpError;
 
-pub mod dashboard_engine;
 pub mod chart_builder;
-pub mod data_processor;
-pub mod interactive_widgets;
-pub mod real_time_updates;
-pub mod export_renderer;
-pub mod theme_manager;
-pub mod layout_manager;
+pub mod sharing;
+
+// Cell size widgets are drawn at in HTML renderings
+const HTML_WIDGET_COLUMNS: usize = 40;
+const HTML_WIDGET_ROWS: usize = 8;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dashboard {
@@ -167,10 +165,21 @@ pub struct ConnectionConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AuthenticationConfig {
     None,
-    ApiKey { key: String },
-    Bearer { token: String },
-    Basic { username: String, password: String },
-    OAuth { client_id: String, client_secret: String, token_url: String },
+    ApiKey {
+        key: String,
+    },
+    Bearer {
+        token: String,
+    },
+    Basic {
+        username: String,
+        password: String,
+    },
+    OAuth {
+        client_id: String,
+        client_secret: String,
+        token_url: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -658,34 +667,30 @@ pub enum ExportFormat {
 
 pub struct VisualizationManager {
     dashboards: Arc<Mutex<HashMap<String, Dashboard>>>,
-    dashboard_engine: Arc<dashboard_engine::DashboardEngine>,
     chart_builder: Arc<chart_builder::ChartBuilder>,
-    data_processor: Arc<data_processor::DataProcessor>,
-    interactive_widgets: Arc<interactive_widgets::InteractiveWidgetManager>,
-    real_time_updates: Arc<real_time_updates::RealTimeUpdateManager>,
-    export_renderer: Arc<export_renderer::ExportRenderer>,
-    theme_manager: Arc<theme_manager::ThemeManager>,
-    layout_manager: Arc<layout_manager::LayoutManager>,
+    // Latest data pushed for each widget, by widget id
+    widget_data: Arc<Mutex<HashMap<String, chart_builder::WidgetData>>>,
+    sharing: Arc<sharing::ShareManager>,
 }
 
 impl VisualizationManager {
     pub async fn new() -> Result<Self, WarpError> {
         Ok(Self {
             dashboards: Arc::new(Mutex::new(HashMap::new())),
-            dashboard_engine: Arc::new(dashboard_engine::DashboardEngine::new().await?),
             chart_builder: Arc::new(chart_builder::ChartBuilder::new().await?),
-            data_processor: Arc::new(data_processor::DataProcessor::new().await?),
-            interactive_widgets: Arc::new(interactive_widgets::InteractiveWidgetManager::new().await?),
-            real_time_updates: Arc::new(real_time_updates::RealTimeUpdateManager::new().await?),
-            export_renderer: Arc::new(export_renderer::ExportRenderer::new().await?),
-            theme_manager: Arc::new(theme_manager::ThemeManager::new().await?),
-            layout_manager: Arc::new(layout_manager::LayoutManager::new().await?),
+            widget_data: Arc::new(Mutex::new(HashMap::new())),
+            sharing: Arc::new(sharing::ShareManager::new()),
         })
     }
 
-    pub async fn create_dashboard(&self, owner_id: &str, name: &str, description: &str) -> Result<String, WarpError> {
+    pub async fn create_dashboard(
+        &self,
+        owner_id: &str,
+        name: &str,
+        description: &str,
+    ) -> Result<String, WarpError> {
         let dashboard_id = uuid::Uuid::new_v4().to_string();
-        
+
         let dashboard = Dashboard {
             id: dashboard_id.clone(),
             name: name.to_string(),
@@ -702,16 +707,37 @@ impl VisualizationManager {
                     min_widget_height: 150,
                 },
                 responsive_breakpoints: HashMap::from([
-                    ("mobile".to_string(), BreakpointConfig { min_width: 0, columns: 1, widget_scaling: 0.8 }),
-                    ("tablet".to_string(), BreakpointConfig { min_width: 768, columns: 6, widget_scaling: 0.9 }),
-                    ("desktop".to_string(), BreakpointConfig { min_width: 1024, columns: 12, widget_scaling: 1.0 }),
+                    (
+                        "mobile".to_string(),
+                        BreakpointConfig {
+                            min_width: 0,
+                            columns: 1,
+                            widget_scaling: 0.8,
+                        },
+                    ),
+                    (
+                        "tablet".to_string(),
+                        BreakpointConfig {
+                            min_width: 768,
+                            columns: 6,
+                            widget_scaling: 0.9,
+                        },
+                    ),
+                    (
+                        "desktop".to_string(),
+                        BreakpointConfig {
+                            min_width: 1024,
+                            columns: 12,
+                            widget_scaling: 1.0,
+                        },
+                    ),
                 ]),
                 auto_arrange: false,
             },
             widgets: Vec::new(),
             data_sources: Vec::new(),
             filters: Vec::new(),
-            theme: self.theme_manager.get_default_theme().await?,
+            theme: DashboardTheme::default(),
             settings: DashboardSettings::default(),
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
@@ -725,9 +751,16 @@ impl VisualizationManager {
         Ok(dashboard_id)
     }
 
-    pub async fn add_widget(&self, dashboard_id: &str, widget_type: WidgetType, title: &str, position: WidgetPosition, size: WidgetSize) -> Result<String, WarpError> {
+    pub async fn add_widget(
+        &self,
+        dashboard_id: &str,
+        widget_type: WidgetType,
+        title: &str,
+        position: WidgetPosition,
+        size: WidgetSize,
+    ) -> Result<String, WarpError> {
         let widget_id = uuid::Uuid::new_v4().to_string();
-        
+
         let widget = Widget {
             id: widget_id.clone(),
             widget_type: widget_type.clone(),
@@ -766,13 +799,19 @@ impl VisualizationManager {
             dashboard.updated_at = chrono::Utc::now();
             Ok(widget_id)
         } else {
-            Err(WarpError::ConfigError("Dashboard not found".to_string()))
+            Err(WarpError::not_found("dashboard", dashboard_id))
         }
     }
 
-    pub async fn add_data_source(&self, dashboard_id: &str, name: &str, source_type: DataSourceType, connection_config: ConnectionConfig) -> Result<String, WarpError> {
+    pub async fn add_data_source(
+        &self,
+        dashboard_id: &str,
+        name: &str,
+        source_type: DataSourceType,
+        connection_config: ConnectionConfig,
+    ) -> Result<String, WarpError> {
         let data_source_id = uuid::Uuid::new_v4().to_string();
-        
+
         let data_source = DataSource {
             id: data_source_id.clone(),
             name: name.to_string(),
@@ -790,42 +829,239 @@ impl VisualizationManager {
             dashboard.updated_at = chrono::Utc::now();
             Ok(data_source_id)
         } else {
-            Err(WarpError::ConfigError("Dashboard not found".to_string()))
+            Err(WarpError::not_found("dashboard", dashboard_id))
         }
     }
 
-    pub async fn render_dashboard(&self, dashboard_id: &str, format: RenderFormat) -> Result<RenderResult, WarpError> {
+    // Only HTML is rendered; the other formats need a browser-side renderer
+    pub async fn render_dashboard(
+        &self,
+        dashboard_id: &str,
+        format: RenderFormat,
+    ) -> Result<RenderResult, WarpError> {
+        if !matches!(format, RenderFormat::HTML) {
+            return Err(WarpError::Validation(format!(
+                "Dashboards can't be rendered as {:?}",
+                format
+            )));
+        }
         let dashboards = self.dashboards.lock().await;
-        if let Some(dashboard) = dashboards.get(dashboard_id) {
-            self.dashboard_engine.render_dashboard(dashboard, format).await
-        } else {
-            Err(WarpError::ConfigError("Dashboard not found".to_string()))
+        let dashboard = dashboards
+            .get(dashboard_id)
+            .ok_or_else(|| WarpError::not_found("dashboard", dashboard_id))?;
+        Ok(self.render_html(dashboard).await)
+    }
+
+    // Whatever collects the data pushes it here; it's drawn on the next render
+    pub async fn update_widget_data(
+        &self,
+        dashboard_id: &str,
+        widget_id: &str,
+        data: chart_builder::WidgetData,
+    ) -> Result<(), WarpError> {
+        let dashboards = self.dashboards.lock().await;
+        let dashboard = dashboards
+            .get(dashboard_id)
+            .ok_or_else(|| WarpError::not_found("dashboard", dashboard_id))?;
+        if !dashboard
+            .widgets
+            .iter()
+            .any(|widget| widget.id == widget_id)
+        {
+            return Err(WarpError::not_found("widget", widget_id));
         }
+        self.widget_data
+            .lock()
+            .await
+            .insert(widget_id.to_string(), data);
+        Ok(())
     }
 
-    pub async fn update_widget_data(&self, dashboard_id: &str, widget_id: &str) -> Result<(), WarpError> {
+    // The dashboard definition as JSON; image and document formats aren't produced here
+    pub async fn export_dashboard(
+        &self,
+        dashboard_id: &str,
+        format: ExportFormat,
+    ) -> Result<Vec<u8>, WarpError> {
+        if !matches!(format, ExportFormat::JSON) {
+            return Err(WarpError::Validation(format!(
+                "Dashboards can't be exported as {:?}",
+                format
+            )));
+        }
         let dashboards = self.dashboards.lock().await;
-        if let Some(dashboard) = dashboards.get(dashboard_id) {
-            if let Some(widget) = dashboard.widgets.iter().find(|w| w.id == widget_id) {
-                if let Some(data_source) = dashboard.data_sources.iter().find(|ds| ds.id == widget.data_source_id) {
-                    let data = self.data_processor.fetch_data(data_source, &widget.query).await?;
-                    self.real_time_updates.update_widget_data(dashboard_id, widget_id, data).await?;
-                }
-            }
+        let dashboard = dashboards
+            .get(dashboard_id)
+            .ok_or_else(|| WarpError::not_found("dashboard", dashboard_id))?;
+        Ok(serde_json::to_vec_pretty(dashboard)?)
+    }
+
+    // Users who see the live dashboard alongside its owner
+    pub async fn share_dashboard(
+        &self,
+        dashboard_id: &str,
+        user_id: &str,
+        users: Vec<String>,
+    ) -> Result<(), WarpError> {
+        let mut dashboards = self.dashboards.lock().await;
+        let dashboard = Self::owned_mut(&mut dashboards, dashboard_id, user_id)?;
+        dashboard.shared_with = users;
+        dashboard.updated_at = chrono::Utc::now();
+        Ok(())
+    }
+
+    pub async fn set_public(
+        &self,
+        dashboard_id: &str,
+        user_id: &str,
+        public: bool,
+    ) -> Result<(), WarpError> {
+        let mut dashboards = self.dashboards.lock().await;
+        let dashboard = Self::owned_mut(&mut dashboards, dashboard_id, user_id)?;
+        dashboard.is_public = public;
+        dashboard.updated_at = chrono::Utc::now();
+        if !public {
+            let revoked = self.sharing.revoke_dashboard(dashboard_id).await;
+            log::info!(
+                "Dashboard {} made private; revoked {} snapshot(s)",
+                dashboard_id,
+                revoked
+            );
         }
         Ok(())
     }
 
-    pub async fn export_dashboard(&self, dashboard_id: &str, format: ExportFormat) -> Result<Vec<u8>, WarpError> {
+    pub async fn get_dashboard(
+        &self,
+        dashboard_id: &str,
+        user_id: &str,
+    ) -> Result<Dashboard, WarpError> {
         let dashboards = self.dashboards.lock().await;
-        if let Some(dashboard) = dashboards.get(dashboard_id) {
-            self.export_renderer.export_dashboard(dashboard, format).await
-        } else {
-            Err(WarpError::ConfigError("Dashboard not found".to_string()))
+        let dashboard = dashboards
+            .get(dashboard_id)
+            .ok_or_else(|| WarpError::not_found("dashboard", dashboard_id))?;
+        if !sharing::ShareManager::can_view(dashboard, user_id) {
+            return Err(WarpError::not_found("dashboard", dashboard_id));
         }
+        Ok(dashboard.clone())
     }
 
-    async fn get_default_visualization_config(&self, widget_type: &WidgetType) -> Result<VisualizationConfig, WarpError> {
+    // Renders the dashboard as it is now and publishes it behind a token.
+    // The token is only returned here.
+    pub async fn create_snapshot(
+        &self,
+        dashboard_id: &str,
+        user_id: &str,
+        ttl: Option<chrono::Duration>,
+    ) -> Result<(sharing::Share, String), WarpError> {
+        let dashboards = self.dashboards.lock().await;
+        let dashboard = dashboards
+            .get(dashboard_id)
+            .ok_or_else(|| WarpError::not_found("dashboard", dashboard_id))?;
+        let rendered = self.render_html(dashboard).await;
+        self.sharing
+            .create(dashboard, &rendered.content, user_id, ttl)
+            .await
+    }
+
+    pub fn sharing(&self) -> Arc<sharing::ShareManager> {
+        self.sharing.clone()
+    }
+
+    fn owned_mut<'a>(
+        dashboards: &'a mut HashMap<String, Dashboard>,
+        dashboard_id: &str,
+        user_id: &str,
+    ) -> Result<&'a mut Dashboard, WarpError> {
+        let dashboard = dashboards
+            .get_mut(dashboard_id)
+            .ok_or_else(|| WarpError::not_found("dashboard", dashboard_id))?;
+        if dashboard.owner_id != user_id {
+            return Err(WarpError::Permission(format!(
+                "Only the owner can change sharing for '{}'",
+                dashboard.name
+            )));
+        }
+        Ok(dashboard)
+    }
+
+    // Sparklines, gauges and log tails drawn as text for terminal dashboards
+    pub fn render_widget_text(
+        &self,
+        widget_type: &WidgetType,
+        data: &chart_builder::WidgetData,
+        width: usize,
+        height: usize,
+    ) -> Result<chart_builder::RenderedWidget, WarpError> {
+        self.chart_builder
+            .render_text(widget_type, data, width, height)
+    }
+
+    // Visible widgets in order, each drawn by its text renderer when it has
+    // one and data to draw
+    async fn render_html(&self, dashboard: &Dashboard) -> RenderResult {
+        let started = std::time::Instant::now();
+        let widget_data = self.widget_data.lock().await;
+        let mut content = format!(
+            "<h1>{}</h1>\n<p>{}</p>\n",
+            sharing::escape(&dashboard.name),
+            sharing::escape(&dashboard.description)
+        );
+        let mut data_points = 0;
+        let mut widgets_rendered = 0;
+        let mut errors = Vec::new();
+        for widget in dashboard.widgets.iter().filter(|widget| widget.is_visible) {
+            let body = match widget_data.get(&widget.id) {
+                Some(data) if self.chart_builder.supports(&widget.widget_type) => {
+                    match self.chart_builder.render_text(
+                        &widget.widget_type,
+                        data,
+                        HTML_WIDGET_COLUMNS,
+                        HTML_WIDGET_ROWS,
+                    ) {
+                        Ok(rendered) => {
+                            widgets_rendered += 1;
+                            data_points += match data {
+                                chart_builder::WidgetData::Series(values) => values.len() as u32,
+                                chart_builder::WidgetData::Value { .. } => 1,
+                                chart_builder::WidgetData::Lines(lines) => lines.len() as u32,
+                            };
+                            format!("<pre>{}</pre>", sharing::escape(&rendered.lines.join("\n")))
+                        }
+                        Err(e) => {
+                            errors.push(format!("{}: {}", widget.title, e));
+                            "<p><em>Could not draw this widget</em></p>".to_string()
+                        }
+                    }
+                }
+                Some(_) => format!(
+                    "<p><em>{:?} widgets aren't drawn in snapshots</em></p>",
+                    widget.widget_type
+                ),
+                None => "<p><em>No data yet</em></p>".to_string(),
+            };
+            content.push_str(&format!(
+                "<section>\n<h2>{}</h2>\n{}\n</section>\n",
+                sharing::escape(&widget.title),
+                body
+            ));
+        }
+        RenderResult {
+            content,
+            metadata: RenderMetadata {
+                render_time: started.elapsed(),
+                data_points,
+                widgets_rendered,
+                cache_hits: 0,
+                errors,
+            },
+        }
+    }
+
+    async fn get_default_visualization_config(
+        &self,
+        widget_type: &WidgetType,
+    ) -> Result<VisualizationConfig, WarpError> {
         Ok(VisualizationConfig {
             chart_config: ChartConfig {
                 chart_type: widget_type.clone(),
@@ -996,6 +1232,27 @@ pub struct RenderMetadata {
     pub errors: Vec<String>,
 }
 
+impl Default for DashboardTheme {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            primary_color: "#3498db".to_string(),
+            secondary_color: "#2c3e50".to_string(),
+            background_color: "#ffffff".to_string(),
+            surface_color: "#f8f9fa".to_string(),
+            text_color: "#2c3e50".to_string(),
+            accent_color: "#3498db".to_string(),
+            error_color: "#e74c3c".to_string(),
+            warning_color: "#f39c12".to_string(),
+            success_color: "#2ecc71".to_string(),
+            info_color: "#3498db".to_string(),
+            font_family: "Inter, sans-serif".to_string(),
+            border_radius: 8.0,
+            shadow_elevation: 2.0,
+        }
+    }
+}
+
 impl Default for DashboardSettings {
     fn default() -> Self {
         Self {
@@ -1018,3 +1275,102 @@ impl Default for DashboardSettings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_draws_pushed_widget_data() {
+        let manager = VisualizationManager::new().await.unwrap();
+        let dashboard_id = manager
+            .create_dashboard("alice", "Builds <ci>", "")
+            .await
+            .unwrap();
+        let position = WidgetPosition {
+            x: 0,
+            y: 0,
+            z_index: 0,
+        };
+        let size = WidgetSize {
+            width: 4,
+            height: 2,
+            min_width: 0,
+            min_height: 0,
+            max_width: None,
+            max_height: None,
+            resizable: true,
+        };
+        let gauge = manager
+            .add_widget(
+                &dashboard_id,
+                WidgetType::Gauge,
+                "Queue",
+                position.clone(),
+                size.clone(),
+            )
+            .await
+            .unwrap();
+        manager
+            .add_widget(
+                &dashboard_id,
+                WidgetType::Sparkline,
+                "Latency",
+                position,
+                size,
+            )
+            .await
+            .unwrap();
+        manager
+            .update_widget_data(
+                &dashboard_id,
+                &gauge,
+                chart_builder::WidgetData::Value {
+                    value: 5.0,
+                    max: 10.0,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(manager
+            .update_widget_data(
+                &dashboard_id,
+                "missing",
+                chart_builder::WidgetData::Lines(Vec::new())
+            )
+            .await
+            .is_err());
+
+        let rendered = manager
+            .render_dashboard(&dashboard_id, RenderFormat::HTML)
+            .await
+            .unwrap();
+        assert!(rendered.content.contains("<h1>Builds &lt;ci&gt;</h1>"));
+        assert!(rendered.content.contains("50%"));
+        assert!(rendered.content.contains("No data yet"));
+        assert_eq!(rendered.metadata.widgets_rendered, 1);
+        assert!(manager
+            .render_dashboard(&dashboard_id, RenderFormat::SVG)
+            .await
+            .is_err());
+
+        assert!(manager
+            .create_snapshot(&dashboard_id, "alice", None)
+            .await
+            .is_err());
+        manager
+            .set_public(&dashboard_id, "alice", true)
+            .await
+            .unwrap();
+        let (_, token) = manager
+            .create_snapshot(&dashboard_id, "alice", None)
+            .await
+            .unwrap();
+        assert!(manager
+            .sharing()
+            .open(&token, "", "")
+            .await
+            .unwrap()
+            .contains("50%"));
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::Dashboard;
+use crate::api::{APIRequest, APIResponse};
+use crate::error::WarpError;
+
+// Path the embedded API serves snapshots under, followed by the share token
+pub const SNAPSHOT_ROUTE: &str = "/snapshots";
+// Access log entries kept across all shares
+const ACCESS_LOG_CAPACITY: usize = 1000;
+// Snapshots can't call out for data, run scripts or load remote assets
+const SNAPSHOT_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; img-src data:";
+
+// A frozen, read-only rendering of a dashboard reachable with its token.
+// Only a hash of the token is kept, so the share list can't be replayed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub id: String,
+    pub dashboard_id: String,
+    pub dashboard_name: String,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub views: u64,
+    token_hash: String,
+    html: String,
+}
+
+impl Share {
+    pub fn is_active(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires| now < expires)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AccessOutcome {
+    Served,
+    UnknownToken,
+    Expired,
+    Revoked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessRecord {
+    pub share_id: Option<String>, // None when the token matched nothing
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub remote: String,
+    pub user_agent: String,
+    pub outcome: AccessOutcome,
+}
+
+pub struct ShareManager {
+    shares: Mutex<HashMap<String, Share>>,
+    access_log: Mutex<VecDeque<AccessRecord>>,
+    rng: SystemRandom,
+}
+
+impl Default for ShareManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShareManager {
+    pub fn new() -> Self {
+        Self {
+            shares: Mutex::new(HashMap::new()),
+            access_log: Mutex::new(VecDeque::new()),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    // The owner and the users it is shared with see the live dashboard;
+    // anyone else needs it to be public
+    pub fn can_view(dashboard: &Dashboard, user_id: &str) -> bool {
+        dashboard.is_public
+            || dashboard.owner_id == user_id
+            || dashboard.shared_with.iter().any(|user| user == user_id)
+    }
+
+    // Snapshots leave the sharing list behind, so only a public dashboard's
+    // owner can publish one. Returns the share and its token, which is not
+    // retrievable afterwards.
+    pub async fn create(
+        &self,
+        dashboard: &Dashboard,
+        rendered: &str,
+        user_id: &str,
+        ttl: Option<chrono::Duration>,
+    ) -> Result<(Share, String), WarpError> {
+        if dashboard.owner_id != user_id {
+            return Err(WarpError::Permission(format!(
+                "Only the owner can share dashboard '{}'",
+                dashboard.name
+            )));
+        }
+        if !dashboard.is_public {
+            return Err(WarpError::Validation(format!(
+                "Dashboard '{}' must be made public before publishing a snapshot",
+                dashboard.name
+            )));
+        }
+        self.publish(&dashboard.id, &dashboard.name, rendered, user_id, ttl)
+            .await
+    }
+
+    async fn publish(
+        &self,
+        dashboard_id: &str,
+        name: &str,
+        rendered: &str,
+        user_id: &str,
+        ttl: Option<chrono::Duration>,
+    ) -> Result<(Share, String), WarpError> {
+        let token = self.random_token()?;
+        let now = chrono::Utc::now();
+        let share = Share {
+            id: uuid::Uuid::new_v4().to_string(),
+            dashboard_id: dashboard_id.to_string(),
+            dashboard_name: name.to_string(),
+            created_by: user_id.to_string(),
+            created_at: now,
+            expires_at: ttl.map(|ttl| now + ttl),
+            revoked_at: None,
+            views: 0,
+            token_hash: hash_token(&token),
+            html: bundle(name, rendered, now),
+        };
+        self.shares
+            .lock()
+            .await
+            .insert(share.id.clone(), share.clone());
+        Ok((share, token))
+    }
+
+    pub async fn revoke(&self, share_id: &str) -> Result<(), WarpError> {
+        let mut shares = self.shares.lock().await;
+        let share = shares
+            .get_mut(share_id)
+            .ok_or_else(|| WarpError::not_found("share", share_id))?;
+        share.revoked_at.get_or_insert_with(chrono::Utc::now);
+        Ok(())
+    }
+
+    // Making a dashboard private again takes every snapshot of it down
+    pub async fn revoke_dashboard(&self, dashboard_id: &str) -> usize {
+        let now = chrono::Utc::now();
+        let mut shares = self.shares.lock().await;
+        shares
+            .values_mut()
+            .filter(|share| share.dashboard_id == dashboard_id && share.revoked_at.is_none())
+            .map(|share| share.revoked_at = Some(now))
+            .count()
+    }
+
+    pub async fn shares_for(&self, dashboard_id: &str) -> Vec<Share> {
+        let mut shares: Vec<Share> = self
+            .shares
+            .lock()
+            .await
+            .values()
+            .filter(|share| share.dashboard_id == dashboard_id)
+            .cloned()
+            .collect();
+        shares.sort_by_key(|share| share.created_at);
+        shares
+    }
+
+    // The snapshot for a token, recording the attempt either way
+    pub async fn open(
+        &self,
+        token: &str,
+        remote: &str,
+        user_agent: &str,
+    ) -> Result<String, WarpError> {
+        let now = chrono::Utc::now();
+        let hash = hash_token(token);
+        let mut shares = self.shares.lock().await;
+        let share = shares.values_mut().find(|share| share.token_hash == hash);
+        let (share_id, outcome) = match &share {
+            None => (None, AccessOutcome::UnknownToken),
+            Some(share) if share.revoked_at.is_some() => {
+                (Some(share.id.clone()), AccessOutcome::Revoked)
+            }
+            Some(share) if !share.is_active(now) => {
+                (Some(share.id.clone()), AccessOutcome::Expired)
+            }
+            Some(share) => (Some(share.id.clone()), AccessOutcome::Served),
+        };
+        self.record(AccessRecord {
+            share_id,
+            at: now,
+            remote: remote.to_string(),
+            user_agent: user_agent.to_string(),
+            outcome: outcome.clone(),
+        })
+        .await;
+        match (share, outcome) {
+            (Some(share), AccessOutcome::Served) => {
+                share.views += 1;
+                Ok(share.html.clone())
+            }
+            // Unknown, expired and revoked look the same from outside
+            _ => Err(WarpError::not_found("snapshot", "")),
+        }
+    }
+
+    // Answers `GET /snapshots/<token>` for the embedded API. The token is
+    // the credential, so the route is public to the auth middleware.
+    pub async fn serve(&self, request: &APIRequest) -> Option<APIResponse> {
+        let token = request
+            .path
+            .strip_prefix(SNAPSHOT_ROUTE)?
+            .strip_prefix('/')?;
+        if request.method != "GET" || token.is_empty() || token.contains('/') {
+            return None;
+        }
+        let started = std::time::Instant::now();
+        let (status_code, body) = match self
+            .open(token, &request.ip_address, &request.user_agent)
+            .await
+        {
+            Ok(html) => (200, html),
+            Err(_) => (404, "Snapshot not found".to_string()),
+        };
+        let content_type = if status_code == 200 {
+            "text/html; charset=utf-8"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+        Some(APIResponse {
+            request_id: request.request_id.clone(),
+            status_code,
+            headers: HashMap::from([
+                ("Content-Type".to_string(), content_type.to_string()),
+                (
+                    "Content-Security-Policy".to_string(),
+                    SNAPSHOT_CSP.to_string(),
+                ),
+                ("Cache-Control".to_string(), "no-store".to_string()),
+                ("Referrer-Policy".to_string(), "no-referrer".to_string()),
+                ("X-Robots-Tag".to_string(), "noindex".to_string()),
+            ]),
+            body: Some(serde_json::Value::String(body)),
+            processing_time: started.elapsed(),
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    // Writes the snapshot as a standalone HTML file for sending by hand
+    pub async fn write_bundle(&self, share_id: &str, path: &Path) -> Result<(), WarpError> {
+        let html = self
+            .shares
+            .lock()
+            .await
+            .get(share_id)
+            .map(|share| share.html.clone())
+            .ok_or_else(|| WarpError::not_found("share", share_id))?;
+        crate::shutdown::write_atomic(path, html.as_bytes()).await?;
+        Ok(())
+    }
+
+    pub async fn access_log(&self, share_id: Option<&str>) -> Vec<AccessRecord> {
+        self.access_log
+            .lock()
+            .await
+            .iter()
+            .filter(|record| share_id.is_none_or(|id| record.share_id.as_deref() == Some(id)))
+            .cloned()
+            .collect()
+    }
+
+    async fn record(&self, record: AccessRecord) {
+        let mut log = self.access_log.lock().await;
+        if log.len() == ACCESS_LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(record);
+    }
+
+    fn random_token(&self) -> Result<String, WarpError> {
+        let mut bytes = [0u8; 32];
+        self.rng
+            .fill(&mut bytes)
+            .map_err(|_| WarpError::Config("Could not generate a share token".to_string()))?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+    URL_SAFE_NO_PAD.encode(digest.as_ref())
+}
+
+// Wraps the rendered dashboard in a page that is complete on its own and
+// says when the data was captured
+fn bundle(name: &str, rendered: &str, at: chrono::DateTime<chrono::Utc>) -> String {
+    let name = escape(name);
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"Content-Security-Policy\" content=\"{csp}\">\n\
+         <title>{name}</title></head>\n<body>\n\
+         <header style=\"font-family: sans-serif; color: #666\">{name} &middot; read-only snapshot taken {at}</header>\n\
+         {rendered}\n</body></html>\n",
+        csp = SNAPSHOT_CSP,
+        name = name,
+        at = at.format("%Y-%m-%d %H:%M UTC"),
+        rendered = rendered,
+    )
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_share_lifecycle() {
+        let manager = ShareManager::new();
+        let (share, token) = manager
+            .publish("d1", "Builds <ci>", "<p>x</p>", "alice", None)
+            .await
+            .unwrap();
+        let html = manager.open(&token, "10.0.0.5", "curl").await.unwrap();
+        assert!(html.contains("<title>Builds &lt;ci&gt;</title>"));
+        assert!(html.contains("<p>x</p>"));
+        assert!(manager.open("wrong", "10.0.0.6", "curl").await.is_err());
+
+        manager.revoke(&share.id).await.unwrap();
+        assert!(manager.open(&token, "10.0.0.5", "curl").await.is_err());
+
+        let log = manager.access_log(Some(&share.id)).await;
+        let outcomes: Vec<AccessOutcome> = log.into_iter().map(|record| record.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![AccessOutcome::Served, AccessOutcome::Revoked]
+        );
+        assert_eq!(manager.access_log(None).await.len(), 3);
+        assert_eq!(manager.shares_for("d1").await[0].views, 1);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_and_dashboard_revocation() {
+        let manager = ShareManager::new();
+        let (_, token) = manager
+            .publish("d1", "CI", "", "alice", Some(chrono::Duration::seconds(-1)))
+            .await
+            .unwrap();
+        assert!(manager.open(&token, "", "").await.is_err());
+        assert_eq!(
+            manager.access_log(None).await[0].outcome,
+            AccessOutcome::Expired
+        );
+
+        manager
+            .publish("d1", "CI", "", "alice", None)
+            .await
+            .unwrap();
+        manager
+            .publish("d2", "Other", "", "alice", None)
+            .await
+            .unwrap();
+        assert_eq!(manager.revoke_dashboard("d1").await, 2);
+        assert!(manager.shares_for("d2").await[0].revoked_at.is_none());
+    }
+}
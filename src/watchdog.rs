@@ -0,0 +1,341 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, System};
+
+use crate::config::{LimitAction, ResourcePolicy, WatchdogConfig, WatchdogRule};
+use crate::error::WarpError;
+use crate::i18n;
+use crate::process_tree::{build_tree, format_bytes, snapshot};
+
+// Output still captured per second once a command is throttled
+const THROTTLED_BYTES_PER_SEC: f64 = 64.0 * 1024.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Runtime,
+    Memory,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub limit: Limit,
+    pub action: LimitAction,
+    pub observed: String,
+    pub allowed: String,
+}
+
+impl Violation {
+    pub fn message(&self, command: &str) -> String {
+        let key = match self.limit {
+            Limit::Runtime => "watchdog.runtime",
+            Limit::Memory => "watchdog.memory",
+            Limit::Output => "watchdog.output",
+        };
+        let action = match self.action {
+            LimitAction::Warn => i18n::t("watchdog.action_warn"),
+            LimitAction::Throttle => i18n::t("watchdog.action_throttle"),
+            LimitAction::Kill => i18n::t("watchdog.action_kill"),
+        };
+        i18n::t_args(
+            key,
+            &[
+                ("command", command),
+                ("observed", &self.observed),
+                ("allowed", &self.allowed),
+                ("action", &action),
+            ],
+        )
+    }
+}
+
+fn compile(rules: &[WatchdogRule]) -> Result<Vec<(Regex, ResourcePolicy)>, WarpError> {
+    rules
+        .iter()
+        .map(|rule| {
+            Regex::new(&rule.pattern)
+                .map(|regex| (regex, rule.policy))
+                .map_err(|e| {
+                    WarpError::config_err(format!(
+                        "Invalid watchdog rule '{}': {}",
+                        rule.pattern, e
+                    ))
+                })
+        })
+        .collect()
+}
+
+pub struct Watchdog {
+    default_policy: ResourcePolicy,
+    rules: Vec<(Regex, ResourcePolicy)>,
+    projects: Vec<(PathBuf, Vec<(Regex, ResourcePolicy)>)>,
+}
+
+impl Watchdog {
+    pub fn from_config(config: &WatchdogConfig) -> Result<Self, WarpError> {
+        let mut projects = config
+            .projects
+            .iter()
+            .map(|(root, rules)| compile(rules).map(|rules| (root.clone(), rules)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Most specific root first so nested projects override their parents
+        projects.sort_by_key(|(root, _)| std::cmp::Reverse(root.components().count()));
+        Ok(Self {
+            default_policy: config.default_policy,
+            rules: compile(&config.rules)?,
+            projects,
+        })
+    }
+
+    pub fn policy_for(&self, command: &str, cwd: &Path) -> ResourcePolicy {
+        let project_rules = self
+            .projects
+            .iter()
+            .filter(|(root, _)| cwd.starts_with(root))
+            .flat_map(|(_, rules)| rules);
+        project_rules
+            .chain(&self.rules)
+            .find(|(regex, _)| regex.is_match(command))
+            .map_or(self.default_policy, |(_, policy)| *policy)
+    }
+
+    pub fn watch(&self, command: &str, cwd: &Path, now: Instant) -> WatchedCommand {
+        WatchedCommand::new(command.to_string(), self.policy_for(command, cwd), now)
+    }
+}
+
+// Token bucket refilled at THROTTLED_BYTES_PER_SEC
+struct Throttle {
+    allowance: f64,
+    last: Instant,
+    dropped_bytes: u64,
+}
+
+pub struct WatchedCommand {
+    pub command: String,
+    policy: ResourcePolicy,
+    started: Instant,
+    output_bytes: u64,
+    // Each limit is reported once per command
+    reported: Vec<Limit>,
+    throttle: Option<Throttle>,
+}
+
+impl WatchedCommand {
+    pub fn new(command: String, policy: ResourcePolicy, now: Instant) -> Self {
+        Self {
+            command,
+            policy,
+            started: now,
+            output_bytes: 0,
+            reported: Vec::new(),
+            throttle: None,
+        }
+    }
+
+    fn exceed(
+        &mut self,
+        limit: Limit,
+        observed: String,
+        allowed: String,
+        now: Instant,
+    ) -> Option<Violation> {
+        if self.reported.contains(&limit) {
+            return None;
+        }
+        self.reported.push(limit);
+        if self.policy.action == LimitAction::Throttle && self.throttle.is_none() {
+            self.throttle = Some(Throttle {
+                allowance: THROTTLED_BYTES_PER_SEC,
+                last: now,
+                dropped_bytes: 0,
+            });
+        }
+        Some(Violation {
+            limit,
+            action: self.policy.action,
+            observed,
+            allowed,
+        })
+    }
+
+    // Counts a chunk of output. Returns whether to keep it, and a violation
+    // the first time the output limit is crossed.
+    pub fn record_output(&mut self, bytes: usize, now: Instant) -> (bool, Option<Violation>) {
+        self.output_bytes += bytes as u64;
+        let violation = match self.policy.max_output_bytes {
+            Some(max) if self.output_bytes > max => self.exceed(
+                Limit::Output,
+                format_bytes(self.output_bytes),
+                format_bytes(max),
+                now,
+            ),
+            _ => None,
+        };
+        let keep = match self.throttle.as_mut() {
+            None => true,
+            Some(throttle) => {
+                let elapsed = now.saturating_duration_since(throttle.last).as_secs_f64();
+                throttle.last = now;
+                throttle.allowance = (throttle.allowance + elapsed * THROTTLED_BYTES_PER_SEC)
+                    .min(THROTTLED_BYTES_PER_SEC);
+                if throttle.allowance >= bytes as f64 {
+                    throttle.allowance -= bytes as f64;
+                    true
+                } else {
+                    throttle.dropped_bytes += bytes as u64;
+                    false
+                }
+            }
+        };
+        (keep, violation)
+    }
+
+    pub fn runtime(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.started)
+    }
+
+    pub fn dropped_bytes(&self) -> u64 {
+        self.throttle
+            .as_ref()
+            .map_or(0, |throttle| throttle.dropped_bytes)
+    }
+
+    // Runtime and memory limits, checked on each watchdog tick
+    pub fn check(&mut self, memory_bytes: u64, now: Instant) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if let Some(max) = self.policy.max_runtime_secs {
+            let runtime = self.runtime(now);
+            if runtime > Duration::from_secs(max) {
+                violations.extend(self.exceed(
+                    Limit::Runtime,
+                    format!("{}s", runtime.as_secs()),
+                    format!("{}s", max),
+                    now,
+                ));
+            }
+        }
+        if let Some(max) = self.policy.max_memory_bytes {
+            if memory_bytes > max {
+                violations.extend(self.exceed(
+                    Limit::Memory,
+                    format_bytes(memory_bytes),
+                    format_bytes(max),
+                    now,
+                ));
+            }
+        }
+        violations
+    }
+}
+
+// Processes started from the shell, i.e. the running command; the shell
+// itself is left out. Deepest first, so they can be killed in that order.
+pub fn command_processes(system: &System, shell_pid: u32) -> Vec<(u32, u64)> {
+    let mut nodes = build_tree(&snapshot(system), shell_pid);
+    nodes.retain(|node| node.depth > 0);
+    nodes.sort_by_key(|node| std::cmp::Reverse(node.depth));
+    nodes
+        .into_iter()
+        .map(|node| (node.info.pid, node.info.memory_bytes))
+        .collect()
+}
+
+pub fn kill_processes(system: &System, pids: &[u32]) -> Result<(), WarpError> {
+    for pid in pids {
+        if let Some(process) = system.process(Pid::from_u32(*pid)) {
+            if process.kill_with(sysinfo::Signal::Kill) == Some(false) {
+                return Err(WarpError::Permission(format!("Could not kill {}", pid)));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn policy(runtime: Option<u64>, output: Option<u64>, action: LimitAction) -> ResourcePolicy {
+        ResourcePolicy {
+            max_runtime_secs: runtime,
+            max_memory_bytes: Some(1024),
+            max_output_bytes: output,
+            action,
+        }
+    }
+
+    #[test]
+    fn test_project_rules_override_global() {
+        let rule = |pattern: &str, action| WatchdogRule {
+            pattern: pattern.to_string(),
+            policy: policy(None, None, action),
+        };
+        let config = WatchdogConfig {
+            enabled: true,
+            default_policy: policy(None, None, LimitAction::Warn),
+            rules: vec![rule(r"^cargo\b", LimitAction::Throttle)],
+            projects: HashMap::from([(
+                PathBuf::from("/work/api"),
+                vec![rule(r"^cargo test", LimitAction::Kill)],
+            )]),
+        };
+        let watchdog = Watchdog::from_config(&config).unwrap();
+        let action = |command, cwd| watchdog.policy_for(command, Path::new(cwd)).action;
+        assert_eq!(action("cargo test", "/work/api/src"), LimitAction::Kill);
+        assert_eq!(action("cargo build", "/work/api"), LimitAction::Throttle);
+        assert_eq!(action("cargo test", "/work/web"), LimitAction::Throttle);
+        assert_eq!(action("make", "/work/api"), LimitAction::Warn);
+    }
+
+    #[test]
+    fn test_limits_reported_once() {
+        let start = Instant::now();
+        let mut watched = WatchedCommand::new(
+            "yes".to_string(),
+            policy(Some(10), None, LimitAction::Kill),
+            start,
+        );
+        assert!(watched
+            .check(512, start + Duration::from_secs(5))
+            .is_empty());
+
+        let violations = watched.check(4096, start + Duration::from_secs(11));
+        let limits: Vec<Limit> = violations.iter().map(|v| v.limit).collect();
+        assert_eq!(limits, vec![Limit::Runtime, Limit::Memory]);
+        assert_eq!(violations[1].allowed, "1.0K");
+        assert!(watched
+            .check(4096, start + Duration::from_secs(12))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_throttle_caps_captured_output() {
+        let start = Instant::now();
+        let mut watched = WatchedCommand::new(
+            "cat big".to_string(),
+            policy(None, Some(1000), LimitAction::Throttle),
+            start,
+        );
+        assert_eq!(watched.record_output(800, start), (true, None));
+
+        let (keep, violation) = watched.record_output(800, start);
+        assert!(keep);
+        assert_eq!(violation.unwrap().limit, Limit::Output);
+
+        // The bucket holds one second's worth; a burst beyond it is dropped
+        let chunk = 16 * 1024;
+        let kept = (0..8)
+            .filter(|_| watched.record_output(chunk, start).0)
+            .count();
+        assert_eq!(kept, 3);
+        assert_eq!(watched.dropped_bytes(), 5 * chunk as u64);
+        assert!(
+            watched
+                .record_output(chunk, start + Duration::from_secs(1))
+                .0
+        );
+    }
+}
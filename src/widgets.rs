@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use crossterm::style::Color;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use unicode_width::UnicodeWidthStr;
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+// A log line that never ends shouldn't grow without bound
+const MAX_PARTIAL_LINE: usize = 64 * 1024;
+
+// The last `capacity` samples of a metric, oldest first
+#[derive(Debug, Clone)]
+pub struct History {
+    samples: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+    }
+
+    pub fn values(&self) -> Vec<f64> {
+        self.samples.iter().copied().collect()
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.samples.back().copied()
+    }
+}
+
+// The most recent `width` values as block characters. With `max` set the
+// scale is 0..max (CPU percent, say); otherwise it spans the values shown.
+pub fn sparkline(values: &[f64], width: usize, max: Option<f64>) -> String {
+    let shown = &values[values.len().saturating_sub(width)..];
+    let finite = shown.iter().copied().filter(|value| value.is_finite());
+    let (low, high) = match max {
+        Some(max) => (0.0, max),
+        None => finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        }),
+    };
+    shown
+        .iter()
+        .map(|value| {
+            if !value.is_finite() {
+                return ' ';
+            }
+            let fraction = if high > low {
+                (value - low) / (high - low)
+            } else {
+                0.0
+            };
+            let level =
+                (fraction.clamp(0.0, 1.0) * (SPARK_LEVELS.len() - 1) as f64).round() as usize;
+            SPARK_LEVELS[level]
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaugeLevel {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl GaugeLevel {
+    pub fn of(fraction: f64, warning: f64, critical: f64) -> Self {
+        if fraction >= critical {
+            GaugeLevel::Critical
+        } else if fraction >= warning {
+            GaugeLevel::Warning
+        } else {
+            GaugeLevel::Normal
+        }
+    }
+}
+
+// A `width`-cell bar followed by the percentage, e.g. `██████░░░░ 61%`
+pub fn gauge(fraction: f64, width: usize) -> String {
+    let fraction = if fraction.is_finite() {
+        fraction.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let filled = (fraction * width as f64).round() as usize;
+    format!(
+        "{}{} {:.0}%",
+        "█".repeat(filled),
+        "░".repeat(width - filled),
+        fraction * 100.0
+    )
+}
+
+// The tail of a log: either a file followed as it grows, or lines pushed
+// from a pane's output. Keeps the last `capacity` lines, without colour.
+pub struct LogStream {
+    path: Option<PathBuf>,
+    offset: u64,
+    partial: String,
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl LogStream {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            path: None,
+            offset: 0,
+            partial: String::new(),
+            lines: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn file(path: impl Into<PathBuf>, capacity: usize) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::new(capacity)
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // Reads whatever was appended since the last poll. A file that shrank
+    // was truncated or rotated, so it is read again from the start.
+    // Returns whether anything new was read.
+    pub async fn poll(&mut self) -> std::io::Result<bool> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+        let mut file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            // Not created yet; keep waiting for it
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let len = file.metadata().await?.len();
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset {
+            return Ok(false);
+        }
+        file.seek(std::io::SeekFrom::Start(self.offset)).await?;
+        let mut bytes = Vec::with_capacity((len - self.offset) as usize);
+        file.take(len - self.offset).read_to_end(&mut bytes).await?;
+        self.offset += bytes.len() as u64;
+        self.push(&String::from_utf8_lossy(&bytes));
+        Ok(true)
+    }
+
+    pub fn push(&mut self, text: &str) {
+        self.partial.push_str(text);
+        while let Some(end) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=end).collect();
+            self.push_line(line.trim_end_matches(['\r', '\n']));
+        }
+        if self.partial.len() > MAX_PARTIAL_LINE {
+            let line = std::mem::take(&mut self.partial);
+            self.push_line(&line);
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(strip_ansi(line));
+    }
+
+    // The newest `count` lines, oldest first
+    pub fn tail(&self, count: usize) -> Vec<&str> {
+        self.lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(count))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn last_line(&self) -> Option<&str> {
+        self.lines.back().map(String::as_str)
+    }
+}
+
+// Drops CSI and OSC sequences so coloured logs render as plain text
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PresenceState {
+    Online,
+    Away,
+    Offline,
+}
+
+// One collaborator in the header's participant strip
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticipantBadge {
+    pub name: String,
+    pub state: PresenceState,
+    pub typing: bool,
+    pub speaking: bool,
+    // Where their cursor is in a shared file, e.g. `main.rs:12`
+    pub location: Option<String>,
+    pub color: Color,
+}
+
+impl ParticipantBadge {
+    // `●AL✎@main.rs:12`: state, initials, activity, cursor
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        label.push(match self.state {
+            PresenceState::Online => '●',
+            PresenceState::Away => '◐',
+            PresenceState::Offline => '○',
+        });
+        label.push_str(&initials(&self.name));
+        if self.speaking {
+            label.push('♪');
+        }
+        if self.typing {
+            label.push('✎');
+        }
+        if let Some(location) = &self.location {
+            label.push('@');
+            label.push_str(location);
+        }
+        label
+    }
+}
+
+// "Ada Lovelace" -> "AL", "grace_hopper" -> "GH", "bob" -> "BO"
+pub fn initials(name: &str) -> String {
+    let words: Vec<&str> = name
+        .split([' ', '.', '_', '-'])
+        .filter(|word| !word.is_empty())
+        .collect();
+    let letters: String = match words.as_slice() {
+        [] => "?".to_string(),
+        [word] => word.chars().take(2).collect(),
+        [first, second, ..] => first
+            .chars()
+            .take(1)
+            .chain(second.chars().take(1))
+            .collect(),
+    };
+    letters.to_uppercase()
+}
+
+// How many labels fit in `width` columns, one space apart, keeping room for
+// a `+N` counter when some don't
+pub fn strip_fit(labels: &[String], width: usize) -> usize {
+    let mut used = 0;
+    for (index, label) in labels.iter().enumerate() {
+        let remaining = labels.len() - index - 1;
+        let counter = if remaining > 0 {
+            format!(" +{}", remaining).len()
+        } else {
+            0
+        };
+        let needed = used + usize::from(index > 0) + label.width();
+        if needed + counter > width {
+            return index;
+        }
+        used = needed;
+    }
+    labels.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline_and_gauge() {
+        assert_eq!(sparkline(&[0.0, 50.0, 100.0], 10, Some(100.0)), "▁▅█");
+        assert_eq!(sparkline(&[1.0, 2.0, 3.0, 4.0], 2, None), "▁█");
+        assert_eq!(sparkline(&[5.0, 5.0], 4, None), "▁▁");
+        assert_eq!(gauge(0.6, 10), "██████░░░░ 60%");
+        assert_eq!(gauge(1.7, 4), "████ 100%");
+        assert_eq!(GaugeLevel::of(0.85, 0.75, 0.9), GaugeLevel::Warning);
+
+        let mut history = History::new(2);
+        history.push(1.0);
+        history.push(2.0);
+        history.push(3.0);
+        assert_eq!(history.values(), vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_participant_strip() {
+        assert_eq!(initials("Ada Lovelace"), "AL");
+        assert_eq!(initials("grace_hopper"), "GH");
+        assert_eq!(initials("bob"), "BO");
+        let badge = ParticipantBadge {
+            name: "Ada Lovelace".to_string(),
+            state: PresenceState::Online,
+            typing: true,
+            speaking: false,
+            location: Some("main.rs:12".to_string()),
+            color: Color::Cyan,
+        };
+        assert_eq!(badge.label(), "●AL✎@main.rs:12");
+
+        let labels: Vec<String> = ["●AB", "◐CD", "○EF"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(strip_fit(&labels, 20), 3);
+        // "●AB +2" fits, "●AB ◐CD +1" doesn't
+        assert_eq!(strip_fit(&labels, 9), 1);
+        assert_eq!(strip_fit(&labels, 2), 0);
+    }
+
+    #[tokio::test]
+    async fn test_log_stream_follows_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut stream = LogStream::file(&path, 2);
+        assert!(!stream.poll().await.unwrap());
+
+        tokio::fs::write(&path, "one\n\x1b[31mtwo\x1b[0m\nthr")
+            .await
+            .unwrap();
+        assert!(stream.poll().await.unwrap());
+        assert_eq!(stream.tail(5), vec!["one", "two"]);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, b"ee\n").unwrap();
+        stream.poll().await.unwrap();
+        assert_eq!(stream.tail(5), vec!["two", "three"]);
+
+        // Rotation: the file starts over
+        tokio::fs::write(&path, "fresh\n").await.unwrap();
+        stream.poll().await.unwrap();
+        assert_eq!(stream.last_line(), Some("fresh"));
+    }
+}
@@ -103,7 +103,7 @@ impl WorkflowManager {
     async fn load_workflow_file(&self, path: &PathBuf) -> Result<Workflow, WarpError> {
         let content = fs::read_to_string(path).await?;
         let workflow: Workflow = serde_yaml::from_str(&content)
-            .map_err(|e| WarpError::ConfigError(format!("Failed to parse workflow: {}", e)))?;
+            .map_err(|e| WarpError::Config(format!("Failed to parse workflow: {}", e)))?;
         Ok(workflow)
     }
 
@@ -0,0 +1,415 @@
+use crossterm::style::Color;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::PaneDefaults;
+use crate::error::WarpError;
+use crate::i18n;
+use crate::shutdown::write_atomic;
+use crate::ui::{PanelItem, PanelKind, PanelView};
+
+// What a repository can bring along that the terminal would otherwise act
+// on by itself
+const PROJECT_CONFIG: &str = ".warp/config.toml";
+const ENV_FILE: &str = ".env";
+const WORKFLOWS_DIR: &str = ".warp/workflows";
+const SCRIPTS_DIR: &str = ".warp/scripts";
+
+// The per-project files found at a root. Nothing in them is read until the
+// root is trusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectFiles {
+    pub root: PathBuf,
+    pub config: Option<PathBuf>,
+    pub env: Option<PathBuf>,
+    pub workflows: Vec<PathBuf>,
+    pub scripts: Vec<PathBuf>,
+}
+
+impl ProjectFiles {
+    pub fn scan(root: &Path) -> Self {
+        let file = |name: &str| Some(root.join(name)).filter(|path| path.is_file());
+        Self {
+            root: root.to_path_buf(),
+            config: file(PROJECT_CONFIG),
+            env: file(ENV_FILE),
+            workflows: dir_files(&root.join(WORKFLOWS_DIR)),
+            scripts: dir_files(&root.join(SCRIPTS_DIR)),
+        }
+    }
+
+    // The nearest directory at or above `cwd` with anything to apply
+    pub fn find(cwd: &Path) -> Option<Self> {
+        cwd.ancestors()
+            .map(Self::scan)
+            .find(|files| !files.is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.config.is_none()
+            && self.env.is_none()
+            && self.workflows.is_empty()
+            && self.scripts.is_empty()
+    }
+
+    // Every file, relative to the root, for the prompt
+    pub fn paths(&self) -> Vec<String> {
+        self.config
+            .iter()
+            .chain(&self.env)
+            .chain(&self.workflows)
+            .chain(&self.scripts)
+            .map(|path| {
+                path.strip_prefix(&self.root)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+fn dir_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+// What a trusted project changes for tabs opened inside it
+#[derive(Debug, Clone, Default)]
+pub struct ProjectSettings {
+    pub defaults: PaneDefaults,
+    pub env: Vec<(String, String)>,
+}
+
+impl ProjectSettings {
+    pub fn load(files: &ProjectFiles) -> Result<Self, WarpError> {
+        let defaults = match &files.config {
+            Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+            None => PaneDefaults::default(),
+        };
+        let env = match &files.env {
+            Some(path) => parse_env(&std::fs::read_to_string(path)?),
+            None => Vec::new(),
+        };
+        Ok(Self { defaults, env })
+    }
+}
+
+// KEY=value lines as dotenv writes them: comments, blank lines, `export`
+// and quotes around the value are allowed; no interpolation
+pub fn parse_env(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return None;
+            }
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+                .unwrap_or(value);
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    Trusted,
+    Restricted, // the user chose restricted mode this session
+    Unknown,    // not asked yet; restricted until answered
+}
+
+// Trusted folders, kept on disk; trusting a folder trusts everything under
+// it. Restricted answers only last the session so the prompt comes back
+// next time.
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    trusted: BTreeSet<PathBuf>,
+    restricted: HashSet<PathBuf>,
+}
+
+impl TrustStore {
+    pub fn default_path() -> Result<PathBuf, WarpError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| WarpError::config_err("Could not find data directory"))?;
+        Ok(data_dir.join("warp").join("trusted_workspaces.json"))
+    }
+
+    pub async fn load(path: &Path) -> Result<Self, WarpError> {
+        let trusted = match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            trusted,
+            ..Self::default()
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WarpError> {
+        Ok(serde_json::to_vec_pretty(&self.trusted)?)
+    }
+
+    pub async fn save(bytes: &[u8], path: &Path) -> Result<(), WarpError> {
+        write_atomic(path, bytes).await
+    }
+
+    pub fn trust_for(&self, root: &Path) -> Trust {
+        if self.trusted.iter().any(|trusted| root.starts_with(trusted)) {
+            Trust::Trusted
+        } else if self.restricted.contains(root) {
+            Trust::Restricted
+        } else {
+            Trust::Unknown
+        }
+    }
+
+    pub fn trusted(&self) -> impl Iterator<Item = &Path> {
+        self.trusted.iter().map(PathBuf::as_path)
+    }
+
+    pub fn trust(&mut self, path: &Path) {
+        self.restricted.retain(|root| !root.starts_with(path));
+        self.trusted.insert(path.to_path_buf());
+    }
+
+    pub fn restrict(&mut self, root: &Path) {
+        self.restricted.insert(root.to_path_buf());
+    }
+
+    pub fn revoke(&mut self, path: &Path) {
+        self.trusted.remove(path);
+    }
+}
+
+// The settings a project may apply: only when it's trusted, and only what
+// loads cleanly
+pub fn settings_for(store: &TrustStore, cwd: &Path) -> Option<ProjectSettings> {
+    let files = ProjectFiles::find(cwd)?;
+    if store.trust_for(&files.root) != Trust::Trusted {
+        return None;
+    }
+    match ProjectSettings::load(&files) {
+        Ok(settings) => Some(settings),
+        Err(e) => {
+            log::warn!(
+                "Ignoring project settings in {}: {}",
+                files.root.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustCommand {
+    None,
+    Trust(PathBuf),
+    Restrict(PathBuf),
+    Revoke(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    Prompt(ProjectFiles),
+    Trusted(PathBuf),
+}
+
+// The prompt for the project the shell is in, when it hasn't been answered,
+// above the list of trusted folders with revoke
+pub struct TrustPanel {
+    rows: Vec<Row>,
+    selected: usize,
+}
+
+impl TrustPanel {
+    pub fn new(store: &TrustStore, prompt: Option<ProjectFiles>) -> Self {
+        let prompt = prompt.map(Row::Prompt);
+        let trusted = store.trusted().map(|path| Row::Trusted(path.to_path_buf()));
+        Self {
+            rows: prompt.into_iter().chain(trusted).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn handle_key(&mut self, key: char, index: usize) -> TrustCommand {
+        self.select(index);
+        match (key, self.rows.get(self.selected)) {
+            ('t', Some(Row::Prompt(files))) => TrustCommand::Trust(files.root.clone()),
+            ('p', Some(Row::Prompt(files))) => {
+                let parent = files.root.parent().unwrap_or(&files.root);
+                TrustCommand::Trust(parent.to_path_buf())
+            }
+            ('r', Some(Row::Prompt(files))) => TrustCommand::Restrict(files.root.clone()),
+            ('x', Some(Row::Trusted(path))) => TrustCommand::Revoke(path.clone()),
+            _ => TrustCommand::None,
+        }
+    }
+
+    pub fn view(&self) -> PanelView {
+        let items = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Prompt(files) => PanelItem::styled(vec![
+                    (format!("{} ", files.root.display()), Some(Color::Yellow)),
+                    (i18n::t("workspace_trust.untrusted"), None),
+                ]),
+                Row::Trusted(path) => {
+                    PanelItem::styled(vec![(path.display().to_string(), Some(Color::Green))])
+                }
+            })
+            .collect();
+        let preview = match self.rows.get(self.selected) {
+            Some(Row::Prompt(files)) => {
+                let mut lines = vec![i18n::t("workspace_trust.prompt_hint"), String::new()];
+                lines.extend(files.paths().into_iter().map(|path| format!("  {}", path)));
+                lines.push(String::new());
+                lines.push(i18n::t("workspace_trust.restricted_hint"));
+                lines.into_iter().map(Into::into).collect()
+            }
+            Some(Row::Trusted(_)) => vec![i18n::t("workspace_trust.trusted_hint").into()],
+            None => vec![i18n::t("workspace_trust.empty").into()],
+        };
+        PanelView {
+            kind: PanelKind::WorkspaceTrust,
+            title: i18n::t("panel.workspace_trust"),
+            items,
+            footer: Some(i18n::t("panel.workspace_trust_keys")),
+            preview,
+            image: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".warp/workflows")).unwrap();
+        std::fs::write(dir.path().join(".warp/config.toml"), "shell = \"fish\"\n").unwrap();
+        std::fs::write(
+            dir.path().join(".warp/workflows/deploy.yaml"),
+            "name: deploy\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join(".env"),
+            "# local\nexport API_URL=\"http://localhost\"\nDEBUG=1\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let env = parse_env(
+            "A=1\n\n# comment\nexport B='two words'\nC = \"x=y\"\nnot a pair\nBAD KEY=1\n",
+        );
+        assert_eq!(
+            env,
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "two words".to_string()),
+                ("C".to_string(), "x=y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_untrusted_projects_apply_nothing() {
+        let dir = project();
+        let nested = dir.path().join("src/bin");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let files = ProjectFiles::find(&nested).unwrap();
+        assert_eq!(files.root, dir.path());
+        assert_eq!(
+            files.paths(),
+            vec![".warp/config.toml", ".env", ".warp/workflows/deploy.yaml"]
+        );
+
+        let mut store = TrustStore::default();
+        assert_eq!(store.trust_for(&files.root), Trust::Unknown);
+        assert!(settings_for(&store, &nested).is_none());
+        store.restrict(&files.root);
+        assert_eq!(store.trust_for(&files.root), Trust::Restricted);
+        assert!(settings_for(&store, &nested).is_none());
+
+        // Trusting the parent covers the project
+        store.trust(dir.path().parent().unwrap());
+        let settings = settings_for(&store, &nested).unwrap();
+        assert_eq!(settings.defaults.shell.as_deref(), Some("fish"));
+        assert_eq!(
+            settings.env[0],
+            ("API_URL".to_string(), "http://localhost".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trust_persists_and_revokes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trusted_workspaces.json");
+        let mut store = TrustStore::default();
+        store.trust(Path::new("/home/me/work"));
+        store.restrict(Path::new("/tmp/download"));
+        TrustStore::save(&store.to_bytes().unwrap(), &path)
+            .await
+            .unwrap();
+
+        let mut loaded = TrustStore::load(&path).await.unwrap();
+        assert_eq!(
+            loaded.trust_for(Path::new("/home/me/work/app")),
+            Trust::Trusted
+        );
+        assert_eq!(loaded.trust_for(Path::new("/tmp/download")), Trust::Unknown);
+        loaded.revoke(Path::new("/home/me/work"));
+        assert_eq!(
+            loaded.trust_for(Path::new("/home/me/work/app")),
+            Trust::Unknown
+        );
+    }
+
+    #[test]
+    fn test_panel_keys() {
+        let dir = project();
+        let mut store = TrustStore::default();
+        store.trust(Path::new("/home/me/work"));
+        let mut panel = TrustPanel::new(&store, Some(ProjectFiles::scan(dir.path())));
+        assert_eq!(panel.view().items.len(), 2);
+        assert_eq!(
+            panel.handle_key('t', 0),
+            TrustCommand::Trust(dir.path().to_path_buf())
+        );
+        assert_eq!(
+            panel.handle_key('p', 0),
+            TrustCommand::Trust(dir.path().parent().unwrap().to_path_buf())
+        );
+        assert_eq!(panel.handle_key('x', 0), TrustCommand::None);
+        assert_eq!(
+            panel.handle_key('x', 1),
+            TrustCommand::Revoke(PathBuf::from("/home/me/work"))
+        );
+    }
+}